@@ -0,0 +1,184 @@
+use log::{error, info};
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{exit, Command};
+
+use crate::build::BuildOpts;
+
+/// We only build for 64-bit ARM, which covers practically all devices that support OpenGL ES 3.
+const RUST_TARGET: &str = "aarch64-linux-android";
+const ANDROID_ABI: &str = "arm64-v8a";
+/// Android 8.0, which practically all devices in use run.
+const MIN_SDK_VERSION: u32 = 26;
+
+const ANDROID_MANIFEST: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<!-- Generated by `cargo zaplib build --target android`. -->
+<manifest xmlns:android="http://schemas.android.com/apk/res/android"
+    package="__ZAPLIB_PACKAGE_ID__"
+    android:versionCode="1"
+    android:versionName="__ZAPLIB_VERSION__">
+    <uses-sdk android:minSdkVersion="__ZAPLIB_MIN_SDK_VERSION__" android:targetSdkVersion="__ZAPLIB_TARGET_SDK_VERSION__" />
+    <uses-feature android:glEsVersion="0x00030000" android:required="true" />
+    <uses-permission android:name="android.permission.INTERNET" />
+    <application android:label="__ZAPLIB_NAME__" android:hasCode="false" android:debuggable="__ZAPLIB_DEBUGGABLE__">
+        <activity
+            android:name="android.app.NativeActivity"
+            android:configChanges="orientation|screenSize|screenLayout|smallestScreenSize|keyboard|keyboardHidden|density|uiMode"
+            android:theme="@android:style/Theme.NoTitleBar.Fullscreen"
+            android:exported="true">
+            <meta-data android:name="android.app.lib_name" android:value="__ZAPLIB_LIB_NAME__" />
+            <intent-filter>
+                <action android:name="android.intent.action.MAIN" />
+                <category android:name="android.intent.category.LAUNCHER" />
+            </intent-filter>
+        </activity>
+    </application>
+</manifest>
+"#;
+
+/// Find the highest version in a directory of versioned SDK components, like `build-tools/30.0.3`.
+fn latest_sdk_component(dir: &Path, prefix: &str) -> Option<PathBuf> {
+    let mut versions: Vec<(Vec<u32>, PathBuf)> = fs::read_dir(dir)
+        .ok()?
+        .filter_map(|entry| {
+            let path = entry.ok()?.path();
+            let name = path.file_name()?.to_str()?.strip_prefix(prefix)?.to_string();
+            let version = name.split('.').map(|part| part.parse().ok()).collect::<Option<Vec<u32>>>()?;
+            Some((version, path))
+        })
+        .collect();
+    versions.sort();
+    versions.pop().map(|(_, path)| path)
+}
+
+pub(crate) fn env_path(names: &[&str]) -> Option<PathBuf> {
+    names.iter().find_map(std::env::var_os).map(PathBuf::from)
+}
+
+pub(crate) fn run(command: &mut Command) {
+    info!("Running {command:?}");
+    let exit_status = command.spawn().expect("Failed to execute command").wait().unwrap();
+    if !exit_status.success() {
+        exit(exit_status.code().unwrap_or(1));
+    }
+}
+
+/// Build an APK that runs the app in a `NativeActivity`.
+///
+/// The package needs a library target with `crate-type = ["cdylib"]` that calls `main_app!`, since Android loads apps
+/// as shared libraries.
+pub(crate) fn build_android(opts: BuildOpts) {
+    if opts.package.is_empty() {
+        error!("Specify which package to build for Android using -p");
+        exit(1);
+    }
+    let sdk = env_path(&["ANDROID_HOME", "ANDROID_SDK_ROOT"]).unwrap_or_else(|| {
+        error!("Set ANDROID_HOME to the location of the Android SDK");
+        exit(1);
+    });
+    let ndk = env_path(&["ANDROID_NDK_HOME", "ANDROID_NDK_ROOT"])
+        .or_else(|| latest_sdk_component(&sdk.join("ndk"), ""))
+        .unwrap_or_else(|| {
+            error!("Set ANDROID_NDK_HOME to the location of the Android NDK, or install it using the SDK manager");
+            exit(1);
+        });
+    let build_tools = latest_sdk_component(&sdk.join("build-tools"), "").unwrap_or_else(|| {
+        error!("Could not find the Android build tools in {}", sdk.display());
+        exit(1);
+    });
+    let platform = latest_sdk_component(&sdk.join("platforms"), "android-").unwrap_or_else(|| {
+        error!("Could not find an Android platform in {}", sdk.display());
+        exit(1);
+    });
+    let target_sdk_version = platform.file_name().unwrap().to_str().unwrap().trim_start_matches("android-").to_string();
+
+    let host_tag = if cfg!(target_os = "macos") {
+        "darwin-x86_64"
+    } else if cfg!(target_os = "windows") {
+        "windows-x86_64"
+    } else {
+        "linux-x86_64"
+    };
+    let ndk_bin = ndk.join("toolchains/llvm/prebuilt").join(host_tag).join("bin");
+    let linker = ndk_bin.join(format!("{RUST_TARGET}{MIN_SDK_VERSION}-clang"));
+
+    let mut args = vec!["+nightly-2022-01-18", "build", "--lib", "--target", RUST_TARGET, "-p", &opts.package];
+    if opts.release {
+        args.push("--release");
+    }
+    if !opts.features.is_empty() {
+        args.push("--features");
+        args.push(&opts.features);
+    }
+    run(Command::new("cargo")
+        .env("CARGO_TARGET_AARCH64_LINUX_ANDROID_LINKER", &linker)
+        .env("CC_aarch64_linux_android", &linker)
+        .env("AR_aarch64_linux_android", ndk_bin.join("llvm-ar"))
+        .args(args));
+
+    let lib_name = opts.package.replace('-', "_");
+    let target_dir = env_path(&["CARGO_TARGET_DIR"]).unwrap_or_else(|| PathBuf::from("target"));
+    let profile = if opts.release { "release" } else { "debug" };
+    let lib_path = target_dir.join(RUST_TARGET).join(profile).join(format!("lib{lib_name}.so"));
+    if !lib_path.is_file() {
+        error!("Could not find {}; make sure that {} has `crate-type = [\"cdylib\"]`", lib_path.display(), opts.package);
+        exit(1);
+    }
+
+    let out = target_dir.join("android").join(&opts.package);
+    let _ = fs::remove_dir_all(&out);
+    let apk_dir = out.join("apk");
+    let lib_dir = apk_dir.join("lib").join(ANDROID_ABI);
+    fs::create_dir_all(&lib_dir).unwrap();
+    fs::copy(&lib_path, lib_dir.join(format!("lib{lib_name}.so"))).unwrap();
+
+    let manifest = ANDROID_MANIFEST
+        .replace("__ZAPLIB_PACKAGE_ID__", &format!("com.zaplib.{lib_name}"))
+        .replace("__ZAPLIB_VERSION__", "0.0.1")
+        .replace("__ZAPLIB_MIN_SDK_VERSION__", &MIN_SDK_VERSION.to_string())
+        .replace("__ZAPLIB_TARGET_SDK_VERSION__", &target_sdk_version)
+        .replace("__ZAPLIB_NAME__", &opts.package)
+        .replace("__ZAPLIB_DEBUGGABLE__", if opts.release { "false" } else { "true" })
+        .replace("__ZAPLIB_LIB_NAME__", &lib_name);
+    let manifest_path = out.join("AndroidManifest.xml");
+    fs::write(&manifest_path, manifest).unwrap();
+
+    let unaligned_apk = out.join("unaligned.apk");
+    let aligned_apk = out.join("aligned.apk");
+    let apk = out.join(format!("{}.apk", opts.package));
+    run(Command::new(build_tools.join("aapt"))
+        .args(["package", "-f", "-M"])
+        .arg(&manifest_path)
+        .arg("-I")
+        .arg(platform.join("android.jar"))
+        .arg("-F")
+        .arg(&unaligned_apk));
+    // `aapt add` uses the given path as the path in the APK, so run it from the APK directory.
+    run(Command::new(build_tools.join("aapt"))
+        .current_dir(&apk_dir)
+        .arg("add")
+        .arg(fs::canonicalize(&unaligned_apk).unwrap())
+        .arg(format!("lib/{ANDROID_ABI}/lib{lib_name}.so")));
+    run(Command::new(build_tools.join("zipalign")).args(["-f", "4"]).arg(&unaligned_apk).arg(&aligned_apk));
+
+    // Sign with the same debug key as Android Studio, so that you can install over builds from there.
+    let home = env_path(&["HOME", "USERPROFILE"]).unwrap_or_default();
+    let keystore = home.join(".android").join("debug.keystore");
+    if !keystore.is_file() {
+        fs::create_dir_all(keystore.parent().unwrap()).unwrap();
+        run(Command::new("keytool")
+            .args(["-genkeypair", "-keystore"])
+            .arg(&keystore)
+            .args(["-storepass", "android", "-alias", "androiddebugkey", "-keypass", "android", "-keyalg", "RSA"])
+            .args(["-validity", "10000", "-dname", "CN=Android Debug,O=Android,C=US"]));
+    }
+    run(Command::new(build_tools.join("apksigner"))
+        .args(["sign", "--ks"])
+        .arg(&keystore)
+        .args(["--ks-pass", "pass:android", "--out"])
+        .arg(&apk)
+        .arg(&aligned_apk));
+
+    info!("Done! Install the app using `adb install -r {}`, and see its logs using `adb logcat -s zaplib`", apk.display());
+}
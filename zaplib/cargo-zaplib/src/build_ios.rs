@@ -0,0 +1,187 @@
+use log::{error, info};
+
+use std::fs;
+use std::path::PathBuf;
+use std::process::{exit, Command};
+
+use crate::build::BuildOpts;
+use crate::build_android::{env_path, run};
+
+const DEVICE_TARGET: &str = "aarch64-apple-ios";
+/// The simulator runs apps for the architecture of the Mac it runs on.
+const SIMULATOR_TARGET: &str = if cfg!(target_arch = "aarch64") { "aarch64-apple-ios-sim" } else { "x86_64-apple-ios" };
+/// iOS 14 is the first version that supports `UILaunchScreen`, which we need to run fullscreen without a storyboard.
+const MIN_IOS_VERSION: &str = "14.0";
+
+const INFO_PLIST: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<!-- Generated by `cargo zaplib build --target ios`. -->
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+    <key>CFBundleDevelopmentRegion</key>
+    <string>en</string>
+    <key>CFBundleExecutable</key>
+    <string>__ZAPLIB_EXECUTABLE__</string>
+    <key>CFBundleIdentifier</key>
+    <string>__ZAPLIB_BUNDLE_ID__</string>
+    <key>CFBundleInfoDictionaryVersion</key>
+    <string>6.0</string>
+    <key>CFBundleName</key>
+    <string>__ZAPLIB_NAME__</string>
+    <key>CFBundlePackageType</key>
+    <string>APPL</string>
+    <key>CFBundleShortVersionString</key>
+    <string>__ZAPLIB_VERSION__</string>
+    <key>CFBundleVersion</key>
+    <string>1</string>
+    <key>CFBundleSupportedPlatforms</key>
+    <array>
+        <string>__ZAPLIB_PLATFORM__</string>
+    </array>
+    <key>MinimumOSVersion</key>
+    <string>__ZAPLIB_MIN_IOS_VERSION__</string>
+    <key>UIDeviceFamily</key>
+    <array>
+        <integer>1</integer>
+        <integer>2</integer>
+    </array>
+    <key>UIRequiredDeviceCapabilities</key>
+    <array>
+        <string>arm64</string>
+        <string>metal</string>
+    </array>
+    <key>UILaunchScreen</key>
+    <dict/>
+    <key>UIStatusBarHidden</key>
+    <true/>
+    <key>UIRequiresFullScreen</key>
+    <true/>
+    <key>UIApplicationSupportsIndirectInputEvents</key>
+    <true/>
+    <key>UISupportedInterfaceOrientations</key>
+    <array>
+        <string>UIInterfaceOrientationPortrait</string>
+        <string>UIInterfaceOrientationLandscapeLeft</string>
+        <string>UIInterfaceOrientationLandscapeRight</string>
+    </array>
+    <key>UISupportedInterfaceOrientations~ipad</key>
+    <array>
+        <string>UIInterfaceOrientationPortrait</string>
+        <string>UIInterfaceOrientationPortraitUpsideDown</string>
+        <string>UIInterfaceOrientationLandscapeLeft</string>
+        <string>UIInterfaceOrientationLandscapeRight</string>
+    </array>
+</dict>
+</plist>
+"#;
+
+fn output(command: &mut Command) -> Vec<u8> {
+    info!("Running {command:?}");
+    let output = command.output().expect("Failed to execute command");
+    if !output.status.success() {
+        error!("{}", String::from_utf8_lossy(&output.stderr));
+        exit(output.status.code().unwrap_or(1));
+    }
+    output.stdout
+}
+
+/// Build an `.app` bundle for iOS devices, or for the simulator if `simulator` is set.
+///
+/// Simulator builds are signed ad hoc. Device builds need `ZAPLIB_IOS_SIGNING_IDENTITY` (e.g. "Apple Development: ..."
+/// or "Apple Distribution: ...", see `security find-identity -v -p codesigning`) and `ZAPLIB_IOS_PROVISIONING_PROFILE`
+/// (a `.mobileprovision` file from the Apple Developer portal), and are also packaged as an `.ipa` for uploading to
+/// App Store Connect. Set `ZAPLIB_IOS_BUNDLE_ID` to the bundle identifier that the provisioning profile is for.
+pub(crate) fn build_ios(opts: BuildOpts, simulator: bool) {
+    if !cfg!(target_os = "macos") {
+        error!("Building for iOS requires Xcode, which only runs on Mac OS X");
+        exit(1);
+    }
+    if opts.package.is_empty() {
+        error!("Specify which package to build for iOS using -p");
+        exit(1);
+    }
+    let signing_identity = std::env::var("ZAPLIB_IOS_SIGNING_IDENTITY").ok();
+    let provisioning_profile = env_path(&["ZAPLIB_IOS_PROVISIONING_PROFILE"]);
+    if !simulator && (signing_identity.is_none() || provisioning_profile.is_none()) {
+        error!("Set ZAPLIB_IOS_SIGNING_IDENTITY and ZAPLIB_IOS_PROVISIONING_PROFILE to sign the app for iOS devices");
+        exit(1);
+    }
+
+    let rust_target = if simulator { SIMULATOR_TARGET } else { DEVICE_TARGET };
+    let mut args = vec!["+nightly-2022-01-18", "build", "--bins", "--target", rust_target, "-p", &opts.package];
+    if opts.release {
+        args.push("--release");
+    }
+    if !opts.features.is_empty() {
+        args.push("--features");
+        args.push(&opts.features);
+    }
+    run(Command::new("cargo").env("IPHONEOS_DEPLOYMENT_TARGET", MIN_IOS_VERSION).args(args));
+
+    let target_dir = env_path(&["CARGO_TARGET_DIR"]).unwrap_or_else(|| PathBuf::from("target"));
+    let profile = if opts.release { "release" } else { "debug" };
+    let executable_path = target_dir.join(rust_target).join(profile).join(&opts.package);
+    if !executable_path.is_file() {
+        error!(
+            "Could not find {}; make sure that {} has a binary target with the same name",
+            executable_path.display(),
+            opts.package
+        );
+        exit(1);
+    }
+
+    let out = target_dir.join("ios").join(&opts.package);
+    let _ = fs::remove_dir_all(&out);
+    let app_dir = out.join("Payload").join(format!("{}.app", opts.package));
+    fs::create_dir_all(&app_dir).unwrap();
+    let executable_name = opts.package.replace('-', "_");
+    fs::copy(&executable_path, app_dir.join(&executable_name)).unwrap();
+
+    let bundle_id = std::env::var("ZAPLIB_IOS_BUNDLE_ID").unwrap_or_else(|_| format!("com.zaplib.{executable_name}"));
+    let info_plist = INFO_PLIST
+        .replace("__ZAPLIB_EXECUTABLE__", &executable_name)
+        .replace("__ZAPLIB_BUNDLE_ID__", &bundle_id)
+        .replace("__ZAPLIB_NAME__", &opts.package)
+        .replace("__ZAPLIB_VERSION__", "0.0.1")
+        .replace("__ZAPLIB_PLATFORM__", if simulator { "iPhoneSimulator" } else { "iPhoneOS" })
+        .replace("__ZAPLIB_MIN_IOS_VERSION__", MIN_IOS_VERSION);
+    fs::write(app_dir.join("Info.plist"), info_plist).unwrap();
+
+    match (signing_identity, provisioning_profile) {
+        (Some(signing_identity), Some(provisioning_profile)) => {
+            fs::copy(&provisioning_profile, app_dir.join("embedded.mobileprovision")).unwrap();
+            // The entitlements (like the team and app id) have to match the provisioning profile, so take them from it.
+            let profile_plist = out.join("profile.plist");
+            fs::write(&profile_plist, output(Command::new("security").args(["cms", "-D", "-i"]).arg(&provisioning_profile)))
+                .unwrap();
+            let entitlements = out.join("entitlements.plist");
+            run(Command::new("plutil").args(["-extract", "Entitlements", "xml1", "-o"]).arg(&entitlements).arg(&profile_plist));
+            run(Command::new("codesign")
+                .args(["--force", "--timestamp=none", "--sign", &signing_identity, "--entitlements"])
+                .arg(&entitlements)
+                .arg(&app_dir));
+        }
+        _ => {
+            run(Command::new("codesign").args(["--force", "--sign", "-"]).arg(&app_dir));
+        }
+    }
+
+    if simulator {
+        info!(
+            "Done! Run the app using `xcrun simctl install booted {}` and `xcrun simctl launch --console booted {}`",
+            app_dir.display(),
+            bundle_id
+        );
+    } else {
+        // An `.ipa` is just a zip file of the `Payload` directory.
+        let ipa_name = format!("{}.ipa", opts.package);
+        run(Command::new("zip").current_dir(&out).args(["-qr", "--symlinks", &ipa_name, "Payload"]));
+        let ipa = out.join(ipa_name);
+        info!(
+            "Done! Install the app using `xcrun devicectl device install app --device <device> {}`, or upload {} to App Store \
+             Connect using Transporter",
+            app_dir.display(),
+            ipa.display()
+        );
+    }
+}
@@ -35,7 +35,59 @@ pub(crate) fn cmd() {
                 .arg(Arg::new("features").long("features").takes_value(true).help("Specify feature flags."))
                 .arg(Arg::new("all-targets").long("all-targets").takes_value(false).help("Build all targets."))
                 .arg(Arg::new("workspace").long("workspace").takes_value(false).help("Build all members in the workspace."))
-                .arg(Arg::new("simd128").long("simd128").takes_value(false).help("Use 128-bit SIMD instruction set for WASM")),
+                .arg(Arg::new("simd128").long("simd128").takes_value(false).help("Use 128-bit SIMD instruction set for WASM"))
+                .arg(
+                    Arg::new("target")
+                        .long("target")
+                        .takes_value(true)
+                        .possible_values(["wasm", "android", "ios", "ios-simulator"])
+                        .default_value("wasm")
+                        .help("Build for the web, build an Android APK, or build an iOS app for devices or the simulator"),
+                ),
+        )
+        .subcommand(
+            Command::new("generate-types")
+                .about("Write TypeScript types for the functions registered using `register_call_rust!(fns: ...)`")
+                .arg(Arg::new("package").short('p').long("package").takes_value(true).help("Run the specified package."))
+                .arg(
+                    Arg::new("out")
+                        .short('o')
+                        .long("out")
+                        .takes_value(true)
+                        .default_value("rust_functions.ts")
+                        .help("File to write the types to."),
+                ),
+        )
+        .subcommand(
+            Command::new("package")
+                .about("Wrap a built web app into a desktop app")
+                .arg(Arg::new("path").takes_value(true).default_value(".").help("Path to the built web app"))
+                .arg(Arg::new("electron").long("electron").takes_value(false).help("Create an Electron project"))
+                .arg(
+                    Arg::new("index")
+                        .long("index")
+                        .takes_value(true)
+                        .default_value("index.html")
+                        .help("HTML file to open, relative to the path"),
+                )
+                .arg(
+                    Arg::new("out")
+                        .short('o')
+                        .long("out")
+                        .takes_value(true)
+                        .default_value("electron")
+                        .help("Directory to write the project to (overwritten if it exists)"),
+                )
+                .arg(Arg::new("name").long("name").takes_value(true).default_value("zaplib-app").help("Name of the app"))
+                .arg(
+                    Arg::new("app-version")
+                        .long("app-version")
+                        .takes_value(true)
+                        .default_value("0.0.1")
+                        .help("Version of the app"),
+                )
+                .arg(Arg::new("icon").long("icon").takes_value(true).help("PNG file to use as the app icon"))
+                .arg(Arg::new("update-url").long("update-url").takes_value(true).help("URL to check for updates")),
         )
         .subcommand(
             Command::new("serve")
@@ -48,14 +100,20 @@ pub(crate) fn cmd() {
         .get_matches();
 
     if let Some(cmd) = matches.subcommand_matches("build") {
-        crate::build::build(crate::build::BuildOpts {
+        let opts = crate::build::BuildOpts {
             release: cmd.is_present("release"),
             use_simd128: cmd.is_present("simd128"),
             all_targets: cmd.is_present("all-targets"),
             workspace: cmd.is_present("workspace"),
             features: cmd.value_of("features").unwrap_or("").to_string(),
             package: cmd.value_of("package").unwrap_or("").to_string(),
-        });
+        };
+        match cmd.value_of("target") {
+            Some("android") => crate::build_android::build_android(opts),
+            Some("ios") => crate::build_ios::build_ios(opts, false),
+            Some("ios-simulator") => crate::build_ios::build_ios(opts, true),
+            _ => crate::build::build(opts),
+        }
     }
 
     if let Some(cmd) = matches.subcommand_matches("install-deps") {
@@ -66,6 +124,23 @@ pub(crate) fn cmd() {
         }
     }
 
+    if let Some(cmd) = matches.subcommand_matches("generate-types") {
+        crate::generate_types::generate_types(cmd.value_of("package").unwrap_or(""), cmd.value_of("out").unwrap());
+    }
+
+    if let Some(cmd) = matches.subcommand_matches("package") {
+        crate::package::package(crate::package::PackageOpts {
+            electron: cmd.is_present("electron"),
+            path: cmd.value_of("path").unwrap().to_string(),
+            index: cmd.value_of("index").unwrap().to_string(),
+            out: cmd.value_of("out").unwrap().to_string(),
+            name: cmd.value_of("name").unwrap().to_string(),
+            version: cmd.value_of("app-version").unwrap().to_string(),
+            icon: cmd.value_of("icon").unwrap_or("").to_string(),
+            update_url: cmd.value_of("update-url").unwrap_or("").to_string(),
+        });
+    }
+
     if let Some(cmd) = matches.subcommand_matches("serve") {
         crate::serve::serve(cmd.value_of_t_or_exit("path"), cmd.value_of_t_or_exit("port"), cmd.is_present("ssl"));
     }
@@ -0,0 +1,37 @@
+use log::{error, info};
+
+use std::process::{exit, Command};
+
+/// Used by `register_call_rust!(fns: ...)` in zaplib; keep in sync with call_rust_fns.rs.
+const WRITE_TYPESCRIPT_ENV_VAR: &str = "ZAPLIB_WRITE_TYPESCRIPT";
+
+/// Run the app natively, asking it to write the TypeScript types for its `CallRustFns` to `out`.
+pub(crate) fn generate_types(package: &str, out: &str) {
+    // Make the path absolute, since `cargo run` might run the app from a different directory.
+    let out = std::env::current_dir().expect("Failed to get current directory").join(out);
+    let _ = std::fs::remove_file(&out);
+
+    let mut args = vec!["run"];
+    if !package.is_empty() {
+        args.push("-p");
+        args.push(package);
+    }
+
+    let string_args = args.join(" ");
+    info!("Running {WRITE_TYPESCRIPT_ENV_VAR}='{}' cargo {string_args}", out.display());
+    let exit_status = Command::new("cargo")
+        .env(WRITE_TYPESCRIPT_ENV_VAR, &out)
+        .args(args)
+        .spawn()
+        .expect("Failed to execute command")
+        .wait()
+        .unwrap();
+    if !exit_status.success() {
+        exit(exit_status.code().unwrap_or(1));
+    }
+    if !out.exists() {
+        error!("The app exited without writing {}; make sure it uses `register_call_rust!(fns: ...)`", out.display());
+        exit(1);
+    }
+    info!("Wrote TypeScript types to {}", out.display());
+}
@@ -1,12 +1,20 @@
 #[cfg(not(target_arch = "wasm32"))]
 mod build;
 #[cfg(not(target_arch = "wasm32"))]
+mod build_android;
+#[cfg(not(target_arch = "wasm32"))]
+mod build_ios;
+#[cfg(not(target_arch = "wasm32"))]
 mod build_npm_package;
 #[cfg(not(target_arch = "wasm32"))]
 mod cmd;
 #[cfg(not(target_arch = "wasm32"))]
+mod generate_types;
+#[cfg(not(target_arch = "wasm32"))]
 mod install_deps;
 #[cfg(not(target_arch = "wasm32"))]
+mod package;
+#[cfg(not(target_arch = "wasm32"))]
 mod serve;
 
 // Use an empty main() function in the wasm32 case, so you can run
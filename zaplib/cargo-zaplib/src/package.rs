@@ -0,0 +1,215 @@
+use log::{error, info};
+
+use std::fs;
+use std::path::Path;
+use std::process::exit;
+
+#[derive(Default, Debug)]
+pub(crate) struct PackageOpts {
+    pub(crate) electron: bool,
+    /// Directory with the built web app, which gets copied into the bundle.
+    pub(crate) path: String,
+    /// HTML file to open, relative to `path`.
+    pub(crate) index: String,
+    pub(crate) out: String,
+    pub(crate) name: String,
+    pub(crate) version: String,
+    /// PNG file to use as the application icon.
+    pub(crate) icon: String,
+    /// URL to check for updates using `electron-updater`; no auto-updating if empty.
+    pub(crate) update_url: String,
+}
+
+/// Directories that we never copy into the bundle.
+const SKIPPED_DIRS: &[&str] = &[".git", "node_modules"];
+
+/// Entry point of the Electron app. Serves the web app through a custom protocol, so that we can set the
+/// same cross-origin isolation headers as `cargo zaplib serve`, which are needed for `SharedArrayBuffer`.
+const ELECTRON_MAIN_JS: &str = r#"// Generated by `cargo zaplib package --electron`.
+const { app, BrowserWindow, protocol } = require("electron");
+const fs = require("fs");
+const path = require("path");
+
+const APP_DIR = path.join(__dirname, "app");
+const INDEX = "__ZAPLIB_INDEX__";
+const MIME_TYPES = {
+  ".css": "text/css",
+  ".html": "text/html",
+  ".js": "text/javascript",
+  ".json": "application/json",
+  ".png": "image/png",
+  ".svg": "image/svg+xml",
+  ".ttf": "font/ttf",
+  ".wasm": "application/wasm",
+};
+
+protocol.registerSchemesAsPrivileged([
+  {
+    scheme: "app",
+    privileges: {
+      standard: true,
+      secure: true,
+      supportFetchAPI: true,
+      corsEnabled: true,
+    },
+  },
+]);
+
+function serveFile(request, callback) {
+  const { pathname } = new URL(request.url);
+  const filePath = path.normalize(
+    path.join(APP_DIR, decodeURIComponent(pathname))
+  );
+  if (!filePath.startsWith(APP_DIR + path.sep)) {
+    callback({ statusCode: 403 });
+    return;
+  }
+  fs.readFile(filePath, (err, data) => {
+    if (err) {
+      callback({ statusCode: 404 });
+      return;
+    }
+    callback({
+      mimeType:
+        MIME_TYPES[path.extname(filePath)] || "application/octet-stream",
+      data,
+      headers: {
+        "Cross-Origin-Opener-Policy": "same-origin",
+        "Cross-Origin-Embedder-Policy": "require-corp",
+      },
+    });
+  });
+}
+
+function createWindow() {
+  const window = new BrowserWindow({ width: 1280, height: 800 });
+  window.loadURL(`app://bundle/${INDEX}`);
+}
+
+app.whenReady().then(() => {
+  protocol.registerBufferProtocol("app", serveFile);
+  createWindow();
+__ZAPLIB_AUTO_UPDATE__  app.on("activate", () => {
+    if (BrowserWindow.getAllWindows().length === 0) createWindow();
+  });
+});
+
+app.on("window-all-closed", () => {
+  if (process.platform !== "darwin") app.quit();
+});
+"#;
+
+const ELECTRON_AUTO_UPDATE_JS: &str = "  require(\"electron-updater\").autoUpdater.checkForUpdatesAndNotify();\n";
+
+/// Escape a string for use in a JSON or JS string literal.
+fn escape_string(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn package_json(opts: &PackageOpts) -> String {
+    let name = escape_string(&opts.name);
+    let version = escape_string(&opts.version);
+    // npm package names and app ids can't contain spaces and such; `name` is still used as the product name.
+    let package_name: String =
+        opts.name.chars().map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_lowercase() } else { '-' }).collect();
+    let app_id = package_name.replace('-', "");
+    let (updater_dependency, publish) = if opts.update_url.is_empty() {
+        ("".to_string(), "".to_string())
+    } else {
+        (
+            "\n    \"electron-updater\": \"^4.6.5\"".to_string(),
+            format!(",\n    \"publish\": [{{ \"provider\": \"generic\", \"url\": \"{}\" }}]", escape_string(&opts.update_url)),
+        )
+    };
+    format!(
+        r#"{{
+  "name": "{package_name}",
+  "version": "{version}",
+  "main": "main.js",
+  "scripts": {{
+    "start": "electron .",
+    "dist": "electron-builder"
+  }},
+  "dependencies": {{{updater_dependency}
+  }},
+  "devDependencies": {{
+    "electron": "^17.1.0",
+    "electron-builder": "^22.14.13"
+  }},
+  "build": {{
+    "appId": "com.zaplib.{app_id}",
+    "productName": "{name}",
+    "files": ["main.js", "app/**/*"]{publish}
+  }}
+}}
+"#
+    )
+}
+
+/// Recursively copy `from` into `to`, skipping [`SKIPPED_DIRS`] and `skip` (the output directory, in case it's
+/// inside of `from`).
+fn copy_dir(from: &Path, to: &Path, skip: &Path) -> std::io::Result<()> {
+    fs::create_dir_all(to)?;
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        let path = entry.path();
+        if SKIPPED_DIRS.iter().any(|dir| entry.file_name() == *dir) || path.canonicalize()? == skip {
+            continue;
+        }
+        if entry.file_type()?.is_dir() {
+            copy_dir(&path, &to.join(entry.file_name()), skip)?;
+        } else {
+            fs::copy(&path, to.join(entry.file_name()))?;
+        }
+    }
+    Ok(())
+}
+
+fn write_electron_project(opts: &PackageOpts) -> std::io::Result<()> {
+    let out = Path::new(&opts.out);
+    if out.exists() && Path::new(&opts.path).canonicalize()?.starts_with(out.canonicalize()?) {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "the output directory contains the web app, and would get overwritten",
+        ));
+    }
+    if out.exists() {
+        fs::remove_dir_all(out)?;
+    }
+    fs::create_dir_all(out)?;
+    let out = out.canonicalize()?;
+
+    copy_dir(Path::new(&opts.path), &out.join("app"), &out)?;
+
+    let auto_update = if opts.update_url.is_empty() { "" } else { ELECTRON_AUTO_UPDATE_JS };
+    let main_js =
+        ELECTRON_MAIN_JS.replace("__ZAPLIB_INDEX__", &escape_string(&opts.index)).replace("__ZAPLIB_AUTO_UPDATE__", auto_update);
+    fs::write(out.join("main.js"), main_js)?;
+    fs::write(out.join("package.json"), package_json(opts))?;
+
+    if !opts.icon.is_empty() {
+        // electron-builder picks up `build/icon.png` automatically.
+        fs::create_dir_all(out.join("build"))?;
+        fs::copy(&opts.icon, out.join("build/icon.png"))?;
+    }
+    Ok(())
+}
+
+/// Wrap a built web app into a desktop app project, which can then be bundled using its `dist` script.
+pub(crate) fn package(opts: PackageOpts) {
+    if !opts.electron {
+        error!("Specify what to package for; currently only --electron is supported");
+        exit(1);
+    }
+    if !Path::new(&opts.path).join(&opts.index).is_file() {
+        error!("Could not find {} in {}; use --index to specify the HTML file of your app", opts.index, opts.path);
+        exit(1);
+    }
+
+    info!("Writing Electron project to {}", opts.out);
+    if let Err(err) = write_electron_project(&opts) {
+        error!("Failed to write Electron project: {err}");
+        exit(1);
+    }
+    info!("Done! Run `yarn && yarn start` in {} to try it out, and `yarn dist` to build installers", opts.out);
+}
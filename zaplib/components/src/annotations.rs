@@ -0,0 +1,799 @@
+//! Arrows, boxes, freehand strokes, and text notes that users draw over a view, e.g. for reviewing and marking up
+//! charts, maps, or images.
+
+use crate::background::*;
+use zaplib::undo::*;
+use zaplib::*;
+
+const STROKE_WIDTH: f32 = 2.;
+/// How close (in pixels) the pointer has to be to a stroke to select it.
+const HIT_DISTANCE: f32 = 5.;
+const ARROW_HEAD_LENGTH: f32 = 12.;
+/// Shapes smaller than this (in pixels) are assumed to be accidental clicks, and are not added.
+const MIN_SHAPE_SIZE: f32 = 4.;
+/// Freehand strokes only get a new point after the pointer moved this many pixels, to keep them small.
+const FREEHAND_MIN_DISTANCE: f32 = 2.;
+const TEXT_PADDING: f32 = 6.;
+
+/// Identifies an [`Annotation`]; stays the same when other annotations are added or removed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde-support", derive(serde::Serialize, serde::Deserialize))]
+pub struct AnnotationId(pub u64);
+
+/// What an [`Annotation`] looks like. Positions are relative to the top-left corner of the [`AnnotationLayer`].
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde-support", derive(serde::Serialize, serde::Deserialize))]
+pub enum AnnotationShape {
+    Arrow {
+        from: Vec2,
+        to: Vec2,
+    },
+    Rect(Rect),
+    Freehand(Vec<Vec2>),
+    /// A note, with `pos` being its top-left corner.
+    Text {
+        pos: Vec2,
+        text: String,
+    },
+}
+
+impl AnnotationShape {
+    fn translate(&mut self, delta: Vec2) {
+        match self {
+            AnnotationShape::Arrow { from, to } => {
+                *from += delta;
+                *to += delta;
+            }
+            AnnotationShape::Rect(rect) => rect.pos += delta,
+            AnnotationShape::Freehand(points) => points.iter_mut().for_each(|point| *point += delta),
+            AnnotationShape::Text { pos, .. } => *pos += delta,
+        }
+    }
+
+    /// The straight lines that make up the shape; empty for text.
+    fn segments(&self) -> Vec<(Vec2, Vec2)> {
+        match self {
+            AnnotationShape::Arrow { from, to } => {
+                let mut segments = vec![(*from, *to)];
+                let length = from.distance(to);
+                if length > 0. {
+                    let direction = (*to - *from) / length;
+                    let normal = vec2(-direction.y, direction.x);
+                    let head_length = ARROW_HEAD_LENGTH.min(length / 2.);
+                    let base = *to - direction * head_length;
+                    segments.push((base + normal * (head_length / 2.), *to));
+                    segments.push((base - normal * (head_length / 2.), *to));
+                }
+                segments
+            }
+            AnnotationShape::Rect(rect) => {
+                let (min, max) = (rect.pos, rect.pos + rect.size);
+                let corners = [min, vec2(max.x, min.y), max, vec2(min.x, max.y)];
+                (0..4).map(|index| (corners[index], corners[(index + 1) % 4])).collect()
+            }
+            AnnotationShape::Freehand(points) => match points.len() {
+                0 => vec![],
+                1 => vec![(points[0], points[0])],
+                _ => points.windows(2).map(|pair| (pair[0], pair[1])).collect(),
+            },
+            AnnotationShape::Text { .. } => vec![],
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde-support", derive(serde::Serialize, serde::Deserialize))]
+pub struct Annotation {
+    pub id: AnnotationId,
+    pub shape: AnnotationShape,
+    pub color: Vec4,
+}
+
+/// The annotations shown by an [`AnnotationLayer`]. With the `serde-support` feature this can be saved and loaded as
+/// JSON, using [`AnnotationData::to_json`] and [`AnnotationData::from_json`].
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde-support", derive(serde::Serialize, serde::Deserialize))]
+pub struct AnnotationData {
+    /// In drawing order, so later annotations are on top.
+    pub annotations: Vec<Annotation>,
+}
+
+impl AnnotationData {
+    /// An id that isn't used yet, for a new [`Annotation`].
+    pub fn next_id(&self) -> AnnotationId {
+        AnnotationId(self.annotations.iter().map(|annotation| annotation.id.0 + 1).max().unwrap_or(0))
+    }
+
+    pub fn add(&mut self, shape: AnnotationShape, color: Vec4) -> AnnotationId {
+        let id = self.next_id();
+        self.annotations.push(Annotation { id, shape, color });
+        id
+    }
+
+    pub fn get(&self, id: AnnotationId) -> Option<&Annotation> {
+        self.annotations.iter().find(|annotation| annotation.id == id)
+    }
+
+    pub fn get_mut(&mut self, id: AnnotationId) -> Option<&mut Annotation> {
+        self.annotations.iter_mut().find(|annotation| annotation.id == id)
+    }
+
+    #[cfg(feature = "serde-support")]
+    pub fn to_json(&self) -> Result<String, state_snapshot::SnapshotError> {
+        let bytes = state_snapshot::snapshot(self)?;
+        Ok(String::from_utf8(bytes).expect("JSON is always valid UTF-8"))
+    }
+
+    #[cfg(feature = "serde-support")]
+    pub fn from_json(json: &str) -> Result<Self, state_snapshot::SnapshotError> {
+        state_snapshot::restore(json.as_bytes())
+    }
+}
+
+/// A change to [`AnnotationData`], which can be undone using an [`UndoStack`]. [`AnnotationLayer`] uses these for
+/// all edits, and keeps its own stack; see [`AnnotationLayer::push_command`].
+#[derive(Clone, Debug, PartialEq)]
+pub enum AnnotationCommand {
+    /// Add an annotation on top of the others.
+    Add(Annotation),
+    /// Remove annotations, which are kept together with their index so that undoing puts them back in the same
+    /// order. Create this using [`AnnotationCommand::remove`].
+    Remove(Vec<(usize, Annotation)>),
+    Move {
+        ids: Vec<AnnotationId>,
+        delta: Vec2,
+    },
+}
+
+impl AnnotationCommand {
+    /// A command that removes the annotations in `ids` from `data`.
+    pub fn remove(data: &AnnotationData, ids: &[AnnotationId]) -> Self {
+        AnnotationCommand::Remove(
+            data.annotations
+                .iter()
+                .enumerate()
+                .filter(|(_, annotation)| ids.contains(&annotation.id))
+                .map(|(index, annotation)| (index, annotation.clone()))
+                .collect(),
+        )
+    }
+}
+
+impl UndoCommand for AnnotationCommand {
+    type Target = AnnotationData;
+
+    fn apply(&mut self, data: &mut AnnotationData) {
+        match self {
+            AnnotationCommand::Add(annotation) => data.annotations.push(annotation.clone()),
+            AnnotationCommand::Remove(removed) => {
+                // Going backwards, so indices of annotations that still have to be removed don't change.
+                for (index, _) in removed.iter().rev() {
+                    data.annotations.remove(*index);
+                }
+            }
+            AnnotationCommand::Move { ids, delta } => {
+                for annotation in data.annotations.iter_mut().filter(|annotation| ids.contains(&annotation.id)) {
+                    annotation.shape.translate(*delta);
+                }
+            }
+        }
+    }
+
+    fn revert(&mut self, data: &mut AnnotationData) {
+        match self {
+            AnnotationCommand::Add(annotation) => data.annotations.retain(|other| other.id != annotation.id),
+            AnnotationCommand::Remove(removed) => {
+                for (index, annotation) in removed.iter() {
+                    data.annotations.insert(*index, annotation.clone());
+                }
+            }
+            AnnotationCommand::Move { ids, delta } => {
+                for annotation in data.annotations.iter_mut().filter(|annotation| ids.contains(&annotation.id)) {
+                    annotation.shape.translate(-*delta);
+                }
+            }
+        }
+    }
+
+    /// Dragging annotations results in lots of moves, which get undone at once.
+    fn merge(&mut self, next: &Self) -> bool {
+        match (self, next) {
+            (AnnotationCommand::Move { ids, delta }, AnnotationCommand::Move { ids: next_ids, delta: next_delta })
+                if ids == next_ids =>
+            {
+                *delta += *next_delta;
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+/// What dragging or clicking on an [`AnnotationLayer`] does.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AnnotationTool {
+    /// Select and move annotations. Pointer events that don't hit an annotation go to the view below.
+    Select,
+    Arrow,
+    Rect,
+    Freehand,
+    /// Click to place a note, and type its text; press Return or Escape (or click elsewhere) to finish it.
+    Text,
+}
+
+impl Default for AnnotationTool {
+    fn default() -> Self {
+        AnnotationTool::Select
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum AnnotationEvent {
+    None,
+    /// The annotations changed, because the user edited them, or used undo or redo; see [`AnnotationLayer::data`].
+    Changed,
+    /// See [`AnnotationLayer::selection`].
+    SelectionChanged,
+}
+
+#[derive(Clone, Copy, Default)]
+#[repr(C)]
+struct StrokeIns {
+    base: QuadIns,
+    color: Vec4,
+    start: Vec2,
+    end: Vec2,
+    width: f32,
+}
+
+static STROKE_SHADER: Shader = Shader {
+    build_geom: Some(QuadIns::build_geom),
+    code_to_concatenate: &[
+        Cx::STD_SHADER,
+        QuadIns::SHADER,
+        code_fragment!(
+            r#"
+            instance color: vec4;
+            instance start: vec2;
+            instance end: vec2;
+            instance width: float;
+
+            fn pixel() -> vec4 {
+                // Each instance is one straight line of an annotation, in absolute coordinates.
+                let df = Df::viewport(pos * rect_size);
+                df.move_to(start - rect_pos);
+                df.line_to(end - rect_pos);
+                return df.stroke(color, width);
+            }"#
+        ),
+    ],
+    ..Shader::DEFAULT
+};
+
+enum AnnotationDrag {
+    None,
+    /// Drawing [`AnnotationLayer::draft`], which started at `start`.
+    Drawing {
+        start: Vec2,
+    },
+    /// Moving the selection, which was last moved to follow the pointer at `last`.
+    Moving {
+        last: Vec2,
+    },
+}
+
+/// Lets the user draw annotations over another view, select them (Shift+click to add to the selection), drag them
+/// around, and remove them using Delete or Backspace. Edits can be undone using the usual shortcuts when the layer
+/// has focus.
+///
+/// Draw it right after the view that it annotates, with the same [`Rect`], and call [`AnnotationLayer::handle`]
+/// before that view's `handle`, so that the layer gets to claim pointer events first:
+///
+/// ```ignore
+/// self.annotations.handle(cx, event);
+/// self.chart.handle(cx, event);
+///
+/// let rect = cx.get_box_rect();
+/// self.chart.draw(cx);
+/// self.annotations.draw(cx, rect);
+/// ```
+pub struct AnnotationLayer {
+    component_id: ComponentId,
+    data: AnnotationData,
+    undo_stack: UndoStack<AnnotationCommand>,
+    tool: AnnotationTool,
+    /// Color for new annotations.
+    color: Vec4,
+    accent_color: Vec4,
+    selection: Vec<AnnotationId>,
+    /// The annotation that is being drawn or typed, which gets added to [`AnnotationLayer::data`] when it's done.
+    draft: Option<Annotation>,
+    drag: AnnotationDrag,
+    /// The area that the annotations are drawn in, in absolute coordinates.
+    rect: Rect,
+    note_background: Background,
+}
+
+impl Default for AnnotationLayer {
+    fn default() -> Self {
+        Self {
+            component_id: Default::default(),
+            data: AnnotationData::default(),
+            undo_stack: UndoStack::new(),
+            tool: AnnotationTool::default(),
+            color: vec4(1., 0.3, 0.25, 1.),
+            accent_color: vec4(0.3, 0.6, 1., 1.),
+            selection: vec![],
+            draft: None,
+            drag: AnnotationDrag::None,
+            rect: Rect::default(),
+            note_background: Background::default().with_radius(3.),
+        }
+    }
+}
+
+impl AnnotationLayer {
+    #[must_use]
+    pub fn with_data(self, data: AnnotationData) -> Self {
+        Self { data, ..self }
+    }
+    #[must_use]
+    pub fn with_tool(self, tool: AnnotationTool) -> Self {
+        Self { tool, ..self }
+    }
+    #[must_use]
+    pub fn with_color(self, color: Vec4) -> Self {
+        Self { color, ..self }
+    }
+    /// Color of the outline around selected annotations.
+    #[must_use]
+    pub fn with_accent_color(self, accent_color: Vec4) -> Self {
+        Self { accent_color, ..self }
+    }
+
+    pub fn data(&self) -> &AnnotationData {
+        &self.data
+    }
+
+    /// Replace all annotations, e.g. after loading them. This can't be undone, and clears the undo history.
+    pub fn set_data(&mut self, cx: &mut Cx, data: AnnotationData) {
+        self.data = data;
+        self.undo_stack.clear();
+        self.selection.clear();
+        self.draft = None;
+        self.end_drag();
+        cx.request_draw();
+    }
+
+    pub fn tool(&self) -> AnnotationTool {
+        self.tool
+    }
+
+    /// Switch tools, finishing the note that is being typed, if any.
+    pub fn set_tool(&mut self, cx: &mut Cx, tool: AnnotationTool) {
+        self.finish_draft(cx);
+        self.tool = tool;
+        cx.request_draw();
+    }
+
+    /// Set the color for new annotations.
+    pub fn set_color(&mut self, color: Vec4) {
+        self.color = color;
+    }
+
+    pub fn selection(&self) -> &[AnnotationId] {
+        &self.selection
+    }
+
+    pub fn set_selection(&mut self, cx: &mut Cx, selection: Vec<AnnotationId>) {
+        self.selection = selection;
+        cx.request_draw();
+    }
+
+    /// Apply `command`, as if the user made the change, so that it can be undone.
+    pub fn push_command(&mut self, cx: &mut Cx, command: AnnotationCommand) {
+        self.undo_stack.push(&mut self.data, command, cx.last_event_time);
+        self.selection.retain(|id| self.data.get(*id).is_some());
+        cx.request_draw();
+    }
+
+    pub fn can_undo(&self) -> bool {
+        self.undo_stack.can_undo()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        self.undo_stack.can_redo()
+    }
+
+    pub fn undo(&mut self, cx: &mut Cx) -> bool {
+        self.finish_draft(cx);
+        let changed = self.undo_stack.undo(&mut self.data);
+        self.after_undo_or_redo(cx, changed)
+    }
+
+    pub fn redo(&mut self, cx: &mut Cx) -> bool {
+        self.finish_draft(cx);
+        let changed = self.undo_stack.redo(&mut self.data);
+        self.after_undo_or_redo(cx, changed)
+    }
+
+    fn after_undo_or_redo(&mut self, cx: &mut Cx, changed: bool) -> bool {
+        if changed {
+            self.selection.retain(|id| self.data.get(*id).is_some());
+            cx.request_draw();
+        }
+        changed
+    }
+
+    /// Remove the selected annotations.
+    pub fn delete_selection(&mut self, cx: &mut Cx) -> AnnotationEvent {
+        if self.selection.is_empty() {
+            return AnnotationEvent::None;
+        }
+        let command = AnnotationCommand::remove(&self.data, &self.selection);
+        self.push_command(cx, command);
+        AnnotationEvent::Changed
+    }
+
+    /// Stop drawing or moving, and close the undo group of a move so that it becomes one undo step.
+    fn end_drag(&mut self) {
+        if let AnnotationDrag::Moving { .. } = self.drag {
+            self.undo_stack.end_group();
+        }
+        self.drag = AnnotationDrag::None;
+    }
+
+    /// Add the annotation that is being drawn or typed, unless it's too small or empty. Returns whether it was added.
+    fn finish_draft(&mut self, cx: &mut Cx) -> bool {
+        self.end_drag();
+        let draft = match self.draft.take() {
+            Some(draft) => draft,
+            None => return false,
+        };
+        cx.request_draw();
+        let keep = match &draft.shape {
+            AnnotationShape::Text { text, .. } => !text.trim().is_empty(),
+            shape => {
+                let points: Vec<Vec2> = shape.segments().into_iter().flat_map(|(a, b)| [a, b]).collect();
+                let min = points.iter().fold(Vec2::all(f32::INFINITY), |min, point| min.min(point));
+                let max = points.iter().fold(Vec2::all(f32::NEG_INFINITY), |max, point| max.max(point));
+                (max.x - min.x).max(max.y - min.y) >= MIN_SHAPE_SIZE
+            }
+        };
+        if keep {
+            self.push_command(cx, AnnotationCommand::Add(draft));
+        }
+        keep
+    }
+
+    fn note_props(color: Vec4) -> TextInsProps {
+        TextInsProps { color, ..TextInsProps::DEFAULT }
+    }
+
+    /// The box around a note, relative to [`AnnotationLayer::rect`].
+    fn note_rect(cx: &Cx, pos: Vec2, text: &str, color: Vec4) -> Rect {
+        let metrics = cx.measure_wrapped_text(&Self::note_props(color), text, f32::INFINITY);
+        let size = vec2(metrics.width, metrics.height.max(metrics.line_height)) + Vec2::all(TEXT_PADDING * 2.);
+        Rect { pos, size }
+    }
+
+    /// The topmost annotation at `pos` (relative to [`AnnotationLayer::rect`]).
+    fn annotation_at(&self, cx: &Cx, pos: Vec2) -> Option<AnnotationId> {
+        let hit_distance = HIT_DISTANCE + STROKE_WIDTH / 2.;
+        let hits = |annotation: &Annotation| match &annotation.shape {
+            AnnotationShape::Text { pos: note_pos, text } => Self::note_rect(cx, *note_pos, text, annotation.color).contains(pos),
+            shape => shape.segments().into_iter().any(|(a, b)| distance_to_segment(pos, a, b) <= hit_distance),
+        };
+        self.data.annotations.iter().rev().find(|annotation| hits(annotation)).map(|annotation| annotation.id)
+    }
+
+    fn handle_keyboard(&mut self, cx: &mut Cx, event: &mut Event) -> AnnotationEvent {
+        let is_typing = matches!(&self.draft, Some(Annotation { shape: AnnotationShape::Text { .. }, .. }));
+        match event.hits_keyboard(cx, self.component_id) {
+            Event::TextInput(te) if is_typing => {
+                if let Some(Annotation { shape: AnnotationShape::Text { text, .. }, .. }) = &mut self.draft {
+                    text.push_str(&te.input);
+                    cx.request_draw();
+                }
+            }
+            Event::KeyDown(ke) if is_typing => match ke.key_code {
+                KeyCode::Backspace => {
+                    if let Some(Annotation { shape: AnnotationShape::Text { text, .. }, .. }) = &mut self.draft {
+                        text.pop();
+                        cx.request_draw();
+                    }
+                }
+                KeyCode::Return if ke.modifiers.shift => {
+                    if let Some(Annotation { shape: AnnotationShape::Text { text, .. }, .. }) = &mut self.draft {
+                        text.push('\n');
+                        cx.request_draw();
+                    }
+                }
+                KeyCode::Return | KeyCode::Escape if self.finish_draft(cx) => return AnnotationEvent::Changed,
+                _ => {}
+            },
+            Event::KeyDown(ke) => match ke.key_code {
+                KeyCode::Delete | KeyCode::Backspace => return self.delete_selection(cx),
+                KeyCode::Escape if !self.selection.is_empty() => {
+                    self.set_selection(cx, vec![]);
+                    return AnnotationEvent::SelectionChanged;
+                }
+                _ => {}
+            },
+            _ => {}
+        }
+        AnnotationEvent::None
+    }
+
+    pub fn handle(&mut self, cx: &mut Cx, event: &mut Event) -> AnnotationEvent {
+        let keyboard_event = self.handle_keyboard(cx, event);
+        if keyboard_event != AnnotationEvent::None {
+            return keyboard_event;
+        }
+        if cx.has_key_focus(Some(self.component_id)) && self.draft.is_none() {
+            let changed = self.undo_stack.handle(cx, event, &mut self.data);
+            if self.after_undo_or_redo(cx, changed) {
+                return AnnotationEvent::Changed;
+            }
+        }
+
+        // When selecting, only claim the pointer when it's over an annotation, so the view below stays usable.
+        let claims_pointer = match event {
+            _ if self.tool != AnnotationTool::Select => true,
+            Event::PointerDown(pe) => self.annotation_at(cx, pe.abs - self.rect.pos).is_some(),
+            Event::PointerHover(pe) => self.annotation_at(cx, pe.abs - self.rect.pos).is_some(),
+            _ => !matches!(self.drag, AnnotationDrag::None),
+        };
+        let rect = if claims_pointer { Some(self.rect) } else { None };
+        match event.hits_pointer(cx, self.component_id, rect) {
+            Event::PointerHover(pe) if pe.hover_state != HoverState::Out => {
+                cx.set_hover_mouse_cursor(if self.tool == AnnotationTool::Select {
+                    MouseCursor::Move
+                } else {
+                    MouseCursor::Crosshair
+                });
+            }
+            Event::PointerDown(pe) => {
+                cx.set_key_focus(Some(self.component_id));
+                let pos = pe.abs - self.rect.pos;
+                // Clicking elsewhere finishes a note that is being typed.
+                let finished = self.finish_draft(cx);
+                let event = self.pointer_down(cx, pos, pe.modifiers.shift);
+                if finished && event == AnnotationEvent::None {
+                    return AnnotationEvent::Changed;
+                }
+                return event;
+            }
+            Event::PointerMove(pe) => return self.pointer_move(cx, pe.abs - self.rect.pos),
+            Event::PointerUp(_) => match self.drag {
+                AnnotationDrag::Drawing { .. } => {
+                    if self.finish_draft(cx) {
+                        return AnnotationEvent::Changed;
+                    }
+                }
+                AnnotationDrag::Moving { .. } => self.end_drag(),
+                AnnotationDrag::None => {}
+            },
+            _ => {}
+        }
+        AnnotationEvent::None
+    }
+
+    fn pointer_down(&mut self, cx: &mut Cx, pos: Vec2, shift: bool) -> AnnotationEvent {
+        let shape = match self.tool {
+            AnnotationTool::Select => {
+                let id = match self.annotation_at(cx, pos) {
+                    Some(id) => id,
+                    None => return AnnotationEvent::None,
+                };
+                let old_selection = self.selection.clone();
+                if shift {
+                    if let Some(index) = self.selection.iter().position(|selected| *selected == id) {
+                        self.selection.remove(index);
+                    } else {
+                        self.selection.push(id);
+                    }
+                } else if !self.selection.contains(&id) {
+                    self.selection = vec![id];
+                }
+                if self.selection.contains(&id) {
+                    // Moving the selection is undone in one go.
+                    self.undo_stack.begin_group();
+                    self.drag = AnnotationDrag::Moving { last: pos };
+                }
+                cx.request_draw();
+                return if self.selection != old_selection { AnnotationEvent::SelectionChanged } else { AnnotationEvent::None };
+            }
+            AnnotationTool::Arrow => AnnotationShape::Arrow { from: pos, to: pos },
+            AnnotationTool::Rect => AnnotationShape::Rect(Rect { pos, size: Vec2::default() }),
+            AnnotationTool::Freehand => AnnotationShape::Freehand(vec![pos]),
+            AnnotationTool::Text => AnnotationShape::Text { pos, text: String::new() },
+        };
+        if self.tool != AnnotationTool::Text {
+            self.drag = AnnotationDrag::Drawing { start: pos };
+        }
+        self.draft = Some(Annotation { id: self.data.next_id(), shape, color: self.color });
+        cx.request_draw();
+        AnnotationEvent::None
+    }
+
+    fn pointer_move(&mut self, cx: &mut Cx, pos: Vec2) -> AnnotationEvent {
+        match &mut self.drag {
+            AnnotationDrag::Moving { last } => {
+                let delta = pos - *last;
+                *last = pos;
+                let ids = self.selection.clone();
+                self.push_command(cx, AnnotationCommand::Move { ids, delta });
+                return AnnotationEvent::Changed;
+            }
+            AnnotationDrag::Drawing { start } => {
+                let start = *start;
+                match self.draft.as_mut().map(|draft| &mut draft.shape) {
+                    Some(AnnotationShape::Arrow { to, .. }) => *to = pos,
+                    Some(AnnotationShape::Rect(rect)) => {
+                        let min = start.min(&pos);
+                        *rect = Rect { pos: min, size: start.max(&pos) - min };
+                    }
+                    Some(AnnotationShape::Freehand(points))
+                        if points.last().map_or(true, |last| last.distance(&pos) >= FREEHAND_MIN_DISTANCE) =>
+                    {
+                        points.push(pos)
+                    }
+                    _ => {}
+                }
+                cx.request_draw();
+            }
+            AnnotationDrag::None => {}
+        }
+        AnnotationEvent::None
+    }
+
+    fn draw_annotation(&mut self, cx: &mut Cx, annotation: &Annotation, is_draft: bool) {
+        let origin = self.rect.pos;
+        let mut strokes: Vec<StrokeIns> = annotation
+            .shape
+            .segments()
+            .into_iter()
+            .map(|(a, b)| stroke(a + origin, b + origin, annotation.color, STROKE_WIDTH))
+            .collect();
+
+        let bounds = match &annotation.shape {
+            AnnotationShape::Text { pos, text } => {
+                // Show a cursor while typing.
+                let text = if is_draft { format!("{}|", text) } else { text.clone() };
+                let rect = Self::note_rect(cx, *pos, &text, annotation.color).translate(origin);
+                self.note_background.draw(cx, rect, vec4(0., 0., 0., 0.7));
+                TextIns::draw_str(cx, &text, rect.pos + Vec2::all(TEXT_PADDING), &Self::note_props(annotation.color));
+                rect
+            }
+            _ => {
+                let points: Vec<Vec2> = strokes.iter().flat_map(|stroke| [stroke.start, stroke.end]).collect();
+                let min = points.iter().fold(Vec2::all(f32::INFINITY), |min, point| min.min(point));
+                let max = points.iter().fold(Vec2::all(f32::NEG_INFINITY), |max, point| max.max(point));
+                Rect { pos: min, size: max - min }
+            }
+        };
+
+        if self.selection.contains(&annotation.id) {
+            let outline = AnnotationShape::Rect(Rect { pos: bounds.pos - Vec2::all(4.), size: bounds.size + Vec2::all(8.) });
+            strokes.extend(outline.segments().into_iter().map(|(a, b)| stroke(a, b, self.accent_color, 1.)));
+        }
+        cx.add_instances(&STROKE_SHADER, &strokes);
+    }
+
+    /// Draw the annotations over `rect`, which is typically the [`Rect`] of the view that is being annotated.
+    pub fn draw(&mut self, cx: &mut Cx, rect: Rect) {
+        self.rect = rect;
+        let annotations = std::mem::take(&mut self.data.annotations);
+        for annotation in &annotations {
+            self.draw_annotation(cx, annotation, false);
+        }
+        self.data.annotations = annotations;
+        if let Some(draft) = self.draft.take() {
+            self.draw_annotation(cx, &draft, true);
+            self.draft = Some(draft);
+        }
+    }
+}
+
+fn stroke(start: Vec2, end: Vec2, color: Vec4, width: f32) -> StrokeIns {
+    let pad = Vec2::all(width + 1.);
+    let min = start.min(&end) - pad;
+    let size = start.max(&end) + pad - min;
+    StrokeIns { base: QuadIns::from_rect(Rect { pos: min, size }), color, start, end, width }
+}
+
+fn distance_to_segment(p: Vec2, a: Vec2, b: Vec2) -> f32 {
+    let ab = b - a;
+    let length_squared = ab.dot(ab);
+    let t = if length_squared > 0. { ((p - a).dot(ab) / length_squared).clamp(0., 1.) } else { 0. };
+    (a + ab * t).distance(&p)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    use zaplib::undo::*;
+    use zaplib::*;
+
+    #[test]
+    fn test_annotation_commands() {
+        let mut data = AnnotationData::default();
+        let arrow = data.add(AnnotationShape::Arrow { from: vec2(0., 0.), to: vec2(10., 0.) }, COLOR_WHITE);
+        let note = data.add(AnnotationShape::Text { pos: vec2(5., 5.), text: "Look".to_string() }, COLOR_WHITE);
+        let original = data.clone();
+
+        let mut undo_stack = UndoStack::new();
+        undo_stack.push(&mut data, AnnotationCommand::Move { ids: vec![note], delta: vec2(1., 0.) }, 0.);
+        undo_stack.push(&mut data, AnnotationCommand::Move { ids: vec![note], delta: vec2(2., 1.) }, 0.1);
+        assert_eq!(data.get(note).unwrap().shape, AnnotationShape::Text { pos: vec2(8., 6.), text: "Look".to_string() });
+
+        let remove = AnnotationCommand::remove(&data, &[arrow]);
+        undo_stack.push(&mut data, remove, 5.);
+        assert_eq!(data.annotations.len(), 1);
+
+        // The moves got merged, so undoing twice gets back to the start.
+        assert!(undo_stack.undo(&mut data));
+        assert!(undo_stack.undo(&mut data));
+        assert_eq!(data, original);
+    }
+
+    #[test]
+    fn test_annotation_layer_draw_select_and_undo() {
+        let mut layer = AnnotationLayer::default().with_tool(AnnotationTool::Rect);
+        let rect = Rect { pos: vec2(0., 0.), size: vec2(400., 300.) };
+        let mut test_cx = TestCx::new(vec2(400., 300.));
+        test_cx.draw(|cx| layer.draw(cx, rect));
+
+        test_cx.pointer_down(vec2(20., 20.), |cx, event| layer.handle(cx, event));
+        test_cx.pointer_move(vec2(120., 80.), |cx, event| layer.handle(cx, event));
+        let events = test_cx.pointer_up(vec2(120., 80.), |cx, event| layer.handle(cx, event));
+        assert_eq!(events, vec![AnnotationEvent::Changed]);
+        assert_eq!(layer.data().annotations[0].shape, AnnotationShape::Rect(Rect { pos: vec2(20., 20.), size: vec2(100., 60.) }));
+
+        // Drag the rectangle by its edge.
+        test_cx.dispatch(Event::None, |cx, _| layer.set_tool(cx, AnnotationTool::Select));
+        test_cx.draw(|cx| layer.draw(cx, rect));
+        let events = test_cx.pointer_down(vec2(20., 50.), |cx, event| layer.handle(cx, event));
+        assert!(events.contains(&AnnotationEvent::SelectionChanged));
+        test_cx.pointer_move(vec2(30., 50.), |cx, event| layer.handle(cx, event));
+        test_cx.pointer_move(vec2(40., 60.), |cx, event| layer.handle(cx, event));
+        test_cx.pointer_up(vec2(40., 60.), |cx, event| layer.handle(cx, event));
+        assert_eq!(layer.data().annotations[0].shape, AnnotationShape::Rect(Rect { pos: vec2(40., 30.), size: vec2(100., 60.) }));
+
+        // Undoing the move takes one step, and then the rectangle can be removed.
+        let control = KeyModifiers { control: true, ..KeyModifiers::default() };
+        test_cx.key_down(KeyCode::KeyZ, control.clone(), |cx, event| layer.handle(cx, event));
+        assert_eq!(layer.data().annotations[0].shape, AnnotationShape::Rect(Rect { pos: vec2(20., 20.), size: vec2(100., 60.) }));
+        test_cx.key_down(KeyCode::Delete, KeyModifiers::default(), |cx, event| layer.handle(cx, event));
+        assert!(layer.data().annotations.is_empty());
+        test_cx.key_down(KeyCode::KeyZ, control.clone(), |cx, event| layer.handle(cx, event));
+        assert_eq!(layer.data().annotations.len(), 1);
+
+        // Undo is ignored while moving, and the move is still one step afterwards.
+        test_cx.pointer_down(vec2(20., 50.), |cx, event| layer.handle(cx, event));
+        test_cx.pointer_move(vec2(30., 50.), |cx, event| layer.handle(cx, event));
+        test_cx.key_down(KeyCode::KeyZ, control.clone(), |cx, event| layer.handle(cx, event));
+        test_cx.pointer_move(vec2(40., 50.), |cx, event| layer.handle(cx, event));
+        test_cx.pointer_up(vec2(40., 50.), |cx, event| layer.handle(cx, event));
+        assert_eq!(layer.data().annotations[0].shape, AnnotationShape::Rect(Rect { pos: vec2(40., 20.), size: vec2(100., 60.) }));
+        test_cx.key_down(KeyCode::KeyZ, control, |cx, event| layer.handle(cx, event));
+        assert_eq!(layer.data().annotations[0].shape, AnnotationShape::Rect(Rect { pos: vec2(20., 20.), size: vec2(100., 60.) }));
+
+        // Dragging inside the rectangle doesn't hit it, so it's left to the view below.
+        test_cx.pointer_down(vec2(90., 60.), |cx, event| layer.handle(cx, event));
+        test_cx.pointer_move(vec2(150., 60.), |cx, event| layer.handle(cx, event));
+        test_cx.pointer_up(vec2(150., 60.), |cx, event| layer.handle(cx, event));
+        assert_eq!(layer.data().annotations[0].shape, AnnotationShape::Rect(Rect { pos: vec2(20., 20.), size: vec2(100., 60.) }));
+    }
+
+    #[cfg(feature = "serde-support")]
+    #[test]
+    fn test_annotation_json() {
+        let mut data = AnnotationData::default();
+        data.add(AnnotationShape::Freehand(vec![vec2(0., 0.), vec2(3., 4.)]), COLOR_WHITE);
+        data.add(AnnotationShape::Text { pos: vec2(5., 5.), text: "Spike\nhere".to_string() }, COLOR_WHITE);
+        assert_eq!(AnnotationData::from_json(&data.to_json().unwrap()).unwrap(), data);
+        assert!(AnnotationData::from_json("{}").is_err());
+    }
+}
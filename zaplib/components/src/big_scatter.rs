@@ -0,0 +1,869 @@
+//! Scatter plot for large datasets, with brushing to select points.
+
+use crate::background::*;
+use crate::plot_axes::*;
+use std::cmp::Ordering;
+use std::sync::Arc;
+use zaplib::spatial_index::SpatialIndex;
+use zaplib::*;
+
+/// How close (in pixels) the pointer has to be to a point to hover or click it.
+const POINT_HIT_DISTANCE: f32 = 8.;
+/// Pointer movement (in pixels) below which a left drag counts as a click.
+const CLICK_DISTANCE: f32 = 3.;
+/// Minimum distance (in pixels) between the points of a lasso.
+const LASSO_SPACING: f32 = 3.;
+
+/// Maps a value between 0 and 1 to a color, e.g. to color points by a column of a [`BigScatter`].
+#[derive(Clone, Debug, PartialEq)]
+pub enum Colormap {
+    Viridis,
+    Plasma,
+    Grayscale,
+    /// Colors that are evenly spaced between 0 and 1, with linear interpolation in between.
+    Custom(Vec<Vec4>),
+}
+
+impl Default for Colormap {
+    fn default() -> Self {
+        Colormap::Viridis
+    }
+}
+
+impl Colormap {
+    const VIRIDIS: [u32; 9] =
+        [0x440154ff, 0x472c7aff, 0x3b518bff, 0x2c718eff, 0x21908dff, 0x27ad81ff, 0x5cc863ff, 0xaadc32ff, 0xfde725ff];
+    const PLASMA: [u32; 9] =
+        [0x0d0887ff, 0x4c02a1ff, 0x7e03a8ff, 0xa92395ff, 0xcc4778ff, 0xe56b5dff, 0xf89441ff, 0xfdc328ff, 0xf0f921ff];
+
+    fn stops(&self) -> Vec<Vec4> {
+        match self {
+            Colormap::Viridis => Self::VIRIDIS.iter().map(|hex| Vec4::from_u32(*hex)).collect(),
+            Colormap::Plasma => Self::PLASMA.iter().map(|hex| Vec4::from_u32(*hex)).collect(),
+            Colormap::Grayscale => vec![vec4(0., 0., 0., 1.), vec4(1., 1., 1., 1.)],
+            Colormap::Custom(colors) => colors.clone(),
+        }
+    }
+
+    /// The color at `t`, which gets clamped between 0 and 1.
+    pub fn sample(&self, t: f32) -> Vec4 {
+        let stops = self.stops();
+        match stops.len() {
+            0 => vec4(0., 0., 0., 1.),
+            1 => stops[0],
+            len => {
+                let position = t.clamp(0., 1.) * (len - 1) as f32;
+                let index = (position.floor() as usize).min(len - 2);
+                Vec4::mix(stops[index], stops[index + 1], position - index as f32)
+            }
+        }
+    }
+}
+
+/// How dragging with the left mouse button selects points in a [`BigScatter`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BrushMode {
+    /// Select the points inside a rectangle.
+    Box,
+    /// Select the points inside a freehand shape.
+    Lasso,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum BigScatterEvent {
+    None,
+    /// The point under the pointer changed; [`None`] when there's no point under it anymore.
+    PointHovered(Option<usize>),
+    /// The user selected points, by brushing or clicking; contains the indices of all selected points, in
+    /// increasing order. Not sent for [`BigScatter::set_selection`] and for changes of the linked selection (see
+    /// [`BigScatter::with_linked_selection`]).
+    SelectionChanged(Vec<usize>),
+    /// The user panned or zoomed.
+    ViewChanged,
+}
+
+#[derive(Clone, Copy, Default)]
+#[repr(C)]
+struct PointIns {
+    data_pos: Vec2,
+    color_value: f32,
+    size_value: f32,
+    selected: f32,
+}
+
+#[repr(C)]
+struct PointUniforms {
+    base_color: Vec4,
+    view_min: Vec2,
+    view_max: Vec2,
+    plot_pos: Vec2,
+    plot_size: Vec2,
+    color_domain: Vec2,
+    size_domain: Vec2,
+    radius_range: Vec2,
+    use_colormap: f32,
+    has_selection: f32,
+}
+
+static POINT_SHADER: Shader = Shader {
+    build_geom: Some(QuadIns::build_geom),
+    code_to_concatenate: &[
+        Cx::STD_SHADER,
+        code_fragment!(
+            r#"
+            uniform base_color: vec4;
+            uniform view_min: vec2;
+            uniform view_max: vec2;
+            uniform plot_pos: vec2;
+            uniform plot_size: vec2;
+            uniform color_domain: vec2;
+            uniform size_domain: vec2;
+            uniform radius_range: vec2;
+            uniform use_colormap: float;
+            uniform has_selection: float;
+            texture colormap: texture2D;
+
+            geometry geom: vec2;
+
+            instance data_pos: vec2;
+            instance color_value: float;
+            instance size_value: float;
+            instance selected: float;
+
+            varying offset: vec2;
+            varying radius: float;
+
+            fn vertex() -> vec4 {
+                // Points are in data coordinates, so panning and zooming only changes the uniforms.
+                let size_span = max(size_domain.y - size_domain.x, 0.000001);
+                radius = mix(radius_range.x, radius_range.y, clamp((size_value - size_domain.x) / size_span, 0., 1.));
+                let t = (data_pos - view_min) / (view_max - view_min);
+                let center = plot_pos + vec2(t.x, 1. - t.y) * plot_size;
+
+                let scr = snap_to_pixel(draw_scroll);
+                let clipped: vec2 = clamp(
+                    center + (geom * 2. - 1.) * (radius + 1.) - scr,
+                    draw_clip.xy,
+                    draw_clip.zw
+                );
+                offset = clipped + scr - center;
+                return camera_projection * (camera_view * vec4(clipped.x, clipped.y, draw_zbias, 1.));
+            }
+
+            fn pixel() -> vec4 {
+                let color_span = max(color_domain.y - color_domain.x, 0.000001);
+                let color_t = clamp((color_value - color_domain.x) / color_span, 0., 1.);
+                let color = mix(base_color, sample2d(colormap, vec2(color_t, 0.5)), use_colormap);
+                let coverage = clamp(radius - length(offset) + 0.5, 0., 1.);
+                // Fade out the points that are not selected, while there is a selection.
+                let alpha = color.a * coverage * mix(1., 0.15, has_selection * (1. - selected));
+                return vec4(color.rgb * alpha, alpha);
+            }"#
+        ),
+    ],
+    ..Shader::DEFAULT
+};
+
+#[derive(Clone, Copy, Default)]
+#[repr(C)]
+struct LassoIns {
+    base: QuadIns,
+    color: Vec4,
+    start: Vec2,
+    end: Vec2,
+}
+
+static LASSO_SHADER: Shader = Shader {
+    build_geom: Some(QuadIns::build_geom),
+    code_to_concatenate: &[
+        Cx::STD_SHADER,
+        QuadIns::SHADER,
+        code_fragment!(
+            r#"
+            instance color: vec4;
+            instance start: vec2;
+            instance end: vec2;
+
+            fn pixel() -> vec4 {
+                // Each instance is one segment of the lasso, in absolute coordinates.
+                let df = Df::viewport(pos * rect_size);
+                df.move_to(start - rect_pos);
+                df.line_to(end - rect_pos);
+                return df.stroke(color, 1.5);
+            }"#
+        ),
+    ],
+    ..Shader::DEFAULT
+};
+
+enum ScatterDrag {
+    None,
+    Panning {
+        start_abs: Vec2,
+        start_view: (Vec2, Vec2),
+    },
+    /// Brushing with the left mouse button, in absolute coordinates. `points` contains the lasso for
+    /// [`BrushMode::Lasso`], and is empty for [`BrushMode::Box`].
+    Brushing {
+        start: Vec2,
+        end: Vec2,
+        points: Vec<Vec2>,
+        additive: bool,
+    },
+}
+
+/// A scatter plot that stays fast with millions of points.
+///
+/// Points are uploaded to the GPU once, in data coordinates, so panning and zooming doesn't touch them. They can
+/// be colored by a column using a [`Colormap`] (see [`BigScatter::set_color_column`]), and sized by another (see
+/// [`BigScatter::set_size_column`]); that mapping happens in the shader as well.
+///
+/// Drag with the left mouse button to select points using a box or a lasso (see [`BigScatter::with_brush_mode`]);
+/// hold shift to add to the selection. Click a point to select just that point, or click next to the points to
+/// clear the selection. Drag with the right mouse button to pan, use the mouse wheel or pinch to zoom, and
+/// double-click to show all points again; see [`Cx::interaction_policy`] for changing or constraining zooming.
+///
+/// The selection can be shared with other components, e.g. to highlight the same rows in a table; see
+/// [`BigScatter::with_linked_selection`].
+///
+/// To find points under the pointer and inside brushes, a [`SpatialIndex`] gets built on a different thread
+/// whenever the data changes; until it is ready, all points are checked one by one.
+pub struct BigScatter {
+    component_id: ComponentId,
+    view: View,
+    points_view: View,
+    points_area: Area,
+    background: Background,
+    plot_background: Background,
+    hover_background: Background,
+    brush_background: Background,
+    axes: PlotAxes,
+    positions: Arc<Vec<Vec2>>,
+    color_column: Option<Vec<f32>>,
+    size_column: Option<Vec<f32>>,
+    color_domain: (f32, f32),
+    size_domain: (f32, f32),
+    data_min: Vec2,
+    data_max: Vec2,
+    /// The data coordinates at the bottom left and top right of the plot, once the user panned or zoomed. When
+    /// [`None`], the plot fits all the data.
+    view_range: Option<(Vec2, Vec2)>,
+    index: Option<SpatialIndex>,
+    index_task: Option<Task<SpatialIndex>>,
+    /// Sorted indices of the selected points.
+    selection: Vec<usize>,
+    /// For every point whether it's in [`BigScatter::selection`].
+    selected: Vec<bool>,
+    hovered: Option<usize>,
+    drag: ScatterDrag,
+    brush_mode: BrushMode,
+    /// See [`BigScatter::with_linked_selection`].
+    linked_selection: Option<String>,
+    /// The area that points are drawn in, in absolute coordinates.
+    plot_rect: Rect,
+    /// Changes whenever the point instances need to be uploaded again.
+    points_generation: u64,
+    colormap: Colormap,
+    colormap_texture: Texture,
+    colormap_dirty: bool,
+    color: Vec4,
+    radius_range: (f32, f32),
+    background_color: Vec4,
+    plot_color: Vec4,
+    accent_color: Vec4,
+}
+
+impl Default for BigScatter {
+    fn default() -> Self {
+        Self {
+            component_id: Default::default(),
+            view: View::default(),
+            points_view: View::default(),
+            points_area: Area::Empty,
+            background: Background::default(),
+            plot_background: Background::default(),
+            hover_background: Background::default(),
+            brush_background: Background::default(),
+            axes: PlotAxes::default(),
+            positions: Arc::new(vec![]),
+            color_column: None,
+            size_column: None,
+            color_domain: (0., 1.),
+            size_domain: (0., 1.),
+            data_min: vec2(0., 0.),
+            data_max: vec2(1., 1.),
+            view_range: None,
+            index: None,
+            index_task: None,
+            selection: vec![],
+            selected: vec![],
+            hovered: None,
+            drag: ScatterDrag::None,
+            brush_mode: BrushMode::Box,
+            linked_selection: None,
+            plot_rect: Rect::default(),
+            points_generation: 0,
+            colormap: Colormap::default(),
+            colormap_texture: Texture::default(),
+            colormap_dirty: true,
+            color: vec4(0.35, 0.6, 0.95, 0.8),
+            radius_range: (2., 2.),
+            background_color: vec4(0.12, 0.12, 0.12, 1.),
+            plot_color: vec4(0.08, 0.08, 0.08, 1.),
+            accent_color: vec4(1., 0.75, 0.2, 1.),
+        }
+    }
+}
+
+/// The minimum and maximum of the finite values in `values`, or [`None`] if there are none.
+fn finite_range(values: &[f32]) -> Option<(f32, f32)> {
+    values.iter().filter(|value| value.is_finite()).fold(None, |range, &value| match range {
+        None => Some((value, value)),
+        Some((min, max)) => Some((value.min(min), value.max(max))),
+    })
+}
+
+/// Whether `point` is inside `polygon`, using the even-odd rule.
+fn polygon_contains(polygon: &[Vec2], point: Vec2) -> bool {
+    let mut inside = false;
+    for (index, a) in polygon.iter().enumerate() {
+        let b = polygon[(index + 1) % polygon.len()];
+        if (a.y > point.y) != (b.y > point.y) && point.x < a.x + (point.y - a.y) / (b.y - a.y) * (b.x - a.x) {
+            inside = !inside;
+        }
+    }
+    inside
+}
+
+impl BigScatter {
+    const COLORMAP_SIZE: usize = 256;
+
+    /// Color of the points when there's no color column.
+    #[must_use]
+    pub fn with_color(self, color: Vec4) -> Self {
+        Self { color, ..self }
+    }
+    /// Radius of the points in pixels. With a size column, the points range from `min_radius` to `max_radius`;
+    /// otherwise they all get `min_radius`.
+    #[must_use]
+    pub fn with_radius_range(self, min_radius: f32, max_radius: f32) -> Self {
+        Self { radius_range: (min_radius, max_radius), ..self }
+    }
+    #[must_use]
+    pub fn with_colormap(self, colormap: Colormap) -> Self {
+        Self { colormap, colormap_dirty: true, ..self }
+    }
+    #[must_use]
+    pub fn with_brush_mode(self, brush_mode: BrushMode) -> Self {
+        Self { brush_mode, ..self }
+    }
+    /// Share the selection with other components that show the same data, under `name`; see
+    /// [`Cx::publish_selection`]. Indices are those of the points.
+    #[must_use]
+    pub fn with_linked_selection(self, name: &str) -> Self {
+        Self { linked_selection: Some(name.to_string()), ..self }
+    }
+    /// Color of the selection brush and of the highlight of the hovered point.
+    #[must_use]
+    pub fn with_accent_color(self, accent_color: Vec4) -> Self {
+        Self { accent_color, ..self }
+    }
+
+    /// Set the positions of the points; `x` and `y` should have the same length. Clears the selection and the
+    /// color and size columns.
+    pub fn set_data(&mut self, cx: &mut Cx, x: &[f32], y: &[f32]) {
+        assert_eq!(x.len(), y.len(), "x and y should have the same length");
+        let positions: Vec<Vec2> = x.iter().zip(y).map(|(&x, &y)| vec2(x, y)).collect();
+        let (min_x, max_x) = finite_range(x).unwrap_or((0., 1.));
+        let (min_y, max_y) = finite_range(y).unwrap_or((0., 1.));
+        self.data_min = vec2(min_x, min_y);
+        self.data_max = vec2(max_x, max_y);
+        self.positions = Arc::new(positions);
+        self.color_column = None;
+        self.size_column = None;
+        self.selection.clear();
+        self.selected = vec![false; x.len()];
+        self.hovered = None;
+        if let Some(name) = &self.linked_selection {
+            let selection = cx.linked_selection(name).indices().take_while(|index| *index < x.len()).collect();
+            self.apply_selection(selection);
+        }
+
+        self.index = None;
+        let positions = Arc::clone(&self.positions);
+        self.index_task = Some(cx.spawn(spawn_blocking(move || SpatialIndex::from_points(&positions))));
+        self.points_generation += 1;
+        cx.request_draw();
+    }
+
+    /// Color the points by `values` (one per point), using the [`Colormap`]; the lowest value gets the start of
+    /// the colormap and the highest value the end. Pass [`None`] to give all points the same color again.
+    pub fn set_color_column(&mut self, cx: &mut Cx, values: Option<Vec<f32>>) {
+        if let Some(values) = &values {
+            assert_eq!(values.len(), self.positions.len(), "Color column should have a value for every point");
+            self.color_domain = finite_range(values).unwrap_or((0., 1.));
+        }
+        self.color_column = values;
+        self.points_generation += 1;
+        cx.request_draw();
+    }
+
+    /// Size the points by `values` (one per point), from the minimum to the maximum radius set using
+    /// [`BigScatter::with_radius_range`]. Pass [`None`] to give all points the same size again.
+    pub fn set_size_column(&mut self, cx: &mut Cx, values: Option<Vec<f32>>) {
+        if let Some(values) = &values {
+            assert_eq!(values.len(), self.positions.len(), "Size column should have a value for every point");
+            self.size_domain = finite_range(values).unwrap_or((0., 1.));
+        }
+        self.size_column = values;
+        self.points_generation += 1;
+        cx.request_draw();
+    }
+
+    pub fn set_colormap(&mut self, cx: &mut Cx, colormap: Colormap) {
+        self.colormap = colormap;
+        self.colormap_dirty = true;
+        cx.request_draw();
+    }
+
+    /// Sorted indices of the selected points.
+    pub fn selection(&self) -> &[usize] {
+        &self.selection
+    }
+
+    /// Select points by their indices, e.g. to show the selection of a linked plot. Indices that are out of range
+    /// are ignored.
+    pub fn set_selection(&mut self, cx: &mut Cx, selection: &[usize]) {
+        self.apply_selection(selection.iter().copied().filter(|index| *index < self.positions.len()).collect());
+        cx.request_draw();
+    }
+
+    /// The point under the pointer, if any.
+    pub fn hovered(&self) -> Option<usize> {
+        self.hovered
+    }
+
+    /// The data coordinates at the bottom left and top right of the plot.
+    pub fn view_range(&self) -> (Vec2, Vec2) {
+        self.view_range.unwrap_or_else(|| {
+            // Leave a bit of room around the data, and make sure that the range is never empty.
+            let padding = ((self.data_max - self.data_min) * 0.05).max(&Vec2::all(0.5));
+            (self.data_min - padding, self.data_max + padding)
+        })
+    }
+
+    pub fn set_view_range(&mut self, cx: &mut Cx, min: Vec2, max: Vec2) {
+        self.view_range = Some((min, max));
+        cx.request_draw();
+    }
+
+    /// Fit all the data in the plot again, after panning or zooming.
+    pub fn reset_view(&mut self, cx: &mut Cx) {
+        self.view_range = None;
+        cx.request_draw();
+    }
+
+    fn to_screen(&self, data_pos: Vec2) -> Vec2 {
+        let (min, max) = self.view_range();
+        let t = (data_pos - min) / (max - min);
+        self.plot_rect.pos + vec2(t.x, 1. - t.y) * self.plot_rect.size
+    }
+
+    fn to_data(&self, abs: Vec2) -> Vec2 {
+        let (min, max) = self.view_range();
+        let t = (abs - self.plot_rect.pos) / self.plot_rect.size;
+        min + vec2(t.x, 1. - t.y) * (max - min)
+    }
+
+    /// The data coordinates covered by a rectangle in absolute coordinates.
+    fn to_data_rect(&self, a: Vec2, b: Vec2) -> Rect {
+        let (a, b) = (self.to_data(a), self.to_data(b));
+        let min = a.min(&b);
+        Rect { pos: min, size: a.max(&b) - min }
+    }
+
+    fn radius(&self, index: usize) -> f32 {
+        match &self.size_column {
+            Some(values) => {
+                let span = (self.size_domain.1 - self.size_domain.0).max(1e-6);
+                let t = ((values[index] - self.size_domain.0) / span).clamp(0., 1.);
+                self.radius_range.0 + (self.radius_range.1 - self.radius_range.0) * t
+            }
+            None => self.radius_range.0,
+        }
+    }
+
+    /// Indices of the points inside `rect`, in data coordinates.
+    fn points_in(&self, rect: Rect) -> Vec<usize> {
+        match &self.index {
+            Some(index) => index.search(rect),
+            None => {
+                let max = rect.pos + rect.size;
+                (0..self.positions.len())
+                    .filter(|&index| {
+                        let pos = self.positions[index];
+                        pos.x >= rect.pos.x && pos.x <= max.x && pos.y >= rect.pos.y && pos.y <= max.y
+                    })
+                    .collect()
+            }
+        }
+    }
+
+    fn point_at(&self, abs: Vec2) -> Option<usize> {
+        if !self.plot_rect.contains(abs) {
+            return None;
+        }
+        let rect = self.to_data_rect(abs - Vec2::all(POINT_HIT_DISTANCE), abs + Vec2::all(POINT_HIT_DISTANCE));
+        // The index works in data coordinates, where distances are stretched differently horizontally and
+        // vertically, so pick the nearest point in pixels ourselves.
+        self.points_in(rect)
+            .into_iter()
+            .map(|index| (index, self.to_screen(self.positions[index]).distance(&abs)))
+            .filter(|(_, distance)| *distance <= POINT_HIT_DISTANCE)
+            .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(Ordering::Equal))
+            .map(|(index, _)| index)
+    }
+
+    /// Set the selection; returns whether it changed.
+    fn apply_selection(&mut self, mut selection: Vec<usize>) -> bool {
+        selection.sort_unstable();
+        selection.dedup();
+        if selection == self.selection {
+            return false;
+        }
+        for index in &self.selection {
+            self.selected[*index] = false;
+        }
+        for index in &selection {
+            self.selected[*index] = true;
+        }
+        self.selection = selection;
+        self.points_generation += 1;
+        true
+    }
+
+    /// Tell the outside world that the user changed the selection.
+    fn selection_event(&self, cx: &mut Cx) -> BigScatterEvent {
+        if let Some(name) = &self.linked_selection {
+            cx.publish_selection(name, self.component_id, SelectionSet::from_indices(&self.selection));
+        }
+        BigScatterEvent::SelectionChanged(self.selection.clone())
+    }
+
+    fn pointer_down(&mut self, cx: &mut Cx, pe: &PointerDownEvent) {
+        if pe.button == MouseButton::Right {
+            cx.set_down_mouse_cursor(MouseCursor::Grabbing);
+            self.drag = ScatterDrag::Panning { start_abs: pe.abs, start_view: self.view_range() };
+        } else {
+            let points = if self.brush_mode == BrushMode::Lasso { vec![pe.abs] } else { vec![] };
+            self.drag = ScatterDrag::Brushing { start: pe.abs, end: pe.abs, points, additive: pe.modifiers.shift };
+        }
+    }
+
+    fn pointer_move(&mut self, cx: &mut Cx, pe: &PointerMoveEvent) -> BigScatterEvent {
+        match &mut self.drag {
+            ScatterDrag::None => BigScatterEvent::None,
+            ScatterDrag::Panning { start_abs, start_view: (min, max) } => {
+                let delta = (pe.abs - *start_abs) / self.plot_rect.size * (*max - *min);
+                let delta = vec2(-delta.x, delta.y);
+                self.view_range = Some((*min + delta, *max + delta));
+                cx.request_draw();
+                BigScatterEvent::ViewChanged
+            }
+            ScatterDrag::Brushing { end, points, .. } => {
+                *end = pe.abs;
+                if !points.is_empty() && points.last().unwrap().distance(&pe.abs) >= LASSO_SPACING {
+                    points.push(pe.abs);
+                }
+                cx.request_draw();
+                BigScatterEvent::None
+            }
+        }
+    }
+
+    fn pointer_up(&mut self, cx: &mut Cx) -> BigScatterEvent {
+        let (start, end, points, additive) = match std::mem::replace(&mut self.drag, ScatterDrag::None) {
+            ScatterDrag::Brushing { start, end, points, additive } => (start, end, points, additive),
+            _ => return BigScatterEvent::None,
+        };
+        cx.request_draw();
+        let mut selection = if additive { self.selection.clone() } else { vec![] };
+
+        let is_click = if points.is_empty() {
+            start.distance(&end) < CLICK_DISTANCE
+        } else {
+            points.iter().all(|point| point.distance(&start) < CLICK_DISTANCE)
+        };
+        if is_click {
+            if let Some(index) = self.point_at(start) {
+                match selection.iter().position(|selected| *selected == index) {
+                    Some(position) if additive => {
+                        selection.remove(position);
+                    }
+                    _ => selection.push(index),
+                }
+            }
+        } else if points.is_empty() {
+            selection.extend(self.points_in(self.to_data_rect(start, end)));
+        } else {
+            // Check the bounding box of the lasso first, to only test the remaining points against the polygon.
+            let polygon: Vec<Vec2> = points.iter().map(|point| self.to_data(*point)).collect();
+            let min = polygon.iter().fold(polygon[0], |min, point| min.min(point));
+            let max = polygon.iter().fold(polygon[0], |max, point| max.max(point));
+            let candidates = self.points_in(Rect { pos: min, size: max - min });
+            selection.extend(candidates.into_iter().filter(|index| polygon_contains(&polygon, self.positions[*index])));
+        }
+
+        if self.apply_selection(selection) {
+            self.selection_event(cx)
+        } else {
+            BigScatterEvent::None
+        }
+    }
+
+    fn pointer_scroll(&mut self, cx: &mut Cx, pe: &PointerScrollEvent) {
+        let (min, max) = self.view_range();
+        match cx.interaction_policy().scroll_action(pe) {
+            ScrollAction::Zoom { factor, axes } => {
+                // Keep the point under the pointer in the same place.
+                let factors = axes.factors(factor);
+                let anchor = self.to_data(pe.abs);
+                self.view_range = Some((anchor - (anchor - min) / factors, anchor + (max - anchor) / factors));
+            }
+            ScrollAction::Pan(delta) => {
+                let delta = delta / self.plot_rect.size * (max - min);
+                let delta = vec2(delta.x, -delta.y);
+                self.view_range = Some((min + delta, max + delta));
+            }
+        }
+        cx.request_draw();
+    }
+
+    pub fn handle(&mut self, cx: &mut Cx, event: &mut Event) -> BigScatterEvent {
+        if let Some(index) = self.index_task.as_ref().and_then(|task| task.take_output(event)) {
+            self.index_task = None;
+            self.index = Some(index);
+            return BigScatterEvent::None;
+        }
+
+        if let Some(name) = &self.linked_selection {
+            if let Some(selection) = cx.linked_selection_changed(event, name, self.component_id) {
+                let len = self.positions.len();
+                let selection = selection.indices().take_while(|index| *index < len).collect();
+                if self.apply_selection(selection) {
+                    cx.request_draw();
+                }
+                return BigScatterEvent::None;
+            }
+        }
+
+        if let Event::KeyDown(ke) = event.hits_keyboard(cx, self.component_id) {
+            if ke.key_code == KeyCode::Escape && self.apply_selection(vec![]) {
+                cx.request_draw();
+                return self.selection_event(cx);
+            }
+        }
+
+        match event.hits_pointer(cx, self.component_id, self.plot_background.area().get_rect_for_first_instance(cx)) {
+            Event::PointerHover(pe) => {
+                let hovered = if pe.hover_state == HoverState::Out { None } else { self.point_at(pe.abs) };
+                cx.set_hover_mouse_cursor(if hovered.is_some() { MouseCursor::Hand } else { MouseCursor::Crosshair });
+                if hovered != self.hovered {
+                    self.hovered = hovered;
+                    cx.request_draw();
+                    return BigScatterEvent::PointHovered(hovered);
+                }
+            }
+            Event::PointerDown(pe) if cx.interaction_policy().is_reset(&pe) => {
+                self.reset_view(cx);
+                return BigScatterEvent::ViewChanged;
+            }
+            Event::PointerDown(pe) => {
+                cx.set_key_focus(Some(self.component_id));
+                self.pointer_down(cx, &pe);
+            }
+            Event::PointerMove(pe) => return self.pointer_move(cx, &pe),
+            Event::PointerUp(_) => return self.pointer_up(cx),
+            Event::PointerScroll(pe) => {
+                self.pointer_scroll(cx, &pe);
+                return BigScatterEvent::ViewChanged;
+            }
+            _ => {}
+        }
+        BigScatterEvent::None
+    }
+
+    fn update_colormap_texture(&mut self, cx: &mut Cx) {
+        let handle = self.colormap_texture.get_with_dimensions(cx, Self::COLORMAP_SIZE, 1);
+        if !self.colormap_dirty {
+            return;
+        }
+        self.colormap_dirty = false;
+        let colormap = &self.colormap;
+        for (index, pixel) in handle.get_image_mut(cx).iter_mut().enumerate() {
+            let color = colormap.sample(index as f32 / (Self::COLORMAP_SIZE - 1) as f32);
+            let to_byte = |value: f32| (value.clamp(0., 1.) * 255.).round() as u8;
+            *pixel = u32::from_le_bytes([to_byte(color.x), to_byte(color.y), to_byte(color.z), to_byte(color.w)]);
+        }
+    }
+
+    fn draw_points(&mut self, cx: &mut Cx) {
+        cx.set_draw_pos(self.plot_rect.pos);
+        let layout_size = LayoutSize::new(Width::Fix(self.plot_rect.size.x), Height::Fix(self.plot_rect.size.y));
+        if self.points_view.begin_cached_view(cx, layout_size, self.points_generation) {
+            let instances: Vec<PointIns> = self
+                .positions
+                .iter()
+                .enumerate()
+                .map(|(index, data_pos)| PointIns {
+                    data_pos: *data_pos,
+                    color_value: self.color_column.as_ref().map_or(0., |values| values[index]),
+                    size_value: self.size_column.as_ref().map_or(0., |values| values[index]),
+                    selected: if self.selected[index] { 1. } else { 0. },
+                })
+                .collect();
+            self.points_area = cx.add_instances(&POINT_SHADER, &instances);
+            self.points_view.end_view(cx);
+        }
+
+        let handle = self.colormap_texture.unwrap_texture_handle();
+        self.points_area.write_texture_2d(cx, "colormap", handle);
+        let (view_min, view_max) = self.view_range();
+        let radius_range =
+            if self.size_column.is_some() { self.radius_range } else { (self.radius_range.0, self.radius_range.0) };
+        self.points_area.write_user_uniforms(
+            cx,
+            PointUniforms {
+                base_color: self.color,
+                view_min,
+                view_max,
+                plot_pos: self.plot_rect.pos,
+                plot_size: self.plot_rect.size,
+                color_domain: vec2(self.color_domain.0, self.color_domain.1),
+                size_domain: vec2(self.size_domain.0, self.size_domain.1),
+                radius_range: vec2(radius_range.0, radius_range.1),
+                use_colormap: if self.color_column.is_some() { 1. } else { 0. },
+                has_selection: if self.selection.is_empty() { 0. } else { 1. },
+            },
+        );
+    }
+
+    fn draw_brush(&mut self, cx: &mut Cx) {
+        if let ScatterDrag::Brushing { start, end, points, .. } = &self.drag {
+            let fill_color = vec4(self.accent_color.x, self.accent_color.y, self.accent_color.z, 0.2);
+            if points.is_empty() {
+                let min = start.min(end);
+                self.brush_background.draw(cx, Rect { pos: min, size: start.max(end) - min }, fill_color);
+                return;
+            }
+            // Close the lasso, so it's clear what will get selected.
+            let instances: Vec<LassoIns> = points
+                .iter()
+                .zip(points.iter().skip(1).chain(points.first()))
+                .map(|(a, b)| {
+                    let pad = Vec2::all(2.);
+                    let min = a.min(b) - pad;
+                    let size = a.max(b) + pad - min;
+                    LassoIns { base: QuadIns::from_rect(Rect { pos: min, size }), color: self.accent_color, start: *a, end: *b }
+                })
+                .collect();
+            cx.add_instances(&LASSO_SHADER, &instances);
+        }
+    }
+
+    pub fn draw(&mut self, cx: &mut Cx) {
+        self.view.begin_view(cx, LayoutSize::FILL);
+        let rect = cx.add_box(LayoutSize::FILL);
+        self.plot_rect = PlotAxes::plot_rect(rect);
+        self.background.draw(cx, rect, self.background_color);
+        self.plot_background.draw(cx, self.plot_rect, self.plot_color);
+
+        let (view_min, view_max) = self.view_range();
+        self.axes.draw_x(cx, self.plot_rect, (view_min.x as f64, view_max.x as f64));
+        self.axes.draw_y(cx, self.plot_rect, (view_min.y as f64, view_max.y as f64));
+
+        self.update_colormap_texture(cx);
+        self.draw_points(cx);
+
+        if let Some(index) = self.hovered {
+            let radius = self.radius(index) + 3.;
+            let center = self.to_screen(self.positions[index]);
+            let rect = Rect { pos: center - Vec2::all(radius), size: Vec2::all(radius * 2.) };
+            self.hover_background = Background::default().with_radius(radius);
+            let color = vec4(self.accent_color.x, self.accent_color.y, self.accent_color.z, 0.6);
+            self.hover_background.draw(cx, rect, color);
+        }
+        self.draw_brush(cx);
+
+        self.view.end_view(cx);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_colormap_sample() {
+        assert_eq!(Colormap::Grayscale.sample(0.5), vec4(0.5, 0.5, 0.5, 1.));
+        assert_eq!(Colormap::Grayscale.sample(2.), vec4(1., 1., 1., 1.));
+        assert_eq!(Colormap::Viridis.sample(0.), Vec4::from_u32(0x440154ff));
+        assert_eq!(Colormap::Custom(vec![vec4(1., 0., 0., 1.)]).sample(0.7), vec4(1., 0., 0., 1.));
+        assert!(polygon_contains(&[vec2(0., 0.), vec2(10., 0.), vec2(0., 10.)], vec2(2., 2.)));
+        assert!(!polygon_contains(&[vec2(0., 0.), vec2(10., 0.), vec2(0., 10.)], vec2(8., 8.)));
+    }
+
+    #[test]
+    fn test_big_scatter_brushing() {
+        let mut cx = TestCx::new(vec2(400., 300.));
+        let mut scatter = BigScatter::default();
+        // Data from 0 to 10 in both directions, on a plot that is 400 by 300 pixels including the axes.
+        let xs: Vec<f32> = (0..=10).map(|i| i as f32).collect();
+        cx.draw(|cx| {
+            scatter.set_data(cx, &xs, &xs);
+            scatter.set_view_range(cx, vec2(0., 0.), vec2(10., 10.));
+            scatter.draw(cx);
+        });
+        let plot_rect = scatter.plot_rect;
+        let screen = |x: f32, y: f32| plot_rect.pos + vec2(x / 10., 1. - y / 10.) * plot_rect.size;
+
+        cx.pointer_down(screen(1.5, 1.5), |cx, event| scatter.handle(cx, event));
+        cx.pointer_move(screen(4.5, 4.5), |cx, event| scatter.handle(cx, event));
+        let events = cx.pointer_up(screen(4.5, 4.5), |cx, event| scatter.handle(cx, event));
+        assert!(events.contains(&BigScatterEvent::SelectionChanged(vec![2, 3, 4])));
+
+        let events = cx.click(screen(7., 7.1), |cx, event| scatter.handle(cx, event));
+        assert!(events.contains(&BigScatterEvent::SelectionChanged(vec![7])));
+
+        let events = cx.click(screen(7.5, 2.), |cx, event| scatter.handle(cx, event));
+        assert!(events.contains(&BigScatterEvent::SelectionChanged(vec![])));
+    }
+
+    #[test]
+    fn test_big_scatter_linked_selection() {
+        let mut cx = TestCx::new(vec2(400., 300.));
+        let mut scatter = BigScatter::default().with_linked_selection("points");
+        let xs: Vec<f32> = (0..10).map(|i| i as f32).collect();
+        cx.draw(|cx| {
+            scatter.set_data(cx, &xs, &xs);
+            scatter.draw(cx);
+        });
+
+        let table = ComponentId::default();
+        let events = cx.dispatch(Event::None, |cx, event| {
+            if let Event::None = event {
+                cx.publish_selection("points", table, SelectionSet::from_indices(&[3, 4, 20]));
+            }
+            scatter.handle(cx, event)
+        });
+        assert!(events.iter().all(|event| *event == BigScatterEvent::None));
+        assert_eq!(scatter.selection(), &[3, 4]);
+
+        let component_id = scatter.component_id;
+        cx.dispatch(Event::None, |cx, _| cx.set_key_focus(Some(component_id)));
+        let events = cx.key_down(KeyCode::Escape, KeyModifiers::default(), |cx, event| scatter.handle(cx, event));
+        assert!(events.contains(&BigScatterEvent::SelectionChanged(vec![])));
+        let results = cx.dispatch(Event::None, |cx, _| cx.linked_selection("points").is_empty());
+        assert_eq!(results, vec![true]);
+    }
+}
@@ -0,0 +1,239 @@
+//! A path of clickable segments, for showing and navigating where the user is in a hierarchy.
+
+use crate::background::*;
+use crate::button::ButtonEvent;
+use crate::buttonlogic::*;
+use crate::icon::*;
+use std::ops::Range;
+use zaplib::*;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum BreadcrumbBarEvent {
+    None,
+    /// The user clicked the segment at this index. The segments after it are not removed automatically; call
+    /// [`BreadcrumbBar::truncate`] if the navigation succeeds.
+    Navigate(usize),
+}
+
+#[derive(Default)]
+struct BreadcrumbSegment {
+    label: String,
+    component_id: ComponentId,
+    background: Background,
+}
+
+impl BreadcrumbSegment {
+    fn new(label: &str) -> Self {
+        Self { label: label.to_string(), component_id: ComponentId::default(), background: Background::default().with_radius(3.) }
+    }
+}
+
+/// Shows a path like "Home > Documents > Reports", where every segment but the last one can be clicked (or focused
+/// and activated using Space or Return) to navigate to it. When the path doesn't fit, segments in the middle are
+/// collapsed into an ellipsis, which navigates to the last collapsed segment and shows them all in its tooltip.
+pub struct BreadcrumbBar {
+    segments: Vec<BreadcrumbSegment>,
+    /// The segments that were collapsed into [`BreadcrumbBar::ellipsis`] during the last draw.
+    collapsed: Range<usize>,
+    ellipsis: BreadcrumbSegment,
+    /// The segment (or ellipsis) that the pointer is over.
+    hover: Option<ComponentId>,
+    hover_color: Vec4,
+    text_color: Vec4,
+    current_color: Vec4,
+}
+
+impl Default for BreadcrumbBar {
+    fn default() -> Self {
+        Self {
+            segments: vec![],
+            collapsed: 0..0,
+            ellipsis: BreadcrumbSegment::new("\u{2026}"),
+            hover: None,
+            hover_color: vec4(1., 1., 1., 0.1),
+            text_color: vec4(0.62, 0.62, 0.62, 1.),
+            current_color: vec4(0.9, 0.9, 0.9, 1.),
+        }
+    }
+}
+
+impl BreadcrumbBar {
+    const HEIGHT: f32 = 24.;
+    const PADDING_H: f32 = 6.;
+    const SEPARATOR_WIDTH: f32 = 14.;
+    const SEPARATOR_ICON_SIZE: f32 = 10.;
+
+    #[must_use]
+    pub fn with_segments(self, labels: &[&str]) -> Self {
+        Self { segments: labels.iter().map(|label| BreadcrumbSegment::new(label)).collect(), ..self }
+    }
+    #[must_use]
+    pub fn with_text_color(self, text_color: Vec4) -> Self {
+        Self { text_color, ..self }
+    }
+    #[must_use]
+    pub fn with_current_color(self, current_color: Vec4) -> Self {
+        Self { current_color, ..self }
+    }
+
+    pub fn len(&self) -> usize {
+        self.segments.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.segments.is_empty()
+    }
+
+    pub fn label(&self, index: usize) -> &str {
+        &self.segments[index].label
+    }
+
+    pub fn set_segments(&mut self, cx: &mut Cx, labels: &[&str]) {
+        self.segments = labels.iter().map(|label| BreadcrumbSegment::new(label)).collect();
+        self.hover = None;
+        cx.request_draw();
+    }
+
+    /// Add a segment at the end, e.g. when opening a folder.
+    pub fn push(&mut self, cx: &mut Cx, label: &str) {
+        self.segments.push(BreadcrumbSegment::new(label));
+        cx.request_draw();
+    }
+
+    /// Keep only the first `len` segments, e.g. after navigating to segment `len - 1`.
+    pub fn truncate(&mut self, cx: &mut Cx, len: usize) {
+        self.segments.truncate(len);
+        self.hover = None;
+        cx.request_draw();
+    }
+
+    /// Handle clicks and keys for a segment that can be navigated to, returning whether it got activated.
+    fn handle_segment(cx: &mut Cx, event: &mut Event, segment: &BreadcrumbSegment, hover: &mut Option<ComponentId>) -> bool {
+        if let Event::KeyDown(ke) = event.hits_keyboard(cx, segment.component_id) {
+            if (ke.key_code == KeyCode::Space || ke.key_code == KeyCode::Return) && !ke.is_repeat {
+                return true;
+            }
+        }
+        let hit_event = event.hits_pointer(cx, segment.component_id, segment.background.area().get_rect_for_first_instance(cx));
+        let button_event = handle_button_logic(cx, hit_event, |cx, logic_event| {
+            let new_hover = match logic_event {
+                ButtonLogicEvent::Default if *hover == Some(segment.component_id) => None,
+                ButtonLogicEvent::Default => *hover,
+                ButtonLogicEvent::Over | ButtonLogicEvent::Down => Some(segment.component_id),
+            };
+            if new_hover != *hover {
+                *hover = new_hover;
+                cx.request_draw();
+            }
+        });
+        button_event == ButtonEvent::Clicked
+    }
+
+    pub fn handle(&mut self, cx: &mut Cx, event: &mut Event) -> BreadcrumbBarEvent {
+        // The last segment is where the user is, so it can't be navigated to.
+        for index in 0..self.segments.len().saturating_sub(1) {
+            if self.collapsed.contains(&index) {
+                continue;
+            }
+            if Self::handle_segment(cx, event, &self.segments[index], &mut self.hover) {
+                return BreadcrumbBarEvent::Navigate(index);
+            }
+        }
+        if !self.collapsed.is_empty() && Self::handle_segment(cx, event, &self.ellipsis, &mut self.hover) {
+            return BreadcrumbBarEvent::Navigate(self.collapsed.end - 1);
+        }
+        BreadcrumbBarEvent::None
+    }
+
+    fn draw_segment(&self, cx: &mut Cx, segment: &mut BreadcrumbSegment, width: f32, is_current: bool) {
+        let rect = cx.add_box(LayoutSize::new(Width::Fix(width), Height::Fix(Self::HEIGHT)));
+        let color = if !is_current && self.hover == Some(segment.component_id) { self.hover_color } else { Vec4::default() };
+        segment.background.draw(cx, rect, color);
+        let props =
+            TextInsProps { color: if is_current { self.current_color } else { self.text_color }, ..TextInsProps::DEFAULT };
+        let line_height = cx.measure_wrapped_text(&props, "X", f32::INFINITY).line_height;
+        TextIns::draw_str(cx, &segment.label, rect.pos + vec2(Self::PADDING_H, (Self::HEIGHT - line_height) / 2.), &props);
+        if !is_current {
+            cx.add_focusable(segment.component_id, segment.background.area(), Focusable::default());
+        }
+    }
+
+    fn draw_separator(&self, cx: &mut Cx) {
+        let rect = cx.add_box(LayoutSize::new(Width::Fix(Self::SEPARATOR_WIDTH), Height::Fix(Self::HEIGHT)));
+        let offset = (rect.size - Vec2::all(Self::SEPARATOR_ICON_SIZE)) / 2.;
+        let icon_rect = Rect { pos: rect.pos + offset, size: Vec2::all(Self::SEPARATOR_ICON_SIZE) };
+        Icon::ChevronRight.draw(cx, icon_rect, self.text_color, 0.);
+    }
+
+    pub fn draw(&mut self, cx: &mut Cx) {
+        cx.begin_row(Width::Fill, Height::Fix(Self::HEIGHT));
+
+        let props = TextInsProps::DEFAULT;
+        let segment_width =
+            |cx: &mut Cx, label: &str| cx.measure_wrapped_text(&props, label, f32::INFINITY).width + Self::PADDING_H * 2.;
+        let widths: Vec<f32> = self.segments.iter().map(|segment| segment_width(cx, &segment.label)).collect();
+        let ellipsis_width = segment_width(cx, &self.ellipsis.label);
+
+        // Collapse segments after the first one until the rest fits, always keeping the first and last segments.
+        let available = cx.get_width_left();
+        let mut total: f32 = widths.iter().sum::<f32>() + Self::SEPARATOR_WIDTH * widths.len().saturating_sub(1) as f32;
+        self.collapsed = 1..1;
+        while total > available && self.collapsed.end + 1 < self.segments.len() {
+            if self.collapsed.is_empty() {
+                total += ellipsis_width + Self::SEPARATOR_WIDTH;
+            }
+            total -= widths[self.collapsed.end] + Self::SEPARATOR_WIDTH;
+            self.collapsed.end += 1;
+        }
+
+        // For the tooltip of the ellipsis. Collected up front, since we borrow `segments` mutably while drawing.
+        let hidden: Vec<&str> = self.segments[self.collapsed.clone()].iter().map(|segment| segment.label.as_str()).collect();
+        let hidden = hidden.join(" / ");
+
+        let mut segments = std::mem::take(&mut self.segments);
+        let last = segments.len().saturating_sub(1);
+        for (index, segment) in segments.iter_mut().enumerate() {
+            if self.collapsed.contains(&index) {
+                continue;
+            }
+            if index > 0 {
+                self.draw_separator(cx);
+            }
+            self.draw_segment(cx, segment, widths[index], index == last);
+            if index == 0 && !self.collapsed.is_empty() {
+                self.draw_separator(cx);
+                let mut ellipsis = std::mem::take(&mut self.ellipsis);
+                self.draw_segment(cx, &mut ellipsis, ellipsis_width, false);
+                cx.add_tooltip(ellipsis.component_id, ellipsis.background.area(), &hidden);
+                self.ellipsis = ellipsis;
+            }
+        }
+        self.segments = segments;
+
+        cx.end_row();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    use zaplib::*;
+
+    #[test]
+    fn test_breadcrumb_collapses_middle_segments() {
+        let mut breadcrumb = BreadcrumbBar::default().with_segments(&["Home", "Documents", "Work", "2024", "Reports"]);
+
+        let mut test_cx = TestCx::new(vec2(2000., 100.));
+        test_cx.draw(|cx| breadcrumb.draw(cx));
+        assert!(breadcrumb.collapsed.is_empty());
+
+        // Clicking the first segment navigates to it.
+        let events = test_cx.click(vec2(10., 12.), |cx, event| breadcrumb.handle(cx, event));
+        assert!(events.contains(&BreadcrumbBarEvent::Navigate(0)));
+
+        let mut test_cx = TestCx::new(vec2(200., 100.));
+        test_cx.draw(|cx| breadcrumb.draw(cx));
+        assert_eq!(breadcrumb.collapsed.start, 1);
+        assert!(breadcrumb.collapsed.end > 1 && breadcrumb.collapsed.end < 5);
+    }
+}
@@ -64,6 +64,7 @@ const ANIM_DEFAULT: Anim = Anim {
         // TextIns::color
         Track::Vec4 { key_frames: &[(1.0, vec4(0.6, 0.6, 0.6, 1.))], ease: Ease::DEFAULT },
     ],
+    ..Anim::DEFAULT
 };
 
 const ANIM_HOVER: Anim = Anim {
@@ -77,6 +78,7 @@ const ANIM_HOVER: Anim = Anim {
         // TextIns::color
         Track::Vec4 { key_frames: &[(1.0, vec4(1., 1., 1., 1.))], ease: Ease::DEFAULT },
     ],
+    ..Anim::DEFAULT
 };
 
 const ANIM_DOWN: Anim = Anim {
@@ -104,6 +106,13 @@ impl Button {
         if self.animator.handle(cx, event) {
             self.animate(cx);
         }
+        if let Event::KeyDown(ke) = event.hits_keyboard(cx, self.component_id) {
+            if !ke.is_repeat && (ke.key_code == KeyCode::Space || ke.key_code == KeyCode::Return) {
+                self.animator.play_anim(cx, ANIM_DOWN);
+                self.animator.play_anim(cx, ANIM_DEFAULT);
+                return ButtonEvent::Clicked;
+            }
+        }
         let animator = &mut self.animator;
         let hit_event = event.hits_pointer(cx, self.component_id, self.bg_area.get_rect_for_first_instance(cx));
         handle_button_logic(cx, hit_event, |cx, logic_event| match logic_event {
@@ -123,6 +132,7 @@ impl Button {
             let rect = cx.end_padding_box();
 
             self.bg_area = cx.add_instances(&SHADER, &[BgIns { base: QuadIns::from_rect(rect), ..Default::default() }]);
+            cx.add_focusable(self.component_id, self.bg_area, Focusable::default());
         }
         cx.end_padding_box();
 
@@ -131,3 +141,41 @@ impl Button {
         cx.end_shader_group();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    use zaplib::*;
+
+    #[test]
+    fn test_button_click() {
+        let mut button = Button::default();
+        let mut test_cx = TestCx::new(vec2(400., 300.));
+        test_cx.draw(|cx| button.draw(cx, "Hello"));
+
+        let events = test_cx.pointer_down(vec2(10., 10.), |cx, event| button.handle(cx, event));
+        assert!(events.contains(&ButtonEvent::Down));
+
+        // Releasing outside of the button should not count as a click.
+        let events = test_cx.pointer_up(vec2(300., 200.), |cx, event| button.handle(cx, event));
+        assert!(events.contains(&ButtonEvent::Up));
+
+        let events = test_cx.click(vec2(10., 10.), |cx, event| button.handle(cx, event));
+        assert!(events.contains(&ButtonEvent::Clicked));
+    }
+
+    #[test]
+    fn test_button_keyboard() {
+        let mut button = Button::default();
+        let mut test_cx = TestCx::new(vec2(400., 300.));
+        test_cx.draw(|cx| button.draw(cx, "Hello"));
+
+        // Not focused yet.
+        let events = test_cx.key_down(KeyCode::Space, KeyModifiers::default(), |cx, event| button.handle(cx, event));
+        assert!(!events.contains(&ButtonEvent::Clicked));
+
+        test_cx.key_down(KeyCode::Tab, KeyModifiers::default(), |cx, event| button.handle(cx, event));
+        let events = test_cx.key_down(KeyCode::Space, KeyModifiers::default(), |cx, event| button.handle(cx, event));
+        assert!(events.contains(&ButtonEvent::Clicked));
+    }
+}
@@ -243,6 +243,54 @@ pub trait ChartPlugin {
     fn draw(&mut self, cx: &mut Cx, config: &ChartConfig, chart_bounds: &Rect);
 }
 
+/// Shares the position of the pointer along the horizontal axis between charts, e.g. stacked charts that show
+/// different metrics over the same time range.
+///
+/// Charts that get the same crosshair using [`Chart::set_crosshair`] all draw a vertical line at the hovered value,
+/// together with the values of their datasets at that point, instead of their regular tooltip.
+///
+/// ```ignore
+/// let crosshair = Arc::new(RwLock::new(ChartCrosshair::default()));
+/// cpu_chart.set_crosshair(cx, Some(Arc::clone(&crosshair)));
+/// memory_chart.set_crosshair(cx, Some(crosshair));
+/// ```
+#[derive(Debug, Default)]
+pub struct ChartCrosshair {
+    position: Option<f32>,
+    source: Option<ComponentId>,
+}
+
+impl ChartCrosshair {
+    /// The horizontal value to draw the crosshair at, in the units of the horizontal axis.
+    pub fn position(&self) -> Option<f32> {
+        self.position
+    }
+
+    /// The chart that the pointer is over, if the crosshair follows the pointer.
+    pub fn source(&self) -> Option<ComponentId> {
+        self.source
+    }
+
+    /// Move the crosshair without hovering a chart, e.g. to follow playback. Call [`Cx::request_draw`] afterwards.
+    pub fn set_position(&mut self, position: Option<f32>) {
+        self.position = position;
+        self.source = None;
+    }
+
+    fn hover(&mut self, source: ComponentId, position: f32) {
+        self.position = Some(position);
+        self.source = Some(source);
+    }
+
+    /// Only clear the position if `source` set it; the pointer might have moved to another chart already.
+    fn unhover(&mut self, source: ComponentId) {
+        if self.source == Some(source) {
+            self.position = None;
+            self.source = None;
+        }
+    }
+}
+
 /// Tooltip configuration
 ///
 /// TODO(Hernan): Provide customization options for background, current element
@@ -271,7 +319,10 @@ pub struct ChartConfig<'a> {
     pub scales: HashMap<String, ChartScale>,
     pub style: ChartStyle,
     pub tooltip: ChartTooltipConfig,
+    /// Zoom using the mouse wheel or by pinching, as set in [`Cx::interaction_policy`]. Double-clicking shows the
+    /// whole chart again.
     pub zoom_enabled: bool,
+    /// Pan by dragging, or by scrolling on a trackpad.
     pub pan_enabled: bool,
 }
 
@@ -338,6 +389,10 @@ pub struct Chart {
     zoom_enabled: bool,
     pan_enabled: bool,
     panning: bool,
+    /// Which datasets are hidden or highlighted; see [`Chart::set_series_state`].
+    series_state: SeriesState,
+    crosshair: Option<Arc<RwLock<ChartCrosshair>>>,
+    crosshair_background: Background,
 
     /// Set to `None` when we're viewing the whole chart (e.g. initial state,
     /// and when clicking the "reset view" button) which means that the axes
@@ -352,6 +407,10 @@ pub struct Chart {
 impl Chart {
     pub fn handle(&mut self, cx: &mut Cx, event: &mut Event) -> ChartEvent {
         match event.hits_pointer(cx, self.component_id, self.texture_area.get_rect_for_first_instance(cx)) {
+            Event::PointerDown(pd) if (self.zoom_enabled || self.pan_enabled) && cx.interaction_policy().is_reset(&pd) => {
+                self.reset_zoom_pan();
+                cx.request_draw();
+            }
             Event::PointerDown(pd) => {
                 if self.pan_enabled {
                     self.last_pointer_pos = pd.rel;
@@ -379,32 +438,31 @@ impl Chart {
                     self.panning = false;
                 }
             }
-            Event::PointerScroll(ps) => {
-                if self.zoom_enabled {
-                    if self.zoom_pan.is_none() {
-                        self.zoom_pan = Some(self.bounds);
-                    }
-
-                    if let Some(zoom_pan) = &mut self.zoom_pan {
-                        let old_size = zoom_pan.size;
-                        let new_size = old_size - vec2(ps.scroll.y, ps.scroll.y);
-                        let zoom_factor = (new_size / old_size).y;
-
-                        // Compute new offset
-                        // See: https://stackoverflow.com/a/38302057
-                        let old_pos = zoom_pan.pos;
-                        let new_pos = ps.rel - zoom_factor * (ps.rel - old_pos);
-
-                        zoom_pan.size = new_size;
-                        zoom_pan.pos = new_pos;
-                    }
-
+            Event::PointerScroll(ps) => match cx.interaction_policy().scroll_action(&ps) {
+                ScrollAction::Zoom { factor, axes } if self.zoom_enabled => {
+                    let zoom_pan = self.zoom_pan.get_or_insert(self.bounds);
+                    let zoom_factors = axes.factors(factor);
+
+                    // Compute new offset
+                    // See: https://stackoverflow.com/a/38302057
+                    zoom_pan.pos = ps.rel - zoom_factors * (ps.rel - zoom_pan.pos);
+                    zoom_pan.size *= zoom_factors;
                     cx.request_draw();
                 }
-            }
+                // Trackpads pan by scrolling, just like dragging.
+                ScrollAction::Pan(delta) if self.pan_enabled => {
+                    self.zoom_pan.get_or_insert(self.bounds).pos -= delta;
+                    cx.request_draw();
+                }
+                _ => {}
+            },
             Event::PointerHover(pe) => {
                 if self.panning || pe.hover_state == HoverState::Out {
                     self.tooltip_visible = false;
+                    if let Some(crosshair) = &self.crosshair {
+                        crosshair.write().unwrap().unhover(self.component_id);
+                        cx.request_draw();
+                    }
                     return ChartEvent::PointerOut;
                 }
 
@@ -412,7 +470,11 @@ impl Chart {
                 let cursor = mouse_pos_rel.clamp(&self.bounds.pos, &(self.bounds.pos + self.bounds.size));
                 let cursor_value = self.denormalize_data_point(cursor);
                 let current_element = self.get_element_at(cx, cursor_value);
-                if let Some(current_element) = &current_element {
+                if let Some(crosshair) = &self.crosshair {
+                    // The crosshair shows the values of all datasets, so it replaces the tooltip.
+                    crosshair.write().unwrap().hover(self.component_id, cursor_value.x);
+                    self.tooltip_visible = false;
+                } else if let Some(current_element) = &current_element {
                     self.tooltip.update(current_element);
                     self.tooltip_visible = true;
                 } else {
@@ -794,7 +856,7 @@ impl Chart {
         vec2(value.x, if value.y < 0. { -Self::round_up_to_10s(value.y.abs()) } else { Self::round_down_to_10s(value.y) })
     }
 
-    fn get_min_max(config: &ChartConfig) -> (Vec2, Vec2) {
+    fn get_min_max(config: &ChartConfig, series_state: &SeriesState) -> (Vec2, Vec2) {
         let mut min = vec2(INFINITY, INFINITY);
         let mut max = vec2(NEG_INFINITY, NEG_INFINITY);
 
@@ -803,7 +865,10 @@ impl Chart {
             max.x = x_scale.max;
         }
 
-        for dataset in &config.datasets {
+        for (index, dataset) in config.datasets.iter().enumerate() {
+            if !series_state.is_visible(index) {
+                continue;
+            }
             let (lo, hi) = dataset.data.min_max(min, max);
             min = lo;
             max = hi;
@@ -823,6 +888,71 @@ impl Chart {
         self.zoom_pan = None;
     }
 
+    /// Share a crosshair with other charts; see [`ChartCrosshair`]. Pass [`None`] to show the regular tooltip again.
+    pub fn set_crosshair(&mut self, cx: &mut Cx, crosshair: Option<Arc<RwLock<ChartCrosshair>>>) {
+        self.crosshair = crosshair;
+        self.tooltip_visible = false;
+        cx.request_draw();
+    }
+
+    /// Draw a vertical line at the position of the crosshair, with the values of the nearest points next to it.
+    fn draw_crosshair(&mut self, cx: &mut Cx, config: &ChartConfig) {
+        let position = match self.crosshair.as_ref().and_then(|crosshair| crosshair.read().unwrap().position()) {
+            Some(position) => position,
+            None => return,
+        };
+        let x = self.normalize_data_point(vec2(position, 0.)).x;
+        let (min_y, max_y) = (self.bounds.pos.y, self.bounds.pos.y + self.bounds.size.y);
+        if x < self.bounds.pos.x || x > self.bounds.pos.x + self.bounds.size.x {
+            return;
+        }
+        let line = DrawLines3dInstance::from_segment(vec3(x, min_y, 0.), vec3(x, max_y, 0.), config.style.label_color, 1.);
+        DrawLines3d::draw(cx, &[line], Default::default());
+
+        let mut lines = vec![format!("{:.2}", position)];
+        let mut markers = vec![];
+        for (dataset_index, area) in self.areas.iter().enumerate() {
+            let points = area.get_slice::<DrawPoints3dInstance>(cx);
+            if let Some(datum_index) = Self::get_nearest_element_index(points, position) {
+                let point = points[datum_index];
+                lines.push(format!("{}: {:.2}", config.datasets[dataset_index].label, point.user_info.y));
+                markers.push(DrawPoints3dInstance { size: point.size * 1.5, ..point });
+            }
+        }
+        DrawPoints3d::draw(
+            cx,
+            &markers,
+            DrawPoints3dOptions {
+                use_screen_space: true,
+                point_style: DrawPoints3dStyle::Circle,
+                ..DrawPoints3dOptions::default()
+            },
+        );
+
+        let props = TextInsProps { color: config.style.label_color, ..TextInsProps::DEFAULT };
+        let line_height = cx.measure_wrapped_text(&props, "X", f32::INFINITY).line_height;
+        let width = lines.iter().map(|line| cx.measure_wrapped_text(&props, line, f32::INFINITY).width).fold(0., f32::max);
+        let size = vec2(width + 12., lines.len() as f32 * line_height + 8.);
+        // Show the readout to the right of the line, unless it doesn't fit there.
+        let mut pos = vec2(x + 8., min_y + 4.);
+        if pos.x + size.x > self.bounds.pos.x + self.bounds.size.x {
+            pos.x = x - 8. - size.x;
+        }
+        let mut background_color = config.style.background_color;
+        background_color.w = 0.85;
+        self.crosshair_background.draw(cx, Rect { pos, size }, background_color);
+        for (index, line) in lines.iter().enumerate() {
+            TextIns::draw_str(cx, line, pos + vec2(6., 4. + index as f32 * line_height), &props);
+        }
+    }
+
+    /// Hide datasets, or highlight one of them by dimming the others, e.g. using the state of a [`Legend`]. Indices
+    /// refer to [`ChartConfig::datasets`].
+    pub fn set_series_state(&mut self, cx: &mut Cx, series_state: &SeriesState) {
+        self.series_state = series_state.clone();
+        cx.request_draw();
+    }
+
     fn draw_chart(&mut self, cx: &mut Cx, config: &ChartConfig) {
         self.chart_view.begin_view(cx, LayoutSize::FILL);
 
@@ -843,7 +973,7 @@ impl Chart {
         if self.zoom_pan.is_none() {
             // Compute min/max for all datasets before rendering
             // Only update min/max values if we're not panning/zooming
-            let (data_min, data_max) = Self::get_min_max(config);
+            let (data_min, data_max) = Self::get_min_max(config, &self.series_state);
             self.min = data_min;
             self.max = data_max;
         }
@@ -852,16 +982,26 @@ impl Chart {
 
         self.draw_grid(cx, config);
 
-        for dataset in &config.datasets {
+        for (index, dataset) in config.datasets.iter().enumerate() {
+            if !self.series_state.is_visible(index) {
+                // Keep `areas` in the same order as the datasets, so tooltips refer to the right one.
+                self.areas.push(Area::Empty);
+                continue;
+            }
+            // Points don't support transparency, so dim them by blending with the background instead.
+            let dimmed = self.series_state.is_dimmed(index);
+            let dim = |color: Vec4| if dimmed { Vec4::mix(color, config.style.background_color, 0.75) } else { color };
             let points = dataset.data.points();
             let normalized_data = self.normalize(&points);
-            if !normalized_data.is_empty() {
-                self.draw_lines(cx, &normalized_data, dataset.border_color, dataset.border_width * current_dpi);
+            if normalized_data.is_empty() {
+                self.areas.push(Area::Empty);
+            } else {
+                self.draw_lines(cx, &normalized_data, dim(dataset.border_color), dataset.border_width * current_dpi);
                 let area = self.draw_points(
                     cx,
                     &normalized_data,
                     &points,
-                    dataset.point_background_color,
+                    dim(dataset.point_background_color),
                     dataset.point_radius * current_dpi,
                     dataset.point_style,
                 );
@@ -873,6 +1013,8 @@ impl Chart {
             plugin.write().unwrap().draw(cx, config, &self.bounds)
         }
 
+        self.draw_crosshair(cx, config);
+
         if self.tooltip_visible {
             self.tooltip.draw(cx, config);
         }
@@ -905,9 +1047,22 @@ impl Chart {
 
 #[cfg(test)]
 mod tests {
-    use zaplib::vec2;
+    use zaplib::{vec2, ComponentId};
 
-    use crate::Chart;
+    use crate::{Chart, ChartCrosshair};
+
+    #[test]
+    fn it_shares_crosshair() {
+        let (first, second) = (ComponentId::default(), ComponentId::default());
+        let mut crosshair = ChartCrosshair::default();
+        crosshair.hover(first, 10.);
+        // The pointer moves to the second chart before the first one handles the pointer leaving it.
+        crosshair.hover(second, 12.);
+        crosshair.unhover(first);
+        assert_eq!((crosshair.position(), crosshair.source()), (Some(12.), Some(second)));
+        crosshair.unhover(second);
+        assert_eq!(crosshair.position(), None);
+    }
 
     #[test]
     fn it_rounds_up() {
@@ -74,6 +74,8 @@ static SHADER: Shader = Shader {
     ..Shader::DEFAULT
 };
 
+/// A checkbox that shows a loading animation until `loaded` is set, like in lists of charts that are fetched when
+/// checked. For a regular checkbox, see [`crate::Toggle`].
 #[derive(Default)]
 pub struct Checkbox {
     component_id: ComponentId,
@@ -98,6 +100,7 @@ const ANIM_DEFAULT: Anim = Anim {
         // CheckboxIns::down
         Track::Float { key_frames: &[(1.0, 0.0)], ease: Ease::DEFAULT },
     ],
+    ..Anim::DEFAULT
 };
 
 const ANIM_HOVER: Anim = Anim {
@@ -109,6 +112,7 @@ const ANIM_HOVER: Anim = Anim {
         // CheckboxIns::down
         Track::Float { key_frames: &[(1.0, 0.0)], ease: Ease::DEFAULT },
     ],
+    ..Anim::DEFAULT
 };
 
 const ANIM_DOWN: Anim = Anim {
@@ -142,6 +146,12 @@ impl Checkbox {
             self.animator_animate(cx);
         }
 
+        if let Event::KeyDown(ke) = event.hits_keyboard(cx, self.component_id) {
+            if ke.key_code == KeyCode::Space && !ke.is_repeat {
+                return CheckboxEvent::Toggled;
+            }
+        }
+
         match event.hits_pointer(cx, self.component_id, self.area.get_rect_for_first_instance(cx)) {
             Event::PointerDown(_pe) => {
                 let checkbox = self.area.get_first::<CheckboxIns>(cx);
@@ -198,6 +208,7 @@ impl Checkbox {
                     ..Default::default()
                 }],
             );
+            cx.add_focusable(self.component_id, self.area, Focusable::default());
 
             cx.add_box(LayoutSize::new(Width::Fix(20.), Height::Fix(0.)));
             let draw_str_props = TextInsProps {
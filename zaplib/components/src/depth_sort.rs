@@ -0,0 +1,100 @@
+//! Drawing semi-transparent 3D things back to front, so they blend correctly regardless of the order they were
+//! added in.
+//!
+//! Blending only works if whatever is behind a semi-transparent surface has already been drawn when that surface
+//! gets drawn; otherwise the depth test throws it away, or it gets blended in the wrong order. Instances of one
+//! [`DrawCall`] are drawn in order, so sorting them with [`sort_back_to_front`] fixes this within a draw call, e.g. for
+//! the points of a point cloud. [`DepthSortedDraws`] does the same for whole draw calls, e.g. for separate meshes.
+//!
+//! Draw opaque things first, and semi-transparent things after them. The order depends on the camera, so draw
+//! again when it moves, e.g. whenever [`crate::Viewport3D::handle`] returns a new camera, instead of using
+//! [`crate::Viewport3D::skip_draw`].
+
+use zaplib::*;
+
+/// How far `position` is in front of the camera with view matrix `camera_view` (see [`crate::Viewport3D::camera_view`]);
+/// larger values are further away.
+pub fn view_depth(camera_view: &Mat4, position: Vec3) -> f32 {
+    // The camera looks along the negative Z axis in view space.
+    -camera_view.transform_vec4(vec4(position.x, position.y, position.z, 1.)).z
+}
+
+/// Sort `instances` so the ones furthest away from the camera with view matrix `camera_view` come first. `position`
+/// returns the position of an instance in the same coordinates that `camera_view` applies to, i.e. after any model
+/// transform.
+pub fn sort_back_to_front<T: Copy>(instances: &mut [T], camera_view: &Mat4, position: impl Fn(&T) -> Vec3) {
+    // Compute each depth only once, since sorting compares every instance many times.
+    let mut sorted: Vec<(f32, T)> =
+        instances.iter().map(|instance| (view_depth(camera_view, position(instance)), *instance)).collect();
+    sorted.sort_by(|(a, _), (b, _)| b.partial_cmp(a).unwrap_or(std::cmp::Ordering::Equal));
+    for (instance, (_, sorted_instance)) in instances.iter_mut().zip(sorted) {
+        *instance = sorted_instance;
+    }
+}
+
+/// A draw added to [`DepthSortedDraws`].
+type DepthSortedDraw<'a> = Box<dyn FnOnce(&mut Cx) + 'a>;
+
+/// Collects draws of semi-transparent objects, and then draws them back to front. Each draw has a position, like
+/// the center of a mesh, that determines its order; draws that overlap in depth, like intersecting meshes, can
+/// still blend incorrectly.
+///
+/// ```ignore
+/// let mut transparent = DepthSortedDraws::new(self.viewport_3d.camera_view());
+/// for mesh in &self.glass_meshes {
+///     transparent.add(mesh.center, move |cx| {
+///         cx.add_mesh_instances(&GLASS_SHADER, &mesh.instances, mesh.geometry.clone());
+///     });
+/// }
+/// transparent.draw(cx);
+/// ```
+pub struct DepthSortedDraws<'a> {
+    camera_view: Mat4,
+    draws: Vec<(f32, DepthSortedDraw<'a>)>,
+}
+
+impl<'a> DepthSortedDraws<'a> {
+    /// Sort draws for the camera with view matrix `camera_view`; see [`crate::Viewport3D::camera_view`].
+    pub fn new(camera_view: Mat4) -> Self {
+        Self { camera_view, draws: vec![] }
+    }
+
+    /// Add a draw, which gets called in [`DepthSortedDraws::draw`].
+    pub fn add(&mut self, position: Vec3, draw: impl FnOnce(&mut Cx) + 'a) {
+        self.draws.push((view_depth(&self.camera_view, position), Box::new(draw)));
+    }
+
+    /// Call all draws, furthest away first. Draws at the same depth are called in the order they were added.
+    pub fn draw(mut self, cx: &mut Cx) {
+        self.draws.sort_by(|(a, _), (b, _)| b.partial_cmp(a).unwrap_or(std::cmp::Ordering::Equal));
+        for (_, draw) in self.draws {
+            draw(cx);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+
+    #[test]
+    fn test_sort_back_to_front() {
+        // A camera at z = 10, looking towards the origin.
+        let camera_view = Mat4::translation(0., 0., -10.);
+        assert_eq!(view_depth(&camera_view, vec3(0., 0., 0.)), 10.);
+
+        let mut positions = [vec3(0., 0., 5.), vec3(3., 0., -5.), vec3(0., 1., 0.)];
+        sort_back_to_front(&mut positions, &camera_view, |position| *position);
+        assert_eq!(positions, [vec3(3., 0., -5.), vec3(0., 1., 0.), vec3(0., 0., 5.)]);
+
+        let order = RefCell::new(vec![]);
+        let mut draws = DepthSortedDraws::new(camera_view);
+        draws.add(vec3(0., 0., 5.), |_| order.borrow_mut().push("near"));
+        draws.add(vec3(0., 0., -5.), |_| order.borrow_mut().push("far"));
+        let mut draws = Some(draws);
+        let mut test_cx = TestCx::new(vec2(100., 100.));
+        test_cx.draw(|cx| draws.take().unwrap().draw(cx));
+        assert_eq!(*order.borrow(), vec!["far", "near"]);
+    }
+}
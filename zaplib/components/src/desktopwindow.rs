@@ -97,7 +97,7 @@ impl DesktopWindow {
             self.window.minimize_window(cx);
         }
         if let ButtonEvent::Clicked = self.max_btn.handle(cx, event) {
-            if self.window.is_fullscreen(cx) {
+            if self.window.is_maximized(cx) {
                 self.window.restore_window(cx);
             } else {
                 self.window.maximize_window(cx);
@@ -136,6 +136,7 @@ impl DesktopWindow {
                 Event::PointerHover(ev) => ev.window_id != window_id,
                 Event::PointerUp(ev) => ev.window_id != window_id,
                 Event::PointerScroll(ev) => ev.window_id != window_id,
+                Event::PointerRawMove(ev) => ev.window_id != window_id,
                 _ => false,
             };
             if is_for_other_window {
@@ -159,6 +160,8 @@ impl DesktopWindow {
 
         let process_chrome = match cx.platform_type {
             PlatformType::Linux { custom_window_chrome } => custom_window_chrome,
+            // Android and iOS apps are always fullscreen, so there is no window chrome to draw.
+            PlatformType::Android | PlatformType::IOS => false,
             _ => true,
         };
         if process_chrome {
@@ -175,7 +178,7 @@ impl DesktopWindow {
                     }
                     cx.begin_right_box();
                     self.min_btn.draw(cx, DesktopButtonType::WindowsMin);
-                    if self.window.is_fullscreen(cx) {
+                    if self.window.is_maximized(cx) {
                         self.max_btn.draw(cx, DesktopButtonType::WindowsMaxToggled);
                     } else {
                         self.max_btn.draw(cx, DesktopButtonType::WindowsMax);
@@ -206,6 +209,7 @@ impl DesktopWindow {
                     cx.end_center_x_and_y_align();
                     self.caption_bg.end_draw(cx);
                 }
+                PlatformType::Android | PlatformType::IOS => {}
                 PlatformType::Web { .. } => {
                     if self.window.is_fullscreen(cx) {
                         // put a bar at the top
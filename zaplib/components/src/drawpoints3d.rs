@@ -1,3 +1,4 @@
+use crate::*;
 use zaplib::*;
 
 static SHADER: Shader = Shader {
@@ -10,6 +11,7 @@ static SHADER: Shader = Shader {
             uniform use_screen_space: float;
             uniform point_style: float;
             uniform vertex_transform: mat4;
+            uniform opacity: float;
 
             geometry geom: vec2;
 
@@ -50,10 +52,10 @@ static SHADER: Shader = Shader {
                 if point_style == 1. {
                     let df = Df::viewport(geom);
                     df.circle(vec2(0.5), 0.5);
-                    df.fill(vec4(in_color, 1.));
+                    df.fill(vec4(in_color, opacity));
                     return df.result;
                 } else {
-                    return vec4(in_color, 1.);
+                    return vec4(in_color * opacity, opacity);
                 }
             }"#
         ),
@@ -78,6 +80,7 @@ struct DrawPoints3dUniforms {
     use_screen_space: f32,
     point_style: f32,
     vertex_transform: Mat4,
+    opacity: f32,
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -94,11 +97,22 @@ pub struct DrawPoints3dOptions {
     pub point_style: DrawPoints3dStyle,
     /// Custom transformation to do on all vertices
     pub vertex_transform: Mat4,
+    /// Makes all points semi-transparent when below 1; set [`DrawPoints3dOptions::sort_for_camera`] as well.
+    pub opacity: f32,
+    /// The view matrix of the camera (see [`crate::Viewport3D::camera_view`]) to sort the points for, so they get
+    /// drawn back to front and semi-transparent points blend correctly; see [`crate::sort_back_to_front`].
+    pub sort_for_camera: Option<Mat4>,
 }
 
 impl Default for DrawPoints3dOptions {
     fn default() -> Self {
-        Self { use_screen_space: false, point_style: DrawPoints3dStyle::Quad, vertex_transform: Mat4::identity() }
+        Self {
+            use_screen_space: false,
+            point_style: DrawPoints3dStyle::Quad,
+            vertex_transform: Mat4::identity(),
+            opacity: 1.,
+            sort_for_camera: None,
+        }
     }
 }
 
@@ -109,7 +123,17 @@ impl DrawPoints3d {
     /// Following Webviz's implementation, points can be rendered in either world or screen space using the `use_screen_space`
     /// flag. Regardless of the render space, all points are rendered as billboards, facing the camera.
     pub fn draw(cx: &mut Cx, data: &[DrawPoints3dInstance], options: DrawPoints3dOptions) -> Area {
-        let area = cx.add_instances(&SHADER, data);
+        let area = if let Some(camera_view) = &options.sort_for_camera {
+            let mut sorted = data.to_vec();
+            let vertex_transform = &options.vertex_transform;
+            sort_back_to_front(&mut sorted, camera_view, |point| {
+                let position = vertex_transform.transform_vec4(vec4(point.position.x, point.position.y, point.position.z, 1.));
+                vec3(position.x, position.y, position.z) / position.w
+            });
+            cx.add_instances(&SHADER, &sorted)
+        } else {
+            cx.add_instances(&SHADER, data)
+        };
 
         let rect = cx.get_box_rect();
         area.write_user_uniforms(
@@ -122,6 +146,7 @@ impl DrawPoints3d {
                     DrawPoints3dStyle::Circle => POINT_STYLE_CIRCLE,
                 },
                 vertex_transform: options.vertex_transform,
+                opacity: options.opacity,
             },
         );
 
@@ -0,0 +1,232 @@
+//! A button that opens a list of items to choose from.
+
+use crate::background::*;
+use zaplib::*;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum DropDownEvent {
+    None,
+    /// The user picked the item at this index.
+    Selected(usize),
+}
+
+/// Shows the selected item, and opens a list of all items when clicked. Can also be used with the keyboard: Space
+/// or Return opens and closes the list, and the arrow keys move the selection.
+pub struct DropDown {
+    component_id: ComponentId,
+    items: Vec<String>,
+    selected: Option<usize>,
+    /// Shown when nothing is selected.
+    placeholder: String,
+    open: bool,
+    /// The item that the pointer or arrow keys are on while the list is open.
+    highlighted: Option<usize>,
+    button: Background,
+    list_view: View,
+    list: Background,
+    highlight: Background,
+    /// For hit testing the list while it's open.
+    list_component_id: ComponentId,
+    list_rect: Option<Rect>,
+    item_rects: Vec<Rect>,
+    background_color: Vec4,
+    list_color: Vec4,
+    highlight_color: Vec4,
+}
+
+impl Default for DropDown {
+    fn default() -> Self {
+        Self {
+            component_id: ComponentId::default(),
+            items: vec![],
+            selected: None,
+            placeholder: String::new(),
+            open: false,
+            highlighted: None,
+            button: Background::default().with_radius(2.5),
+            list_view: View::default().with_is_overlay(true),
+            list: Background::default().with_radius(2.5).with_draw_depth(5.),
+            highlight: Background::default().with_draw_depth(5.1),
+            list_component_id: ComponentId::default(),
+            list_rect: None,
+            item_rects: vec![],
+            background_color: vec4(0.2, 0.2, 0.2, 1.),
+            list_color: vec4(0.15, 0.15, 0.15, 1.),
+            highlight_color: vec4(0.27, 0.56, 0.93, 1.),
+        }
+    }
+}
+
+impl DropDown {
+    const PADDING: Padding = Padding::vh(6., 8.);
+    /// Room for the arrow after the selected item.
+    const ARROW: &'static str = " \u{25be}";
+
+    #[must_use]
+    pub fn with_items(self, items: &[&str]) -> Self {
+        Self { items: items.iter().map(|item| item.to_string()).collect(), ..self }
+    }
+    #[must_use]
+    pub fn with_placeholder(self, placeholder: &str) -> Self {
+        Self { placeholder: placeholder.to_string(), ..self }
+    }
+    #[must_use]
+    pub fn with_selected(self, selected: Option<usize>) -> Self {
+        Self { selected, ..self }
+    }
+    #[must_use]
+    pub fn with_background_color(self, background_color: Vec4) -> Self {
+        Self { background_color, ..self }
+    }
+
+    pub fn items(&self) -> &[String] {
+        &self.items
+    }
+
+    pub fn set_items(&mut self, cx: &mut Cx, items: &[&str]) {
+        self.items = items.iter().map(|item| item.to_string()).collect();
+        if self.selected.map_or(false, |selected| selected >= self.items.len()) {
+            self.selected = None;
+        }
+        self.close(cx);
+    }
+
+    pub fn selected(&self) -> Option<usize> {
+        self.selected
+    }
+
+    pub fn set_selected(&mut self, cx: &mut Cx, selected: Option<usize>) {
+        self.selected = selected;
+        cx.request_draw();
+    }
+
+    fn close(&mut self, cx: &mut Cx) {
+        self.open = false;
+        self.highlighted = None;
+        cx.request_draw();
+    }
+
+    fn select(&mut self, cx: &mut Cx, index: usize) -> DropDownEvent {
+        self.selected = Some(index);
+        self.close(cx);
+        DropDownEvent::Selected(index)
+    }
+
+    pub fn handle(&mut self, cx: &mut Cx, event: &mut Event) -> DropDownEvent {
+        if self.open {
+            // Hit test the list first, so that clicks on it don't go through to whatever is underneath.
+            match event.hits_pointer(cx, self.list_component_id, self.list_rect) {
+                Event::PointerDown(pe) => {
+                    if let Some(index) = self.item_rects.iter().position(|rect| rect.contains(pe.abs)) {
+                        return self.select(cx, index);
+                    }
+                }
+                Event::PointerHover(pe) => {
+                    cx.set_hover_mouse_cursor(MouseCursor::Hand);
+                    let highlighted = self.item_rects.iter().position(|rect| rect.contains(pe.abs));
+                    if highlighted.is_some() && highlighted != self.highlighted {
+                        self.highlighted = highlighted;
+                        cx.request_draw();
+                    }
+                }
+                _ => {}
+            }
+            if let Event::PointerDown(pe) = event {
+                let on_button = self.button.area().get_rect_for_first_instance(cx).map_or(false, |rect| rect.contains(pe.abs));
+                let on_list = self.list_rect.map_or(false, |rect| rect.contains(pe.abs));
+                if !on_button && !on_list {
+                    self.close(cx);
+                }
+            }
+        }
+
+        match event.hits_keyboard(cx, self.component_id) {
+            Event::KeyDown(ke) => match ke.key_code {
+                KeyCode::Space | KeyCode::Return if !ke.is_repeat => {
+                    if let (true, Some(index)) = (self.open, self.highlighted) {
+                        return self.select(cx, index);
+                    }
+                    self.open = !self.open;
+                    self.highlighted = self.selected;
+                    cx.request_draw();
+                }
+                KeyCode::Escape if self.open => self.close(cx),
+                KeyCode::ArrowDown | KeyCode::ArrowUp if !self.items.is_empty() => {
+                    let current = if self.open { self.highlighted } else { self.selected };
+                    let next = match (current, ke.key_code) {
+                        (None, _) => 0,
+                        (Some(index), KeyCode::ArrowDown) => (index + 1).min(self.items.len() - 1),
+                        (Some(index), _) => index.saturating_sub(1),
+                    };
+                    if self.open {
+                        self.highlighted = Some(next);
+                        cx.request_draw();
+                    } else if Some(next) != self.selected {
+                        self.selected = Some(next);
+                        cx.request_draw();
+                        return DropDownEvent::Selected(next);
+                    }
+                }
+                _ => {}
+            },
+            Event::KeyFocusLost(_) if self.open => self.close(cx),
+            _ => {}
+        }
+
+        match event.hits_pointer(cx, self.component_id, self.button.area().get_rect_for_first_instance(cx)) {
+            Event::PointerDown(_) => {
+                cx.set_key_focus(Some(self.component_id));
+                self.open = !self.open;
+                self.highlighted = self.selected;
+                cx.request_draw();
+            }
+            Event::PointerHover(_) => cx.set_hover_mouse_cursor(MouseCursor::Hand),
+            _ => {}
+        }
+        DropDownEvent::None
+    }
+
+    pub fn draw(&mut self, cx: &mut Cx) {
+        let (text, color) = match self.selected.and_then(|selected| self.items.get(selected)) {
+            Some(item) => (item.as_str(), vec4(0.9, 0.9, 0.9, 1.)),
+            None => (self.placeholder.as_str(), vec4(0.5, 0.5, 0.5, 1.)),
+        };
+        self.button.begin_draw(cx, Width::Compute, Height::Compute, self.background_color);
+        cx.begin_padding_box(Self::PADDING);
+        TextIns::draw_walk(cx, &format!("{}{}", text, Self::ARROW), &TextInsProps { color, ..TextInsProps::DEFAULT });
+        cx.end_padding_box();
+        self.button.end_draw(cx);
+        cx.add_focusable(self.component_id, self.button.area(), Focusable::default());
+
+        self.item_rects.clear();
+        self.list_rect = None;
+        if !self.open {
+            return;
+        }
+        let button_rect = match self.button.area().get_rect_for_first_instance(cx) {
+            Some(rect) => rect,
+            None => return,
+        };
+        let props = TextInsProps { draw_depth: 5.2, color: vec4(0.9, 0.9, 0.9, 1.), ..TextInsProps::DEFAULT };
+        let padding = Self::PADDING;
+        let item_height = cx.measure_wrapped_text(&props, "X", f32::INFINITY).line_height + padding.t + padding.b;
+        let width = self.items.iter().fold(button_rect.size.x, |width, item| {
+            width.max(cx.measure_wrapped_text(&props, item, f32::INFINITY).width + padding.l + padding.r)
+        });
+
+        self.list_view.begin_view(cx, LayoutSize::FILL);
+        let list_pos = vec2(button_rect.pos.x, button_rect.pos.y + button_rect.size.y + 2.);
+        let list_rect = Rect { pos: list_pos, size: vec2(width, item_height * self.items.len() as f32) };
+        self.list.draw(cx, list_rect, self.list_color);
+        self.list_rect = Some(list_rect);
+        for (index, item) in self.items.iter().enumerate() {
+            let rect = Rect { pos: list_pos + vec2(0., item_height * index as f32), size: vec2(width, item_height) };
+            if self.highlighted == Some(index) {
+                self.highlight.draw(cx, rect, self.highlight_color);
+            }
+            TextIns::draw_str(cx, item, rect.pos + vec2(padding.l, padding.t), &props);
+            self.item_rects.push(rect);
+        }
+        self.list_view.end_view(cx);
+    }
+}
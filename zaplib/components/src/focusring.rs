@@ -0,0 +1,73 @@
+//! Outline around the component that has keyboard focus, see [`Cx::get_key_focus_rect`].
+
+use zaplib::*;
+
+#[derive(Clone, Copy, Default)]
+#[repr(C)]
+struct FocusRingIns {
+    quad: QuadIns,
+    color: Vec4,
+}
+
+static SHADER: Shader = Shader {
+    build_geom: Some(QuadIns::build_geom),
+    code_to_concatenate: &[
+        Cx::STD_SHADER,
+        QuadIns::SHADER,
+        code_fragment!(
+            r#"
+            instance color: vec4;
+
+            const stroke_width: float = 2.;
+            const border_radius: float = 4.;
+
+            fn pixel() -> vec4 {
+                let df = Df::viewport(pos * rect_size);
+                df.box(vec2(stroke_width / 2.), rect_size - stroke_width, border_radius);
+                return df.stroke(color, stroke_width);
+            }"#
+        ),
+    ],
+    ..Shader::DEFAULT
+};
+
+/// Draws a ring around the component that has keyboard focus, but only when the focus was moved using the
+/// keyboard (see [`Cx::is_focus_visible`]).
+///
+/// Draw this after everything else in your window, so it sits on top and it can find the focused component.
+pub struct FocusRing {
+    color: Vec4,
+    /// How far the ring sits outside of the focused component.
+    offset: f32,
+}
+
+impl Default for FocusRing {
+    fn default() -> Self {
+        Self { color: vec4(0.3, 0.6, 1., 1.), offset: 2. }
+    }
+}
+
+impl FocusRing {
+    #[must_use]
+    pub fn with_color(self, color: Vec4) -> Self {
+        Self { color, ..self }
+    }
+    #[must_use]
+    pub fn with_offset(self, offset: f32) -> Self {
+        Self { offset, ..self }
+    }
+
+    pub fn draw(&mut self, cx: &mut Cx) {
+        if let Some(rect) = cx.get_key_focus_rect() {
+            let rect =
+                Rect { pos: rect.pos - vec2(self.offset, self.offset), size: rect.size + vec2(self.offset, self.offset) * 2. };
+            // The rect is already scrolled, so don't scroll it again.
+            cx.add_instances_with_scroll_sticky(
+                &SHADER,
+                &[FocusRingIns { quad: QuadIns::from_rect(rect).with_draw_depth(10.), color: self.color }],
+                true,
+                true,
+            );
+        }
+    }
+}
@@ -0,0 +1,510 @@
+//! Forms that group input components, validate them, and turn them into a typed value on submit.
+
+use crate::button::*;
+use crate::dropdown::*;
+use crate::texteditor::TextEditorEvent;
+use crate::textinput::*;
+use crate::toggle::*;
+use std::str::FromStr;
+use zaplib::*;
+
+/// The value of a field in a [`Form`].
+#[derive(Clone, Debug, PartialEq)]
+pub enum FieldValue {
+    Text(String),
+    Bool(bool),
+    /// The index of the selected item, if any.
+    Choice(Option<usize>),
+}
+
+type ValidateFn = dyn Fn(&FieldValue) -> Result<(), String>;
+
+/// Checks a [`FieldValue`], returning an error message to show under the field if it's not valid.
+pub struct Validator(Box<ValidateFn>);
+
+impl Validator {
+    pub fn new(validate: impl Fn(&FieldValue) -> Result<(), String> + 'static) -> Self {
+        Self(Box::new(validate))
+    }
+
+    /// Text that isn't just whitespace, a checked checkbox, or a selected item.
+    pub fn required(message: &str) -> Self {
+        let message = message.to_string();
+        Self::new(move |value| {
+            let present = match value {
+                FieldValue::Text(text) => !text.trim().is_empty(),
+                FieldValue::Bool(checked) => *checked,
+                FieldValue::Choice(selected) => selected.is_some(),
+            };
+            if present {
+                Ok(())
+            } else {
+                Err(message.clone())
+            }
+        })
+    }
+
+    /// Text of at least `min` characters. Empty text is fine; combine with [`Validator::required`] to disallow that.
+    pub fn min_length(min: usize, message: &str) -> Self {
+        let message = message.to_string();
+        Self::text(move |text| text.is_empty() || text.chars().count() >= min, message)
+    }
+
+    /// Text of at most `max` characters.
+    pub fn max_length(max: usize, message: &str) -> Self {
+        let message = message.to_string();
+        Self::text(move |text| text.chars().count() <= max, message)
+    }
+
+    /// Text that parses as `F` (ignoring surrounding whitespace), e.g. `Validator::parses::<u32>("Not a number")`.
+    /// Empty text is fine.
+    pub fn parses<F: FromStr>(message: &str) -> Self {
+        let message = message.to_string();
+        Self::text(move |text| text.trim().is_empty() || text.trim().parse::<F>().is_ok(), message)
+    }
+
+    /// Text that is a number between `min` and `max` (inclusive). Empty text is fine.
+    pub fn range(min: f64, max: f64, message: &str) -> Self {
+        let message = message.to_string();
+        Self::text(
+            move |text| {
+                text.trim().is_empty() || text.trim().parse::<f64>().map_or(false, |number| number >= min && number <= max)
+            },
+            message,
+        )
+    }
+
+    /// Apply `is_valid` to text values; other values are always valid.
+    fn text(is_valid: impl Fn(&str) -> bool + 'static, message: String) -> Self {
+        Self::new(move |value| match value {
+            FieldValue::Text(text) if !is_valid(text) => Err(message.clone()),
+            _ => Ok(()),
+        })
+    }
+
+    pub fn validate(&self, value: &FieldValue) -> Result<(), String> {
+        (self.0)(value)
+    }
+}
+
+/// An error for a specific field, e.g. returned from the function passed into [`Form::new`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct FieldError {
+    pub field: String,
+    pub message: String,
+}
+
+impl FieldError {
+    pub fn new(field: &str, message: &str) -> Self {
+        Self { field: field.to_string(), message: message.to_string() }
+    }
+}
+
+/// The values of all fields in a [`Form`], by name. Passed into the function given to [`Form::new`].
+pub struct FormValues<'a> {
+    fields: &'a [FormField],
+}
+
+impl<'a> FormValues<'a> {
+    pub fn get(&self, name: &str) -> Option<FieldValue> {
+        self.fields.iter().find(|field| field.name == name).map(|field| field.value())
+    }
+
+    /// The text of a text field, or an empty string if there's no such field.
+    pub fn text(&self, name: &str) -> String {
+        match self.get(name) {
+            Some(FieldValue::Text(text)) => text,
+            _ => String::new(),
+        }
+    }
+
+    /// Whether a checkbox field is checked.
+    pub fn bool(&self, name: &str) -> bool {
+        matches!(self.get(name), Some(FieldValue::Bool(true)))
+    }
+
+    /// The index of the selected item of a drop down field.
+    pub fn choice(&self, name: &str) -> Option<usize> {
+        match self.get(name) {
+            Some(FieldValue::Choice(selected)) => selected,
+            _ => None,
+        }
+    }
+
+    /// Parse the (trimmed) text of a text field, returning a [`FieldError`] with `message` if that fails. Typically
+    /// used with `?` in the function passed into [`Form::new`].
+    pub fn parse<F: FromStr>(&self, name: &str, message: &str) -> Result<F, FieldError> {
+        self.text(name).trim().parse().map_err(|_| FieldError::new(name, message))
+    }
+}
+
+enum FieldInput {
+    Text(Box<TextInput>),
+    Checkbox(Box<Toggle>),
+    DropDown(Box<DropDown>),
+}
+
+struct FormField {
+    name: String,
+    label: String,
+    input: FieldInput,
+    validators: Vec<Validator>,
+    /// For dirty tracking; see [`Form::is_dirty`].
+    initial_value: FieldValue,
+    error: Option<String>,
+    /// Whether to show [`FormField::error`]. We only do that after the user is done with a field (or tried to
+    /// submit), so we don't complain about text that is still being typed.
+    touched: bool,
+}
+
+impl FormField {
+    fn value(&self) -> FieldValue {
+        match &self.input {
+            FieldInput::Text(text_input) => FieldValue::Text(text_input.get_value()),
+            FieldInput::Checkbox(toggle) => FieldValue::Bool(toggle.checked()),
+            FieldInput::DropDown(dropdown) => FieldValue::Choice(dropdown.selected()),
+        }
+    }
+
+    fn set_value(&mut self, cx: &mut Cx, value: &FieldValue) {
+        match (&mut self.input, value) {
+            (FieldInput::Text(text_input), FieldValue::Text(text)) => text_input.set_value(cx, text),
+            (FieldInput::Checkbox(toggle), FieldValue::Bool(value)) => toggle.set_checked(cx, *value),
+            (FieldInput::DropDown(dropdown), FieldValue::Choice(selected)) => dropdown.set_selected(cx, *selected),
+            _ => panic!("Wrong type of value for form field {}", self.name),
+        }
+    }
+
+    /// Run the validators, returning whether the field is valid.
+    fn validate(&mut self) -> bool {
+        let value = self.value();
+        self.error = self.validators.iter().find_map(|validator| validator.validate(&value).err());
+        self.error.is_none()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum FormEvent<T> {
+    None,
+    /// The value of this field changed.
+    Changed(String),
+    /// All fields were valid, and this is what the function passed into [`Form::new`] made of them.
+    Submitted(T),
+    /// The user tried to submit, but some fields are invalid. They now show their errors.
+    Invalid,
+}
+
+type ToValueFn<T> = dyn Fn(&FormValues) -> Result<T, FieldError>;
+
+/// A column of labeled input components, with a submit button at the bottom.
+///
+/// Fields have [`Validator`]s, and show the first error under the field once the user is done editing it (or tries
+/// to submit). On submit, when all fields are valid, the function passed into [`Form::new`] turns the
+/// [`FormValues`] into a value of your own type, which comes back in [`FormEvent::Submitted`].
+///
+/// ```ignore
+/// struct Person { name: String, age: u32, newsletter: bool }
+///
+/// let form = Form::new(|values| {
+///     Ok(Person { name: values.text("name"), age: values.parse("age", "Not a number")?, newsletter: values.bool("newsletter") })
+/// })
+/// .with_text_field("name", "Name", vec![Validator::required("Please enter a name")])
+/// .with_text_field("age", "Age", vec![Validator::required("Please enter an age"), Validator::range(0., 150., "Invalid age")])
+/// .with_checkbox_field("newsletter", "Subscribe to the newsletter", vec![]);
+/// ```
+pub struct Form<T> {
+    fields: Vec<FormField>,
+    to_value: Box<ToValueFn<T>>,
+    submit_label: String,
+    submit_button: Button,
+}
+
+impl<T> Form<T> {
+    const ERROR_COLOR: Vec4 = vec4(0.94, 0.3, 0.3, 1.);
+
+    /// Create a form without any fields yet. `to_value` gets called on submit, when all fields are valid.
+    pub fn new(to_value: impl Fn(&FormValues) -> Result<T, FieldError> + 'static) -> Self {
+        Self {
+            fields: vec![],
+            to_value: Box::new(to_value),
+            submit_label: "Submit".to_string(),
+            submit_button: Button::default(),
+        }
+    }
+
+    fn with_field(mut self, name: &str, label: &str, input: FieldInput, validators: Vec<Validator>) -> Self {
+        assert!(self.fields.iter().all(|field| field.name != name), "Duplicate form field {}", name);
+        let mut field = FormField {
+            name: name.to_string(),
+            label: label.to_string(),
+            input,
+            validators,
+            initial_value: FieldValue::Bool(false),
+            error: None,
+            touched: false,
+        };
+        field.initial_value = field.value();
+        self.fields.push(field);
+        self
+    }
+
+    #[must_use]
+    pub fn with_text_field(self, name: &str, label: &str, validators: Vec<Validator>) -> Self {
+        let text_input = TextInput::new(TextInputOptions::default());
+        self.with_field(name, label, FieldInput::Text(Box::new(text_input)), validators)
+    }
+    #[must_use]
+    pub fn with_checkbox_field(self, name: &str, label: &str, validators: Vec<Validator>) -> Self {
+        self.with_field(name, label, FieldInput::Checkbox(Box::new(Toggle::checkbox(label))), validators)
+    }
+    #[must_use]
+    pub fn with_dropdown_field(self, name: &str, label: &str, items: &[&str], validators: Vec<Validator>) -> Self {
+        let dropdown = DropDown::default().with_items(items).with_placeholder("Select...");
+        self.with_field(name, label, FieldInput::DropDown(Box::new(dropdown)), validators)
+    }
+    #[must_use]
+    pub fn with_submit_label(self, submit_label: &str) -> Self {
+        Self { submit_label: submit_label.to_string(), ..self }
+    }
+
+    fn field_mut(&mut self, name: &str) -> &mut FormField {
+        self.fields.iter_mut().find(|field| field.name == name).unwrap_or_else(|| panic!("No form field {}", name))
+    }
+
+    pub fn values(&self) -> FormValues<'_> {
+        FormValues { fields: &self.fields }
+    }
+
+    /// Set the value of a field, e.g. when loading an existing record. Panics if there is no such field, or if the
+    /// value has the wrong type.
+    pub fn set_value(&mut self, cx: &mut Cx, name: &str, value: FieldValue) {
+        let field = self.field_mut(name);
+        field.set_value(cx, &value);
+        field.error = None;
+        field.touched = false;
+    }
+
+    /// Whether any field is different from when it was last marked clean (see [`Form::mark_clean`]).
+    pub fn is_dirty(&self) -> bool {
+        self.fields.iter().any(|field| field.value() != field.initial_value)
+    }
+
+    /// Whether a single field is different from when it was last marked clean.
+    pub fn is_field_dirty(&self, name: &str) -> bool {
+        self.fields.iter().any(|field| field.name == name && field.value() != field.initial_value)
+    }
+
+    /// Make the current values the ones to compare to for [`Form::is_dirty`], e.g. after saving them.
+    pub fn mark_clean(&mut self) {
+        for field in &mut self.fields {
+            field.initial_value = field.value();
+        }
+    }
+
+    /// Go back to the values of when the form was last marked clean, and hide all errors.
+    pub fn reset(&mut self, cx: &mut Cx) {
+        for field in &mut self.fields {
+            let initial_value = field.initial_value.clone();
+            field.set_value(cx, &initial_value);
+            field.error = None;
+            field.touched = false;
+        }
+        cx.request_draw();
+    }
+
+    /// The error that is currently shown for a field, if any.
+    pub fn field_error(&self, name: &str) -> Option<&str> {
+        let field = self.fields.iter().find(|field| field.name == name)?;
+        field.touched.then(|| field.error.as_deref()).flatten()
+    }
+
+    /// Show an error for a field, e.g. when the server rejected a value. It goes away when the field changes.
+    pub fn set_field_error(&mut self, cx: &mut Cx, name: &str, message: &str) {
+        let field = self.field_mut(name);
+        field.error = Some(message.to_string());
+        field.touched = true;
+        cx.request_draw();
+    }
+
+    /// Validate all fields and show their errors. Returns whether all of them are valid.
+    pub fn validate(&mut self, cx: &mut Cx) -> bool {
+        let mut valid = true;
+        for field in &mut self.fields {
+            field.touched = true;
+            valid &= field.validate();
+        }
+        cx.request_draw();
+        valid
+    }
+
+    /// Validate everything and convert the values, like when clicking the submit button.
+    pub fn submit(&mut self, cx: &mut Cx) -> FormEvent<T> {
+        if !self.validate(cx) {
+            return FormEvent::Invalid;
+        }
+        match (self.to_value)(&self.values()) {
+            Ok(value) => FormEvent::Submitted(value),
+            Err(error) => {
+                self.set_field_error(cx, &error.field, &error.message);
+                FormEvent::Invalid
+            }
+        }
+    }
+
+    pub fn handle(&mut self, cx: &mut Cx, event: &mut Event) -> FormEvent<T> {
+        let mut form_event = FormEvent::None;
+        let mut submit = false;
+        for field in &mut self.fields {
+            let (changed, done) = match &mut field.input {
+                FieldInput::Text(text_input) => match text_input.handle(cx, event) {
+                    TextEditorEvent::Change => (true, false),
+                    TextEditorEvent::KeyFocusLost => (false, true),
+                    TextEditorEvent::Return => {
+                        submit = true;
+                        (false, true)
+                    }
+                    _ => (false, false),
+                },
+                FieldInput::Checkbox(toggle) => match toggle.handle(cx, event) {
+                    ToggleEvent::Changed(_) => (true, true),
+                    ToggleEvent::None => (false, false),
+                },
+                FieldInput::DropDown(dropdown) => match dropdown.handle(cx, event) {
+                    DropDownEvent::Selected(_) => (true, true),
+                    DropDownEvent::None => (false, false),
+                },
+            };
+            if done {
+                field.touched = true;
+            }
+            // Errors update while typing once they are shown, so they go away as soon as the value is fixed.
+            if changed || done {
+                field.validate();
+                cx.request_draw();
+            }
+            if changed {
+                form_event = FormEvent::Changed(field.name.clone());
+            }
+        }
+        if self.submit_button.handle(cx, event) == ButtonEvent::Clicked {
+            submit = true;
+        }
+        if submit {
+            return self.submit(cx);
+        }
+        form_event
+    }
+
+    pub fn draw(&mut self, cx: &mut Cx) {
+        cx.begin_column(Width::Fill, Height::Compute);
+        let label_props = TextInsProps { color: vec4(0.8, 0.8, 0.8, 1.), ..TextInsProps::DEFAULT };
+        let error_props = TextInsProps { color: Self::ERROR_COLOR, ..TextInsProps::DEFAULT };
+        for field in &mut self.fields {
+            let error = if field.touched { field.error.as_deref() } else { None };
+            cx.begin_padding_box(Padding::bottom(10.));
+            cx.begin_column(Width::Fill, Height::Compute);
+            match &mut field.input {
+                FieldInput::Text(text_input) => {
+                    TextIns::draw_walk(cx, &field.label, &label_props);
+                    cx.add_box(LayoutSize::new(Width::Fill, Height::Fix(4.)));
+                    cx.begin_row(Width::Fill, Height::Compute);
+                    text_input.draw(cx);
+                    cx.end_row();
+                }
+                FieldInput::Checkbox(toggle) => toggle.draw(cx),
+                FieldInput::DropDown(dropdown) => {
+                    TextIns::draw_walk(cx, &field.label, &label_props);
+                    cx.add_box(LayoutSize::new(Width::Fill, Height::Fix(4.)));
+                    cx.begin_row(Width::Fill, Height::Compute);
+                    dropdown.draw(cx);
+                    cx.end_row();
+                }
+            }
+            if let Some(error) = error {
+                cx.add_box(LayoutSize::new(Width::Fill, Height::Fix(2.)));
+                TextIns::draw_walk(cx, error, &error_props);
+            }
+            cx.end_column();
+            cx.end_padding_box();
+        }
+        cx.begin_row(Width::Fill, Height::Compute);
+        self.submit_button.draw(cx, &self.submit_label);
+        cx.end_row();
+        cx.end_column();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    use zaplib::*;
+
+    #[derive(Debug, PartialEq)]
+    struct Person {
+        name: String,
+        age: u32,
+        newsletter: bool,
+    }
+
+    fn person_form() -> Form<Person> {
+        Form::new(|values| {
+            Ok(Person {
+                name: values.text("name").trim().to_string(),
+                age: values.parse("age", "Not a number")?,
+                newsletter: values.bool("newsletter"),
+            })
+        })
+        .with_text_field("name", "Name", vec![Validator::required("Required"), Validator::max_length(5, "Too long")])
+        .with_text_field("age", "Age", vec![Validator::range(0., 150., "Invalid age")])
+        .with_checkbox_field("newsletter", "Newsletter", vec![])
+        .with_dropdown_field("country", "Country", &["NL", "US"], vec![])
+    }
+
+    #[test]
+    fn test_validation_and_submit() {
+        let mut test_cx = TestCx::new(vec2(400., 300.));
+        let cx = &mut test_cx.cx;
+        let mut form = person_form();
+
+        assert_eq!(form.submit(cx), FormEvent::Invalid);
+        assert_eq!(form.field_error("name"), Some("Required"));
+        assert_eq!(form.field_error("age"), None);
+
+        form.set_value(cx, "name", FieldValue::Text("Alexander".to_string()));
+        form.set_value(cx, "age", FieldValue::Text("200".to_string()));
+        assert_eq!(form.field_error("name"), None);
+        assert_eq!(form.submit(cx), FormEvent::Invalid);
+        assert_eq!(form.field_error("name"), Some("Too long"));
+        assert_eq!(form.field_error("age"), Some("Invalid age"));
+
+        // Passes the range validator, but the conversion to `u32` fails.
+        form.set_value(cx, "name", FieldValue::Text("Alex".to_string()));
+        form.set_value(cx, "age", FieldValue::Text("4.5".to_string()));
+        assert_eq!(form.submit(cx), FormEvent::Invalid);
+        assert_eq!(form.field_error("age"), Some("Not a number"));
+
+        form.set_value(cx, "age", FieldValue::Text(" 42 ".to_string()));
+        form.set_value(cx, "newsletter", FieldValue::Bool(true));
+        assert_eq!(form.submit(cx), FormEvent::Submitted(Person { name: "Alex".to_string(), age: 42, newsletter: true }));
+    }
+
+    #[test]
+    fn test_dirty_tracking() {
+        let mut test_cx = TestCx::new(vec2(400., 300.));
+        let cx = &mut test_cx.cx;
+        let mut form = person_form();
+        assert!(!form.is_dirty());
+
+        form.set_value(cx, "country", FieldValue::Choice(Some(1)));
+        assert!(form.is_dirty());
+        assert!(form.is_field_dirty("country"));
+        assert!(!form.is_field_dirty("name"));
+
+        form.mark_clean();
+        assert!(!form.is_dirty());
+        form.set_value(cx, "country", FieldValue::Choice(None));
+        form.reset(cx);
+        assert_eq!(form.values().choice("country"), Some(1));
+        assert!(!form.is_dirty());
+    }
+}
@@ -0,0 +1,594 @@
+//! Loading [`SkinnedMesh`]es from glTF files.
+//!
+//! Only binary glTF files (`.glb`) are supported, since regular `.gltf` files refer to other files.
+
+use crate::*;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::io::Read;
+use zaplib::*;
+
+const GLB_MAGIC: u32 = 0x46546C67; // "glTF"
+const CHUNK_TYPE_JSON: u32 = 0x4E4F534A;
+const CHUNK_TYPE_BIN: u32 = 0x004E4942;
+
+// The file data isn't necessarily aligned in memory, so copy out the bytes instead of reading through pointers.
+fn read_u16(bytes: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes(bytes[offset..offset + 2].try_into().unwrap())
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+}
+
+fn read_f32(bytes: &[u8], offset: usize) -> f32 {
+    f32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GltfJson {
+    #[serde(default)]
+    nodes: Vec<GltfNode>,
+    #[serde(default)]
+    meshes: Vec<GltfMesh>,
+    #[serde(default)]
+    skins: Vec<GltfSkin>,
+    #[serde(default)]
+    animations: Vec<GltfAnimation>,
+    #[serde(default)]
+    accessors: Vec<GltfAccessor>,
+    #[serde(default)]
+    buffer_views: Vec<GltfBufferView>,
+}
+
+#[derive(Deserialize)]
+struct GltfNode {
+    #[serde(default)]
+    name: String,
+    #[serde(default)]
+    children: Vec<usize>,
+    mesh: Option<usize>,
+    skin: Option<usize>,
+    matrix: Option<[f32; 16]>,
+    translation: Option<[f32; 3]>,
+    rotation: Option<[f32; 4]>,
+    scale: Option<[f32; 3]>,
+}
+
+#[derive(Deserialize)]
+struct GltfMesh {
+    primitives: Vec<GltfPrimitive>,
+}
+
+#[derive(Deserialize)]
+struct GltfPrimitive {
+    attributes: HashMap<String, usize>,
+    indices: Option<usize>,
+    mode: Option<u32>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GltfSkin {
+    joints: Vec<usize>,
+    inverse_bind_matrices: Option<usize>,
+}
+
+#[derive(Deserialize)]
+struct GltfAnimation {
+    #[serde(default)]
+    name: String,
+    channels: Vec<GltfChannel>,
+    samplers: Vec<GltfSampler>,
+}
+
+#[derive(Deserialize)]
+struct GltfChannel {
+    sampler: usize,
+    target: GltfTarget,
+}
+
+#[derive(Deserialize)]
+struct GltfTarget {
+    node: Option<usize>,
+    path: String,
+}
+
+#[derive(Deserialize)]
+struct GltfSampler {
+    input: usize,
+    output: usize,
+    interpolation: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GltfAccessor {
+    buffer_view: Option<usize>,
+    #[serde(default)]
+    byte_offset: usize,
+    component_type: u32,
+    #[serde(default)]
+    normalized: bool,
+    count: usize,
+    #[serde(rename = "type")]
+    ty: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GltfBufferView {
+    #[serde(default)]
+    byte_offset: usize,
+    byte_length: usize,
+    byte_stride: Option<usize>,
+}
+
+impl GltfNode {
+    fn transform(&self) -> JointTransform {
+        if let Some(matrix) = self.matrix {
+            return decompose(&Mat4 { v: matrix });
+        }
+        let mut transform = JointTransform::IDENTITY;
+        if let Some([x, y, z]) = self.translation {
+            transform.translation = vec3(x, y, z);
+        }
+        if let Some([a, b, c, d]) = self.rotation {
+            transform.rotation = Quat { a, b, c, d };
+        }
+        if let Some([x, y, z]) = self.scale {
+            transform.scale = vec3(x, y, z);
+        }
+        transform
+    }
+}
+
+/// Split a matrix without shearing into translation, rotation, and scale.
+fn decompose(matrix: &Mat4) -> JointTransform {
+    let m = &matrix.v;
+    let scale = vec3(vec3(m[0], m[1], m[2]).length(), vec3(m[4], m[5], m[6]).length(), vec3(m[8], m[9], m[10]).length());
+    // Rotation matrix element at `row`, `column`.
+    let r = |row: usize, column: usize| m[column * 4 + row] / [scale.x, scale.y, scale.z][column];
+    let trace = r(0, 0) + r(1, 1) + r(2, 2);
+    let rotation = if trace > 0. {
+        let s = (trace + 1.).sqrt() * 2.;
+        Quat { a: (r(2, 1) - r(1, 2)) / s, b: (r(0, 2) - r(2, 0)) / s, c: (r(1, 0) - r(0, 1)) / s, d: 0.25 * s }
+    } else if r(0, 0) > r(1, 1) && r(0, 0) > r(2, 2) {
+        let s = (1. + r(0, 0) - r(1, 1) - r(2, 2)).sqrt() * 2.;
+        Quat { a: 0.25 * s, b: (r(0, 1) + r(1, 0)) / s, c: (r(0, 2) + r(2, 0)) / s, d: (r(2, 1) - r(1, 2)) / s }
+    } else if r(1, 1) > r(2, 2) {
+        let s = (1. + r(1, 1) - r(0, 0) - r(2, 2)).sqrt() * 2.;
+        Quat { a: (r(0, 1) + r(1, 0)) / s, b: 0.25 * s, c: (r(1, 2) + r(2, 1)) / s, d: (r(0, 2) - r(2, 0)) / s }
+    } else {
+        let s = (1. + r(2, 2) - r(0, 0) - r(1, 1)).sqrt() * 2.;
+        Quat { a: (r(0, 2) + r(2, 0)) / s, b: (r(1, 2) + r(2, 1)) / s, c: 0.25 * s, d: (r(1, 0) - r(0, 1)) / s }
+    };
+    JointTransform { translation: vec3(m[12], m[13], m[14]), rotation, scale }
+}
+
+struct GltfReader<'a> {
+    json: GltfJson,
+    bin: &'a [u8],
+}
+
+impl<'a> GltfReader<'a> {
+    /// All values of an accessor as floats, with `components` (e.g. 3 for a `VEC3`) floats per element.
+    fn read(&self, accessor_index: usize, components: usize) -> Result<Vec<f32>, String> {
+        let accessor =
+            self.json.accessors.get(accessor_index).ok_or_else(|| format!("Accessor {accessor_index} doesn't exist"))?;
+        let accessor_components = match accessor.ty.as_str() {
+            "SCALAR" => 1,
+            "VEC2" => 2,
+            "VEC3" => 3,
+            "VEC4" => 4,
+            "MAT4" => 16,
+            ty => return Err(format!("Unsupported accessor type {ty}")),
+        };
+        if accessor_components != components {
+            return Err(format!("Accessor {accessor_index} has type {}, expected {components} components", accessor.ty));
+        }
+        let component_size = match accessor.component_type {
+            5120 | 5121 => 1,
+            5122 | 5123 => 2,
+            5125 | 5126 => 4,
+            component_type => return Err(format!("Unsupported accessor component type {component_type}")),
+        };
+
+        let buffer_view = match accessor.buffer_view {
+            Some(buffer_view) => {
+                self.json.buffer_views.get(buffer_view).ok_or_else(|| format!("Buffer view {buffer_view} doesn't exist"))?
+            }
+            // Accessors without buffer views are all zeros.
+            None => return Ok(vec![0.; accessor.count * components]),
+        };
+        let stride = buffer_view.byte_stride.unwrap_or(components * component_size);
+        let start = buffer_view.byte_offset + accessor.byte_offset;
+        if accessor.count > 0 {
+            let end = start + stride * (accessor.count - 1) + components * component_size;
+            if end > buffer_view.byte_offset + buffer_view.byte_length || end > self.bin.len() {
+                return Err(format!("Accessor {accessor_index} is out of bounds"));
+            }
+        }
+
+        let data = self.bin;
+        let normalized = accessor.normalized;
+        let mut values = Vec::with_capacity(accessor.count * components);
+        for element in 0..accessor.count {
+            for component in 0..components {
+                let offset = start + element * stride + component * component_size;
+                values.push(match accessor.component_type {
+                    5120 if normalized => (data[offset] as i8 as f32 / 127.).max(-1.),
+                    5121 if normalized => data[offset] as f32 / 255.,
+                    5122 if normalized => (read_u16(data, offset) as i16 as f32 / 32767.).max(-1.),
+                    5123 if normalized => read_u16(data, offset) as f32 / 65535.,
+                    5120 => data[offset] as i8 as f32,
+                    5121 => data[offset] as f32,
+                    5122 => read_u16(data, offset) as i16 as f32,
+                    5123 => read_u16(data, offset) as f32,
+                    5125 => read_u32(data, offset) as f32,
+                    _ => read_f32(data, offset),
+                });
+            }
+        }
+        Ok(values)
+    }
+
+    fn read_vec3s(&self, accessor_index: usize) -> Result<Vec<Vec3>, String> {
+        Ok(self.read(accessor_index, 3)?.chunks(3).map(|v| vec3(v[0], v[1], v[2])).collect())
+    }
+
+    fn read_vec4s(&self, accessor_index: usize) -> Result<Vec<Vec4>, String> {
+        Ok(self.read(accessor_index, 4)?.chunks(4).map(|v| vec4(v[0], v[1], v[2], v[3])).collect())
+    }
+
+    /// Reads indices separately, since `u32` indices don't fit in `f32`.
+    fn read_indices(&self, accessor_index: usize) -> Result<Vec<u32>, String> {
+        let accessor =
+            self.json.accessors.get(accessor_index).ok_or_else(|| format!("Accessor {accessor_index} doesn't exist"))?;
+        if accessor.component_type != 5125 {
+            return Ok(self.read(accessor_index, 1)?.into_iter().map(|index| index as u32).collect());
+        }
+        let buffer_view = accessor
+            .buffer_view
+            .and_then(|buffer_view| self.json.buffer_views.get(buffer_view))
+            .ok_or_else(|| format!("Accessor {accessor_index} has no buffer view"))?;
+        let start = buffer_view.byte_offset + accessor.byte_offset;
+        let stride = buffer_view.byte_stride.unwrap_or(4);
+        if accessor.count > 0 && start + stride * (accessor.count - 1) + 4 > self.bin.len() {
+            return Err(format!("Accessor {accessor_index} is out of bounds"));
+        }
+        Ok((0..accessor.count).map(|index| read_u32(self.bin, start + index * stride)).collect())
+    }
+}
+
+impl SkinnedMesh {
+    /// Load a binary glTF file (`.glb`); see [`SkinnedMesh::from_glb`].
+    pub fn load_glb(path: &str) -> Result<Self, String> {
+        let mut data = vec![];
+        UniversalFile::open(path)
+            .and_then(|mut file| file.read_to_end(&mut data))
+            .map_err(|err| format!("Could not read {path}: {err}"))?;
+        Self::from_glb(&data).map_err(|err| format!("{path}: {err}"))
+    }
+
+    /// Parse a binary glTF file (`.glb`), using the first node that has both a mesh and a skin, and all animations of
+    /// that skin. Ancestors of the joints of the skin become joints as well, after the joints of the skin.
+    ///
+    /// Only triangle meshes are supported, and cubic spline interpolation gets approximated linearly. Morph targets
+    /// are ignored.
+    pub fn from_glb(data: &[u8]) -> Result<Self, String> {
+        if data.len() < 20 || read_u32(data, 0) != GLB_MAGIC {
+            return Err("Not a binary glTF file".to_string());
+        }
+        if read_u32(data, 4) != 2 {
+            return Err(format!("Unsupported glTF version {}", read_u32(data, 4)));
+        }
+        let mut json = None;
+        let mut bin: &[u8] = &[];
+        let mut offset = 12;
+        while offset + 8 <= data.len() {
+            let chunk_length = read_u32(data, offset) as usize;
+            let chunk_type = read_u32(data, offset + 4);
+            let chunk = data.get(offset + 8..offset + 8 + chunk_length).ok_or("Chunk is out of bounds")?;
+            match chunk_type {
+                CHUNK_TYPE_JSON => {
+                    json = Some(serde_json::from_slice::<GltfJson>(chunk).map_err(|err| format!("Invalid glTF JSON: {err}"))?)
+                }
+                CHUNK_TYPE_BIN => bin = chunk,
+                _ => {}
+            }
+            offset += 8 + chunk_length;
+        }
+        let reader = GltfReader { json: json.ok_or("No JSON chunk")?, bin };
+        let nodes = &reader.json.nodes;
+
+        let mesh_node =
+            nodes.iter().find(|node| node.mesh.is_some() && node.skin.is_some()).ok_or("No node with a skinned mesh")?;
+        let mesh = reader.json.meshes.get(mesh_node.mesh.unwrap()).ok_or("Mesh doesn't exist")?;
+        let skin = reader.json.skins.get(mesh_node.skin.unwrap()).ok_or("Skin doesn't exist")?;
+
+        let mut parents = vec![None; nodes.len()];
+        for (index, node) in nodes.iter().enumerate() {
+            for &child in &node.children {
+                *parents.get_mut(child).ok_or_else(|| format!("Node {child} doesn't exist"))? = Some(index);
+            }
+        }
+
+        for mut node in 0..nodes.len() {
+            for _ in 0..=nodes.len() {
+                match parents[node] {
+                    Some(parent) => node = parent,
+                    None => break,
+                }
+            }
+            if parents[node].is_some() {
+                return Err("The node hierarchy has a cycle".to_string());
+            }
+        }
+
+        // Skin joints come first, so the joint indices of vertices stay the same. Their ancestors become joints as
+        // well, so the transforms and animations of those aren't lost.
+        let mut joint_nodes: Vec<usize> = skin.joints.clone();
+        if let Some(&node) = joint_nodes.iter().find(|&&node| node >= nodes.len()) {
+            return Err(format!("Node {node} doesn't exist"));
+        }
+        let mut joint_indices: HashMap<usize, usize> =
+            joint_nodes.iter().enumerate().map(|(joint_index, &node)| (node, joint_index)).collect();
+        let mut index = 0;
+        while index < joint_nodes.len() {
+            if let Some(parent) = parents[joint_nodes[index]] {
+                if !joint_indices.contains_key(&parent) {
+                    joint_indices.insert(parent, joint_nodes.len());
+                    joint_nodes.push(parent);
+                }
+            }
+            index += 1;
+        }
+
+        let inverse_bind_matrices: Vec<Mat4> = match skin.inverse_bind_matrices {
+            Some(accessor) => reader.read(accessor, 16)?.chunks(16).map(|v| Mat4 { v: v.try_into().unwrap() }).collect(),
+            None => vec![],
+        };
+        let joints = joint_nodes
+            .iter()
+            .enumerate()
+            .map(|(joint_index, &node)| Joint {
+                name: nodes[node].name.clone(),
+                parent: parents[node].map(|parent| joint_indices[&parent]),
+                rest_pose: nodes[node].transform(),
+                inverse_bind_matrix: inverse_bind_matrices.get(joint_index).copied().unwrap_or_else(Mat4::identity),
+            })
+            .collect();
+        let skeleton = Skeleton::new(joints);
+        if skeleton.joints().len() > Self::MAX_JOINTS {
+            return Err(format!(
+                "Skeleton has {} joints, but at most {} are supported",
+                skeleton.joints().len(),
+                Self::MAX_JOINTS
+            ));
+        }
+
+        let mut vertices = vec![];
+        let mut indices = vec![];
+        for primitive in &mesh.primitives {
+            if primitive.mode.unwrap_or(4) != 4 {
+                return Err("Only triangle meshes are supported".to_string());
+            }
+            let attribute = |name: &str| primitive.attributes.get(name).copied();
+            let positions = reader.read_vec3s(attribute("POSITION").ok_or("Mesh has no positions")?)?;
+            let normals = match attribute("NORMAL") {
+                Some(accessor) => reader.read_vec3s(accessor)?,
+                None => vec![vec3(0., 1., 0.); positions.len()],
+            };
+            let vertex_joints = reader.read_vec4s(attribute("JOINTS_0").ok_or("Mesh has no joints")?)?;
+            let weights = reader.read_vec4s(attribute("WEIGHTS_0").ok_or("Mesh has no weights")?)?;
+            if normals.len() != positions.len() || vertex_joints.len() != positions.len() || weights.len() != positions.len() {
+                return Err("Mesh attributes have different lengths".to_string());
+            }
+
+            let first_vertex = vertices.len() as u32;
+            let primitive_indices = match primitive.indices {
+                Some(accessor) => reader.read_indices(accessor)?,
+                None => (0..positions.len() as u32).collect(),
+            };
+            if primitive_indices.iter().any(|&index| index as usize >= positions.len()) {
+                return Err("Mesh has out of bounds indices".to_string());
+            }
+            indices.extend(
+                primitive_indices
+                    .chunks_exact(3)
+                    .map(|triangle| [triangle[0], triangle[1], triangle[2]].map(|index| index + first_vertex)),
+            );
+            for index in 0..positions.len() {
+                vertices.push(SkinnedVertex {
+                    position: positions[index],
+                    normal: normals[index],
+                    joints: vertex_joints[index],
+                    weights: weights[index],
+                });
+            }
+        }
+
+        let mut clips = vec![];
+        for animation in &reader.json.animations {
+            let mut clip = AnimationClip { name: animation.name.clone(), ..AnimationClip::default() };
+            for channel in &animation.channels {
+                let joint = match channel.target.node.and_then(|node| joint_indices.get(&node)) {
+                    Some(&joint) => joint,
+                    // Nodes that don't affect the skin.
+                    None => continue,
+                };
+                let sampler = animation.samplers.get(channel.sampler).ok_or("Animation sampler doesn't exist")?;
+                let times = reader.read(sampler.input, 1)?;
+                let (interpolation, cubic_spline) = match sampler.interpolation.as_deref() {
+                    Some("STEP") => (Interpolation::Step, false),
+                    Some("CUBICSPLINE") => (Interpolation::Linear, true),
+                    _ => (Interpolation::Linear, false),
+                };
+                // Cubic spline key frames consist of an in-tangent, a value, and an out-tangent; only keep the values.
+                let key_frame_values = |values: Vec<f32>, components: usize| -> Vec<f32> {
+                    if cubic_spline {
+                        values
+                            .chunks(components * 3)
+                            .flat_map(|key_frame| key_frame[components..components * 2].to_vec())
+                            .collect()
+                    } else {
+                        values
+                    }
+                };
+                let values = match channel.target.path.as_str() {
+                    "translation" => ChannelValues::Translation(
+                        key_frame_values(reader.read(sampler.output, 3)?, 3).chunks(3).map(|v| vec3(v[0], v[1], v[2])).collect(),
+                    ),
+                    "rotation" => ChannelValues::Rotation(
+                        key_frame_values(reader.read(sampler.output, 4)?, 4)
+                            .chunks(4)
+                            .map(|v| Quat { a: v[0], b: v[1], c: v[2], d: v[3] })
+                            .collect(),
+                    ),
+                    "scale" => ChannelValues::Scale(
+                        key_frame_values(reader.read(sampler.output, 3)?, 3).chunks(3).map(|v| vec3(v[0], v[1], v[2])).collect(),
+                    ),
+                    // Morph target weights.
+                    _ => continue,
+                };
+                let values_len = match &values {
+                    ChannelValues::Translation(values) | ChannelValues::Scale(values) => values.len(),
+                    ChannelValues::Rotation(values) => values.len(),
+                };
+                if values_len != times.len() {
+                    return Err("Animation channel has a different number of times and values".to_string());
+                }
+                clip.duration = times.iter().copied().fold(clip.duration, f32::max);
+                clip.channels.push(AnimationChannel { joint, interpolation, times, values });
+            }
+            clips.push(clip);
+        }
+
+        Ok(Self::new(vertices, indices, skeleton, clips))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::PI;
+
+    fn push_f32s(bin: &mut Vec<u8>, values: &[f32]) {
+        for value in values {
+            bin.extend_from_slice(&value.to_le_bytes());
+        }
+    }
+
+    /// An arm in an armature that's moved up by 5, with an elbow 1 to the right of the shoulder, and one triangle that
+    /// follows the elbow. An animation raises the arm by rotating the shoulder by 90 degrees.
+    fn arm_glb() -> Vec<u8> {
+        let mut bin = vec![];
+        push_f32s(&mut bin, &[0., 5., 0., 2., 5., 0., 2., 6., 0.]);
+        bin.extend_from_slice(&[1, 0, 0, 0, 1, 0, 0, 0, 1, 0, 0, 0]);
+        push_f32s(&mut bin, &[1., 0., 0., 0., 1., 0., 0., 0., 1., 0., 0., 0.]);
+        push_f32s(&mut bin, &Mat4::translation(0., -5., 0.).v);
+        push_f32s(&mut bin, &Mat4::translation(-1., -5., 0.).v);
+        push_f32s(&mut bin, &[0., 1.]);
+        let raised = Quat::from_axis_angle(vec3(0., 0., 1.), PI / 2.);
+        push_f32s(&mut bin, &[0., 0., 0., 1., raised.a, raised.b, raised.c, raised.d]);
+
+        let json = r#"{
+            "nodes": [
+                { "name": "armature", "translation": [0, 5, 0], "children": [1, 3] },
+                { "name": "shoulder", "children": [2] },
+                { "name": "elbow", "translation": [1, 0, 0] },
+                { "mesh": 0, "skin": 0 }
+            ],
+            "meshes": [{ "primitives": [{ "attributes": { "POSITION": 0, "JOINTS_0": 1, "WEIGHTS_0": 2 } }] }],
+            "skins": [{ "joints": [1, 2], "inverseBindMatrices": 3 }],
+            "animations": [{
+                "name": "raise",
+                "channels": [{ "sampler": 0, "target": { "node": 1, "path": "rotation" } }],
+                "samplers": [{ "input": 4, "output": 5 }]
+            }],
+            "accessors": [
+                { "bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3" },
+                { "bufferView": 1, "componentType": 5121, "count": 3, "type": "VEC4" },
+                { "bufferView": 2, "componentType": 5126, "count": 3, "type": "VEC4" },
+                { "bufferView": 3, "componentType": 5126, "count": 2, "type": "MAT4" },
+                { "bufferView": 4, "componentType": 5126, "count": 2, "type": "SCALAR" },
+                { "bufferView": 5, "componentType": 5126, "count": 2, "type": "VEC4" }
+            ],
+            "bufferViews": [
+                { "buffer": 0, "byteOffset": 0, "byteLength": 36 },
+                { "buffer": 0, "byteOffset": 36, "byteLength": 12 },
+                { "buffer": 0, "byteOffset": 48, "byteLength": 48 },
+                { "buffer": 0, "byteOffset": 96, "byteLength": 128 },
+                { "buffer": 0, "byteOffset": 224, "byteLength": 8 },
+                { "buffer": 0, "byteOffset": 232, "byteLength": 32 }
+            ],
+            "buffers": [{ "byteLength": 264 }]
+        }"#;
+        let mut json = json.as_bytes().to_vec();
+        while json.len() % 4 != 0 {
+            json.push(b' ');
+        }
+
+        let mut glb = vec![];
+        glb.extend_from_slice(&GLB_MAGIC.to_le_bytes());
+        glb.extend_from_slice(&2u32.to_le_bytes());
+        glb.extend_from_slice(&((12 + 8 + json.len() + 8 + bin.len()) as u32).to_le_bytes());
+        glb.extend_from_slice(&(json.len() as u32).to_le_bytes());
+        glb.extend_from_slice(&CHUNK_TYPE_JSON.to_le_bytes());
+        glb.extend_from_slice(&json);
+        glb.extend_from_slice(&(bin.len() as u32).to_le_bytes());
+        glb.extend_from_slice(&CHUNK_TYPE_BIN.to_le_bytes());
+        glb.extend_from_slice(&bin);
+        glb
+    }
+
+    #[test]
+    fn test_from_glb() {
+        let mesh = SkinnedMesh::from_glb(&arm_glb()).unwrap();
+        assert_eq!(mesh.vertices().len(), 3);
+        assert_eq!(mesh.indices(), &[[0, 1, 2]]);
+        assert_eq!(mesh.vertices()[1].joints, vec4(1., 0., 0., 0.));
+
+        // The armature becomes a joint after the joints of the skin.
+        let joints = mesh.skeleton().joints();
+        assert_eq!(joints.iter().map(|joint| joint.name.as_str()).collect::<Vec<_>>(), ["shoulder", "elbow", "armature"]);
+        assert_eq!(joints[0].parent, Some(2));
+        assert_eq!(joints[1].parent, Some(0));
+
+        let hand = |pose: &[JointTransform]| {
+            let joint_matrices = mesh.skeleton().joint_matrices(pose);
+            joint_matrices[1].transform_vec4(vec4(2., 5., 0., 1.)).to_vec3()
+        };
+        let mut pose = mesh.skeleton().rest_pose();
+        assert!((hand(&pose) - vec3(2., 5., 0.)).length() < 1e-4);
+
+        let clip = &mesh.clips()[mesh.clip_index("raise").unwrap()];
+        assert_eq!(clip.duration, 1.);
+        clip.sample(1., &mut pose);
+        assert!((hand(&pose) - vec3(0., 7., 0.)).length() < 1e-4);
+    }
+
+    #[test]
+    fn test_from_glb_errors() {
+        assert!(SkinnedMesh::from_glb(b"not a glb file at all").is_err());
+        let mut glb = arm_glb();
+        // Cut off the binary chunk.
+        glb.truncate(glb.len() - 100);
+        assert!(SkinnedMesh::from_glb(&glb).is_err());
+    }
+
+    #[test]
+    fn test_decompose() {
+        let transform = JointTransform {
+            translation: vec3(1., 2., 3.),
+            rotation: Quat::from_axis_angle(vec3(0., 1., 0.), 2.5),
+            scale: vec3(2., 3., 4.),
+        };
+        let decomposed = decompose(&transform.to_mat4());
+        assert!((decomposed.translation - transform.translation).length() < 1e-4);
+        assert!((decomposed.scale - transform.scale).length() < 1e-4);
+        assert!((decomposed.rotation.dot(transform.rotation).abs() - 1.).abs() < 1e-4);
+    }
+}
@@ -0,0 +1,179 @@
+//! A small set of vector icons for buttons and menus.
+
+use zaplib::*;
+
+#[derive(Clone, Copy, Default)]
+#[repr(C)]
+struct IconIns {
+    base: QuadIns,
+    color: Vec4,
+    icon: f32,
+}
+
+static SHADER: Shader = Shader {
+    build_geom: Some(QuadIns::build_geom),
+    code_to_concatenate: &[
+        Cx::STD_SHADER,
+        QuadIns::SHADER,
+        code_fragment!(
+            r#"
+            instance color: vec4;
+            instance icon: float;
+
+            fn pixel() -> vec4 {
+                // Icons are designed on a 16x16 grid, and scale with the rect.
+                let df = Df::viewport(pos * 16.);
+                let w = 1.5;
+                if icon == 0. { // Add
+                    df.move_to(vec2(8., 3.));
+                    df.line_to(vec2(8., 13.));
+                    df.move_to(vec2(3., 8.));
+                    df.line_to(vec2(13., 8.));
+                    df.stroke(color, w);
+                } else if icon == 1. { // Remove
+                    df.move_to(vec2(3., 8.));
+                    df.line_to(vec2(13., 8.));
+                    df.stroke(color, w);
+                } else if icon == 2. { // Close
+                    df.move_to(vec2(4., 4.));
+                    df.line_to(vec2(12., 12.));
+                    df.move_to(vec2(12., 4.));
+                    df.line_to(vec2(4., 12.));
+                    df.stroke(color, w);
+                } else if icon == 3. { // Check
+                    df.move_to(vec2(3., 8.5));
+                    df.line_to(vec2(6.5, 12.));
+                    df.line_to(vec2(13., 4.5));
+                    df.stroke(color, w);
+                } else if icon == 4. { // ChevronLeft
+                    df.move_to(vec2(10., 3.));
+                    df.line_to(vec2(5., 8.));
+                    df.line_to(vec2(10., 13.));
+                    df.stroke(color, w);
+                } else if icon == 5. { // ChevronRight
+                    df.move_to(vec2(6., 3.));
+                    df.line_to(vec2(11., 8.));
+                    df.line_to(vec2(6., 13.));
+                    df.stroke(color, w);
+                } else if icon == 6. { // ChevronDown
+                    df.move_to(vec2(3., 6.));
+                    df.line_to(vec2(8., 11.));
+                    df.line_to(vec2(13., 6.));
+                    df.stroke(color, w);
+                } else if icon == 7. { // Menu
+                    df.move_to(vec2(2.5, 4.));
+                    df.line_to(vec2(13.5, 4.));
+                    df.move_to(vec2(2.5, 8.));
+                    df.line_to(vec2(13.5, 8.));
+                    df.move_to(vec2(2.5, 12.));
+                    df.line_to(vec2(13.5, 12.));
+                    df.stroke(color, w);
+                } else if icon == 8. { // More
+                    df.circle(vec2(3., 8.), 1.5);
+                    df.circle(vec2(8., 8.), 1.5);
+                    df.circle(vec2(13., 8.), 1.5);
+                    df.fill(color);
+                } else if icon == 9. { // Search
+                    df.circle(vec2(7., 7.), 4.5);
+                    df.stroke(color, w);
+                    df.new_path();
+                    df.move_to(vec2(10.5, 10.5));
+                    df.line_to(vec2(14., 14.));
+                    df.stroke(color, w);
+                } else if icon == 10. { // Play
+                    df.triangle(vec2(4., 2.5), vec2(13.5, 8.), vec2(4., 13.5));
+                    df.fill(color);
+                } else if icon == 11. { // Pause
+                    df.rect(vec2(3.5, 3.), vec2(3., 10.));
+                    df.rect(vec2(9.5, 3.), vec2(3., 10.));
+                    df.fill(color);
+                } else if icon == 12. { // Stop
+                    df.box(vec2(3.), vec2(10.), 1.);
+                    df.fill(color);
+                } else if icon == 13. || icon == 14. { // Undo, Redo
+                    if icon == 14. {
+                        df.pos.x = 16. - df.pos.x;
+                    }
+                    df.move_to(vec2(6., 3.));
+                    df.line_to(vec2(3., 6.));
+                    df.line_to(vec2(6., 9.));
+                    df.move_to(vec2(3., 6.));
+                    df.line_to(vec2(10., 6.));
+                    df.line_to(vec2(13., 9.));
+                    df.line_to(vec2(10., 12.));
+                    df.line_to(vec2(6., 12.));
+                    df.stroke(color, w);
+                } else if icon == 15. { // Home
+                    df.move_to(vec2(2., 8.));
+                    df.line_to(vec2(8., 2.5));
+                    df.line_to(vec2(14., 8.));
+                    df.move_to(vec2(4., 7.));
+                    df.line_to(vec2(4., 13.5));
+                    df.line_to(vec2(12., 13.5));
+                    df.line_to(vec2(12., 7.));
+                    df.stroke(color, w);
+                }
+                return df.result;
+            }"#
+        ),
+    ],
+    ..Shader::DEFAULT
+};
+
+/// Built-in icons, drawn using [`Icon::draw`]. They are designed on a 16x16 grid, so they look sharpest at that size.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Icon {
+    Add,
+    Remove,
+    Close,
+    Check,
+    ChevronLeft,
+    ChevronRight,
+    ChevronDown,
+    /// Three horizontal lines.
+    Menu,
+    /// Three dots.
+    More,
+    Search,
+    Play,
+    Pause,
+    Stop,
+    Undo,
+    Redo,
+    Home,
+}
+
+impl Icon {
+    /// The size that icons are designed at.
+    pub const SIZE: f32 = 16.;
+
+    /// Draw the icon filling `rect`, which should be square.
+    pub fn draw(self, cx: &mut Cx, rect: Rect, color: Vec4, draw_depth: f32) -> Area {
+        // Matches the numbers in `SHADER`.
+        let icon = match self {
+            Icon::Add => 0.,
+            Icon::Remove => 1.,
+            Icon::Close => 2.,
+            Icon::Check => 3.,
+            Icon::ChevronLeft => 4.,
+            Icon::ChevronRight => 5.,
+            Icon::ChevronDown => 6.,
+            Icon::Menu => 7.,
+            Icon::More => 8.,
+            Icon::Search => 9.,
+            Icon::Play => 10.,
+            Icon::Pause => 11.,
+            Icon::Stop => 12.,
+            Icon::Undo => 13.,
+            Icon::Redo => 14.,
+            Icon::Home => 15.,
+        };
+        cx.add_instances(&SHADER, &[IconIns { base: QuadIns::from_rect(rect).with_draw_depth(draw_depth), color, icon }])
+    }
+
+    /// Draw the icon at [`Icon::SIZE`], using the layout system.
+    pub fn draw_walk(self, cx: &mut Cx, color: Vec4) -> Area {
+        let rect = cx.add_box(LayoutSize::new(Width::Fix(Self::SIZE), Height::Fix(Self::SIZE)));
+        self.draw(cx, rect, color, 0.)
+    }
+}
@@ -0,0 +1,255 @@
+//! A legend for the chart components, which also lets the user hide and highlight series.
+
+use crate::buttonlogic::*;
+use crate::*;
+use zaplib::*;
+
+/// The name and color of one series in a chart, as shown in a [`Legend`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct LegendItem {
+    pub label: String,
+    pub color: Vec4,
+}
+
+impl LegendItem {
+    pub fn new(label: &str, color: Vec4) -> Self {
+        Self { label: label.to_string(), color }
+    }
+
+    /// One item per dataset of a [`Chart`], using the line color, or the point color if there is no line.
+    pub fn from_chart_datasets(datasets: &[ChartDataset]) -> Vec<Self> {
+        datasets
+            .iter()
+            .map(|dataset| {
+                Self::new(&dataset.label, if dataset.show_line { dataset.border_color } else { dataset.point_background_color })
+            })
+            .collect()
+    }
+}
+
+/// Which series of a chart are hidden, and which one is highlighted. A [`Legend`] keeps track of this, and charts
+/// draw accordingly after passing it to e.g. [`Chart::set_series_state`] or [`StatPlot::set_series_state`].
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SeriesState {
+    /// Indexed by series; series past the end are visible.
+    hidden: Vec<bool>,
+    highlighted: Option<usize>,
+}
+
+impl SeriesState {
+    pub fn is_visible(&self, index: usize) -> bool {
+        !self.hidden.get(index).copied().unwrap_or(false)
+    }
+
+    pub fn set_visible(&mut self, index: usize, visible: bool) {
+        if self.hidden.len() <= index {
+            self.hidden.resize(index + 1, false);
+        }
+        self.hidden[index] = !visible;
+    }
+
+    /// The series to draw on top of the others, which get dimmed.
+    pub fn highlighted(&self) -> Option<usize> {
+        self.highlighted
+    }
+
+    pub fn set_highlighted(&mut self, highlighted: Option<usize>) {
+        self.highlighted = highlighted;
+    }
+
+    /// Whether a series should be drawn less prominently, because another one is highlighted.
+    pub fn is_dimmed(&self, index: usize) -> bool {
+        self.highlighted.map_or(false, |highlighted| highlighted != index)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum LegendEvent {
+    None,
+    /// The user clicked an item (or focused it and pressed Space or Return), to hide or show its series.
+    VisibilityChanged {
+        index: usize,
+        visible: bool,
+    },
+    /// The pointer moved over or away from the item of a visible series.
+    Highlighted(Option<usize>),
+}
+
+struct LegendEntry {
+    item: LegendItem,
+    component_id: ComponentId,
+    background: Background,
+    swatch: Background,
+}
+
+impl LegendEntry {
+    fn new(item: LegendItem) -> Self {
+        Self {
+            item,
+            component_id: ComponentId::default(),
+            background: Background::default().with_radius(3.),
+            swatch: Background::default().with_radius(2.),
+        }
+    }
+}
+
+/// A row of colored labels for the series of a chart, e.g. created using [`LegendItem::from_chart_datasets`] or
+/// [`StatPlot::legend_items`]. Clicking an item hides or shows its series, and hovering it highlights that series.
+///
+/// Pass [`Legend::series_state`] to the chart whenever this returns something other than [`LegendEvent::None`]:
+///
+/// ```ignore
+/// if self.legend.handle(cx, event) != LegendEvent::None {
+///     self.stat_plot.set_series_state(cx, self.legend.series_state());
+/// }
+/// ```
+pub struct Legend {
+    entries: Vec<LegendEntry>,
+    state: SeriesState,
+    /// The item that the pointer is over.
+    hover: Option<usize>,
+    hover_color: Vec4,
+    text_color: Vec4,
+    hidden_text_color: Vec4,
+}
+
+impl Default for Legend {
+    fn default() -> Self {
+        Self {
+            entries: vec![],
+            state: SeriesState::default(),
+            hover: None,
+            hover_color: vec4(1., 1., 1., 0.1),
+            text_color: vec4(0.9, 0.9, 0.9, 1.),
+            hidden_text_color: vec4(0.45, 0.45, 0.45, 1.),
+        }
+    }
+}
+
+impl Legend {
+    const HEIGHT: f32 = 24.;
+    const PADDING_H: f32 = 6.;
+    const SWATCH_SIZE: f32 = 10.;
+    const SWATCH_SPACING: f32 = 6.;
+
+    #[must_use]
+    pub fn with_items(self, items: Vec<LegendItem>) -> Self {
+        Self { entries: items.into_iter().map(LegendEntry::new).collect(), ..self }
+    }
+    #[must_use]
+    pub fn with_text_color(self, text_color: Vec4) -> Self {
+        Self { text_color, ..self }
+    }
+
+    /// Replace the items, e.g. when the series of the chart change. Series keep their visibility by index.
+    pub fn set_items(&mut self, cx: &mut Cx, items: Vec<LegendItem>) {
+        self.entries = items.into_iter().map(LegendEntry::new).collect();
+        self.hover = None;
+        self.state.highlighted = None;
+        self.state.hidden.truncate(self.entries.len());
+        cx.request_draw();
+    }
+
+    pub fn series_state(&self) -> &SeriesState {
+        &self.state
+    }
+
+    pub fn set_visible(&mut self, cx: &mut Cx, index: usize, visible: bool) {
+        self.state.set_visible(index, visible);
+        self.update_highlighted();
+        cx.request_draw();
+    }
+
+    /// Highlight the series that the pointer is over, unless it's hidden. Returns whether that changed.
+    fn update_highlighted(&mut self) -> bool {
+        let highlighted = self.hover.filter(|index| self.state.is_visible(*index));
+        let changed = highlighted != self.state.highlighted;
+        self.state.highlighted = highlighted;
+        changed
+    }
+
+    pub fn handle(&mut self, cx: &mut Cx, event: &mut Event) -> LegendEvent {
+        for (index, entry) in self.entries.iter().enumerate() {
+            let mut activated = false;
+            if let Event::KeyDown(ke) = event.hits_keyboard(cx, entry.component_id) {
+                if (ke.key_code == KeyCode::Space || ke.key_code == KeyCode::Return) && !ke.is_repeat {
+                    activated = true;
+                }
+            }
+            let hover = &mut self.hover;
+            let hit_event = event.hits_pointer(cx, entry.component_id, entry.background.area().get_rect_for_first_instance(cx));
+            let button_event = handle_button_logic(cx, hit_event, |cx, logic_event| {
+                let new_hover = match logic_event {
+                    ButtonLogicEvent::Default if *hover == Some(index) => None,
+                    ButtonLogicEvent::Default => *hover,
+                    ButtonLogicEvent::Over | ButtonLogicEvent::Down => Some(index),
+                };
+                if new_hover != *hover {
+                    *hover = new_hover;
+                    cx.request_draw();
+                }
+            });
+
+            if activated || button_event == ButtonEvent::Clicked {
+                let visible = !self.state.is_visible(index);
+                self.set_visible(cx, index, visible);
+                return LegendEvent::VisibilityChanged { index, visible };
+            }
+        }
+        if self.update_highlighted() {
+            return LegendEvent::Highlighted(self.state.highlighted);
+        }
+        LegendEvent::None
+    }
+
+    pub fn draw(&mut self, cx: &mut Cx) {
+        cx.begin_row(Width::Fill, Height::Fix(Self::HEIGHT));
+        for (index, entry) in self.entries.iter_mut().enumerate() {
+            let visible = self.state.is_visible(index);
+            let props =
+                TextInsProps { color: if visible { self.text_color } else { self.hidden_text_color }, ..TextInsProps::DEFAULT };
+            let text_size = cx.measure_wrapped_text(&props, &entry.item.label, f32::INFINITY);
+            let width = Self::PADDING_H * 2. + Self::SWATCH_SIZE + Self::SWATCH_SPACING + text_size.width;
+            let rect = cx.add_box(LayoutSize::new(Width::Fix(width), Height::Fix(Self::HEIGHT)));
+
+            let color = if self.hover == Some(index) { self.hover_color } else { Vec4::default() };
+            entry.background.draw(cx, rect, color);
+            // Hidden series keep a faint swatch, so it's still clear which item is which.
+            let swatch_color = if visible { entry.item.color } else { Vec4::mix(entry.item.color, Vec4::default(), 0.75) };
+            let swatch_pos = rect.pos + vec2(Self::PADDING_H, (Self::HEIGHT - Self::SWATCH_SIZE) / 2.);
+            entry.swatch.draw(cx, Rect { pos: swatch_pos, size: Vec2::all(Self::SWATCH_SIZE) }, swatch_color);
+            let text_pos = vec2(swatch_pos.x + Self::SWATCH_SIZE + Self::SWATCH_SPACING, rect.pos.y);
+            TextIns::draw_str(cx, &entry.item.label, text_pos + vec2(0., (Self::HEIGHT - text_size.line_height) / 2.), &props);
+            cx.add_focusable(entry.component_id, entry.background.area(), Focusable::default());
+        }
+        cx.end_row();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    use zaplib::*;
+
+    #[test]
+    fn test_legend_toggles_and_highlights() {
+        let items = vec![LegendItem::new("Sales", vec4(1., 0., 0., 1.)), LegendItem::new("Costs", vec4(0., 0., 1., 1.))];
+        let mut legend = Legend::default().with_items(items);
+        let mut test_cx = TestCx::new(vec2(400., 100.));
+        test_cx.draw(|cx| legend.draw(cx));
+
+        let events = test_cx.pointer_hover(vec2(10., 12.), |cx, event| legend.handle(cx, event));
+        assert!(events.contains(&LegendEvent::Highlighted(Some(0))));
+        assert!(legend.series_state().is_dimmed(1) && !legend.series_state().is_dimmed(0));
+
+        // Hiding the hovered series also stops highlighting it.
+        let events = test_cx.click(vec2(10., 12.), |cx, event| legend.handle(cx, event));
+        assert!(events.contains(&LegendEvent::VisibilityChanged { index: 0, visible: false }));
+        assert!(!legend.series_state().is_visible(0) && legend.series_state().is_visible(1));
+        assert_eq!(legend.series_state().highlighted(), None);
+
+        let events = test_cx.click(vec2(10., 12.), |cx, event| legend.handle(cx, event));
+        assert!(events.contains(&LegendEvent::VisibilityChanged { index: 0, visible: true }));
+        assert_eq!(legend.series_state().highlighted(), Some(0));
+    }
+}
@@ -9,6 +9,8 @@
 
 mod background;
 pub use crate::background::*;
+mod icon;
+pub use crate::icon::*;
 mod axis;
 pub use crate::axis::*;
 mod scrollview;
@@ -21,6 +23,31 @@ mod tab;
 pub use crate::tab::*;
 mod tabcontrol;
 pub use crate::tabcontrol::*;
+mod tab_bar;
+pub use crate::tab_bar::*;
+mod toolbar;
+pub use crate::toolbar::*;
+mod breadcrumb;
+pub use crate::breadcrumb::*;
+mod minimap;
+pub use crate::minimap::*;
+mod node_graph;
+pub use crate::node_graph::*;
+mod timeline;
+pub use crate::timeline::*;
+mod tile_map;
+pub use crate::tile_map::*;
+mod big_scatter;
+pub use crate::big_scatter::*;
+mod plot_axes;
+mod stat_plot;
+pub use crate::stat_plot::*;
+mod legend;
+pub use crate::legend::*;
+mod annotations;
+pub use crate::annotations::*;
+mod zoom_pan_view;
+pub use crate::zoom_pan_view::*;
 mod dock;
 pub use crate::dock::*;
 mod desktopwindow;
@@ -49,6 +76,24 @@ mod popover;
 pub use crate::popover::*;
 mod checkbox;
 pub use crate::checkbox::*;
+mod toggle;
+pub use crate::toggle::*;
+mod dropdown;
+pub use crate::dropdown::*;
+mod form;
+pub use crate::form::*;
+mod slider;
+pub use crate::slider::*;
+mod focusring;
+pub use crate::focusring::*;
+mod tooltip;
+pub use crate::tooltip::*;
+mod notifications;
+pub use crate::notifications::*;
+mod progress;
+pub use crate::progress::*;
+mod task_tracker;
+pub use crate::task_tracker::*;
 mod viewport3d;
 pub use crate::viewport3d::*;
 mod fps_counter;
@@ -62,8 +107,24 @@ mod drawlines3d;
 pub use crate::drawlines3d::*;
 mod drawpoints3d;
 pub use crate::drawpoints3d::*;
+mod depth_sort;
+pub use crate::depth_sort::*;
+mod shadow_map;
+pub use crate::shadow_map::*;
+#[cfg(feature = "serde-support")]
+mod gltf;
+mod skinning;
+pub use crate::skinning::*;
+mod point_cloud;
+pub use crate::point_cloud::*;
+mod volume;
+pub use crate::volume::*;
+mod mesh_debug;
+pub use crate::mesh_debug::*;
 mod arrow_pointer;
 pub use crate::arrow_pointer::*;
+mod transition;
+pub use crate::transition::*;
 
 mod internal;
 pub(crate) use crate::internal::*;
@@ -0,0 +1,302 @@
+//! Debug visualizations for 3D meshes, e.g. to check imported models without writing custom shaders.
+
+use zaplib::*;
+
+/// What [`MeshDebugView`] shows on the surface of a mesh.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MeshDebugMode {
+    /// Plain gray, lit from above.
+    Shaded,
+    /// Normals in world space as colors, with X, Y, and Z mapped to red, green, and blue. Surfaces facing
+    /// e.g. +Y are light green, and surfaces facing -Y dark purple.
+    Normals,
+    /// Texture coordinates, with U and V mapped to red and green, and a checkerboard to show stretching and seams.
+    Uvs,
+}
+
+impl MeshDebugMode {
+    pub const ALL: [MeshDebugMode; 3] = [MeshDebugMode::Shaded, MeshDebugMode::Normals, MeshDebugMode::Uvs];
+
+    /// The next mode in [`MeshDebugMode::ALL`], wrapping around; e.g. for cycling through modes with a key.
+    #[must_use]
+    pub fn next(self) -> Self {
+        let index = Self::ALL.iter().position(|&mode| mode == self).unwrap();
+        Self::ALL[(index + 1) % Self::ALL.len()]
+    }
+}
+
+/// Where the attributes that [`MeshDebugView`] needs are in the vertices of a [`Geometry`], as offsets in floats
+/// from the start of every vertex.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MeshAttributes {
+    pub position: usize,
+    /// When missing, every triangle gets its own flat normal.
+    pub normal: Option<usize>,
+    /// When missing, [`MeshDebugMode::Uvs`] shows all texture coordinates as 0.
+    pub uv: Option<usize>,
+}
+
+impl MeshAttributes {
+    /// The attributes of geometry made by e.g. [`crate::Geometry3d::cube`].
+    pub const GEOMETRY_3D: Self = Self { position: 0, normal: Some(4), uv: Some(7) };
+    /// The attributes of [`crate::SkinnedVertex`]. Skinned meshes are shown in the pose they were modeled in.
+    pub const SKINNED_VERTEX: Self = Self { position: 0, normal: Some(3), uv: None };
+}
+
+/// A vertex of the debug geometry. Triangles don't share vertices, so every vertex can have its own barycentric
+/// coordinates for drawing the wireframe.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[repr(C)]
+struct DebugVertex {
+    position: Vec3,
+    normal: Vec3,
+    uv: Vec2,
+    barycentric: Vec3,
+}
+
+/// Copy every triangle of `geometry` into separate vertices, with barycentric coordinates.
+fn build_debug_vertices(geometry: &Geometry, attributes: MeshAttributes) -> Vec<DebugVertex> {
+    let floats = geometry.vertices_f32_slice();
+    let stride = geometry.floats_per_vertex();
+    let vec3_at = |vertex: usize, offset: usize| {
+        let start = vertex * stride + offset;
+        vec3(floats[start], floats[start + 1], floats[start + 2])
+    };
+    let mut vertices = Vec::with_capacity(geometry.indices_u32_slice().len());
+    for triangle in geometry.indices_u32_slice().chunks_exact(3) {
+        let positions = [0, 1, 2].map(|corner| vec3_at(triangle[corner] as usize, attributes.position));
+        let flat_normal = Vec3::cross(positions[1] - positions[0], positions[2] - positions[0]).normalize();
+        for (corner, &index) in triangle.iter().enumerate() {
+            let index = index as usize;
+            let uv = attributes.uv.map_or(Vec2::default(), |offset| {
+                let start = index * stride + offset;
+                vec2(floats[start], floats[start + 1])
+            });
+            let mut barycentric = Vec3::default();
+            match corner {
+                0 => barycentric.x = 1.,
+                1 => barycentric.y = 1.,
+                _ => barycentric.z = 1.,
+            }
+            vertices.push(DebugVertex {
+                position: positions[corner],
+                normal: attributes.normal.map_or(flat_normal, |offset| vec3_at(index, offset)),
+                uv,
+                barycentric,
+            });
+        }
+    }
+    vertices
+}
+
+#[derive(Clone, Copy)]
+#[repr(C)]
+struct MeshDebugIns {
+    transform: Mat4,
+    wireframe_color: Vec4,
+    /// The index of the [`MeshDebugMode`].
+    mode: f32,
+    /// In pixels, or 0 for no wireframe.
+    wireframe_width: f32,
+}
+
+static SHADER: Shader = Shader {
+    build_geom: None,
+    code_to_concatenate: &[
+        Cx::STD_SHADER,
+        code_fragment!(
+            r#"
+            geometry geom_pos: vec3;
+            geometry geom_normal: vec3;
+            geometry geom_uv: vec2;
+            geometry geom_barycentric: vec3;
+
+            instance transform: mat4;
+            instance wireframe_color: vec4;
+            instance mode: float;
+            instance wireframe_width: float;
+
+            varying world_normal: vec3;
+            varying uv: vec2;
+            varying barycentric: vec3;
+
+            fn vertex() -> vec4 {
+                world_normal = (transform * vec4(geom_normal, 0.)).xyz;
+                uv = geom_uv;
+                barycentric = geom_barycentric;
+                return camera_projection * camera_view * transform * vec4(geom_pos, 1.);
+            }
+
+            fn pixel() -> vec4 {
+                let normal = normalize(world_normal);
+                let color = vec3(0.);
+                if mode < 0.5 {
+                    // Light from above and in front, plus some ambient light so the other sides aren't black.
+                    let diffuse = max(dot(normal, normalize(vec3(0.3, 1., 0.5))), 0.);
+                    color = vec3(0.75) * (0.3 + 0.7 * diffuse);
+                } else if mode < 1.5 {
+                    color = normal * 0.5 + 0.5;
+                } else {
+                    let checker = mod(floor(uv.x * 8.) + floor(uv.y * 8.), 2.);
+                    color = vec3(fract(uv), 0.) * (0.75 + 0.25 * checker);
+                }
+                if wireframe_width > 0. {
+                    // The distance to the closest edge in pixels, using how fast the barycentric coordinates change
+                    // across pixels, so lines have the same width regardless of distance and angle.
+                    let pixels = barycentric / (abs(dFdx(barycentric)) + abs(dFdy(barycentric)));
+                    let distance = min(min(pixels.x, pixels.y), pixels.z);
+                    let line = 1. - clamp(distance - wireframe_width * 0.5 + 0.5, 0., 1.);
+                    color = mix(color, wireframe_color.rgb, line * wireframe_color.a);
+                }
+                return vec4(color, 1.);
+            }"#
+        ),
+    ],
+    ..Shader::DEFAULT
+};
+
+/// Draws any 3D [`Geometry`] with a debug visualization, like normals or texture coordinates, optionally with its
+/// triangles outlined, to debug imported meshes. The mode and wireframe can be changed at any time:
+///
+/// ```ignore
+/// // When loading the mesh:
+/// self.debug_view = MeshDebugView::new(&Geometry3d::sphere(16, 16, 1.), MeshAttributes::GEOMETRY_3D);
+///
+/// // In `handle`:
+/// if let Event::KeyDown(KeyEvent { key_code: KeyCode::KeyM, .. }) = event {
+///     self.debug_view.set_mode(cx, self.debug_view.mode().next());
+/// }
+/// if let Event::KeyDown(KeyEvent { key_code: KeyCode::KeyW, .. }) = event {
+///     self.debug_view.set_wireframe(cx, !self.debug_view.wireframe());
+/// }
+///
+/// // In `draw`, between `Viewport3D::begin_draw` and `Viewport3D::end_draw`:
+/// self.debug_view.draw(cx, Mat4::identity());
+/// ```
+///
+/// Every triangle gets its own vertices for the wireframe, so this uses up to 3 times as much GPU memory as the
+/// original geometry.
+pub struct MeshDebugView {
+    vertices: Vec<DebugVertex>,
+    geometry: Option<GpuGeometry>,
+    mode: MeshDebugMode,
+    wireframe: bool,
+    wireframe_color: Vec4,
+    wireframe_width: f32,
+}
+
+impl MeshDebugView {
+    /// Panics if `attributes` point outside of the vertices of `geometry`.
+    pub fn new(geometry: &Geometry, attributes: MeshAttributes) -> Self {
+        let stride = geometry.floats_per_vertex();
+        assert!(
+            attributes.position + 3 <= stride
+                && attributes.normal.map_or(true, |normal| normal + 3 <= stride)
+                && attributes.uv.map_or(true, |uv| uv + 2 <= stride),
+            "Mesh attributes don't fit in vertices of {} floats",
+            stride
+        );
+        Self {
+            vertices: build_debug_vertices(geometry, attributes),
+            geometry: None,
+            mode: MeshDebugMode::Shaded,
+            wireframe: true,
+            wireframe_color: vec4(0., 0., 0., 1.),
+            wireframe_width: 1.,
+        }
+    }
+
+    #[must_use]
+    pub fn with_mode(self, mode: MeshDebugMode) -> Self {
+        Self { mode, ..self }
+    }
+    /// Whether to outline the triangles; on by default.
+    #[must_use]
+    pub fn with_wireframe(self, wireframe: bool) -> Self {
+        Self { wireframe, ..self }
+    }
+    /// Black by default.
+    #[must_use]
+    pub fn with_wireframe_color(self, wireframe_color: Vec4) -> Self {
+        Self { wireframe_color, ..self }
+    }
+    /// The width of wireframe lines in pixels; 1 by default.
+    #[must_use]
+    pub fn with_wireframe_width(self, wireframe_width: f32) -> Self {
+        Self { wireframe_width, ..self }
+    }
+
+    pub fn mode(&self) -> MeshDebugMode {
+        self.mode
+    }
+
+    pub fn set_mode(&mut self, cx: &mut Cx, mode: MeshDebugMode) {
+        self.mode = mode;
+        cx.request_draw();
+    }
+
+    pub fn wireframe(&self) -> bool {
+        self.wireframe
+    }
+
+    pub fn set_wireframe(&mut self, cx: &mut Cx, wireframe: bool) {
+        self.wireframe = wireframe;
+        cx.request_draw();
+    }
+
+    /// Draw the mesh, transformed by `transform`. Call this between [`crate::Viewport3D::begin_draw`] and
+    /// [`crate::Viewport3D::end_draw`].
+    pub fn draw(&mut self, cx: &mut Cx, transform: Mat4) -> Area {
+        let vertices = &self.vertices;
+        let geometry = self
+            .geometry
+            .get_or_insert_with(|| {
+                let indices = (0..vertices.len() as u32 / 3).map(|triangle| [0, 1, 2].map(|corner| triangle * 3 + corner));
+                GpuGeometry::new(cx, Geometry::new(vertices.clone(), indices.collect()))
+            })
+            .clone();
+        let mode = MeshDebugMode::ALL.iter().position(|&mode| mode == self.mode).unwrap() as f32;
+        let wireframe_width = if self.wireframe { self.wireframe_width } else { 0. };
+        cx.add_mesh_instances(
+            &SHADER,
+            &[MeshDebugIns { transform, wireframe_color: self.wireframe_color, mode, wireframe_width }],
+            geometry,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_debug_vertices() {
+        #[derive(Clone, Copy)]
+        #[repr(C)]
+        struct Vertex {
+            uv: Vec2,
+            position: Vec3,
+        }
+        let geometry = Geometry::new(
+            vec![
+                Vertex { uv: vec2(0., 0.), position: vec3(0., 0., 0.) },
+                Vertex { uv: vec2(1., 0.), position: vec3(1., 0., 0.) },
+                Vertex { uv: vec2(0., 1.), position: vec3(0., 1., 0.) },
+                Vertex { uv: vec2(1., 1.), position: vec3(1., 1., 0.) },
+            ],
+            vec![[0, 1, 2], [2, 1, 3]],
+        );
+        let vertices = build_debug_vertices(&geometry, MeshAttributes { position: 2, normal: None, uv: Some(0) });
+        assert_eq!(vertices.len(), 6);
+        // Shared vertices get copied into every triangle, with their own barycentric coordinates.
+        assert_eq!(vertices[1].position, vec3(1., 0., 0.));
+        assert_eq!(vertices[4].position, vec3(1., 0., 0.));
+        assert_eq!(vertices[1].barycentric, vec3(0., 1., 0.));
+        assert_eq!(vertices[4].barycentric, vec3(0., 1., 0.));
+        assert_eq!(vertices[5].uv, vec2(1., 1.));
+        // Without normals, triangles get flat normals, following counter-clockwise winding.
+        assert!(vertices.iter().all(|vertex| vertex.normal == vec3(0., 0., 1.)));
+
+        assert_eq!(MeshDebugMode::Uvs.next(), MeshDebugMode::Shaded);
+    }
+}
@@ -0,0 +1,211 @@
+//! A scaled-down overview of a large canvas, for navigating it.
+
+use crate::background::*;
+use zaplib::*;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum MinimapEvent {
+    None,
+    /// The user clicked or dragged in the minimap, and the visible part of the content should move so that its
+    /// top-left corner is at this position (in content coordinates), e.g. using [`crate::ScrollView::set_scroll_pos`].
+    ScrollTo(Vec2),
+}
+
+/// Shows a scaled-down copy of some large content (like a node graph, timeline, or code file), with a rectangle
+/// for the part that is currently visible. Clicking moves that rectangle to the pointer, and dragging it scrolls.
+///
+/// The content is rendered into an offscreen [`Pass`] at a low resolution, so it doesn't have to be drawn in a
+/// special way. Call [`Minimap::draw`] where the minimap should go, and then draw the content again between
+/// [`Minimap::begin_content`] and [`Minimap::end_content`]:
+///
+/// ```ignore
+/// self.minimap.set_viewport(cx, visible_rect, content_size);
+/// self.minimap.draw(cx);
+/// if content_changed {
+///     self.minimap.begin_content(cx);
+///     self.draw_content(cx);
+///     self.minimap.end_content(cx);
+/// }
+/// ```
+///
+/// Like with [`crate::Viewport3D`], the offscreen [`Pass`] keeps its last contents when it isn't drawn, so the
+/// content only has to be drawn again when it changes.
+pub struct Minimap {
+    component_id: ComponentId,
+    pass: Pass,
+    color_texture: Texture,
+    content_view: View,
+    frame: Background,
+    viewport: Background,
+    size: Vec2,
+    content_size: Vec2,
+    /// The visible part of the content, in content coordinates.
+    visible_rect: Rect,
+    /// Where the scaled-down content was drawn during the last draw.
+    image_rect: Rect,
+    /// Set while dragging, to the pointer position relative to the top-left of the viewport rectangle.
+    drag_offset: Option<Vec2>,
+    background_color: Vec4,
+    viewport_color: Vec4,
+}
+
+impl Default for Minimap {
+    fn default() -> Self {
+        Self {
+            component_id: Default::default(),
+            pass: Default::default(),
+            color_texture: Default::default(),
+            content_view: Default::default(),
+            frame: Background::default(),
+            viewport: Background::default().with_radius(2.).with_draw_depth(1.),
+            size: vec2(200., 150.),
+            content_size: Vec2::default(),
+            visible_rect: Rect::default(),
+            image_rect: Rect::default(),
+            drag_offset: None,
+            background_color: vec4(0.1, 0.1, 0.1, 1.),
+            viewport_color: vec4(1., 1., 1., 0.15),
+        }
+    }
+}
+
+impl Minimap {
+    #[must_use]
+    pub fn with_size(self, size: Vec2) -> Self {
+        Self { size, ..self }
+    }
+    #[must_use]
+    pub fn with_background_color(self, background_color: Vec4) -> Self {
+        Self { background_color, ..self }
+    }
+    #[must_use]
+    pub fn with_viewport_color(self, viewport_color: Vec4) -> Self {
+        Self { viewport_color, ..self }
+    }
+
+    /// Set the part of the content that is visible (in content coordinates), and the total size of the content.
+    /// Call this whenever either changes, e.g. when the target view scrolls or resizes.
+    pub fn set_viewport(&mut self, cx: &mut Cx, visible_rect: Rect, content_size: Vec2) {
+        if self.visible_rect != visible_rect || self.content_size != content_size {
+            self.visible_rect = visible_rect;
+            self.content_size = content_size;
+            cx.request_draw();
+        }
+    }
+
+    /// How much the content is scaled down to fit in the minimap.
+    fn scale(&self) -> f32 {
+        if self.content_size.x <= 0. || self.content_size.y <= 0. {
+            return 1.;
+        }
+        (self.size.x / self.content_size.x).min(self.size.y / self.content_size.y)
+    }
+
+    fn viewport_rect(&self) -> Rect {
+        let scale = self.scale();
+        Rect { pos: self.image_rect.pos + self.visible_rect.pos * scale, size: self.visible_rect.size * scale }
+    }
+
+    /// Move the viewport rectangle so its top-left is at `abs` (in screen coordinates), and return where that is in
+    /// content coordinates.
+    fn scroll_to_abs(&mut self, cx: &mut Cx, abs: Vec2) -> MinimapEvent {
+        let max = (self.content_size - self.visible_rect.size).max(&Vec2::default());
+        let pos = ((abs - self.image_rect.pos) / self.scale()).clamp(&Vec2::default(), &max);
+        if pos == self.visible_rect.pos {
+            return MinimapEvent::None;
+        }
+        self.visible_rect.pos = pos;
+        cx.request_draw();
+        MinimapEvent::ScrollTo(pos)
+    }
+
+    pub fn handle(&mut self, cx: &mut Cx, event: &mut Event) -> MinimapEvent {
+        match event.hits_pointer(cx, self.component_id, self.frame.area().get_rect_for_first_instance(cx)) {
+            Event::PointerHover(pe) => {
+                let cursor = if self.viewport_rect().contains(pe.abs) { MouseCursor::Move } else { MouseCursor::Hand };
+                cx.set_hover_mouse_cursor(cursor);
+            }
+            Event::PointerDown(pe) => {
+                cx.set_down_mouse_cursor(MouseCursor::Move);
+                let viewport_rect = self.viewport_rect();
+                if viewport_rect.contains(pe.abs) {
+                    self.drag_offset = Some(pe.abs - viewport_rect.pos);
+                } else {
+                    // Center the viewport rectangle on the pointer, and keep dragging from there.
+                    let drag_offset = viewport_rect.size / 2.;
+                    self.drag_offset = Some(drag_offset);
+                    return self.scroll_to_abs(cx, pe.abs - drag_offset);
+                }
+            }
+            Event::PointerMove(pe) => {
+                if let Some(drag_offset) = self.drag_offset {
+                    return self.scroll_to_abs(cx, pe.abs - drag_offset);
+                }
+            }
+            Event::PointerUp(_) => {
+                self.drag_offset = None;
+            }
+            _ => {}
+        }
+        MinimapEvent::None
+    }
+
+    pub fn draw(&mut self, cx: &mut Cx) {
+        let rect = cx.add_box(LayoutSize::new(Width::Fix(self.size.x), Height::Fix(self.size.y)));
+        self.frame.draw(cx, rect, self.background_color);
+
+        // Keep the aspect ratio of the content, and center it.
+        let image_size = self.content_size * self.scale();
+        self.image_rect = Rect { pos: rect.pos + (rect.size - image_size) / 2., size: image_size };
+        let color_texture_handle = self.color_texture.get_color(cx);
+        ImageIns::draw(cx, self.image_rect, color_texture_handle);
+
+        let viewport_rect = self.viewport_rect();
+        self.viewport.draw(cx, viewport_rect, self.viewport_color);
+    }
+
+    /// Start drawing the content into the offscreen [`Pass`]. The content should be drawn at its full size, starting
+    /// at the origin; it gets rendered at a lower resolution to fit in the minimap.
+    pub fn begin_content(&mut self, cx: &mut Cx) {
+        self.pass.begin_pass_without_textures(cx);
+        // The pass covers the whole content, but by lowering its DPI factor the texture only gets as many pixels as
+        // the minimap shows.
+        let dpi_factor = cx.current_dpi_factor * self.scale();
+        self.pass.override_dpi_factor(cx, dpi_factor);
+        self.pass.set_size(cx, self.content_size);
+        let color_texture_handle = self.color_texture.get_color(cx);
+        self.pass.add_color_texture(cx, color_texture_handle, ClearColor::ClearWith(self.background_color));
+        self.content_view.begin_view(cx, LayoutSize::FILL);
+    }
+
+    pub fn end_content(&mut self, cx: &mut Cx) {
+        self.content_view.end_view(cx);
+        self.pass.end_pass(cx);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    use zaplib::*;
+
+    #[test]
+    fn test_minimap_click_and_drag() {
+        let mut minimap = Minimap::default().with_size(vec2(200., 100.));
+        let mut test_cx = TestCx::new(vec2(400., 400.));
+        test_cx.draw(|cx| {
+            minimap.set_viewport(cx, Rect { pos: Vec2::default(), size: vec2(400., 200.) }, vec2(2000., 1000.));
+            minimap.draw(cx);
+        });
+
+        // Clicking centers the viewport on the pointer, at a scale of 0.1.
+        let events = test_cx.click(vec2(100., 50.), |cx, event| minimap.handle(cx, event));
+        assert!(events.contains(&MinimapEvent::ScrollTo(vec2(800., 400.))));
+
+        // Dragging the viewport rectangle moves it along, but not past the end of the content.
+        test_cx.draw(|cx| minimap.draw(cx));
+        test_cx.pointer_down(vec2(100., 50.), |cx, event| minimap.handle(cx, event));
+        let events = test_cx.pointer_move(vec2(300., 50.), |cx, event| minimap.handle(cx, event));
+        assert_eq!(events, vec![MinimapEvent::ScrollTo(vec2(1600., 400.))]);
+    }
+}
@@ -0,0 +1,987 @@
+//! An editor for graphs of nodes with typed ports, e.g. for dataflow tools.
+
+use crate::background::*;
+use zaplib::*;
+
+const NODE_WIDTH: f32 = 160.;
+const HEADER_HEIGHT: f32 = 24.;
+const PORT_ROW_HEIGHT: f32 = 20.;
+const NODE_PADDING: f32 = 6.;
+const PORT_RADIUS: f32 = 5.;
+/// How close (in graph coordinates) the pointer has to be to a port to grab it.
+const PORT_HIT_RADIUS: f32 = 9.;
+/// How close (in screen coordinates) the pointer has to be to an edge to select it.
+const EDGE_HIT_DISTANCE: f32 = 5.;
+/// Number of line segments that edges get split into, both for drawing and hit testing.
+const EDGE_SEGMENTS: usize = 24;
+const MIN_ZOOM: f32 = 0.2;
+const MAX_ZOOM: f32 = 3.;
+
+/// Identifies a [`GraphNode`]; stays the same when other nodes are added or removed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde-support", derive(serde::Serialize, serde::Deserialize))]
+pub struct NodeId(pub u64);
+
+/// An input or output of a [`GraphNode`].
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde-support", derive(serde::Serialize, serde::Deserialize))]
+pub struct NodePort {
+    pub name: String,
+    /// Only ports with the same type can be connected, e.g. `"float"` or `"image"`. Also determines the color of the
+    /// port and its edges; see [`NodeGraph::with_port_type_color`].
+    pub port_type: String,
+}
+
+impl NodePort {
+    pub fn new(name: &str, port_type: &str) -> Self {
+        Self { name: name.to_string(), port_type: port_type.to_string() }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde-support", derive(serde::Serialize, serde::Deserialize))]
+pub struct GraphNode {
+    pub id: NodeId,
+    pub title: String,
+    /// Top-left corner, in graph coordinates.
+    pub pos: Vec2,
+    pub inputs: Vec<NodePort>,
+    pub outputs: Vec<NodePort>,
+}
+
+impl GraphNode {
+    /// The area that the node covers, in graph coordinates.
+    pub fn rect(&self) -> Rect {
+        let rows = self.inputs.len().max(self.outputs.len()) as f32;
+        Rect { pos: self.pos, size: vec2(NODE_WIDTH, HEADER_HEIGHT + rows * PORT_ROW_HEIGHT + NODE_PADDING) }
+    }
+
+    fn port_pos(&self, side: PortSide, index: usize) -> Vec2 {
+        let x = match side {
+            PortSide::Input => 0.,
+            PortSide::Output => NODE_WIDTH,
+        };
+        self.pos + vec2(x, HEADER_HEIGHT + PORT_ROW_HEIGHT * (index as f32 + 0.5))
+    }
+
+    fn port(&self, side: PortSide, index: usize) -> Option<&NodePort> {
+        match side {
+            PortSide::Input => self.inputs.get(index),
+            PortSide::Output => self.outputs.get(index),
+        }
+    }
+}
+
+/// Points to the port at index `port` in either [`GraphNode::inputs`] or [`GraphNode::outputs`] of `node`,
+/// depending on where it's used.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde-support", derive(serde::Serialize, serde::Deserialize))]
+pub struct PortRef {
+    pub node: NodeId,
+    pub port: usize,
+}
+
+/// A connection from an output port to an input port.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde-support", derive(serde::Serialize, serde::Deserialize))]
+pub struct GraphEdge {
+    /// Points into [`GraphNode::outputs`].
+    pub from: PortRef,
+    /// Points into [`GraphNode::inputs`].
+    pub to: PortRef,
+}
+
+/// The nodes and edges shown by a [`NodeGraph`]. With the `serde-support` feature this can be serialized, e.g. to
+/// save it to a file using `zaplib::state_snapshot`.
+///
+/// Every input has at most one edge going into it, and edges can't form cycles.
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde-support", derive(serde::Serialize, serde::Deserialize))]
+pub struct NodeGraphData {
+    /// In drawing order, so later nodes are on top.
+    pub nodes: Vec<GraphNode>,
+    pub edges: Vec<GraphEdge>,
+}
+
+impl NodeGraphData {
+    pub fn add_node(&mut self, title: &str, pos: Vec2, inputs: Vec<NodePort>, outputs: Vec<NodePort>) -> NodeId {
+        let id = NodeId(self.nodes.iter().map(|node| node.id.0 + 1).max().unwrap_or(0));
+        self.nodes.push(GraphNode { id, title: title.to_string(), pos, inputs, outputs });
+        id
+    }
+
+    /// Remove a node, returning the edges that were connected to it (which are removed as well).
+    pub fn remove_node(&mut self, id: NodeId) -> Vec<GraphEdge> {
+        self.nodes.retain(|node| node.id != id);
+        let (removed, edges) =
+            std::mem::take(&mut self.edges).into_iter().partition(|edge| edge.from.node == id || edge.to.node == id);
+        self.edges = edges;
+        removed
+    }
+
+    pub fn node(&self, id: NodeId) -> Option<&GraphNode> {
+        self.nodes.iter().find(|node| node.id == id)
+    }
+
+    pub fn node_mut(&mut self, id: NodeId) -> Option<&mut GraphNode> {
+        self.nodes.iter_mut().find(|node| node.id == id)
+    }
+
+    /// Whether `from` (an output) and `to` (an input) exist, have the same [`NodePort::port_type`], and connecting them
+    /// wouldn't create a cycle.
+    pub fn can_connect(&self, from: PortRef, to: PortRef) -> bool {
+        let from_port = self.node(from.node).and_then(|node| node.outputs.get(from.port));
+        let to_port = self.node(to.node).and_then(|node| node.inputs.get(to.port));
+        match (from_port, to_port) {
+            (Some(from_port), Some(to_port)) => {
+                from_port.port_type == to_port.port_type && !self.is_downstream(to.node, from.node)
+            }
+            _ => false,
+        }
+    }
+
+    /// Whether `target` can be reached from `start` by following edges (or is `start` itself).
+    fn is_downstream(&self, start: NodeId, target: NodeId) -> bool {
+        let mut stack = vec![start];
+        let mut visited = vec![];
+        while let Some(id) = stack.pop() {
+            if id == target {
+                return true;
+            }
+            if !visited.contains(&id) {
+                visited.push(id);
+                stack.extend(self.edges.iter().filter(|edge| edge.from.node == id).map(|edge| edge.to.node));
+            }
+        }
+        false
+    }
+
+    /// Connect `from` (an output) to `to` (an input), replacing any edge that went into `to` before. Returns `false`
+    /// if the ports can't be connected; see [`NodeGraphData::can_connect`].
+    pub fn connect(&mut self, from: PortRef, to: PortRef) -> bool {
+        if !self.can_connect(from, to) {
+            return false;
+        }
+        self.edges.retain(|edge| edge.to != to);
+        self.edges.push(GraphEdge { from, to });
+        true
+    }
+
+    /// Remove the edge going into `to`, if any.
+    pub fn disconnect(&mut self, to: PortRef) -> Option<GraphEdge> {
+        let index = self.edges.iter().position(|edge| edge.to == to)?;
+        Some(self.edges.remove(index))
+    }
+
+    /// The area covering all nodes, in graph coordinates; e.g. to use as the content size of a [`crate::Minimap`].
+    pub fn bounds(&self) -> Rect {
+        let mut rects = self.nodes.iter().map(|node| node.rect());
+        let first = match rects.next() {
+            Some(first) => first,
+            None => return Rect::default(),
+        };
+        let (min, max) = rects
+            .fold((first.pos, first.pos + first.size), |(min, max), rect| (min.min(&rect.pos), max.max(&(rect.pos + rect.size))));
+        Rect { pos: min, size: max - min }
+    }
+
+    fn edge_endpoints(&self, edge: &GraphEdge) -> Option<(Vec2, Vec2)> {
+        let from = self.node(edge.from.node)?.port_pos(PortSide::Output, edge.from.port);
+        let to = self.node(edge.to.node)?.port_pos(PortSide::Input, edge.to.port);
+        Some((from, to))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum NodeGraphEvent {
+    None,
+    /// Nodes or edges were selected or deselected; see [`NodeGraph::selection`].
+    SelectionChanged,
+    /// The user dragged these nodes around; their [`GraphNode::pos`] is already updated.
+    NodesMoved(Vec<NodeId>),
+    /// The user connected two ports. If the input was connected to something else before, that edge was replaced.
+    Connected(GraphEdge),
+    /// The user pulled an edge off of its input.
+    Disconnected(GraphEdge),
+    /// The user pressed Delete or Backspace, which removed the selected nodes and edges, as well as any edges that
+    /// were connected to the removed nodes.
+    Deleted {
+        nodes: Vec<NodeId>,
+        edges: Vec<GraphEdge>,
+    },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum PortSide {
+    Input,
+    Output,
+}
+
+enum NodeGraphDrag {
+    None,
+    Panning {
+        start_pan: Vec2,
+    },
+    MovingNodes {
+        start_positions: Vec<(NodeId, Vec2)>,
+        moved: bool,
+    },
+    /// Dragging a new edge out of `port`. `pointer` is in graph coordinates.
+    Connecting {
+        port: PortRef,
+        side: PortSide,
+        pointer: Vec2,
+    },
+    /// `start` and `end` are in graph coordinates; `initial` is the selection from before, when holding Shift.
+    BoxSelecting {
+        start: Vec2,
+        end: Vec2,
+        initial: Vec<NodeId>,
+    },
+}
+
+#[derive(Clone, Copy, Default)]
+#[repr(C)]
+struct GridIns {
+    base: QuadIns,
+    color: Vec4,
+    grid_color: Vec4,
+    grid_size: f32,
+    grid_offset: Vec2,
+}
+
+static GRID_SHADER: Shader = Shader {
+    build_geom: Some(QuadIns::build_geom),
+    code_to_concatenate: &[
+        Cx::STD_SHADER,
+        QuadIns::SHADER,
+        code_fragment!(
+            r#"
+            instance color: vec4;
+            instance grid_color: vec4;
+            instance grid_size: float;
+            instance grid_offset: vec2;
+
+            fn pixel() -> vec4 {
+                let p = pos * rect_size - grid_offset;
+                let x = mod(p.x, grid_size);
+                let y = mod(p.y, grid_size);
+                let d = min(min(x, grid_size - x), min(y, grid_size - y));
+                let c = mix(grid_color, color, clamp(d, 0., 1.));
+                return vec4(c.rgb * c.a, c.a);
+            }"#
+        ),
+    ],
+    ..Shader::DEFAULT
+};
+
+#[derive(Clone, Copy, Default)]
+#[repr(C)]
+struct EdgeIns {
+    base: QuadIns,
+    color: Vec4,
+    start: Vec2,
+    end: Vec2,
+    width: f32,
+}
+
+static EDGE_SHADER: Shader = Shader {
+    build_geom: Some(QuadIns::build_geom),
+    code_to_concatenate: &[
+        Cx::STD_SHADER,
+        QuadIns::SHADER,
+        code_fragment!(
+            r#"
+            instance color: vec4;
+            instance start: vec2;
+            instance end: vec2;
+            instance width: float;
+
+            fn pixel() -> vec4 {
+                // Each instance is one straight piece of an edge, in absolute coordinates.
+                let df = Df::viewport(pos * rect_size);
+                df.move_to(start - rect_pos);
+                df.line_to(end - rect_pos);
+                return df.stroke(color, width);
+            }"#
+        ),
+    ],
+    ..Shader::DEFAULT
+};
+
+#[derive(Clone, Copy, Default)]
+#[repr(C)]
+struct NodeIns {
+    base: QuadIns,
+    color: Vec4,
+    header_color: Vec4,
+    border_color: Vec4,
+    header_height: f32,
+    radius: f32,
+}
+
+static NODE_SHADER: Shader = Shader {
+    build_geom: Some(QuadIns::build_geom),
+    code_to_concatenate: &[
+        Cx::STD_SHADER,
+        QuadIns::SHADER,
+        code_fragment!(
+            r#"
+            instance color: vec4;
+            instance header_color: vec4;
+            instance border_color: vec4;
+            instance header_height: float;
+            instance radius: float;
+
+            fn pixel() -> vec4 {
+                let df = Df::viewport(pos * rect_size);
+                df.box(vec2(1.), rect_size - 2., radius);
+                if pos.y * rect_size.y < header_height {
+                    df.fill(header_color);
+                } else {
+                    df.fill(color);
+                }
+                return df.stroke(border_color, 1.);
+            }"#
+        ),
+    ],
+    ..Shader::DEFAULT
+};
+
+#[derive(Clone, Copy, Default)]
+#[repr(C)]
+struct PortIns {
+    base: QuadIns,
+    color: Vec4,
+    highlight: f32,
+}
+
+static PORT_SHADER: Shader = Shader {
+    build_geom: Some(QuadIns::build_geom),
+    code_to_concatenate: &[
+        Cx::STD_SHADER,
+        QuadIns::SHADER,
+        code_fragment!(
+            r#"
+            instance color: vec4;
+            instance highlight: float;
+
+            fn pixel() -> vec4 {
+                let df = Df::viewport(pos * rect_size);
+                let center = rect_size * 0.5;
+                df.circle(center, center.x - 1.5);
+                df.fill(color);
+                if highlight > 0.5 {
+                    df.stroke(#fff, 1.);
+                }
+                return df.result;
+            }"#
+        ),
+    ],
+    ..Shader::DEFAULT
+};
+
+fn bezier_points(from: Vec2, to: Vec2) -> Vec<Vec2> {
+    // Leave and enter the ports horizontally, curving more when the nodes are further apart.
+    let d = ((to.x - from.x).abs() / 2.).max(40.);
+    let (c1, c2) = (from + vec2(d, 0.), to - vec2(d, 0.));
+    (0..=EDGE_SEGMENTS)
+        .map(|i| {
+            let t = i as f32 / EDGE_SEGMENTS as f32;
+            let u = 1. - t;
+            from * (u * u * u) + c1 * (3. * u * u * t) + c2 * (3. * u * t * t) + to * (t * t * t)
+        })
+        .collect()
+}
+
+fn distance_to_segment(p: Vec2, a: Vec2, b: Vec2) -> f32 {
+    let ab = b - a;
+    let length_squared = ab.dot(ab);
+    let t = if length_squared > 0. { ((p - a).dot(ab) / length_squared).clamp(0., 1.) } else { 0. };
+    (a + ab * t).distance(&p)
+}
+
+/// Shows a [`NodeGraphData`] and lets the user edit it:
+/// * Drag nodes by their body to move them; click to select, and hold Shift to add to or remove from the selection.
+/// * Drag from a port to another port to connect them; only ports of the same type can be connected. Dragging from
+///   an input that is already connected pulls off its edge.
+/// * Click an edge to select it.
+/// * Drag on the background to select nodes in a box.
+/// * Press Delete or Backspace to remove the selection, or Ctrl+A (Cmd+A) to select all nodes.
+/// * Drag with the right mouse button or scroll on a trackpad to pan, and use a mouse wheel, pinch, or Ctrl+scroll
+///   to zoom; see [`Cx::interaction_policy`].
+///
+/// The graph itself is updated directly; [`NodeGraphEvent`]s tell you what changed.
+pub struct NodeGraph {
+    component_id: ComponentId,
+    data: NodeGraphData,
+    view: View,
+    grid_area: Area,
+    selection_box: Background,
+    /// Top-left of the graph on screen, during the last draw or event.
+    origin: Vec2,
+    pan: Vec2,
+    zoom: f32,
+    selection: Vec<NodeId>,
+    selected_edges: Vec<GraphEdge>,
+    hover_port: Option<(PortRef, PortSide)>,
+    hover_edge: Option<GraphEdge>,
+    drag: NodeGraphDrag,
+    port_type_colors: Vec<(String, Vec4)>,
+    background_color: Vec4,
+    grid_color: Vec4,
+    node_color: Vec4,
+    header_color: Vec4,
+    text_color: Vec4,
+    accent_color: Vec4,
+}
+
+impl Default for NodeGraph {
+    fn default() -> Self {
+        Self {
+            component_id: Default::default(),
+            data: Default::default(),
+            view: Default::default(),
+            grid_area: Default::default(),
+            selection_box: Background::default().with_radius(2.).with_draw_depth(10.),
+            origin: Vec2::default(),
+            pan: Vec2::default(),
+            zoom: 1.,
+            selection: vec![],
+            selected_edges: vec![],
+            hover_port: None,
+            hover_edge: None,
+            drag: NodeGraphDrag::None,
+            port_type_colors: vec![],
+            background_color: vec4(0.12, 0.12, 0.12, 1.),
+            grid_color: vec4(0.17, 0.17, 0.17, 1.),
+            node_color: vec4(0.2, 0.2, 0.22, 1.),
+            header_color: vec4(0.27, 0.29, 0.35, 1.),
+            text_color: vec4(0.85, 0.85, 0.85, 1.),
+            accent_color: vec4(0.35, 0.6, 1., 1.),
+        }
+    }
+}
+
+impl NodeGraph {
+    const GRID_SIZE: f32 = 20.;
+    const DEFAULT_PORT_COLOR: Vec4 = vec4(0.65, 0.65, 0.65, 1.);
+
+    #[must_use]
+    pub fn with_data(self, data: NodeGraphData) -> Self {
+        Self { data, ..self }
+    }
+    /// Color ports and edges of type `port_type` with `color`. Other types are gray.
+    #[must_use]
+    pub fn with_port_type_color(self, port_type: &str, color: Vec4) -> Self {
+        let mut port_type_colors = self.port_type_colors;
+        port_type_colors.push((port_type.to_string(), color));
+        Self { port_type_colors, ..self }
+    }
+    #[must_use]
+    pub fn with_accent_color(self, accent_color: Vec4) -> Self {
+        Self { accent_color, ..self }
+    }
+
+    pub fn data(&self) -> &NodeGraphData {
+        &self.data
+    }
+
+    /// Change the graph directly, e.g. to add nodes. Call [`Cx::request_draw`] afterwards.
+    pub fn data_mut(&mut self) -> &mut NodeGraphData {
+        &mut self.data
+    }
+
+    /// Replace the whole graph, e.g. when loading a file. Clears the selection.
+    pub fn set_data(&mut self, cx: &mut Cx, data: NodeGraphData) {
+        self.data = data;
+        self.selection.clear();
+        self.selected_edges.clear();
+        self.hover_port = None;
+        self.hover_edge = None;
+        self.drag = NodeGraphDrag::None;
+        cx.request_draw();
+    }
+
+    /// The selected nodes.
+    pub fn selection(&self) -> &[NodeId] {
+        &self.selection
+    }
+
+    pub fn selected_edges(&self) -> &[GraphEdge] {
+        &self.selected_edges
+    }
+
+    pub fn set_selection(&mut self, cx: &mut Cx, selection: Vec<NodeId>) {
+        self.selection = selection;
+        self.selected_edges.clear();
+        cx.request_draw();
+    }
+
+    /// How far the graph is panned (in screen pixels), and how much it is zoomed in.
+    pub fn pan_and_zoom(&self) -> (Vec2, f32) {
+        (self.pan, self.zoom)
+    }
+
+    pub fn set_pan_and_zoom(&mut self, cx: &mut Cx, pan: Vec2, zoom: f32) {
+        self.pan = pan;
+        self.zoom = zoom.clamp(MIN_ZOOM, MAX_ZOOM);
+        cx.request_draw();
+    }
+
+    fn to_graph(&self, abs: Vec2) -> Vec2 {
+        (abs - self.origin - self.pan) / self.zoom
+    }
+
+    fn to_screen(&self, graph_pos: Vec2) -> Vec2 {
+        self.origin + self.pan + graph_pos * self.zoom
+    }
+
+    fn port_type_color(&self, port_type: &str) -> Vec4 {
+        self.port_type_colors.iter().find(|(t, _)| t == port_type).map_or(Self::DEFAULT_PORT_COLOR, |(_, color)| *color)
+    }
+
+    fn port_at(&self, graph_pos: Vec2) -> Option<(PortRef, PortSide)> {
+        for node in self.data.nodes.iter().rev() {
+            for side in [PortSide::Input, PortSide::Output] {
+                let len = if side == PortSide::Input { node.inputs.len() } else { node.outputs.len() };
+                for port in 0..len {
+                    if node.port_pos(side, port).distance(&graph_pos) <= PORT_HIT_RADIUS {
+                        return Some((PortRef { node: node.id, port }, side));
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    fn node_at(&self, graph_pos: Vec2) -> Option<NodeId> {
+        self.data.nodes.iter().rev().find(|node| node.rect().contains(graph_pos)).map(|node| node.id)
+    }
+
+    fn edge_at(&self, graph_pos: Vec2) -> Option<GraphEdge> {
+        let max_distance = EDGE_HIT_DISTANCE / self.zoom;
+        self.data.edges.iter().rev().copied().find(|edge| {
+            self.data.edge_endpoints(edge).map_or(false, |(from, to)| {
+                bezier_points(from, to).windows(2).any(|w| distance_to_segment(graph_pos, w[0], w[1]) <= max_distance)
+            })
+        })
+    }
+
+    /// Turn a drag between two ports into an edge going from the output to the input, if they are on opposite sides.
+    fn edge_between(a: (PortRef, PortSide), b: (PortRef, PortSide)) -> Option<GraphEdge> {
+        match (a.1, b.1) {
+            (PortSide::Output, PortSide::Input) => Some(GraphEdge { from: a.0, to: b.0 }),
+            (PortSide::Input, PortSide::Output) => Some(GraphEdge { from: b.0, to: a.0 }),
+            _ => None,
+        }
+    }
+
+    fn update_hover(&mut self, cx: &mut Cx, graph_pos: Option<Vec2>) {
+        let hover_port = graph_pos.and_then(|p| self.port_at(p));
+        let hover_edge = if hover_port.is_none() { graph_pos.and_then(|p| self.edge_at(p)) } else { None };
+        if hover_port != self.hover_port || hover_edge != self.hover_edge {
+            self.hover_port = hover_port;
+            self.hover_edge = hover_edge;
+            cx.request_draw();
+        }
+    }
+
+    fn select_node(&mut self, id: NodeId, toggle: bool) -> bool {
+        let old = (self.selection.clone(), self.selected_edges.clone());
+        if toggle {
+            if let Some(index) = self.selection.iter().position(|selected| *selected == id) {
+                self.selection.remove(index);
+            } else {
+                self.selection.push(id);
+            }
+        } else if !self.selection.contains(&id) {
+            self.selection = vec![id];
+            self.selected_edges.clear();
+        }
+        (self.selection.clone(), self.selected_edges.clone()) != old
+    }
+
+    fn delete_selection(&mut self, cx: &mut Cx) -> NodeGraphEvent {
+        if self.selection.is_empty() && self.selected_edges.is_empty() {
+            return NodeGraphEvent::None;
+        }
+        let nodes = std::mem::take(&mut self.selection);
+        let mut edges = std::mem::take(&mut self.selected_edges);
+        self.data.edges.retain(|edge| !edges.contains(edge));
+        for id in &nodes {
+            edges.extend(self.data.remove_node(*id));
+        }
+        self.hover_port = None;
+        self.hover_edge = None;
+        cx.request_draw();
+        NodeGraphEvent::Deleted { nodes, edges }
+    }
+
+    fn pointer_down(&mut self, cx: &mut Cx, pe: &PointerDownEvent) -> NodeGraphEvent {
+        let graph_pos = self.to_graph(pe.abs);
+        if pe.button == MouseButton::Right {
+            self.drag = NodeGraphDrag::Panning { start_pan: self.pan };
+            return NodeGraphEvent::None;
+        }
+
+        if let Some((port, side)) = self.port_at(graph_pos) {
+            if side == PortSide::Input {
+                // Pull the existing edge off of this input, and keep dragging it from its output.
+                if let Some(edge) = self.data.disconnect(port) {
+                    self.drag = NodeGraphDrag::Connecting { port: edge.from, side: PortSide::Output, pointer: graph_pos };
+                    self.selected_edges.retain(|selected| *selected != edge);
+                    cx.request_draw();
+                    return NodeGraphEvent::Disconnected(edge);
+                }
+            }
+            self.drag = NodeGraphDrag::Connecting { port, side, pointer: graph_pos };
+            cx.request_draw();
+            return NodeGraphEvent::None;
+        }
+
+        if let Some(id) = self.node_at(graph_pos) {
+            let changed = self.select_node(id, pe.modifiers.shift);
+            // Bring the node to the front.
+            if let Some(index) = self.data.nodes.iter().position(|node| node.id == id) {
+                let node = self.data.nodes.remove(index);
+                self.data.nodes.push(node);
+            }
+            let start_positions =
+                self.data.nodes.iter().filter(|node| self.selection.contains(&node.id)).map(|node| (node.id, node.pos)).collect();
+            self.drag = NodeGraphDrag::MovingNodes { start_positions, moved: false };
+            cx.request_draw();
+            return if changed { NodeGraphEvent::SelectionChanged } else { NodeGraphEvent::None };
+        }
+
+        if let Some(edge) = self.edge_at(graph_pos) {
+            self.drag = NodeGraphDrag::None;
+            if pe.modifiers.shift {
+                if let Some(index) = self.selected_edges.iter().position(|selected| *selected == edge) {
+                    self.selected_edges.remove(index);
+                } else {
+                    self.selected_edges.push(edge);
+                }
+            } else {
+                self.selection.clear();
+                self.selected_edges = vec![edge];
+            }
+            cx.request_draw();
+            return NodeGraphEvent::SelectionChanged;
+        }
+
+        let initial = if pe.modifiers.shift { self.selection.clone() } else { vec![] };
+        self.drag = NodeGraphDrag::BoxSelecting { start: graph_pos, end: graph_pos, initial };
+        let has_selection = !self.selection.is_empty() || !self.selected_edges.is_empty();
+        if !pe.modifiers.shift && has_selection {
+            self.selection.clear();
+            self.selected_edges.clear();
+            cx.request_draw();
+            return NodeGraphEvent::SelectionChanged;
+        }
+        NodeGraphEvent::None
+    }
+
+    fn pointer_move(&mut self, cx: &mut Cx, pe: &PointerMoveEvent) {
+        let graph_pos = self.to_graph(pe.abs);
+        match &mut self.drag {
+            NodeGraphDrag::None => {}
+            NodeGraphDrag::Panning { start_pan } => {
+                self.pan = *start_pan + pe.abs - pe.abs_start;
+            }
+            NodeGraphDrag::MovingNodes { start_positions, moved } => {
+                let delta = (pe.abs - pe.abs_start) / self.zoom;
+                *moved = true;
+                for (id, start_pos) in start_positions.iter() {
+                    if let Some(node) = self.data.nodes.iter_mut().find(|node| node.id == *id) {
+                        node.pos = *start_pos + delta;
+                    }
+                }
+            }
+            NodeGraphDrag::Connecting { pointer, .. } => {
+                *pointer = graph_pos;
+                self.update_hover(cx, Some(graph_pos));
+            }
+            NodeGraphDrag::BoxSelecting { start, end, initial } => {
+                *end = graph_pos;
+                let min = start.min(end);
+                let box_rect = Rect { pos: min, size: start.max(end) - min };
+                let mut selection = initial.clone();
+                for node in &self.data.nodes {
+                    if box_rect.intersects(node.rect()) && !selection.contains(&node.id) {
+                        selection.push(node.id);
+                    }
+                }
+                self.selection = selection;
+            }
+        }
+        cx.request_draw();
+    }
+
+    fn pointer_up(&mut self, cx: &mut Cx) -> NodeGraphEvent {
+        let drag = std::mem::replace(&mut self.drag, NodeGraphDrag::None);
+        cx.request_draw();
+        match drag {
+            NodeGraphDrag::MovingNodes { start_positions, moved: true } => {
+                NodeGraphEvent::NodesMoved(start_positions.into_iter().map(|(id, _)| id).collect())
+            }
+            NodeGraphDrag::Connecting { port, side, pointer } => {
+                match self.port_at(pointer).and_then(|target| Self::edge_between((port, side), target)) {
+                    Some(edge) if self.data.connect(edge.from, edge.to) => NodeGraphEvent::Connected(edge),
+                    _ => NodeGraphEvent::None,
+                }
+            }
+            NodeGraphDrag::BoxSelecting { start, end, .. } if start != end => NodeGraphEvent::SelectionChanged,
+            _ => NodeGraphEvent::None,
+        }
+    }
+
+    fn pointer_scroll(&mut self, cx: &mut Cx, pe: &PointerScrollEvent) {
+        match cx.interaction_policy().scroll_action(pe) {
+            ScrollAction::Zoom { factor, .. } => {
+                // Keep the point under the pointer in the same place.
+                let graph_pos = self.to_graph(pe.abs);
+                self.zoom = (self.zoom * factor).clamp(MIN_ZOOM, MAX_ZOOM);
+                self.pan = pe.abs - self.origin - graph_pos * self.zoom;
+            }
+            ScrollAction::Pan(delta) => self.pan -= delta,
+        }
+        cx.request_draw();
+    }
+
+    pub fn handle(&mut self, cx: &mut Cx, event: &mut Event) -> NodeGraphEvent {
+        if let Event::KeyDown(ke) = event.hits_keyboard(cx, self.component_id) {
+            match ke.key_code {
+                KeyCode::Delete | KeyCode::Backspace => return self.delete_selection(cx),
+                KeyCode::KeyA if ke.modifiers.control || ke.modifiers.logo => {
+                    self.selection = self.data.nodes.iter().map(|node| node.id).collect();
+                    self.selected_edges.clear();
+                    cx.request_draw();
+                    return NodeGraphEvent::SelectionChanged;
+                }
+                _ => {}
+            }
+        }
+
+        let rect = self.grid_area.get_rect_for_first_instance(cx);
+        if let Some(rect) = rect {
+            self.origin = rect.pos;
+        }
+        match event.hits_pointer(cx, self.component_id, rect) {
+            Event::PointerHover(pe) => {
+                let graph_pos = if pe.hover_state == HoverState::Out { None } else { Some(self.to_graph(pe.abs)) };
+                self.update_hover(cx, graph_pos);
+            }
+            Event::PointerDown(pe) => {
+                cx.set_key_focus(Some(self.component_id));
+                return self.pointer_down(cx, &pe);
+            }
+            Event::PointerMove(pe) => self.pointer_move(cx, &pe),
+            Event::PointerUp(_) => return self.pointer_up(cx),
+            Event::PointerScroll(pe) => self.pointer_scroll(cx, &pe),
+            _ => {}
+        }
+        NodeGraphEvent::None
+    }
+
+    fn draw_edge(&self, cx: &mut Cx, from: Vec2, to: Vec2, color: Vec4, width: f32) {
+        let points: Vec<Vec2> = bezier_points(from, to).into_iter().map(|p| self.to_screen(p)).collect();
+        let instances: Vec<EdgeIns> = points
+            .windows(2)
+            .map(|w| {
+                let pad = Vec2::all(width + 1.);
+                let min = w[0].min(&w[1]) - pad;
+                let size = w[0].max(&w[1]) + pad - min;
+                EdgeIns { base: QuadIns::from_rect(Rect { pos: min, size }), color, start: w[0], end: w[1], width }
+            })
+            .collect();
+        cx.add_instances(&EDGE_SHADER, &instances);
+    }
+
+    fn draw_node(&self, cx: &mut Cx, node: &GraphNode, draw_depth: f32) {
+        let rect = node.rect();
+        let screen_rect = Rect { pos: self.to_screen(rect.pos), size: rect.size * self.zoom };
+        let border_color = if self.selection.contains(&node.id) { self.accent_color } else { vec4(0., 0., 0., 0.5) };
+        cx.add_instances(
+            &NODE_SHADER,
+            &[NodeIns {
+                base: QuadIns::from_rect(screen_rect).with_draw_depth(draw_depth),
+                color: self.node_color,
+                header_color: self.header_color,
+                border_color,
+                header_height: HEADER_HEIGHT * self.zoom,
+                radius: 4. * self.zoom,
+            }],
+        );
+
+        let connecting_from = match self.drag {
+            NodeGraphDrag::Connecting { port, side, .. } => Some((port, side)),
+            _ => None,
+        };
+        for side in [PortSide::Input, PortSide::Output] {
+            let ports = if side == PortSide::Input { &node.inputs } else { &node.outputs };
+            for (index, port) in ports.iter().enumerate() {
+                let port_ref = PortRef { node: node.id, port: index };
+                // While connecting, only highlight ports that the new edge could actually go to.
+                let highlight = match (self.hover_port, connecting_from) {
+                    (Some(hover), Some(from)) => {
+                        hover == (port_ref, side)
+                            && Self::edge_between(from, hover).map_or(false, |edge| self.data.can_connect(edge.from, edge.to))
+                    }
+                    (Some(hover), None) => hover == (port_ref, side),
+                    _ => false,
+                };
+                let center = self.to_screen(node.port_pos(side, index));
+                let radius = PORT_RADIUS * self.zoom;
+                cx.add_instances(
+                    &PORT_SHADER,
+                    &[PortIns {
+                        base: QuadIns::from_rect(Rect { pos: center - Vec2::all(radius), size: Vec2::all(radius * 2.) })
+                            .with_draw_depth(draw_depth + 0.001),
+                        color: self.port_type_color(&port.port_type),
+                        highlight: if highlight { 1. } else { 0. },
+                    }],
+                );
+            }
+        }
+
+        // Text gets unreadable when zoomed out far, so leave it out.
+        if self.zoom < 0.4 {
+            return;
+        }
+        let props = TextInsProps {
+            color: self.text_color,
+            font_scale: self.zoom,
+            draw_depth: draw_depth + 0.002,
+            ..TextInsProps::DEFAULT
+        };
+        let line_height = cx.measure_wrapped_text(&props, "X", f32::INFINITY).line_height;
+        let text_offset = (PORT_ROW_HEIGHT * self.zoom - line_height) / 2.;
+        let title_pos = screen_rect.pos + vec2(8. * self.zoom, (HEADER_HEIGHT * self.zoom - line_height) / 2.);
+        TextIns::draw_str(cx, &node.title, title_pos, &props);
+        for (index, port) in node.inputs.iter().enumerate() {
+            let row = self.to_screen(node.port_pos(PortSide::Input, index) - vec2(0., PORT_ROW_HEIGHT / 2.));
+            TextIns::draw_str(cx, &port.name, row + vec2(10. * self.zoom, text_offset), &props);
+        }
+        for (index, port) in node.outputs.iter().enumerate() {
+            let row = self.to_screen(node.port_pos(PortSide::Output, index) - vec2(0., PORT_ROW_HEIGHT / 2.));
+            let width = cx.measure_wrapped_text(&props, &port.name, f32::INFINITY).width;
+            TextIns::draw_str(cx, &port.name, row + vec2(-10. * self.zoom - width, text_offset), &props);
+        }
+    }
+
+    pub fn draw(&mut self, cx: &mut Cx) {
+        self.view.begin_view(cx, LayoutSize::FILL);
+        let rect = cx.add_box(LayoutSize::FILL);
+        self.origin = rect.pos;
+
+        let grid_size = Self::GRID_SIZE * self.zoom;
+        self.grid_area = cx.add_instances(
+            &GRID_SHADER,
+            &[GridIns {
+                base: QuadIns::from_rect(rect),
+                color: self.background_color,
+                grid_color: self.grid_color,
+                grid_size,
+                grid_offset: self.pan,
+            }],
+        );
+
+        for edge in &self.data.edges {
+            if let Some((from, to)) = self.data.edge_endpoints(edge) {
+                let color = if self.selected_edges.contains(edge) {
+                    self.accent_color
+                } else {
+                    self.data
+                        .node(edge.from.node)
+                        .and_then(|node| node.port(PortSide::Output, edge.from.port))
+                        .map_or(Self::DEFAULT_PORT_COLOR, |port| self.port_type_color(&port.port_type))
+                };
+                let width = if self.hover_edge == Some(*edge) { 2.5 } else { 1.5 };
+                self.draw_edge(cx, from, to, color, width);
+            }
+        }
+        if let NodeGraphDrag::Connecting { port, side, pointer } = self.drag {
+            if let Some(node) = self.data.node(port.node) {
+                let port_pos = node.port_pos(side, port.port);
+                let color = node.port(side, port.port).map_or(Self::DEFAULT_PORT_COLOR, |p| self.port_type_color(&p.port_type));
+                let (from, to) = if side == PortSide::Output { (port_pos, pointer) } else { (pointer, port_pos) };
+                self.draw_edge(cx, from, to, color, 1.5);
+            }
+        }
+
+        for (index, node) in self.data.nodes.iter().enumerate() {
+            self.draw_node(cx, node, 0.1 + index as f32 * 0.01);
+        }
+
+        if let NodeGraphDrag::BoxSelecting { start, end, .. } = self.drag {
+            let min = self.to_screen(start.min(&end));
+            let max = self.to_screen(start.max(&end));
+            let color = vec4(self.accent_color.x, self.accent_color.y, self.accent_color.z, 0.2);
+            self.selection_box.draw(cx, Rect { pos: min, size: max - min }, color);
+        }
+
+        self.view.end_view(cx);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    use zaplib::*;
+
+    fn test_data() -> (NodeGraphData, NodeId, NodeId) {
+        let mut data = NodeGraphData::default();
+        let a = data.add_node("Source", vec2(0., 0.), vec![], vec![NodePort::new("out", "float")]);
+        let b = data.add_node(
+            "Sink",
+            vec2(300., 0.),
+            vec![NodePort::new("in", "float"), NodePort::new("image", "image")],
+            vec![NodePort::new("out", "float")],
+        );
+        (data, a, b)
+    }
+
+    #[test]
+    fn test_node_graph_data_connect() {
+        let (mut data, a, b) = test_data();
+        let out = PortRef { node: a, port: 0 };
+
+        // Types have to match.
+        assert!(!data.connect(out, PortRef { node: b, port: 1 }));
+        assert!(data.connect(out, PortRef { node: b, port: 0 }));
+        assert_eq!(data.edges.len(), 1);
+
+        // No cycles, including to the node itself.
+        assert!(!data.can_connect(PortRef { node: b, port: 0 }, PortRef { node: b, port: 0 }));
+
+        assert_eq!(data.remove_node(a), vec![GraphEdge { from: out, to: PortRef { node: b, port: 0 } }]);
+        assert!(data.edges.is_empty());
+    }
+
+    #[test]
+    fn test_node_graph_connect_and_box_select() {
+        let (data, a, b) = test_data();
+        let mut node_graph = NodeGraph::default().with_data(data);
+        let mut test_cx = TestCx::new(vec2(800., 600.));
+        test_cx.draw(|cx| node_graph.draw(cx));
+
+        // Drag from the output of the first node to the first input of the second node.
+        let output = vec2(160., 34.);
+        let input = vec2(300., 34.);
+        test_cx.pointer_down(output, |cx, event| node_graph.handle(cx, event));
+        test_cx.pointer_move(input, |cx, event| node_graph.handle(cx, event));
+        let events = test_cx.pointer_up(input, |cx, event| node_graph.handle(cx, event));
+        let edge = GraphEdge { from: PortRef { node: a, port: 0 }, to: PortRef { node: b, port: 0 } };
+        assert_eq!(events, vec![NodeGraphEvent::Connected(edge)]);
+        assert_eq!(node_graph.data().edges, vec![edge]);
+
+        // Box select both nodes, starting on the background.
+        test_cx.draw(|cx| node_graph.draw(cx));
+        test_cx.pointer_down(vec2(700., 500.), |cx, event| node_graph.handle(cx, event));
+        test_cx.pointer_move(vec2(100., 20.), |cx, event| node_graph.handle(cx, event));
+        let events = test_cx.pointer_up(vec2(100., 20.), |cx, event| node_graph.handle(cx, event));
+        assert_eq!(events, vec![NodeGraphEvent::SelectionChanged]);
+        assert_eq!(node_graph.selection().len(), 2);
+    }
+}
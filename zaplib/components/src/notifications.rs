@@ -0,0 +1,480 @@
+//! Toast notifications that stack up in a corner of the window.
+
+use crate::background::*;
+use crate::button::ButtonEvent;
+use crate::buttonlogic::*;
+use zaplib::*;
+
+/// How important a [`Notification`] is, which determines its accent color.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum NotificationSeverity {
+    Info,
+    Success,
+    Warning,
+    Error,
+}
+
+impl NotificationSeverity {
+    fn color(self) -> Vec4 {
+        match self {
+            NotificationSeverity::Info => vec4(0.27, 0.56, 0.93, 1.),
+            NotificationSeverity::Success => vec4(0.3, 0.73, 0.39, 1.),
+            NotificationSeverity::Warning => vec4(0.95, 0.65, 0.2, 1.),
+            NotificationSeverity::Error => vec4(0.9, 0.3, 0.3, 1.),
+        }
+    }
+}
+
+/// A notification to show using [`Notifications::push`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct Notification {
+    pub title: String,
+    pub message: String,
+    pub severity: NotificationSeverity,
+    /// How many seconds to show the notification for, or [`None`] to keep it until the user closes it.
+    pub duration: Option<f64>,
+    /// Labels of buttons to show below the message; see [`NotificationsEvent::Action`].
+    pub actions: Vec<String>,
+}
+
+impl Notification {
+    /// An [`NotificationSeverity::Info`] notification that disappears after 5 seconds.
+    pub fn new(title: &str, message: &str) -> Self {
+        Self {
+            title: title.to_string(),
+            message: message.to_string(),
+            severity: NotificationSeverity::Info,
+            duration: Some(5.),
+            actions: vec![],
+        }
+    }
+    #[must_use]
+    pub fn with_severity(self, severity: NotificationSeverity) -> Self {
+        Self { severity, ..self }
+    }
+    #[must_use]
+    pub fn with_duration(self, duration: Option<f64>) -> Self {
+        Self { duration, ..self }
+    }
+    #[must_use]
+    pub fn with_action(mut self, label: &str) -> Self {
+        self.actions.push(label.to_string());
+        self
+    }
+}
+
+/// Identifies a notification that was pushed using [`Notifications::push`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct NotificationId(u64);
+
+/// Which corner of the window notifications stack up in.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum NotificationCorner {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum NotificationsEvent {
+    None,
+    /// The user clicked one of [`Notification::actions`], by index. This also closes the notification.
+    Action {
+        id: NotificationId,
+        action: usize,
+    },
+    /// The notification started closing, because its duration passed or the user closed it.
+    Closed(NotificationId),
+}
+
+/// A notification that has been pushed and not removed yet.
+struct Toast {
+    id: NotificationId,
+    notification: Notification,
+    /// Whether this toast is showing, as opposed to waiting for others to go away (see
+    /// [`Notifications::with_max_visible`]).
+    visible: bool,
+    closing: bool,
+    timer: Timer,
+    /// Distance from the corner, for moving toasts when others appear or go away. [`None`] until first drawn.
+    offset: Option<Spring<f32>>,
+    /// 0 when fully in view, 1 when moved out of the window completely.
+    slide: Spring<f32>,
+    opacity: Spring<f32>,
+    background: Background,
+    accent: Background,
+    close_component_id: ComponentId,
+    close_rect: Option<Rect>,
+    action_component_ids: Vec<ComponentId>,
+    action_rects: Vec<Rect>,
+}
+
+/// Shows [`Notification`]s as "toasts" in a corner of the window. They slide in, stack on top of each other, and
+/// fade out after their [`Notification::duration`], or when the user closes them.
+///
+/// Optionally, notifications that are pushed while the app doesn't have focus also get shown as system
+/// notifications, see [`Notifications::with_system_notifications`].
+///
+/// Draw this after everything else in your window, so it sits on top.
+pub struct Notifications {
+    view: View,
+    toasts: Vec<Toast>,
+    next_id: u64,
+    corner: NotificationCorner,
+    max_visible: usize,
+    width: f32,
+    text_style: TextStyle,
+    background_color: Vec4,
+    text_color: Vec4,
+    system_notifications: bool,
+    /// Tracked using [`Event::AppFocus`] and [`Event::AppFocusLost`].
+    app_focused: bool,
+}
+
+impl Default for Notifications {
+    fn default() -> Self {
+        Self {
+            view: View::default().with_is_overlay(true),
+            toasts: vec![],
+            next_id: 0,
+            corner: NotificationCorner::BottomRight,
+            max_visible: 5,
+            width: 320.,
+            text_style: TEXT_STYLE_NORMAL,
+            background_color: vec4(0.15, 0.15, 0.15, 0.97),
+            text_color: vec4(0.9, 0.9, 0.9, 1.),
+            system_notifications: false,
+            app_focused: true,
+        }
+    }
+}
+
+impl Notifications {
+    const PADDING: Padding = Padding::all(10.);
+    /// Space between toasts, and between toasts and the edge of the window.
+    const MARGIN: f32 = 10.;
+    const ACCENT_WIDTH: f32 = 4.;
+    const ACTION_SPACING: f32 = 16.;
+    const DRAW_DEPTH: f32 = 10.;
+
+    #[must_use]
+    pub fn with_corner(self, corner: NotificationCorner) -> Self {
+        Self { corner, ..self }
+    }
+    /// How many notifications to show at once. Others wait until there is room. Defaults to 5.
+    #[must_use]
+    pub fn with_max_visible(self, max_visible: usize) -> Self {
+        assert!(max_visible > 0, "Notifications need to be able to show at least one notification");
+        Self { max_visible, ..self }
+    }
+    #[must_use]
+    pub fn with_width(self, width: f32) -> Self {
+        Self { width, ..self }
+    }
+    #[must_use]
+    pub fn with_text_style(self, text_style: TextStyle) -> Self {
+        Self { text_style, ..self }
+    }
+    #[must_use]
+    pub fn with_background_color(self, background_color: Vec4) -> Self {
+        Self { background_color, ..self }
+    }
+    #[must_use]
+    pub fn with_text_color(self, text_color: Vec4) -> Self {
+        Self { text_color, ..self }
+    }
+    /// Also show notifications using [`Cx::show_system_notification`] when they are pushed while the app doesn't
+    /// have focus, so the user doesn't miss them. Defaults to `false`.
+    #[must_use]
+    pub fn with_system_notifications(self, system_notifications: bool) -> Self {
+        Self { system_notifications, ..self }
+    }
+
+    /// Show a notification, or queue it if [`Notifications::with_max_visible`] are already showing.
+    pub fn push(&mut self, cx: &mut Cx, notification: Notification) -> NotificationId {
+        let id = NotificationId(self.next_id);
+        self.next_id += 1;
+
+        if self.system_notifications && !self.app_focused {
+            let future = cx.show_system_notification(&notification.title, &notification.message);
+            cx.spawn(async move {
+                if let Err(err) = future.await {
+                    log!("{}", err);
+                }
+            });
+        }
+
+        self.toasts.push(Toast {
+            id,
+            notification,
+            visible: false,
+            closing: false,
+            timer: Timer::empty(),
+            offset: None,
+            slide: Spring::new(1.),
+            opacity: Spring::new(0.),
+            background: Background::default().with_radius(4.).with_draw_depth(Self::DRAW_DEPTH),
+            accent: Background::default().with_radius(2.).with_draw_depth(Self::DRAW_DEPTH + 0.1),
+            close_component_id: ComponentId::default(),
+            close_rect: None,
+            action_component_ids: vec![],
+            action_rects: vec![],
+        });
+        self.show_queued(cx);
+        id
+    }
+
+    /// Start closing a notification. Does nothing if it's already closing or gone.
+    pub fn close(&mut self, cx: &mut Cx, id: NotificationId) {
+        if let Some(index) = self.toasts.iter().position(|toast| toast.id == id) {
+            if self.toasts[index].visible {
+                let toast = &mut self.toasts[index];
+                if !toast.closing {
+                    toast.closing = true;
+                    cx.stop_timer(&mut toast.timer);
+                    toast.slide.set_target(cx, 1.);
+                    toast.opacity.set_target(cx, 0.);
+                }
+            } else {
+                self.toasts.remove(index);
+            }
+        }
+    }
+
+    /// Remove all notifications right away.
+    pub fn clear(&mut self, cx: &mut Cx) {
+        for toast in &mut self.toasts {
+            cx.stop_timer(&mut toast.timer);
+        }
+        self.toasts.clear();
+        cx.request_draw();
+    }
+
+    /// The number of notifications that are showing or queued.
+    pub fn len(&self) -> usize {
+        self.toasts.iter().filter(|toast| !toast.closing).count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Show queued notifications if there is room.
+    fn show_queued(&mut self, cx: &mut Cx) {
+        let mut visible_count = self.toasts.iter().filter(|toast| toast.visible).count();
+        for toast in &mut self.toasts {
+            if visible_count >= self.max_visible {
+                break;
+            }
+            if !toast.visible {
+                toast.visible = true;
+                visible_count += 1;
+                toast.slide.set_target(cx, 0.);
+                toast.opacity.set_target(cx, 1.);
+                if let Some(duration) = toast.notification.duration {
+                    toast.timer = cx.start_timer(duration, false);
+                }
+            }
+        }
+        cx.request_draw();
+    }
+
+    pub fn handle(&mut self, cx: &mut Cx, event: &mut Event) -> NotificationsEvent {
+        match event {
+            Event::AppFocus => self.app_focused = true,
+            Event::AppFocusLost => self.app_focused = false,
+            Event::Timer(te) => {
+                if let Some(toast) = self.toasts.iter().find(|toast| toast.timer.is_timer(te)) {
+                    let id = toast.id;
+                    self.close(cx, id);
+                    return NotificationsEvent::Closed(id);
+                }
+            }
+            Event::NextFrame => {
+                let mut changed = false;
+                for toast in &mut self.toasts {
+                    if let Some(offset) = &mut toast.offset {
+                        changed |= offset.handle(cx, event);
+                    }
+                    changed |= toast.slide.handle(cx, event);
+                    changed |= toast.opacity.handle(cx, event);
+                }
+                let len = self.toasts.len();
+                self.toasts.retain(|toast| !(toast.closing && toast.opacity.is_at_rest()));
+                if self.toasts.len() != len {
+                    self.show_queued(cx);
+                } else if changed {
+                    cx.request_draw();
+                }
+            }
+            _ => {}
+        }
+
+        for index in 0..self.toasts.len() {
+            let toast = &self.toasts[index];
+            if !toast.visible || toast.closing {
+                continue;
+            }
+            let id = toast.id;
+            let close_component_id = toast.close_component_id;
+            let close_rect = toast.close_rect;
+            let hit_event = event.hits_pointer(cx, close_component_id, close_rect);
+            if handle_button_logic(cx, hit_event, |_, _| ()) == ButtonEvent::Clicked {
+                self.close(cx, id);
+                return NotificationsEvent::Closed(id);
+            }
+            for action in 0..self.toasts[index].action_rects.len() {
+                let component_id = self.toasts[index].action_component_ids[action];
+                let rect = self.toasts[index].action_rects[action];
+                let hit_event = event.hits_pointer(cx, component_id, Some(rect));
+                if handle_button_logic(cx, hit_event, |_, _| ()) == ButtonEvent::Clicked {
+                    self.close(cx, id);
+                    return NotificationsEvent::Action { id, action };
+                }
+            }
+        }
+        NotificationsEvent::None
+    }
+
+    pub fn draw(&mut self, cx: &mut Cx) {
+        if self.toasts.is_empty() {
+            return;
+        }
+        self.view.begin_view(cx, LayoutSize::FILL);
+        let bounds = cx.get_box_rect();
+        let padding = Self::PADDING;
+        let text_width = self.width - padding.l - padding.r - Self::ACCENT_WIDTH;
+        let message_props = TextInsProps {
+            text_style: self.text_style,
+            wrapping: Wrapping::Word,
+            draw_depth: Self::DRAW_DEPTH + 0.2,
+            ..TextInsProps::DEFAULT
+        };
+        let title_height = cx.measure_wrapped_text(&message_props, "X", f32::INFINITY).line_height;
+
+        // The newest notification sits in the corner, and older ones get pushed away from it.
+        let mut next_offset = 0.;
+        for toast in self.toasts.iter_mut().rev() {
+            if !toast.visible {
+                continue;
+            }
+            let message_metrics = cx.measure_wrapped_text(&message_props, &toast.notification.message, text_width);
+            let actions_height = if toast.notification.actions.is_empty() { 0. } else { title_height + padding.t };
+            let height = padding.t + title_height + 4. + message_metrics.height + actions_height + padding.b;
+
+            let offset = match &mut toast.offset {
+                Some(offset) => {
+                    if offset.get_target() != next_offset {
+                        offset.set_target(cx, next_offset);
+                    }
+                    offset.get()
+                }
+                None => {
+                    toast.offset = Some(Spring::new(next_offset));
+                    next_offset
+                }
+            };
+            if !toast.closing {
+                next_offset += height + Self::MARGIN;
+            }
+
+            let slide = toast.slide.get() * (self.width + Self::MARGIN);
+            let x = match self.corner {
+                NotificationCorner::TopLeft | NotificationCorner::BottomLeft => bounds.pos.x + Self::MARGIN - slide,
+                NotificationCorner::TopRight | NotificationCorner::BottomRight => {
+                    bounds.pos.x + bounds.size.x - Self::MARGIN - self.width + slide
+                }
+            };
+            let y = match self.corner {
+                NotificationCorner::TopLeft | NotificationCorner::TopRight => bounds.pos.y + Self::MARGIN + offset,
+                NotificationCorner::BottomLeft | NotificationCorner::BottomRight => {
+                    bounds.pos.y + bounds.size.y - Self::MARGIN - offset - height
+                }
+            };
+            let opacity = toast.opacity.get().clamp(0., 1.);
+            let fade = |color: Vec4| vec4(color.x, color.y, color.z, color.w * opacity);
+
+            let rect = Rect { pos: vec2(x, y), size: vec2(self.width, height) };
+            toast.background.draw(cx, rect, fade(self.background_color));
+            toast.accent.draw(
+                cx,
+                Rect { pos: rect.pos, size: vec2(Self::ACCENT_WIDTH, height) },
+                fade(toast.notification.severity.color()),
+            );
+
+            let text_x = x + Self::ACCENT_WIDTH + padding.l;
+            let mut line_y = y + padding.t;
+            let title_props = TextInsProps { wrapping: Wrapping::None, color: fade(self.text_color), ..message_props };
+            TextIns::draw_str(cx, &toast.notification.title, vec2(text_x, line_y), &title_props);
+
+            let close_pos = vec2(x + self.width - padding.r - title_height, line_y);
+            TextIns::draw_str(cx, "×", close_pos, &TextInsProps { color: fade(self.text_color * 0.7), ..title_props });
+            toast.close_rect = Some(Rect { pos: close_pos, size: vec2(title_height, title_height) });
+            line_y += title_height + 4.;
+
+            let message_color = fade(vec4(self.text_color.x, self.text_color.y, self.text_color.z, self.text_color.w * 0.8));
+            let message_props = TextInsProps { color: message_color, ..message_props };
+            // Same line heights as `Cx::measure_wrapped_text`.
+            let newline_height = (self.text_style.font_size * self.text_style.line_spacing).max(message_metrics.line_height);
+            for line in cx.wrap_text(&message_props, &toast.notification.message, text_width) {
+                let text = line.text.trim_end();
+                if !text.is_empty() {
+                    TextIns::draw_str(
+                        cx,
+                        text,
+                        vec2(text_x, line_y),
+                        &TextInsProps { wrapping: Wrapping::None, ..message_props },
+                    );
+                }
+                line_y += if line.newline { newline_height } else { message_metrics.line_height };
+            }
+
+            toast.action_component_ids.resize_with(toast.notification.actions.len(), ComponentId::default);
+            toast.action_rects.clear();
+            let mut action_x = text_x;
+            let action_y = y + height - padding.b - title_height;
+            let action_props = TextInsProps { color: fade(toast.notification.severity.color()), ..title_props };
+            for label in &toast.notification.actions {
+                let width = cx.measure_wrapped_text(&action_props, label, f32::INFINITY).width;
+                TextIns::draw_str(cx, label, vec2(action_x, action_y), &action_props);
+                toast.action_rects.push(Rect { pos: vec2(action_x, action_y), size: vec2(width, title_height) });
+                action_x += width + Self::ACTION_SPACING;
+            }
+        }
+        self.view.end_view(cx);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    use zaplib::*;
+
+    #[test]
+    fn test_queue_and_close() {
+        let mut notifications = Notifications::default().with_max_visible(1);
+        let mut test_cx = TestCx::new(vec2(400., 300.));
+        let first = notifications.push(&mut test_cx.cx, Notification::new("First", "Hello").with_duration(None));
+        let second = notifications.push(&mut test_cx.cx, Notification::new("Second", "World").with_action("Undo"));
+        assert_eq!(notifications.len(), 2);
+        test_cx.draw(|cx| notifications.draw(cx));
+
+        // Clicking the close button of the first notification closes it after fading out.
+        let close_pos = notifications.toasts[0].close_rect.unwrap().pos + vec2(2., 2.);
+        let events = test_cx.click(close_pos, |cx, event| notifications.handle(cx, event));
+        assert!(events.contains(&NotificationsEvent::Closed(first)));
+        assert_eq!(notifications.len(), 1);
+        for _ in 0..10 {
+            test_cx.next_frame(0.1, |cx, event| notifications.handle(cx, event));
+        }
+        assert_eq!(notifications.toasts.len(), 1);
+        assert!(notifications.toasts[0].visible);
+
+        test_cx.draw(|cx| notifications.draw(cx));
+        let action_pos = notifications.toasts[0].action_rects[0].pos + vec2(2., 2.);
+        let events = test_cx.click(action_pos, |cx, event| notifications.handle(cx, event));
+        assert!(events.contains(&NotificationsEvent::Action { id: second, action: 0 }));
+        assert!(notifications.is_empty());
+    }
+}
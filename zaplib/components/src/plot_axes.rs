@@ -0,0 +1,105 @@
+//! Grid lines and tick labels for the chart components.
+
+use crate::background::*;
+use zaplib::*;
+
+/// Ticks at "nice" values (multiples of 1, 2, or 5 times a power of 10) between `min` and `max`, at least
+/// `min_spacing` pixels apart on an axis that is `length` pixels long. Also returns the distance between ticks.
+pub(crate) fn nice_ticks(min: f64, max: f64, length: f64, min_spacing: f64) -> (Vec<f64>, f64) {
+    let span = max - min;
+    if span.is_nan() || span <= 0. || length <= 0. {
+        return (vec![], 1.);
+    }
+    let min_step = span * min_spacing / length;
+    let magnitude = 10_f64.powf(min_step.log10().floor());
+    let step = [1., 2., 5.].iter().map(|m| m * magnitude).find(|step| *step >= min_step).unwrap_or(magnitude * 10.);
+    // Count in steps rather than adding them up, so rounding errors don't accumulate.
+    let ticks = ((min / step).ceil() as i64..=(max / step).floor() as i64).map(|index| index as f64 * step).collect();
+    (ticks, step)
+}
+
+/// Format a tick value with as many decimals as needed to tell ticks that are `step` apart from each other.
+pub(crate) fn format_tick(value: f64, step: f64) -> String {
+    let decimals = (-step.log10().floor()).max(0.) as usize;
+    // Avoid showing "-0".
+    let value = if value.abs() < step / 2. { 0. } else { value };
+    format!("{:.*}", decimals, value)
+}
+
+/// Draws grid lines behind a plot, and tick labels to its left and below it.
+pub(crate) struct PlotAxes {
+    lines: Background,
+    pub(crate) grid_color: Vec4,
+    pub(crate) text_color: Vec4,
+}
+
+impl Default for PlotAxes {
+    fn default() -> Self {
+        Self { lines: Background::default(), grid_color: vec4(1., 1., 1., 0.08), text_color: vec4(0.62, 0.62, 0.62, 1.) }
+    }
+}
+
+impl PlotAxes {
+    /// Space to the left of the plot, for the labels of the vertical axis.
+    pub(crate) const MARGIN_LEFT: f32 = 56.;
+    /// Space below the plot, for the labels of the horizontal axis.
+    pub(crate) const MARGIN_BOTTOM: f32 = 24.;
+    const MARGIN_TOP_RIGHT: f32 = 8.;
+    const MIN_TICK_SPACING_X: f64 = 80.;
+    const MIN_TICK_SPACING_Y: f64 = 40.;
+
+    /// The part of `rect` that is left for the plot itself.
+    pub(crate) fn plot_rect(rect: Rect) -> Rect {
+        Rect {
+            pos: rect.pos + vec2(Self::MARGIN_LEFT, Self::MARGIN_TOP_RIGHT),
+            size: (rect.size - vec2(Self::MARGIN_LEFT + Self::MARGIN_TOP_RIGHT, Self::MARGIN_BOTTOM + Self::MARGIN_TOP_RIGHT))
+                .max(&Vec2::default()),
+        }
+    }
+
+    /// Draw vertical grid lines for `range` (the values at the left and right edge of `plot_rect`), with labels below.
+    pub(crate) fn draw_x(&mut self, cx: &mut Cx, plot_rect: Rect, range: (f64, f64)) {
+        let props = TextInsProps { color: self.text_color, position_anchoring: TEXT_ANCHOR_CENTER_H, ..TextInsProps::DEFAULT };
+        let (ticks, step) = nice_ticks(range.0, range.1, plot_rect.size.x as f64, Self::MIN_TICK_SPACING_X);
+        for tick in ticks {
+            let x = plot_rect.pos.x + ((tick - range.0) / (range.1 - range.0)) as f32 * plot_rect.size.x;
+            self.lines.draw(cx, Rect { pos: vec2(x, plot_rect.pos.y), size: vec2(1., plot_rect.size.y) }, self.grid_color);
+            let label_pos = vec2(x, plot_rect.pos.y + plot_rect.size.y + 4.);
+            TextIns::draw_str(cx, &format_tick(tick, step), label_pos, &props);
+        }
+    }
+
+    /// Draw horizontal grid lines for `range` (the values at the bottom and top edge of `plot_rect`), with labels to
+    /// the left.
+    pub(crate) fn draw_y(&mut self, cx: &mut Cx, plot_rect: Rect, range: (f64, f64)) {
+        let props = TextInsProps {
+            color: self.text_color,
+            position_anchoring: TEXT_ANCHOR_RIGHT + TEXT_ANCHOR_CENTER_V,
+            ..TextInsProps::DEFAULT
+        };
+        let (ticks, step) = nice_ticks(range.0, range.1, plot_rect.size.y as f64, Self::MIN_TICK_SPACING_Y);
+        for tick in ticks {
+            let y = plot_rect.pos.y + plot_rect.size.y - ((tick - range.0) / (range.1 - range.0)) as f32 * plot_rect.size.y;
+            self.lines.draw(cx, Rect { pos: vec2(plot_rect.pos.x, y), size: vec2(plot_rect.size.x, 1.) }, self.grid_color);
+            TextIns::draw_str(cx, &format_tick(tick, step), vec2(plot_rect.pos.x - 6., y), &props);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_nice_ticks() {
+        let (ticks, step) = nice_ticks(-1., 9.5, 1000., 100.);
+        assert_eq!(step, 2.);
+        assert_eq!(ticks, vec![0., 2., 4., 6., 8.]);
+        assert_eq!(format_tick(ticks[1], step), "2");
+
+        let (ticks, step) = nice_ticks(0.12, 0.5, 400., 100.);
+        assert_eq!(ticks.len(), 4);
+        assert_eq!(format_tick(ticks[0], step), "0.2");
+        assert!(nice_ticks(1., 1., 400., 100.).0.is_empty());
+    }
+}
@@ -0,0 +1,865 @@
+//! Rendering point clouds with tens of millions of points, like LIDAR scans and photogrammetry.
+//!
+//! Points are stored in an octree file, built once using [`PointCloud::build_octree`]. Every node of the octree has a
+//! sample of the points in its bounds, and its children have the points that didn't fit, so drawing only the nodes
+//! close to the camera gives a coarse version far away and full detail nearby. [`PointCloud`] loads the nodes it
+//! needs on background threads, and keeps recently used ones in memory.
+
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::f32::consts::PI;
+use std::io::{Read, Seek, SeekFrom};
+use std::sync::Arc;
+use zaplib::thread_pool::ThreadPool;
+use zaplib::universal_file::UniversalFile;
+use zaplib::*;
+
+/// Identifies point cloud octree files: "ZPC1".
+const OCTREE_MAGIC: u32 = 0x3143505A;
+/// Magic, node count, and the minimum corner and size of the root node.
+const HEADER_SIZE: usize = 24;
+/// Offset of the points (u64), point count, index of the first child, and a bit mask of which octants have children.
+const NODE_ENTRY_SIZE: usize = 20;
+/// Position (3 x f32), and color as 0x00RRGGBB (u32).
+const POINT_ENTRY_SIZE: usize = 16;
+/// Each node keeps one point per cell of a grid with this many cells along each axis, and passes the other points on
+/// to its children. The points of a node are therefore spaced roughly `1 / SAMPLING_GRID` of its size apart.
+const SAMPLING_GRID: usize = 64;
+/// Nodes with at most this many points don't get split.
+const MAX_LEAF_POINTS: usize = 20_000;
+/// Stop splitting nodes at this depth, e.g. when a lot of points are at the same position.
+const MAX_DEPTH: usize = 20;
+/// Round splats are polygons with this many sides.
+const ROUND_SPLAT_SIDES: u32 = 12;
+
+/// A point to store using [`PointCloud::build_octree`].
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct PointCloudPoint {
+    pub position: Vec3,
+    /// Red, green, and blue, from 0 to 1.
+    pub color: Vec3,
+}
+
+/// The shape that every point of a [`PointCloud`] gets drawn as.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PointCloudShape {
+    Square,
+    Round,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[repr(C)]
+struct PointIns {
+    position: Vec3,
+    /// The color as `0xRRGGBB`, which floats represent exactly.
+    color: f32,
+}
+
+impl PointIns {
+    fn new(point: &PointCloudPoint) -> Self {
+        let channel = |value: f32| (value.clamp(0., 1.) * 255.).round() as u32;
+        let color = channel(point.color.x) << 16 | channel(point.color.y) << 8 | channel(point.color.z);
+        Self { position: point.position, color: color as f32 }
+    }
+}
+
+#[derive(Clone, Copy)]
+#[repr(C)]
+struct SplatUniforms {
+    viewport_size: Vec2,
+    point_size: f32,
+    edl_strength: f32,
+    edl_radius: f32,
+}
+
+/// Splats are polygons instead of quads with transparent corners, since those corners would still hide the points
+/// behind them in the depth buffer.
+fn build_square_geom() -> Geometry {
+    let vertices = vec![vec2(-1., -1.), vec2(1., -1.), vec2(1., 1.), vec2(-1., 1.)];
+    Geometry::new(vertices, vec![[0, 1, 2], [2, 3, 0]])
+}
+
+fn build_round_geom() -> Geometry {
+    let vertices: Vec<Vec2> = (0..ROUND_SPLAT_SIDES)
+        .map(|side| {
+            let angle = side as f32 / ROUND_SPLAT_SIDES as f32 * 2. * PI;
+            vec2(angle.cos(), angle.sin())
+        })
+        .collect();
+    Geometry::new(vertices, (1..ROUND_SPLAT_SIDES - 1).map(|side| [0, side, side + 1]).collect())
+}
+
+const SPLAT_SHADER: CodeFragment = code_fragment!(
+    r#"
+    uniform viewport_size: vec2;
+    uniform point_size: float;
+    uniform edl_strength: float;
+    uniform edl_radius: float;
+
+    geometry geom: vec2;
+
+    instance in_position: vec3;
+    instance in_color: float;
+
+    varying view_depth: float;
+
+    // The corner `geom` (from -1 to 1) of a splat that is `point_size` pixels wide.
+    fn splat_position() -> vec4 {
+        let view_pos = camera_view * vec4(in_position, 1.);
+        view_depth = -view_pos.z;
+        let clip_pos = camera_projection * view_pos;
+        return clip_pos + vec4(geom * point_size / viewport_size * clip_pos.w, 0., 0.);
+    }
+
+    // The log2 of the depth, mapped from depths between 1/256 and 65536 to between 0 and 1.
+    fn encoded_depth() -> float {
+        return clamp((log2(max(view_depth, 0.00001)) + 8.) / 24., 0., 0.999999);
+    }
+"#
+);
+
+/// Renders the depth of the points for eye-dome lighting, packed into 24 bits of color like in
+/// [`crate::ShadowMap::CASTER_SHADER`].
+const DEPTH_SHADER: CodeFragment = code_fragment!(
+    r#"
+    fn vertex() -> vec4 {
+        return splat_position();
+    }
+
+    fn pixel() -> vec4 {
+        let packed = fract(vec3(1., 255., 65025.) * encoded_depth());
+        return vec4(packed - packed.yzz * vec3(1. / 255., 1. / 255., 0.), 1.);
+    }
+"#
+);
+
+const COLOR_SHADER: CodeFragment = code_fragment!(
+    r#"
+    texture edl_depth: texture2D;
+
+    varying splat_color: vec3;
+    varying screen_uv: vec2;
+
+    fn vertex() -> vec4 {
+        splat_color = vec3(floor(in_color / 65536.), mod(floor(in_color / 256.), 256.), mod(in_color, 256.)) / 255.;
+        let position = splat_position();
+        screen_uv = vec2(position.x / position.w * 0.5 + 0.5, 0.5 - position.y / position.w * 0.5);
+        return position;
+    }
+
+    fn edl_sample(uv: vec2) -> float {
+        // Sample texel centers, since interpolating packed depths between texels gives nonsense.
+        let size = viewport_size * dpi_factor;
+        let texel_center = (floor(uv * size) + 0.5) / size;
+        return dot(sample2d(edl_depth, texel_center).xyz, vec3(1., 1. / 255., 1. / 65025.));
+    }
+
+    fn pixel() -> vec4 {
+        if edl_strength <= 0. {
+            return vec4(splat_color, 1.);
+        }
+        // Eye-dome lighting: darken points that are further away than the points around them on the screen. This
+        // outlines edges and brings out the shape of surfaces, without needing normals.
+        let depth = encoded_depth() * 24.;
+        let response = 0.;
+        for i from 0 to 8 {
+            let angle = float(i) * 0.7853982;
+            let offset = vec2(cos(angle), sin(angle)) * edl_radius / viewport_size;
+            response += max(0., depth - edl_sample(screen_uv + offset) * 24.);
+        }
+        let shade = exp(-response / 8. * 300. * edl_strength);
+        return vec4(splat_color * shade, 1.);
+    }
+"#
+);
+
+static SQUARE_SHADER: Shader = Shader {
+    build_geom: Some(build_square_geom),
+    code_to_concatenate: &[Cx::STD_SHADER, SPLAT_SHADER, COLOR_SHADER],
+    ..Shader::DEFAULT
+};
+
+static ROUND_SHADER: Shader = Shader {
+    build_geom: Some(build_round_geom),
+    code_to_concatenate: &[Cx::STD_SHADER, SPLAT_SHADER, COLOR_SHADER],
+    ..Shader::DEFAULT
+};
+
+static SQUARE_DEPTH_SHADER: Shader = Shader {
+    build_geom: Some(build_square_geom),
+    code_to_concatenate: &[Cx::STD_SHADER, SPLAT_SHADER, DEPTH_SHADER],
+    ..Shader::DEFAULT
+};
+
+static ROUND_DEPTH_SHADER: Shader = Shader {
+    build_geom: Some(build_round_geom),
+    code_to_concatenate: &[Cx::STD_SHADER, SPLAT_SHADER, DEPTH_SHADER],
+    ..Shader::DEFAULT
+};
+
+/// A node of an octree file, as read by [`read_octree`].
+#[derive(Clone, Debug, Default, PartialEq)]
+struct OctreeNode {
+    /// The corner of the bounds of the node with the lowest coordinates.
+    min: Vec3,
+    /// The width, height, and depth of the bounds of the node.
+    size: f32,
+    /// Where the points of this node start in the file.
+    offset: u64,
+    point_count: usize,
+    children: Vec<usize>,
+}
+
+/// The offset of the child in `octant` (from 0 to 7, with bit 0 for X, bit 1 for Y, and bit 2 for Z) from the minimum
+/// corner of its parent, as a fraction of the size of the parent.
+fn octant_offset(octant: usize) -> Vec3 {
+    vec3((octant & 1) as f32, (octant >> 1 & 1) as f32, (octant >> 2 & 1) as f32) * 0.5
+}
+
+fn invalid_data(message: &str) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, message.to_string())
+}
+
+// Fields in octree files aren't aligned, so copy out the bytes instead of reading through pointers.
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+}
+
+fn read_u64(bytes: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(bytes[offset..offset + 8].try_into().unwrap())
+}
+
+fn read_f32(bytes: &[u8], offset: usize) -> f32 {
+    f32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+}
+
+/// Read the structure of an octree file; nodes come in breadth-first order, starting with the root.
+fn read_octree(file: &mut impl Read) -> std::io::Result<Vec<OctreeNode>> {
+    let mut header = [0; HEADER_SIZE];
+    file.read_exact(&mut header)?;
+    if read_u32(&header, 0) != OCTREE_MAGIC {
+        return Err(invalid_data("Not a point cloud octree file"));
+    }
+    let node_count = read_u32(&header, 4) as usize;
+    if node_count == 0 {
+        return Err(invalid_data("Point cloud octree has no nodes"));
+    }
+    // Read using `take`, so a corrupt node count doesn't allocate a huge buffer up front.
+    let mut table = vec![];
+    file.take((node_count * NODE_ENTRY_SIZE) as u64).read_to_end(&mut table)?;
+    if table.len() != node_count * NODE_ENTRY_SIZE {
+        return Err(invalid_data("Point cloud octree is truncated"));
+    }
+
+    let mut nodes: Vec<OctreeNode> = (0..node_count)
+        .map(|index| {
+            let entry = index * NODE_ENTRY_SIZE;
+            OctreeNode {
+                offset: read_u64(&table, entry),
+                point_count: read_u32(&table, entry + 8) as usize,
+                ..OctreeNode::default()
+            }
+        })
+        .collect();
+    nodes[0].min = vec3(read_f32(&header, 8), read_f32(&header, 12), read_f32(&header, 16));
+    nodes[0].size = read_f32(&header, 20);
+    for index in 0..node_count {
+        let entry = index * NODE_ENTRY_SIZE;
+        let mut child = read_u32(&table, entry + 12) as usize;
+        let child_mask = read_u32(&table, entry + 16);
+        for octant in 0..8 {
+            if child_mask & (1 << octant) == 0 {
+                continue;
+            }
+            // Children always come after their parents, so parents have their bounds before their children need them.
+            if child <= index || child >= node_count {
+                return Err(invalid_data("Point cloud octree has an invalid child node"));
+            }
+            nodes[child].min = nodes[index].min + octant_offset(octant) * nodes[index].size;
+            nodes[child].size = nodes[index].size / 2.;
+            nodes[index].children.push(child);
+            child += 1;
+        }
+    }
+    Ok(nodes)
+}
+
+/// Read the points of one node of an octree file.
+fn read_node_points(file: &mut (impl Read + Seek), node: &OctreeNode) -> std::io::Result<Vec<PointIns>> {
+    let mut data = vec![0; node.point_count * POINT_ENTRY_SIZE];
+    file.seek(SeekFrom::Start(node.offset))?;
+    file.read_exact(&mut data)?;
+    Ok(data
+        .chunks_exact(POINT_ENTRY_SIZE)
+        .map(|point| PointIns {
+            position: vec3(read_f32(point, 0), read_f32(point, 4), read_f32(point, 8)),
+            color: read_u32(point, 12) as f32,
+        })
+        .collect())
+}
+
+/// A node of the octree while building it in [`PointCloud::build_octree`].
+struct BuildNode {
+    points: Vec<PointIns>,
+    children: [Option<Box<BuildNode>>; 8],
+}
+
+fn build_node(points: Vec<PointIns>, min: Vec3, size: f32, depth: usize) -> BuildNode {
+    let mut children: [Option<Box<BuildNode>>; 8] = Default::default();
+    if points.len() <= MAX_LEAF_POINTS || depth == MAX_DEPTH {
+        return BuildNode { points, children };
+    }
+
+    let mut occupied = vec![false; SAMPLING_GRID * SAMPLING_GRID * SAMPLING_GRID];
+    let mut kept = vec![];
+    let mut octants: [Vec<PointIns>; 8] = Default::default();
+    let cell = |value: f32, min: f32| (((value - min) / size * SAMPLING_GRID as f32) as usize).min(SAMPLING_GRID - 1);
+    for point in points {
+        let (x, y, z) = (cell(point.position.x, min.x), cell(point.position.y, min.y), cell(point.position.z, min.z));
+        let index = (z * SAMPLING_GRID + y) * SAMPLING_GRID + x;
+        if !occupied[index] {
+            occupied[index] = true;
+            kept.push(point);
+        } else {
+            // Use the grid cell to pick the octant, so rounding can't put a point outside of its node.
+            let half = SAMPLING_GRID / 2;
+            octants[(x >= half) as usize | ((y >= half) as usize) << 1 | ((z >= half) as usize) << 2].push(point);
+        }
+    }
+    for (octant, octant_points) in octants.into_iter().enumerate() {
+        if !octant_points.is_empty() {
+            let child_min = min + octant_offset(octant) * size;
+            children[octant] = Some(Box::new(build_node(octant_points, child_min, size / 2., depth + 1)));
+        }
+    }
+    BuildNode { points: kept, children }
+}
+
+/// An entry in the queue of [`PointCloud::visible_nodes`], where the highest `priority` comes out first.
+struct QueuedNode {
+    priority: f32,
+    node: usize,
+}
+
+impl PartialEq for QueuedNode {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for QueuedNode {}
+
+impl PartialOrd for QueuedNode {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for QueuedNode {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.priority.partial_cmp(&other.priority).unwrap_or(Ordering::Equal)
+    }
+}
+
+/// Whether any part of the bounds of `node` might be visible with `view_projection`.
+fn in_frustum(view_projection: &Mat4, node: &OctreeNode) -> bool {
+    let corners: Vec<Vec4> = (0..8)
+        .map(|corner| {
+            let position = node.min + octant_offset(corner) * (node.size * 2.);
+            view_projection.transform_vec4(vec4(position.x, position.y, position.z, 1.))
+        })
+        .collect();
+    let outside = |test: fn(&Vec4) -> bool| corners.iter().all(test);
+    !(outside(|c| c.x < -c.w)
+        || outside(|c| c.x > c.w)
+        || outside(|c| c.y < -c.w)
+        || outside(|c| c.y > c.w)
+        || outside(|c| c.z < -c.w)
+        || outside(|c| c.z > c.w))
+}
+
+/// Sent from the loader threads when the points of a node have been read.
+struct NodeLoaded {
+    component_id: ComponentId,
+    /// The value of [`PointCloud::file_id`] when loading started, to ignore nodes of files that got replaced.
+    file_id: usize,
+    node: usize,
+    /// [`None`] if reading failed.
+    points: Option<Arc<Vec<PointIns>>>,
+}
+
+#[derive(Clone, Default)]
+struct NodeState {
+    points: Option<Arc<Vec<PointIns>>>,
+    loading: bool,
+    failed: bool,
+    /// The value of [`PointCloud::draw_counter`] when this node was last drawn.
+    last_used: u64,
+}
+
+/// Shows a point cloud from an octree file (see [`PointCloud::build_octree`]) in a 3D view, e.g. a
+/// [`crate::Viewport3D`].
+///
+/// Only the nodes of the octree that the camera needs get drawn, up to [`PointCloud::with_point_budget`] points:
+/// nodes get refined while their points are further apart on the screen than the size of the points, closest to the
+/// camera first. Nodes get loaded on background threads as they're needed, so detail streams in from coarse to fine.
+///
+/// Eye-dome lighting (EDL) darkens points that are behind the points around them on the screen, which outlines
+/// edges and shows the shape of surfaces, since scans usually don't come with normals. This draws the points twice:
+/// once into an offscreen [`Pass`] to get their depths, and once as usual.
+///
+/// ```ignore
+/// // When the user opens a file:
+/// self.point_cloud.open(cx, UniversalFile::open("scan.zpc")?)?;
+///
+/// // In `handle`:
+/// if self.point_cloud.handle(cx, event) {
+///     self.needs_draw = true;
+/// }
+///
+/// // In `draw`:
+/// self.viewport_3d.begin_draw(cx, Viewport3DProps::DEFAULT);
+/// self.point_cloud.draw(cx, &self.viewport_3d.matrix_mode());
+/// self.viewport_3d.end_draw(cx);
+/// ```
+pub struct PointCloud {
+    component_id: ComponentId,
+    file: Option<UniversalFile>,
+    /// Incremented in [`PointCloud::open`].
+    file_id: usize,
+    nodes: Vec<OctreeNode>,
+    node_states: Vec<NodeState>,
+    loader: Option<ThreadPool>,
+    loads_in_flight: usize,
+    loaded_points: usize,
+    visible_points: usize,
+    /// Counts draws, to find out which nodes were used least recently.
+    draw_counter: u64,
+    point_budget: usize,
+    cache_size: usize,
+    point_size: f32,
+    shape: PointCloudShape,
+    edl_strength: f32,
+    edl_radius: f32,
+    depth_pass: Pass,
+    depth_view: View,
+    /// The packed depths that eye-dome lighting uses.
+    edl_texture: Texture,
+    depth_texture: Texture,
+}
+
+impl Default for PointCloud {
+    fn default() -> Self {
+        Self {
+            component_id: Default::default(),
+            file: None,
+            file_id: 0,
+            nodes: vec![],
+            node_states: vec![],
+            loader: None,
+            loads_in_flight: 0,
+            loaded_points: 0,
+            visible_points: 0,
+            draw_counter: 0,
+            point_budget: 3_000_000,
+            cache_size: 15_000_000,
+            point_size: 2.,
+            shape: PointCloudShape::Square,
+            edl_strength: 1.,
+            edl_radius: 1.4,
+            depth_pass: Pass::default(),
+            depth_view: View::default(),
+            edl_texture: Texture::default(),
+            depth_texture: Texture::default(),
+        }
+    }
+}
+
+impl PointCloud {
+    /// How many nodes get loaded at the same time.
+    const MAX_LOADS: usize = 4;
+
+    /// The maximum number of points to draw; 3 million by default.
+    #[must_use]
+    pub fn with_point_budget(self, point_budget: usize) -> Self {
+        Self { point_budget, ..self }
+    }
+    /// How many points to keep in memory, including ones that aren't drawn anymore, so moving back doesn't load them
+    /// again; 15 million by default.
+    #[must_use]
+    pub fn with_cache_size(self, cache_size: usize) -> Self {
+        Self { cache_size, ..self }
+    }
+    /// The width of points, in pixels; 2 by default. Larger points cover the gaps between them with fewer points, so
+    /// less detail gets loaded.
+    #[must_use]
+    pub fn with_point_size(self, point_size: f32) -> Self {
+        Self { point_size, ..self }
+    }
+    #[must_use]
+    pub fn with_shape(self, shape: PointCloudShape) -> Self {
+        Self { shape, ..self }
+    }
+    /// How strongly eye-dome lighting darkens points; 0 turns it off, which also skips drawing the points twice.
+    #[must_use]
+    pub fn with_edl_strength(self, edl_strength: f32) -> Self {
+        Self { edl_strength, ..self }
+    }
+    /// How far away the neighbors that eye-dome lighting compares points with are, in pixels; larger values give
+    /// thicker outlines.
+    #[must_use]
+    pub fn with_edl_radius(self, edl_radius: f32) -> Self {
+        Self { edl_radius, ..self }
+    }
+
+    /// Write `points` into an octree file, to show using [`PointCloud::open`]. This keeps all points in memory, so
+    /// split up larger datasets first.
+    pub fn build_octree(points: &[PointCloudPoint]) -> Vec<u8> {
+        let mut min = Vec3::all(f32::INFINITY);
+        let mut max = Vec3::all(f32::NEG_INFINITY);
+        for point in points {
+            let position = point.position;
+            min = vec3(min.x.min(position.x), min.y.min(position.y), min.z.min(position.z));
+            max = vec3(max.x.max(position.x), max.y.max(position.y), max.z.max(position.z));
+        }
+        if points.is_empty() {
+            min = Vec3::all(0.);
+            max = Vec3::all(0.);
+        }
+        let extent = (max.x - min.x).max(max.y - min.y).max(max.z - min.z);
+        // Grow the bounds a bit, so the points at the maximum are inside.
+        let size = if extent > 0. { extent * 1.0001 } else { 1. };
+        let root = build_node(points.iter().map(PointIns::new).collect(), min, size, 0);
+
+        // Breadth-first, so the children of every node are next to each other.
+        let mut nodes: Vec<&BuildNode> = vec![&root];
+        let mut index = 0;
+        while index < nodes.len() {
+            let node = nodes[index];
+            nodes.extend(node.children.iter().flatten().map(|child| child.as_ref()));
+            index += 1;
+        }
+
+        let mut bytes = Vec::with_capacity(HEADER_SIZE + nodes.len() * NODE_ENTRY_SIZE + points.len() * POINT_ENTRY_SIZE);
+        bytes.extend_from_slice(&OCTREE_MAGIC.to_le_bytes());
+        bytes.extend_from_slice(&(nodes.len() as u32).to_le_bytes());
+        for value in [min.x, min.y, min.z, size] {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        let mut offset = (HEADER_SIZE + nodes.len() * NODE_ENTRY_SIZE) as u64;
+        let mut first_child = 1;
+        for node in &nodes {
+            let child_mask =
+                (0..8).filter(|&octant| node.children[octant].is_some()).fold(0u32, |mask, octant| mask | 1 << octant);
+            bytes.extend_from_slice(&offset.to_le_bytes());
+            bytes.extend_from_slice(&(node.points.len() as u32).to_le_bytes());
+            bytes.extend_from_slice(&(first_child as u32).to_le_bytes());
+            bytes.extend_from_slice(&child_mask.to_le_bytes());
+            offset += (node.points.len() * POINT_ENTRY_SIZE) as u64;
+            first_child += child_mask.count_ones() as usize;
+        }
+        for node in &nodes {
+            for point in &node.points {
+                for value in [point.position.x, point.position.y, point.position.z] {
+                    bytes.extend_from_slice(&value.to_le_bytes());
+                }
+                bytes.extend_from_slice(&(point.color as u32).to_le_bytes());
+            }
+        }
+        bytes
+    }
+
+    /// Show the octree file in `file` (see [`PointCloud::build_octree`]), instead of what was shown before. Only reads
+    /// the structure of the octree; points get loaded on background threads when they're drawn.
+    pub fn open(&mut self, cx: &mut Cx, mut file: UniversalFile) -> std::io::Result<()> {
+        file.seek(SeekFrom::Start(0))?;
+        self.nodes = read_octree(&mut file)?;
+        self.node_states = vec![NodeState::default(); self.nodes.len()];
+        self.file = Some(file);
+        self.file_id += 1;
+        self.loads_in_flight = 0;
+        self.loaded_points = 0;
+        self.visible_points = 0;
+        cx.request_draw();
+        Ok(())
+    }
+
+    /// The total number of points in the octree file.
+    pub fn point_count(&self) -> usize {
+        self.nodes.iter().map(|node| node.point_count).sum()
+    }
+
+    /// How many points were drawn in the last draw.
+    pub fn visible_point_count(&self) -> usize {
+        self.visible_points
+    }
+
+    /// Handle nodes that finished loading. Returns true when new points have to be drawn, in which case the
+    /// [`crate::Viewport3D`] that shows them shouldn't skip drawing (see [`crate::Viewport3D::skip_draw`]).
+    pub fn handle(&mut self, cx: &mut Cx, event: &mut Event) -> bool {
+        if let Event::UserMessage(ume) = event {
+            if let Some(loaded) = ume.get::<NodeLoaded>() {
+                if loaded.component_id == self.component_id && loaded.file_id == self.file_id {
+                    self.loads_in_flight -= 1;
+                    let state = &mut self.node_states[loaded.node];
+                    state.loading = false;
+                    state.failed = loaded.points.is_none();
+                    state.points = loaded.points.clone();
+                    self.loaded_points += loaded.points.as_ref().map_or(0, |points| points.len());
+                    cx.request_draw();
+                    return true;
+                }
+            }
+        }
+        false
+    }
+
+    /// The nodes to draw, most important first: nodes get refined as long as their points are further apart on the
+    /// screen than [`PointCloud::with_point_size`], starting with the nodes whose points are furthest apart, until the
+    /// point budget is reached. Nodes that aren't loaded yet don't get refined.
+    fn visible_nodes(&self, projection: &Mat4, view: &Mat4, viewport_size: Vec2) -> Vec<usize> {
+        if self.nodes.is_empty() {
+            return vec![];
+        }
+        // `Mat4::mul(a, b)` applies `a` first.
+        let view_projection = Mat4::mul(view, projection);
+        let camera_position = view.invert().transform_vec4(vec4(0., 0., 0., 1.)).to_vec3();
+        // How many pixels one unit is wide at a distance of one unit from the camera.
+        let pixels_per_unit = viewport_size.y / 2. * projection.v[5];
+        let point_spacing = |node: &OctreeNode| {
+            let center = node.min + Vec3::all(node.size / 2.);
+            // Use the distance to the closest possible point in the node, which is within half a diagonal.
+            let distance = (center - camera_position).length() - node.size * 0.866;
+            if distance <= 0. {
+                f32::INFINITY
+            } else {
+                node.size / SAMPLING_GRID as f32 * pixels_per_unit / distance
+            }
+        };
+
+        let mut queue = BinaryHeap::new();
+        if in_frustum(&view_projection, &self.nodes[0]) {
+            queue.push(QueuedNode { priority: point_spacing(&self.nodes[0]), node: 0 });
+        }
+        let mut visible = vec![];
+        let mut points = 0;
+        while let Some(QueuedNode { priority, node }) = queue.pop() {
+            let octree_node = &self.nodes[node];
+            if points + octree_node.point_count > self.point_budget && !visible.is_empty() {
+                break;
+            }
+            points += octree_node.point_count;
+            visible.push(node);
+            if priority <= self.point_size || self.node_states[node].points.is_none() {
+                continue;
+            }
+            for &child in &octree_node.children {
+                if in_frustum(&view_projection, &self.nodes[child]) {
+                    queue.push(QueuedNode { priority: point_spacing(&self.nodes[child]), node: child });
+                }
+            }
+        }
+        visible
+    }
+
+    /// Start loading the points of a node, if that isn't already happening and there's room for another load.
+    fn request_node(&mut self, node: usize) {
+        let state = &mut self.node_states[node];
+        if state.loading || state.failed || state.points.is_some() || self.loads_in_flight >= Self::MAX_LOADS {
+            return;
+        }
+        let mut file = match &self.file {
+            Some(file) => file.clone(),
+            None => return,
+        };
+        state.loading = true;
+        self.loads_in_flight += 1;
+
+        let octree_node = self.nodes[node].clone();
+        let (component_id, file_id) = (self.component_id, self.file_id);
+        let loader = self.loader.get_or_insert_with(|| ThreadPool::new(Self::MAX_LOADS));
+        loader.spawn(move || {
+            let points = read_node_points(&mut file, &octree_node).ok().map(Arc::new);
+            Cx::post_user_message(NodeLoaded { component_id, file_id, node, points });
+        });
+    }
+
+    /// Forget the points of the least recently drawn nodes when more than [`PointCloud::with_cache_size`] points are
+    /// loaded.
+    fn evict_nodes(&mut self) {
+        if self.loaded_points <= self.cache_size {
+            return;
+        }
+        let mut nodes: Vec<(u64, usize)> = self
+            .node_states
+            .iter()
+            .enumerate()
+            .filter(|(_, state)| state.points.is_some() && state.last_used < self.draw_counter)
+            .map(|(node, state)| (state.last_used, node))
+            .collect();
+        nodes.sort_unstable();
+        for (_, node) in nodes {
+            if self.loaded_points <= self.cache_size {
+                break;
+            }
+            if let Some(points) = self.node_states[node].points.take() {
+                self.loaded_points -= points.len();
+            }
+        }
+    }
+
+    fn draw_nodes(
+        cx: &mut Cx,
+        shader: &'static Shader,
+        nodes: &[Arc<Vec<PointIns>>],
+        uniforms: SplatUniforms,
+        edl_texture: Option<TextureHandle>,
+    ) {
+        for points in nodes {
+            let area = cx.add_instances(shader, points);
+            area.write_user_uniforms(cx, uniforms);
+            if let Some(edl_texture) = edl_texture {
+                area.write_texture_2d(cx, "edl_depth", edl_texture);
+            }
+        }
+    }
+
+    /// Draw the points that the camera of `matrix_mode` needs (see [`crate::Viewport3D::matrix_mode`]), in a 3D
+    /// view, e.g. between [`crate::Viewport3D::begin_draw`] and [`crate::Viewport3D::end_draw`]. Starts loading the
+    /// nodes that aren't loaded yet.
+    pub fn draw(&mut self, cx: &mut Cx, matrix_mode: &PassMatrixMode) {
+        self.draw_counter += 1;
+        let viewport_size = vec2(cx.get_width_total(), cx.get_height_total());
+        let (projection, view) = match matrix_mode {
+            PassMatrixMode::Ortho => {
+                (Mat4::ortho(0., viewport_size.x, 0., viewport_size.y, 100., -100., 1., 1.), Mat4::identity())
+            }
+            PassMatrixMode::Projection { fov_y, near, far, cam } => {
+                (Mat4::perspective(*fov_y, viewport_size.x / viewport_size.y, *near, *far), *cam)
+            }
+            PassMatrixMode::Custom { projection, view } => (*projection, *view),
+        };
+
+        let visible = self.visible_nodes(&projection, &view, viewport_size);
+        for &node in &visible {
+            self.node_states[node].last_used = self.draw_counter;
+            self.request_node(node);
+        }
+        self.evict_nodes();
+
+        let nodes: Vec<Arc<Vec<PointIns>>> = visible.iter().filter_map(|&node| self.node_states[node].points.clone()).collect();
+        self.visible_points = nodes.iter().map(|points| points.len()).sum();
+        if nodes.is_empty() {
+            return;
+        }
+
+        let uniforms = SplatUniforms {
+            viewport_size,
+            point_size: self.point_size,
+            edl_strength: self.edl_strength,
+            edl_radius: self.edl_radius,
+        };
+        let (shader, depth_shader) = match self.shape {
+            PointCloudShape::Square => (&SQUARE_SHADER, &SQUARE_DEPTH_SHADER),
+            PointCloudShape::Round => (&ROUND_SHADER, &ROUND_DEPTH_SHADER),
+        };
+        let mut edl_texture = None;
+        if self.edl_strength > 0. {
+            self.depth_pass.begin_pass_without_textures(cx);
+            self.depth_pass.set_size(cx, viewport_size);
+            let edl_texture_handle = self.edl_texture.get_color(cx);
+            // White unpacks to more than the maximum depth, so empty space never darkens points.
+            self.depth_pass.add_color_texture(cx, edl_texture_handle, ClearColor::ClearWith(Vec4::all(1.)));
+            let depth_texture_handle = self.depth_texture.get_depth(cx);
+            self.depth_pass.set_depth_texture(cx, depth_texture_handle, ClearDepth::ClearWith(1.));
+            self.depth_pass.set_matrix_mode(cx, matrix_mode.clone());
+            self.depth_view.begin_view(cx, LayoutSize::FILL);
+            Self::draw_nodes(cx, depth_shader, &nodes, uniforms, None);
+            self.depth_view.end_view(cx);
+            self.depth_pass.end_pass(cx);
+            edl_texture = Some(edl_texture_handle);
+        }
+        Self::draw_nodes(cx, shader, &nodes, uniforms, edl_texture);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::viewport3d::look_at;
+    use std::io::Cursor;
+
+    /// A flat square of 320 by 320 points, 0.1 apart, on the ground.
+    fn ground_points() -> Vec<PointCloudPoint> {
+        (0..320 * 320)
+            .map(|index| PointCloudPoint {
+                position: vec3((index % 320) as f32 * 0.1, 0., (index / 320) as f32 * 0.1),
+                color: vec3(1., 0.5, (index % 2) as f32),
+            })
+            .collect()
+    }
+
+    fn open_in_memory(bytes: &[u8]) -> PointCloud {
+        let nodes = read_octree(&mut Cursor::new(bytes)).unwrap();
+        // Pretend every node is loaded.
+        let node_states = vec![NodeState { points: Some(Arc::new(vec![])), ..NodeState::default() }; nodes.len()];
+        PointCloud { nodes, node_states, ..PointCloud::default() }
+    }
+
+    #[test]
+    fn test_build_octree() {
+        let points = ground_points();
+        let bytes = PointCloud::build_octree(&points);
+        let nodes = read_octree(&mut Cursor::new(&bytes)).unwrap();
+        assert!(nodes.len() > 1);
+        assert_eq!(nodes.iter().map(|node| node.point_count).sum::<usize>(), points.len());
+        // The root has one point for every occupied cell of its sampling grid.
+        assert_eq!(nodes[0].point_count, SAMPLING_GRID * SAMPLING_GRID);
+        assert_eq!(nodes[0].min, vec3(0., 0., 0.));
+
+        let mut file = Cursor::new(&bytes);
+        for node in &nodes {
+            for point in read_node_points(&mut file, node).unwrap() {
+                let max = node.min + Vec3::all(node.size);
+                let position = point.position;
+                assert!(position.x >= node.min.x && position.y >= node.min.y && position.z >= node.min.z);
+                assert!(position.x < max.x && position.y < max.y && position.z < max.z);
+            }
+        }
+        let root_points = read_node_points(&mut file, &nodes[0]).unwrap();
+        assert_eq!(root_points[0], PointIns::new(&points[0]));
+        assert_eq!(PointIns::new(&points[1]).color, 0xff80ff as f32);
+
+        assert!(read_octree(&mut Cursor::new(b"not a point cloud at all")).is_err());
+        assert!(read_octree(&mut Cursor::new(&bytes[..HEADER_SIZE + 10])).is_err());
+        let empty = PointCloud::build_octree(&[]);
+        assert_eq!(read_octree(&mut Cursor::new(&empty)).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_visible_nodes() {
+        let point_cloud = open_in_memory(&PointCloud::build_octree(&ground_points()));
+        let viewport_size = vec2(800., 600.);
+        let projection = Mat4::perspective(40., viewport_size.x / viewport_size.y, 0.1, 10000.);
+        let center = vec3(16., 0., 16.);
+
+        // From far away only the root is needed.
+        let far = look_at(center + vec3(0., 3000., 1.), center, vec3(0., 1., 0.));
+        assert_eq!(point_cloud.visible_nodes(&projection, &far, viewport_size), vec![0]);
+
+        // Up close everything near the camera gets refined, but not what's behind it.
+        let close = look_at(vec3(1., 2., 1.), vec3(0., 0., 0.), vec3(0., 1., 0.));
+        let visible = point_cloud.visible_nodes(&projection, &close, viewport_size);
+        assert!(visible.len() > 1);
+        assert!(visible.len() < point_cloud.nodes.len());
+        assert_eq!(visible[0], 0);
+
+        // Looking away from the points shows nothing.
+        let away = look_at(vec3(16., 2., -10.), vec3(16., 2., -20.), vec3(0., 1., 0.));
+        assert!(point_cloud.visible_nodes(&projection, &away, viewport_size).is_empty());
+
+        // The root is always shown, even when it doesn't fit in the budget.
+        let point_cloud = PointCloud { point_budget: 10, ..point_cloud };
+        assert_eq!(point_cloud.visible_nodes(&projection, &close, viewport_size), vec![0]);
+    }
+}
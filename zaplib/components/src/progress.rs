@@ -0,0 +1,210 @@
+//! Progress bars and spinners, for showing that something is happening.
+
+use zaplib::*;
+
+#[derive(Clone, Copy, Default)]
+#[repr(C)]
+struct ProgressBarIns {
+    base: QuadIns,
+    color: Vec4,
+    track_color: Vec4,
+    /// From 0 to 1, or negative for indeterminate progress.
+    progress: f32,
+}
+
+static PROGRESS_BAR_SHADER: Shader = Shader {
+    build_geom: Some(QuadIns::build_geom),
+    code_to_concatenate: &[
+        Cx::STD_SHADER,
+        QuadIns::SHADER,
+        code_fragment!(
+            r#"
+            uniform time: float;
+            instance color: vec4;
+            instance track_color: vec4;
+            instance progress: float;
+
+            fn pixel() -> vec4 {
+                let df = Df::viewport(pos * rect_size);
+                let radius = rect_size.y / 2.;
+                df.box(vec2(0.), rect_size, radius);
+                df.fill(track_color);
+                df.new_path();
+
+                let start = 0.;
+                let end = progress;
+                if progress < 0. {
+                    // A segment of a third of the width that slides across repeatedly.
+                    let t = mod(time * 0.7, 1.);
+                    start = t * 1.33 - 0.33;
+                    end = start + 0.33;
+                }
+                start = clamp(start, 0., 1.) * rect_size.x;
+                end = clamp(end, 0., 1.) * rect_size.x;
+                if end - start > 0.5 {
+                    df.box(vec2(start, 0.), vec2(end - start, rect_size.y), radius);
+                    df.fill(color);
+                }
+                return df.result;
+            }"#
+        ),
+    ],
+    ..Shader::DEFAULT
+};
+
+/// A horizontal bar that fills up as progress is made, or shows an animation when the progress is unknown.
+pub struct ProgressBar {
+    area: Area,
+    color: Vec4,
+    track_color: Vec4,
+    height: f32,
+    /// Whether the last draw was indeterminate, so we need to keep animating.
+    indeterminate: bool,
+}
+
+impl Default for ProgressBar {
+    fn default() -> Self {
+        Self {
+            area: Area::Empty,
+            color: vec4(0.27, 0.56, 0.93, 1.),
+            track_color: vec4(1., 1., 1., 0.1),
+            height: 6.,
+            indeterminate: false,
+        }
+    }
+}
+
+impl ProgressBar {
+    #[must_use]
+    pub fn with_color(self, color: Vec4) -> Self {
+        Self { color, ..self }
+    }
+    #[must_use]
+    pub fn with_track_color(self, track_color: Vec4) -> Self {
+        Self { track_color, ..self }
+    }
+    #[must_use]
+    pub fn with_height(self, height: f32) -> Self {
+        Self { height, ..self }
+    }
+
+    /// Keep animating while indeterminate, until the bar is no longer drawn.
+    fn animate(&mut self, cx: &mut Cx) {
+        if self.indeterminate && self.area.get_rect_for_first_instance(cx).is_some() {
+            self.area.write_user_uniforms(cx, cx.last_event_time as f32);
+            cx.request_next_frame();
+        }
+    }
+
+    pub fn handle(&mut self, cx: &mut Cx, event: &mut Event) {
+        if let Event::NextFrame = event {
+            self.animate(cx);
+        }
+    }
+
+    /// Draw the bar filling up the available width. `progress` goes from 0 to 1, or is [`None`] when it's unknown
+    /// how long things will take.
+    pub fn draw(&mut self, cx: &mut Cx, progress: Option<f32>) {
+        let rect = cx.add_box(LayoutSize::new(Width::Fill, Height::Fix(self.height)));
+        self.indeterminate = progress.is_none();
+        self.area = cx.add_instances(
+            &PROGRESS_BAR_SHADER,
+            &[ProgressBarIns {
+                base: QuadIns::from_rect(rect),
+                color: self.color,
+                track_color: self.track_color,
+                progress: progress.map_or(-1., |progress| progress.clamp(0., 1.)),
+            }],
+        );
+        self.animate(cx);
+    }
+}
+
+#[derive(Clone, Copy, Default)]
+#[repr(C)]
+struct SpinnerIns {
+    base: QuadIns,
+    color: Vec4,
+}
+
+static SPINNER_SHADER: Shader = Shader {
+    build_geom: Some(QuadIns::build_geom),
+    code_to_concatenate: &[
+        Cx::STD_SHADER,
+        QuadIns::SHADER,
+        code_fragment!(
+            r#"
+            uniform time: float;
+            instance color: vec4;
+
+            fn pixel() -> vec4 {
+                let df = Df::viewport(pos * rect_size);
+                let stroke_width = rect_size.x / 8.;
+                let center = rect_size / 2.;
+                let radius = rect_size.x / 2. - stroke_width;
+                df.circle(center, radius);
+                df.stroke(vec4(color.rgb, color.a * 0.2), stroke_width);
+                df.new_path();
+
+                // An arc that rotates, and grows and shrinks.
+                let angle_start = mod(time * 5., 2. * PI);
+                let angle_end = angle_start + PI * (0.6 + 0.4 * sin(time * 2.));
+                if angle_end > 2. * PI {
+                    df.arc(center, radius, angle_start, 2. * PI);
+                    df.stroke(color, stroke_width);
+                    df.new_path();
+                    df.arc(center, radius, 0., angle_end - 2. * PI);
+                } else {
+                    df.arc(center, radius, angle_start, angle_end);
+                }
+                df.stroke(color, stroke_width);
+                return df.result;
+            }"#
+        ),
+    ],
+    ..Shader::DEFAULT
+};
+
+/// A spinning circle, for when it's unknown how long things will take.
+pub struct Spinner {
+    area: Area,
+    color: Vec4,
+    size: f32,
+}
+
+impl Default for Spinner {
+    fn default() -> Self {
+        Self { area: Area::Empty, color: vec4(0.9, 0.9, 0.9, 1.), size: 16. }
+    }
+}
+
+impl Spinner {
+    #[must_use]
+    pub fn with_color(self, color: Vec4) -> Self {
+        Self { color, ..self }
+    }
+    #[must_use]
+    pub fn with_size(self, size: f32) -> Self {
+        Self { size, ..self }
+    }
+
+    /// Keep animating until the spinner is no longer drawn.
+    fn animate(&mut self, cx: &mut Cx) {
+        if self.area.get_rect_for_first_instance(cx).is_some() {
+            self.area.write_user_uniforms(cx, cx.last_event_time as f32);
+            cx.request_next_frame();
+        }
+    }
+
+    pub fn handle(&mut self, cx: &mut Cx, event: &mut Event) {
+        if let Event::NextFrame = event {
+            self.animate(cx);
+        }
+    }
+
+    pub fn draw(&mut self, cx: &mut Cx) {
+        let rect = cx.add_box(LayoutSize::new(Width::Fix(self.size), Height::Fix(self.size)));
+        self.area = cx.add_instances(&SPINNER_SHADER, &[SpinnerIns { base: QuadIns::from_rect(rect), color: self.color }]);
+        self.animate(cx);
+    }
+}
@@ -0,0 +1,277 @@
+//! Shadows from a directional light, using a shadow map.
+
+use crate::viewport3d::look_at;
+use zaplib::*;
+
+/// A light that shines in one direction everywhere, like the sun. Shadows are only cast within `radius` of `center`,
+/// so keep that as small as the scene allows, for sharper shadows.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DirectionalLight {
+    /// The direction that the light travels in; e.g. pointing down for light from straight above.
+    pub direction: Vec3,
+    pub center: Vec3,
+    pub radius: f32,
+}
+
+impl DirectionalLight {
+    /// The projection and view matrices of the light, as used while drawing into a [`ShadowMap`]. Depth goes from
+    /// the side of the sphere facing the light to the other side.
+    pub fn matrices(&self) -> (Mat4, Mat4) {
+        let direction = self.direction.normalize();
+        // Any up vector works, as long as it's not parallel to the direction.
+        let up = if direction.x.abs() < 0.01 && direction.z.abs() < 0.01 { vec3(0., 0., 1.) } else { vec3(0., 1., 0.) };
+        let view = look_at(self.center - direction * self.radius, self.center, up);
+        let mut projection = Mat4::identity();
+        projection.v[0] = 1. / self.radius;
+        projection.v[5] = 1. / self.radius;
+        projection.v[10] = -1. / self.radius;
+        projection.v[14] = -1.;
+        (projection, view)
+    }
+
+    /// Maps world coordinates to the clip space of the light; the `shadow_matrix` uniform of
+    /// [`ShadowMap::RECEIVER_SHADER`].
+    pub fn shadow_matrix(&self) -> Mat4 {
+        let (projection, view) = self.matrices();
+        // `Mat4::mul(a, b)` applies `a` first.
+        Mat4::mul(&view, &projection)
+    }
+}
+
+/// The uniforms declared in [`ShadowMap::RECEIVER_SHADER`]; see [`ShadowMap::uniforms`].
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct ShadowUniforms {
+    pub shadow_matrix: Mat4,
+    pub shadow_map_size: f32,
+    pub shadow_bias: f32,
+    pub shadow_strength: f32,
+    pub shadow_pcf_radius: f32,
+}
+
+/// Renders the depth of the scene as seen from a [`DirectionalLight`] into a texture, which other shaders then use to
+/// darken what's in shadow.
+///
+/// Shadow casters get drawn twice: once between [`ShadowMap::begin_draw`] and [`ShadowMap::end_draw`] with a shader
+/// that includes [`ShadowMap::CASTER_SHADER`], and once as usual. Shaders that receive shadows include
+/// [`ShadowMap::RECEIVER_SHADER`], and get the shadow map using [`ShadowMap::bind`]:
+///
+/// ```ignore
+/// static CASTER_SHADER: Shader = Shader {
+///     code_to_concatenate: &[Cx::STD_SHADER, ShadowMap::CASTER_SHADER, code_fragment!(r#"
+///         geometry position: vec3;
+///         fn vertex() -> vec4 { return shadow_caster_position(position); }
+///         fn pixel() -> vec4 { return shadow_caster_pixel(); }
+///     "#)],
+///     ..Shader::DEFAULT
+/// };
+/// static MESH_SHADER: Shader = Shader {
+///     code_to_concatenate: &[Cx::STD_SHADER, ShadowMap::RECEIVER_SHADER, code_fragment!(r#"
+///         geometry position: vec3;
+///         varying world_pos: vec3;
+///         fn vertex() -> vec4 {
+///             world_pos = position;
+///             return camera_projection * camera_view * vec4(position, 1.);
+///         }
+///         fn pixel() -> vec4 { return vec4(vec3(0.8) * shadow_factor(world_pos), 1.); }
+///     "#)],
+///     ..Shader::DEFAULT
+/// };
+///
+/// // In `draw`:
+/// self.shadow_map.begin_draw(cx, &self.light);
+/// cx.add_mesh_instances(&CASTER_SHADER, &instances, geometry.clone());
+/// self.shadow_map.end_draw(cx);
+///
+/// self.viewport_3d.begin_draw(cx, Viewport3DProps::DEFAULT);
+/// let area = cx.add_mesh_instances(&MESH_SHADER, &instances, geometry);
+/// self.shadow_map.bind(cx, &area);
+/// self.viewport_3d.end_draw(cx);
+/// ```
+///
+/// Depth is packed into the color channels of the texture, so this works on every platform, including WebGL.
+pub struct ShadowMap {
+    pass: Pass,
+    color_texture: Texture,
+    depth_texture: Texture,
+    view: View,
+    resolution: usize,
+    bias: f32,
+    strength: f32,
+    pcf_radius: f32,
+    shadow_matrix: Mat4,
+}
+
+impl Default for ShadowMap {
+    fn default() -> Self {
+        Self {
+            pass: Pass::default(),
+            color_texture: Texture::default(),
+            depth_texture: Texture::default(),
+            view: View::default(),
+            resolution: 2048,
+            bias: 0.002,
+            strength: 0.6,
+            pcf_radius: 1.,
+            shadow_matrix: Mat4::identity(),
+        }
+    }
+}
+
+impl ShadowMap {
+    /// Helpers for shaders that draw shadow casters into a [`ShadowMap`]:
+    /// * `shadow_caster_position(world_pos: vec3) -> vec4`: call this from `vertex()` and return its result.
+    /// * `shadow_caster_pixel() -> vec4`: return this from `pixel()`.
+    pub const CASTER_SHADER: CodeFragment = code_fragment!(
+        r#"
+        varying shadow_depth: float;
+
+        fn shadow_caster_position(world_pos: vec3) -> vec4 {
+            let clip_pos = camera_projection * camera_view * vec4(world_pos, 1.);
+            shadow_depth = clip_pos.z / clip_pos.w * 0.5 + 0.5;
+            return clip_pos;
+        }
+
+        // Packs the depth into 24 bits of color. Alpha stays 1, so blending doesn't change it.
+        fn shadow_caster_pixel() -> vec4 {
+            let packed = fract(vec3(1., 255., 65025.) * clamp(shadow_depth, 0., 0.999999));
+            return vec4(packed - packed.yzz * vec3(1. / 255., 1. / 255., 0.), 1.);
+        }
+    "#
+    );
+
+    /// Helpers for shaders that receive shadows, which need the texture and uniforms set using [`ShadowMap::bind`]:
+    /// * `shadow_factor(world_pos: vec3) -> float`: 1 where `world_pos` is lit, and lower where it's in shadow,
+    ///   using percentage-closer filtering (PCF) over 3x3 texels for soft edges.
+    /// * `shadow_sample(uv: vec2) -> float`: the depth stored in the shadow map at `uv`, from 0 to 1.
+    pub const RECEIVER_SHADER: CodeFragment = code_fragment!(
+        r#"
+        texture shadow_map: texture2D;
+        uniform shadow_matrix: mat4;
+        uniform shadow_map_size: float;
+        uniform shadow_bias: float;
+        uniform shadow_strength: float;
+        uniform shadow_pcf_radius: float;
+
+        fn shadow_sample(uv: vec2) -> float {
+            // Sample texel centers, since interpolating packed depths between texels gives nonsense.
+            let texel_center = (floor(uv * shadow_map_size) + 0.5) / shadow_map_size;
+            return dot(sample2d(shadow_map, texel_center).xyz, vec3(1., 1. / 255., 1. / 65025.));
+        }
+
+        fn shadow_factor(world_pos: vec3) -> float {
+            let light_pos = shadow_matrix * vec4(world_pos, 1.);
+            let ndc = light_pos.xyz / light_pos.w;
+            let uv = vec2(ndc.x * 0.5 + 0.5, 0.5 - ndc.y * 0.5);
+            let depth = ndc.z * 0.5 + 0.5 - shadow_bias;
+            if uv.x < 0. || uv.x > 1. || uv.y < 0. || uv.y > 1. || depth > 1. {
+                return 1.;
+            }
+            let lit = 0.;
+            for y from 0 to 3 {
+                for x from 0 to 3 {
+                    let offset = vec2(float(x) - 1., float(y) - 1.) * shadow_pcf_radius / shadow_map_size;
+                    lit += step(depth, shadow_sample(uv + offset));
+                }
+            }
+            return 1. - shadow_strength * (1. - lit / 9.);
+        }
+    "#
+    );
+
+    /// The width and height of the shadow map texture, in pixels; 2048 by default.
+    #[must_use]
+    pub fn with_resolution(self, resolution: usize) -> Self {
+        Self { resolution, ..self }
+    }
+    /// How much closer a surface has to be to the light to cast a shadow, in the 0 to 1 depth range of the light.
+    /// Too little makes surfaces shadow themselves in stripes ("shadow acne"); too much detaches shadows from the
+    /// objects that cast them.
+    #[must_use]
+    pub fn with_bias(self, bias: f32) -> Self {
+        Self { bias, ..self }
+    }
+    /// How dark shadows are, from 0 (invisible) to 1 (black).
+    #[must_use]
+    pub fn with_strength(self, strength: f32) -> Self {
+        Self { strength, ..self }
+    }
+    /// How far apart the filtered texels are, in texels; larger values give softer shadow edges.
+    #[must_use]
+    pub fn with_pcf_radius(self, pcf_radius: f32) -> Self {
+        Self { pcf_radius, ..self }
+    }
+
+    /// Start drawing shadow casters as seen from `light`, into an offscreen [`Pass`] that gets rendered before the
+    /// pass that this is called in.
+    pub fn begin_draw(&mut self, cx: &mut Cx, light: &DirectionalLight) {
+        self.shadow_matrix = light.shadow_matrix();
+        self.pass.begin_pass_without_textures(cx);
+        // The texture should have exactly `resolution` pixels, regardless of the screen.
+        self.pass.override_dpi_factor(cx, 1.);
+        self.pass.set_size(cx, Vec2::all(self.resolution as f32));
+        let color_texture_handle = self.color_texture.get_color(cx);
+        // White unpacks to more than the maximum depth, so nothing shadows empty space.
+        self.pass.add_color_texture(cx, color_texture_handle, ClearColor::ClearWith(Vec4::all(1.)));
+        let depth_texture_handle = self.depth_texture.get_depth(cx);
+        self.pass.set_depth_texture(cx, depth_texture_handle, ClearDepth::ClearWith(1.));
+        let (projection, view) = light.matrices();
+        self.pass.set_matrix_mode(cx, PassMatrixMode::Custom { projection, view });
+        self.view.begin_view(cx, LayoutSize::FILL);
+    }
+
+    pub fn end_draw(&mut self, cx: &mut Cx) {
+        self.view.end_view(cx);
+        self.pass.end_pass(cx);
+    }
+
+    /// The uniforms for [`ShadowMap::RECEIVER_SHADER`]. Use this instead of [`ShadowMap::bind`] when a shader has
+    /// other uniforms as well, by putting these first in its uniforms struct, and including
+    /// [`ShadowMap::RECEIVER_SHADER`] before the code that declares the other uniforms.
+    pub fn uniforms(&self) -> ShadowUniforms {
+        ShadowUniforms {
+            shadow_matrix: self.shadow_matrix,
+            shadow_map_size: self.resolution as f32,
+            shadow_bias: self.bias,
+            shadow_strength: self.strength,
+            shadow_pcf_radius: self.pcf_radius,
+        }
+    }
+
+    /// Give the draw call of `area`, whose shader includes [`ShadowMap::RECEIVER_SHADER`], the shadow map texture.
+    /// Also sets its uniforms, so the shader shouldn't declare any uniforms of its own; otherwise write
+    /// [`ShadowMap::uniforms`] along with them.
+    pub fn bind(&mut self, cx: &mut Cx, area: &Area) {
+        self.bind_texture(cx, area);
+        area.write_user_uniforms(cx, self.uniforms());
+    }
+
+    /// Give the draw call of `area` the shadow map texture, without setting uniforms.
+    pub fn bind_texture(&mut self, cx: &mut Cx, area: &Area) {
+        let color_texture_handle = self.color_texture.get_color(cx);
+        area.write_texture_2d(cx, "shadow_map", color_texture_handle);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_directional_light_matrices() {
+        let light = DirectionalLight { direction: vec3(0., -1., 0.), center: vec3(0., 0., 0.), radius: 10. };
+        let shadow_matrix = light.shadow_matrix();
+        let (projection, view) = light.matrices();
+        let to_light = |position: Vec3| shadow_matrix.transform_vec4(vec4(position.x, position.y, position.z, 1.)).to_vec3();
+
+        let position = vec4(3., 4., 5., 1.);
+        let expected = projection.transform_vec4(view.transform_vec4(position)).to_vec3();
+        assert!((to_light(position.to_vec3()) - expected).length() < 1e-5);
+
+        // The center ends up in the middle of the map, and things closer to the light have a smaller depth.
+        assert!(to_light(vec3(0., 0., 0.)).length() < 1e-5);
+        assert!((to_light(vec3(0., 10., 0.)).z + 1.).abs() < 1e-5);
+        assert!((to_light(vec3(0., -10., 0.)).z - 1.).abs() < 1e-5);
+        assert!((to_light(vec3(10., 0., 0.)).x.abs() - 1.).abs() < 1e-5);
+    }
+}
@@ -0,0 +1,546 @@
+//! Skeletal animation: meshes whose vertices follow a hierarchy of joints, posed by animation clips.
+//!
+//! A [`SkinnedMesh`] combines vertices with a [`Skeleton`] and [`AnimationClip`]s, and is typically loaded from a glTF
+//! file using `SkinnedMesh::from_glb` (which requires the `serde-support` feature). An [`AnimationPlayer`] plays the
+//! clips, and [`SkinnedMesh::draw`] draws the mesh in the resulting pose, moving the vertices along with their joints
+//! in the vertex shader ("palette skinning").
+
+use zaplib::*;
+
+/// The translation, rotation, and scale of a [`Joint`], relative to its parent.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct JointTransform {
+    pub translation: Vec3,
+    pub rotation: Quat,
+    pub scale: Vec3,
+}
+
+impl JointTransform {
+    pub const IDENTITY: Self =
+        Self { translation: vec3(0., 0., 0.), rotation: Quat { a: 0., b: 0., c: 0., d: 1. }, scale: vec3(1., 1., 1.) };
+
+    /// A matrix that scales, then rotates, and then translates.
+    pub fn to_mat4(&self) -> Mat4 {
+        let x = self.rotation.rotate_vec(vec3(self.scale.x, 0., 0.));
+        let y = self.rotation.rotate_vec(vec3(0., self.scale.y, 0.));
+        let z = self.rotation.rotate_vec(vec3(0., 0., self.scale.z));
+        let t = self.translation;
+        Mat4 { v: [x.x, x.y, x.z, 0., y.x, y.y, y.z, 0., z.x, z.y, z.z, 0., t.x, t.y, t.z, 1.] }
+    }
+}
+
+impl Default for JointTransform {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+/// One joint (or "bone") of a [`Skeleton`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct Joint {
+    pub name: String,
+    /// The index of the parent joint in [`Skeleton::joints`], or [`None`] for root joints.
+    pub parent: Option<usize>,
+    /// The transform relative to the parent when no [`AnimationClip`] changes it.
+    pub rest_pose: JointTransform,
+    /// Transforms mesh coordinates into the coordinates of this joint in the pose that the mesh was modeled in.
+    pub inverse_bind_matrix: Mat4,
+}
+
+/// A hierarchy of [`Joint`]s that move the vertices of a [`SkinnedMesh`].
+///
+/// A pose of a skeleton is a `Vec<JointTransform>` with a transform for every joint, like the one returned by
+/// [`Skeleton::rest_pose`], and changed by [`AnimationClip::sample`].
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Skeleton {
+    joints: Vec<Joint>,
+    /// Indices of [`Skeleton::joints`], with every parent before its children.
+    order: Vec<usize>,
+}
+
+impl Skeleton {
+    /// Panics if the parents of `joints` form a cycle.
+    pub fn new(joints: Vec<Joint>) -> Self {
+        let mut order = Vec::with_capacity(joints.len());
+        let mut ordered = vec![false; joints.len()];
+        while order.len() < joints.len() {
+            let previous_len = order.len();
+            for (index, joint) in joints.iter().enumerate() {
+                if !ordered[index] && joint.parent.map_or(true, |parent| ordered[parent]) {
+                    order.push(index);
+                    ordered[index] = true;
+                }
+            }
+            assert!(order.len() > previous_len, "The parents of the joints in a Skeleton form a cycle");
+        }
+        Self { joints, order }
+    }
+
+    pub fn joints(&self) -> &[Joint] {
+        &self.joints
+    }
+
+    pub fn joint_index(&self, name: &str) -> Option<usize> {
+        self.joints.iter().position(|joint| joint.name == name)
+    }
+
+    /// The pose in which every joint has its [`Joint::rest_pose`].
+    pub fn rest_pose(&self) -> Vec<JointTransform> {
+        self.joints.iter().map(|joint| joint.rest_pose).collect()
+    }
+
+    /// The transform of every joint relative to the mesh, i.e. including the transforms of its parents.
+    pub fn world_matrices(&self, pose: &[JointTransform]) -> Vec<Mat4> {
+        let mut world_matrices = vec![Mat4::identity(); self.joints.len()];
+        for &index in &self.order {
+            let local = pose[index].to_mat4();
+            world_matrices[index] = match self.joints[index].parent {
+                // `Mat4::mul(a, b)` applies `a` first.
+                Some(parent) => Mat4::mul(&local, &world_matrices[parent]),
+                None => local,
+            };
+        }
+        world_matrices
+    }
+
+    /// The matrices that move vertices from where they were modeled to where they are in `pose`, for passing to
+    /// [`SkinnedMesh::draw`].
+    pub fn joint_matrices(&self, pose: &[JointTransform]) -> Vec<Mat4> {
+        self.world_matrices(pose)
+            .iter()
+            .zip(&self.joints)
+            .map(|(world_matrix, joint)| Mat4::mul(&joint.inverse_bind_matrix, world_matrix))
+            .collect()
+    }
+}
+
+/// How values change between the key frames of an [`AnimationChannel`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Interpolation {
+    Linear,
+    /// Keep the value of the previous key frame until the next one.
+    Step,
+}
+
+/// The key frame values of an [`AnimationChannel`], and which property of the joint they change.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ChannelValues {
+    Translation(Vec<Vec3>),
+    Rotation(Vec<Quat>),
+    Scale(Vec<Vec3>),
+}
+
+/// Key frames for one property of one joint.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AnimationChannel {
+    /// The index of the joint in [`Skeleton::joints`].
+    pub joint: usize,
+    pub interpolation: Interpolation,
+    /// The time of each key frame in seconds, in increasing order.
+    pub times: Vec<f32>,
+    /// One value for each of [`AnimationChannel::times`].
+    pub values: ChannelValues,
+}
+
+impl AnimationChannel {
+    /// The key frames before and after `time`, and how far to interpolate between them.
+    fn key_frames(&self, time: f32) -> (usize, usize, f32) {
+        let next = self.times.partition_point(|key_frame_time| *key_frame_time <= time);
+        if next == 0 {
+            return (0, 0, 0.);
+        }
+        if next == self.times.len() {
+            return (next - 1, next - 1, 0.);
+        }
+        let previous = next - 1;
+        match self.interpolation {
+            Interpolation::Linear => (previous, next, (time - self.times[previous]) / (self.times[next] - self.times[previous])),
+            Interpolation::Step => (previous, previous, 0.),
+        }
+    }
+
+    fn sample(&self, time: f32, pose: &mut [JointTransform]) {
+        if self.times.is_empty() {
+            return;
+        }
+        let (previous, next, f) = self.key_frames(time);
+        let transform = &mut pose[self.joint];
+        match &self.values {
+            ChannelValues::Translation(values) => transform.translation = Vec3::from_lerp(values[previous], values[next], f),
+            ChannelValues::Rotation(values) => transform.rotation = Quat::from_slerp(values[previous], values[next], f),
+            ChannelValues::Scale(values) => transform.scale = Vec3::from_lerp(values[previous], values[next], f),
+        }
+    }
+}
+
+/// An animation of a [`Skeleton`], like walking or waving.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct AnimationClip {
+    pub name: String,
+    /// In seconds.
+    pub duration: f32,
+    pub channels: Vec<AnimationChannel>,
+}
+
+impl AnimationClip {
+    /// Change the joints in `pose` that this clip animates to how they are at `time` seconds into the clip. Other
+    /// joints keep their transform, so clips that animate different joints can be combined.
+    pub fn sample(&self, time: f32, pose: &mut [JointTransform]) {
+        for channel in &self.channels {
+            channel.sample(time, pose);
+        }
+    }
+}
+
+/// A vertex of a [`SkinnedMesh`], which moves along with up to four joints.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[repr(C)]
+pub struct SkinnedVertex {
+    pub position: Vec3,
+    pub normal: Vec3,
+    /// Indices into [`Skeleton::joints`]. These are floats, since shaders don't support integer attributes.
+    pub joints: Vec4,
+    /// How much each of [`SkinnedVertex::joints`] moves this vertex; these should add up to 1.
+    pub weights: Vec4,
+}
+
+impl SkinnedVertex {
+    /// Geometry fields corresponding to the fields in [`SkinnedVertex`], and the joint matrices as uniforms (see
+    /// [`SkinningUniforms`]), for writing custom shaders for skinned meshes:
+    /// * `skin_matrix() -> mat4`: transforms `geom_pos` and `geom_normal` from where they were modeled to where they
+    ///   are in the current pose.
+    pub const SHADER: CodeFragment = code_fragment!(
+        r#"
+        geometry geom_pos: vec3;
+        geometry geom_normal: vec3;
+        geometry geom_joints: vec4;
+        geometry geom_weights: vec4;
+
+        uniform joint_matrices: mat4[48];
+
+        fn skin_matrix() -> mat4 {
+            return joint_matrices[int(geom_joints.x)] * geom_weights.x
+                + joint_matrices[int(geom_joints.y)] * geom_weights.y
+                + joint_matrices[int(geom_joints.z)] * geom_weights.z
+                + joint_matrices[int(geom_joints.w)] * geom_weights.w;
+        }
+    "#
+    );
+}
+
+/// The uniforms declared in [`SkinnedVertex::SHADER`]. Custom shaders with more uniforms should include
+/// [`SkinnedVertex::SHADER`] before declaring them, and put this first in their uniforms struct.
+#[derive(Clone, Copy, Debug)]
+#[repr(C)]
+pub struct SkinningUniforms {
+    pub joint_matrices: [Mat4; SkinnedMesh::MAX_JOINTS],
+}
+
+impl SkinningUniforms {
+    /// Panics if there are more than [`SkinnedMesh::MAX_JOINTS`] joint matrices.
+    pub fn new(joint_matrices: &[Mat4]) -> Self {
+        assert!(
+            joint_matrices.len() <= SkinnedMesh::MAX_JOINTS,
+            "Skinned meshes can have at most {} joints, got {}",
+            SkinnedMesh::MAX_JOINTS,
+            joint_matrices.len()
+        );
+        let mut uniforms = Self { joint_matrices: [Mat4::identity(); SkinnedMesh::MAX_JOINTS] };
+        uniforms.joint_matrices[..joint_matrices.len()].copy_from_slice(joint_matrices);
+        uniforms
+    }
+}
+
+#[derive(Clone, Copy)]
+#[repr(C)]
+struct SkinnedMeshIns {
+    transform: Mat4,
+    color: Vec4,
+}
+
+static SHADER: Shader = Shader {
+    build_geom: None,
+    code_to_concatenate: &[
+        Cx::STD_SHADER,
+        SkinnedVertex::SHADER,
+        code_fragment!(
+            r#"
+            instance transform: mat4;
+            instance color: vec4;
+
+            varying world_normal: vec3;
+
+            fn vertex() -> vec4 {
+                let model = transform * skin_matrix();
+                world_normal = (model * vec4(geom_normal, 0.)).xyz;
+                return camera_projection * camera_view * model * vec4(geom_pos, 1.);
+            }
+
+            fn pixel() -> vec4 {
+                // Light from above and in front, plus some ambient light so the other sides aren't black.
+                let diffuse = max(dot(normalize(world_normal), normalize(vec3(0.3, 1., 0.5))), 0.);
+                return vec4(color.rgb * (0.3 + 0.7 * diffuse) * color.a, color.a);
+            }
+            "#
+        ),
+    ],
+    ..Shader::DEFAULT
+};
+
+/// A mesh that moves along with a [`Skeleton`], with [`AnimationClip`]s for that skeleton.
+///
+/// See [`AnimationPlayer`] for an example.
+#[derive(Clone, Default)]
+pub struct SkinnedMesh {
+    vertices: Vec<SkinnedVertex>,
+    indices: Vec<[u32; 3]>,
+    skeleton: Skeleton,
+    clips: Vec<AnimationClip>,
+    geometry: Option<GpuGeometry>,
+}
+
+impl SkinnedMesh {
+    /// The maximum number of joints in the [`Skeleton`]s of skinned meshes, as supported by
+    /// [`SkinnedVertex::SHADER`]. The joint matrices take up most of the space that Metal has for uniforms, and
+    /// more than the minimum that WebGL guarantees, though practically every GPU supports more.
+    pub const MAX_JOINTS: usize = 48;
+
+    /// Panics if the skeleton has more than [`SkinnedMesh::MAX_JOINTS`] joints.
+    pub fn new(vertices: Vec<SkinnedVertex>, indices: Vec<[u32; 3]>, skeleton: Skeleton, clips: Vec<AnimationClip>) -> Self {
+        assert!(
+            skeleton.joints().len() <= Self::MAX_JOINTS,
+            "Skinned meshes can have at most {} joints, got {}",
+            Self::MAX_JOINTS,
+            skeleton.joints().len()
+        );
+        Self { vertices, indices, skeleton, clips, geometry: None }
+    }
+
+    pub fn vertices(&self) -> &[SkinnedVertex] {
+        &self.vertices
+    }
+
+    pub fn indices(&self) -> &[[u32; 3]] {
+        &self.indices
+    }
+
+    pub fn skeleton(&self) -> &Skeleton {
+        &self.skeleton
+    }
+
+    pub fn clips(&self) -> &[AnimationClip] {
+        &self.clips
+    }
+
+    pub fn clip_index(&self, name: &str) -> Option<usize> {
+        self.clips.iter().position(|clip| clip.name == name)
+    }
+
+    /// Draw the mesh in a pose, given as joint matrices from [`AnimationPlayer::joint_matrices`] or
+    /// [`Skeleton::joint_matrices`], and then transformed by `transform`. Call this between
+    /// [`crate::Viewport3D::begin_draw`] and [`crate::Viewport3D::end_draw`].
+    pub fn draw(&mut self, cx: &mut Cx, joint_matrices: &[Mat4], transform: Mat4, color: Vec4) -> Area {
+        let vertices = &self.vertices;
+        let indices = &self.indices;
+        let geometry =
+            self.geometry.get_or_insert_with(|| GpuGeometry::new(cx, Geometry::new(vertices.clone(), indices.clone()))).clone();
+        let area = cx.add_mesh_instances(&SHADER, &[SkinnedMeshIns { transform, color }], geometry);
+        area.write_user_uniforms(cx, SkinningUniforms::new(joint_matrices));
+        area
+    }
+}
+
+/// Plays the [`AnimationClip`]s of a [`SkinnedMesh`] using an [`Animator`], so they can be paused, resumed, and
+/// reversed like other animations.
+///
+/// ```ignore
+/// // In `handle`:
+/// if self.player.handle(cx, event) {
+///     cx.request_draw();
+/// }
+/// if let Some(clip) = self.mesh.clip_index("Walk") {
+///     self.player.play(cx, &self.mesh, clip, true);
+/// }
+///
+/// // In `draw`, between `Viewport3D::begin_draw` and `Viewport3D::end_draw`:
+/// let joint_matrices = self.player.joint_matrices(cx, &self.mesh);
+/// self.mesh.draw(cx, &joint_matrices, Mat4::identity(), vec4(0.8, 0.8, 0.8, 1.));
+/// ```
+#[derive(Debug, Default)]
+pub struct AnimationPlayer {
+    /// Has a single track, which goes from 0 to 1 over the duration of the clip.
+    animator: Animator,
+    clip: Option<usize>,
+    duration: f32,
+}
+
+impl AnimationPlayer {
+    const ANIM_DEFAULT: Anim = Anim { tracks: &[Track::Float { key_frames: &[(0., 0.)], ease: Ease::Lin }], ..Anim::DEFAULT };
+    const ANIM_PLAY: Anim =
+        Anim { tracks: &[Track::Float { key_frames: &[(0., 0.), (1., 1.)], ease: Ease::Lin }], ..Anim::DEFAULT };
+
+    /// Play the clip with index `clip` in [`SkinnedMesh::clips`] from the start. A clip that isn't `looping` stays in
+    /// its last pose when it's done.
+    pub fn play(&mut self, cx: &mut Cx, mesh: &SkinnedMesh, clip: usize, looping: bool) {
+        self.clip = Some(clip);
+        self.duration = mesh.clips[clip].duration;
+        self.animator.draw(cx, Self::ANIM_DEFAULT);
+        self.animator.play_anim(cx, Anim { duration: self.duration as f64, looping, ..Self::ANIM_PLAY });
+    }
+
+    /// Stop playing, returning to the rest pose.
+    pub fn stop(&mut self, cx: &mut Cx) {
+        self.clip = None;
+        self.animator.play_anim(cx, Self::ANIM_DEFAULT);
+    }
+
+    /// The index of the clip that's playing, paused, or done playing, in [`SkinnedMesh::clips`].
+    pub fn clip(&self) -> Option<usize> {
+        self.clip
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.animator.is_playing()
+    }
+
+    pub fn pause(&mut self, cx: &Cx) {
+        self.animator.pause(cx);
+    }
+
+    pub fn resume(&mut self, cx: &mut Cx) {
+        self.animator.resume(cx);
+    }
+
+    /// See [`Animator::reverse`].
+    pub fn reverse(&mut self, cx: &mut Cx) {
+        self.animator.reverse(cx);
+    }
+
+    /// Returns whether the pose changed, in which case the mesh should be drawn again.
+    pub fn handle(&mut self, cx: &mut Cx, event: &Event) -> bool {
+        self.animator.handle(cx, event)
+    }
+
+    /// How far into the current clip we are, in seconds.
+    pub fn time(&mut self, cx: &mut Cx) -> f32 {
+        self.animator.draw(cx, Self::ANIM_DEFAULT);
+        self.animator.get_float(0) * self.duration
+    }
+
+    /// The joint matrices of `mesh` for the current time in the current clip, for passing to [`SkinnedMesh::draw`].
+    pub fn joint_matrices(&mut self, cx: &mut Cx, mesh: &SkinnedMesh) -> Vec<Mat4> {
+        let mut pose = mesh.skeleton.rest_pose();
+        if let Some(clip) = self.clip {
+            mesh.clips[clip].sample(self.time(cx), &mut pose);
+        }
+        mesh.skeleton.joint_matrices(&pose)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::PI;
+
+    fn assert_close(a: Vec3, b: Vec3) {
+        assert!((a - b).length() < 1e-4, "{:?} != {:?}", a, b);
+    }
+
+    fn transform_point(matrix: &Mat4, point: Vec3) -> Vec3 {
+        matrix.transform_vec4(vec4(point.x, point.y, point.z, 1.)).to_vec3()
+    }
+
+    /// An arm along the X axis: a shoulder at the origin, and an elbow at x = 1.
+    fn arm() -> (Skeleton, AnimationClip) {
+        let elbow_rest_pose = JointTransform { translation: vec3(1., 0., 0.), ..JointTransform::IDENTITY };
+        let skeleton = Skeleton::new(vec![
+            // Children may come before their parents.
+            Joint {
+                name: "elbow".to_string(),
+                parent: Some(1),
+                rest_pose: elbow_rest_pose,
+                inverse_bind_matrix: Mat4::translation(-1., 0., 0.),
+            },
+            Joint {
+                name: "shoulder".to_string(),
+                parent: None,
+                rest_pose: JointTransform::IDENTITY,
+                inverse_bind_matrix: Mat4::identity(),
+            },
+        ]);
+        let clip = AnimationClip {
+            name: "raise".to_string(),
+            duration: 2.,
+            channels: vec![AnimationChannel {
+                joint: 1,
+                interpolation: Interpolation::Linear,
+                times: vec![0., 2.],
+                values: ChannelValues::Rotation(vec![
+                    JointTransform::IDENTITY.rotation,
+                    Quat::from_axis_angle(vec3(0., 0., 1.), PI / 2.),
+                ]),
+            }],
+        };
+        (skeleton, clip)
+    }
+
+    #[test]
+    fn test_skeleton_joint_matrices() {
+        let (skeleton, clip) = arm();
+        assert_eq!(skeleton.joint_index("shoulder"), Some(1));
+
+        // In the rest pose, vertices stay where they were modeled.
+        let joint_matrices = skeleton.joint_matrices(&skeleton.rest_pose());
+        assert_close(transform_point(&joint_matrices[0], vec3(2., 0., 0.)), vec3(2., 0., 0.));
+
+        // Raising the shoulder by 90 degrees moves the hand up, since the elbow follows its parent.
+        let mut pose = skeleton.rest_pose();
+        clip.sample(2., &mut pose);
+        let joint_matrices = skeleton.joint_matrices(&pose);
+        assert_close(transform_point(&joint_matrices[0], vec3(2., 0., 0.)), vec3(0., 2., 0.));
+        assert_close(transform_point(&skeleton.world_matrices(&pose)[0], vec3(0., 0., 0.)), vec3(0., 1., 0.));
+
+        // Halfway through, the arm is at 45 degrees.
+        clip.sample(1., &mut pose);
+        let hand = transform_point(&skeleton.joint_matrices(&pose)[0], vec3(2., 0., 0.));
+        assert_close(hand, vec3(2f32.sqrt(), 2f32.sqrt(), 0.));
+    }
+
+    #[test]
+    fn test_animation_channel_interpolation() {
+        let mut channel = AnimationChannel {
+            joint: 0,
+            interpolation: Interpolation::Linear,
+            times: vec![1., 2., 4.],
+            values: ChannelValues::Translation(vec![vec3(0., 0., 0.), vec3(2., 0., 0.), vec3(2., 4., 0.)]),
+        };
+        let mut pose = vec![JointTransform::IDENTITY];
+        for (time, expected) in [(0., vec3(0., 0., 0.)), (1.5, vec3(1., 0., 0.)), (3., vec3(2., 2., 0.)), (5., vec3(2., 4., 0.))]
+        {
+            channel.sample(time, &mut pose);
+            assert_close(pose[0].translation, expected);
+        }
+        channel.interpolation = Interpolation::Step;
+        channel.sample(3., &mut pose);
+        assert_close(pose[0].translation, vec3(2., 0., 0.));
+    }
+
+    #[test]
+    fn test_animation_player() {
+        let (skeleton, clip) = arm();
+        let mesh = SkinnedMesh::new(vec![], vec![], skeleton, vec![clip]);
+        let mut player = AnimationPlayer::default();
+        let mut test_cx = TestCx::new(vec2(100., 100.));
+
+        player.play(&mut test_cx.cx, &mesh, 0, false);
+        assert!(player.is_playing());
+        test_cx.next_frame(0.5, |cx, event| player.handle(cx, event));
+        assert!((player.time(&mut test_cx.cx) - 0.5).abs() < 1e-5);
+
+        // A clip that doesn't loop stays in its last pose.
+        test_cx.next_frame(2., |cx, event| player.handle(cx, event));
+        assert!(!player.is_playing());
+        let joint_matrices = player.joint_matrices(&mut test_cx.cx, &mesh);
+        assert_close(transform_point(&joint_matrices[0], vec3(2., 0., 0.)), vec3(0., 2., 0.));
+    }
+}
@@ -71,6 +71,40 @@ static SHADER: Shader = Shader {
     ..Shader::DEFAULT
 };
 
+#[derive(Clone, Copy, Default)]
+#[repr(C)]
+struct CubeMapIns {
+    brightness: f32,
+}
+
+static CUBE_MAP_SHADER: Shader = Shader {
+    build_geom: Some(build_geom),
+    code_to_concatenate: &[
+        Cx::STD_SHADER,
+        Geometry3d::SHADER,
+        code_fragment!(
+            r#"
+            texture cube_map: textureCube;
+            instance brightness: float;
+            varying direction: vec3;
+
+            fn vertex() -> vec4 {
+                direction = geom_pos;
+                // Only rotate with the camera, so the sky stays infinitely far away.
+                let position = camera_projection * vec4((camera_view * vec4(geom_pos, 0.)).xyz, 1.);
+                // Put it right before the far plane, behind everything else.
+                return vec4(position.xy, position.w * 0.99999, position.w);
+            }
+
+            fn pixel() -> vec4 {
+                let color = sample_cube(cube_map, direction);
+                return vec4(color.rgb * brightness, 1.);
+            }"#
+        ),
+    ],
+    ..Shader::DEFAULT
+};
+
 pub struct SkyBox;
 
 impl SkyBox {
@@ -83,4 +117,13 @@ impl SkyBox {
 
         cx.add_instances(&SHADER, &[cube]);
     }
+
+    /// Draw a cube texture (see [`Texture::get_cube_with_size`] and [`CubeMapImage`]) as the sky around a 3D view.
+    /// The sky moves along with the camera, so it always looks infinitely far away, and it's drawn behind
+    /// everything else regardless of the drawing order. `brightness` multiplies the colors, e.g. to dim the sky at
+    /// night.
+    pub fn draw_cube_map(cx: &mut Cx, cube_map: TextureHandle, brightness: f32) {
+        let area = cx.add_instances(&CUBE_MAP_SHADER, &[CubeMapIns { brightness }]);
+        area.write_texture_cube(cx, "cube_map", cube_map);
+    }
 }
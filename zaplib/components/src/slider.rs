@@ -0,0 +1,715 @@
+//! Sliders and knobs for picking a value from a range.
+
+use zaplib::*;
+
+/// A value that can be picked using a [`Slider`], [`RangeSlider`], or [`Knob`], like `f32` or `u32`. Change events
+/// contain values of this type. These components all support:
+/// * Snapping to a step size ([`Slider::with_step`]), and to specific values when getting close to them
+///   ([`Slider::with_snap_points`]).
+/// * Keyboard control when focused: the arrow keys move by one step (or 1% of the range without a step size, but
+///   at least 1 for integers), ten times as much while holding Shift or using Page Up and Page Down, and Home and End
+///   go to the ends of the range.
+pub trait SliderValue: Copy + PartialOrd + std::fmt::Debug {
+    fn to_f64(self) -> f64;
+    /// Convert back, rounding if needed.
+    fn from_f64(value: f64) -> Self;
+    /// The smallest amount that the arrow keys move by, so that e.g. integers don't get rounded back to where they were.
+    const MIN_STEP: f64 = 0.;
+}
+
+macro_rules! impl_slider_value_float {
+    ($($ty:ty),*) => {$(
+        impl SliderValue for $ty {
+            fn to_f64(self) -> f64 {
+                self as f64
+            }
+            fn from_f64(value: f64) -> Self {
+                value as $ty
+            }
+        }
+    )*};
+}
+impl_slider_value_float!(f32, f64);
+
+macro_rules! impl_slider_value_int {
+    ($($ty:ty),*) => {$(
+        impl SliderValue for $ty {
+            fn to_f64(self) -> f64 {
+                self as f64
+            }
+            fn from_f64(value: f64) -> Self {
+                value.round() as $ty
+            }
+            const MIN_STEP: f64 = 1.;
+        }
+    )*};
+}
+impl_slider_value_int!(i32, i64, u8, u32, u64, usize);
+
+/// Sent by [`Slider`] and [`Knob`].
+#[derive(Clone, Debug, PartialEq)]
+pub enum SliderEvent<T> {
+    None,
+    /// The value changed, e.g. while dragging.
+    Changed(T),
+    /// The user let go after dragging, so you can e.g. add a single undo step.
+    DoneChanging(T),
+}
+
+/// Sent by [`RangeSlider`].
+#[derive(Clone, Debug, PartialEq)]
+pub enum RangeSliderEvent<T> {
+    None,
+    Changed { low: T, high: T },
+    DoneChanging { low: T, high: T },
+}
+
+/// Maps values to and from a 0 to 1 range, with snapping.
+#[derive(Clone, Debug)]
+struct ValueScale<T> {
+    min: T,
+    max: T,
+    step: Option<T>,
+    snap_points: Vec<T>,
+}
+
+/// How close a value needs to be to a snap point to snap to it, as a fraction of the whole range.
+const SNAP_DISTANCE: f64 = 0.02;
+
+impl<T: SliderValue> ValueScale<T> {
+    fn normalize(&self, value: T) -> f64 {
+        let (min, max) = (self.min.to_f64(), self.max.to_f64());
+        if max > min {
+            ((value.to_f64() - min) / (max - min)).clamp(0., 1.)
+        } else {
+            0.
+        }
+    }
+
+    /// The value for a position between 0 and 1, snapped to snap points and steps.
+    fn denormalize(&self, norm: f64) -> T {
+        let norm = norm.clamp(0., 1.);
+        if let Some(&snap_point) =
+            self.snap_points.iter().find(|&&snap_point| (self.normalize(snap_point) - norm).abs() <= SNAP_DISTANCE)
+        {
+            return snap_point;
+        }
+        let (min, max) = (self.min.to_f64(), self.max.to_f64());
+        self.snap_to_step(min + norm * (max - min))
+    }
+
+    fn snap_to_step(&self, value: f64) -> T {
+        let (min, max) = (self.min.to_f64(), self.max.to_f64());
+        let value = match self.step.map(T::to_f64) {
+            Some(step) if step > 0. => min + ((value - min) / step).round() * step,
+            _ => value,
+        };
+        T::from_f64(value.clamp(min, max))
+    }
+
+    /// Handle the keys described in the module docs, returning the new value.
+    fn handle_key(&self, value: T, ke: &KeyEvent) -> Option<T> {
+        let (min, max) = (self.min.to_f64(), self.max.to_f64());
+        let step = match self.step.map(T::to_f64) {
+            Some(step) if step > 0. => step,
+            _ => ((max - min) / 100.).max(T::MIN_STEP),
+        };
+        let multiplier = if ke.modifiers.shift { 10. } else { 1. };
+        let new_value = match ke.key_code {
+            KeyCode::ArrowRight | KeyCode::ArrowUp => value.to_f64() + step * multiplier,
+            KeyCode::ArrowLeft | KeyCode::ArrowDown => value.to_f64() - step * multiplier,
+            KeyCode::PageUp => value.to_f64() + step * 10.,
+            KeyCode::PageDown => value.to_f64() - step * 10.,
+            KeyCode::Home => min,
+            KeyCode::End => max,
+            _ => return None,
+        };
+        Some(self.snap_to_step(new_value))
+    }
+}
+
+macro_rules! impl_value_scale_builders {
+    () => {
+        /// Round values to multiples of `step` (counting from the minimum). Also used for keyboard stepping.
+        #[must_use]
+        pub fn with_step(mut self, step: T) -> Self {
+            self.scale.step = Some(step);
+            self
+        }
+        /// Snap to these values when getting close to them, e.g. to make it easy to get back to a default.
+        #[must_use]
+        pub fn with_snap_points(mut self, snap_points: &[T]) -> Self {
+            self.scale.snap_points = snap_points.to_vec();
+            self
+        }
+        #[must_use]
+        pub fn with_color(self, color: Vec4) -> Self {
+            Self { color, ..self }
+        }
+    };
+}
+
+#[derive(Clone, Copy, Default)]
+#[repr(C)]
+struct SliderIns {
+    base: QuadIns,
+    track_color: Vec4,
+    fill_color: Vec4,
+    /// Where the filled part of the track starts and ends, from 0 to 1.
+    low: f32,
+    high: f32,
+    /// Whether to draw a thumb at `low` too, for [`RangeSlider`].
+    has_low_thumb: f32,
+    hover: f32,
+}
+
+static SLIDER_SHADER: Shader = Shader {
+    build_geom: Some(QuadIns::build_geom),
+    code_to_concatenate: &[
+        Cx::STD_SHADER,
+        QuadIns::SHADER,
+        code_fragment!(
+            r#"
+            instance track_color: vec4;
+            instance fill_color: vec4;
+            instance low: float;
+            instance high: float;
+            instance has_low_thumb: float;
+            instance hover: float;
+
+            const thumb_radius: float = 7.;
+            const track_height: float = 4.;
+
+            fn pixel() -> vec4 {
+                let df = Df::viewport(pos * rect_size);
+                let width = rect_size.x - thumb_radius * 2.;
+                let y = rect_size.y / 2.;
+                df.box(vec2(thumb_radius, y - track_height / 2.), vec2(width, track_height), track_height / 2.);
+                df.fill(track_color);
+
+                let low_x = thumb_radius + width * low;
+                let high_x = thumb_radius + width * high;
+                if high_x - low_x > 0.5 {
+                    df.new_path();
+                    df.box(vec2(low_x, y - track_height / 2.), vec2(high_x - low_x, track_height), track_height / 2.);
+                    df.fill(fill_color);
+                }
+
+                let thumb_color = mix(#c, #f, hover);
+                df.new_path();
+                df.circle(vec2(high_x, y), thumb_radius - 1.);
+                if has_low_thumb > 0.5 {
+                    df.circle(vec2(low_x, y), thumb_radius - 1.);
+                }
+                df.fill(thumb_color);
+                return df.result;
+            }"#
+        ),
+    ],
+    ..Shader::DEFAULT
+};
+
+/// Same as `thumb_radius` in [`SLIDER_SHADER`]; the track is inset by this much so the thumbs fit at the ends.
+const THUMB_RADIUS: f32 = 7.;
+const SLIDER_HEIGHT: f32 = 24.;
+const TRACK_COLOR: Vec4 = vec4(1., 1., 1., 0.15);
+const FILL_COLOR: Vec4 = vec4(0.27, 0.56, 0.93, 1.);
+
+/// The position of `abs` along the track of a slider drawn at `rect`, from 0 to 1.
+fn slider_norm(rect: Rect, abs: Vec2) -> f64 {
+    let width = rect.size.x - THUMB_RADIUS * 2.;
+    if width > 0. {
+        ((abs.x - rect.pos.x - THUMB_RADIUS) / width).clamp(0., 1.) as f64
+    } else {
+        0.
+    }
+}
+
+/// A horizontal slider for picking a single value. See [`SliderValue`] for the common features.
+pub struct Slider<T: SliderValue> {
+    component_id: ComponentId,
+    area: Area,
+    scale: ValueScale<T>,
+    value: T,
+    color: Vec4,
+    hover: bool,
+    dragging: bool,
+}
+
+impl<T: SliderValue> Slider<T> {
+    /// A slider for values from `min` to `max`, starting at `min`.
+    pub fn new(min: T, max: T) -> Self {
+        Self {
+            component_id: ComponentId::default(),
+            area: Area::Empty,
+            scale: ValueScale { min, max, step: None, snap_points: vec![] },
+            value: min,
+            color: FILL_COLOR,
+            hover: false,
+            dragging: false,
+        }
+    }
+
+    impl_value_scale_builders!();
+
+    #[must_use]
+    pub fn with_value(self, value: T) -> Self {
+        let value = self.scale.snap_to_step(value.to_f64());
+        Self { value, ..self }
+    }
+
+    pub fn value(&self) -> T {
+        self.value
+    }
+
+    pub fn set_value(&mut self, cx: &mut Cx, value: T) {
+        self.value = self.scale.snap_to_step(value.to_f64());
+        cx.request_draw();
+    }
+
+    fn change(&mut self, cx: &mut Cx, value: T) -> SliderEvent<T> {
+        if value == self.value {
+            return SliderEvent::None;
+        }
+        self.value = value;
+        cx.request_draw();
+        SliderEvent::Changed(value)
+    }
+
+    pub fn handle(&mut self, cx: &mut Cx, event: &mut Event) -> SliderEvent<T> {
+        if let Event::KeyDown(ke) = event.hits_keyboard(cx, self.component_id) {
+            if let Some(value) = self.scale.handle_key(self.value, &ke) {
+                return self.change(cx, value);
+            }
+        }
+
+        match event.hits_pointer(cx, self.component_id, self.area.get_rect_for_first_instance(cx)) {
+            Event::PointerHover(pe) => {
+                let hover = pe.hover_state != HoverState::Out;
+                if hover != self.hover {
+                    self.hover = hover;
+                    cx.request_draw();
+                }
+            }
+            Event::PointerDown(pe) => {
+                cx.set_key_focus(Some(self.component_id));
+                self.dragging = true;
+                return self.change(cx, self.scale.denormalize(slider_norm(pe.rect, pe.abs)));
+            }
+            Event::PointerMove(pe) => return self.change(cx, self.scale.denormalize(slider_norm(pe.rect, pe.abs))),
+            Event::PointerUp(_) => {
+                self.dragging = false;
+                return SliderEvent::DoneChanging(self.value);
+            }
+            _ => {}
+        }
+        SliderEvent::None
+    }
+
+    pub fn draw(&mut self, cx: &mut Cx) {
+        let rect = cx.add_box(LayoutSize::new(Width::Fill, Height::Fix(SLIDER_HEIGHT)));
+        self.area = cx.add_instances(
+            &SLIDER_SHADER,
+            &[SliderIns {
+                base: QuadIns::from_rect(rect),
+                track_color: TRACK_COLOR,
+                fill_color: self.color,
+                low: 0.,
+                high: self.scale.normalize(self.value) as f32,
+                has_low_thumb: 0.,
+                hover: (self.hover || self.dragging) as u8 as f32,
+            }],
+        );
+        cx.add_focusable(self.component_id, self.area, Focusable::default());
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Thumb {
+    Low,
+    High,
+}
+
+/// A horizontal slider with two thumbs, for picking a range of values. See [`SliderValue`] for the common
+/// features. The thumbs can be focused separately using Tab.
+pub struct RangeSlider<T: SliderValue> {
+    component_id: ComponentId,
+    low_component_id: ComponentId,
+    high_component_id: ComponentId,
+    area: Area,
+    scale: ValueScale<T>,
+    low: T,
+    high: T,
+    color: Vec4,
+    hover: bool,
+    /// The thumb being dragged.
+    dragging: Option<Thumb>,
+}
+
+impl<T: SliderValue> RangeSlider<T> {
+    /// A slider for ranges within `min` and `max`, starting at the whole range.
+    pub fn new(min: T, max: T) -> Self {
+        Self {
+            component_id: ComponentId::default(),
+            low_component_id: ComponentId::default(),
+            high_component_id: ComponentId::default(),
+            area: Area::Empty,
+            scale: ValueScale { min, max, step: None, snap_points: vec![] },
+            low: min,
+            high: max,
+            color: FILL_COLOR,
+            hover: false,
+            dragging: None,
+        }
+    }
+
+    impl_value_scale_builders!();
+
+    #[must_use]
+    pub fn with_range(mut self, low: T, high: T) -> Self {
+        self.set_range_internal(low, high);
+        self
+    }
+
+    pub fn range(&self) -> (T, T) {
+        (self.low, self.high)
+    }
+
+    pub fn set_range(&mut self, cx: &mut Cx, low: T, high: T) {
+        self.set_range_internal(low, high);
+        cx.request_draw();
+    }
+
+    fn set_range_internal(&mut self, low: T, high: T) {
+        let low = self.scale.snap_to_step(low.to_f64());
+        let high = self.scale.snap_to_step(high.to_f64());
+        if low <= high {
+            self.low = low;
+            self.high = high;
+        } else {
+            self.low = high;
+            self.high = low;
+        }
+    }
+
+    /// Move one of the thumbs, without passing the other one.
+    fn change(&mut self, cx: &mut Cx, thumb: Thumb, value: T) -> RangeSliderEvent<T> {
+        let (low, high) = match thumb {
+            Thumb::Low => (if value > self.high { self.high } else { value }, self.high),
+            Thumb::High => (self.low, if value < self.low { self.low } else { value }),
+        };
+        if low == self.low && high == self.high {
+            return RangeSliderEvent::None;
+        }
+        self.low = low;
+        self.high = high;
+        cx.request_draw();
+        RangeSliderEvent::Changed { low, high }
+    }
+
+    pub fn handle(&mut self, cx: &mut Cx, event: &mut Event) -> RangeSliderEvent<T> {
+        for (thumb, component_id) in [(Thumb::Low, self.low_component_id), (Thumb::High, self.high_component_id)] {
+            if let Event::KeyDown(ke) = event.hits_keyboard(cx, component_id) {
+                let value = if thumb == Thumb::Low { self.low } else { self.high };
+                if let Some(value) = self.scale.handle_key(value, &ke) {
+                    return self.change(cx, thumb, value);
+                }
+            }
+        }
+
+        match event.hits_pointer(cx, self.component_id, self.area.get_rect_for_first_instance(cx)) {
+            Event::PointerHover(pe) => {
+                let hover = pe.hover_state != HoverState::Out;
+                if hover != self.hover {
+                    self.hover = hover;
+                    cx.request_draw();
+                }
+            }
+            Event::PointerDown(pe) => {
+                let norm = slider_norm(pe.rect, pe.abs);
+                let low_distance = (norm - self.scale.normalize(self.low)).abs();
+                let high_distance = (norm - self.scale.normalize(self.high)).abs();
+                // When the thumbs are on top of each other, pick the one that can move in the direction of the click.
+                let thumb =
+                    if low_distance < high_distance || (low_distance == high_distance && norm < self.scale.normalize(self.low)) {
+                        Thumb::Low
+                    } else {
+                        Thumb::High
+                    };
+                cx.set_key_focus(Some(if thumb == Thumb::Low { self.low_component_id } else { self.high_component_id }));
+                self.dragging = Some(thumb);
+                return self.change(cx, thumb, self.scale.denormalize(norm));
+            }
+            Event::PointerMove(pe) => {
+                if let Some(thumb) = self.dragging {
+                    return self.change(cx, thumb, self.scale.denormalize(slider_norm(pe.rect, pe.abs)));
+                }
+            }
+            Event::PointerUp(_) => {
+                self.dragging = None;
+                return RangeSliderEvent::DoneChanging { low: self.low, high: self.high };
+            }
+            _ => {}
+        }
+        RangeSliderEvent::None
+    }
+
+    pub fn draw(&mut self, cx: &mut Cx) {
+        let rect = cx.add_box(LayoutSize::new(Width::Fill, Height::Fix(SLIDER_HEIGHT)));
+        self.area = cx.add_instances(
+            &SLIDER_SHADER,
+            &[SliderIns {
+                base: QuadIns::from_rect(rect),
+                track_color: TRACK_COLOR,
+                fill_color: self.color,
+                low: self.scale.normalize(self.low) as f32,
+                high: self.scale.normalize(self.high) as f32,
+                has_low_thumb: 1.,
+                hover: (self.hover || self.dragging.is_some()) as u8 as f32,
+            }],
+        );
+        cx.add_focusable(self.low_component_id, self.area, Focusable::default());
+        cx.add_focusable(self.high_component_id, self.area, Focusable::default());
+    }
+}
+
+#[derive(Clone, Copy, Default)]
+#[repr(C)]
+struct KnobIns {
+    base: QuadIns,
+    track_color: Vec4,
+    fill_color: Vec4,
+    value: f32,
+    hover: f32,
+}
+
+static KNOB_SHADER: Shader = Shader {
+    build_geom: Some(QuadIns::build_geom),
+    code_to_concatenate: &[
+        Cx::STD_SHADER,
+        QuadIns::SHADER,
+        code_fragment!(
+            r#"
+            instance track_color: vec4;
+            instance fill_color: vec4;
+            instance value: float;
+            instance hover: float;
+
+            // Angles are clockwise from the top (see `Df::arc`); the knob goes from the bottom left to the bottom right.
+            const start_angle: float = 3.927;
+            const sweep: float = 4.712;
+            const ring_width: float = 3.;
+
+            fn pixel() -> vec4 {
+                let df = Df::viewport(pos * rect_size);
+                let center = rect_size / 2.;
+                let radius = min(rect_size.x, rect_size.y) / 2. - 1.;
+
+                df.arc(center, radius, start_angle, 2. * PI);
+                df.arc(center, radius, 0., start_angle + sweep - 2. * PI);
+                df.circle(center, radius - ring_width);
+                df.subtract();
+                df.fill(track_color);
+
+                let end_angle = start_angle + sweep * value;
+                df.new_path();
+                df.arc(center, radius, start_angle, min(end_angle, 2. * PI));
+                if end_angle > 2. * PI {
+                    df.arc(center, radius, 0., end_angle - 2. * PI);
+                }
+                df.circle(center, radius - ring_width);
+                df.subtract();
+                df.fill(fill_color);
+
+                df.new_path();
+                df.circle(center, radius - ring_width - 3.);
+                df.fill(mix(#3, #4, hover));
+
+                let direction = vec2(sin(end_angle), -cos(end_angle));
+                df.new_path();
+                df.move_to(center + direction * (radius - ring_width - 3.) * 0.3);
+                df.line_to(center + direction * (radius - ring_width - 5.));
+                df.stroke(#e, 1.5);
+                return df.result;
+            }"#
+        ),
+    ],
+    ..Shader::DEFAULT
+};
+
+/// A rotary knob, like on audio equipment. Drag up or down (or scroll) to change the value; hold Shift while
+/// dragging for finer control. See [`SliderValue`] for the common features.
+pub struct Knob<T: SliderValue> {
+    component_id: ComponentId,
+    area: Area,
+    scale: ValueScale<T>,
+    value: T,
+    /// Double clicking goes back to this value.
+    default_value: Option<T>,
+    color: Vec4,
+    size: f32,
+    hover: bool,
+    /// The normalized value when we started dragging.
+    drag_start: Option<f64>,
+}
+
+impl<T: SliderValue> Knob<T> {
+    /// How many pixels to drag to go through the whole range.
+    const DRAG_DISTANCE: f64 = 200.;
+
+    /// A knob for values from `min` to `max`, starting at `min`.
+    pub fn new(min: T, max: T) -> Self {
+        Self {
+            component_id: ComponentId::default(),
+            area: Area::Empty,
+            scale: ValueScale { min, max, step: None, snap_points: vec![] },
+            value: min,
+            default_value: None,
+            color: FILL_COLOR,
+            size: 40.,
+            hover: false,
+            drag_start: None,
+        }
+    }
+
+    impl_value_scale_builders!();
+
+    #[must_use]
+    pub fn with_value(self, value: T) -> Self {
+        let value = self.scale.snap_to_step(value.to_f64());
+        Self { value, ..self }
+    }
+    /// Go back to this value when double clicking.
+    #[must_use]
+    pub fn with_default_value(self, default_value: T) -> Self {
+        Self { default_value: Some(default_value), ..self }
+    }
+    #[must_use]
+    pub fn with_size(self, size: f32) -> Self {
+        Self { size, ..self }
+    }
+
+    pub fn value(&self) -> T {
+        self.value
+    }
+
+    pub fn set_value(&mut self, cx: &mut Cx, value: T) {
+        self.value = self.scale.snap_to_step(value.to_f64());
+        cx.request_draw();
+    }
+
+    fn change(&mut self, cx: &mut Cx, value: T) -> SliderEvent<T> {
+        if value == self.value {
+            return SliderEvent::None;
+        }
+        self.value = value;
+        cx.request_draw();
+        SliderEvent::Changed(value)
+    }
+
+    pub fn handle(&mut self, cx: &mut Cx, event: &mut Event) -> SliderEvent<T> {
+        if let Event::KeyDown(ke) = event.hits_keyboard(cx, self.component_id) {
+            if let Some(value) = self.scale.handle_key(self.value, &ke) {
+                return self.change(cx, value);
+            }
+        }
+
+        match event.hits_pointer(cx, self.component_id, self.area.get_rect_for_first_instance(cx)) {
+            Event::PointerHover(pe) => {
+                let hover = pe.hover_state != HoverState::Out;
+                if hover != self.hover {
+                    self.hover = hover;
+                    cx.request_draw();
+                }
+            }
+            Event::PointerDown(pe) => {
+                cx.set_key_focus(Some(self.component_id));
+                if pe.tap_count == 2 {
+                    if let Some(default_value) = self.default_value {
+                        return self.change(cx, default_value);
+                    }
+                }
+                self.drag_start = Some(self.scale.normalize(self.value));
+            }
+            Event::PointerMove(pe) => {
+                if let Some(drag_start) = self.drag_start {
+                    let distance = if pe.modifiers.shift { Self::DRAG_DISTANCE * 10. } else { Self::DRAG_DISTANCE };
+                    let norm = drag_start + (pe.abs_start.y - pe.abs.y) as f64 / distance;
+                    return self.change(cx, self.scale.denormalize(norm));
+                }
+            }
+            Event::PointerUp(_) if self.drag_start.take().is_some() => {
+                return SliderEvent::DoneChanging(self.value);
+            }
+            Event::PointerScroll(pe) => {
+                let norm = self.scale.normalize(self.value) - pe.scroll.y as f64 / (Self::DRAG_DISTANCE * 5.);
+                return self.change(cx, self.scale.denormalize(norm));
+            }
+            _ => {}
+        }
+        SliderEvent::None
+    }
+
+    pub fn draw(&mut self, cx: &mut Cx) {
+        let rect = cx.add_box(LayoutSize::new(Width::Fix(self.size), Height::Fix(self.size)));
+        self.area = cx.add_instances(
+            &KNOB_SHADER,
+            &[KnobIns {
+                base: QuadIns::from_rect(rect),
+                track_color: TRACK_COLOR,
+                fill_color: self.color,
+                value: self.scale.normalize(self.value) as f32,
+                hover: (self.hover || self.drag_start.is_some()) as u8 as f32,
+            }],
+        );
+        cx.add_focusable(self.component_id, self.area, Focusable::default());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(key_code: KeyCode, shift: bool) -> KeyEvent {
+        KeyEvent { key_code, is_repeat: false, modifiers: KeyModifiers { shift, ..KeyModifiers::default() }, time: 0. }
+    }
+
+    #[test]
+    fn test_value_scale() {
+        let scale = ValueScale { min: 0u32, max: 100, step: Some(5), snap_points: vec![42] };
+        assert_eq!(scale.normalize(25), 0.25);
+        assert_eq!(scale.denormalize(0.26), 25);
+        assert_eq!(scale.denormalize(0.43), 42);
+        assert_eq!(scale.denormalize(1.5), 100);
+
+        assert_eq!(scale.handle_key(25, &key(KeyCode::ArrowRight, false)), Some(30));
+        assert_eq!(scale.handle_key(25, &key(KeyCode::ArrowLeft, true)), Some(0));
+        assert_eq!(scale.handle_key(25, &key(KeyCode::End, false)), Some(100));
+        assert_eq!(scale.handle_key(25, &key(KeyCode::KeyA, false)), None);
+
+        // Without a step size, keys move by 1% of the range.
+        let scale = ValueScale { min: -1f32, max: 1., step: None, snap_points: vec![] };
+        assert_eq!(scale.handle_key(0., &key(KeyCode::ArrowUp, false)), Some(0.02));
+
+        // .. but at least 1 for integers, even if the range is small.
+        let scale = ValueScale { min: 0u32, max: 50, step: None, snap_points: vec![] };
+        assert_eq!(scale.handle_key(25, &key(KeyCode::ArrowRight, false)), Some(26));
+        assert_eq!(scale.handle_key(25, &key(KeyCode::ArrowLeft, false)), Some(24));
+        assert_eq!(scale.handle_key(25, &key(KeyCode::ArrowRight, true)), Some(35));
+    }
+
+    #[test]
+    fn test_range_slider_thumbs_dont_cross() {
+        let mut test_cx = TestCx::new(vec2(214., 100.));
+        let mut slider = RangeSlider::new(0., 100.).with_step(1.).with_range(20., 60.);
+        test_cx.draw(|cx| slider.draw(cx));
+
+        // The track starts at `THUMB_RADIUS` and is 200 pixels wide. Clicking closer to the high thumb moves it.
+        test_cx.pointer_down(vec2(7. + 160., 12.), |cx, event| slider.handle(cx, event));
+        assert_eq!(slider.range(), (20., 80.));
+        test_cx.pointer_move(vec2(7. + 10., 12.), |cx, event| slider.handle(cx, event));
+        assert_eq!(slider.range(), (20., 20.));
+        let events = test_cx.pointer_up(vec2(7. + 10., 12.), |cx, event| slider.handle(cx, event));
+        assert!(events.contains(&RangeSliderEvent::DoneChanging { low: 20., high: 20. }));
+    }
+}
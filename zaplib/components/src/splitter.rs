@@ -232,8 +232,7 @@ impl Splitter {
                     }
             }
         };
-        let dpi_factor = cx.get_dpi_factor_of(&self.bg.area());
-        self._calc_pos -= self._calc_pos % (1.0 / dpi_factor);
+        self._calc_pos = cx.snap_to_pixel(self._calc_pos);
         match self.axis {
             Axis::Horizontal => cx.begin_row(Width::Fill, Height::Fix(self._calc_pos)),
             Axis::Vertical => cx.begin_row(Width::Fix(self._calc_pos), Height::Fill),
@@ -0,0 +1,797 @@
+//! Histograms, box plots, and violin plots, for showing how values are distributed.
+
+use crate::background::*;
+use crate::legend::*;
+use crate::plot_axes::*;
+use std::sync::Arc;
+use zaplib::*;
+
+/// Most bins that [`StatPlot`] picks by itself; see [`StatPlot::with_bin_count`].
+const MAX_AUTO_BINS: usize = 200;
+/// Number of points at which densities get estimated for violin plots.
+const DENSITY_SAMPLES: usize = 96;
+/// Values get counted into this many bins before estimating densities, so that it stays fast for large columns.
+const DENSITY_BINS: usize = 1024;
+
+/// Counts of values in bins of equal width.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct HistogramBins {
+    /// Lower edge of the first bin.
+    pub start: f64,
+    pub bin_width: f64,
+    pub counts: Vec<usize>,
+}
+
+impl HistogramBins {
+    /// Lower and upper edge of bin `index`.
+    pub fn bin_range(&self, index: usize) -> (f64, f64) {
+        let start = self.start + index as f64 * self.bin_width;
+        (start, start + self.bin_width)
+    }
+
+    /// Upper edge of the last bin.
+    pub fn end(&self) -> f64 {
+        self.start + self.counts.len() as f64 * self.bin_width
+    }
+}
+
+/// Count `values` in `bin_count` bins of equal width from `start` to `end`. The last bin includes `end`; values
+/// outside of the range and values that are not finite are left out.
+pub fn histogram(values: &[f32], start: f64, end: f64, bin_count: usize) -> HistogramBins {
+    let bin_count = bin_count.max(1);
+    let bin_width = if end > start { (end - start) / bin_count as f64 } else { 1. };
+    let mut counts = vec![0; bin_count];
+    for &value in values {
+        let value = value as f64;
+        if value.is_finite() && value >= start && value <= end {
+            counts[(((value - start) / bin_width) as usize).min(bin_count - 1)] += 1;
+        }
+    }
+    HistogramBins { start, bin_width, counts }
+}
+
+/// Quantile `q` (between 0 and 1) of non-empty sorted values, interpolating between the closest two.
+fn quantile(sorted: &[f64], q: f64) -> f64 {
+    let position = q * (sorted.len() - 1) as f64;
+    let index = position.floor() as usize;
+    let next = (index + 1).min(sorted.len() - 1);
+    sorted[index] + (sorted[next] - sorted[index]) * (position - index as f64)
+}
+
+/// Summary of a distribution, as shown in a box plot. The whiskers extend to the furthest values that are within
+/// 1.5 times the interquartile range from the box; values beyond that are outliers.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct BoxStats {
+    pub count: usize,
+    pub min: f64,
+    pub q1: f64,
+    pub median: f64,
+    pub q3: f64,
+    pub max: f64,
+    pub lower_whisker: f64,
+    pub upper_whisker: f64,
+    pub outliers: Vec<f64>,
+}
+
+impl BoxStats {
+    /// The statistics of the finite values in `values`, or [`None`] if there are none.
+    pub fn from_values(values: &[f32]) -> Option<Self> {
+        let sorted = sorted_finite(values);
+        if sorted.is_empty() {
+            None
+        } else {
+            Some(Self::from_sorted(&sorted))
+        }
+    }
+
+    fn from_sorted(sorted: &[f64]) -> Self {
+        let q1 = quantile(sorted, 0.25);
+        let q3 = quantile(sorted, 0.75);
+        let fences = (q1 - 1.5 * (q3 - q1), q3 + 1.5 * (q3 - q1));
+        Self {
+            count: sorted.len(),
+            min: sorted[0],
+            q1,
+            median: quantile(sorted, 0.5),
+            q3,
+            max: sorted[sorted.len() - 1],
+            lower_whisker: sorted.iter().copied().find(|value| *value >= fences.0).unwrap_or(q1),
+            upper_whisker: sorted.iter().rev().copied().find(|value| *value <= fences.1).unwrap_or(q3),
+            outliers: sorted.iter().copied().filter(|value| *value < fences.0 || *value > fences.1).collect(),
+        }
+    }
+}
+
+fn sorted_finite(values: &[f32]) -> Vec<f64> {
+    let mut sorted: Vec<f64> = values.iter().filter(|value| value.is_finite()).map(|value| *value as f64).collect();
+    sorted.sort_unstable_by(|a, b| a.partial_cmp(b).unwrap());
+    sorted
+}
+
+/// Estimate the probability density of `values` at `sample_count` evenly spaced values, using a Gaussian kernel
+/// with a bandwidth from Silverman's rule of thumb. Returns pairs of value and density, from 3 bandwidths below the
+/// lowest value to 3 bandwidths above the highest value. Values that are not finite are left out.
+pub fn kernel_density(values: &[f32], sample_count: usize) -> Vec<(f64, f64)> {
+    kernel_density_sorted(&sorted_finite(values), sample_count)
+}
+
+fn kernel_density_sorted(sorted: &[f64], sample_count: usize) -> Vec<(f64, f64)> {
+    if sorted.is_empty() || sample_count < 2 {
+        return vec![];
+    }
+    let count = sorted.len() as f64;
+    let mean = sorted.iter().sum::<f64>() / count;
+    let deviation = (sorted.iter().map(|value| (value - mean).powi(2)).sum::<f64>() / count).sqrt();
+    let spread = deviation.min((quantile(sorted, 0.75) - quantile(sorted, 0.25)) / 1.34);
+    let spread = if spread > 0. { spread } else { deviation };
+    let bandwidth = if spread > 0. { 0.9 * spread * count.powf(-0.2) } else { 1. };
+
+    let (min, max) = (sorted[0], sorted[sorted.len() - 1]);
+    let bin_width = if max > min { (max - min) / DENSITY_BINS as f64 } else { 1. };
+    let mut bins = vec![0_usize; DENSITY_BINS];
+    for value in sorted {
+        bins[(((value - min) / bin_width) as usize).min(DENSITY_BINS - 1)] += 1;
+    }
+
+    let (start, end) = (min - 3. * bandwidth, max + 3. * bandwidth);
+    let step = (end - start) / (sample_count - 1) as f64;
+    let normalization = 1. / (count * bandwidth * (2. * std::f64::consts::PI).sqrt());
+    (0..sample_count)
+        .map(|index| {
+            let value = start + index as f64 * step;
+            let density: f64 = bins
+                .iter()
+                .enumerate()
+                .filter(|(_, bin_count)| **bin_count > 0)
+                .map(|(bin, bin_count)| {
+                    let z = (value - (min + (bin as f64 + 0.5) * bin_width)) / bandwidth;
+                    *bin_count as f64 * (-0.5 * z * z).exp()
+                })
+                .sum();
+            (value, density * normalization)
+        })
+        .collect()
+}
+
+/// Statistics of one [`StatSeries`], computed by [`StatPlot`].
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SeriesStats {
+    /// Uses the same bins for all series of a [`StatPlot`].
+    pub histogram: HistogramBins,
+    /// [`None`] when the series has no finite values.
+    pub box_stats: Option<BoxStats>,
+    /// Pairs of value and density; see [`kernel_density`].
+    pub density: Vec<(f64, f64)>,
+}
+
+fn compute_stats(columns: &[Vec<f32>], bin_count: Option<usize>) -> Vec<SeriesStats> {
+    let sorted_columns: Vec<Vec<f64>> = columns.iter().map(|values| sorted_finite(values)).collect();
+    let min = sorted_columns.iter().filter_map(|sorted| sorted.first()).copied().fold(f64::INFINITY, f64::min);
+    let max = sorted_columns.iter().filter_map(|sorted| sorted.last()).copied().fold(f64::NEG_INFINITY, f64::max);
+    let (min, max) = if min <= max { (min, max) } else { (0., 1.) };
+    // Sturges' rule, for the biggest series.
+    let largest = sorted_columns.iter().map(|sorted| sorted.len()).max().unwrap_or(0).max(1);
+    let bin_count = bin_count.unwrap_or_else(|| ((largest as f64).log2().ceil() as usize + 1).min(MAX_AUTO_BINS));
+
+    columns
+        .iter()
+        .zip(&sorted_columns)
+        .map(|(values, sorted)| SeriesStats {
+            histogram: histogram(values, min, max, bin_count),
+            box_stats: if sorted.is_empty() { None } else { Some(BoxStats::from_sorted(sorted)) },
+            density: kernel_density_sorted(sorted, DENSITY_SAMPLES),
+        })
+        .collect()
+}
+
+/// A named column of values for a [`StatPlot`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct StatSeries {
+    pub name: String,
+    pub values: Vec<f32>,
+    /// When [`None`], the [`StatPlot`] picks a color.
+    pub color: Option<Vec4>,
+}
+
+impl StatSeries {
+    pub fn new(name: &str, values: Vec<f32>) -> Self {
+        Self { name: name.to_string(), values, color: None }
+    }
+    #[must_use]
+    pub fn with_color(self, color: Vec4) -> Self {
+        Self { color: Some(color), ..self }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum StatPlotKind {
+    /// Bars with the number of values in each bin; multiple series are drawn over each other.
+    Histogram,
+    /// A box from the first to the third quartile for every series, with whiskers and outliers.
+    BoxPlot,
+    /// The estimated density of every series, mirrored, with the quartiles inside.
+    Violin,
+}
+
+/// What the pointer is over in a [`StatPlot`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum StatPlotHover {
+    /// A bin of a [`StatPlotKind::Histogram`].
+    Bin(usize),
+    /// A series of a [`StatPlotKind::BoxPlot`] or [`StatPlotKind::Violin`].
+    Series(usize),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum StatPlotEvent {
+    None,
+    /// Statistics finished computing after [`StatPlot::set_series`] or [`StatPlot::set_bin_count`]; see
+    /// [`StatPlot::stats`].
+    StatsComputed,
+    Hovered(Option<StatPlotHover>),
+}
+
+#[derive(Clone, Copy, Default)]
+#[repr(C)]
+struct RectIns {
+    base: QuadIns,
+    color: Vec4,
+}
+
+static RECT_SHADER: Shader = Shader {
+    build_geom: Some(QuadIns::build_geom),
+    code_to_concatenate: &[
+        Cx::STD_SHADER,
+        QuadIns::SHADER,
+        code_fragment!(
+            r#"
+            instance color: vec4;
+
+            fn pixel() -> vec4 {
+                return vec4(color.rgb * color.a, color.a);
+            }"#
+        ),
+    ],
+    ..Shader::DEFAULT
+};
+
+static CIRCLE_SHADER: Shader = Shader {
+    build_geom: Some(QuadIns::build_geom),
+    code_to_concatenate: &[
+        Cx::STD_SHADER,
+        QuadIns::SHADER,
+        code_fragment!(
+            r#"
+            instance color: vec4;
+
+            fn pixel() -> vec4 {
+                let df = Df::viewport(pos * rect_size);
+                let center = rect_size * 0.5;
+                df.circle(center, center.x);
+                return df.fill(color);
+            }"#
+        ),
+    ],
+    ..Shader::DEFAULT
+};
+
+#[derive(Clone, Copy, Default)]
+#[repr(C)]
+struct ViolinIns {
+    base: QuadIns,
+    color: Vec4,
+    top_width: f32,
+    bottom_width: f32,
+}
+
+static VIOLIN_SHADER: Shader = Shader {
+    build_geom: Some(QuadIns::build_geom),
+    code_to_concatenate: &[
+        Cx::STD_SHADER,
+        QuadIns::SHADER,
+        code_fragment!(
+            r#"
+            instance color: vec4;
+            instance top_width: float;
+            instance bottom_width: float;
+
+            fn pixel() -> vec4 {
+                // Each instance is a horizontal slice of a violin, which gets narrower or wider linearly between
+                // the densities at its top and bottom.
+                let half_width = mix(top_width, bottom_width, pos.y) * 0.5;
+                let x = abs((pos.x - 0.5) * rect_size.x);
+                let alpha = color.a * clamp(half_width - x + 0.5, 0., 1.);
+                return vec4(color.rgb * alpha, alpha);
+            }"#
+        ),
+    ],
+    ..Shader::DEFAULT
+};
+
+/// Shows how the values of one or more [`StatSeries`] are distributed, as a histogram, box plot, or violin plot;
+/// see [`StatPlotKind`]. Hovering shows the numbers behind a bin or series.
+///
+/// Bins, quartiles, and densities get computed on a different thread (see [`StatPlot::stats`]), so this also works
+/// for columns with millions of values. Histograms use the same bins for all series; by default the number of bins
+/// follows Sturges' rule.
+pub struct StatPlot {
+    component_id: ComponentId,
+    view: View,
+    background: Background,
+    plot_background: Background,
+    readout_background: Background,
+    axes: PlotAxes,
+    kind: StatPlotKind,
+    bin_count: Option<usize>,
+    names: Vec<String>,
+    colors: Vec<Vec4>,
+    columns: Arc<Vec<Vec<f32>>>,
+    stats: Option<Vec<SeriesStats>>,
+    stats_task: Option<Task<Vec<SeriesStats>>>,
+    hover: Option<StatPlotHover>,
+    /// Which series are hidden or highlighted; see [`StatPlot::set_series_state`].
+    series_state: SeriesState,
+    pointer: Vec2,
+    /// The area inside the axes, in absolute coordinates.
+    plot_rect: Rect,
+    background_color: Vec4,
+    plot_color: Vec4,
+    text_color: Vec4,
+}
+
+impl Default for StatPlot {
+    fn default() -> Self {
+        Self {
+            component_id: Default::default(),
+            view: View::default(),
+            background: Background::default(),
+            plot_background: Background::default(),
+            readout_background: Background::default().with_radius(3.).with_draw_depth(1.),
+            axes: PlotAxes::default(),
+            kind: StatPlotKind::Histogram,
+            bin_count: None,
+            names: vec![],
+            colors: vec![],
+            columns: Arc::new(vec![]),
+            stats: None,
+            stats_task: None,
+            hover: None,
+            series_state: SeriesState::default(),
+            pointer: Vec2::default(),
+            plot_rect: Rect::default(),
+            background_color: vec4(0.12, 0.12, 0.12, 1.),
+            plot_color: vec4(0.08, 0.08, 0.08, 1.),
+            text_color: vec4(0.9, 0.9, 0.9, 1.),
+        }
+    }
+}
+
+impl StatPlot {
+    /// Colors for series that don't have one.
+    const PALETTE: [Vec4; 6] = [
+        vec4(0.35, 0.6, 0.95, 1.),
+        vec4(0.95, 0.55, 0.25, 1.),
+        vec4(0.4, 0.8, 0.45, 1.),
+        vec4(0.85, 0.4, 0.75, 1.),
+        vec4(0.95, 0.85, 0.3, 1.),
+        vec4(0.5, 0.85, 0.9, 1.),
+    ];
+
+    #[must_use]
+    pub fn with_kind(self, kind: StatPlotKind) -> Self {
+        Self { kind, ..self }
+    }
+    /// Number of histogram bins; [`None`] to pick it based on the number of values.
+    #[must_use]
+    pub fn with_bin_count(self, bin_count: Option<usize>) -> Self {
+        Self { bin_count, ..self }
+    }
+
+    pub fn kind(&self) -> StatPlotKind {
+        self.kind
+    }
+
+    pub fn set_kind(&mut self, cx: &mut Cx, kind: StatPlotKind) {
+        self.kind = kind;
+        self.hover = None;
+        cx.request_draw();
+    }
+
+    /// Show `series`, and start computing their statistics. Until that's done, nothing gets drawn.
+    pub fn set_series(&mut self, cx: &mut Cx, series: Vec<StatSeries>) {
+        self.names = series.iter().map(|series| series.name.clone()).collect();
+        self.colors = series
+            .iter()
+            .enumerate()
+            .map(|(index, series)| series.color.unwrap_or(Self::PALETTE[index % Self::PALETTE.len()]))
+            .collect();
+        self.columns = Arc::new(series.into_iter().map(|series| series.values).collect());
+        self.stats = None;
+        self.hover = None;
+        self.compute_stats(cx);
+    }
+
+    /// Names and colors of the series, for a [`Legend`].
+    pub fn legend_items(&self) -> Vec<LegendItem> {
+        self.names.iter().zip(&self.colors).map(|(name, color)| LegendItem::new(name, *color)).collect()
+    }
+
+    /// Hide series, or highlight one of them by dimming the others, e.g. using the state of a [`Legend`]. Hidden
+    /// series keep their column in box and violin plots, so the others don't move around.
+    pub fn set_series_state(&mut self, cx: &mut Cx, series_state: &SeriesState) {
+        self.series_state = series_state.clone();
+        self.hover = None;
+        cx.request_draw();
+    }
+
+    /// Change the number of histogram bins; see [`StatPlot::with_bin_count`]. Keeps showing the old bins until the
+    /// new ones are computed.
+    pub fn set_bin_count(&mut self, cx: &mut Cx, bin_count: Option<usize>) {
+        self.bin_count = bin_count;
+        self.compute_stats(cx);
+    }
+
+    fn compute_stats(&mut self, cx: &mut Cx) {
+        let columns = Arc::clone(&self.columns);
+        let bin_count = self.bin_count;
+        // Replacing the task means that the output of an older one never gets taken.
+        self.stats_task = Some(cx.spawn(spawn_blocking(move || compute_stats(&columns, bin_count))));
+    }
+
+    /// Statistics of every series, in the order of [`StatPlot::set_series`]; [`None`] while they are being computed.
+    pub fn stats(&self) -> Option<&[SeriesStats]> {
+        self.stats.as_deref()
+    }
+
+    /// The range of values for the vertical axis of box and violin plots.
+    fn value_range(&self, stats: &[SeriesStats]) -> (f64, f64) {
+        let mut range = (f64::INFINITY, f64::NEG_INFINITY);
+        for (_, series) in stats.iter().enumerate().filter(|(index, _)| self.series_state.is_visible(*index)) {
+            let extremes: Vec<f64> = match (self.kind, &series.box_stats) {
+                (StatPlotKind::Violin, _) => series.density.iter().map(|(value, _)| *value).collect(),
+                (_, Some(box_stats)) => vec![box_stats.min, box_stats.max],
+                (_, None) => vec![],
+            };
+            for value in extremes {
+                range = (range.0.min(value), range.1.max(value));
+            }
+        }
+        if range.0 > range.1 {
+            return (0., 1.);
+        }
+        let padding = ((range.1 - range.0) * 0.05).max(0.5);
+        (range.0 - padding, range.1 + padding)
+    }
+
+    fn to_screen_x(&self, value: f64, range: (f64, f64)) -> f32 {
+        self.plot_rect.pos.x + ((value - range.0) / (range.1 - range.0)) as f32 * self.plot_rect.size.x
+    }
+
+    fn to_screen_y(&self, value: f64, range: (f64, f64)) -> f32 {
+        self.plot_rect.pos.y + self.plot_rect.size.y - ((value - range.0) / (range.1 - range.0)) as f32 * self.plot_rect.size.y
+    }
+
+    /// Width of the column that every series gets in box and violin plots.
+    fn slot_width(&self) -> f32 {
+        self.plot_rect.size.x / self.names.len().max(1) as f32
+    }
+
+    fn hover_at(&self, abs: Vec2) -> Option<StatPlotHover> {
+        let stats = self.stats.as_ref()?;
+        if !self.plot_rect.contains(abs) {
+            return None;
+        }
+        match self.kind {
+            StatPlotKind::Histogram => {
+                let bins = &stats.first()?.histogram;
+                let t = ((abs.x - self.plot_rect.pos.x) / self.plot_rect.size.x) as f64;
+                let bin = (t * bins.counts.len() as f64).floor() as usize;
+                if bin < bins.counts.len() {
+                    Some(StatPlotHover::Bin(bin))
+                } else {
+                    None
+                }
+            }
+            StatPlotKind::BoxPlot | StatPlotKind::Violin => {
+                let series = ((abs.x - self.plot_rect.pos.x) / self.slot_width()) as usize;
+                if series < stats.len() && stats[series].box_stats.is_some() && self.series_state.is_visible(series) {
+                    Some(StatPlotHover::Series(series))
+                } else {
+                    None
+                }
+            }
+        }
+    }
+
+    pub fn handle(&mut self, cx: &mut Cx, event: &mut Event) -> StatPlotEvent {
+        if let Some(stats) = self.stats_task.as_ref().and_then(|task| task.take_output(event)) {
+            self.stats_task = None;
+            self.stats = Some(stats);
+            self.hover = None;
+            cx.request_draw();
+            return StatPlotEvent::StatsComputed;
+        }
+
+        if let Event::PointerHover(pe) =
+            event.hits_pointer(cx, self.component_id, self.plot_background.area().get_rect_for_first_instance(cx))
+        {
+            let hover = if pe.hover_state == HoverState::Out { None } else { self.hover_at(pe.abs) };
+            self.pointer = pe.abs;
+            if hover.is_some() || hover != self.hover {
+                // The readout follows the pointer.
+                cx.request_draw();
+            }
+            if hover != self.hover {
+                self.hover = hover;
+                return StatPlotEvent::Hovered(hover);
+            }
+        }
+        StatPlotEvent::None
+    }
+
+    fn color(&self, series: usize, alpha: f32) -> Vec4 {
+        let color = self.colors[series];
+        let alpha = if self.series_state.is_dimmed(series) { alpha * 0.3 } else { alpha };
+        vec4(color.x, color.y, color.z, alpha)
+    }
+
+    fn draw_histogram(&mut self, cx: &mut Cx, stats: &[SeriesStats]) {
+        let bins = match stats.first() {
+            Some(first) => &first.histogram,
+            None => return,
+        };
+        let x_range = (bins.start, bins.end());
+        let visible: Vec<(usize, &SeriesStats)> =
+            stats.iter().enumerate().filter(|(index, _)| self.series_state.is_visible(*index)).collect();
+        let max_count = visible.iter().flat_map(|(_, series)| series.histogram.counts.iter().copied()).max().unwrap_or(0).max(1);
+        let y_range = (0., max_count as f64 * 1.1);
+        self.axes.draw_x(cx, self.plot_rect, x_range);
+        self.axes.draw_y(cx, self.plot_rect, y_range);
+
+        // Overlapping series need to be see-through.
+        let alpha = if visible.len() > 1 { 0.55 } else { 0.85 };
+        let bottom = self.plot_rect.pos.y + self.plot_rect.size.y;
+        let mut instances = vec![];
+        for (series, series_stats) in visible {
+            for (bin, count) in series_stats.histogram.counts.iter().enumerate() {
+                if *count == 0 {
+                    continue;
+                }
+                let (lower, upper) = series_stats.histogram.bin_range(bin);
+                let left = self.to_screen_x(lower, x_range);
+                let right = self.to_screen_x(upper, x_range);
+                let top = self.to_screen_y(*count as f64, y_range);
+                let alpha = if self.hover == Some(StatPlotHover::Bin(bin)) { 1. } else { alpha };
+                instances.push(RectIns {
+                    base: QuadIns::from_rect(Rect {
+                        pos: vec2(left + 0.5, top),
+                        size: vec2((right - left - 1.).max(1.), bottom - top),
+                    }),
+                    color: self.color(series, alpha),
+                });
+            }
+        }
+        cx.add_instances(&RECT_SHADER, &instances);
+    }
+
+    /// Draw the name of every series below its column, for box and violin plots.
+    fn draw_series_names(&self, cx: &mut Cx) {
+        let props =
+            TextInsProps { color: self.axes.text_color, position_anchoring: TEXT_ANCHOR_CENTER_H, ..TextInsProps::DEFAULT };
+        for (index, name) in self.names.iter().enumerate() {
+            let x = self.plot_rect.pos.x + (index as f32 + 0.5) * self.slot_width();
+            TextIns::draw_str(cx, name, vec2(x, self.plot_rect.pos.y + self.plot_rect.size.y + 4.), &props);
+        }
+    }
+
+    fn draw_box_plot(&mut self, cx: &mut Cx, stats: &[SeriesStats]) {
+        let range = self.value_range(stats);
+        self.axes.draw_y(cx, self.plot_rect, range);
+        self.draw_series_names(cx);
+
+        let slot_width = self.slot_width();
+        let box_width = (slot_width * 0.5).min(60.);
+        let mut rects = vec![];
+        let mut circles = vec![];
+        for (series, box_stats) in stats
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| self.series_state.is_visible(*index))
+            .filter_map(|(index, s)| Some((index, s.box_stats.as_ref()?)))
+        {
+            let center = self.plot_rect.pos.x + (series as f32 + 0.5) * slot_width;
+            let y = |value: f64| self.to_screen_y(value, range);
+            let alpha = if self.hover == Some(StatPlotHover::Series(series)) { 1. } else { 0.7 };
+            let color = self.color(series, alpha);
+            let mut rect = |left: f32, top: f32, width: f32, height: f32, color: Vec4| {
+                rects.push(RectIns { base: QuadIns::from_rect(Rect { pos: vec2(left, top), size: vec2(width, height) }), color });
+            };
+            // Whisker, with caps at both ends.
+            rect(center - 0.5, y(box_stats.upper_whisker), 1., y(box_stats.lower_whisker) - y(box_stats.upper_whisker), color);
+            for whisker in [box_stats.lower_whisker, box_stats.upper_whisker] {
+                rect(center - box_width / 4., y(whisker) - 0.5, box_width / 2., 1., color);
+            }
+            rect(center - box_width / 2., y(box_stats.q3), box_width, (y(box_stats.q1) - y(box_stats.q3)).max(1.), color);
+            rect(center - box_width / 2., y(box_stats.median) - 1., box_width, 2., vec4(1., 1., 1., 1.));
+            for outlier in &box_stats.outliers {
+                circles.push(RectIns {
+                    base: QuadIns::from_rect(Rect { pos: vec2(center - 2.5, y(*outlier) - 2.5), size: Vec2::all(5.) }),
+                    color,
+                });
+            }
+        }
+        cx.add_instances(&RECT_SHADER, &rects);
+        cx.add_instances(&CIRCLE_SHADER, &circles);
+    }
+
+    fn draw_violin(&mut self, cx: &mut Cx, stats: &[SeriesStats]) {
+        let range = self.value_range(stats);
+        self.axes.draw_y(cx, self.plot_rect, range);
+        self.draw_series_names(cx);
+
+        let slot_width = self.slot_width();
+        // All violins use the same scale, so their areas can be compared.
+        let max_density = stats.iter().flat_map(|series| series.density.iter().map(|(_, density)| *density)).fold(0., f64::max);
+        let width_scale = if max_density > 0. { slot_width as f64 * 0.9 / max_density } else { 0. };
+        let mut slices = vec![];
+        let mut rects = vec![];
+        let mut circles = vec![];
+        for (series, series_stats) in stats.iter().enumerate().filter(|(index, _)| self.series_state.is_visible(*index)) {
+            let center = self.plot_rect.pos.x + (series as f32 + 0.5) * slot_width;
+            let alpha = if self.hover == Some(StatPlotHover::Series(series)) { 1. } else { 0.7 };
+            let color = self.color(series, alpha);
+            for pair in series_stats.density.windows(2) {
+                let ((lower, lower_density), (upper, upper_density)) = (pair[0], pair[1]);
+                let (bottom_width, top_width) = ((lower_density * width_scale) as f32, (upper_density * width_scale) as f32);
+                let top = self.to_screen_y(upper, range);
+                let width = bottom_width.max(top_width) + 2.;
+                slices.push(ViolinIns {
+                    base: QuadIns::from_rect(Rect {
+                        pos: vec2(center - width / 2., top),
+                        size: vec2(width, self.to_screen_y(lower, range) - top),
+                    }),
+                    color,
+                    top_width,
+                    bottom_width,
+                });
+            }
+            if let Some(box_stats) = &series_stats.box_stats {
+                let top = self.to_screen_y(box_stats.q3, range);
+                let bottom = self.to_screen_y(box_stats.q1, range);
+                rects.push(RectIns {
+                    base: QuadIns::from_rect(Rect { pos: vec2(center - 3., top), size: vec2(6., (bottom - top).max(1.)) })
+                        .with_draw_depth(0.1),
+                    color: vec4(0., 0., 0., 0.6),
+                });
+                let median = self.to_screen_y(box_stats.median, range);
+                circles.push(RectIns {
+                    base: QuadIns::from_rect(Rect { pos: vec2(center - 3., median - 3.), size: Vec2::all(6.) })
+                        .with_draw_depth(0.2),
+                    color: vec4(1., 1., 1., 1.),
+                });
+            }
+        }
+        cx.add_instances(&VIOLIN_SHADER, &slices);
+        cx.add_instances(&RECT_SHADER, &rects);
+        cx.add_instances(&CIRCLE_SHADER, &circles);
+    }
+
+    /// Lines of text describing what's under the pointer.
+    fn readout_lines(&self, stats: &[SeriesStats]) -> Vec<String> {
+        match self.hover {
+            Some(StatPlotHover::Bin(bin)) => {
+                let bins = &stats[0].histogram;
+                let (lower, upper) = bins.bin_range(bin);
+                // Show one more decimal than the axis, since bins are smaller than the distance between ticks.
+                let precision = bins.bin_width / 10.;
+                let mut lines = vec![format!("{} to {}", format_tick(lower, precision), format_tick(upper, precision))];
+                for (index, (name, series)) in self.names.iter().zip(stats).enumerate() {
+                    if !self.series_state.is_visible(index) {
+                        continue;
+                    }
+                    lines.push(format!("{}: {}", name, series.histogram.counts[bin]));
+                }
+                lines
+            }
+            Some(StatPlotHover::Series(series)) => match &stats[series].box_stats {
+                Some(box_stats) => {
+                    let range = self.value_range(stats);
+                    let precision = (range.1 - range.0) / 1000.;
+                    let mut lines = vec![self.names[series].clone(), format!("count: {}", box_stats.count)];
+                    for (label, value) in [
+                        ("max", box_stats.max),
+                        ("q3", box_stats.q3),
+                        ("median", box_stats.median),
+                        ("q1", box_stats.q1),
+                        ("min", box_stats.min),
+                    ] {
+                        lines.push(format!("{}: {}", label, format_tick(value, precision)));
+                    }
+                    lines
+                }
+                None => vec![],
+            },
+            None => vec![],
+        }
+    }
+
+    fn draw_readout(&mut self, cx: &mut Cx, stats: &[SeriesStats]) {
+        let lines = self.readout_lines(stats);
+        if lines.is_empty() {
+            return;
+        }
+        let props = TextInsProps { color: self.text_color, draw_depth: 1.1, ..TextInsProps::DEFAULT };
+        let line_height = cx.measure_wrapped_text(&props, "X", f32::INFINITY).line_height;
+        let width = lines.iter().map(|line| cx.measure_wrapped_text(&props, line, f32::INFINITY).width).fold(0., f32::max);
+        let size = vec2(width + 16., lines.len() as f32 * line_height + 12.);
+        // Keep the readout inside the plot, flipping it to the other side of the pointer when needed.
+        let mut pos = self.pointer + vec2(14., 14.);
+        let plot_max = self.plot_rect.pos + self.plot_rect.size;
+        if pos.x + size.x > plot_max.x {
+            pos.x = self.pointer.x - 14. - size.x;
+        }
+        if pos.y + size.y > plot_max.y {
+            pos.y = (plot_max.y - size.y).max(self.plot_rect.pos.y);
+        }
+        self.readout_background.draw(cx, Rect { pos, size }, vec4(0.2, 0.2, 0.2, 0.95));
+        for (index, line) in lines.iter().enumerate() {
+            TextIns::draw_str(cx, line, pos + vec2(8., 6. + index as f32 * line_height), &props);
+        }
+    }
+
+    pub fn draw(&mut self, cx: &mut Cx) {
+        self.view.begin_view(cx, LayoutSize::FILL);
+        let rect = cx.add_box(LayoutSize::FILL);
+        self.plot_rect = PlotAxes::plot_rect(rect);
+        self.background.draw(cx, rect, self.background_color);
+        self.plot_background.draw(cx, self.plot_rect, self.plot_color);
+
+        if let Some(stats) = self.stats.take() {
+            match self.kind {
+                StatPlotKind::Histogram => self.draw_histogram(cx, &stats),
+                StatPlotKind::BoxPlot => self.draw_box_plot(cx, &stats),
+                StatPlotKind::Violin => self.draw_violin(cx, &stats),
+            }
+            self.draw_readout(cx, &stats);
+            self.stats = Some(stats);
+        }
+
+        self.view.end_view(cx);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_histogram_and_box_stats() {
+        let values = [1., 2., 2., 3., 3., 3., 4., f32::NAN, 20.];
+        let bins = histogram(&values, 0., 5., 5);
+        assert_eq!(bins.counts, vec![0, 1, 2, 3, 1]);
+        assert_eq!(bins.bin_range(2), (2., 3.));
+
+        let box_stats = BoxStats::from_values(&values).unwrap();
+        assert_eq!(box_stats.count, 8);
+        assert_eq!((box_stats.q1, box_stats.median, box_stats.q3), (2., 3., 3.25));
+        assert_eq!((box_stats.lower_whisker, box_stats.upper_whisker), (1., 4.));
+        assert_eq!(box_stats.outliers, vec![20.]);
+        assert_eq!(BoxStats::from_values(&[f32::NAN]), None);
+    }
+
+    #[test]
+    fn test_kernel_density() {
+        let values: Vec<f32> = (0..1000).map(|i| (i % 100) as f32 / 10.).collect();
+        let density = kernel_density(&values, 200);
+        assert_eq!(density.len(), 200);
+        // The estimated density should integrate to about 1.
+        let step = density[1].0 - density[0].0;
+        let total: f64 = density.iter().map(|(_, density)| density * step).sum();
+        assert!((total - 1.).abs() < 0.01, "{}", total);
+
+        let stats = compute_stats(&[values, vec![]], None);
+        assert_eq!(stats[0].histogram.counts.len(), 11);
+        assert_eq!(stats[1].histogram.counts.iter().sum::<usize>(), 0);
+        assert_eq!(stats[1].box_stats, None);
+    }
+}
@@ -0,0 +1,516 @@
+//! A row of tabs that can be selected, closed, and reordered by dragging.
+
+use crate::scrollview::*;
+use zaplib::*;
+
+#[derive(Clone, Copy, Default)]
+#[repr(C)]
+struct TabBarTabIns {
+    base: QuadIns,
+    color: Vec4,
+    accent_color: Vec4,
+    text_color: Vec4,
+    selected: f32,
+    /// 0 when the close button is hidden, 1 when it's visible, and 2 when the pointer is over it.
+    close_state: f32,
+    dirty: f32,
+}
+
+static SHADER: Shader = Shader {
+    build_geom: Some(QuadIns::build_geom),
+    code_to_concatenate: &[
+        Cx::STD_SHADER,
+        QuadIns::SHADER,
+        code_fragment!(
+            r#"
+            instance color: vec4;
+            instance accent_color: vec4;
+            instance text_color: vec4;
+            instance selected: float;
+            instance close_state: float;
+            instance dirty: float;
+
+            // Distance from the right edge of the tab to the center of the close button.
+            const close_offset: float = 18.;
+
+            fn pixel() -> vec4 {
+                let df = Df::viewport(pos * rect_size);
+                df.rect(vec2(0.), rect_size);
+                df.fill(color);
+                if selected > 0.5 {
+                    df.new_path();
+                    df.rect(vec2(0., rect_size.y - 2.), vec2(rect_size.x, 2.));
+                    df.fill(accent_color);
+                }
+
+                let c = vec2(rect_size.x - close_offset, rect_size.y / 2.);
+                if close_state > 0.5 {
+                    if close_state > 1.5 {
+                        df.new_path();
+                        df.circle(c, 8.);
+                        df.fill(#fff2);
+                    }
+                    df.new_path();
+                    df.move_to(c - vec2(3.5));
+                    df.line_to(c + vec2(3.5));
+                    df.move_to(c + vec2(3.5, -3.5));
+                    df.line_to(c + vec2(-3.5, 3.5));
+                    df.stroke(text_color, 1.2);
+                } else if dirty > 0.5 {
+                    df.new_path();
+                    df.circle(c, 3.5);
+                    df.fill(text_color);
+                }
+                return df.result;
+            }"#
+        ),
+    ],
+    ..Shader::DEFAULT
+};
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum TabBarEvent {
+    None,
+    /// The user selected the tab at this index.
+    Selected(usize),
+    /// The user clicked the close button of the tab at this index. The tab is not removed automatically, so you can
+    /// e.g. ask to save unsaved changes first; call [`TabBar::remove_tab`] to actually close it.
+    CloseRequested(usize),
+    /// The user dragged a tab from one index to another. The [`TabBar`] has already moved it; do the same in your own
+    /// list of tabs, using `let tab = tabs.remove(from); tabs.insert(to, tab);`.
+    Reordered {
+        from: usize,
+        to: usize,
+    },
+}
+
+struct TabBarTab {
+    label: String,
+    /// Shows a dot instead of the close button, until hovering over the tab. Typically used for unsaved changes.
+    dirty: bool,
+    closeable: bool,
+    component_id: ComponentId,
+    close_component_id: ComponentId,
+    area: Area,
+    /// Where the tab was laid out during the last draw, before scrolling and ignoring dragging.
+    layout_rect: Rect,
+}
+
+struct TabDrag {
+    /// The x position of the dragged tab in layout coordinates, when the drag started.
+    origin_x: f32,
+    /// How far the pointer has moved horizontally since the drag started.
+    offset: f32,
+    /// Only start dragging after moving a few pixels, so that clicks don't reorder.
+    started: bool,
+}
+
+/// A horizontally scrollable row of tabs, independent of [`crate::Dock`]. Tabs are identified by their index, which
+/// changes when tabs get reordered, inserted, or removed.
+///
+/// Tabs get selected when clicking on them or when using the arrow keys while the bar has focus, and can be dragged
+/// to reorder them. Closeable tabs show a close button when selected or hovered over, and dirty tabs show a dot in
+/// its place otherwise.
+pub struct TabBar {
+    component_id: ComponentId,
+    tabs: Vec<TabBarTab>,
+    selected: Option<usize>,
+    /// The tab that the pointer is over.
+    hover: Option<usize>,
+    /// The tab whose close button the pointer is over.
+    close_hover: Option<usize>,
+    /// The tab whose close button the pointer went down on.
+    close_pressed: Option<usize>,
+    /// The tab that is being dragged, see [`TabDrag`].
+    drag: Option<(usize, TabDrag)>,
+    scroll_view: ScrollView,
+    /// Set when the selection changes, so we scroll it into view after the next draw.
+    scroll_to_selected: bool,
+    color: Vec4,
+    selected_color: Vec4,
+    accent_color: Vec4,
+    text_color: Vec4,
+    selected_text_color: Vec4,
+}
+
+impl Default for TabBar {
+    fn default() -> Self {
+        Self {
+            component_id: ComponentId::default(),
+            tabs: vec![],
+            selected: None,
+            hover: None,
+            close_hover: None,
+            close_pressed: None,
+            drag: None,
+            scroll_view: ScrollView::default().with_scroll_h(
+                ScrollBarConfig::default().with_bar_size(4.).with_smoothing(0.15).with_use_vertical_pointer_scroll(true),
+            ),
+            scroll_to_selected: false,
+            color: vec4(0.2, 0.2, 0.2, 1.),
+            selected_color: vec4(0.16, 0.16, 0.16, 1.),
+            accent_color: vec4(0.27, 0.56, 0.93, 1.),
+            text_color: vec4(0.62, 0.62, 0.62, 1.),
+            selected_text_color: vec4(1., 1., 1., 1.),
+        }
+    }
+}
+
+impl TabBar {
+    const HEIGHT: f32 = 32.;
+    const PADDING_H: f32 = 12.;
+    /// Room for the close button or dirty dot; see `close_offset` in [`SHADER`].
+    const CLOSE_WIDTH: f32 = 26.;
+    /// Radius around the center of the close button that counts as clicking it.
+    const CLOSE_RADIUS: f32 = 8.;
+    const DRAG_THRESHOLD: f32 = 5.;
+    const DRAG_DRAW_DEPTH: f32 = 2.;
+
+    /// Add tabs with these labels, selecting the first one.
+    #[must_use]
+    pub fn with_tabs(mut self, labels: &[&str]) -> Self {
+        self.tabs.extend(labels.iter().map(|label| Self::new_tab(label)));
+        if self.selected.is_none() && !self.tabs.is_empty() {
+            self.selected = Some(0);
+        }
+        self
+    }
+    #[must_use]
+    pub fn with_color(self, color: Vec4) -> Self {
+        Self { color, ..self }
+    }
+    #[must_use]
+    pub fn with_selected_color(self, selected_color: Vec4) -> Self {
+        Self { selected_color, ..self }
+    }
+    #[must_use]
+    pub fn with_accent_color(self, accent_color: Vec4) -> Self {
+        Self { accent_color, ..self }
+    }
+
+    fn new_tab(label: &str) -> TabBarTab {
+        TabBarTab {
+            label: label.to_string(),
+            dirty: false,
+            closeable: true,
+            component_id: ComponentId::default(),
+            close_component_id: ComponentId::default(),
+            area: Area::Empty,
+            layout_rect: Rect::default(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.tabs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tabs.is_empty()
+    }
+
+    /// Add a tab at the end, returning its index. Doesn't change the selection, unless there was no selected tab yet.
+    pub fn add_tab(&mut self, cx: &mut Cx, label: &str) -> usize {
+        self.insert_tab(cx, self.tabs.len(), label);
+        self.tabs.len() - 1
+    }
+
+    pub fn insert_tab(&mut self, cx: &mut Cx, index: usize, label: &str) {
+        self.tabs.insert(index, Self::new_tab(label));
+        self.selected = match self.selected {
+            Some(selected) if selected >= index => Some(selected + 1),
+            Some(selected) => Some(selected),
+            None => Some(index),
+        };
+        self.reset_pointer_state();
+        cx.request_draw();
+    }
+
+    /// Remove a tab, e.g. after getting [`TabBarEvent::CloseRequested`]. When removing the selected tab, the tab after
+    /// it gets selected (or the one before it when removing the last tab), without sending [`TabBarEvent::Selected`].
+    pub fn remove_tab(&mut self, cx: &mut Cx, index: usize) {
+        self.tabs.remove(index);
+        self.selected = match self.selected {
+            _ if self.tabs.is_empty() => None,
+            Some(selected) if selected > index => Some(selected - 1),
+            Some(selected) if selected == index => Some(index.min(self.tabs.len() - 1)),
+            selected => selected,
+        };
+        self.reset_pointer_state();
+        self.scroll_to_selected = true;
+        cx.request_draw();
+    }
+
+    /// Indices shift when adding or removing tabs, so forget about any tab that the pointer is on.
+    fn reset_pointer_state(&mut self) {
+        self.hover = None;
+        self.close_hover = None;
+        self.close_pressed = None;
+        self.drag = None;
+    }
+
+    pub fn label(&self, index: usize) -> &str {
+        &self.tabs[index].label
+    }
+
+    pub fn set_label(&mut self, cx: &mut Cx, index: usize, label: &str) {
+        self.tabs[index].label = label.to_string();
+        cx.request_draw();
+    }
+
+    pub fn is_dirty(&self, index: usize) -> bool {
+        self.tabs[index].dirty
+    }
+
+    /// Show a dot on the tab, typically for unsaved changes.
+    pub fn set_dirty(&mut self, cx: &mut Cx, index: usize, dirty: bool) {
+        self.tabs[index].dirty = dirty;
+        cx.request_draw();
+    }
+
+    /// Whether to show a close button on the tab. Tabs are closeable by default.
+    pub fn set_closeable(&mut self, cx: &mut Cx, index: usize, closeable: bool) {
+        self.tabs[index].closeable = closeable;
+        cx.request_draw();
+    }
+
+    pub fn selected(&self) -> Option<usize> {
+        self.selected
+    }
+
+    /// Select a tab and scroll it into view, without sending [`TabBarEvent::Selected`].
+    pub fn set_selected(&mut self, cx: &mut Cx, selected: Option<usize>) {
+        self.selected = selected;
+        self.scroll_to_selected = true;
+        cx.request_draw();
+    }
+
+    fn select(&mut self, cx: &mut Cx, index: usize) -> TabBarEvent {
+        if self.selected == Some(index) {
+            return TabBarEvent::None;
+        }
+        self.set_selected(cx, Some(index));
+        TabBarEvent::Selected(index)
+    }
+
+    /// The center of the close button of a tab drawn at `rect`.
+    fn close_center(rect: Rect) -> Vec2 {
+        vec2(rect.pos.x + rect.size.x - Self::CLOSE_WIDTH / 2. - 5., rect.pos.y + rect.size.y / 2.)
+    }
+
+    fn close_rect(&self, cx: &Cx, index: usize) -> Option<Rect> {
+        let tab = &self.tabs[index];
+        if !tab.closeable {
+            return None;
+        }
+        let rect = tab.area.get_rect_for_first_instance(cx)?;
+        let center = Self::close_center(rect);
+        Some(Rect { pos: center - vec2(Self::CLOSE_RADIUS, Self::CLOSE_RADIUS), size: Vec2::all(Self::CLOSE_RADIUS * 2.) })
+    }
+
+    /// Move the dragged tab to where the pointer is, swapping it with its neighbours as it passes their centers.
+    fn handle_drag(&mut self, cx: &mut Cx, index: usize, offset: f32) -> TabBarEvent {
+        let (from, drag) = match &mut self.drag {
+            Some((from, drag)) if *from == index => (*from, drag),
+            _ => return TabBarEvent::None,
+        };
+        if !drag.started && offset.abs() < Self::DRAG_THRESHOLD {
+            return TabBarEvent::None;
+        }
+        drag.started = true;
+        drag.offset = offset;
+        cx.request_draw();
+
+        let left = drag.origin_x + offset;
+        let right = left + self.tabs[from].layout_rect.size.x;
+        let center = |tab: &TabBarTab| tab.layout_rect.pos.x + tab.layout_rect.size.x / 2.;
+        let to = if from + 1 < self.tabs.len() && right > center(&self.tabs[from + 1]) {
+            from + 1
+        } else if from > 0 && left < center(&self.tabs[from - 1]) {
+            from - 1
+        } else {
+            return TabBarEvent::None;
+        };
+
+        // Swap the layout rects too, so the next pointer move compares against the new neighbours even before
+        // redrawing.
+        let x = self.tabs[from].layout_rect.pos.x.min(self.tabs[to].layout_rect.pos.x);
+        self.tabs.swap(from, to);
+        let (first, second) = (from.min(to), from.max(to));
+        self.tabs[first].layout_rect.pos.x = x;
+        self.tabs[second].layout_rect.pos.x = x + self.tabs[first].layout_rect.size.x;
+
+        if let Some((dragged, _)) = &mut self.drag {
+            *dragged = to;
+        }
+        if self.selected == Some(from) {
+            self.selected = Some(to);
+        } else if self.selected == Some(to) {
+            self.selected = Some(from);
+        }
+        self.hover = Some(to);
+        TabBarEvent::Reordered { from, to }
+    }
+
+    pub fn handle(&mut self, cx: &mut Cx, event: &mut Event) -> TabBarEvent {
+        self.scroll_view.handle(cx, event);
+
+        if let Event::KeyDown(ke) = event.hits_keyboard(cx, self.component_id) {
+            match (ke.key_code, self.selected) {
+                (KeyCode::ArrowLeft, Some(selected)) if selected > 0 => return self.select(cx, selected - 1),
+                (KeyCode::ArrowRight, Some(selected)) if selected + 1 < self.tabs.len() => return self.select(cx, selected + 1),
+                (KeyCode::ArrowLeft | KeyCode::ArrowRight, None) if !self.tabs.is_empty() => return self.select(cx, 0),
+                _ => {}
+            }
+        }
+
+        for index in 0..self.tabs.len() {
+            // Hit test the close button first, so clicking it doesn't select the tab.
+            let close_rect = self.close_rect(cx, index);
+            match event.hits_pointer(cx, self.tabs[index].close_component_id, close_rect) {
+                Event::PointerHover(pe) => {
+                    cx.set_hover_mouse_cursor(MouseCursor::Hand);
+                    let close_hover = if pe.hover_state == HoverState::Out { None } else { Some(index) };
+                    if close_hover != self.close_hover {
+                        self.close_hover = close_hover;
+                        cx.request_draw();
+                    }
+                }
+                Event::PointerDown(_) => self.close_pressed = Some(index),
+                Event::PointerUp(pe) if self.close_pressed.take() == Some(index) && pe.is_over => {
+                    return TabBarEvent::CloseRequested(index);
+                }
+                _ => {}
+            }
+
+            let rect = self.tabs[index].area.get_rect_for_first_instance(cx);
+            match event.hits_pointer(cx, self.tabs[index].component_id, rect) {
+                Event::PointerHover(pe) => {
+                    let hover = if pe.hover_state == HoverState::Out { None } else { Some(index) };
+                    if hover != self.hover && (hover.is_some() || self.hover == Some(index)) {
+                        self.hover = hover;
+                        cx.request_draw();
+                    }
+                }
+                Event::PointerDown(_) => {
+                    cx.set_key_focus(Some(self.component_id));
+                    let origin_x = self.tabs[index].layout_rect.pos.x;
+                    self.drag = Some((index, TabDrag { origin_x, offset: 0., started: false }));
+                    return self.select(cx, index);
+                }
+                Event::PointerMove(pe) => return self.handle_drag(cx, index, pe.abs.x - pe.abs_start.x),
+                Event::PointerUp(_) if self.drag.take().map_or(false, |(_, drag)| drag.started) => cx.request_draw(),
+                _ => {}
+            }
+        }
+        TabBarEvent::None
+    }
+
+    pub fn draw(&mut self, cx: &mut Cx) {
+        self.scroll_view.begin_view(cx, LayoutSize::new(Width::Fill, Height::Compute));
+        cx.begin_row(Width::Compute, Height::Fix(Self::HEIGHT));
+
+        let line_height = cx.measure_wrapped_text(&TextInsProps::DEFAULT, "X", f32::INFINITY).line_height;
+        for (index, tab) in self.tabs.iter_mut().enumerate() {
+            let is_selected = self.selected == Some(index);
+            let text_props = TextInsProps {
+                color: if is_selected { self.selected_text_color } else { self.text_color },
+                ..TextInsProps::DEFAULT
+            };
+            let text_width = cx.measure_wrapped_text(&text_props, &tab.label, f32::INFINITY).width;
+            let close_width = if tab.closeable || tab.dirty { Self::CLOSE_WIDTH } else { Self::PADDING_H };
+            let width = Self::PADDING_H + text_width + close_width;
+            tab.layout_rect = cx.add_box(LayoutSize::new(Width::Fix(width), Height::Fix(Self::HEIGHT)));
+
+            let mut rect = tab.layout_rect;
+            let mut draw_depth = 0.;
+            if let Some((dragged, drag)) = &self.drag {
+                if *dragged == index && drag.started {
+                    rect.pos.x = drag.origin_x + drag.offset;
+                    draw_depth = Self::DRAG_DRAW_DEPTH;
+                }
+            }
+
+            let hovered = self.hover == Some(index) || self.close_hover == Some(index);
+            let close_state = if self.close_hover == Some(index) {
+                2.
+            } else if tab.closeable && (hovered || (is_selected && !tab.dirty)) {
+                1.
+            } else {
+                0.
+            };
+            tab.area = cx.add_instances(
+                &SHADER,
+                &[TabBarTabIns {
+                    base: QuadIns::from_rect(rect).with_draw_depth(draw_depth),
+                    color: if is_selected { self.selected_color } else { self.color },
+                    accent_color: self.accent_color,
+                    text_color: text_props.color,
+                    selected: is_selected as u8 as f32,
+                    close_state,
+                    dirty: tab.dirty as u8 as f32,
+                }],
+            );
+            let text_pos = vec2(rect.pos.x + Self::PADDING_H, rect.pos.y + (Self::HEIGHT - line_height) / 2.);
+            TextIns::draw_str(cx, &tab.label, text_pos, &TextInsProps { draw_depth: draw_depth + 0.1, ..text_props });
+        }
+
+        cx.end_row();
+        self.scroll_view.end_view(cx);
+
+        let focus_area = self.selected.or(if self.tabs.is_empty() { None } else { Some(0) });
+        if let Some(index) = focus_area {
+            cx.add_focusable(self.component_id, self.tabs[index].area, Focusable::default());
+        }
+
+        if self.scroll_to_selected {
+            self.scroll_to_selected = false;
+            if let Some(selected) = self.selected {
+                let rect = self.tabs[selected].layout_rect;
+                self.scroll_view.scroll_into_view_abs(cx, rect);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    use zaplib::*;
+
+    #[test]
+    fn test_tab_bar_add_remove() {
+        let mut test_cx = TestCx::new(vec2(400., 300.));
+        let cx = &mut test_cx.cx;
+        let mut tab_bar = TabBar::default().with_tabs(&["main.rs", "lib.rs", "mod.rs"]);
+        tab_bar.set_selected(cx, Some(1));
+
+        tab_bar.insert_tab(cx, 0, "new.rs");
+        assert_eq!(tab_bar.selected(), Some(2));
+
+        tab_bar.remove_tab(cx, 2);
+        assert_eq!(tab_bar.label(2), "mod.rs");
+        assert_eq!(tab_bar.selected(), Some(2));
+
+        tab_bar.remove_tab(cx, 2);
+        assert_eq!(tab_bar.selected(), Some(1));
+        tab_bar.remove_tab(cx, 0);
+        tab_bar.remove_tab(cx, 0);
+        assert_eq!(tab_bar.selected(), None);
+        assert!(tab_bar.is_empty());
+    }
+
+    #[test]
+    fn test_tab_bar_drag_reorder() {
+        let mut test_cx = TestCx::new(vec2(800., 300.));
+        let mut tab_bar = TabBar::default().with_tabs(&["first", "second", "third"]);
+        test_cx.draw(|cx| tab_bar.draw(cx));
+
+        let events = test_cx.pointer_down(vec2(15., 16.), |cx, event| tab_bar.handle(cx, event));
+        assert!(events.iter().all(|event| *event == TabBarEvent::None));
+        let events = test_cx.pointer_move(vec2(215., 16.), |cx, event| tab_bar.handle(cx, event));
+        assert!(events.contains(&TabBarEvent::Reordered { from: 0, to: 1 }));
+        assert_eq!(tab_bar.label(1), "first");
+        assert_eq!(tab_bar.selected(), Some(1));
+        test_cx.pointer_up(vec2(215., 16.), |cx, event| tab_bar.handle(cx, event));
+    }
+}
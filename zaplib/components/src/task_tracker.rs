@@ -0,0 +1,258 @@
+//! Running jobs in the background while showing their progress, with buttons to cancel them.
+
+use crate::button::*;
+use crate::progress::*;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use zaplib::*;
+
+/// Sent by jobs when they made progress or finished.
+const STATUS_TASK_UPDATE: StatusId = location_hash!();
+
+/// Identifies a job that was started using [`TaskTracker::spawn`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct TaskId(u64);
+
+/// State that is shared between a job and the [`TaskTracker`].
+struct TaskState<T> {
+    progress: Option<f32>,
+    message: String,
+    result: Option<T>,
+    finished: bool,
+}
+
+struct TaskShared<T> {
+    state: Mutex<TaskState<T>>,
+    cancelled: AtomicBool,
+    signal: Signal,
+}
+
+/// Passed into jobs started using [`TaskTracker::spawn`], for reporting progress and checking if the job was
+/// cancelled.
+pub struct TaskProgress<T> {
+    shared: Arc<TaskShared<T>>,
+}
+
+impl<T> TaskProgress<T> {
+    /// Set the progress, from 0 to 1, or [`None`] if it's unknown how long things will take.
+    pub fn set_progress(&self, progress: Option<f32>) {
+        let progress = progress.map(|progress| progress.clamp(0., 1.));
+        let mut state = self.shared.state.lock().unwrap();
+        // Don't bother the main thread with changes that aren't visible.
+        let changed = match (state.progress, progress) {
+            (Some(old), Some(new)) => (old - new).abs() >= 0.001,
+            (old, new) => old.is_some() != new.is_some(),
+        };
+        state.progress = progress;
+        drop(state);
+        if changed {
+            Cx::post_signal(self.shared.signal, STATUS_TASK_UPDATE);
+        }
+    }
+
+    /// Set a message to show next to the progress bar, like the file currently being processed.
+    pub fn set_message(&self, message: &str) {
+        self.shared.state.lock().unwrap().message = message.to_string();
+        Cx::post_signal(self.shared.signal, STATUS_TASK_UPDATE);
+    }
+
+    /// Whether the user cancelled the job. Check this regularly, and return early when it's `true`; whatever you
+    /// return after cancellation gets thrown away.
+    pub fn is_cancelled(&self) -> bool {
+        self.shared.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+/// Marks the job as finished when it's dropped, even when the job panics.
+struct FinishGuard<T> {
+    shared: Arc<TaskShared<T>>,
+}
+
+impl<T> Drop for FinishGuard<T> {
+    fn drop(&mut self) {
+        self.shared.state.lock().unwrap().finished = true;
+        Cx::post_signal(self.shared.signal, STATUS_TASK_UPDATE);
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum TaskTrackerEvent<T> {
+    None,
+    /// The job returned this value.
+    Finished {
+        id: TaskId,
+        result: T,
+    },
+    /// The job finished after it was cancelled, see [`TaskProgress::is_cancelled`].
+    Cancelled(TaskId),
+    /// The job panicked. This also results in an [`Event::WorkerPanic`].
+    Panicked(TaskId),
+}
+
+struct TrackedTask<T> {
+    id: TaskId,
+    label: String,
+    shared: Arc<TaskShared<T>>,
+    progress_bar: ProgressBar,
+    cancel_button: Button,
+}
+
+/// Runs jobs on background threads using [`universal_thread::spawn`], and shows a row with a label, progress bar,
+/// and cancel button for each of them. Jobs report their progress using the [`TaskProgress`] passed into them, and
+/// what they return comes back in [`TaskTrackerEvent::Finished`].
+///
+/// ```ignore
+/// let id = self.task_tracker.spawn(cx, "Indexing files", move |progress| {
+///     for (index, path) in paths.iter().enumerate() {
+///         if progress.is_cancelled() {
+///             return 0;
+///         }
+///         progress.set_progress(Some(index as f32 / paths.len() as f32));
+///         index_file(path);
+///     }
+///     paths.len()
+/// });
+///
+/// // In `handle`:
+/// if let TaskTrackerEvent::Finished { result, .. } = self.task_tracker.handle(cx, event) {
+///     log!("Indexed {} files", result);
+/// }
+/// ```
+pub struct TaskTracker<T: Send + 'static = ()> {
+    tasks: Vec<TrackedTask<T>>,
+    next_id: u64,
+    signal: Signal,
+}
+
+impl<T: Send + 'static> Default for TaskTracker<T> {
+    fn default() -> Self {
+        Self { tasks: vec![], next_id: 0, signal: Signal::default() }
+    }
+}
+
+impl<T: Send + 'static> TaskTracker<T> {
+    /// Start running `job` on a background thread, showing `label` next to its progress. Progress is indeterminate
+    /// until the job calls [`TaskProgress::set_progress`].
+    pub fn spawn(&mut self, cx: &mut Cx, label: &str, job: impl FnOnce(&TaskProgress<T>) -> T + Send + 'static) -> TaskId {
+        if self.signal.signal_id == 0 {
+            self.signal = cx.new_signal();
+        }
+        let id = TaskId(self.next_id);
+        self.next_id += 1;
+
+        let shared = Arc::new(TaskShared {
+            state: Mutex::new(TaskState { progress: None, message: String::new(), result: None, finished: false }),
+            cancelled: AtomicBool::new(false),
+            signal: self.signal,
+        });
+        let progress = TaskProgress { shared: Arc::clone(&shared) };
+        universal_thread::spawn(move || {
+            let _guard = FinishGuard { shared: Arc::clone(&progress.shared) };
+            let result = job(&progress);
+            progress.shared.state.lock().unwrap().result = Some(result);
+        });
+
+        self.tasks.push(TrackedTask {
+            id,
+            label: label.to_string(),
+            shared,
+            progress_bar: ProgressBar::default(),
+            cancel_button: Button::default(),
+        });
+        cx.request_draw();
+        id
+    }
+
+    /// Ask a job to stop, see [`TaskProgress::is_cancelled`]. It keeps showing until it actually stops.
+    pub fn cancel(&mut self, cx: &mut Cx, id: TaskId) {
+        if let Some(task) = self.tasks.iter().find(|task| task.id == id) {
+            task.shared.cancelled.store(true, Ordering::Relaxed);
+            cx.request_draw();
+        }
+    }
+
+    /// Whether the job is still running.
+    pub fn is_running(&self, id: TaskId) -> bool {
+        self.tasks.iter().any(|task| task.id == id)
+    }
+
+    /// The number of jobs that are still running.
+    pub fn len(&self) -> usize {
+        self.tasks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tasks.is_empty()
+    }
+
+    pub fn handle(&mut self, cx: &mut Cx, event: &mut Event) -> TaskTrackerEvent<T> {
+        for index in 0..self.tasks.len() {
+            let task = &mut self.tasks[index];
+            task.progress_bar.handle(cx, event);
+            if task.cancel_button.handle(cx, event) == ButtonEvent::Clicked {
+                let id = task.id;
+                self.cancel(cx, id);
+            }
+        }
+
+        if let Event::Signal(se) = event {
+            if se.signals.contains_key(&self.signal) {
+                cx.request_draw();
+                if let Some(index) = self.tasks.iter().position(|task| task.shared.state.lock().unwrap().finished) {
+                    let task = self.tasks.remove(index);
+                    // Other jobs might have finished too; get another signal to handle them.
+                    if self.tasks.iter().any(|task| task.shared.state.lock().unwrap().finished) {
+                        cx.send_signal(self.signal, STATUS_TASK_UPDATE);
+                    }
+                    if task.shared.cancelled.load(Ordering::Relaxed) {
+                        return TaskTrackerEvent::Cancelled(task.id);
+                    }
+                    let result = task.shared.state.lock().unwrap().result.take();
+                    return match result {
+                        Some(result) => TaskTrackerEvent::Finished { id: task.id, result },
+                        None => TaskTrackerEvent::Panicked(task.id),
+                    };
+                }
+            }
+        }
+        TaskTrackerEvent::None
+    }
+
+    /// Draw a row for every running job.
+    pub fn draw(&mut self, cx: &mut Cx) {
+        /// Room for the cancel button.
+        const BUTTON_WIDTH: f32 = 90.;
+
+        for task in &mut self.tasks {
+            let (progress, message) = {
+                let state = task.shared.state.lock().unwrap();
+                (state.progress, state.message.clone())
+            };
+            let cancelled = task.shared.cancelled.load(Ordering::Relaxed);
+
+            cx.begin_row(Width::Fill, Height::Compute);
+            cx.begin_column(Width::Fix((cx.get_width_left() - BUTTON_WIDTH).max(0.)), Height::Compute);
+            let mut text = task.label.clone();
+            if cancelled {
+                text.push_str(" (cancelling)");
+            } else if !message.is_empty() {
+                text.push_str(": ");
+                text.push_str(&message);
+            }
+            if let Some(progress) = progress {
+                text.push_str(&format!(" {:.0}%", progress * 100.));
+            }
+            let props = TextInsProps {
+                wrapping: Wrapping::Ellipsis(cx.get_width_left()),
+                color: vec4(0.8, 0.8, 0.8, 1.),
+                ..TextInsProps::DEFAULT
+            };
+            TextIns::draw_walk(cx, &text, &props);
+            cx.add_box(LayoutSize::new(Width::Fill, Height::Fix(4.)));
+            task.progress_bar.draw(cx, progress);
+            cx.end_column();
+            task.cancel_button.draw(cx, "Cancel");
+            cx.end_row();
+        }
+    }
+}
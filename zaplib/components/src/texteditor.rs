@@ -870,7 +870,8 @@ impl TextEditor {
                 //return CodeEditorEvent::FoldStart
             }
             KeyCode::Tab => {
-                if !self.read_only {
+                // Single line editors use Tab for moving to the next focusable component instead.
+                if !self.read_only && self.multiline {
                     if ke.modifiers.shift {
                         self.cursors.remove_tab(text_buffer, 4);
                     } else {
@@ -1321,6 +1322,7 @@ impl TextEditor {
                 &draw_str_props.text_style,
                 &cx.fonts_data,
                 cx.current_dpi_factor,
+                cx.current_text_rendering().subpixel_positioning,
                 draw_str_props.font_scale,
                 draw_str_props.draw_depth,
                 draw_str_props.color,
@@ -1579,6 +1581,7 @@ impl TextEditor {
                     &TEXT_STYLE_MONO,
                     &cx.fonts_data,
                     cx.current_dpi_factor,
+                    cx.current_text_rendering().subpixel_positioning,
                     self.current_font_scale,
                     0.,
                     color,
@@ -1597,6 +1600,7 @@ impl TextEditor {
                     &TEXT_STYLE_MONO,
                     &cx.fonts_data,
                     cx.current_dpi_factor,
+                    cx.current_text_rendering().subpixel_positioning,
                     self.current_font_scale,
                     0.,
                     color,
@@ -1748,6 +1752,9 @@ impl TextEditor {
 
         cx.end_row();
         self.view.end_view(cx);
+        // Tab inserts a tab character when editing multiple lines, so then you can't leave using Tab.
+        let captures_tab = self.multiline && !self.read_only;
+        cx.add_focusable(self.component_id, self.view.area(), Focusable { captures_tab, ..Focusable::default() });
 
         if let Some(((head, tail), at_top)) = self._set_last_cursor {
             self._set_last_cursor = None;
@@ -0,0 +1,825 @@
+//! A map made out of raster tiles (a "slippy map"), with markers and lines on top.
+
+use crate::background::*;
+use std::collections::HashMap;
+use std::f64::consts::PI;
+use std::io::Read;
+use std::sync::Arc;
+use zaplib::thread_pool::ThreadPool;
+use zaplib::universal_file::UniversalFile;
+use zaplib::*;
+
+/// A position on Earth, in degrees.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct LngLat {
+    pub lng: f64,
+    pub lat: f64,
+}
+
+impl LngLat {
+    /// Latitudes beyond this can't be shown, since they make the web mercator projection square.
+    pub const MAX_LATITUDE: f64 = 85.051_128_779_806_59;
+
+    pub fn new(lng: f64, lat: f64) -> Self {
+        Self { lng, lat }
+    }
+
+    /// Project using web mercator, to coordinates that go from 0 to 1 over the whole world, starting at the top-left
+    /// (north-west). Multiply by the size of the world in pixels to get pixel coordinates at some zoom level.
+    pub fn to_world(self) -> (f64, f64) {
+        let lat = self.lat.clamp(-Self::MAX_LATITUDE, Self::MAX_LATITUDE).to_radians();
+        ((self.lng + 180.) / 360., (1. - lat.tan().asinh() / PI) / 2.)
+    }
+
+    /// The inverse of [`LngLat::to_world`].
+    pub fn from_world(x: f64, y: f64) -> Self {
+        Self { lng: x * 360. - 180., lat: (PI * (1. - 2. * y)).sinh().atan().to_degrees() }
+    }
+}
+
+/// Identifies a tile: at zoom level `z` the world is split into `2^z` by `2^z` tiles, numbered from the top-left.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct TileKey {
+    pub z: u32,
+    pub x: u32,
+    pub y: u32,
+}
+
+impl TileKey {
+    fn parent(self) -> Option<TileKey> {
+        (self.z > 0).then(|| TileKey { z: self.z - 1, x: self.x / 2, y: self.y / 2 })
+    }
+
+    /// Fill in `{z}`, `{x}`, `{y}`, and `{r}` (which becomes "@2x" for retina tiles) in a URL template.
+    fn url(self, template: &str, retina: bool) -> String {
+        template
+            .replace("{z}", &self.z.to_string())
+            .replace("{x}", &self.x.to_string())
+            .replace("{y}", &self.y.to_string())
+            .replace("{r}", if retina { "@2x" } else { "" })
+    }
+}
+
+/// A dot on a [`TileMap`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct MapMarker {
+    pub position: LngLat,
+    pub color: Vec4,
+    /// In pixels; markers stay the same size when zooming.
+    pub radius: f32,
+}
+
+impl MapMarker {
+    pub fn new(position: LngLat) -> Self {
+        Self { position, color: vec4(0.9, 0.3, 0.2, 1.), radius: 6. }
+    }
+    #[must_use]
+    pub fn with_color(self, color: Vec4) -> Self {
+        Self { color, ..self }
+    }
+    #[must_use]
+    pub fn with_radius(self, radius: f32) -> Self {
+        Self { radius, ..self }
+    }
+}
+
+/// A line through a number of points on a [`TileMap`], like a route.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MapPolyline {
+    pub points: Vec<LngLat>,
+    pub color: Vec4,
+    /// In pixels; lines stay the same width when zooming.
+    pub width: f32,
+}
+
+impl MapPolyline {
+    pub fn new(points: Vec<LngLat>) -> Self {
+        Self { points, color: vec4(0.2, 0.5, 0.9, 1.), width: 3. }
+    }
+    #[must_use]
+    pub fn with_color(self, color: Vec4) -> Self {
+        Self { color, ..self }
+    }
+    #[must_use]
+    pub fn with_width(self, width: f32) -> Self {
+        Self { width, ..self }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum TileMapEvent {
+    None,
+    /// The user panned or zoomed; see [`TileMap::center`] and [`TileMap::zoom`].
+    ViewChanged,
+    /// The user clicked the marker at this index.
+    MarkerClicked(usize),
+}
+
+#[derive(Default)]
+struct CachedTile {
+    /// The encoded image, so the tile can be decoded again without fetching it after it was evicted from the atlas.
+    bytes: Option<Arc<Vec<u8>>>,
+    /// Decoded pixels that still have to be copied into the atlas.
+    pixels: Option<Vec<u32>>,
+    /// Where the tile is in the atlas, if it's there.
+    slot: Option<usize>,
+    /// Set while the tile is being fetched or decoded.
+    loading: bool,
+    failed: bool,
+    /// The value of [`TileMap::draw_counter`] when this tile was last needed.
+    last_used: u64,
+}
+
+/// Sent from the loader threads when a tile has been fetched and decoded.
+struct TileLoaded {
+    component_id: ComponentId,
+    key: TileKey,
+    bytes: Option<Arc<Vec<u8>>>,
+    pixels: Option<Vec<u32>>,
+}
+
+/// A tile to draw, and where.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct VisibleTile {
+    key: TileKey,
+    rect: Rect,
+}
+
+#[derive(Clone, Copy, Default)]
+#[repr(C)]
+struct TileIns {
+    base: QuadIns,
+    /// The part of the atlas to show, in texture coordinates.
+    tex_pos: Vec2,
+    tex_size: Vec2,
+}
+
+static TILE_SHADER: Shader = Shader {
+    build_geom: Some(QuadIns::build_geom),
+    code_to_concatenate: &[
+        Cx::STD_SHADER,
+        QuadIns::SHADER,
+        code_fragment!(
+            r#"
+            texture atlas: texture2D;
+            instance tex_pos: vec2;
+            instance tex_size: vec2;
+
+            fn pixel() -> vec4 {
+                return vec4(sample2d(atlas, tex_pos + pos * tex_size).rgb, 1.);
+            }"#
+        ),
+    ],
+    ..Shader::DEFAULT
+};
+
+#[derive(Clone, Copy, Default)]
+#[repr(C)]
+struct LineIns {
+    base: QuadIns,
+    color: Vec4,
+    start: Vec2,
+    end: Vec2,
+    width: f32,
+}
+
+static LINE_SHADER: Shader = Shader {
+    build_geom: Some(QuadIns::build_geom),
+    code_to_concatenate: &[
+        Cx::STD_SHADER,
+        QuadIns::SHADER,
+        code_fragment!(
+            r#"
+            instance color: vec4;
+            instance start: vec2;
+            instance end: vec2;
+            instance width: float;
+
+            fn pixel() -> vec4 {
+                // Each instance is one segment of a line, in absolute coordinates.
+                let df = Df::viewport(pos * rect_size);
+                df.move_to(start - rect_pos);
+                df.line_to(end - rect_pos);
+                return df.stroke(color, width);
+            }"#
+        ),
+    ],
+    ..Shader::DEFAULT
+};
+
+#[derive(Clone, Copy, Default)]
+#[repr(C)]
+struct MarkerIns {
+    base: QuadIns,
+    color: Vec4,
+}
+
+static MARKER_SHADER: Shader = Shader {
+    build_geom: Some(QuadIns::build_geom),
+    code_to_concatenate: &[
+        Cx::STD_SHADER,
+        QuadIns::SHADER,
+        code_fragment!(
+            r#"
+            instance color: vec4;
+
+            fn pixel() -> vec4 {
+                let df = Df::viewport(pos * rect_size);
+                let center = rect_size * 0.5;
+                df.circle(center, center.x - 1.5);
+                df.fill(color);
+                df.stroke(#fff, 1.5);
+                return df.result;
+            }"#
+        ),
+    ],
+    ..Shader::DEFAULT
+};
+
+/// Shows a map made out of XYZ raster tiles, like the ones from OpenStreetMap, which the user can pan by dragging
+/// and zoom using the mouse wheel or by pinching (see [`Cx::interaction_policy`]). [`MapMarker`]s and
+/// [`MapPolyline`]s are drawn on top, and [`TileMap::project`] converts positions to the screen for drawing other
+/// things.
+///
+/// Tiles are fetched on background threads from the URL template set with [`TileMap::with_tile_url`], e.g.
+/// `"https://tile.example.com/{z}/{x}/{y}{r}.png"`. On high-DPI screens `{r}` becomes "@2x", to load tiles with
+/// twice the resolution; this is decided at the first draw. Only PNG tiles are supported. While tiles load, parts of
+/// lower zoom levels that are already loaded are shown scaled up.
+///
+/// Loaded tiles are kept in one texture atlas of 4096 by 4096 pixels, and the encoded images of recently used tiles
+/// are kept in memory (see [`TileMap::with_cache_size`]), so panning back doesn't fetch them again. Check the usage
+/// policy of your tile server, and set the attribution it requires using [`TileMap::with_attribution`].
+pub struct TileMap {
+    component_id: ComponentId,
+    view: View,
+    background: Background,
+    attribution_background: Background,
+    url_template: String,
+    attribution: String,
+    /// In web mercator coordinates from 0 to 1; see [`LngLat::to_world`].
+    center: (f64, f64),
+    /// Fractional zoom level; the world is `256 * 2^zoom` pixels wide.
+    zoom: f64,
+    min_zoom: f64,
+    max_zoom: f64,
+    max_tile_zoom: u32,
+    markers: Vec<MapMarker>,
+    polylines: Vec<MapPolyline>,
+    origin: Vec2,
+    size: Vec2,
+    drag_start_center: Option<(f64, f64)>,
+    tiles: HashMap<TileKey, CachedTile>,
+    cache_size: usize,
+    /// Counts draws, to find out which tiles were used least recently.
+    draw_counter: u64,
+    atlas: Texture,
+    /// Which tile is in each slot of [`TileMap::atlas`].
+    atlas_slots: Vec<Option<TileKey>>,
+    /// The width and height of tiles in pixels; 0 until the first draw.
+    tile_pixels: usize,
+    loader: Option<ThreadPool>,
+    requests_in_flight: usize,
+    background_color: Vec4,
+}
+
+impl Default for TileMap {
+    fn default() -> Self {
+        Self {
+            component_id: Default::default(),
+            view: View::default(),
+            background: Background::default(),
+            attribution_background: Background::default().with_draw_depth(1.),
+            url_template: String::new(),
+            attribution: String::new(),
+            center: LngLat::default().to_world(),
+            zoom: 2.,
+            min_zoom: 0.,
+            max_zoom: 20.,
+            max_tile_zoom: 19,
+            markers: vec![],
+            polylines: vec![],
+            origin: Vec2::default(),
+            size: Vec2::default(),
+            drag_start_center: None,
+            tiles: HashMap::new(),
+            cache_size: 512,
+            draw_counter: 0,
+            atlas: Texture::default(),
+            atlas_slots: vec![],
+            tile_pixels: 0,
+            loader: None,
+            requests_in_flight: 0,
+            background_color: vec4(0.85, 0.85, 0.82, 1.),
+        }
+    }
+}
+
+impl TileMap {
+    /// The size of a tile on the screen, before zooming in between zoom levels.
+    const TILE_SIZE: f64 = 256.;
+    const ATLAS_SIZE: usize = 4096;
+    /// Tile servers usually don't allow more requests than this at the same time.
+    const MAX_REQUESTS: usize = 6;
+
+    /// Where to fetch tiles from, with `{z}`, `{x}`, `{y}`, and optionally `{r}` in it.
+    #[must_use]
+    pub fn with_tile_url(self, url_template: &str) -> Self {
+        Self { url_template: url_template.to_string(), ..self }
+    }
+    /// The highest zoom level that the tile server has; when zooming in further, its tiles get scaled up.
+    #[must_use]
+    pub fn with_max_tile_zoom(self, max_tile_zoom: u32) -> Self {
+        Self { max_tile_zoom, ..self }
+    }
+    /// Text to show in the bottom-right corner, like "© OpenStreetMap contributors".
+    #[must_use]
+    pub fn with_attribution(self, attribution: &str) -> Self {
+        Self { attribution: attribution.to_string(), ..self }
+    }
+    #[must_use]
+    pub fn with_view(self, center: LngLat, zoom: f64) -> Self {
+        Self { center: center.to_world(), zoom: zoom.clamp(self.min_zoom, self.max_zoom), ..self }
+    }
+    #[must_use]
+    pub fn with_zoom_range(self, min_zoom: f64, max_zoom: f64) -> Self {
+        Self { min_zoom, max_zoom, zoom: self.zoom.clamp(min_zoom, max_zoom), ..self }
+    }
+    /// How many encoded tiles to keep in memory; 512 by default.
+    #[must_use]
+    pub fn with_cache_size(self, cache_size: usize) -> Self {
+        Self { cache_size, ..self }
+    }
+    #[must_use]
+    pub fn with_markers(self, markers: Vec<MapMarker>) -> Self {
+        Self { markers, ..self }
+    }
+    #[must_use]
+    pub fn with_polylines(self, polylines: Vec<MapPolyline>) -> Self {
+        Self { polylines, ..self }
+    }
+
+    pub fn center(&self) -> LngLat {
+        LngLat::from_world(self.center.0, self.center.1)
+    }
+
+    pub fn zoom(&self) -> f64 {
+        self.zoom
+    }
+
+    pub fn set_view(&mut self, cx: &mut Cx, center: LngLat, zoom: f64) {
+        self.center = center.to_world();
+        self.zoom = zoom.clamp(self.min_zoom, self.max_zoom);
+        cx.request_draw();
+    }
+
+    pub fn markers(&self) -> &[MapMarker] {
+        &self.markers
+    }
+
+    pub fn set_markers(&mut self, cx: &mut Cx, markers: Vec<MapMarker>) {
+        self.markers = markers;
+        cx.request_draw();
+    }
+
+    pub fn polylines(&self) -> &[MapPolyline] {
+        &self.polylines
+    }
+
+    pub fn set_polylines(&mut self, cx: &mut Cx, polylines: Vec<MapPolyline>) {
+        self.polylines = polylines;
+        cx.request_draw();
+    }
+
+    /// The size of the whole world in pixels, at the current zoom.
+    fn world_size(&self) -> f64 {
+        Self::TILE_SIZE * self.zoom.exp2()
+    }
+
+    /// Where `position` is on the screen, as of the last draw.
+    pub fn project(&self, position: LngLat) -> Vec2 {
+        let (x, y) = position.to_world();
+        let world_size = self.world_size();
+        let center_offset = vec2(((x - self.center.0) * world_size) as f32, ((y - self.center.1) * world_size) as f32);
+        self.origin + self.size / 2. + center_offset
+    }
+
+    /// The position at `screen_pos`, as of the last draw.
+    pub fn unproject(&self, screen_pos: Vec2) -> LngLat {
+        let (x, y) = self.screen_to_world(screen_pos);
+        LngLat::from_world(x, y)
+    }
+
+    fn screen_to_world(&self, screen_pos: Vec2) -> (f64, f64) {
+        let center_offset = screen_pos - self.origin - self.size / 2.;
+        let world_size = self.world_size();
+        (self.center.0 + center_offset.x as f64 / world_size, self.center.1 + center_offset.y as f64 / world_size)
+    }
+
+    /// Set the center, wrapping around horizontally, and stopping at the top and bottom of the world.
+    fn set_center(&mut self, x: f64, y: f64) {
+        self.center = (x.rem_euclid(1.), y.clamp(0., 1.));
+    }
+
+    /// Zoom to `zoom`, keeping the position under `screen_pos` in place.
+    fn zoom_around(&mut self, screen_pos: Vec2, zoom: f64) {
+        let (x, y) = self.screen_to_world(screen_pos);
+        self.zoom = zoom.clamp(self.min_zoom, self.max_zoom);
+        let center_offset = screen_pos - self.origin - self.size / 2.;
+        let world_size = self.world_size();
+        self.set_center(x - center_offset.x as f64 / world_size, y - center_offset.y as f64 / world_size);
+    }
+
+    fn marker_at(&self, abs: Vec2) -> Option<usize> {
+        // Markers are drawn in order, so the last one is on top.
+        self.markers.iter().rposition(|marker| self.project(marker.position).distance(&abs) <= marker.radius)
+    }
+
+    fn handle_tile_loaded(&mut self, cx: &mut Cx, loaded: &TileLoaded) {
+        self.requests_in_flight -= 1;
+        let tile = self.tiles.entry(loaded.key).or_default();
+        tile.loading = false;
+        tile.failed = loaded.pixels.is_none();
+        tile.bytes = loaded.bytes.clone();
+        tile.pixels = loaded.pixels.clone();
+        cx.request_draw();
+    }
+
+    pub fn handle(&mut self, cx: &mut Cx, event: &mut Event) -> TileMapEvent {
+        if let Event::UserMessage(ume) = event {
+            if let Some(loaded) = ume.get::<TileLoaded>() {
+                if loaded.component_id == self.component_id {
+                    self.handle_tile_loaded(cx, loaded);
+                }
+            }
+            return TileMapEvent::None;
+        }
+
+        match event.hits_pointer(cx, self.component_id, self.background.area().get_rect_for_first_instance(cx)) {
+            Event::PointerHover(pe) => {
+                let cursor = if self.marker_at(pe.abs).is_some() { MouseCursor::Hand } else { MouseCursor::Grab };
+                cx.set_hover_mouse_cursor(cursor);
+            }
+            Event::PointerDown(pe) => {
+                if let Some(index) = self.marker_at(pe.abs) {
+                    return TileMapEvent::MarkerClicked(index);
+                }
+                cx.set_down_mouse_cursor(MouseCursor::Grabbing);
+                self.drag_start_center = Some(self.center);
+            }
+            Event::PointerMove(pe) => {
+                if let Some((x, y)) = self.drag_start_center {
+                    let delta = pe.abs - pe.abs_start;
+                    let world_size = self.world_size();
+                    self.set_center(x - delta.x as f64 / world_size, y - delta.y as f64 / world_size);
+                    cx.request_draw();
+                    return TileMapEvent::ViewChanged;
+                }
+            }
+            Event::PointerUp(_) => {
+                self.drag_start_center = None;
+            }
+            Event::PointerScroll(pe) => {
+                match cx.interaction_policy().scroll_action(&pe) {
+                    ScrollAction::Zoom { factor, .. } => self.zoom_around(pe.abs, self.zoom + (factor as f64).log2()),
+                    ScrollAction::Pan(delta) => {
+                        let world_size = self.world_size();
+                        self.set_center(self.center.0 + delta.x as f64 / world_size, self.center.1 + delta.y as f64 / world_size);
+                    }
+                }
+                cx.request_draw();
+                return TileMapEvent::ViewChanged;
+            }
+            _ => {}
+        }
+        TileMapEvent::None
+    }
+
+    /// The zoom level of the tiles to show.
+    fn tile_zoom(&self) -> u32 {
+        // Retina tiles have twice the pixels, so they still look sharp when scaled up to twice their size, and the
+        // atlas has room for fewer of them.
+        let z = if self.tile_pixels > Self::TILE_SIZE as usize { self.zoom.floor() } else { self.zoom.round() };
+        (z.max(0.) as u32).min(self.max_tile_zoom)
+    }
+
+    /// The tiles at zoom level `z` that cover the view, nearest to the center first.
+    fn visible_tiles(&self, z: u32) -> Vec<VisibleTile> {
+        let tile_count = 1i64 << z;
+        let tile_size = self.world_size() / tile_count as f64;
+        let center = (self.center.0 * tile_count as f64, self.center.1 * tile_count as f64);
+        let half_size = (self.size.x as f64 / 2. / tile_size, self.size.y as f64 / 2. / tile_size);
+        let screen_center = self.origin + self.size / 2.;
+        // Compute both edges of every tile the same way, so that neighbouring tiles line up without gaps.
+        let to_screen = |tx: i64, ty: i64| {
+            screen_center + vec2(((tx as f64 - center.0) * tile_size) as f32, ((ty as f64 - center.1) * tile_size) as f32)
+        };
+
+        let mut tiles = vec![];
+        let first = ((center.0 - half_size.0).floor() as i64, ((center.1 - half_size.1).floor() as i64).max(0));
+        let last = ((center.0 + half_size.0).ceil() as i64 - 1, ((center.1 + half_size.1).ceil() as i64 - 1).min(tile_count - 1));
+        for ty in first.1..=last.1 {
+            for tx in first.0..=last.0 {
+                // The world repeats horizontally.
+                let key = TileKey { z, x: tx.rem_euclid(tile_count) as u32, y: ty as u32 };
+                let (min, max) = (to_screen(tx, ty), to_screen(tx + 1, ty + 1));
+                tiles.push(VisibleTile { key, rect: Rect { pos: min, size: max - min } });
+            }
+        }
+        let distance = |tile: &VisibleTile| (tile.rect.pos + tile.rect.size / 2.).distance(&screen_center);
+        tiles.sort_by(|a, b| distance(a).partial_cmp(&distance(b)).unwrap());
+        tiles
+    }
+
+    fn atlas_slots_per_side(&self) -> usize {
+        Self::ATLAS_SIZE / self.tile_pixels
+    }
+
+    /// The part of the atlas that has the tile in `slot`, in texture coordinates.
+    fn slot_tex_rect(&self, slot: usize) -> Rect {
+        let per_side = self.atlas_slots_per_side();
+        let pixel = vec2((slot % per_side * self.tile_pixels) as f32, (slot / per_side * self.tile_pixels) as f32);
+        // Stay half a pixel away from the edges, so neighbouring tiles in the atlas don't bleed in.
+        let inset = Vec2::all(0.5);
+        Rect {
+            pos: (pixel + inset) / Self::ATLAS_SIZE as f32,
+            size: (Vec2::all(self.tile_pixels as f32) - inset * 2.) / Self::ATLAS_SIZE as f32,
+        }
+    }
+
+    /// Find a slot in the atlas, taking the one of the least recently used tile if needed, but never the one of a
+    /// tile that is used in the current draw.
+    fn allocate_slot(&mut self) -> Option<usize> {
+        if let Some(slot) = self.atlas_slots.iter().position(Option::is_none) {
+            return Some(slot);
+        }
+        let last_used = |key: &TileKey| self.tiles.get(key).map_or(0, |tile| tile.last_used);
+        let (slot, key) = self
+            .atlas_slots
+            .iter()
+            .enumerate()
+            .filter_map(|(slot, key)| Some((slot, (*key)?)))
+            .filter(|(_, key)| last_used(key) < self.draw_counter)
+            .min_by_key(|(_, key)| last_used(key))?;
+        if let Some(tile) = self.tiles.get_mut(&key) {
+            tile.slot = None;
+        }
+        Some(slot)
+    }
+
+    /// Make sure the tile is in the atlas if it has been decoded, and return its slot.
+    fn upload_tile(&mut self, cx: &mut Cx, key: TileKey) -> Option<usize> {
+        let tile = self.tiles.get_mut(&key)?;
+        tile.last_used = self.draw_counter;
+        if tile.slot.is_some() || tile.pixels.is_none() {
+            return tile.slot;
+        }
+        let slot = self.allocate_slot()?;
+        let pixels = self.tiles.get_mut(&key)?.pixels.take()?;
+
+        let per_side = self.atlas_slots_per_side();
+        let (slot_x, slot_y) = (slot % per_side * self.tile_pixels, slot / per_side * self.tile_pixels);
+        let image = self.atlas.unwrap_texture_handle().get_image_mut(cx);
+        for (row, row_pixels) in pixels.chunks(self.tile_pixels).enumerate() {
+            let start = (slot_y + row) * Self::ATLAS_SIZE + slot_x;
+            image[start..start + self.tile_pixels].copy_from_slice(row_pixels);
+        }
+        self.atlas_slots[slot] = Some(key);
+        self.tiles.get_mut(&key).unwrap().slot = Some(slot);
+        Some(slot)
+    }
+
+    /// Start fetching or decoding the tile, if that isn't already happening and there's room for another request.
+    fn request_tile(&mut self, key: TileKey) {
+        let tile = self.tiles.entry(key).or_default();
+        tile.last_used = self.draw_counter;
+        if tile.loading || tile.failed || tile.pixels.is_some() || self.requests_in_flight >= Self::MAX_REQUESTS {
+            return;
+        }
+        tile.loading = true;
+        self.requests_in_flight += 1;
+
+        let bytes = tile.bytes.clone();
+        let url = key.url(&self.url_template, self.tile_pixels > Self::TILE_SIZE as usize);
+        let (component_id, tile_pixels) = (self.component_id, self.tile_pixels);
+        let loader = self.loader.get_or_insert_with(|| ThreadPool::new(Self::MAX_REQUESTS));
+        loader.spawn(move || {
+            let bytes = bytes.or_else(|| fetch_tile(&url).map(Arc::new));
+            let pixels = bytes.as_deref().and_then(|bytes| decode_tile(bytes, tile_pixels));
+            Cx::post_user_message(TileLoaded { component_id, key, bytes, pixels });
+        });
+    }
+
+    /// Forget the least recently used tiles when there are more than [`TileMap::cache_size`].
+    fn evict_tiles(&mut self) {
+        if self.tiles.len() <= self.cache_size {
+            return;
+        }
+        let mut keys: Vec<(u64, TileKey)> = self
+            .tiles
+            .iter()
+            .filter(|(_, tile)| !tile.loading && tile.last_used < self.draw_counter)
+            .map(|(key, tile)| (tile.last_used, *key))
+            .collect();
+        keys.sort_unstable_by_key(|(last_used, _)| *last_used);
+        for (_, key) in keys.into_iter().take(self.tiles.len() - self.cache_size) {
+            if let Some(slot) = self.tiles.remove(&key).and_then(|tile| tile.slot) {
+                self.atlas_slots[slot] = None;
+            }
+        }
+    }
+
+    fn draw_tiles(&mut self, cx: &mut Cx) {
+        if self.tile_pixels == 0 {
+            let retina = cx.current_dpi_factor >= 1.5 && self.url_template.contains("{r}");
+            self.tile_pixels = if retina { 512 } else { 256 };
+            self.atlas.get_with_dimensions(cx, Self::ATLAS_SIZE, Self::ATLAS_SIZE);
+            self.atlas_slots = vec![None; self.atlas_slots_per_side() * self.atlas_slots_per_side()];
+        }
+        self.draw_counter += 1;
+
+        // Parts of lower zoom levels go first, so that the actual tiles get drawn over them.
+        let mut fallback_instances = vec![];
+        let mut instances = vec![];
+        for VisibleTile { key, rect } in self.visible_tiles(self.tile_zoom()) {
+            if let Some(slot) = self.upload_tile(cx, key) {
+                let tex_rect = self.slot_tex_rect(slot);
+                instances.push(TileIns { base: QuadIns::from_rect(rect), tex_pos: tex_rect.pos, tex_size: tex_rect.size });
+                continue;
+            }
+            if !self.url_template.is_empty() {
+                self.request_tile(key);
+            }
+
+            // Show the part of the nearest loaded ancestor that covers this tile.
+            let mut ancestor = key;
+            while let Some(parent) = ancestor.parent() {
+                ancestor = parent;
+                if let Some(slot) = self.upload_tile(cx, ancestor) {
+                    let scale = (1 << (key.z - ancestor.z)) as f32;
+                    let offset = vec2(
+                        (key.x - (ancestor.x << (key.z - ancestor.z))) as f32,
+                        (key.y - (ancestor.y << (key.z - ancestor.z))) as f32,
+                    );
+                    let tex_rect = self.slot_tex_rect(slot);
+                    fallback_instances.push(TileIns {
+                        base: QuadIns::from_rect(rect),
+                        tex_pos: tex_rect.pos + offset * tex_rect.size / scale,
+                        tex_size: tex_rect.size / scale,
+                    });
+                    break;
+                }
+            }
+        }
+        self.evict_tiles();
+
+        fallback_instances.extend(instances);
+        let area = cx.add_instances(&TILE_SHADER, &fallback_instances);
+        if !fallback_instances.is_empty() {
+            area.write_texture_2d(cx, "atlas", self.atlas.unwrap_texture_handle());
+        }
+    }
+
+    fn draw_polylines(&self, cx: &mut Cx) {
+        let mut instances = vec![];
+        for polyline in &self.polylines {
+            let points: Vec<Vec2> = polyline.points.iter().map(|point| self.project(*point)).collect();
+            for segment in points.windows(2) {
+                let pad = Vec2::all(polyline.width + 1.);
+                let min = segment[0].min(&segment[1]) - pad;
+                let size = segment[0].max(&segment[1]) + pad - min;
+                instances.push(LineIns {
+                    base: QuadIns::from_rect(Rect { pos: min, size }),
+                    color: polyline.color,
+                    start: segment[0],
+                    end: segment[1],
+                    width: polyline.width / 2.,
+                });
+            }
+        }
+        cx.add_instances(&LINE_SHADER, &instances);
+    }
+
+    fn draw_markers(&self, cx: &mut Cx) {
+        let instances: Vec<MarkerIns> = self
+            .markers
+            .iter()
+            .map(|marker| {
+                let radius = Vec2::all(marker.radius + 1.5);
+                let rect = Rect { pos: self.project(marker.position) - radius, size: radius * 2. };
+                MarkerIns { base: QuadIns::from_rect(rect), color: marker.color }
+            })
+            .collect();
+        cx.add_instances(&MARKER_SHADER, &instances);
+    }
+
+    fn draw_attribution(&mut self, cx: &mut Cx) {
+        if self.attribution.is_empty() {
+            return;
+        }
+        let props = TextInsProps { color: vec4(0.2, 0.2, 0.2, 1.), font_scale: 0.8, draw_depth: 1.1, ..TextInsProps::DEFAULT };
+        let text_size = cx.measure_wrapped_text(&props, &self.attribution, f32::INFINITY);
+        let padding = vec2(4., 2.);
+        let size = vec2(text_size.width, text_size.line_height) + padding * 2.;
+        let rect = Rect { pos: self.origin + self.size - size, size };
+        self.attribution_background.draw(cx, rect, vec4(1., 1., 1., 0.7));
+        TextIns::draw_str(cx, &self.attribution, rect.pos + padding, &props);
+    }
+
+    pub fn draw(&mut self, cx: &mut Cx) {
+        self.view.begin_view(cx, LayoutSize::FILL);
+        let rect = cx.add_box(LayoutSize::FILL);
+        self.origin = rect.pos;
+        self.size = rect.size;
+
+        self.background.draw(cx, rect, self.background_color);
+        self.draw_tiles(cx);
+        self.draw_polylines(cx);
+        self.draw_markers(cx);
+        self.draw_attribution(cx);
+
+        self.view.end_view(cx);
+    }
+}
+
+fn fetch_tile(url: &str) -> Option<Vec<u8>> {
+    let mut bytes = vec![];
+    UniversalFile::open_url(url).ok()?.read_to_end(&mut bytes).ok()?;
+    Some(bytes)
+}
+
+/// Decode a PNG tile into `tile_pixels` by `tile_pixels` pixels for the atlas, scaling it if the server sent a
+/// different size.
+fn decode_tile(bytes: &[u8], tile_pixels: usize) -> Option<Vec<u32>> {
+    let png = png::decode_png(bytes)?;
+    if png.width == 0 || png.height == 0 {
+        return None;
+    }
+    let mut pixels = Vec::with_capacity(tile_pixels * tile_pixels);
+    for y in 0..tile_pixels {
+        let row = y * png.height / tile_pixels * png.width;
+        for x in 0..tile_pixels {
+            pixels.push(u32::from_le_bytes(png.pixels[row + x * png.width / tile_pixels]));
+        }
+    }
+    Some(pixels)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tile_map_projection() {
+        let amsterdam = LngLat::new(4.9, 52.37);
+        let (x, y) = amsterdam.to_world();
+        let round_trip = LngLat::from_world(x, y);
+        assert!((round_trip.lng - amsterdam.lng).abs() < 1e-9 && (round_trip.lat - amsterdam.lat).abs() < 1e-9);
+        assert_eq!(LngLat::new(0., 0.).to_world(), (0.5, 0.5));
+        assert_eq!(LngLat::new(-180., LngLat::MAX_LATITUDE).to_world().0, 0.);
+
+        let key = TileKey { z: 3, x: 4, y: 2 };
+        assert_eq!(key.url("https://tiles/{z}/{x}/{y}{r}.png", true), "https://tiles/3/4/2@2x.png");
+        assert_eq!(key.parent(), Some(TileKey { z: 2, x: 2, y: 1 }));
+
+        // At zoom 1 the world is 512 pixels, so a 512 by 256 view in the middle shows all four tiles, half of each.
+        let map = TileMap { size: vec2(512., 256.), tile_pixels: 256, ..TileMap::default().with_view(LngLat::default(), 1.) };
+        let tiles = map.visible_tiles(1);
+        assert_eq!(tiles.len(), 4);
+        assert!(tiles.contains(&VisibleTile {
+            key: TileKey { z: 1, x: 0, y: 0 },
+            rect: Rect { pos: vec2(0., -128.), size: vec2(256., 256.) }
+        }));
+
+        // Panning across the antimeridian wraps around.
+        let map = TileMap { size: vec2(256., 256.), tile_pixels: 256, ..TileMap::default().with_view(LngLat::new(180., 0.), 0.) };
+        let keys: Vec<TileKey> = map.visible_tiles(0).iter().map(|tile| tile.key).collect();
+        assert_eq!(keys, vec![TileKey { z: 0, x: 0, y: 0 }, TileKey { z: 0, x: 0, y: 0 }]);
+    }
+
+    #[test]
+    fn test_tile_map_pan_and_zoom() {
+        let mut map =
+            TileMap::default().with_view(LngLat::default(), 4.).with_markers(vec![MapMarker::new(LngLat::new(10., 10.))]);
+        let mut test_cx = TestCx::new(vec2(400., 400.));
+        test_cx.draw(|cx| map.draw(cx));
+
+        // Clicking a marker.
+        let marker_pos = map.project(LngLat::new(10., 10.));
+        let events = test_cx.click(marker_pos, |cx, event| map.handle(cx, event));
+        assert!(events.contains(&TileMapEvent::MarkerClicked(0)));
+
+        // Dragging to the left moves the center to the east.
+        test_cx.pointer_down(vec2(200., 200.), |cx, event| map.handle(cx, event));
+        let events = test_cx.pointer_move(vec2(100., 200.), |cx, event| map.handle(cx, event));
+        test_cx.pointer_up(vec2(100., 200.), |cx, event| map.handle(cx, event));
+        assert_eq!(events, vec![TileMapEvent::ViewChanged]);
+        assert!((map.center().lng - 100. / 4096. * 360.).abs() < 1e-6);
+        assert_eq!(map.center().lat, 0.);
+
+        // Zooming in keeps the position under the pointer in place.
+        let under_pointer = map.unproject(vec2(300., 100.));
+        map.zoom_around(vec2(300., 100.), 5.);
+        let after = map.unproject(vec2(300., 100.));
+        assert!((after.lng - under_pointer.lng).abs() < 1e-9 && (after.lat - under_pointer.lat).abs() < 1e-9);
+    }
+}
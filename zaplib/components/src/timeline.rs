@@ -0,0 +1,604 @@
+//! A zoomable timeline of bars in lanes, like a Gantt chart or a profiler trace.
+
+use crate::background::*;
+use std::cmp::Ordering;
+use zaplib::*;
+
+const AXIS_HEIGHT: f32 = 24.;
+const ROW_HEIGHT: f32 = 22.;
+const LABEL_WIDTH: f32 = 140.;
+const BAR_MARGIN: f32 = 3.;
+/// How close (in pixels) the pointer has to be to the start or end of a bar to resize it.
+const RESIZE_HANDLE_WIDTH: f32 = 5.;
+/// The minimum distance between ticks on the time axis.
+const MIN_TICK_SPACING: f64 = 80.;
+
+/// A bar on a [`Timeline`]. Times are in seconds.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TimelineItem {
+    /// Index into all the lanes of all [`TimelineGroup`]s together.
+    pub lane: usize,
+    pub start: f64,
+    pub end: f64,
+    pub label: String,
+    pub color: Vec4,
+}
+
+impl TimelineItem {
+    pub fn new(lane: usize, start: f64, end: f64, label: &str) -> Self {
+        Self { lane, start, end, label: label.to_string(), color: vec4(0.3, 0.5, 0.8, 1.) }
+    }
+    #[must_use]
+    pub fn with_color(self, color: Vec4) -> Self {
+        Self { color, ..self }
+    }
+}
+
+/// A set of lanes with a header that can be clicked to collapse them. A group without a name has no header.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TimelineGroup {
+    pub name: String,
+    pub lanes: Vec<String>,
+    pub collapsed: bool,
+}
+
+impl TimelineGroup {
+    pub fn new(name: &str, lanes: &[&str]) -> Self {
+        Self { name: name.to_string(), lanes: lanes.iter().map(|lane| lane.to_string()).collect(), collapsed: false }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum TimelineEvent {
+    None,
+    /// The user clicked the item with this index.
+    ItemSelected(usize),
+    /// The user finished moving or resizing the item with this index; it's already updated.
+    ItemChanged(usize),
+    /// The user collapsed or expanded the group with this index.
+    GroupToggled(usize),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum TimelineRow {
+    Group(usize),
+    Lane(usize),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum ItemPart {
+    Start,
+    Body,
+    End,
+}
+
+enum TimelineDrag {
+    None,
+    Panning {
+        start_time_offset: f64,
+        start_scroll_y: f32,
+    },
+    /// Moving or resizing an item; `start` and `end` are its times when the drag began.
+    Item {
+        index: usize,
+        part: ItemPart,
+        start: f64,
+        end: f64,
+        changed: bool,
+    },
+}
+
+/// Find a "nice" distance between ticks (1, 2, or 5 times a power of 10) so that they are at least
+/// `min_spacing` pixels apart.
+fn tick_step(pixels_per_second: f64, min_spacing: f64) -> f64 {
+    let min_step = min_spacing / pixels_per_second;
+    let magnitude = 10_f64.powf(min_step.log10().floor());
+    [1., 2., 5.].iter().map(|m| m * magnitude).find(|step| *step >= min_step).unwrap_or(magnitude * 10.)
+}
+
+/// Format `time` in the largest unit where ticks that are `step` apart still have distinct labels.
+fn format_time(time: f64, step: f64) -> String {
+    let (scale, unit) = if step >= 0.1 {
+        (1., "s")
+    } else if step >= 1e-4 {
+        (1e3, "ms")
+    } else {
+        (1e6, "µs")
+    };
+    let decimals = (-(step * scale).log10().floor()).max(0.) as usize;
+    format!("{:.*}{}", decimals, time * scale, unit)
+}
+
+/// Shows [`TimelineItem`]s as bars on a time axis, in lanes that are grouped into [`TimelineGroup`]s. Use the mouse
+/// wheel, pinch, or scroll with Ctrl (Cmd) held to zoom (see [`Cx::interaction_policy`]), scroll on a trackpad or
+/// drag the background to pan, and drag bars or their edges to move or resize them (unless disabled using
+/// [`Timeline::with_editable`]).
+///
+/// Only the items in view are drawn, and items that end up smaller than a pixel are merged, so this stays fast with
+/// tens of thousands of items.
+pub struct Timeline {
+    component_id: ComponentId,
+    view: View,
+    frame: Background,
+    background: Background,
+    bars: Background,
+    groups: Vec<TimelineGroup>,
+    items: Vec<TimelineItem>,
+    /// For every lane, the indices of its items sorted by start time.
+    lane_items: Vec<Vec<usize>>,
+    /// For every lane, the duration of its longest item, to know how far back to look for visible items.
+    lane_max_duration: Vec<f64>,
+    /// The time at the left edge of the lanes.
+    time_offset: f64,
+    pixels_per_second: f64,
+    scroll_y: f32,
+    selected: Option<usize>,
+    drag: TimelineDrag,
+    editable: bool,
+    /// Where the timeline was drawn during the last draw.
+    rect: Rect,
+    background_color: Vec4,
+    label_background_color: Vec4,
+    stripe_color: Vec4,
+    grid_color: Vec4,
+    text_color: Vec4,
+    accent_color: Vec4,
+}
+
+impl Default for Timeline {
+    fn default() -> Self {
+        Self {
+            component_id: Default::default(),
+            view: Default::default(),
+            frame: Background::default(),
+            background: Background::default(),
+            bars: Background::default().with_radius(2.),
+            groups: vec![],
+            items: vec![],
+            lane_items: vec![],
+            lane_max_duration: vec![],
+            time_offset: 0.,
+            pixels_per_second: 100.,
+            scroll_y: 0.,
+            selected: None,
+            drag: TimelineDrag::None,
+            editable: true,
+            rect: Rect::default(),
+            background_color: vec4(0.12, 0.12, 0.12, 1.),
+            label_background_color: vec4(0.16, 0.16, 0.16, 1.),
+            stripe_color: vec4(1., 1., 1., 0.02),
+            grid_color: vec4(1., 1., 1., 0.06),
+            text_color: vec4(0.85, 0.85, 0.85, 1.),
+            accent_color: vec4(1., 0.8, 0.3, 1.),
+        }
+    }
+}
+
+impl Timeline {
+    const MIN_PIXELS_PER_SECOND: f64 = 1e-6;
+    const MAX_PIXELS_PER_SECOND: f64 = 1e9;
+
+    #[must_use]
+    pub fn with_groups(self, groups: Vec<TimelineGroup>) -> Self {
+        let mut timeline = Self { groups, ..self };
+        timeline.rebuild_index();
+        timeline
+    }
+    #[must_use]
+    pub fn with_items(self, items: Vec<TimelineItem>) -> Self {
+        let mut timeline = Self { items, ..self };
+        timeline.rebuild_index();
+        timeline
+    }
+    /// Whether items can be moved and resized; defaults to `true`. Turn this off for read-only views like traces.
+    #[must_use]
+    pub fn with_editable(self, editable: bool) -> Self {
+        Self { editable, ..self }
+    }
+    #[must_use]
+    pub fn with_accent_color(self, accent_color: Vec4) -> Self {
+        Self { accent_color, ..self }
+    }
+
+    pub fn groups(&self) -> &[TimelineGroup] {
+        &self.groups
+    }
+
+    pub fn set_groups(&mut self, cx: &mut Cx, groups: Vec<TimelineGroup>) {
+        self.groups = groups;
+        self.rebuild_index();
+        cx.request_draw();
+    }
+
+    pub fn items(&self) -> &[TimelineItem] {
+        &self.items
+    }
+
+    pub fn set_items(&mut self, cx: &mut Cx, items: Vec<TimelineItem>) {
+        self.items = items;
+        self.selected = None;
+        self.drag = TimelineDrag::None;
+        self.rebuild_index();
+        cx.request_draw();
+    }
+
+    pub fn selected(&self) -> Option<usize> {
+        self.selected
+    }
+
+    pub fn set_selected(&mut self, cx: &mut Cx, selected: Option<usize>) {
+        self.selected = selected;
+        cx.request_draw();
+    }
+
+    /// The times at the left and right edge of the lanes, during the last draw.
+    pub fn visible_range(&self) -> (f64, f64) {
+        let width = (self.rect.size.x - LABEL_WIDTH).max(1.) as f64;
+        (self.time_offset, self.time_offset + width / self.pixels_per_second)
+    }
+
+    /// Pan and zoom so that the lanes show the times from `start` to `end`.
+    pub fn set_visible_range(&mut self, cx: &mut Cx, start: f64, end: f64) {
+        let width = (self.rect.size.x - LABEL_WIDTH).max(1.) as f64;
+        self.time_offset = start;
+        self.pixels_per_second =
+            (width / (end - start).max(f64::EPSILON)).clamp(Self::MIN_PIXELS_PER_SECOND, Self::MAX_PIXELS_PER_SECOND);
+        cx.request_draw();
+    }
+
+    fn lane_count(&self) -> usize {
+        self.groups.iter().map(|group| group.lanes.len()).sum()
+    }
+
+    fn rebuild_index(&mut self) {
+        let lane_count = self.lane_count();
+        self.lane_items = vec![vec![]; lane_count];
+        self.lane_max_duration = vec![0.; lane_count];
+        for (index, item) in self.items.iter().enumerate() {
+            if item.lane < lane_count {
+                self.lane_items[item.lane].push(index);
+                self.lane_max_duration[item.lane] = self.lane_max_duration[item.lane].max(item.end - item.start);
+            }
+        }
+        let items = &self.items;
+        for indices in &mut self.lane_items {
+            indices.sort_by(|a, b| items[*a].start.partial_cmp(&items[*b].start).unwrap_or(Ordering::Equal));
+        }
+    }
+
+    /// Change the times and lane of an item, keeping the index up to date.
+    fn update_item(&mut self, index: usize, start: f64, end: f64, lane: usize) {
+        let old_lane = self.items[index].lane;
+        self.items[index].start = start;
+        self.items[index].end = end;
+        self.items[index].lane = lane;
+        if let Some(indices) = self.lane_items.get_mut(old_lane) {
+            indices.retain(|i| *i != index);
+        }
+        if let Some(indices) = self.lane_items.get_mut(lane) {
+            let items = &self.items;
+            let position = indices.partition_point(|i| items[*i].start < start);
+            indices.insert(position, index);
+            self.lane_max_duration[lane] = self.lane_max_duration[lane].max(end - start);
+        }
+    }
+
+    /// The indices of items in `lane` that overlap the times from `start` to `end`, sorted by start time.
+    fn visible_items(&self, lane: usize, start: f64, end: f64) -> impl Iterator<Item = usize> + '_ {
+        let indices = &self.lane_items[lane];
+        let max_duration = self.lane_max_duration[lane];
+        let from = indices.partition_point(|i| self.items[*i].start < start - max_duration);
+        let to = indices.partition_point(|i| self.items[*i].start < end);
+        indices[from..to.max(from)].iter().copied().filter(move |i| self.items[*i].end > start)
+    }
+
+    /// All rows with their top, in content coordinates (so without the axis and scrolling).
+    fn rows(&self) -> Vec<(TimelineRow, f32)> {
+        let mut rows = vec![];
+        let mut y = 0.;
+        let mut lane = 0;
+        for (group_index, group) in self.groups.iter().enumerate() {
+            if !group.name.is_empty() {
+                rows.push((TimelineRow::Group(group_index), y));
+                y += ROW_HEIGHT;
+            }
+            if !group.collapsed {
+                for offset in 0..group.lanes.len() {
+                    rows.push((TimelineRow::Lane(lane + offset), y));
+                    y += ROW_HEIGHT;
+                }
+            }
+            lane += group.lanes.len();
+        }
+        rows
+    }
+
+    fn lanes_rect(&self) -> Rect {
+        Rect {
+            pos: self.rect.pos + vec2(LABEL_WIDTH, AXIS_HEIGHT),
+            size: (self.rect.size - vec2(LABEL_WIDTH, AXIS_HEIGHT)).max(&Vec2::default()),
+        }
+    }
+
+    fn time_to_x(&self, time: f64) -> f32 {
+        self.rect.pos.x + LABEL_WIDTH + ((time - self.time_offset) * self.pixels_per_second) as f32
+    }
+
+    fn x_to_time(&self, x: f32) -> f64 {
+        self.time_offset + (x - self.rect.pos.x - LABEL_WIDTH) as f64 / self.pixels_per_second
+    }
+
+    fn row_at(&self, y: f32) -> Option<TimelineRow> {
+        let content_y = y - self.rect.pos.y - AXIS_HEIGHT + self.scroll_y;
+        self.rows().into_iter().find(|(_, top)| content_y >= *top && content_y < top + ROW_HEIGHT).map(|(row, _)| row)
+    }
+
+    fn item_at(&self, abs: Vec2) -> Option<(usize, ItemPart)> {
+        if !self.lanes_rect().contains(abs) {
+            return None;
+        }
+        let lane = match self.row_at(abs.y) {
+            Some(TimelineRow::Lane(lane)) => lane,
+            _ => return None,
+        };
+        let tolerance = RESIZE_HANDLE_WIDTH as f64 / self.pixels_per_second;
+        let time = self.x_to_time(abs.x);
+        // Later items are drawn on top, so prefer those.
+        let index = self.visible_items(lane, time - tolerance, time + tolerance).last()?;
+        let item = &self.items[index];
+        let (x0, x1) = (self.time_to_x(item.start), self.time_to_x(item.end));
+        // Keep some of the body grabbable for very short bars.
+        let handle = RESIZE_HANDLE_WIDTH.min((x1 - x0) / 3.);
+        let part = if abs.x < x0 + handle {
+            ItemPart::Start
+        } else if abs.x > x1 - handle {
+            ItemPart::End
+        } else {
+            ItemPart::Body
+        };
+        Some((index, part))
+    }
+
+    fn max_scroll_y(&self) -> f32 {
+        let content_height = self.rows().len() as f32 * ROW_HEIGHT;
+        (content_height - self.lanes_rect().size.y).max(0.)
+    }
+
+    fn zoom_around(&mut self, x: f32, factor: f64) {
+        let time = self.x_to_time(x);
+        self.pixels_per_second =
+            (self.pixels_per_second * factor).clamp(Self::MIN_PIXELS_PER_SECOND, Self::MAX_PIXELS_PER_SECOND);
+        self.time_offset = time - (x - self.rect.pos.x - LABEL_WIDTH) as f64 / self.pixels_per_second;
+    }
+
+    fn pointer_down(&mut self, cx: &mut Cx, pe: &PointerDownEvent) -> TimelineEvent {
+        if let Some(TimelineRow::Group(group)) = self.row_at(pe.abs.y) {
+            if pe.abs.y >= self.rect.pos.y + AXIS_HEIGHT {
+                self.groups[group].collapsed = !self.groups[group].collapsed;
+                self.scroll_y = self.scroll_y.min(self.max_scroll_y());
+                cx.request_draw();
+                return TimelineEvent::GroupToggled(group);
+            }
+        }
+        if let Some((index, part)) = self.item_at(pe.abs) {
+            if self.editable {
+                let item = &self.items[index];
+                self.drag = TimelineDrag::Item { index, part, start: item.start, end: item.end, changed: false };
+            }
+            if self.selected != Some(index) {
+                self.selected = Some(index);
+                cx.request_draw();
+                return TimelineEvent::ItemSelected(index);
+            }
+            return TimelineEvent::None;
+        }
+        self.drag = TimelineDrag::Panning { start_time_offset: self.time_offset, start_scroll_y: self.scroll_y };
+        TimelineEvent::None
+    }
+
+    fn pointer_move(&mut self, cx: &mut Cx, pe: &PointerMoveEvent) {
+        let delta = pe.abs - pe.abs_start;
+        let dt = delta.x as f64 / self.pixels_per_second;
+        match self.drag {
+            TimelineDrag::None => return,
+            TimelineDrag::Panning { start_time_offset, start_scroll_y } => {
+                self.time_offset = start_time_offset - dt;
+                self.scroll_y = (start_scroll_y - delta.y).clamp(0., self.max_scroll_y());
+            }
+            TimelineDrag::Item { index, part, start, end, .. } => {
+                // Don't let items get shorter than a pixel.
+                let min_duration = 1. / self.pixels_per_second;
+                let (start, end, lane) = match part {
+                    ItemPart::Start => ((start + dt).min(end - min_duration), end, self.items[index].lane),
+                    ItemPart::End => (start, (end + dt).max(start + min_duration), self.items[index].lane),
+                    ItemPart::Body => {
+                        let lane = match self.row_at(pe.abs.y) {
+                            Some(TimelineRow::Lane(lane)) => lane,
+                            _ => self.items[index].lane,
+                        };
+                        (start + dt, end + dt, lane)
+                    }
+                };
+                self.update_item(index, start, end, lane);
+                if let TimelineDrag::Item { changed, .. } = &mut self.drag {
+                    *changed = true;
+                }
+            }
+        }
+        cx.request_draw();
+    }
+
+    pub fn handle(&mut self, cx: &mut Cx, event: &mut Event) -> TimelineEvent {
+        match event.hits_pointer(cx, self.component_id, self.frame.area().get_rect_for_first_instance(cx)) {
+            Event::PointerHover(pe) => {
+                let cursor = match self.item_at(pe.abs) {
+                    Some((_, ItemPart::Start | ItemPart::End)) if self.editable => MouseCursor::EwResize,
+                    Some((_, ItemPart::Body)) if self.editable => MouseCursor::Move,
+                    Some(_) => MouseCursor::Hand,
+                    None => MouseCursor::Default,
+                };
+                cx.set_hover_mouse_cursor(cursor);
+            }
+            Event::PointerDown(pe) => return self.pointer_down(cx, &pe),
+            Event::PointerMove(pe) => self.pointer_move(cx, &pe),
+            Event::PointerUp(_) => {
+                if let TimelineDrag::Item { index, changed: true, .. } = std::mem::replace(&mut self.drag, TimelineDrag::None) {
+                    return TimelineEvent::ItemChanged(index);
+                }
+            }
+            Event::PointerScroll(pe) => {
+                match cx.interaction_policy().scroll_action(&pe) {
+                    // Only time gets zoomed, so there's no need to check the axes.
+                    ScrollAction::Zoom { factor, .. } => self.zoom_around(pe.abs.x, factor as f64),
+                    ScrollAction::Pan(delta) => {
+                        // Shift turns vertical mouse wheel scrolling into horizontal scrolling.
+                        let delta = if pe.modifiers.shift { vec2(delta.y, delta.x) } else { delta };
+                        self.time_offset += delta.x as f64 / self.pixels_per_second;
+                        self.scroll_y = (self.scroll_y + delta.y).clamp(0., self.max_scroll_y());
+                    }
+                }
+                cx.request_draw();
+            }
+            _ => {}
+        }
+        TimelineEvent::None
+    }
+
+    fn draw_axis(&mut self, cx: &mut Cx, lanes_rect: Rect) {
+        let axis_rect = Rect { pos: vec2(lanes_rect.pos.x, self.rect.pos.y), size: vec2(lanes_rect.size.x, AXIS_HEIGHT) };
+        self.background.draw(cx, axis_rect, self.label_background_color);
+
+        let props = TextInsProps { color: self.text_color, ..TextInsProps::DEFAULT };
+        let line_height = cx.measure_wrapped_text(&props, "X", f32::INFINITY).line_height;
+        let step = tick_step(self.pixels_per_second, MIN_TICK_SPACING);
+        let (start, end) = self.visible_range();
+        // Count in steps rather than adding them up, so rounding errors don't accumulate.
+        let mut step_index = (start / step).floor() as i64;
+        loop {
+            let tick = step_index as f64 * step;
+            if tick > end {
+                break;
+            }
+            step_index += 1;
+            let x = self.time_to_x(tick);
+            if x >= lanes_rect.pos.x {
+                let line = Rect { pos: vec2(x, axis_rect.pos.y), size: vec2(1., self.rect.size.y) };
+                self.background.draw(cx, line, self.grid_color);
+                let label_pos = vec2(x + 4., axis_rect.pos.y + (AXIS_HEIGHT - line_height) / 2.);
+                TextIns::draw_str(cx, &format_time(tick, step), label_pos, &props);
+            }
+        }
+    }
+
+    fn draw_lane(&mut self, cx: &mut Cx, lane: usize, y: f32, lanes_rect: Rect) {
+        let (start, end) = self.visible_range();
+        let props = TextInsProps { color: vec4(1., 1., 1., 0.9), ..TextInsProps::DEFAULT };
+        let line_height = cx.measure_wrapped_text(&props, "X", f32::INFINITY).line_height;
+        let min_x = lanes_rect.pos.x;
+        let mut last_x1 = f32::NEG_INFINITY;
+        let visible: Vec<usize> = self.visible_items(lane, start, end).collect();
+        for index in visible {
+            let item = &self.items[index];
+            let x0 = self.time_to_x(item.start).max(min_x);
+            // Make every item at least a pixel wide, and skip items that would be drawn entirely on top of the
+            // previous one anyway.
+            let x1 = self.time_to_x(item.end).max(x0 + 1.);
+            if x1 <= last_x1 && self.selected != Some(index) {
+                continue;
+            }
+            last_x1 = last_x1.max(x1);
+
+            let bar = Rect { pos: vec2(x0, y + BAR_MARGIN), size: vec2(x1 - x0, ROW_HEIGHT - BAR_MARGIN * 2.) };
+            if self.selected == Some(index) {
+                let outline = Rect { pos: bar.pos - Vec2::all(1.5), size: bar.size + Vec2::all(3.) };
+                self.bars.draw(cx, outline, self.accent_color);
+            }
+            self.bars.draw(cx, bar, item.color);
+            if bar.size.x > 30. {
+                let width = cx.measure_wrapped_text(&props, &item.label, f32::INFINITY).width;
+                if width + 8. <= bar.size.x {
+                    TextIns::draw_str(cx, &item.label, bar.pos + vec2(4., (bar.size.y - line_height) / 2.), &props);
+                }
+            }
+        }
+    }
+
+    pub fn draw(&mut self, cx: &mut Cx) {
+        self.view.begin_view(cx, LayoutSize::FILL);
+        self.rect = cx.add_box(LayoutSize::FILL);
+        self.frame.draw(cx, self.rect, self.background_color);
+        let lanes_rect = self.lanes_rect();
+        self.scroll_y = self.scroll_y.min(self.max_scroll_y());
+
+        let props = TextInsProps { color: self.text_color, ..TextInsProps::DEFAULT };
+        let line_height = cx.measure_wrapped_text(&props, "X", f32::INFINITY).line_height;
+        let label_rect = Rect { pos: self.rect.pos, size: vec2(LABEL_WIDTH, self.rect.size.y) };
+        let mut labels = vec![];
+
+        // Only draw the rows that are in view.
+        let (first_visible, last_visible) = (self.scroll_y - ROW_HEIGHT, self.scroll_y + lanes_rect.size.y);
+        for (row, top) in self.rows() {
+            if top < first_visible || top > last_visible {
+                continue;
+            }
+            let y = lanes_rect.pos.y + top - self.scroll_y;
+            match row {
+                TimelineRow::Group(group) => {
+                    let header = Rect { pos: vec2(self.rect.pos.x, y), size: vec2(self.rect.size.x, ROW_HEIGHT) };
+                    self.background.draw(cx, header, self.label_background_color);
+                    let arrow = if self.groups[group].collapsed { "\u{25b8}" } else { "\u{25be}" };
+                    labels.push((format!("{} {}", arrow, self.groups[group].name), y));
+                }
+                TimelineRow::Lane(lane) => {
+                    if lane % 2 == 1 {
+                        let stripe = Rect { pos: vec2(lanes_rect.pos.x, y), size: vec2(lanes_rect.size.x, ROW_HEIGHT) };
+                        self.background.draw(cx, stripe, self.stripe_color);
+                    }
+                    self.draw_lane(cx, lane, y, lanes_rect);
+                    let name = self.groups.iter().flat_map(|group| group.lanes.iter()).nth(lane).cloned().unwrap_or_default();
+                    labels.push((format!("  {}", name), y));
+                }
+            }
+        }
+
+        // The labels and axis go on top of the bars, which can stick out to the left and top when scrolled.
+        self.background.draw(cx, label_rect, self.label_background_color);
+        for (label, y) in labels {
+            if y + ROW_HEIGHT > lanes_rect.pos.y {
+                TextIns::draw_str(cx, &label, vec2(self.rect.pos.x + 6., y + (ROW_HEIGHT - line_height) / 2.), &props);
+            }
+        }
+        self.draw_axis(cx, lanes_rect);
+
+        self.view.end_view(cx);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tick_step_and_format_time() {
+        assert!((tick_step(100., 80.) - 1.).abs() < 1e-9);
+        assert!((tick_step(1000., 80.) - 0.1).abs() < 1e-9);
+        assert!((tick_step(30., 80.) - 5.).abs() < 1e-9);
+        assert_eq!(format_time(3., 1.), "3s");
+        assert_eq!(format_time(1.5, 0.5), "1.5s");
+        assert_eq!(format_time(0.25, 0.05), "250ms");
+        assert_eq!(format_time(0.0125, 0.0005), "12.5ms");
+    }
+
+    #[test]
+    fn test_visible_items() {
+        let items = (0..10_000).map(|i| TimelineItem::new(i % 2, i as f64, i as f64 + 1.5, "")).collect();
+        let timeline = Timeline::default().with_groups(vec![TimelineGroup::new("", &["Even", "Odd"])]).with_items(items);
+
+        // Items in lane 0 start at even seconds and take 1.5s, so 100..104 overlaps items 100 and 102, but not 98,
+        // which ends at 99.5.
+        let visible: Vec<usize> = timeline.visible_items(0, 100., 104.).collect();
+        assert_eq!(visible, vec![100, 102]);
+        let visible: Vec<usize> = timeline.visible_items(1, 100., 104.).collect();
+        assert_eq!(visible, vec![99, 101, 103]);
+    }
+}
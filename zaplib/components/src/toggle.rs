@@ -0,0 +1,503 @@
+//! Checkboxes, toggle switches, and radio groups.
+
+use crate::button::ButtonEvent;
+use crate::buttonlogic::*;
+use zaplib::*;
+
+/// Colors and text style for [`Toggle`] and [`RadioGroup`].
+#[derive(Clone, Copy, Debug)]
+pub struct ToggleTheme {
+    /// The fill of checked checkboxes and radio buttons, and the track of switches that are on.
+    pub accent_color: Vec4,
+    /// The outline of unchecked checkboxes and radio buttons, and the track of switches that are off.
+    pub border_color: Vec4,
+    /// The fill of unchecked checkboxes and radio buttons.
+    pub background_color: Vec4,
+    /// The checkmark, and the thumb of switches.
+    pub mark_color: Vec4,
+    pub text_style: TextStyle,
+    pub text_color: Vec4,
+}
+
+impl ToggleTheme {
+    pub const DEFAULT: ToggleTheme = ToggleTheme {
+        accent_color: vec4(0.27, 0.56, 0.93, 1.),
+        border_color: vec4(0.5, 0.5, 0.5, 1.),
+        background_color: vec4(0.15, 0.15, 0.15, 1.),
+        mark_color: vec4(1., 1., 1., 1.),
+        text_style: TEXT_STYLE_NORMAL,
+        text_color: vec4(0.9, 0.9, 0.9, 1.),
+    };
+}
+
+impl Default for ToggleTheme {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+#[derive(Clone, Copy, Default)]
+#[repr(C)]
+struct ToggleIns {
+    base: QuadIns,
+    accent_color: Vec4,
+    border_color: Vec4,
+    background_color: Vec4,
+    mark_color: Vec4,
+    /// See the constants at the top of [`TOGGLE_SHADER`].
+    kind: f32,
+    /// Animates between 0 (unchecked) and 1 (checked).
+    checked: f32,
+    hover: f32,
+}
+
+static TOGGLE_SHADER: Shader = Shader {
+    build_geom: Some(QuadIns::build_geom),
+    code_to_concatenate: &[
+        Cx::STD_SHADER,
+        QuadIns::SHADER,
+        code_fragment!(
+            r#"
+            instance accent_color: vec4;
+            instance border_color: vec4;
+            instance background_color: vec4;
+            instance mark_color: vec4;
+            instance kind: float;
+            instance checked: float;
+            instance hover: float;
+
+            const kind_checkbox: float = 0.;
+            const kind_radio: float = 1.;
+
+            fn pixel() -> vec4 {
+                let df = Df::viewport(pos * rect_size);
+                let center = rect_size / 2.;
+                let border = mix(mix(border_color, #f, hover * 0.3), accent_color, checked);
+
+                if kind == kind_checkbox {
+                    let corner = center - vec2(8.);
+                    df.box(corner + vec2(0.5), vec2(15.), 3.);
+                    df.fill(mix(background_color, accent_color, checked));
+                    df.stroke(border, 1.);
+                    df.new_path();
+                    df.move_to(corner + vec2(4., 8.));
+                    df.line_to(corner + vec2(7., 11.));
+                    df.line_to(corner + vec2(12., 5.));
+                    df.stroke(vec4(mark_color.rgb, mark_color.a * checked), 1.8);
+                } else if kind == kind_radio {
+                    df.circle(center, 7.5);
+                    df.fill(background_color);
+                    df.stroke(border, 1.);
+                    df.new_path();
+                    df.circle(center, 4. * checked);
+                    df.fill(accent_color);
+                } else {
+                    let size = vec2(32., 18.);
+                    let corner = center - size / 2.;
+                    df.box(corner, size, size.y / 2.);
+                    df.fill(mix(mix(border_color, #f, hover * 0.3), accent_color, checked));
+                    df.new_path();
+                    let thumb_x = mix(corner.x + size.y / 2., corner.x + size.x - size.y / 2., checked);
+                    df.circle(vec2(thumb_x, center.y), size.y / 2. - 2.);
+                    df.fill(mark_color);
+                }
+                return df.result;
+            }"#
+        ),
+    ],
+    ..Shader::DEFAULT
+};
+
+const KIND_CHECKBOX: f32 = 0.;
+const KIND_RADIO: f32 = 1.;
+const KIND_SWITCH: f32 = 2.;
+
+const ROW_HEIGHT: f32 = 22.;
+/// Space between the control and its label.
+const LABEL_GAP: f32 = 6.;
+
+/// Draw a control followed by its label, in a row. Returns the area of the control, and the rect of the whole row.
+fn draw_labeled(cx: &mut Cx, theme: &ToggleTheme, kind: f32, checked: f32, hover: bool, label: &str) -> (Area, Rect) {
+    cx.begin_row(Width::Compute, Height::Fix(ROW_HEIGHT));
+    let control_width = if kind == KIND_SWITCH { 36. } else { 18. };
+    let rect = cx.add_box(LayoutSize::new(Width::Fix(control_width), Height::Fix(ROW_HEIGHT)));
+    let area = cx.add_instances(
+        &TOGGLE_SHADER,
+        &[ToggleIns {
+            base: QuadIns::from_rect(rect),
+            accent_color: theme.accent_color,
+            border_color: theme.border_color,
+            background_color: theme.background_color,
+            mark_color: theme.mark_color,
+            kind,
+            checked,
+            hover: hover as u8 as f32,
+        }],
+    );
+    if !label.is_empty() {
+        cx.add_box(LayoutSize::new(Width::Fix(LABEL_GAP), Height::Fix(0.)));
+        cx.begin_center_y_align();
+        let props = TextInsProps {
+            text_style: theme.text_style,
+            color: theme.text_color,
+            wrapping: Wrapping::Ellipsis(cx.get_width_left()),
+            ..TextInsProps::DEFAULT
+        };
+        TextIns::draw_walk(cx, label, &props);
+        cx.end_center_y_align();
+    }
+    let row_rect = cx.end_row();
+    (area, row_rect)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ToggleKind {
+    /// A box with a checkmark.
+    Checkbox,
+    /// A switch that slides between off and on, for settings that take effect immediately.
+    Switch,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ToggleEvent {
+    None,
+    /// The user checked or unchecked the toggle; contains the new state.
+    Changed(bool),
+}
+
+/// A checkbox or toggle switch with a label. Clicking anywhere on the label also toggles it, and so does pressing
+/// Space while it has focus.
+///
+/// ```ignore
+/// let mut dark_mode = Toggle::switch("Dark mode").with_checked(true);
+///
+/// // In `handle`:
+/// if let ToggleEvent::Changed(checked) = dark_mode.handle(cx, event) {
+///     self.set_dark_mode(cx, checked);
+/// }
+/// ```
+///
+/// For the checkbox with a loading state that is used in lists of charts, see [`crate::Checkbox`].
+pub struct Toggle {
+    component_id: ComponentId,
+    area: Area,
+    rect: Option<Rect>,
+    kind: ToggleKind,
+    label: String,
+    checked: bool,
+    checked_anim: Spring<f32>,
+    hover: bool,
+    theme: ToggleTheme,
+}
+
+impl Toggle {
+    pub fn new(kind: ToggleKind, label: &str) -> Self {
+        Self {
+            component_id: ComponentId::default(),
+            area: Area::Empty,
+            rect: None,
+            kind,
+            label: label.to_string(),
+            checked: false,
+            checked_anim: Spring::with_stiffness(0., 40.),
+            hover: false,
+            theme: ToggleTheme::DEFAULT,
+        }
+    }
+
+    pub fn checkbox(label: &str) -> Self {
+        Self::new(ToggleKind::Checkbox, label)
+    }
+
+    pub fn switch(label: &str) -> Self {
+        Self::new(ToggleKind::Switch, label)
+    }
+
+    #[must_use]
+    pub fn with_checked(mut self, checked: bool) -> Self {
+        self.checked = checked;
+        self.checked_anim.set_immediate(checked as u8 as f32);
+        self
+    }
+    #[must_use]
+    pub fn with_theme(self, theme: ToggleTheme) -> Self {
+        Self { theme, ..self }
+    }
+
+    pub fn checked(&self) -> bool {
+        self.checked
+    }
+
+    pub fn set_checked(&mut self, cx: &mut Cx, checked: bool) {
+        self.checked = checked;
+        self.checked_anim.set_target(cx, checked as u8 as f32);
+        cx.request_draw();
+    }
+
+    pub fn set_label(&mut self, cx: &mut Cx, label: &str) {
+        self.label = label.to_string();
+        cx.request_draw();
+    }
+
+    fn toggle(&mut self, cx: &mut Cx) -> ToggleEvent {
+        self.set_checked(cx, !self.checked);
+        ToggleEvent::Changed(self.checked)
+    }
+
+    pub fn handle(&mut self, cx: &mut Cx, event: &mut Event) -> ToggleEvent {
+        if self.checked_anim.handle(cx, event) {
+            cx.request_draw();
+        }
+
+        if let Event::KeyDown(ke) = event.hits_keyboard(cx, self.component_id) {
+            if ke.key_code == KeyCode::Space && !ke.is_repeat {
+                return self.toggle(cx);
+            }
+        }
+
+        let hit_event = event.hits_pointer(cx, self.component_id, self.rect);
+        if let Event::PointerDown(_) = hit_event {
+            cx.set_key_focus(Some(self.component_id));
+        }
+        let mut hover = self.hover;
+        let button_event = handle_button_logic(cx, hit_event, |_, logic_event| {
+            hover = logic_event != ButtonLogicEvent::Default;
+        });
+        if hover != self.hover {
+            self.hover = hover;
+            cx.request_draw();
+        }
+        if button_event == ButtonEvent::Clicked {
+            return self.toggle(cx);
+        }
+        ToggleEvent::None
+    }
+
+    pub fn draw(&mut self, cx: &mut Cx) {
+        let kind = match self.kind {
+            ToggleKind::Checkbox => KIND_CHECKBOX,
+            ToggleKind::Switch => KIND_SWITCH,
+        };
+        let (area, rect) = draw_labeled(cx, &self.theme, kind, self.checked_anim.get(), self.hover, &self.label);
+        self.area = area;
+        self.rect = Some(rect);
+        cx.add_focusable(self.component_id, self.area, Focusable::default());
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum RadioGroupEvent {
+    None,
+    /// The user selected the item at this index.
+    Selected(usize),
+}
+
+/// A list of radio buttons, of which at most one can be selected. The group is a single stop when tabbing through
+/// components; the arrow keys then move the selection, and Space selects the first item if nothing is selected yet.
+pub struct RadioGroup {
+    component_id: ComponentId,
+    items: Vec<String>,
+    selected: Option<usize>,
+    checked_anims: Vec<Spring<f32>>,
+    /// The item that the pointer is over.
+    hover: Option<usize>,
+    /// The item that the pointer went down on; it only gets selected if the pointer also goes up on it.
+    pressed: Option<usize>,
+    horizontal: bool,
+    theme: ToggleTheme,
+    rect: Option<Rect>,
+    item_rects: Vec<Rect>,
+}
+
+impl Default for RadioGroup {
+    fn default() -> Self {
+        Self {
+            component_id: ComponentId::default(),
+            items: vec![],
+            selected: None,
+            checked_anims: vec![],
+            hover: None,
+            pressed: None,
+            horizontal: false,
+            theme: ToggleTheme::DEFAULT,
+            rect: None,
+            item_rects: vec![],
+        }
+    }
+}
+
+impl RadioGroup {
+    /// Space between items when laid out horizontally.
+    const HORIZONTAL_GAP: f32 = 16.;
+
+    #[must_use]
+    pub fn with_items(mut self, items: &[&str]) -> Self {
+        self.set_items_internal(items);
+        self
+    }
+    #[must_use]
+    pub fn with_selected(mut self, selected: Option<usize>) -> Self {
+        self.selected = selected;
+        for (index, checked_anim) in self.checked_anims.iter_mut().enumerate() {
+            checked_anim.set_immediate((Some(index) == selected) as u8 as f32);
+        }
+        self
+    }
+    /// Lay out the items in a row instead of a column.
+    #[must_use]
+    pub fn with_horizontal(self, horizontal: bool) -> Self {
+        Self { horizontal, ..self }
+    }
+    #[must_use]
+    pub fn with_theme(self, theme: ToggleTheme) -> Self {
+        Self { theme, ..self }
+    }
+
+    pub fn items(&self) -> &[String] {
+        &self.items
+    }
+
+    pub fn set_items(&mut self, cx: &mut Cx, items: &[&str]) {
+        self.set_items_internal(items);
+        cx.request_draw();
+    }
+
+    fn set_items_internal(&mut self, items: &[&str]) {
+        self.items = items.iter().map(|item| item.to_string()).collect();
+        if self.selected.map_or(false, |selected| selected >= self.items.len()) {
+            self.selected = None;
+        }
+        self.checked_anims =
+            (0..self.items.len()).map(|index| Spring::with_stiffness((Some(index) == self.selected) as u8 as f32, 40.)).collect();
+        self.hover = None;
+        self.pressed = None;
+    }
+
+    pub fn selected(&self) -> Option<usize> {
+        self.selected
+    }
+
+    pub fn set_selected(&mut self, cx: &mut Cx, selected: Option<usize>) {
+        self.selected = selected;
+        for (index, checked_anim) in self.checked_anims.iter_mut().enumerate() {
+            checked_anim.set_target(cx, (Some(index) == selected) as u8 as f32);
+        }
+        cx.request_draw();
+    }
+
+    fn select(&mut self, cx: &mut Cx, index: usize) -> RadioGroupEvent {
+        if self.selected == Some(index) {
+            return RadioGroupEvent::None;
+        }
+        self.set_selected(cx, Some(index));
+        RadioGroupEvent::Selected(index)
+    }
+
+    fn item_at(&self, abs: Vec2) -> Option<usize> {
+        self.item_rects.iter().position(|rect| rect.contains(abs))
+    }
+
+    pub fn handle(&mut self, cx: &mut Cx, event: &mut Event) -> RadioGroupEvent {
+        let mut animating = false;
+        for checked_anim in &mut self.checked_anims {
+            animating |= checked_anim.handle(cx, event);
+        }
+        if animating {
+            cx.request_draw();
+        }
+
+        if let Event::KeyDown(ke) = event.hits_keyboard(cx, self.component_id) {
+            if self.items.is_empty() {
+                return RadioGroupEvent::None;
+            }
+            let last = self.items.len() - 1;
+            // Like native radio buttons, the arrow keys wrap around.
+            match (ke.key_code, self.selected) {
+                (KeyCode::Space, None) => return self.select(cx, 0),
+                (KeyCode::ArrowDown | KeyCode::ArrowRight, None) => return self.select(cx, 0),
+                (KeyCode::ArrowDown | KeyCode::ArrowRight, Some(selected)) => {
+                    return self.select(cx, if selected == last { 0 } else { selected + 1 })
+                }
+                (KeyCode::ArrowUp | KeyCode::ArrowLeft, None) => return self.select(cx, last),
+                (KeyCode::ArrowUp | KeyCode::ArrowLeft, Some(selected)) => {
+                    return self.select(cx, if selected == 0 { last } else { selected - 1 })
+                }
+                _ => {}
+            }
+        }
+
+        match event.hits_pointer(cx, self.component_id, self.rect) {
+            Event::PointerHover(pe) => {
+                let hover = if pe.hover_state == HoverState::Out { None } else { self.item_at(pe.abs) };
+                if hover.is_some() {
+                    cx.set_hover_mouse_cursor(MouseCursor::Hand);
+                }
+                if hover != self.hover {
+                    self.hover = hover;
+                    cx.request_draw();
+                }
+            }
+            Event::PointerDown(pe) => {
+                cx.set_key_focus(Some(self.component_id));
+                self.pressed = self.item_at(pe.abs);
+            }
+            Event::PointerUp(pe) => {
+                let pressed = self.pressed.take();
+                if let (true, Some(index)) = (pe.is_over, pressed) {
+                    if self.item_at(pe.abs) == Some(index) {
+                        return self.select(cx, index);
+                    }
+                }
+            }
+            _ => {}
+        }
+        RadioGroupEvent::None
+    }
+
+    pub fn draw(&mut self, cx: &mut Cx) {
+        if self.horizontal {
+            cx.begin_row(Width::Compute, Height::Compute);
+        } else {
+            cx.begin_column(Width::Compute, Height::Compute);
+        }
+        self.item_rects.clear();
+        let mut focus_area = Area::Empty;
+        for (index, item) in self.items.iter().enumerate() {
+            if self.horizontal && index > 0 {
+                cx.add_box(LayoutSize::new(Width::Fix(Self::HORIZONTAL_GAP), Height::Fix(0.)));
+            }
+            let checked = self.checked_anims[index].get();
+            let (area, rect) = draw_labeled(cx, &self.theme, KIND_RADIO, checked, self.hover == Some(index), item);
+            // Show the focus ring around the selected item, or the first one if nothing is selected.
+            if index == 0 || Some(index) == self.selected {
+                focus_area = area;
+            }
+            self.item_rects.push(rect);
+        }
+        self.rect = Some(if self.horizontal { cx.end_row() } else { cx.end_column() });
+        cx.add_focusable(self.component_id, focus_area, Focusable::default());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    use zaplib::*;
+
+    #[test]
+    fn test_radio_group() {
+        let mut radio_group = RadioGroup::default().with_items(&["Small", "Medium", "Large"]);
+        let mut test_cx = TestCx::new(vec2(400., 300.));
+        test_cx.draw(|cx| radio_group.draw(cx));
+
+        // Clicking on the label of the second item selects it.
+        let events = test_cx.click(vec2(40., 22. + 11.), |cx, event| radio_group.handle(cx, event));
+        assert!(events.contains(&RadioGroupEvent::Selected(1)));
+        assert_eq!(radio_group.selected(), Some(1));
+
+        // The arrow keys wrap around.
+        test_cx.key_down(KeyCode::ArrowDown, KeyModifiers::default(), |cx, event| radio_group.handle(cx, event));
+        let events = test_cx.key_down(KeyCode::ArrowDown, KeyModifiers::default(), |cx, event| radio_group.handle(cx, event));
+        assert!(events.contains(&RadioGroupEvent::Selected(0)));
+    }
+}
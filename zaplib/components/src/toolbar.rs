@@ -0,0 +1,440 @@
+//! A row of icon buttons, typically at the top of a window or panel.
+
+use crate::background::*;
+use crate::button::ButtonEvent;
+use crate::buttonlogic::*;
+use crate::icon::*;
+use zaplib::*;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ToolbarItem {
+    /// An icon button, that sends [`ToolbarEvent::Clicked`] with `command` when clicked. The tooltip is also used as
+    /// its label in the overflow menu.
+    Button { command: CommandId, icon: Icon, tooltip: String },
+    /// A vertical line between groups of buttons.
+    Separator,
+}
+
+impl ToolbarItem {
+    pub fn button(command: CommandId, icon: Icon, tooltip: &str) -> Self {
+        ToolbarItem::Button { command, icon, tooltip: tooltip.to_string() }
+    }
+
+    pub fn separator() -> Self {
+        ToolbarItem::Separator
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ToolbarEvent {
+    None,
+    /// A button was clicked, either directly or from the overflow menu.
+    Clicked(CommandId),
+}
+
+struct ToolbarEntry {
+    item: ToolbarItem,
+    component_id: ComponentId,
+    background: Background,
+    enabled: bool,
+    /// Highlighted, e.g. for a "bold" button when the selected text is bold; see [`Toolbar::set_active`].
+    active: bool,
+}
+
+impl ToolbarEntry {
+    fn command(&self) -> Option<CommandId> {
+        match self.item {
+            ToolbarItem::Button { command, .. } => Some(command),
+            ToolbarItem::Separator => None,
+        }
+    }
+}
+
+/// A row of icon buttons with tooltips, identified by [`CommandId`]s like items in native menus. Buttons that don't
+/// fit go into a menu that opens from a "more" button at the end.
+///
+/// ```ignore
+/// const COMMAND_SEARCH: CommandId = location_hash!();
+///
+/// let mut toolbar = Toolbar::default().with_items(vec![
+///     ToolbarItem::button(Cx::COMMAND_UNDO, Icon::Undo, "Undo"),
+///     ToolbarItem::button(Cx::COMMAND_REDO, Icon::Redo, "Redo"),
+///     ToolbarItem::separator(),
+///     ToolbarItem::button(COMMAND_SEARCH, Icon::Search, "Search"),
+/// ]);
+///
+/// // In `handle`:
+/// if let ToolbarEvent::Clicked(command) = toolbar.handle(cx, event) {
+///     self.run_command(cx, command);
+/// }
+/// ```
+pub struct Toolbar {
+    entries: Vec<ToolbarEntry>,
+    background: Background,
+    /// The button that the pointer is over or pressing.
+    hover: Option<usize>,
+    pressed: Option<usize>,
+    /// Entries from this index onwards didn't fit during the last draw, and are in the overflow menu.
+    overflow_start: usize,
+    overflow_component_id: ComponentId,
+    overflow_background: Background,
+    overflow_hover: bool,
+    menu_open: bool,
+    menu_view: View,
+    menu_background: Background,
+    menu_highlight: Background,
+    menu_component_id: ComponentId,
+    menu_rect: Option<Rect>,
+    /// The entry index and rect of every button in the open menu.
+    menu_item_rects: Vec<(usize, Rect)>,
+    menu_hover: Option<usize>,
+    color: Vec4,
+    hover_color: Vec4,
+    active_color: Vec4,
+    icon_color: Vec4,
+}
+
+impl Default for Toolbar {
+    fn default() -> Self {
+        Self {
+            entries: vec![],
+            background: Background::default(),
+            hover: None,
+            pressed: None,
+            overflow_start: 0,
+            overflow_component_id: ComponentId::default(),
+            overflow_background: Background::default().with_radius(4.),
+            overflow_hover: false,
+            menu_open: false,
+            menu_view: View::default().with_is_overlay(true),
+            menu_background: Background::default().with_radius(2.5).with_draw_depth(5.),
+            menu_highlight: Background::default().with_draw_depth(5.1),
+            menu_component_id: ComponentId::default(),
+            menu_rect: None,
+            menu_item_rects: vec![],
+            menu_hover: None,
+            color: vec4(0.2, 0.2, 0.2, 1.),
+            hover_color: vec4(1., 1., 1., 0.1),
+            active_color: vec4(0.27, 0.56, 0.93, 0.5),
+            icon_color: vec4(0.9, 0.9, 0.9, 1.),
+        }
+    }
+}
+
+impl Toolbar {
+    const HEIGHT: f32 = 32.;
+    const BUTTON_SIZE: f32 = 28.;
+    const BUTTON_GAP: f32 = 2.;
+    const SEPARATOR_WIDTH: f32 = 9.;
+    const MENU_PADDING: Padding = Padding::vh(6., 8.);
+
+    #[must_use]
+    pub fn with_items(self, items: Vec<ToolbarItem>) -> Self {
+        let entries = items
+            .into_iter()
+            .map(|item| ToolbarEntry {
+                item,
+                component_id: ComponentId::default(),
+                background: Background::default().with_radius(4.),
+                enabled: true,
+                active: false,
+            })
+            .collect();
+        Self { entries, ..self }
+    }
+    #[must_use]
+    pub fn with_color(self, color: Vec4) -> Self {
+        Self { color, ..self }
+    }
+    #[must_use]
+    pub fn with_icon_color(self, icon_color: Vec4) -> Self {
+        Self { icon_color, ..self }
+    }
+
+    fn entry_mut(&mut self, command: CommandId) -> Option<&mut ToolbarEntry> {
+        self.entries.iter_mut().find(|entry| entry.command() == Some(command))
+    }
+
+    /// Disabled buttons are dimmed, and can't be clicked.
+    pub fn set_enabled(&mut self, cx: &mut Cx, command: CommandId, enabled: bool) {
+        if let Some(entry) = self.entry_mut(command) {
+            entry.enabled = enabled;
+            cx.request_draw();
+        }
+    }
+
+    /// Highlight a button, e.g. for toggles like "bold".
+    pub fn set_active(&mut self, cx: &mut Cx, command: CommandId, active: bool) {
+        if let Some(entry) = self.entry_mut(command) {
+            entry.active = active;
+            cx.request_draw();
+        }
+    }
+
+    fn close_menu(&mut self, cx: &mut Cx) {
+        self.menu_open = false;
+        self.menu_hover = None;
+        cx.request_draw();
+    }
+
+    pub fn handle(&mut self, cx: &mut Cx, event: &mut Event) -> ToolbarEvent {
+        if self.menu_open {
+            // Hit test the menu first, so that clicks on it don't go through to whatever is underneath.
+            match event.hits_pointer(cx, self.menu_component_id, self.menu_rect) {
+                Event::PointerHover(pe) => {
+                    let menu_hover = self.menu_item_rects.iter().find(|(_, rect)| rect.contains(pe.abs)).map(|(index, _)| *index);
+                    if menu_hover.map_or(false, |index| self.entries[index].enabled) {
+                        cx.set_hover_mouse_cursor(MouseCursor::Hand);
+                    }
+                    if menu_hover != self.menu_hover {
+                        self.menu_hover = menu_hover;
+                        cx.request_draw();
+                    }
+                }
+                Event::PointerDown(pe) => {
+                    let clicked = self.menu_item_rects.iter().find(|(_, rect)| rect.contains(pe.abs)).map(|(index, _)| *index);
+                    if let Some(index) = clicked {
+                        if let (true, Some(command)) = (self.entries[index].enabled, self.entries[index].command()) {
+                            self.close_menu(cx);
+                            return ToolbarEvent::Clicked(command);
+                        }
+                    }
+                }
+                _ => {}
+            }
+            if let Event::PointerDown(pe) = event {
+                let on_button =
+                    self.overflow_background.area().get_rect_for_first_instance(cx).map_or(false, |rect| rect.contains(pe.abs));
+                let on_menu = self.menu_rect.map_or(false, |rect| rect.contains(pe.abs));
+                if !on_button && !on_menu {
+                    self.close_menu(cx);
+                }
+            }
+            if let Event::KeyDown(KeyEvent { key_code: KeyCode::Escape, .. }) = event {
+                self.close_menu(cx);
+            }
+        }
+
+        for index in 0..self.overflow_start.min(self.entries.len()) {
+            let entry = &self.entries[index];
+            let command = match (entry.enabled, entry.command()) {
+                (true, Some(command)) => command,
+                _ => continue,
+            };
+            if let Event::KeyDown(ke) = event.hits_keyboard(cx, entry.component_id) {
+                if (ke.key_code == KeyCode::Space || ke.key_code == KeyCode::Return) && !ke.is_repeat {
+                    return ToolbarEvent::Clicked(command);
+                }
+            }
+
+            let hit_event = event.hits_pointer(cx, entry.component_id, entry.background.area().get_rect_for_first_instance(cx));
+            let (mut hover, mut pressed) = (self.hover, self.pressed);
+            let button_event = handle_button_logic(cx, hit_event, |_, logic_event| {
+                let this = Some(index);
+                match logic_event {
+                    ButtonLogicEvent::Over => (hover, pressed) = (this, None),
+                    ButtonLogicEvent::Down => (hover, pressed) = (this, this),
+                    ButtonLogicEvent::Default => {
+                        if hover == this {
+                            hover = None;
+                        }
+                        if pressed == this {
+                            pressed = None;
+                        }
+                    }
+                }
+            });
+            if (hover, pressed) != (self.hover, self.pressed) {
+                self.hover = hover;
+                self.pressed = pressed;
+                cx.request_draw();
+            }
+            if button_event == ButtonEvent::Clicked {
+                return ToolbarEvent::Clicked(command);
+            }
+        }
+
+        let hit_event =
+            event.hits_pointer(cx, self.overflow_component_id, self.overflow_background.area().get_rect_for_first_instance(cx));
+        let mut overflow_hover = self.overflow_hover;
+        let button_event =
+            handle_button_logic(cx, hit_event, |_, logic_event| overflow_hover = logic_event != ButtonLogicEvent::Default);
+        if overflow_hover != self.overflow_hover {
+            self.overflow_hover = overflow_hover;
+            cx.request_draw();
+        }
+        if button_event == ButtonEvent::Down {
+            self.menu_open = !self.menu_open;
+            self.menu_hover = None;
+            cx.request_draw();
+        }
+        ToolbarEvent::None
+    }
+
+    fn entry_width(entry: &ToolbarEntry) -> f32 {
+        match entry.item {
+            ToolbarItem::Button { .. } => Self::BUTTON_SIZE + Self::BUTTON_GAP,
+            ToolbarItem::Separator => Self::SEPARATOR_WIDTH,
+        }
+    }
+
+    /// Draw a button or separator background, with the icon on top. Returns the rect of the background.
+    fn draw_button(cx: &mut Cx, background: &mut Background, icon: Option<Icon>, color: Vec4, icon_color: Vec4) -> Rect {
+        let rect = match icon {
+            Some(_) => cx.add_box(LayoutSize::new(Width::Fix(Self::BUTTON_SIZE + Self::BUTTON_GAP), Height::Fix(Self::HEIGHT))),
+            None => cx.add_box(LayoutSize::new(Width::Fix(Self::SEPARATOR_WIDTH), Height::Fix(Self::HEIGHT))),
+        };
+        match icon {
+            Some(icon) => {
+                let offset = (Self::HEIGHT - Self::BUTTON_SIZE) / 2.;
+                let button_rect =
+                    Rect { pos: rect.pos + vec2(Self::BUTTON_GAP / 2., offset), size: Vec2::all(Self::BUTTON_SIZE) };
+                background.draw(cx, button_rect, color);
+                let icon_offset = (Self::BUTTON_SIZE - Icon::SIZE) / 2.;
+                let icon_rect = Rect { pos: button_rect.pos + Vec2::all(icon_offset), size: Vec2::all(Icon::SIZE) };
+                icon.draw(cx, icon_rect, icon_color, 0.);
+                button_rect
+            }
+            None => {
+                let line_rect = Rect { pos: rect.pos + vec2(4., 7.), size: vec2(1., Self::HEIGHT - 14.) };
+                background.draw(cx, line_rect, icon_color * vec4(1., 1., 1., 0.3));
+                line_rect
+            }
+        }
+    }
+
+    pub fn draw(&mut self, cx: &mut Cx) {
+        self.background.begin_draw(cx, Width::Fill, Height::Fix(Self::HEIGHT), self.color);
+
+        // Figure out how many entries fit, leaving room for the overflow button if not all of them do.
+        let available = cx.get_width_left();
+        let total: f32 = self.entries.iter().map(Self::entry_width).sum();
+        self.overflow_start = self.entries.len();
+        if total > available {
+            let mut width = Self::BUTTON_SIZE + Self::BUTTON_GAP;
+            self.overflow_start = self
+                .entries
+                .iter()
+                .position(|entry| {
+                    width += Self::entry_width(entry);
+                    width > available
+                })
+                .unwrap_or(self.entries.len());
+            // Don't end with a separator right before the overflow button.
+            while self.overflow_start > 0 && self.entries[self.overflow_start - 1].item == ToolbarItem::Separator {
+                self.overflow_start -= 1;
+            }
+        }
+
+        for (index, entry) in self.entries.iter_mut().enumerate().take(self.overflow_start) {
+            let icon = match &entry.item {
+                ToolbarItem::Button { icon, .. } => Some(*icon),
+                ToolbarItem::Separator => None,
+            };
+            let color = if self.pressed == Some(index) || entry.active {
+                self.active_color
+            } else if self.hover == Some(index) && entry.enabled {
+                self.hover_color
+            } else {
+                Vec4::default()
+            };
+            let icon_color = if entry.enabled { self.icon_color } else { self.icon_color * vec4(1., 1., 1., 0.35) };
+            Self::draw_button(cx, &mut entry.background, icon, color, icon_color);
+            if let ToolbarItem::Button { tooltip, .. } = &entry.item {
+                cx.add_tooltip(entry.component_id, entry.background.area(), tooltip);
+                if entry.enabled {
+                    cx.add_focusable(entry.component_id, entry.background.area(), Focusable::default());
+                }
+            }
+        }
+
+        if self.overflow_start < self.entries.len() {
+            let color = if self.menu_open {
+                self.active_color
+            } else if self.overflow_hover {
+                self.hover_color
+            } else {
+                Vec4::default()
+            };
+            Self::draw_button(cx, &mut self.overflow_background, Some(Icon::More), color, self.icon_color);
+            cx.add_tooltip(self.overflow_component_id, self.overflow_background.area(), "More");
+        } else {
+            self.menu_open = false;
+        }
+        self.background.end_draw(cx);
+
+        self.draw_menu(cx);
+    }
+
+    fn draw_menu(&mut self, cx: &mut Cx) {
+        self.menu_rect = None;
+        self.menu_item_rects.clear();
+        if !self.menu_open {
+            return;
+        }
+        let button_rect = match self.overflow_background.area().get_rect_for_first_instance(cx) {
+            Some(rect) => rect,
+            None => return,
+        };
+
+        let padding = Self::MENU_PADDING;
+        let props = TextInsProps { draw_depth: 5.2, color: self.icon_color, ..TextInsProps::DEFAULT };
+        let line_height = cx.measure_wrapped_text(&props, "X", f32::INFINITY).line_height;
+        let item_height = line_height.max(Icon::SIZE) + padding.t + padding.b;
+        let text_x = padding.l + Icon::SIZE + 8.;
+        let mut width = button_rect.size.x;
+        let mut height = 0.;
+        for entry in &self.entries[self.overflow_start..] {
+            match &entry.item {
+                ToolbarItem::Button { tooltip, .. } => {
+                    width = width.max(text_x + cx.measure_wrapped_text(&props, tooltip, f32::INFINITY).width + padding.r);
+                    height += item_height;
+                }
+                ToolbarItem::Separator => height += Self::SEPARATOR_WIDTH,
+            }
+        }
+
+        self.menu_view.begin_view(cx, LayoutSize::FILL);
+        // Align the right edge of the menu with the overflow button, as it is typically at the right of the window.
+        let menu_pos =
+            vec2((button_rect.pos.x + button_rect.size.x - width).max(0.), button_rect.pos.y + button_rect.size.y + 2.);
+        let menu_rect = Rect { pos: menu_pos, size: vec2(width, height) };
+        self.menu_background.draw(cx, menu_rect, self.color);
+        self.menu_rect = Some(menu_rect);
+
+        let mut y = menu_pos.y;
+        for (index, entry) in self.entries.iter().enumerate().skip(self.overflow_start) {
+            match &entry.item {
+                ToolbarItem::Button { icon, tooltip, .. } => {
+                    let rect = Rect { pos: vec2(menu_pos.x, y), size: vec2(width, item_height) };
+                    if self.menu_hover == Some(index) && entry.enabled {
+                        self.menu_highlight.draw(cx, rect, self.hover_color);
+                    }
+                    let alpha = if entry.enabled { 1. } else { 0.35 };
+                    let icon_pos = rect.pos + vec2(padding.l, (item_height - Icon::SIZE) / 2.);
+                    icon.draw(
+                        cx,
+                        Rect { pos: icon_pos, size: Vec2::all(Icon::SIZE) },
+                        self.icon_color * vec4(1., 1., 1., alpha),
+                        5.2,
+                    );
+                    let text_pos = rect.pos + vec2(text_x, (item_height - line_height) / 2.);
+                    TextIns::draw_str(
+                        cx,
+                        tooltip,
+                        text_pos,
+                        &TextInsProps { color: props.color * vec4(1., 1., 1., alpha), ..props },
+                    );
+                    self.menu_item_rects.push((index, rect));
+                    y += item_height;
+                }
+                ToolbarItem::Separator => {
+                    let rect = Rect { pos: vec2(menu_pos.x + 4., y + Self::SEPARATOR_WIDTH / 2.), size: vec2(width - 8., 1.) };
+                    self.menu_highlight.draw(cx, rect, self.icon_color * vec4(1., 1., 1., 0.3));
+                    y += Self::SEPARATOR_WIDTH;
+                }
+            }
+        }
+        self.menu_view.end_view(cx);
+    }
+}
@@ -0,0 +1,88 @@
+//! Draws the tooltip that is active, see [`Cx::add_tooltip`].
+
+use crate::background::*;
+use zaplib::*;
+
+/// Draws the text of the active tooltip (see [`Cx::get_active_tooltip`]) in a box near the pointer, keeping it
+/// within the window, and fading it in.
+///
+/// Draw this after everything else in your window, so it sits on top and all tooltips have been registered.
+pub struct Tooltip {
+    view: View,
+    background: Background,
+    background_color: Vec4,
+    text_color: Vec4,
+    text_style: TextStyle,
+    /// Longer text gets wrapped at this width.
+    max_width: f32,
+}
+
+impl Default for Tooltip {
+    fn default() -> Self {
+        Self {
+            view: View::default().with_is_overlay(true),
+            background: Background::default().with_radius(3.).with_draw_depth(20.),
+            background_color: vec4(0.1, 0.1, 0.1, 0.95),
+            text_color: vec4(0.9, 0.9, 0.9, 1.),
+            text_style: TEXT_STYLE_NORMAL,
+            max_width: 300.,
+        }
+    }
+}
+
+impl Tooltip {
+    const PADDING: Padding = Padding::vh(4., 6.);
+
+    #[must_use]
+    pub fn with_background_color(self, background_color: Vec4) -> Self {
+        Self { background_color, ..self }
+    }
+    #[must_use]
+    pub fn with_text_color(self, text_color: Vec4) -> Self {
+        Self { text_color, ..self }
+    }
+    #[must_use]
+    pub fn with_text_style(self, text_style: TextStyle) -> Self {
+        Self { text_style, ..self }
+    }
+    #[must_use]
+    pub fn with_max_width(self, max_width: f32) -> Self {
+        Self { max_width, ..self }
+    }
+
+    pub fn draw(&mut self, cx: &mut Cx) {
+        let tooltip = match cx.get_active_tooltip() {
+            Some(tooltip) => tooltip,
+            None => return,
+        };
+
+        let props = TextInsProps {
+            text_style: self.text_style,
+            wrapping: Wrapping::Word,
+            draw_depth: 20.1,
+            color: vec4(self.text_color.x, self.text_color.y, self.text_color.z, self.text_color.w * tooltip.opacity),
+            ..TextInsProps::DEFAULT
+        };
+        let lines = cx.wrap_text(&props, &tooltip.text, self.max_width);
+        let metrics = cx.measure_wrapped_text(&props, &tooltip.text, self.max_width);
+        let padding = Self::PADDING;
+        let size = vec2(metrics.width + padding.l + padding.r, metrics.height + padding.t + padding.b);
+
+        self.view.begin_view(cx, LayoutSize::FILL);
+        let pos = tooltip.position(size, cx.get_box_rect());
+        let color = self.background_color;
+        self.background.draw(cx, Rect { pos, size }, vec4(color.x, color.y, color.z, color.w * tooltip.opacity));
+        // Same line heights as `Cx::measure_wrapped_text`.
+        let line_height = metrics.line_height;
+        let newline_height = (self.text_style.font_size * self.text_style.line_spacing).max(line_height);
+        let mut line_pos = pos + vec2(padding.l, padding.t);
+        for line in &lines {
+            let text = line.text.trim_end();
+            if !text.is_empty() {
+                TextIns::draw_str(cx, text, line_pos, &TextInsProps { wrapping: Wrapping::None, ..props });
+            }
+            line_pos.y += if line.newline { newline_height } else { line_height };
+        }
+        self.view.end_view(cx);
+    }
+}
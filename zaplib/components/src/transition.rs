@@ -0,0 +1,205 @@
+//! Enter and exit transitions for when widgets start or stop being drawn.
+
+use zaplib::*;
+
+/// Runs an animation when a widget starts being drawn ("enter"), and keeps drawing it while running
+/// another animation when it stops being drawn ("exit"), before actually removing it.
+///
+/// Widgets declare their transitions by passing in an enter and exit [`Anim`], which should both have
+/// two tracks:
+/// * Track 0: opacity ([`Track::Float`]) between 0 and 1. Since we can't fade arbitrary shaders, it's up to
+///   the widget to apply this using [`Transition::get_opacity`], e.g. by multiplying it with its colors.
+/// * Track 1: offset ([`Track::Vec2`]) in pixels, which is applied automatically to everything that's
+///   drawn between [`Transition::begin_draw`] and [`Transition::end_draw`].
+///
+/// For the enter [`Anim`], key frames should start at 0, since it can interrupt an exit transition (or
+/// vice versa), in which case it continues from the current values. Some common transitions are defined
+/// as constants, such as [`Transition::FADE_IN`] and [`Transition::FADE_OUT`].
+///
+/// Usage:
+/// ```ignore
+/// // In `handle`:
+/// if self.transition.handle(cx, event) {
+///     cx.request_draw();
+/// }
+/// // In `draw`:
+/// if self.transition.begin_draw(cx, self.is_open) {
+///     let opacity = self.transition.get_opacity();
+///     // draw the widget..
+///     self.transition.end_draw(cx);
+/// }
+/// ```
+#[derive(Debug)]
+pub struct Transition {
+    animator: Animator,
+    enter: Anim,
+    exit: Anim,
+    /// Whether we should currently be drawing, which includes while exiting.
+    drawn: bool,
+    /// Whether we're currently entering or fully visible, as opposed to exiting or hidden.
+    visible: bool,
+}
+
+impl Transition {
+    pub const FADE_IN: Anim = Anim {
+        duration: 0.2,
+        tracks: &[
+            Track::Float { key_frames: &[(0.0, 0.), (1.0, 1.)], ease: Ease::OutQuad },
+            Track::Vec2 { key_frames: &[(0.0, vec2(0., 0.)), (1.0, vec2(0., 0.))], ease: Ease::Lin },
+        ],
+        ..Anim::DEFAULT
+    };
+    pub const FADE_OUT: Anim = Anim {
+        duration: 0.2,
+        tracks: &[
+            Track::Float { key_frames: &[(1.0, 0.)], ease: Ease::InQuad },
+            Track::Vec2 { key_frames: &[(1.0, vec2(0., 0.))], ease: Ease::Lin },
+        ],
+        ..Anim::DEFAULT
+    };
+    pub const SLIDE_IN_FROM_BOTTOM: Anim = Anim {
+        duration: 0.25,
+        tracks: &[
+            Track::Float { key_frames: &[(0.0, 0.), (1.0, 1.)], ease: Ease::OutQuad },
+            Track::Vec2 { key_frames: &[(0.0, vec2(0., 20.)), (1.0, vec2(0., 0.))], ease: Ease::OutCubic },
+        ],
+        ..Anim::DEFAULT
+    };
+    pub const SLIDE_OUT_TO_BOTTOM: Anim = Anim {
+        duration: 0.25,
+        tracks: &[
+            Track::Float { key_frames: &[(1.0, 0.)], ease: Ease::InQuad },
+            Track::Vec2 { key_frames: &[(1.0, vec2(0., 20.))], ease: Ease::InCubic },
+        ],
+        ..Anim::DEFAULT
+    };
+
+    /// Create a new [`Transition`] with the given enter and exit [`Anim`]s. See [`Transition`] for
+    /// which tracks these need to have.
+    pub fn new(enter: Anim, exit: Anim) -> Self {
+        Self { animator: Animator::default(), enter, exit, drawn: false, visible: false }
+    }
+
+    /// A [`Transition`] that fades in and out.
+    pub fn fade() -> Self {
+        Self::new(Self::FADE_IN, Self::FADE_OUT)
+    }
+
+    /// A [`Transition`] that fades in while sliding up, and vice versa.
+    pub fn slide() -> Self {
+        Self::new(Self::SLIDE_IN_FROM_BOTTOM, Self::SLIDE_OUT_TO_BOTTOM)
+    }
+
+    /// Handle [`Event::NextFrame`] to advance the animations. Returns true if something changed, in
+    /// which case you should redraw.
+    pub fn handle(&mut self, cx: &mut Cx, event: &mut Event) -> bool {
+        match self.animator.handle_event(cx, event) {
+            AnimatorEvent::None => false,
+            AnimatorEvent::Updated => true,
+            AnimatorEvent::Completed => {
+                if !self.visible {
+                    // The exit transition is done, so we can stop drawing.
+                    self.drawn = false;
+                }
+                true
+            }
+        }
+    }
+
+    /// Whether the widget is currently entering or exiting.
+    pub fn is_animating(&self) -> bool {
+        self.animator.is_playing()
+    }
+
+    /// Current opacity between 0 and 1, which the widget should apply itself.
+    pub fn get_opacity(&self) -> f32 {
+        self.animator.get_float(0)
+    }
+
+    /// Current offset, which gets applied in [`Transition::end_draw`].
+    pub fn get_offset(&self) -> Vec2 {
+        self.animator.get_vec2(1)
+    }
+
+    /// Call this every time you would draw the widget, with `visible` set to whether or not it should
+    /// be shown. This starts an enter or exit transition if `visible` changed. Returns whether to draw the
+    /// widget; if so, be sure to call [`Transition::end_draw`] afterwards.
+    pub fn begin_draw(&mut self, cx: &mut Cx, visible: bool) -> bool {
+        self.animator.draw(cx, self.exit.clone());
+        if visible != self.visible {
+            self.visible = visible;
+            if visible {
+                self.drawn = true;
+                self.animator.play_anim(cx, self.enter.clone());
+            } else if self.drawn {
+                self.animator.play_anim(cx, self.exit.clone());
+            }
+        }
+        if self.drawn {
+            cx.begin_offset_box();
+        }
+        self.drawn
+    }
+
+    /// Ends [`Transition::begin_draw`], moving everything that was drawn by the current offset.
+    pub fn end_draw(&mut self, cx: &mut Cx) {
+        cx.end_offset_box(self.get_offset());
+    }
+}
+
+impl Default for Transition {
+    fn default() -> Self {
+        Self::fade()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    use zaplib::*;
+
+    fn draw(test_cx: &mut TestCx, transition: &mut Transition, background: &mut Background, visible: bool) -> bool {
+        let mut drawn = false;
+        test_cx.draw(|cx| {
+            if transition.begin_draw(cx, visible) {
+                background.begin_draw(cx, Width::Fix(100.), Height::Fix(50.), vec4(1., 1., 1., transition.get_opacity()));
+                background.end_draw(cx);
+                transition.end_draw(cx);
+                drawn = true;
+            }
+        });
+        drawn
+    }
+
+    #[test]
+    fn test_slide_in_and_out() {
+        let mut transition = Transition::slide();
+        let mut background = Background::default();
+        let mut test_cx = TestCx::new(vec2(400., 300.));
+
+        assert!(!draw(&mut test_cx, &mut transition, &mut background, false));
+
+        // Halfway through entering we should be offset and partially transparent.
+        assert!(draw(&mut test_cx, &mut transition, &mut background, true));
+        test_cx.next_frame(0.1, |cx, event| transition.handle(cx, event));
+        assert!(draw(&mut test_cx, &mut transition, &mut background, true));
+        assert!(transition.get_opacity() > 0. && transition.get_opacity() < 1.);
+        let rect = background.area().get_rect_for_first_instance(&test_cx.cx).unwrap();
+        assert!(rect.pos.y > 0.);
+
+        // Done entering.
+        test_cx.next_frame(1., |cx, event| transition.handle(cx, event));
+        assert!(!transition.is_animating());
+        assert!(draw(&mut test_cx, &mut transition, &mut background, true));
+        assert_eq!(transition.get_opacity(), 1.);
+        let rect = background.area().get_rect_for_first_instance(&test_cx.cx).unwrap();
+        assert_eq!(rect.pos, vec2(0., 0.));
+
+        // Keep drawing while exiting, until the exit animation completes.
+        assert!(draw(&mut test_cx, &mut transition, &mut background, false));
+        test_cx.next_frame(0.1, |cx, event| transition.handle(cx, event));
+        assert!(draw(&mut test_cx, &mut transition, &mut background, false));
+        test_cx.next_frame(1., |cx, event| transition.handle(cx, event));
+        assert!(!draw(&mut test_cx, &mut transition, &mut background, false));
+    }
+}
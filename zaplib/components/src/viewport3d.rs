@@ -8,7 +8,7 @@ const EPSILON: f32 = 0.0001;
 
 /// A nice article about how a 3D camera's look_at function works:
 /// <https://www.scratchapixel.com/lessons/mathematics-physics-for-computer-graphics/lookat-function>
-fn look_at(eye: Vec3, at: Vec3, up: Vec3) -> Mat4 {
+pub(crate) fn look_at(eye: Vec3, at: Vec3, up: Vec3) -> Mat4 {
     let forward = (eye - at).normalize();
     let left = Vec3::cross(up, forward).normalize();
     let up = Vec3::cross(forward, left);
@@ -96,6 +96,7 @@ pub struct Viewport3D {
     camera_target_offset_start: Option<Vec3>,
     props: Viewport3DProps,
     has_read_props: bool,
+    xr_stereo: bool,
 }
 
 impl Default for Viewport3D {
@@ -118,6 +119,7 @@ impl Default for Viewport3D {
             view_2d: Default::default(),
             has_read_props: Default::default(),
             props: Default::default(),
+            xr_stereo: Default::default(),
         }
     }
 }
@@ -146,23 +148,27 @@ impl Viewport3D {
                 let min_distance = 1.0; // a little more than near
                 let max_distance = 900.; // a little less than far
                 let zoom_speed = (self.camera_position.radius * (PI / 4.) / max_distance).sin().abs() / 2.0;
-                self.camera_position.radius =
-                    (self.camera_position.radius + pe.scroll.y * zoom_speed).max(min_distance).min(max_distance);
+                // Pinching in zooms in, so move the camera closer by the same factor.
+                let radius = (self.camera_position.radius + pe.scroll.y * zoom_speed) / (1. + pe.pinch);
+                self.camera_position.radius = radius.max(min_distance).min(max_distance);
                 return Some(self.pass_set_matrix_mode(cx));
             }
-            Event::PointerMove(pe) => {
+            // Rotate using raw mouse motion, so that it doesn't stop when the pointer hits the edge of the screen.
+            Event::PointerRawMove(pe) if self.camera_position_start.is_some() => {
                 // Using standard makeSafe approach to clamp to slightly less than the limits for phi/theta
                 // Concept borrowed from ThreeJS:
                 // https://github.com/mrdoob/three.js/blob/342946c8392639028da439b6dc0597e58209c696/src/math/Spherical.js#L43
-                if let Some(SphericalAngles { phi, theta, radius }) = self.camera_position_start {
-                    let rotate_speed = 1. / 175.;
-                    self.camera_position = SphericalAngles {
-                        theta: (theta - (pe.abs.x - pe.abs_start.x) * rotate_speed) % (PI * 2.),
-                        phi: (phi - (pe.abs.y - pe.abs_start.y) * rotate_speed).clamp(EPSILON, PI - EPSILON),
-                        radius,
-                    };
-                    return Some(self.pass_set_matrix_mode(cx));
-                } else if let Some(camera_target_offset_start) = self.camera_target_offset_start {
+                let SphericalAngles { phi, theta, radius } = self.camera_position;
+                let rotate_speed = 1. / 175.;
+                self.camera_position = SphericalAngles {
+                    theta: (theta - pe.delta.x * rotate_speed) % (PI * 2.),
+                    phi: (phi - pe.delta.y * rotate_speed).clamp(EPSILON, PI - EPSILON),
+                    radius,
+                };
+                return Some(self.pass_set_matrix_mode(cx));
+            }
+            Event::PointerMove(pe) => {
+                if let Some(camera_target_offset_start) = self.camera_target_offset_start {
                     // TODO(Shobhit): Whenever we do Orthographic view properly, we need to adjust the panning accordingly
                     // We would need to consider viewable area's width and height into consideration just like how
                     // worldview does it:
@@ -196,28 +202,37 @@ impl Viewport3D {
         None
     }
 
-    fn get_matrix_projection(&self) -> PassMatrixMode {
+    /// The view matrix of the camera (`camera_view` in shaders), e.g. for sorting semi-transparent things with
+    /// [`crate::sort_back_to_front`]. Only changes in [`Viewport3D::handle`] and [`Viewport3D::begin_draw`].
+    pub fn camera_view(&self) -> Mat4 {
         let SphericalAngles { phi, theta, radius } = self.camera_position;
         let eye = radius * vec3(phi.sin() * theta.sin(), phi.cos(), phi.sin() * theta.cos());
+        look_at(
+            eye + self.props.camera_target + self.camera_target_offset,
+            self.props.camera_target + self.camera_target_offset,
+            vec3(0., 1., 0.),
+        )
+    }
 
-        PassMatrixMode::Projection {
-            fov_y: 40.0,
-            near: 0.1,
-            far: 1000.0,
-            cam: look_at(
-                eye + self.props.camera_target + self.camera_target_offset,
-                self.props.camera_target + self.camera_target_offset,
-                vec3(0., 1., 0.),
-            ),
-        }
+    /// The camera of the viewport, as set on its [`Pass`]; e.g. for level of detail in [`crate::PointCloud::draw`].
+    /// Changes at the same times as [`Viewport3D::camera_view`].
+    pub fn matrix_mode(&self) -> PassMatrixMode {
+        PassMatrixMode::Projection { fov_y: 40.0, near: 0.1, far: 1000.0, cam: self.camera_view() }
     }
 
     fn pass_set_matrix_mode(&mut self, cx: &mut Cx) -> PassMatrixMode {
-        let matrix_mode = self.get_matrix_projection();
+        let matrix_mode = self.matrix_mode();
         self.pass.set_matrix_mode(cx, matrix_mode.clone());
         matrix_mode
     }
 
+    /// Show the scene in the VR headset while the window is presenting to one; see [`Pass::set_xr_stereo`]. The
+    /// camera then follows the head instead, and the viewport in the page keeps showing the last frame from before.
+    pub fn set_xr_stereo(&mut self, cx: &mut Cx, xr_stereo: bool) {
+        self.xr_stereo = xr_stereo;
+        self.pass.set_xr_stereo(cx, xr_stereo);
+    }
+
     /// TODO(JP): This is kind of exploiting a potential bug in the framework.. We don't clean up [`Pass`]es,
     /// so if we just don't call [`Pass::begin_pass`] then it will happily keep on rendering. Is this a bug
     /// or a feature? I'm not sure.. See [`Pass::begin_pass`] for more thoughts.
@@ -250,6 +265,7 @@ impl Viewport3D {
         self.pass.add_color_texture(cx, color_texture_handle, ClearColor::ClearWith(self.clear_color));
         let depth_texture_handle = self.depth_texture.get_depth(cx);
         self.pass.set_depth_texture(cx, depth_texture_handle, ClearDepth::ClearWith(1.0));
+        self.pass.set_xr_stereo(cx, self.xr_stereo);
 
         self.view_3d.begin_view(cx, LayoutSize::FILL);
     }
@@ -0,0 +1,900 @@
+//! Volume rendering of 3D scalar fields, like CT and MRI scans or simulation output.
+//!
+//! A [`ScalarVolume`] gets uploaded to the GPU by a [`VolumeRenderer`], which raymarches through it front to back,
+//! coloring every value using a [`TransferFunction`]. Users can edit transfer functions using a
+//! [`TransferFunctionEditor`], and cut away parts of the volume using [`SlicePlane`]s.
+//!
+//! Not every platform supports 3D textures, so the slices of a volume get tiled into one 2D texture, and the shader
+//! interpolates between slices itself.
+
+use std::cmp::Ordering;
+use zaplib::*;
+
+/// The largest width and height of the texture that a volume gets tiled into; every platform supports this.
+const MAX_ATLAS_SIZE: usize = 4096;
+/// The width of transfer function textures.
+const TRANSFER_FUNCTION_SIZE: usize = 256;
+
+/// A 3D grid of values, with X changing fastest and Z slowest.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ScalarVolume {
+    width: usize,
+    height: usize,
+    depth: usize,
+    values: Vec<f32>,
+}
+
+impl ScalarVolume {
+    pub fn new(width: usize, height: usize, depth: usize, values: Vec<f32>) -> Self {
+        assert_eq!(values.len(), width * height * depth, "Expected width * height * depth values");
+        Self { width, height, depth, values }
+    }
+
+    /// The width, height, and depth, in voxels.
+    pub fn dimensions(&self) -> [usize; 3] {
+        [self.width, self.height, self.depth]
+    }
+
+    pub fn values(&self) -> &[f32] {
+        &self.values
+    }
+
+    pub fn get(&self, x: usize, y: usize, z: usize) -> f32 {
+        self.values[(z * self.height + y) * self.width + x]
+    }
+
+    /// The lowest and highest value, ignoring NaNs; `(0., 0.)` if there are none.
+    pub fn value_range(&self) -> (f32, f32) {
+        let mut values = self.values.iter().filter(|value| !value.is_nan());
+        let first = values.next().copied().unwrap_or(0.);
+        values.fold((first, first), |(min, max), &value| (min.min(value), max.max(value)))
+    }
+
+    /// How many values fall into each of `bins` equally wide bins between the lowest and highest value, e.g. to
+    /// show in a [`TransferFunctionEditor`].
+    pub fn histogram(&self, bins: usize) -> Vec<usize> {
+        let mut histogram = vec![0; bins];
+        let (min, max) = self.value_range();
+        let span = (max - min).max(f32::MIN_POSITIVE);
+        for value in self.values.iter().filter(|value| !value.is_nan()) {
+            histogram[(((value - min) / span * bins as f32) as usize).min(bins - 1)] += 1;
+        }
+        histogram
+    }
+
+    /// Half the size along every axis, averaging blocks of 2 by 2 by 2 voxels.
+    fn downsampled(&self) -> Self {
+        let (width, height, depth) = ((self.width + 1) / 2, (self.height + 1) / 2, (self.depth + 1) / 2);
+        let mut values = Vec::with_capacity(width * height * depth);
+        for z in 0..depth {
+            for y in 0..height {
+                for x in 0..width {
+                    let mut sum = 0.;
+                    let mut count = 0.;
+                    for (dx, dy, dz) in (0..8).map(|corner| (corner & 1, corner >> 1 & 1, corner >> 2 & 1)) {
+                        let (source_x, source_y, source_z) = (x * 2 + dx, y * 2 + dy, z * 2 + dz);
+                        if source_x < self.width && source_y < self.height && source_z < self.depth {
+                            sum += self.get(source_x, source_y, source_z);
+                            count += 1.;
+                        }
+                    }
+                    values.push(sum / count);
+                }
+            }
+        }
+        Self { width, height, depth, values }
+    }
+}
+
+/// How to tile the slices of a volume with `dimensions` into one texture: the number of columns, and the width and
+/// height of the texture; [`None`] if it doesn't fit in [`MAX_ATLAS_SIZE`].
+fn atlas_layout(dimensions: [usize; 3]) -> Option<(usize, usize, usize)> {
+    let [width, height, depth] = dimensions;
+    if width > MAX_ATLAS_SIZE || height > MAX_ATLAS_SIZE {
+        return None;
+    }
+    let columns = (MAX_ATLAS_SIZE / width).min(depth).max(1);
+    let rows = (depth + columns - 1) / columns;
+    (rows * height <= MAX_ATLAS_SIZE).then(|| (columns, columns * width, rows * height))
+}
+
+/// A point of a [`TransferFunction`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TransferFunctionPoint {
+    /// From 0 for the lowest value of the volume, to 1 for the highest; see [`ScalarVolume::value_range`].
+    pub value: f32,
+    /// The color and opacity of this value. Opacity is per voxel, so it doesn't depend on how often a
+    /// [`VolumeRenderer`] samples.
+    pub color: Vec4,
+}
+
+/// Maps the values of a volume to colors and opacities, by interpolating linearly between points.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TransferFunction {
+    points: Vec<TransferFunctionPoint>,
+}
+
+impl Default for TransferFunction {
+    /// From transparent black for the lowest value, to opaque white for the highest.
+    fn default() -> Self {
+        Self::new(vec![
+            TransferFunctionPoint { value: 0., color: vec4(0., 0., 0., 0.) },
+            TransferFunctionPoint { value: 1., color: vec4(1., 1., 1., 1.) },
+        ])
+    }
+}
+
+impl TransferFunction {
+    /// Create a transfer function from at least one point, in any order.
+    pub fn new(mut points: Vec<TransferFunctionPoint>) -> Self {
+        assert!(!points.is_empty(), "A transfer function needs at least one point");
+        points.sort_by(|a, b| a.value.partial_cmp(&b.value).unwrap_or(Ordering::Equal));
+        Self { points }
+    }
+
+    /// The points, ordered by value.
+    pub fn points(&self) -> &[TransferFunctionPoint] {
+        &self.points
+    }
+
+    /// The color and opacity of `value`, between 0 and 1; values outside of the points get the color of the
+    /// closest point.
+    pub fn sample(&self, value: f32) -> Vec4 {
+        let index = self.points.partition_point(|point| point.value <= value);
+        if index == 0 {
+            return self.points[0].color;
+        }
+        if index == self.points.len() {
+            return self.points[index - 1].color;
+        }
+        let (before, after) = (self.points[index - 1], self.points[index]);
+        let t = (value - before.value) / (after.value - before.value).max(f32::MIN_POSITIVE);
+        Vec4::mix(before.color, after.color, t)
+    }
+
+    /// Add a point with `opacity`, and the color that `value` currently has. Returns the index of the new point.
+    pub fn insert_point(&mut self, value: f32, opacity: f32) -> usize {
+        let color = self.sample(value);
+        let index = self.points.partition_point(|point| point.value <= value);
+        let color = vec4(color.x, color.y, color.z, opacity.clamp(0., 1.));
+        self.points.insert(index, TransferFunctionPoint { value: value.clamp(0., 1.), color });
+        index
+    }
+
+    /// Move the point at `index`, keeping its color. The value gets clamped between the neighboring points, so
+    /// points never change order.
+    pub fn move_point(&mut self, index: usize, value: f32, opacity: f32) {
+        let min = if index > 0 { self.points[index - 1].value } else { 0. };
+        let max = self.points.get(index + 1).map_or(1., |point| point.value);
+        let point = &mut self.points[index];
+        point.value = value.clamp(min, max);
+        point.color.w = opacity.clamp(0., 1.);
+    }
+
+    /// Remove the point at `index`, unless it's the last one. Returns whether it got removed.
+    pub fn remove_point(&mut self, index: usize) -> bool {
+        if self.points.len() <= 1 {
+            return false;
+        }
+        self.points.remove(index);
+        true
+    }
+
+    /// Write the transfer function into a row of `pixels`, for sampling in shaders.
+    fn write_pixels(&self, pixels: &mut [u32]) {
+        let len = pixels.len();
+        for (index, pixel) in pixels.iter_mut().enumerate() {
+            let color = self.sample(index as f32 / (len - 1) as f32);
+            let to_byte = |value: f32| (value.clamp(0., 1.) * 255.).round() as u8;
+            *pixel = u32::from_le_bytes([to_byte(color.x), to_byte(color.y), to_byte(color.z), to_byte(color.w)]);
+        }
+    }
+}
+
+/// Cuts away the part of a volume on the side of the plane that `normal` points away from, e.g. to look inside it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SlicePlane {
+    /// Any point on the plane, in world coordinates.
+    pub point: Vec3,
+    /// Points towards the part of the volume to keep.
+    pub normal: Vec3,
+}
+
+/// A box from 0 to 1 in every direction.
+fn build_unit_cube_geom() -> Geometry {
+    let vertices: Vec<Vec3> =
+        (0..8).map(|corner| vec3((corner & 1) as f32, (corner >> 1 & 1) as f32, (corner >> 2 & 1) as f32)).collect();
+    let indices = vec![
+        [0, 2, 1],
+        [1, 2, 3],
+        [4, 5, 6],
+        [5, 7, 6],
+        [0, 1, 4],
+        [1, 5, 4],
+        [2, 6, 3],
+        [3, 6, 7],
+        [0, 4, 2],
+        [2, 4, 6],
+        [1, 3, 5],
+        [3, 7, 5],
+    ];
+    Geometry::new(vertices, indices)
+}
+
+#[derive(Clone, Copy, Default)]
+#[repr(C)]
+struct VolumeIns {
+    bounds_min: Vec3,
+    bounds_size: Vec3,
+}
+
+#[repr(C)]
+struct VolumeUniforms {
+    camera_pos: Vec3,
+    volume_size: Vec3,
+    atlas_size: Vec2,
+    atlas_columns: f32,
+    step_size: f32,
+    opacity_exponent: f32,
+    solid_slices: f32,
+    slice_plane_0: Vec4,
+    slice_plane_1: Vec4,
+    slice_plane_2: Vec4,
+}
+
+static VOLUME_SHADER: Shader = Shader {
+    build_geom: Some(build_unit_cube_geom),
+    code_to_concatenate: &[
+        Cx::STD_SHADER,
+        code_fragment!(
+            r#"
+            // Everything is in volume coordinates, which go from 0 to 1 over the box of the volume.
+            uniform camera_pos: vec3;
+            uniform volume_size: vec3;
+            uniform atlas_size: vec2;
+            uniform atlas_columns: float;
+            uniform step_size: float;
+            uniform opacity_exponent: float;
+            uniform solid_slices: float;
+            uniform slice_plane_0: vec4;
+            uniform slice_plane_1: vec4;
+            uniform slice_plane_2: vec4;
+            texture volume: texture2D;
+            texture transfer_function: texture2D;
+
+            geometry geom: vec3;
+
+            instance bounds_min: vec3;
+            instance bounds_size: vec3;
+
+            varying volume_pos: vec3;
+
+            fn vertex() -> vec4 {
+                volume_pos = geom;
+                return camera_projection * camera_view * vec4(bounds_min + geom * bounds_size, 1.);
+            }
+
+            fn sample_slice(xy: vec2, slice: float) -> float {
+                let tile = vec2(mod(slice, atlas_columns), floor(slice / atlas_columns));
+                // Stay half a texel inside the slice, so filtering doesn't blend in the neighboring slice.
+                let texel = clamp(xy * volume_size.xy, vec2(0.5), volume_size.xy - 0.5);
+                return sample2d(volume, (tile * volume_size.xy + texel) / atlas_size).x;
+            }
+
+            // Filtering interpolates within slices, so only interpolate between slices here.
+            fn sample_volume(pos: vec3) -> float {
+                let z = clamp(pos.z * volume_size.z - 0.5, 0., volume_size.z - 1.);
+                let slice = floor(z);
+                let next_slice = min(slice + 1., volume_size.z - 1.);
+                return mix(sample_slice(pos.xy, slice), sample_slice(pos.xy, next_slice), z - slice);
+            }
+
+            // Narrows `range` (where the ray `origin + t * dir` enters and leaves the volume) down to the side of
+            // `plane` that gets kept. The third component becomes 1 when the ray now enters through the plane.
+            fn clip_range(range: vec3, origin: vec3, dir: vec3, plane: vec4) -> vec3 {
+                let distance = dot(plane.xyz, origin) + plane.w;
+                let speed = dot(plane.xyz, dir);
+                if abs(speed) < 0.0000001 {
+                    if distance < 0. {
+                        return vec3(1., 0., 0.);
+                    }
+                    return range;
+                }
+                let t = -distance / speed;
+                if speed > 0. {
+                    if t > range.x {
+                        return vec3(t, range.y, 1.);
+                    }
+                    return range;
+                }
+                return vec3(range.x, min(range.y, t), range.z);
+            }
+
+            fn pixel() -> vec4 {
+                // This fragment is at `t = 1`.
+                let dir = volume_pos - camera_pos;
+                let safe_dir = dir + vec3(0.0000001);
+                let t_low = -camera_pos / safe_dir;
+                let t_high = (vec3(1.) - camera_pos) / safe_dir;
+                let t_min = min(t_low, t_high);
+                let t_max = max(t_low, t_high);
+                let box_enter = max(max(t_min.x, t_min.y), t_min.z);
+                let box_exit = min(min(t_max.x, t_max.y), t_max.z);
+                // Both the front and the back of the box cover every pixel, so only march from the first side that
+                // the ray hits: the front, or the back when the camera is inside the box.
+                if box_enter > 0. && box_enter + box_exit < 2. {
+                    return vec4(0.);
+                }
+
+                let range = vec3(max(box_enter, 0.), box_exit, 0.);
+                range = clip_range(range, camera_pos, dir, slice_plane_0);
+                range = clip_range(range, camera_pos, dir, slice_plane_1);
+                range = clip_range(range, camera_pos, dir, slice_plane_2);
+
+                let step = step_size / length(dir);
+                let color = vec3(0.);
+                let alpha = 0.;
+                let t = range.x;
+                for i from 0 to 2048 {
+                    if t > range.y || alpha > 0.99 {
+                        break;
+                    }
+                    let sample = sample2d(transfer_function, vec2(sample_volume(camera_pos + dir * t), 0.5));
+                    // Correct the opacity for the distance between samples.
+                    let sample_alpha = 1. - pow(1. - min(sample.a, 0.9999), opacity_exponent);
+                    if float(i) < 0.5 && range.z * solid_slices > 0.5 {
+                        sample_alpha = 1.;
+                    }
+                    color += (1. - alpha) * sample_alpha * sample.rgb;
+                    alpha += (1. - alpha) * sample_alpha;
+                    t += step;
+                }
+                return vec4(color, alpha);
+            }"#
+        ),
+    ],
+    ..Shader::DEFAULT
+};
+
+/// Renders a [`ScalarVolume`] in a 3D view, e.g. a [`crate::Viewport3D`], by raymarching through it front to back.
+///
+/// Values are mapped to colors and opacities using a [`TransferFunction`], which users can edit using a
+/// [`TransferFunctionEditor`]:
+///
+/// ```ignore
+/// // When loading the volume:
+/// self.volume_renderer.set_volume(cx, &volume);
+/// self.transfer_function_editor.set_histogram(cx, &volume.histogram(128));
+///
+/// // In `handle`:
+/// if let TransferFunctionEditorEvent::Changed = self.transfer_function_editor.handle(cx, event) {
+///     let transfer_function = self.transfer_function_editor.transfer_function().clone();
+///     self.volume_renderer.set_transfer_function(cx, transfer_function);
+/// }
+///
+/// // In `draw`:
+/// self.viewport_3d.begin_draw(cx, Viewport3DProps::DEFAULT);
+/// self.volume_renderer.draw(cx, &self.viewport_3d.matrix_mode());
+/// self.viewport_3d.end_draw(cx);
+/// self.transfer_function_editor.draw(cx);
+/// ```
+///
+/// Volumes that don't fit in a texture of 4096 by 4096 pixels get scaled down until they do, e.g. 512 by 512 by 512
+/// voxels become 256 by 256 by 256. The box of the volume writes depth, so draw opaque things inside of it before
+/// drawing the volume; they aren't blended in at the right depth though.
+pub struct VolumeRenderer {
+    atlas: Texture,
+    transfer_function_texture: Texture,
+    transfer_function: TransferFunction,
+    transfer_function_dirty: bool,
+    /// The dimensions of the volume in the atlas, after scaling down.
+    volume_size: [usize; 3],
+    /// The dimensions of the volume before scaling down.
+    original_size: [usize; 3],
+    atlas_columns: usize,
+    value_range: (f32, f32),
+    bounds: Option<(Vec3, Vec3)>,
+    slice_planes: Vec<SlicePlane>,
+    solid_slices: bool,
+    samples_per_voxel: f32,
+}
+
+impl Default for VolumeRenderer {
+    fn default() -> Self {
+        Self {
+            atlas: Texture::default(),
+            transfer_function_texture: Texture::default(),
+            transfer_function: TransferFunction::default(),
+            transfer_function_dirty: true,
+            volume_size: [0; 3],
+            original_size: [0; 3],
+            atlas_columns: 1,
+            value_range: (0., 0.),
+            bounds: None,
+            slice_planes: vec![],
+            solid_slices: false,
+            samples_per_voxel: 2.,
+        }
+    }
+}
+
+impl VolumeRenderer {
+    /// The maximum number of [`SlicePlane`]s.
+    pub const MAX_SLICE_PLANES: usize = 3;
+
+    /// Where the volume is, in world coordinates. By default it's centered on the origin, with its longest side 10
+    /// long, and with cube-shaped voxels; set this to get other voxel sizes.
+    #[must_use]
+    pub fn with_bounds(self, min: Vec3, max: Vec3) -> Self {
+        Self { bounds: Some((min, max)), ..self }
+    }
+    /// How many samples to take per voxel along each ray; 2 by default. Fewer samples are faster, but can show
+    /// banding.
+    #[must_use]
+    pub fn with_samples_per_voxel(self, samples_per_voxel: f32) -> Self {
+        Self { samples_per_voxel, ..self }
+    }
+    /// Whether the surfaces cut by [`SlicePlane`]s are opaque, to show the values on them like a 2D slice of the
+    /// volume, instead of using the opacity of the [`TransferFunction`].
+    #[must_use]
+    pub fn with_solid_slices(self, solid_slices: bool) -> Self {
+        Self { solid_slices, ..self }
+    }
+    #[must_use]
+    pub fn with_transfer_function(self, transfer_function: TransferFunction) -> Self {
+        Self { transfer_function, transfer_function_dirty: true, ..self }
+    }
+
+    /// Upload `volume` to the GPU, replacing the previous one. Values get mapped from [`ScalarVolume::value_range`] to
+    /// 0 to 1, which is what the [`TransferFunction`] gets, and stored with 8 bits of precision.
+    pub fn set_volume(&mut self, cx: &mut Cx, volume: &ScalarVolume) {
+        self.original_size = volume.dimensions();
+        self.value_range = volume.value_range();
+        if self.original_size.contains(&0) {
+            self.volume_size = [0; 3];
+            return;
+        }
+        let mut scaled_down = None;
+        let (columns, atlas_width, atlas_height) = loop {
+            let dimensions = scaled_down.as_ref().unwrap_or(volume).dimensions();
+            if let Some(layout) = atlas_layout(dimensions) {
+                break layout;
+            }
+            scaled_down = Some(scaled_down.as_ref().unwrap_or(volume).downsampled());
+        };
+        let volume = scaled_down.as_ref().unwrap_or(volume);
+        self.volume_size = volume.dimensions();
+        self.atlas_columns = columns;
+
+        let handle = self.atlas.get_with_dimensions(cx, atlas_width, atlas_height);
+        handle.set_dimensions(cx, atlas_width, atlas_height);
+        let (min, max) = self.value_range;
+        let span = (max - min).max(f32::MIN_POSITIVE);
+        let [width, height, depth] = self.volume_size;
+        let image = handle.get_image_mut(cx);
+        for z in 0..depth {
+            let (tile_x, tile_y) = (z % columns * width, z / columns * height);
+            for y in 0..height {
+                for x in 0..width {
+                    let value = volume.get(x, y, z);
+                    let byte = if value.is_nan() { 0 } else { ((value - min) / span * 255.).round().clamp(0., 255.) as u8 };
+                    // The same value in every channel, so it doesn't matter in which order the platform stores them.
+                    image[(tile_y + y) * atlas_width + tile_x + x] = u32::from_le_bytes([byte; 4]);
+                }
+            }
+        }
+        cx.request_draw();
+    }
+
+    /// The values that 0 and 1 in the [`TransferFunction`] correspond to.
+    pub fn value_range(&self) -> (f32, f32) {
+        self.value_range
+    }
+
+    pub fn transfer_function(&self) -> &TransferFunction {
+        &self.transfer_function
+    }
+
+    pub fn set_transfer_function(&mut self, cx: &mut Cx, transfer_function: TransferFunction) {
+        self.transfer_function = transfer_function;
+        self.transfer_function_dirty = true;
+        cx.request_draw();
+    }
+
+    pub fn slice_planes(&self) -> &[SlicePlane] {
+        &self.slice_planes
+    }
+
+    /// Cut away parts of the volume, using at most [`VolumeRenderer::MAX_SLICE_PLANES`] planes.
+    pub fn set_slice_planes(&mut self, cx: &mut Cx, slice_planes: Vec<SlicePlane>) {
+        assert!(slice_planes.len() <= Self::MAX_SLICE_PLANES, "At most {} slice planes are supported", Self::MAX_SLICE_PLANES);
+        self.slice_planes = slice_planes;
+        cx.request_draw();
+    }
+
+    /// The box of the volume in world coordinates, as the minimum and maximum corner; see
+    /// [`VolumeRenderer::with_bounds`].
+    pub fn bounds(&self) -> (Vec3, Vec3) {
+        if let Some(bounds) = self.bounds {
+            return bounds;
+        }
+        let [width, height, depth] = self.original_size;
+        let longest = width.max(height).max(depth).max(1) as f32;
+        let half_size = vec3(width as f32, height as f32, depth as f32) / longest * 5.;
+        (-half_size, half_size)
+    }
+
+    /// `plane` in volume coordinates, which go from 0 to 1 over the box of the volume, as the normal and the offset
+    /// from the origin of the plane equation `dot(normal, pos) + offset = 0`.
+    fn volume_plane(&self, plane: &SlicePlane) -> Vec4 {
+        let (min, max) = self.bounds();
+        let normal = plane.normal * (max - min);
+        vec4(normal.x, normal.y, normal.z, plane.normal.dot(min - plane.point))
+    }
+
+    /// Draw the volume, as seen by the camera of `matrix_mode` (see [`crate::Viewport3D::matrix_mode`]), in a 3D
+    /// view, e.g. between [`crate::Viewport3D::begin_draw`] and [`crate::Viewport3D::end_draw`].
+    pub fn draw(&mut self, cx: &mut Cx, matrix_mode: &PassMatrixMode) {
+        if self.volume_size.contains(&0) {
+            return;
+        }
+        let handle = self.transfer_function_texture.get_with_dimensions(cx, TRANSFER_FUNCTION_SIZE, 1);
+        if self.transfer_function_dirty {
+            self.transfer_function_dirty = false;
+            self.transfer_function.write_pixels(handle.get_image_mut(cx));
+        }
+
+        let (min, max) = self.bounds();
+        let area = cx.add_instances(&VOLUME_SHADER, &[VolumeIns { bounds_min: min, bounds_size: max - min }]);
+        let view = match matrix_mode {
+            PassMatrixMode::Ortho => Mat4::identity(),
+            PassMatrixMode::Projection { cam, .. } => *cam,
+            PassMatrixMode::Custom { view, .. } => *view,
+        };
+        let camera_pos = view.invert().transform_vec4(vec4(0., 0., 0., 1.)).to_vec3();
+        let [width, height, depth] = self.volume_size;
+        let longest = width.max(height).max(depth) as f32;
+        let step_size = 1. / (longest * self.samples_per_voxel);
+        // No planes in the unused slots: the whole volume is on the kept side of `0 * pos + 1 = 0`.
+        let mut slice_planes = [vec4(0., 0., 0., 1.); Self::MAX_SLICE_PLANES];
+        for (slot, plane) in slice_planes.iter_mut().zip(&self.slice_planes) {
+            *slot = self.volume_plane(plane);
+        }
+        area.write_user_uniforms(
+            cx,
+            VolumeUniforms {
+                camera_pos: (camera_pos - min) / (max - min),
+                volume_size: vec3(width as f32, height as f32, depth as f32),
+                atlas_size: vec2(
+                    (self.atlas_columns * width) as f32,
+                    ((depth + self.atlas_columns - 1) / self.atlas_columns * height) as f32,
+                ),
+                atlas_columns: self.atlas_columns as f32,
+                step_size,
+                opacity_exponent: step_size * longest,
+                solid_slices: if self.solid_slices { 1. } else { 0. },
+                slice_plane_0: slice_planes[0],
+                slice_plane_1: slice_planes[1],
+                slice_plane_2: slice_planes[2],
+            },
+        );
+        let atlas_handle = self.atlas.unwrap_texture_handle();
+        area.write_texture_2d(cx, "volume", atlas_handle);
+        area.write_texture_2d(cx, "transfer_function", handle);
+    }
+}
+
+#[derive(Clone, Copy, Default)]
+#[repr(C)]
+struct EditorIns {
+    base: QuadIns,
+    /// The height of the color bar at the bottom.
+    color_bar_height: f32,
+}
+
+static EDITOR_SHADER: Shader = Shader {
+    build_geom: Some(QuadIns::build_geom),
+    code_to_concatenate: &[
+        Cx::STD_SHADER,
+        QuadIns::SHADER,
+        code_fragment!(
+            r#"
+            // The first row has the transfer function, and the second row the histogram.
+            texture editor_texture: texture2D;
+            instance color_bar_height: float;
+
+            fn pixel() -> vec4 {
+                let transfer = sample2d(editor_texture, vec2(pos.x, 0.25));
+                let pixel_pos = pos * rect_size;
+                if pixel_pos.y > rect_size.y - color_bar_height {
+                    return vec4(transfer.rgb, 1.);
+                }
+                let plot_height = rect_size.y - color_bar_height;
+                let height = 1. - pixel_pos.y / plot_height;
+                let histogram = sample2d(editor_texture, vec2(pos.x, 0.75)).x;
+                let background = mix(#202020, #383838, step(height, histogram));
+                // Fill the area under the opacity curve with the color, and draw the curve itself.
+                let fill = mix(background.rgb, transfer.rgb, step(height, transfer.a) * 0.6);
+                let line = clamp(1.5 - abs(height - transfer.a) * plot_height, 0., 1.);
+                return vec4(mix(fill, vec3(1.), line), 1.);
+            }"#
+        ),
+    ],
+    ..Shader::DEFAULT
+};
+
+#[derive(Clone, Copy, Default)]
+#[repr(C)]
+struct HandleIns {
+    base: QuadIns,
+    color: Vec4,
+}
+
+static HANDLE_SHADER: Shader = Shader {
+    build_geom: Some(QuadIns::build_geom),
+    code_to_concatenate: &[
+        Cx::STD_SHADER,
+        QuadIns::SHADER,
+        code_fragment!(
+            r#"
+            instance color: vec4;
+
+            fn pixel() -> vec4 {
+                let df = Df::viewport(pos * rect_size);
+                let center = rect_size * 0.5;
+                df.circle(center, center.x - 1.5);
+                df.fill(vec4(color.rgb, 1.));
+                df.stroke(#fff, 1.5);
+                return df.result;
+            }"#
+        ),
+    ],
+    ..Shader::DEFAULT
+};
+
+pub enum TransferFunctionEditorEvent {
+    None,
+    /// The user changed the transfer function; see [`TransferFunctionEditor::transfer_function`].
+    Changed,
+}
+
+/// Lets users edit a [`TransferFunction`]: the opacity is plotted over the value, on top of a histogram of the
+/// volume (see [`TransferFunctionEditor::set_histogram`]), with the colors in a bar below.
+///
+/// Drag points to change their value and opacity, click elsewhere to add a point, and right-click a point to remove
+/// it. New points get the color that their value already had; set colors using
+/// [`TransferFunctionEditor::set_transfer_function`].
+pub struct TransferFunctionEditor {
+    component_id: ComponentId,
+    transfer_function: TransferFunction,
+    /// Between 0 and 1, on a logarithmic scale.
+    histogram: Vec<f32>,
+    texture: Texture,
+    texture_dirty: bool,
+    area: Area,
+    rect: Rect,
+    height: f32,
+    dragging: Option<usize>,
+}
+
+impl Default for TransferFunctionEditor {
+    fn default() -> Self {
+        Self {
+            component_id: Default::default(),
+            transfer_function: TransferFunction::default(),
+            histogram: vec![],
+            texture: Texture::default(),
+            texture_dirty: true,
+            area: Area::Empty,
+            rect: Rect::default(),
+            height: 120.,
+            dragging: None,
+        }
+    }
+}
+
+impl TransferFunctionEditor {
+    const COLOR_BAR_HEIGHT: f32 = 12.;
+    const HANDLE_RADIUS: f32 = 5.;
+    /// How close (in pixels) the pointer has to be to a point to drag it.
+    const HANDLE_HIT_DISTANCE: f32 = 8.;
+
+    /// The height of the editor; 120 by default. It fills the available width.
+    #[must_use]
+    pub fn with_height(self, height: f32) -> Self {
+        Self { height, ..self }
+    }
+    #[must_use]
+    pub fn with_transfer_function(self, transfer_function: TransferFunction) -> Self {
+        Self { transfer_function, ..self }
+    }
+
+    pub fn transfer_function(&self) -> &TransferFunction {
+        &self.transfer_function
+    }
+
+    pub fn set_transfer_function(&mut self, cx: &mut Cx, transfer_function: TransferFunction) {
+        self.transfer_function = transfer_function;
+        self.dragging = None;
+        self.texture_dirty = true;
+        cx.request_draw();
+    }
+
+    /// Show the distribution of values behind the opacity curve, e.g. from [`ScalarVolume::histogram`]. Counts are
+    /// shown on a logarithmic scale, since a few values, like the air around a scan, often dominate.
+    pub fn set_histogram(&mut self, cx: &mut Cx, histogram: &[usize]) {
+        let max = (histogram.iter().copied().max().unwrap_or(0) as f32).ln_1p().max(f32::MIN_POSITIVE);
+        self.histogram = histogram.iter().map(|&count| (count as f32).ln_1p() / max).collect();
+        self.texture_dirty = true;
+        cx.request_draw();
+    }
+
+    fn plot_height(&self) -> f32 {
+        self.rect.size.y - Self::COLOR_BAR_HEIGHT
+    }
+
+    /// Where the point at `index` is drawn, in absolute coordinates.
+    fn handle_pos(&self, index: usize) -> Vec2 {
+        let point = self.transfer_function.points()[index];
+        self.rect.pos + vec2(point.value * self.rect.size.x, (1. - point.color.w) * self.plot_height())
+    }
+
+    /// The value and opacity at `abs`, clamped to the plot.
+    fn value_and_opacity(&self, abs: Vec2) -> (f32, f32) {
+        let rel = abs - self.rect.pos;
+        ((rel.x / self.rect.size.x).clamp(0., 1.), (1. - rel.y / self.plot_height()).clamp(0., 1.))
+    }
+
+    fn handle_at(&self, abs: Vec2) -> Option<usize> {
+        (0..self.transfer_function.points().len())
+            .map(|index| (index, (self.handle_pos(index) - abs).length()))
+            .filter(|(_, distance)| *distance <= Self::HANDLE_HIT_DISTANCE)
+            .min_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap_or(Ordering::Equal))
+            .map(|(index, _)| index)
+    }
+
+    fn changed(&mut self, cx: &mut Cx) -> TransferFunctionEditorEvent {
+        self.texture_dirty = true;
+        cx.request_draw();
+        TransferFunctionEditorEvent::Changed
+    }
+
+    pub fn handle(&mut self, cx: &mut Cx, event: &mut Event) -> TransferFunctionEditorEvent {
+        match event.hits_pointer(cx, self.component_id, self.area.get_rect_for_first_instance(cx)) {
+            Event::PointerHover(pe) => {
+                let cursor = if self.handle_at(pe.abs).is_some() { MouseCursor::Hand } else { MouseCursor::Crosshair };
+                cx.set_hover_mouse_cursor(cursor);
+            }
+            Event::PointerDown(pe) => {
+                let handle = self.handle_at(pe.abs);
+                if pe.button == MouseButton::Right {
+                    if let Some(index) = handle {
+                        if self.transfer_function.remove_point(index) {
+                            return self.changed(cx);
+                        }
+                    }
+                } else if pe.button == MouseButton::Left {
+                    cx.set_down_mouse_cursor(MouseCursor::Hand);
+                    if handle.is_some() {
+                        self.dragging = handle;
+                    } else if pe.abs.y - self.rect.pos.y < self.plot_height() {
+                        let (value, opacity) = self.value_and_opacity(pe.abs);
+                        self.dragging = Some(self.transfer_function.insert_point(value, opacity));
+                        return self.changed(cx);
+                    }
+                }
+            }
+            Event::PointerMove(pe) => {
+                if let Some(index) = self.dragging {
+                    let (value, opacity) = self.value_and_opacity(pe.abs);
+                    self.transfer_function.move_point(index, value, opacity);
+                    return self.changed(cx);
+                }
+            }
+            Event::PointerUp(_) => {
+                self.dragging = None;
+            }
+            _ => {}
+        }
+        TransferFunctionEditorEvent::None
+    }
+
+    fn update_texture(&mut self, cx: &mut Cx) {
+        let handle = self.texture.get_with_dimensions(cx, TRANSFER_FUNCTION_SIZE, 2);
+        if !self.texture_dirty {
+            return;
+        }
+        self.texture_dirty = false;
+        let image = handle.get_image_mut(cx);
+        let (transfer_row, histogram_row) = image.split_at_mut(TRANSFER_FUNCTION_SIZE);
+        self.transfer_function.write_pixels(transfer_row);
+        for (index, pixel) in histogram_row.iter_mut().enumerate() {
+            let bin = index * self.histogram.len() / TRANSFER_FUNCTION_SIZE;
+            let byte = (self.histogram.get(bin).copied().unwrap_or(0.) * 255.).round() as u8;
+            *pixel = u32::from_le_bytes([byte; 4]);
+        }
+    }
+
+    pub fn draw(&mut self, cx: &mut Cx) {
+        self.update_texture(cx);
+        self.rect = cx.add_box(LayoutSize::new(Width::Fill, Height::Fix(self.height)));
+        self.area = cx.add_instances(
+            &EDITOR_SHADER,
+            &[EditorIns { base: QuadIns::from_rect(self.rect), color_bar_height: Self::COLOR_BAR_HEIGHT }],
+        );
+        let handle = self.texture.unwrap_texture_handle();
+        self.area.write_texture_2d(cx, "editor_texture", handle);
+
+        let handles: Vec<HandleIns> = (0..self.transfer_function.points().len())
+            .map(|index| HandleIns {
+                base: QuadIns::from_rect(Rect {
+                    pos: self.handle_pos(index) - Vec2::all(Self::HANDLE_RADIUS),
+                    size: Vec2::all(Self::HANDLE_RADIUS * 2.),
+                })
+                .with_draw_depth(1.),
+                color: self.transfer_function.points()[index].color,
+            })
+            .collect();
+        cx.add_instances(&HANDLE_SHADER, &handles);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scalar_volume() {
+        let volume = ScalarVolume::new(3, 2, 2, (0..12).map(|value| value as f32).collect());
+        assert_eq!(volume.get(2, 1, 1), 11.);
+        assert_eq!(volume.value_range(), (0., 11.));
+        assert_eq!(volume.histogram(4), vec![3, 3, 3, 3]);
+
+        let downsampled = volume.downsampled();
+        assert_eq!(downsampled.dimensions(), [2, 1, 1]);
+        // The average of 0, 1, 3, 4, 6, 7, 9, and 10; and of 2, 5, 8, and 11, since the volume has an odd width.
+        assert_eq!(downsampled.values(), &[5., 6.5]);
+
+        assert_eq!(atlas_layout([256, 256, 256]), Some((16, 4096, 4096)));
+        assert_eq!(atlas_layout([64, 32, 10]), Some((10, 640, 32)));
+        assert_eq!(atlas_layout([512, 512, 512]), None);
+    }
+
+    #[test]
+    fn test_transfer_function() {
+        let mut transfer_function = TransferFunction::new(vec![
+            TransferFunctionPoint { value: 1., color: vec4(1., 1., 1., 1.) },
+            TransferFunctionPoint { value: 0.5, color: vec4(1., 0., 0., 0.) },
+        ]);
+        assert_eq!(transfer_function.points()[0].value, 0.5);
+        assert_eq!(transfer_function.sample(0.), vec4(1., 0., 0., 0.));
+        assert_eq!(transfer_function.sample(0.75), vec4(1., 0.5, 0.5, 0.5));
+        assert_eq!(transfer_function.sample(2.), vec4(1., 1., 1., 1.));
+
+        // New points get the color at their value, and can't be dragged past their neighbors.
+        let index = transfer_function.insert_point(0.75, 0.2);
+        assert_eq!(index, 1);
+        assert_eq!(transfer_function.points()[1].color, vec4(1., 0.5, 0.5, 0.2));
+        transfer_function.move_point(1, 0.1, 1.5);
+        assert_eq!(transfer_function.points()[1].value, 0.5);
+        assert_eq!(transfer_function.points()[1].color.w, 1.);
+
+        assert!(transfer_function.remove_point(0));
+        assert!(transfer_function.remove_point(0));
+        assert!(!transfer_function.remove_point(0));
+    }
+
+    #[test]
+    fn test_volume_plane() {
+        let renderer = VolumeRenderer::default().with_bounds(vec3(0., 0., 0.), vec3(10., 20., 30.));
+        // Keep everything above y = 5, which is a quarter of the way up the volume.
+        let plane = renderer.volume_plane(&SlicePlane { point: vec3(3., 5., 7.), normal: vec3(0., 1., 0.) });
+        let distance = |pos: Vec3| plane.to_vec3().dot(pos) + plane.w;
+        assert_eq!(distance(vec3(0.5, 0.25, 0.5)), 0.);
+        assert!(distance(vec3(0., 1., 0.)) > 0.);
+        assert!(distance(vec3(0., 0., 0.)) < 0.);
+
+        let renderer = VolumeRenderer { original_size: [100, 50, 25], ..VolumeRenderer::default() };
+        assert_eq!(renderer.bounds(), (vec3(-5., -2.5, -1.25), vec3(5., 2.5, 1.25)));
+    }
+}
@@ -0,0 +1,361 @@
+//! A container that zooms and pans its contents.
+
+use zaplib::*;
+
+/// How a [`ZoomPanView`] maps content coordinates to the screen.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ZoomPanTransform {
+    /// The top-left corner of the view on the screen.
+    pub origin: Vec2,
+    /// The content coordinates that are shown at [`ZoomPanTransform::origin`].
+    pub pan: Vec2,
+    /// How many screen pixels one unit of content takes up.
+    pub zoom: f32,
+}
+
+impl ZoomPanTransform {
+    pub fn to_screen(&self, content_pos: Vec2) -> Vec2 {
+        self.origin + (content_pos - self.pan) * self.zoom
+    }
+
+    pub fn to_content(&self, screen_pos: Vec2) -> Vec2 {
+        self.pan + (screen_pos - self.origin) / self.zoom
+    }
+
+    pub fn rect_to_screen(&self, content_rect: Rect) -> Rect {
+        Rect { pos: self.to_screen(content_rect.pos), size: content_rect.size * self.zoom }
+    }
+}
+
+/// Draws its contents zoomed and panned, like a map or a drawing canvas. The user can zoom using a mouse wheel,
+/// pinch, or Ctrl (Cmd) and scroll, pan by dragging or scrolling on a trackpad, and double-click to reset the view,
+/// depending on [`Cx::interaction_policy`].
+///
+/// The contents are drawn in content coordinates between [`ZoomPanView::begin_view`] and
+/// [`ZoomPanView::end_view`], into an offscreen [`Pass`] that has a higher DPI factor when zoomed in, so text and
+/// shapes stay sharp. Pass events to the contents through [`ZoomPanView::handle_content`], which converts pointer
+/// positions so that [`Event::hits_pointer`] and [`Area::get_rect_for_first_instance`] line up again. Since panning
+/// works by scrolling the contents, those are relative to [`ZoomPanTransform::pan`], just like in a
+/// [`crate::ScrollView`]; use [`ZoomPanView::transform`] to get actual content coordinates, or to draw things like
+/// axes outside of the view.
+///
+/// ```ignore
+/// // In `handle`:
+/// self.zoom_pan_view.handle_content(cx, event, |cx, event| self.canvas.handle(cx, event));
+/// self.zoom_pan_view.handle(cx, event);
+///
+/// // In `draw`:
+/// self.zoom_pan_view.begin_view(cx, LayoutSize::FILL);
+/// self.canvas.draw(cx);
+/// self.zoom_pan_view.end_view(cx);
+/// ```
+pub struct ZoomPanView {
+    component_id: ComponentId,
+    /// The texture that the contents get rendered into, drawn in the parent view.
+    area: Area,
+    pass: Pass,
+    color_texture: Texture,
+    content_view: View,
+    origin: Vec2,
+    size: Vec2,
+    pan: Vec2,
+    zoom: f32,
+    min_zoom: f32,
+    max_zoom: f32,
+    /// The center of the view (in content coordinates) and the natural logarithm of the zoom, so that zooming
+    /// animates at an even pace.
+    animation: Spring<Vec3>,
+    /// Set when [`ZoomPanView::zoom_to_fit`] is called before the first draw, when the size is still unknown.
+    pending_fit: Option<Rect>,
+    /// The last rect passed to [`ZoomPanView::zoom_to_fit`], to go back to in [`ZoomPanView::reset_view`].
+    home_rect: Option<Rect>,
+    drag_start_pan: Option<Vec2>,
+    background_color: Vec4,
+}
+
+impl Default for ZoomPanView {
+    fn default() -> Self {
+        Self {
+            component_id: Default::default(),
+            area: Default::default(),
+            pass: Default::default(),
+            color_texture: Default::default(),
+            content_view: Default::default(),
+            origin: Vec2::default(),
+            size: Vec2::default(),
+            pan: Vec2::default(),
+            zoom: 1.,
+            min_zoom: 0.05,
+            max_zoom: 20.,
+            animation: Spring::new(Vec3::default()),
+            pending_fit: None,
+            home_rect: None,
+            drag_start_pan: None,
+            background_color: vec4(0., 0., 0., 0.),
+        }
+    }
+}
+
+/// The pointer positions in `event`, if it's a pointer event.
+fn pointer_positions(event: &mut Event) -> Vec<&mut Vec2> {
+    match event {
+        Event::PointerDown(pe) => vec![&mut pe.abs],
+        Event::PointerMove(pe) => vec![&mut pe.abs, &mut pe.abs_start],
+        Event::PointerUp(pe) => vec![&mut pe.abs, &mut pe.abs_start],
+        Event::PointerHover(pe) => vec![&mut pe.abs],
+        Event::PointerScroll(pe) => vec![&mut pe.abs],
+        _ => vec![],
+    }
+}
+
+impl ZoomPanView {
+    /// Extra space around the content in [`ZoomPanView::zoom_to_fit`], in screen pixels.
+    const FIT_PADDING: f32 = 20.;
+
+    #[must_use]
+    pub fn with_zoom_range(self, min_zoom: f32, max_zoom: f32) -> Self {
+        Self { min_zoom, max_zoom, zoom: self.zoom.clamp(min_zoom, max_zoom), ..self }
+    }
+    /// The color that the contents are drawn on; transparent by default.
+    #[must_use]
+    pub fn with_background_color(self, background_color: Vec4) -> Self {
+        Self { background_color, ..self }
+    }
+
+    /// The current mapping from content coordinates to the screen.
+    pub fn transform(&self) -> ZoomPanTransform {
+        ZoomPanTransform { origin: self.origin, pan: self.pan, zoom: self.zoom }
+    }
+
+    /// The part of the content that is in view, in content coordinates.
+    pub fn visible_rect(&self) -> Rect {
+        Rect { pos: self.pan, size: self.size / self.zoom }
+    }
+
+    /// Immediately show the content coordinates `pan` at the top-left, at `zoom`.
+    pub fn set_transform(&mut self, cx: &mut Cx, pan: Vec2, zoom: f32) {
+        self.pan = pan;
+        self.zoom = zoom.clamp(self.min_zoom, self.max_zoom);
+        self.stop_animation();
+        cx.request_draw();
+    }
+
+    /// The pan and zoom that show all of `content_rect`, centered, with `padding` screen pixels around it.
+    fn fit_rect(&self, content_rect: Rect, padding: f32) -> (Vec2, f32) {
+        let available = (self.size - Vec2::all(padding * 2.)).max(&Vec2::all(1.));
+        let zoom = (available.x / content_rect.size.x.max(f32::EPSILON))
+            .min(available.y / content_rect.size.y.max(f32::EPSILON))
+            .clamp(self.min_zoom, self.max_zoom);
+        let center = content_rect.pos + content_rect.size / 2.;
+        (center - self.size / (2. * zoom), zoom)
+    }
+
+    fn animation_value(&self) -> Vec3 {
+        let center = self.pan + self.size / (2. * self.zoom);
+        vec3(center.x, center.y, self.zoom.ln())
+    }
+
+    fn stop_animation(&mut self) {
+        self.animation.set_immediate(self.animation_value());
+    }
+
+    /// Immediately zoom and pan so that all of `content_rect` is in view, e.g. after loading a document. Can be
+    /// called before the first draw.
+    pub fn zoom_to_fit(&mut self, cx: &mut Cx, content_rect: Rect) {
+        self.home_rect = Some(content_rect);
+        if self.size.x <= 0. || self.size.y <= 0. {
+            self.pending_fit = Some(content_rect);
+        } else {
+            let (pan, zoom) = self.fit_rect(content_rect, Self::FIT_PADDING);
+            self.set_transform(cx, pan, zoom);
+        }
+        cx.request_draw();
+    }
+
+    /// Smoothly zoom and pan so that `content_rect` fills the view, e.g. to focus on a selected item.
+    pub fn zoom_to_rect(&mut self, cx: &mut Cx, content_rect: Rect) {
+        if self.size.x <= 0. || self.size.y <= 0. {
+            self.zoom_to_fit(cx, content_rect);
+            return;
+        }
+        let (pan, zoom) = self.fit_rect(content_rect, 0.);
+        self.animate_to(cx, pan, zoom);
+    }
+
+    /// Smoothly go back to the view of the last [`ZoomPanView::zoom_to_fit`], or to no zoom and pan if that was never
+    /// called. Double-clicking does this, unless turned off in [`Cx::interaction_policy`].
+    pub fn reset_view(&mut self, cx: &mut Cx) {
+        match self.home_rect {
+            Some(home_rect) if self.size.x <= 0. || self.size.y <= 0. => self.zoom_to_fit(cx, home_rect),
+            Some(home_rect) => {
+                let (pan, zoom) = self.fit_rect(home_rect, Self::FIT_PADDING);
+                self.animate_to(cx, pan, zoom);
+            }
+            None => self.animate_to(cx, Vec2::default(), 1f32.clamp(self.min_zoom, self.max_zoom)),
+        }
+    }
+
+    fn animate_to(&mut self, cx: &mut Cx, pan: Vec2, zoom: f32) {
+        let center = pan + self.size / (2. * zoom);
+        self.stop_animation();
+        self.animation.set_target(cx, vec3(center.x, center.y, zoom.ln()));
+    }
+
+    /// Zoom by `factor`, keeping the content under `screen_pos` in place.
+    fn zoom_around(&mut self, screen_pos: Vec2, factor: f32) {
+        let content_pos = self.transform().to_content(screen_pos);
+        self.zoom = (self.zoom * factor).clamp(self.min_zoom, self.max_zoom);
+        self.pan = content_pos - (screen_pos - self.origin) / self.zoom;
+    }
+
+    /// Call `handle` with `event`, with pointer positions converted to match the areas drawn between
+    /// [`ZoomPanView::begin_view`] and [`ZoomPanView::end_view`]. Call this before [`ZoomPanView::handle`], so the
+    /// contents get to handle pointer events first.
+    pub fn handle_content<F, R>(&mut self, cx: &mut Cx, event: &mut Event, handle: F) -> R
+    where
+        F: FnOnce(&mut Cx, &mut Event) -> R,
+    {
+        if let Some(rect) = self.area.get_rect_for_first_instance(cx) {
+            self.origin = rect.pos;
+        }
+        let (origin, zoom) = (self.origin, self.zoom);
+        let saved: Vec<Vec2> = pointer_positions(event)
+            .into_iter()
+            .map(|pos| {
+                let screen_pos = *pos;
+                *pos = (screen_pos - origin) / zoom;
+                screen_pos
+            })
+            .collect();
+        let result = handle(cx, event);
+        for (pos, screen_pos) in pointer_positions(event).into_iter().zip(saved) {
+            *pos = screen_pos;
+        }
+        result
+    }
+
+    /// Handle zooming and panning. Returns true if the transform changed.
+    pub fn handle(&mut self, cx: &mut Cx, event: &mut Event) -> bool {
+        if self.animation.handle(cx, event) {
+            let value = self.animation.get();
+            self.zoom = value.z.exp().clamp(self.min_zoom, self.max_zoom);
+            self.pan = vec2(value.x, value.y) - self.size / (2. * self.zoom);
+            cx.request_draw();
+            return true;
+        }
+
+        match event.hits_pointer(cx, self.component_id, self.area.get_rect_for_first_instance(cx)) {
+            Event::PointerHover(_) => {
+                cx.set_hover_mouse_cursor(MouseCursor::Grab);
+            }
+            Event::PointerDown(pe) if cx.interaction_policy().is_reset(&pe) => {
+                self.reset_view(cx);
+            }
+            Event::PointerDown(_) => {
+                cx.set_down_mouse_cursor(MouseCursor::Grabbing);
+                self.drag_start_pan = Some(self.pan);
+            }
+            Event::PointerMove(pe) => {
+                if let Some(drag_start_pan) = self.drag_start_pan {
+                    self.pan = drag_start_pan - (pe.abs - pe.abs_start) / self.zoom;
+                    self.stop_animation();
+                    cx.request_draw();
+                    return true;
+                }
+            }
+            Event::PointerUp(_) => {
+                self.drag_start_pan = None;
+            }
+            Event::PointerScroll(pe) => {
+                // The zoom is the same in both directions, so zooming a single axis zooms both.
+                match cx.interaction_policy().scroll_action(&pe) {
+                    ScrollAction::Zoom { factor, .. } => self.zoom_around(pe.abs, factor),
+                    ScrollAction::Pan(delta) => self.pan += delta / self.zoom,
+                }
+                self.stop_animation();
+                cx.request_draw();
+                return true;
+            }
+            _ => {}
+        }
+        false
+    }
+
+    /// Start drawing the contents, in content coordinates.
+    pub fn begin_view(&mut self, cx: &mut Cx, layout_size: LayoutSize) {
+        let rect = cx.add_box(layout_size);
+        self.origin = rect.pos;
+        self.size = rect.size;
+        if let Some(content_rect) = self.pending_fit.take() {
+            let (pan, zoom) = self.fit_rect(content_rect, Self::FIT_PADDING);
+            self.pan = pan;
+            self.zoom = zoom;
+            self.stop_animation();
+        }
+
+        // Show what was rendered into the texture.
+        let color_texture_handle = self.color_texture.get_color(cx);
+        self.area = ImageIns::draw(cx, rect, color_texture_handle);
+
+        // The pass covers the visible part of the content, and zooming raises its DPI factor, so the texture always
+        // has as many pixels as the view.
+        self.pass.begin_pass_without_textures(cx);
+        let dpi_factor = cx.current_dpi_factor * self.zoom;
+        self.pass.override_dpi_factor(cx, dpi_factor);
+        self.pass.set_size(cx, rect.size / self.zoom);
+        self.pass.add_color_texture(cx, color_texture_handle, ClearColor::ClearWith(self.background_color));
+        self.content_view.begin_view(cx, LayoutSize::FILL);
+        let view_id = self.content_view.view_id.unwrap();
+        cx.set_view_scroll_x(view_id, self.pan.x);
+        cx.set_view_scroll_y(view_id, self.pan.y);
+    }
+
+    pub fn end_view(&mut self, cx: &mut Cx) {
+        self.content_view.end_view(cx);
+        self.pass.end_pass(cx);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_zoom_pan_view_fit_and_transform() {
+        let mut view = ZoomPanView { size: vec2(200., 100.), origin: vec2(10., 10.), ..ZoomPanView::default() };
+
+        // The content is square, so the height determines the zoom.
+        let (pan, zoom) = view.fit_rect(Rect { pos: vec2(0., 0.), size: vec2(1000., 1000.) }, 0.);
+        assert_eq!(zoom, 0.1);
+        assert_eq!(pan, vec2(-500., 0.));
+
+        view.pan = pan;
+        view.zoom = zoom;
+        let transform = view.transform();
+        assert_eq!(transform.to_screen(vec2(500., 500.)), vec2(110., 60.));
+        assert_eq!(transform.to_content(vec2(110., 60.)), vec2(500., 500.));
+
+        // Zooming in keeps the point under the pointer in place.
+        view.zoom_around(vec2(110., 60.), 2.);
+        assert_eq!(view.zoom, 0.2);
+        assert_eq!(view.transform().to_content(vec2(110., 60.)), vec2(500., 500.));
+    }
+
+    #[test]
+    fn test_zoom_pan_view_handle_content() {
+        let mut view = ZoomPanView { origin: vec2(100., 100.), zoom: 2., ..ZoomPanView::default() };
+        let mut test_cx = TestCx::new(vec2(400., 400.));
+        let pointer_abs = |event: &Event| match event {
+            Event::PointerScroll(pe) => pe.abs,
+            _ => Vec2::default(),
+        };
+
+        let event = Event::PointerScroll(PointerScrollEvent { abs: vec2(150., 120.), ..Default::default() });
+        let results = test_cx.dispatch(event, |cx, event| {
+            let content_abs = view.handle_content(cx, event, |_, event| pointer_abs(event));
+            // The event is restored afterwards.
+            (content_abs, pointer_abs(event))
+        });
+        assert_eq!(results, vec![(vec2(25., 10.), vec2(150., 120.))]);
+    }
+}
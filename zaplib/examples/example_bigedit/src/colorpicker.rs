@@ -248,7 +248,7 @@ impl ColorPicker {
         // i wanna draw a wheel with 'width' set but height a fixed height.
         self.size = cx.get_box_rect().size.x;
 
-        let rect = cx.add_box(LayoutSize { width: Width::Fill, height: Height::Fix(self.size * height_scale) });
+        let rect = cx.add_box(LayoutSize::new(Width::Fill, Height::Fix(self.size * height_scale)));
 
         self.area = cx.add_instances(
             &SHADER,
@@ -76,7 +76,7 @@ pub enum FileTreeEvent {
     SelectFolder { path: String },
 }
 
-const FILLER_WALK: LayoutSize = LayoutSize { width: Width::Fix(10.0), height: Height::Fill };
+const FILLER_WALK: LayoutSize = LayoutSize::new(Width::Fix(10.0), Height::Fill);
 const FILLER_PADDING: Padding = Padding { l: 1., t: 0., r: 4., b: 0. };
 
 const NODE_PADDING: Padding = Padding { l: 5., t: 0., r: 0., b: 1. };
@@ -32,21 +32,25 @@ const ANIM_UNMARKED: Anim = Anim {
     duration: 0.01,
     chain: true,
     tracks: &[Track::Vec4 { key_frames: &[(1.0, vec4(0.12, 0.12, 0.12, 1.0))], ease: Ease::DEFAULT }],
+    ..Anim::DEFAULT
 };
 const ANIM_MARKED: Anim = Anim {
     duration: 0.01,
     chain: true,
     tracks: &[Track::Vec4 { key_frames: &[(1.0, vec4(0.07, 0.27, 0.43, 1.0))], ease: Ease::DEFAULT }],
+    ..Anim::DEFAULT
 };
 const ANIM_UNMARKED_OVER: Anim = Anim {
     duration: 0.02,
     chain: true,
     tracks: &[Track::Vec4 { key_frames: &[(0.0, vec4(0.2, 0.2, 0.2, 1.0))], ease: Ease::DEFAULT }],
+    ..Anim::DEFAULT
 };
 const ANIM_MARKED_OVER: Anim = Anim {
     duration: 0.02,
     chain: true,
     tracks: &[Track::Vec4 { key_frames: &[(0.0, vec4(0.07, 0.27, 0.43, 1.0))], ease: Ease::DEFAULT }],
+    ..Anim::DEFAULT
 };
 
 impl SearchResults {
@@ -62,6 +62,7 @@ const ANIM_DEFAULT: Anim = Anim {
         // BgIns::down
         Track::Float { key_frames: &[(1.0, 0.0)], ease: Ease::DEFAULT },
     ],
+    ..Anim::DEFAULT
 };
 
 const ANIM_HOVER: Anim = Anim {
@@ -73,6 +74,7 @@ const ANIM_HOVER: Anim = Anim {
         // BgIns::down
         Track::Float { key_frames: &[(1.0, 0.0)], ease: Ease::DEFAULT },
     ],
+    ..Anim::DEFAULT
 };
 
 const ANIM_DOWN: Anim = Anim {
@@ -0,0 +1,185 @@
+//! Derive macros for Zaplib. Use them through the `zaplib` crate, which re-exports them.
+
+extern crate proc_macro;
+
+use proc_macro2::{Span, TokenStream};
+use quote::quote;
+use syn::spanned::Spanned;
+use syn::{parse_macro_input, Data, DeriveInput, Fields, Lit, Meta, NestedMeta};
+
+/// Implements `zaplib::ZapParamStruct`, `zaplib::ZerdeField`, and `zaplib::IntoParam` for a struct with named
+/// fields, encoding it as a `zaplib::ZerdeRecord`. See `zaplib::ZapParamStruct` for details.
+///
+/// Attributes:
+/// * `#[zap_param(version = 2, min_version = 1)]` on the struct: the version to encode, and the oldest version
+///   that we can still decode (defaults to `version`, which itself defaults to 1).
+/// * `#[zap_param(default)]` on a field: use `Default::default()` when the field is missing, e.g. because it was
+///   added in a newer version.
+/// * `#[zap_param(rename = "otherName")]` on a field: use a different name in the encoding and in TypeScript.
+#[proc_macro_derive(ZapParam, attributes(zap_param))]
+pub fn derive_zap_param(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    derive_zap_param_impl(input).unwrap_or_else(|error| error.to_compile_error()).into()
+}
+
+/// Key-value pairs and flags from `#[zap_param(...)]` attributes.
+fn parse_attrs(attrs: &[syn::Attribute]) -> syn::Result<Vec<(String, Option<Lit>, Span)>> {
+    let mut result = Vec::new();
+    for attr in attrs.iter().filter(|attr| attr.path.is_ident("zap_param")) {
+        let list = match attr.parse_meta()? {
+            Meta::List(list) => list,
+            meta => return Err(syn::Error::new_spanned(meta, "expected #[zap_param(...)]")),
+        };
+        for nested in list.nested {
+            match nested {
+                NestedMeta::Meta(Meta::Path(path)) if path.get_ident().is_some() => {
+                    result.push((path.get_ident().unwrap().to_string(), None, path.span()));
+                }
+                NestedMeta::Meta(Meta::NameValue(name_value)) if name_value.path.get_ident().is_some() => {
+                    let name = name_value.path.get_ident().unwrap().to_string();
+                    result.push((name, Some(name_value.lit.clone()), name_value.span()));
+                }
+                nested => return Err(syn::Error::new_spanned(nested, "unexpected zap_param attribute")),
+            }
+        }
+    }
+    Ok(result)
+}
+
+fn lit_u32(lit: &Option<Lit>, name: &str, span: Span) -> syn::Result<u32> {
+    match lit {
+        Some(Lit::Int(int)) => int.base10_parse(),
+        _ => Err(syn::Error::new(span, format!("expected `{} = <number>`", name))),
+    }
+}
+
+fn derive_zap_param_impl(input: DeriveInput) -> syn::Result<TokenStream> {
+    let ident = &input.ident;
+    if !input.generics.params.is_empty() {
+        return Err(syn::Error::new_spanned(&input.generics, "ZapParam can't be derived for generic structs"));
+    }
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => return Err(syn::Error::new_spanned(ident, "ZapParam can only be derived for structs with named fields")),
+        },
+        _ => return Err(syn::Error::new_spanned(ident, "ZapParam can only be derived for structs")),
+    };
+
+    let mut version = None;
+    let mut min_version = None;
+    for (name, lit, span) in parse_attrs(&input.attrs)? {
+        match name.as_str() {
+            "version" => version = Some(lit_u32(&lit, "version", span)?),
+            "min_version" => min_version = Some(lit_u32(&lit, "min_version", span)?),
+            _ => return Err(syn::Error::new(span, format!("unknown zap_param attribute `{}` on struct", name))),
+        }
+    }
+    let version = version.unwrap_or(1);
+    let min_version = min_version.unwrap_or(version);
+
+    let mut to_fields = Vec::new();
+    let mut from_fields = Vec::new();
+    let mut ts_type_fields = Vec::new();
+    let mut ts_schema_fields = Vec::new();
+    let mut field_types = Vec::new();
+    for field in fields {
+        let field_ident = field.ident.as_ref().unwrap();
+        let ty = &field.ty;
+        let mut name = field_ident.to_string();
+        let mut default = false;
+        for (attr_name, lit, span) in parse_attrs(&field.attrs)? {
+            match (attr_name.as_str(), lit) {
+                ("default", None) => default = true,
+                ("rename", Some(Lit::Str(str))) => name = str.value(),
+                _ => return Err(syn::Error::new(span, format!("unknown zap_param attribute `{}` on field", attr_name))),
+            }
+        }
+
+        to_fields.push(quote! {
+            record.insert(#name, ::zaplib::ZerdeField::to_zerde_value(&self.#field_ident));
+        });
+        from_fields.push(if default {
+            quote! { #field_ident: record.get_or_else(#name, ::std::default::Default::default)? }
+        } else {
+            quote! { #field_ident: record.get(#name)? }
+        });
+        ts_type_fields.push(quote! {
+            declaration += &format!(
+                "  {:?}{}: {};\n",
+                #name,
+                if #default || <#ty as ::zaplib::ZerdeField>::OPTIONAL { "?" } else { "" },
+                <#ty as ::zaplib::ZerdeField>::typescript_type(),
+            );
+        });
+        field_types.push(ty);
+        ts_schema_fields.push(quote! {
+            declaration += &format!("    {:?}: {},\n", #name, <#ty as ::zaplib::ZerdeField>::typescript_schema());
+        });
+    }
+
+    let name = ident.to_string();
+    Ok(quote! {
+        impl ::zaplib::ZapParamStruct for #ident {
+            const VERSION: u32 = #version;
+            const MIN_VERSION: u32 = #min_version;
+
+            fn to_zerde_record(&self) -> ::zaplib::ZerdeRecord {
+                let mut record = ::zaplib::ZerdeRecord::new(#version);
+                #(#to_fields)*
+                record
+            }
+
+            fn from_zerde_record(record: &::zaplib::ZerdeRecord) -> ::std::result::Result<Self, ::zaplib::ZerdeError> {
+                record.check_version(#min_version, #version)?;
+                Ok(Self { #(#from_fields,)* })
+            }
+
+            fn typescript_declaration() -> String {
+                let mut declaration = format!("export type {} = {{\n", #name);
+                #(#ts_type_fields)*
+                declaration += &format!(
+                    "}};\nexport const {}Schema: ZapParamSchema = {{\n  version: {},\n  minVersion: {},\n  fields: {{\n",
+                    #name, #version, #min_version,
+                );
+                #(#ts_schema_fields)*
+                declaration += "  },\n};\n";
+                declaration
+            }
+        }
+
+        impl ::zaplib::ZerdeField for #ident {
+            const TYPE_NAME: &'static str = "record";
+
+            fn to_zerde_value(&self) -> ::zaplib::ZerdeValue {
+                ::zaplib::ZerdeValue::Record(::zaplib::ZapParamStruct::to_zerde_record(self))
+            }
+
+            fn from_zerde_value(value: &::zaplib::ZerdeValue, field: &str) -> ::std::result::Result<Self, ::zaplib::ZerdeError> {
+                ::zaplib::from_nested_zerde_record(value, field)
+            }
+
+            fn typescript_type() -> String {
+                #name.to_string()
+            }
+
+            fn typescript_schema() -> String {
+                format!("{{ record: {}Schema }}", #name)
+            }
+
+            fn collect_typescript_declarations(declarations: &mut Vec<String>) {
+                let declaration = <Self as ::zaplib::ZapParamStruct>::typescript_declaration();
+                if !declarations.contains(&declaration) {
+                    #(<#field_types as ::zaplib::ZerdeField>::collect_typescript_declarations(declarations);)*
+                    declarations.push(declaration);
+                }
+            }
+        }
+
+        impl ::zaplib::IntoParam for #ident {
+            fn into_param(self) -> ::zaplib::ZapParam {
+                ::zaplib::IntoParam::into_param(::zaplib::ZapParamStruct::to_zerde_record(&self))
+            }
+        }
+    })
+}
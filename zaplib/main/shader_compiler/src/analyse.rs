@@ -194,14 +194,21 @@ impl<'a> ShaderAnalyser<'a> {
     fn analyse_texture_decl(&mut self, decl: &TextureDecl) -> Result<(), ParseError> {
         let ty = self.ty_checker().ty_check_ty_expr(&decl.ty_expr)?;
         match ty {
-            Ty::Texture2D => {}
-            _ => return Err(ParseError { span: decl.span, message: String::from("texture must be a texture2D") }),
+            Ty::Texture2D | Ty::TextureCube => {}
+            _ => return Err(ParseError { span: decl.span, message: String::from("texture must be a texture2D or textureCube") }),
         }
         self.env.insert_sym(decl.span, IdentPath::from_ident(decl.ident), Sym::Var { is_mut: false, ty, kind: VarKind::Texture })
     }
 
     fn analyse_uniform_decl(&mut self, decl: &UniformDecl) -> Result<(), ParseError> {
         let ty = self.ty_checker().ty_check_ty_expr(&decl.ty_expr)?;
+        match ty {
+            // Not every backend can upload arrays of other types.
+            Ty::Array { ref elem_ty, .. } if **elem_ty != Ty::Mat4 => {
+                return Err(ParseError { span: decl.span, message: String::from("uniform arrays must be arrays of mat4") })
+            }
+            _ => {}
+        }
         self.env.insert_sym(decl.span, IdentPath::from_ident(decl.ident), Sym::Var { is_mut: false, ty, kind: VarKind::Uniform })
     }
 
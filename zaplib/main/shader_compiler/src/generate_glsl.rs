@@ -741,6 +741,11 @@ impl BackendWriter for GlslBackendWriter {
                 write!(string, " ").unwrap();
                 self.write_ident(string, ident);
             }
+            Ty::TextureCube => {
+                self.write_ty_lit(string, TyLit::TextureCube);
+                write!(string, " ").unwrap();
+                self.write_ident(string, ident);
+            }
             Ty::Array { ref elem_ty, len } => {
                 self.write_var_decl(string, is_inout, is_packed, ident, elem_ty);
                 write!(string, "[{}]", len).unwrap();
@@ -773,6 +778,7 @@ impl BackendWriter for GlslBackendWriter {
                 TyLit::Mat3 => "mat3",
                 TyLit::Mat4 => "mat4",
                 TyLit::Texture2D => "sampler2D",
+                TyLit::TextureCube => "samplerCube",
             }
         )
         .unwrap();
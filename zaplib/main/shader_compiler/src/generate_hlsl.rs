@@ -42,6 +42,11 @@ impl<'a> ShaderGenerator<'a> {
             "float4 sample2d(Texture2D tex, float2 pos){{return tex.Sample(mpsc_default_texture_sampler,pos);}}"
         )
         .unwrap();
+        writeln!(
+            self.string,
+            "float4 sample_cube(TextureCube tex, float3 dir){{return tex.Sample(mpsc_default_texture_sampler,dir);}}"
+        )
+        .unwrap();
         self.generate_struct_decls();
         self.generate_uniform_structs();
         self.generate_texture_defs();
@@ -130,8 +135,11 @@ impl<'a> ShaderGenerator<'a> {
         for decl in &self.shader.decls {
             match decl {
                 Decl::Texture(decl) => {
-                    assert_eq!(*decl.ty_expr.ty.borrow().as_ref().unwrap(), Ty::Texture2D);
-                    write!(self.string, "Texture2D ").unwrap();
+                    match decl.ty_expr.ty.borrow().as_ref().unwrap() {
+                        Ty::Texture2D => write!(self.string, "Texture2D ").unwrap(),
+                        Ty::TextureCube => write!(self.string, "TextureCube ").unwrap(),
+                        _ => panic!(),
+                    }
                     self.backend_writer.write_ident(self.string, decl.ident);
                     writeln!(self.string, ": register(t{});", index).unwrap();
                     index += 1;
@@ -771,7 +779,7 @@ impl BackendWriter for HlslBackendWriter {
                 write!(string, " ").unwrap();
                 self.write_ident(string, ident);
             }
-            Ty::Texture2D => panic!(), // TODO
+            Ty::Texture2D | Ty::TextureCube => panic!(), // TODO
             Ty::Array { ref elem_ty, len } => {
                 self.write_var_decl(string, is_inout, is_packed, ident, elem_ty);
                 write!(string, " ").unwrap();
@@ -805,7 +813,7 @@ impl BackendWriter for HlslBackendWriter {
                 TyLit::Mat2 => "float2x2",
                 TyLit::Mat3 => "float3x3",
                 TyLit::Mat4 => "float4x4",
-                TyLit::Texture2D => panic!(), // TODO
+                TyLit::Texture2D | TyLit::TextureCube => panic!(), // TODO
             }
         )
         .unwrap();
@@ -36,6 +36,12 @@ impl<'a> ShaderGenerator<'a> {
              tex.sample(sampler(mag_filter::linear,min_filter::linear),pos);}}"
         )
         .unwrap();
+        writeln!(
+            self.string,
+            "float4 sample_cube(texturecube<float> tex, float3 dir){{return \
+             tex.sample(sampler(mag_filter::linear,min_filter::linear),dir);}}"
+        )
+        .unwrap();
         self.generate_struct_decls();
         self.generate_uniform_structs();
         self.generate_texture_struct();
@@ -113,8 +119,11 @@ impl<'a> ShaderGenerator<'a> {
         for decl in &self.shader.decls {
             match decl {
                 Decl::Texture(decl) => {
-                    assert_eq!(*decl.ty_expr.ty.borrow().as_ref().unwrap(), Ty::Texture2D);
-                    write!(self.string, "    texture2d<float> ").unwrap();
+                    match decl.ty_expr.ty.borrow().as_ref().unwrap() {
+                        Ty::Texture2D => write!(self.string, "    texture2d<float> ").unwrap(),
+                        Ty::TextureCube => write!(self.string, "    texturecube<float> ").unwrap(),
+                        _ => panic!(),
+                    }
                     self.backend_writer.write_ident(self.string, decl.ident);
                     write!(self.string, " [[texture({})]];", index).unwrap();
                     index += 1;
@@ -686,7 +695,7 @@ impl BackendWriter for MetalBackendWriter {
                 write!(string, " {}", ref_prefix).unwrap();
                 self.write_ident(string, ident);
             }
-            Ty::Texture2D => panic!(), // TODO
+            Ty::Texture2D | Ty::TextureCube => panic!(), // TODO
             Ty::Array { ref elem_ty, len } => {
                 self.write_var_decl(string, is_inout, is_packed, ident, elem_ty);
                 write!(string, "[{}]", len).unwrap();
@@ -718,7 +727,7 @@ impl BackendWriter for MetalBackendWriter {
                 TyLit::Mat2 => "float2x2",
                 TyLit::Mat3 => "float3x3",
                 TyLit::Mat4 => "float4x4",
-                TyLit::Texture2D => panic!(), // TODO
+                TyLit::Texture2D | TyLit::TextureCube => panic!(), // TODO
             }
         )
         .unwrap();
@@ -255,6 +255,7 @@ where
                     "bvec3" => Token::TyLit(TyLit::Bvec3),
                     "bvec4" => Token::TyLit(TyLit::Bvec4),
                     "texture2D" => Token::TyLit(TyLit::Texture2D),
+                    "textureCube" => Token::TyLit(TyLit::TextureCube),
                     "const" => Token::Const,
                     "continue" => Token::Continue,
                     "else" => Token::Else,
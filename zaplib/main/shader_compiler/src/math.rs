@@ -10,6 +10,7 @@ pub fn f32_from_lerp(from: f32, to: f32, t: f32) -> f32 {
 
 /// 4x4 matrix; very common in graphics programming.
 #[derive(Clone, Copy, Default, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[repr(C)]
 pub struct Mat4 {
     pub v: [f32; 16],
@@ -20,6 +21,7 @@ pub struct Mat4 {
 ///
 /// TODO(JP): Maybe rename orientation/position to rotation/translation?
 #[derive(Clone, Copy, Default, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Transform {
     pub orientation: Quat,
     pub position: Vec3,
@@ -78,6 +80,7 @@ pub const fn vec4(x: f32, y: f32, z: f32, w: f32) -> Vec4 {
 
 /// Vector (as in linear algebra, not as in [`Vec`]!) with two elements.
 #[derive(Clone, Copy, Default, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[repr(C)]
 pub struct Vec2 {
     pub x: f32,
@@ -150,6 +153,7 @@ const TODEG: f32 = 57.295_78;
 
 /// Vector (as in linear algebra, not as in [`Vec`]!) with three elements.
 #[derive(Clone, Copy, Default, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[repr(C)]
 pub struct Vec3 {
     pub x: f32,
@@ -220,6 +224,7 @@ impl fmt::Display for Vec3 {
 
 /// Vector (as in linear algebra, not as in [`Vec`]!) with four elements.
 #[derive(Clone, Copy, Default, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[repr(C)]
 pub struct Vec4 {
     pub x: f32,
@@ -443,6 +448,7 @@ impl Vec4 {
 /// Represents an (axis-aligned) rectangle. Axis-aligned means that you can't
 /// rotate it.
 #[derive(Clone, Copy, Default, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Rect {
     pub pos: Vec2,
     pub size: Vec2,
@@ -486,6 +492,7 @@ impl Rect {
 /// seem to actually honor that in the layout boxes code. Might be good to look into that
 /// and see if we should forbid that or not (we seem to never actually do that yet).
 #[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Padding {
     pub l: f32,
     pub t: f32,
@@ -535,6 +542,7 @@ impl Default for Padding {
 ///
 /// Let's give it up for [Hamilton](https://www.youtube.com/watch?v=SZXHoWwBcDc).
 #[derive(Clone, Copy, Default, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Quat {
     pub a: f32,
     pub b: f32,
@@ -578,7 +586,7 @@ impl Quat {
             a: scale0 * n.a + scale1 * m.a,
             b: scale0 * n.b + scale1 * m.b,
             c: scale0 * n.c + scale1 * m.c,
-            d: scale0 * m.d + scale1 * m.d,
+            d: scale0 * n.d + scale1 * m.d,
         })
         .normalized()
     }
@@ -1443,4 +1451,13 @@ mod tests {
         let c = Vec4::from_lerp(a, b, t);
         assert_eq!(c, vec4(3.0, 4.0, 5.0, 6.0));
     }
+
+    #[test]
+    fn test_quat_from_slerp() {
+        let a = Quat::from_axis_angle(vec3(0., 0., 1.), 0.);
+        let b = Quat::from_axis_angle(vec3(0., 0., 1.), PI / 2.);
+        let c = Quat::from_slerp(a, b, 0.5);
+        let expected = Quat::from_axis_angle(vec3(0., 0., 1.), PI / 4.);
+        assert!((c.dot(expected) - 1.).abs() < 1e-5);
+    }
 }
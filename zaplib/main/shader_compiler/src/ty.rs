@@ -24,6 +24,7 @@ pub enum Ty {
     Mat3,
     Mat4,
     Texture2D,
+    TextureCube,
     Array { elem_ty: Rc<Ty>, len: usize },
     Struct { ident: Ident },
 }
@@ -48,6 +49,7 @@ impl Ty {
             Ty::Mat3 => Some(TyLit::Mat3),
             Ty::Mat4 => Some(TyLit::Mat4),
             Ty::Texture2D => Some(TyLit::Bool),
+            Ty::TextureCube => Some(TyLit::Bool),
             Ty::Array { .. } => None,
             Ty::Struct { .. } => None,
         }
@@ -84,6 +86,7 @@ impl Ty {
             Ty::Mat3 => 9,
             Ty::Mat4 => 16,
             Ty::Texture2D { .. } => panic!(),
+            Ty::TextureCube => panic!(),
             Ty::Array { elem_ty, len } => elem_ty.size() * len,
             Ty::Struct { .. } => panic!(),
         }
@@ -110,6 +113,7 @@ impl fmt::Display for Ty {
             Ty::Mat3 => write!(f, "mat3"),
             Ty::Mat4 => write!(f, "mat4"),
             Ty::Texture2D => write!(f, "texture2D"),
+            Ty::TextureCube => write!(f, "textureCube"),
             Ty::Array { elem_ty, len } => write!(f, "{}[{}]", elem_ty, len),
             Ty::Struct { ident, .. } => write!(f, "{}", ident),
         }
@@ -147,6 +151,7 @@ pub enum TyLit {
     Mat3,
     Mat4,
     Texture2D,
+    TextureCube,
 }
 
 impl TyLit {
@@ -172,6 +177,7 @@ impl TyLit {
             TyLit::Mat3 => Ty::Mat3,
             TyLit::Mat4 => Ty::Mat4,
             TyLit::Texture2D => Ty::Texture2D,
+            TyLit::TextureCube => Ty::TextureCube,
         }
     }
 
@@ -207,6 +213,9 @@ impl TyLit {
         if ident == Ident::new("Texture2D") {
             return Some(TyLit::Texture2D);
         }
+        if ident == Ident::new("TextureCube") {
+            return Some(TyLit::TextureCube);
+        }
         if ident == Ident::new("BVec2") {
             return Some(TyLit::Bvec2);
         }
@@ -251,6 +260,7 @@ impl fmt::Display for TyLit {
                 TyLit::Mat3 => "mat3",
                 TyLit::Mat4 => "mat4",
                 TyLit::Texture2D => "texture2D",
+                TyLit::TextureCube => "textureCube",
             }
         )
     }
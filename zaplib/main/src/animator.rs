@@ -38,6 +38,29 @@ pub struct Animator {
     /// The last timestamp we updated our animation, used to avoid computing the
     /// animation values multiple times for the same timestamp.
     last_processed_time: f64,
+
+    /// The values at the time that [`Animator::current`] started playing. Used as the starting point
+    /// for [`Track`]s that don't have a key frame at 0, and as the end state when playing in reverse.
+    start_values: Option<Vec<AnimValue>>,
+
+    /// If set, [`Animator::current`] is paused, and this is how far we were into the animation (in
+    /// seconds). See [`Animator::pause`] and [`Animator::resume`].
+    paused_elapsed: Option<f64>,
+
+    /// Whether [`Animator::current`] is playing backwards. See [`Animator::reverse`].
+    reversed: bool,
+}
+
+/// Returned by [`Animator::handle_event`], to tell whether values have changed.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AnimatorEvent {
+    /// Nothing changed.
+    None,
+    /// [`Animator`] values were updated; you should update your "draw objects".
+    Updated,
+    /// [`Animator`] values were updated, and an animation finished playing (it reached its end, or its
+    /// start when playing in reverse). Does not fire for every iteration of a looping [`Anim`].
+    Completed,
 }
 
 impl Animator {
@@ -52,6 +75,9 @@ impl Animator {
             self.current = Some(anim);
             self.next = None;
             self.current_start_time = cx.last_event_time;
+            self.start_values = self.values.clone();
+            self.paused_elapsed = None;
+            self.reversed = false;
             // Make sure that we request a new frame to play our animation in.
             cx.request_next_frame();
         } else {
@@ -61,6 +87,50 @@ impl Animator {
         }
     }
 
+    /// Pause the current animation, if any. Values will stay the same until calling [`Animator::resume`],
+    /// or until a new animation is played.
+    pub fn pause(&mut self, cx: &Cx) {
+        if self.current.is_some() && self.paused_elapsed.is_none() {
+            self.paused_elapsed = Some(cx.last_event_time - self.current_start_time);
+        }
+    }
+
+    /// Resume an animation that was paused using [`Animator::pause`].
+    pub fn resume(&mut self, cx: &mut Cx) {
+        if let Some(paused_elapsed) = self.paused_elapsed.take() {
+            self.current_start_time = cx.last_event_time - paused_elapsed;
+            cx.request_next_frame();
+        }
+    }
+
+    /// Flip the direction of the current animation, if any, continuing from the current position.
+    ///
+    /// When an animation plays backwards, it ends up at the values from before it started playing. Note
+    /// that any animation queued up using [`Anim::chain`] will still play after this one is done.
+    pub fn reverse(&mut self, cx: &mut Cx) {
+        if let Some(current_anim) = &self.current {
+            let elapsed = self.paused_elapsed.unwrap_or(cx.last_event_time - self.current_start_time);
+            let elapsed = current_anim.duration - elapsed.min(current_anim.duration);
+            if self.paused_elapsed.is_some() {
+                self.paused_elapsed = Some(elapsed);
+            } else {
+                self.current_start_time = cx.last_event_time - elapsed;
+                cx.request_next_frame();
+            }
+            self.reversed = !self.reversed;
+        }
+    }
+
+    /// Whether there is an animation that is currently playing (and not paused).
+    pub fn is_playing(&self) -> bool {
+        self.current.is_some() && self.paused_elapsed.is_none()
+    }
+
+    /// Whether the current animation is paused using [`Animator::pause`].
+    pub fn is_paused(&self) -> bool {
+        self.paused_elapsed.is_some()
+    }
+
     /// Process animations from a "draw" function. This must be called before reading any values.
     ///
     /// The `anim_default` will initialze the Animator if it's currently uninitialized.
@@ -74,10 +144,18 @@ impl Animator {
     /// Convenient function for only calling [`Animator::run_animator`] if the event is
     /// an [`Event::NextFrame`]. Returns true if we processed the animation so you
     /// can update your "draw objects".
+    ///
+    /// Use [`Animator::handle_event`] if you also want to know when an animation completes.
     pub fn handle(&mut self, cx: &mut Cx, event: &Event) -> bool {
+        self.handle_event(cx, event) != AnimatorEvent::None
+    }
+
+    /// Same as [`Animator::handle`], but returns an [`AnimatorEvent`], which also tells you when an
+    /// animation has completed.
+    pub fn handle_event(&mut self, cx: &mut Cx, event: &Event) -> AnimatorEvent {
         match event {
-            Event::NextFrame => self.values.is_some() && self.run_animator(cx),
-            _ => false,
+            Event::NextFrame if self.values.is_some() => self.run_animator(cx),
+            _ => AnimatorEvent::None,
         }
     }
 
@@ -86,36 +164,48 @@ impl Animator {
     /// your "draw objects" accordingly. Note that [`Animator::values`] are the "source of
     /// truth", so when in doubt it's always safe to just update your objects
     /// based on [`Animator::values`] regardless of the return value of [`Animator::draw`].
-    fn run_animator(&mut self, cx: &mut Cx) -> bool {
+    fn run_animator(&mut self, cx: &mut Cx) -> AnimatorEvent {
         // Skip if time hasn't changed, unless this is the initial call.
-        if cx.last_event_time == self.last_processed_time {
-            return false;
+        if cx.last_event_time == self.last_processed_time || self.paused_elapsed.is_some() {
+            return AnimatorEvent::None;
         }
         self.last_processed_time = cx.last_event_time;
 
         // First check if the current animation has expired, in which case we need to either stop
-        // animating, or start with the queued up animation.
+        // animating, loop, or start with the queued up animation.
+        let mut completed = false;
         if let Some(current_anim) = &self.current {
             if self.current_start_time + current_anim.duration <= cx.last_event_time {
-                // Make sure that `values` actually reflects the "end state" of the animation, since
-                // at the previous rendering step we were probably a little bit before the actual
-                // end.
-                self.values = Some(current_anim.get_last_values());
-
-                if self.next.is_none() {
-                    // If there was no animation queued up, just bail out, but still return `true`
-                    // since we've changed `values`.
-                    self.current = None;
-                    return true;
+                if current_anim.looping && self.next.is_none() && current_anim.duration > 0. {
+                    // Move the start time forward by a whole number of iterations, so we keep the
+                    // phase of the animation intact.
+                    let elapsed = cx.last_event_time - self.current_start_time;
+                    self.current_start_time += (elapsed / current_anim.duration).floor() * current_anim.duration;
                 } else {
-                    // Don't just set the new current_start_time to the current time, since most
-                    // likely we have overshot a little and are actually a tiny bit into the next
-                    // animation already.
-                    self.current_start_time += current_anim.duration;
-                    std::mem::swap(&mut self.current, &mut self.next);
-                    self.next = None;
-                    // Fall through, so we compute the current values based on the animation that
-                    // was queued up (and which is now `current`).
+                    // Make sure that `values` actually reflects the "end state" of the animation, since
+                    // at the previous rendering step we were probably a little bit before the actual
+                    // end.
+                    self.values = Some(self.get_end_values(current_anim));
+                    completed = true;
+
+                    if self.next.is_none() {
+                        // If there was no animation queued up, just bail out, but still return
+                        // since we've changed `values`.
+                        self.current = None;
+                        self.reversed = false;
+                        return AnimatorEvent::Completed;
+                    } else {
+                        // Don't just set the new current_start_time to the current time, since most
+                        // likely we have overshot a little and are actually a tiny bit into the next
+                        // animation already.
+                        self.current_start_time += current_anim.duration;
+                        std::mem::swap(&mut self.current, &mut self.next);
+                        self.next = None;
+                        self.start_values = self.values.clone();
+                        self.reversed = false;
+                        // Fall through, so we compute the current values based on the animation that
+                        // was queued up (and which is now `current`).
+                    }
                 }
             }
         }
@@ -126,50 +216,40 @@ impl Animator {
             cx.request_next_frame();
 
             // Compute the fraction between 0 and 1 of how far we are into the current animation.
-            let time_fraction = (cx.last_event_time - self.current_start_time) / current_anim.duration;
-
-            let values = self.values.as_mut().unwrap();
+            let time_fraction = if current_anim.duration > 0. {
+                ((cx.last_event_time - self.current_start_time) / current_anim.duration).min(1.)
+            } else {
+                1.
+            };
+            let time_fraction = if self.reversed { 1. - time_fraction } else { time_fraction };
 
             // Update all the individual values based on how far we are into the current animation.
-            for (index, track) in current_anim.tracks.iter().enumerate() {
-                match track {
-                    Track::Float { key_frames, ease } => {
-                        values[index] = AnimValue::Float(Track::compute_track_float(
-                            time_fraction,
-                            key_frames,
-                            values[index].unwrap_float(),
-                            ease,
-                        ));
-                    }
-                    Track::Vec2 { key_frames, ease } => {
-                        values[index] = AnimValue::Vec2(Track::compute_track_vec2(
-                            time_fraction,
-                            key_frames,
-                            values[index].unwrap_vec2(),
-                            ease,
-                        ));
-                    }
-                    Track::Vec3 { key_frames, ease } => {
-                        values[index] = AnimValue::Vec3(Track::compute_track_vec3(
-                            time_fraction,
-                            key_frames,
-                            values[index].unwrap_vec3(),
-                            ease,
-                        ));
-                    }
-                    Track::Vec4 { key_frames, ease } => {
-                        values[index] = AnimValue::Vec4(Track::compute_track_vec4(
-                            time_fraction,
-                            key_frames,
-                            values[index].unwrap_vec4(),
-                            ease,
-                        ));
-                    }
-                }
+            let start_values = self.start_values.as_ref().or(self.values.as_ref()).unwrap();
+            let values = current_anim
+                .tracks
+                .iter()
+                .enumerate()
+                .map(|(index, track)| track.compute_value(time_fraction, &start_values[index]))
+                .collect();
+            self.values = Some(values);
+            return if completed { AnimatorEvent::Completed } else { AnimatorEvent::Updated };
+        }
+        AnimatorEvent::None
+    }
+
+    /// The values that an animation should end up with, taking into account [`Animator::reversed`].
+    fn get_end_values(&self, anim: &Anim) -> Vec<AnimValue> {
+        if self.reversed {
+            if let Some(start_values) = &self.start_values {
+                return anim
+                    .tracks
+                    .iter()
+                    .enumerate()
+                    .map(|(index, track)| track.compute_value(0., &start_values[index]))
+                    .collect();
             }
-            return true;
         }
-        false
+        anim.get_last_values()
     }
 
     /// Get the value of the given track as a float. Be sure to call this only if the given track is
@@ -264,6 +344,10 @@ pub struct Anim {
     /// animation playing.
     pub chain: bool,
 
+    /// If set, this animation will start over when it's done, until another
+    /// animation is played (or queued up using [`Anim::chain`]).
+    pub looping: bool,
+
     /// The actual tracks of values that will change during this animation.
     /// Should remain consistent between the different animations that you pass
     /// into a single [`Animator`].
@@ -276,7 +360,7 @@ pub struct Anim {
 impl Anim {
     /// TODO(JP): Replace these with Anim::default() when
     /// <https://github.com/rust-lang/rust/issues/67792> gets done
-    pub const DEFAULT: Anim = Anim { duration: 0., chain: false, tracks: &[] };
+    pub const DEFAULT: Anim = Anim { duration: 0., chain: false, looping: false, tracks: &[] };
 
     /// Get the values for the "end state" of an animation, ie. the values for
     /// when the animation is done.
@@ -630,115 +714,133 @@ pub enum Track {
     Vec4 { ease: Ease, key_frames: &'static [(f64, Vec4)] },
 }
 
+/// Values that can be interpolated between key frames of a [`Track`].
+trait AnimLerp: Copy {
+    fn anim_lerp(a: Self, b: Self, f: f32) -> Self;
+}
+
+impl AnimLerp for f32 {
+    fn anim_lerp(a: f32, b: f32, f: f32) -> f32 {
+        a * (1.0 - f) + b * f
+    }
+}
+
+impl AnimLerp for Vec2 {
+    fn anim_lerp(a: Vec2, b: Vec2, f: f32) -> Vec2 {
+        let nf = 1.0 - f;
+        Vec2 { x: a.x * nf + b.x * f, y: a.y * nf + b.y * f }
+    }
+}
+
+impl AnimLerp for Vec3 {
+    fn anim_lerp(a: Vec3, b: Vec3, f: f32) -> Vec3 {
+        let nf = 1.0 - f;
+        Vec3 { x: a.x * nf + b.x * f, y: a.y * nf + b.y * f, z: a.z * nf + b.z * f }
+    }
+}
+
+impl AnimLerp for Vec4 {
+    fn anim_lerp(a: Vec4, b: Vec4, f: f32) -> Vec4 {
+        let nf = 1.0 - f;
+        Vec4 { x: a.x * nf + b.x * f, y: a.y * nf + b.y * f, z: a.z * nf + b.z * f, w: a.w * nf + b.w * f }
+    }
+}
+
 impl Track {
-    fn compute_track_float(time: f64, track: &[(f64, f32)], init: f32, ease: &Ease) -> f32 {
-        if track.is_empty() {
-            return init;
-        }
-        fn lerp(a: f32, b: f32, f: f32) -> f32 {
-            a * (1.0 - f) + b * f
-        }
-        // find the 2 keys we want
-        for i in 0..track.len() {
-            if time >= track[i].0 {
-                // we found the left key
-                let val1 = &track[i];
-                if i == track.len() - 1 {
-                    // last key
-                    return val1.1;
-                }
-                let val2 = &track[i + 1];
-                // lerp it
-                let f = ease.map((time - val1.0) / (val2.0 - val1.0)) as f32;
-                return lerp(val1.1, val2.1, f);
+    /// Compute the value of this track at `time` (a fraction between 0 and 1), where `init` is the
+    /// value from before the animation started, which is used if the first key frame is not at 0.
+    fn compute_value(&self, time: f64, init: &AnimValue) -> AnimValue {
+        match self {
+            Track::Float { key_frames, ease } => {
+                AnimValue::Float(Self::compute_track(time, key_frames, init.unwrap_float(), ease))
             }
+            Track::Vec2 { key_frames, ease } => AnimValue::Vec2(Self::compute_track(time, key_frames, init.unwrap_vec2(), ease)),
+            Track::Vec3 { key_frames, ease } => AnimValue::Vec3(Self::compute_track(time, key_frames, init.unwrap_vec3(), ease)),
+            Track::Vec4 { key_frames, ease } => AnimValue::Vec4(Self::compute_track(time, key_frames, init.unwrap_vec4(), ease)),
         }
-        let val2 = &track[0];
-        let f = ease.map(time / val2.0) as f32;
-        lerp(init, val2.1, f)
     }
 
-    fn compute_track_vec2(time: f64, track: &[(f64, Vec2)], init: Vec2, ease: &Ease) -> Vec2 {
+    fn compute_track<T: AnimLerp>(time: f64, track: &[(f64, T)], init: T, ease: &Ease) -> T {
         if track.is_empty() {
             return init;
         }
-        fn lerp(a: Vec2, b: Vec2, f: f32) -> Vec2 {
-            let nf = 1.0 - f;
-            Vec2 { x: a.x * nf + b.x * f, y: a.y * nf + b.y * f }
-        }
-        // find the 2 keys we want
-        for i in 0..track.len() {
-            if time >= track[i].0 {
-                // we found the left key
-                let val1 = &track[i];
-                if i == track.len() - 1 {
-                    // last key
-                    return val1.1;
-                }
-                let val2 = &track[i + 1];
-                // lerp it
-                let f = ease.map((time - val1.0) / (val2.0 - val1.0)) as f32;
-                return lerp(val1.1, val2.1, f);
+        // Find the last key frame that we're past (the "left" key).
+        if let Some(i) = track.iter().rposition(|key_frame| time >= key_frame.0) {
+            let val1 = &track[i];
+            if i == track.len() - 1 {
+                // last key
+                return val1.1;
             }
+            let val2 = &track[i + 1];
+            let f = ease.map((time - val1.0) / (val2.0 - val1.0)) as f32;
+            return T::anim_lerp(val1.1, val2.1, f);
         }
+        // We're before the first key frame, so interpolate from the initial value.
         let val2 = &track[0];
         let f = ease.map(time / val2.0) as f32;
-        lerp(init, val2.1, f)
+        T::anim_lerp(init, val2.1, f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    const ANIM_FADE: Anim = Anim {
+        duration: 1.0,
+        tracks: &[Track::Float { key_frames: &[(0.0, 0.0), (0.5, 1.0), (1.0, 0.5)], ease: Ease::Lin }],
+        ..Anim::DEFAULT
+    };
+
+    fn run_frame(cx: &mut Cx, animator: &mut Animator, time: f64) -> AnimatorEvent {
+        cx.last_event_time = time;
+        animator.handle_event(cx, &Event::NextFrame)
     }
 
-    fn compute_track_vec3(time: f64, track: &[(f64, Vec3)], init: Vec3, ease: &Ease) -> Vec3 {
-        if track.is_empty() {
-            return init;
-        }
-        fn lerp(a: Vec3, b: Vec3, f: f32) -> Vec3 {
-            let nf = 1.0 - f;
-            Vec3 { x: a.x * nf + b.x * f, y: a.y * nf + b.y * f, z: a.z * nf + b.z * f }
-        }
-        // find the 2 keys we want
-        for i in 0..track.len() {
-            if time >= track[i].0 {
-                // we found the left key
-                let val1 = &track[i];
-                if i == track.len() - 1 {
-                    // last key
-                    return val1.1;
-                }
-                let val2 = &track[i + 1];
-                // lerp it
-                let f = ease.map((time - val1.0) / (val2.0 - val1.0)) as f32;
-                return lerp(val1.1, val2.1, f);
-            }
-        }
-        let val2 = &track[0];
-        let f = ease.map(time / val2.0) as f32;
-        lerp(init, val2.1, f)
+    #[test]
+    fn test_multiple_key_frames() {
+        let mut cx = Cx::new_test();
+        let mut animator = Animator::default();
+        animator.draw(&mut cx, Anim { tracks: &[Track::Float { key_frames: &[(1.0, 0.0)], ease: Ease::Lin }], ..ANIM_FADE });
+        animator.play_anim(&mut cx, ANIM_FADE);
+
+        assert_eq!(run_frame(&mut cx, &mut animator, 0.25), AnimatorEvent::Updated);
+        assert!((animator.get_float(0) - 0.5).abs() < 0.001);
+        assert_eq!(run_frame(&mut cx, &mut animator, 0.75), AnimatorEvent::Updated);
+        assert!((animator.get_float(0) - 0.75).abs() < 0.001);
+        assert_eq!(run_frame(&mut cx, &mut animator, 1.5), AnimatorEvent::Completed);
+        assert_eq!(animator.get_float(0), 0.5);
+        assert!(!animator.is_playing());
     }
 
-    fn compute_track_vec4(time: f64, track: &[(f64, Vec4)], init: Vec4, ease: &Ease) -> Vec4 {
-        if track.is_empty() {
-            return init;
-        }
-        fn lerp(a: Vec4, b: Vec4, f: f32) -> Vec4 {
-            let nf = 1.0 - f;
-            Vec4 { x: a.x * nf + b.x * f, y: a.y * nf + b.y * f, z: a.z * nf + b.z * f, w: a.w * nf + b.w * f }
-        }
-        // find the 2 keys we want
-        for i in 0..track.len() {
-            if time >= track[i].0 {
-                // we found the left key
-                let val1 = &track[i];
-                if i == track.len() - 1 {
-                    // last key
-                    return val1.1;
-                }
-                let val2 = &track[i + 1];
-                // lerp it
-                let f = ease.map((time - val1.0) / (val2.0 - val1.0)) as f32;
-                return lerp(val1.1, val2.1, f);
-            }
-        }
-        let val2 = &track[0];
-        let f = ease.map(time / val2.0) as f32;
-        lerp(init, val2.1, f)
+    #[test]
+    fn test_pause_reverse_and_loop() {
+        let mut cx = Cx::new_test();
+        let mut animator = Animator::default();
+        animator.draw(&mut cx, Anim { tracks: &[Track::Float { key_frames: &[(1.0, 0.2)], ease: Ease::Lin }], ..ANIM_FADE });
+        let anim = Anim { tracks: &[Track::Float { key_frames: &[(1.0, 1.2)], ease: Ease::Lin }], ..ANIM_FADE };
+        animator.play_anim(&mut cx, anim.clone());
+
+        run_frame(&mut cx, &mut animator, 0.5);
+        assert!((animator.get_float(0) - 0.7).abs() < 0.001);
+
+        // Pausing keeps the values the same.
+        animator.pause(&cx);
+        assert_eq!(run_frame(&mut cx, &mut animator, 10.0), AnimatorEvent::None);
+        assert!((animator.get_float(0) - 0.7).abs() < 0.001);
+        animator.resume(&mut cx);
+
+        // Reversing goes back to the original value.
+        animator.reverse(&mut cx);
+        run_frame(&mut cx, &mut animator, 10.25);
+        assert!((animator.get_float(0) - 0.45).abs() < 0.001);
+        assert_eq!(run_frame(&mut cx, &mut animator, 11.0), AnimatorEvent::Completed);
+        assert!((animator.get_float(0) - 0.2).abs() < 0.001);
+
+        // Looping animations never complete.
+        animator.play_anim(&mut cx, Anim { looping: true, ..anim });
+        run_frame(&mut cx, &mut animator, 13.25);
+        assert!(animator.is_playing());
+        assert!((animator.get_float(0) - 0.45).abs() < 0.001);
     }
 }
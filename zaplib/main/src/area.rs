@@ -140,7 +140,8 @@ impl Area {
             Area::InstanceRange(inst) => {
                 // Pull it directly out of the draw uniforms.
                 let cxview = &cx.views[inst.view_id];
-                let draw_call = &cxview.draw_calls[inst.draw_call_id];
+                let (draw_call_id, _) = cxview.resolve_instance_range(inst.draw_call_id, inst.instance_offset);
+                let draw_call = &cxview.draw_calls[draw_call_id];
                 Vec2 { x: draw_call.draw_uniforms.draw_scroll_x, y: draw_call.draw_uniforms.draw_scroll_y }
             }
             Area::View(view_area) => {
@@ -171,15 +172,16 @@ impl Area {
                     return None;
                 }
                 let cxview = &cx.views[inst.view_id];
-                let draw_call = &cxview.draw_calls[inst.draw_call_id];
+                let (draw_call_id, instance_offset) = cxview.resolve_instance_range(inst.draw_call_id, inst.instance_offset);
+                let draw_call = &cxview.draw_calls[draw_call_id];
                 assert!(!draw_call.instances.is_empty());
                 let sh = &cx.shaders[draw_call.shader_id];
                 if let Some(rect_pos) = sh.mapping.rect_instance_props.rect_pos {
-                    let x = draw_call.instances[inst.instance_offset + rect_pos];
-                    let y = draw_call.instances[inst.instance_offset + rect_pos + 1];
+                    let x = draw_call.instances[instance_offset + rect_pos];
+                    let y = draw_call.instances[instance_offset + rect_pos + 1];
                     if let Some(rect_size) = sh.mapping.rect_instance_props.rect_size {
-                        let w = draw_call.instances[inst.instance_offset + rect_size];
-                        let h = draw_call.instances[inst.instance_offset + rect_size + 1];
+                        let w = draw_call.instances[instance_offset + rect_size];
+                        let h = draw_call.instances[instance_offset + rect_size + 1];
                         return Some(draw_call.clip_and_scroll_rect(x, y, w, h));
                     }
                 }
@@ -204,7 +206,8 @@ impl Area {
         match self {
             Area::InstanceRange(inst) => {
                 let cxview = &cx.views[inst.view_id];
-                let draw_call = &cxview.draw_calls[inst.draw_call_id];
+                let (draw_call_id, instance_offset) = cxview.resolve_instance_range(inst.draw_call_id, inst.instance_offset);
+                let draw_call = &cxview.draw_calls[draw_call_id];
                 let sh = &cx.shaders[draw_call.shader_id];
 
                 let total_instance_slots = sh.mapping.instance_props.total_slots;
@@ -217,10 +220,7 @@ impl Area {
 
                 // TODO(JP): Move to cast.rs?
                 unsafe {
-                    std::slice::from_raw_parts(
-                        draw_call.instances.as_ptr().add(inst.instance_offset) as *const T,
-                        inst.instance_count,
-                    )
+                    std::slice::from_raw_parts(draw_call.instances.as_ptr().add(instance_offset) as *const T, inst.instance_count)
                 }
             }
             _ => &mut [],
@@ -245,11 +245,7 @@ impl Area {
         if let Some(first) = self.get_slice::<T>(cx).get(0) {
             first
         } else {
-            let len = cx.temp_default_data.len();
-            cx.temp_default_data.push(Box::new(T::default()));
-            // TODO(JP): Use https://doc.rust-lang.org/std/option/enum.Option.html#method.unwrap_unchecked here
-            // once it's stable.
-            unsafe { cx.temp_default_data.get_unchecked(len).downcast_ref().unwrap() }
+            cx.temp_default_data.alloc(T::default())
         }
     }
 
@@ -264,7 +260,8 @@ impl Area {
         match self {
             Area::InstanceRange(inst) => {
                 let cxview = &mut cx.views[inst.view_id];
-                let draw_call = &mut cxview.draw_calls[inst.draw_call_id];
+                let (draw_call_id, instance_offset) = cxview.resolve_instance_range(inst.draw_call_id, inst.instance_offset);
+                let draw_call = &mut cxview.draw_calls[draw_call_id];
                 let sh = &cx.shaders[draw_call.shader_id];
 
                 let total_instance_slots = sh.mapping.instance_props.total_slots;
@@ -286,7 +283,7 @@ impl Area {
                 // TODO(JP): Move to cast.rs?
                 unsafe {
                     std::slice::from_raw_parts_mut(
-                        draw_call.instances.as_mut_ptr().add(inst.instance_offset) as *mut T,
+                        draw_call.instances.as_mut_ptr().add(instance_offset) as *mut T,
                         inst.instance_count,
                     )
                 }
@@ -317,11 +314,7 @@ impl Area {
         if let Some(first) = self.get_slice_mut::<T>(cx).get_mut(0) {
             first
         } else {
-            let len = cx.temp_default_data.len();
-            cx.temp_default_data.push(Box::new(T::default()));
-            // TODO(JP): Use https://doc.rust-lang.org/std/option/enum.Option.html#method.unwrap_unchecked here
-            // once it's stable.
-            unsafe { cx.temp_default_data.get_unchecked_mut(len).downcast_mut().unwrap() }
+            cx.temp_default_data.alloc(T::default())
         }
     }
 
@@ -336,6 +329,8 @@ impl Area {
         match self {
             Area::InstanceRange(inst) => {
                 let cxview = &mut cx.views[inst.view_id];
+                // Other draw calls might share this one's uniforms and textures after batching.
+                cxview.unbatch_draw_call(inst.draw_call_id);
                 let draw_call = &mut cxview.draw_calls[inst.draw_call_id];
 
                 let shader_bytes = draw_call.user_uniforms.len() * std::mem::size_of::<f32>();
@@ -363,6 +358,8 @@ impl Area {
         if self.is_valid(cx) {
             if let Area::InstanceRange(inst) = self {
                 let cxview = &mut cx.views[inst.view_id];
+                // Other draw calls might share this one's uniforms and textures after batching.
+                cxview.unbatch_draw_call(inst.draw_call_id);
                 let draw_call = &mut cxview.draw_calls[inst.draw_call_id];
                 let sh = &cx.shaders[draw_call.shader_id];
                 for (index, prop) in sh.mapping.textures.iter().enumerate() {
@@ -375,4 +372,25 @@ impl Area {
         }
         panic!("Cannot find texture2D prop {}", name)
     }
+
+    /// Write a cube [`Texture`] (see [`Texture::get_cube_with_size`]) into the the [`DrawCall`] associated with this
+    /// [`Area::InstanceRange`], for a `textureCube` property.
+    pub fn write_texture_cube(&self, cx: &mut Cx, name: &str, texture_handle: TextureHandle) {
+        assert!(cx.textures[texture_handle.texture_id as usize].desc.cube, "{} needs a cube texture", name);
+        if self.is_valid(cx) {
+            if let Area::InstanceRange(inst) = self {
+                let cxview = &mut cx.views[inst.view_id];
+                let (draw_call_id, _) = cxview.resolve_instance_range(inst.draw_call_id, inst.instance_offset);
+                let draw_call = &mut cxview.draw_calls[draw_call_id];
+                let sh = &cx.shaders[draw_call.shader_id];
+                for (index, prop) in sh.mapping.textures.iter().enumerate() {
+                    if prop.name == name && prop.ty == Ty::TextureCube {
+                        draw_call.textures_2d[index] = texture_handle.texture_id;
+                        return;
+                    }
+                }
+            }
+        }
+        panic!("Cannot find textureCube prop {}", name)
+    }
 }
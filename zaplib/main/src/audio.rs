@@ -0,0 +1,851 @@
+//! Audio output and input, for apps that want to make sound (e.g. synthesizers or sonification of data), or capture
+//! it from a microphone (e.g. audio visualizers or voice tools).
+//!
+//! You provide a callback that fills buffers of `f32` samples, which is called on a separate (realtime)
+//! thread whenever the audio device needs more data. Samples are interleaved, so for stereo the buffer
+//! contains left, right, left, right, etc., and should be in the range -1 to 1.
+//!
+//! ```ignore
+//! let mut phase = 0.0f32;
+//! let output = audio::start_output(cx, AudioOutputOptions::default(), move |buffer| {
+//!     let step = 440. / buffer.sample_rate as f32;
+//!     for frame in buffer.frames_mut() {
+//!         let sample = (phase * std::f32::consts::TAU).sin() * 0.2;
+//!         frame.fill(sample);
+//!         phase = (phase + step) % 1.;
+//!     }
+//! });
+//! self.output_task = Some(cx.spawn(async move { output.await }));
+//! ```
+//!
+//! Sound stops when the [`AudioOutput`] is dropped.
+//!
+//! Capturing works the other way around: [`start_input`] calls your callback with buffers of captured samples. The
+//! backend's capture thread writes the samples into a ring buffer, and a separate thread calls the callback with
+//! blocks of [`AudioInputOptions::buffer_size`] frames, so a slow callback doesn't cause glitches in the capture
+//! itself. If the callback can't keep up on average, samples are dropped.
+//!
+//! ```ignore
+//! let input = audio::start_input(cx, AudioInputOptions::default(), move |buffer| {
+//!     let peak = buffer.samples.iter().fold(0.0f32, |peak, sample| peak.max(sample.abs()));
+//!     peak_level.store(peak.to_bits(), Ordering::Relaxed);
+//! });
+//! ```
+//!
+//! Starting input asks the user for permission to use the microphone where the platform requires that (browsers and
+//! Mac). When the user denies it you get [`AudioError::NotAllowed`]. Capturing stops when the [`AudioInput`] is
+//! dropped.
+//!
+//! Backends:
+//! * WebAssembly: an `AudioWorklet`, which reads the samples that the callback wrote into a ring buffer in shared
+//!   memory. Browsers only allow starting audio in response to user input, so call [`start_output`] when handling
+//!   e.g. [`Event::PointerDown`] or [`Event::KeyDown`]; otherwise you get [`AudioError::NotAllowed`]. For input, the
+//!   worklet writes into the ring buffer instead.
+//! * Linux: ALSA, which is loaded at runtime, so apps still start on systems without `libasound`.
+//! * Mac: CoreAudio.
+//! * Windows: WASAPI (shared mode).
+
+use crate::*;
+use std::fmt;
+use std::future::Future;
+#[cfg(not(target_arch = "wasm32"))]
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicUsize, Ordering};
+#[cfg(not(target_arch = "wasm32"))]
+use std::sync::Arc;
+
+/// Default for [`AudioInputOptions::buffer_size`].
+const DEFAULT_INPUT_BUFFER_SIZE: u32 = 1024;
+
+/// An audio device, as returned by [`output_devices`] or [`input_devices`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct AudioDevice {
+    /// Pass this to [`AudioOutputOptions::device_id`] or [`AudioInputOptions::device_id`] to use this device.
+    pub id: String,
+    /// Human-readable name. In browsers this is empty until the user has given permission to use a microphone.
+    pub name: String,
+    /// Whether this is the device that the system uses by default.
+    pub is_default: bool,
+}
+
+/// Options for [`start_output`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct AudioOutputOptions {
+    /// The [`AudioDevice::id`] of the device to play on, or [`None`] for the default device.
+    pub device_id: Option<String>,
+    /// Preferred sample rate in Hz, or [`None`] to use the native sample rate of the device (usually 44100 or
+    /// 48000). When the device doesn't support this rate, audio is resampled if possible; otherwise the device's
+    /// rate is used. Always check [`AudioBuffer::sample_rate`] or [`AudioOutput::sample_rate`].
+    pub sample_rate: Option<u32>,
+    /// Number of interleaved channels that the callback fills.
+    pub channels: u16,
+    /// Preferred number of frames per callback, or [`None`] for a default that balances latency and the risk of
+    /// glitches. Lower values mean lower latency. This is just a hint.
+    pub buffer_size: Option<u32>,
+}
+
+impl Default for AudioOutputOptions {
+    fn default() -> Self {
+        Self { device_id: None, sample_rate: None, channels: 2, buffer_size: None }
+    }
+}
+
+/// Options for [`start_input`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct AudioInputOptions {
+    /// The [`AudioDevice::id`] of the device to capture from, or [`None`] for the default device.
+    pub device_id: Option<String>,
+    /// Preferred sample rate in Hz, or [`None`] to use the native sample rate of the device. Not all platforms can
+    /// resample input (Mac can't), so always check [`AudioBuffer::sample_rate`] or [`AudioInput::sample_rate`].
+    pub sample_rate: Option<u32>,
+    /// Number of interleaved channels that the callback receives. Most microphones are mono.
+    pub channels: u16,
+    /// Number of frames per callback, or [`None`] for 1024. Unlike for output this is exact, which is convenient
+    /// for e.g. FFTs.
+    pub buffer_size: Option<u32>,
+}
+
+impl Default for AudioInputOptions {
+    fn default() -> Self {
+        Self { device_id: None, sample_rate: None, channels: 1, buffer_size: None }
+    }
+}
+
+/// A buffer of samples. For [`start_output`] this is filled with silence before each call, and the callback should
+/// fill it. For [`start_input`] it contains the captured samples.
+#[derive(Debug)]
+pub struct AudioBuffer<'a> {
+    /// Interleaved samples; `samples.len()` is the number of frames times [`AudioBuffer::channels`].
+    pub samples: &'a mut [f32],
+    /// Number of interleaved channels.
+    pub channels: usize,
+    /// Sample rate in Hz.
+    pub sample_rate: u32,
+}
+
+impl<'a> AudioBuffer<'a> {
+    /// Number of frames, i.e. samples per channel.
+    pub fn frame_count(&self) -> usize {
+        self.samples.len() / self.channels
+    }
+
+    /// Iterate over frames, each containing one sample per channel.
+    pub fn frames(&self) -> std::slice::ChunksExact<'_, f32> {
+        self.samples.chunks_exact(self.channels)
+    }
+
+    /// Iterate mutably over frames, each containing one sample per channel.
+    pub fn frames_mut(&mut self) -> std::slice::ChunksExactMut<'_, f32> {
+        self.samples.chunks_exact_mut(self.channels)
+    }
+}
+
+/// Why an audio operation failed.
+#[derive(Clone, Debug, PartialEq)]
+pub enum AudioError {
+    /// The browser did not allow starting audio, because it wasn't started in response to user input, or the user
+    /// (or system settings) denied access to the microphone.
+    NotAllowed,
+    /// There is no device with the given [`AudioOutputOptions::device_id`] or [`AudioInputOptions::device_id`], or
+    /// no such device at all.
+    DeviceNotFound,
+    /// Audio (or the requested option) is not supported on this platform, e.g. when ALSA is not installed.
+    NotSupported(String),
+    /// Any other error reported by the platform.
+    Other(String),
+}
+
+impl fmt::Display for AudioError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AudioError::NotAllowed => write!(f, "Audio is not allowed to start"),
+            AudioError::DeviceNotFound => write!(f, "Audio device not found"),
+            AudioError::NotSupported(message) => write!(f, "Audio not supported: {}", message),
+            AudioError::Other(message) => write!(f, "Audio error: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for AudioError {}
+
+#[cfg(target_arch = "wasm32")]
+impl From<CallJsError> for AudioError {
+    fn from(err: CallJsError) -> Self {
+        match err {
+            // See `audio.ts`.
+            CallJsError::Thrown(message) if message.starts_with("NotAllowedError") => AudioError::NotAllowed,
+            CallJsError::Thrown(message)
+                if message.starts_with("NotFoundError") || message.starts_with("OverconstrainedError") =>
+            {
+                AudioError::DeviceNotFound
+            }
+            CallJsError::Thrown(message) if message.starts_with("NotSupportedError") => AudioError::NotSupported(message),
+            CallJsError::Thrown(message) => AudioError::Other(message),
+            CallJsError::TimedOut => AudioError::Other("Timed out".to_string()),
+        }
+    }
+}
+
+/// Whether to list or open output or input devices, for the native backends.
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum AudioDirection {
+    Output,
+    Input,
+}
+
+/// The callback passed to [`start_output`] or [`start_input`].
+pub(crate) type AudioCallback = Box<dyn FnMut(&mut AudioBuffer) + Send>;
+
+/// Calls the [`AudioCallback`] from an audio thread. Catches panics so that they don't unwind into platform code;
+/// after a panic it doesn't call the callback anymore.
+pub(crate) struct AudioCallbackRunner {
+    callback: AudioCallback,
+    channels: usize,
+    sample_rate: u32,
+    panicked: bool,
+}
+
+impl AudioCallbackRunner {
+    pub(crate) fn new(callback: AudioCallback, channels: usize, sample_rate: u32) -> Self {
+        Self { callback, channels, sample_rate, panicked: false }
+    }
+
+    /// Clear `samples` and let the callback fill them. Outputs silence after a panic.
+    pub(crate) fn fill(&mut self, samples: &mut [f32]) {
+        samples.fill(0.);
+        if !self.call(samples) {
+            samples.fill(0.);
+        }
+    }
+
+    /// Call the callback with captured `samples`. Returns `false` if the callback panicked (now or before).
+    pub(crate) fn call(&mut self, samples: &mut [f32]) -> bool {
+        if self.panicked {
+            return false;
+        }
+        let mut buffer = AudioBuffer { samples, channels: self.channels, sample_rate: self.sample_rate };
+        let callback = &mut self.callback;
+        if let Err(payload) = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| callback(&mut buffer))) {
+            self.panicked = true;
+            universal_thread::report_worker_panic(universal_thread::panic_payload_to_string(&*payload));
+        }
+        !self.panicked
+    }
+}
+
+/// Ring buffer between a native backend's capture thread, which [pushes](InputRing::push) samples, and the
+/// [`InputDelivery`] thread that calls the callback of [`start_input`]. Lock-free, since CoreAudio pushes from a
+/// realtime thread.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) struct InputRing {
+    /// Interleaved samples, stored as bits so that we can share them between threads without `unsafe`.
+    samples: Vec<AtomicU32>,
+    /// Number of frames in `samples`; a power of two.
+    capacity: usize,
+    channels: usize,
+    /// Write and read index, in frames, wrapping around.
+    write_index: AtomicUsize,
+    read_index: AtomicUsize,
+    stop: AtomicBool,
+    /// The [`InputDelivery`] thread, which we wake up when pushing.
+    consumer: once_cell::sync::OnceCell<std::thread::Thread>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl InputRing {
+    pub(crate) fn new(channels: usize, buffer_size: usize) -> Self {
+        // Enough for a few blocks, and at least a third of a second at common sample rates.
+        let capacity = (buffer_size * 8).max(16384).next_power_of_two();
+        Self {
+            samples: (0..capacity * channels).map(|_| AtomicU32::new(0)).collect(),
+            capacity,
+            channels,
+            write_index: AtomicUsize::new(0),
+            read_index: AtomicUsize::new(0),
+            stop: AtomicBool::new(false),
+            consumer: once_cell::sync::OnceCell::new(),
+        }
+    }
+
+    /// Add interleaved samples with [`InputRing::channels`] channels. Drops the frames that don't fit, which happens
+    /// when the callback can't keep up.
+    pub(crate) fn push(&self, samples: &[f32]) {
+        let write_index = self.write_index.load(Ordering::Relaxed);
+        let read_index = self.read_index.load(Ordering::Acquire);
+        let free_frames = self.capacity - write_index.wrapping_sub(read_index);
+        let frames = (samples.len() / self.channels).min(free_frames);
+        for (frame_index, frame) in samples.chunks_exact(self.channels).take(frames).enumerate() {
+            let start = (write_index.wrapping_add(frame_index) & (self.capacity - 1)) * self.channels;
+            for (sample, &value) in self.samples[start..start + self.channels].iter().zip(frame) {
+                sample.store(value.to_bits(), Ordering::Relaxed);
+            }
+        }
+        self.write_index.store(write_index.wrapping_add(frames), Ordering::Release);
+        if let Some(consumer) = self.consumer.get() {
+            consumer.unpark();
+        }
+    }
+
+    /// Take exactly `block.len() / channels` frames if that many are available.
+    fn pop(&self, block: &mut [f32]) -> bool {
+        let frames = block.len() / self.channels;
+        let read_index = self.read_index.load(Ordering::Relaxed);
+        let write_index = self.write_index.load(Ordering::Acquire);
+        if write_index.wrapping_sub(read_index) < frames {
+            return false;
+        }
+        for (frame_index, frame) in block.chunks_exact_mut(self.channels).enumerate() {
+            let start = (read_index.wrapping_add(frame_index) & (self.capacity - 1)) * self.channels;
+            for (value, sample) in frame.iter_mut().zip(&self.samples[start..start + self.channels]) {
+                *value = f32::from_bits(sample.load(Ordering::Relaxed));
+            }
+        }
+        self.read_index.store(read_index.wrapping_add(frames), Ordering::Release);
+        true
+    }
+}
+
+/// Thread that calls the callback of [`start_input`] with blocks of samples from an [`InputRing`]. Stops when
+/// dropped.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) struct InputDelivery {
+    ring: Arc<InputRing>,
+    thread: Option<std::thread::JoinHandle<()>>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl InputDelivery {
+    pub(crate) fn start(
+        ring: Arc<InputRing>,
+        callback: AudioCallback,
+        sample_rate: u32,
+        buffer_size: usize,
+    ) -> Result<Self, AudioError> {
+        let thread_ring = Arc::clone(&ring);
+        let thread = std::thread::Builder::new()
+            .name("Zaplib audio input".to_string())
+            .spawn(move || {
+                let ring = thread_ring;
+                let _ = ring.consumer.set(std::thread::current());
+                let mut runner = AudioCallbackRunner::new(callback, ring.channels, sample_rate);
+                let mut block = vec![0.; buffer_size * ring.channels];
+                while !ring.stop.load(Ordering::Relaxed) {
+                    if ring.pop(&mut block) {
+                        if !runner.call(&mut block) {
+                            break;
+                        }
+                    } else {
+                        // Time out regularly, in case we miss an unpark before `consumer` was set.
+                        std::thread::park_timeout(std::time::Duration::from_millis(100));
+                    }
+                }
+            })
+            .map_err(|err| AudioError::Other(err.to_string()))?;
+        Ok(Self { ring, thread: Some(thread) })
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Drop for InputDelivery {
+    fn drop(&mut self) {
+        self.ring.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            thread.thread().unpark();
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Playing audio output, created using [`start_output`]. Stops playing when dropped.
+pub struct AudioOutput {
+    sample_rate: u32,
+    channels: u16,
+    #[cfg(target_arch = "wasm32")]
+    _stream: web::WebAudioStream,
+    #[cfg(target_os = "linux")]
+    _stream: crate::audio_alsa::AlsaStream,
+    #[cfg(target_os = "macos")]
+    _stream: crate::audio_coreaudio::CoreAudioStream,
+    #[cfg(target_os = "windows")]
+    _stream: crate::audio_wasapi::WasapiStream,
+    #[cfg(any(target_os = "android", target_os = "ios"))]
+    _stream: (),
+}
+
+impl AudioOutput {
+    /// The sample rate in Hz that was negotiated with the device.
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// Number of interleaved channels per frame.
+    pub fn channels(&self) -> u16 {
+        self.channels
+    }
+}
+
+/// Capturing audio input, created using [`start_input`]. Stops capturing when dropped.
+pub struct AudioInput {
+    sample_rate: u32,
+    channels: u16,
+    #[cfg(target_arch = "wasm32")]
+    _stream: web::WebAudioStream,
+    #[cfg(target_os = "linux")]
+    _stream: crate::audio_alsa::AlsaStream,
+    #[cfg(target_os = "macos")]
+    _stream: crate::audio_coreaudio::CoreAudioStream,
+    #[cfg(target_os = "windows")]
+    _stream: crate::audio_wasapi::WasapiStream,
+    #[cfg(any(target_os = "android", target_os = "ios"))]
+    _stream: (),
+    /// Dropped after `_stream`, so that the capture thread has stopped pushing by then.
+    #[cfg(not(target_arch = "wasm32"))]
+    _delivery: InputDelivery,
+}
+
+impl AudioInput {
+    /// The sample rate in Hz that was negotiated with the device.
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// Number of interleaved channels per frame.
+    pub fn channels(&self) -> u16 {
+        self.channels
+    }
+}
+
+/// Get the available audio output devices.
+pub fn output_devices(cx: &mut Cx) -> impl Future<Output = Result<Vec<AudioDevice>, AudioError>> {
+    #[cfg(target_arch = "wasm32")]
+    {
+        web::devices(cx, "_zaplibAudioOutputDevices")
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        let _ = cx;
+        #[cfg(target_os = "linux")]
+        let devices = crate::audio_alsa::devices(AudioDirection::Output);
+        #[cfg(target_os = "macos")]
+        let devices = crate::audio_coreaudio::devices(AudioDirection::Output);
+        #[cfg(target_os = "windows")]
+        let devices = crate::audio_wasapi::devices(AudioDirection::Output);
+        #[cfg(target_os = "android")]
+        let devices = Err(AudioError::NotSupported("Audio is not supported on Android yet".to_string()));
+        #[cfg(target_os = "ios")]
+        let devices = Err(AudioError::NotSupported("Audio is not supported on iOS yet".to_string()));
+        std::future::ready(devices)
+    }
+}
+
+/// Get the available audio input devices, such as microphones.
+///
+/// In browsers, [`AudioDevice::name`] is empty until the user has given permission to use a microphone, e.g. by
+/// calling [`start_input`].
+pub fn input_devices(cx: &mut Cx) -> impl Future<Output = Result<Vec<AudioDevice>, AudioError>> {
+    #[cfg(target_arch = "wasm32")]
+    {
+        web::devices(cx, "_zaplibAudioInputDevices")
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        let _ = cx;
+        #[cfg(target_os = "linux")]
+        let devices = crate::audio_alsa::devices(AudioDirection::Input);
+        #[cfg(target_os = "macos")]
+        let devices = crate::audio_coreaudio::devices(AudioDirection::Input);
+        #[cfg(target_os = "windows")]
+        let devices = crate::audio_wasapi::devices(AudioDirection::Input);
+        #[cfg(target_os = "android")]
+        let devices = Err(AudioError::NotSupported("Audio is not supported on Android yet".to_string()));
+        #[cfg(target_os = "ios")]
+        let devices = Err(AudioError::NotSupported("Audio is not supported on iOS yet".to_string()));
+        std::future::ready(devices)
+    }
+}
+
+/// Start playing audio, by calling `callback` whenever the device needs more samples. See the
+/// [module documentation](self) for an example.
+///
+/// The callback runs on a realtime thread, so it should not block (e.g. by waiting for locks held by other threads
+/// for long, or doing I/O), or you'll hear glitches.
+pub fn start_output(
+    cx: &mut Cx,
+    options: AudioOutputOptions,
+    callback: impl FnMut(&mut AudioBuffer) + Send + 'static,
+) -> impl Future<Output = Result<AudioOutput, AudioError>> {
+    let callback: AudioCallback = Box::new(callback);
+    let channels = options.channels;
+    #[cfg(target_arch = "wasm32")]
+    {
+        let future = web::start(cx, &options);
+        async move {
+            let (stream, sample_rate) = future.await?;
+            let stream = stream.run(callback, sample_rate);
+            Ok(AudioOutput { sample_rate, channels, _stream: stream })
+        }
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        let _ = cx;
+        #[cfg(target_os = "linux")]
+        let result = crate::audio_alsa::AlsaStream::start(&options, callback);
+        #[cfg(target_os = "macos")]
+        let result = crate::audio_coreaudio::CoreAudioStream::start(&options, callback);
+        #[cfg(target_os = "windows")]
+        let result = crate::audio_wasapi::WasapiStream::start(&options, callback);
+        #[cfg(target_os = "android")]
+        let result: Result<((), u32), AudioError> = {
+            drop(callback);
+            Err(AudioError::NotSupported("Audio output is not supported on Android yet".to_string()))
+        };
+        #[cfg(target_os = "ios")]
+        let result: Result<((), u32), AudioError> = {
+            drop(callback);
+            Err(AudioError::NotSupported("Audio output is not supported on iOS yet".to_string()))
+        };
+        std::future::ready(result.map(|(stream, sample_rate)| AudioOutput { sample_rate, channels, _stream: stream }))
+    }
+}
+
+/// Start capturing audio, by calling `callback` with every [`AudioInputOptions::buffer_size`] frames of captured
+/// samples. See the [module documentation](self) for an example.
+///
+/// The callback runs on its own thread, so it may take a while, as long as it keeps up on average.
+pub fn start_input(
+    cx: &mut Cx,
+    options: AudioInputOptions,
+    callback: impl FnMut(&mut AudioBuffer) + Send + 'static,
+) -> impl Future<Output = Result<AudioInput, AudioError>> {
+    let callback: AudioCallback = Box::new(callback);
+    let channels = options.channels.max(1);
+    let buffer_size = options.buffer_size.unwrap_or(DEFAULT_INPUT_BUFFER_SIZE).max(1);
+    #[cfg(target_arch = "wasm32")]
+    {
+        let future = web::start_input(cx, &options, buffer_size);
+        async move {
+            let (stream, sample_rate) = future.await?;
+            let stream = stream.run_input(callback, sample_rate);
+            Ok(AudioInput { sample_rate, channels, _stream: stream })
+        }
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        let _ = cx;
+        let ring = Arc::new(InputRing::new(channels as usize, buffer_size as usize));
+        #[cfg(target_os = "linux")]
+        let result = crate::audio_alsa::AlsaStream::start_input(&options, Arc::clone(&ring));
+        #[cfg(target_os = "macos")]
+        let result = crate::audio_coreaudio::CoreAudioStream::start_input(&options, Arc::clone(&ring));
+        #[cfg(target_os = "windows")]
+        let result = crate::audio_wasapi::WasapiStream::start_input(&options, Arc::clone(&ring));
+        #[cfg(target_os = "android")]
+        let result: Result<((), u32), AudioError> = {
+            drop(ring);
+            Err(AudioError::NotSupported("Audio input is not supported on Android yet".to_string()))
+        };
+        #[cfg(target_os = "ios")]
+        let result: Result<((), u32), AudioError> = {
+            drop(ring);
+            Err(AudioError::NotSupported("Audio input is not supported on iOS yet".to_string()))
+        };
+        std::future::ready(result.and_then(|(stream, sample_rate)| {
+            let delivery = InputDelivery::start(ring, callback, sample_rate, buffer_size as usize)?;
+            Ok(AudioInput { sample_rate, channels, _stream: stream, _delivery: delivery })
+        }))
+    }
+}
+
+/// Backend based on an `AudioWorklet`; see `audio.ts`.
+///
+/// We allocate a ring buffer and a few indices in Wasm memory, which is shared with the worklet. For output, a
+/// thread (web worker) keeps the ring buffer filled up to the requested buffer size by calling the callback, and the
+/// worklet reads from it. For input it's the other way around. This way the callback can run in Rust without the
+/// worklet having to instantiate the Wasm module.
+#[cfg(target_arch = "wasm32")]
+mod web {
+    use super::*;
+    use std::sync::atomic::{AtomicI32, Ordering};
+
+    /// Indices into [`RingBuffer::state`]; keep in sync with `audio.ts`.
+    const WRITE_INDEX: usize = 0;
+    const READ_INDEX: usize = 1;
+    const STATUS: usize = 2;
+    /// Values for `state[STATUS]`.
+    const STATUS_RUNNING: i32 = 0;
+    const STATUS_STOP_REQUESTED: i32 = 1;
+    const STATUS_STOPPED: i32 = 2;
+
+    /// The worklet processes 128 frames at a time, so there's no point in filling less.
+    const FRAMES_PER_CALLBACK: u32 = 128;
+    const DEFAULT_BUFFER_SIZE: u32 = 1024;
+
+    /// Memory that is shared with the worklet. `state` contains the write and read index (in frames, wrapping
+    /// around), and the status.
+    struct RingBuffer {
+        state: [AtomicI32; 3],
+        samples: Vec<f32>,
+        /// Number of frames in `samples`; a power of two.
+        capacity: u32,
+        channels: usize,
+        /// For output, the number of frames to keep buffered. For input, the number of frames per callback.
+        buffer_size: u32,
+    }
+
+    impl RingBuffer {
+        /// Wait for the worklet to acknowledge [`STATUS_STOP_REQUESTED`], and free the ring buffer if it did.
+        unsafe fn free_when_stopped(ring_ptr: usize) {
+            let ring = &*(ring_ptr as *const RingBuffer);
+            core::arch::wasm32::memory_atomic_wait32(
+                &ring.state[STATUS] as *const AtomicI32 as *mut i32,
+                STATUS_STOP_REQUESTED,
+                1_000_000_000,
+            );
+            if ring.state[STATUS].load(Ordering::SeqCst) == STATUS_STOPPED {
+                drop(Box::from_raw(ring_ptr as *mut RingBuffer));
+            }
+        }
+    }
+
+    /// Stops the worklet and the thread that fills the ring buffer when dropped.
+    ///
+    /// The [`RingBuffer`] is freed by that thread, but only after the worklet has acknowledged that it stopped
+    /// reading. Otherwise (e.g. when the page is being closed, or when this is dropped before [`WebAudioStream::run`]
+    /// was called) we leak it, to be safe.
+    pub(super) struct WebAudioStream {
+        /// Pointer to a leaked [`RingBuffer`]. Stored as [`usize`] so that [`AudioOutput`] is [`Send`].
+        ring_ptr: usize,
+    }
+
+    impl WebAudioStream {
+        fn ring(&self) -> &RingBuffer {
+            unsafe { &*(self.ring_ptr as *const RingBuffer) }
+        }
+
+        /// Start a thread that keeps the ring buffer filled.
+        pub(super) fn run(self, callback: AudioCallback, sample_rate: u32) -> Self {
+            let ring_ptr = self.ring_ptr;
+            universal_thread::spawn(move || {
+                let ring = unsafe { &mut *(ring_ptr as *mut RingBuffer) };
+                let mut runner = AudioCallbackRunner::new(callback, ring.channels, sample_rate);
+                let mut block = vec![0.; FRAMES_PER_CALLBACK as usize * ring.channels];
+                while ring.state[STATUS].load(Ordering::SeqCst) == STATUS_RUNNING {
+                    let read_index = ring.state[READ_INDEX].load(Ordering::SeqCst);
+                    let write_index = ring.state[WRITE_INDEX].load(Ordering::SeqCst);
+                    if write_index.wrapping_sub(read_index) as u32 + FRAMES_PER_CALLBACK > ring.buffer_size {
+                        // Wait for the worklet to read more (it notifies on `READ_INDEX`), with a timeout so that
+                        // we notice when we should stop.
+                        unsafe {
+                            core::arch::wasm32::memory_atomic_wait32(
+                                &ring.state[READ_INDEX] as *const AtomicI32 as *mut i32,
+                                read_index,
+                                10_000_000,
+                            );
+                        }
+                        continue;
+                    }
+                    runner.fill(&mut block);
+                    for (frame_index, frame) in block.chunks_exact(ring.channels).enumerate() {
+                        let ring_frame = (write_index as u32).wrapping_add(frame_index as u32) & (ring.capacity - 1);
+                        let start = ring_frame as usize * ring.channels;
+                        ring.samples[start..start + ring.channels].copy_from_slice(frame);
+                    }
+                    ring.state[WRITE_INDEX].store(write_index.wrapping_add(FRAMES_PER_CALLBACK as i32), Ordering::SeqCst);
+                }
+                unsafe { RingBuffer::free_when_stopped(ring_ptr) };
+            });
+            self
+        }
+
+        /// Start a thread that calls the callback whenever the worklet has written [`RingBuffer::buffer_size`]
+        /// frames.
+        pub(super) fn run_input(self, callback: AudioCallback, sample_rate: u32) -> Self {
+            let ring_ptr = self.ring_ptr;
+            universal_thread::spawn(move || {
+                let ring = unsafe { &*(ring_ptr as *const RingBuffer) };
+                let mut runner = AudioCallbackRunner::new(callback, ring.channels, sample_rate);
+                let mut block = vec![0.; ring.buffer_size as usize * ring.channels];
+                while ring.state[STATUS].load(Ordering::SeqCst) == STATUS_RUNNING {
+                    let read_index = ring.state[READ_INDEX].load(Ordering::SeqCst);
+                    let write_index = ring.state[WRITE_INDEX].load(Ordering::SeqCst);
+                    if (write_index.wrapping_sub(read_index) as u32) < ring.buffer_size {
+                        // Wait for the worklet to write more (it notifies on `WRITE_INDEX`), with a timeout so that
+                        // we notice when we should stop.
+                        unsafe {
+                            core::arch::wasm32::memory_atomic_wait32(
+                                &ring.state[WRITE_INDEX] as *const AtomicI32 as *mut i32,
+                                write_index,
+                                10_000_000,
+                            );
+                        }
+                        continue;
+                    }
+                    for (frame_index, frame) in block.chunks_exact_mut(ring.channels).enumerate() {
+                        let ring_frame = (read_index as u32).wrapping_add(frame_index as u32) & (ring.capacity - 1);
+                        let start = ring_frame as usize * ring.channels;
+                        frame.copy_from_slice(&ring.samples[start..start + ring.channels]);
+                    }
+                    ring.state[READ_INDEX].store(read_index.wrapping_add(ring.buffer_size as i32), Ordering::SeqCst);
+                    if !runner.call(&mut block) {
+                        break;
+                    }
+                }
+                // After a panic we're still running, but nobody reads anymore; the worklet drops what doesn't fit.
+                unsafe { RingBuffer::free_when_stopped(ring_ptr) };
+            });
+            self
+        }
+    }
+
+    impl Drop for WebAudioStream {
+        fn drop(&mut self) {
+            // The worklet sets `STATUS_STOPPED` in response, and closes the `AudioContext`.
+            self.ring().state[STATUS].store(STATUS_STOP_REQUESTED, Ordering::SeqCst);
+        }
+    }
+
+    /// List devices using `_zaplibAudioOutputDevices` or `_zaplibAudioInputDevices`.
+    pub(super) fn devices(cx: &mut Cx, name: &str) -> impl Future<Output = Result<Vec<AudioDevice>, AudioError>> {
+        let future = cx.call_js_async(name, vec![], None);
+        async move {
+            let params: Vec<String> = future.await?.into_iter().map(ZapParam::into_string).collect();
+            Ok(params
+                .chunks_exact(3)
+                .map(|device| AudioDevice { id: device[0].clone(), name: device[1].clone(), is_default: device[2] == "1" })
+                .collect())
+        }
+    }
+
+    /// Create the `AudioContext` and output worklet. Resolves with a [`WebAudioStream`] that should be
+    /// [run](WebAudioStream::run), and the sample rate of the `AudioContext`.
+    pub(super) fn start(
+        cx: &mut Cx,
+        options: &AudioOutputOptions,
+    ) -> impl Future<Output = Result<(WebAudioStream, u32), AudioError>> {
+        let buffer_size = options.buffer_size.unwrap_or(DEFAULT_BUFFER_SIZE).max(FRAMES_PER_CALLBACK * 2);
+        let capacity = buffer_size.next_power_of_two() * 2;
+        start_worklet(cx, "_zaplibAudioStart", &options.device_id, options.sample_rate, options.channels, buffer_size, capacity)
+    }
+
+    /// Ask for permission to use the microphone, and create the `AudioContext` and input worklet. Resolves with a
+    /// [`WebAudioStream`] that should be [run](WebAudioStream::run_input), and the sample rate of the `AudioContext`.
+    pub(super) fn start_input(
+        cx: &mut Cx,
+        options: &AudioInputOptions,
+        buffer_size: u32,
+    ) -> impl Future<Output = Result<(WebAudioStream, u32), AudioError>> {
+        // Leave room for the worklet to keep writing while the callback runs.
+        let capacity = (buffer_size * 4).max(16384).next_power_of_two();
+        start_worklet(
+            cx,
+            "_zaplibAudioInputStart",
+            &options.device_id,
+            options.sample_rate,
+            options.channels,
+            buffer_size,
+            capacity,
+        )
+    }
+
+    /// Allocate a [`RingBuffer`] and call `name` in `audio.ts` to create the worklet.
+    fn start_worklet(
+        cx: &mut Cx,
+        name: &str,
+        device_id: &Option<String>,
+        sample_rate: Option<u32>,
+        channels: u16,
+        buffer_size: u32,
+        capacity: u32,
+    ) -> impl Future<Output = Result<(WebAudioStream, u32), AudioError>> {
+        let channels = channels.max(1) as usize;
+        let ring = Box::new(RingBuffer {
+            state: [AtomicI32::new(0), AtomicI32::new(0), AtomicI32::new(STATUS_RUNNING)],
+            samples: vec![0.; capacity as usize * channels],
+            capacity,
+            channels,
+            buffer_size,
+        });
+        let params = vec![
+            device_id.clone().unwrap_or_default().into_param(),
+            sample_rate.unwrap_or(0).to_string().into_param(),
+            channels.to_string().into_param(),
+            (ring.state.as_ptr() as usize).to_string().into_param(),
+            (ring.samples.as_ptr() as usize).to_string().into_param(),
+            capacity.to_string().into_param(),
+        ];
+        let ring_ptr = Box::into_raw(ring) as usize;
+        let future = cx.call_js_async(name, params, None);
+        async move {
+            match future.await {
+                Ok(params) => {
+                    let stream = WebAudioStream { ring_ptr };
+                    let sample_rate =
+                        params[0].as_str().parse().map_err(|_| AudioError::Other("Invalid sample rate".to_string()))?;
+                    Ok((stream, sample_rate))
+                }
+                Err(err) => {
+                    // The worklet was not created, so nothing else uses the ring buffer.
+                    drop(unsafe { Box::from_raw(ring_ptr as *mut RingBuffer) });
+                    Err(err.into())
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_callback_runner() {
+        let mut calls = 0;
+        let mut runner = AudioCallbackRunner::new(
+            Box::new(move |buffer: &mut AudioBuffer| {
+                calls += 1;
+                assert_eq!(buffer.frame_count(), 3);
+                assert!(buffer.samples.iter().all(|&sample| sample == 0.));
+                for (index, frame) in buffer.frames_mut().enumerate() {
+                    frame[0] = index as f32;
+                }
+                if calls == 2 {
+                    panic!("Callback panicked");
+                }
+            }),
+            2,
+            48000,
+        );
+        let mut samples = [1.; 6];
+        runner.fill(&mut samples);
+        assert_eq!(samples, [0., 0., 1., 0., 2., 0.]);
+        // After a panic we only output silence, and don't call the callback anymore.
+        runner.fill(&mut samples);
+        assert_eq!(samples, [0.; 6]);
+        samples = [1.; 6];
+        runner.fill(&mut samples);
+        assert_eq!(samples, [0.; 6]);
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[test]
+    fn test_input_ring() {
+        let ring = InputRing::new(2, 1);
+        assert_eq!(ring.capacity, 16384);
+        let mut block = [0.; 4];
+        assert!(!ring.pop(&mut block));
+        ring.push(&[1., 2., 3., 4., 5., 6.]);
+        assert!(ring.pop(&mut block));
+        assert_eq!(block, [1., 2., 3., 4.]);
+        assert!(!ring.pop(&mut block));
+
+        // Wrap around, and drop what doesn't fit.
+        let samples: Vec<f32> = (0..ring.capacity * 2 + 2).map(|index| index as f32).collect();
+        ring.push(&samples);
+        assert!(ring.pop(&mut block));
+        assert_eq!(block, [5., 6., 0., 1.]);
+        let mut rest = vec![0.; (ring.capacity - 2) * 2];
+        assert!(ring.pop(&mut rest));
+        assert_eq!(rest[rest.len() - 2..], [samples.len() as f32 - 6., samples.len() as f32 - 5.]);
+        assert!(!ring.pop(&mut block));
+    }
+}
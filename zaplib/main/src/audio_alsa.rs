@@ -0,0 +1,293 @@
+//! ALSA backend for [`crate::audio`].
+//!
+//! We load `libasound` at runtime using `dlopen`, so that apps that don't use audio (or run on systems
+//! without ALSA) don't need it.
+
+use crate::audio::*;
+use std::ffi::{CStr, CString};
+use std::os::raw::{c_char, c_int, c_long, c_uint, c_ulong, c_void};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+
+const SND_PCM_STREAM_PLAYBACK: c_int = 0;
+const SND_PCM_STREAM_CAPTURE: c_int = 1;
+const SND_PCM_FORMAT_FLOAT_LE: c_int = 14;
+const SND_PCM_ACCESS_RW_INTERLEAVED: c_int = 3;
+const DEFAULT_SAMPLE_RATE: u32 = 48000;
+const DEFAULT_LATENCY_US: u32 = 20_000;
+
+#[allow(non_camel_case_types)]
+type snd_pcm_t = c_void;
+
+/// Functions that we use from `libasound`.
+struct Alsa {
+    snd_pcm_open: unsafe extern "C" fn(*mut *mut snd_pcm_t, *const c_char, c_int, c_int) -> c_int,
+    snd_pcm_set_params: unsafe extern "C" fn(*mut snd_pcm_t, c_int, c_int, c_uint, c_uint, c_int, c_uint) -> c_int,
+    snd_pcm_get_params: unsafe extern "C" fn(*mut snd_pcm_t, *mut c_ulong, *mut c_ulong) -> c_int,
+    snd_pcm_writei: unsafe extern "C" fn(*mut snd_pcm_t, *const c_void, c_ulong) -> c_long,
+    snd_pcm_readi: unsafe extern "C" fn(*mut snd_pcm_t, *mut c_void, c_ulong) -> c_long,
+    snd_pcm_recover: unsafe extern "C" fn(*mut snd_pcm_t, c_int, c_int) -> c_int,
+    snd_pcm_drop: unsafe extern "C" fn(*mut snd_pcm_t) -> c_int,
+    snd_pcm_close: unsafe extern "C" fn(*mut snd_pcm_t) -> c_int,
+    snd_strerror: unsafe extern "C" fn(c_int) -> *const c_char,
+    snd_device_name_hint: unsafe extern "C" fn(c_int, *const c_char, *mut *mut *mut c_void) -> c_int,
+    snd_device_name_get_hint: unsafe extern "C" fn(*const c_void, *const c_char) -> *mut c_char,
+    snd_device_name_free_hint: unsafe extern "C" fn(*mut *mut c_void) -> c_int,
+}
+
+static ALSA: once_cell::sync::OnceCell<Result<Alsa, AudioError>> = once_cell::sync::OnceCell::new();
+
+impl Alsa {
+    fn get() -> Result<&'static Alsa, AudioError> {
+        ALSA.get_or_init(|| unsafe { Self::load() }).as_ref().map_err(Clone::clone)
+    }
+
+    unsafe fn load() -> Result<Alsa, AudioError> {
+        // Never closed, since we keep the function pointers around.
+        let lib_name = CString::new("libasound.so.2").unwrap();
+        let lib = libc::dlopen(lib_name.as_ptr(), libc::RTLD_NOW);
+        if lib.is_null() {
+            return Err(AudioError::NotSupported("Could not load libasound.so.2".to_string()));
+        }
+        macro_rules! sym {
+            ($name: ident) => {{
+                let ptr = libc::dlsym(lib, concat!(stringify!($name), "\0").as_ptr() as *const c_char);
+                if ptr.is_null() {
+                    return Err(AudioError::NotSupported(format!("Could not find {} in libasound", stringify!($name))));
+                }
+                // Function pointers have the same size as data pointers on the platforms we support.
+                std::mem::transmute_copy(&ptr)
+            }};
+        }
+        Ok(Alsa {
+            snd_pcm_open: sym!(snd_pcm_open),
+            snd_pcm_set_params: sym!(snd_pcm_set_params),
+            snd_pcm_get_params: sym!(snd_pcm_get_params),
+            snd_pcm_writei: sym!(snd_pcm_writei),
+            snd_pcm_readi: sym!(snd_pcm_readi),
+            snd_pcm_recover: sym!(snd_pcm_recover),
+            snd_pcm_drop: sym!(snd_pcm_drop),
+            snd_pcm_close: sym!(snd_pcm_close),
+            snd_strerror: sym!(snd_strerror),
+            snd_device_name_hint: sym!(snd_device_name_hint),
+            snd_device_name_get_hint: sym!(snd_device_name_get_hint),
+            snd_device_name_free_hint: sym!(snd_device_name_free_hint),
+        })
+    }
+
+    fn error(&self, function: &str, err: c_int) -> AudioError {
+        let message = unsafe { CStr::from_ptr((self.snd_strerror)(err)) }.to_string_lossy();
+        if err == -libc::ENOENT || err == -libc::ENODEV {
+            AudioError::DeviceNotFound
+        } else {
+            AudioError::Other(format!("{} failed: {}", function, message))
+        }
+    }
+}
+
+/// Get a hint (such as "NAME" or "DESC") from an entry of `snd_device_name_hint`.
+unsafe fn get_hint(alsa: &Alsa, hint: *const c_void, id: &str) -> Option<String> {
+    let id = CString::new(id).unwrap();
+    let value = (alsa.snd_device_name_get_hint)(hint, id.as_ptr());
+    if value.is_null() {
+        return None;
+    }
+    let string = CStr::from_ptr(value).to_string_lossy().into_owned();
+    libc::free(value as *mut c_void);
+    Some(string)
+}
+
+pub(crate) fn devices(direction: AudioDirection) -> Result<Vec<AudioDevice>, AudioError> {
+    let alsa = Alsa::get()?;
+    let wanted_ioid = match direction {
+        AudioDirection::Output => "Output",
+        AudioDirection::Input => "Input",
+    };
+    let mut devices = vec![];
+    unsafe {
+        let mut hints: *mut *mut c_void = std::ptr::null_mut();
+        let iface = CString::new("pcm").unwrap();
+        let err = (alsa.snd_device_name_hint)(-1, iface.as_ptr(), &mut hints);
+        if err < 0 {
+            return Err(alsa.error("snd_device_name_hint", err));
+        }
+        let mut hint = hints;
+        while !(*hint).is_null() {
+            // A missing "IOID" means that the device supports both input and output.
+            let matches_direction = !matches!(get_hint(alsa, *hint, "IOID").as_deref(), Some(ioid) if ioid != wanted_ioid);
+            if let (true, Some(id)) = (matches_direction, get_hint(alsa, *hint, "NAME")) {
+                // Descriptions can span multiple lines, e.g. "HDA Intel PCH\nFront output / input".
+                let name = get_hint(alsa, *hint, "DESC").map_or_else(|| id.clone(), |desc| desc.replace('\n', ", "));
+                let is_default = id == "default";
+                devices.push(AudioDevice { id, name, is_default });
+            }
+            hint = hint.add(1);
+        }
+        (alsa.snd_device_name_free_hint)(hints);
+    }
+    Ok(devices)
+}
+
+/// Raw pointer to the PCM, so that we can move it to the audio thread.
+struct Pcm(*mut snd_pcm_t);
+unsafe impl Send for Pcm {}
+
+/// Open a PCM for playback or capture, with interleaved `f32` samples. Returns the PCM, the sample rate, and the
+/// number of frames per period.
+fn open_pcm(
+    alsa: &Alsa,
+    direction: AudioDirection,
+    device_id: Option<&str>,
+    channels: usize,
+    sample_rate: Option<u32>,
+    buffer_size: Option<u32>,
+) -> Result<(Pcm, u32, usize), AudioError> {
+    let device_name = CString::new(device_id.unwrap_or("default")).map_err(|_| AudioError::DeviceNotFound)?;
+    let stream = match direction {
+        AudioDirection::Output => SND_PCM_STREAM_PLAYBACK,
+        AudioDirection::Input => SND_PCM_STREAM_CAPTURE,
+    };
+    // ALSA's "plug" layer (used by "default") resamples if necessary, so we get the rate we ask for.
+    let sample_rate = sample_rate.unwrap_or(DEFAULT_SAMPLE_RATE);
+    let latency_us = buffer_size.map_or(DEFAULT_LATENCY_US, |frames| {
+        // We request a latency of about two buffers, and ALSA picks the period size based on that.
+        (frames as u64 * 2 * 1_000_000 / sample_rate as u64) as u32
+    });
+
+    let mut pcm = std::ptr::null_mut();
+    unsafe {
+        let err = (alsa.snd_pcm_open)(&mut pcm, device_name.as_ptr(), stream, 0);
+        if err < 0 {
+            return Err(alsa.error("snd_pcm_open", err));
+        }
+        let err = (alsa.snd_pcm_set_params)(
+            pcm,
+            SND_PCM_FORMAT_FLOAT_LE,
+            SND_PCM_ACCESS_RW_INTERLEAVED,
+            channels as c_uint,
+            sample_rate,
+            1,
+            latency_us,
+        );
+        if err < 0 {
+            (alsa.snd_pcm_close)(pcm);
+            return Err(alsa.error("snd_pcm_set_params", err));
+        }
+    }
+    let mut buffer_frames = 0;
+    let mut period_frames = 0;
+    let err = unsafe { (alsa.snd_pcm_get_params)(pcm, &mut buffer_frames, &mut period_frames) };
+    if err < 0 || period_frames == 0 {
+        period_frames = buffer_size.unwrap_or(512) as c_ulong;
+    }
+    Ok((Pcm(pcm), sample_rate, period_frames as usize))
+}
+
+/// Plays or captures audio on a thread that writes to or reads from the PCM (which blocks until ALSA's buffer has
+/// room or data).
+pub(crate) struct AlsaStream {
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl AlsaStream {
+    pub(crate) fn start(options: &AudioOutputOptions, callback: AudioCallback) -> Result<(Self, u32), AudioError> {
+        let alsa = Alsa::get()?;
+        let channels = options.channels.max(1) as usize;
+        let (pcm, sample_rate, period_frames) = open_pcm(
+            alsa,
+            AudioDirection::Output,
+            options.device_id.as_deref(),
+            channels,
+            options.sample_rate,
+            options.buffer_size,
+        )?;
+        let stream = Self::spawn(alsa, pcm, move |alsa, pcm, stop| {
+            let mut runner = AudioCallbackRunner::new(callback, channels, sample_rate);
+            let mut samples = vec![0.; period_frames * channels];
+            'outer: while !stop.load(Ordering::Relaxed) {
+                runner.fill(&mut samples);
+                let mut offset = 0;
+                while offset < period_frames {
+                    let remaining = &samples[offset * channels..];
+                    let written = unsafe {
+                        (alsa.snd_pcm_writei)(pcm.0, remaining.as_ptr() as *const c_void, (remaining.len() / channels) as c_ulong)
+                    };
+                    if written < 0 {
+                        // E.g. an underrun (-EPIPE); try to recover.
+                        let err = unsafe { (alsa.snd_pcm_recover)(pcm.0, written as c_int, 1) };
+                        if err < 0 {
+                            log!("{}", alsa.error("snd_pcm_writei", err));
+                            break 'outer;
+                        }
+                    } else {
+                        offset += written as usize;
+                    }
+                }
+            }
+        })?;
+        Ok((stream, sample_rate))
+    }
+
+    pub(crate) fn start_input(options: &AudioInputOptions, ring: Arc<InputRing>) -> Result<(Self, u32), AudioError> {
+        let alsa = Alsa::get()?;
+        let channels = options.channels.max(1) as usize;
+        let (pcm, sample_rate, period_frames) = open_pcm(
+            alsa,
+            AudioDirection::Input,
+            options.device_id.as_deref(),
+            channels,
+            options.sample_rate,
+            options.buffer_size,
+        )?;
+        let stream = Self::spawn(alsa, pcm, move |alsa, pcm, stop| {
+            let mut samples = vec![0.; period_frames * channels];
+            while !stop.load(Ordering::Relaxed) {
+                let read = unsafe { (alsa.snd_pcm_readi)(pcm.0, samples.as_mut_ptr() as *mut c_void, period_frames as c_ulong) };
+                if read < 0 {
+                    // E.g. an overrun (-EPIPE); try to recover.
+                    let err = unsafe { (alsa.snd_pcm_recover)(pcm.0, read as c_int, 1) };
+                    if err < 0 {
+                        log!("{}", alsa.error("snd_pcm_readi", err));
+                        break;
+                    }
+                } else {
+                    ring.push(&samples[..read as usize * channels]);
+                }
+            }
+        })?;
+        Ok((stream, sample_rate))
+    }
+
+    /// Run `f` on a new thread, and close the PCM when it returns.
+    fn spawn(
+        alsa: &'static Alsa,
+        pcm: Pcm,
+        f: impl FnOnce(&'static Alsa, &Pcm, &AtomicBool) + Send + 'static,
+    ) -> Result<Self, AudioError> {
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread_stop = Arc::clone(&stop);
+        let thread = std::thread::Builder::new()
+            .name("Zaplib audio".to_string())
+            .spawn(move || {
+                let pcm = pcm;
+                f(alsa, &pcm, &thread_stop);
+                unsafe {
+                    (alsa.snd_pcm_drop)(pcm.0);
+                    (alsa.snd_pcm_close)(pcm.0);
+                }
+            })
+            .map_err(|err| AudioError::Other(err.to_string()))?;
+        Ok(Self { stop, thread: Some(thread) })
+    }
+}
+
+impl Drop for AlsaStream {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
@@ -0,0 +1,610 @@
+//! CoreAudio backend for [`crate::audio`], using an output `AudioUnit`. For input we use a HAL output unit with
+//! only its input side enabled, which is how CoreAudio does capture.
+
+#![allow(non_upper_case_globals)]
+#![allow(non_snake_case)]
+
+use crate::audio::*;
+use crate::cx_apple::{class, id, msg_send, nsstring_to_string, sel, sel_impl};
+use std::ffi::c_void;
+use std::ptr;
+use std::sync::Arc;
+
+type OSStatus = i32;
+type AudioObjectID = u32;
+type AudioUnit = *mut c_void;
+type AudioComponent = *mut c_void;
+
+#[repr(C)]
+struct AudioObjectPropertyAddress {
+    mSelector: u32,
+    mScope: u32,
+    mElement: u32,
+}
+
+#[repr(C)]
+struct AudioComponentDescription {
+    componentType: u32,
+    componentSubType: u32,
+    componentManufacturer: u32,
+    componentFlags: u32,
+    componentFlagsMask: u32,
+}
+
+#[repr(C)]
+#[derive(Default)]
+struct AudioStreamBasicDescription {
+    mSampleRate: f64,
+    mFormatID: u32,
+    mFormatFlags: u32,
+    mBytesPerPacket: u32,
+    mFramesPerPacket: u32,
+    mBytesPerFrame: u32,
+    mChannelsPerFrame: u32,
+    mBitsPerChannel: u32,
+    mReserved: u32,
+}
+
+#[repr(C)]
+struct AudioBufferListBuffer {
+    mNumberChannels: u32,
+    mDataByteSize: u32,
+    mData: *mut c_void,
+}
+
+#[repr(C)]
+struct AudioBufferList {
+    mNumberBuffers: u32,
+    mBuffers: [AudioBufferListBuffer; 1],
+}
+
+type AURenderCallback = unsafe extern "C" fn(
+    inRefCon: *mut c_void,
+    ioActionFlags: *mut u32,
+    inTimeStamp: *const c_void,
+    inBusNumber: u32,
+    inNumberFrames: u32,
+    ioData: *mut AudioBufferList,
+) -> OSStatus;
+
+#[repr(C)]
+struct AURenderCallbackStruct {
+    inputProc: AURenderCallback,
+    inputProcRefCon: *mut c_void,
+}
+
+const kAudioObjectSystemObject: AudioObjectID = 1;
+const kAudioObjectPropertyScopeGlobal: u32 = 0x676c6f62; // 'glob'
+const kAudioObjectPropertyScopeOutput: u32 = 0x6f757470; // 'outp'
+const kAudioObjectPropertyScopeInput: u32 = 0x696e7074; // 'inpt'
+const kAudioObjectPropertyElementMaster: u32 = 0;
+const kAudioHardwarePropertyDevices: u32 = 0x64657623; // 'dev#'
+const kAudioHardwarePropertyDefaultOutputDevice: u32 = 0x644f7574; // 'dOut'
+const kAudioHardwarePropertyDefaultInputDevice: u32 = 0x64496e20; // 'dIn '
+const kAudioDevicePropertyStreams: u32 = 0x73746d23; // 'stm#'
+const kAudioDevicePropertyDeviceUID: u32 = 0x75696420; // 'uid '
+const kAudioObjectPropertyName: u32 = 0x6c6e616d; // 'lnam'
+
+const kAudioUnitType_Output: u32 = 0x61756f75; // 'auou'
+const kAudioUnitSubType_HALOutput: u32 = 0x6168616c; // 'ahal'
+const kAudioUnitSubType_DefaultOutput: u32 = 0x64656620; // 'def '
+const kAudioUnitManufacturer_Apple: u32 = 0x6170706c; // 'appl'
+const kAudioUnitProperty_StreamFormat: u32 = 8;
+const kAudioUnitProperty_MaximumFramesPerSlice: u32 = 14;
+const kAudioUnitProperty_SetRenderCallback: u32 = 23;
+const kAudioOutputUnitProperty_CurrentDevice: u32 = 2000;
+const kAudioOutputUnitProperty_EnableIO: u32 = 2003;
+const kAudioOutputUnitProperty_SetInputCallback: u32 = 2005;
+const kAudioDevicePropertyBufferFrameSize: u32 = 0x6673697a; // 'fsiz'
+const kAudioUnitScope_Global: u32 = 0;
+const kAudioUnitScope_Input: u32 = 1;
+const kAudioUnitScope_Output: u32 = 2;
+const kAudioFormatLinearPCM: u32 = 0x6c70636d; // 'lpcm'
+const kAudioFormatFlagIsFloat: u32 = 1;
+const kAudioFormatFlagIsPacked: u32 = 8;
+/// Elements ("buses") of a HAL output unit.
+const OUTPUT_ELEMENT: u32 = 0;
+const INPUT_ELEMENT: u32 = 1;
+/// `AVAuthorizationStatus` values.
+const AVAuthorizationStatusRestricted: i64 = 1;
+const AVAuthorizationStatusDenied: i64 = 2;
+
+#[link(name = "CoreAudio", kind = "framework")]
+extern "C" {
+    fn AudioObjectGetPropertyDataSize(
+        inObjectID: AudioObjectID,
+        inAddress: *const AudioObjectPropertyAddress,
+        inQualifierDataSize: u32,
+        inQualifierData: *const c_void,
+        outDataSize: *mut u32,
+    ) -> OSStatus;
+    fn AudioObjectGetPropertyData(
+        inObjectID: AudioObjectID,
+        inAddress: *const AudioObjectPropertyAddress,
+        inQualifierDataSize: u32,
+        inQualifierData: *const c_void,
+        ioDataSize: *mut u32,
+        outData: *mut c_void,
+    ) -> OSStatus;
+    fn AudioObjectSetPropertyData(
+        inObjectID: AudioObjectID,
+        inAddress: *const AudioObjectPropertyAddress,
+        inQualifierDataSize: u32,
+        inQualifierData: *const c_void,
+        inDataSize: u32,
+        inData: *const c_void,
+    ) -> OSStatus;
+}
+
+#[link(name = "AudioToolbox", kind = "framework")]
+extern "C" {
+    fn AudioComponentFindNext(inComponent: AudioComponent, inDesc: *const AudioComponentDescription) -> AudioComponent;
+    fn AudioComponentInstanceNew(inComponent: AudioComponent, outInstance: *mut AudioUnit) -> OSStatus;
+    fn AudioComponentInstanceDispose(inInstance: AudioUnit) -> OSStatus;
+    fn AudioUnitInitialize(inUnit: AudioUnit) -> OSStatus;
+    fn AudioUnitUninitialize(inUnit: AudioUnit) -> OSStatus;
+    fn AudioUnitSetProperty(
+        inUnit: AudioUnit,
+        inID: u32,
+        inScope: u32,
+        inElement: u32,
+        inData: *const c_void,
+        inDataSize: u32,
+    ) -> OSStatus;
+    fn AudioUnitGetProperty(
+        inUnit: AudioUnit,
+        inID: u32,
+        inScope: u32,
+        inElement: u32,
+        outData: *mut c_void,
+        ioDataSize: *mut u32,
+    ) -> OSStatus;
+    fn AudioOutputUnitStart(ci: AudioUnit) -> OSStatus;
+    fn AudioOutputUnitStop(ci: AudioUnit) -> OSStatus;
+    fn AudioUnitRender(
+        inUnit: AudioUnit,
+        ioActionFlags: *mut u32,
+        inTimeStamp: *const c_void,
+        inOutputBusNumber: u32,
+        inNumberFrames: u32,
+        ioData: *mut AudioBufferList,
+    ) -> OSStatus;
+}
+
+#[link(name = "AVFoundation", kind = "framework")]
+extern "C" {
+    static AVMediaTypeAudio: id;
+}
+
+#[link(name = "CoreFoundation", kind = "framework")]
+extern "C" {
+    fn CFRelease(cf: *const c_void);
+}
+
+fn check(function: &str, status: OSStatus) -> Result<(), AudioError> {
+    if status == 0 {
+        Ok(())
+    } else {
+        Err(AudioError::Other(format!("{} failed with status {}", function, status)))
+    }
+}
+
+fn address(selector: u32, scope: u32) -> AudioObjectPropertyAddress {
+    AudioObjectPropertyAddress { mSelector: selector, mScope: scope, mElement: kAudioObjectPropertyElementMaster }
+}
+
+/// Get a property that is a single value of type `T`.
+unsafe fn get_property<T>(object: AudioObjectID, address: &AudioObjectPropertyAddress, value: &mut T) -> Result<(), AudioError> {
+    let mut size = std::mem::size_of::<T>() as u32;
+    check(
+        "AudioObjectGetPropertyData",
+        AudioObjectGetPropertyData(object, address, 0, ptr::null(), &mut size, value as *mut T as *mut c_void),
+    )
+}
+
+/// Get a `CFString` property, such as the name or UID of a device.
+unsafe fn get_string_property(object: AudioObjectID, selector: u32) -> Result<String, AudioError> {
+    let mut string: id = ptr::null_mut();
+    get_property(object, &address(selector, kAudioObjectPropertyScopeGlobal), &mut string)?;
+    if string.is_null() {
+        return Ok(String::new());
+    }
+    // `CFStringRef` is toll-free bridged with `NSString`.
+    let result = nsstring_to_string(string);
+    CFRelease(string as *const c_void);
+    Ok(result)
+}
+
+unsafe fn all_devices() -> Result<Vec<AudioObjectID>, AudioError> {
+    let devices_address = address(kAudioHardwarePropertyDevices, kAudioObjectPropertyScopeGlobal);
+    let mut size = 0;
+    check(
+        "AudioObjectGetPropertyDataSize",
+        AudioObjectGetPropertyDataSize(kAudioObjectSystemObject, &devices_address, 0, ptr::null(), &mut size),
+    )?;
+    let mut devices = vec![0 as AudioObjectID; size as usize / std::mem::size_of::<AudioObjectID>()];
+    check(
+        "AudioObjectGetPropertyData",
+        AudioObjectGetPropertyData(
+            kAudioObjectSystemObject,
+            &devices_address,
+            0,
+            ptr::null(),
+            &mut size,
+            devices.as_mut_ptr() as *mut c_void,
+        ),
+    )?;
+    devices.truncate(size as usize / std::mem::size_of::<AudioObjectID>());
+    Ok(devices)
+}
+
+/// Property scope of the streams of `direction`.
+fn stream_scope(direction: AudioDirection) -> u32 {
+    match direction {
+        AudioDirection::Output => kAudioObjectPropertyScopeOutput,
+        AudioDirection::Input => kAudioObjectPropertyScopeInput,
+    }
+}
+
+unsafe fn has_streams(device: AudioObjectID, direction: AudioDirection) -> bool {
+    let mut size = 0;
+    let status = AudioObjectGetPropertyDataSize(
+        device,
+        &address(kAudioDevicePropertyStreams, stream_scope(direction)),
+        0,
+        ptr::null(),
+        &mut size,
+    );
+    status == 0 && size > 0
+}
+
+/// The system's default device for `direction`, or 0 if there is none.
+unsafe fn default_device(direction: AudioDirection) -> AudioObjectID {
+    let selector = match direction {
+        AudioDirection::Output => kAudioHardwarePropertyDefaultOutputDevice,
+        AudioDirection::Input => kAudioHardwarePropertyDefaultInputDevice,
+    };
+    let mut device: AudioObjectID = 0;
+    // Not fatal, e.g. when there is no such device at all.
+    let _ = get_property(kAudioObjectSystemObject, &address(selector, kAudioObjectPropertyScopeGlobal), &mut device);
+    device
+}
+
+/// Find a device by its UID, which we use as [`AudioDevice::id`].
+unsafe fn find_device(device_id: &str) -> Result<AudioObjectID, AudioError> {
+    all_devices()?
+        .into_iter()
+        .find(|&device| matches!(get_string_property(device, kAudioDevicePropertyDeviceUID), Ok(uid) if uid == device_id))
+        .ok_or(AudioError::DeviceNotFound)
+}
+
+pub(crate) fn devices(direction: AudioDirection) -> Result<Vec<AudioDevice>, AudioError> {
+    unsafe {
+        let default_device = default_device(direction);
+        let mut devices = vec![];
+        for device in all_devices()? {
+            if has_streams(device, direction) {
+                devices.push(AudioDevice {
+                    id: get_string_property(device, kAudioDevicePropertyDeviceUID)?,
+                    name: get_string_property(device, kAudioObjectPropertyName)?,
+                    is_default: device == default_device,
+                });
+            }
+        }
+        Ok(devices)
+    }
+}
+
+/// Called by CoreAudio on its realtime thread. `ref_con` points to an [`AudioCallbackRunner`].
+unsafe extern "C" fn render_callback(
+    ref_con: *mut c_void,
+    _action_flags: *mut u32,
+    _time_stamp: *const c_void,
+    _bus_number: u32,
+    _number_frames: u32,
+    data: *mut AudioBufferList,
+) -> OSStatus {
+    let runner = &mut *(ref_con as *mut AudioCallbackRunner);
+    // We use an interleaved format, so there's only one buffer.
+    let buffer = &mut (*data).mBuffers[0];
+    let samples = std::slice::from_raw_parts_mut(buffer.mData as *mut f32, buffer.mDataByteSize as usize / 4);
+    runner.fill(samples);
+    0
+}
+
+/// Passed to [`input_callback`].
+struct InputContext {
+    audio_unit: AudioUnit,
+    ring: Arc<InputRing>,
+    /// Interleaved samples, allocated up front since we can't allocate on the realtime thread.
+    samples: Vec<f32>,
+    channels: usize,
+}
+
+/// Called by CoreAudio on its realtime thread when captured samples are available. `ref_con` points to an
+/// [`InputContext`].
+unsafe extern "C" fn input_callback(
+    ref_con: *mut c_void,
+    action_flags: *mut u32,
+    time_stamp: *const c_void,
+    bus_number: u32,
+    number_frames: u32,
+    _data: *mut AudioBufferList,
+) -> OSStatus {
+    let context = &mut *(ref_con as *mut InputContext);
+    let len = (number_frames as usize * context.channels).min(context.samples.len());
+    let mut buffer_list = AudioBufferList {
+        mNumberBuffers: 1,
+        mBuffers: [AudioBufferListBuffer {
+            mNumberChannels: context.channels as u32,
+            mDataByteSize: len as u32 * 4,
+            mData: context.samples.as_mut_ptr() as *mut c_void,
+        }],
+    };
+    let status = AudioUnitRender(context.audio_unit, action_flags, time_stamp, bus_number, number_frames, &mut buffer_list);
+    if status == 0 {
+        context.ring.push(&context.samples[..buffer_list.mBuffers[0].mDataByteSize as usize / 4]);
+    }
+    status
+}
+
+unsafe fn drop_boxed<T>(ptr: *mut c_void) {
+    drop(Box::from_raw(ptr as *mut T));
+}
+
+/// An `AudioUnit` that calls the callback from CoreAudio's realtime thread.
+pub(crate) struct CoreAudioStream {
+    audio_unit: AudioUnit,
+    /// Pointer to a boxed [`AudioCallbackRunner`] or [`InputContext`], which is passed to [`render_callback`] or
+    /// [`input_callback`].
+    context: *mut c_void,
+    /// Frees `context`.
+    drop_context: unsafe fn(*mut c_void),
+}
+
+// The `AudioUnit` can be stopped and disposed from any thread.
+unsafe impl Send for CoreAudioStream {}
+
+impl CoreAudioStream {
+    pub(crate) fn start(options: &AudioOutputOptions, callback: AudioCallback) -> Result<(Self, u32), AudioError> {
+        unsafe {
+            // The default output unit follows the system's default device, also when it changes; for a specific
+            // device we need a HAL output unit.
+            let device = match &options.device_id {
+                Some(device_id) => Some(find_device(device_id)?),
+                None => None,
+            };
+            let subtype = if device.is_some() { kAudioUnitSubType_HALOutput } else { kAudioUnitSubType_DefaultOutput };
+            let mut stream = Self::new(subtype)?;
+            let audio_unit = stream.audio_unit;
+
+            if let Some(device) = device {
+                stream.set_current_device(device)?;
+            }
+            if let Some(buffer_size) = options.buffer_size {
+                // Best effort; the device might not support this buffer size.
+                let current_device = device.unwrap_or_else(|| default_device(AudioDirection::Output));
+                AudioObjectSetPropertyData(
+                    current_device,
+                    &address(kAudioDevicePropertyBufferFrameSize, kAudioObjectPropertyScopeGlobal),
+                    0,
+                    ptr::null(),
+                    std::mem::size_of::<u32>() as u32,
+                    &buffer_size as *const u32 as *const c_void,
+                );
+            }
+
+            // Use the sample rate of the device (on the output side of the unit), unless a different one was
+            // requested, in which case the unit converts it.
+            let mut device_format = AudioStreamBasicDescription::default();
+            let mut size = std::mem::size_of::<AudioStreamBasicDescription>() as u32;
+            check(
+                "AudioUnitGetProperty",
+                AudioUnitGetProperty(
+                    audio_unit,
+                    kAudioUnitProperty_StreamFormat,
+                    kAudioUnitScope_Output,
+                    0,
+                    &mut device_format as *mut AudioStreamBasicDescription as *mut c_void,
+                    &mut size,
+                ),
+            )?;
+            let sample_rate = options.sample_rate.unwrap_or(device_format.mSampleRate as u32);
+            let channels = options.channels.max(1) as u32;
+            let format = float_format(sample_rate, channels);
+            check(
+                "AudioUnitSetProperty",
+                AudioUnitSetProperty(
+                    audio_unit,
+                    kAudioUnitProperty_StreamFormat,
+                    kAudioUnitScope_Input,
+                    0,
+                    &format as *const AudioStreamBasicDescription as *const c_void,
+                    std::mem::size_of::<AudioStreamBasicDescription>() as u32,
+                ),
+            )?;
+
+            let runner = AudioCallbackRunner::new(callback, channels as usize, sample_rate);
+            stream.context = Box::into_raw(Box::new(runner)) as *mut c_void;
+            stream.drop_context = drop_boxed::<AudioCallbackRunner>;
+            let render_callback_struct = AURenderCallbackStruct { inputProc: render_callback, inputProcRefCon: stream.context };
+            check(
+                "AudioUnitSetProperty",
+                AudioUnitSetProperty(
+                    audio_unit,
+                    kAudioUnitProperty_SetRenderCallback,
+                    kAudioUnitScope_Input,
+                    0,
+                    &render_callback_struct as *const AURenderCallbackStruct as *const c_void,
+                    std::mem::size_of::<AURenderCallbackStruct>() as u32,
+                ),
+            )?;
+            check("AudioUnitInitialize", AudioUnitInitialize(audio_unit))?;
+            check("AudioOutputUnitStart", AudioOutputUnitStart(audio_unit))?;
+            Ok((stream, sample_rate))
+        }
+    }
+
+    pub(crate) fn start_input(options: &AudioInputOptions, ring: Arc<InputRing>) -> Result<(Self, u32), AudioError> {
+        unsafe {
+            // The system asks the user for permission when we start capturing, but we can tell up front if they denied
+            // it before.
+            let status: i64 = msg_send![class!(AVCaptureDevice), authorizationStatusForMediaType: AVMediaTypeAudio];
+            if status == AVAuthorizationStatusDenied || status == AVAuthorizationStatusRestricted {
+                return Err(AudioError::NotAllowed);
+            }
+
+            let device = match &options.device_id {
+                Some(device_id) => find_device(device_id)?,
+                None => default_device(AudioDirection::Input),
+            };
+            if device == 0 || !has_streams(device, AudioDirection::Input) {
+                return Err(AudioError::DeviceNotFound);
+            }
+            let mut stream = Self::new(kAudioUnitSubType_HALOutput)?;
+            let audio_unit = stream.audio_unit;
+            // Enable the input side and disable the output side. This has to happen before setting the device.
+            for (element, enable) in [(INPUT_ELEMENT, 1u32), (OUTPUT_ELEMENT, 0u32)] {
+                check(
+                    "AudioUnitSetProperty",
+                    AudioUnitSetProperty(
+                        audio_unit,
+                        kAudioOutputUnitProperty_EnableIO,
+                        if element == INPUT_ELEMENT { kAudioUnitScope_Input } else { kAudioUnitScope_Output },
+                        element,
+                        &enable as *const u32 as *const c_void,
+                        std::mem::size_of::<u32>() as u32,
+                    ),
+                )?;
+            }
+            stream.set_current_device(device)?;
+
+            // The unit can convert the channel count and format, but not the sample rate of input, so we always use
+            // the sample rate of the device (on the input side of the input element).
+            let mut device_format = AudioStreamBasicDescription::default();
+            let mut size = std::mem::size_of::<AudioStreamBasicDescription>() as u32;
+            check(
+                "AudioUnitGetProperty",
+                AudioUnitGetProperty(
+                    audio_unit,
+                    kAudioUnitProperty_StreamFormat,
+                    kAudioUnitScope_Input,
+                    INPUT_ELEMENT,
+                    &mut device_format as *mut AudioStreamBasicDescription as *mut c_void,
+                    &mut size,
+                ),
+            )?;
+            let sample_rate = device_format.mSampleRate as u32;
+            let channels = options.channels.max(1) as u32;
+            let format = float_format(sample_rate, channels);
+            check(
+                "AudioUnitSetProperty",
+                AudioUnitSetProperty(
+                    audio_unit,
+                    kAudioUnitProperty_StreamFormat,
+                    kAudioUnitScope_Output,
+                    INPUT_ELEMENT,
+                    &format as *const AudioStreamBasicDescription as *const c_void,
+                    std::mem::size_of::<AudioStreamBasicDescription>() as u32,
+                ),
+            )?;
+
+            let mut max_frames: u32 = 4096;
+            let mut size = std::mem::size_of::<u32>() as u32;
+            let _ = AudioUnitGetProperty(
+                audio_unit,
+                kAudioUnitProperty_MaximumFramesPerSlice,
+                kAudioUnitScope_Global,
+                0,
+                &mut max_frames as *mut u32 as *mut c_void,
+                &mut size,
+            );
+            let context = InputContext {
+                audio_unit,
+                ring,
+                samples: vec![0.; max_frames as usize * channels as usize],
+                channels: channels as usize,
+            };
+            stream.context = Box::into_raw(Box::new(context)) as *mut c_void;
+            stream.drop_context = drop_boxed::<InputContext>;
+            let input_callback_struct = AURenderCallbackStruct { inputProc: input_callback, inputProcRefCon: stream.context };
+            check(
+                "AudioUnitSetProperty",
+                AudioUnitSetProperty(
+                    audio_unit,
+                    kAudioOutputUnitProperty_SetInputCallback,
+                    kAudioUnitScope_Global,
+                    0,
+                    &input_callback_struct as *const AURenderCallbackStruct as *const c_void,
+                    std::mem::size_of::<AURenderCallbackStruct>() as u32,
+                ),
+            )?;
+            check("AudioUnitInitialize", AudioUnitInitialize(audio_unit))?;
+            check("AudioOutputUnitStart", AudioOutputUnitStart(audio_unit))?;
+            Ok((stream, sample_rate))
+        }
+    }
+
+    /// Create an output unit of the given subtype. The returned stream disposes of it when dropped, also when
+    /// setting it up fails halfway.
+    unsafe fn new(subtype: u32) -> Result<Self, AudioError> {
+        let description = AudioComponentDescription {
+            componentType: kAudioUnitType_Output,
+            componentSubType: subtype,
+            componentManufacturer: kAudioUnitManufacturer_Apple,
+            componentFlags: 0,
+            componentFlagsMask: 0,
+        };
+        let component = AudioComponentFindNext(ptr::null_mut(), &description);
+        if component.is_null() {
+            return Err(AudioError::NotSupported("No output AudioUnit found".to_string()));
+        }
+        let mut audio_unit: AudioUnit = ptr::null_mut();
+        check("AudioComponentInstanceNew", AudioComponentInstanceNew(component, &mut audio_unit))?;
+        Ok(Self { audio_unit, context: ptr::null_mut(), drop_context: drop_boxed::<()> })
+    }
+
+    /// Use a specific device, which requires a HAL output unit.
+    unsafe fn set_current_device(&mut self, device: AudioObjectID) -> Result<(), AudioError> {
+        check(
+            "AudioUnitSetProperty",
+            AudioUnitSetProperty(
+                self.audio_unit,
+                kAudioOutputUnitProperty_CurrentDevice,
+                kAudioUnitScope_Global,
+                0,
+                &device as *const AudioObjectID as *const c_void,
+                std::mem::size_of::<AudioObjectID>() as u32,
+            ),
+        )
+    }
+}
+
+/// Interleaved `f32` samples.
+fn float_format(sample_rate: u32, channels: u32) -> AudioStreamBasicDescription {
+    AudioStreamBasicDescription {
+        mSampleRate: sample_rate as f64,
+        mFormatID: kAudioFormatLinearPCM,
+        mFormatFlags: kAudioFormatFlagIsFloat | kAudioFormatFlagIsPacked,
+        mBytesPerPacket: 4 * channels,
+        mFramesPerPacket: 1,
+        mBytesPerFrame: 4 * channels,
+        mChannelsPerFrame: channels,
+        mBitsPerChannel: 32,
+        mReserved: 0,
+    }
+}
+
+impl Drop for CoreAudioStream {
+    fn drop(&mut self) {
+        unsafe {
+            // Stopping waits for the callback to return, so after this we can free the context.
+            AudioOutputUnitStop(self.audio_unit);
+            AudioUnitUninitialize(self.audio_unit);
+            AudioComponentInstanceDispose(self.audio_unit);
+            if !self.context.is_null() {
+                (self.drop_context)(self.context);
+            }
+        }
+    }
+}
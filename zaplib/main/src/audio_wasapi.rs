@@ -0,0 +1,427 @@
+//! WASAPI backend for [`crate::audio`], using shared-mode streams with event-driven buffering.
+
+use crate::audio::*;
+use std::ffi::OsStr;
+use std::os::windows::ffi::OsStrExt;
+use std::ptr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread::JoinHandle;
+use winapi::shared::guiddef::GUID;
+use winapi::shared::ksmedia::KSDATAFORMAT_SUBTYPE_IEEE_FLOAT;
+use winapi::shared::minwindef::{DWORD, FALSE, WORD};
+use winapi::shared::mmreg::{WAVEFORMATEX, WAVEFORMATEXTENSIBLE, WAVE_FORMAT_EXTENSIBLE};
+use winapi::shared::winerror::{FAILED, HRESULT, RPC_E_CHANGED_MODE};
+use winapi::um::audioclient::{IAudioCaptureClient, IAudioClient, IAudioRenderClient, AUDCLNT_BUFFERFLAGS_SILENT};
+use winapi::um::audiosessiontypes::{AUDCLNT_SHAREMODE_SHARED, AUDCLNT_STREAMFLAGS_EVENTCALLBACK};
+use winapi::um::combaseapi::{CoCreateInstance, CoInitializeEx, CoTaskMemFree, CoUninitialize, PropVariantClear, CLSCTX_ALL};
+use winapi::um::coml2api::STGM_READ;
+use winapi::um::functiondiscoverykeys_devpkey::PKEY_Device_FriendlyName;
+use winapi::um::handleapi::CloseHandle;
+use winapi::um::mmdeviceapi::{
+    eCapture, eConsole, eRender, CLSID_MMDeviceEnumerator, EDataFlow, IMMDevice, IMMDeviceCollection, IMMDeviceEnumerator,
+    DEVICE_STATE_ACTIVE,
+};
+use winapi::um::objbase::COINIT_MULTITHREADED;
+use winapi::um::propidl::PROPVARIANT;
+use winapi::um::propsys::IPropertyStore;
+use winapi::um::synchapi::{CreateEventW, WaitForSingleObject};
+use winapi::um::unknwnbase::IUnknown;
+use winapi::um::winbase::WAIT_OBJECT_0;
+use winapi::um::winnt::{HANDLE, LPWSTR};
+use winapi::Interface;
+
+/// Not in `winapi` yet. Let the audio engine convert the sample rate and channel count if necessary.
+const AUDCLNT_STREAMFLAGS_AUTOCONVERTPCM: DWORD = 0x80000000;
+const AUDCLNT_STREAMFLAGS_SRC_DEFAULT_QUALITY: DWORD = 0x08000000;
+/// `E_NOTFOUND`, i.e. `HRESULT_FROM_WIN32(ERROR_NOT_FOUND)`.
+const E_NOTFOUND: HRESULT = 0x80070490_u32 as HRESULT;
+/// 100-nanosecond units, as used by `IAudioClient::Initialize`.
+const DEFAULT_BUFFER_DURATION: i64 = 200_000;
+
+fn check(function: &str, hresult: HRESULT) -> Result<(), AudioError> {
+    if hresult == E_NOTFOUND {
+        Err(AudioError::DeviceNotFound)
+    } else if FAILED(hresult) {
+        Err(AudioError::Other(format!("{} failed with HRESULT 0x{:08X}", function, hresult as u32)))
+    } else {
+        Ok(())
+    }
+}
+
+/// Owned COM pointer, which is released when dropped.
+struct ComPtr<T: Interface>(*mut T);
+
+impl<T: Interface> ComPtr<T> {
+    fn null() -> Self {
+        Self(ptr::null_mut())
+    }
+
+    fn as_out(&mut self) -> *mut *mut T {
+        &mut self.0
+    }
+
+    fn as_out_void(&mut self) -> *mut *mut winapi::ctypes::c_void {
+        &mut self.0 as *mut *mut T as *mut *mut winapi::ctypes::c_void
+    }
+}
+
+impl<T: Interface> std::ops::Deref for ComPtr<T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        unsafe { &*self.0 }
+    }
+}
+
+impl<T: Interface> Drop for ComPtr<T> {
+    fn drop(&mut self) {
+        if !self.0.is_null() {
+            unsafe { (*(self.0 as *mut IUnknown)).Release() };
+        }
+    }
+}
+
+/// Initializes COM for the current thread, and uninitializes it when dropped.
+struct ComInitialized(bool);
+
+impl ComInitialized {
+    fn new() -> Self {
+        let hresult = unsafe { CoInitializeEx(ptr::null_mut(), COINIT_MULTITHREADED) };
+        // If COM was already initialized with a different mode (e.g. by the windowing code) it still works for us,
+        // but we shouldn't uninitialize it.
+        Self(hresult != RPC_E_CHANGED_MODE && !FAILED(hresult))
+    }
+}
+
+impl Drop for ComInitialized {
+    fn drop(&mut self) {
+        if self.0 {
+            unsafe { CoUninitialize() };
+        }
+    }
+}
+
+fn device_enumerator() -> Result<ComPtr<IMMDeviceEnumerator>, AudioError> {
+    let mut enumerator = ComPtr::<IMMDeviceEnumerator>::null();
+    check("CoCreateInstance", unsafe {
+        CoCreateInstance(
+            &CLSID_MMDeviceEnumerator,
+            ptr::null_mut(),
+            CLSCTX_ALL,
+            &IMMDeviceEnumerator::uuidof(),
+            enumerator.as_out_void(),
+        )
+    })?;
+    Ok(enumerator)
+}
+
+/// Convert a string that was allocated by COM, and free it.
+unsafe fn take_com_string(string: LPWSTR) -> String {
+    let len = (0..).take_while(|&i| *string.offset(i) != 0).count();
+    let result = String::from_utf16_lossy(std::slice::from_raw_parts(string, len));
+    CoTaskMemFree(string as *mut winapi::ctypes::c_void);
+    result
+}
+
+unsafe fn device_id(device: &IMMDevice) -> Result<String, AudioError> {
+    let mut id: LPWSTR = ptr::null_mut();
+    check("IMMDevice::GetId", device.GetId(&mut id))?;
+    Ok(take_com_string(id))
+}
+
+unsafe fn device_name(device: &IMMDevice) -> Result<String, AudioError> {
+    let mut store = ComPtr::<IPropertyStore>::null();
+    check("IMMDevice::OpenPropertyStore", device.OpenPropertyStore(STGM_READ, store.as_out()))?;
+    let mut value: PROPVARIANT = std::mem::zeroed();
+    check("IPropertyStore::GetValue", store.GetValue(&PKEY_Device_FriendlyName, &mut value))?;
+    let name_ptr = *value.data.pwszVal();
+    let name = if name_ptr.is_null() {
+        String::new()
+    } else {
+        let len = (0..).take_while(|&i| *name_ptr.offset(i) != 0).count();
+        String::from_utf16_lossy(std::slice::from_raw_parts(name_ptr, len))
+    };
+    PropVariantClear(&mut value);
+    Ok(name)
+}
+
+fn data_flow(direction: AudioDirection) -> EDataFlow {
+    match direction {
+        AudioDirection::Output => eRender,
+        AudioDirection::Input => eCapture,
+    }
+}
+
+pub(crate) fn devices(direction: AudioDirection) -> Result<Vec<AudioDevice>, AudioError> {
+    let _com = ComInitialized::new();
+    unsafe {
+        let enumerator = device_enumerator()?;
+        let mut default_device = ComPtr::<IMMDevice>::null();
+        let default_id = if enumerator.GetDefaultAudioEndpoint(data_flow(direction), eConsole, default_device.as_out()) >= 0 {
+            Some(device_id(&default_device)?)
+        } else {
+            None
+        };
+
+        let mut collection = ComPtr::<IMMDeviceCollection>::null();
+        check(
+            "IMMDeviceEnumerator::EnumAudioEndpoints",
+            enumerator.EnumAudioEndpoints(data_flow(direction), DEVICE_STATE_ACTIVE, collection.as_out()),
+        )?;
+        let mut count = 0;
+        // winapi declares this out parameter as `*const`.
+        check("IMMDeviceCollection::GetCount", collection.GetCount(&mut count as *mut u32 as *const u32))?;
+        let mut devices = vec![];
+        for index in 0..count {
+            let mut device = ComPtr::<IMMDevice>::null();
+            check("IMMDeviceCollection::Item", collection.Item(index, device.as_out()))?;
+            let id = device_id(&device)?;
+            let is_default = default_id.as_ref() == Some(&id);
+            devices.push(AudioDevice { name: device_name(&device)?, id, is_default });
+        }
+        Ok(devices)
+    }
+}
+
+/// Sent from the audio thread once the stream has started (with the sample rate) or failed to start.
+type StartedSender = mpsc::Sender<Result<u32, AudioError>>;
+
+/// Plays or captures audio on a thread that waits for WASAPI to signal that it needs or has more data.
+pub(crate) struct WasapiStream {
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl WasapiStream {
+    pub(crate) fn start(options: &AudioOutputOptions, callback: AudioCallback) -> Result<(Self, u32), AudioError> {
+        let options = options.clone();
+        Self::spawn(move |stop, started| unsafe { run_output(&options, callback, stop, started) })
+    }
+
+    pub(crate) fn start_input(options: &AudioInputOptions, ring: Arc<InputRing>) -> Result<(Self, u32), AudioError> {
+        let options = options.clone();
+        Self::spawn(move |stop, started| unsafe { run_input(&options, &ring, stop, started) })
+    }
+
+    /// COM objects are tied to the thread that created them, so we do all the setup on the audio thread, and `run`
+    /// sends back the result through a [`StartedSender`].
+    fn spawn(
+        run: impl FnOnce(&AtomicBool, &StartedSender) -> Result<(), AudioError> + Send + 'static,
+    ) -> Result<(Self, u32), AudioError> {
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread_stop = Arc::clone(&stop);
+        let (sender, receiver) = mpsc::channel();
+        let thread = std::thread::Builder::new()
+            .name("Zaplib audio".to_string())
+            .spawn(move || {
+                let _com = ComInitialized::new();
+                if let Err(err) = run(&thread_stop, &sender) {
+                    let _ = sender.send(Err(err));
+                }
+            })
+            .map_err(|err| AudioError::Other(err.to_string()))?;
+        let sample_rate = receiver.recv().map_err(|_| AudioError::Other("Audio thread stopped".to_string()))??;
+        Ok((Self { stop, thread: Some(thread) }, sample_rate))
+    }
+}
+
+impl Drop for WasapiStream {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Closes an event handle when dropped.
+struct EventHandle(HANDLE);
+
+impl Drop for EventHandle {
+    fn drop(&mut self) {
+        unsafe { CloseHandle(self.0) };
+    }
+}
+
+/// An initialized `IAudioClient` that signals `event` when it needs or has more data.
+struct Client {
+    client: ComPtr<IAudioClient>,
+    event: EventHandle,
+    sample_rate: u32,
+    channels: usize,
+    buffer_frames: u32,
+}
+
+/// Open and initialize a client for the given device, or the default device for `direction`.
+unsafe fn open_client(
+    direction: AudioDirection,
+    device_id: Option<&str>,
+    sample_rate: Option<u32>,
+    channels: u16,
+    buffer_size: Option<u32>,
+) -> Result<Client, AudioError> {
+    let enumerator = device_enumerator()?;
+    let mut device = ComPtr::<IMMDevice>::null();
+    match device_id {
+        Some(device_id) => {
+            let wide_id: Vec<u16> = OsStr::new(device_id).encode_wide().chain(Some(0)).collect();
+            check("IMMDeviceEnumerator::GetDevice", enumerator.GetDevice(wide_id.as_ptr(), device.as_out()))?;
+        }
+        None => check(
+            "IMMDeviceEnumerator::GetDefaultAudioEndpoint",
+            enumerator.GetDefaultAudioEndpoint(data_flow(direction), eConsole, device.as_out()),
+        )?,
+    }
+    let mut client = ComPtr::<IAudioClient>::null();
+    check("IMMDevice::Activate", device.Activate(&IAudioClient::uuidof(), CLSCTX_ALL, ptr::null_mut(), client.as_out_void()))?;
+
+    // Shared mode uses the sample rate of the audio engine ("mix format"), unless a different one was requested, in
+    // which case we let the audio engine convert it.
+    let mut mix_format: *mut WAVEFORMATEX = ptr::null_mut();
+    check("IAudioClient::GetMixFormat", client.GetMixFormat(&mut mix_format))?;
+    let mix_sample_rate = (*mix_format).nSamplesPerSec;
+    CoTaskMemFree(mix_format as *mut winapi::ctypes::c_void);
+    let sample_rate = sample_rate.unwrap_or(mix_sample_rate);
+    let channels = channels.max(1) as WORD;
+    let format = WAVEFORMATEXTENSIBLE {
+        Format: WAVEFORMATEX {
+            wFormatTag: WAVE_FORMAT_EXTENSIBLE,
+            nChannels: channels,
+            nSamplesPerSec: sample_rate,
+            nAvgBytesPerSec: sample_rate * 4 * channels as DWORD,
+            nBlockAlign: 4 * channels,
+            wBitsPerSample: 32,
+            cbSize: (std::mem::size_of::<WAVEFORMATEXTENSIBLE>() - std::mem::size_of::<WAVEFORMATEX>()) as WORD,
+        },
+        Samples: 32,
+        // Zero means that channels are not assigned to specific speakers; the audio engine maps them.
+        dwChannelMask: 0,
+        SubFormat: KSDATAFORMAT_SUBTYPE_IEEE_FLOAT,
+    };
+    let buffer_duration = buffer_size.map_or(DEFAULT_BUFFER_DURATION, |frames| {
+        // Two buffers, in 100-nanosecond units.
+        frames as i64 * 2 * 10_000_000 / sample_rate as i64
+    });
+    check(
+        "IAudioClient::Initialize",
+        client.Initialize(
+            AUDCLNT_SHAREMODE_SHARED,
+            AUDCLNT_STREAMFLAGS_EVENTCALLBACK | AUDCLNT_STREAMFLAGS_AUTOCONVERTPCM | AUDCLNT_STREAMFLAGS_SRC_DEFAULT_QUALITY,
+            buffer_duration,
+            0,
+            &format as *const WAVEFORMATEXTENSIBLE as *const WAVEFORMATEX,
+            ptr::null::<GUID>(),
+        ),
+    )?;
+
+    let event = CreateEventW(ptr::null_mut(), FALSE, FALSE, ptr::null());
+    if event.is_null() {
+        return Err(AudioError::Other("CreateEventW failed".to_string()));
+    }
+    let event = EventHandle(event);
+    check("IAudioClient::SetEventHandle", client.SetEventHandle(event.0))?;
+
+    let mut buffer_frames = 0;
+    check("IAudioClient::GetBufferSize", client.GetBufferSize(&mut buffer_frames))?;
+    Ok(Client { client, event, sample_rate, channels: channels as usize, buffer_frames })
+}
+
+/// Set up an output stream, send the sample rate through `started`, and keep filling buffers until `stop` is set.
+unsafe fn run_output(
+    options: &AudioOutputOptions,
+    callback: AudioCallback,
+    stop: &AtomicBool,
+    started: &StartedSender,
+) -> Result<(), AudioError> {
+    let Client { client, event, sample_rate, channels, buffer_frames } = open_client(
+        AudioDirection::Output,
+        options.device_id.as_deref(),
+        options.sample_rate,
+        options.channels,
+        options.buffer_size,
+    )?;
+    let mut render_client = ComPtr::<IAudioRenderClient>::null();
+    check("IAudioClient::GetService", client.GetService(&IAudioRenderClient::uuidof(), render_client.as_out_void()))?;
+    check("IAudioClient::Start", client.Start())?;
+    let _ = started.send(Ok(sample_rate));
+
+    let mut runner = AudioCallbackRunner::new(callback, channels, sample_rate);
+    while !stop.load(Ordering::Relaxed) {
+        // Time out regularly, so that we notice when we should stop.
+        if WaitForSingleObject(event.0, 100) != WAIT_OBJECT_0 {
+            continue;
+        }
+        let mut padding = 0;
+        if FAILED(client.GetCurrentPadding(&mut padding)) {
+            // E.g. the device was unplugged.
+            break;
+        }
+        let frames = buffer_frames - padding;
+        if frames == 0 {
+            continue;
+        }
+        let mut data = ptr::null_mut();
+        if FAILED(render_client.GetBuffer(frames, &mut data)) {
+            break;
+        }
+        runner.fill(std::slice::from_raw_parts_mut(data as *mut f32, frames as usize * channels));
+        render_client.ReleaseBuffer(frames, 0);
+    }
+    client.Stop();
+    Ok(())
+}
+
+/// Set up an input stream, send the sample rate through `started`, and keep pushing captured samples to `ring` until
+/// `stop` is set.
+unsafe fn run_input(
+    options: &AudioInputOptions,
+    ring: &InputRing,
+    stop: &AtomicBool,
+    started: &StartedSender,
+) -> Result<(), AudioError> {
+    let Client { client, event, sample_rate, channels, buffer_frames } = open_client(
+        AudioDirection::Input,
+        options.device_id.as_deref(),
+        options.sample_rate,
+        options.channels,
+        options.buffer_size,
+    )?;
+    let mut capture_client = ComPtr::<IAudioCaptureClient>::null();
+    check("IAudioClient::GetService", client.GetService(&IAudioCaptureClient::uuidof(), capture_client.as_out_void()))?;
+    check("IAudioClient::Start", client.Start())?;
+    let _ = started.send(Ok(sample_rate));
+
+    // Pushed instead of the data of packets that are flagged as silent.
+    let silence = vec![0.; buffer_frames as usize * channels];
+    'outer: while !stop.load(Ordering::Relaxed) {
+        // Time out regularly, so that we notice when we should stop.
+        if WaitForSingleObject(event.0, 100) != WAIT_OBJECT_0 {
+            continue;
+        }
+        loop {
+            let mut packet_frames = 0;
+            if FAILED(capture_client.GetNextPacketSize(&mut packet_frames)) {
+                // E.g. the device was unplugged.
+                break 'outer;
+            }
+            if packet_frames == 0 {
+                break;
+            }
+            let mut data = ptr::null_mut();
+            let mut frames = 0;
+            let mut flags = 0;
+            if FAILED(capture_client.GetBuffer(&mut data, &mut frames, &mut flags, ptr::null_mut(), ptr::null_mut())) {
+                break 'outer;
+            }
+            let len = frames as usize * channels;
+            if flags & AUDCLNT_BUFFERFLAGS_SILENT != 0 {
+                ring.push(&silence[..len.min(silence.len())]);
+            } else {
+                ring.push(std::slice::from_raw_parts(data as *const f32, len));
+            }
+            capture_client.ReleaseBuffer(frames);
+        }
+    }
+    client.Stop();
+    Ok(())
+}
@@ -0,0 +1,321 @@
+//! A typed registry of functions that can be called from JS using `zaplib.callRustSync` and
+//! `zaplib.callRustAsync`, which can also generate TypeScript types for them.
+//!
+//! Instead of matching on function names and unpacking [`ZapParam`]s by hand, register regular Rust
+//! functions with [`CallRustFns`], and use `register_call_rust!(fns: ...)`. Then run
+//! `cargo zaplib generate-types` to get a TypeScript file, so that calls from JS are type-checked.
+
+use crate::*;
+use once_cell::sync::OnceCell;
+use std::sync::Arc;
+
+/// A type that can be passed to (or returned from) a function registered with [`CallRustFns`]. Implemented
+/// for [`String`], `Vec<u8>`, `Vec<u32>`, `Vec<f32>`, `Arc<Vec<..>>` of those (read-only buffers), and
+/// structs that use `#[derive(ZapParam)]`.
+pub trait CallRustParam: Sized {
+    fn from_zap_param(param: ZapParam) -> Result<Self, String>;
+
+    fn into_zap_param(self) -> ZapParam;
+
+    /// TypeScript type of the corresponding `ZapParam` in JS.
+    fn typescript_type() -> String;
+
+    /// See [`ZerdeField::collect_typescript_declarations`].
+    fn collect_typescript_declarations(_declarations: &mut Vec<String>) {}
+}
+
+macro_rules! impl_call_rust_param {
+    ($ty:ty, $variant:ident, $typescript_type:expr) => {
+        impl CallRustParam for $ty {
+            fn from_zap_param(param: ZapParam) -> Result<Self, String> {
+                match param {
+                    ZapParam::$variant(value) => Ok(value),
+                    param => Err(format!("expected {}, got {:?}", stringify!($variant), param)),
+                }
+            }
+            fn into_zap_param(self) -> ZapParam {
+                ZapParam::$variant(self)
+            }
+            fn typescript_type() -> String {
+                $typescript_type.to_string()
+            }
+        }
+    };
+}
+
+impl_call_rust_param!(String, String, "string");
+impl_call_rust_param!(Vec<u8>, MutableU8Buffer, "Uint8Array");
+impl_call_rust_param!(Vec<u32>, MutableU32Buffer, "Uint32Array");
+impl_call_rust_param!(Vec<f32>, MutableF32Buffer, "Float32Array");
+impl_call_rust_param!(Arc<Vec<u8>>, ReadOnlyU8Buffer, "Uint8Array");
+impl_call_rust_param!(Arc<Vec<u32>>, ReadOnlyU32Buffer, "Uint32Array");
+impl_call_rust_param!(Arc<Vec<f32>>, ReadOnlyF32Buffer, "Float32Array");
+
+impl<T: ZapParamStruct + ZerdeField> CallRustParam for T {
+    fn from_zap_param(param: ZapParam) -> Result<Self, String> {
+        T::from_param(&param).map_err(|error| error.to_string())
+    }
+    fn into_zap_param(self) -> ZapParam {
+        self.to_zerde_record().into_param()
+    }
+    fn typescript_type() -> String {
+        format!("ZapParamBytes<{}>", T::typescript_type())
+    }
+    fn collect_typescript_declarations(declarations: &mut Vec<String>) {
+        T::collect_typescript_declarations(declarations);
+    }
+}
+
+/// Return type of a function registered with [`CallRustFns`]: `()` for no return values, a single
+/// [`CallRustParam`], or a tuple of two or three of them.
+pub trait CallRustReturn {
+    fn into_zap_params(self) -> Vec<ZapParam>;
+
+    /// TypeScript types of the returned `ZapParam`s.
+    fn typescript_types(declarations: &mut Vec<String>) -> Vec<String>;
+}
+
+impl CallRustReturn for () {
+    fn into_zap_params(self) -> Vec<ZapParam> {
+        Vec::new()
+    }
+    fn typescript_types(_declarations: &mut Vec<String>) -> Vec<String> {
+        Vec::new()
+    }
+}
+
+impl<T: CallRustParam> CallRustReturn for T {
+    fn into_zap_params(self) -> Vec<ZapParam> {
+        vec![self.into_zap_param()]
+    }
+    fn typescript_types(declarations: &mut Vec<String>) -> Vec<String> {
+        T::collect_typescript_declarations(declarations);
+        vec![T::typescript_type()]
+    }
+}
+
+macro_rules! impl_call_rust_return_tuple {
+    ($($ty:ident),*) => {
+        impl<$($ty: CallRustParam),*> CallRustReturn for ($($ty,)*) {
+            #[allow(non_snake_case)]
+            fn into_zap_params(self) -> Vec<ZapParam> {
+                let ($($ty,)*) = self;
+                vec![$($ty.into_zap_param()),*]
+            }
+            fn typescript_types(declarations: &mut Vec<String>) -> Vec<String> {
+                $($ty::collect_typescript_declarations(declarations);)*
+                vec![$($ty::typescript_type()),*]
+            }
+        }
+    };
+}
+
+impl_call_rust_return_tuple!(A, B);
+impl_call_rust_return_tuple!(A, B, C);
+
+/// A function that can be registered with [`CallRustFns::add`]. Implemented for functions and closures
+/// with up to 4 [`CallRustParam`] arguments, returning a [`CallRustReturn`]. `Args` is a tuple of the
+/// argument types, which is only there to tell the implementations apart.
+pub trait CallRustHandler<Args>: Send + Sync + 'static {
+    fn call(&self, params: Vec<ZapParam>) -> Result<Vec<ZapParam>, String>;
+
+    /// TypeScript type describing the parameters and return values.
+    fn typescript_signature(declarations: &mut Vec<String>) -> String;
+}
+
+macro_rules! impl_call_rust_handler {
+    ($($arg:ident),*) => {
+        impl<F, R, $($arg: CallRustParam),*> CallRustHandler<($($arg,)*)> for F
+        where
+            F: Fn($($arg),*) -> R + Send + Sync + 'static,
+            R: CallRustReturn,
+        {
+            #[allow(non_snake_case, unused_mut, unused_variables)]
+            fn call(&self, params: Vec<ZapParam>) -> Result<Vec<ZapParam>, String> {
+                let expected: &[&str] = &[$(stringify!($arg)),*];
+                if params.len() != expected.len() {
+                    return Err(format!("expected {} params, got {}", expected.len(), params.len()));
+                }
+                let mut params = params.into_iter().enumerate();
+                $(
+                    let (index, param) = params.next().unwrap();
+                    let $arg = <$arg as CallRustParam>::from_zap_param(param)
+                        .map_err(|error| format!("param {}: {}", index, error))?;
+                )*
+                Ok(self($($arg),*).into_zap_params())
+            }
+
+            fn typescript_signature(declarations: &mut Vec<String>) -> String {
+                let params: Vec<String> = vec![$({
+                    <$arg as CallRustParam>::collect_typescript_declarations(declarations);
+                    <$arg as CallRustParam>::typescript_type()
+                }),*];
+                let returns = R::typescript_types(declarations);
+                format!("{{ params: [{}]; returns: [{}] }}", params.join(", "), returns.join(", "))
+            }
+        }
+    };
+}
+
+impl_call_rust_handler!();
+impl_call_rust_handler!(A);
+impl_call_rust_handler!(A, B);
+impl_call_rust_handler!(A, B, C);
+impl_call_rust_handler!(A, B, C, D);
+
+type BoxedCallRustFn = Box<dyn Fn(Vec<ZapParam>) -> Result<Vec<ZapParam>, String> + Send + Sync>;
+
+struct RegisteredFn {
+    name: String,
+    typescript_signature: String,
+    typescript_declarations: Vec<String>,
+    call: BoxedCallRustFn,
+}
+
+/// Functions that can be called from JS, by name. Use with `register_call_rust!(fns: ...)`.
+///
+/// ```
+/// fn sum(values: Arc<Vec<u8>>) -> String {
+///     values.iter().map(|&v| v as u32).sum::<u32>().to_string()
+/// }
+///
+/// fn call_rust_fns() -> CallRustFns {
+///     CallRustFns::new().add("sum", sum).add("get_user", |id: String| User { name: id, ..User::default() })
+/// }
+///
+/// register_call_rust!(fns: call_rust_fns);
+/// ```
+#[derive(Default)]
+pub struct CallRustFns {
+    fns: Vec<RegisteredFn>,
+}
+
+/// Set by `register_call_rust!(fns: ...)`.
+static REGISTERED_CALL_RUST_FNS: OnceCell<CallRustFns> = OnceCell::new();
+
+/// Environment variable used by `cargo zaplib generate-types` to ask the app to write its TypeScript types.
+#[cfg(not(target_arch = "wasm32"))]
+const WRITE_TYPESCRIPT_ENV_VAR: &str = "ZAPLIB_WRITE_TYPESCRIPT";
+
+impl CallRustFns {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a function under `name`. Panics if `name` was already registered.
+    #[must_use]
+    pub fn add<Args>(mut self, name: &str, handler: impl CallRustHandler<Args>) -> Self {
+        assert!(self.fns.iter().all(|registered| registered.name != name), "CallRustFns: `{}` was registered twice", name);
+        let mut typescript_declarations = Vec::new();
+        let typescript_signature = typescript_signature_of(&handler, &mut typescript_declarations);
+        self.fns.push(RegisteredFn {
+            name: name.to_string(),
+            typescript_signature,
+            typescript_declarations,
+            call: Box::new(move |params| handler.call(params)),
+        });
+        self
+    }
+
+    /// Call the function registered under `name`. Panics if there is no such function, or if the
+    /// params don't match its arguments.
+    pub fn call(&self, name: String, params: Vec<ZapParam>) -> Vec<ZapParam> {
+        let registered = self
+            .fns
+            .iter()
+            .find(|registered| registered.name == name)
+            .unwrap_or_else(|| panic!("call_rust: no function registered with name `{}`", name));
+        (registered.call)(params).unwrap_or_else(|error| panic!("call_rust: `{}`: {}", name, error))
+    }
+
+    /// TypeScript source with a `RustFunctions` interface describing all registered functions, along with
+    /// types and schemas for structs that use `#[derive(ZapParam)]`. Use it with `TypedCallRustSync` or
+    /// `TypedCallRustAsync` in JS.
+    pub fn typescript_declarations(&self) -> String {
+        let mut declarations: Vec<&String> = Vec::new();
+        for declaration in self.fns.iter().flat_map(|registered| &registered.typescript_declarations) {
+            if !declarations.contains(&declaration) {
+                declarations.push(declaration);
+            }
+        }
+
+        let mut out = String::from("// Generated by zaplib from the registered `CallRustFns`; do not edit.\n\n");
+        out += "import type { ZapParamBytes, ZapParamSchema } from \"zaplib\";\n\n";
+        for declaration in declarations {
+            out += declaration;
+            out += "\n";
+        }
+        out += "export interface RustFunctions {\n";
+        for registered in &self.fns {
+            out += &format!("  {:?}: {};\n", registered.name, registered.typescript_signature);
+        }
+        out += "}\n";
+        out
+    }
+
+    /// Used by `register_call_rust!(fns: ...)`; initializes the registered functions the first time.
+    #[doc(hidden)]
+    pub fn registered(init: fn() -> CallRustFns) -> &'static CallRustFns {
+        REGISTERED_CALL_RUST_FNS.get_or_init(init)
+    }
+
+    /// Used by `register_call_rust!(fns: ...)`; when running `cargo zaplib generate-types`, writes
+    /// [`CallRustFns::typescript_declarations`] to the requested file and exits.
+    #[doc(hidden)]
+    pub fn write_typescript_if_requested(&self) {
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(path) = std::env::var_os(WRITE_TYPESCRIPT_ENV_VAR) {
+            std::fs::write(&path, self.typescript_declarations())
+                .unwrap_or_else(|error| panic!("Failed to write TypeScript types to {:?}: {}", path, error));
+            std::process::exit(0);
+        }
+    }
+}
+
+fn typescript_signature_of<Args, H: CallRustHandler<Args>>(_handler: &H, declarations: &mut Vec<String>) -> String {
+    H::typescript_signature(declarations)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Default, PartialEq, ZapParam)]
+    struct Point {
+        x: f32,
+        y: f32,
+    }
+
+    fn call_rust_fns() -> CallRustFns {
+        CallRustFns::new()
+            .add("length", |values: Arc<Vec<u8>>| values.len().to_string())
+            .add("flip", |point: Point| Point { x: point.y, y: point.x })
+            .add("split", |a: String, b: String| (b, a))
+            .add("noop", || {})
+    }
+
+    #[test]
+    fn test_call_rust_fns() {
+        let fns = call_rust_fns();
+        assert_eq!(
+            fns.call("length".to_string(), vec![Arc::new(vec![1u8, 2, 3]).into_param()]),
+            vec!["3".to_string().into_param()]
+        );
+        let flipped = fns.call("flip".to_string(), vec![Point { x: 1., y: 2. }.into_param()]);
+        assert_eq!(Point::from_param(&flipped[0]), Ok(Point { x: 2., y: 1. }));
+        assert_eq!(fns.call("noop".to_string(), vec![]), vec![]);
+
+        let call_split = || fns.call("split".to_string(), vec!["a".to_string().into_param()]);
+        let error = std::panic::catch_unwind(std::panic::AssertUnwindSafe(call_split));
+        assert_eq!(error.unwrap_err().downcast_ref::<String>().unwrap(), "call_rust: `split`: expected 2 params, got 1");
+
+        let declarations = fns.typescript_declarations();
+        assert!(declarations.contains("export type Point = {\n"));
+        assert!(declarations.contains("export interface RustFunctions {\n"));
+        assert!(declarations.contains("  \"length\": { params: [Uint8Array]; returns: [string] };\n"));
+        assert!(declarations.contains("  \"flip\": { params: [ZapParamBytes<Point>]; returns: [ZapParamBytes<Point>] };\n"));
+        assert!(declarations.contains("  \"split\": { params: [string, string]; returns: [string, string] };\n"));
+        assert!(declarations.contains("  \"noop\": { params: []; returns: [] };\n"));
+        assert_eq!(declarations.matches("export type Point").count(), 1);
+    }
+}
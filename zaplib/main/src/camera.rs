@@ -0,0 +1,519 @@
+//! Capturing video from a camera into a [`Texture`], e.g. for computer vision demos or overlays drawn with shaders.
+//!
+//! ```ignore
+//! // In `handle`:
+//! if let Event::Construct = event {
+//!     self.camera.start(cx, CameraOptions::default());
+//! }
+//! match self.camera.handle(cx, event) {
+//!     Some(CameraEvent::Frame) => {
+//!         // Pixels of the new frame, as RGBA bytes.
+//!         let pixels = self.camera.texture_handle().unwrap().get_image_mut(cx);
+//!     }
+//!     Some(CameraEvent::Error(err)) => log!("{}", err),
+//!     _ => {}
+//! }
+//!
+//! // In `draw`:
+//! if let Some(texture_handle) = self.camera.texture_handle() {
+//!     ImageIns::draw(cx, rect, texture_handle);
+//! }
+//! ```
+//!
+//! Backends:
+//! * WebAssembly: `getUserMedia`, which asks the user for permission. The stream plays in an `HTMLVideoElement`,
+//!   which we copy frames from like in [`crate::video`].
+//! * Native: `ffmpeg`, which needs to be installed and on the `PATH`, using Video4Linux on Linux, AVFoundation on
+//!   Mac, and DirectShow on Windows.
+
+use crate::*;
+use std::fmt;
+use std::future::Future;
+
+/// A camera, as returned by [`devices`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct CameraDevice {
+    /// Identifier to pass in [`CameraOptions::device_id`].
+    pub id: String,
+    /// Human-readable name. Browsers only give names after the user has given permission to use a camera.
+    pub name: String,
+    /// Whether this is the device that is used when [`CameraOptions::device_id`] is [`None`].
+    pub is_default: bool,
+}
+
+/// Options for [`CameraTexture::start`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct CameraOptions {
+    /// Device to capture from, as given by [`devices`]. [`None`] for the default device.
+    pub device_id: Option<String>,
+    /// Requested width in pixels. Browsers pick the closest resolution that the camera supports; check
+    /// [`CameraEvent::Started`] for what you actually got. On native platforms frames are scaled to this size.
+    pub width: usize,
+    /// Requested height in pixels; see [`CameraOptions::width`].
+    pub height: usize,
+    /// Requested number of frames per second.
+    pub frame_rate: f64,
+}
+
+impl Default for CameraOptions {
+    fn default() -> Self {
+        Self { device_id: None, width: 640, height: 480, frame_rate: 30. }
+    }
+}
+
+/// Information about a started camera, as given by [`CameraEvent::Started`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct CameraInfo {
+    /// Width of the frames (and thus the texture) in pixels.
+    pub width: usize,
+    /// Height of the frames (and thus the texture) in pixels.
+    pub height: usize,
+}
+
+/// Returned by [`CameraTexture::handle`].
+#[derive(Clone, Debug, PartialEq)]
+pub enum CameraEvent {
+    /// The camera passed to [`CameraTexture::start`] is running, and frames are on their way to the texture.
+    Started(CameraInfo),
+    /// A new frame was copied to the texture.
+    Frame,
+    /// Starting or capturing failed, e.g. because the camera was disconnected. The camera is stopped.
+    Error(CameraError),
+}
+
+/// Why starting or capturing from a camera failed.
+#[derive(Clone, Debug, PartialEq)]
+pub enum CameraError {
+    /// The user (or browser) did not give permission to use the camera.
+    NotAllowed,
+    /// The requested device does not exist, or there is no camera at all.
+    DeviceNotFound,
+    /// Camera capture is not supported, e.g. because `ffmpeg` is not installed.
+    NotSupported(String),
+    /// Any other error.
+    Other(String),
+}
+
+impl fmt::Display for CameraError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CameraError::NotAllowed => write!(f, "Not allowed to use the camera"),
+            CameraError::DeviceNotFound => write!(f, "Camera not found"),
+            CameraError::NotSupported(message) => write!(f, "Camera not supported: {}", message),
+            CameraError::Other(message) => write!(f, "Camera error: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for CameraError {}
+
+#[cfg(target_arch = "wasm32")]
+impl From<CallJsError> for CameraError {
+    fn from(err: CallJsError) -> Self {
+        match err {
+            // See `video.ts`.
+            CallJsError::Thrown(message) if message.starts_with("NotAllowedError") => CameraError::NotAllowed,
+            CallJsError::Thrown(message)
+                if message.starts_with("NotFoundError") || message.starts_with("OverconstrainedError") =>
+            {
+                CameraError::DeviceNotFound
+            }
+            CallJsError::Thrown(message) if message.starts_with("NotSupportedError") => CameraError::NotSupported(message),
+            CallJsError::Thrown(message) => CameraError::Other(message),
+            CallJsError::TimedOut => CameraError::Other("Timed out".to_string()),
+        }
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+use web::Camera;
+
+#[cfg(not(target_arch = "wasm32"))]
+use native::Camera;
+
+/// List the cameras that can be passed in [`CameraOptions::device_id`].
+pub fn devices(cx: &mut Cx) -> impl Future<Output = Result<Vec<CameraDevice>, CameraError>> {
+    Camera::devices(cx)
+}
+
+/// A [`Texture`] that shows the latest frame from a camera.
+///
+/// Call [`CameraTexture::handle`] for every event. See the [module documentation](self) for an example.
+#[derive(Default)]
+pub struct CameraTexture {
+    texture: Texture,
+    camera: Option<Camera>,
+    info: Option<CameraInfo>,
+    start_task: Option<Task<Result<(Camera, CameraInfo), CameraError>>>,
+    /// Frame being copied into by JS.
+    #[cfg(target_arch = "wasm32")]
+    frame_task: Option<Task<video::web::FrameOutput<CallJsError>>>,
+    /// Buffer for the next [`CameraTexture::frame_task`], reused between frames.
+    #[cfg(target_arch = "wasm32")]
+    frame_buffer: Vec<u32>,
+    /// Stream time of the last copied frame, to tell whether there is a new one.
+    #[cfg(target_arch = "wasm32")]
+    last_frame_time: f64,
+}
+
+impl CameraTexture {
+    /// Start capturing, replacing any running camera. [`CameraTexture::handle`] returns [`CameraEvent::Started`]
+    /// once frames are coming in.
+    pub fn start(&mut self, cx: &mut Cx, options: CameraOptions) {
+        self.stop();
+        let future = Camera::start(cx, options);
+        self.start_task = Some(cx.spawn(future));
+    }
+
+    /// Stop capturing. The texture keeps showing the last frame.
+    pub fn stop(&mut self) {
+        self.camera = None;
+        self.info = None;
+        self.start_task = None;
+        #[cfg(target_arch = "wasm32")]
+        {
+            self.frame_task = None;
+            self.last_frame_time = -1.;
+        }
+    }
+
+    /// Handle an event. Returns a [`CameraEvent`] when the camera started, there is a new frame, or something failed.
+    pub fn handle(&mut self, cx: &mut Cx, event: &mut Event) -> Option<CameraEvent> {
+        if let Some(result) = self.start_task.as_ref().and_then(|task| task.take_output(event)) {
+            self.start_task = None;
+            return match result {
+                Ok((camera, info)) => {
+                    if let Some(texture_handle) = self.texture.handle {
+                        texture_handle.set_dimensions(cx, info.width, info.height);
+                    } else {
+                        self.texture.get_with_dimensions(cx, info.width, info.height);
+                    }
+                    self.camera = Some(camera);
+                    self.info = Some(info.clone());
+                    cx.request_next_frame();
+                    Some(CameraEvent::Started(info))
+                }
+                Err(err) => Some(CameraEvent::Error(err)),
+            };
+        }
+
+        #[cfg(target_arch = "wasm32")]
+        {
+            if let Some(camera_event) = self.handle_frame_task(cx, event) {
+                return Some(camera_event);
+            }
+        }
+
+        if let Event::NextFrame = event {
+            return self.next_frame(cx);
+        }
+        None
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn handle_frame_task(&mut self, cx: &mut Cx, event: &mut Event) -> Option<CameraEvent> {
+        let (frame_buffer, result) = self.frame_task.as_ref().and_then(|task| task.take_output(event))?;
+        self.frame_task = None;
+        self.camera.as_ref()?;
+        match result {
+            Ok(state) => {
+                cx.request_next_frame();
+                let is_new_frame = state.current_time != self.last_frame_time;
+                self.last_frame_time = state.current_time;
+                if is_new_frame {
+                    if let Some(texture_handle) = self.texture.handle {
+                        texture_handle.get_image_mut(cx).copy_from_slice(&frame_buffer);
+                        cx.request_draw();
+                    }
+                }
+                self.frame_buffer = frame_buffer;
+                if is_new_frame {
+                    Some(CameraEvent::Frame)
+                } else {
+                    None
+                }
+            }
+            Err(err) => {
+                self.stop();
+                Some(CameraEvent::Error(err.into()))
+            }
+        }
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn next_frame(&mut self, cx: &mut Cx) -> Option<CameraEvent> {
+        let (camera, info) = (self.camera.as_ref()?, self.info.as_ref()?);
+        if self.frame_task.is_none() {
+            let mut frame_buffer = std::mem::take(&mut self.frame_buffer);
+            frame_buffer.resize(info.width * info.height, 0);
+            let copy_frame = camera.video.copy_frame(cx, frame_buffer, info.width, info.height);
+            self.frame_task = Some(cx.spawn(copy_frame));
+        }
+        None
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn next_frame(&mut self, cx: &mut Cx) -> Option<CameraEvent> {
+        let camera = self.camera.as_ref()?;
+        match camera.latest_frame() {
+            Ok(frame) => {
+                cx.request_next_frame();
+                let (frame, texture_handle) = (frame?, self.texture.handle?);
+                texture_handle.get_image_mut(cx).copy_from_slice(&frame);
+                cx.request_draw();
+                Some(CameraEvent::Frame)
+            }
+            Err(err) => {
+                self.stop();
+                Some(CameraEvent::Error(err))
+            }
+        }
+    }
+
+    /// Whether the camera is started (and not stopped since).
+    pub fn is_running(&self) -> bool {
+        self.camera.is_some()
+    }
+
+    /// Information about the running camera, or [`None`] if there's no camera or it's still starting.
+    pub fn info(&self) -> Option<&CameraInfo> {
+        self.info.as_ref()
+    }
+
+    /// The texture to draw, e.g. using [`ImageIns::draw`]. [`None`] until a camera has started.
+    pub fn texture_handle(&self) -> Option<TextureHandle> {
+        self.texture.handle
+    }
+}
+
+/// Backend that plays a `getUserMedia` stream in a video element; see `video.ts`.
+#[cfg(target_arch = "wasm32")]
+mod web {
+    use super::*;
+    use crate::video::web::Video;
+
+    pub(super) struct Camera {
+        pub(super) video: Video,
+    }
+
+    impl Camera {
+        pub(super) fn devices(cx: &mut Cx) -> impl Future<Output = Result<Vec<CameraDevice>, CameraError>> {
+            let future = cx.call_js_async("_zaplibCameraDevices", vec![], None);
+            async move {
+                let params: Vec<String> = future.await?.into_iter().map(ZapParam::into_string).collect();
+                Ok(params
+                    .chunks_exact(3)
+                    .map(|device| CameraDevice { id: device[0].clone(), name: device[1].clone(), is_default: device[2] == "1" })
+                    .collect())
+            }
+        }
+
+        pub(super) fn start(
+            cx: &mut Cx,
+            options: CameraOptions,
+        ) -> impl Future<Output = Result<(Camera, CameraInfo), CameraError>> {
+            let params = vec![
+                options.device_id.unwrap_or_default().into_param(),
+                options.width.to_string().into_param(),
+                options.height.to_string().into_param(),
+                options.frame_rate.to_string().into_param(),
+            ];
+            let future = Video::open(cx, "_zaplibCameraStart", params);
+            async move {
+                let (video, params) = future.await?;
+                let parse_error = |_| CameraError::Other("Invalid camera dimensions".to_string());
+                let info = CameraInfo {
+                    width: params[0].parse().map_err(parse_error)?,
+                    height: params[1].parse().map_err(parse_error)?,
+                };
+                Ok((Camera { video }, info))
+            }
+        }
+    }
+}
+
+/// Backend that runs `ffmpeg` with the platform's capture API as input; see [`crate::ffmpeg`].
+#[cfg(not(target_arch = "wasm32"))]
+mod native {
+    use super::*;
+    use crate::ffmpeg::{Decoder, DecoderOutput, FfmpegError};
+    use std::process::{Command, Stdio};
+
+    pub(super) struct Camera {
+        decoder: Decoder,
+    }
+
+    impl Camera {
+        pub(super) fn devices(cx: &mut Cx) -> impl Future<Output = Result<Vec<CameraDevice>, CameraError>> {
+            let _ = cx;
+            spawn_blocking(list_devices)
+        }
+
+        pub(super) fn start(
+            cx: &mut Cx,
+            options: CameraOptions,
+        ) -> impl Future<Output = Result<(Camera, CameraInfo), CameraError>> {
+            let _ = cx;
+            spawn_blocking(move || {
+                let device_id = match options.device_id {
+                    Some(device_id) => device_id,
+                    None => list_devices()?.into_iter().find(|device| device.is_default).ok_or(CameraError::DeviceNotFound)?.id,
+                };
+                if cfg!(target_os = "linux") && !std::path::Path::new(&device_id).exists() {
+                    return Err(CameraError::DeviceNotFound);
+                }
+                let (width, height) = (options.width, options.height);
+                let frame_rate = options.frame_rate.to_string();
+                let video_size = format!("{}x{}", width, height);
+                let input = if cfg!(target_os = "linux") {
+                    device_id
+                } else if cfg!(target_os = "macos") {
+                    // Video device index, and no audio.
+                    format!("{}:none", device_id)
+                } else {
+                    format!("video={}", device_id)
+                };
+                let decoder = Decoder::start(
+                    &["-f", input_format(), "-framerate", &frame_rate, "-video_size", &video_size, "-i", &input],
+                    // Cameras might not support the requested size, in which case they pick another one.
+                    &["-vf", &format!("scale={}:{}", width, height)],
+                    width,
+                    height,
+                )?;
+                Ok((Camera { decoder }, CameraInfo { width, height }))
+            })
+        }
+
+        /// Get the latest frame, if there are new ones, skipping older ones.
+        pub(super) fn latest_frame(&self) -> Result<Option<Vec<u32>>, CameraError> {
+            let mut latest = None;
+            loop {
+                match self.decoder.try_next()? {
+                    DecoderOutput::Frame(frame) => latest = Some(frame),
+                    DecoderOutput::Pending => return Ok(latest),
+                    DecoderOutput::Ended => return Err(CameraError::Other("Camera stopped".to_string())),
+                }
+            }
+        }
+    }
+
+    impl From<FfmpegError> for CameraError {
+        fn from(err: FfmpegError) -> Self {
+            match err {
+                FfmpegError::NotInstalled => CameraError::NotSupported("Camera capture requires ffmpeg".to_string()),
+                FfmpegError::Failed(message) => CameraError::Other(message),
+            }
+        }
+    }
+
+    /// Name of the `ffmpeg` input device for the current platform.
+    fn input_format() -> &'static str {
+        if cfg!(target_os = "linux") {
+            "v4l2"
+        } else if cfg!(target_os = "macos") {
+            "avfoundation"
+        } else {
+            "dshow"
+        }
+    }
+
+    fn list_devices() -> Result<Vec<CameraDevice>, CameraError> {
+        if cfg!(target_os = "linux") {
+            let mut devices: Vec<CameraDevice> = std::fs::read_dir("/sys/class/video4linux")
+                .map(|entries| {
+                    entries
+                        .filter_map(|entry| entry.ok())
+                        .map(|entry| {
+                            let name = std::fs::read_to_string(entry.path().join("name")).unwrap_or_default();
+                            let id = format!("/dev/{}", entry.file_name().to_string_lossy());
+                            CameraDevice {
+                                name: if name.trim().is_empty() { id.clone() } else { name.trim().to_string() },
+                                id,
+                                is_default: false,
+                            }
+                        })
+                        .collect()
+                })
+                .unwrap_or_default();
+            devices.sort_by(|a, b| a.id.cmp(&b.id));
+            let default_index = devices.iter().position(|device| device.id == "/dev/video0").unwrap_or(0);
+            if let Some(device) = devices.get_mut(default_index) {
+                device.is_default = true;
+            }
+            Ok(devices)
+        } else {
+            // `ffmpeg` lists devices on stderr, and then fails because there's no output.
+            let output = Command::new("ffmpeg")
+                .args(["-hide_banner", "-f", input_format(), "-list_devices", "true", "-i", ""])
+                .stdin(Stdio::null())
+                .output()
+                .map_err(FfmpegError::from)?;
+            let log = String::from_utf8_lossy(&output.stderr);
+            Ok(if cfg!(target_os = "macos") { parse_avfoundation_devices(&log) } else { parse_dshow_devices(&log) })
+        }
+    }
+
+    /// Parse video devices from `ffmpeg -f avfoundation -list_devices true -i ""`, which logs lines like
+    /// "[AVFoundation indev @ 0x7f8] [0] FaceTime HD Camera", under a line with "AVFoundation video devices:".
+    pub(super) fn parse_avfoundation_devices(log: &str) -> Vec<CameraDevice> {
+        let mut devices = vec![];
+        let mut in_video_devices = false;
+        for line in log.lines() {
+            if line.contains("AVFoundation video devices") {
+                in_video_devices = true;
+            } else if line.contains("AVFoundation audio devices") {
+                in_video_devices = false;
+            } else if in_video_devices {
+                let device = line.split_once("] [").and_then(|(_, rest)| rest.split_once("] "));
+                if let Some((index, name)) = device {
+                    devices.push(CameraDevice {
+                        id: index.to_string(),
+                        name: name.trim().to_string(),
+                        is_default: devices.is_empty(),
+                    });
+                }
+            }
+        }
+        devices
+    }
+
+    /// Parse video devices from `ffmpeg -f dshow -list_devices true -i ""`, which logs lines like
+    /// "[dshow @ 000001] "Integrated Camera" (video)".
+    pub(super) fn parse_dshow_devices(log: &str) -> Vec<CameraDevice> {
+        let mut devices = vec![];
+        for line in log.lines().filter(|line| line.trim_end().ends_with("(video)")) {
+            let mut parts = line.split('"');
+            if let (Some(_), Some(name)) = (parts.next(), parts.next()) {
+                devices.push(CameraDevice { id: name.to_string(), name: name.to_string(), is_default: devices.is_empty() });
+            }
+        }
+        devices
+    }
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use super::native::*;
+    use super::*;
+
+    #[test]
+    fn test_parse_devices() {
+        let log = "[AVFoundation indev @ 0x7f8] AVFoundation video devices:\n[AVFoundation indev @ 0x7f8] [0] FaceTime HD \
+                   Camera\n[AVFoundation indev @ 0x7f8] [1] Capture screen 0\n[AVFoundation indev @ 0x7f8] AVFoundation audio \
+                   devices:\n[AVFoundation indev @ 0x7f8] [0] MacBook Pro Microphone\n";
+        assert_eq!(
+            parse_avfoundation_devices(log),
+            vec![
+                CameraDevice { id: "0".to_string(), name: "FaceTime HD Camera".to_string(), is_default: true },
+                CameraDevice { id: "1".to_string(), name: "Capture screen 0".to_string(), is_default: false },
+            ]
+        );
+
+        let log = "[dshow @ 000001] \"Integrated Camera\" (video)\n[dshow @ 000001]   Alternative name \
+                   \"@device_pnp_\\\\?\\usb\"\n[dshow @ 000001] \"Microphone Array\" (audio)\n";
+        assert_eq!(
+            parse_dshow_devices(log),
+            vec![CameraDevice { id: "Integrated Camera".to_string(), name: "Integrated Camera".to_string(), is_default: true }]
+        );
+    }
+}
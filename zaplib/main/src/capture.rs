@@ -0,0 +1,472 @@
+//! Capturing what is drawn in the main window, e.g. for making demo videos or visual artifacts in automated tests.
+//!
+//! * [`Cx::capture_frame`] grabs a single frame as a [`CapturedFrame`], which you can turn into a PNG file using
+//!   [`CapturedFrame::to_png`].
+//! * [`Cx::start_recording`] and [`Cx::stop_recording`] record a WebM video or a sequence of PNG files. On the web
+//!   this uses `MediaRecorder`, and on native platforms we pipe frames into `ffmpeg` (which needs to be installed and
+//!   on the `PATH`).
+//!
+//! Recordings and frames are returned in memory; to write them to a file, use e.g. [`save_file_async`] or
+//! [`std::fs::write`].
+//!
+//! On native platforms this is currently only supported on Linux.
+
+use crate::*;
+use std::fmt;
+use std::future::Future;
+use std::io::Write;
+#[cfg(not(target_arch = "wasm32"))]
+use std::{
+    cell::RefCell,
+    pin::Pin,
+    rc::Rc,
+    task::{Context, Poll, Waker},
+};
+
+/// A frame captured using [`Cx::capture_frame`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct CapturedFrame {
+    /// Width in physical pixels.
+    pub width: usize,
+    /// Height in physical pixels.
+    pub height: usize,
+    /// Pixels from top to bottom, each containing RGBA bytes (as in [`TextureHandle::get_image_mut`]).
+    pub pixels: Vec<u32>,
+}
+
+impl CapturedFrame {
+    /// Encode as a PNG file.
+    pub fn to_png(&self) -> Vec<u8> {
+        // Every row starts with a filter type byte, for which we always use 0 (none).
+        let mut raw = Vec::with_capacity(self.height * (1 + self.width * 4));
+        for row in self.pixels.chunks_exact(self.width.max(1)).take(self.height) {
+            raw.push(0);
+            for pixel in row {
+                raw.extend_from_slice(&pixel.to_ne_bytes());
+            }
+        }
+        let mut encoder = flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::fast());
+        encoder.write_all(&raw).unwrap();
+        let image_data = encoder.finish().unwrap();
+
+        let mut header = Vec::with_capacity(13);
+        header.extend_from_slice(&(self.width as u32).to_be_bytes());
+        header.extend_from_slice(&(self.height as u32).to_be_bytes());
+        // Bit depth 8, color type 6 (RGBA), default compression, filtering, and no interlacing.
+        header.extend_from_slice(&[8, 6, 0, 0, 0]);
+
+        let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+        write_png_chunk(&mut png, b"IHDR", &header);
+        write_png_chunk(&mut png, b"IDAT", &image_data);
+        write_png_chunk(&mut png, b"IEND", &[]);
+        png
+    }
+}
+
+fn write_png_chunk(png: &mut Vec<u8>, chunk_type: &[u8; 4], data: &[u8]) {
+    png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    png.extend_from_slice(chunk_type);
+    png.extend_from_slice(data);
+    let mut crc = flate2::Crc::new();
+    crc.update(chunk_type);
+    crc.update(data);
+    png.extend_from_slice(&crc.sum().to_be_bytes());
+}
+
+/// Why capturing or recording failed.
+#[derive(Clone, Debug, PartialEq)]
+pub enum CaptureError {
+    /// Capturing is not supported on this platform or in this browser, or `ffmpeg` is not installed.
+    NotSupported(String),
+    /// [`Cx::start_recording`] was called while already recording, or [`Cx::stop_recording`] while not recording.
+    InvalidState(String),
+    /// Any other error.
+    Other(String),
+}
+
+impl fmt::Display for CaptureError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CaptureError::NotSupported(message) => write!(f, "Capturing not supported: {}", message),
+            CaptureError::InvalidState(message) => write!(f, "Invalid capture state: {}", message),
+            CaptureError::Other(message) => write!(f, "Capture error: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for CaptureError {}
+
+#[cfg(target_arch = "wasm32")]
+impl From<CallJsError> for CaptureError {
+    fn from(err: CallJsError) -> Self {
+        match err {
+            // See `capture.ts`.
+            CallJsError::Thrown(message) if message.starts_with("NotSupportedError") => CaptureError::NotSupported(message),
+            CallJsError::Thrown(message) => CaptureError::Other(message),
+            CallJsError::TimedOut => CaptureError::Other("Timed out".to_string()),
+        }
+    }
+}
+
+/// Format of a recording; see [`RecordingOptions::format`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RecordingFormat {
+    /// A WebM video file.
+    WebM,
+    /// A PNG file for every frame.
+    PngSequence,
+}
+
+/// Options for [`Cx::start_recording`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct RecordingOptions {
+    pub format: RecordingFormat,
+    /// Number of frames per second in the recording. When the app draws less often (e.g. because nothing changes),
+    /// we repeat frames.
+    pub frame_rate: f64,
+}
+
+impl Default for RecordingOptions {
+    fn default() -> Self {
+        Self { format: RecordingFormat::WebM, frame_rate: 30. }
+    }
+}
+
+/// Result of [`Cx::stop_recording`].
+#[derive(Clone, Debug, PartialEq)]
+pub enum RecordingOutput {
+    /// Contents of a WebM file.
+    WebM(Vec<u8>),
+    /// Contents of a PNG file for every frame.
+    PngSequence(Vec<Vec<u8>>),
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Default)]
+struct FrameCaptureState {
+    result: Option<Result<CapturedFrame, CaptureError>>,
+    waker: Option<Waker>,
+}
+
+/// Resolves with the next frame drawn in the main window. Created using [`Cx::capture_frame`].
+#[cfg(not(target_arch = "wasm32"))]
+struct FrameCaptureFuture {
+    state: Rc<RefCell<FrameCaptureState>>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Future for FrameCaptureFuture {
+    type Output = Result<CapturedFrame, CaptureError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut state = self.state.borrow_mut();
+        if let Some(result) = state.result.take() {
+            Poll::Ready(result)
+        } else {
+            state.waker = Some(cx.waker().clone());
+            Poll::Pending
+        }
+    }
+}
+
+/// Pending captures and the current recording, which the renderer feeds with frames of the main window.
+#[derive(Default)]
+pub(crate) struct CxFrameCapture {
+    #[cfg(not(target_arch = "wasm32"))]
+    frame_requests: Vec<Rc<RefCell<FrameCaptureState>>>,
+    #[cfg(not(target_arch = "wasm32"))]
+    recording: Option<native::Recording>,
+    #[cfg(target_arch = "wasm32")]
+    is_recording: bool,
+}
+
+impl CxFrameCapture {
+    /// Whether the renderer should read back the next frame of the main window and pass it to
+    /// [`CxFrameCapture::process_frame`].
+    #[cfg(target_os = "linux")]
+    pub(crate) fn wants_frame(&self) -> bool {
+        !self.frame_requests.is_empty() || self.recording.is_some()
+    }
+
+    #[cfg(target_os = "linux")]
+    pub(crate) fn process_frame(&mut self, frame: CapturedFrame) {
+        if let Some(recording) = &mut self.recording {
+            recording.push_frame(&frame);
+        }
+        for request in self.frame_requests.drain(..) {
+            let mut state = request.borrow_mut();
+            state.result = Some(Ok(frame.clone()));
+            if let Some(waker) = state.waker.take() {
+                waker.wake();
+            }
+        }
+    }
+}
+
+impl Cx {
+    /// Capture the next frame drawn in the main window. This also triggers a redraw, so the frame reflects the
+    /// current state.
+    ///
+    /// ```ignore
+    /// // In `handle`:
+    /// if let Event::KeyDown(KeyEvent { key_code: KeyCode::F12, .. }) = event {
+    ///     let future = cx.capture_frame();
+    ///     self.capture_task = Some(cx.spawn(future));
+    /// }
+    /// if let Some(Ok(frame)) = self.capture_task.as_ref().and_then(|task| task.take_output(event)) {
+    ///     save_file_async(cx, "screenshot.png", frame.to_png());
+    /// }
+    /// ```
+    pub fn capture_frame(&mut self) -> impl Future<Output = Result<CapturedFrame, CaptureError>> {
+        #[cfg(target_arch = "wasm32")]
+        {
+            self.repaint_main_window();
+            let future = self.call_js_async("_zaplibCaptureFrame", vec![], None);
+            async move {
+                let params = future.await?;
+                let parse_error = |_| CaptureError::Other("Invalid frame dimensions".to_string());
+                let width = params[0].as_str().parse().map_err(parse_error)?;
+                let height = params[1].as_str().parse().map_err(parse_error)?;
+                let pixels =
+                    params[2].as_u8_slice().chunks_exact(4).map(|rgba| u32::from_ne_bytes([rgba[0], rgba[1], rgba[2], rgba[3]]));
+                Ok(CapturedFrame { width, height, pixels: pixels.collect() })
+            }
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            let state = Rc::new(RefCell::new(FrameCaptureState::default()));
+            if cfg!(target_os = "linux") {
+                self.frame_capture.frame_requests.push(Rc::clone(&state));
+                self.repaint_main_window();
+            } else {
+                state.borrow_mut().result = Some(Err(CaptureError::NotSupported("Only supported on Linux".to_string())));
+            }
+            FrameCaptureFuture { state }
+        }
+    }
+
+    /// Start recording the main window. Use [`Cx::stop_recording`] to get the result. Only one recording can run at
+    /// a time.
+    ///
+    /// In WebAssembly, errors such as the browser not supporting WebM are returned by [`Cx::stop_recording`].
+    pub fn start_recording(&mut self, options: RecordingOptions) -> Result<(), CaptureError> {
+        if self.is_recording() {
+            return Err(CaptureError::InvalidState("Already recording".to_string()));
+        }
+        #[cfg(target_arch = "wasm32")]
+        {
+            let format = match options.format {
+                RecordingFormat::WebM => "webm",
+                RecordingFormat::PngSequence => "png",
+            };
+            self.call_js(
+                "_zaplibRecordingStart",
+                vec![format.to_string().into_param(), options.frame_rate.to_string().into_param()],
+            );
+            self.frame_capture.is_recording = true;
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            if !cfg!(target_os = "linux") {
+                return Err(CaptureError::NotSupported("Only supported on Linux".to_string()));
+            }
+            self.frame_capture.recording = Some(native::Recording::start(options)?);
+            self.repaint_main_window();
+        }
+        Ok(())
+    }
+
+    /// Stop the recording started using [`Cx::start_recording`], and get the recorded files.
+    pub fn stop_recording(&mut self) -> impl Future<Output = Result<RecordingOutput, CaptureError>> {
+        #[cfg(target_arch = "wasm32")]
+        {
+            let is_recording = std::mem::replace(&mut self.frame_capture.is_recording, false);
+            let future = is_recording.then(|| self.call_js_async("_zaplibRecordingStop", vec![], None));
+            async move {
+                let future = future.ok_or_else(|| CaptureError::InvalidState("Not recording".to_string()))?;
+                let mut params = future.await?;
+                let format = params.remove(0).into_string();
+                let mut files = params.into_iter().map(ZapParam::into_vec_u8);
+                if format == "webm" {
+                    Ok(RecordingOutput::WebM(files.next().unwrap_or_default()))
+                } else {
+                    Ok(RecordingOutput::PngSequence(files.collect()))
+                }
+            }
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            let recording = self.frame_capture.recording.take();
+            async move {
+                let recording = recording.ok_or_else(|| CaptureError::InvalidState("Not recording".to_string()))?;
+                recording.finish().await
+            }
+        }
+    }
+
+    /// Whether a recording started using [`Cx::start_recording`] is running.
+    pub fn is_recording(&self) -> bool {
+        #[cfg(target_arch = "wasm32")]
+        return self.frame_capture.is_recording;
+        #[cfg(not(target_arch = "wasm32"))]
+        return self.frame_capture.recording.is_some();
+    }
+
+    /// Make sure that the main window gets painted, even if nothing changed.
+    fn repaint_main_window(&mut self) {
+        for pass in &mut self.passes {
+            if let CxPassDepOf::Window(0) = pass.dep_of {
+                pass.paint_dirty = true;
+            }
+        }
+        self.request_draw();
+    }
+}
+
+/// Encodes recordings on a separate thread, so that drawing doesn't slow down. For WebM we pipe raw frames into
+/// `ffmpeg`; see also [`crate::ffmpeg`].
+#[cfg(not(target_arch = "wasm32"))]
+mod native {
+    use super::*;
+    use crate::ffmpeg::FfmpegError;
+    use std::io::Read;
+    use std::process::{Command, Stdio};
+    use std::sync::mpsc;
+    use std::sync::Arc;
+    use std::thread::JoinHandle;
+
+    pub(super) struct Recording {
+        frame_rate: f64,
+        started_at: UniversalInstant,
+        /// Number of frames sent to the encoder so far.
+        frame_count: u64,
+        last_frame: Option<Arc<CapturedFrame>>,
+        sender: mpsc::Sender<Arc<CapturedFrame>>,
+        encoder: JoinHandle<Result<RecordingOutput, CaptureError>>,
+    }
+
+    impl Recording {
+        pub(super) fn start(options: RecordingOptions) -> Result<Self, CaptureError> {
+            if options.frame_rate.is_nan() || options.frame_rate <= 0. {
+                return Err(CaptureError::Other("Frame rate must be positive".to_string()));
+            }
+            let (sender, receiver) = mpsc::channel();
+            let frame_rate = options.frame_rate;
+            let encoder = std::thread::Builder::new()
+                .name("Zaplib recording encoder".to_string())
+                .spawn(move || match options.format {
+                    RecordingFormat::WebM => encode_webm(receiver, frame_rate),
+                    RecordingFormat::PngSequence => {
+                        Ok(RecordingOutput::PngSequence(receiver.into_iter().map(|frame| frame.to_png()).collect()))
+                    }
+                })
+                .map_err(|err| CaptureError::Other(err.to_string()))?;
+            Ok(Self { frame_rate, started_at: UniversalInstant::now(), frame_count: 0, last_frame: None, sender, encoder })
+        }
+
+        /// Called for every frame that is drawn. We send a frame for every frame time that passed, since the app
+        /// doesn't necessarily draw at a constant rate.
+        #[cfg(target_os = "linux")]
+        pub(super) fn push_frame(&mut self, frame: &CapturedFrame) {
+            let frame = Arc::new(frame.clone());
+            self.send_frames_until_now(&frame);
+            self.last_frame = Some(frame);
+        }
+
+        fn send_frames_until_now(&mut self, frame: &Arc<CapturedFrame>) {
+            let due_count = (self.started_at.elapsed().as_secs_f64() * self.frame_rate) as u64 + 1;
+            while self.frame_count < due_count {
+                // Fails only if the encoder stopped, in which case `finish` returns its error.
+                let _ = self.sender.send(Arc::clone(frame));
+                self.frame_count += 1;
+            }
+        }
+
+        pub(super) fn finish(mut self) -> impl Future<Output = Result<RecordingOutput, CaptureError>> {
+            // Repeat the last frame until now, since it's still on screen.
+            if let Some(last_frame) = self.last_frame.take() {
+                self.send_frames_until_now(&last_frame);
+            }
+            let Recording { sender, encoder, .. } = self;
+            drop(sender);
+            spawn_blocking(move || encoder.join().unwrap_or_else(|_| Err(CaptureError::Other("Encoder panicked".to_string()))))
+        }
+    }
+
+    impl From<FfmpegError> for CaptureError {
+        fn from(err: FfmpegError) -> Self {
+            match err {
+                FfmpegError::NotInstalled => CaptureError::NotSupported("Recording WebM requires ffmpeg".to_string()),
+                FfmpegError::Failed(message) => CaptureError::Other(message),
+            }
+        }
+    }
+
+    fn encode_webm(receiver: mpsc::Receiver<Arc<CapturedFrame>>, frame_rate: f64) -> Result<RecordingOutput, CaptureError> {
+        // We need the size of the first frame before starting `ffmpeg`.
+        let first_frame = match receiver.recv() {
+            Ok(frame) => frame,
+            Err(_) => return Err(CaptureError::Other("No frames were recorded".to_string())),
+        };
+        let (width, height) = (first_frame.width, first_frame.height);
+        let mut child = Command::new("ffmpeg")
+            .args(["-v", "error", "-f", "rawvideo", "-pix_fmt", "rgba"])
+            .args(["-s", &format!("{}x{}", width, height), "-r", &frame_rate.to_string(), "-i", "-"])
+            .args(["-c:v", "libvpx", "-b:v", "8M", "-deadline", "realtime", "-cpu-used", "8", "-f", "webm", "-"])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(FfmpegError::from)?;
+        let mut stdout = child.stdout.take().unwrap();
+        let output_reader = std::thread::spawn(move || {
+            let mut output = vec![];
+            stdout.read_to_end(&mut output).map(|_| output)
+        });
+
+        let mut stdin = child.stdin.take().unwrap();
+        let mut last_frame = first_frame;
+        let mut write_result = Ok(());
+        for frame in std::iter::once(Arc::clone(&last_frame)).chain(receiver) {
+            // The window might have been resized, in which case we repeat the last frame that has the right size.
+            if frame.width == width && frame.height == height {
+                last_frame = frame;
+            }
+            let bytes =
+                unsafe { std::slice::from_raw_parts(last_frame.pixels.as_ptr() as *const u8, last_frame.pixels.len() * 4) };
+            write_result = stdin.write_all(bytes);
+            if write_result.is_err() {
+                break;
+            }
+        }
+        drop(stdin);
+
+        let output = output_reader.join().unwrap().map_err(|err| CaptureError::Other(err.to_string()))?;
+        let mut errors = String::new();
+        if let Some(mut stderr) = child.stderr.take() {
+            let _ = stderr.read_to_string(&mut errors);
+        }
+        let status = child.wait().map_err(|err| CaptureError::Other(err.to_string()))?;
+        if !status.success() || write_result.is_err() {
+            return Err(CaptureError::Other(errors.trim().to_string()));
+        }
+        Ok(RecordingOutput::WebM(output))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_png() {
+        let frame = CapturedFrame { width: 2, height: 1, pixels: vec![u32::from_ne_bytes([255, 0, 0, 255]); 2] };
+        let png = frame.to_png();
+        assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+        // IHDR chunk with the dimensions and its CRC, as computed by other PNG encoders.
+        assert_eq!(&png[8..16], &[0, 0, 0, 13, b'I', b'H', b'D', b'R']);
+        assert_eq!(&png[16..29], &[0, 0, 0, 2, 0, 0, 0, 1, 8, 6, 0, 0, 0]);
+        assert_eq!(&png[29..33], &[0xf4, 0x22, 0x7f, 0x8a]);
+        assert_eq!(&png[png.len() - 12..], &[0, 0, 0, 0, b'I', b'E', b'N', b'D', 0xae, 0x42, 0x60, 0x82]);
+    }
+}
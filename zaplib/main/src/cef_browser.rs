@@ -104,6 +104,12 @@ impl MaybeCefBrowser {
         params.insert(0, format!("{}", callback_id).into_param());
         self.call_js("_zaplibReturnParams", params);
     }
+
+    #[allow(dead_code)] // We never call this in win/linux currently.
+    pub(crate) fn stream_to_js(&mut self, callback_id: u32, mut params: Vec<ZapParam>) {
+        params.insert(0, format!("{}", callback_id).into_param());
+        self.call_js("_zaplibStreamChunk", params);
+    }
 }
 
 #[derive(Debug)]
@@ -316,10 +322,14 @@ impl RenderProcessHandler for MyRenderProcessHandler {
             let name = args[0].get_string_value();
             let params = get_zap_params(&args[1]);
             let callback_id = args[2].get_uint_value();
+            let streaming = args[3].get_bool_value();
             Cx::send_event_from_any_thread(Event::System(SystemEvent::WebRustCall(Some(WebRustCallEvent {
                 name,
                 params,
                 callback_id,
+                streaming,
+                // TODO(JP): Support cancellation in CEF.
+                cancel_token: Default::default(),
             }))));
             None
         }));
@@ -695,7 +705,8 @@ impl CefBrowser {
     }
 
     fn set_mouse_cursor(&mut self, cursor: MouseCursor) {
-        let code = format!("if (window.fromCefSetMouseCursor) window.fromCefSetMouseCursor({});", cursor as u8);
+        // [`MouseCursor::Custom`] is not in cursor_map.ts, so it shows the default cursor.
+        let code = format!("if (window.fromCefSetMouseCursor) window.fromCefSetMouseCursor({});", cursor.cursor_map_index());
         let script_url = "".to_string();
         let start_line = 0;
         let frame = self.browser.get_main_frame().unwrap();
@@ -2,6 +2,10 @@
 //!
 //! Be sure to keep this in sync with cursor_map.ts!
 
+use std::hash::{Hash, Hasher};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
 use crate::*;
 
 /// The type of mouse cursor to show. Enjoy the ASCII art here.
@@ -9,7 +13,7 @@ use crate::*;
 #[repr(u8)]
 pub enum MouseCursor {
     /// Don't show the cursor.
-    Hidden = 0,
+    Hidden,
 
     /// ```text
     ///  *
@@ -172,28 +176,6 @@ pub enum MouseCursor {
     /// ```
     NoDrop,
 
-    /// ```text
-    ///
-    ///    * * * *
-    ///    * * * *
-    /// *  * * * *
-    /// *  *     *
-    ///  * *     *
-    ///  *      *
-    /// ```
-    Grab,
-
-    /// ```text
-    ///
-    ///
-    ///    * * * *
-    ///  * * * * *
-    /// *  *     *
-    ///  * *     *
-    ///  *      *
-    /// ```
-    Grabbing,
-
     /// ```text
     ///     ^
     ///   < * >
@@ -308,6 +290,93 @@ pub enum MouseCursor {
     ///     v
     /// ```
     RowResize,
+
+    /// An open hand, e.g. for content that can be panned by dragging.
+    ///
+    /// ```text
+    ///
+    ///    * * * *
+    ///    * * * *
+    /// *  * * * *
+    /// *  *     *
+    ///  * *     *
+    ///  *      *
+    /// ```
+    Grab,
+
+    /// A closed hand, e.g. while panning content by dragging.
+    ///
+    /// ```text
+    ///
+    ///
+    ///    * * * *
+    ///  * * * * *
+    /// *  *     *
+    ///  * *     *
+    ///  *      *
+    /// ```
+    Grabbing,
+
+    /// A cursor with your own image; see [`CustomCursor`].
+    Custom(CustomCursor),
+}
+
+impl MouseCursor {
+    /// The index of the variant, as used in cursor_map.ts. [`MouseCursor::Custom`] is sent separately.
+    #[cfg(any(target_arch = "wasm32", feature = "cef"))]
+    pub(crate) fn cursor_map_index(&self) -> u8 {
+        // With `#[repr(u8)]` the discriminant is always the first byte, also for variants with fields.
+        unsafe { *(self as *const Self as *const u8) }
+    }
+}
+
+/// The next number to use for [`CustomCursor::id`].
+static NEXT_CUSTOM_CURSOR_ID: AtomicU64 = AtomicU64::new(1);
+
+/// A mouse cursor with your own image, for use with [`MouseCursor::Custom`].
+///
+/// Platforms create a native cursor the first time a [`CustomCursor`] is shown and keep it around, so create
+/// these once (e.g. when constructing your component) and clone them, instead of creating new ones every frame.
+/// Clones are cheap and share the same image.
+///
+/// Not supported on Android and iOS.
+#[derive(Clone, Debug)]
+pub struct CustomCursor {
+    /// Unique per call to [`CustomCursor::new`]; used by platforms to cache native cursors.
+    pub(crate) id: u64,
+    pub(crate) width: usize,
+    pub(crate) height: usize,
+    /// 8-bit RGBA pixels (not premultiplied), row by row starting at the top left, like [`WindowIcon::rgba`].
+    pub(crate) rgba: Arc<Vec<u8>>,
+    /// The point in the image that is at the mouse position, in pixels from the top left.
+    pub(crate) hotspot: Vec2,
+}
+
+impl CustomCursor {
+    /// Create a cursor from 8-bit RGBA pixels (not premultiplied), row by row starting at the top left. The
+    /// `hotspot` is the point in the image that is at the mouse position, in pixels from the top left.
+    ///
+    /// The image is shown at one image pixel per logical pixel on Mac OS X, Wayland, and the web, and per physical
+    /// pixel on Windows and X11. Keep it at most 32x32 for it to work everywhere; some platforms refuse larger
+    /// cursors, and then show the default cursor instead.
+    pub fn new(width: usize, height: usize, rgba: Vec<u8>, hotspot: Vec2) -> Self {
+        assert_eq!(rgba.len(), width * height * 4, "CustomCursor::new: rgba must have 4 bytes per pixel");
+        assert!(width > 0 && height > 0, "CustomCursor::new: image can't be empty");
+        let hotspot = Vec2 { x: hotspot.x.max(0.).min((width - 1) as f32), y: hotspot.y.max(0.).min((height - 1) as f32) };
+        Self { id: NEXT_CUSTOM_CURSOR_ID.fetch_add(1, Ordering::Relaxed), width, height, rgba: Arc::new(rgba), hotspot }
+    }
+}
+
+impl PartialEq for CustomCursor {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+    }
+}
+impl Eq for CustomCursor {}
+impl Hash for CustomCursor {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.id.hash(state);
+    }
 }
 
 impl Cx {
@@ -317,6 +386,54 @@ impl Cx {
     pub fn set_hover_mouse_cursor(&mut self, mouse_cursor: MouseCursor) {
         self.hover_mouse_cursor = Some(mouse_cursor);
     }
+
+    /// Show `mouse_cursor` until [`Cx::pop_mouse_cursor`] is called with the same `component_id`, e.g. for a tool
+    /// mode that stays active after releasing the mouse. Takes precedence over [`Cx::set_hover_mouse_cursor`], but
+    /// not over [`Cx::set_down_mouse_cursor`].
+    ///
+    /// When multiple components push a cursor, the most recently pushed one is shown. Pushing again from the same
+    /// component replaces its previous cursor and moves it to the top. When a component pops its cursor, the
+    /// cursor of the component that pushed before it shows again, regardless of the order in which they pop.
+    pub fn push_mouse_cursor(&mut self, component_id: ComponentId, mouse_cursor: MouseCursor) {
+        self.cursor_stack.retain(|(id, _)| *id != component_id);
+        self.cursor_stack.push((component_id, mouse_cursor));
+    }
+
+    /// Remove the cursor that `component_id` pushed using [`Cx::push_mouse_cursor`], if any. Be sure to also call
+    /// this when your component goes away.
+    pub fn pop_mouse_cursor(&mut self, component_id: ComponentId) {
+        self.cursor_stack.retain(|(id, _)| *id != component_id);
+    }
+
+    /// Lock or unlock the mouse pointer. While locked, the cursor is hidden and stays in place, so you can use mouse
+    /// movement for e.g. rotating the camera in a 3D view, without the cursor hitting the edge of the screen.
+    ///
+    /// The platform can release the lock by itself, e.g. when the window loses focus, or when the user presses
+    /// Escape on the web; then you get an [`Event::PointerLockChanged`]. On the web, browsers only allow locking
+    /// while handling a click or key press. Not supported on Android and iOS.
+    pub fn set_pointer_lock(&mut self, locked: bool) {
+        self.pointer_lock_requested = locked;
+    }
+
+    /// Whether the mouse pointer is currently locked; see [`Cx::set_pointer_lock`].
+    pub fn is_pointer_locked(&self) -> bool {
+        self.pointer_locked
+    }
+
+    /// The cursor that platforms should show right now.
+    pub(crate) fn current_mouse_cursor(&self) -> MouseCursor {
+        if self.pointer_locked {
+            MouseCursor::Hidden
+        } else if let Some(down_mouse_cursor) = &self.down_mouse_cursor {
+            down_mouse_cursor.clone()
+        } else if let Some((_, mouse_cursor)) = self.cursor_stack.last() {
+            mouse_cursor.clone()
+        } else if let Some(hover_mouse_cursor) = &self.hover_mouse_cursor {
+            hover_mouse_cursor.clone()
+        } else {
+            MouseCursor::Default
+        }
+    }
 }
 
 impl Eq for MouseCursor {}
@@ -3,14 +3,19 @@
 
 use crate::*;
 
+use capture::CxFrameCapture;
 #[cfg(feature = "cef")]
 use cef_browser::MaybeCefBrowser;
 use debug_log::DebugLog;
+use frame_arena::FrameArena;
 use std::{
     any::{Any, TypeId},
     collections::{BTreeSet, HashMap},
     fmt::Write,
-    sync::{Arc, RwLock},
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc, RwLock,
+    },
 };
 use zaplib_shader_compiler::generate_shader_ast::*;
 
@@ -21,6 +26,8 @@ pub enum PlatformType {
     Windows,
     OSX,
     Linux { custom_window_chrome: bool },
+    Android,
+    IOS,
     Web { protocol: String, hostname: String, port: u16, pathname: String, search: String, hash: String },
 }
 
@@ -31,6 +38,8 @@ impl PlatformType {
             PlatformType::Windows => true,
             PlatformType::OSX => true,
             PlatformType::Linux { .. } => true,
+            PlatformType::Android => false,
+            PlatformType::IOS => false,
             PlatformType::Web { .. } => false,
         }
     }
@@ -38,6 +47,82 @@ impl PlatformType {
 
 pub type CallRustSyncFn = fn(name: String, params: Vec<ZapParam>) -> Vec<ZapParam>;
 
+/// Handle for sending results to a `zaplib.callRustStream` call, which is passed to the function
+/// registered through [`Cx::on_call_rust_stream`]. Every [`CallRustStream::send_chunk`] shows up as
+/// one item of the async iterator in JS, and [`CallRustStream::finish`] ends the iteration.
+///
+/// Sending requires a [`Cx`], so this can only be used on the main thread. To stream results from
+/// another thread, send them to the main thread along with this handle, e.g. using
+/// [`Cx::post_user_message`].
+#[derive(Debug)]
+#[must_use = "JS keeps waiting for chunks until `CallRustStream::finish` is called"]
+pub struct CallRustStream {
+    callback_id: u32,
+    cancel_token: CallRustCancelToken,
+}
+
+impl CallRustStream {
+    /// See [`CallRustCancelToken`]. JS cancels a stream when it stops iterating early, e.g. using `break`
+    /// in a `for await` loop. You still have to call [`CallRustStream::finish`] afterwards.
+    pub fn cancel_token(&self) -> &CallRustCancelToken {
+        &self.cancel_token
+    }
+
+    /// Shorthand for `stream.cancel_token().is_cancelled()`.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancel_token.is_cancelled()
+    }
+
+    /// Send a chunk of results (e.g. progress, or partial results) to JS.
+    pub fn send_chunk(&self, cx: &mut Cx, params: Vec<ZapParam>) {
+        cx.stream_to_js(self.callback_id, params);
+    }
+
+    /// End the stream in JS. Dropping a [`CallRustStream`] without calling this leaves JS waiting forever.
+    pub fn finish(self, cx: &mut Cx) {
+        cx.return_to_js(self.callback_id, vec![]);
+    }
+}
+
+/// Cooperative cancellation of a `zaplib.callRustAsync` or `zaplib.callRustStream` call. JS cancels a call
+/// using `.cancel()` on the `Promise` returned by `callRustAsync`, or by stopping to iterate over a stream.
+/// Long-running handlers can check [`CallRustCancelToken::is_cancelled`] every now and then to stop early.
+/// Get one using [`Cx::call_rust_cancel_token`] or [`CallRustStream::cancel_token`]; it can be cloned and
+/// sent to other threads.
+///
+/// In WebAssembly the flag lives in shared memory, so JS can set it even while the handler blocks the
+/// main Rust thread. Cancellation is not supported in CEF yet; there the token is never cancelled.
+#[derive(Clone, Debug, Default)]
+pub struct CallRustCancelToken {
+    cancelled: Arc<AtomicU32>,
+}
+
+impl CallRustCancelToken {
+    /// Whether JS (or [`CallRustCancelToken::cancel`]) has cancelled the call.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Acquire) != 0
+    }
+
+    /// Cancel the call from Rust, e.g. to also stop work on other threads that share this token.
+    pub fn cancel(&self) {
+        self.cancelled.store(1, Ordering::Release);
+    }
+
+    /// Take a reference to a flag created by `createCancelToken`; 0 means that JS passed no token.
+    ///
+    /// # Safety
+    /// `ptr` must be 0 or come from `createCancelToken`, and JS must still hold its own reference.
+    #[cfg(target_arch = "wasm32")]
+    pub(crate) unsafe fn from_js_ptr(ptr: u32) -> Self {
+        if ptr == 0 {
+            return Self::default();
+        }
+        let ptr = ptr as usize as *const AtomicU32;
+        Arc::increment_strong_count(ptr);
+        Self { cancelled: Arc::from_raw(ptr) }
+    }
+}
+
 /// The main "context" object which contains pretty much everything we need within the framework.
 pub struct Cx {
     /// See [`PlatformType`].
@@ -50,6 +135,9 @@ pub struct Cx {
     /// TODO(JP): Should we be more explicit and use [`Option`] in [`Cx::windows`]?
     pub(crate) windows_free: Vec<usize>,
 
+    /// See [`Cx::displays`]. Set by the platform before [`Event::Construct`], and updated by [`Event::DisplaysChanged`].
+    pub(crate) displays: Vec<DisplayInfo>,
+
     /// List of actual [`CxPass`] objects. [`Pass::pass_id`] represents an index in this list.
     ///
     /// TODO(JP): We currently never remove old [`CxPass`]es.
@@ -68,6 +156,9 @@ pub struct Cx {
     pub(crate) shaders: Vec<CxShader>,
     /// Shader IDs (indices in [`Cx::shaders`]) that need to be recompiled.
     pub(crate) shader_recompile_ids: Vec<usize>,
+    /// Shader IDs by the address of their [`Shader`], for when we can't store them in [`Shader::shader_id`]
+    /// because there are multiple [`Cx`]es in the same process, like when running tests that use [`TestCx`].
+    pub(crate) local_shader_ids: Option<HashMap<usize, usize>>,
     /// List of actual [`CxTexture`] objects. [`TextureHandle::texture_id`] represents an index in this list.
     pub(crate) textures: Vec<CxTexture>,
     /// List of actual [`CxGpuGeometry`] objects. [`GpuGeometry::gpu_geometry_id`] represents an index in this list.
@@ -125,8 +216,15 @@ pub struct Cx {
 
     /// The last [`Timer::timer_id`] that was issued.
     pub(crate) last_timer_id: u64,
+    /// [`Timer`]s that are currently running, mapped to whether or not they repeat. Used to drop
+    /// [`Event::Timer`]s for timers that have already been stopped.
+    pub(crate) active_timers: HashMap<u64, bool>,
     /// The last [`Signal::signal_id`] that was issued.
     pub(crate) last_signal_id: usize,
+    /// [`Future`](std::future::Future)s spawned using [`Cx::spawn`].
+    pub(crate) executor: CxExecutor,
+    /// Frames requested using [`Cx::capture_frame`], and the recording started using [`Cx::start_recording`].
+    pub(crate) frame_capture: CxFrameCapture,
 
     /// The current [`ComponentId`] that has keyboard focus, so it can register key input [`Event`]s.
     ///
@@ -145,8 +243,22 @@ pub struct Cx {
     /// TODO(JP): It's possible to set this during the draw cycle instead of during an
     /// event handler, and then it won't update [`Cx::key_focus`] until the next event
     /// is handled. We should probably guard against that.
-    next_key_focus: Option<Option<ComponentId>>,
+    pub(crate) next_key_focus: Option<Option<ComponentId>>,
+    /// Components registered using [`Cx::add_focusable`] during the last draw, in draw order.
+    pub(crate) focusables: Vec<CxFocusable>,
+    /// See [`Cx::is_focus_visible`].
+    pub(crate) focus_visible: bool,
+    /// Text runs drawn within each [`TextSelection::begin_draw`] and [`TextSelection::end_draw`] pair.
+    pub(crate) selectable_text_stack: Vec<Vec<SelectableTextRun>>,
     pub(crate) keys_down: Vec<KeyEvent>,
+    /// See [`Cx::locale`] and [`Cx::add_message_catalog`].
+    pub(crate) localization: CxLocalization,
+    /// See [`Cx::add_tooltip`].
+    pub(crate) tooltips: CxTooltips,
+    /// See [`Cx::publish_selection`].
+    pub(crate) linked_selections: CxLinkedSelections,
+    /// See [`Cx::interaction_policy`].
+    pub(crate) interaction_policy: InteractionPolicy,
 
     /// The cursor type that the user sees while holding the mouse down. Gets reset to [`None`] when
     /// you release the mouse button ([`Event::PointerUp`]).
@@ -156,18 +268,36 @@ pub struct Cx {
     /// Gets reset when there's a new [`Event::PointerHover`], so you have to periodically set this.
     pub(crate) hover_mouse_cursor: Option<MouseCursor>,
 
+    /// Cursors pushed using [`Cx::push_mouse_cursor`], with the most recent one last.
+    pub(crate) cursor_stack: Vec<(ComponentId, MouseCursor)>,
+
+    /// See [`Cx::set_pointer_lock`]. Platforms lock or unlock the pointer when this differs from
+    /// [`Cx::pointer_locked`], and then set [`Cx::pointer_locked`] to match.
+    pub(crate) pointer_lock_requested: bool,
+    /// See [`Cx::is_pointer_locked`].
+    pub(crate) pointer_locked: bool,
+
     /// The current state of each "pointer" that we track.
     ///
     /// TODO(JP): This seems mostly relevant for multi-touch, which we don't really support very
     /// well yet. Should we keep this?
     pub(crate) pointers: Vec<CxPerPointer>,
 
+    /// Continues trackpad scrolling on platforms that don't send momentum themselves.
+    pub(crate) scroll_momentum: ScrollMomentum,
+
     /// Whether [`Cx::request_next_frame`] was called.
     pub(crate) requested_next_frame: bool,
 
     /// Whether [`Cx::request_draw`] was called.
     pub(crate) requested_draw: bool,
 
+    /// Incremented by [`Cx::invalidate_view_caches`], so that [`View::begin_cached_view`] draws again.
+    pub(crate) view_cache_generation: u64,
+
+    /// See [`Cx::get_draw_call_batching_stats`].
+    pub(crate) draw_call_batching_stats: DrawCallBatchingStats,
+
     /// The local "signals", which are like custom events that also work across threads.
     ///
     /// See also [`Signal`] and [`SignalEvent`].
@@ -199,14 +329,14 @@ pub struct Cx {
     /// we need to make use of locks.
     pub fonts_data: Arc<RwLock<CxFontsData>>,
 
-    /// A buffer with temporary data used in [`Area::get_first`] and [`Area::get_first_mut`].
+    /// Temporary data used in [`Area::get_first`] and [`Area::get_first_mut`].
     ///
     /// Shouldn't be used excessively. Gets cleared out after a draw cycle.
     ///
     /// TODO(JP): It would be nice if we can eliminate this altogether, e.g. by guaranteeing
     /// that handle functions are only called after a component has been drawn, or by making
     /// shader animations a more integral part of the framework.
-    pub(crate) temp_default_data: Vec<Box<dyn Any>>,
+    pub(crate) temp_default_data: FrameArena,
 
     /// See [`CxDebugFlags`].
     pub(crate) debug_flags: CxDebugFlags,
@@ -221,6 +351,12 @@ pub struct Cx {
     /// Function registered through [`Cx::on_call_rust_async`]
     pub call_rust_async_fn: Option<usize>,
 
+    /// Function registered through [`Cx::on_call_rust_stream`]
+    pub call_rust_stream_fn: Option<usize>,
+
+    /// Token of the `callRustAsync` call that is currently being handled; see [`Cx::call_rust_cancel_token`].
+    pub(crate) call_rust_cancel_token: Option<CallRustCancelToken>,
+
     /// Reference to the main_app type
     pub app_type_id: TypeId,
 
@@ -235,8 +371,9 @@ pub struct CxDebugFlags {
     /// See [`CxDebugDrawTree`].
     pub draw_tree: CxDebugDrawTree,
 
-    /// Makes it so every call to `Cx::add_instances` gets a fresh [`DrawCall`]. This is useful for debugging,
-    /// since the batching of draw calls can be confusing sometimes (and you should never rely on it happening).
+    /// Makes it so every call to `Cx::add_instances` gets a fresh [`DrawCall`], and disables merging them
+    /// afterwards in [`Cx::batch_draw_calls`]. This is useful for debugging, since the batching of draw calls can
+    /// be confusing sometimes (and you should never rely on it happening).
     pub disable_draw_call_batching: bool,
 
     /// Enables overlay with borders of CxLayoutBox rects
@@ -263,7 +400,8 @@ impl Default for CxDebugDrawTree {
 
 /// Settings for "commands"; see [`CommandId`].
 ///
-/// Only supported on OSX for now.
+/// Key bindings are only supported on OSX for now. Whether a command is enabled is also used for [`Tray`] items on
+/// all desktop platforms.
 #[cfg_attr(not(target_os = "macos"), allow(dead_code))]
 #[derive(Clone, Copy, Default)]
 pub(crate) struct CxCommandSetting {
@@ -290,7 +428,13 @@ impl Cx {
         pointers.resize(NUM_POINTERS, CxPerPointer::default());
 
         let textures = vec![CxTexture {
-            desc: TextureDesc { format: TextureFormat::ImageRGBA, width: Some(4), height: Some(4), multisample: None },
+            desc: TextureDesc {
+                format: TextureFormat::ImageRGBA,
+                width: Some(4),
+                height: Some(4),
+                multisample: None,
+                cube: false,
+            },
             image_u32: vec![0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
             update_image: true,
             platform: CxPlatformTexture::default(),
@@ -302,13 +446,15 @@ impl Cx {
 
             windows: Vec::new(),
             windows_free: Vec::new(),
+            displays: Vec::new(),
             passes: Vec::with_capacity(10),
             // TODO(JP): See my note up at [`Cx::views`].
             views: vec![CxView::default()],
-            fonts_data: Arc::new(RwLock::new(CxFontsData::default())),
+            fonts_data: Arc::new(RwLock::new(CxFontsData::new())),
             textures,
             shaders: Vec::with_capacity(50),
             shader_recompile_ids: Vec::with_capacity(50),
+            local_shader_ids: None,
             gpu_geometries: Vec::new(),
 
             default_dpi_factor: 1.0,
@@ -325,16 +471,30 @@ impl Cx {
 
             redraw_id: 1,
             last_timer_id: 1,
+            active_timers: HashMap::new(),
             last_signal_id: 1,
+            executor: CxExecutor::default(),
+            frame_capture: CxFrameCapture::default(),
 
             next_key_focus: None,
             prev_key_focus: None,
             key_focus: None,
+            focusables: Vec::new(),
+            focus_visible: false,
+            selectable_text_stack: Vec::new(),
             keys_down: Vec::new(),
+            localization: CxLocalization::default(),
+            tooltips: CxTooltips::default(),
+            linked_selections: CxLinkedSelections::default(),
+            interaction_policy: InteractionPolicy::default(),
 
             down_mouse_cursor: None,
             hover_mouse_cursor: None,
+            cursor_stack: Vec::new(),
+            pointer_lock_requested: false,
+            pointer_locked: false,
             pointers,
+            scroll_momentum: ScrollMomentum::default(),
 
             shader_ast_generator: ShaderAstGenerator::new(),
 
@@ -342,6 +502,8 @@ impl Cx {
 
             requested_next_frame: false,
             requested_draw: false,
+            view_cache_generation: 0,
+            draw_call_batching_stats: DrawCallBatchingStats::default(),
 
             profiles: HashMap::new(),
 
@@ -353,7 +515,7 @@ impl Cx {
 
             event_handler: None,
 
-            temp_default_data: Vec::with_capacity(1000),
+            temp_default_data: FrameArena::default(),
 
             debug_flags: Default::default(),
 
@@ -363,6 +525,8 @@ impl Cx {
             debug_logs: Vec::new(),
 
             call_rust_async_fn: None,
+            call_rust_stream_fn: None,
+            call_rust_cancel_token: None,
             app_type_id,
             finished_app_new: false,
         }
@@ -371,12 +535,28 @@ impl Cx {
     #[cfg(test)]
     pub fn new_test() -> Self {
         let mut cx = Self::new(TypeId::of::<()>());
+        cx.local_shader_ids = Some(HashMap::new());
         cx.load_fonts();
         cx
     }
 
     pub(crate) fn process_pre_event(&mut self, event: &mut Event) {
+        self.process_tooltip_event(event);
         match event {
+            Event::Timer(te) => match self.active_timers.get(&te.timer_id) {
+                // The timer was stopped after the platform already fired it, so drop the event.
+                None => *event = Event::None,
+                Some(false) => {
+                    self.active_timers.remove(&te.timer_id);
+                }
+                Some(true) => {}
+            },
+            Event::Signal(se) => self.resolve_signal_waiters(&se.signals),
+            Event::DisplaysChanged(de) => self.displays = de.displays.clone(),
+            Event::PointerLockChanged(pe) => {
+                self.pointer_lock_requested = pe.locked;
+                self.pointer_locked = pe.locked;
+            }
             Event::PointerHover(pe) => {
                 self.pointers[pe.digit].over_last = None;
                 self.hover_mouse_cursor = None;
@@ -445,7 +625,11 @@ impl Cx {
                 //    self.hover_mouse_cursor = None;
                 //}
             }
-            Event::PointerScroll(_) => {
+            Event::PointerScroll(pe) => {
+                let momentum_started = self.scroll_momentum.track(pe);
+                if momentum_started {
+                    self.request_next_frame();
+                }
                 // check for anything being paint or draw dirty
                 #[cfg(not(target_arch = "wasm32"))]
                 if self.requested_draw {
@@ -486,6 +670,24 @@ impl Cx {
         1.0
     }
 
+    /// The monitors that are currently connected, with the primary one first. Listen to [`Event::DisplaysChanged`] to
+    /// find out when this changes. Always empty on the web, since browsers don't tell which displays there are.
+    pub fn displays(&self) -> &[DisplayInfo] {
+        &self.displays
+    }
+
+    /// Round a position or size in logical pixels to the nearest physical pixel of the current [`Pass`], which keeps
+    /// edges and hairlines crisp, also on fractional scales like 125% or 150%. See also `snap_to_pixel` in
+    /// [`Cx::STD_SHADER`] for doing the same in shaders.
+    pub fn snap_to_pixel(&self, value: f32) -> f32 {
+        (value * self.current_dpi_factor).round() / self.current_dpi_factor
+    }
+
+    /// The width of a single physical pixel of the current [`Pass`] in logical pixels, for drawing hairlines.
+    pub fn hairline_width(&self) -> f32 {
+        1. / self.current_dpi_factor
+    }
+
     pub(crate) fn get_delegated_dpi_factor(&mut self, pass_id: usize) -> f32 {
         let mut dpi_factor = 1.0;
         let mut pass_id_walk = pass_id;
@@ -573,6 +775,13 @@ impl Cx {
         self.requested_draw = true;
     }
 
+    /// Make all [`View`]s that use [`View::begin_cached_view`] draw again on the next draw, and request a draw.
+    /// Use this when something changed that their content hashes don't cover, like a theme.
+    pub fn invalidate_view_caches(&mut self) {
+        self.view_cache_generation += 1;
+        self.request_draw();
+    }
+
     /// Sets a [`ComponentId`] that will become [`Cx::key_focus`] when the current events are handled.
     ///
     /// TODO(JP): It's possible to set this during the draw cycle instead of during an
@@ -629,9 +838,14 @@ impl Cx {
     pub(crate) fn call_event_handler(&mut self, event: &mut Event) {
         let event_handler = self.event_handler.unwrap();
 
+        push_event_breadcrumb(event);
+        set_main_thread_busy(true);
         unsafe {
             (*event_handler)(self, event);
         }
+        set_main_thread_busy(false);
+
+        self.process_focus_event(event);
 
         // Someone has to call `set_key_focus` or `keep_key_focus` when handling `PointerDown`, otherwise
         // the key focus will be reset.
@@ -641,6 +855,22 @@ impl Cx {
             }
         }
 
+        // Windows get their first geometry with a `dpi_factor` of 0, which is not a change that anyone cares about.
+        if let Event::WindowGeomChange(wg) = event {
+            if wg.old_geom.dpi_factor > 0. && wg.old_geom.dpi_factor != wg.new_geom.dpi_factor {
+                let mut dpi_change_event = Event::WindowDpiChange(WindowDpiChangeEvent {
+                    window_id: wg.window_id,
+                    old_dpi_factor: wg.old_geom.dpi_factor,
+                    new_dpi_factor: wg.new_geom.dpi_factor,
+                });
+                // Everything needs to be laid out and rasterized again to line up with the new physical pixels.
+                self.request_draw();
+                unsafe {
+                    (*event_handler)(self, &mut dpi_change_event);
+                }
+            }
+        }
+
         if let Some(next_key_focus) = self.next_key_focus {
             if next_key_focus != self.key_focus {
                 self.prev_key_focus = self.key_focus;
@@ -655,7 +885,35 @@ impl Cx {
             self.next_key_focus = None;
         }
 
-        self.temp_default_data.clear();
+        if let Some(locale) = self.localization.next_locale.take() {
+            if locale != self.localization.locale {
+                let old_locale = std::mem::replace(&mut self.localization.locale, locale);
+                // Any text might be different now.
+                self.invalidate_view_caches();
+                unsafe {
+                    (*event_handler)(
+                        self,
+                        &mut Event::LocaleChanged(LocaleChangedEvent {
+                            old_locale,
+                            new_locale: self.localization.locale.clone(),
+                        }),
+                    );
+                }
+            }
+        }
+
+        // Handlers might publish selections in turn, e.g. to derive one selection from another. That ends because
+        // publishing a selection that didn't change doesn't fire an event.
+        while !self.linked_selections.changed.is_empty() {
+            let changed = std::mem::take(&mut self.linked_selections.changed);
+            for selection_changed in changed {
+                unsafe {
+                    (*event_handler)(self, &mut Event::SelectionChanged(selection_changed));
+                }
+            }
+        }
+
+        self.temp_default_data.reset();
     }
 
     pub(crate) fn call_draw_event(&mut self) {
@@ -664,6 +922,8 @@ impl Cx {
         self.redraw_id += 1;
         self.layout_box_align_list.clear();
         self.debug_logs.clear();
+        self.focusables.clear();
+        self.tooltips.begin_draw();
 
         // TODO(Paras): Terrible hack.
         //
@@ -678,6 +938,8 @@ impl Cx {
 
         self.call_event_handler(&mut Event::System(SystemEvent::Draw));
         self.in_redraw_cycle = false;
+        self.batch_draw_calls();
+        self.diff_retained_instances();
         if !self.view_stack.is_empty() {
             panic!("View stack disaligned, forgot an end_view(cx)");
         }
@@ -693,11 +955,19 @@ impl Cx {
         if !self.shader_group_instance_offsets.is_empty() {
             panic!("Shader group stack disaligned, forgot an end_shader_group()");
         }
+        if !self.selectable_text_stack.is_empty() {
+            panic!("Selectable text stack disaligned, forgot a TextSelection::end_draw()");
+        }
         //self.profile();
     }
 
     pub(crate) fn call_next_frame_event(&mut self) {
         self.requested_next_frame = false;
+        if let Some(pe) = self.scroll_momentum.next_event(self.last_event_time) {
+            self.request_next_frame();
+            self.call_event_handler(&mut Event::PointerScroll(pe));
+        }
+        self.update_tooltip_timing();
         self.call_event_handler(&mut Event::NextFrame);
     }
 
@@ -706,6 +976,37 @@ impl Cx {
         self.requested_next_frame = true;
     }
 
+    /// Start a [`Timer`] that fires once after `interval` seconds. Shorthand for [`CxPlatformCommon::start_timer`].
+    pub fn start_timeout(&mut self, interval: f64) -> Timer {
+        self.start_timer(interval, false)
+    }
+
+    /// Start a [`Timer`] that fires every `interval` seconds until it is stopped using
+    /// [`CxPlatformCommon::stop_timer`]. Shorthand for [`CxPlatformCommon::start_timer`].
+    pub fn start_interval(&mut self, interval: f64) -> Timer {
+        self.start_timer(interval, true)
+    }
+
+    /// Whether `timer` might still fire; i.e. it has been started, and it has not been stopped yet,
+    /// and it's either repeating or it hasn't fired yet.
+    pub fn is_timer_active(&self, timer: &Timer) -> bool {
+        self.active_timers.contains_key(&timer.timer_id)
+    }
+
+    /// Issue a new [`Timer::timer_id`] and mark it as active. For use in [`CxPlatformCommon::start_timer`].
+    pub(crate) fn new_timer_id(&mut self, repeats: bool) -> u64 {
+        self.last_timer_id += 1;
+        self.active_timers.insert(self.last_timer_id, repeats);
+        self.last_timer_id
+    }
+
+    /// Mark `timer` as stopped and reset it. Returns the [`Timer::timer_id`] if the timer was still
+    /// active, so the platform can cancel it. For use in [`CxPlatformCommon::stop_timer`].
+    pub(crate) fn take_active_timer_id(&mut self, timer: &mut Timer) -> Option<u64> {
+        let timer_id = std::mem::take(&mut timer.timer_id);
+        self.active_timers.remove(&timer_id).map(|_| timer_id)
+    }
+
     /// Create a new [`Signal`], which is used to send and capture custom
     /// events.
     ///
@@ -737,14 +1038,27 @@ impl Cx {
         }
     }
 
+    /// Send a message of any type from any thread (e.g. a [`universal_thread`] doing some work),
+    /// which gets delivered on the main thread as an [`Event::UserMessage`]. Use
+    /// [`UserMessageEvent::get`] to check for your message type.
+    ///
+    /// Unlike [`Cx::post_signal`] this lets you send actual data, instead of just an ID. The message
+    /// doesn't get copied, so it's fine to send large buffers this way.
+    pub fn post_user_message<T: Any + Send + Sync>(message: T) {
+        Cx::send_event_from_any_thread(Event::UserMessage(UserMessageEvent::new(message)));
+    }
+
     pub(crate) fn call_signals(&mut self) {
+        self.run_woken_tasks();
         let mut counter = 0;
         while !self.signals.is_empty() {
             counter += 1;
             let mut signals = HashMap::new();
             std::mem::swap(&mut self.signals, &mut signals);
 
+            self.resolve_signal_waiters(&signals);
             self.call_event_handler(&mut Event::Signal(SignalEvent { signals }));
+            self.run_woken_tasks();
 
             if counter > 100 {
                 println!("Signal feedback loop detected");
@@ -834,6 +1148,9 @@ impl Cx {
                     vec2(draw_call.draw_uniforms.draw_local_scroll_x, draw_call.draw_uniforms.draw_local_scroll_y)
                 )
                 .unwrap();
+                if let Some((target_draw_call_id, _)) = draw_call.batched_into {
+                    writeln!(s, "{}  batched into call {}", indent, target_draw_call_id).unwrap();
+                }
                 if dump_instances {
                     for inst in 0..instances.min(1) {
                         let mut out = String::new();
@@ -891,6 +1208,65 @@ impl Cx {
         self.call_rust_async_fn = Some(Box::into_raw(Box::new(func)) as usize);
     }
 
+    /// Register function to handle `callRustStream` from JavaScript. Registered function must be a method on the main
+    /// app. Instead of returning results, it gets a [`CallRustStream`] to send any number of chunks with.
+    pub fn on_call_rust_stream<T: 'static>(
+        &mut self,
+        func: fn(this: &mut T, cx: &mut Cx, name: String, params: Vec<ZapParam>, stream: CallRustStream),
+    ) {
+        if self.call_rust_stream_fn.is_some() {
+            panic!("Attempting to call on_call_rust_stream twice.");
+        }
+
+        if self.app_type_id != TypeId::of::<T>() {
+            panic!("Error in on_call_rust_stream: Function must be a method on the main_app.");
+        }
+        self.call_rust_stream_fn = Some(Box::into_raw(Box::new(func)) as usize);
+    }
+
+    /// Call the function registered through [`Cx::on_call_rust_async`] or [`Cx::on_call_rust_stream`], and
+    /// return the results to JS. Also receives return values for `Cx::call_js_async`. Called by the `main_app!`
+    /// macro; don't call this in user code.
+    ///
+    /// # Safety
+    /// `T` must be the type of the main app (which we check in [`Cx::on_call_rust_async`] and
+    /// [`Cx::on_call_rust_stream`]).
+    #[doc(hidden)]
+    pub unsafe fn handle_web_rust_call<T: 'static>(&mut self, app: &mut T, event: WebRustCallEvent) {
+        let WebRustCallEvent { name, params, callback_id, streaming, cancel_token } = event;
+        #[cfg(any(target_arch = "wasm32", feature = "cef"))]
+        if name == "_zaplibCallJsReturn" {
+            self.resolve_call_js(params);
+            self.return_to_js(callback_id, vec![]);
+            return;
+        }
+        if streaming {
+            let call_rust_stream_fn =
+                self.call_rust_stream_fn.expect("`callRustStream` called but no on_call_rust_stream registered");
+            let func = &*(call_rust_stream_fn
+                as *const fn(this: &mut T, cx: &mut Cx, name: String, params: Vec<ZapParam>, stream: CallRustStream));
+            func(app, self, name, params, CallRustStream { callback_id, cancel_token });
+        } else {
+            let call_rust_async_fn =
+                self.call_rust_async_fn.expect("`callRustAsync` called but no on_call_rust_async registered");
+            let func = &*(call_rust_async_fn
+                as *const fn(this: &mut T, cx: &mut Cx, name: String, params: Vec<ZapParam>) -> Vec<ZapParam>);
+            self.call_rust_cancel_token = Some(cancel_token);
+            let return_params = func(app, self, name, params);
+            self.call_rust_cancel_token = None;
+            self.return_to_js(callback_id, return_params);
+        }
+    }
+
+    /// Get the [`CallRustCancelToken`] of the `callRustAsync` call that is currently being handled. Can only be
+    /// called from within the function registered through [`Cx::on_call_rust_async`]; for streams use
+    /// [`CallRustStream::cancel_token`] instead.
+    pub fn call_rust_cancel_token(&self) -> CallRustCancelToken {
+        self.call_rust_cancel_token
+            .clone()
+            .expect("call_rust_cancel_token can only be called from within an on_call_rust_async handler")
+    }
+
     /// Set the callback for `zaplib.callRustSync` calls.
     ///
     /// Can only be called in the `new` function of your app, since we do some thread-unsafe
@@ -970,6 +1346,9 @@ pub trait CxDesktopVsWasmCommon {
 
     /// Mechanism to communicate back returns values from `callRustAsync` functions.
     fn return_to_js(&mut self, callback_id: u32, params: Vec<ZapParam>);
+
+    /// Mechanism to send chunks for `callRustStream` functions; see [`CallRustStream`].
+    fn stream_to_js(&mut self, callback_id: u32, params: Vec<ZapParam>);
 }
 
 /// A bunch of traits that are common between the different target platforms. This trait makes sure
@@ -980,17 +1359,68 @@ pub trait CxPlatformCommon {
     fn show_text_ime(&mut self, x: f32, y: f32);
     /// Hide the IME shown by [`CxPlatformCommon::show_text_ime`].
     fn hide_text_ime(&mut self);
-    /// Start a new [`Timer`] with the given `interval`, and which may `repeat` if required.
+    /// Start a new [`Timer`] with the given `interval` (in seconds), and which may `repeat` if required.
+    /// See also [`Cx::start_timeout`] and [`Cx::start_interval`].
+    ///
+    /// Delivery guarantees, which are the same on all platforms:
+    /// * An [`Event::Timer`] is never delivered earlier than `interval` after starting the timer (or
+    ///   after the previous event, for repeating timers), but it can be delivered later if the event
+    ///   loop is busy.
+    /// * If a repeating timer falls behind, missed intervals are coalesced into a single event instead
+    ///   of being delivered in a burst.
+    /// * After calling [`CxPlatformCommon::stop_timer`], no more events are delivered for that timer,
+    ///   even if the platform had already fired it.
+    /// * A one-shot timer becomes inactive (see [`Cx::is_timer_active`]) once its event is delivered.
+    ///
+    /// Make sure to stop repeating timers when you no longer need them (e.g. when a component goes
+    /// away), since otherwise they keep firing for the lifetime of the app.
     fn start_timer(&mut self, interval: f64, repeats: bool) -> Timer;
-    /// Stop zap`Timer`] given by [`CxPlatformCommon::start_timer`].
+    /// Stop a [`Timer`] given by [`CxPlatformCommon::start_timer`], and reset it to [`Timer::empty`].
+    /// It's safe to call this multiple times, or on timers that have already fired.
     fn stop_timer(&mut self, timer: &mut Timer);
     /// Post a [`Signal`] from any thread. If you don't need to use this from a thread, you may
     /// instead use [`Cx::send_signal`], which might be faster.
     fn post_signal(signal: Signal, status: StatusId);
     /// Set a [`Menu`].
     fn update_menu(&mut self, menu: &Menu);
+    /// Show a [`Tray`] icon, or remove it by passing `None`. Only supported on native desktop platforms.
+    fn update_tray(&mut self, tray: Option<&Tray>);
     /// Copy the given text to the clipboard, if possible.
     fn copy_text_to_clipboard(&mut self, text: &str);
     /// Send zaplib Event for processing from any thread
     fn send_event_from_any_thread(event: Event);
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    fn fire(test_cx: &mut TestCx, timer_id: u64) -> bool {
+        let events = test_cx.dispatch(Event::Timer(TimerEvent { timer_id }), |_cx, event| matches!(event, Event::Timer(_)));
+        events.contains(&true)
+    }
+
+    #[test]
+    fn test_timer_delivery() {
+        let mut test_cx = TestCx::new(vec2(100., 100.));
+
+        // One-shot timers become inactive after firing.
+        let timeout = test_cx.cx.start_timeout(1.);
+        assert!(test_cx.cx.is_timer_active(&timeout));
+        assert!(fire(&mut test_cx, timeout.timer_id));
+        assert!(!test_cx.cx.is_timer_active(&timeout));
+
+        // Repeating timers keep firing until stopped, after which pending events get dropped.
+        let mut interval = test_cx.cx.start_interval(1.);
+        let interval_id = interval.timer_id;
+        assert!(fire(&mut test_cx, interval_id));
+        assert!(fire(&mut test_cx, interval_id));
+        test_cx.cx.stop_timer(&mut interval);
+        assert!(!test_cx.cx.is_timer_active(&interval));
+        assert!(!fire(&mut test_cx, interval_id));
+
+        // Stopping again is harmless.
+        test_cx.cx.stop_timer(&mut interval);
+        assert_eq!(interval.timer_id, 0);
+    }
+}
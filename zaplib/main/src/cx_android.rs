@@ -0,0 +1,1588 @@
+//! Android platform-specific entry point, using `NativeActivity`.
+//!
+//! [`main_app!`] exports `ANativeActivity_onCreate`, which `NativeActivity` calls on the Java UI thread. We register
+//! the activity callbacks there and run the app on a separate thread, like `android_native_app_glue` does. The activity
+//! callbacks send an [`AndroidCommand`] to the app thread, and wait for it to be processed when Android requires that
+//! (e.g. we have to stop drawing into a window before the callback that destroys it returns).
+//!
+//! We draw using OpenGL ES 3 through EGL, sharing the renderer with Linux (see [`OpenglCx::Egl`]).
+
+#![allow(non_camel_case_types)]
+
+use crate::*;
+use std::collections::{BTreeSet, HashMap, VecDeque};
+use std::ffi::{CStr, CString};
+use std::io::BufRead;
+use std::os::raw::{c_char, c_int, c_void};
+use std::os::unix::io::FromRawFd;
+use std::path::PathBuf;
+use std::ptr;
+use std::sync::{Condvar, Mutex};
+use time::precise_time_ns;
+
+static mut GLOBAL_ANDROID_APP: *mut AndroidApp = 0 as *mut _;
+static mut ACTIVITY: *mut ANativeActivity = 0 as *mut _;
+
+#[repr(C)]
+pub(crate) struct ANativeWindow {
+    _private: [u8; 0],
+}
+
+#[repr(C)]
+pub(crate) struct AInputQueue {
+    _private: [u8; 0],
+}
+
+#[repr(C)]
+struct AInputEvent {
+    _private: [u8; 0],
+}
+
+#[repr(C)]
+struct ALooper {
+    _private: [u8; 0],
+}
+
+#[repr(C)]
+struct AConfiguration {
+    _private: [u8; 0],
+}
+
+#[repr(C)]
+#[allow(dead_code)]
+struct ARect {
+    left: i32,
+    top: i32,
+    right: i32,
+    bottom: i32,
+}
+
+#[repr(C)]
+#[allow(dead_code)]
+struct ANativeActivity {
+    callbacks: *mut ANativeActivityCallbacks,
+    vm: *mut JavaVM,
+    env: *mut JNIEnv,
+    clazz: *mut c_void,
+    internal_data_path: *const c_char,
+    external_data_path: *const c_char,
+    sdk_version: i32,
+    instance: *mut c_void,
+    asset_manager: *mut c_void,
+    obb_path: *const c_char,
+}
+
+#[repr(C)]
+#[allow(dead_code)]
+struct ANativeActivityCallbacks {
+    on_start: Option<unsafe extern "C" fn(*mut ANativeActivity)>,
+    on_resume: Option<unsafe extern "C" fn(*mut ANativeActivity)>,
+    on_save_instance_state: Option<unsafe extern "C" fn(*mut ANativeActivity, *mut usize) -> *mut c_void>,
+    on_pause: Option<unsafe extern "C" fn(*mut ANativeActivity)>,
+    on_stop: Option<unsafe extern "C" fn(*mut ANativeActivity)>,
+    on_destroy: Option<unsafe extern "C" fn(*mut ANativeActivity)>,
+    on_window_focus_changed: Option<unsafe extern "C" fn(*mut ANativeActivity, c_int)>,
+    on_native_window_created: Option<unsafe extern "C" fn(*mut ANativeActivity, *mut ANativeWindow)>,
+    on_native_window_resized: Option<unsafe extern "C" fn(*mut ANativeActivity, *mut ANativeWindow)>,
+    on_native_window_redraw_needed: Option<unsafe extern "C" fn(*mut ANativeActivity, *mut ANativeWindow)>,
+    on_native_window_destroyed: Option<unsafe extern "C" fn(*mut ANativeActivity, *mut ANativeWindow)>,
+    on_input_queue_created: Option<unsafe extern "C" fn(*mut ANativeActivity, *mut AInputQueue)>,
+    on_input_queue_destroyed: Option<unsafe extern "C" fn(*mut ANativeActivity, *mut AInputQueue)>,
+    on_content_rect_changed: Option<unsafe extern "C" fn(*mut ANativeActivity, *const ARect)>,
+    on_configuration_changed: Option<unsafe extern "C" fn(*mut ANativeActivity)>,
+    on_low_memory: Option<unsafe extern "C" fn(*mut ANativeActivity)>,
+}
+
+/// A `JNIEnv` or `JavaVM` is a pointer to a table of functions; see [`jni_fn`].
+type JNIEnv = *const *const c_void;
+type JavaVM = *const *const c_void;
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+#[allow(dead_code)]
+union jvalue {
+    i: i32,
+    l: *mut c_void,
+}
+
+type ALooper_callbackFunc = Option<unsafe extern "C" fn(c_int, c_int, *mut c_void) -> c_int>;
+
+#[link(name = "android")]
+extern "C" {
+    fn ANativeActivity_finish(activity: *mut ANativeActivity);
+    fn ANativeActivity_showSoftInput(activity: *mut ANativeActivity, flags: u32);
+    fn ANativeActivity_hideSoftInput(activity: *mut ANativeActivity, flags: u32);
+    fn ANativeWindow_getWidth(window: *mut ANativeWindow) -> i32;
+    fn ANativeWindow_getHeight(window: *mut ANativeWindow) -> i32;
+    fn ANativeWindow_setBuffersGeometry(window: *mut ANativeWindow, width: i32, height: i32, format: i32) -> i32;
+    fn ALooper_prepare(opts: c_int) -> *mut ALooper;
+    fn ALooper_addFd(
+        looper: *mut ALooper,
+        fd: c_int,
+        ident: c_int,
+        events: c_int,
+        callback: ALooper_callbackFunc,
+        data: *mut c_void,
+    ) -> c_int;
+    fn ALooper_removeFd(looper: *mut ALooper, fd: c_int) -> c_int;
+    fn ALooper_pollAll(timeout_millis: c_int, out_fd: *mut c_int, out_events: *mut c_int, out_data: *mut *mut c_void) -> c_int;
+    fn AInputQueue_attachLooper(
+        queue: *mut AInputQueue,
+        looper: *mut ALooper,
+        ident: c_int,
+        callback: ALooper_callbackFunc,
+        data: *mut c_void,
+    );
+    fn AInputQueue_detachLooper(queue: *mut AInputQueue);
+    fn AInputQueue_getEvent(queue: *mut AInputQueue, out_event: *mut *mut AInputEvent) -> i32;
+    fn AInputQueue_preDispatchEvent(queue: *mut AInputQueue, event: *mut AInputEvent) -> i32;
+    fn AInputQueue_finishEvent(queue: *mut AInputQueue, event: *mut AInputEvent, handled: c_int);
+    fn AInputEvent_getType(event: *const AInputEvent) -> i32;
+    fn AMotionEvent_getAction(event: *const AInputEvent) -> i32;
+    fn AMotionEvent_getPointerCount(event: *const AInputEvent) -> usize;
+    fn AMotionEvent_getPointerId(event: *const AInputEvent, pointer_index: usize) -> i32;
+    fn AMotionEvent_getToolType(event: *const AInputEvent, pointer_index: usize) -> i32;
+    fn AMotionEvent_getX(event: *const AInputEvent, pointer_index: usize) -> f32;
+    fn AMotionEvent_getY(event: *const AInputEvent, pointer_index: usize) -> f32;
+    fn AMotionEvent_getAxisValue(event: *const AInputEvent, axis: i32, pointer_index: usize) -> f32;
+    fn AMotionEvent_getMetaState(event: *const AInputEvent) -> i32;
+    fn AKeyEvent_getAction(event: *const AInputEvent) -> i32;
+    fn AKeyEvent_getKeyCode(event: *const AInputEvent) -> i32;
+    fn AKeyEvent_getMetaState(event: *const AInputEvent) -> i32;
+    fn AKeyEvent_getRepeatCount(event: *const AInputEvent) -> i32;
+    fn AConfiguration_new() -> *mut AConfiguration;
+    fn AConfiguration_delete(config: *mut AConfiguration);
+    fn AConfiguration_fromAssetManager(config: *mut AConfiguration, asset_manager: *mut c_void);
+    fn AConfiguration_getDensity(config: *mut AConfiguration) -> i32;
+}
+
+#[link(name = "log")]
+extern "C" {
+    fn __android_log_write(prio: c_int, tag: *const c_char, text: *const c_char) -> c_int;
+}
+
+#[link(name = "EGL")]
+extern "C" {
+    fn eglGetDisplay(display_id: *mut c_void) -> *mut c_void;
+    fn eglInitialize(display: *mut c_void, major: *mut i32, minor: *mut i32) -> u32;
+    fn eglBindAPI(api: u32) -> u32;
+    fn eglChooseConfig(
+        display: *mut c_void,
+        attrib_list: *const i32,
+        configs: *mut *mut c_void,
+        config_size: i32,
+        num_config: *mut i32,
+    ) -> u32;
+    fn eglGetConfigAttrib(display: *mut c_void, config: *mut c_void, attribute: i32, value: *mut i32) -> u32;
+    fn eglCreateContext(display: *mut c_void, config: *mut c_void, share: *mut c_void, attrib_list: *const i32) -> *mut c_void;
+    fn eglCreateWindowSurface(
+        display: *mut c_void,
+        config: *mut c_void,
+        window: *mut c_void,
+        attrib_list: *const i32,
+    ) -> *mut c_void;
+    fn eglCreatePbufferSurface(display: *mut c_void, config: *mut c_void, attrib_list: *const i32) -> *mut c_void;
+    fn eglDestroySurface(display: *mut c_void, surface: *mut c_void) -> u32;
+    fn eglMakeCurrent(display: *mut c_void, draw: *mut c_void, read: *mut c_void, context: *mut c_void) -> u32;
+    fn eglSwapBuffers(display: *mut c_void, surface: *mut c_void) -> u32;
+    fn eglGetProcAddress(procname: *const c_char) -> *const c_void;
+}
+
+const ANDROID_LOG_INFO: c_int = 4;
+
+const ALOOPER_PREPARE_ALLOW_NON_CALLBACKS: c_int = 1;
+const ALOOPER_EVENT_INPUT: c_int = 1;
+const LOOPER_ID_COMMANDS: c_int = 1;
+const LOOPER_ID_INPUT: c_int = 2;
+const LOOPER_ID_SIGNALS: c_int = 3;
+
+const ANATIVEACTIVITY_SHOW_SOFT_INPUT_FORCED: u32 = 2;
+
+const AINPUT_EVENT_TYPE_KEY: i32 = 1;
+const AINPUT_EVENT_TYPE_MOTION: i32 = 2;
+const AKEY_EVENT_ACTION_DOWN: i32 = 0;
+const AKEY_EVENT_ACTION_UP: i32 = 1;
+const AMOTION_EVENT_ACTION_MASK: i32 = 0xff;
+const AMOTION_EVENT_ACTION_POINTER_INDEX_MASK: i32 = 0xff00;
+const AMOTION_EVENT_ACTION_POINTER_INDEX_SHIFT: i32 = 8;
+const AMOTION_EVENT_ACTION_DOWN: i32 = 0;
+const AMOTION_EVENT_ACTION_UP: i32 = 1;
+const AMOTION_EVENT_ACTION_MOVE: i32 = 2;
+const AMOTION_EVENT_ACTION_CANCEL: i32 = 3;
+const AMOTION_EVENT_ACTION_POINTER_DOWN: i32 = 5;
+const AMOTION_EVENT_ACTION_POINTER_UP: i32 = 6;
+const AMOTION_EVENT_ACTION_HOVER_MOVE: i32 = 7;
+const AMOTION_EVENT_ACTION_SCROLL: i32 = 8;
+const AMOTION_EVENT_ACTION_HOVER_EXIT: i32 = 10;
+const AMOTION_EVENT_AXIS_VSCROLL: i32 = 9;
+const AMOTION_EVENT_AXIS_HSCROLL: i32 = 10;
+const AMOTION_EVENT_TOOL_TYPE_MOUSE: i32 = 3;
+const AMETA_SHIFT_ON: i32 = 0x01;
+const AMETA_ALT_ON: i32 = 0x02;
+const AMETA_CTRL_ON: i32 = 0x1000;
+const AMETA_META_ON: i32 = 0x10000;
+const AKEYCODE_BACK: i32 = 4;
+
+const EGL_NONE: i32 = 0x3038;
+const EGL_ALPHA_SIZE: i32 = 0x3021;
+const EGL_BLUE_SIZE: i32 = 0x3022;
+const EGL_GREEN_SIZE: i32 = 0x3023;
+const EGL_RED_SIZE: i32 = 0x3024;
+const EGL_DEPTH_SIZE: i32 = 0x3025;
+const EGL_SURFACE_TYPE: i32 = 0x3033;
+const EGL_RENDERABLE_TYPE: i32 = 0x3040;
+const EGL_NATIVE_VISUAL_ID: i32 = 0x302E;
+const EGL_WIDTH: i32 = 0x3057;
+const EGL_HEIGHT: i32 = 0x3056;
+const EGL_CONTEXT_MAJOR_VERSION: i32 = 0x3098;
+const EGL_PBUFFER_BIT: i32 = 0x0001;
+const EGL_WINDOW_BIT: i32 = 0x0004;
+const EGL_OPENGL_ES3_BIT: i32 = 0x0040;
+const EGL_OPENGL_ES_API: u32 = 0x30A0;
+
+// Indices into the `JNIEnv` and `JavaVM` function tables, from `jni.h`.
+const JNI_FIND_CLASS: usize = 6;
+const JNI_EXCEPTION_CLEAR: usize = 17;
+const JNI_DELETE_LOCAL_REF: usize = 23;
+const JNI_NEW_OBJECT_A: usize = 30;
+const JNI_GET_METHOD_ID: usize = 33;
+const JNI_CALL_INT_METHOD_A: usize = 51;
+const JNI_EXCEPTION_CHECK: usize = 228;
+const JAVA_VM_ATTACH_CURRENT_THREAD: usize = 4;
+const JAVA_VM_DETACH_CURRENT_THREAD: usize = 5;
+
+/// Get function `index` from a JNI function table.
+unsafe fn jni_fn<T: Copy>(table: *const *const *const c_void, index: usize) -> T {
+    std::mem::transmute_copy(&*(*table).add(index))
+}
+
+/// Messages from the activity callbacks, which run on the Java UI thread, to the app thread.
+enum AndroidCommand {
+    WindowCreated(*mut ANativeWindow),
+    /// The size of the window, the content rect or the configuration (e.g. the screen density) changed.
+    WindowChanged,
+    WindowRedrawNeeded,
+    WindowDestroyed,
+    InputQueueCreated(*mut AInputQueue),
+    InputQueueDestroyed,
+    Resume,
+    Pause,
+    FocusChanged(bool),
+    Destroy,
+}
+
+struct AndroidCommands {
+    queue: VecDeque<AndroidCommand>,
+    /// Number of commands that were sent and processed, so the UI thread can wait for its command to be processed.
+    sent: u64,
+    processed: u64,
+    /// Whether the app thread is still running; if not there's no point in waiting for commands to be processed.
+    app_running: bool,
+    /// Whether `onDestroy` was called, after which we must not use the activity anymore.
+    destroyed: bool,
+    /// Written to for every command, to wake up the app thread.
+    pipe: [c_int; 2],
+}
+
+// The raw pointers in the commands are only used on the app thread.
+unsafe impl Send for AndroidCommands {}
+
+static COMMANDS: once_cell::sync::Lazy<(Mutex<AndroidCommands>, Condvar)> = once_cell::sync::Lazy::new(|| {
+    (
+        Mutex::new(AndroidCommands {
+            queue: VecDeque::new(),
+            sent: 0,
+            processed: 0,
+            app_running: false,
+            destroyed: false,
+            pipe: [-1, -1],
+        }),
+        Condvar::new(),
+    )
+});
+
+fn send_command(command: AndroidCommand, wait: bool) {
+    let (lock, condvar) = &*COMMANDS;
+    let mut commands = lock.lock().unwrap();
+    if let AndroidCommand::Destroy = command {
+        commands.destroyed = true;
+    }
+    commands.queue.push_back(command);
+    commands.sent += 1;
+    let sequence = commands.sent;
+    unsafe { libc::write(commands.pipe[1], [0u8].as_ptr() as *const c_void, 1) };
+    if wait {
+        while commands.app_running && commands.processed < sequence {
+            commands = condvar.wait(commands).unwrap();
+        }
+    }
+}
+
+unsafe extern "C" fn on_resume(_activity: *mut ANativeActivity) {
+    send_command(AndroidCommand::Resume, false);
+}
+
+unsafe extern "C" fn on_pause(_activity: *mut ANativeActivity) {
+    send_command(AndroidCommand::Pause, false);
+}
+
+unsafe extern "C" fn on_destroy(_activity: *mut ANativeActivity) {
+    send_command(AndroidCommand::Destroy, true);
+}
+
+unsafe extern "C" fn on_window_focus_changed(_activity: *mut ANativeActivity, has_focus: c_int) {
+    send_command(AndroidCommand::FocusChanged(has_focus != 0), false);
+}
+
+unsafe extern "C" fn on_native_window_created(_activity: *mut ANativeActivity, window: *mut ANativeWindow) {
+    send_command(AndroidCommand::WindowCreated(window), true);
+}
+
+unsafe extern "C" fn on_native_window_changed(_activity: *mut ANativeActivity, _window: *mut ANativeWindow) {
+    send_command(AndroidCommand::WindowChanged, false);
+}
+
+unsafe extern "C" fn on_native_window_redraw_needed(_activity: *mut ANativeActivity, _window: *mut ANativeWindow) {
+    send_command(AndroidCommand::WindowRedrawNeeded, true);
+}
+
+unsafe extern "C" fn on_native_window_destroyed(_activity: *mut ANativeActivity, _window: *mut ANativeWindow) {
+    send_command(AndroidCommand::WindowDestroyed, true);
+}
+
+unsafe extern "C" fn on_input_queue_created(_activity: *mut ANativeActivity, queue: *mut AInputQueue) {
+    send_command(AndroidCommand::InputQueueCreated(queue), true);
+}
+
+unsafe extern "C" fn on_input_queue_destroyed(_activity: *mut ANativeActivity, _queue: *mut AInputQueue) {
+    send_command(AndroidCommand::InputQueueDestroyed, true);
+}
+
+unsafe extern "C" fn on_content_rect_changed(_activity: *mut ANativeActivity, _rect: *const ARect) {
+    send_command(AndroidCommand::WindowChanged, false);
+}
+
+unsafe extern "C" fn on_configuration_changed(_activity: *mut ANativeActivity) {
+    send_command(AndroidCommand::WindowChanged, false);
+}
+
+/// Android throws away stdout and stderr, so we send them to logcat instead. That way [`log!`] and panic messages
+/// show up in `adb logcat`.
+fn redirect_stdio_to_logcat() {
+    static REDIRECT: std::sync::Once = std::sync::Once::new();
+    REDIRECT.call_once(|| unsafe {
+        let mut fds = [0; 2];
+        if libc::pipe(fds.as_mut_ptr()) != 0 {
+            return;
+        }
+        libc::dup2(fds[1], libc::STDOUT_FILENO);
+        libc::dup2(fds[1], libc::STDERR_FILENO);
+        std::thread::spawn(move || {
+            let tag = CString::new("zaplib").unwrap();
+            let reader = std::io::BufReader::new(std::fs::File::from_raw_fd(fds[0]));
+            for line in reader.lines() {
+                let line = if let Ok(line) = line { line } else { break };
+                if let Ok(line) = CString::new(line) {
+                    __android_log_write(ANDROID_LOG_INFO, tag.as_ptr(), line.as_ptr());
+                }
+            }
+        });
+    });
+}
+
+/// The app's private data directory, e.g. `/data/data/com.example.app/files`.
+pub(crate) fn internal_data_path() -> Option<PathBuf> {
+    unsafe {
+        if ACTIVITY.is_null() || (*ACTIVITY).internal_data_path.is_null() {
+            return None;
+        }
+        Some(PathBuf::from(CStr::from_ptr((*ACTIVITY).internal_data_path).to_string_lossy().into_owned()))
+    }
+}
+
+impl Cx {
+    /// Called from `ANativeActivity_onCreate`, which is generated by [`main_app!`]. Runs `main` on a new thread.
+    #[doc(hidden)]
+    pub unsafe fn android_on_create(activity: *mut c_void, main: fn()) {
+        redirect_stdio_to_logcat();
+
+        let activity = activity as *mut ANativeActivity;
+        ACTIVITY = activity;
+        {
+            let mut commands = COMMANDS.0.lock().unwrap();
+            if commands.pipe[0] < 0 && libc::pipe(commands.pipe.as_mut_ptr()) != 0 {
+                panic!("Could not create the command pipe");
+            }
+            commands.queue.clear();
+            commands.sent = 0;
+            commands.processed = 0;
+            commands.app_running = true;
+            commands.destroyed = false;
+        }
+
+        let callbacks = &mut *(*activity).callbacks;
+        callbacks.on_resume = Some(on_resume);
+        callbacks.on_pause = Some(on_pause);
+        callbacks.on_destroy = Some(on_destroy);
+        callbacks.on_window_focus_changed = Some(on_window_focus_changed);
+        callbacks.on_native_window_created = Some(on_native_window_created);
+        callbacks.on_native_window_resized = Some(on_native_window_changed);
+        callbacks.on_native_window_redraw_needed = Some(on_native_window_redraw_needed);
+        callbacks.on_native_window_destroyed = Some(on_native_window_destroyed);
+        callbacks.on_input_queue_created = Some(on_input_queue_created);
+        callbacks.on_input_queue_destroyed = Some(on_input_queue_destroyed);
+        callbacks.on_content_rect_changed = Some(on_content_rect_changed);
+        callbacks.on_configuration_changed = Some(on_configuration_changed);
+
+        // Raw pointers aren't `Send`.
+        let activity = activity as usize;
+        std::thread::spawn(move || {
+            main();
+            let (lock, condvar) = &*COMMANDS;
+            let mut commands = lock.lock().unwrap();
+            // The event loop stopped without Android asking us to (e.g. because the window was closed), so close the
+            // activity too.
+            if !commands.destroyed {
+                ANativeActivity_finish(activity as *mut ANativeActivity);
+            }
+            commands.app_running = false;
+            condvar.notify_all();
+        });
+    }
+
+    pub fn event_loop<F>(&mut self, mut event_handler: F)
+    where
+        F: FnMut(&mut Cx, &mut Event),
+    {
+        self.event_handler =
+            Some(&mut event_handler as *const dyn FnMut(&mut Cx, &mut Event) as *mut dyn FnMut(&mut Cx, &mut Event));
+        self.event_loop_core();
+        self.event_handler = None;
+    }
+
+    fn event_loop_core(&mut self) {
+        self.platform_type = PlatformType::Android;
+
+        let mut android_app = AndroidApp::new().unwrap_or_else(|err| panic!("Could not start the Android app: {}", err));
+        let opengl_cx = OpenglCx::Egl(android_app.egl_cx);
+
+        self.load_fonts();
+
+        self.call_event_handler(&mut Event::Construct);
+
+        self.request_draw();
+
+        let mut passes_todo = Vec::new();
+
+        android_app.event_loop(|android_app, events| {
+            self.last_event_time = android_app.time_now();
+            for event in events {
+                self.process_pre_event(event);
+
+                match &event {
+                    Event::WindowGeomChange(re) => {
+                        self.windows[re.window_id].window_geom = re.new_geom.clone();
+                        // Android also sends this when the window surface got recreated, so always redraw.
+                        self.request_draw();
+                        self.call_event_handler(event);
+                    }
+                    Event::WindowClosed(wc) => {
+                        self.windows[wc.window_id].window_state = CxWindowState::Closed;
+                        self.windows_free.push(wc.window_id);
+                        if android_app.window.is_none() {
+                            android_app.terminate_event_loop();
+                        }
+                        self.call_event_handler(event);
+                    }
+                    Event::System(e) => {
+                        match e {
+                            SystemEvent::WindowSetHoverCursor(mc) => {
+                                self.set_hover_mouse_cursor(mc.clone());
+                            }
+                            SystemEvent::Paint => {
+                                let _vsync = self.process_desktop_paint_callbacks();
+
+                                // construct or destruct windows
+                                for (index, window) in self.windows.iter_mut().enumerate() {
+                                    window.window_state = match &window.window_state {
+                                        CxWindowState::Create { .. } => {
+                                            android_app.create_window(index);
+                                            if let Some(android_window) = &android_app.window {
+                                                window.window_geom = android_window.window_geom.clone();
+                                            }
+                                            CxWindowState::Created
+                                        }
+                                        CxWindowState::Close => {
+                                            android_app.close_window(index);
+                                            CxWindowState::Closed
+                                        }
+                                        CxWindowState::Created => CxWindowState::Created,
+                                        CxWindowState::Closed => CxWindowState::Closed,
+                                    };
+
+                                    // Android apps are always fullscreen, and don't have a title or an icon.
+                                    window.window_command = CxWindowCmd::None;
+                                    window.window_set_title = None;
+                                    window.window_set_icon = None;
+                                    window.window_style_changed = false;
+                                }
+
+                                if let Some(visible) = self.platform.set_ime_visible.take() {
+                                    android_app.set_soft_input_visible(visible);
+                                }
+
+                                if self.tray_needs_update() {
+                                    android_app.update_tray(self.platform.desktop.tray.as_ref());
+                                }
+
+                                while !self.platform.start_timer.is_empty() {
+                                    let (timer_id, interval, repeats) = self.platform.start_timer.pop().unwrap();
+                                    android_app.start_timer(timer_id, interval, repeats);
+                                }
+
+                                while !self.platform.stop_timer.is_empty() {
+                                    let timer_id = self.platform.stop_timer.pop().unwrap();
+                                    android_app.stop_timer(timer_id);
+                                }
+
+                                // build a list of renderpasses to repaint
+                                let mut windows_need_repaint = 0;
+                                self.compute_passes_to_repaint(&mut passes_todo, &mut windows_need_repaint);
+
+                                if !passes_todo.is_empty() {
+                                    self.opengl_compile_shaders(&opengl_cx);
+                                    for pass_id in &passes_todo {
+                                        match self.passes[*pass_id].dep_of.clone() {
+                                            CxPassDepOf::Window(window_id) => {
+                                                windows_need_repaint -= 1;
+                                                if let Some(android_window) = &mut android_app.window {
+                                                    // Leave the pass dirty while we don't have a surface; we get a
+                                                    // `WindowGeomChange` when we get one again.
+                                                    if android_window.window_id != window_id || !android_window.has_surface() {
+                                                        continue;
+                                                    }
+                                                    let dpi_factor = android_window.window_geom.dpi_factor;
+                                                    self.passes[*pass_id].set_dpi_factor(dpi_factor);
+                                                    self.passes[*pass_id].paint_dirty = false;
+                                                    self.draw_pass_to_android_window(
+                                                        *pass_id,
+                                                        dpi_factor,
+                                                        android_window,
+                                                        &opengl_cx,
+                                                    );
+                                                }
+                                            }
+                                            CxPassDepOf::Pass(parent_pass_id) => {
+                                                let dpi_factor = self.get_delegated_dpi_factor(parent_pass_id);
+                                                self.draw_pass_to_texture(*pass_id, dpi_factor, &opengl_cx);
+                                            }
+                                            CxPassDepOf::None => {
+                                                self.draw_pass_to_texture(*pass_id, 1.0, &opengl_cx);
+                                            }
+                                        }
+                                    }
+                                }
+                            }
+                            _ => {
+                                self.call_event_handler(event);
+                            }
+                        }
+                    }
+                    Event::None => {}
+                    Event::Signal { .. } => {
+                        self.call_event_handler(event);
+                        self.call_signals();
+                    }
+                    _ => {
+                        self.call_event_handler(event);
+                    }
+                }
+                self.process_post_event(event);
+            }
+
+            !(self.requested_draw || self.requested_next_frame)
+        })
+    }
+
+    pub(crate) fn draw_pass_to_android_window(
+        &mut self,
+        pass_id: usize,
+        dpi_factor: f32,
+        android_window: &mut AndroidWindow,
+        opengl_cx: &OpenglCx,
+    ) {
+        let OpenglCx::Egl(egl_cx) = opengl_cx;
+        unsafe {
+            eglMakeCurrent(egl_cx.display, android_window.egl_surface, android_window.egl_surface, egl_cx.context);
+        }
+        self.draw_pass_to_current_framebuffer(
+            pass_id,
+            dpi_factor,
+            android_window.window_id,
+            &android_window.window_geom,
+            opengl_cx,
+        );
+        // This blocks until the next vsync, so we don't need to throttle drawing ourselves.
+        unsafe {
+            eglSwapBuffers(egl_cx.display, android_window.egl_surface);
+        }
+    }
+
+    /// TODO(JP): Generalize [`Cx::post_signal`] into this.
+    #[cfg(feature = "cef")]
+    pub(crate) fn send_event_from_any_thread(_event: Event) {
+        todo!();
+    }
+
+    #[cfg(feature = "cef")]
+    pub(crate) fn cef_schedule_message_pump_work(_delay_ms: i64) {
+        todo!();
+    }
+}
+
+impl CxPlatformCommon for Cx {
+    /// See [`CxPlatformCommon::show_text_ime`] for documentation.
+    fn show_text_ime(&mut self, _x: f32, _y: f32) {
+        self.platform.set_ime_visible = Some(true);
+    }
+
+    /// See [`CxPlatformCommon::hide_text_ime`] for documentation.
+    fn hide_text_ime(&mut self) {
+        self.platform.set_ime_visible = Some(false);
+    }
+
+    /// See [`CxPlatformCommon::start_timer`] for documentation.
+    fn start_timer(&mut self, interval: f64, repeats: bool) -> Timer {
+        let timer_id = self.new_timer_id(repeats);
+        self.platform.start_timer.push((timer_id, interval, repeats));
+        Timer { timer_id }
+    }
+
+    /// See [`CxPlatformCommon::stop_timer`] for documentation.
+    fn stop_timer(&mut self, timer: &mut Timer) {
+        if let Some(timer_id) = self.take_active_timer_id(timer) {
+            self.platform.stop_timer.push(timer_id);
+        }
+    }
+
+    /// See [`CxPlatformCommon::post_signal`] for documentation.
+    fn post_signal(signal: Signal, status: StatusId) {
+        AndroidApp::post_signal(signal, status);
+    }
+
+    /// See [`CxPlatformCommon::update_menu`] for documentation.
+    fn update_menu(&mut self, _menu: &Menu) {}
+
+    /// See [`CxPlatformCommon::update_tray`] for documentation.
+    fn update_tray(&mut self, tray: Option<&Tray>) {
+        self.platform.desktop.update_tray(tray);
+    }
+
+    /// See [`CxPlatformCommon::update_menu`] for documentation.
+    fn copy_text_to_clipboard(&mut self, _text: &str) {
+        log!("Copying to the clipboard is not supported on Android yet");
+    }
+
+    /// See [`CxPlatformCommon::send_event_from_any_thread`] for documentation.
+    fn send_event_from_any_thread(event: Event) {
+        AndroidApp::send_event_from_any_thread(event);
+    }
+}
+
+#[derive(Clone, Default)]
+pub(crate) struct CxPlatform {
+    /// Whether to show or hide the soft keyboard on the next paint.
+    pub(crate) set_ime_visible: Option<bool>,
+    pub(crate) start_timer: Vec<(u64, f64, bool)>,
+    pub(crate) stop_timer: Vec<u64>,
+    pub(crate) desktop: CxDesktop,
+}
+
+pub(crate) struct AndroidApp {
+    looper: *mut ALooper,
+    input_queue: *mut AInputQueue,
+    native_window: *mut ANativeWindow,
+    /// The [`CxWindow`] that we draw into the activity's native window. Android only gives us a single window.
+    pub(crate) window: Option<AndroidWindow>,
+    /// The screen that the activity is on, once we have a native window.
+    displays: Vec<DisplayInfo>,
+    pub(crate) egl_cx: EglCx,
+    /// Written to by [`AndroidApp::send_event_from_any_thread`] to wake up the event loop.
+    signal_pipe: [c_int; 2],
+    command_pipe: c_int,
+    /// The `JNIEnv` of the app thread, for calling into Java.
+    jni_env: *mut JNIEnv,
+
+    pub(crate) time_start: u64,
+    pub(crate) event_callback: Option<*mut dyn FnMut(&mut AndroidApp, &mut Vec<Event>) -> bool>,
+    pub(crate) event_recur_block: bool,
+    pub(crate) event_loop_running: bool,
+    pub(crate) loop_block: bool,
+    timers: Vec<AndroidTimer>,
+    pub(crate) signals: Mutex<Vec<Event>>,
+    /// Events that we can't send right away because we're already in a callback, e.g. when closing a window.
+    pending_events: Vec<Event>,
+
+    /// The pointer id of every touch that's down, indexed by digit.
+    touch_digits: Vec<Option<i32>>,
+    soft_input_visible: bool,
+}
+
+pub(crate) struct AndroidWindow {
+    pub(crate) window_id: usize,
+    pub(crate) window_geom: WindowGeom,
+    /// Null while Android hasn't given us a native window, e.g. when the app is in the background.
+    egl_surface: *mut c_void,
+}
+
+#[derive(Clone, Copy)]
+struct AndroidTimer {
+    id: u64,
+    interval: f64,
+    repeats: bool,
+    /// In seconds since [`AndroidApp::time_start`].
+    fire_time: f64,
+}
+
+impl AndroidApp {
+    pub(crate) fn new() -> Result<Box<AndroidApp>, String> {
+        unsafe {
+            let activity = ACTIVITY;
+            if activity.is_null() {
+                return Err("Zaplib apps on Android have to be started through `NativeActivity`".to_string());
+            }
+
+            let egl_cx = EglCx::new()?;
+
+            let mut jni_env = ptr::null_mut();
+            let vm = (*activity).vm;
+            let attach_current_thread: unsafe extern "C" fn(*mut JavaVM, *mut *mut JNIEnv, *mut c_void) -> i32 =
+                jni_fn(vm, JAVA_VM_ATTACH_CURRENT_THREAD);
+            if attach_current_thread(vm, &mut jni_env, ptr::null_mut()) != 0 {
+                return Err("Could not attach the app thread to the Java VM".to_string());
+            }
+
+            let mut signal_pipe = [0; 2];
+            if libc::pipe(signal_pipe.as_mut_ptr()) != 0 {
+                return Err("Could not create the signal pipe".to_string());
+            }
+            libc::fcntl(signal_pipe[0], libc::F_SETFL, libc::O_NONBLOCK);
+            let command_pipe = COMMANDS.0.lock().unwrap().pipe[0];
+            libc::fcntl(command_pipe, libc::F_SETFL, libc::O_NONBLOCK);
+
+            let looper = ALooper_prepare(ALOOPER_PREPARE_ALLOW_NON_CALLBACKS);
+            ALooper_addFd(looper, command_pipe, LOOPER_ID_COMMANDS, ALOOPER_EVENT_INPUT, None, ptr::null_mut());
+            ALooper_addFd(looper, signal_pipe[0], LOOPER_ID_SIGNALS, ALOOPER_EVENT_INPUT, None, ptr::null_mut());
+
+            let mut android_app = Box::new(AndroidApp {
+                looper,
+                input_queue: ptr::null_mut(),
+                native_window: ptr::null_mut(),
+                window: None,
+                displays: Vec::new(),
+                egl_cx,
+                signal_pipe,
+                command_pipe,
+                jni_env,
+                time_start: precise_time_ns(),
+                event_callback: None,
+                event_recur_block: false,
+                event_loop_running: true,
+                loop_block: false,
+                timers: Vec::new(),
+                signals: Mutex::new(Vec::new()),
+                pending_events: Vec::new(),
+                touch_digits: vec![None; NUM_POINTERS],
+                soft_input_visible: false,
+            });
+            GLOBAL_ANDROID_APP = &mut *android_app as *mut AndroidApp;
+            Ok(android_app)
+        }
+    }
+
+    pub(crate) fn event_loop<F>(&mut self, mut event_handler: F)
+    where
+        F: FnMut(&mut AndroidApp, &mut Vec<Event>) -> bool,
+    {
+        unsafe {
+            self.event_callback = Some(
+                &mut event_handler as *const dyn FnMut(&mut AndroidApp, &mut Vec<Event>) -> bool
+                    as *mut dyn FnMut(&mut AndroidApp, &mut Vec<Event>) -> bool,
+            );
+
+            self.do_callback(&mut vec![Event::System(SystemEvent::Paint)]);
+
+            while self.event_loop_running {
+                // Without a surface there's nothing to draw into, so we can block until something happens.
+                let can_draw = self.window.as_ref().map_or(false, |window| window.has_surface());
+                let mut timeout = if self.loop_block || !can_draw {
+                    self.next_fire_time().map_or(-1, |fire_time| ((fire_time - self.time_now()).max(0.) * 1000.).ceil() as c_int)
+                } else {
+                    0
+                };
+                loop {
+                    let (mut fd, mut events, mut data) = (0, 0, ptr::null_mut());
+                    let ident = ALooper_pollAll(timeout, &mut fd, &mut events, &mut data);
+                    // Handle everything else that's ready, but don't block again.
+                    timeout = 0;
+                    match ident {
+                        LOOPER_ID_COMMANDS => self.process_commands(),
+                        LOOPER_ID_INPUT => self.process_input(),
+                        LOOPER_ID_SIGNALS => {
+                            let mut buffer = [0u8; 64];
+                            while libc::read(self.signal_pipe[0], buffer.as_mut_ptr() as *mut c_void, buffer.len()) > 0 {}
+                        }
+                        _ => break,
+                    }
+                    if !self.event_loop_running {
+                        break;
+                    }
+                }
+
+                self.fire_timers();
+
+                let mut pending_events = std::mem::take(&mut self.pending_events);
+                if !pending_events.is_empty() {
+                    self.do_callback(&mut pending_events);
+                }
+
+                // process all signals in the queue
+                let mut proc_signals = if let Ok(mut signals) = self.signals.lock() {
+                    let sigs = signals.clone();
+                    signals.truncate(0);
+                    sigs
+                } else {
+                    Vec::new()
+                };
+                if !proc_signals.is_empty() {
+                    self.do_callback(&mut proc_signals);
+                }
+
+                self.do_callback(&mut vec![Event::System(SystemEvent::Paint)]);
+            }
+
+            self.event_callback = None;
+        }
+    }
+
+    pub(crate) fn do_callback(&mut self, events: &mut Vec<Event>) {
+        unsafe {
+            if self.event_callback.is_none() || self.event_recur_block {
+                return;
+            };
+            self.event_recur_block = true;
+            let callback = self.event_callback.unwrap();
+            self.loop_block = (*callback)(self, events);
+            self.event_recur_block = false;
+        }
+    }
+
+    pub(crate) fn time_now(&self) -> f64 {
+        let time_now = precise_time_ns();
+        (time_now - self.time_start) as f64 / 1_000_000_000.0
+    }
+
+    pub(crate) fn start_timer(&mut self, id: u64, interval: f64, repeats: bool) {
+        let fire_time = self.time_now() + interval;
+        self.timers.push(AndroidTimer { id, interval, repeats, fire_time });
+    }
+
+    pub(crate) fn stop_timer(&mut self, id: u64) {
+        self.timers.retain(|timer| timer.id != id);
+    }
+
+    fn next_fire_time(&self) -> Option<f64> {
+        self.timers.iter().map(|timer| timer.fire_time).reduce(f64::min)
+    }
+
+    fn fire_timers(&mut self) {
+        let time_now = self.time_now();
+        let mut fired_timer_ids = Vec::new();
+        for timer in &mut self.timers {
+            if timer.fire_time <= time_now {
+                fired_timer_ids.push(timer.id);
+                // Don't fire a repeating timer multiple times in a row if we fell behind.
+                timer.fire_time = (timer.fire_time + timer.interval).max(time_now);
+            }
+        }
+        self.timers.retain(|timer| timer.repeats || !fired_timer_ids.contains(&timer.id));
+        for timer_id in fired_timer_ids {
+            self.do_callback(&mut vec![Event::Timer(TimerEvent { timer_id })]);
+        }
+    }
+
+    pub(crate) fn post_signal(signal: Signal, status: StatusId) {
+        let mut signals = HashMap::new();
+        let mut set = BTreeSet::new();
+        set.insert(status);
+        signals.insert(signal, set);
+        AndroidApp::send_event_from_any_thread(Event::Signal(SignalEvent { signals }));
+    }
+
+    pub(crate) fn send_event_from_any_thread(event: Event) {
+        unsafe {
+            // No event loop (e.g. in tests), so there is no one to send the event to.
+            if GLOBAL_ANDROID_APP.is_null() {
+                return;
+            }
+            if let Ok(mut signals_locked) = (*GLOBAL_ANDROID_APP).signals.lock() {
+                signals_locked.push(event);
+            }
+            // Wake up the event loop.
+            libc::write((*GLOBAL_ANDROID_APP).signal_pipe[1], [0u8].as_ptr() as *const c_void, 1);
+        }
+    }
+
+    pub(crate) fn terminate_event_loop(&mut self) {
+        self.event_loop_running = false;
+    }
+
+    fn process_commands(&mut self) {
+        let mut buffer = [0u8; 64];
+        while unsafe { libc::read(self.command_pipe, buffer.as_mut_ptr() as *mut c_void, buffer.len()) } > 0 {}
+
+        let (lock, condvar) = &*COMMANDS;
+        loop {
+            // Don't hold the lock while handling the command, since that calls into the app.
+            let command = lock.lock().unwrap().queue.pop_front();
+            let command = if let Some(command) = command { command } else { break };
+            self.handle_command(command);
+            lock.lock().unwrap().processed += 1;
+            condvar.notify_all();
+        }
+    }
+
+    fn handle_command(&mut self, command: AndroidCommand) {
+        unsafe {
+            match command {
+                AndroidCommand::WindowCreated(native_window) => {
+                    self.native_window = native_window;
+                    let egl_cx = self.egl_cx;
+                    if let Some(window) = &mut self.window {
+                        window.egl_surface = egl_cx.create_window_surface(native_window);
+                    }
+                    self.send_change_event();
+                }
+                AndroidCommand::WindowChanged | AndroidCommand::WindowRedrawNeeded => self.send_change_event(),
+                AndroidCommand::WindowDestroyed => {
+                    let egl_cx = self.egl_cx;
+                    if let Some(window) = &mut self.window {
+                        egl_cx.destroy_window_surface(window.egl_surface);
+                        window.egl_surface = ptr::null_mut();
+                    }
+                    self.native_window = ptr::null_mut();
+                }
+                AndroidCommand::InputQueueCreated(input_queue) => {
+                    self.input_queue = input_queue;
+                    AInputQueue_attachLooper(input_queue, self.looper, LOOPER_ID_INPUT, None, ptr::null_mut());
+                }
+                AndroidCommand::InputQueueDestroyed => {
+                    if !self.input_queue.is_null() {
+                        AInputQueue_detachLooper(self.input_queue);
+                        self.input_queue = ptr::null_mut();
+                    }
+                }
+                // We keep running while paused, since Android destroys the window when we're hidden anyway, and
+                // we stop drawing then.
+                AndroidCommand::Resume | AndroidCommand::Pause => {}
+                AndroidCommand::FocusChanged(true) => self.do_callback(&mut vec![Event::AppFocus]),
+                AndroidCommand::FocusChanged(false) => self.do_callback(&mut vec![Event::AppFocusLost]),
+                AndroidCommand::Destroy => self.terminate_event_loop(),
+            }
+        }
+    }
+
+    fn get_window_geom(&self) -> WindowGeom {
+        let dpi_factor = self.dpi_factor();
+        let size = if self.native_window.is_null() {
+            self.window.as_ref().map_or(Vec2::default(), |window| window.window_geom.inner_size)
+        } else {
+            unsafe {
+                Vec2 {
+                    x: ANativeWindow_getWidth(self.native_window) as f32 / dpi_factor,
+                    y: ANativeWindow_getHeight(self.native_window) as f32 / dpi_factor,
+                }
+            }
+        };
+        WindowGeom {
+            xr_is_presenting: false,
+            xr_can_present: false,
+            can_fullscreen: false,
+            is_topmost: false,
+            is_fullscreen: true,
+            is_maximized: true,
+            inner_size: size,
+            outer_size: size,
+            dpi_factor,
+            position: Vec2::default(),
+        }
+    }
+
+    /// Android's density buckets are relative to 160 dpi, which is what it calls a density-independent pixel.
+    fn dpi_factor(&self) -> f32 {
+        unsafe {
+            let config = AConfiguration_new();
+            AConfiguration_fromAssetManager(config, (*ACTIVITY).asset_manager);
+            let density = AConfiguration_getDensity(config);
+            AConfiguration_delete(config);
+            if density > 0 {
+                density as f32 / 160.
+            } else {
+                1.
+            }
+        }
+    }
+
+    fn send_change_event(&mut self) {
+        let new_geom = self.get_window_geom();
+        let (window_id, old_geom) = if let Some(window) = &mut self.window {
+            (window.window_id, std::mem::replace(&mut window.window_geom, new_geom.clone()))
+        } else {
+            return;
+        };
+        let mut events = vec![Event::WindowGeomChange(WindowGeomChangeEvent { window_id, old_geom, new_geom: new_geom.clone() })];
+        // We run fullscreen, so the screen is the size of the window, and it changes when rotating the device.
+        let displays = vec![DisplayInfo {
+            name: "Built-in".to_string(),
+            size: new_geom.inner_size,
+            work_area: Rect { pos: Vec2::default(), size: new_geom.inner_size },
+            dpi_factor: new_geom.dpi_factor,
+            is_primary: true,
+            ..DisplayInfo::default()
+        }];
+        if displays != self.displays {
+            self.displays = displays.clone();
+            events.push(Event::DisplaysChanged(DisplaysChangedEvent { displays }));
+        }
+        events.push(Event::System(SystemEvent::Paint));
+        self.do_callback(&mut events);
+    }
+
+    fn process_input(&mut self) {
+        if self.input_queue.is_null() {
+            return;
+        }
+        unsafe {
+            let mut event = ptr::null_mut();
+            while AInputQueue_getEvent(self.input_queue, &mut event) >= 0 {
+                // This lets the soft keyboard handle the event first.
+                if AInputQueue_preDispatchEvent(self.input_queue, event) != 0 {
+                    continue;
+                }
+                let handled = match AInputEvent_getType(event) {
+                    AINPUT_EVENT_TYPE_MOTION => self.handle_motion_event(event),
+                    AINPUT_EVENT_TYPE_KEY => self.handle_key_event(event),
+                    _ => false,
+                };
+                AInputQueue_finishEvent(self.input_queue, event, handled as c_int);
+            }
+        }
+    }
+
+    unsafe fn handle_motion_event(&mut self, event: *mut AInputEvent) -> bool {
+        let window_id = if let Some(window) = &self.window { window.window_id } else { return false };
+        let dpi_factor = self.dpi_factor();
+        let action = AMotionEvent_getAction(event);
+        let action_index =
+            ((action & AMOTION_EVENT_ACTION_POINTER_INDEX_MASK) >> AMOTION_EVENT_ACTION_POINTER_INDEX_SHIFT) as usize;
+        let modifiers = modifiers_from_meta_state(AMotionEvent_getMetaState(event));
+        let time = self.time_now();
+        let pos = |index: usize| Vec2 {
+            x: AMotionEvent_getX(event, index) / dpi_factor,
+            y: AMotionEvent_getY(event, index) / dpi_factor,
+        };
+        let input_type = |index: usize| {
+            if AMotionEvent_getToolType(event, index) == AMOTION_EVENT_TOOL_TYPE_MOUSE {
+                PointerInputType::Mouse
+            } else {
+                PointerInputType::Touch
+            }
+        };
+
+        let mut events = Vec::new();
+        match action & AMOTION_EVENT_ACTION_MASK {
+            AMOTION_EVENT_ACTION_DOWN | AMOTION_EVENT_ACTION_POINTER_DOWN => {
+                let pointer_id = AMotionEvent_getPointerId(event, action_index);
+                let digit = if let Some(digit) = self.touch_digits.iter().position(|id| id.is_none()) {
+                    digit
+                } else {
+                    // More touches than we can track.
+                    return true;
+                };
+                self.touch_digits[digit] = Some(pointer_id);
+                events.push(Event::PointerDown(PointerDownEvent {
+                    window_id,
+                    abs: pos(action_index),
+                    rel: pos(action_index),
+                    rect: Rect::default(),
+                    digit,
+                    button: MouseButton::Left,
+                    handled: false,
+                    input_type: input_type(action_index),
+                    modifiers,
+                    tap_count: 0,
+                    time,
+                }));
+            }
+            AMOTION_EVENT_ACTION_MOVE => {
+                for index in 0..AMotionEvent_getPointerCount(event) {
+                    let pointer_id = AMotionEvent_getPointerId(event, index);
+                    if let Some(digit) = self.touch_digits.iter().position(|id| *id == Some(pointer_id)) {
+                        events.push(Event::PointerMove(PointerMoveEvent {
+                            window_id,
+                            abs: pos(index),
+                            rel: pos(index),
+                            rect: Rect::default(),
+                            digit,
+                            abs_start: Vec2::default(),
+                            rel_start: Vec2::default(),
+                            is_over: false,
+                            input_type: input_type(index),
+                            modifiers: modifiers.clone(),
+                            time,
+                        }));
+                    }
+                }
+            }
+            action_masked @ (AMOTION_EVENT_ACTION_UP | AMOTION_EVENT_ACTION_POINTER_UP | AMOTION_EVENT_ACTION_CANCEL) => {
+                // A cancel (e.g. when a system gesture takes over) ends all touches.
+                let indices = if action_masked == AMOTION_EVENT_ACTION_CANCEL {
+                    0..AMotionEvent_getPointerCount(event)
+                } else {
+                    action_index..action_index + 1
+                };
+                for index in indices {
+                    let pointer_id = AMotionEvent_getPointerId(event, index);
+                    if let Some(digit) = self.touch_digits.iter().position(|id| *id == Some(pointer_id)) {
+                        self.touch_digits[digit] = None;
+                        events.push(Event::PointerUp(PointerUpEvent {
+                            window_id,
+                            abs: pos(index),
+                            rel: pos(index),
+                            rect: Rect::default(),
+                            abs_start: Vec2::default(),
+                            rel_start: Vec2::default(),
+                            digit,
+                            button: MouseButton::Left,
+                            is_over: false,
+                            input_type: input_type(index),
+                            modifiers: modifiers.clone(),
+                            time,
+                        }));
+                    }
+                }
+            }
+            action_masked @ (AMOTION_EVENT_ACTION_HOVER_MOVE | AMOTION_EVENT_ACTION_HOVER_EXIT) => {
+                events.push(Event::PointerHover(PointerHoverEvent {
+                    digit: 0,
+                    window_id,
+                    abs: pos(0),
+                    rel: pos(0),
+                    any_down: false,
+                    rect: Rect::default(),
+                    handled: false,
+                    hover_state: if action_masked == AMOTION_EVENT_ACTION_HOVER_EXIT {
+                        HoverState::Out
+                    } else {
+                        HoverState::Over
+                    },
+                    modifiers,
+                    time,
+                }));
+            }
+            AMOTION_EVENT_ACTION_SCROLL => {
+                // Android reports scroll wheel steps with positive values meaning up and right.
+                let step = DEFAULT_LINES_PER_WHEEL_STEP * SCROLL_LINE_HEIGHT;
+                let scroll = Vec2 {
+                    x: -AMotionEvent_getAxisValue(event, AMOTION_EVENT_AXIS_HSCROLL, 0) * step,
+                    y: -AMotionEvent_getAxisValue(event, AMOTION_EVENT_AXIS_VSCROLL, 0) * step,
+                };
+                events.push(Event::PointerScroll(PointerScrollEvent {
+                    digit: 0,
+                    window_id,
+                    scroll,
+                    pinch: 0.,
+                    device: ScrollDevice::Wheel,
+                    phase: ScrollPhase::Changed,
+                    abs: pos(0),
+                    rel: pos(0),
+                    rect: Rect::default(),
+                    input_type: PointerInputType::Mouse,
+                    modifiers,
+                    handled_x: false,
+                    handled_y: false,
+                    time,
+                }));
+            }
+            _ => return false,
+        }
+        self.do_callback(&mut events);
+        true
+    }
+
+    unsafe fn handle_key_event(&mut self, event: *mut AInputEvent) -> bool {
+        let android_key_code = AKeyEvent_getKeyCode(event);
+        // Let Android handle the back button, which closes the app.
+        if android_key_code == AKEYCODE_BACK {
+            return false;
+        }
+        let action = AKeyEvent_getAction(event);
+        let meta_state = AKeyEvent_getMetaState(event);
+        let key_event = KeyEvent {
+            key_code: android_keycode_to_keycode(android_key_code),
+            is_repeat: AKeyEvent_getRepeatCount(event) > 0,
+            modifiers: modifiers_from_meta_state(meta_state),
+            time: self.time_now(),
+        };
+        match action {
+            AKEY_EVENT_ACTION_DOWN => {
+                let modifiers = key_event.modifiers.clone();
+                self.do_callback(&mut vec![Event::KeyDown(key_event)]);
+                if !(modifiers.control || modifiers.logo || modifiers.alt) {
+                    if let Some(char_code) = self.get_unicode_char(android_key_code, meta_state) {
+                        if char_code >= ' ' && char_code != 127 as char {
+                            self.do_callback(&mut vec![Event::TextInput(TextInputEvent {
+                                input: char_code.to_string(),
+                                was_paste: false,
+                                replace_last: false,
+                            })]);
+                        }
+                    }
+                }
+                true
+            }
+            AKEY_EVENT_ACTION_UP => {
+                self.do_callback(&mut vec![Event::KeyUp(key_event)]);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// The NDK has no way to get the character that a key produces, so we ask Java using
+    /// `new KeyEvent(ACTION_DOWN, keyCode).getUnicodeChar(metaState)`.
+    unsafe fn get_unicode_char(&self, key_code: i32, meta_state: i32) -> Option<char> {
+        let env = self.jni_env;
+        let find_class: unsafe extern "C" fn(*mut JNIEnv, *const c_char) -> *mut c_void = jni_fn(env, JNI_FIND_CLASS);
+        let get_method_id: unsafe extern "C" fn(*mut JNIEnv, *mut c_void, *const c_char, *const c_char) -> *mut c_void =
+            jni_fn(env, JNI_GET_METHOD_ID);
+        let new_object_a: unsafe extern "C" fn(*mut JNIEnv, *mut c_void, *mut c_void, *const jvalue) -> *mut c_void =
+            jni_fn(env, JNI_NEW_OBJECT_A);
+        let call_int_method_a: unsafe extern "C" fn(*mut JNIEnv, *mut c_void, *mut c_void, *const jvalue) -> i32 =
+            jni_fn(env, JNI_CALL_INT_METHOD_A);
+        let delete_local_ref: unsafe extern "C" fn(*mut JNIEnv, *mut c_void) = jni_fn(env, JNI_DELETE_LOCAL_REF);
+        let exception_check: unsafe extern "C" fn(*mut JNIEnv) -> u8 = jni_fn(env, JNI_EXCEPTION_CHECK);
+        let exception_clear: unsafe extern "C" fn(*mut JNIEnv) = jni_fn(env, JNI_EXCEPTION_CLEAR);
+
+        let class = find_class(env, b"android/view/KeyEvent\0".as_ptr() as *const c_char);
+        if class.is_null() {
+            exception_clear(env);
+            return None;
+        }
+        let constructor = get_method_id(env, class, b"<init>\0".as_ptr() as *const c_char, b"(II)V\0".as_ptr() as *const c_char);
+        let get_unicode_char =
+            get_method_id(env, class, b"getUnicodeChar\0".as_ptr() as *const c_char, b"(I)I\0".as_ptr() as *const c_char);
+        let key_event =
+            new_object_a(env, class, constructor, [jvalue { i: AKEY_EVENT_ACTION_DOWN }, jvalue { i: key_code }].as_ptr());
+        let unicode_char = call_int_method_a(env, key_event, get_unicode_char, [jvalue { i: meta_state }].as_ptr());
+        if exception_check(env) != 0 {
+            exception_clear(env);
+        }
+        delete_local_ref(env, key_event);
+        delete_local_ref(env, class);
+        char::from_u32(unicode_char as u32).filter(|char_code| *char_code != '\0')
+    }
+
+    pub(crate) fn set_soft_input_visible(&mut self, visible: bool) {
+        if self.soft_input_visible == visible {
+            return;
+        }
+        self.soft_input_visible = visible;
+        unsafe {
+            if visible {
+                ANativeActivity_showSoftInput(ACTIVITY, ANATIVEACTIVITY_SHOW_SOFT_INPUT_FORCED);
+            } else {
+                ANativeActivity_hideSoftInput(ACTIVITY, 0);
+            }
+        }
+    }
+
+    pub(crate) fn update_tray(&mut self, tray: Option<&Tray>) {
+        if tray.is_some() {
+            log!("Tray icons are not supported on Android");
+        }
+    }
+
+    /// Android gives every activity a single native window, so we only support one window.
+    pub(crate) fn create_window(&mut self, window_id: usize) {
+        if let Some(window) = &self.window {
+            log!("Android only supports a single window; not creating window {} next to window {}", window_id, window.window_id);
+            return;
+        }
+        let egl_surface =
+            if self.native_window.is_null() { ptr::null_mut() } else { self.egl_cx.create_window_surface(self.native_window) };
+        let window_geom = self.get_window_geom();
+        self.window = Some(AndroidWindow { window_id, window_geom, egl_surface });
+    }
+
+    pub(crate) fn close_window(&mut self, window_id: usize) {
+        if !matches!(&self.window, Some(window) if window.window_id == window_id) {
+            return;
+        }
+        if let Some(window) = self.window.take() {
+            self.egl_cx.destroy_window_surface(window.egl_surface);
+        }
+        // We're in the middle of a callback, so send this later.
+        self.pending_events.push(Event::WindowClosed(WindowClosedEvent { window_id }));
+    }
+}
+
+impl Drop for AndroidApp {
+    fn drop(&mut self) {
+        unsafe {
+            GLOBAL_ANDROID_APP = ptr::null_mut();
+            if let Some(window) = self.window.take() {
+                self.egl_cx.destroy_window_surface(window.egl_surface);
+            }
+            if !self.input_queue.is_null() {
+                AInputQueue_detachLooper(self.input_queue);
+            }
+            ALooper_removeFd(self.looper, self.command_pipe);
+            ALooper_removeFd(self.looper, self.signal_pipe[0]);
+            libc::close(self.signal_pipe[0]);
+            libc::close(self.signal_pipe[1]);
+            let vm = (*ACTIVITY).vm;
+            let detach_current_thread: unsafe extern "C" fn(*mut JavaVM) -> i32 = jni_fn(vm, JAVA_VM_DETACH_CURRENT_THREAD);
+            detach_current_thread(vm);
+        }
+    }
+}
+
+impl AndroidWindow {
+    pub(crate) fn has_surface(&self) -> bool {
+        !self.egl_surface.is_null()
+    }
+}
+
+fn modifiers_from_meta_state(meta_state: i32) -> KeyModifiers {
+    KeyModifiers {
+        shift: meta_state & AMETA_SHIFT_ON != 0,
+        control: meta_state & AMETA_CTRL_ON != 0,
+        alt: meta_state & AMETA_ALT_ON != 0,
+        logo: meta_state & AMETA_META_ON != 0,
+    }
+}
+
+/// See `android/keycodes.h`.
+fn android_keycode_to_keycode(key_code: i32) -> KeyCode {
+    match key_code {
+        7 => KeyCode::Key0,
+        8 => KeyCode::Key1,
+        9 => KeyCode::Key2,
+        10 => KeyCode::Key3,
+        11 => KeyCode::Key4,
+        12 => KeyCode::Key5,
+        13 => KeyCode::Key6,
+        14 => KeyCode::Key7,
+        15 => KeyCode::Key8,
+        16 => KeyCode::Key9,
+        19 => KeyCode::ArrowUp,
+        20 => KeyCode::ArrowDown,
+        21 => KeyCode::ArrowLeft,
+        22 => KeyCode::ArrowRight,
+        29 => KeyCode::KeyA,
+        30 => KeyCode::KeyB,
+        31 => KeyCode::KeyC,
+        32 => KeyCode::KeyD,
+        33 => KeyCode::KeyE,
+        34 => KeyCode::KeyF,
+        35 => KeyCode::KeyG,
+        36 => KeyCode::KeyH,
+        37 => KeyCode::KeyI,
+        38 => KeyCode::KeyJ,
+        39 => KeyCode::KeyK,
+        40 => KeyCode::KeyL,
+        41 => KeyCode::KeyM,
+        42 => KeyCode::KeyN,
+        43 => KeyCode::KeyO,
+        44 => KeyCode::KeyP,
+        45 => KeyCode::KeyQ,
+        46 => KeyCode::KeyR,
+        47 => KeyCode::KeyS,
+        48 => KeyCode::KeyT,
+        49 => KeyCode::KeyU,
+        50 => KeyCode::KeyV,
+        51 => KeyCode::KeyW,
+        52 => KeyCode::KeyX,
+        53 => KeyCode::KeyY,
+        54 => KeyCode::KeyZ,
+        55 => KeyCode::Comma,
+        56 => KeyCode::Period,
+        57 | 58 => KeyCode::Alt,
+        59 | 60 => KeyCode::Shift,
+        61 => KeyCode::Tab,
+        62 => KeyCode::Space,
+        66 => KeyCode::Return,
+        67 => KeyCode::Backspace,
+        68 => KeyCode::Backtick,
+        69 => KeyCode::Minus,
+        70 => KeyCode::Equals,
+        71 => KeyCode::LBracket,
+        72 => KeyCode::RBracket,
+        73 => KeyCode::Backslash,
+        74 => KeyCode::Semicolon,
+        75 => KeyCode::Quote,
+        76 => KeyCode::Slash,
+        92 => KeyCode::PageUp,
+        93 => KeyCode::PageDown,
+        111 => KeyCode::Escape,
+        112 => KeyCode::Delete,
+        113 | 114 => KeyCode::Control,
+        115 => KeyCode::Capslock,
+        116 => KeyCode::Scrolllock,
+        117 | 118 => KeyCode::Logo,
+        120 => KeyCode::PrintScreen,
+        121 => KeyCode::Pause,
+        122 => KeyCode::Home,
+        123 => KeyCode::End,
+        124 => KeyCode::Insert,
+        131 => KeyCode::F1,
+        132 => KeyCode::F2,
+        133 => KeyCode::F3,
+        134 => KeyCode::F4,
+        135 => KeyCode::F5,
+        136 => KeyCode::F6,
+        137 => KeyCode::F7,
+        138 => KeyCode::F8,
+        139 => KeyCode::F9,
+        140 => KeyCode::F10,
+        141 => KeyCode::F11,
+        142 => KeyCode::F12,
+        143 => KeyCode::Numlock,
+        144 => KeyCode::Numpad0,
+        145 => KeyCode::Numpad1,
+        146 => KeyCode::Numpad2,
+        147 => KeyCode::Numpad3,
+        148 => KeyCode::Numpad4,
+        149 => KeyCode::Numpad5,
+        150 => KeyCode::Numpad6,
+        151 => KeyCode::Numpad7,
+        152 => KeyCode::Numpad8,
+        153 => KeyCode::Numpad9,
+        154 => KeyCode::NumpadDivide,
+        155 => KeyCode::NumpadMultiply,
+        156 => KeyCode::NumpadSubtract,
+        157 => KeyCode::NumpadAdd,
+        158 => KeyCode::NumpadDecimal,
+        160 => KeyCode::NumpadEnter,
+        161 => KeyCode::NumpadEquals,
+        _ => KeyCode::Unknown,
+    }
+}
+
+/// An OpenGL ES 3 context created with EGL; see [`OpenglCx::Egl`].
+#[derive(Clone, Copy)]
+pub(crate) struct EglCx {
+    pub(crate) display: *mut c_void,
+    config: *mut c_void,
+    context: *mut c_void,
+    /// A tiny offscreen surface, for when we don't have a window to draw into.
+    pbuffer: *mut c_void,
+}
+
+impl EglCx {
+    fn new() -> Result<EglCx, String> {
+        unsafe {
+            let display = eglGetDisplay(ptr::null_mut());
+            if display.is_null() {
+                return Err("Could not get the EGL display".to_string());
+            }
+            let (mut major, mut minor) = (0, 0);
+            if eglInitialize(display, &mut major, &mut minor) == 0 {
+                return Err("Could not initialize EGL".to_string());
+            }
+            eglBindAPI(EGL_OPENGL_ES_API);
+
+            let config_attribs = [
+                EGL_SURFACE_TYPE,
+                EGL_WINDOW_BIT | EGL_PBUFFER_BIT,
+                EGL_RENDERABLE_TYPE,
+                EGL_OPENGL_ES3_BIT,
+                EGL_RED_SIZE,
+                8,
+                EGL_GREEN_SIZE,
+                8,
+                EGL_BLUE_SIZE,
+                8,
+                EGL_ALPHA_SIZE,
+                0,
+                EGL_DEPTH_SIZE,
+                24,
+                EGL_NONE,
+            ];
+            let mut config = ptr::null_mut();
+            let mut config_count = 0;
+            if eglChooseConfig(display, config_attribs.as_ptr(), &mut config, 1, &mut config_count) == 0 || config_count == 0 {
+                return Err("Could not choose an EGL framebuffer configuration".to_string());
+            }
+
+            let context_attribs = [EGL_CONTEXT_MAJOR_VERSION, 3, EGL_NONE];
+            let context = eglCreateContext(display, config, ptr::null_mut(), context_attribs.as_ptr());
+            if context.is_null() {
+                return Err("Could not create an OpenGL ES 3 context".to_string());
+            }
+
+            let pbuffer_attribs = [EGL_WIDTH, 1, EGL_HEIGHT, 1, EGL_NONE];
+            let pbuffer = eglCreatePbufferSurface(display, config, pbuffer_attribs.as_ptr());
+            if pbuffer.is_null() {
+                return Err("Could not create an EGL pbuffer surface".to_string());
+            }
+
+            let egl_cx = EglCx { display, config, context, pbuffer };
+            egl_cx.make_current_without_window();
+
+            // `eglGetProcAddress` is only guaranteed to return extension functions on older Android versions, so look
+            // up core functions in `libGLESv3.so` directly.
+            let gles = libc::dlopen(b"libGLESv3.so\0".as_ptr() as *const c_char, libc::RTLD_NOW | libc::RTLD_LOCAL);
+            gl::load_with(|symbol| {
+                let symbol = CString::new(symbol).unwrap();
+                let function = eglGetProcAddress(symbol.as_ptr());
+                if function.is_null() && !gles.is_null() {
+                    libc::dlsym(gles, symbol.as_ptr()) as *const c_void
+                } else {
+                    function
+                }
+            });
+            Ok(egl_cx)
+        }
+    }
+
+    pub(crate) fn make_current_without_window(&self) {
+        unsafe { eglMakeCurrent(self.display, self.pbuffer, self.pbuffer, self.context) };
+    }
+
+    fn create_window_surface(&self, native_window: *mut ANativeWindow) -> *mut c_void {
+        unsafe {
+            // The window's buffers have to match the pixel format of our config.
+            let mut format = 0;
+            eglGetConfigAttrib(self.display, self.config, EGL_NATIVE_VISUAL_ID, &mut format);
+            ANativeWindow_setBuffersGeometry(native_window, 0, 0, format);
+            let surface = eglCreateWindowSurface(self.display, self.config, native_window as *mut c_void, ptr::null());
+            if surface.is_null() {
+                log!("Could not create an EGL window surface");
+            }
+            surface
+        }
+    }
+
+    fn destroy_window_surface(&self, surface: *mut c_void) {
+        if surface.is_null() {
+            return;
+        }
+        unsafe {
+            // Android requires that we stop using the surface before the native window is destroyed.
+            self.make_current_without_window();
+            eglDestroySurface(self.display, surface);
+        }
+    }
+}
@@ -1,4 +1,4 @@
-//! Bindings for OSX Core Foundation and Metal.
+//! Bindings for Apple's Core Foundation and Metal, shared between Mac OS X and iOS.
 
 // Clippy TODO
 #![allow(clippy::enum_variant_names)]
@@ -57,6 +57,7 @@ extern "C" {
     pub(crate) fn NSStringFromClass(cls: id) -> id;
 }
 
+#[cfg(target_os = "macos")]
 #[link(name = "AppKit", kind = "framework")]
 extern "C" {
     pub(crate) static NSStringPboardType: id;
@@ -66,8 +67,12 @@ extern "C" {
 
 #[link(name = "CoreGraphics", kind = "framework")]
 extern "C" {
+    #[cfg(target_os = "macos")]
     pub(crate) fn CGMainDisplayID() -> u32;
+    #[cfg(target_os = "macos")]
     pub(crate) fn CGDisplayPixelsHigh(display: u32) -> u64;
+    #[cfg(target_os = "macos")]
+    pub(crate) fn CGAssociateMouseAndMouseCursorPosition(connected: u32) -> i32;
     pub(crate) fn CGColorCreateSRGB(red: f64, green: f64, blue: f64, alpha: f64) -> id;
 }
 
@@ -95,7 +100,7 @@ extern "C" {}
 pub(crate) fn get_all_metal_devices() -> Vec<id> {
     #[cfg(target_os = "ios")]
     {
-        MTLCreateSystemDefaultDevice().into_iter().collect()
+        get_default_metal_device().into_iter().collect()
     }
     #[cfg(not(target_os = "ios"))]
     unsafe {
@@ -135,12 +140,14 @@ pub(crate) fn str_to_nsstring(val: &str) -> id {
     }
 }
 
+#[cfg(target_os = "macos")]
 pub(crate) fn load_native_cursor(cursor_name: &str) -> id {
     let sel = Sel::register(cursor_name);
     let id: id = unsafe { msg_send![class!(NSCursor), performSelector: sel] };
     id
 }
 
+#[cfg(target_os = "macos")]
 pub(crate) fn load_undocumented_cursor(cursor_name: &str) -> id {
     unsafe {
         let class = class!(NSCursor);
@@ -151,6 +158,7 @@ pub(crate) fn load_undocumented_cursor(cursor_name: &str) -> id {
     }
 }
 
+#[cfg(target_os = "macos")]
 pub(crate) fn load_webkit_cursor(cursor_name_str: &str) -> id {
     unsafe {
         static CURSOR_ROOT: &str = "/System/Library/Frameworks/ApplicationServices.framework/Versions/A/Frameworks/HIServices.\
@@ -608,6 +616,11 @@ pub(crate) enum MTLCPUCacheMode {
     WriteCombined = 1,
 }
 
+/// Values of `NSEventPhase`, which is a bit mask (NSUInteger).
+pub(crate) const NSEventPhaseNone: u64 = 0;
+pub(crate) const NSEventPhaseEnded: u64 = 1 << 3;
+pub(crate) const NSEventPhaseCancelled: u64 = 1 << 4;
+
 pub(crate) const MTLResourceCPUCacheModeShift: u64 = 0;
 pub(crate) const MTLResourceCPUCacheModeMask: u64 = 0xf << MTLResourceCPUCacheModeShift;
 pub(crate) const MTLResourceStorageModeShift: u64 = 4;
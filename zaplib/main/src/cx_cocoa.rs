@@ -63,8 +63,12 @@ pub(crate) struct CocoaApp {
     pub(crate) timer_delegate_instance: id,
     pub(crate) timers: Vec<CocoaTimer>,
     pub(crate) cocoa_windows: Vec<(id, id)>,
+    /// The `NSStatusItem` for the current [`Tray`], or `nil`.
+    pub(crate) status_item: id,
     pub(crate) last_key_mod: KeyModifiers,
     pub(crate) startup_focus_hack_ran: bool,
+    /// See [`CocoaApp::update_displays`].
+    pub(crate) displays: Vec<DisplayInfo>,
     pub(crate) event_callback: Option<*mut dyn FnMut(&mut CocoaApp, &mut Vec<Event>) -> bool>,
     pub(crate) event_recur_block: bool,
     pub(crate) event_loop_running: bool,
@@ -75,6 +79,8 @@ pub(crate) struct CocoaApp {
     pub(crate) cursors: HashMap<MouseCursor, id>,
     #[cfg(not(feature = "cef"))]
     pub(crate) current_cursor: MouseCursor,
+    /// Whether the mouse is disconnected from the cursor; see [`CocoaApp::set_pointer_lock`].
+    pub(crate) pointer_locked: bool,
     #[cfg(feature = "cef")]
     cef_timer: std::sync::RwLock<id>,
     // TODO(Paras): Should we actually use this field to block handling events in Rust?
@@ -109,6 +115,7 @@ impl CocoaApp {
                     count: const_attributes.len()
                 ],
                 startup_focus_hack_ran: false,
+                displays: Vec::new(),
                 const_empty_string: str_to_nsstring(""),
                 time_start: Instant::now(),
                 timer_delegate_instance,
@@ -123,6 +130,7 @@ impl CocoaApp {
                 app_delegate_instance,
                 timers: Vec::new(),
                 cocoa_windows: Vec::new(),
+                status_item: nil,
                 loop_block: false,
                 last_key_mod: KeyModifiers { ..Default::default() },
                 event_callback: None,
@@ -135,6 +143,7 @@ impl CocoaApp {
                 cursors: HashMap::new(),
                 #[cfg(not(feature = "cef"))]
                 current_cursor: MouseCursor::Default,
+                pointer_locked: false,
                 #[cfg(feature = "cef")]
                 cef_timer: std::sync::RwLock::new(nil),
                 #[cfg(feature = "cef")]
@@ -144,70 +153,43 @@ impl CocoaApp {
     }
 
     pub(crate) fn update_app_menu(&mut self, menu: &Menu, command_settings: &HashMap<CommandId, CxCommandSetting>) {
-        unsafe fn make_menu(
-            parent_menu: id,
-            delegate: id,
-            menu_target_class: *const Class,
-            menu: &Menu,
-            command_settings: &HashMap<CommandId, CxCommandSetting>,
-        ) {
-            match menu {
-                Menu::Main { items } => {
-                    let main_menu: id = msg_send![class!(NSMenu), new];
-                    let () = msg_send![main_menu, setTitle: str_to_nsstring("MainMenu")];
-                    let () = msg_send![main_menu, setAutoenablesItems: NO];
-                    let () = msg_send![main_menu, setDelegate: delegate];
-
-                    for item in items {
-                        make_menu(main_menu, delegate, menu_target_class, item, command_settings);
-                    }
-                    let ns_app: id = msg_send![class!(NSApplication), sharedApplication];
-                    let () = msg_send![ns_app, setMainMenu: main_menu];
-                }
-                Menu::Sub { name, items } => {
-                    let sub_menu: id = msg_send![class!(NSMenu), new];
-                    let () = msg_send![sub_menu, setTitle: str_to_nsstring(name)];
-                    let () = msg_send![sub_menu, setAutoenablesItems: NO];
-                    let () = msg_send![sub_menu, setDelegate: delegate];
-                    // append item to parebt
-                    let sub_item: id = msg_send![
-                        parent_menu,
-                        addItemWithTitle: str_to_nsstring(name)
-                        action: nil
-                        keyEquivalent: str_to_nsstring("")
-                    ];
-                    // connect submenu
-                    let () = msg_send![parent_menu, setSubmenu: sub_menu forItem: sub_item];
-                    for item in items {
-                        make_menu(sub_menu, delegate, menu_target_class, item, command_settings);
-                    }
-                }
-                Menu::Item { name, command } => {
-                    let settings =
-                        if let Some(settings) = command_settings.get(command) { *settings } else { CxCommandSetting::default() };
-                    let sub_item: id = msg_send![
-                        parent_menu,
-                        addItemWithTitle: str_to_nsstring(name)
-                        action: sel!(menuAction:)
-                        keyEquivalent: str_to_nsstring(keycode_to_menu_key(settings.key_code, settings.shift))
-                    ];
-                    let target: id = msg_send![menu_target_class, new];
-                    let () = msg_send![sub_item, setTarget: target];
-                    let () = msg_send![sub_item, setEnabled: if settings.enabled {YES}else {NO}];
+        unsafe {
+            make_menu(nil, self.menu_delegate_instance, self.menu_target_class, menu, command_settings);
+        }
+    }
 
-                    let command_usize = command.0;
-                    (*target).set_ivar("cocoa_app_ptr", GLOBAL_COCOA_APP as *mut _ as *mut c_void);
-                    (*target).set_ivar("command_usize", command_usize);
-                }
-                Menu::Line => {
-                    let sep_item: id = msg_send![class!(NSMenuItem), separatorItem];
-                    let () = msg_send![parent_menu, addItem: sep_item];
+    /// Show a [`Tray`] item in the menu bar, or remove it when passing `None`.
+    pub(crate) fn update_tray(&mut self, tray: Option<&Tray>, command_settings: &HashMap<CommandId, CxCommandSetting>) {
+        unsafe {
+            let status_bar: id = msg_send![class!(NSStatusBar), systemStatusBar];
+            if self.status_item != nil {
+                let () = msg_send![status_bar, removeStatusItem: self.status_item];
+                let () = msg_send![self.status_item, release];
+                self.status_item = nil;
+            }
+            if let Some(tray) = tray {
+                // -1 is `NSVariableStatusItemLength`.
+                let status_item: id = msg_send![status_bar, statusItemWithLength: -1.0f64];
+                let () = msg_send![status_item, retain];
+                let button: id = msg_send![status_item, button];
+                let image = rgba_to_nsimage(tray.icon.width, tray.icon.height, &tray.icon.rgba);
+                // Menu bar icons are 18 points high.
+                let () = msg_send![image, setSize: NSSize { width: 18., height: 18. }];
+                let () = msg_send![button, setImage: image];
+                let () = msg_send![image, release];
+                let () = msg_send![button, setToolTip: str_to_nsstring(&tray.tooltip)];
+
+                let menu: id = msg_send![class!(NSMenu), new];
+                let () = msg_send![menu, setAutoenablesItems: NO];
+                let () = msg_send![menu, setDelegate: self.menu_delegate_instance];
+                for item in &tray.items {
+                    make_menu(menu, self.menu_delegate_instance, self.menu_target_class, item, command_settings);
                 }
+                let () = msg_send![status_item, setMenu: menu];
+                let () = msg_send![menu, release];
+                self.status_item = status_item;
             }
         }
-        unsafe {
-            make_menu(nil, self.menu_delegate_instance, self.menu_target_class, menu, command_settings);
-        }
     }
 
     pub(crate) fn startup_focus_hack(&mut self) {
@@ -269,6 +251,54 @@ impl CocoaApp {
         (time_now.duration_since(self.time_start)).as_micros() as f64 / 1_000_000.0
     }
 
+    /// List `NSScreen.screens`, of which the first one is the primary display. Returns whether
+    /// [`CocoaApp::displays`] changed.
+    pub(crate) fn update_displays(&mut self) -> bool {
+        let displays: Vec<DisplayInfo> = unsafe {
+            let screens: id = msg_send![class!(NSScreen), screens];
+            let count: u64 = msg_send![screens, count];
+            // Cocoa puts the origin at the bottom-left of the primary display, with y going up.
+            let mut primary_height = 0.;
+            (0..count)
+                .map(|display_id| {
+                    let screen: id = msg_send![screens, objectAtIndex: display_id];
+                    let frame: NSRect = msg_send![screen, frame];
+                    let visible_frame: NSRect = msg_send![screen, visibleFrame];
+                    let scale: f64 = msg_send![screen, backingScaleFactor];
+                    if display_id == 0 {
+                        primary_height = frame.size.height;
+                    }
+                    // `localizedName` and `maximumFramesPerSecond` need Mac OS X 10.15 and 12.
+                    let has_name: BOOL = msg_send![screen, respondsToSelector: sel!(localizedName)];
+                    let name = if has_name == YES { nsstring_to_string(msg_send![screen, localizedName]) } else { String::new() };
+                    let has_refresh_rate: BOOL = msg_send![screen, respondsToSelector: sel!(maximumFramesPerSecond)];
+                    let refresh_rate: i64 = if has_refresh_rate == YES { msg_send![screen, maximumFramesPerSecond] } else { 0 };
+                    let top = primary_height - (frame.origin.y + frame.size.height);
+                    let visible_top = primary_height - (visible_frame.origin.y + visible_frame.size.height);
+                    DisplayInfo {
+                        display_id: display_id as usize,
+                        name,
+                        position: vec2(frame.origin.x as f32, top as f32),
+                        size: vec2(frame.size.width as f32, frame.size.height as f32),
+                        work_area: Rect {
+                            pos: vec2((visible_frame.origin.x - frame.origin.x) as f32, (visible_top - top) as f32),
+                            size: vec2(visible_frame.size.width as f32, visible_frame.size.height as f32),
+                        },
+                        dpi_factor: scale as f32,
+                        refresh_rate: refresh_rate as f32,
+                        is_primary: display_id == 0,
+                    }
+                })
+                .collect()
+        };
+        if displays != self.displays {
+            self.displays = displays;
+            true
+        } else {
+            false
+        }
+    }
+
     unsafe fn process_ns_event(&mut self, ns_event: id) {
         let ev_type: NSEventType = msg_send![ns_event, type];
 
@@ -484,6 +514,15 @@ impl CocoaApp {
         }
     }
 
+    /// Keep the cursor in place while the mouse moves, or undo that. The cursor gets hidden through
+    /// [`Cx::current_mouse_cursor`]. Mouse events keep coming in with their deltas, but with the same position.
+    pub(crate) fn set_pointer_lock(&mut self, locked: bool) {
+        unsafe {
+            CGAssociateMouseAndMouseCursorPosition(if locked { 0 } else { 1 });
+        }
+        self.pointer_locked = locked;
+    }
+
     pub(crate) fn send_event_from_any_thread(event: Event) {
         unsafe {
             let pool: id = msg_send![class!(NSAutoreleasePool), new];
@@ -597,6 +636,97 @@ impl CocoaApp {
     }
 }
 
+/// Add `menu` to `parent_menu`, or set it as the main menu if it is a [`Menu::Main`].
+unsafe fn make_menu(
+    parent_menu: id,
+    delegate: id,
+    menu_target_class: *const Class,
+    menu: &Menu,
+    command_settings: &HashMap<CommandId, CxCommandSetting>,
+) {
+    match menu {
+        Menu::Main { items } => {
+            let main_menu: id = msg_send![class!(NSMenu), new];
+            let () = msg_send![main_menu, setTitle: str_to_nsstring("MainMenu")];
+            let () = msg_send![main_menu, setAutoenablesItems: NO];
+            let () = msg_send![main_menu, setDelegate: delegate];
+
+            for item in items {
+                make_menu(main_menu, delegate, menu_target_class, item, command_settings);
+            }
+            let ns_app: id = msg_send![class!(NSApplication), sharedApplication];
+            let () = msg_send![ns_app, setMainMenu: main_menu];
+        }
+        Menu::Sub { name, items } => {
+            let sub_menu: id = msg_send![class!(NSMenu), new];
+            let () = msg_send![sub_menu, setTitle: str_to_nsstring(name)];
+            let () = msg_send![sub_menu, setAutoenablesItems: NO];
+            let () = msg_send![sub_menu, setDelegate: delegate];
+            // append item to parebt
+            let sub_item: id = msg_send![
+                parent_menu,
+                addItemWithTitle: str_to_nsstring(name)
+                action: nil
+                keyEquivalent: str_to_nsstring("")
+            ];
+            // connect submenu
+            let () = msg_send![parent_menu, setSubmenu: sub_menu forItem: sub_item];
+            for item in items {
+                make_menu(sub_menu, delegate, menu_target_class, item, command_settings);
+            }
+        }
+        Menu::Item { name, command } => {
+            let settings =
+                if let Some(settings) = command_settings.get(command) { *settings } else { CxCommandSetting::default() };
+            let sub_item: id = msg_send![
+                parent_menu,
+                addItemWithTitle: str_to_nsstring(name)
+                action: sel!(menuAction:)
+                keyEquivalent: str_to_nsstring(keycode_to_menu_key(settings.key_code, settings.shift))
+            ];
+            let target: id = msg_send![menu_target_class, new];
+            let () = msg_send![sub_item, setTarget: target];
+            let () = msg_send![sub_item, setEnabled: if settings.enabled {YES}else {NO}];
+
+            let command_usize = command.0;
+            (*target).set_ivar("cocoa_app_ptr", GLOBAL_COCOA_APP as *mut _ as *mut c_void);
+            (*target).set_ivar("command_usize", command_usize);
+        }
+        Menu::Line => {
+            let sep_item: id = msg_send![class!(NSMenuItem), separatorItem];
+            let () = msg_send![parent_menu, addItem: sep_item];
+        }
+    }
+}
+
+/// Create an `NSImage` from 8-bit RGBA pixels, like in [`WindowIcon`] or [`CustomCursor`]. The caller is responsible for
+/// releasing it.
+unsafe fn rgba_to_nsimage(width: usize, height: usize, rgba: &[u8]) -> id {
+    // Passing null planes makes the image rep allocate its own buffer, which we then copy into.
+    let image_rep: id = msg_send![class!(NSBitmapImageRep), alloc];
+    let image_rep: id = msg_send![
+        image_rep,
+        initWithBitmapDataPlanes: std::ptr::null_mut::<c_void>()
+        pixelsWide: width as i64
+        pixelsHigh: height as i64
+        bitsPerSample: 8i64
+        samplesPerPixel: 4i64
+        hasAlpha: YES
+        isPlanar: NO
+        colorSpaceName: str_to_nsstring("NSDeviceRGBColorSpace")
+        bytesPerRow: (width * 4) as i64
+        bitsPerPixel: 32i64
+    ];
+    let bitmap_data: *mut u8 = msg_send![image_rep, bitmapData];
+    std::ptr::copy_nonoverlapping(rgba.as_ptr(), bitmap_data, rgba.len().min(width * height * 4));
+
+    let image: id = msg_send![class!(NSImage), alloc];
+    let image: id = msg_send![image, initWithSize: NSSize { width: width as f64, height: height as f64 }];
+    let () = msg_send![image, addRepresentation: image_rep];
+    let () = msg_send![image_rep, release];
+    image
+}
+
 impl CocoaWindow {
     pub(crate) fn new(cocoa_app: &mut CocoaApp, window_id: usize) -> CocoaWindow {
         unsafe {
@@ -767,6 +897,52 @@ impl CocoaWindow {
 
     pub(crate) fn set_topmost(&mut self, _topmost: bool) {}
 
+    pub(crate) fn set_fullscreen(&mut self, fullscreen: bool) {
+        // `is_fullscreen` gets updated by the window delegate once the animation is done.
+        if fullscreen != self.is_fullscreen {
+            unsafe {
+                let () = msg_send![self.window, toggleFullScreen: nil];
+            }
+        }
+    }
+
+    pub(crate) fn set_title(&mut self, title: &str) {
+        unsafe {
+            let () = msg_send![self.window, setTitle: str_to_nsstring(title)];
+        }
+    }
+
+    /// Set the icon of the app in the dock, since Mac windows don't have their own icons.
+    pub(crate) fn set_icon(icon: &WindowIcon) {
+        unsafe {
+            let pool: id = msg_send![class!(NSAutoreleasePool), new];
+            let image = rgba_to_nsimage(icon.width, icon.height, &icon.rgba);
+            let app: id = msg_send![class!(NSApplication), sharedApplication];
+            let () = msg_send![app, setApplicationIconImage: image];
+            let () = msg_send![image, release];
+            let () = msg_send![pool, release];
+        }
+    }
+
+    pub(crate) fn set_style(&mut self, style: &CxWindowStyle) {
+        let mut window_masks = NSWindowStyleMask::NSFullSizeContentViewWindowMask as u64;
+        if !style.borderless {
+            window_masks |= NSWindowStyleMask::NSClosableWindowMask as u64
+                | NSWindowStyleMask::NSMiniaturizableWindowMask as u64
+                | NSWindowStyleMask::NSTitledWindowMask as u64;
+        }
+        if style.resizable {
+            window_masks |= NSWindowStyleMask::NSResizableWindowMask as u64;
+        }
+        let min_size = style.min_size.unwrap_or_default();
+        let max_size = style.max_size.unwrap_or(Vec2 { x: f32::MAX, y: f32::MAX });
+        unsafe {
+            let () = msg_send![self.window, setStyleMask: window_masks];
+            let () = msg_send![self.window, setContentMinSize: NSSize { width: min_size.x as f64, height: min_size.y as f64 }];
+            let () = msg_send![self.window, setContentMaxSize: NSSize { width: max_size.x as f64, height: max_size.y as f64 }];
+        }
+    }
+
     pub(crate) fn time_now(&self) -> f64 {
         let time_now = Instant::now(); //unsafe {mach_absolute_time()};
         (time_now.duration_since(self.time_start)).as_micros() as f64 / 1_000_000.0
@@ -778,7 +954,8 @@ impl CocoaWindow {
             xr_can_present: false,
             is_topmost: false,
             is_fullscreen: self.is_fullscreen,
-            can_fullscreen: false,
+            is_maximized: false,
+            can_fullscreen: true,
             inner_size: self.get_inner_size(),
             outer_size: self.get_outer_size(),
             dpi_factor: self.get_dpi_factor(),
@@ -802,6 +979,29 @@ impl CocoaWindow {
         };
     }
 
+    /// Center the window in the visible frame of a screen from [`CocoaApp::displays`]. Fullscreen windows live in
+    /// their own space, so we leave those alone.
+    pub(crate) fn move_to_display(&mut self, display_id: usize) {
+        if self.is_fullscreen {
+            return;
+        }
+        unsafe {
+            let screens: id = msg_send![class!(NSScreen), screens];
+            let count: u64 = msg_send![screens, count];
+            if display_id as u64 >= count {
+                return;
+            }
+            let screen: id = msg_send![screens, objectAtIndex: display_id as u64];
+            let visible_frame: NSRect = msg_send![screen, visibleFrame];
+            let mut window_frame: NSRect = msg_send![self.window, frame];
+            window_frame.size.width = window_frame.size.width.min(visible_frame.size.width);
+            window_frame.size.height = window_frame.size.height.min(visible_frame.size.height);
+            window_frame.origin.x = visible_frame.origin.x + (visible_frame.size.width - window_frame.size.width) / 2.;
+            window_frame.origin.y = visible_frame.origin.y + (visible_frame.size.height - window_frame.size.height) / 2.;
+            let () = msg_send![self.window, setFrame: window_frame display: YES];
+        }
+    }
+
     pub(crate) fn get_position(&self) -> Vec2 {
         let window_frame: NSRect = unsafe { msg_send![self.window, frame] };
         Vec2 { x: window_frame.origin.x as f32, y: window_frame.origin.y as f32 }
@@ -861,6 +1061,12 @@ impl CocoaWindow {
 
     pub(crate) fn send_focus_lost_event(&mut self) {
         self.do_callback(&mut vec![Event::AppFocusLost]);
+        // Don't keep the mouse stuck when switching to another app.
+        let cocoa_app = unsafe { &mut *self.cocoa_app };
+        if cocoa_app.pointer_locked {
+            cocoa_app.set_pointer_lock(false);
+            self.do_callback(&mut vec![Event::PointerLockChanged(PointerLockChangedEvent { locked: false })]);
+        }
     }
 
     pub(crate) fn mouse_down_can_drag_window(&mut self) -> bool {
@@ -976,14 +1182,30 @@ impl CocoaWindow {
         self.do_callback(&mut vec![Event::TextInput(TextInputEvent { input, was_paste: false, replace_last })])
     }
 
-    pub(crate) fn send_scroll(&mut self, dx: f64, dy: f64, has_prec: bool, modifiers: KeyModifiers) {
-        let scroll =
-            if has_prec { Vec2 { x: -dx as f32, y: -dy as f32 } } else { Vec2 { x: -dx as f32 * 32., y: -dy as f32 * 32. } };
+    /// Without precise deltas, `dx` and `dy` are in lines, which already take the wheel acceleration of Mac OS X into
+    /// account.
+    pub(crate) fn send_scroll(
+        &mut self,
+        dx: f64,
+        dy: f64,
+        pinch: f32,
+        has_prec: bool,
+        phase: ScrollPhase,
+        modifiers: KeyModifiers,
+    ) {
+        let scroll = if has_prec {
+            Vec2 { x: -dx as f32, y: -dy as f32 }
+        } else {
+            Vec2 { x: -dx as f32 * SCROLL_LINE_HEIGHT, y: -dy as f32 * SCROLL_LINE_HEIGHT }
+        };
 
         self.do_callback(&mut vec![Event::PointerScroll(PointerScrollEvent {
             digit: 0,
             window_id: self.window_id,
             scroll,
+            pinch,
+            device: if has_prec { ScrollDevice::Trackpad } else { ScrollDevice::Wheel },
+            phase,
             abs: self.last_mouse_pos,
             rel: self.last_mouse_pos,
             rect: Rect::default(),
@@ -1379,10 +1601,22 @@ pub(crate) fn define_app_delegate() -> *const Class {
         }
     }
 
+    extern "C" fn application_did_change_screen_parameters(this: &Object, _: Sel, _notification: id) {
+        let ca = get_cocoa_app(this);
+        if ca.update_displays() {
+            let displays = ca.displays.clone();
+            ca.do_callback(&mut vec![Event::DisplaysChanged(DisplaysChangedEvent { displays })]);
+        }
+    }
+
     let superclass = class!(NSObject);
     let mut decl = ClassDecl::new("AppDelegate", superclass).unwrap();
     unsafe {
         decl.add_method(sel!(application:openFiles:), open_files as extern "C" fn(&Object, Sel, id, id));
+        decl.add_method(
+            sel!(applicationDidChangeScreenParameters:),
+            application_did_change_screen_parameters as extern "C" fn(&Object, Sel, id),
+        );
     }
     decl.add_ivar::<*mut c_void>("cocoa_app_ptr");
     decl.register()
@@ -1665,6 +1899,18 @@ pub(crate) fn define_cocoa_window_class() -> *const Class {
 
                 // mouse_motion
                 let modifiers = get_event_key_modifier(ns_event);
+                // The deltas keep coming when the cursor is at the edge of the screen or when the pointer is locked.
+                let dx: f64 = msg_send![ns_event, deltaX];
+                let dy: f64 = msg_send![ns_event, deltaY];
+                if dx != 0.0 || dy != 0.0 {
+                    let time = cocoa_window.time_now();
+                    cocoa_window.do_callback(&mut vec![Event::PointerRawMove(PointerRawMoveEvent {
+                        window_id: cocoa_window.window_id,
+                        delta: vec2(dx as f32, dy as f32),
+                        modifiers: modifiers.clone(),
+                        time,
+                    })]);
+                }
                 cocoa_window.send_pointer_hover_and_move(pos, modifiers);
             },
             NSEventType::NSLeftMouseDown => {
@@ -1695,9 +1941,23 @@ pub(crate) fn define_cocoa_window_class() -> *const Class {
                 let dx: f64 = unsafe { msg_send![ns_event, scrollingDeltaX] };
                 let dy: f64 = unsafe { msg_send![ns_event, scrollingDeltaY] };
                 let has_prec: BOOL = unsafe { msg_send![ns_event, hasPreciseScrollingDeltas] };
+                let phase: u64 = unsafe { msg_send![ns_event, phase] };
+                let momentum_phase: u64 = unsafe { msg_send![ns_event, momentumPhase] };
                 let modifiers = get_event_key_modifier(ns_event);
 
-                cocoa_window.send_scroll(dx, dy, has_prec == YES, modifiers);
+                let phase = if momentum_phase != NSEventPhaseNone {
+                    ScrollPhase::Momentum
+                } else if phase & (NSEventPhaseEnded | NSEventPhaseCancelled) != 0 {
+                    ScrollPhase::Ended
+                } else {
+                    ScrollPhase::Changed
+                };
+                cocoa_window.send_scroll(dx, dy, 0., has_prec == YES, phase, modifiers);
+            }
+            NSEventType::NSEventTypeMagnify => {
+                let magnification: f64 = unsafe { msg_send![ns_event, magnification] };
+                let modifiers = get_event_key_modifier(ns_event);
+                cocoa_window.send_scroll(0., 0., magnification as f32, true, ScrollPhase::Changed, modifiers);
             }
             _ => (),
         }
@@ -2001,14 +2261,18 @@ pub(crate) fn bottom_left_to_top_left(rect: NSRect) -> f64 {
 #[cfg(not(feature = "cef"))]
 fn load_mouse_cursor(cursor: MouseCursor) -> id {
     match cursor {
-        MouseCursor::Arrow | MouseCursor::Default | MouseCursor::Hidden => load_native_cursor("arrowCursor"),
+        MouseCursor::Arrow | MouseCursor::Default => load_native_cursor("arrowCursor"),
+        // There's no hidden cursor, so use a transparent one.
+        MouseCursor::Hidden => load_custom_cursor(&CustomCursor::new(1, 1, vec![0; 4], Vec2::default())),
+        MouseCursor::Custom(custom_cursor) => load_custom_cursor(&custom_cursor),
         MouseCursor::Hand => load_native_cursor("pointingHandCursor"),
         MouseCursor::Text => load_native_cursor("IBeamCursor"),
         // ` | MouseCursor::NoDrop`
         MouseCursor::NotAllowed => load_native_cursor("operationNotAllowedCursor"),
         MouseCursor::Crosshair => load_native_cursor("crosshairCursor"),
+        MouseCursor::Grab => load_native_cursor("openHandCursor"),
+        MouseCursor::Grabbing => load_native_cursor("closedHandCursor"),
         /*
-        MouseCursor::Grabbing | MouseCursor::Grab => load_native_cursor("closedHandCursor"),
         MouseCursor::VerticalText => load_native_cursor("IBeamCursorForVerticalLayout"),
         MouseCursor::Copy => load_native_cursor("dragCopyCursor"),
         MouseCursor::Alias => load_native_cursor("dragLinkCursor"),
@@ -2054,6 +2318,20 @@ fn load_mouse_cursor(cursor: MouseCursor) -> id {
     }
 }
 
+/// Create an `NSCursor` from a [`CustomCursor`].
+#[cfg(not(feature = "cef"))]
+fn load_custom_cursor(custom_cursor: &CustomCursor) -> id {
+    unsafe {
+        let image = rgba_to_nsimage(custom_cursor.width, custom_cursor.height, &custom_cursor.rgba);
+        let cursor: id = msg_send![class!(NSCursor), alloc];
+        // Hotspots are from the top left in `NSCursor` as well.
+        let hotspot = NSPoint { x: custom_cursor.hotspot.x as f64, y: custom_cursor.hotspot.y as f64 };
+        let cursor: id = msg_send![cursor, initWithImage: image hotSpot: hotspot];
+        let () = msg_send![image, release];
+        cursor
+    }
+}
+
 #[cfg(feature = "cef")]
 /// If Cef is enabled, we need to make sure it does not interfere with dragging
 /// files to the main window. This function will recursively traverse the view
@@ -8,6 +8,21 @@ use std::net::TcpStream;
 #[derive(Clone, Default)]
 pub(crate) struct CxDesktop {
     pub(crate) repaint_via_scroll_event: bool,
+    /// The last [`Tray`] passed to [`CxPlatformCommon::update_tray`].
+    pub(crate) tray: Option<Tray>,
+    /// Set when [`CxDesktop::tray`] changed, so the platform event loop should apply it.
+    pub(crate) tray_changed: bool,
+    /// Which commands of [`CxDesktop::tray`] were enabled when we last applied it.
+    pub(crate) tray_enabled_commands: Vec<CommandId>,
+}
+
+impl CxDesktop {
+    pub(crate) fn update_tray(&mut self, tray: Option<&Tray>) {
+        if self.tray.as_ref() != tray {
+            self.tray = tray.cloned();
+            self.tray_changed = true;
+        }
+    }
 }
 
 impl CxDesktopVsWasmCommon for Cx {
@@ -100,9 +115,46 @@ impl CxDesktopVsWasmCommon for Cx {
     /// This never gets called if cef is not enabled, but we need it to pass compilation.
     #[cfg(not(feature = "cef"))]
     fn return_to_js(&mut self, _callback_id: u32, _params: Vec<ZapParam>) {}
+
+    /// See [`CxDesktopVsWasmCommon::stream_to_js`] for documentation.
+    #[cfg(feature = "cef")]
+    fn stream_to_js(&mut self, callback_id: u32, params: Vec<ZapParam>) {
+        self.cef_browser.stream_to_js(callback_id, params);
+    }
+    /// This never gets called if cef is not enabled, but we need it to pass compilation.
+    #[cfg(not(feature = "cef"))]
+    fn stream_to_js(&mut self, _callback_id: u32, _params: Vec<ZapParam>) {}
 }
 
 impl Cx {
+    /// Whether [`CxDesktop::tray`] or which of its commands are enabled changed since the last call, in which case the
+    /// platform event loop should apply it again.
+    pub(crate) fn tray_needs_update(&mut self) -> bool {
+        fn add_enabled_commands(items: &[Menu], cx: &Cx, enabled_commands: &mut Vec<CommandId>) {
+            for item in items {
+                match item {
+                    Menu::Item { command, .. } => {
+                        if matches!(cx.command_settings.get(command), Some(settings) if settings.enabled) {
+                            enabled_commands.push(*command);
+                        }
+                    }
+                    Menu::Sub { items, .. } | Menu::Main { items } => add_enabled_commands(items, cx, enabled_commands),
+                    Menu::Line => {}
+                }
+            }
+        }
+
+        let mut enabled_commands = Vec::new();
+        if let Some(tray) = &self.platform.desktop.tray {
+            add_enabled_commands(&tray.items, self, &mut enabled_commands);
+        }
+        if enabled_commands != self.platform.desktop.tray_enabled_commands {
+            self.platform.desktop.tray_enabled_commands = enabled_commands;
+            self.platform.desktop.tray_changed = true;
+        }
+        std::mem::replace(&mut self.platform.desktop.tray_changed, false)
+    }
+
     pub(crate) fn process_desktop_paint_callbacks(&mut self) -> bool {
         let mut vsync = false; //self.platform.desktop.repaint_via_scroll_event;
         self.platform.desktop.repaint_via_scroll_event = false;
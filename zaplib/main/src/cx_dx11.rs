@@ -3,14 +3,16 @@
 #![allow(dead_code)]
 
 use crate::cx_win32::*;
+use crate::shader_cache::ShaderCache;
 use crate::*;
 use std::ffi;
 use std::mem;
 use std::ptr;
 use winapi::shared::guiddef::GUID;
 use winapi::shared::minwindef::{FALSE, TRUE};
-use winapi::shared::{dxgi, dxgi1_2, dxgiformat, dxgitype, winerror};
-use winapi::um::{d3d11, d3dcommon, d3dcompiler};
+use winapi::shared::{dxgi, dxgi1_2, dxgi1_3, dxgi1_4, dxgi1_5, dxgiformat, dxgitype, winerror};
+use winapi::um::winnt::HANDLE;
+use winapi::um::{d3d11, d3dcommon, d3dcompiler, handleapi, synchapi};
 use winapi::Interface;
 use wio::com::ComPtr;
 use zaplib_shader_compiler::generate_hlsl;
@@ -39,6 +41,10 @@ impl Cx {
 
         for draw_call_id in 0..draw_calls_len {
             let sub_view_id = self.views[view_id].draw_calls[draw_call_id].sub_view_id;
+            if self.views[view_id].draw_calls[draw_call_id].batched_into.is_some() {
+                // Got merged into an earlier draw call; see `Cx::batch_draw_calls`.
+                continue;
+            }
             if sub_view_id != 0 {
                 self.render_view(
                     pass_id,
@@ -123,7 +129,14 @@ impl Cx {
                     match cxtexture.desc.format {
                         // we only allocate Image and Mapped textures.
                         TextureFormat::ImageRGBA => {
-                            if cxtexture.update_image {
+                            if cxtexture.update_image && cxtexture.desc.cube {
+                                cxtexture.update_image = false;
+                                d3d11_cx.update_platform_texture_cube_rgba(
+                                    &mut cxtexture.platform,
+                                    cxtexture.desc.width.unwrap(),
+                                    &cxtexture.image_u32,
+                                );
+                            } else if cxtexture.update_image {
                                 cxtexture.update_image = false;
                                 d3d11_cx.update_platform_texture_image_rgba(
                                     &mut cxtexture.platform,
@@ -261,7 +274,7 @@ impl Cx {
             &mut zbias,
             zbias_step,
         );
-        d3d11_window.present(vsync);
+        d3d11_window.present(vsync, d3d11_cx);
         //println!("{}", (Cx::profile_time_ns() - time1)as f64 / 1000.0);
     }
 
@@ -292,7 +305,7 @@ impl Cx {
                 println!("--------------- Shader {} --------------- \n{}\n", &shader.name, hlsl);
             }
 
-            let vs_blob = d3d11_cx.compile_shader("vs", "mpsc_vertex_main".as_bytes(), hlsl.as_bytes());
+            let vs_blob = d3d11_cx.compile_shader_cached("vs", "mpsc_vertex_main".as_bytes(), hlsl.as_bytes());
 
             fn split_source(src: &str) -> String {
                 let mut r = String::new();
@@ -312,7 +325,7 @@ impl Cx {
             }
             let vs_blob = vs_blob.unwrap();
 
-            let ps_blob = d3d11_cx.compile_shader("ps", "mpsc_fragment_main".as_bytes(), hlsl.as_bytes());
+            let ps_blob = d3d11_cx.compile_shader_cached("ps", "mpsc_fragment_main".as_bytes(), hlsl.as_bytes());
 
             if let Err(msg) = ps_blob {
                 println!("{}\n{}", msg, split_source(&hlsl));
@@ -432,6 +445,8 @@ pub(crate) struct D3d11Window {
     pub(crate) alloc_size: Vec2,
     pub(crate) first_draw: bool,
     pub(crate) swap_chain: ComPtr<dxgi1_2::IDXGISwapChain1>,
+    /// Signaled when the swap chain is ready for a new frame; see [`D3d11Window::wait_for_swap_chain`].
+    frame_latency_waitable: HANDLE,
 }
 
 impl D3d11Window {
@@ -451,6 +466,8 @@ impl D3d11Window {
         let swap_chain =
             d3d11_cx.create_swap_chain_for_hwnd(&window_geom, &win32_window).expect("Cannot create_swap_chain_for_hwnd");
 
+        let frame_latency_waitable = d3d11_cx.configure_swap_chain(&swap_chain);
+
         let swap_texture = D3d11Cx::get_swap_texture(&swap_chain).expect("Cannot get swap texture");
 
         let render_target_view =
@@ -471,6 +488,7 @@ impl D3d11Window {
             //d2d1_hwnd_target: None,
             //d2d1_bitmap: None,
             swap_chain,
+            frame_latency_waitable,
             //raster_state: raster_state,
             //blend_state: blend_state
         }
@@ -504,16 +522,43 @@ impl D3d11Window {
         self.swap_texture = Some(swap_texture);
     }
 
-    pub(crate) fn present(&mut self, vsync: bool) {
-        unsafe { self.swap_chain.Present(if vsync { 1 } else { 0 }, 0) };
+    /// Block until the swap chain is ready for a new frame. Call this before drawing rather than letting `Present`
+    /// block, so that we draw with the latest input, instead of queueing up a frame that is already outdated.
+    pub(crate) fn wait_for_swap_chain(&self) {
+        if !self.frame_latency_waitable.is_null() {
+            unsafe { synchapi::WaitForSingleObjectEx(self.frame_latency_waitable, 1000, TRUE) };
+        }
+    }
+
+    /// Without vsync we allow tearing (if supported), like `displaySyncEnabled` on Metal.
+    pub(crate) fn present(&mut self, vsync: bool, d3d11_cx: &D3d11Cx) {
+        let flags = if !vsync && d3d11_cx.allow_tearing { DXGI_PRESENT_ALLOW_TEARING } else { 0 };
+        unsafe { self.swap_chain.Present(if vsync { 1 } else { 0 }, flags) };
     }
 }
 
+impl Drop for D3d11Window {
+    fn drop(&mut self) {
+        if !self.frame_latency_waitable.is_null() {
+            unsafe { handleapi::CloseHandle(self.frame_latency_waitable) };
+        }
+    }
+}
+
+// From dxgi.h.
+const DXGI_SWAP_CHAIN_FLAG_FRAME_LATENCY_WAITABLE_OBJECT: u32 = 64;
+const DXGI_SWAP_CHAIN_FLAG_ALLOW_TEARING: u32 = 2048;
+const DXGI_PRESENT_ALLOW_TEARING: u32 = 0x200;
+
 #[derive(Clone)]
 pub(crate) struct D3d11Cx {
     pub(crate) device: ComPtr<d3d11::ID3D11Device>,
     pub(crate) context: ComPtr<d3d11::ID3D11DeviceContext>,
     pub(crate) factory: ComPtr<dxgi1_2::IDXGIFactory2>,
+    /// Whether we can present without vsync in windowed mode, which needs Windows 10 and a recent driver.
+    pub(crate) allow_tearing: bool,
+    /// Compiled shader bytecode from previous runs; see [`D3d11Cx::compile_shader_cached`].
+    shader_cache: ShaderCache,
     //    pub(crate) d2d1_factory: ComPtr<d2d1::ID2D1Factory>
 }
 
@@ -522,11 +567,14 @@ impl D3d11Cx {
         let factory = D3d11Cx::create_dxgi_factory1(&dxgi1_2::IDXGIFactory2::uuidof()).expect("cannot create_dxgi_factory1");
         let adapter = D3d11Cx::enum_adapters(&factory).expect("cannot enum_adapters");
         let (device, context) = D3d11Cx::create_d3d11_device(&adapter).expect("cannot create_d3d11_device");
+        let allow_tearing = D3d11Cx::check_tearing_support(&factory);
         // let d2d1_factory = D3d11Cx::create_d2d1_factory().expect("cannot create_d2d1_factory");
         D3d11Cx {
             device,
             context,
             factory,
+            allow_tearing,
+            shader_cache: ShaderCache::new(),
             //    d2d1_factory: d2d1_factory
         }
     }
@@ -654,6 +702,33 @@ impl D3d11Cx {
         unsafe { self.context.DrawIndexedInstanced(num_vertices as u32, num_instances as u32, 0, 0, 0) };
     }
 
+    /// Like [`D3d11Cx::compile_shader`], but uses bytecode from a previous run of the app if the shader source
+    /// didn't change, which saves a lot of startup time for apps with many shaders.
+    pub(crate) fn compile_shader_cached(
+        &self,
+        stage: &str,
+        entry: &[u8],
+        shader: &[u8],
+    ) -> Result<ComPtr<d3dcommon::ID3DBlob>, String> {
+        let kind = format!("hlsl_{}", stage);
+        if let Some(bytecode) = self.shader_cache.get(&kind, shader) {
+            let mut blob = ptr::null_mut();
+            let hr = unsafe { d3dcompiler::D3DCreateBlob(bytecode.len(), &mut blob as *mut *mut _) };
+            if winerror::SUCCEEDED(hr) {
+                let blob = unsafe { ComPtr::<d3dcommon::ID3DBlob>::from_raw(blob) };
+                unsafe {
+                    ptr::copy_nonoverlapping(bytecode.as_ptr(), blob.GetBufferPointer() as *mut u8, bytecode.len());
+                }
+                return Ok(blob);
+            }
+        }
+
+        let blob = self.compile_shader(stage, entry, shader)?;
+        let bytecode = unsafe { std::slice::from_raw_parts(blob.GetBufferPointer() as *const u8, blob.GetBufferSize()) };
+        self.shader_cache.set(&kind, shader, bytecode);
+        Ok(blob)
+    }
+
     pub(crate) fn compile_shader(&self, stage: &str, entry: &[u8], shader: &[u8]) -> Result<ComPtr<d3dcommon::ID3DBlob>, String> {
         let mut blob = ptr::null_mut();
         let mut error = ptr::null_mut();
@@ -873,6 +948,45 @@ impl D3d11Cx {
         }
     }
 
+    /// Flags that we have to pass both when creating the swap chain and when resizing it.
+    fn swap_chain_flags(&self) -> u32 {
+        DXGI_SWAP_CHAIN_FLAG_FRAME_LATENCY_WAITABLE_OBJECT
+            | if self.allow_tearing { DXGI_SWAP_CHAIN_FLAG_ALLOW_TEARING } else { 0 }
+    }
+
+    fn check_tearing_support(factory: &ComPtr<dxgi1_2::IDXGIFactory2>) -> bool {
+        let factory5 = if let Ok(factory5) = factory.cast::<dxgi1_5::IDXGIFactory5>() { factory5 } else { return false };
+        let mut allow_tearing: i32 = FALSE;
+        let hr = unsafe {
+            factory5.CheckFeatureSupport(
+                dxgi1_5::DXGI_FEATURE_PRESENT_ALLOW_TEARING,
+                &mut allow_tearing as *mut _ as *mut _,
+                mem::size_of::<i32>() as u32,
+            )
+        };
+        winerror::SUCCEEDED(hr) && allow_tearing != FALSE
+    }
+
+    /// Set up a new swap chain for low latency and sRGB output, and return its frame latency waitable object.
+    ///
+    /// We render in sRGB space (just like with `BGRA8Unorm` on Metal), so we keep `UNORM` views, which flip model swap
+    /// chains require anyway, and tell DWM that the buffers contain sRGB, so it doesn't reinterpret them (e.g. when
+    /// HDR is turned on).
+    fn configure_swap_chain(&self, swap_chain: &ComPtr<dxgi1_2::IDXGISwapChain1>) -> HANDLE {
+        if let Ok(swap_chain3) = swap_chain.cast::<dxgi1_4::IDXGISwapChain3>() {
+            unsafe { swap_chain3.SetColorSpace1(dxgitype::DXGI_COLOR_SPACE_RGB_FULL_G22_NONE_P709) };
+        }
+        if let Ok(swap_chain2) = swap_chain.cast::<dxgi1_3::IDXGISwapChain2>() {
+            unsafe {
+                // The default is 3 frames, which is where the extra latency came from.
+                swap_chain2.SetMaximumFrameLatency(1);
+                swap_chain2.GetFrameLatencyWaitableObject()
+            }
+        } else {
+            ptr::null_mut()
+        }
+    }
+
     pub(crate) fn create_swap_chain_for_hwnd(
         &self,
         wg: &WindowGeom,
@@ -885,7 +999,7 @@ impl D3d11Cx {
             Width: (wg.inner_size.x * wg.dpi_factor) as u32,
             Height: (wg.inner_size.y * wg.dpi_factor) as u32,
             Format: dxgiformat::DXGI_FORMAT_B8G8R8A8_UNORM,
-            Flags: 0,
+            Flags: self.swap_chain_flags(),
             BufferUsage: dxgitype::DXGI_USAGE_RENDER_TARGET_OUTPUT,
             SampleDesc: dxgitype::DXGI_SAMPLE_DESC { Count: 1, Quality: 0 },
             Scaling: dxgi1_2::DXGI_SCALING_NONE,
@@ -917,7 +1031,7 @@ impl D3d11Cx {
                 (wg.inner_size.x * wg.dpi_factor) as u32,
                 (wg.inner_size.y * wg.dpi_factor) as u32,
                 dxgiformat::DXGI_FORMAT_B8G8R8A8_UNORM,
-                0,
+                self.swap_chain_flags(),
             );
             if !winerror::SUCCEEDED(hr) {
                 panic!("Could not resize swapchain");
@@ -1122,6 +1236,53 @@ impl D3d11Cx {
             panic!("update_platform_texture_image_rgba failed");
         }
     }
+
+    /// Create a cube texture from 6 faces of `size` by `size` pixels, stored one after another in the order of
+    /// `D3D11_TEXTURECUBE_FACE_POSITIVE_X..D3D11_TEXTURECUBE_FACE_NEGATIVE_Z`.
+    pub(crate) fn update_platform_texture_cube_rgba(&self, res: &mut CxPlatformTexture, size: usize, image_u32: &[u32]) {
+        if image_u32.len() != size * size * 6 {
+            println!("update_platform_texture_cube_rgba with wrong buffer_u32 size!");
+            return;
+        }
+
+        let sub_data: Vec<d3d11::D3D11_SUBRESOURCE_DATA> = image_u32
+            .chunks_exact(size * size)
+            .map(|face| d3d11::D3D11_SUBRESOURCE_DATA {
+                pSysMem: face.as_ptr() as *const _,
+                SysMemPitch: (size * 4) as u32,
+                SysMemSlicePitch: 0,
+            })
+            .collect();
+
+        let texture_desc = d3d11::D3D11_TEXTURE2D_DESC {
+            Width: size as u32,
+            Height: size as u32,
+            MipLevels: 1,
+            ArraySize: 6,
+            Format: dxgiformat::DXGI_FORMAT_R8G8B8A8_UNORM,
+            SampleDesc: dxgitype::DXGI_SAMPLE_DESC { Count: 1, Quality: 0 },
+            Usage: d3d11::D3D11_USAGE_DEFAULT,
+            BindFlags: d3d11::D3D11_BIND_SHADER_RESOURCE,
+            CPUAccessFlags: 0,
+            MiscFlags: d3d11::D3D11_RESOURCE_MISC_TEXTURECUBE,
+        };
+        let mut texture = ptr::null_mut();
+        let hr = unsafe { self.device.CreateTexture2D(&texture_desc, sub_data.as_ptr(), &mut texture as *mut *mut _) };
+        if winerror::SUCCEEDED(hr) {
+            let mut srv_desc: d3d11::D3D11_SHADER_RESOURCE_VIEW_DESC = unsafe { mem::zeroed() };
+            srv_desc.Format = dxgiformat::DXGI_FORMAT_R8G8B8A8_UNORM;
+            srv_desc.ViewDimension = d3dcommon::D3D11_SRV_DIMENSION_TEXTURECUBE;
+            *unsafe { srv_desc.u.TextureCube_mut() } = d3d11::D3D11_TEXCUBE_SRV { MostDetailedMip: 0, MipLevels: 1 };
+            let mut shader_resource = ptr::null_mut();
+            unsafe { self.device.CreateShaderResourceView(texture as *mut _, &srv_desc, &mut shader_resource as *mut *mut _) };
+            res.width = size;
+            res.height = size;
+            res.texture = Some(unsafe { ComPtr::from_raw(texture as *mut _) });
+            res.shader_resource = Some(unsafe { ComPtr::from_raw(shader_resource as *mut _) });
+        } else {
+            panic!("update_platform_texture_cube_rgba failed");
+        }
+    }
 }
 
 #[derive(Clone, Default)]
@@ -0,0 +1,1047 @@
+//! iOS platform-specific entry point, using UIKit.
+//!
+//! `UIApplicationMain` never returns, so we call it from within [`IosApp::event_loop`], which keeps the event handler
+//! alive for as long as the app runs. UIKit then calls into us from its own run loop: for touches, keyboard input,
+//! lifecycle changes, and a `CADisplayLink` that drives painting, similar to `requestAnimationFrame` in browsers. We
+//! pause the display link when nothing needs to be drawn.
+//!
+//! We draw using the same Metal renderer as Mac OS X, into the `CAMetalLayer` that backs our root view.
+
+use crate::cx_apple::*;
+use crate::*;
+use std::collections::{BTreeSet, HashMap};
+use std::os::raw::{c_char, c_int};
+use std::ptr;
+use std::time::{Duration, Instant};
+
+static mut GLOBAL_IOS_APP: *mut IosApp = 0 as *mut _;
+
+#[link(name = "UIKit", kind = "framework")]
+extern "C" {
+    fn UIApplicationMain(argc: c_int, argv: *mut *mut c_char, principal_class_name: id, delegate_class_name: id) -> c_int;
+}
+
+// For `CAMetalLayer` and `CADisplayLink`.
+#[link(name = "QuartzCore", kind = "framework")]
+extern "C" {}
+
+const APP_DELEGATE_CLASS_NAME: &str = "ZapAppDelegate";
+
+const UI_KEY_MODIFIER_SHIFT: u64 = 1 << 17;
+const UI_KEY_MODIFIER_CONTROL: u64 = 1 << 18;
+const UI_KEY_MODIFIER_ALTERNATE: u64 = 1 << 19;
+const UI_KEY_MODIFIER_COMMAND: u64 = 1 << 20;
+const UI_TEXT_AUTOCAPITALIZATION_TYPE_NONE: i64 = 0;
+const UI_TEXT_AUTOCORRECTION_TYPE_NO: i64 = 1;
+
+impl Cx {
+    pub fn event_loop<F>(&mut self, mut event_handler: F)
+    where
+        F: FnMut(&mut Cx, &mut Event),
+    {
+        self.event_handler =
+            Some(&mut event_handler as *const dyn FnMut(&mut Cx, &mut Event) as *mut dyn FnMut(&mut Cx, &mut Event));
+        self.event_loop_core();
+        self.event_handler = None;
+    }
+
+    fn event_loop_core(&mut self) {
+        self.platform_type = PlatformType::IOS;
+
+        let mut metal_cx = MetalCx::new();
+
+        let mut ios_app = IosApp::new(&metal_cx);
+
+        self.load_fonts();
+
+        self.call_event_handler(&mut Event::Construct);
+
+        self.request_draw();
+
+        let mut passes_todo = Vec::new();
+
+        ios_app.event_loop(|ios_app, events| {
+            self.last_event_time = ios_app.time_now();
+
+            for event in events {
+                self.process_pre_event(event);
+
+                match &event {
+                    Event::WindowGeomChange(re) => {
+                        self.windows[re.window_id].window_geom = re.new_geom.clone();
+                        // Rotating the device keeps the scale, but changes the size.
+                        if re.old_geom.inner_size != re.new_geom.inner_size || re.old_geom.dpi_factor != re.new_geom.dpi_factor {
+                            self.request_draw();
+                        }
+                        self.call_event_handler(event);
+                    }
+                    Event::WindowClosed(wc) => {
+                        // iOS apps can't quit themselves, so we just stop drawing.
+                        self.windows[wc.window_id].window_state = CxWindowState::Closed;
+                        self.windows_free.push(wc.window_id);
+                        self.call_event_handler(event);
+                    }
+                    Event::System(e) => {
+                        match e {
+                            SystemEvent::Paint => {
+                                let _vsync = self.process_desktop_paint_callbacks();
+
+                                // construct or destruct windows
+                                for (index, window) in self.windows.iter_mut().enumerate() {
+                                    window.window_state = match &window.window_state {
+                                        CxWindowState::Create { .. } => {
+                                            ios_app.create_window(index);
+                                            if let Some(ios_window) = &ios_app.window {
+                                                window.window_geom = ios_window.window_geom.clone();
+                                            }
+                                            CxWindowState::Created
+                                        }
+                                        CxWindowState::Close => {
+                                            ios_app.close_window(index);
+                                            CxWindowState::Closed
+                                        }
+                                        CxWindowState::Created => CxWindowState::Created,
+                                        CxWindowState::Closed => CxWindowState::Closed,
+                                    };
+
+                                    // iOS apps are always fullscreen, and don't have a title or an icon.
+                                    window.window_command = CxWindowCmd::None;
+                                    window.window_set_title = None;
+                                    window.window_set_icon = None;
+                                    window.window_style_changed = false;
+                                }
+
+                                if let Some(visible) = self.platform.set_ime_visible.take() {
+                                    ios_app.set_keyboard_visible(visible);
+                                }
+
+                                if self.tray_needs_update() {
+                                    ios_app.update_tray(self.platform.desktop.tray.as_ref());
+                                }
+
+                                while !self.platform.start_timer.is_empty() {
+                                    let (timer_id, interval, repeats) = self.platform.start_timer.pop().unwrap();
+                                    ios_app.start_timer(timer_id, interval, repeats);
+                                }
+
+                                while !self.platform.stop_timer.is_empty() {
+                                    let timer_id = self.platform.stop_timer.pop().unwrap();
+                                    ios_app.stop_timer(timer_id);
+                                }
+
+                                // build a list of renderpasses to repaint
+                                let mut windows_need_repaint = 0;
+                                self.compute_passes_to_repaint(&mut passes_todo, &mut windows_need_repaint);
+
+                                if !passes_todo.is_empty() {
+                                    self.mtl_compile_shaders(&metal_cx);
+
+                                    for pass_id in &passes_todo {
+                                        match self.passes[*pass_id].dep_of.clone() {
+                                            CxPassDepOf::Window(window_id) => {
+                                                windows_need_repaint -= 1;
+                                                if let Some(ios_window) = &mut ios_app.window {
+                                                    if ios_window.window_id != window_id {
+                                                        continue;
+                                                    }
+                                                    let dpi_factor = ios_window.window_geom.dpi_factor;
+                                                    ios_window.resize_metal_layer();
+                                                    self.draw_pass_to_layer(
+                                                        *pass_id,
+                                                        dpi_factor,
+                                                        ios_window.ca_layer,
+                                                        &mut metal_cx,
+                                                        false,
+                                                    );
+                                                }
+                                            }
+                                            CxPassDepOf::Pass(parent_pass_id) => {
+                                                let dpi_factor = self.get_delegated_dpi_factor(parent_pass_id);
+                                                self.draw_pass_to_texture(*pass_id, dpi_factor, &metal_cx);
+                                            }
+                                            CxPassDepOf::None => {
+                                                self.draw_pass_to_texture(*pass_id, 1.0, &metal_cx);
+                                            }
+                                        }
+                                    }
+                                }
+                            }
+                            _ => {
+                                self.call_event_handler(event);
+                            }
+                        }
+                    }
+                    Event::None => {}
+                    Event::Signal { .. } => {
+                        self.call_event_handler(event);
+                        self.call_signals();
+                    }
+                    _ => {
+                        self.call_event_handler(event);
+                    }
+                }
+                self.process_post_event(event);
+            }
+
+            !(self.requested_draw || self.requested_next_frame)
+        })
+    }
+
+    /// TODO(JP): Generalize [`Cx::post_signal`] into this.
+    #[cfg(feature = "cef")]
+    pub(crate) fn send_event_from_any_thread(_event: Event) {
+        todo!();
+    }
+
+    #[cfg(feature = "cef")]
+    pub(crate) fn cef_schedule_message_pump_work(_delay_ms: i64) {
+        todo!();
+    }
+}
+
+impl CxPlatformCommon for Cx {
+    /// See [`CxPlatformCommon::show_text_ime`] for documentation.
+    fn show_text_ime(&mut self, _x: f32, _y: f32) {
+        self.platform.set_ime_visible = Some(true);
+    }
+
+    /// See [`CxPlatformCommon::hide_text_ime`] for documentation.
+    fn hide_text_ime(&mut self) {
+        self.platform.set_ime_visible = Some(false);
+    }
+
+    /// See [`CxPlatformCommon::start_timer`] for documentation.
+    fn start_timer(&mut self, interval: f64, repeats: bool) -> Timer {
+        let timer_id = self.new_timer_id(repeats);
+        self.platform.start_timer.push((timer_id, interval, repeats));
+        Timer { timer_id }
+    }
+
+    /// See [`CxPlatformCommon::stop_timer`] for documentation.
+    fn stop_timer(&mut self, timer: &mut Timer) {
+        if let Some(timer_id) = self.take_active_timer_id(timer) {
+            self.platform.stop_timer.push(timer_id);
+        }
+    }
+
+    /// See [`CxPlatformCommon::post_signal`] for documentation.
+    fn post_signal(signal: Signal, status: StatusId) {
+        if signal.signal_id != 0 {
+            let mut signals = HashMap::new();
+            let mut new_set = BTreeSet::new();
+            new_set.insert(status);
+            signals.insert(signal, new_set);
+            IosApp::send_event_from_any_thread(Event::Signal(SignalEvent { signals }));
+        }
+    }
+
+    /// See [`CxPlatformCommon::update_menu`] for documentation.
+    fn update_menu(&mut self, _menu: &Menu) {}
+
+    /// See [`CxPlatformCommon::update_tray`] for documentation.
+    fn update_tray(&mut self, tray: Option<&Tray>) {
+        self.platform.desktop.update_tray(tray);
+    }
+
+    /// See [`CxPlatformCommon::update_menu`] for documentation.
+    fn copy_text_to_clipboard(&mut self, text: &str) {
+        IosApp::copy_text_to_clipboard(text);
+    }
+
+    /// See [`CxPlatformCommon::send_event_from_any_thread`] for documentation.
+    fn send_event_from_any_thread(event: Event) {
+        IosApp::send_event_from_any_thread(event);
+    }
+}
+
+#[derive(Clone, Default)]
+pub(crate) struct CxPlatform {
+    pub(crate) bytes_written: usize,
+    pub(crate) draw_calls_done: usize,
+    /// Whether to show or hide the on-screen keyboard on the next paint.
+    pub(crate) set_ime_visible: Option<bool>,
+    pub(crate) start_timer: Vec<(u64, f64, bool)>,
+    pub(crate) stop_timer: Vec<u64>,
+    pub(crate) desktop: CxDesktop,
+}
+
+pub(crate) struct IosApp {
+    view_class: *const Class,
+    view_controller_class: *const Class,
+    timer_delegate_instance: id,
+    metal_device: id,
+    /// The root view, whose layer we draw into. `nil` until UIKit has finished launching the app.
+    view: id,
+    display_link: id,
+    /// The [`CxWindow`] that we draw into the root view. iOS apps only have a single window.
+    pub(crate) window: Option<IosWindow>,
+    /// The main screen, once UIKit has finished launching the app.
+    displays: Vec<DisplayInfo>,
+
+    pub(crate) time_start: Instant,
+    pub(crate) event_callback: Option<*mut dyn FnMut(&mut IosApp, &mut Vec<Event>) -> bool>,
+    pub(crate) event_recur_block: bool,
+    pub(crate) loop_block: bool,
+    timers: Vec<IosTimer>,
+    /// Events that we can't send right away because we're already in a callback, e.g. when closing a window.
+    pending_events: Vec<Event>,
+
+    /// The `UITouch` of every touch that's down, indexed by digit, or `nil`.
+    touch_digits: Vec<id>,
+    /// Keys that are held down on a hardware keyboard, so we don't send duplicate events for `UIKeyInput` callbacks.
+    keys_down: Vec<KeyCode>,
+    keyboard_visible: bool,
+}
+
+pub(crate) struct IosWindow {
+    pub(crate) window_id: usize,
+    pub(crate) window_geom: WindowGeom,
+    pub(crate) ca_layer: id,
+    drawable_size: Vec2,
+}
+
+struct IosTimer {
+    timer_id: u64,
+    nstimer: id,
+    repeats: bool,
+}
+
+impl IosApp {
+    pub(crate) fn new(metal_cx: &MetalCx) -> Box<IosApp> {
+        let timer_delegate_class = define_ios_timer_delegate();
+        define_ios_app_delegate();
+        let mut ios_app = Box::new(IosApp {
+            view_class: define_ios_view_class(),
+            view_controller_class: define_ios_view_controller_class(),
+            timer_delegate_instance: unsafe { msg_send![timer_delegate_class, new] },
+            metal_device: metal_cx.device,
+            view: nil,
+            display_link: nil,
+            window: None,
+            displays: Vec::new(),
+            time_start: Instant::now(),
+            event_callback: None,
+            event_recur_block: false,
+            loop_block: false,
+            timers: Vec::new(),
+            pending_events: Vec::new(),
+            touch_digits: vec![nil; NUM_POINTERS],
+            keys_down: Vec::new(),
+            keyboard_visible: false,
+        });
+        unsafe {
+            GLOBAL_IOS_APP = &mut *ios_app as *mut IosApp;
+        }
+        ios_app
+    }
+
+    pub(crate) fn event_loop<F>(&mut self, mut event_handler: F)
+    where
+        F: FnMut(&mut IosApp, &mut Vec<Event>) -> bool,
+    {
+        unsafe {
+            self.event_callback = Some(
+                &mut event_handler as *const dyn FnMut(&mut IosApp, &mut Vec<Event>) -> bool
+                    as *mut dyn FnMut(&mut IosApp, &mut Vec<Event>) -> bool,
+            );
+
+            // This never returns; UIKit calls `application:didFinishLaunchingWithOptions:` once it's ready, and
+            // everything else from its run loop.
+            UIApplicationMain(0, ptr::null_mut(), nil, str_to_nsstring(APP_DELEGATE_CLASS_NAME));
+
+            self.event_callback = None;
+        }
+    }
+
+    pub(crate) fn do_callback(&mut self, events: &mut Vec<Event>) {
+        unsafe {
+            if self.event_callback.is_none() || self.event_recur_block {
+                return;
+            };
+            self.event_recur_block = true;
+            let callback = self.event_callback.unwrap();
+            self.loop_block = (*callback)(self, events);
+            self.event_recur_block = false;
+        }
+        let mut pending_events = std::mem::take(&mut self.pending_events);
+        if !pending_events.is_empty() {
+            self.do_callback(&mut pending_events);
+        }
+        // Only paint on the next display refresh if something needs to be drawn.
+        if self.display_link != nil {
+            let () = unsafe { msg_send![self.display_link, setPaused: self.loop_block] };
+        }
+    }
+
+    pub(crate) fn time_now(&self) -> f64 {
+        let time_now = Instant::now();
+        (time_now.duration_since(self.time_start)).as_micros() as f64 / 1_000_000.0
+    }
+
+    /// Called by UIKit once the app has launched, to set up the window and root view.
+    fn did_finish_launching(&mut self) {
+        unsafe {
+            let screen: id = msg_send![class!(UIScreen), mainScreen];
+            let bounds: NSRect = msg_send![screen, bounds];
+            let native_scale: f64 = msg_send![screen, nativeScale];
+
+            let view: id = msg_send![self.view_class, alloc];
+            let view: id = msg_send![view, initWithFrame: bounds];
+            let () = msg_send![view, setMultipleTouchEnabled: YES];
+            let () = msg_send![view, setContentScaleFactor: native_scale];
+
+            let ca_layer: id = msg_send![view, layer];
+            let () = msg_send![ca_layer, setDevice: self.metal_device];
+            let () = msg_send![ca_layer, setPixelFormat: MTLPixelFormat::BGRA8Unorm];
+            let () = msg_send![ca_layer, setPresentsWithTransaction: NO];
+            let () = msg_send![ca_layer, setMaximumDrawableCount: 3u64];
+            let () = msg_send![ca_layer, setOpaque: YES];
+
+            let view_controller: id = msg_send![self.view_controller_class, new];
+            let () = msg_send![view_controller, setView: view];
+
+            let ui_window: id = msg_send![class!(UIWindow), alloc];
+            let ui_window: id = msg_send![ui_window, initWithFrame: bounds];
+            let () = msg_send![ui_window, setRootViewController: view_controller];
+            let () = msg_send![ui_window, makeKeyAndVisible];
+            self.view = view;
+
+            self.display_link = msg_send![
+                class!(CADisplayLink),
+                displayLinkWithTarget: self.timer_delegate_instance
+                selector: sel!(receivedDisplayLink:)
+            ];
+            let run_loop: id = msg_send![class!(NSRunLoop), mainRunLoop];
+            let () = msg_send![self.display_link, addToRunLoop: run_loop forMode: NSRunLoopCommonModes];
+        }
+
+        // Paint right away, so that the first frame is ready when the launch screen goes away.
+        self.do_callback(&mut vec![Event::System(SystemEvent::Paint)]);
+    }
+
+    pub(crate) fn start_timer(&mut self, timer_id: u64, interval: f64, repeats: bool) {
+        let nstimer =
+            make_timer(self.timer_delegate_instance, sel!(receivedTimer:), Duration::from_secs_f64(interval), repeats, nil);
+        self.timers.push(IosTimer { timer_id, nstimer, repeats });
+    }
+
+    pub(crate) fn stop_timer(&mut self, timer_id: u64) {
+        if let Some(index) = self.timers.iter().position(|timer| timer.timer_id == timer_id) {
+            let () = unsafe { msg_send![self.timers[index].nstimer, invalidate] };
+            self.timers.remove(index);
+        }
+    }
+
+    fn send_timer_received(&mut self, nstimer: id) {
+        if let Some(index) = self.timers.iter().position(|timer| timer.nstimer == nstimer) {
+            let timer_id = self.timers[index].timer_id;
+            if !self.timers[index].repeats {
+                self.timers.remove(index);
+            }
+            self.do_callback(&mut vec![Event::Timer(TimerEvent { timer_id })]);
+        }
+    }
+
+    pub(crate) fn send_event_from_any_thread(event: Event) {
+        unsafe {
+            // No event loop (e.g. in tests), so there is no one to send the event to.
+            if GLOBAL_IOS_APP.is_null() {
+                return;
+            }
+            let pool: id = msg_send![class!(NSAutoreleasePool), new];
+            let event_ptr = Box::into_raw(Box::new(event));
+            let event_value: id = msg_send![class!(NSValue), valueWithPointer: event_ptr];
+            let () = msg_send![
+                (*GLOBAL_IOS_APP).timer_delegate_instance,
+                performSelectorOnMainThread: sel!(receivedEvent:)
+                withObject: event_value
+                waitUntilDone: NO
+            ];
+            let () = msg_send![pool, release];
+        }
+    }
+
+    pub(crate) fn copy_text_to_clipboard(text: &str) {
+        unsafe {
+            let pasteboard: id = msg_send![class!(UIPasteboard), generalPasteboard];
+            let () = msg_send![pasteboard, setString: str_to_nsstring(text)];
+        }
+    }
+
+    fn paste_from_clipboard(&mut self) {
+        let string = unsafe {
+            let pasteboard: id = msg_send![class!(UIPasteboard), generalPasteboard];
+            let ns_string: id = msg_send![pasteboard, string];
+            if ns_string == nil {
+                return;
+            }
+            nsstring_to_string(ns_string)
+        };
+        self.do_callback(&mut vec![Event::TextInput(TextInputEvent { input: string, was_paste: true, replace_last: false })]);
+    }
+
+    fn get_window_geom(&self) -> WindowGeom {
+        let (inner_size, dpi_factor) = unsafe {
+            let bounds: NSRect = msg_send![self.view, bounds];
+            let scale: f64 = msg_send![self.view, contentScaleFactor];
+            (Vec2 { x: bounds.size.width as f32, y: bounds.size.height as f32 }, scale as f32)
+        };
+        WindowGeom {
+            xr_is_presenting: false,
+            xr_can_present: false,
+            can_fullscreen: false,
+            is_topmost: false,
+            is_fullscreen: true,
+            is_maximized: true,
+            inner_size,
+            outer_size: inner_size,
+            dpi_factor,
+            position: Vec2::default(),
+        }
+    }
+
+    /// Called when the root view gets laid out, e.g. after rotating the device.
+    fn send_change_event(&mut self) {
+        let new_geom = self.get_window_geom();
+        let (window_id, old_geom) = if let Some(window) = &mut self.window {
+            (window.window_id, std::mem::replace(&mut window.window_geom, new_geom.clone()))
+        } else {
+            return;
+        };
+        if old_geom != new_geom {
+            self.do_callback(&mut vec![Event::WindowGeomChange(WindowGeomChangeEvent { window_id, old_geom, new_geom })]);
+        }
+
+        // The bounds of the screen follow the orientation of the device.
+        let displays = unsafe {
+            let screen: id = msg_send![class!(UIScreen), mainScreen];
+            let bounds: NSRect = msg_send![screen, bounds];
+            let native_scale: f64 = msg_send![screen, nativeScale];
+            let refresh_rate: i64 = msg_send![screen, maximumFramesPerSecond];
+            let size = Vec2 { x: bounds.size.width as f32, y: bounds.size.height as f32 };
+            vec![DisplayInfo {
+                name: "Built-in".to_string(),
+                size,
+                work_area: Rect { pos: Vec2::default(), size },
+                dpi_factor: native_scale as f32,
+                refresh_rate: refresh_rate as f32,
+                is_primary: true,
+                ..DisplayInfo::default()
+            }]
+        };
+        if displays != self.displays {
+            self.displays = displays.clone();
+            self.do_callback(&mut vec![Event::DisplaysChanged(DisplaysChangedEvent { displays })]);
+        }
+    }
+
+    fn send_touches(&mut self, phase: TouchPhase, touches: id) {
+        let window_id = if let Some(window) = &self.window { window.window_id } else { return };
+        let time = self.time_now();
+        let mut events = Vec::new();
+        unsafe {
+            let touches: id = msg_send![touches, allObjects];
+            let count: u64 = msg_send![touches, count];
+            for index in 0..count {
+                let touch: id = msg_send![touches, objectAtIndex: index];
+                let point: NSPoint = msg_send![touch, locationInView: self.view];
+                let pos = Vec2 { x: point.x as f32, y: point.y as f32 };
+                match phase {
+                    TouchPhase::Began => {
+                        let digit = if let Some(digit) = self.touch_digits.iter().position(|t| *t == nil) {
+                            digit
+                        } else {
+                            // More touches than we can track.
+                            continue;
+                        };
+                        self.touch_digits[digit] = touch;
+                        let tap_count: u64 = msg_send![touch, tapCount];
+                        events.push(Event::PointerDown(PointerDownEvent {
+                            window_id,
+                            abs: pos,
+                            rel: pos,
+                            rect: Rect::default(),
+                            digit,
+                            button: MouseButton::Left,
+                            handled: false,
+                            input_type: PointerInputType::Touch,
+                            modifiers: KeyModifiers::default(),
+                            tap_count: tap_count as u32,
+                            time,
+                        }));
+                    }
+                    TouchPhase::Moved => {
+                        if let Some(digit) = self.touch_digits.iter().position(|t| *t == touch) {
+                            events.push(Event::PointerMove(PointerMoveEvent {
+                                window_id,
+                                abs: pos,
+                                rel: pos,
+                                rect: Rect::default(),
+                                digit,
+                                abs_start: Vec2::default(),
+                                rel_start: Vec2::default(),
+                                is_over: false,
+                                input_type: PointerInputType::Touch,
+                                modifiers: KeyModifiers::default(),
+                                time,
+                            }));
+                        }
+                    }
+                    TouchPhase::Ended => {
+                        if let Some(digit) = self.touch_digits.iter().position(|t| *t == touch) {
+                            self.touch_digits[digit] = nil;
+                            events.push(Event::PointerUp(PointerUpEvent {
+                                window_id,
+                                abs: pos,
+                                rel: pos,
+                                rect: Rect::default(),
+                                abs_start: Vec2::default(),
+                                rel_start: Vec2::default(),
+                                digit,
+                                button: MouseButton::Left,
+                                is_over: false,
+                                input_type: PointerInputType::Touch,
+                                modifiers: KeyModifiers::default(),
+                                time,
+                            }));
+                        }
+                    }
+                }
+            }
+        }
+        if !events.is_empty() {
+            self.do_callback(&mut events);
+        }
+    }
+
+    /// Key presses from a hardware keyboard. Text still arrives through `UIKeyInput`.
+    fn send_presses(&mut self, presses: id, is_down: bool) {
+        let time = self.time_now();
+        let mut events = Vec::new();
+        unsafe {
+            let presses: id = msg_send![presses, allObjects];
+            let count: u64 = msg_send![presses, count];
+            for index in 0..count {
+                let press: id = msg_send![presses, objectAtIndex: index];
+                // `UIPress.key` is only set for keyboard presses, and needs iOS 13.4.
+                let responds: BOOL = msg_send![press, respondsToSelector: sel!(key)];
+                if responds == NO {
+                    continue;
+                }
+                let key: id = msg_send![press, key];
+                if key == nil {
+                    continue;
+                }
+                let hid_usage: i64 = msg_send![key, keyCode];
+                let modifier_flags: u64 = msg_send![key, modifierFlags];
+                let key_code = hid_usage_to_keycode(hid_usage);
+                let modifiers = KeyModifiers {
+                    shift: modifier_flags & UI_KEY_MODIFIER_SHIFT != 0,
+                    control: modifier_flags & UI_KEY_MODIFIER_CONTROL != 0,
+                    alt: modifier_flags & UI_KEY_MODIFIER_ALTERNATE != 0,
+                    logo: modifier_flags & UI_KEY_MODIFIER_COMMAND != 0,
+                };
+                let is_repeat = is_down && self.keys_down.contains(&key_code);
+                if is_down {
+                    if !is_repeat {
+                        self.keys_down.push(key_code);
+                    }
+                    if modifiers.logo || modifiers.control {
+                        match key_code {
+                            KeyCode::KeyV => self.paste_from_clipboard(),
+                            KeyCode::KeyX | KeyCode::KeyC => self.do_callback(&mut vec![Event::TextCopy]),
+                            _ => {}
+                        }
+                    }
+                    events.push(Event::KeyDown(KeyEvent { key_code, is_repeat, modifiers, time }));
+                } else {
+                    self.keys_down.retain(|k| *k != key_code);
+                    events.push(Event::KeyUp(KeyEvent { key_code, is_repeat: false, modifiers, time }));
+                }
+            }
+        }
+        if !events.is_empty() {
+            self.do_callback(&mut events);
+        }
+    }
+
+    /// The on-screen keyboard doesn't send key events, so we send them for keys that text fields care about.
+    fn send_virtual_key(&mut self, key_code: KeyCode) {
+        // Already sent by `IosApp::send_presses`.
+        if self.keys_down.contains(&key_code) {
+            return;
+        }
+        let time = self.time_now();
+        let key_event = KeyEvent { key_code, is_repeat: false, modifiers: KeyModifiers::default(), time };
+        self.do_callback(&mut vec![Event::KeyDown(key_event.clone()), Event::KeyUp(key_event)]);
+    }
+
+    fn send_insert_text(&mut self, text: String) {
+        if text == "\n" {
+            self.send_virtual_key(KeyCode::Return);
+        } else {
+            self.do_callback(&mut vec![Event::TextInput(TextInputEvent { input: text, was_paste: false, replace_last: false })]);
+        }
+    }
+
+    pub(crate) fn set_keyboard_visible(&mut self, visible: bool) {
+        if self.keyboard_visible == visible || self.view == nil {
+            return;
+        }
+        self.keyboard_visible = visible;
+        unsafe {
+            if visible {
+                let _: BOOL = msg_send![self.view, becomeFirstResponder];
+            } else {
+                let _: BOOL = msg_send![self.view, resignFirstResponder];
+            }
+        }
+    }
+
+    pub(crate) fn update_tray(&mut self, tray: Option<&Tray>) {
+        if tray.is_some() {
+            log!("Tray icons are not supported on iOS");
+        }
+    }
+
+    /// UIKit gives us a single window, so we only support one window.
+    pub(crate) fn create_window(&mut self, window_id: usize) {
+        if let Some(window) = &self.window {
+            log!("iOS only supports a single window; not creating window {} next to window {}", window_id, window.window_id);
+            return;
+        }
+        let ca_layer: id = unsafe { msg_send![self.view, layer] };
+        let window_geom = self.get_window_geom();
+        self.window = Some(IosWindow { window_id, window_geom, ca_layer, drawable_size: Vec2::default() });
+    }
+
+    pub(crate) fn close_window(&mut self, window_id: usize) {
+        if !matches!(&self.window, Some(window) if window.window_id == window_id) {
+            return;
+        }
+        self.window = None;
+        // We're in the middle of a callback, so send this later.
+        self.pending_events.push(Event::WindowClosed(WindowClosedEvent { window_id }));
+    }
+}
+
+impl IosWindow {
+    /// Match the size of the drawable to the view, like `MetalWindow::resize_core_animation_layer` on Mac OS X.
+    pub(crate) fn resize_metal_layer(&mut self) {
+        let drawable_size = Vec2 {
+            x: self.window_geom.inner_size.x * self.window_geom.dpi_factor,
+            y: self.window_geom.inner_size.y * self.window_geom.dpi_factor,
+        };
+        if self.drawable_size != drawable_size {
+            self.drawable_size = drawable_size;
+            unsafe {
+                let () = msg_send![
+                    self.ca_layer,
+                    setDrawableSize: CGSize { width: drawable_size.x as f64, height: drawable_size.y as f64 }
+                ];
+                let () = msg_send![self.ca_layer, setContentsScale: self.window_geom.dpi_factor as f64];
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum TouchPhase {
+    Began,
+    Moved,
+    /// Also used for cancelled touches, e.g. when a system gesture takes over.
+    Ended,
+}
+
+fn get_ios_app() -> &'static mut IosApp {
+    unsafe { &mut *GLOBAL_IOS_APP }
+}
+
+fn make_timer(target: id, selector: Sel, time_to_wait: Duration, repeats: bool, user_info: id) -> id {
+    unsafe {
+        let timer: id = msg_send![
+            class!(NSTimer),
+            timerWithTimeInterval: time_to_wait.as_secs_f64()
+            target: target
+            selector: selector
+            userInfo: user_info
+            repeats: repeats
+        ];
+        // Use the common modes so that timers keep firing while UIKit is tracking touches.
+        let ns_run_loop: id = msg_send![class!(NSRunLoop), mainRunLoop];
+        let () = msg_send![ns_run_loop, addTimer: timer forMode: NSRunLoopCommonModes];
+        timer
+    }
+}
+
+fn define_ios_timer_delegate() -> *const Class {
+    extern "C" fn received_timer(_this: &Object, _: Sel, nstimer: id) {
+        get_ios_app().send_timer_received(nstimer);
+    }
+
+    extern "C" fn received_event(_this: &Object, _: Sel, event_value: id) {
+        let event = unsafe {
+            let event_ptr: *mut Event = msg_send![event_value, pointerValue];
+            *Box::from_raw(event_ptr)
+        };
+        get_ios_app().do_callback(&mut vec![event]);
+    }
+
+    extern "C" fn received_display_link(_this: &Object, _: Sel, _display_link: id) {
+        let ios_app = get_ios_app();
+        if !ios_app.loop_block {
+            ios_app.do_callback(&mut vec![Event::System(SystemEvent::Paint)]);
+        }
+    }
+
+    let mut decl = ClassDecl::new("ZapTimerDelegate", class!(NSObject)).unwrap();
+    unsafe {
+        decl.add_method(sel!(receivedTimer:), received_timer as extern "C" fn(&Object, Sel, id));
+        decl.add_method(sel!(receivedEvent:), received_event as extern "C" fn(&Object, Sel, id));
+        decl.add_method(sel!(receivedDisplayLink:), received_display_link as extern "C" fn(&Object, Sel, id));
+    }
+    decl.register()
+}
+
+/// UIKit creates the app delegate itself, from the class name that we pass to `UIApplicationMain`.
+fn define_ios_app_delegate() -> *const Class {
+    extern "C" fn did_finish_launching(_this: &Object, _: Sel, _application: id, _options: id) -> BOOL {
+        get_ios_app().did_finish_launching();
+        YES
+    }
+
+    extern "C" fn did_become_active(_this: &Object, _: Sel, _application: id) {
+        get_ios_app().do_callback(&mut vec![Event::AppFocus]);
+    }
+
+    extern "C" fn will_resign_active(_this: &Object, _: Sel, _application: id) {
+        get_ios_app().do_callback(&mut vec![Event::AppFocusLost]);
+    }
+
+    let mut decl = ClassDecl::new(APP_DELEGATE_CLASS_NAME, class!(UIResponder)).unwrap();
+    unsafe {
+        decl.add_method(
+            sel!(application:didFinishLaunchingWithOptions:),
+            did_finish_launching as extern "C" fn(&Object, Sel, id, id) -> BOOL,
+        );
+        decl.add_method(sel!(applicationDidBecomeActive:), did_become_active as extern "C" fn(&Object, Sel, id));
+        decl.add_method(sel!(applicationWillResignActive:), will_resign_active as extern "C" fn(&Object, Sel, id));
+    }
+    if let Some(protocol) = Protocol::get("UIApplicationDelegate") {
+        decl.add_protocol(protocol);
+    }
+    decl.register()
+}
+
+fn define_ios_view_controller_class() -> *const Class {
+    extern "C" fn yes(_this: &Object, _: Sel) -> BOOL {
+        YES
+    }
+
+    let mut decl = ClassDecl::new("ZapViewController", class!(UIViewController)).unwrap();
+    unsafe {
+        // Use the whole screen, like we do on Android.
+        decl.add_method(sel!(prefersStatusBarHidden), yes as extern "C" fn(&Object, Sel) -> BOOL);
+        decl.add_method(sel!(prefersHomeIndicatorAutoHidden), yes as extern "C" fn(&Object, Sel) -> BOOL);
+    }
+    decl.register()
+}
+
+fn define_ios_view_class() -> *const Class {
+    extern "C" fn layer_class(_this: &Class, _: Sel) -> *const Class {
+        class!(CAMetalLayer)
+    }
+
+    extern "C" fn layout_subviews(this: &Object, _: Sel) {
+        unsafe {
+            let superclass = superclass(this);
+            let () = msg_send![super(this, superclass), layoutSubviews];
+        }
+        get_ios_app().send_change_event();
+    }
+
+    extern "C" fn touches_began(_this: &Object, _: Sel, touches: id, _event: id) {
+        get_ios_app().send_touches(TouchPhase::Began, touches);
+    }
+
+    extern "C" fn touches_moved(_this: &Object, _: Sel, touches: id, _event: id) {
+        get_ios_app().send_touches(TouchPhase::Moved, touches);
+    }
+
+    extern "C" fn touches_ended(_this: &Object, _: Sel, touches: id, _event: id) {
+        get_ios_app().send_touches(TouchPhase::Ended, touches);
+    }
+
+    extern "C" fn presses_began(this: &Object, _: Sel, presses: id, event: id) {
+        get_ios_app().send_presses(presses, true);
+        // Pass the presses on, so that we still get text through `UIKeyInput`.
+        unsafe {
+            let superclass = superclass(this);
+            let () = msg_send![super(this, superclass), pressesBegan: presses withEvent: event];
+        }
+    }
+
+    extern "C" fn presses_ended(this: &Object, _: Sel, presses: id, event: id) {
+        get_ios_app().send_presses(presses, false);
+        unsafe {
+            let superclass = superclass(this);
+            let () = msg_send![super(this, superclass), pressesEnded: presses withEvent: event];
+        }
+    }
+
+    extern "C" fn yes(_this: &Object, _: Sel) -> BOOL {
+        YES
+    }
+
+    extern "C" fn insert_text(_this: &Object, _: Sel, text: id) {
+        get_ios_app().send_insert_text(nsstring_to_string(text));
+    }
+
+    extern "C" fn delete_backward(_this: &Object, _: Sel) {
+        get_ios_app().send_virtual_key(KeyCode::Backspace);
+    }
+
+    extern "C" fn autocapitalization_type(_this: &Object, _: Sel) -> i64 {
+        UI_TEXT_AUTOCAPITALIZATION_TYPE_NONE
+    }
+
+    extern "C" fn autocorrection_type(_this: &Object, _: Sel) -> i64 {
+        UI_TEXT_AUTOCORRECTION_TYPE_NO
+    }
+
+    let mut decl = ClassDecl::new("ZapView", class!(UIView)).unwrap();
+    unsafe {
+        decl.add_class_method(sel!(layerClass), layer_class as extern "C" fn(&Class, Sel) -> *const Class);
+        decl.add_method(sel!(layoutSubviews), layout_subviews as extern "C" fn(&Object, Sel));
+        decl.add_method(sel!(touchesBegan:withEvent:), touches_began as extern "C" fn(&Object, Sel, id, id));
+        decl.add_method(sel!(touchesMoved:withEvent:), touches_moved as extern "C" fn(&Object, Sel, id, id));
+        decl.add_method(sel!(touchesEnded:withEvent:), touches_ended as extern "C" fn(&Object, Sel, id, id));
+        decl.add_method(sel!(touchesCancelled:withEvent:), touches_ended as extern "C" fn(&Object, Sel, id, id));
+        decl.add_method(sel!(pressesBegan:withEvent:), presses_began as extern "C" fn(&Object, Sel, id, id));
+        decl.add_method(sel!(pressesEnded:withEvent:), presses_ended as extern "C" fn(&Object, Sel, id, id));
+        decl.add_method(sel!(pressesCancelled:withEvent:), presses_ended as extern "C" fn(&Object, Sel, id, id));
+        decl.add_method(sel!(canBecomeFirstResponder), yes as extern "C" fn(&Object, Sel) -> BOOL);
+        // `UIKeyInput`, for the on-screen keyboard.
+        decl.add_method(sel!(hasText), yes as extern "C" fn(&Object, Sel) -> BOOL);
+        decl.add_method(sel!(insertText:), insert_text as extern "C" fn(&Object, Sel, id));
+        decl.add_method(sel!(deleteBackward), delete_backward as extern "C" fn(&Object, Sel));
+        decl.add_method(sel!(autocapitalizationType), autocapitalization_type as extern "C" fn(&Object, Sel) -> i64);
+        decl.add_method(sel!(autocorrectionType), autocorrection_type as extern "C" fn(&Object, Sel) -> i64);
+    }
+    if let Some(protocol) = Protocol::get("UIKeyInput") {
+        decl.add_protocol(protocol);
+    }
+    decl.register()
+}
+
+unsafe fn superclass(this: &Object) -> &Class {
+    let superclass: id = msg_send![this, superclass];
+    &*(superclass as *const _)
+}
+
+/// Map `UIKeyboardHIDUsage` values, which are USB HID usage IDs from the keyboard page.
+fn hid_usage_to_keycode(hid_usage: i64) -> KeyCode {
+    match hid_usage {
+        0x04 => KeyCode::KeyA,
+        0x05 => KeyCode::KeyB,
+        0x06 => KeyCode::KeyC,
+        0x07 => KeyCode::KeyD,
+        0x08 => KeyCode::KeyE,
+        0x09 => KeyCode::KeyF,
+        0x0A => KeyCode::KeyG,
+        0x0B => KeyCode::KeyH,
+        0x0C => KeyCode::KeyI,
+        0x0D => KeyCode::KeyJ,
+        0x0E => KeyCode::KeyK,
+        0x0F => KeyCode::KeyL,
+        0x10 => KeyCode::KeyM,
+        0x11 => KeyCode::KeyN,
+        0x12 => KeyCode::KeyO,
+        0x13 => KeyCode::KeyP,
+        0x14 => KeyCode::KeyQ,
+        0x15 => KeyCode::KeyR,
+        0x16 => KeyCode::KeyS,
+        0x17 => KeyCode::KeyT,
+        0x18 => KeyCode::KeyU,
+        0x19 => KeyCode::KeyV,
+        0x1A => KeyCode::KeyW,
+        0x1B => KeyCode::KeyX,
+        0x1C => KeyCode::KeyY,
+        0x1D => KeyCode::KeyZ,
+        0x1E => KeyCode::Key1,
+        0x1F => KeyCode::Key2,
+        0x20 => KeyCode::Key3,
+        0x21 => KeyCode::Key4,
+        0x22 => KeyCode::Key5,
+        0x23 => KeyCode::Key6,
+        0x24 => KeyCode::Key7,
+        0x25 => KeyCode::Key8,
+        0x26 => KeyCode::Key9,
+        0x27 => KeyCode::Key0,
+        0x28 => KeyCode::Return,
+        0x29 => KeyCode::Escape,
+        0x2A => KeyCode::Backspace,
+        0x2B => KeyCode::Tab,
+        0x2C => KeyCode::Space,
+        0x2D => KeyCode::Minus,
+        0x2E => KeyCode::Equals,
+        0x2F => KeyCode::LBracket,
+        0x30 => KeyCode::RBracket,
+        0x31 => KeyCode::Backslash,
+        0x33 => KeyCode::Semicolon,
+        0x34 => KeyCode::Quote,
+        0x35 => KeyCode::Backtick,
+        0x36 => KeyCode::Comma,
+        0x37 => KeyCode::Period,
+        0x38 => KeyCode::Slash,
+        0x39 => KeyCode::Capslock,
+        0x3A => KeyCode::F1,
+        0x3B => KeyCode::F2,
+        0x3C => KeyCode::F3,
+        0x3D => KeyCode::F4,
+        0x3E => KeyCode::F5,
+        0x3F => KeyCode::F6,
+        0x40 => KeyCode::F7,
+        0x41 => KeyCode::F8,
+        0x42 => KeyCode::F9,
+        0x43 => KeyCode::F10,
+        0x44 => KeyCode::F11,
+        0x45 => KeyCode::F12,
+        0x46 => KeyCode::PrintScreen,
+        0x47 => KeyCode::Scrolllock,
+        0x48 => KeyCode::Pause,
+        0x49 => KeyCode::Insert,
+        0x4A => KeyCode::Home,
+        0x4B => KeyCode::PageUp,
+        0x4C => KeyCode::Delete,
+        0x4D => KeyCode::End,
+        0x4E => KeyCode::PageDown,
+        0x4F => KeyCode::ArrowRight,
+        0x50 => KeyCode::ArrowLeft,
+        0x51 => KeyCode::ArrowDown,
+        0x52 => KeyCode::ArrowUp,
+        0x53 => KeyCode::Numlock,
+        0x54 => KeyCode::NumpadDivide,
+        0x55 => KeyCode::NumpadMultiply,
+        0x56 => KeyCode::NumpadSubtract,
+        0x57 => KeyCode::NumpadAdd,
+        0x58 => KeyCode::NumpadEnter,
+        0x59 => KeyCode::Numpad1,
+        0x5A => KeyCode::Numpad2,
+        0x5B => KeyCode::Numpad3,
+        0x5C => KeyCode::Numpad4,
+        0x5D => KeyCode::Numpad5,
+        0x5E => KeyCode::Numpad6,
+        0x5F => KeyCode::Numpad7,
+        0x60 => KeyCode::Numpad8,
+        0x61 => KeyCode::Numpad9,
+        0x62 => KeyCode::Numpad0,
+        0x63 => KeyCode::NumpadDecimal,
+        0x67 => KeyCode::NumpadEquals,
+        0xE0 | 0xE4 => KeyCode::Control,
+        0xE1 | 0xE5 => KeyCode::Shift,
+        0xE2 | 0xE6 => KeyCode::Alt,
+        0xE3 | 0xE7 => KeyCode::Logo,
+        _ => KeyCode::Unknown,
+    }
+}
@@ -1,5 +1,6 @@
 //! Linux platform-specific entry point.
 
+use crate::cx_wayland::*;
 use crate::cx_xlib::*;
 use crate::*;
 
@@ -17,9 +18,25 @@ impl Cx {
     fn event_loop_core(&mut self) {
         self.platform_type = PlatformType::Linux { custom_window_chrome: LINUX_CUSTOM_WINDOW_CHROME };
 
+        // Prefer Wayland when available, since fractional scaling doesn't work under XWayland. If anything goes wrong we
+        // can still fall back to X11, since Wayland compositors run XWayland for older applications.
+        if WaylandApp::should_try_wayland() {
+            match WaylandApp::new().and_then(|wayland_app| Ok((EglCx::new(&wayland_app)?, wayland_app))) {
+                Ok((egl_cx, mut wayland_app)) => {
+                    self.event_loop_wayland(&mut wayland_app, egl_cx);
+                    return;
+                }
+                Err(err) => log!("Could not use Wayland, falling back to X11: {}", err),
+            }
+        }
+        self.event_loop_xlib();
+    }
+
+    fn event_loop_xlib(&mut self) {
         let mut xlib_app = XlibApp::new();
 
         xlib_app.init();
+        self.displays = xlib_app.displays.clone();
 
         let opengl_cx = OpenglCx::new(xlib_app.display);
 
@@ -111,6 +128,14 @@ impl Cx {
                                         CxWindowState::Closed => CxWindowState::Closed,
                                     };
 
+                                    if let Some(display_id) = window.window_set_display.take() {
+                                        for opengl_window in &mut opengl_windows {
+                                            if opengl_window.window_id == index {
+                                                opengl_window.xlib_window.move_to_display(display_id);
+                                            }
+                                        }
+                                    }
+
                                     window.window_command = match &window.window_command {
                                         CxWindowCmd::Restore => {
                                             for opengl_window in &mut opengl_windows {
@@ -136,6 +161,22 @@ impl Cx {
                                             }
                                             CxWindowCmd::None
                                         }
+                                        CxWindowCmd::FullScreen => {
+                                            for opengl_window in &mut opengl_windows {
+                                                if opengl_window.window_id == index {
+                                                    opengl_window.xlib_window.set_fullscreen(true);
+                                                }
+                                            }
+                                            CxWindowCmd::None
+                                        }
+                                        CxWindowCmd::NormalScreen => {
+                                            for opengl_window in &mut opengl_windows {
+                                                if opengl_window.window_id == index {
+                                                    opengl_window.xlib_window.set_fullscreen(false);
+                                                }
+                                            }
+                                            CxWindowCmd::None
+                                        }
                                         _ => CxWindowCmd::None,
                                     };
 
@@ -146,16 +187,40 @@ impl Cx {
                                             }
                                         }
                                     }
+
+                                    if let Some(title) = window.window_set_title.take() {
+                                        for opengl_window in &mut opengl_windows {
+                                            if opengl_window.window_id == index {
+                                                opengl_window.xlib_window.set_title(&title);
+                                            }
+                                        }
+                                    }
+
+                                    if let Some(icon) = window.window_set_icon.take() {
+                                        for opengl_window in &mut opengl_windows {
+                                            if opengl_window.window_id == index {
+                                                opengl_window.xlib_window.set_icon(&icon);
+                                            }
+                                        }
+                                    }
+
+                                    if window.window_style_changed {
+                                        window.window_style_changed = false;
+                                        for opengl_window in &mut opengl_windows {
+                                            if opengl_window.window_id == index {
+                                                opengl_window.xlib_window.set_style(&window.window_style);
+                                            }
+                                        }
+                                    }
                                 }
-                                // set a cursor
-                                if self.down_mouse_cursor.is_some() {
-                                    xlib_app.set_mouse_cursor(self.down_mouse_cursor.as_ref().unwrap().clone())
-                                } else if self.hover_mouse_cursor.is_some() {
-                                    xlib_app.set_mouse_cursor(self.hover_mouse_cursor.as_ref().unwrap().clone())
-                                } else {
-                                    xlib_app.set_mouse_cursor(MouseCursor::Default)
+                                if self.pointer_lock_requested != self.pointer_locked {
+                                    xlib_app.set_pointer_lock(self.pointer_lock_requested);
+                                    self.pointer_locked = self.pointer_lock_requested;
                                 }
 
+                                // set a cursor
+                                xlib_app.set_mouse_cursor(self.current_mouse_cursor());
+
                                 if let Some(set_ime_position) = self.platform.set_ime_position {
                                     self.platform.set_ime_position = None;
                                     for opengl_window in &mut opengl_windows {
@@ -163,6 +228,10 @@ impl Cx {
                                     }
                                 }
 
+                                if self.tray_needs_update() {
+                                    xlib_app.update_tray(self.platform.desktop.tray.as_ref(), &self.command_settings);
+                                }
+
                                 while !self.platform.start_timer.is_empty() {
                                     let (timer_id, interval, repeats) = self.platform.start_timer.pop().unwrap();
                                     xlib_app.start_timer(timer_id, interval, repeats);
@@ -247,10 +316,180 @@ impl Cx {
         })
     }
 
-    /// TODO(JP): Generalize [`Cx::post_signal`] into this.
-    #[cfg(feature = "cef")]
-    pub(crate) fn send_event_from_any_thread(_event: Event) {
-        todo!();
+    fn event_loop_wayland(&mut self, wayland_app: &mut WaylandApp, egl_cx: EglCx) {
+        let opengl_cx = OpenglCx::Egl(egl_cx);
+        self.displays = wayland_app.displays.clone();
+
+        self.load_fonts();
+
+        self.call_event_handler(&mut Event::Construct);
+
+        self.request_draw();
+
+        let mut passes_todo = Vec::new();
+
+        wayland_app.event_loop(|wayland_app, events| {
+            self.last_event_time = wayland_app.time_now();
+            for event in events {
+                self.process_pre_event(event);
+
+                match &event {
+                    Event::WindowGeomChange(re) => {
+                        self.windows[re.window_id].window_geom = re.new_geom.clone();
+                        if re.old_geom.inner_size != re.new_geom.inner_size || re.old_geom.dpi_factor != re.new_geom.dpi_factor {
+                            self.request_draw();
+                        }
+                        self.call_event_handler(event);
+                    }
+                    Event::WindowClosed(wc) => {
+                        self.windows[wc.window_id].window_state = CxWindowState::Closed;
+                        self.windows_free.push(wc.window_id);
+                        if wayland_app.windows.is_empty() {
+                            wayland_app.terminate_event_loop();
+                        }
+                        self.call_event_handler(event);
+                    }
+                    Event::System(e) => {
+                        match e {
+                            SystemEvent::WindowSetHoverCursor(mc) => {
+                                self.set_hover_mouse_cursor(mc.clone());
+                            }
+                            SystemEvent::Paint => {
+                                let _vsync = self.process_desktop_paint_callbacks();
+
+                                // construct or destruct windows
+                                for (index, window) in self.windows.iter_mut().enumerate() {
+                                    window.window_state = match &window.window_state {
+                                        CxWindowState::Create { inner_size, title, .. } => {
+                                            wayland_app.create_window(index, *inner_size, title, &egl_cx);
+                                            window.window_geom = wayland_app.windows.last().unwrap().window_geom.clone();
+                                            CxWindowState::Created
+                                        }
+                                        CxWindowState::Close => {
+                                            wayland_app.close_window(index);
+                                            CxWindowState::Closed
+                                        }
+                                        CxWindowState::Created => CxWindowState::Created,
+                                        CxWindowState::Closed => CxWindowState::Closed,
+                                    };
+
+                                    let display_output = window
+                                        .window_set_display
+                                        .take()
+                                        .and_then(|display_id| wayland_app.display_output(display_id));
+                                    let wayland_window = wayland_app.windows.iter_mut().find(|w| w.window_id == index);
+                                    let wayland_window = if let Some(wayland_window) = wayland_window {
+                                        wayland_window
+                                    } else {
+                                        continue;
+                                    };
+                                    // Before `window_command`, so that going fullscreen uses the new output.
+                                    if let Some(output) = display_output {
+                                        wayland_window.move_to_display(output);
+                                    }
+                                    match window.window_command {
+                                        CxWindowCmd::Restore => wayland_window.restore(),
+                                        CxWindowCmd::Maximize => wayland_window.maximize(),
+                                        CxWindowCmd::Minimize => wayland_window.minimize(),
+                                        CxWindowCmd::FullScreen => wayland_window.set_fullscreen(true),
+                                        CxWindowCmd::NormalScreen => wayland_window.set_fullscreen(false),
+                                        _ => {}
+                                    }
+                                    window.window_command = CxWindowCmd::None;
+
+                                    if let Some(title) = window.window_set_title.take() {
+                                        wayland_window.set_title(&title);
+                                    }
+
+                                    // Wayland doesn't let clients set their icon or keep themselves on top.
+                                    window.window_set_icon = None;
+
+                                    if window.window_style_changed {
+                                        window.window_style_changed = false;
+                                        wayland_window.set_style(&window.window_style);
+                                    }
+                                }
+                                if self.pointer_lock_requested != self.pointer_locked {
+                                    wayland_app.set_pointer_lock(self.pointer_lock_requested);
+                                    self.pointer_locked = self.pointer_lock_requested;
+                                }
+
+                                // set a cursor
+                                wayland_app.set_mouse_cursor(self.current_mouse_cursor());
+
+                                // We don't support the text-input protocol yet.
+                                self.platform.set_ime_position = None;
+
+                                if self.tray_needs_update() {
+                                    wayland_app.update_tray(self.platform.desktop.tray.as_ref());
+                                }
+
+                                while let Some((timer_id, interval, repeats)) = self.platform.start_timer.pop() {
+                                    wayland_app.start_timer(timer_id, interval, repeats);
+                                }
+
+                                while let Some(timer_id) = self.platform.stop_timer.pop() {
+                                    wayland_app.stop_timer(timer_id);
+                                }
+
+                                // build a list of renderpasses to repaint
+                                let mut windows_need_repaint = 0;
+                                self.compute_passes_to_repaint(&mut passes_todo, &mut windows_need_repaint);
+
+                                if !passes_todo.is_empty() {
+                                    self.opengl_compile_shaders(&opengl_cx);
+                                    for pass_id in &passes_todo {
+                                        match self.passes[*pass_id].dep_of.clone() {
+                                            CxPassDepOf::Window(window_id) => {
+                                                let wayland_window =
+                                                    wayland_app.windows.iter_mut().find(|w| w.window_id == window_id);
+                                                if let Some(wayland_window) = wayland_window {
+                                                    // Leave the pass dirty until the compositor wants a new frame; the
+                                                    // frame callback wakes up the event loop.
+                                                    if !wayland_window.is_configured || wayland_window.frame_pending {
+                                                        continue;
+                                                    }
+                                                    let dpi_factor = wayland_window.window_geom.dpi_factor;
+                                                    self.passes[*pass_id].set_dpi_factor(dpi_factor);
+                                                    self.passes[*pass_id].paint_dirty = false;
+                                                    self.draw_pass_to_wayland_window(
+                                                        *pass_id,
+                                                        dpi_factor,
+                                                        wayland_window,
+                                                        &opengl_cx,
+                                                    );
+                                                }
+                                            }
+                                            CxPassDepOf::Pass(parent_pass_id) => {
+                                                let dpi_factor = self.get_delegated_dpi_factor(parent_pass_id);
+                                                self.draw_pass_to_texture(*pass_id, dpi_factor, &opengl_cx);
+                                            }
+                                            CxPassDepOf::None => {
+                                                self.draw_pass_to_texture(*pass_id, 1.0, &opengl_cx);
+                                            }
+                                        }
+                                    }
+                                }
+                            }
+                            _ => {
+                                self.call_event_handler(event);
+                            }
+                        }
+                    }
+                    Event::None => {}
+                    Event::Signal { .. } => {
+                        self.call_event_handler(event);
+                        self.call_signals();
+                    }
+                    _ => {
+                        self.call_event_handler(event);
+                    }
+                }
+                self.process_post_event(event);
+            }
+
+            !(self.requested_draw || self.requested_next_frame)
+        })
     }
 
     #[cfg(feature = "cef")]
@@ -270,34 +509,51 @@ impl CxPlatformCommon for Cx {
 
     /// See [`CxPlatformCommon::start_timer`] for documentation.
     fn start_timer(&mut self, interval: f64, repeats: bool) -> Timer {
-        self.last_timer_id += 1;
-        self.platform.start_timer.push((self.last_timer_id, interval, repeats));
-        Timer { timer_id: self.last_timer_id }
+        let timer_id = self.new_timer_id(repeats);
+        self.platform.start_timer.push((timer_id, interval, repeats));
+        Timer { timer_id }
     }
 
     /// See [`CxPlatformCommon::stop_timer`] for documentation.
     fn stop_timer(&mut self, timer: &mut Timer) {
-        if timer.timer_id != 0 {
-            self.platform.stop_timer.push(timer.timer_id);
-            timer.timer_id = 0;
+        if let Some(timer_id) = self.take_active_timer_id(timer) {
+            self.platform.stop_timer.push(timer_id);
         }
     }
 
     /// See [`CxPlatformCommon::post_signal`] for documentation.
     fn post_signal(signal: Signal, status: StatusId) {
-        XlibApp::post_signal(signal, status);
+        if WaylandApp::is_running() {
+            WaylandApp::post_signal(signal, status);
+        } else {
+            XlibApp::post_signal(signal, status);
+        }
     }
 
     /// See [`CxPlatformCommon::update_menu`] for documentation.
     fn update_menu(&mut self, _menu: &Menu) {}
 
+    /// See [`CxPlatformCommon::update_tray`] for documentation.
+    fn update_tray(&mut self, tray: Option<&Tray>) {
+        self.platform.desktop.update_tray(tray);
+    }
+
     /// See [`CxPlatformCommon::update_menu`] for documentation.
     fn copy_text_to_clipboard(&mut self, text: &str) {
-        XlibApp::copy_text_to_clipboard(text);
+        if WaylandApp::is_running() {
+            WaylandApp::copy_text_to_clipboard(text);
+        } else {
+            XlibApp::copy_text_to_clipboard(text);
+        }
     }
 
-    fn send_event_from_any_thread(_event: Event) {
-        todo!();
+    /// See [`CxPlatformCommon::send_event_from_any_thread`] for documentation.
+    fn send_event_from_any_thread(event: Event) {
+        if WaylandApp::is_running() {
+            WaylandApp::send_event_from_any_thread(event);
+        } else {
+            XlibApp::send_event_from_any_thread(event);
+        }
     }
 }
 
@@ -26,6 +26,8 @@ impl Cx {
         let mut cocoa_app = CocoaApp::new();
 
         cocoa_app.init();
+        cocoa_app.update_displays();
+        self.displays = cocoa_app.displays.clone();
 
         let mut metal_cx = MetalCx::new();
 
@@ -112,6 +114,7 @@ impl Cx {
                                             cef_url,
                                             #[cfg(feature = "cef-server")]
                                             get_resource_url_callback,
+                                            ..
                                         } => {
                                             // lets create a platformwindow
                                             let metal_window = MetalWindow::new(
@@ -172,6 +175,14 @@ impl Cx {
                                         CxWindowState::Closed => CxWindowState::Closed,
                                     };
 
+                                    if let Some(display_id) = window.window_set_display.take() {
+                                        for metal_window in &mut metal_windows {
+                                            if metal_window.window_id == index {
+                                                metal_window.cocoa_window.move_to_display(display_id);
+                                            }
+                                        }
+                                    }
+
                                     window.window_command = match &window.window_command {
                                         CxWindowCmd::Restore => {
                                             for metal_window in &mut metal_windows {
@@ -197,6 +208,22 @@ impl Cx {
                                             }
                                             CxWindowCmd::None
                                         }
+                                        CxWindowCmd::FullScreen => {
+                                            for metal_window in &mut metal_windows {
+                                                if metal_window.window_id == index {
+                                                    metal_window.cocoa_window.set_fullscreen(true);
+                                                }
+                                            }
+                                            CxWindowCmd::None
+                                        }
+                                        CxWindowCmd::NormalScreen => {
+                                            for metal_window in &mut metal_windows {
+                                                if metal_window.window_id == index {
+                                                    metal_window.cocoa_window.set_fullscreen(false);
+                                                }
+                                            }
+                                            CxWindowCmd::None
+                                        }
                                         _ => CxWindowCmd::None,
                                     };
 
@@ -207,16 +234,37 @@ impl Cx {
                                             }
                                         }
                                     }
+
+                                    if let Some(title) = window.window_set_title.take() {
+                                        for metal_window in &mut metal_windows {
+                                            if metal_window.window_id == index {
+                                                metal_window.cocoa_window.set_title(&title);
+                                            }
+                                        }
+                                    }
+
+                                    if let Some(icon) = window.window_set_icon.take() {
+                                        // The icon is for the whole app, so we don't need to find the window.
+                                        CocoaWindow::set_icon(&icon);
+                                    }
+
+                                    if window.window_style_changed {
+                                        window.window_style_changed = false;
+                                        for metal_window in &mut metal_windows {
+                                            if metal_window.window_id == index {
+                                                metal_window.cocoa_window.set_style(&window.window_style);
+                                            }
+                                        }
+                                    }
+                                }
+
+                                if self.pointer_lock_requested != self.pointer_locked {
+                                    cocoa_app.set_pointer_lock(self.pointer_lock_requested);
+                                    self.pointer_locked = self.pointer_lock_requested;
                                 }
 
                                 // set a cursor
-                                let mouse_cursor = if self.down_mouse_cursor.is_some() {
-                                    self.down_mouse_cursor.as_ref().unwrap().clone()
-                                } else if self.hover_mouse_cursor.is_some() {
-                                    self.hover_mouse_cursor.as_ref().unwrap().clone()
-                                } else {
-                                    MouseCursor::Default
-                                };
+                                let mouse_cursor = self.current_mouse_cursor();
 
                                 #[cfg(not(feature = "cef"))]
                                 cocoa_app.set_mouse_cursor(mouse_cursor);
@@ -248,6 +296,10 @@ impl Cx {
                                     }
                                 }
 
+                                if self.tray_needs_update() {
+                                    cocoa_app.update_tray(self.platform.desktop.tray.as_ref(), &self.command_settings);
+                                }
+
                                 // build a list of renderpasses to repaint
                                 let mut windows_need_repaint = 0;
                                 self.compute_passes_to_repaint(&mut passes_todo, &mut windows_need_repaint);
@@ -347,16 +399,15 @@ impl CxPlatformCommon for Cx {
 
     /// See [`CxPlatformCommon::start_timer`] for documentation.
     fn start_timer(&mut self, interval: f64, repeats: bool) -> Timer {
-        self.last_timer_id += 1;
-        self.platform.start_timer.push((self.last_timer_id, interval, repeats));
-        Timer { timer_id: self.last_timer_id }
+        let timer_id = self.new_timer_id(repeats);
+        self.platform.start_timer.push((timer_id, interval, repeats));
+        Timer { timer_id }
     }
 
     /// See [`CxPlatformCommon::stop_timer`] for documentation.
     fn stop_timer(&mut self, timer: &mut Timer) {
-        if timer.timer_id != 0 {
-            self.platform.stop_timer.push(timer.timer_id);
-            timer.timer_id = 0;
+        if let Some(timer_id) = self.take_active_timer_id(timer) {
+            self.platform.stop_timer.push(timer_id);
         }
     }
 
@@ -381,6 +432,11 @@ impl CxPlatformCommon for Cx {
         }
     }
 
+    /// See [`CxPlatformCommon::update_tray`] for documentation.
+    fn update_tray(&mut self, tray: Option<&Tray>) {
+        self.platform.desktop.update_tray(tray);
+    }
+
     /// See [`CxPlatformCommon::update_menu`] for documentation.
     fn copy_text_to_clipboard(&mut self, text: &str) {
         CocoaApp::copy_text_to_clipboard(text);
@@ -1,4 +1,4 @@
-//! Mac OS X Metal bindings.
+//! Metal bindings, shared between Mac OS X and iOS.
 
 use std::ffi::c_void;
 use std::mem;
@@ -11,9 +11,13 @@ use std::sync::Condvar;
 use std::sync::Mutex;
 
 use crate::cx_apple::*;
+#[cfg(target_os = "macos")]
 use crate::cx_cocoa::*;
+use crate::shader_cache::ShaderCache;
 use crate::*;
+use std::path::PathBuf;
 use zaplib_objc_sys::msg_send;
+#[cfg(target_os = "macos")]
 use zaplib_objc_sys::runtime::YES;
 use zaplib_shader_compiler::generate_metal;
 
@@ -39,6 +43,10 @@ impl Cx {
 
         for draw_call_id in 0..draw_calls_len {
             let sub_view_id = self.views[view_id].draw_calls[draw_call_id].sub_view_id;
+            if self.views[view_id].draw_calls[draw_call_id].batched_into.is_some() {
+                // Got merged into an earlier draw call; see `Cx::batch_draw_calls`.
+                continue;
+            }
             if sub_view_id != 0 {
                 self.render_view(
                     pass_id,
@@ -352,7 +360,9 @@ impl Cx {
             let command_buffer: id = unsafe { msg_send![metal_cx.command_queue, commandBuffer] };
             let encoder: id = unsafe { msg_send![command_buffer, renderCommandEncoderWithDescriptor: render_pass_descriptor] };
 
-            unsafe { msg_send![encoder, textureBarrier] }
+            // Texture barriers only exist on Mac OS X; iOS GPUs don't need them.
+            #[cfg(target_os = "macos")]
+            let () = unsafe { msg_send![encoder, textureBarrier] };
 
             if let Some(depth_state) = self.passes[pass_id].platform.mtl_depth_state {
                 let () = unsafe { msg_send![encoder, setDepthStencilState: depth_state] };
@@ -415,6 +425,7 @@ impl Cx {
             &mut gpu_read_guards,
             metal_cx,
         );
+        #[cfg(target_os = "macos")]
         let () = unsafe { msg_send![encoder, textureBarrier] };
         let () = unsafe { msg_send![encoder, endEncoding] };
         self.commit_command_buffer(command_buffer, gpu_read_guards);
@@ -482,8 +493,62 @@ impl Cx {
 pub(crate) struct MetalCx {
     pub(crate) device: id,
     pub(crate) command_queue: id,
+    binary_archive: Option<MetalBinaryArchive>,
+}
+
+/// Compiled render pipelines from previous runs of the app, so we don't have to compile them again. Stored in the
+/// [`ShaderCache`] directory.
+struct MetalBinaryArchive {
+    archive: RcObjcId,
+    path: PathBuf,
+}
+
+impl MetalBinaryArchive {
+    /// Load the archive from disk, or create an empty one. Returns [`None`] if binary archives are not supported
+    /// (they need macOS 11 or iOS 14), or if the [`ShaderCache`] is disabled.
+    fn new(device: id) -> Option<Self> {
+        let descriptor_class = Class::get("MTLBinaryArchiveDescriptor")?;
+        let path = ShaderCache::new().dir()?.join(format!("metal_pipelines_{}.metallib", env!("CARGO_PKG_VERSION")));
+
+        fn load(descriptor_class: &Class, device: id, url: id) -> Option<RcObjcId> {
+            let descriptor = RcObjcId::from_owned(NonNull::new(unsafe { msg_send![descriptor_class, new] })?);
+            let mut error: id = nil;
+            unsafe {
+                let () = msg_send![descriptor.as_id(), setUrl: url];
+                NonNull::new(msg_send![device, newBinaryArchiveWithDescriptor: descriptor.as_id() error: &mut error])
+                    .map(RcObjcId::from_owned)
+            }
+        }
+        // If loading fails (e.g. because the archive was made by a different OS version), we start over.
+        let archive = if path.exists() { load(descriptor_class, device, path_to_nsurl(&path)?) } else { None }
+            .or_else(|| load(descriptor_class, device, nil))?;
+        Some(Self { archive, path })
+    }
+
+    /// Write the archive to disk, including any pipelines that were added since it got loaded.
+    fn serialize(&self) {
+        // Write to a temporary file first, so that we never leave a partially written archive behind.
+        let tmp_path = self.path.with_extension("metallib.tmp");
+        if let Some(url) = path_to_nsurl(&tmp_path) {
+            let mut error: id = nil;
+            let success: BOOL = unsafe { msg_send![self.archive.as_id(), serializeToURL: url error: &mut error] };
+            if success == YES {
+                let _ = std::fs::rename(&tmp_path, &self.path);
+            }
+        }
+    }
 }
 
+fn path_to_nsurl(path: &std::path::Path) -> Option<id> {
+    let url: id = unsafe { msg_send![class!(NSURL), fileURLWithPath: str_to_nsstring(path.to_str()?)] };
+    if url == nil {
+        None
+    } else {
+        Some(url)
+    }
+}
+
+#[cfg(target_os = "macos")]
 #[derive(Clone)]
 pub(crate) struct MetalWindow {
     pub(crate) window_id: usize,
@@ -495,6 +560,7 @@ pub(crate) struct MetalWindow {
     pub(crate) is_resizing: bool,
 }
 
+#[cfg(target_os = "macos")]
 impl MetalWindow {
     pub(crate) fn new(
         window_id: usize,
@@ -580,12 +646,19 @@ pub(crate) struct CxPlatformPass {
 
 impl Cx {
     pub(crate) fn mtl_compile_shaders(&mut self, metal_cx: &MetalCx) {
+        let mut compiled_any = false;
         for shader_id in self.shader_recompile_ids.drain(..) {
             let shader = unsafe { self.shaders.get_unchecked_mut(shader_id) };
             let shader_ast = shader.shader_ast.as_ref().unwrap();
             let mtlsl = generate_metal::generate_shader(shader_ast);
             shader.platform = Some(CxPlatformShader::new(metal_cx, mtlsl));
             shader.shader_ast = None;
+            compiled_any = true;
+        }
+        if compiled_any {
+            if let Some(binary_archive) = &metal_cx.binary_archive {
+                binary_archive.serialize();
+            }
         }
     }
 }
@@ -606,7 +679,11 @@ impl MetalCx {
         }
         */
         let device = get_default_metal_device().expect("Cannot get default metal device");
-        MetalCx { command_queue: unsafe { msg_send![device, newCommandQueue] }, device }
+        MetalCx {
+            command_queue: unsafe { msg_send![device, newCommandQueue] },
+            binary_archive: MetalBinaryArchive::new(device),
+            device,
+        }
     }
 
     pub(crate) fn update_platform_texture_image2d(&self, cxtexture: &mut CxTexture) {
@@ -623,7 +700,8 @@ impl MetalCx {
             desc_changed = inner.format != cxtexture.desc.format
                 || inner.width != width
                 || inner.height != height
-                || inner.multisample != cxtexture.desc.multisample;
+                || inner.multisample != cxtexture.desc.multisample
+                || inner.cube != cxtexture.desc.cube;
         }
 
         // allocate new texture if descriptor change
@@ -631,10 +709,15 @@ impl MetalCx {
             let descriptor = RcObjcId::from_owned(NonNull::new(unsafe { msg_send![class!(MTLTextureDescriptor), new] }).unwrap());
             let texture = RcObjcId::from_owned(
                 NonNull::new(unsafe {
-                    let _: () = msg_send![descriptor.as_id(), setTextureType: MTLTextureType::D2];
+                    let texture_type = if cxtexture.desc.cube { MTLTextureType::Cube } else { MTLTextureType::D2 };
+                    let _: () = msg_send![descriptor.as_id(), setTextureType: texture_type];
                     let _: () = msg_send![descriptor.as_id(), setWidth: width as u64];
                     let _: () = msg_send![descriptor.as_id(), setHeight: height as u64];
+                    // iOS doesn't have managed storage, but its memory is shared between the CPU and GPU anyway.
+                    #[cfg(target_os = "macos")]
                     let _: () = msg_send![descriptor.as_id(), setStorageMode: MTLStorageMode::Managed];
+                    #[cfg(target_os = "ios")]
+                    let _: () = msg_send![descriptor.as_id(), setStorageMode: MTLStorageMode::Shared];
                     let _: () = msg_send![descriptor.as_id(), setUsage: MTLTextureUsage::RenderTarget];
                     match cxtexture.desc.format {
                         TextureFormat::ImageRGBA => {
@@ -655,6 +738,7 @@ impl MetalCx {
                 height,
                 format: cxtexture.desc.format,
                 multisample: cxtexture.desc.multisample,
+                cube: cxtexture.desc.cube,
                 texture,
             });
         }
@@ -663,7 +747,9 @@ impl MetalCx {
         let inner = cxtexture.platform.inner.as_ref().unwrap();
         match cxtexture.desc.format {
             TextureFormat::ImageRGBA => {
-                if cxtexture.image_u32.len() as u64 != width * height {
+                // Cube textures store their 6 faces one after another.
+                let slices = if cxtexture.desc.cube { 6 } else { 1 };
+                if cxtexture.image_u32.len() as u64 != width * height * slices {
                     panic!("update_platform_texture_image2d with wrong buffer_u32 size!");
                 }
                 let region = MTLRegion {
@@ -671,15 +757,19 @@ impl MetalCx {
                     size: MTLSize { width: width as u64, height: height as u64, depth: 1 },
                 };
                 let mtl_texture = inner.texture.as_id();
-                let () = unsafe {
-                    msg_send![
-                        mtl_texture,
-                        replaceRegion: region
-                        mipmapLevel: 0
-                        withBytes: cxtexture.image_u32.as_ptr() as *const std::ffi::c_void
-                        bytesPerRow: (width as usize * std::mem::size_of::<u32>()) as u64
-                    ]
-                };
+                for (slice, pixels) in cxtexture.image_u32.chunks_exact((width * height) as usize).enumerate() {
+                    let () = unsafe {
+                        msg_send![
+                            mtl_texture,
+                            replaceRegion: region
+                            mipmapLevel: 0
+                            slice: slice as u64
+                            withBytes: pixels.as_ptr() as *const std::ffi::c_void
+                            bytesPerRow: (width as usize * std::mem::size_of::<u32>()) as u64
+                            bytesPerImage: width * height * std::mem::size_of::<u32>() as u64
+                        ]
+                    };
+                }
             }
             _ => {
                 println!("update_platform_texture_image2d with unsupported format");
@@ -751,16 +841,34 @@ impl CxPlatformShader {
 
                 let () = msg_send![descriptor.as_id(), setDepthAttachmentPixelFormat: MTLPixelFormat::Depth32Float_Stencil8];
 
+                // Use pipelines that were compiled in previous runs of the app, if available.
+                if let Some(binary_archive) = &metal_cx.binary_archive {
+                    let binary_archives: id = msg_send![class!(NSArray), arrayWithObject: binary_archive.archive.as_id()];
+                    let () = msg_send![descriptor.as_id(), setBinaryArchives: binary_archives];
+                }
+
                 let mut error: id = nil;
                 msg_send![
                     metal_cx.device,
-                    newRenderPipelineStateWithDescriptor: descriptor
+                    newRenderPipelineStateWithDescriptor: descriptor.as_id()
                     error: &mut error
                 ]
             })
             .unwrap(),
         );
 
+        if let Some(binary_archive) = &metal_cx.binary_archive {
+            // Gets written to disk in `Cx::mtl_compile_shaders`. Does nothing if the pipeline is already in there.
+            let mut error: id = nil;
+            let _: BOOL = unsafe {
+                msg_send![
+                    binary_archive.archive.as_id(),
+                    addRenderPipelineFunctionsWithDescriptor: descriptor.as_id()
+                    error: &mut error
+                ]
+            };
+        }
+
         Self { render_pipeline_state }
     }
 }
@@ -808,6 +916,7 @@ impl MetalBuffer {
         unsafe {
             let contents: *mut u8 = msg_send![inner.buffer.as_id(), contents];
             std::ptr::copy(data.as_ptr() as *const u8, contents, len);
+            #[cfg(target_os = "macos")]
             let _: () = msg_send![
                 inner.buffer.as_id(),
                 didModifyRange: NSRange {
@@ -888,6 +997,7 @@ impl CxPlatformTexture {
             height,
             format: desc.format,
             multisample: desc.multisample,
+            cube: false,
             texture,
         });
     }
@@ -899,6 +1009,7 @@ struct CxPlatformTextureInner {
     height: u64,
     format: TextureFormat,
     multisample: Option<usize>,
+    cube: bool,
     texture: RcObjcId,
 }
 
@@ -1,13 +1,26 @@
-//! Linux OpenGL bindings.
-
+//! Linux and Android OpenGL bindings.
+
+#[cfg(target_os = "linux")]
+use crate::capture::CapturedFrame;
+#[cfg(target_os = "android")]
+use crate::cx_android::*;
+#[cfg(target_os = "linux")]
+use crate::cx_wayland::*;
+#[cfg(target_os = "linux")]
 use crate::cx_xlib::*;
 use crate::*;
-use std::ffi::{CStr, CString};
+#[cfg(target_os = "linux")]
+use std::ffi::CStr;
+use std::ffi::CString;
 use std::mem;
-use std::os::raw::{c_ulong, c_void};
+#[cfg(target_os = "linux")]
+use std::os::raw::c_ulong;
+use std::os::raw::c_void;
 use std::ptr;
+#[cfg(target_os = "linux")]
 use zaplib_glx_sys as glx_sys;
 use zaplib_shader_compiler::generate_glsl;
+#[cfg(target_os = "linux")]
 use zaplib_x11_sys as X11_sys;
 
 impl Cx {
@@ -29,6 +42,10 @@ impl Cx {
         let clip = self.views[view_id].intersect_clip(clip);
         for draw_call_id in 0..draw_calls_len {
             let sub_view_id = self.views[view_id].draw_calls[draw_call_id].sub_view_id;
+            if self.views[view_id].draw_calls[draw_call_id].batched_into.is_some() {
+                // Got merged into an earlier draw call; see `Cx::batch_draw_calls`.
+                continue;
+            }
             if sub_view_id != 0 {
                 self.render_view(
                     pass_id,
@@ -157,14 +174,19 @@ impl Cx {
                         let cxtexture = &mut self.textures[*texture_id as usize];
                         if cxtexture.update_image {
                             cxtexture.update_image = false;
-                            opengl_cx.update_platform_texture_image2d(cxtexture);
+                            if cxtexture.desc.cube {
+                                opengl_cx.update_platform_texture_cube(cxtexture);
+                            } else {
+                                opengl_cx.update_platform_texture_image2d(cxtexture);
+                            }
                         }
                         // get the loc
                         gl::ActiveTexture(gl::TEXTURE0 + i as u32);
+                        let target = if cxtexture.desc.cube { gl::TEXTURE_CUBE_MAP } else { gl::TEXTURE_2D };
                         if let Some(texture) = cxtexture.platform.gl_texture {
-                            gl::BindTexture(gl::TEXTURE_2D, texture);
+                            gl::BindTexture(target, texture);
                         } else {
-                            gl::BindTexture(gl::TEXTURE_2D, 0);
+                            gl::BindTexture(target, 0);
                         }
                     }
 
@@ -187,6 +209,7 @@ impl Cx {
         }
     }
 
+    #[cfg(target_os = "linux")]
     pub(crate) fn draw_pass_to_window(
         &mut self,
         pass_id: usize,
@@ -194,8 +217,6 @@ impl Cx {
         opengl_window: &mut OpenglWindow,
         opengl_cx: &OpenglCx,
     ) -> bool {
-        let view_id = self.passes[pass_id].main_view_id.unwrap();
-
         let mut init_repaint = false;
 
         if opengl_window.opening_repaint_count < 10 {
@@ -207,15 +228,46 @@ impl Cx {
         opengl_window.xlib_window.hide_child_windows();
 
         let window = opengl_window.xlib_window.window.unwrap();
+        let (display, context) = match opengl_cx {
+            OpenglCx::Glx { display, context, .. } => (*display, *context),
+            OpenglCx::Egl(_) => panic!("Xlib windows need a GLX context"),
+        };
+
+        unsafe {
+            glx_sys::glXMakeCurrent(display, window, context);
+        }
+        self.draw_pass_to_current_framebuffer(
+            pass_id,
+            dpi_factor,
+            opengl_window.window_id,
+            &opengl_window.window_geom,
+            opengl_cx,
+        );
+        unsafe {
+            glx_sys::glXSwapBuffers(display, window);
+        }
+        init_repaint
+    }
+
+    /// Draw the main pass of a window into the default framebuffer of the current context, which the caller has to make
+    /// current and swap afterwards (using GLX or EGL).
+    pub(crate) fn draw_pass_to_current_framebuffer(
+        &mut self,
+        pass_id: usize,
+        dpi_factor: f32,
+        window_id: usize,
+        window_geom: &WindowGeom,
+        opengl_cx: &OpenglCx,
+    ) {
+        let view_id = self.passes[pass_id].main_view_id.unwrap();
 
         let pass_size = self.passes[pass_id].pass_size;
         self.passes[pass_id].set_matrix(Vec2::default(), pass_size);
 
-        let pix_width = opengl_window.window_geom.inner_size.x * opengl_window.window_geom.dpi_factor;
-        let pix_height = opengl_window.window_geom.inner_size.y * opengl_window.window_geom.dpi_factor;
+        let pix_width = window_geom.inner_size.x * window_geom.dpi_factor;
+        let pix_height = window_geom.inner_size.y * window_geom.dpi_factor;
 
         unsafe {
-            glx_sys::glXMakeCurrent(opengl_cx.display, window, opengl_cx.context);
             gl::Viewport(0, 0, pix_width as i32, pix_height as i32);
         }
         let view_rect = Rect::default();
@@ -258,10 +310,26 @@ impl Cx {
             zbias_step,
         );
 
-        unsafe {
-            glx_sys::glXSwapBuffers(opengl_cx.display, window);
+        #[cfg(target_os = "linux")]
+        if window_id == 0 && self.frame_capture.wants_frame() {
+            let (width, height) = (pix_width as usize, pix_height as usize);
+            let mut pixels = vec![0u32; width * height];
+            unsafe {
+                gl::ReadBuffer(gl::BACK);
+                gl::ReadPixels(
+                    0,
+                    0,
+                    width as i32,
+                    height as i32,
+                    gl::RGBA,
+                    gl::UNSIGNED_BYTE,
+                    pixels.as_mut_ptr() as *mut c_void,
+                );
+            }
+            // OpenGL returns rows from bottom to top.
+            let pixels = pixels.chunks_exact(width.max(1)).rev().flatten().copied().collect();
+            self.frame_capture.process_frame(CapturedFrame { width, height, pixels });
         }
-        init_repaint
     }
 
     pub(crate) fn draw_pass_to_texture(&mut self, pass_id: usize, inherit_dpi_factor: f32, opengl_cx: &OpenglCx) {
@@ -556,9 +624,7 @@ impl Cx {
             return;
         }
 
-        unsafe {
-            glx_sys::glXMakeCurrent(opengl_cx.display, opengl_cx.hidden_window, opengl_cx.context);
-        }
+        opengl_cx.make_current_without_window();
         for shader_id in self.shader_recompile_ids.drain(..) {
             let shader = unsafe { self.shaders.get_unchecked_mut(shader_id) };
             let shader_ast = shader.shader_ast.as_ref().unwrap();
@@ -572,6 +638,7 @@ impl Cx {
                 precision highp float;
                 precision highp int;
                 vec4 sample2d(sampler2D sampler, vec2 pos){{return texture2D(sampler, vec2(pos.x, 1.0-pos.y));}}
+                vec4 sample_cube(samplerCube sampler, vec3 dir){{return textureCube(sampler, dir);}}
                 {}\0",
                 vertex
             );
@@ -582,6 +649,7 @@ impl Cx {
                 precision highp float;
                 precision highp int;
                 vec4 sample2d(sampler2D sampler, vec2 pos){{return texture2D(sampler, vec2(pos.x, 1.0-pos.y));}}
+                vec4 sample_cube(samplerCube sampler, vec3 dir){{return textureCube(sampler, dir);}}
                 {}\0",
                 fragment
             );
@@ -638,14 +706,20 @@ impl Cx {
     }
 }
 
-pub(crate) struct OpenglCx {
-    pub(crate) display: *mut glx_sys::Display,
-    pub(crate) context: glx_sys::GLXContext,
-    pub(crate) visual_info: glx_sys::XVisualInfo,
-    pub(crate) hidden_window: glx_sys::Window,
+/// The OpenGL context, which uses GLX on X11, or EGL on Wayland and Android (see [`EglCx`]).
+pub(crate) enum OpenglCx {
+    #[cfg(target_os = "linux")]
+    Glx {
+        display: *mut glx_sys::Display,
+        context: glx_sys::GLXContext,
+        visual_info: glx_sys::XVisualInfo,
+        hidden_window: glx_sys::Window,
+    },
+    Egl(EglCx),
 }
 
 impl OpenglCx {
+    #[cfg(target_os = "linux")]
     pub(crate) fn new(display: *mut X11_sys::Display) -> OpenglCx {
         unsafe {
             let display = display as *mut glx_sys::Display;
@@ -759,7 +833,18 @@ impl OpenglCx {
 
             // To make sure the window stays hidden, we simply never call XMapWindow on it.
 
-            OpenglCx { display, context, visual_info, hidden_window }
+            OpenglCx::Glx { display, context, visual_info, hidden_window }
+        }
+    }
+
+    /// Make the context current when we don't have a window to draw into, e.g. to compile shaders.
+    pub(crate) fn make_current_without_window(&self) {
+        match self {
+            #[cfg(target_os = "linux")]
+            OpenglCx::Glx { display, context, hidden_window, .. } => unsafe {
+                glx_sys::glXMakeCurrent(*display, *hidden_window, *context);
+            },
+            OpenglCx::Egl(egl_cx) => egl_cx.make_current_without_window(),
         }
     }
 
@@ -784,8 +869,9 @@ impl OpenglCx {
                         4 => {
                             gl::Uniform4f(loc.loc as i32, uni[o], uni[o + 1], uni[o + 2], uni[o + 3]);
                         }
-                        16 => {
-                            gl::UniformMatrix4fv(loc.loc as i32, 1, 0, uni.as_ptr().add(o));
+                        // A mat4, or an array of them (the only arrays allowed in uniforms).
+                        size if size % 16 == 0 => {
+                            gl::UniformMatrix4fv(loc.loc as i32, (size / 16) as i32, 0, uni.as_ptr().add(o));
                         }
                         _ => (),
                     }
@@ -805,8 +891,6 @@ impl OpenglCx {
         let height = cxtexture.desc.height.unwrap();
 
         // allocate new texture if descriptor change
-        // TODO(Paras): Change this so that we still update image_u32 data
-        // whenever this function is called, not just when the descriptor changes.
         if cxtexture.platform.alloc_desc != cxtexture.desc {
             cxtexture.platform.alloc_desc = cxtexture.desc.clone();
             cxtexture.platform.width = width as u64;
@@ -839,6 +923,81 @@ impl OpenglCx {
                 );
                 gl::BindTexture(gl::TEXTURE_2D, 0);
             }
+        } else if let Some(gl_texture) = cxtexture.platform.gl_texture {
+            // Same dimensions, so just replace the image.
+            unsafe {
+                gl::BindTexture(gl::TEXTURE_2D, gl_texture);
+                gl::TexSubImage2D(
+                    gl::TEXTURE_2D,
+                    0,
+                    0,
+                    0,
+                    width as i32,
+                    height as i32,
+                    gl::RGBA,
+                    gl::UNSIGNED_BYTE,
+                    cxtexture.image_u32.as_ptr() as *const _,
+                );
+                gl::BindTexture(gl::TEXTURE_2D, 0);
+            }
+        }
+
+        cxtexture.update_image = false;
+    }
+
+    /// Upload all 6 faces of a cube texture, which are stored one after another in the order of the
+    /// `TEXTURE_CUBE_MAP_POSITIVE_X..TEXTURE_CUBE_MAP_NEGATIVE_Z` constants.
+    pub(crate) fn update_platform_texture_cube(&self, cxtexture: &mut CxTexture) {
+        let size = cxtexture.desc.width.unwrap();
+        let gl_texture = match cxtexture.platform.gl_texture {
+            None => unsafe {
+                let mut gl_texture = std::mem::MaybeUninit::uninit();
+                gl::GenTextures(1, gl_texture.as_mut_ptr());
+                let gl_texture = gl_texture.assume_init();
+                cxtexture.platform.gl_texture = Some(gl_texture);
+                gl_texture
+            },
+            Some(gl_texture_old) => gl_texture_old,
+        };
+        let reallocate = cxtexture.platform.alloc_desc != cxtexture.desc;
+        cxtexture.platform.alloc_desc = cxtexture.desc.clone();
+        cxtexture.platform.width = size as u64;
+        cxtexture.platform.height = size as u64;
+        unsafe {
+            gl::BindTexture(gl::TEXTURE_CUBE_MAP, gl_texture);
+            gl::TexParameteri(gl::TEXTURE_CUBE_MAP, gl::TEXTURE_MIN_FILTER, gl::LINEAR as i32);
+            gl::TexParameteri(gl::TEXTURE_CUBE_MAP, gl::TEXTURE_MAG_FILTER, gl::LINEAR as i32);
+            gl::TexParameteri(gl::TEXTURE_CUBE_MAP, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as i32);
+            gl::TexParameteri(gl::TEXTURE_CUBE_MAP, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as i32);
+            for (face, pixels) in cxtexture.image_u32.chunks_exact(size * size).enumerate() {
+                let target = gl::TEXTURE_CUBE_MAP_POSITIVE_X + face as u32;
+                if reallocate {
+                    gl::TexImage2D(
+                        target,
+                        0,
+                        gl::RGBA as i32,
+                        size as i32,
+                        size as i32,
+                        0,
+                        gl::RGBA,
+                        gl::UNSIGNED_BYTE,
+                        pixels.as_ptr() as *const _,
+                    );
+                } else {
+                    gl::TexSubImage2D(
+                        target,
+                        0,
+                        0,
+                        0,
+                        size as i32,
+                        size as i32,
+                        gl::RGBA,
+                        gl::UNSIGNED_BYTE,
+                        pixels.as_ptr() as *const _,
+                    );
+                }
+            }
+            gl::BindTexture(gl::TEXTURE_CUBE_MAP, 0);
         }
 
         cxtexture.update_image = false;
@@ -937,6 +1096,7 @@ pub(crate) struct CxPlatformShader {
     pub(crate) user_uniforms: Vec<OpenglUniform>,
 }
 
+#[cfg(target_os = "linux")]
 #[derive(Clone)]
 pub(crate) struct OpenglWindow {
     pub(crate) first_draw: bool,
@@ -947,6 +1107,7 @@ pub(crate) struct OpenglWindow {
     pub(crate) xlib_window: XlibWindow,
 }
 
+#[cfg(target_os = "linux")]
 impl OpenglWindow {
     pub(crate) fn new(
         window_id: usize,
@@ -958,7 +1119,10 @@ impl OpenglWindow {
     ) -> OpenglWindow {
         let mut xlib_window = XlibWindow::new(xlib_app, window_id);
 
-        let visual_info = unsafe { mem::transmute(opengl_cx.visual_info) };
+        let visual_info = match opengl_cx {
+            OpenglCx::Glx { visual_info, .. } => unsafe { mem::transmute(*visual_info) },
+            OpenglCx::Egl(_) => panic!("Xlib windows need a GLX context"),
+        };
         xlib_window.init(title, inner_size, position, visual_info);
 
         OpenglWindow {
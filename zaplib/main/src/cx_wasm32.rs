@@ -1,14 +1,16 @@
 //! WebAssembly platform-specific entry point.
 
 use crate::cx_web::*;
+use crate::instance_buffer_pool::InstanceBufferPool;
 use crate::universal_file::UniversalFile;
 use crate::zerde::*;
 use crate::*;
 use std::alloc;
 use std::cell::UnsafeCell;
-use std::collections::{BTreeSet, HashMap};
+use std::collections::{BTreeSet, HashMap, HashSet};
 use std::mem;
 use std::ptr;
+use std::sync::atomic::AtomicU32;
 use std::sync::Arc;
 
 // These constants must be kept in sync with the ones in web/zerde_eventloop_events.ts
@@ -39,6 +41,9 @@ const MSG_TYPE_DRAG_ENTER: u32 = 27;
 const MSG_TYPE_DRAG_LEAVE: u32 = 28;
 const MSG_TYPE_DRAG_OVER: u32 = 29;
 const MSG_TYPE_CALL_RUST: u32 = 30;
+const MSG_TYPE_WINDOW_RESIZE: u32 = 31;
+const MSG_TYPE_POINTER_LOCK_CHANGED: u32 = 32;
+const MSG_TYPE_POINTER_RAW_MOVE: u32 = 33;
 
 impl Cx {
     /// Initialize global error handlers.
@@ -49,8 +54,14 @@ impl Cx {
             throw_error(&format!("Allocation layout: {:?}", layout));
         });
         std::panic::set_hook(Box::new(|info: &std::panic::PanicInfo| {
+            // We can't unwind in WebAssembly, so this is the last chance to tell the main thread that the
+            // worker is about to go away.
+            if universal_thread::is_worker() {
+                universal_thread::report_worker_panic(info.to_string());
+            }
             throw_error(&info.to_string());
         }));
+        init_logger();
     }
 
     pub fn process_wasm_events<F>(&mut self, msg: u64, mut event_handler: F) -> u64
@@ -86,6 +97,7 @@ impl Cx {
 
                     self.platform.window_geom = WindowGeom {
                         is_fullscreen: false,
+                        is_maximized: false,
                         is_topmost: false,
                         inner_size: Vec2 { x: zerde_parser.parse_f32(), y: zerde_parser.parse_f32() },
                         dpi_factor: zerde_parser.parse_f32(),
@@ -132,6 +144,7 @@ impl Cx {
                         xr_is_presenting: zerde_parser.parse_u32() > 0,
                         xr_can_present: zerde_parser.parse_u32() > 0,
                         is_fullscreen: zerde_parser.parse_u32() > 0,
+                        is_maximized: false,
                         can_fullscreen: old_geom.can_fullscreen,
                     };
                     assert!(self.platform.window_geom.dpi_factor > 0.0);
@@ -140,6 +153,10 @@ impl Cx {
                     if self.windows.len() > 0 {
                         self.windows[0].window_geom = self.platform.window_geom.clone();
                     }
+                    if !new_geom.xr_is_presenting {
+                        self.platform.xr_views.clear();
+                        self.platform.xr_last_inputs.clear();
+                    }
                     if old_geom != new_geom {
                         self.wasm_event_handler(Event::WindowGeomChange(WindowGeomChangeEvent {
                             window_id: 0,
@@ -151,6 +168,30 @@ impl Cx {
                     // do our initial redraw and repaint
                     self.request_draw();
                 }
+                MSG_TYPE_WINDOW_RESIZE => {
+                    // Size of the canvas of a window other than the main one; see `attach_window_canvas`.
+                    let window_id = zerde_parser.parse_u32() as usize;
+                    let inner_size = Vec2 { x: zerde_parser.parse_f32(), y: zerde_parser.parse_f32() };
+                    let dpi_factor = zerde_parser.parse_f32();
+                    assert!(dpi_factor > 0.0);
+
+                    // The canvas might still report its size right after the window got closed.
+                    if let Some(window) = self.windows.get_mut(window_id) {
+                        if let CxWindowState::Created = window.window_state {
+                            let old_geom = window.window_geom.clone();
+                            window.window_geom = WindowGeom { inner_size, dpi_factor, ..old_geom.clone() };
+                            let new_geom = window.window_geom.clone();
+                            if old_geom != new_geom {
+                                self.wasm_event_handler(Event::WindowGeomChange(WindowGeomChangeEvent {
+                                    window_id,
+                                    old_geom,
+                                    new_geom,
+                                }));
+                            }
+                            self.request_draw();
+                        }
+                    }
+                }
                 MSG_TYPE_ANIMATION_FRAME => {
                     is_animation_frame = true;
                     if self.requested_next_frame {
@@ -158,6 +199,7 @@ impl Cx {
                     }
                 }
                 MSG_TYPE_POINTER_DOWN => {
+                    let window_id = zerde_parser.parse_u32() as usize;
                     let abs = Vec2 { x: zerde_parser.parse_f32(), y: zerde_parser.parse_f32() };
                     let button = zerde_parser.parse_u32() as usize;
                     let digit = zerde_parser.parse_u32() as usize;
@@ -166,7 +208,7 @@ impl Cx {
                     let modifiers = unpack_key_modifier(zerde_parser.parse_u32());
                     let time = zerde_parser.parse_f64();
                     self.wasm_event_handler(Event::PointerDown(PointerDownEvent {
-                        window_id: 0,
+                        window_id,
                         abs,
                         rel: abs,
                         rect: Rect::default(),
@@ -180,6 +222,7 @@ impl Cx {
                     }));
                 }
                 MSG_TYPE_POINTER_UP => {
+                    let window_id = zerde_parser.parse_u32() as usize;
                     let abs = Vec2 { x: zerde_parser.parse_f32(), y: zerde_parser.parse_f32() };
                     let button = zerde_parser.parse_u32() as usize;
                     let digit = zerde_parser.parse_u32() as usize;
@@ -188,7 +231,7 @@ impl Cx {
                     let modifiers = unpack_key_modifier(zerde_parser.parse_u32());
                     let time = zerde_parser.parse_f64();
                     self.wasm_event_handler(Event::PointerUp(PointerUpEvent {
-                        window_id: 0,
+                        window_id,
                         abs,
                         rel: abs,
                         rect: Rect::default(),
@@ -203,13 +246,14 @@ impl Cx {
                     }));
                 }
                 MSG_TYPE_POINTER_MOVE => {
+                    let window_id = zerde_parser.parse_u32() as usize;
                     let abs = Vec2 { x: zerde_parser.parse_f32(), y: zerde_parser.parse_f32() };
                     let digit = zerde_parser.parse_u32() as usize;
                     let is_touch = zerde_parser.parse_u32() > 0;
                     let modifiers = unpack_key_modifier(zerde_parser.parse_u32());
                     let time = zerde_parser.parse_f64();
                     self.wasm_event_handler(Event::PointerMove(PointerMoveEvent {
-                        window_id: 0,
+                        window_id,
                         abs,
                         rel: abs,
                         rect: Rect::default(),
@@ -223,13 +267,14 @@ impl Cx {
                     }));
                 }
                 MSG_TYPE_POINTER_HOVER => {
+                    let window_id = zerde_parser.parse_u32() as usize;
                     let abs = Vec2 { x: zerde_parser.parse_f32(), y: zerde_parser.parse_f32() };
                     let modifiers = unpack_key_modifier(zerde_parser.parse_u32());
                     let time = zerde_parser.parse_f64();
                     self.wasm_event_handler(Event::PointerHover(PointerHoverEvent {
                         any_down: false,
                         digit: 0,
-                        window_id: 0,
+                        window_id,
                         abs,
                         rel: abs,
                         rect: Rect::default(),
@@ -240,13 +285,15 @@ impl Cx {
                     }));
                 }
                 MSG_TYPE_POINTER_SCROLL => {
+                    let window_id = zerde_parser.parse_u32() as usize;
                     let abs = Vec2 { x: zerde_parser.parse_f32(), y: zerde_parser.parse_f32() };
                     let scroll = Vec2 { x: zerde_parser.parse_f32(), y: zerde_parser.parse_f32() };
+                    let pinch = zerde_parser.parse_f32();
                     let is_wheel = zerde_parser.parse_u32() != 0;
                     let modifiers = unpack_key_modifier(zerde_parser.parse_u32());
                     let time = zerde_parser.parse_f64();
                     self.wasm_event_handler(Event::PointerScroll(PointerScrollEvent {
-                        window_id: 0,
+                        window_id,
                         digit: 0,
                         abs,
                         rel: abs,
@@ -254,17 +301,29 @@ impl Cx {
                         handled_x: false,
                         handled_y: false,
                         scroll,
+                        pinch,
+                        // Browsers already continue trackpad scrolling with momentum, without telling us.
+                        device: if is_wheel { ScrollDevice::Wheel } else { ScrollDevice::Trackpad },
+                        phase: ScrollPhase::Changed,
                         input_type: if is_wheel { PointerInputType::Mouse } else { PointerInputType::Touch },
                         modifiers,
                         time,
                     }));
                 }
+                MSG_TYPE_POINTER_RAW_MOVE => {
+                    let window_id = zerde_parser.parse_u32() as usize;
+                    let delta = Vec2 { x: zerde_parser.parse_f32(), y: zerde_parser.parse_f32() };
+                    let modifiers = unpack_key_modifier(zerde_parser.parse_u32());
+                    let time = zerde_parser.parse_f64();
+                    self.wasm_event_handler(Event::PointerRawMove(PointerRawMoveEvent { window_id, delta, modifiers, time }));
+                }
                 MSG_TYPE_POINTER_OUT => {
+                    let window_id = zerde_parser.parse_u32() as usize;
                     let abs = Vec2 { x: zerde_parser.parse_f32(), y: zerde_parser.parse_f32() };
                     let modifiers = unpack_key_modifier(zerde_parser.parse_u32());
                     let time = zerde_parser.parse_f64();
                     self.wasm_event_handler(Event::PointerHover(PointerHoverEvent {
-                        window_id: 0,
+                        window_id,
                         digit: 0,
                         any_down: false,
                         abs,
@@ -292,59 +351,60 @@ impl Cx {
                         self.wasm_event_handler(Event::AppFocus);
                     }
                 }
+                MSG_TYPE_POINTER_LOCK_CHANGED => {
+                    // Only report changes that we didn't request, like the user pressing Escape, or the browser
+                    // refusing to lock.
+                    let locked = zerde_parser.parse_u32() != 0;
+                    if locked != self.pointer_locked {
+                        self.wasm_event_handler(Event::PointerLockChanged(PointerLockChangedEvent { locked }));
+                    }
+                }
                 MSG_TYPE_XR_UPDATE => {
-                    // xr_update, TODO(JP): bring this back some day?
-                    // let inputs_len = zerde_parser.parse_u32();
-                    // let time = zerde_parser.parse_f64();
-                    // let head_transform = zerde_parser.parse_transform();
-                    // let mut left_input = XRInput::default();
-                    // let mut right_input = XRInput::default();
-                    // let mut other_inputs = Vec::new();
-                    // for _ in 0..inputs_len {
-                    //     let skip = zerde_parser.parse_u32();
-                    //     if skip == 0 {
-                    //         continue;
-                    //     }
-                    //     let mut input = XRInput::default();
-                    //     input.active = true;
-                    //     input.grip = zerde_parser.parse_transform();
-                    //     input.ray = zerde_parser.parse_transform();
-
-                    //     let hand = zerde_parser.parse_u32();
-                    //     let num_buttons = zerde_parser.parse_u32() as usize;
-                    //     input.num_buttons = num_buttons;
-                    //     for i in 0..num_buttons {
-                    //         input.buttons[i].pressed = zerde_parser.parse_u32() > 0;
-                    //         input.buttons[i].value = zerde_parser.parse_f32();
-                    //     }
-
-                    //     let num_axes = zerde_parser.parse_u32() as usize;
-                    //     input.num_axes = num_axes;
-                    //     for i in 0..num_axes {
-                    //         input.axes[i] = zerde_parser.parse_f32();
-                    //     }
-
-                    //     if hand == 1 {
-                    //         left_input = input;
-                    //     } else if hand == 2 {
-                    //         right_input = input;
-                    //     } else {
-                    //         other_inputs.push(input);
-                    //     }
-                    // }
-                    // // call the VRUpdate event
-                    // self.wasm_event_handler(&mut Event::XRUpdate(XRUpdateEvent {
-                    //     time,
-                    //     head_transform,
-                    //     last_left_input: self.platform.xr_last_left_input.clone(),
-                    //     last_right_input: self.platform.xr_last_right_input.clone(),
-                    //     left_input: left_input.clone(),
-                    //     right_input: right_input.clone(),
-                    //     other_inputs,
-                    // }));
-
-                    // self.platform.xr_last_left_input = left_input;
-                    // self.platform.xr_last_right_input = right_input;
+                    // A frame of the headset; see `xrUpdate` in zerde_eventloop_events.ts.
+                    let time = zerde_parser.parse_f64();
+                    let head = parse_xr_transform(&mut zerde_parser);
+                    let views_len = zerde_parser.parse_u32();
+                    let views: Vec<XrView> = (0..views_len)
+                        .map(|_| XrView {
+                            projection: parse_xr_mat4(&mut zerde_parser),
+                            view: parse_xr_mat4(&mut zerde_parser),
+                            viewport: Rect {
+                                pos: Vec2 { x: zerde_parser.parse_f32(), y: zerde_parser.parse_f32() },
+                                size: Vec2 { x: zerde_parser.parse_f32(), y: zerde_parser.parse_f32() },
+                            },
+                        })
+                        .collect();
+                    let inputs_len = zerde_parser.parse_u32();
+                    let inputs: Vec<XrInput> = (0..inputs_len)
+                        .map(|_| {
+                            let handedness = match zerde_parser.parse_u32() {
+                                1 => XrHandedness::Left,
+                                2 => XrHandedness::Right,
+                                _ => XrHandedness::None,
+                            };
+                            let has_grip = zerde_parser.parse_u32() > 0;
+                            let grip = parse_xr_transform(&mut zerde_parser);
+                            let ray = parse_xr_transform(&mut zerde_parser);
+                            let buttons_len = zerde_parser.parse_u32();
+                            let buttons = (0..buttons_len)
+                                .map(|_| XrButton { pressed: zerde_parser.parse_u32() > 0, value: zerde_parser.parse_f32() })
+                                .collect();
+                            let axes_len = zerde_parser.parse_u32();
+                            let axes = (0..axes_len).map(|_| zerde_parser.parse_f32()).collect();
+                            XrInput { handedness, grip: if has_grip { Some(grip) } else { None }, ray, buttons, axes }
+                        })
+                        .collect();
+
+                    // Stereo passes get drawn on every frame of the headset, since the head is never completely
+                    // still.
+                    for cxpass in &mut self.passes {
+                        if cxpass.xr_stereo {
+                            cxpass.paint_dirty = true;
+                        }
+                    }
+                    self.platform.xr_views = views.clone();
+                    let last_inputs = std::mem::replace(&mut self.platform.xr_last_inputs, inputs.clone());
+                    self.wasm_event_handler(Event::XrUpdate(XrUpdateEvent { time, head, views, inputs, last_inputs }));
                 }
                 MSG_TYPE_PAINT_DIRTY => {
                     // paint_dirty, only set the passes of the main window to dirty
@@ -404,10 +464,16 @@ impl Cx {
                     let name = zerde_parser.parse_string();
                     let params = zerde_parser.parse_zap_params();
                     let callback_id = zerde_parser.parse_u32();
+                    let streaming = zerde_parser.parse_u32() != 0;
+                    // Safety: JS passes 0 or a pointer from `createCancelToken` that it holds on to until the call
+                    // returns.
+                    let cancel_token = unsafe { CallRustCancelToken::from_js_ptr(zerde_parser.parse_u32()) };
                     self.wasm_event_handler(Event::System(SystemEvent::WebRustCall(Some(WebRustCallEvent {
                         name,
                         params,
                         callback_id,
+                        streaming,
+                        cancel_token,
                     }))));
                 }
                 _ => {
@@ -424,11 +490,28 @@ impl Cx {
         }
         self.call_signals();
 
-        for window in &mut self.windows {
+        let mut closed_window_ids = Vec::new();
+        for (window_id, window) in self.windows.iter_mut().enumerate() {
             window.window_state = match &window.window_state {
-                CxWindowState::Create { title, add_drop_target_for_app_open_files, .. } => {
-                    self.platform.zerde_eventloop_msgs.set_document_title(&title);
-                    window.window_geom = self.platform.window_geom.clone();
+                CxWindowState::Create { title, inner_size, position, add_drop_target_for_app_open_files, canvas_id, .. } => {
+                    if window_id == 0 {
+                        self.platform.zerde_eventloop_msgs.set_document_title(&title);
+                        window.window_geom = self.platform.window_geom.clone();
+                    } else {
+                        // Other windows get their own canvas, which reports its actual size
+                        // using `MSG_TYPE_WINDOW_RESIZE`.
+                        self.platform.zerde_eventloop_msgs.attach_window_canvas(
+                            window_id,
+                            canvas_id.as_deref().unwrap_or(""),
+                            *inner_size,
+                            position.unwrap_or_default(),
+                        );
+                        window.window_geom = WindowGeom {
+                            inner_size: *inner_size,
+                            dpi_factor: self.platform.window_geom.dpi_factor,
+                            ..WindowGeom::default()
+                        };
+                    }
 
                     if *add_drop_target_for_app_open_files {
                         self.platform.zerde_eventloop_msgs.enable_global_file_drop_target();
@@ -436,7 +519,14 @@ impl Cx {
 
                     CxWindowState::Created
                 }
-                CxWindowState::Close => CxWindowState::Closed,
+                CxWindowState::Close => {
+                    // The main window can't be closed, since it's drawn into the canvas of the page.
+                    if window_id != 0 {
+                        self.platform.zerde_eventloop_msgs.detach_window_canvas(window_id);
+                        closed_window_ids.push(window_id);
+                    }
+                    CxWindowState::Closed
+                }
                 CxWindowState::Created => CxWindowState::Created,
                 CxWindowState::Closed => CxWindowState::Closed,
             };
@@ -460,15 +550,36 @@ impl Cx {
                 }
                 _ => CxWindowCmd::None,
             };
+
+            // Other windows are just canvases in the page, so they don't have a title.
+            if let Some(title) = window.window_set_title.take() {
+                if window_id == 0 {
+                    self.platform.zerde_eventloop_msgs.set_document_title(&title);
+                }
+            }
+            // Icons, size limits and decorations only apply to native windows.
+            window.window_set_icon = None;
+            window.window_style_changed = false;
+        }
+        for window_id in closed_window_ids {
+            self.windows_free.push(window_id);
+            self.wasm_event_handler(Event::WindowClosed(WindowClosedEvent { window_id }));
+        }
+
+        if self.pointer_lock_requested != self.pointer_locked {
+            self.platform.zerde_eventloop_msgs.set_pointer_lock(self.pointer_lock_requested);
+            self.pointer_locked = self.pointer_lock_requested;
         }
 
         // check if we need to send a cursor
-        if !self.down_mouse_cursor.is_none() {
-            self.platform.zerde_eventloop_msgs.set_mouse_cursor(self.down_mouse_cursor.as_ref().unwrap().clone())
-        } else if !self.hover_mouse_cursor.is_none() {
-            self.platform.zerde_eventloop_msgs.set_mouse_cursor(self.hover_mouse_cursor.as_ref().unwrap().clone())
-        } else {
-            self.platform.zerde_eventloop_msgs.set_mouse_cursor(MouseCursor::Default);
+        match self.current_mouse_cursor() {
+            MouseCursor::Custom(custom_cursor) => {
+                if self.platform.custom_cursors_sent.insert(custom_cursor.id) {
+                    self.platform.zerde_eventloop_msgs.define_custom_mouse_cursor(&custom_cursor);
+                }
+                self.platform.zerde_eventloop_msgs.set_custom_mouse_cursor(custom_cursor.id);
+            }
+            mouse_cursor => self.platform.zerde_eventloop_msgs.set_mouse_cursor(mouse_cursor),
         }
 
         let mut passes_todo = Vec::new();
@@ -480,16 +591,25 @@ impl Cx {
             self.webgl_compile_shaders(&mut zerde_webgl);
             for pass_id in &passes_todo {
                 match self.passes[*pass_id].dep_of.clone() {
-                    CxPassDepOf::Window(_) => {
+                    CxPassDepOf::Window(window_id) => {
                         // find the accompanying render window
                         // its a render window
                         windows_need_repaint -= 1;
-                        let dpi_factor = self.platform.window_geom.dpi_factor;
-                        self.draw_pass_to_canvas(*pass_id, dpi_factor, &mut zerde_webgl);
+                        let dpi_factor = if window_id == 0 {
+                            self.platform.window_geom.dpi_factor
+                        } else {
+                            self.windows[window_id].window_geom.dpi_factor
+                        };
+                        self.draw_pass_to_canvas(*pass_id, window_id, dpi_factor, &mut zerde_webgl);
                     }
                     CxPassDepOf::Pass(parent_pass_id) => {
                         let dpi_factor = self.get_delegated_dpi_factor(parent_pass_id);
-                        self.draw_pass_to_texture(*pass_id, dpi_factor, &mut zerde_webgl);
+                        if self.passes[*pass_id].xr_stereo && !self.platform.xr_views.is_empty() {
+                            let views = self.platform.xr_views.clone();
+                            self.draw_pass_to_xr(*pass_id, dpi_factor, &views, &mut zerde_webgl);
+                        } else {
+                            self.draw_pass_to_texture(*pass_id, dpi_factor, &mut zerde_webgl);
+                        }
                     }
                     CxPassDepOf::None => {
                         self.draw_pass_to_texture(*pass_id, 1.0, &mut zerde_webgl);
@@ -593,6 +713,12 @@ impl CxDesktopVsWasmCommon for Cx {
         params.insert(0, format!("{}", callback_id).into_param());
         self.call_js("_zaplibReturnParams", params);
     }
+
+    /// See [`CxDesktopVsWasmCommon::stream_to_js`] for documentation.
+    fn stream_to_js(&mut self, callback_id: u32, mut params: Vec<ZapParam>) {
+        params.insert(0, format!("{}", callback_id).into_param());
+        self.call_js("_zaplibStreamChunk", params);
+    }
 }
 
 impl CxPlatformCommon for Cx {
@@ -620,22 +746,24 @@ impl CxPlatformCommon for Cx {
 
     /// See [`CxPlatformCommon::start_timer`] for documentation.
     fn start_timer(&mut self, interval: f64, repeats: bool) -> Timer {
-        self.last_timer_id += 1;
-        self.platform.zerde_eventloop_msgs.start_timer(self.last_timer_id, interval, repeats);
-        Timer { timer_id: self.last_timer_id }
+        let timer_id = self.new_timer_id(repeats);
+        self.platform.zerde_eventloop_msgs.start_timer(timer_id, interval, repeats);
+        Timer { timer_id }
     }
 
     /// See [`CxPlatformCommon::stop_timer`] for documentation.
     fn stop_timer(&mut self, timer: &mut Timer) {
-        if timer.timer_id != 0 {
-            self.platform.zerde_eventloop_msgs.stop_timer(timer.timer_id);
-            timer.timer_id = 0;
+        if let Some(timer_id) = self.take_active_timer_id(timer) {
+            self.platform.zerde_eventloop_msgs.stop_timer(timer_id);
         }
     }
 
     /// See [`CxPlatformCommon::update_menu`] for documentation.
     fn update_menu(&mut self, _menu: &Menu) {}
 
+    /// See [`CxPlatformCommon::update_tray`] for documentation.
+    fn update_tray(&mut self, _tray: Option<&Tray>) {}
+
     /// See [`CxPlatformCommon::update_menu`] for documentation.
     fn copy_text_to_clipboard(&mut self, text: &str) {
         self.platform.zerde_eventloop_msgs.text_copy_response(text);
@@ -658,6 +786,27 @@ fn get_mouse_button(button: usize) -> MouseButton {
     };
 }
 
+/// A pose from WebXR: the orientation as a quaternion (x, y, z, w), followed by the position.
+fn parse_xr_transform(zerde_parser: &mut ZerdeParser) -> Transform {
+    Transform {
+        orientation: Quat {
+            a: zerde_parser.parse_f32(),
+            b: zerde_parser.parse_f32(),
+            c: zerde_parser.parse_f32(),
+            d: zerde_parser.parse_f32(),
+        },
+        position: Vec3 { x: zerde_parser.parse_f32(), y: zerde_parser.parse_f32(), z: zerde_parser.parse_f32() },
+    }
+}
+
+fn parse_xr_mat4(zerde_parser: &mut ZerdeParser) -> Mat4 {
+    let mut mat4 = Mat4::default();
+    for value in &mut mat4.v {
+        *value = zerde_parser.parse_f32();
+    }
+    mat4
+}
+
 // storage buffers for graphics API related platform
 pub(crate) struct CxPlatform {
     pub(crate) is_initialized: bool,
@@ -666,10 +815,15 @@ pub(crate) struct CxPlatform {
     pub(crate) vertex_buffers: usize,
     pub(crate) index_buffers: usize,
     pub(crate) vaos: usize,
+    pub(crate) instance_buffer_pool: InstanceBufferPool,
     pub(crate) pointers_down: Vec<bool>,
+    /// The [`CustomCursor::id`]s that we sent using `define_custom_mouse_cursor`.
+    custom_cursors_sent: HashSet<u64>,
     call_rust_sync_fn: UnsafeCell<Option<CallRustSyncFn>>,
-    // pub(crate) xr_last_left_input: XRInput,
-    // pub(crate) xr_last_right_input: XRInput,
+    /// The [`XrUpdateEvent::views`] of the latest frame of the headset, or empty when not presenting.
+    xr_views: Vec<XrView>,
+    /// The [`XrUpdateEvent::inputs`] of the latest frame of the headset.
+    xr_last_inputs: Vec<XrInput>,
 }
 
 impl Default for CxPlatform {
@@ -681,10 +835,12 @@ impl Default for CxPlatform {
             vertex_buffers: 0,
             index_buffers: 0,
             vaos: 0,
+            instance_buffer_pool: InstanceBufferPool::default(),
             pointers_down: Vec::new(),
+            custom_cursors_sent: HashSet::new(),
             call_rust_sync_fn: UnsafeCell::new(None),
-            // xr_last_left_input: XRInput::default(),
-            // xr_last_right_input: XRInput::default(),
+            xr_views: Vec::new(),
+            xr_last_inputs: Vec::new(),
         }
     }
 }
@@ -732,7 +888,7 @@ impl ZerdeEventloopMsgs {
 
     pub(crate) fn set_mouse_cursor(&mut self, mouse_cursor: MouseCursor) {
         self.builder.send_u32(5);
-        self.builder.send_u32(mouse_cursor as u32);
+        self.builder.send_u32(mouse_cursor.cursor_map_index() as u32);
     }
 
     pub(crate) fn show_text_ime(&mut self, x: f32, y: f32) {
@@ -816,6 +972,42 @@ impl ZerdeEventloopMsgs {
 
         self.builder.build_zap_params(params);
     }
+
+    pub(crate) fn attach_window_canvas(&mut self, window_id: usize, canvas_id: &str, inner_size: Vec2, position: Vec2) {
+        self.builder.send_u32(19);
+        self.builder.send_u32(window_id as u32);
+        self.builder.send_string(canvas_id);
+        self.builder.send_f32(inner_size.x);
+        self.builder.send_f32(inner_size.y);
+        self.builder.send_f32(position.x);
+        self.builder.send_f32(position.y);
+    }
+
+    pub(crate) fn detach_window_canvas(&mut self, window_id: usize) {
+        self.builder.send_u32(20);
+        self.builder.send_u32(window_id as u32);
+    }
+
+    /// Send the image of a [`CustomCursor`], so that [`ZerdeEventloopMsgs::set_custom_mouse_cursor`] can refer to it.
+    pub(crate) fn define_custom_mouse_cursor(&mut self, custom_cursor: &CustomCursor) {
+        self.builder.send_u32(21);
+        self.builder.send_f64(custom_cursor.id as f64);
+        self.builder.send_u32(custom_cursor.width as u32);
+        self.builder.send_u32(custom_cursor.height as u32);
+        self.builder.send_f32(custom_cursor.hotspot.x);
+        self.builder.send_f32(custom_cursor.hotspot.y);
+        self.builder.send_u8slice(&custom_cursor.rgba);
+    }
+
+    pub(crate) fn set_custom_mouse_cursor(&mut self, id: u64) {
+        self.builder.send_u32(22);
+        self.builder.send_f64(id as f64);
+    }
+
+    pub(crate) fn set_pointer_lock(&mut self, locked: bool) {
+        self.builder.send_u32(23);
+        self.builder.send_u32(if locked { 1 } else { 0 });
+    }
 }
 
 // for use with sending wasm vec data
@@ -881,14 +1073,43 @@ pub unsafe extern "C" fn decrement_arc(arc_ptr: u64) {
     Arc::decrement_strong_count(arc_ptr as usize as *const Vec<u8>);
 }
 
+/// Create the flag behind a [`CallRustCancelToken`]. JS holds on to this reference until the call has
+/// finished, so it can safely write to the flag until then.
+#[export_name = "createCancelToken"]
+pub extern "C" fn create_cancel_token() -> u64 {
+    Arc::into_raw(Arc::new(AtomicU32::new(0))) as u64
+}
+
+/// Drop JS's reference to a flag created with [`create_cancel_token`].
+///
+/// # Safety
+///
+/// `ptr` must come from [`create_cancel_token`], and can't be used anymore after this.
+#[export_name = "dropCancelToken"]
+pub unsafe extern "C" fn drop_cancel_token(ptr: u64) {
+    Arc::decrement_strong_count(ptr as usize as *const AtomicU32);
+}
+
 #[export_name = "deallocVec"]
 pub unsafe extern "C" fn dealloc_vec(vec_ptr: u64, vec_len: u64, vec_cap: u64) {
     let vec: Vec<u8> = Vec::from_raw_parts(vec_ptr as *mut u8, vec_len as usize, vec_cap as usize);
     drop(vec);
 }
 
+/// Called from JS with a filter string like `"info,zaplib=debug"`; see [`set_log_filter`].
+///
+/// # Safety
+///
+/// `vec_ptr` must point to `vec_len` bytes allocated with [`alloc_wasm_vec`], and ownership is passed to Rust.
+#[export_name = "setLogFilter"]
+pub unsafe extern "C" fn set_log_filter_from_js(vec_ptr: u64, vec_len: u64) {
+    let vec: Vec<u8> = Vec::from_raw_parts(vec_ptr as *mut u8, vec_len as usize, vec_len as usize);
+    set_log_filter(&String::from_utf8_lossy(&vec));
+}
+
 extern "C" {
     fn _consoleLog(chars: u64, len: u64);
+    fn _consoleLogWithLevel(level: u32, chars: u64, len: u64);
     fn _throwError(chars: u64, len: u64);
     pub fn performanceNow() -> f64;
     fn _sendEventFromAnyThread(event_ptr: u64);
@@ -901,6 +1122,14 @@ pub fn console_log(val: &str) {
     }
 }
 
+/// Log to the `console.*` method that corresponds to `level`. See [`init_logger`].
+pub(crate) fn console_log_with_level(level: log::Level, val: &str) {
+    unsafe {
+        let chars = val.chars().collect::<Vec<char>>();
+        _consoleLogWithLevel(level as u32, chars.as_ptr() as u64, chars.len() as u64);
+    }
+}
+
 pub fn throw_error(val: &str) {
     unsafe {
         let chars = val.chars().collect::<Vec<char>>();
@@ -0,0 +1,2637 @@
+//! Wayland API bindings.
+//!
+//! Like [`crate::cx_xlib`] this talks to the display server directly, without a toolkit. We load `libwayland-client`,
+//! `libwayland-egl`, `libwayland-cursor`, `libEGL` and `libxkbcommon` at runtime using `dlopen`, so that the same binary
+//! still runs on systems without Wayland; see `Cx::event_loop` for how we pick between Wayland and X11.
+//!
+//! `libwayland-client` only contains the core protocol, so the other protocols that we use (xdg-shell, xdg-decoration,
+//! viewporter and fractional-scale) are defined at the bottom of this file. We don't use the generated listener
+//! structs, but a single dispatcher function for all objects (see [`WaylandObject`]).
+
+#![allow(non_camel_case_types)]
+
+use crate::cx_xlib::{get_mouse_button_from_digit, keysym_to_keycode, LINUX_CUSTOM_WINDOW_CHROME};
+use crate::*;
+use std::collections::{BTreeSet, HashMap};
+use std::ffi::{CStr, CString};
+use std::os::raw::{c_char, c_int, c_uint, c_ulong, c_void};
+use std::ptr;
+use std::sync::Mutex;
+use time::precise_time_ns;
+
+static mut GLOBAL_WAYLAND_APP: *mut WaylandApp = ptr::null_mut();
+
+#[repr(C)]
+pub(crate) struct wl_message {
+    name: *const c_char,
+    signature: *const c_char,
+    types: *const *const wl_interface,
+}
+
+#[repr(C)]
+pub(crate) struct wl_interface {
+    name: *const c_char,
+    version: c_int,
+    method_count: c_int,
+    methods: *const wl_message,
+    event_count: c_int,
+    events: *const wl_message,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+union wl_argument {
+    i: i32,
+    u: u32,
+    f: i32,
+    s: *const c_char,
+    o: *mut c_void,
+    n: u32,
+    a: *mut wl_array,
+    h: i32,
+}
+
+#[repr(C)]
+struct wl_array {
+    size: usize,
+    alloc: usize,
+    data: *mut c_void,
+}
+
+#[repr(C)]
+struct wl_cursor_image {
+    width: u32,
+    height: u32,
+    hotspot_x: u32,
+    hotspot_y: u32,
+    delay: u32,
+}
+
+#[repr(C)]
+struct wl_cursor {
+    image_count: c_uint,
+    images: *mut *mut wl_cursor_image,
+    name: *mut c_char,
+}
+
+/// All Wayland objects are proxies on the client side.
+type wl_proxy = c_void;
+
+type wl_dispatcher_func_t = unsafe extern "C" fn(*const c_void, *mut wl_proxy, u32, *const wl_message, *mut wl_argument) -> c_int;
+
+const EGL_NONE: i32 = 0x3038;
+const EGL_ALPHA_SIZE: i32 = 0x3021;
+const EGL_BLUE_SIZE: i32 = 0x3022;
+const EGL_GREEN_SIZE: i32 = 0x3023;
+const EGL_RED_SIZE: i32 = 0x3024;
+const EGL_DEPTH_SIZE: i32 = 0x3025;
+const EGL_SURFACE_TYPE: i32 = 0x3033;
+const EGL_RENDERABLE_TYPE: i32 = 0x3040;
+const EGL_CONTEXT_MAJOR_VERSION: i32 = 0x3098;
+const EGL_WINDOW_BIT: i32 = 0x0004;
+const EGL_OPENGL_ES3_BIT: i32 = 0x0040;
+const EGL_OPENGL_ES_API: c_uint = 0x30A0;
+
+const XKB_KEYMAP_FORMAT_TEXT_V1: c_int = 1;
+const XKB_STATE_MODS_EFFECTIVE: c_int = 1 << 3;
+
+/// Functions (and protocol interfaces) that we use from the Wayland, EGL and xkbcommon libraries.
+struct WaylandLibs {
+    wl_display_connect: unsafe extern "C" fn(*const c_char) -> *mut wl_proxy,
+    wl_display_disconnect: unsafe extern "C" fn(*mut wl_proxy),
+    wl_display_get_fd: unsafe extern "C" fn(*mut wl_proxy) -> c_int,
+    wl_display_roundtrip: unsafe extern "C" fn(*mut wl_proxy) -> c_int,
+    wl_display_flush: unsafe extern "C" fn(*mut wl_proxy) -> c_int,
+    wl_display_prepare_read: unsafe extern "C" fn(*mut wl_proxy) -> c_int,
+    wl_display_read_events: unsafe extern "C" fn(*mut wl_proxy) -> c_int,
+    wl_display_cancel_read: unsafe extern "C" fn(*mut wl_proxy),
+    wl_display_dispatch_pending: unsafe extern "C" fn(*mut wl_proxy) -> c_int,
+    wl_proxy_marshal_array: unsafe extern "C" fn(*mut wl_proxy, u32, *mut wl_argument),
+    wl_proxy_marshal_array_constructor_versioned:
+        unsafe extern "C" fn(*mut wl_proxy, u32, *mut wl_argument, *const wl_interface, u32) -> *mut wl_proxy,
+    wl_proxy_add_dispatcher: unsafe extern "C" fn(*mut wl_proxy, wl_dispatcher_func_t, *const c_void, *mut c_void) -> c_int,
+    wl_proxy_destroy: unsafe extern "C" fn(*mut wl_proxy),
+    wl_proxy_get_version: unsafe extern "C" fn(*mut wl_proxy) -> u32,
+    wl_proxy_get_user_data: unsafe extern "C" fn(*mut wl_proxy) -> *mut c_void,
+
+    wl_registry_interface: *const wl_interface,
+    wl_compositor_interface: *const wl_interface,
+    wl_surface_interface: *const wl_interface,
+    wl_callback_interface: *const wl_interface,
+    wl_seat_interface: *const wl_interface,
+    wl_pointer_interface: *const wl_interface,
+    wl_keyboard_interface: *const wl_interface,
+    wl_output_interface: *const wl_interface,
+    wl_shm_interface: *const wl_interface,
+    wl_shm_pool_interface: *const wl_interface,
+    wl_buffer_interface: *const wl_interface,
+    wl_data_device_manager_interface: *const wl_interface,
+    wl_data_device_interface: *const wl_interface,
+    wl_data_source_interface: *const wl_interface,
+
+    wl_egl_window_create: unsafe extern "C" fn(*mut wl_proxy, c_int, c_int) -> *mut c_void,
+    wl_egl_window_resize: unsafe extern "C" fn(*mut c_void, c_int, c_int, c_int, c_int),
+    wl_egl_window_destroy: unsafe extern "C" fn(*mut c_void),
+
+    wl_cursor_theme_load: unsafe extern "C" fn(*const c_char, c_int, *mut wl_proxy) -> *mut c_void,
+    wl_cursor_theme_destroy: unsafe extern "C" fn(*mut c_void),
+    wl_cursor_theme_get_cursor: unsafe extern "C" fn(*mut c_void, *const c_char) -> *mut wl_cursor,
+    wl_cursor_image_get_buffer: unsafe extern "C" fn(*mut wl_cursor_image) -> *mut wl_proxy,
+
+    xkb_context_new: unsafe extern "C" fn(c_int) -> *mut c_void,
+    xkb_keymap_new_from_string: unsafe extern "C" fn(*mut c_void, *const c_char, c_int, c_int) -> *mut c_void,
+    xkb_keymap_unref: unsafe extern "C" fn(*mut c_void),
+    xkb_keymap_key_repeats: unsafe extern "C" fn(*mut c_void, u32) -> c_int,
+    xkb_state_new: unsafe extern "C" fn(*mut c_void) -> *mut c_void,
+    xkb_state_unref: unsafe extern "C" fn(*mut c_void),
+    xkb_state_update_mask: unsafe extern "C" fn(*mut c_void, u32, u32, u32, u32, u32, u32) -> c_int,
+    xkb_state_key_get_one_sym: unsafe extern "C" fn(*mut c_void, u32) -> u32,
+    xkb_state_key_get_utf8: unsafe extern "C" fn(*mut c_void, u32, *mut c_char, usize) -> c_int,
+    xkb_state_mod_name_is_active: unsafe extern "C" fn(*mut c_void, *const c_char, c_int) -> c_int,
+
+    egl_get_display: unsafe extern "C" fn(*mut c_void) -> *mut c_void,
+    egl_initialize: unsafe extern "C" fn(*mut c_void, *mut i32, *mut i32) -> c_uint,
+    egl_bind_api: unsafe extern "C" fn(c_uint) -> c_uint,
+    egl_choose_config: unsafe extern "C" fn(*mut c_void, *const i32, *mut *mut c_void, i32, *mut i32) -> c_uint,
+    egl_create_context: unsafe extern "C" fn(*mut c_void, *mut c_void, *mut c_void, *const i32) -> *mut c_void,
+    egl_create_window_surface: unsafe extern "C" fn(*mut c_void, *mut c_void, *mut c_void, *const i32) -> *mut c_void,
+    egl_destroy_surface: unsafe extern "C" fn(*mut c_void, *mut c_void) -> c_uint,
+    egl_make_current: unsafe extern "C" fn(*mut c_void, *mut c_void, *mut c_void, *mut c_void) -> c_uint,
+    egl_swap_buffers: unsafe extern "C" fn(*mut c_void, *mut c_void) -> c_uint,
+    egl_swap_interval: unsafe extern "C" fn(*mut c_void, i32) -> c_uint,
+    egl_get_proc_address: unsafe extern "C" fn(*const c_char) -> *const c_void,
+}
+
+// The interface pointers point to constant data in the libraries.
+unsafe impl Send for WaylandLibs {}
+unsafe impl Sync for WaylandLibs {}
+
+static WAYLAND_LIBS: once_cell::sync::OnceCell<Result<WaylandLibs, String>> = once_cell::sync::OnceCell::new();
+
+impl WaylandLibs {
+    fn get() -> Result<&'static WaylandLibs, String> {
+        WAYLAND_LIBS.get_or_init(|| unsafe { Self::load() }).as_ref().map_err(Clone::clone)
+    }
+
+    unsafe fn load() -> Result<WaylandLibs, String> {
+        // Never closed, since we keep the function pointers around.
+        let open = |name: &str| {
+            let lib_name = CString::new(name).unwrap();
+            let lib = libc::dlopen(lib_name.as_ptr(), libc::RTLD_NOW);
+            if lib.is_null() {
+                Err(format!("Could not load {}", name))
+            } else {
+                Ok(lib)
+            }
+        };
+        let client = open("libwayland-client.so.0")?;
+        let egl_window = open("libwayland-egl.so.1")?;
+        let cursor = open("libwayland-cursor.so.0")?;
+        let xkb = open("libxkbcommon.so.0")?;
+        let egl = open("libEGL.so.1")?;
+        // Symbols that aren't snake case (like the EGL functions) are passed in as a string.
+        macro_rules! sym {
+            ($lib: ident, $name: ident) => {
+                sym!($lib, stringify!($name))
+            };
+            ($lib: ident, $name: expr) => {{
+                let name: &str = $name;
+                let c_name = CString::new(name).unwrap();
+                let ptr = libc::dlsym($lib, c_name.as_ptr());
+                if ptr.is_null() {
+                    return Err(format!("Could not find {}", name));
+                }
+                // Function pointers have the same size as data pointers on the platforms we support.
+                std::mem::transmute_copy(&ptr)
+            }};
+        }
+        Ok(WaylandLibs {
+            wl_display_connect: sym!(client, wl_display_connect),
+            wl_display_disconnect: sym!(client, wl_display_disconnect),
+            wl_display_get_fd: sym!(client, wl_display_get_fd),
+            wl_display_roundtrip: sym!(client, wl_display_roundtrip),
+            wl_display_flush: sym!(client, wl_display_flush),
+            wl_display_prepare_read: sym!(client, wl_display_prepare_read),
+            wl_display_read_events: sym!(client, wl_display_read_events),
+            wl_display_cancel_read: sym!(client, wl_display_cancel_read),
+            wl_display_dispatch_pending: sym!(client, wl_display_dispatch_pending),
+            wl_proxy_marshal_array: sym!(client, wl_proxy_marshal_array),
+            wl_proxy_marshal_array_constructor_versioned: sym!(client, wl_proxy_marshal_array_constructor_versioned),
+            wl_proxy_add_dispatcher: sym!(client, wl_proxy_add_dispatcher),
+            wl_proxy_destroy: sym!(client, wl_proxy_destroy),
+            wl_proxy_get_version: sym!(client, wl_proxy_get_version),
+            wl_proxy_get_user_data: sym!(client, wl_proxy_get_user_data),
+            wl_registry_interface: sym!(client, wl_registry_interface),
+            wl_compositor_interface: sym!(client, wl_compositor_interface),
+            wl_surface_interface: sym!(client, wl_surface_interface),
+            wl_callback_interface: sym!(client, wl_callback_interface),
+            wl_seat_interface: sym!(client, wl_seat_interface),
+            wl_pointer_interface: sym!(client, wl_pointer_interface),
+            wl_keyboard_interface: sym!(client, wl_keyboard_interface),
+            wl_output_interface: sym!(client, wl_output_interface),
+            wl_shm_interface: sym!(client, wl_shm_interface),
+            wl_shm_pool_interface: sym!(client, wl_shm_pool_interface),
+            wl_buffer_interface: sym!(client, wl_buffer_interface),
+            wl_data_device_manager_interface: sym!(client, wl_data_device_manager_interface),
+            wl_data_device_interface: sym!(client, wl_data_device_interface),
+            wl_data_source_interface: sym!(client, wl_data_source_interface),
+            wl_egl_window_create: sym!(egl_window, wl_egl_window_create),
+            wl_egl_window_resize: sym!(egl_window, wl_egl_window_resize),
+            wl_egl_window_destroy: sym!(egl_window, wl_egl_window_destroy),
+            wl_cursor_theme_load: sym!(cursor, wl_cursor_theme_load),
+            wl_cursor_theme_destroy: sym!(cursor, wl_cursor_theme_destroy),
+            wl_cursor_theme_get_cursor: sym!(cursor, wl_cursor_theme_get_cursor),
+            wl_cursor_image_get_buffer: sym!(cursor, wl_cursor_image_get_buffer),
+            xkb_context_new: sym!(xkb, xkb_context_new),
+            xkb_keymap_new_from_string: sym!(xkb, xkb_keymap_new_from_string),
+            xkb_keymap_unref: sym!(xkb, xkb_keymap_unref),
+            xkb_keymap_key_repeats: sym!(xkb, xkb_keymap_key_repeats),
+            xkb_state_new: sym!(xkb, xkb_state_new),
+            xkb_state_unref: sym!(xkb, xkb_state_unref),
+            xkb_state_update_mask: sym!(xkb, xkb_state_update_mask),
+            xkb_state_key_get_one_sym: sym!(xkb, xkb_state_key_get_one_sym),
+            xkb_state_key_get_utf8: sym!(xkb, xkb_state_key_get_utf8),
+            xkb_state_mod_name_is_active: sym!(xkb, xkb_state_mod_name_is_active),
+            egl_get_display: sym!(egl, "eglGetDisplay"),
+            egl_initialize: sym!(egl, "eglInitialize"),
+            egl_bind_api: sym!(egl, "eglBindAPI"),
+            egl_choose_config: sym!(egl, "eglChooseConfig"),
+            egl_create_context: sym!(egl, "eglCreateContext"),
+            egl_create_window_surface: sym!(egl, "eglCreateWindowSurface"),
+            egl_destroy_surface: sym!(egl, "eglDestroySurface"),
+            egl_make_current: sym!(egl, "eglMakeCurrent"),
+            egl_swap_buffers: sym!(egl, "eglSwapBuffers"),
+            egl_swap_interval: sym!(egl, "eglSwapInterval"),
+            egl_get_proc_address: sym!(egl, "eglGetProcAddress"),
+        })
+    }
+
+    /// Send a request that doesn't create a new object.
+    unsafe fn request(&self, proxy: *mut wl_proxy, opcode: u32, args: &mut [wl_argument]) {
+        (self.wl_proxy_marshal_array)(proxy, opcode, args.as_mut_ptr());
+    }
+
+    /// Send a request that creates a new object, which gets the same version as `proxy`. `args` has to contain a
+    /// placeholder for the new object, which gets filled in by libwayland.
+    unsafe fn request_new(
+        &self,
+        proxy: *mut wl_proxy,
+        opcode: u32,
+        args: &mut [wl_argument],
+        interface: *const wl_interface,
+        object: &'static WaylandObject,
+        user_data: usize,
+    ) -> *mut wl_proxy {
+        let version = (self.wl_proxy_get_version)(proxy);
+        let new_proxy = (self.wl_proxy_marshal_array_constructor_versioned)(proxy, opcode, args.as_mut_ptr(), interface, version);
+        self.add_dispatcher(new_proxy, object, user_data);
+        new_proxy
+    }
+
+    unsafe fn add_dispatcher(&self, proxy: *mut wl_proxy, object: &'static WaylandObject, user_data: usize) {
+        if !proxy.is_null() {
+            (self.wl_proxy_add_dispatcher)(
+                proxy,
+                dispatch_event,
+                object as *const WaylandObject as *const c_void,
+                user_data as *mut c_void,
+            );
+        }
+    }
+
+    /// Send a `destroy` request (if the interface has one) and destroy the proxy.
+    unsafe fn destroy(&self, proxy: *mut wl_proxy, destroy_opcode: Option<u32>) {
+        if !proxy.is_null() {
+            if let Some(opcode) = destroy_opcode {
+                self.request(proxy, opcode, &mut []);
+            }
+            (self.wl_proxy_destroy)(proxy);
+        }
+    }
+}
+
+fn arg_i(i: i32) -> wl_argument {
+    wl_argument { i }
+}
+
+fn arg_u(u: u32) -> wl_argument {
+    wl_argument { u }
+}
+
+fn arg_o(o: *mut wl_proxy) -> wl_argument {
+    wl_argument { o }
+}
+
+fn arg_s(s: &CStr) -> wl_argument {
+    wl_argument { s: s.as_ptr() }
+}
+
+fn arg_h(h: c_int) -> wl_argument {
+    wl_argument { h }
+}
+
+/// Placeholder for the object created by a request.
+fn arg_new() -> wl_argument {
+    wl_argument { o: ptr::null_mut() }
+}
+
+/// Convert a `wl_fixed_t` (24.8 fixed point) to a float.
+fn fixed_to_f32(fixed: i32) -> f32 {
+    fixed as f32 / 256.
+}
+
+/// The kinds of objects that we receive events for, which we pass as the "implementation" of
+/// [`dispatch_event`]. For objects that belong to a window, the user data of the proxy is the `window_id`.
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum WaylandObject {
+    Registry,
+    WmBase,
+    Seat,
+    Pointer,
+    Keyboard,
+    Output,
+    Surface,
+    XdgSurface,
+    XdgToplevel,
+    Decoration,
+    FractionalScale,
+    FrameCallback,
+    LockedPointer,
+    RelativePointer,
+    DataDevice,
+    DataOffer,
+    DataSource,
+    /// Objects that we don't need any events from.
+    Ignored,
+}
+
+unsafe extern "C" fn dispatch_event(
+    implementation: *const c_void,
+    proxy: *mut wl_proxy,
+    opcode: u32,
+    _message: *const wl_message,
+    args: *mut wl_argument,
+) -> c_int {
+    let object = *(implementation as *const WaylandObject);
+    let wayland_app = &mut (*GLOBAL_WAYLAND_APP);
+    wayland_app.handle_event(object, proxy, opcode, args);
+    0
+}
+
+/// Opcodes of the requests that we send.
+const WL_DISPLAY_GET_REGISTRY: u32 = 1;
+const WL_REGISTRY_BIND: u32 = 0;
+const WL_COMPOSITOR_CREATE_SURFACE: u32 = 0;
+const WL_SURFACE_DESTROY: u32 = 0;
+const WL_SURFACE_ATTACH: u32 = 1;
+const WL_SURFACE_DAMAGE: u32 = 2;
+const WL_SURFACE_FRAME: u32 = 3;
+const WL_SURFACE_COMMIT: u32 = 6;
+const WL_SURFACE_SET_BUFFER_SCALE: u32 = 8;
+const WL_SEAT_GET_POINTER: u32 = 0;
+const WL_SEAT_GET_KEYBOARD: u32 = 1;
+const WL_POINTER_SET_CURSOR: u32 = 0;
+const WL_SHM_CREATE_POOL: u32 = 0;
+const WL_SHM_POOL_CREATE_BUFFER: u32 = 0;
+const WL_SHM_POOL_DESTROY: u32 = 1;
+const WL_DATA_DEVICE_MANAGER_CREATE_DATA_SOURCE: u32 = 0;
+const WL_DATA_DEVICE_MANAGER_GET_DATA_DEVICE: u32 = 1;
+const WL_DATA_DEVICE_SET_SELECTION: u32 = 1;
+const WL_DATA_OFFER_RECEIVE: u32 = 1;
+const WL_DATA_OFFER_DESTROY: u32 = 2;
+const WL_DATA_SOURCE_OFFER: u32 = 0;
+const WL_DATA_SOURCE_DESTROY: u32 = 1;
+const XDG_WM_BASE_GET_XDG_SURFACE: u32 = 2;
+const XDG_WM_BASE_PONG: u32 = 3;
+const XDG_SURFACE_DESTROY: u32 = 0;
+const XDG_SURFACE_GET_TOPLEVEL: u32 = 1;
+const XDG_SURFACE_ACK_CONFIGURE: u32 = 4;
+const XDG_TOPLEVEL_DESTROY: u32 = 0;
+const XDG_TOPLEVEL_SET_TITLE: u32 = 2;
+const XDG_TOPLEVEL_SET_APP_ID: u32 = 3;
+const XDG_TOPLEVEL_MOVE: u32 = 5;
+const XDG_TOPLEVEL_RESIZE: u32 = 6;
+const XDG_TOPLEVEL_SET_MAX_SIZE: u32 = 7;
+const XDG_TOPLEVEL_SET_MIN_SIZE: u32 = 8;
+const XDG_TOPLEVEL_SET_MAXIMIZED: u32 = 9;
+const XDG_TOPLEVEL_UNSET_MAXIMIZED: u32 = 10;
+const XDG_TOPLEVEL_SET_FULLSCREEN: u32 = 11;
+const XDG_TOPLEVEL_UNSET_FULLSCREEN: u32 = 12;
+const XDG_TOPLEVEL_SET_MINIMIZED: u32 = 13;
+const ZXDG_DECORATION_MANAGER_V1_GET_TOPLEVEL_DECORATION: u32 = 1;
+const ZXDG_TOPLEVEL_DECORATION_V1_DESTROY: u32 = 0;
+const ZXDG_TOPLEVEL_DECORATION_V1_SET_MODE: u32 = 1;
+const WP_VIEWPORTER_GET_VIEWPORT: u32 = 1;
+const WP_VIEWPORT_DESTROY: u32 = 0;
+const WP_VIEWPORT_SET_DESTINATION: u32 = 2;
+const WP_FRACTIONAL_SCALE_MANAGER_V1_GET_FRACTIONAL_SCALE: u32 = 1;
+const WP_FRACTIONAL_SCALE_V1_DESTROY: u32 = 0;
+const ZWP_POINTER_CONSTRAINTS_V1_LOCK_POINTER: u32 = 1;
+const ZWP_LOCKED_POINTER_V1_DESTROY: u32 = 0;
+const ZWP_RELATIVE_POINTER_MANAGER_V1_GET_RELATIVE_POINTER: u32 = 1;
+
+const WL_SEAT_CAPABILITY_POINTER: u32 = 1;
+const WL_SEAT_CAPABILITY_KEYBOARD: u32 = 2;
+const WL_OUTPUT_MODE_CURRENT: u32 = 1;
+const WL_SHM_FORMAT_ARGB8888: u32 = 0;
+const ZWP_POINTER_CONSTRAINTS_V1_LIFETIME_ONESHOT: u32 = 1;
+const WL_POINTER_AXIS_SOURCE_FINGER: u32 = 1;
+const WL_POINTER_AXIS_SOURCE_CONTINUOUS: u32 = 2;
+/// The first version of `wl_pointer` that groups axis events using `wl_pointer.frame`.
+const WL_POINTER_FRAME_SINCE_VERSION: u32 = 5;
+/// How far compositors typically scroll for a wheel step, in `wl_pointer.axis` values.
+const WL_POINTER_AXIS_VALUE_PER_STEP: f32 = 10.;
+const XDG_TOPLEVEL_STATE_MAXIMIZED: u32 = 1;
+const XDG_TOPLEVEL_STATE_FULLSCREEN: u32 = 2;
+const ZXDG_TOPLEVEL_DECORATION_V1_MODE_CLIENT_SIDE: u32 = 1;
+const ZXDG_TOPLEVEL_DECORATION_V1_MODE_SERVER_SIDE: u32 = 2;
+
+/// Edges for `xdg_toplevel.resize`.
+const XDG_TOPLEVEL_RESIZE_EDGE_TOP: u32 = 1;
+const XDG_TOPLEVEL_RESIZE_EDGE_BOTTOM: u32 = 2;
+const XDG_TOPLEVEL_RESIZE_EDGE_LEFT: u32 = 4;
+const XDG_TOPLEVEL_RESIZE_EDGE_TOP_LEFT: u32 = 5;
+const XDG_TOPLEVEL_RESIZE_EDGE_BOTTOM_LEFT: u32 = 6;
+const XDG_TOPLEVEL_RESIZE_EDGE_RIGHT: u32 = 8;
+const XDG_TOPLEVEL_RESIZE_EDGE_TOP_RIGHT: u32 = 9;
+const XDG_TOPLEVEL_RESIZE_EDGE_BOTTOM_RIGHT: u32 = 10;
+
+/// Linux input event codes for mouse buttons, as used by `wl_pointer.button`.
+const BTN_LEFT: u32 = 0x110;
+const BTN_RIGHT: u32 = 0x111;
+const BTN_MIDDLE: u32 = 0x112;
+
+/// Mime types that we offer and accept for the clipboard, in order of preference.
+const TEXT_MIME_TYPES: &[&str] = &["text/plain;charset=utf-8", "UTF8_STRING", "text/plain"];
+
+type WaylandEventCallback = dyn FnMut(&mut WaylandApp, &mut Vec<Event>) -> bool;
+
+pub(crate) struct WaylandApp {
+    libs: &'static WaylandLibs,
+    pub(crate) display: *mut wl_proxy,
+    display_fd: c_int,
+    /// Written to by [`WaylandApp::send_event_from_any_thread`] to wake up the event loop.
+    signal_pipe: [c_int; 2],
+    registry: *mut wl_proxy,
+    compositor: *mut wl_proxy,
+    wm_base: *mut wl_proxy,
+    shm: *mut wl_proxy,
+    seat: *mut wl_proxy,
+    pointer: *mut wl_proxy,
+    keyboard: *mut wl_proxy,
+    data_device_manager: *mut wl_proxy,
+    data_device: *mut wl_proxy,
+    decoration_manager: *mut wl_proxy,
+    viewporter: *mut wl_proxy,
+    fractional_scale_manager: *mut wl_proxy,
+    pointer_constraints: *mut wl_proxy,
+    /// Our `zwp_locked_pointer_v1`, while we want the pointer locked; see [`WaylandApp::set_pointer_lock`].
+    locked_pointer: *mut wl_proxy,
+    relative_pointer_manager: *mut wl_proxy,
+    /// Our `zwp_relative_pointer_v1`, which keeps sending [`Event::PointerRawMove`] while the pointer is locked; see
+    /// [`WaylandApp::create_relative_pointer`].
+    relative_pointer: *mut wl_proxy,
+    outputs: Vec<WaylandOutput>,
+    /// See [`WaylandApp::update_displays`].
+    pub(crate) displays: Vec<DisplayInfo>,
+    pub(crate) windows: Vec<WaylandWindow>,
+
+    pub(crate) time_start: u64,
+    pub(crate) event_callback: Option<*mut WaylandEventCallback>,
+    pub(crate) event_recur_block: bool,
+    pub(crate) event_loop_running: bool,
+    pub(crate) loop_block: bool,
+    timers: Vec<WaylandTimer>,
+    pub(crate) signals: Mutex<Vec<Event>>,
+    /// Events that we can't send right away because we're already in a callback, e.g. when closing a window.
+    pending_events: Vec<Event>,
+    /// The serial of the last input event, which we need for the clipboard and for moving windows.
+    last_serial: u32,
+
+    pointer_window_id: Option<usize>,
+    pointer_enter_serial: u32,
+    scroll_frame: WaylandScrollFrame,
+    last_click_time: f64,
+    last_click_pos: Vec2,
+    current_cursor: MouseCursor,
+    cursor_theme: *mut c_void,
+    cursor_theme_scale: i32,
+    cursor_surface: *mut wl_proxy,
+    /// Buffers with the images of [`MouseCursor::Custom`] cursors, by [`CustomCursor::id`].
+    custom_cursor_buffers: HashMap<u64, *mut wl_proxy>,
+
+    xkb_context: *mut c_void,
+    xkb_keymap: *mut c_void,
+    xkb_state: *mut c_void,
+    modifiers: KeyModifiers,
+    /// Key repeat rate (per second) and delay (in seconds), from `wl_keyboard.repeat_info`.
+    repeat_rate: f64,
+    repeat_delay: f64,
+    key_repeat: Option<WaylandKeyRepeat>,
+
+    clipboard: String,
+    /// Our source for the clipboard, while we own it.
+    data_source: *mut wl_proxy,
+    /// Offers that we've been told about, with their mime types.
+    data_offers: Vec<(*mut wl_proxy, Vec<String>)>,
+    selection_offer: *mut wl_proxy,
+}
+
+pub(crate) struct WaylandWindow {
+    libs: &'static WaylandLibs,
+    pub(crate) window_id: usize,
+    surface: *mut wl_proxy,
+    xdg_surface: *mut wl_proxy,
+    xdg_toplevel: *mut wl_proxy,
+    decoration: *mut wl_proxy,
+    viewport: *mut wl_proxy,
+    fractional_scale: *mut wl_proxy,
+    egl_window: *mut c_void,
+    egl_display: *mut c_void,
+    pub(crate) egl_surface: *mut c_void,
+
+    /// Size in logical pixels.
+    inner_size: Vec2,
+    /// Size and states from the last `xdg_toplevel.configure`, applied when `xdg_surface.configure` arrives.
+    pending_size: Option<Vec2>,
+    pending_maximized: bool,
+    pending_fullscreen: bool,
+    is_maximized: bool,
+    is_fullscreen: bool,
+    /// We can't draw before the first `xdg_surface.configure`.
+    pub(crate) is_configured: bool,
+    /// Set while we wait for the compositor to tell us that it's a good time to draw a new frame.
+    pub(crate) frame_pending: bool,
+
+    /// Scale from `wp_fractional_scale_v1`, if supported.
+    preferred_fractional_scale: Option<f32>,
+    /// Scale from `wl_surface.preferred_buffer_scale`, if supported.
+    preferred_buffer_scale: Option<i32>,
+    /// Outputs that the surface is on, to compute the scale if neither of the above is supported.
+    entered_outputs: Vec<*mut wl_proxy>,
+    /// The integer scale that we last set using `wl_surface.set_buffer_scale`.
+    buffer_scale: i32,
+    /// The output to go fullscreen on, set by [`WaylandWindow::move_to_display`]. Null lets the compositor choose.
+    fullscreen_output: *mut wl_proxy,
+
+    pub(crate) window_geom: WindowGeom,
+    style: CxWindowStyle,
+    last_mouse_pos: Vec2,
+    pointers_down: Vec<bool>,
+    last_nc_mode: Option<WaylandNcMode>,
+}
+
+/// What we know about a `wl_output`. Apart from the scale, this only gets applied on `wl_output.done`.
+#[derive(Clone)]
+struct WaylandOutput {
+    /// The name of the global in the registry.
+    registry_name: u32,
+    proxy: *mut wl_proxy,
+    scale: i32,
+    /// From `wl_output.geometry`, in the compositor's global space.
+    position: Vec2,
+    /// From the current `wl_output.mode`, in physical pixels.
+    mode_size: Vec2,
+    /// In Hz, or 0 if unknown.
+    refresh_rate: f32,
+    /// From `wl_output.name`, e.g. "DP-1".
+    name: String,
+    /// Whether we got `wl_output.done` at least once.
+    is_done: bool,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum WaylandNcMode {
+    Move,
+    Resize(u32),
+}
+
+#[derive(Clone, Copy)]
+struct WaylandTimer {
+    id: u64,
+    interval: f64,
+    repeats: bool,
+    /// In seconds since [`WaylandApp::time_start`].
+    fire_time: f64,
+}
+
+/// The scrolling from the `wl_pointer` axis events since the last `wl_pointer.frame`, which groups them.
+#[derive(Clone, Copy, Default)]
+struct WaylandScrollFrame {
+    /// From `wl_pointer.axis`.
+    scroll: Vec2,
+    /// Wheel steps from `wl_pointer.axis_discrete`, which we use instead of [`WaylandScrollFrame::scroll`] when set.
+    steps: Vec2,
+    /// From the last `wl_pointer.axis_source`.
+    source: Option<u32>,
+    /// Whether we got a `wl_pointer.axis_stop`, which means that the fingers were lifted off the trackpad.
+    stopped: bool,
+}
+
+#[derive(Clone, Copy)]
+struct WaylandKeyRepeat {
+    key: u32,
+    /// In seconds since [`WaylandApp::time_start`].
+    fire_time: f64,
+}
+
+impl WaylandApp {
+    /// Whether we should try Wayland before X11. Set `ZAPLIB_BACKEND` to `x11` or `wayland` to override; by default we
+    /// use Wayland if `WAYLAND_DISPLAY` is set.
+    pub(crate) fn should_try_wayland() -> bool {
+        match std::env::var("ZAPLIB_BACKEND").as_deref() {
+            Ok("x11") => false,
+            Ok("wayland") => true,
+            _ => std::env::var("WAYLAND_DISPLAY").map_or(false, |display| !display.is_empty()),
+        }
+    }
+
+    /// Connect to the compositor. This is boxed since [`GLOBAL_WAYLAND_APP`] points to it while we receive events,
+    /// which already happens here.
+    pub(crate) fn new() -> Result<Box<WaylandApp>, String> {
+        let libs = WaylandLibs::get()?;
+        unsafe {
+            let display = (libs.wl_display_connect)(ptr::null());
+            if display.is_null() {
+                return Err("Could not connect to the Wayland compositor".to_string());
+            }
+            let mut signal_pipe = [0; 2];
+            libc::pipe2(signal_pipe.as_mut_ptr(), libc::O_CLOEXEC | libc::O_NONBLOCK);
+
+            let mut wayland_app = Box::new(WaylandApp {
+                libs,
+                display,
+                display_fd: (libs.wl_display_get_fd)(display),
+                signal_pipe,
+                registry: ptr::null_mut(),
+                compositor: ptr::null_mut(),
+                wm_base: ptr::null_mut(),
+                shm: ptr::null_mut(),
+                seat: ptr::null_mut(),
+                pointer: ptr::null_mut(),
+                keyboard: ptr::null_mut(),
+                data_device_manager: ptr::null_mut(),
+                data_device: ptr::null_mut(),
+                decoration_manager: ptr::null_mut(),
+                viewporter: ptr::null_mut(),
+                fractional_scale_manager: ptr::null_mut(),
+                pointer_constraints: ptr::null_mut(),
+                locked_pointer: ptr::null_mut(),
+                relative_pointer_manager: ptr::null_mut(),
+                relative_pointer: ptr::null_mut(),
+                outputs: Vec::new(),
+                displays: Vec::new(),
+                windows: Vec::new(),
+                time_start: precise_time_ns(),
+                event_callback: None,
+                event_recur_block: false,
+                event_loop_running: true,
+                loop_block: false,
+                timers: Vec::new(),
+                signals: Mutex::new(Vec::new()),
+                pending_events: Vec::new(),
+                last_serial: 0,
+                pointer_window_id: None,
+                pointer_enter_serial: 0,
+                scroll_frame: WaylandScrollFrame::default(),
+                last_click_time: 0.,
+                last_click_pos: Vec2::default(),
+                current_cursor: MouseCursor::Default,
+                cursor_theme: ptr::null_mut(),
+                cursor_theme_scale: 0,
+                cursor_surface: ptr::null_mut(),
+                custom_cursor_buffers: HashMap::new(),
+                xkb_context: (libs.xkb_context_new)(0),
+                xkb_keymap: ptr::null_mut(),
+                xkb_state: ptr::null_mut(),
+                modifiers: KeyModifiers::default(),
+                repeat_rate: 25.,
+                repeat_delay: 0.6,
+                key_repeat: None,
+                clipboard: String::new(),
+                data_source: ptr::null_mut(),
+                data_offers: Vec::new(),
+                selection_offer: ptr::null_mut(),
+            });
+            GLOBAL_WAYLAND_APP = &mut *wayland_app;
+
+            wayland_app.registry = libs.request_new(
+                display,
+                WL_DISPLAY_GET_REGISTRY,
+                &mut [arg_new()],
+                libs.wl_registry_interface,
+                &WaylandObject::Registry,
+                0,
+            );
+            // The first roundtrip gets us the globals, and the second one the events that we get after binding them,
+            // such as the capabilities of the seat.
+            (libs.wl_display_roundtrip)(display);
+            (libs.wl_display_roundtrip)(display);
+
+            // Dropping `wayland_app` disconnects again.
+            if wayland_app.compositor.is_null() || wayland_app.wm_base.is_null() {
+                return Err("The Wayland compositor doesn't support xdg-shell".to_string());
+            }
+            if !wayland_app.data_device_manager.is_null() && !wayland_app.seat.is_null() {
+                wayland_app.data_device = libs.request_new(
+                    wayland_app.data_device_manager,
+                    WL_DATA_DEVICE_MANAGER_GET_DATA_DEVICE,
+                    &mut [arg_new(), arg_o(wayland_app.seat)],
+                    libs.wl_data_device_interface,
+                    &WaylandObject::DataDevice,
+                    0,
+                );
+            }
+            Ok(wayland_app)
+        }
+    }
+
+    /// Whether we're using Wayland, as opposed to X11.
+    pub(crate) fn is_running() -> bool {
+        unsafe { !GLOBAL_WAYLAND_APP.is_null() }
+    }
+
+    pub(crate) fn event_loop<F>(&mut self, mut event_handler: F)
+    where
+        F: FnMut(&mut WaylandApp, &mut Vec<Event>) -> bool,
+    {
+        unsafe {
+            self.event_callback = Some(
+                &mut event_handler as *const dyn FnMut(&mut WaylandApp, &mut Vec<Event>) -> bool
+                    as *mut dyn FnMut(&mut WaylandApp, &mut Vec<Event>) -> bool,
+            );
+
+            self.do_callback(&mut vec![Event::System(SystemEvent::Paint)]);
+
+            while self.event_loop_running {
+                let libs = self.libs;
+                // Events might already have been read while sending requests; we have to dispatch those first.
+                while (libs.wl_display_prepare_read)(self.display) != 0 {
+                    (libs.wl_display_dispatch_pending)(self.display);
+                }
+                (libs.wl_display_flush)(self.display);
+
+                // If we want to draw but all windows are still waiting for their frame callback, there is nothing to do
+                // until the compositor tells us that it's time to draw again. That's how we get vsync.
+                let nothing_to_draw = self.windows.iter().all(|window| window.frame_pending || !window.is_configured);
+                let timeout = if self.loop_block || nothing_to_draw {
+                    self.next_fire_time().map_or(-1, |fire_time| ((fire_time - self.time_now()).max(0.) * 1000.).ceil() as c_int)
+                } else {
+                    0
+                };
+                let mut fds = [
+                    libc::pollfd { fd: self.display_fd, events: libc::POLLIN, revents: 0 },
+                    libc::pollfd { fd: self.signal_pipe[0], events: libc::POLLIN, revents: 0 },
+                ];
+                libc::poll(fds.as_mut_ptr(), fds.len() as libc::nfds_t, timeout);
+
+                if fds[0].revents & libc::POLLIN != 0 {
+                    (libs.wl_display_read_events)(self.display);
+                } else {
+                    (libs.wl_display_cancel_read)(self.display);
+                }
+                if (libs.wl_display_dispatch_pending)(self.display) < 0 {
+                    log!("Lost the connection to the Wayland compositor");
+                    self.event_loop_running = false;
+                    break;
+                }
+                if fds[1].revents & libc::POLLIN != 0 {
+                    let mut buffer = [0u8; 64];
+                    while libc::read(self.signal_pipe[0], buffer.as_mut_ptr() as *mut c_void, buffer.len()) > 0 {}
+                }
+
+                self.fire_timers();
+
+                let mut pending_events = std::mem::take(&mut self.pending_events);
+                if !pending_events.is_empty() {
+                    self.do_callback(&mut pending_events);
+                }
+
+                // process all signals in the queue
+                let mut proc_signals = if let Ok(mut signals) = self.signals.lock() {
+                    let sigs = signals.clone();
+                    signals.truncate(0);
+                    sigs
+                } else {
+                    Vec::new()
+                };
+                if !proc_signals.is_empty() {
+                    self.do_callback(&mut proc_signals);
+                }
+
+                self.do_callback(&mut vec![Event::System(SystemEvent::Paint)]);
+            }
+
+            self.event_callback = None;
+        }
+    }
+
+    pub(crate) fn do_callback(&mut self, events: &mut Vec<Event>) {
+        unsafe {
+            if self.event_callback.is_none() || self.event_recur_block {
+                return;
+            };
+            self.event_recur_block = true;
+            let callback = self.event_callback.unwrap();
+            self.loop_block = (*callback)(self, events);
+            self.event_recur_block = false;
+        }
+    }
+
+    pub(crate) fn time_now(&self) -> f64 {
+        let time_now = precise_time_ns();
+        (time_now - self.time_start) as f64 / 1_000_000_000.0
+    }
+
+    pub(crate) fn start_timer(&mut self, id: u64, interval: f64, repeats: bool) {
+        let fire_time = self.time_now() + interval;
+        self.timers.push(WaylandTimer { id, interval, repeats, fire_time });
+    }
+
+    pub(crate) fn stop_timer(&mut self, id: u64) {
+        self.timers.retain(|timer| timer.id != id);
+    }
+
+    /// The time at which the next timer or key repeat should fire, if any.
+    fn next_fire_time(&self) -> Option<f64> {
+        self.timers
+            .iter()
+            .map(|timer| timer.fire_time)
+            .chain(self.key_repeat.map(|key_repeat| key_repeat.fire_time))
+            .reduce(f64::min)
+    }
+
+    fn fire_timers(&mut self) {
+        let time_now = self.time_now();
+        let mut fired_timer_ids = Vec::new();
+        for timer in &mut self.timers {
+            if timer.fire_time <= time_now {
+                fired_timer_ids.push(timer.id);
+                // Don't fire a repeating timer multiple times in a row if we fell behind.
+                timer.fire_time = (timer.fire_time + timer.interval).max(time_now);
+            }
+        }
+        self.timers.retain(|timer| timer.repeats || !fired_timer_ids.contains(&timer.id));
+        for timer_id in fired_timer_ids {
+            self.do_callback(&mut vec![Event::Timer(TimerEvent { timer_id })]);
+        }
+
+        if let Some(key_repeat) = &mut self.key_repeat {
+            if key_repeat.fire_time <= time_now {
+                key_repeat.fire_time = (key_repeat.fire_time + 1. / self.repeat_rate).max(time_now);
+                let key = key_repeat.key;
+                self.send_key_events(key, true, true);
+            }
+        }
+    }
+
+    pub(crate) fn post_signal(signal: Signal, status: StatusId) {
+        let mut signals = HashMap::new();
+        let mut set = BTreeSet::new();
+        set.insert(status);
+        signals.insert(signal, set);
+        WaylandApp::send_event_from_any_thread(Event::Signal(SignalEvent { signals }));
+    }
+
+    pub(crate) fn send_event_from_any_thread(event: Event) {
+        unsafe {
+            // No event loop (e.g. in tests), so there is no one to send the event to.
+            if GLOBAL_WAYLAND_APP.is_null() {
+                return;
+            }
+            if let Ok(mut signals_locked) = (*GLOBAL_WAYLAND_APP).signals.lock() {
+                signals_locked.push(event);
+            }
+            // Wake up the event loop.
+            libc::write((*GLOBAL_WAYLAND_APP).signal_pipe[1], [0u8].as_ptr() as *const c_void, 1);
+        }
+    }
+
+    pub(crate) fn terminate_event_loop(&mut self) {
+        self.event_loop_running = false;
+    }
+
+    fn window_mut(&mut self, window_id: usize) -> Option<&mut WaylandWindow> {
+        self.windows.iter_mut().find(|window| window.window_id == window_id)
+    }
+
+    fn window_id_for_surface(&self, surface: *mut wl_proxy) -> Option<usize> {
+        self.windows.iter().find(|window| window.surface == surface).map(|window| window.window_id)
+    }
+
+    /// Handle an event from the compositor; see [`dispatch_event`].
+    unsafe fn handle_event(&mut self, object: WaylandObject, proxy: *mut wl_proxy, opcode: u32, args: *mut wl_argument) {
+        let libs = self.libs;
+        let arg = |index: usize| *args.add(index);
+        let user_data = (libs.wl_proxy_get_user_data)(proxy) as usize;
+        match (object, opcode) {
+            // wl_registry.global
+            (WaylandObject::Registry, 0) => {
+                let name = arg(0).u;
+                let interface = CStr::from_ptr(arg(1).s).to_str().unwrap_or("");
+                let version = arg(2).u;
+                self.bind_global(name, interface, version);
+            }
+            // wl_registry.global_remove
+            (WaylandObject::Registry, 1) => {
+                let name = arg(0).u;
+                if let Some(index) = self.outputs.iter().position(|output| output.registry_name == name) {
+                    let output = self.outputs.remove(index).proxy;
+                    for window in &mut self.windows {
+                        window.entered_outputs.retain(|entered_output| *entered_output != output);
+                        if window.fullscreen_output == output {
+                            window.fullscreen_output = ptr::null_mut();
+                        }
+                    }
+                    libs.destroy(output, None);
+                    self.send_displays_changed_event();
+                }
+            }
+            // xdg_wm_base.ping
+            (WaylandObject::WmBase, 0) => {
+                libs.request(self.wm_base, XDG_WM_BASE_PONG, &mut [arg_u(arg(0).u)]);
+            }
+            // wl_seat.capabilities
+            (WaylandObject::Seat, 0) => {
+                let capabilities = arg(0).u;
+                if capabilities & WL_SEAT_CAPABILITY_POINTER != 0 && self.pointer.is_null() {
+                    self.pointer = libs.request_new(
+                        self.seat,
+                        WL_SEAT_GET_POINTER,
+                        &mut [arg_new()],
+                        libs.wl_pointer_interface,
+                        &WaylandObject::Pointer,
+                        0,
+                    );
+                    self.create_relative_pointer();
+                }
+                if capabilities & WL_SEAT_CAPABILITY_KEYBOARD != 0 && self.keyboard.is_null() {
+                    self.keyboard = libs.request_new(
+                        self.seat,
+                        WL_SEAT_GET_KEYBOARD,
+                        &mut [arg_new()],
+                        libs.wl_keyboard_interface,
+                        &WaylandObject::Keyboard,
+                        0,
+                    );
+                }
+            }
+            // wl_output.geometry
+            (WaylandObject::Output, 0) => {
+                if let Some(output) = self.outputs.iter_mut().find(|output| output.proxy == proxy) {
+                    output.position = vec2(arg(0).i as f32, arg(1).i as f32);
+                }
+            }
+            // wl_output.mode
+            (WaylandObject::Output, 1) => {
+                let (flags, width, height, refresh) = (arg(0).u, arg(1).i, arg(2).i, arg(3).i);
+                if let Some(output) = self.outputs.iter_mut().find(|output| output.proxy == proxy) {
+                    // Only the current mode matters; the others are just what the output supports.
+                    if flags & WL_OUTPUT_MODE_CURRENT != 0 {
+                        output.mode_size = vec2(width as f32, height as f32);
+                        output.refresh_rate = refresh as f32 / 1000.;
+                    }
+                }
+            }
+            // wl_output.done
+            (WaylandObject::Output, 2) => {
+                if let Some(output) = self.outputs.iter_mut().find(|output| output.proxy == proxy) {
+                    output.is_done = true;
+                }
+                self.send_displays_changed_event();
+            }
+            // wl_output.scale
+            (WaylandObject::Output, 3) => {
+                let scale = arg(0).i;
+                if let Some(output) = self.outputs.iter_mut().find(|output| output.proxy == proxy) {
+                    output.scale = scale;
+                }
+                self.update_window_scales();
+            }
+            // wl_output.name
+            (WaylandObject::Output, 4) => {
+                let name = CStr::from_ptr(arg(0).s).to_string_lossy().into_owned();
+                if let Some(output) = self.outputs.iter_mut().find(|output| output.proxy == proxy) {
+                    output.name = name;
+                }
+            }
+            // wl_surface.enter
+            (WaylandObject::Surface, 0) => {
+                let output = arg(0).o;
+                if let Some(window) = self.window_mut(user_data) {
+                    window.entered_outputs.push(output);
+                }
+                self.update_window_scales();
+            }
+            // wl_surface.leave
+            (WaylandObject::Surface, 1) => {
+                let output = arg(0).o;
+                if let Some(window) = self.window_mut(user_data) {
+                    window.entered_outputs.retain(|entered_output| *entered_output != output);
+                }
+                self.update_window_scales();
+            }
+            // wl_surface.preferred_buffer_scale
+            (WaylandObject::Surface, 2) => {
+                let scale = arg(0).i;
+                if let Some(window) = self.window_mut(user_data) {
+                    window.preferred_buffer_scale = Some(scale);
+                }
+                self.update_window_scales();
+            }
+            // wp_fractional_scale_v1.preferred_scale
+            (WaylandObject::FractionalScale, 0) => {
+                let scale = arg(0).u as f32 / 120.;
+                if let Some(window) = self.window_mut(user_data) {
+                    window.preferred_fractional_scale = Some(scale);
+                }
+                self.update_window_scales();
+            }
+            // xdg_surface.configure
+            (WaylandObject::XdgSurface, 0) => {
+                libs.request(proxy, XDG_SURFACE_ACK_CONFIGURE, &mut [arg_u(arg(0).u)]);
+                if let Some(window) = self.window_mut(user_data) {
+                    if let Some(size) = window.pending_size.take() {
+                        window.inner_size = size;
+                    }
+                    window.is_maximized = window.pending_maximized;
+                    window.is_fullscreen = window.pending_fullscreen;
+                    window.is_configured = true;
+                    window.apply_size_and_scale();
+                }
+                self.send_change_event(user_data);
+            }
+            // xdg_toplevel.configure
+            (WaylandObject::XdgToplevel, 0) => {
+                let (width, height) = (arg(0).i, arg(1).i);
+                let states = &*arg(2).a;
+                let states = std::slice::from_raw_parts(states.data as *const u32, states.size / 4);
+                if let Some(window) = self.window_mut(user_data) {
+                    // A size of 0 means that we can pick the size ourselves.
+                    if width > 0 && height > 0 {
+                        window.pending_size = Some(Vec2 { x: width as f32, y: height as f32 });
+                    }
+                    window.pending_maximized = states.contains(&XDG_TOPLEVEL_STATE_MAXIMIZED);
+                    window.pending_fullscreen = states.contains(&XDG_TOPLEVEL_STATE_FULLSCREEN);
+                }
+            }
+            // xdg_toplevel.close
+            (WaylandObject::XdgToplevel, 1) => {
+                let mut events =
+                    vec![Event::WindowCloseRequested(WindowCloseRequestedEvent { window_id: user_data, accept_close: true })];
+                self.do_callback(&mut events);
+                if let Event::WindowCloseRequested(cre) = &events[0] {
+                    if cre.accept_close {
+                        self.close_window(user_data);
+                    }
+                }
+            }
+            // wl_callback.done
+            (WaylandObject::FrameCallback, 0) => {
+                libs.destroy(proxy, None);
+                if let Some(window) = self.window_mut(user_data) {
+                    window.frame_pending = false;
+                }
+            }
+            // wl_pointer.enter
+            (WaylandObject::Pointer, 0) => {
+                self.pointer_enter_serial = arg(0).u;
+                self.pointer_window_id = self.window_id_for_surface(arg(1).o);
+                self.update_cursor();
+                self.handle_pointer_motion(Vec2 { x: fixed_to_f32(arg(2).f), y: fixed_to_f32(arg(3).f) });
+            }
+            // wl_pointer.leave
+            (WaylandObject::Pointer, 1) => {
+                if let Some(window_id) = self.pointer_window_id.take() {
+                    let last_mouse_pos = self.window_mut(window_id).map_or(Vec2::default(), |window| window.last_mouse_pos);
+                    self.do_callback(&mut vec![Event::PointerHover(PointerHoverEvent {
+                        digit: 0,
+                        window_id,
+                        any_down: false,
+                        abs: last_mouse_pos,
+                        rel: last_mouse_pos,
+                        rect: Rect::default(),
+                        handled: false,
+                        hover_state: HoverState::Out,
+                        modifiers: KeyModifiers::default(),
+                        time: self.time_now(),
+                    })]);
+                }
+            }
+            // wl_pointer.motion
+            (WaylandObject::Pointer, 2) => {
+                let pos = Vec2 { x: fixed_to_f32(arg(1).f), y: fixed_to_f32(arg(2).f) };
+                // Without relative pointer support, we can only get raw motion from within the window.
+                if self.relative_pointer.is_null() {
+                    if let Some(window_id) = self.pointer_window_id {
+                        let last_mouse_pos = self.window_mut(window_id).map_or(pos, |window| window.last_mouse_pos);
+                        self.send_pointer_raw_move(window_id, pos - last_mouse_pos);
+                    }
+                }
+                self.handle_pointer_motion(pos);
+            }
+            // wl_pointer.button
+            (WaylandObject::Pointer, 3) => {
+                self.last_serial = arg(0).u;
+                self.handle_pointer_button(arg(2).u, arg(3).u == 1);
+            }
+            // wl_pointer.axis
+            (WaylandObject::Pointer, 4) => {
+                let value = fixed_to_f32(arg(2).f);
+                // Axis 0 is vertical, and 1 is horizontal.
+                if arg(1).u == 0 {
+                    self.scroll_frame.scroll.y += value;
+                } else {
+                    self.scroll_frame.scroll.x += value;
+                }
+                if (libs.wl_proxy_get_version)(proxy) < WL_POINTER_FRAME_SINCE_VERSION {
+                    self.send_scroll_frame();
+                }
+            }
+            // wl_pointer.frame
+            (WaylandObject::Pointer, 5) => self.send_scroll_frame(),
+            // wl_pointer.axis_source
+            (WaylandObject::Pointer, 6) => self.scroll_frame.source = Some(arg(0).u),
+            // wl_pointer.axis_stop
+            (WaylandObject::Pointer, 7) => self.scroll_frame.stopped = true,
+            // wl_pointer.axis_discrete
+            (WaylandObject::Pointer, 8) => {
+                let steps = arg(1).i as f32;
+                if arg(0).u == 0 {
+                    self.scroll_frame.steps.y += steps;
+                } else {
+                    self.scroll_frame.steps.x += steps;
+                }
+            }
+            // wl_keyboard.keymap
+            (WaylandObject::Keyboard, 0) => {
+                let (format, fd, size) = (arg(0).u, arg(1).h, arg(2).u as usize);
+                if format == XKB_KEYMAP_FORMAT_TEXT_V1 as u32 {
+                    let map = libc::mmap(ptr::null_mut(), size, libc::PROT_READ, libc::MAP_PRIVATE, fd, 0);
+                    if map != libc::MAP_FAILED {
+                        let keymap = (libs.xkb_keymap_new_from_string)(
+                            self.xkb_context,
+                            map as *const c_char,
+                            XKB_KEYMAP_FORMAT_TEXT_V1,
+                            0,
+                        );
+                        libc::munmap(map, size);
+                        if !keymap.is_null() {
+                            if !self.xkb_state.is_null() {
+                                (libs.xkb_state_unref)(self.xkb_state);
+                                (libs.xkb_keymap_unref)(self.xkb_keymap);
+                            }
+                            self.xkb_keymap = keymap;
+                            self.xkb_state = (libs.xkb_state_new)(keymap);
+                        }
+                    }
+                }
+                libc::close(fd);
+            }
+            // wl_keyboard.enter
+            (WaylandObject::Keyboard, 1) => {
+                self.do_callback(&mut vec![Event::AppFocus]);
+            }
+            // wl_keyboard.leave
+            (WaylandObject::Keyboard, 2) => {
+                self.key_repeat = None;
+                self.do_callback(&mut vec![Event::AppFocusLost]);
+            }
+            // wl_keyboard.key
+            (WaylandObject::Keyboard, 3) => {
+                self.last_serial = arg(0).u;
+                let (key, pressed) = (arg(2).u, arg(3).u == 1);
+                if pressed {
+                    if !self.xkb_keymap.is_null()
+                        && (libs.xkb_keymap_key_repeats)(self.xkb_keymap, key + 8) != 0
+                        && self.repeat_rate > 0.
+                    {
+                        self.key_repeat = Some(WaylandKeyRepeat { key, fire_time: self.time_now() + self.repeat_delay });
+                    }
+                } else if matches!(self.key_repeat, Some(key_repeat) if key_repeat.key == key) {
+                    self.key_repeat = None;
+                }
+                self.send_key_events(key, pressed, false);
+            }
+            // wl_keyboard.modifiers
+            (WaylandObject::Keyboard, 4) if !self.xkb_state.is_null() => {
+                (libs.xkb_state_update_mask)(self.xkb_state, arg(1).u, arg(2).u, arg(3).u, 0, 0, arg(4).u);
+                let is_active = |name: &[u8]| {
+                    (libs.xkb_state_mod_name_is_active)(self.xkb_state, name.as_ptr() as *const c_char, XKB_STATE_MODS_EFFECTIVE)
+                        > 0
+                };
+                self.modifiers = KeyModifiers {
+                    shift: is_active(b"Shift\0"),
+                    control: is_active(b"Control\0"),
+                    alt: is_active(b"Mod1\0"),
+                    logo: is_active(b"Mod4\0"),
+                };
+            }
+            // wl_keyboard.repeat_info
+            (WaylandObject::Keyboard, 5) => {
+                self.repeat_rate = arg(0).i as f64;
+                self.repeat_delay = arg(1).i as f64 / 1000.;
+            }
+            // wl_data_device.data_offer
+            (WaylandObject::DataDevice, 0) => {
+                let offer = arg(0).o;
+                libs.add_dispatcher(offer, &WaylandObject::DataOffer, 0);
+                self.data_offers.push((offer, Vec::new()));
+            }
+            // wl_data_device.enter; we don't support dropping onto windows, so we don't need drag-and-drop offers.
+            (WaylandObject::DataDevice, 1) => {
+                let offer = arg(4).o;
+                if !offer.is_null() {
+                    self.destroy_data_offer(offer);
+                }
+            }
+            // wl_data_device.selection
+            (WaylandObject::DataDevice, 5) => {
+                let offer = arg(0).o;
+                if self.selection_offer != offer && !self.selection_offer.is_null() {
+                    self.destroy_data_offer(self.selection_offer);
+                }
+                self.selection_offer = offer;
+            }
+            // wl_data_offer.offer
+            (WaylandObject::DataOffer, 0) => {
+                let mime_type = CStr::from_ptr(arg(0).s).to_string_lossy().into_owned();
+                if let Some((_, mime_types)) = self.data_offers.iter_mut().find(|(offer, _)| *offer == proxy) {
+                    mime_types.push(mime_type);
+                }
+            }
+            // wl_data_source.send
+            (WaylandObject::DataSource, 1) => {
+                let fd = arg(1).h;
+                let mut written = 0;
+                while written < self.clipboard.len() {
+                    let result =
+                        libc::write(fd, self.clipboard[written..].as_ptr() as *const c_void, self.clipboard.len() - written);
+                    if result <= 0 {
+                        break;
+                    }
+                    written += result as usize;
+                }
+                libc::close(fd);
+            }
+            // wl_data_source.cancelled, when another client took over the clipboard.
+            (WaylandObject::DataSource, 2) => {
+                libs.destroy(proxy, Some(WL_DATA_SOURCE_DESTROY));
+                if self.data_source == proxy {
+                    self.data_source = ptr::null_mut();
+                }
+            }
+            // zwp_locked_pointer_v1.unlocked, e.g. when the window loses focus. Since we use a oneshot lock, the lock
+            // is gone for good, so we report that.
+            // zwp_relative_pointer_v1.relative_motion
+            (WaylandObject::RelativePointer, 0) => {
+                if let Some(window_id) = self.pointer_window_id {
+                    // Use the unaccelerated motion, which is what games and 3d viewers want.
+                    self.send_pointer_raw_move(window_id, Vec2 { x: fixed_to_f32(arg(4).f), y: fixed_to_f32(arg(5).f) });
+                }
+            }
+            (WaylandObject::LockedPointer, 1) => {
+                libs.destroy(proxy, Some(ZWP_LOCKED_POINTER_V1_DESTROY));
+                if self.locked_pointer == proxy {
+                    self.locked_pointer = ptr::null_mut();
+                    self.do_callback(&mut vec![Event::PointerLockChanged(PointerLockChangedEvent { locked: false })]);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    unsafe fn bind_global(&mut self, name: u32, interface: &str, version: u32) {
+        let libs = self.libs;
+        // Never bind a higher version than libwayland knows about, since it would then get events it can't parse.
+        let bind = |interface: *const wl_interface, max_version: u32, object: &'static WaylandObject| {
+            let version = version.min(max_version).min((*interface).version as u32);
+            let proxy = (libs.wl_proxy_marshal_array_constructor_versioned)(
+                self.registry,
+                WL_REGISTRY_BIND,
+                [arg_u(name), arg_s(CStr::from_ptr((*interface).name)), arg_u(version), arg_new()].as_mut_ptr(),
+                interface,
+                version,
+            );
+            libs.add_dispatcher(proxy, object, 0);
+            proxy
+        };
+        match interface {
+            "wl_compositor" => self.compositor = bind(libs.wl_compositor_interface, 6, &WaylandObject::Ignored),
+            "wl_shm" => self.shm = bind(libs.wl_shm_interface, 1, &WaylandObject::Ignored),
+            "wl_seat" if self.seat.is_null() => self.seat = bind(libs.wl_seat_interface, 5, &WaylandObject::Seat),
+            "wl_output" => {
+                let proxy = bind(libs.wl_output_interface, 4, &WaylandObject::Output);
+                self.outputs.push(WaylandOutput {
+                    registry_name: name,
+                    proxy,
+                    scale: 1,
+                    position: Vec2::default(),
+                    mode_size: Vec2::default(),
+                    refresh_rate: 0.,
+                    name: String::new(),
+                    is_done: false,
+                });
+            }
+            "wl_data_device_manager" => {
+                self.data_device_manager = bind(libs.wl_data_device_manager_interface, 3, &WaylandObject::Ignored)
+            }
+            "xdg_wm_base" => self.wm_base = bind(&XDG_WM_BASE_INTERFACE.0, 2, &WaylandObject::WmBase),
+            "zxdg_decoration_manager_v1" => {
+                self.decoration_manager = bind(&ZXDG_DECORATION_MANAGER_V1_INTERFACE.0, 1, &WaylandObject::Ignored)
+            }
+            "wp_viewporter" => self.viewporter = bind(&WP_VIEWPORTER_INTERFACE.0, 1, &WaylandObject::Ignored),
+            "wp_fractional_scale_manager_v1" => {
+                self.fractional_scale_manager = bind(&WP_FRACTIONAL_SCALE_MANAGER_V1_INTERFACE.0, 1, &WaylandObject::Ignored)
+            }
+            "zwp_pointer_constraints_v1" => {
+                self.pointer_constraints = bind(&ZWP_POINTER_CONSTRAINTS_V1_INTERFACE.0, 1, &WaylandObject::Ignored)
+            }
+            "zwp_relative_pointer_manager_v1" => {
+                self.relative_pointer_manager = bind(&ZWP_RELATIVE_POINTER_MANAGER_V1_INTERFACE.0, 1, &WaylandObject::Ignored);
+                self.create_relative_pointer();
+            }
+            _ => {}
+        }
+    }
+
+    /// Build [`WaylandApp::displays`] from the outputs, in the order that the compositor announced them, since
+    /// Wayland has no notion of a primary output. Clients can't see panels, so the work area is the whole output.
+    /// Returns whether the displays changed.
+    fn update_displays(&mut self) -> bool {
+        let displays: Vec<DisplayInfo> = self
+            .outputs
+            .iter()
+            .filter(|output| output.is_done)
+            .enumerate()
+            .map(|(display_id, output)| {
+                let dpi_factor = output.scale.max(1) as f32;
+                let size = output.mode_size / dpi_factor;
+                DisplayInfo {
+                    display_id,
+                    name: output.name.clone(),
+                    position: output.position,
+                    size,
+                    work_area: Rect { pos: Vec2::default(), size },
+                    dpi_factor,
+                    refresh_rate: output.refresh_rate,
+                    is_primary: display_id == 0,
+                }
+            })
+            .collect();
+        if displays != self.displays {
+            self.displays = displays;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn send_displays_changed_event(&mut self) {
+        if self.update_displays() {
+            let displays = self.displays.clone();
+            self.do_callback(&mut vec![Event::DisplaysChanged(DisplaysChangedEvent { displays })]);
+        }
+    }
+
+    /// The `wl_output` of a display in [`WaylandApp::displays`].
+    pub(crate) fn display_output(&self, display_id: usize) -> Option<*mut wl_proxy> {
+        self.outputs.iter().filter(|output| output.is_done).nth(display_id).map(|output| output.proxy)
+    }
+
+    /// Recompute the scale of all windows, e.g. after they moved to another output, and send
+    /// [`Event::WindowGeomChange`] for the ones that changed.
+    fn update_window_scales(&mut self) {
+        let outputs = self.outputs.clone();
+        let mut changed_window_ids = Vec::new();
+        for window in &mut self.windows {
+            let output_scale = window
+                .entered_outputs
+                .iter()
+                .filter_map(|entered_output| outputs.iter().find(|output| output.proxy == *entered_output))
+                .map(|output| output.scale)
+                .max()
+                .unwrap_or(1);
+            let old_dpi_factor = window.dpi_factor();
+            let old_buffer_scale = window.buffer_scale;
+            window.buffer_scale = window.preferred_buffer_scale.unwrap_or(output_scale).max(1);
+            if window.is_configured && (window.dpi_factor() != old_dpi_factor || window.buffer_scale != old_buffer_scale) {
+                window.apply_size_and_scale();
+                changed_window_ids.push(window.window_id);
+            }
+        }
+        for window_id in changed_window_ids {
+            self.send_change_event(window_id);
+        }
+        if self.pointer_window_id.is_some() {
+            self.update_cursor();
+        }
+    }
+
+    fn send_change_event(&mut self, window_id: usize) {
+        let (old_geom, new_geom) = if let Some(window) = self.window_mut(window_id) {
+            let new_geom = window.get_window_geom();
+            let old_geom = std::mem::replace(&mut window.window_geom, new_geom.clone());
+            (old_geom, new_geom)
+        } else {
+            return;
+        };
+        self.do_callback(&mut vec![
+            Event::WindowGeomChange(WindowGeomChangeEvent { window_id, old_geom, new_geom }),
+            Event::System(SystemEvent::Paint),
+        ]);
+    }
+
+    /// Get relative motion for [`WaylandApp::pointer`], once we have both the pointer and the relative pointer
+    /// manager, which can come in either order.
+    fn create_relative_pointer(&mut self) {
+        if self.pointer.is_null() || self.relative_pointer_manager.is_null() || !self.relative_pointer.is_null() {
+            return;
+        }
+        self.relative_pointer = unsafe {
+            self.libs.request_new(
+                self.relative_pointer_manager,
+                ZWP_RELATIVE_POINTER_MANAGER_V1_GET_RELATIVE_POINTER,
+                &mut [arg_new(), arg_o(self.pointer)],
+                &ZWP_RELATIVE_POINTER_V1_INTERFACE.0,
+                &WaylandObject::RelativePointer,
+                0,
+            )
+        };
+    }
+
+    fn send_pointer_raw_move(&mut self, window_id: usize, delta: Vec2) {
+        if delta.x == 0. && delta.y == 0. {
+            return;
+        }
+        self.do_callback(&mut vec![Event::PointerRawMove(PointerRawMoveEvent {
+            window_id,
+            delta,
+            modifiers: self.modifiers.clone(),
+            time: self.time_now(),
+        })]);
+    }
+
+    /// Send the scrolling that we got since the last `wl_pointer.frame`, converting wheel steps to lines, and telling
+    /// [`Cx`] when the fingers are lifted off a trackpad so that it can continue with momentum, since Wayland doesn't.
+    fn send_scroll_frame(&mut self) {
+        let frame = std::mem::take(&mut self.scroll_frame);
+        // Not every frame has a `wl_pointer.axis_source`, e.g. the one with `wl_pointer.axis_stop`.
+        self.scroll_frame.source = frame.source;
+        let window_id = if let Some(window_id) = self.pointer_window_id { window_id } else { return };
+        let step = DEFAULT_LINES_PER_WHEEL_STEP * SCROLL_LINE_HEIGHT;
+        let (device, scroll) = match frame.source {
+            Some(WL_POINTER_AXIS_SOURCE_FINGER | WL_POINTER_AXIS_SOURCE_CONTINUOUS) => (ScrollDevice::Trackpad, frame.scroll),
+            _ => {
+                let pick = |steps: f32, value: f32| if steps != 0. { steps } else { value / WL_POINTER_AXIS_VALUE_PER_STEP };
+                let steps = Vec2 { x: pick(frame.steps.x, frame.scroll.x), y: pick(frame.steps.y, frame.scroll.y) };
+                (ScrollDevice::Wheel, steps * step)
+            }
+        };
+        let phase = if frame.stopped && frame.source == Some(WL_POINTER_AXIS_SOURCE_FINGER) {
+            ScrollPhase::Ended
+        } else if scroll.x != 0. || scroll.y != 0. {
+            ScrollPhase::Changed
+        } else {
+            return;
+        };
+        let last_mouse_pos = self.window_mut(window_id).map_or(Vec2::default(), |window| window.last_mouse_pos);
+        self.do_callback(&mut vec![Event::PointerScroll(PointerScrollEvent {
+            digit: 0,
+            window_id,
+            scroll: if phase == ScrollPhase::Ended { Vec2::default() } else { scroll },
+            pinch: 0.,
+            device,
+            phase,
+            abs: last_mouse_pos,
+            rel: last_mouse_pos,
+            rect: Rect::default(),
+            input_type: PointerInputType::Mouse,
+            modifiers: self.modifiers.clone(),
+            handled_x: false,
+            handled_y: false,
+            time: self.time_now(),
+        })]);
+    }
+
+    fn handle_pointer_motion(&mut self, pos: Vec2) {
+        let window_id = if let Some(window_id) = self.pointer_window_id { window_id } else { return };
+        let last_mouse_pos = if let Some(window) = self.window_mut(window_id) { window.last_mouse_pos } else { return };
+
+        // query window for chrome
+        let mut drag_query_events = vec![Event::WindowDragQuery(WindowDragQueryEvent {
+            window_id,
+            abs: last_mouse_pos,
+            response: WindowDragQueryResponse::NoAnswer,
+        })];
+        self.do_callback(&mut drag_query_events);
+        let caption_nc_mode = match &drag_query_events[0] {
+            Event::WindowDragQuery(WindowDragQueryEvent { response: WindowDragQueryResponse::Caption, .. }) => {
+                Some(WaylandNcMode::Move)
+            }
+            _ => None,
+        };
+
+        let modifiers = self.modifiers.clone();
+        let time = self.time_now();
+        let window = if let Some(window) = self.window_mut(window_id) { window } else { return };
+        window.last_mouse_pos = pos;
+        let mut events = Vec::new();
+        for (digit, down) in window.pointers_down.iter().enumerate() {
+            if *down {
+                events.push(Event::PointerMove(PointerMoveEvent {
+                    window_id,
+                    abs: pos,
+                    rel: pos,
+                    rect: Rect::default(),
+                    digit,
+                    abs_start: Vec2::default(),
+                    rel_start: Vec2::default(),
+                    is_over: false,
+                    input_type: PointerInputType::Mouse,
+                    modifiers: modifiers.clone(),
+                    time,
+                }));
+            }
+        }
+        events.push(Event::PointerHover(PointerHoverEvent {
+            digit: 0,
+            window_id,
+            abs: pos,
+            rel: pos,
+            any_down: false,
+            rect: Rect::default(),
+            handled: false,
+            hover_state: HoverState::Over,
+            modifiers,
+            time,
+        }));
+
+        // With custom window chrome we do the resizing ourselves, so check if we're hovering the window edges.
+        let resize_edge = if window.style.resizable && !window.style.borderless && LINUX_CUSTOM_WINDOW_CHROME {
+            resize_edge_at(pos, window.inner_size)
+        } else {
+            None
+        };
+        if let Some((edge, cursor)) = resize_edge {
+            window.last_nc_mode = Some(WaylandNcMode::Resize(edge));
+            events.push(Event::System(SystemEvent::WindowSetHoverCursor(cursor)));
+        } else {
+            window.last_nc_mode = caption_nc_mode;
+        }
+        self.do_callback(&mut events);
+    }
+
+    fn handle_pointer_button(&mut self, button: u32, pressed: bool) {
+        let window_id = if let Some(window_id) = self.pointer_window_id { window_id } else { return };
+        // Use the same digits as X11; see `get_mouse_button_from_digit`.
+        let digit = match button {
+            BTN_LEFT => 1,
+            BTN_MIDDLE => 2,
+            BTN_RIGHT => 3,
+            _ => return,
+        };
+        let time_now = self.time_now();
+        let modifiers = self.modifiers.clone();
+        let (libs, seat, last_serial) = (self.libs, self.seat, self.last_serial);
+        let (last_click_time, last_click_pos) = (self.last_click_time, self.last_click_pos);
+        let window = if let Some(window) = self.window_mut(window_id) { window } else { return };
+        let pos = window.last_mouse_pos;
+
+        let mut events = Vec::new();
+        if pressed {
+            match window.last_nc_mode {
+                Some(WaylandNcMode::Move)
+                    if time_now - last_click_time < 0.35
+                        && (pos.x - last_click_pos.x).abs() < 5.
+                        && (pos.y - last_click_pos.y).abs() < 5. =>
+                {
+                    if window.is_maximized {
+                        window.restore();
+                    } else {
+                        window.maximize();
+                    }
+                }
+                Some(WaylandNcMode::Move) => unsafe {
+                    libs.request(window.xdg_toplevel, XDG_TOPLEVEL_MOVE, &mut [arg_o(seat), arg_u(last_serial)]);
+                },
+                Some(WaylandNcMode::Resize(edge)) => unsafe {
+                    libs.request(window.xdg_toplevel, XDG_TOPLEVEL_RESIZE, &mut [arg_o(seat), arg_u(last_serial), arg_u(edge)]);
+                },
+                None => {
+                    window.pointers_down[digit] = true;
+                    events.push(Event::PointerDown(PointerDownEvent {
+                        window_id,
+                        abs: pos,
+                        rel: pos,
+                        rect: Rect::default(),
+                        digit,
+                        button: get_mouse_button_from_digit(digit),
+                        handled: false,
+                        input_type: PointerInputType::Mouse,
+                        modifiers,
+                        tap_count: 0,
+                        time: time_now,
+                    }));
+                }
+            }
+            self.last_click_time = time_now;
+            self.last_click_pos = pos;
+        } else {
+            window.pointers_down[digit] = false;
+            events.push(Event::PointerUp(PointerUpEvent {
+                window_id,
+                abs: pos,
+                rel: pos,
+                rect: Rect::default(),
+                abs_start: Vec2::default(),
+                rel_start: Vec2::default(),
+                digit,
+                button: get_mouse_button_from_digit(digit),
+                is_over: false,
+                input_type: PointerInputType::Mouse,
+                modifiers,
+                time: time_now,
+            }));
+        }
+        self.do_callback(&mut events);
+    }
+
+    fn send_key_events(&mut self, key: u32, pressed: bool, is_repeat: bool) {
+        if self.xkb_state.is_null() {
+            return;
+        }
+        let libs = self.libs;
+        // xkbcommon keycodes are evdev keycodes plus 8, for historical reasons.
+        let xkb_key = key + 8;
+        let keysym = unsafe { (libs.xkb_state_key_get_one_sym)(self.xkb_state, xkb_key) };
+        let key_code = keysym_to_keycode(keysym as c_ulong);
+        let modifiers = self.modifiers.clone();
+        let key_event = KeyEvent { key_code, is_repeat, modifiers: modifiers.clone(), time: self.time_now() };
+        if !pressed {
+            self.do_callback(&mut vec![Event::KeyUp(key_event)]);
+            return;
+        }
+
+        if (modifiers.control || modifiers.logo) && !is_repeat {
+            match key_code {
+                KeyCode::KeyV => self.paste(),
+                KeyCode::KeyX | KeyCode::KeyC => self.do_callback(&mut vec![Event::TextCopy]),
+                _ => {}
+            }
+        }
+        self.do_callback(&mut vec![Event::KeyDown(key_event)]);
+
+        if !(modifiers.control || modifiers.logo || modifiers.alt) {
+            let mut buffer = [0u8; 32];
+            let count = unsafe {
+                (libs.xkb_state_key_get_utf8)(self.xkb_state, xkb_key, buffer.as_mut_ptr() as *mut c_char, buffer.len())
+            };
+            let utf8 = std::str::from_utf8(&buffer[..(count.max(0) as usize).min(buffer.len())]).unwrap_or("").to_string();
+            let char_code = utf8.chars().next().unwrap_or('\0');
+            if char_code >= ' ' && char_code != 127 as char {
+                self.do_callback(&mut vec![Event::TextInput(TextInputEvent {
+                    input: utf8,
+                    was_paste: false,
+                    replace_last: false,
+                })]);
+            }
+        }
+    }
+
+    pub(crate) fn set_mouse_cursor(&mut self, cursor: MouseCursor) {
+        if self.current_cursor != cursor {
+            self.current_cursor = cursor;
+            self.update_cursor();
+        }
+    }
+
+    /// Set [`WaylandApp::current_cursor`] for the window that the pointer is in; Wayland resets it whenever the pointer
+    /// enters a window.
+    fn update_cursor(&mut self) {
+        let libs = self.libs;
+        if self.pointer.is_null() || self.shm.is_null() {
+            return;
+        }
+        let scale = self
+            .pointer_window_id
+            .and_then(|window_id| self.windows.iter().find(|window| window.window_id == window_id))
+            .map_or(1, |window| window.dpi_factor().ceil() as i32);
+        unsafe {
+            if self.current_cursor == MouseCursor::Hidden {
+                libs.request(
+                    self.pointer,
+                    WL_POINTER_SET_CURSOR,
+                    &mut [arg_u(self.pointer_enter_serial), arg_o(ptr::null_mut()), arg_i(0), arg_i(0)],
+                );
+                return;
+            }
+            if let MouseCursor::Custom(custom_cursor) = self.current_cursor.clone() {
+                self.set_custom_cursor(&custom_cursor);
+                return;
+            }
+            if self.cursor_theme.is_null() || self.cursor_theme_scale != scale {
+                if !self.cursor_theme.is_null() {
+                    (libs.wl_cursor_theme_destroy)(self.cursor_theme);
+                }
+                let size: c_int = std::env::var("XCURSOR_SIZE").ok().and_then(|size| size.parse().ok()).unwrap_or(24);
+                let theme_name = std::env::var("XCURSOR_THEME").ok().and_then(|name| CString::new(name).ok());
+                self.cursor_theme = (libs.wl_cursor_theme_load)(
+                    theme_name.as_ref().map_or(ptr::null(), |name| name.as_ptr()),
+                    size * scale,
+                    self.shm,
+                );
+                self.cursor_theme_scale = scale;
+            }
+            if self.cursor_surface.is_null() {
+                self.cursor_surface = libs.request_new(
+                    self.compositor,
+                    WL_COMPOSITOR_CREATE_SURFACE,
+                    &mut [arg_new()],
+                    libs.wl_surface_interface,
+                    &WaylandObject::Ignored,
+                    0,
+                );
+            }
+            if self.cursor_theme.is_null() {
+                return;
+            }
+            let cursor = xcursor_names(&self.current_cursor)
+                .iter()
+                .map(|name| (libs.wl_cursor_theme_get_cursor)(self.cursor_theme, name.as_ptr() as *const c_char))
+                .find(|cursor| !cursor.is_null() && (**cursor).image_count > 0);
+            if let Some(cursor) = cursor {
+                let image = *(*cursor).images;
+                let buffer = (libs.wl_cursor_image_get_buffer)(image);
+                libs.request(self.cursor_surface, WL_SURFACE_ATTACH, &mut [arg_o(buffer), arg_i(0), arg_i(0)]);
+                libs.request(self.cursor_surface, WL_SURFACE_SET_BUFFER_SCALE, &mut [arg_i(scale)]);
+                libs.request(
+                    self.cursor_surface,
+                    WL_SURFACE_DAMAGE,
+                    &mut [arg_i(0), arg_i(0), arg_i((*image).width as i32), arg_i((*image).height as i32)],
+                );
+                libs.request(self.cursor_surface, WL_SURFACE_COMMIT, &mut []);
+                libs.request(
+                    self.pointer,
+                    WL_POINTER_SET_CURSOR,
+                    &mut [
+                        arg_u(self.pointer_enter_serial),
+                        arg_o(self.cursor_surface),
+                        arg_i((*image).hotspot_x as i32 / scale),
+                        arg_i((*image).hotspot_y as i32 / scale),
+                    ],
+                );
+            }
+        }
+    }
+
+    /// Show a [`CustomCursor`], at one image pixel per logical pixel, since the size of the buffer has to be a
+    /// multiple of the buffer scale.
+    unsafe fn set_custom_cursor(&mut self, custom_cursor: &CustomCursor) {
+        let libs = self.libs;
+        if self.cursor_surface.is_null() {
+            self.cursor_surface = libs.request_new(
+                self.compositor,
+                WL_COMPOSITOR_CREATE_SURFACE,
+                &mut [arg_new()],
+                libs.wl_surface_interface,
+                &WaylandObject::Ignored,
+                0,
+            );
+        }
+        let buffer = match self.custom_cursor_buffers.get(&custom_cursor.id) {
+            Some(buffer) => *buffer,
+            None => {
+                let buffer = self.create_shm_buffer(custom_cursor);
+                if buffer.is_null() {
+                    return;
+                }
+                self.custom_cursor_buffers.insert(custom_cursor.id, buffer);
+                buffer
+            }
+        };
+        libs.request(self.cursor_surface, WL_SURFACE_ATTACH, &mut [arg_o(buffer), arg_i(0), arg_i(0)]);
+        libs.request(self.cursor_surface, WL_SURFACE_SET_BUFFER_SCALE, &mut [arg_i(1)]);
+        libs.request(
+            self.cursor_surface,
+            WL_SURFACE_DAMAGE,
+            &mut [arg_i(0), arg_i(0), arg_i(custom_cursor.width as i32), arg_i(custom_cursor.height as i32)],
+        );
+        libs.request(self.cursor_surface, WL_SURFACE_COMMIT, &mut []);
+        libs.request(
+            self.pointer,
+            WL_POINTER_SET_CURSOR,
+            &mut [
+                arg_u(self.pointer_enter_serial),
+                arg_o(self.cursor_surface),
+                arg_i(custom_cursor.hotspot.x as i32),
+                arg_i(custom_cursor.hotspot.y as i32),
+            ],
+        );
+    }
+
+    /// Create a `wl_buffer` in shared memory with the image of a [`CustomCursor`]. Returns null if that failed.
+    unsafe fn create_shm_buffer(&self, custom_cursor: &CustomCursor) -> *mut wl_proxy {
+        let libs = self.libs;
+        let stride = custom_cursor.width * 4;
+        let size = stride * custom_cursor.height;
+        let fd = libc::memfd_create(b"zaplib-cursor\0".as_ptr() as *const c_char, libc::MFD_CLOEXEC);
+        if fd < 0 {
+            return ptr::null_mut();
+        }
+        if libc::ftruncate(fd, size as libc::off_t) < 0 {
+            libc::close(fd);
+            return ptr::null_mut();
+        }
+        let map = libc::mmap(ptr::null_mut(), size, libc::PROT_READ | libc::PROT_WRITE, libc::MAP_SHARED, fd, 0);
+        if map == libc::MAP_FAILED {
+            libc::close(fd);
+            return ptr::null_mut();
+        }
+        // `WL_SHM_FORMAT_ARGB8888` is premultiplied, and little endian, so BGRA in memory.
+        let pixels = std::slice::from_raw_parts_mut(map as *mut u8, size);
+        for (pixel, rgba) in pixels.chunks_exact_mut(4).zip(custom_cursor.rgba.chunks_exact(4)) {
+            let alpha = rgba[3] as u32;
+            let premultiply = |channel: u8| (channel as u32 * alpha / 255) as u8;
+            pixel.copy_from_slice(&[premultiply(rgba[2]), premultiply(rgba[1]), premultiply(rgba[0]), rgba[3]]);
+        }
+        libc::munmap(map, size);
+
+        let pool = libs.request_new(
+            self.shm,
+            WL_SHM_CREATE_POOL,
+            &mut [arg_new(), arg_h(fd), arg_i(size as i32)],
+            libs.wl_shm_pool_interface,
+            &WaylandObject::Ignored,
+            0,
+        );
+        // The compositor has its own copy of the file descriptor now.
+        libc::close(fd);
+        if pool.is_null() {
+            return ptr::null_mut();
+        }
+        let buffer = libs.request_new(
+            pool,
+            WL_SHM_POOL_CREATE_BUFFER,
+            &mut [
+                arg_new(),
+                arg_i(0),
+                arg_i(custom_cursor.width as i32),
+                arg_i(custom_cursor.height as i32),
+                arg_i(stride as i32),
+                arg_u(WL_SHM_FORMAT_ARGB8888),
+            ],
+            libs.wl_buffer_interface,
+            &WaylandObject::Ignored,
+            0,
+        );
+        // Buffers keep their memory when the pool is destroyed.
+        libs.destroy(pool, Some(WL_SHM_POOL_DESTROY));
+        buffer
+    }
+
+    /// Lock the pointer in the window that it's in, or release it, using the pointer constraints protocol. Reports
+    /// that the lock was released right away if it's not supported, or if the pointer isn't in one of our windows.
+    pub(crate) fn set_pointer_lock(&mut self, locked: bool) {
+        let libs = self.libs;
+        unsafe {
+            if !locked {
+                libs.destroy(self.locked_pointer, Some(ZWP_LOCKED_POINTER_V1_DESTROY));
+                self.locked_pointer = ptr::null_mut();
+                return;
+            }
+            if !self.locked_pointer.is_null() {
+                return;
+            }
+            let surface = self
+                .pointer_window_id
+                .and_then(|window_id| self.windows.iter().find(|window| window.window_id == window_id))
+                .map(|window| window.surface);
+            match surface {
+                Some(surface) if !self.pointer_constraints.is_null() && !self.pointer.is_null() => {
+                    self.locked_pointer = libs.request_new(
+                        self.pointer_constraints,
+                        ZWP_POINTER_CONSTRAINTS_V1_LOCK_POINTER,
+                        &mut [
+                            arg_new(),
+                            arg_o(surface),
+                            arg_o(self.pointer),
+                            arg_o(ptr::null_mut()),
+                            arg_u(ZWP_POINTER_CONSTRAINTS_V1_LIFETIME_ONESHOT),
+                        ],
+                        &ZWP_LOCKED_POINTER_V1_INTERFACE.0,
+                        &WaylandObject::LockedPointer,
+                        0,
+                    );
+                }
+                _ => self.pending_events.push(Event::PointerLockChanged(PointerLockChangedEvent { locked: false })),
+            }
+        }
+    }
+
+    pub(crate) fn copy_text_to_clipboard(text: &str) {
+        unsafe {
+            if GLOBAL_WAYLAND_APP.is_null() {
+                return;
+            }
+            let wayland_app = &mut (*GLOBAL_WAYLAND_APP);
+            let libs = wayland_app.libs;
+            if wayland_app.data_device.is_null() {
+                return;
+            }
+            wayland_app.clipboard = text.to_string();
+            libs.destroy(wayland_app.data_source, Some(WL_DATA_SOURCE_DESTROY));
+            wayland_app.data_source = libs.request_new(
+                wayland_app.data_device_manager,
+                WL_DATA_DEVICE_MANAGER_CREATE_DATA_SOURCE,
+                &mut [arg_new()],
+                libs.wl_data_source_interface,
+                &WaylandObject::DataSource,
+                0,
+            );
+            for mime_type in TEXT_MIME_TYPES {
+                let mime_type = CString::new(*mime_type).unwrap();
+                libs.request(wayland_app.data_source, WL_DATA_SOURCE_OFFER, &mut [arg_s(&mime_type)]);
+            }
+            libs.request(
+                wayland_app.data_device,
+                WL_DATA_DEVICE_SET_SELECTION,
+                &mut [arg_o(wayland_app.data_source), arg_u(wayland_app.last_serial)],
+            );
+        }
+    }
+
+    /// Send the clipboard contents as [`Event::TextInput`].
+    fn paste(&mut self) {
+        let libs = self.libs;
+        let text = if !self.data_source.is_null() {
+            // We own the clipboard, and can't read from ourselves since we'd have to answer our own request.
+            Some(self.clipboard.clone())
+        } else {
+            let mime_type =
+                self.data_offers.iter().find(|(offer, _)| *offer == self.selection_offer).and_then(|(_, mime_types)| {
+                    TEXT_MIME_TYPES.iter().find(|mime_type| mime_types.iter().any(|offered| offered == *mime_type))
+                });
+            mime_type.and_then(|mime_type| unsafe {
+                let mut fds = [0; 2];
+                if libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC) != 0 {
+                    return None;
+                }
+                let mime_type = CString::new(*mime_type).unwrap();
+                libs.request(self.selection_offer, WL_DATA_OFFER_RECEIVE, &mut [arg_s(&mime_type), arg_h(fds[1])]);
+                libc::close(fds[1]);
+                (libs.wl_display_flush)(self.display);
+                let mut bytes = Vec::new();
+                let mut buffer = [0u8; 4096];
+                loop {
+                    // Don't hang forever if the other client doesn't answer.
+                    let mut poll_fd = libc::pollfd { fd: fds[0], events: libc::POLLIN, revents: 0 };
+                    if libc::poll(&mut poll_fd, 1, 1000) <= 0 {
+                        break;
+                    }
+                    let count = libc::read(fds[0], buffer.as_mut_ptr() as *mut c_void, buffer.len());
+                    if count <= 0 {
+                        break;
+                    }
+                    bytes.extend_from_slice(&buffer[..count as usize]);
+                }
+                libc::close(fds[0]);
+                String::from_utf8(bytes).ok()
+            })
+        };
+        if let Some(text) = text {
+            self.do_callback(&mut vec![Event::TextInput(TextInputEvent { input: text, was_paste: true, replace_last: false })]);
+        }
+    }
+
+    fn destroy_data_offer(&mut self, offer: *mut wl_proxy) {
+        self.data_offers.retain(|(data_offer, _)| *data_offer != offer);
+        unsafe { self.libs.destroy(offer, Some(WL_DATA_OFFER_DESTROY)) };
+    }
+
+    /// Tray icons need the StatusNotifierItem D-Bus protocol on Wayland, which we don't support yet.
+    pub(crate) fn update_tray(&mut self, tray: Option<&Tray>) {
+        if tray.is_some() {
+            log!("Tray icons are not supported on Wayland");
+        }
+    }
+
+    /// Open a new window. It only gets drawn once the compositor has configured it; see
+    /// [`WaylandWindow::is_configured`].
+    pub(crate) fn create_window(&mut self, window_id: usize, inner_size: Vec2, title: &str, egl_cx: &EglCx) {
+        let libs = self.libs;
+        unsafe {
+            let surface = libs.request_new(
+                self.compositor,
+                WL_COMPOSITOR_CREATE_SURFACE,
+                &mut [arg_new()],
+                libs.wl_surface_interface,
+                &WaylandObject::Surface,
+                window_id,
+            );
+            let xdg_surface = libs.request_new(
+                self.wm_base,
+                XDG_WM_BASE_GET_XDG_SURFACE,
+                &mut [arg_new(), arg_o(surface)],
+                &XDG_SURFACE_INTERFACE.0,
+                &WaylandObject::XdgSurface,
+                window_id,
+            );
+            let xdg_toplevel = libs.request_new(
+                xdg_surface,
+                XDG_SURFACE_GET_TOPLEVEL,
+                &mut [arg_new()],
+                &XDG_TOPLEVEL_INTERFACE.0,
+                &WaylandObject::XdgToplevel,
+                window_id,
+            );
+            let title = CString::new(title).unwrap_or_default();
+            libs.request(xdg_toplevel, XDG_TOPLEVEL_SET_TITLE, &mut [arg_s(&title)]);
+            // Used by compositors to find the `.desktop` file.
+            if let Some(app_id) =
+                std::env::current_exe().ok().and_then(|exe| exe.file_stem().and_then(|stem| CString::new(stem.to_str()?).ok()))
+            {
+                libs.request(xdg_toplevel, XDG_TOPLEVEL_SET_APP_ID, &mut [arg_s(&app_id)]);
+            }
+
+            let decoration = if self.decoration_manager.is_null() {
+                ptr::null_mut()
+            } else {
+                libs.request_new(
+                    self.decoration_manager,
+                    ZXDG_DECORATION_MANAGER_V1_GET_TOPLEVEL_DECORATION,
+                    &mut [arg_new(), arg_o(xdg_toplevel)],
+                    &ZXDG_TOPLEVEL_DECORATION_V1_INTERFACE.0,
+                    &WaylandObject::Decoration,
+                    window_id,
+                )
+            };
+            // Fractional scaling needs a viewport, since we then render at a size that doesn't match the logical size.
+            let (viewport, fractional_scale) = if self.viewporter.is_null() || self.fractional_scale_manager.is_null() {
+                (ptr::null_mut(), ptr::null_mut())
+            } else {
+                (
+                    libs.request_new(
+                        self.viewporter,
+                        WP_VIEWPORTER_GET_VIEWPORT,
+                        &mut [arg_new(), arg_o(surface)],
+                        &WP_VIEWPORT_INTERFACE.0,
+                        &WaylandObject::Ignored,
+                        window_id,
+                    ),
+                    libs.request_new(
+                        self.fractional_scale_manager,
+                        WP_FRACTIONAL_SCALE_MANAGER_V1_GET_FRACTIONAL_SCALE,
+                        &mut [arg_new(), arg_o(surface)],
+                        &WP_FRACTIONAL_SCALE_V1_INTERFACE.0,
+                        &WaylandObject::FractionalScale,
+                        window_id,
+                    ),
+                )
+            };
+
+            let egl_window = (libs.wl_egl_window_create)(surface, inner_size.x as c_int, inner_size.y as c_int);
+            let egl_surface = egl_cx.create_window_surface(egl_window);
+
+            let mut pointers_down = Vec::new();
+            pointers_down.resize(NUM_POINTERS, false);
+            let mut window = WaylandWindow {
+                libs,
+                window_id,
+                surface,
+                xdg_surface,
+                xdg_toplevel,
+                decoration,
+                viewport,
+                fractional_scale,
+                egl_window,
+                egl_display: egl_cx.display,
+                egl_surface,
+                inner_size,
+                pending_size: None,
+                pending_maximized: false,
+                pending_fullscreen: false,
+                is_maximized: false,
+                is_fullscreen: false,
+                is_configured: false,
+                frame_pending: false,
+                preferred_fractional_scale: None,
+                preferred_buffer_scale: None,
+                entered_outputs: Vec::new(),
+                fullscreen_output: ptr::null_mut(),
+                buffer_scale: 1,
+                window_geom: WindowGeom::default(),
+                style: CxWindowStyle { resizable: true, ..CxWindowStyle::default() },
+                last_mouse_pos: Vec2::default(),
+                pointers_down,
+                last_nc_mode: None,
+            };
+            window.update_decoration_mode();
+            window.window_geom = window.get_window_geom();
+            // The initial commit without a buffer makes the compositor send the first configure.
+            libs.request(surface, WL_SURFACE_COMMIT, &mut []);
+            self.windows.push(window);
+        }
+    }
+
+    /// Destroy a window, and send [`Event::WindowClosed`] for it.
+    pub(crate) fn close_window(&mut self, window_id: usize) {
+        if let Some(index) = self.windows.iter().position(|window| window.window_id == window_id) {
+            self.windows.remove(index);
+            if self.pointer_window_id == Some(window_id) {
+                self.pointer_window_id = None;
+            }
+            self.pending_events.push(Event::WindowClosed(WindowClosedEvent { window_id }));
+        }
+    }
+}
+
+impl Drop for WaylandApp {
+    fn drop(&mut self) {
+        let libs = self.libs;
+        unsafe {
+            self.windows.clear();
+            if !self.cursor_theme.is_null() {
+                (libs.wl_cursor_theme_destroy)(self.cursor_theme);
+            }
+            if !self.xkb_state.is_null() {
+                (libs.xkb_state_unref)(self.xkb_state);
+                (libs.xkb_keymap_unref)(self.xkb_keymap);
+            }
+            (libs.wl_display_disconnect)(self.display);
+            libc::close(self.signal_pipe[0]);
+            libc::close(self.signal_pipe[1]);
+            GLOBAL_WAYLAND_APP = ptr::null_mut();
+        }
+    }
+}
+
+/// If `pos` is on the edge of a window of `size`, the edge for `xdg_toplevel.resize` and the cursor to show.
+fn resize_edge_at(pos: Vec2, size: Vec2) -> Option<(u32, MouseCursor)> {
+    if pos.x < 10.0 && pos.y < 10.0 {
+        Some((XDG_TOPLEVEL_RESIZE_EDGE_TOP_LEFT, MouseCursor::NwResize))
+    } else if pos.x < 10.0 && pos.y >= size.y - 10.0 {
+        Some((XDG_TOPLEVEL_RESIZE_EDGE_BOTTOM_LEFT, MouseCursor::SwResize))
+    } else if pos.x < 5.0 {
+        Some((XDG_TOPLEVEL_RESIZE_EDGE_LEFT, MouseCursor::WResize))
+    } else if pos.x >= size.x - 10.0 && pos.y < 10.0 {
+        Some((XDG_TOPLEVEL_RESIZE_EDGE_TOP_RIGHT, MouseCursor::NeResize))
+    } else if pos.x >= size.x - 10.0 && pos.y >= size.y - 10.0 {
+        Some((XDG_TOPLEVEL_RESIZE_EDGE_BOTTOM_RIGHT, MouseCursor::SeResize))
+    } else if pos.x >= size.x - 5.0 {
+        Some((XDG_TOPLEVEL_RESIZE_EDGE_RIGHT, MouseCursor::EResize))
+    } else if pos.y <= 5.0 {
+        Some((XDG_TOPLEVEL_RESIZE_EDGE_TOP, MouseCursor::NResize))
+    } else if pos.y > size.y - 5.0 {
+        Some((XDG_TOPLEVEL_RESIZE_EDGE_BOTTOM, MouseCursor::SResize))
+    } else {
+        None
+    }
+}
+
+/// Cursor names in Xcursor themes, in order of preference; the same ones as in `XlibApp::set_mouse_cursor`.
+fn xcursor_names(cursor: &MouseCursor) -> &'static [&'static [u8]] {
+    match cursor {
+        MouseCursor::Hidden | MouseCursor::Custom(_) => &[],
+        MouseCursor::EResize => &[b"right_side\0"],
+        MouseCursor::NResize => &[b"top_side\0"],
+        MouseCursor::NeResize => &[b"top_right_corner\0"],
+        MouseCursor::NwResize => &[b"top_left_corner\0"],
+        MouseCursor::SResize => &[b"bottom_side\0"],
+        MouseCursor::SeResize => &[b"bottom_right_corner\0"],
+        MouseCursor::SwResize => &[b"bottom_left_corner\0"],
+        MouseCursor::WResize => &[b"left_side\0"],
+        MouseCursor::Default => &[b"left_ptr\0", b"default\0"],
+        MouseCursor::Crosshair => &[b"crosshair\0"],
+        MouseCursor::Hand => &[b"hand2\0", b"hand1\0", b"pointer\0"],
+        MouseCursor::Arrow => &[b"arrow\0", b"left_ptr\0"],
+        MouseCursor::Move => &[b"move\0", b"fleur\0"],
+        MouseCursor::NotAllowed => &[b"crossed_circle\0", b"not-allowed\0"],
+        MouseCursor::Text => &[b"text\0", b"xterm\0"],
+        MouseCursor::Wait => &[b"watch\0", b"wait\0"],
+        MouseCursor::Help => &[b"question_arrow\0", b"help\0"],
+        MouseCursor::NsResize => &[b"v_double_arrow\0", b"ns-resize\0"],
+        MouseCursor::NeswResize => &[b"fd_double_arrow\0", b"size_fdiag\0"],
+        MouseCursor::EwResize => &[b"h_double_arrow\0", b"ew-resize\0"],
+        MouseCursor::NwseResize => &[b"bd_double_arrow\0", b"size_bdiag\0"],
+        MouseCursor::ColResize => &[b"split_h\0", b"h_double_arrow\0"],
+        MouseCursor::RowResize => &[b"split_v\0", b"v_double_arrow\0"],
+        MouseCursor::Grab => &[b"grab\0", b"openhand\0", b"hand1\0"],
+        MouseCursor::Grabbing => &[b"grabbing\0", b"closedhand\0", b"fleur\0"],
+    }
+}
+
+impl WaylandWindow {
+    /// The scale from logical pixels to buffer pixels.
+    fn dpi_factor(&self) -> f32 {
+        self.preferred_fractional_scale.unwrap_or(self.buffer_scale as f32)
+    }
+
+    fn get_window_geom(&self) -> WindowGeom {
+        WindowGeom {
+            xr_is_presenting: false,
+            xr_can_present: false,
+            can_fullscreen: true,
+            is_topmost: false,
+            is_fullscreen: self.is_fullscreen,
+            is_maximized: self.is_maximized,
+            inner_size: self.inner_size,
+            outer_size: self.inner_size,
+            dpi_factor: self.dpi_factor(),
+            // Wayland doesn't tell clients where their windows are.
+            position: Vec2::default(),
+        }
+    }
+
+    /// Resize the EGL window to the current size and scale. This gets applied on the next swap.
+    fn apply_size_and_scale(&mut self) {
+        let libs = self.libs;
+        let dpi_factor = self.dpi_factor();
+        unsafe {
+            if self.preferred_fractional_scale.is_some() {
+                // The viewport scales our buffer down to the logical size.
+                libs.request(
+                    self.viewport,
+                    WP_VIEWPORT_SET_DESTINATION,
+                    &mut [arg_i(self.inner_size.x as i32), arg_i(self.inner_size.y as i32)],
+                );
+            } else {
+                libs.request(self.surface, WL_SURFACE_SET_BUFFER_SCALE, &mut [arg_i(self.buffer_scale)]);
+            }
+            (libs.wl_egl_window_resize)(
+                self.egl_window,
+                (self.inner_size.x * dpi_factor).round() as c_int,
+                (self.inner_size.y * dpi_factor).round() as c_int,
+                0,
+                0,
+            );
+        }
+    }
+
+    /// Ask the compositor to tell us when to draw the next frame; call this before swapping buffers.
+    pub(crate) fn request_frame_callback(&mut self) {
+        let libs = self.libs;
+        unsafe {
+            libs.request_new(
+                self.surface,
+                WL_SURFACE_FRAME,
+                &mut [arg_new()],
+                libs.wl_callback_interface,
+                &WaylandObject::FrameCallback,
+                self.window_id,
+            );
+        }
+        self.frame_pending = true;
+    }
+
+    fn update_decoration_mode(&self) {
+        // With custom window chrome we draw our own decorations.
+        let mode = if LINUX_CUSTOM_WINDOW_CHROME || self.style.borderless {
+            ZXDG_TOPLEVEL_DECORATION_V1_MODE_CLIENT_SIDE
+        } else {
+            ZXDG_TOPLEVEL_DECORATION_V1_MODE_SERVER_SIDE
+        };
+        if !self.decoration.is_null() {
+            unsafe { self.libs.request(self.decoration, ZXDG_TOPLEVEL_DECORATION_V1_SET_MODE, &mut [arg_u(mode)]) };
+        }
+    }
+
+    pub(crate) fn restore(&self) {
+        unsafe { self.libs.request(self.xdg_toplevel, XDG_TOPLEVEL_UNSET_MAXIMIZED, &mut []) };
+    }
+
+    pub(crate) fn maximize(&self) {
+        unsafe { self.libs.request(self.xdg_toplevel, XDG_TOPLEVEL_SET_MAXIMIZED, &mut []) };
+    }
+
+    pub(crate) fn minimize(&self) {
+        unsafe { self.libs.request(self.xdg_toplevel, XDG_TOPLEVEL_SET_MINIMIZED, &mut []) };
+    }
+
+    pub(crate) fn set_fullscreen(&self, fullscreen: bool) {
+        unsafe {
+            if fullscreen {
+                self.libs.request(self.xdg_toplevel, XDG_TOPLEVEL_SET_FULLSCREEN, &mut [arg_o(self.fullscreen_output)]);
+            } else {
+                self.libs.request(self.xdg_toplevel, XDG_TOPLEVEL_UNSET_FULLSCREEN, &mut []);
+            }
+        }
+    }
+
+    /// Clients can't position their windows, but we can pick the output to go fullscreen on, which also moves a
+    /// window that's already fullscreen.
+    pub(crate) fn move_to_display(&mut self, output: *mut wl_proxy) {
+        self.fullscreen_output = output;
+        if self.is_fullscreen {
+            self.set_fullscreen(true);
+        }
+    }
+
+    pub(crate) fn set_title(&self, title: &str) {
+        let title = CString::new(title).unwrap_or_default();
+        unsafe { self.libs.request(self.xdg_toplevel, XDG_TOPLEVEL_SET_TITLE, &mut [arg_s(&title)]) };
+    }
+
+    pub(crate) fn set_style(&mut self, style: &CxWindowStyle) {
+        self.style = style.clone();
+        // Compositors don't have a notion of resizability, so we fix the size instead.
+        let (min_size, max_size) =
+            if style.resizable { (style.min_size, style.max_size) } else { (Some(self.inner_size), Some(self.inner_size)) };
+        // 0 means no limit.
+        let size_args = |size: Option<Vec2>| {
+            let size = size.unwrap_or_default();
+            [arg_i(size.x as i32), arg_i(size.y as i32)]
+        };
+        unsafe {
+            self.libs.request(self.xdg_toplevel, XDG_TOPLEVEL_SET_MIN_SIZE, &mut size_args(min_size));
+            self.libs.request(self.xdg_toplevel, XDG_TOPLEVEL_SET_MAX_SIZE, &mut size_args(max_size));
+        }
+        self.update_decoration_mode();
+    }
+}
+
+impl Drop for WaylandWindow {
+    fn drop(&mut self) {
+        let libs = self.libs;
+        unsafe {
+            (libs.egl_destroy_surface)(self.egl_display, self.egl_surface);
+            (libs.wl_egl_window_destroy)(self.egl_window);
+            libs.destroy(self.fractional_scale, Some(WP_FRACTIONAL_SCALE_V1_DESTROY));
+            libs.destroy(self.viewport, Some(WP_VIEWPORT_DESTROY));
+            libs.destroy(self.decoration, Some(ZXDG_TOPLEVEL_DECORATION_V1_DESTROY));
+            libs.destroy(self.xdg_toplevel, Some(XDG_TOPLEVEL_DESTROY));
+            libs.destroy(self.xdg_surface, Some(XDG_SURFACE_DESTROY));
+            libs.destroy(self.surface, Some(WL_SURFACE_DESTROY));
+        }
+    }
+}
+
+/// An OpenGL ES context created with EGL, for drawing into Wayland windows; see [`OpenglCx::Egl`].
+#[derive(Clone, Copy)]
+pub(crate) struct EglCx {
+    libs: &'static WaylandLibs,
+    pub(crate) display: *mut c_void,
+    config: *mut c_void,
+    context: *mut c_void,
+}
+
+impl EglCx {
+    pub(crate) fn new(wayland_app: &WaylandApp) -> Result<EglCx, String> {
+        let libs = wayland_app.libs;
+        unsafe {
+            let display = (libs.egl_get_display)(wayland_app.display);
+            if display.is_null() {
+                return Err("Could not get the EGL display".to_string());
+            }
+            let (mut major, mut minor) = (0, 0);
+            if (libs.egl_initialize)(display, &mut major, &mut minor) == 0 {
+                return Err("Could not initialize EGL".to_string());
+            }
+            (libs.egl_bind_api)(EGL_OPENGL_ES_API);
+
+            // No alpha, since the compositor would then make the window translucent.
+            let config_attribs = [
+                EGL_SURFACE_TYPE,
+                EGL_WINDOW_BIT,
+                EGL_RENDERABLE_TYPE,
+                EGL_OPENGL_ES3_BIT,
+                EGL_RED_SIZE,
+                8,
+                EGL_GREEN_SIZE,
+                8,
+                EGL_BLUE_SIZE,
+                8,
+                EGL_ALPHA_SIZE,
+                0,
+                EGL_DEPTH_SIZE,
+                24,
+                EGL_NONE,
+            ];
+            let mut config = ptr::null_mut();
+            let mut config_count = 0;
+            if (libs.egl_choose_config)(display, config_attribs.as_ptr(), &mut config, 1, &mut config_count) == 0
+                || config_count == 0
+            {
+                return Err("Could not choose an EGL framebuffer configuration".to_string());
+            }
+
+            let context_attribs = [EGL_CONTEXT_MAJOR_VERSION, 3, EGL_NONE];
+            let context = (libs.egl_create_context)(display, config, ptr::null_mut(), context_attribs.as_ptr());
+            if context.is_null() {
+                return Err("Could not create an OpenGL ES 3 context".to_string());
+            }
+
+            let egl_cx = EglCx { libs, display, config, context };
+            egl_cx.make_current_without_window();
+            gl::load_with(|symbol| (libs.egl_get_proc_address)(CString::new(symbol).unwrap().as_ptr()));
+            Ok(egl_cx)
+        }
+    }
+
+    /// Uses `EGL_KHR_surfaceless_context`, which is supported by all Mesa drivers.
+    pub(crate) fn make_current_without_window(&self) {
+        unsafe { (self.libs.egl_make_current)(self.display, ptr::null_mut(), ptr::null_mut(), self.context) };
+    }
+
+    fn create_window_surface(&self, egl_window: *mut c_void) -> *mut c_void {
+        unsafe {
+            let surface = (self.libs.egl_create_window_surface)(self.display, self.config, egl_window, ptr::null());
+            // We throttle using frame callbacks, so `eglSwapBuffers` shouldn't block (which it would forever for
+            // windows that are hidden).
+            (self.libs.egl_make_current)(self.display, surface, surface, self.context);
+            (self.libs.egl_swap_interval)(self.display, 0);
+            surface
+        }
+    }
+}
+
+impl Cx {
+    pub(crate) fn draw_pass_to_wayland_window(
+        &mut self,
+        pass_id: usize,
+        dpi_factor: f32,
+        wayland_window: &mut WaylandWindow,
+        opengl_cx: &OpenglCx,
+    ) {
+        let egl_cx = match opengl_cx {
+            OpenglCx::Egl(egl_cx) => egl_cx,
+            OpenglCx::Glx { .. } => panic!("Wayland windows need an EGL context"),
+        };
+        unsafe {
+            (egl_cx.libs.egl_make_current)(
+                egl_cx.display,
+                wayland_window.egl_surface,
+                wayland_window.egl_surface,
+                egl_cx.context,
+            );
+        }
+        self.draw_pass_to_current_framebuffer(
+            pass_id,
+            dpi_factor,
+            wayland_window.window_id,
+            &wayland_window.window_geom,
+            opengl_cx,
+        );
+        wayland_window.request_frame_callback();
+        unsafe {
+            (egl_cx.libs.egl_swap_buffers)(egl_cx.display, wayland_window.egl_surface);
+        }
+    }
+}
+
+/// `wl_interface`s and `wl_message`s are plain data, but contain pointers, so they need this to be put in statics.
+#[repr(transparent)]
+struct SyncWrapper<T>(T);
+unsafe impl<T> Sync for SyncWrapper<T> {}
+
+/// libwayland only uses the types of arguments for objects that get created by events, which we don't have in the
+/// protocols below, so we can leave them all null.
+static NULL_TYPES: SyncWrapper<[*const wl_interface; 8]> = SyncWrapper([ptr::null(); 8]);
+
+macro_rules! wl_messages {
+    ($($name: literal $signature: literal),* $(,)?) => {
+        SyncWrapper([$(wl_message {
+            name: concat!($name, "\0").as_ptr() as *const c_char,
+            signature: concat!($signature, "\0").as_ptr() as *const c_char,
+            types: &NULL_TYPES.0 as *const _ as *const *const wl_interface,
+        }),*])
+    };
+}
+
+macro_rules! wl_protocol_interface {
+    ($interface: ident, $name: literal, $version: literal, $requests: ident, $events: ident) => {
+        static $interface: SyncWrapper<wl_interface> = SyncWrapper(wl_interface {
+            name: concat!($name, "\0").as_ptr() as *const c_char,
+            version: $version,
+            method_count: $requests.0.len() as c_int,
+            methods: &$requests.0 as *const _ as *const wl_message,
+            event_count: $events.0.len() as c_int,
+            events: &$events.0 as *const _ as *const wl_message,
+        });
+    };
+}
+
+static NO_MESSAGES: SyncWrapper<[wl_message; 0]> = SyncWrapper([]);
+
+static XDG_WM_BASE_REQUESTS: SyncWrapper<[wl_message; 4]> =
+    wl_messages!["destroy" "", "create_positioner" "n", "get_xdg_surface" "no", "pong" "u"];
+static XDG_WM_BASE_EVENTS: SyncWrapper<[wl_message; 1]> = wl_messages!["ping" "u"];
+wl_protocol_interface!(XDG_WM_BASE_INTERFACE, "xdg_wm_base", 2, XDG_WM_BASE_REQUESTS, XDG_WM_BASE_EVENTS);
+
+static XDG_SURFACE_REQUESTS: SyncWrapper<[wl_message; 5]> = wl_messages![
+    "destroy" "",
+    "get_toplevel" "n",
+    "get_popup" "n?oo",
+    "set_window_geometry" "iiii",
+    "ack_configure" "u",
+];
+static XDG_SURFACE_EVENTS: SyncWrapper<[wl_message; 1]> = wl_messages!["configure" "u"];
+wl_protocol_interface!(XDG_SURFACE_INTERFACE, "xdg_surface", 2, XDG_SURFACE_REQUESTS, XDG_SURFACE_EVENTS);
+
+static XDG_TOPLEVEL_REQUESTS: SyncWrapper<[wl_message; 14]> = wl_messages![
+    "destroy" "",
+    "set_parent" "?o",
+    "set_title" "s",
+    "set_app_id" "s",
+    "show_window_menu" "ouii",
+    "move" "ou",
+    "resize" "ouu",
+    "set_max_size" "ii",
+    "set_min_size" "ii",
+    "set_maximized" "",
+    "unset_maximized" "",
+    "set_fullscreen" "?o",
+    "unset_fullscreen" "",
+    "set_minimized" "",
+];
+static XDG_TOPLEVEL_EVENTS: SyncWrapper<[wl_message; 2]> = wl_messages!["configure" "iia", "close" ""];
+wl_protocol_interface!(XDG_TOPLEVEL_INTERFACE, "xdg_toplevel", 2, XDG_TOPLEVEL_REQUESTS, XDG_TOPLEVEL_EVENTS);
+
+static ZXDG_DECORATION_MANAGER_V1_REQUESTS: SyncWrapper<[wl_message; 2]> =
+    wl_messages!["destroy" "", "get_toplevel_decoration" "no"];
+wl_protocol_interface!(
+    ZXDG_DECORATION_MANAGER_V1_INTERFACE,
+    "zxdg_decoration_manager_v1",
+    1,
+    ZXDG_DECORATION_MANAGER_V1_REQUESTS,
+    NO_MESSAGES
+);
+
+static ZXDG_TOPLEVEL_DECORATION_V1_REQUESTS: SyncWrapper<[wl_message; 3]> =
+    wl_messages!["destroy" "", "set_mode" "u", "unset_mode" ""];
+static ZXDG_TOPLEVEL_DECORATION_V1_EVENTS: SyncWrapper<[wl_message; 1]> = wl_messages!["configure" "u"];
+wl_protocol_interface!(
+    ZXDG_TOPLEVEL_DECORATION_V1_INTERFACE,
+    "zxdg_toplevel_decoration_v1",
+    1,
+    ZXDG_TOPLEVEL_DECORATION_V1_REQUESTS,
+    ZXDG_TOPLEVEL_DECORATION_V1_EVENTS
+);
+
+static WP_VIEWPORTER_REQUESTS: SyncWrapper<[wl_message; 2]> = wl_messages!["destroy" "", "get_viewport" "no"];
+wl_protocol_interface!(WP_VIEWPORTER_INTERFACE, "wp_viewporter", 1, WP_VIEWPORTER_REQUESTS, NO_MESSAGES);
+
+static WP_VIEWPORT_REQUESTS: SyncWrapper<[wl_message; 3]> =
+    wl_messages!["destroy" "", "set_source" "ffff", "set_destination" "ii"];
+wl_protocol_interface!(WP_VIEWPORT_INTERFACE, "wp_viewport", 1, WP_VIEWPORT_REQUESTS, NO_MESSAGES);
+
+static WP_FRACTIONAL_SCALE_MANAGER_V1_REQUESTS: SyncWrapper<[wl_message; 2]> =
+    wl_messages!["destroy" "", "get_fractional_scale" "no"];
+wl_protocol_interface!(
+    WP_FRACTIONAL_SCALE_MANAGER_V1_INTERFACE,
+    "wp_fractional_scale_manager_v1",
+    1,
+    WP_FRACTIONAL_SCALE_MANAGER_V1_REQUESTS,
+    NO_MESSAGES
+);
+
+static WP_FRACTIONAL_SCALE_V1_REQUESTS: SyncWrapper<[wl_message; 1]> = wl_messages!["destroy" ""];
+static WP_FRACTIONAL_SCALE_V1_EVENTS: SyncWrapper<[wl_message; 1]> = wl_messages!["preferred_scale" "u"];
+wl_protocol_interface!(
+    WP_FRACTIONAL_SCALE_V1_INTERFACE,
+    "wp_fractional_scale_v1",
+    1,
+    WP_FRACTIONAL_SCALE_V1_REQUESTS,
+    WP_FRACTIONAL_SCALE_V1_EVENTS
+);
+
+static ZWP_POINTER_CONSTRAINTS_V1_REQUESTS: SyncWrapper<[wl_message; 3]> =
+    wl_messages!["destroy" "", "lock_pointer" "noo?ou", "confine_pointer" "noo?ou"];
+wl_protocol_interface!(
+    ZWP_POINTER_CONSTRAINTS_V1_INTERFACE,
+    "zwp_pointer_constraints_v1",
+    1,
+    ZWP_POINTER_CONSTRAINTS_V1_REQUESTS,
+    NO_MESSAGES
+);
+
+static ZWP_LOCKED_POINTER_V1_REQUESTS: SyncWrapper<[wl_message; 3]> =
+    wl_messages!["destroy" "", "set_cursor_position_hint" "ff", "set_region" "?o"];
+static ZWP_LOCKED_POINTER_V1_EVENTS: SyncWrapper<[wl_message; 2]> = wl_messages!["locked" "", "unlocked" ""];
+wl_protocol_interface!(
+    ZWP_LOCKED_POINTER_V1_INTERFACE,
+    "zwp_locked_pointer_v1",
+    1,
+    ZWP_LOCKED_POINTER_V1_REQUESTS,
+    ZWP_LOCKED_POINTER_V1_EVENTS
+);
+
+static ZWP_RELATIVE_POINTER_MANAGER_V1_REQUESTS: SyncWrapper<[wl_message; 2]> =
+    wl_messages!["destroy" "", "get_relative_pointer" "no"];
+wl_protocol_interface!(
+    ZWP_RELATIVE_POINTER_MANAGER_V1_INTERFACE,
+    "zwp_relative_pointer_manager_v1",
+    1,
+    ZWP_RELATIVE_POINTER_MANAGER_V1_REQUESTS,
+    NO_MESSAGES
+);
+
+static ZWP_RELATIVE_POINTER_V1_REQUESTS: SyncWrapper<[wl_message; 1]> = wl_messages!["destroy" ""];
+static ZWP_RELATIVE_POINTER_V1_EVENTS: SyncWrapper<[wl_message; 1]> = wl_messages!["relative_motion" "uuffff"];
+wl_protocol_interface!(
+    ZWP_RELATIVE_POINTER_V1_INTERFACE,
+    "zwp_relative_pointer_v1",
+    1,
+    ZWP_RELATIVE_POINTER_V1_REQUESTS,
+    ZWP_RELATIVE_POINTER_V1_EVENTS
+);
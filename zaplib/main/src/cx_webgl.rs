@@ -2,10 +2,11 @@
 //!
 //! Communicates with main_worker.ts using some functions in `cx_wasm32.rs`.
 
-use crate::{zerde::ZerdeBuilder, *};
+use crate::{instance_buffer_pool::InstanceBufferRange, zerde::ZerdeBuilder, *};
 use zaplib_shader_compiler::generate_glsl;
 
 impl Cx {
+    #[allow(clippy::too_many_arguments)]
     pub(crate) fn render_view(
         &mut self,
         pass_id: usize,
@@ -14,6 +15,7 @@ impl Cx {
         clip: (Vec2, Vec2),
         zbias: &mut f32,
         zbias_step: f32,
+        xr_eye: Option<usize>,
         zerde_webgl: &mut ZerdeWebGLMessages,
     ) {
         // tad ugly otherwise the borrow checker locks 'self' and we can't recur
@@ -23,6 +25,10 @@ impl Cx {
         let clip = self.views[view_id].intersect_clip(clip);
         for draw_call_id in 0..draw_calls_len {
             let sub_view_id = self.views[view_id].draw_calls[draw_call_id].sub_view_id;
+            if self.views[view_id].draw_calls[draw_call_id].batched_into.is_some() {
+                // Got merged into an earlier draw call; see `Cx::batch_draw_calls`.
+                continue;
+            }
             if sub_view_id != 0 {
                 self.render_view(
                     pass_id,
@@ -31,6 +37,7 @@ impl Cx {
                     clip,
                     zbias,
                     zbias_step,
+                    xr_eye,
                     zerde_webgl,
                 );
             } else {
@@ -39,18 +46,29 @@ impl Cx {
                 let cxview = &mut self.views[view_id];
                 let draw_call = &mut cxview.draw_calls[draw_call_id];
 
-                if draw_call.instance_dirty || draw_call.platform.inst_vb_id.is_none() {
+                if draw_call.instance_dirty || draw_call.platform.inst_range.is_none() {
                     draw_call.instance_dirty = false;
-                    if draw_call.platform.inst_vb_id.is_none() {
-                        draw_call.platform.inst_vb_id = Some(self.platform.vertex_buffers);
-                        self.platform.vertex_buffers += 1;
+                    // Instead of overwriting the old range (and having to wait for the GPU to stop using it), we
+                    // always get a new range from the pool. See `instance_buffer_pool.rs`.
+                    if let Some(inst_range) = draw_call.platform.inst_range.take() {
+                        self.platform.instance_buffer_pool.free(inst_range);
                     }
-                    zerde_webgl.alloc_array_buffer(
-                        draw_call.platform.inst_vb_id.unwrap(),
-                        draw_call.instances.len(),
+                    let vertex_buffers = &mut self.platform.vertex_buffers;
+                    let (inst_range, orphan_capacity) =
+                        self.platform.instance_buffer_pool.alloc(draw_call.instances.len(), || {
+                            *vertex_buffers += 1;
+                            *vertex_buffers - 1
+                        });
+                    if let Some(capacity) = orphan_capacity {
+                        zerde_webgl.orphan_array_buffer(inst_range.buffer_id, capacity);
+                    }
+                    zerde_webgl.update_array_buffer_range(
+                        inst_range.buffer_id,
+                        inst_range.offset,
+                        inst_range.len,
                         draw_call.instances.as_ptr() as *const f32,
                     );
-                    draw_call.instance_dirty = false;
+                    draw_call.platform.inst_range = Some(inst_range);
                 }
 
                 draw_call.set_zbias(*zbias);
@@ -63,7 +81,11 @@ impl Cx {
                     let cxtexture = &mut self.textures[*texture_id as usize];
                     if cxtexture.update_image {
                         cxtexture.update_image = false;
-                        zerde_webgl.update_texture_image2d(*texture_id as usize, cxtexture);
+                        if cxtexture.desc.cube {
+                            zerde_webgl.update_texture_cube(*texture_id as usize, cxtexture);
+                        } else {
+                            zerde_webgl.update_texture_image2d(*texture_id as usize, cxtexture);
+                        }
                     }
                 }
 
@@ -101,20 +123,20 @@ impl Cx {
                     draw_call.platform.vao = Some(CxPlatformDrawCallVao {
                         vao_id: self.platform.vaos,
                         shader_id: None,
-                        inst_vb_id: None,
+                        inst_range: None,
                         geom_vb_id: None,
                         geom_ib_id: None,
                     });
                     self.platform.vaos += 1;
                 }
                 let vao = draw_call.platform.vao.as_mut().unwrap();
-                if vao.inst_vb_id != draw_call.platform.inst_vb_id
+                if vao.inst_range != draw_call.platform.inst_range
                     || vao.geom_vb_id != geometry.platform.vb_id
                     || vao.geom_ib_id != geometry.platform.ib_id
                     || vao.shader_id != Some(draw_call.shader_id)
                 {
                     vao.shader_id = Some(draw_call.shader_id);
-                    vao.inst_vb_id = draw_call.platform.inst_vb_id;
+                    vao.inst_range = draw_call.platform.inst_range;
                     vao.geom_vb_id = geometry.platform.vb_id;
                     vao.geom_ib_id = geometry.platform.ib_id;
 
@@ -123,14 +145,18 @@ impl Cx {
                         vao.shader_id.unwrap(),
                         vao.geom_ib_id.unwrap(),
                         vao.geom_vb_id.unwrap(),
-                        draw_call.platform.inst_vb_id.unwrap(),
+                        vao.inst_range.unwrap(),
                     );
                 }
 
+                let cxpass = &self.passes[pass_id];
                 zerde_webgl.draw_call(
                     draw_call.shader_id,
                     draw_call.platform.vao.as_ref().unwrap().vao_id,
-                    self.passes[pass_id].pass_uniforms.as_slice(),
+                    match xr_eye {
+                        Some(eye) => cxpass.platform.xr_eye_uniforms[eye].as_slice(),
+                        None => cxpass.pass_uniforms.as_slice(),
+                    },
                     cxview.view_uniforms.as_slice(),
                     draw_call.draw_uniforms.as_slice(),
                     &draw_call.user_uniforms,
@@ -154,7 +180,18 @@ impl Cx {
         self.passes[pass_id].set_dpi_factor(dpi_factor);
     }
 
-    pub(crate) fn draw_pass_to_canvas(&mut self, pass_id: usize, dpi_factor: f32, zerde_webgl: &mut ZerdeWebGLMessages) {
+    /// Draw the main pass of a window. The main window (with id 0) is drawn directly into the main canvas. Other
+    /// windows have their own canvas, but WebGL can't share textures or shaders between canvases, so for those we
+    /// draw into a framebuffer of the main canvas instead, and then copy the pixels over. See
+    /// `WebGLRenderer.presentWindowCanvas`.
+    pub(crate) fn draw_pass_to_canvas(
+        &mut self,
+        pass_id: usize,
+        window_id: usize,
+        dpi_factor: f32,
+        zerde_webgl: &mut ZerdeWebGLMessages,
+    ) {
+        let pass_size = self.passes[pass_id].pass_size;
         let view_id = self.passes[pass_id].main_view_id.unwrap();
 
         // get the color and depth
@@ -170,7 +207,17 @@ impl Cx {
             ClearDepth::InitWith(depth) => depth,
             ClearDepth::ClearWith(depth) => depth,
         };
-        zerde_webgl.begin_main_canvas(clear_color, clear_depth as f32);
+        if window_id == 0 {
+            zerde_webgl.begin_main_canvas(clear_color, clear_depth as f32);
+        } else {
+            zerde_webgl.begin_window_canvas(
+                window_id,
+                (pass_size.x * dpi_factor) as usize,
+                (pass_size.y * dpi_factor) as usize,
+                clear_color,
+                clear_depth as f32,
+            );
+        }
 
         self.setup_render_pass(pass_id, dpi_factor);
 
@@ -186,8 +233,13 @@ impl Cx {
             (Vec2 { x: -50000., y: -50000. }, Vec2 { x: 50000., y: 50000. }),
             &mut zbias,
             zbias_step,
+            None,
             zerde_webgl,
         );
+
+        if window_id != 0 {
+            zerde_webgl.present_window_canvas(window_id);
+        }
     }
 
     pub(crate) fn draw_pass_to_texture(&mut self, pass_id: usize, dpi_factor: f32, zerde_webgl: &mut ZerdeWebGLMessages) {
@@ -235,10 +287,56 @@ impl Cx {
             (Vec2 { x: -50000., y: -50000. }, Vec2 { x: 50000., y: 50000. }),
             &mut zbias,
             zbias_step,
+            None,
             zerde_webgl,
         );
     }
 
+    /// Draw a pass with [`Pass::set_xr_stereo`] into the framebuffer of the VR headset, once for every eye. The
+    /// pass uniforms of every eye are kept in [`CxPlatformPass::xr_eye_uniforms`], since the draw calls only point
+    /// to them.
+    pub(crate) fn draw_pass_to_xr(
+        &mut self,
+        pass_id: usize,
+        dpi_factor: f32,
+        views: &[XrView],
+        zerde_webgl: &mut ZerdeWebGLMessages,
+    ) {
+        let view_id = self.passes[pass_id].main_view_id.unwrap();
+
+        self.setup_render_pass(pass_id, dpi_factor);
+
+        let cxpass = &mut self.passes[pass_id];
+        let clear_color = match cxpass.color_textures.first().map(|color_texture| &color_texture.clear_color) {
+            Some(ClearColor::InitWith(color) | ClearColor::ClearWith(color)) => *color,
+            None => Vec4::default(),
+        };
+        let clear_depth = match cxpass.clear_depth {
+            ClearDepth::InitWith(depth) => depth,
+            ClearDepth::ClearWith(depth) => depth,
+        };
+        cxpass.platform.xr_eye_uniforms = views.iter().map(|view| cxpass.xr_eye_uniforms(view)).collect();
+        zerde_webgl.begin_xr_canvas(clear_color, clear_depth as f32);
+
+        zerde_webgl.set_default_depth_and_blend_mode();
+
+        let zbias_step = self.passes[pass_id].zbias_step;
+        for (eye, view) in views.iter().enumerate() {
+            zerde_webgl.set_xr_eye_viewport(view.viewport);
+            let mut zbias = 0.0;
+            self.render_view(
+                pass_id,
+                view_id,
+                Vec2::default(),
+                (Vec2 { x: -50000., y: -50000. }, Vec2 { x: 50000., y: 50000. }),
+                &mut zbias,
+                zbias_step,
+                Some(eye),
+                zerde_webgl,
+            );
+        }
+    }
+
     pub(crate) fn webgl_compile_shaders(&mut self, zerde_webgl: &mut ZerdeWebGLMessages) {
         for shader_id in self.shader_recompile_ids.drain(..) {
             let shader = unsafe { self.shaders.get_unchecked_mut(shader_id) };
@@ -252,6 +350,7 @@ impl Cx {
                 precision highp float;
                 precision highp int;
                 vec4 sample2d(sampler2D sampler, vec2 pos){{return texture2D(sampler, vec2(pos.x, 1.0-pos.y));}}
+                vec4 sample_cube(samplerCube sampler, vec3 dir){{return textureCube(sampler, dir);}}
                 mat4 transpose(mat4 m){{return \
                  mat4(m[0][0],m[1][0],m[2][0],m[3][0],m[0][1],m[1][1],m[2][1],m[3][1],m[0][2],m[1][2],m[2][2],m[3][3], m[3][0], \
                  m[3][1], m[3][2], m[3][3]);}}
@@ -266,6 +365,7 @@ impl Cx {
                 precision highp float;
                 precision highp int;
                 vec4 sample2d(sampler2D sampler, vec2 pos){{return texture2D(sampler, vec2(pos.x, 1.0-pos.y));}}
+                vec4 sample_cube(samplerCube sampler, vec3 dir){{return textureCube(sampler, dir);}}
                 mat4 transpose(mat4 m){{return \
                  mat4(m[0][0],m[1][0],m[2][0],m[3][0],m[0][1],m[1][1],m[2][1],m[3][1],m[0][2],m[1][2],m[2][2],m[3][3], m[3][0], \
                  m[3][1], m[3][2], m[3][3]);}}
@@ -294,7 +394,10 @@ impl Cx {
 }
 
 #[derive(Default, Clone)]
-pub(crate) struct CxPlatformPass {}
+pub(crate) struct CxPlatformPass {
+    /// See [`Cx::draw_pass_to_xr`].
+    pub(crate) xr_eye_uniforms: Vec<PassUniforms>,
+}
 
 #[derive(Clone, Default)]
 pub(crate) struct CxPlatformView {}
@@ -303,7 +406,7 @@ pub(crate) struct CxPlatformView {}
 pub(crate) struct CxPlatformDrawCallVao {
     pub(crate) vao_id: usize,
     pub(crate) shader_id: Option<usize>,
-    pub(crate) inst_vb_id: Option<usize>,
+    pub(crate) inst_range: Option<InstanceBufferRange>,
     pub(crate) geom_vb_id: Option<usize>,
     pub(crate) geom_ib_id: Option<usize>,
 }
@@ -311,7 +414,7 @@ pub(crate) struct CxPlatformDrawCallVao {
 #[derive(Default, Clone)]
 pub(crate) struct CxPlatformDrawCall {
     pub(crate) vao: Option<CxPlatformDrawCallVao>,
-    pub(crate) inst_vb_id: Option<usize>,
+    pub(crate) inst_range: Option<InstanceBufferRange>,
 }
 
 #[derive(Clone)]
@@ -348,15 +451,19 @@ impl ZerdeWebGLMessages {
     fn send_propdefvec(&mut self, prop_defs: &Vec<PropDef>) {
         self.builder.send_u32(prop_defs.len() as u32);
         for prop_def in prop_defs {
-            self.builder.send_string(match prop_def.ty {
-                Ty::Vec4 => "vec4",
-                Ty::Vec3 => "vec3",
-                Ty::Vec2 => "vec2",
-                Ty::Float => "float",
-                Ty::Mat4 => "mat4",
-                Ty::Texture2D => "sampler2D",
-                _ => panic!("unexpected type in send_propdefvec"),
-            });
+            match prop_def.ty {
+                Ty::Array { ref elem_ty, len } if **elem_ty == Ty::Mat4 => self.builder.send_string(&format!("mat4[{len}]")),
+                _ => self.builder.send_string(match prop_def.ty {
+                    Ty::Vec4 => "vec4",
+                    Ty::Vec3 => "vec3",
+                    Ty::Vec2 => "vec2",
+                    Ty::Float => "float",
+                    Ty::Mat4 => "mat4",
+                    Ty::Texture2D => "sampler2D",
+                    Ty::TextureCube => "samplerCube",
+                    _ => panic!("unexpected type in send_propdefvec"),
+                }),
+            }
             self.builder.send_string(&prop_def.name);
         }
     }
@@ -389,13 +496,22 @@ impl ZerdeWebGLMessages {
         self.builder.send_u32(data as u32);
     }
 
-    pub(crate) fn alloc_vao(&mut self, vao_id: usize, shader_id: usize, geom_ib_id: usize, geom_vb_id: usize, inst_vb_id: usize) {
+    pub(crate) fn alloc_vao(
+        &mut self,
+        vao_id: usize,
+        shader_id: usize,
+        geom_ib_id: usize,
+        geom_vb_id: usize,
+        inst_range: InstanceBufferRange,
+    ) {
         self.builder.send_u32(4);
         self.builder.send_u32(vao_id as u32);
         self.builder.send_u32(shader_id as u32);
         self.builder.send_u32(geom_ib_id as u32);
         self.builder.send_u32(geom_vb_id as u32);
-        self.builder.send_u32(inst_vb_id as u32);
+        self.builder.send_u32(inst_range.buffer_id as u32);
+        self.builder.send_u32(inst_range.offset as u32);
+        self.builder.send_u32(inst_range.len as u32);
     }
 
     pub(crate) fn draw_call(
@@ -467,4 +583,62 @@ impl ZerdeWebGLMessages {
         self.builder.send_f32(color.w);
         self.builder.send_f32(depth);
     }
+
+    pub(crate) fn begin_window_canvas(&mut self, window_id: usize, width: usize, height: usize, color: Vec4, depth: f32) {
+        self.builder.send_u32(13);
+        self.builder.send_u32(window_id as u32);
+        self.builder.send_u32(width as u32);
+        self.builder.send_u32(height as u32);
+        self.builder.send_f32(color.x);
+        self.builder.send_f32(color.y);
+        self.builder.send_f32(color.z);
+        self.builder.send_f32(color.w);
+        self.builder.send_f32(depth);
+    }
+
+    pub(crate) fn present_window_canvas(&mut self, window_id: usize) {
+        self.builder.send_u32(14);
+        self.builder.send_u32(window_id as u32);
+    }
+
+    /// Allocate new storage for a buffer, so we can write to it without waiting for draws that use the old storage.
+    pub(crate) fn orphan_array_buffer(&mut self, buffer_id: usize, capacity: usize) {
+        self.builder.send_u32(15);
+        self.builder.send_u32(buffer_id as u32);
+        self.builder.send_u32(capacity as u32);
+    }
+
+    pub(crate) fn update_array_buffer_range(&mut self, buffer_id: usize, offset: usize, len: usize, data: *const f32) {
+        self.builder.send_u32(16);
+        self.builder.send_u32(buffer_id as u32);
+        self.builder.send_u32(offset as u32);
+        self.builder.send_u32(len as u32);
+        self.builder.send_u32(data as u32);
+    }
+
+    /// Upload all 6 faces of a cube texture; see [`Texture::get_cube_with_size`].
+    pub(crate) fn update_texture_cube(&mut self, texture_id: usize, texture: &mut CxTexture) {
+        self.builder.send_u32(17);
+        self.builder.send_u32(texture_id as u32);
+        self.builder.send_u32(texture.desc.width.unwrap() as u32);
+        self.builder.send_u32(texture.image_u32.as_ptr() as u32)
+    }
+
+    /// Bind and clear the framebuffer of the VR headset; see [`Cx::draw_pass_to_xr`].
+    pub(crate) fn begin_xr_canvas(&mut self, color: Vec4, depth: f32) {
+        self.builder.send_u32(18);
+        self.builder.send_f32(color.x);
+        self.builder.send_f32(color.y);
+        self.builder.send_f32(color.z);
+        self.builder.send_f32(color.w);
+        self.builder.send_f32(depth);
+    }
+
+    pub(crate) fn set_xr_eye_viewport(&mut self, viewport: Rect) {
+        self.builder.send_u32(19);
+        self.builder.send_f32(viewport.pos.x);
+        self.builder.send_f32(viewport.pos.y);
+        self.builder.send_f32(viewport.size.x);
+        self.builder.send_f32(viewport.size.y);
+    }
 }
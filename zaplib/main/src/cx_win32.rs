@@ -11,20 +11,26 @@ use std::ptr;
 use std::sync::Mutex;
 use time::precise_time_ns;
 use winapi::shared::basetsd::UINT_PTR;
-use winapi::shared::minwindef::{BOOL, DWORD, FALSE, LPARAM, LRESULT, UINT, WPARAM};
+use winapi::shared::minwindef::{BOOL, DWORD, FALSE, LPARAM, LRESULT, TRUE, UINT, WPARAM};
 use winapi::shared::ntdef::NULL;
-use winapi::shared::windef::{DPI_AWARENESS_CONTEXT, DPI_AWARENESS_CONTEXT_PER_MONITOR_AWARE, HMONITOR, HWND, RECT};
+use winapi::shared::windef::{
+    DPI_AWARENESS_CONTEXT, DPI_AWARENESS_CONTEXT_PER_MONITOR_AWARE, HCURSOR, HDC, HICON, HMENU, HMONITOR, HWND, LPRECT, POINT,
+    RECT,
+};
 use winapi::shared::winerror::S_OK;
 use winapi::um::libloaderapi::{GetProcAddress, LoadLibraryA};
 use winapi::um::shellscalingapi::{MDT_EFFECTIVE_DPI, MONITOR_DPI_TYPE, PROCESS_DPI_AWARENESS, PROCESS_PER_MONITOR_DPI_AWARE};
 use winapi::um::uxtheme::MARGINS;
-use winapi::um::wingdi::{GetDeviceCaps, LOGPIXELSX};
+use winapi::um::wingdi::{CreateBitmap, DeleteObject, GetDeviceCaps, DEVMODEW, LOGPIXELSX};
 use winapi::um::winnt::{HRESULT, LPCSTR, LPCWSTR};
 use winapi::um::winuser::{MONITOR_DEFAULTTONEAREST, TRACKMOUSEEVENT};
-use winapi::um::{dwmapi, libloaderapi, winbase, winuser};
+use winapi::um::{dwmapi, libloaderapi, shellapi, winbase, winuser};
 
 static mut GLOBAL_WIN32_APP: *mut Win32App = 0 as *mut _;
 
+/// Message that tells the window to process [`Win32App::race_events`].
+const WM_USER_EVENTS: UINT = winuser::WM_USER + 1;
+
 pub(crate) struct Win32App {
     pub(crate) time_start: u64,
     pub(crate) event_callback: Option<*mut dyn FnMut(&mut Win32App, &mut Vec<Event>) -> bool>,
@@ -35,9 +41,20 @@ pub(crate) struct Win32App {
     pub(crate) timers: Vec<Win32Timer>,
     pub(crate) free_timers: Vec<usize>,
     pub(crate) race_signals: Mutex<Vec<(usize, isize)>>,
+    /// Events sent using [`Win32App::send_event_from_any_thread`], to be processed on the main thread.
+    pub(crate) race_events: Mutex<Vec<Event>>,
     pub(crate) loop_block: bool,
     pub(crate) dpi_functions: DpiFunctions,
     pub(crate) current_cursor: MouseCursor,
+    /// Native cursors for [`MouseCursor::Custom`], by [`CustomCursor::id`].
+    custom_cursors: HashMap<u64, HCURSOR>,
+    /// Whether the cursor is currently clipped to its position; see [`Win32App::set_pointer_lock`].
+    pub(crate) pointer_locked: bool,
+    pub(crate) tray: Option<Win32Tray>,
+    /// See [`Win32App::update_displays`].
+    pub(crate) displays: Vec<DisplayInfo>,
+    /// The monitor handle of every display in [`Win32App::displays`].
+    display_monitors: Vec<HMONITOR>,
 }
 
 #[derive(Clone)]
@@ -53,6 +70,10 @@ pub(crate) struct Win32Window {
     pub(crate) pointers_down: Vec<bool>,
     pub(crate) hwnd: Option<HWND>,
     pub(crate) track_mouse_event: bool,
+    pub(crate) style: CxWindowStyle,
+    pub(crate) icon: Option<HICON>,
+    /// The window rectangle to go back to when leaving fullscreen; `Some` while fullscreen.
+    pub(crate) fullscreen_restore_rect: Option<RECT>,
 }
 
 #[derive(Clone)]
@@ -84,12 +105,22 @@ impl Win32App {
         unsafe {
             winuser::RegisterClassExW(&class);
             winuser::IsGUIThread(1);
+
+            // Get unaccelerated mouse motion as `WM_INPUT` in whichever window has focus, for `Event::PointerRawMove`.
+            let raw_input_device = winuser::RAWINPUTDEVICE {
+                usUsagePage: 0x01, // HID_USAGE_PAGE_GENERIC
+                usUsage: 0x02,     // HID_USAGE_GENERIC_MOUSE
+                dwFlags: 0,
+                hwndTarget: ptr::null_mut(),
+            };
+            winuser::RegisterRawInputDevices(&raw_input_device, 1, mem::size_of::<winuser::RAWINPUTDEVICE>() as UINT);
         }
 
         let win32_app = Win32App {
             class_name_wstr,
             time_start: precise_time_ns(),
             race_signals: Mutex::new(Vec::new()),
+            race_events: Mutex::new(Vec::new()),
             event_callback: None,
             event_recur_block: false,
             event_loop_running: true,
@@ -99,6 +130,11 @@ impl Win32App {
             free_timers: Vec::new(),
             dpi_functions: DpiFunctions::new(),
             current_cursor: MouseCursor::Default,
+            custom_cursors: HashMap::new(),
+            pointer_locked: false,
+            tray: None,
+            displays: Vec::new(),
+            display_monitors: Vec::new(),
         };
 
         win32_app.dpi_functions.become_dpi_aware();
@@ -277,6 +313,23 @@ impl Win32App {
         }
     }
 
+    pub(crate) fn send_event_from_any_thread(event: Event) {
+        unsafe {
+            // No event loop (e.g. in tests), so there is no one to send the event to.
+            if GLOBAL_WIN32_APP.is_null() {
+                return;
+            }
+            let win32_app = &mut (*GLOBAL_WIN32_APP);
+            if let Ok(mut events) = win32_app.race_events.lock() {
+                events.push(event);
+                // If we have no windows yet, the message gets posted when the first window is created.
+                if !win32_app.all_windows.is_empty() {
+                    winuser::PostMessageW(win32_app.all_windows[0], WM_USER_EVENTS, 0, 0);
+                }
+            }
+        }
+    }
+
     pub(crate) fn terminate_event_loop(&mut self) {
         unsafe {
             if !self.all_windows.is_empty() {
@@ -293,7 +346,7 @@ impl Win32App {
 
     pub(crate) fn set_mouse_cursor(&mut self, cursor: MouseCursor) {
         if self.current_cursor != cursor {
-            let win32_cursor = match cursor {
+            let win32_cursor = match &cursor {
                 MouseCursor::Hidden => ptr::null(),
                 MouseCursor::Default => winuser::IDC_ARROW,
                 MouseCursor::Crosshair => winuser::IDC_CROSS,
@@ -321,20 +374,57 @@ impl Win32App {
 
                 MouseCursor::ColResize => winuser::IDC_SIZEWE,
                 MouseCursor::RowResize => winuser::IDC_SIZENS,
+
+                // Windows has no hand cursors for dragging, so use the closest ones.
+                MouseCursor::Grab => winuser::IDC_HAND,
+                MouseCursor::Grabbing => winuser::IDC_SIZEALL,
+
+                MouseCursor::Custom(custom_cursor) => {
+                    let hcursor = *self
+                        .custom_cursors
+                        .entry(custom_cursor.id)
+                        .or_insert_with(|| unsafe { create_custom_hcursor(custom_cursor) });
+                    self.current_cursor = cursor.clone();
+                    unsafe {
+                        // Fall back to the arrow if creating the cursor failed.
+                        winuser::SetCursor(if hcursor.is_null() {
+                            winuser::LoadCursorW(ptr::null_mut(), winuser::IDC_ARROW)
+                        } else {
+                            hcursor
+                        });
+                    }
+                    return;
+                }
             };
             self.current_cursor = cursor;
             unsafe {
+                // Our window class has no cursor, so setting a null cursor keeps it hidden while over our windows.
+                // (Using `ShowCursor` instead is error-prone, since it keeps a count of how often it's called.)
                 if win32_cursor == ptr::null() {
-                    winuser::ShowCursor(0);
+                    winuser::SetCursor(ptr::null_mut());
                 } else {
                     winuser::SetCursor(winuser::LoadCursorW(ptr::null_mut(), win32_cursor));
-                    winuser::ShowCursor(1);
                 }
             }
-            //TODO
         }
     }
 
+    /// Lock the cursor in place by clipping it to a single pixel at its current position, or release it.
+    /// Windows releases the clip by itself when switching apps, which we report in `WM_ACTIVATE`.
+    pub(crate) fn set_pointer_lock(&mut self, locked: bool) {
+        unsafe {
+            if locked {
+                let mut point = POINT { x: 0, y: 0 };
+                winuser::GetCursorPos(&mut point);
+                let rect = RECT { left: point.x, top: point.y, right: point.x + 1, bottom: point.y + 1 };
+                winuser::ClipCursor(&rect);
+            } else {
+                winuser::ClipCursor(ptr::null());
+            }
+        }
+        self.pointer_locked = locked;
+    }
+
     pub(crate) fn copy_text_to_clipboard(text: &str) {
         // plug it into the windows clipboard
         // make utf16 dta
@@ -356,6 +446,88 @@ impl Win32App {
             }
         }
     }
+
+    /// List the monitors using `EnumDisplayMonitors`, with the primary one first. Returns whether
+    /// [`Win32App::displays`] changed.
+    pub(crate) fn update_displays(&mut self) -> bool {
+        unsafe extern "system" fn enum_monitor(monitor: HMONITOR, _hdc: HDC, _rect: LPRECT, data: LPARAM) -> BOOL {
+            (*(data as *mut Vec<HMONITOR>)).push(monitor);
+            TRUE
+        }
+
+        let mut monitors: Vec<HMONITOR> = Vec::new();
+        unsafe {
+            winuser::EnumDisplayMonitors(
+                ptr::null_mut(),
+                ptr::null(),
+                Some(enum_monitor),
+                &mut monitors as *mut Vec<HMONITOR> as LPARAM,
+            );
+        }
+        let mut entries: Vec<(DisplayInfo, HMONITOR)> =
+            monitors
+                .into_iter()
+                .filter_map(|monitor| unsafe {
+                    let mut monitor_info: winuser::MONITORINFOEXW = mem::zeroed();
+                    monitor_info.cbSize = mem::size_of::<winuser::MONITORINFOEXW>() as u32;
+                    if winuser::GetMonitorInfoW(monitor, &mut monitor_info as *mut _ as *mut winuser::MONITORINFO) == FALSE {
+                        return None;
+                    }
+                    let device_name = &monitor_info.szDevice;
+                    let mut dev_mode: DEVMODEW = mem::zeroed();
+                    dev_mode.dmSize = mem::size_of::<DEVMODEW>() as u16;
+                    // A frequency of 0 or 1 means "the hardware's default".
+                    let refresh_rate =
+                        if winuser::EnumDisplaySettingsW(device_name.as_ptr(), winuser::ENUM_CURRENT_SETTINGS, &mut dev_mode)
+                            != FALSE
+                            && dev_mode.dmDisplayFrequency > 1
+                        {
+                            dev_mode.dmDisplayFrequency as f32
+                        } else {
+                            0.
+                        };
+                    let name_len = device_name.iter().position(|c| *c == 0).unwrap_or(device_name.len());
+                    let dpi_factor = self.dpi_functions.monitor_dpi_factor(monitor);
+                    let (rect, work) = (monitor_info.rcMonitor, monitor_info.rcWork);
+                    let display = DisplayInfo {
+                        display_id: 0,
+                        // E.g. "\\.\DISPLAY1".
+                        name: String::from_utf16_lossy(&device_name[..name_len]).trim_start_matches("\\\\.\\").to_string(),
+                        position: vec2(rect.left as f32, rect.top as f32) / dpi_factor,
+                        size: vec2((rect.right - rect.left) as f32, (rect.bottom - rect.top) as f32) / dpi_factor,
+                        work_area: Rect {
+                            pos: vec2((work.left - rect.left) as f32, (work.top - rect.top) as f32) / dpi_factor,
+                            size: vec2((work.right - work.left) as f32, (work.bottom - work.top) as f32) / dpi_factor,
+                        },
+                        dpi_factor,
+                        refresh_rate,
+                        is_primary: monitor_info.dwFlags & winuser::MONITORINFOF_PRIMARY != 0,
+                    };
+                    Some((display, monitor))
+                })
+                .collect();
+        entries.sort_by_key(|(display, _)| !display.is_primary);
+        for (display_id, (display, _)) in entries.iter_mut().enumerate() {
+            display.display_id = display_id;
+        }
+        let (displays, display_monitors): (Vec<_>, Vec<_>) = entries.into_iter().unzip();
+        self.display_monitors = display_monitors;
+        if displays != self.displays {
+            self.displays = displays;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Show a [`Tray`] icon, or remove it when passing `None`.
+    pub(crate) fn update_tray(&mut self, tray: Option<&Tray>, command_settings: &HashMap<CommandId, CxCommandSetting>) {
+        // Dropping the old `Win32Tray` removes its icon.
+        self.tray = None;
+        if let Some(tray) = tray {
+            self.tray = Win32Tray::new(tray, command_settings);
+        }
+    }
 }
 
 impl Win32Window {
@@ -373,6 +545,9 @@ impl Win32Window {
             pointers_down,
             hwnd: None,
             track_mouse_event: false,
+            style: CxWindowStyle { resizable: true, ..CxWindowStyle::default() },
+            icon: None,
+            fullscreen_restore_rect: None,
         }
     }
 
@@ -428,6 +603,11 @@ impl Win32Window {
                 }
                 sigs.truncate(0);
             }
+            if let Ok(events) = (*self.win32_app).race_events.lock() {
+                if !events.is_empty() {
+                    winuser::PostMessageW(hwnd, WM_USER_EVENTS, 0, 0);
+                }
+            }
         }
     }
 
@@ -444,6 +624,10 @@ impl Win32Window {
                     window.do_callback(&mut vec![Event::AppFocus]);
                 } else {
                     window.do_callback(&mut vec![Event::AppFocusLost]);
+                    if (*window.win32_app).pointer_locked {
+                        (*window.win32_app).set_pointer_lock(false);
+                        window.do_callback(&mut vec![Event::PointerLockChanged(PointerLockChangedEvent { locked: false })]);
+                    }
                 }
             }
             winuser::WM_NCCALCSIZE => {
@@ -463,39 +647,49 @@ impl Win32Window {
                 let mut rect = RECT { left: 0, top: 0, bottom: 0, right: 0 };
                 const EDGE: i32 = 8;
                 winuser::GetWindowRect(hwnd, &mut rect);
-                if xcoord < rect.left + EDGE {
-                    (*window.win32_app).current_cursor = MouseCursor::Hidden;
-                    if ycoord < rect.top + EDGE {
-                        window.do_callback(&mut vec![Event::System(SystemEvent::WindowSetHoverCursor(MouseCursor::NwseResize))]);
-                        return winuser::HTTOPLEFT;
+                if window.style.resizable && !window.style.borderless && window.fullscreen_restore_rect.is_none() {
+                    if xcoord < rect.left + EDGE {
+                        (*window.win32_app).current_cursor = MouseCursor::Hidden;
+                        if ycoord < rect.top + EDGE {
+                            window.do_callback(&mut vec![Event::System(SystemEvent::WindowSetHoverCursor(
+                                MouseCursor::NwseResize,
+                            ))]);
+                            return winuser::HTTOPLEFT;
+                        }
+                        if ycoord > rect.bottom - EDGE {
+                            window.do_callback(&mut vec![Event::System(SystemEvent::WindowSetHoverCursor(
+                                MouseCursor::NeswResize,
+                            ))]);
+                            return winuser::HTBOTTOMLEFT;
+                        }
+                        window.do_callback(&mut vec![Event::System(SystemEvent::WindowSetHoverCursor(MouseCursor::EwResize))]);
+                        return winuser::HTLEFT;
                     }
-                    if ycoord > rect.bottom - EDGE {
-                        window.do_callback(&mut vec![Event::System(SystemEvent::WindowSetHoverCursor(MouseCursor::NeswResize))]);
-                        return winuser::HTBOTTOMLEFT;
+                    if xcoord > rect.right - EDGE {
+                        (*window.win32_app).current_cursor = MouseCursor::Hidden;
+                        if ycoord < rect.top + EDGE {
+                            window.do_callback(&mut vec![Event::System(SystemEvent::WindowSetHoverCursor(
+                                MouseCursor::NeswResize,
+                            ))]);
+                            return winuser::HTTOPRIGHT;
+                        }
+                        if ycoord > rect.bottom - EDGE {
+                            window.do_callback(&mut vec![Event::System(SystemEvent::WindowSetHoverCursor(
+                                MouseCursor::NwseResize,
+                            ))]);
+                            return winuser::HTBOTTOMRIGHT;
+                        }
+                        window.do_callback(&mut vec![Event::System(SystemEvent::WindowSetHoverCursor(MouseCursor::EwResize))]);
+                        return winuser::HTRIGHT;
                     }
-                    window.do_callback(&mut vec![Event::System(SystemEvent::WindowSetHoverCursor(MouseCursor::EwResize))]);
-                    return winuser::HTLEFT;
-                }
-                if xcoord > rect.right - EDGE {
-                    (*window.win32_app).current_cursor = MouseCursor::Hidden;
                     if ycoord < rect.top + EDGE {
-                        window.do_callback(&mut vec![Event::System(SystemEvent::WindowSetHoverCursor(MouseCursor::NeswResize))]);
-                        return winuser::HTTOPRIGHT;
+                        window.do_callback(&mut vec![Event::System(SystemEvent::WindowSetHoverCursor(MouseCursor::NsResize))]);
+                        return winuser::HTTOP;
                     }
                     if ycoord > rect.bottom - EDGE {
-                        window.do_callback(&mut vec![Event::System(SystemEvent::WindowSetHoverCursor(MouseCursor::NwseResize))]);
-                        return winuser::HTBOTTOMRIGHT;
+                        window.do_callback(&mut vec![Event::System(SystemEvent::WindowSetHoverCursor(MouseCursor::NsResize))]);
+                        return winuser::HTBOTTOM;
                     }
-                    window.do_callback(&mut vec![Event::System(SystemEvent::WindowSetHoverCursor(MouseCursor::EwResize))]);
-                    return winuser::HTRIGHT;
-                }
-                if ycoord < rect.top + EDGE {
-                    window.do_callback(&mut vec![Event::System(SystemEvent::WindowSetHoverCursor(MouseCursor::NsResize))]);
-                    return winuser::HTTOP;
-                }
-                if ycoord > rect.bottom - EDGE {
-                    window.do_callback(&mut vec![Event::System(SystemEvent::WindowSetHoverCursor(MouseCursor::NsResize))]);
-                    return winuser::HTBOTTOM;
                 }
                 let mut events = vec![Event::WindowDragQuery(WindowDragQueryEvent {
                     window_id: window.window_id,
@@ -526,6 +720,19 @@ impl Win32Window {
                 }
                 return winuser::HTCLIENT;
             }
+            winuser::WM_GETMINMAXINFO => {
+                let min_max_info = &mut *(lparam as *mut winuser::MINMAXINFO);
+                let dpi = window.get_dpi_factor();
+                if let Some(min_size) = window.style.min_size {
+                    min_max_info.ptMinTrackSize.x = (min_size.x * dpi) as i32;
+                    min_max_info.ptMinTrackSize.y = (min_size.y * dpi) as i32;
+                }
+                if let Some(max_size) = window.style.max_size {
+                    min_max_info.ptMaxTrackSize.x = (max_size.x * dpi) as i32;
+                    min_max_info.ptMaxTrackSize.y = (max_size.y * dpi) as i32;
+                }
+                return 0;
+            }
             winuser::WM_ERASEBKGND => return 1,
             winuser::WM_MOUSEMOVE => {
                 if !window.track_mouse_event {
@@ -556,21 +763,35 @@ impl Win32Window {
                 })]);
                 (*window.win32_app).current_cursor = MouseCursor::Hidden;
             }
-            winuser::WM_MOUSEWHEEL => {
-                let delta = (wparam >> 16) as u16 as i16 as f32;
-                window.do_callback(&mut vec![Event::PointerScroll(PointerScrollEvent {
-                    digit: 0,
-                    window_id: window.window_id,
-                    scroll: Vec2 { x: 0.0, y: -delta },
-                    abs: window.last_mouse_pos,
-                    rel: window.last_mouse_pos,
-                    rect: Rect::default(),
-                    input_type: PointerInputType::Mouse,
-                    modifiers: Self::get_key_modifiers(),
-                    handled_x: false,
-                    handled_y: false,
-                    time: window.time_now(),
-                })]);
+            // Positive deltas mean scrolling up for the vertical wheel, but right for the horizontal one.
+            winuser::WM_MOUSEWHEEL => window.send_scroll(-((wparam >> 16) as u16 as i16), false),
+            winuser::WM_MOUSEHWHEEL => window.send_scroll((wparam >> 16) as u16 as i16, true),
+            winuser::WM_INPUT => {
+                let mut raw_input: winuser::RAWINPUT = mem::zeroed();
+                let mut size = mem::size_of::<winuser::RAWINPUT>() as UINT;
+                let result = winuser::GetRawInputData(
+                    lparam as winuser::HRAWINPUT,
+                    winuser::RID_INPUT,
+                    &mut raw_input as *mut _ as *mut c_void,
+                    &mut size,
+                    mem::size_of::<winuser::RAWINPUTHEADER>() as UINT,
+                );
+                if result != UINT::MAX && raw_input.header.dwType == winuser::RIM_TYPEMOUSE {
+                    let mouse = raw_input.data.mouse();
+                    // Tablets and remote desktop sessions report absolute positions, which we already get from
+                    // `WM_MOUSEMOVE`.
+                    if mouse.usFlags & winuser::MOUSE_MOVE_ABSOLUTE == 0 && (mouse.lLastX != 0 || mouse.lLastY != 0) {
+                        let dpi = window.get_dpi_factor();
+                        window.do_callback(&mut vec![Event::PointerRawMove(PointerRawMoveEvent {
+                            window_id: window.window_id,
+                            delta: vec2(mouse.lLastX as f32, mouse.lLastY as f32) / dpi,
+                            modifiers: Self::get_key_modifiers(),
+                            time: window.time_now(),
+                        })]);
+                    }
+                }
+                // `DefWindowProcW` has to clean up after `WM_INPUT`.
+                return winuser::DefWindowProcW(hwnd, msg, wparam, lparam);
             }
             winuser::WM_LBUTTONDOWN => window.send_pointer_down(0, MouseButton::Left, Self::get_key_modifiers()),
             winuser::WM_LBUTTONUP => window.send_pointer_up(0, MouseButton::Left, Self::get_key_modifiers()),
@@ -660,7 +881,30 @@ impl Win32Window {
                     window_id: window.window_id,
                 })]);
             }
-            winuser::WM_SIZE | winuser::WM_DPICHANGED => {
+            winuser::WM_DPICHANGED => {
+                // Move to the rectangle that Windows suggests for the new DPI, which keeps the window at the same
+                // logical size when dragging it between monitors with different scales (e.g. 100% and 150%).
+                let rect = &*(lparam as *const RECT);
+                winuser::SetWindowPos(
+                    hwnd,
+                    ptr::null_mut(),
+                    rect.left,
+                    rect.top,
+                    rect.right - rect.left,
+                    rect.bottom - rect.top,
+                    winuser::SWP_NOZORDER | winuser::SWP_NOACTIVATE,
+                );
+                window.send_change_event();
+                // This is also how we find out that the scale of a display was changed in the settings.
+                window.send_displays_changed_event();
+            }
+            winuser::WM_DISPLAYCHANGE => {
+                window.send_displays_changed_event();
+            }
+            winuser::WM_SETTINGCHANGE if wparam == winuser::SPI_SETWORKAREA as WPARAM => {
+                window.send_displays_changed_event();
+            }
+            winuser::WM_SIZE => {
                 //if window.ignore_wmsize > 1{
                 window.send_change_event();
                 // }
@@ -679,6 +923,15 @@ impl Win32Window {
                 signals.insert(Signal { signal_id: wparam as usize }, set);
                 window.do_callback(&mut vec![Event::Signal(SignalEvent { signals })]);
             }
+            WM_USER_EVENTS => {
+                let mut events = match (*window.win32_app).race_events.lock() {
+                    Ok(mut events) => std::mem::take(&mut *events),
+                    Err(_) => Vec::new(),
+                };
+                if !events.is_empty() {
+                    window.do_callback(&mut events);
+                }
+            }
             winuser::WM_CLOSE => {
                 // close requested
                 let mut events = vec![Event::WindowCloseRequested(WindowCloseRequestedEvent {
@@ -782,6 +1035,157 @@ impl Win32Window {
         }
     }
 
+    pub(crate) fn set_fullscreen(&mut self, fullscreen: bool) {
+        unsafe {
+            let hwnd = self.hwnd.unwrap();
+            if fullscreen && self.fullscreen_restore_rect.is_none() {
+                let mut window_rect = RECT { left: 0, top: 0, bottom: 0, right: 0 };
+                winuser::GetWindowRect(hwnd, &mut window_rect);
+                self.fullscreen_restore_rect = Some(window_rect);
+
+                // Our windows don't have a frame, so covering the whole monitor is enough.
+                let monitor = winuser::MonitorFromWindow(hwnd, MONITOR_DEFAULTTONEAREST);
+                let mut monitor_info: winuser::MONITORINFO = mem::zeroed();
+                monitor_info.cbSize = mem::size_of::<winuser::MONITORINFO>() as u32;
+                winuser::GetMonitorInfoW(monitor, &mut monitor_info);
+                let rect = monitor_info.rcMonitor;
+                winuser::SetWindowPos(
+                    hwnd,
+                    winuser::HWND_TOP,
+                    rect.left,
+                    rect.top,
+                    rect.right - rect.left,
+                    rect.bottom - rect.top,
+                    winuser::SWP_NOOWNERZORDER | winuser::SWP_FRAMECHANGED,
+                );
+            } else if !fullscreen {
+                if let Some(rect) = self.fullscreen_restore_rect.take() {
+                    winuser::SetWindowPos(
+                        hwnd,
+                        ptr::null_mut(),
+                        rect.left,
+                        rect.top,
+                        rect.right - rect.left,
+                        rect.bottom - rect.top,
+                        winuser::SWP_NOZORDER | winuser::SWP_NOOWNERZORDER | winuser::SWP_FRAMECHANGED,
+                    );
+                }
+            }
+        }
+        self.send_change_event();
+    }
+
+    /// Center the window in the work area of a display from [`Win32App::displays`], or cover the whole display when
+    /// fullscreen.
+    pub(crate) fn move_to_display(&mut self, display_id: usize) {
+        unsafe {
+            let win32_app = &*self.win32_app;
+            let monitor = if let Some(monitor) = win32_app.display_monitors.get(display_id) {
+                *monitor
+            } else {
+                return;
+            };
+            let hwnd = self.hwnd.unwrap();
+            let mut monitor_info: winuser::MONITORINFO = mem::zeroed();
+            monitor_info.cbSize = mem::size_of::<winuser::MONITORINFO>() as u32;
+            if winuser::GetMonitorInfoW(monitor, &mut monitor_info) == FALSE {
+                return;
+            }
+            if self.fullscreen_restore_rect.is_some() {
+                let rect = monitor_info.rcMonitor;
+                winuser::SetWindowPos(
+                    hwnd,
+                    winuser::HWND_TOP,
+                    rect.left,
+                    rect.top,
+                    rect.right - rect.left,
+                    rect.bottom - rect.top,
+                    winuser::SWP_NOOWNERZORDER | winuser::SWP_FRAMECHANGED,
+                );
+            } else {
+                // Moving to a display with a different scale resizes the window (see `WM_DPICHANGED`), so center it
+                // again if its size changed.
+                let work = monitor_info.rcWork;
+                let mut last_size = None;
+                for _ in 0..2 {
+                    let mut window_rect = RECT { left: 0, top: 0, bottom: 0, right: 0 };
+                    winuser::GetWindowRect(hwnd, &mut window_rect);
+                    let size = (window_rect.right - window_rect.left, window_rect.bottom - window_rect.top);
+                    if last_size == Some(size) {
+                        break;
+                    }
+                    last_size = Some(size);
+                    winuser::SetWindowPos(
+                        hwnd,
+                        ptr::null_mut(),
+                        work.left + ((work.right - work.left) - size.0).max(0) / 2,
+                        work.top + ((work.bottom - work.top) - size.1).max(0) / 2,
+                        0,
+                        0,
+                        winuser::SWP_NOZORDER | winuser::SWP_NOSIZE | winuser::SWP_NOACTIVATE,
+                    );
+                }
+            }
+        }
+        self.send_change_event();
+    }
+
+    pub(crate) fn send_displays_changed_event(&mut self) {
+        unsafe {
+            if (*self.win32_app).update_displays() {
+                let displays = (*self.win32_app).displays.clone();
+                self.do_callback(&mut vec![Event::DisplaysChanged(DisplaysChangedEvent { displays })]);
+            }
+        }
+    }
+
+    pub(crate) fn set_title(&self, title: &str) {
+        unsafe {
+            let title_wstr: Vec<_> = OsStr::new(title).encode_wide().chain(Some(0)).collect();
+            winuser::SetWindowTextW(self.hwnd.unwrap(), title_wstr.as_ptr());
+        }
+    }
+
+    pub(crate) fn set_icon(&mut self, icon: &WindowIcon) {
+        unsafe {
+            let hicon = create_hicon(icon);
+            if hicon.is_null() {
+                return;
+            }
+            let hwnd = self.hwnd.unwrap();
+            winuser::SendMessageW(hwnd, winuser::WM_SETICON, winuser::ICON_BIG as WPARAM, hicon as LPARAM);
+            winuser::SendMessageW(hwnd, winuser::WM_SETICON, winuser::ICON_SMALL as WPARAM, hicon as LPARAM);
+            if let Some(old_icon) = self.icon.replace(hicon) {
+                winuser::DestroyIcon(old_icon);
+            }
+        }
+    }
+
+    /// Min and max sizes get applied in `WM_GETMINMAXINFO`, and resize borders in `WM_NCHITTEST`.
+    pub(crate) fn set_style(&mut self, style: &CxWindowStyle) {
+        self.style = style.clone();
+        unsafe {
+            let hwnd = self.hwnd.unwrap();
+            // Without these the window can still be resized using Aero Snap.
+            let mut window_style = winuser::GetWindowLongW(hwnd, winuser::GWL_STYLE) as u32;
+            if style.resizable {
+                window_style |= winuser::WS_SIZEBOX | winuser::WS_MAXIMIZEBOX;
+            } else {
+                window_style &= !(winuser::WS_SIZEBOX | winuser::WS_MAXIMIZEBOX);
+            }
+            winuser::SetWindowLongW(hwnd, winuser::GWL_STYLE, window_style as i32);
+            winuser::SetWindowPos(
+                hwnd,
+                ptr::null_mut(),
+                0,
+                0,
+                0,
+                0,
+                winuser::SWP_NOMOVE | winuser::SWP_NOSIZE | winuser::SWP_NOZORDER | winuser::SWP_FRAMECHANGED,
+            );
+        }
+    }
+
     pub(crate) fn get_is_topmost(&self) -> bool {
         unsafe {
             let ex_style = winuser::GetWindowLongW(self.hwnd.unwrap(), winuser::GWL_EXSTYLE) as u32;
@@ -796,9 +1200,10 @@ impl Win32Window {
         WindowGeom {
             xr_can_present: false,
             xr_is_presenting: false,
-            can_fullscreen: false,
+            can_fullscreen: true,
             is_topmost: self.get_is_topmost(),
-            is_fullscreen: self.get_is_maximized(),
+            is_fullscreen: self.fullscreen_restore_rect.is_some(),
+            is_maximized: self.get_is_maximized(),
             inner_size: self.get_inner_size(),
             outer_size: self.get_outer_size(),
             dpi_factor: self.get_dpi_factor(),
@@ -963,6 +1368,41 @@ impl Win32Window {
         })]);
     }
 
+    /// Send a scroll for a `WM_MOUSEWHEEL` or `WM_MOUSEHWHEEL`, where `delta` is in multiples of `WHEEL_DELTA` per
+    /// wheel step, using the number of lines or characters per step from the system settings.
+    pub(crate) fn send_scroll(&mut self, delta: i16, horizontal: bool) {
+        let mut lines_per_step: UINT = DEFAULT_LINES_PER_WHEEL_STEP as UINT;
+        let setting = if horizontal { winuser::SPI_GETWHEELSCROLLCHARS } else { winuser::SPI_GETWHEELSCROLLLINES };
+        unsafe {
+            winuser::SystemParametersInfoW(setting, 0, &mut lines_per_step as *mut _ as *mut c_void, 0);
+        }
+        let steps = delta as f32 / winuser::WHEEL_DELTA as f32;
+        let pixels = if lines_per_step == winuser::WHEEL_PAGESCROLL {
+            steps * SCROLL_PAGE_HEIGHT
+        } else {
+            steps * lines_per_step as f32 * SCROLL_LINE_HEIGHT
+        };
+        // Precision touchpads and high-resolution wheels send deltas that are a fraction of a step. They also send
+        // their own momentum.
+        let device = if delta % winuser::WHEEL_DELTA == 0 { ScrollDevice::Wheel } else { ScrollDevice::Trackpad };
+        self.do_callback(&mut vec![Event::PointerScroll(PointerScrollEvent {
+            digit: 0,
+            window_id: self.window_id,
+            scroll: if horizontal { Vec2 { x: pixels, y: 0. } } else { Vec2 { x: 0., y: pixels } },
+            pinch: 0.,
+            device,
+            phase: ScrollPhase::Changed,
+            abs: self.last_mouse_pos,
+            rel: self.last_mouse_pos,
+            rect: Rect::default(),
+            input_type: PointerInputType::Mouse,
+            modifiers: Win32Window::get_key_modifiers(),
+            handled_x: false,
+            handled_y: false,
+            time: self.time_now(),
+        })]);
+    }
+
     pub(crate) fn send_pointer_hover_and_move(&mut self, pos: Vec2, modifiers: KeyModifiers) {
         self.last_mouse_pos = pos;
         let mut events = Vec::new();
@@ -1215,12 +1655,25 @@ impl DpiFunctions {
         None
     }*/
 
-    pub(crate) fn hwnd_dpi_factor(&self, hwnd: HWND) -> f32 {
+    /// The scale of a monitor, falling back to the system scale on Windows versions before 8.1.
+    pub(crate) fn monitor_dpi_factor(&self, monitor: HMONITOR) -> f32 {
         unsafe {
-            let hdc = winuser::GetDC(hwnd);
-            if hdc.is_null() {
-                panic!("`GetDC` returned null!");
+            if let Some(get_dpi_for_monitor) = self.get_dpi_for_monitor {
+                let mut dpi_x = 0;
+                let mut dpi_y = 0;
+                if get_dpi_for_monitor(monitor, MDT_EFFECTIVE_DPI, &mut dpi_x, &mut dpi_y) == S_OK {
+                    return dpi_x as f32 / BASE_DPI as f32;
+                }
             }
+            let hdc = winuser::GetDC(ptr::null_mut());
+            let dpi = GetDeviceCaps(hdc, LOGPIXELSX) as u32;
+            winuser::ReleaseDC(ptr::null_mut(), hdc);
+            dpi as f32 / BASE_DPI as f32
+        }
+    }
+
+    pub(crate) fn hwnd_dpi_factor(&self, hwnd: HWND) -> f32 {
+        unsafe {
             let dpi = if let Some(get_dpi_for_window) = self.get_dpi_for_window {
                 // We are on Windows 10 Anniversary Update (1607) or later.
                 match get_dpi_for_window(hwnd) {
@@ -1246,7 +1699,14 @@ impl DpiFunctions {
                 if winuser::IsProcessDPIAware() != FALSE {
                     // If the process is DPI aware, then scaling must be handled by the application using
                     // this DPI value.
-                    GetDeviceCaps(hdc, LOGPIXELSX) as u32
+                    let hdc = winuser::GetDC(hwnd);
+                    if hdc.is_null() {
+                        panic!("`GetDC` returned null!");
+                    }
+                    let dpi = GetDeviceCaps(hdc, LOGPIXELSX) as u32;
+                    // This gets called for every pointer event, so don't leak device contexts.
+                    winuser::ReleaseDC(hwnd, hdc);
+                    dpi
                 } else {
                     // If the process is DPI unaware, then scaling is performed by the OS; we thus return
                     // 96 (scale factor 1.0) to prevent the window from being re-scaled by both the
@@ -1258,3 +1718,207 @@ impl DpiFunctions {
         }
     }
 }
+
+/// Create an icon from a [`WindowIcon`]. Returns null if that failed.
+unsafe fn create_hicon(icon: &WindowIcon) -> HICON {
+    // Icons are BGRA, with an AND mask of one bit per pixel and rows padded to 16 bits.
+    let bgra: Vec<u8> = icon.rgba.chunks_exact(4).flat_map(|rgba| [rgba[2], rgba[1], rgba[0], rgba[3]]).collect();
+    let and_mask = vec![0u8; (icon.width + 15) / 16 * 2 * icon.height];
+    winuser::CreateIcon(
+        libloaderapi::GetModuleHandleW(ptr::null()),
+        icon.width as i32,
+        icon.height as i32,
+        1,
+        32,
+        and_mask.as_ptr(),
+        bgra.as_ptr(),
+    )
+}
+
+/// Create a cursor from a [`CustomCursor`]. Returns null if that failed.
+unsafe fn create_custom_hcursor(custom_cursor: &CustomCursor) -> HCURSOR {
+    // Like in `create_hicon`, but with a hotspot, which requires `CreateIconIndirect`. The color bitmap has an alpha
+    // channel, so the AND mask is all zeros.
+    let bgra: Vec<u8> = custom_cursor.rgba.chunks_exact(4).flat_map(|rgba| [rgba[2], rgba[1], rgba[0], rgba[3]]).collect();
+    let and_mask = vec![0u8; (custom_cursor.width + 15) / 16 * 2 * custom_cursor.height];
+    let color = CreateBitmap(custom_cursor.width as i32, custom_cursor.height as i32, 1, 32, bgra.as_ptr() as *const c_void);
+    let mask = CreateBitmap(custom_cursor.width as i32, custom_cursor.height as i32, 1, 1, and_mask.as_ptr() as *const c_void);
+    let mut icon_info = winuser::ICONINFO {
+        fIcon: FALSE,
+        xHotspot: custom_cursor.hotspot.x as DWORD,
+        yHotspot: custom_cursor.hotspot.y as DWORD,
+        hbmMask: mask,
+        hbmColor: color,
+    };
+    let hcursor = winuser::CreateIconIndirect(&mut icon_info);
+    // `CreateIconIndirect` copies the bitmaps.
+    DeleteObject(color as *mut c_void);
+    DeleteObject(mask as *mut c_void);
+    hcursor
+}
+
+/// The message that `Shell_NotifyIconW` sends to [`Win32Tray::hwnd`] when the user interacts with the icon.
+const WM_TRAY_ICON: UINT = winuser::WM_USER + 1;
+
+/// An icon in the notification area of the taskbar; see [`Tray`].
+pub(crate) struct Win32Tray {
+    /// A message-only window that receives [`WM_TRAY_ICON`].
+    hwnd: HWND,
+    hicon: HICON,
+    hmenu: HMENU,
+    /// The commands of the menu items; the identifier of a menu item is its index in here plus one, since
+    /// `TrackPopupMenu` returns 0 when no item was picked.
+    commands: Vec<CommandId>,
+}
+
+impl Win32Tray {
+    /// Add a new icon to the notification area. Returns `None` if that failed, e.g. when Explorer isn't running.
+    fn new(tray: &Tray, command_settings: &HashMap<CommandId, CxCommandSetting>) -> Option<Win32Tray> {
+        unsafe {
+            let class_name_wstr: Vec<u16> = OsStr::new("ZapTray").encode_wide().chain(Some(0)).collect();
+            let class = winuser::WNDCLASSEXW {
+                cbSize: mem::size_of::<winuser::WNDCLASSEXW>() as UINT,
+                style: 0,
+                lpfnWndProc: Some(Win32Tray::window_proc),
+                cbClsExtra: 0,
+                cbWndExtra: 0,
+                hInstance: libloaderapi::GetModuleHandleW(ptr::null()),
+                hIcon: ptr::null_mut(),
+                hCursor: ptr::null_mut(),
+                hbrBackground: ptr::null_mut(),
+                lpszMenuName: ptr::null(),
+                lpszClassName: class_name_wstr.as_ptr(),
+                hIconSm: ptr::null_mut(),
+            };
+            // This fails after the first time, since the class is already registered, which is fine.
+            winuser::RegisterClassExW(&class);
+            let hwnd = winuser::CreateWindowExW(
+                0,
+                class_name_wstr.as_ptr(),
+                class_name_wstr.as_ptr(),
+                0,
+                0,
+                0,
+                0,
+                0,
+                winuser::HWND_MESSAGE,
+                ptr::null_mut(),
+                libloaderapi::GetModuleHandleW(ptr::null()),
+                ptr::null_mut(),
+            );
+
+            let hicon = create_hicon(&tray.icon);
+            let mut notify_icon_data = Self::notify_icon_data(hwnd);
+            notify_icon_data.uFlags = shellapi::NIF_MESSAGE | shellapi::NIF_ICON | shellapi::NIF_TIP;
+            notify_icon_data.uCallbackMessage = WM_TRAY_ICON;
+            notify_icon_data.hIcon = hicon;
+            // Leave room for the null terminator.
+            let tooltip_wstr: Vec<u16> = OsStr::new(&tray.tooltip).encode_wide().take(notify_icon_data.szTip.len() - 1).collect();
+            notify_icon_data.szTip[..tooltip_wstr.len()].copy_from_slice(&tooltip_wstr);
+
+            let hmenu = winuser::CreatePopupMenu();
+            let mut commands = Vec::new();
+            Self::append_items(hmenu, &tray.items, command_settings, &mut commands);
+
+            let tray = Win32Tray { hwnd, hicon, hmenu, commands };
+            if shellapi::Shell_NotifyIconW(shellapi::NIM_ADD, &mut notify_icon_data) == FALSE {
+                log!("Adding the tray icon failed");
+                return None;
+            }
+            Some(tray)
+        }
+    }
+
+    unsafe fn notify_icon_data(hwnd: HWND) -> shellapi::NOTIFYICONDATAW {
+        let mut notify_icon_data = mem::zeroed::<shellapi::NOTIFYICONDATAW>();
+        notify_icon_data.cbSize = mem::size_of::<shellapi::NOTIFYICONDATAW>() as DWORD;
+        notify_icon_data.hWnd = hwnd;
+        notify_icon_data.uID = 1;
+        notify_icon_data
+    }
+
+    unsafe fn append_items(
+        hmenu: HMENU,
+        items: &[Menu],
+        command_settings: &HashMap<CommandId, CxCommandSetting>,
+        commands: &mut Vec<CommandId>,
+    ) {
+        for item in items {
+            match item {
+                Menu::Item { name, command } => {
+                    let name_wstr: Vec<u16> = OsStr::new(name).encode_wide().chain(Some(0)).collect();
+                    let enabled = matches!(command_settings.get(command), Some(settings) if settings.enabled);
+                    commands.push(*command);
+                    winuser::AppendMenuW(
+                        hmenu,
+                        if enabled { winuser::MF_STRING } else { winuser::MF_STRING | winuser::MF_GRAYED },
+                        commands.len(),
+                        name_wstr.as_ptr(),
+                    );
+                }
+                Menu::Sub { name, items } => {
+                    let name_wstr: Vec<u16> = OsStr::new(name).encode_wide().chain(Some(0)).collect();
+                    let sub_menu = winuser::CreatePopupMenu();
+                    Self::append_items(sub_menu, items, command_settings, commands);
+                    winuser::AppendMenuW(hmenu, winuser::MF_POPUP, sub_menu as UINT_PTR, name_wstr.as_ptr());
+                }
+                Menu::Main { items } => Self::append_items(hmenu, items, command_settings, commands),
+                Menu::Line => {
+                    winuser::AppendMenuW(hmenu, winuser::MF_SEPARATOR, 0, ptr::null());
+                }
+            }
+        }
+    }
+
+    /// Show the menu at the mouse cursor, and return the command of the picked item, if any.
+    unsafe fn show_menu(&self) -> Option<CommandId> {
+        let mut point = POINT { x: 0, y: 0 };
+        winuser::GetCursorPos(&mut point);
+        // Without this the menu doesn't close when clicking outside of it.
+        winuser::SetForegroundWindow(self.hwnd);
+        let item_id = winuser::TrackPopupMenu(
+            self.hmenu,
+            winuser::TPM_RETURNCMD | winuser::TPM_NONOTIFY | winuser::TPM_RIGHTBUTTON,
+            point.x,
+            point.y,
+            0,
+            self.hwnd,
+            ptr::null(),
+        );
+        winuser::PostMessageW(self.hwnd, winuser::WM_NULL, 0, 0);
+        if item_id > 0 {
+            self.commands.get(item_id as usize - 1).copied()
+        } else {
+            None
+        }
+    }
+
+    unsafe extern "system" fn window_proc(hwnd: HWND, msg: UINT, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
+        if msg == WM_TRAY_ICON {
+            // Without `NOTIFYICON_VERSION_4`, `lparam` is just the mouse message.
+            let mouse_msg = lparam as UINT;
+            if mouse_msg == winuser::WM_LBUTTONUP || mouse_msg == winuser::WM_RBUTTONUP {
+                let win32_app = &mut (*GLOBAL_WIN32_APP);
+                if let Some(command) = win32_app.tray.as_ref().and_then(|tray| tray.show_menu()) {
+                    win32_app.do_callback(&mut vec![Event::Command(command)]);
+                }
+            }
+            return 0;
+        }
+        winuser::DefWindowProcW(hwnd, msg, wparam, lparam)
+    }
+}
+
+impl Drop for Win32Tray {
+    fn drop(&mut self) {
+        unsafe {
+            shellapi::Shell_NotifyIconW(shellapi::NIM_DELETE, &mut Self::notify_icon_data(self.hwnd));
+            // This also destroys the submenus.
+            winuser::DestroyMenu(self.hmenu);
+            if !self.hicon.is_null() {
+                winuser::DestroyIcon(self.hicon);
+            }
+            winuser::DestroyWindow(self.hwnd);
+        }
+    }
+}
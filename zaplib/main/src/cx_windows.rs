@@ -21,6 +21,8 @@ impl Cx {
         let mut win32_app = Win32App::new();
 
         win32_app.init();
+        win32_app.update_displays();
+        self.displays = win32_app.displays.clone();
 
         let mut d3d11_windows: Vec<D3d11Window> = Vec::new();
 
@@ -137,6 +139,15 @@ impl Cx {
                                         }
                                     }
 
+                                    // Before `window_command`, so that going fullscreen uses the new display.
+                                    if let Some(display_id) = window.window_set_display.take() {
+                                        for d3d11_window in &mut d3d11_windows {
+                                            if d3d11_window.window_id == index {
+                                                d3d11_window.win32_window.move_to_display(display_id);
+                                            }
+                                        }
+                                    }
+
                                     window.window_command = match &window.window_command {
                                         CxWindowCmd::Restore => {
                                             for d3d11_window in &mut d3d11_windows {
@@ -162,6 +173,22 @@ impl Cx {
                                             }
                                             CxWindowCmd::None
                                         }
+                                        CxWindowCmd::FullScreen => {
+                                            for d3d11_window in &mut d3d11_windows {
+                                                if d3d11_window.window_id == index {
+                                                    d3d11_window.win32_window.set_fullscreen(true);
+                                                }
+                                            }
+                                            CxWindowCmd::None
+                                        }
+                                        CxWindowCmd::NormalScreen => {
+                                            for d3d11_window in &mut d3d11_windows {
+                                                if d3d11_window.window_id == index {
+                                                    d3d11_window.win32_window.set_fullscreen(false);
+                                                }
+                                            }
+                                            CxWindowCmd::None
+                                        }
                                         _ => CxWindowCmd::None,
                                     };
 
@@ -174,17 +201,41 @@ impl Cx {
                                             }
                                         }
                                     }
+
+                                    if let Some(title) = window.window_set_title.take() {
+                                        for d3d11_window in &mut d3d11_windows {
+                                            if d3d11_window.window_id == index {
+                                                d3d11_window.win32_window.set_title(&title);
+                                            }
+                                        }
+                                    }
+
+                                    if let Some(icon) = window.window_set_icon.take() {
+                                        for d3d11_window in &mut d3d11_windows {
+                                            if d3d11_window.window_id == index {
+                                                d3d11_window.win32_window.set_icon(&icon);
+                                            }
+                                        }
+                                    }
+
+                                    if window.window_style_changed {
+                                        window.window_style_changed = false;
+                                        for d3d11_window in &mut d3d11_windows {
+                                            if d3d11_window.window_id == index {
+                                                d3d11_window.win32_window.set_style(&window.window_style);
+                                            }
+                                        }
+                                    }
                                 }
 
-                                // set a cursor
-                                if self.down_mouse_cursor.is_some() {
-                                    win32_app.set_mouse_cursor(self.down_mouse_cursor.as_ref().unwrap().clone())
-                                } else if self.hover_mouse_cursor.is_some() {
-                                    win32_app.set_mouse_cursor(self.hover_mouse_cursor.as_ref().unwrap().clone())
-                                } else {
-                                    win32_app.set_mouse_cursor(MouseCursor::Default)
+                                if self.pointer_lock_requested != self.pointer_locked {
+                                    win32_app.set_pointer_lock(self.pointer_lock_requested);
+                                    self.pointer_locked = self.pointer_lock_requested;
                                 }
 
+                                // set a cursor
+                                win32_app.set_mouse_cursor(self.current_mouse_cursor());
+
                                 if let Some(set_ime_position) = self.platform.set_ime_position {
                                     self.platform.set_ime_position = None;
                                     for d3d11_window in &mut d3d11_windows {
@@ -192,6 +243,10 @@ impl Cx {
                                     }
                                 }
 
+                                if self.tray_needs_update() {
+                                    win32_app.update_tray(self.platform.desktop.tray.as_ref(), &self.command_settings);
+                                }
+
                                 while !self.platform.start_timer.is_empty() {
                                     let (timer_id, interval, repeats) = self.platform.start_timer.pop().unwrap();
                                     win32_app.start_timer(timer_id, interval, repeats);
@@ -221,6 +276,7 @@ impl Cx {
                                                     self.passes[*pass_id].set_dpi_factor(dpi_factor);
 
                                                     d3d11_window.resize_buffers(&d3d11_cx);
+                                                    d3d11_window.wait_for_swap_chain();
 
                                                     self.draw_pass_to_window(
                                                         *pass_id,
@@ -270,12 +326,6 @@ impl Cx {
         })
     }
 
-    /// TODO(JP): Generalize [`Cx::post_signal`] into this.
-    #[cfg(feature = "cef")]
-    pub(crate) fn send_event_from_any_thread(_event: Event) {
-        todo!();
-    }
-
     #[cfg(feature = "cef")]
     pub(crate) fn cef_schedule_message_pump_work(_delay_ms: i64) {
         todo!();
@@ -293,16 +343,15 @@ impl CxPlatformCommon for Cx {
 
     /// See [`CxPlatformCommon::start_timer`] for documentation.
     fn start_timer(&mut self, interval: f64, repeats: bool) -> Timer {
-        self.last_timer_id += 1;
-        self.platform.start_timer.push((self.last_timer_id, interval, repeats));
-        Timer { timer_id: self.last_timer_id }
+        let timer_id = self.new_timer_id(repeats);
+        self.platform.start_timer.push((timer_id, interval, repeats));
+        Timer { timer_id }
     }
 
     /// See [`CxPlatformCommon::stop_timer`] for documentation.
     fn stop_timer(&mut self, timer: &mut Timer) {
-        if timer.timer_id != 0 {
-            self.platform.stop_timer.push(timer.timer_id);
-            timer.timer_id = 0;
+        if let Some(timer_id) = self.take_active_timer_id(timer) {
+            self.platform.stop_timer.push(timer_id);
         }
     }
 
@@ -314,13 +363,19 @@ impl CxPlatformCommon for Cx {
     /// See [`CxPlatformCommon::update_menu`] for documentation.
     fn update_menu(&mut self, _menu: &Menu) {}
 
+    /// See [`CxPlatformCommon::update_tray`] for documentation.
+    fn update_tray(&mut self, tray: Option<&Tray>) {
+        self.platform.desktop.update_tray(tray);
+    }
+
     /// See [`CxPlatformCommon::update_menu`] for documentation.
     fn copy_text_to_clipboard(&mut self, text: &str) {
         Win32App::copy_text_to_clipboard(text);
     }
 
-    fn send_event_from_any_thread(_event: Event) {
-        todo!();
+    /// See [`CxPlatformCommon::send_event_from_any_thread`] for documentation.
+    fn send_event_from_any_thread(event: Event) {
+        Win32App::send_event_from_any_thread(event);
     }
 }
 
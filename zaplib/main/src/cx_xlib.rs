@@ -33,7 +33,6 @@ pub(crate) struct XlibApp {
     pub(crate) signal_fd: c_int,
     pub(crate) window_map: HashMap<c_ulong, *mut XlibWindow>,
     pub(crate) time_start: u64,
-    pub(crate) last_scroll_time: f64,
     pub(crate) last_click_time: f64,
     pub(crate) last_click_pos: (i32, i32),
     pub(crate) event_callback: Option<*mut dyn FnMut(&mut XlibApp, &mut Vec<Event>) -> bool>,
@@ -44,6 +43,14 @@ pub(crate) struct XlibApp {
     pub(crate) signals: Mutex<Vec<Event>>,
     pub(crate) loop_block: bool,
     pub(crate) current_cursor: MouseCursor,
+    /// The position on the root window that the pointer is locked at, if any; see [`XlibApp::set_pointer_lock`].
+    pub(crate) pointer_lock_position: Option<(c_int, c_int)>,
+    /// The position on the root window of the last `MotionNotify`, to compute [`Event::PointerRawMove`] from.
+    pub(crate) last_motion_root: Option<(c_int, c_int)>,
+    /// From the `Xft.dpi` resource, which X11 has for the whole screen rather than per monitor.
+    pub(crate) dpi_factor: f32,
+    /// See [`XlibApp::update_displays`].
+    pub(crate) displays: Vec<DisplayInfo>,
 
     pub(crate) atom_clipboard: X11_sys::Atom,
     pub(crate) atom_net_wm_moveresize: X11_sys::Atom,
@@ -53,14 +60,20 @@ pub(crate) struct XlibApp {
     pub(crate) atom_net_wm_state: X11_sys::Atom,
     pub(crate) atom_new_wm_state_maximized_horz: X11_sys::Atom,
     pub(crate) atom_new_wm_state_maximized_vert: X11_sys::Atom,
+    pub(crate) atom_net_wm_state_fullscreen: X11_sys::Atom,
+    pub(crate) atom_net_wm_name: X11_sys::Atom,
+    pub(crate) atom_net_wm_icon: X11_sys::Atom,
+    pub(crate) atom_cardinal: X11_sys::Atom,
     pub(crate) atom_targets: X11_sys::Atom,
     pub(crate) atom_utf8_string: X11_sys::Atom,
     pub(crate) atom_text: X11_sys::Atom,
     pub(crate) atom_multiple: X11_sys::Atom,
     pub(crate) atom_text_plain: X11_sys::Atom,
     pub(crate) atom_atom: X11_sys::Atom,
+    pub(crate) atom_resource_manager: X11_sys::Atom,
 
     pub(crate) dnd: Dnd,
+    pub(crate) tray: Option<XlibTray>,
 }
 
 #[derive(Clone)]
@@ -76,6 +89,7 @@ pub(crate) struct XlibWindow {
     pub(crate) xlib_app: *mut XlibApp,
     pub(crate) last_window_geom: WindowGeom,
     pub(crate) time_start: u64,
+    pub(crate) style: CxWindowStyle,
 
     pub(crate) ime_spot: Vec2,
     pub(crate) current_cursor: MouseCursor,
@@ -131,18 +145,26 @@ impl XlibApp {
                     CString::new("_NET_WM_STATE_MAXIMIZED_VERT").unwrap().as_ptr(),
                     0,
                 ),
+                atom_net_wm_state_fullscreen: X11_sys::XInternAtom(
+                    display,
+                    CString::new("_NET_WM_STATE_FULLSCREEN").unwrap().as_ptr(),
+                    0,
+                ),
+                atom_net_wm_name: X11_sys::XInternAtom(display, CString::new("_NET_WM_NAME").unwrap().as_ptr(), 0),
+                atom_net_wm_icon: X11_sys::XInternAtom(display, CString::new("_NET_WM_ICON").unwrap().as_ptr(), 0),
+                atom_cardinal: X11_sys::XInternAtom(display, CString::new("CARDINAL").unwrap().as_ptr(), 0),
                 atom_targets: X11_sys::XInternAtom(display, CString::new("TARGETS").unwrap().as_ptr(), 0),
                 atom_utf8_string: X11_sys::XInternAtom(display, CString::new("UTF8_STRING").unwrap().as_ptr(), 1),
                 atom_atom: X11_sys::XInternAtom(display, CString::new("ATOM").unwrap().as_ptr(), 0),
                 atom_text: X11_sys::XInternAtom(display, CString::new("TEXT").unwrap().as_ptr(), 0),
                 atom_text_plain: X11_sys::XInternAtom(display, CString::new("text/plain").unwrap().as_ptr(), 0),
                 atom_multiple: X11_sys::XInternAtom(display, CString::new("MULTIPLE").unwrap().as_ptr(), 0),
+                atom_resource_manager: X11_sys::XInternAtom(display, CString::new("RESOURCE_MANAGER").unwrap().as_ptr(), 0),
                 xim,
                 display,
                 display_fd,
                 signal_fd,
                 clipboard: String::new(),
-                last_scroll_time: 0.0,
                 last_click_time: 0.0,
                 last_click_pos: (0, 0),
                 window_map: HashMap::new(),
@@ -155,7 +177,12 @@ impl XlibApp {
                 timers: VecDeque::new(),
                 free_timers: Vec::new(),
                 current_cursor: MouseCursor::Default,
+                pointer_lock_position: None,
+                last_motion_root: None,
+                dpi_factor: 1.0,
+                displays: Vec::new(),
                 dnd: Dnd::new(display),
+                tray: None,
             }
         }
     }
@@ -166,6 +193,187 @@ impl XlibApp {
             X11_sys::XrmInitialize();
             //}
             GLOBAL_XLIB_APP = self;
+
+            // Desktop environments update `Xft.dpi` on the root window when the scale changes in their settings, and
+            // the root window gets resized when monitors are connected, disconnected or rearranged.
+            let root_window = X11_sys::XRootWindow(self.display, X11_sys::XDefaultScreen(self.display));
+            X11_sys::XSelectInput(
+                self.display,
+                root_window,
+                (X11_sys::PropertyChangeMask | X11_sys::StructureNotifyMask) as c_long,
+            );
+            self.dpi_factor = self.read_dpi_factor();
+            self.update_displays();
+        }
+    }
+
+    /// Read `Xft.dpi` from the `RESOURCE_MANAGER` property of the root window. We don't use `XResourceManagerString`,
+    /// since that returns the value from when the display was opened. Fractional scales like 125% show up as e.g. 120.
+    fn read_dpi_factor(&self) -> f32 {
+        unsafe {
+            let root_window = X11_sys::XRootWindow(self.display, X11_sys::XDefaultScreen(self.display));
+            let mut actual_type = mem::MaybeUninit::uninit();
+            let mut actual_format = mem::MaybeUninit::uninit();
+            let mut n_items = mem::MaybeUninit::uninit();
+            let mut bytes_after = mem::MaybeUninit::uninit();
+            let mut ret = mem::MaybeUninit::uninit();
+            X11_sys::XGetWindowProperty(
+                self.display,
+                root_window,
+                self.atom_resource_manager,
+                0,
+                c_long::MAX / 4,
+                0,
+                X11_sys::AnyPropertyType as c_ulong,
+                actual_type.as_mut_ptr(),
+                actual_format.as_mut_ptr(),
+                n_items.as_mut_ptr(),
+                bytes_after.as_mut_ptr(),
+                ret.as_mut_ptr(),
+            );
+            let ret = ret.assume_init();
+            if ret.is_null() {
+                return 1.0;
+            }
+            let db = X11_sys::XrmGetStringDatabase(ret as *const c_char);
+            X11_sys::XFree(ret as *mut c_void);
+            let mut ty = mem::MaybeUninit::uninit();
+            let mut value = mem::MaybeUninit::uninit();
+            let found = X11_sys::XrmGetResource(
+                db,
+                CString::new("Xft.dpi").unwrap().as_ptr(),
+                CString::new("String").unwrap().as_ptr(),
+                ty.as_mut_ptr(),
+                value.as_mut_ptr(),
+            );
+            let dpi = if found != 0 {
+                let value = value.assume_init();
+                CStr::from_ptr(value.addr).to_str().ok().and_then(|dpi| dpi.trim().parse::<f32>().ok())
+            } else {
+                None
+            };
+            X11_sys::XrmDestroyDatabase(db);
+            match dpi {
+                Some(dpi) if dpi > 0. => dpi / 96.0,
+                _ => 1.0, // TODO find some other way to figure it out
+            }
+        }
+    }
+
+    /// List the monitors using XRandR, with the primary one first. Without XRandR we report the whole screen as a
+    /// single display. X11 has no per-monitor work area, so that's always the whole monitor. Returns whether
+    /// [`XlibApp::displays`] changed.
+    pub(crate) fn update_displays(&mut self) -> bool {
+        let mut displays = unsafe {
+            let root_window = X11_sys::XRootWindow(self.display, X11_sys::XDefaultScreen(self.display));
+            let monitors = if let Ok(xrandr) = XrandrLibs::get() { self.xrandr_monitors(xrandr, root_window) } else { vec![] };
+            if monitors.is_empty() {
+                let screen = X11_sys::XDefaultScreen(self.display);
+                vec![(
+                    String::new(),
+                    Rect {
+                        pos: Vec2::default(),
+                        size: vec2(
+                            X11_sys::XDisplayWidth(self.display, screen) as f32,
+                            X11_sys::XDisplayHeight(self.display, screen) as f32,
+                        ),
+                    },
+                    0.,
+                    true,
+                )]
+            } else {
+                monitors
+            }
+        }
+        .into_iter()
+        .map(|(name, rect, refresh_rate, is_primary)| {
+            let size = rect.size / self.dpi_factor;
+            DisplayInfo {
+                display_id: 0,
+                name,
+                position: rect.pos / self.dpi_factor,
+                size,
+                work_area: Rect { pos: Vec2::default(), size },
+                dpi_factor: self.dpi_factor,
+                refresh_rate,
+                is_primary,
+            }
+        })
+        .collect::<Vec<_>>();
+        // There can be no primary monitor at all, in which case we pick the first one.
+        if !displays.iter().any(|display| display.is_primary) {
+            displays[0].is_primary = true;
+        }
+        displays.sort_by_key(|display| !display.is_primary);
+        for (display_id, display) in displays.iter_mut().enumerate() {
+            display.display_id = display_id;
+        }
+        if displays != self.displays {
+            self.displays = displays;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// The name, rectangle in physical pixels, refresh rate and whether it's the primary one, for every monitor.
+    unsafe fn xrandr_monitors(&self, xrandr: &XrandrLibs, root_window: c_ulong) -> Vec<(String, Rect, f32, bool)> {
+        // The refresh rate is in the mode of the CRTC that drives the monitor, which we find by its position.
+        let mut crtc_refresh_rates: Vec<(c_int, c_int, f32)> = vec![];
+        let resources = (xrandr.XRRGetScreenResourcesCurrent)(self.display, root_window);
+        if !resources.is_null() {
+            let modes = slice::from_raw_parts((*resources).modes, (*resources).nmode as usize);
+            for crtc in slice::from_raw_parts((*resources).crtcs, (*resources).ncrtc as usize) {
+                let crtc_info = (xrandr.XRRGetCrtcInfo)(self.display, resources, *crtc);
+                if crtc_info.is_null() {
+                    continue;
+                }
+                if let Some(mode) = modes.iter().find(|mode| mode.id == (*crtc_info).mode) {
+                    if mode.hTotal > 0 && mode.vTotal > 0 {
+                        let refresh_rate = mode.dotClock as f64 / (mode.hTotal as f64 * mode.vTotal as f64);
+                        crtc_refresh_rates.push(((*crtc_info).x, (*crtc_info).y, refresh_rate as f32));
+                    }
+                }
+                (xrandr.XRRFreeCrtcInfo)(crtc_info);
+            }
+            (xrandr.XRRFreeScreenResources)(resources);
+        }
+
+        let mut count = 0;
+        let monitors = (xrandr.XRRGetMonitors)(self.display, root_window, 1, &mut count);
+        if monitors.is_null() {
+            return vec![];
+        }
+        let result = slice::from_raw_parts(monitors, count.max(0) as usize)
+            .iter()
+            .map(|monitor| {
+                let atom_name = X11_sys::XGetAtomName(self.display, monitor.name);
+                let name = if atom_name.is_null() {
+                    String::new()
+                } else {
+                    let name = CStr::from_ptr(atom_name).to_string_lossy().into_owned();
+                    X11_sys::XFree(atom_name as *mut c_void);
+                    name
+                };
+                let refresh_rate = crtc_refresh_rates
+                    .iter()
+                    .find(|(x, y, _)| *x == monitor.x && *y == monitor.y)
+                    .map_or(0., |(_, _, refresh_rate)| *refresh_rate);
+                let rect = Rect {
+                    pos: vec2(monitor.x as f32, monitor.y as f32),
+                    size: vec2(monitor.width as f32, monitor.height as f32),
+                };
+                (name, rect, refresh_rate, monitor.primary != 0)
+            })
+            .collect();
+        (xrandr.XRRFreeMonitors)(monitors);
+        result
+    }
+
+    fn send_displays_changed_event(&mut self) {
+        if self.update_displays() {
+            let displays = self.displays.clone();
+            self.do_callback(&mut vec![Event::DisplaysChanged(DisplaysChangedEvent { displays })]);
         }
     }
 
@@ -243,6 +451,14 @@ impl XlibApp {
                     let mut event = mem::MaybeUninit::uninit();
                     X11_sys::XNextEvent(self.display, event.as_mut_ptr());
                     let mut event = event.assume_init();
+                    if let Some(tray) = &mut self.tray {
+                        if tray.owns_window(event.xany.window) {
+                            if let Some(command) = tray.handle_event(&event) {
+                                self.do_callback(&mut vec![Event::Command(command)]);
+                            }
+                            continue;
+                        }
+                    }
                     match event.type_ as u32 {
                         X11_sys::SelectionNotify => {
                             let selection = event.xselection;
@@ -365,13 +581,28 @@ impl XlibApp {
                         }
                         X11_sys::ConfigureNotify => {
                             let cfg = event.xconfigure;
-                            if let Some(window_ptr) = self.window_map.get(&cfg.window) {
+                            if cfg.window == X11_sys::XRootWindow(self.display, X11_sys::XDefaultScreen(self.display)) {
+                                self.send_displays_changed_event();
+                            } else if let Some(window_ptr) = self.window_map.get(&cfg.window) {
                                 let window = &mut (**window_ptr);
                                 if cfg.window == window.window.unwrap() {
                                     window.send_change_event();
                                 }
                             }
                         }
+                        X11_sys::PropertyNotify => {
+                            let property = event.xproperty;
+                            if property.atom == self.atom_resource_manager {
+                                let dpi_factor = self.read_dpi_factor();
+                                if dpi_factor != self.dpi_factor {
+                                    self.dpi_factor = dpi_factor;
+                                    for window_ptr in self.window_map.values().copied().collect::<Vec<_>>() {
+                                        (*window_ptr).send_change_event();
+                                    }
+                                    self.send_displays_changed_event();
+                                }
+                            }
+                        }
                         X11_sys::EnterNotify => {}
                         X11_sys::LeaveNotify => {
                             let crossing = event.xcrossing;
@@ -396,6 +627,20 @@ impl XlibApp {
                         X11_sys::MotionNotify => {
                             // mousemove
                             let motion = event.xmotion;
+                            // X11 has no way to keep the pointer in place, so move it back whenever it moves. The
+                            // `MotionNotify` that the warp causes has no delta, since it's back at the lock position.
+                            let last_root = if let Some((lock_x, lock_y)) = self.pointer_lock_position {
+                                if motion.x_root != lock_x || motion.y_root != lock_y {
+                                    X11_sys::XWarpPointer(self.display, 0, motion.root, 0, 0, 0, 0, lock_x, lock_y);
+                                }
+                                Some((lock_x, lock_y))
+                            } else {
+                                self.last_motion_root
+                            };
+                            self.last_motion_root = Some((motion.x_root, motion.y_root));
+                            let raw_delta = last_root
+                                .map(|(last_x, last_y)| (motion.x_root - last_x, motion.y_root - last_y))
+                                .filter(|&delta| delta != (0, 0));
                             if let Some(window_ptr) = self.window_map.get(&motion.window) {
                                 let window = &mut (**window_ptr);
                                 let mut x = motion.x;
@@ -419,6 +664,15 @@ impl XlibApp {
                                     y: y as f32 / window.last_window_geom.dpi_factor,
                                 };
 
+                                if let Some((dx, dy)) = raw_delta {
+                                    window.do_callback(&mut vec![Event::PointerRawMove(PointerRawMoveEvent {
+                                        window_id: window.window_id,
+                                        delta: vec2(dx as f32, dy as f32) / window.last_window_geom.dpi_factor,
+                                        modifiers: KeyModifiers::default(),
+                                        time: window.time_now(),
+                                    })]);
+                                }
+
                                 // query window for chrome
                                 let mut drag_query_events = vec![Event::WindowDragQuery(WindowDragQueryEvent {
                                     window_id: window.window_id,
@@ -430,7 +684,16 @@ impl XlibApp {
                                 //println!("{} {}", window.last_window_geom.inner_size.x, pos.x);
                                 window.send_pointer_hover_and_move(pos, KeyModifiers::default());
                                 let window_size = window.last_window_geom.inner_size;
-                                if pos.x >= 0.0 && pos.x < 10.0 && pos.y >= 0.0 && pos.y < 10.0 {
+                                let caption_nc_mode = match &drag_query_events[0] {
+                                    Event::WindowDragQuery(wd) => match &wd.response {
+                                        WindowDragQueryResponse::Caption => Some(_NET_WM_MOVERESIZE_MOVE),
+                                        _ => None,
+                                    },
+                                    _ => None,
+                                };
+                                if !window.style.resizable || window.style.borderless {
+                                    window.last_nc_mode = caption_nc_mode;
+                                } else if pos.x >= 0.0 && pos.x < 10.0 && pos.y >= 0.0 && pos.y < 10.0 {
                                     window.last_nc_mode = Some(_NET_WM_MOVERESIZE_SIZE_TOPLEFT);
                                     window.do_callback(&mut vec![Event::System(SystemEvent::WindowSetHoverCursor(
                                         MouseCursor::NwResize,
@@ -471,17 +734,7 @@ impl XlibApp {
                                         MouseCursor::SResize,
                                     ))]);
                                 } else {
-                                    match &drag_query_events[0] {
-                                        Event::WindowDragQuery(wd) => match &wd.response {
-                                            WindowDragQueryResponse::Caption => {
-                                                window.last_nc_mode = Some(_NET_WM_MOVERESIZE_MOVE);
-                                            }
-                                            _ => {
-                                                window.last_nc_mode = None;
-                                            }
-                                        },
-                                        _ => (),
-                                    }
+                                    window.last_nc_mode = caption_nc_mode;
                                 }
                             }
                         }
@@ -499,29 +752,22 @@ impl XlibApp {
                                 );
 
                                 if button.button >= 4 && button.button <= 7 {
-                                    let last_scroll_time = self.last_scroll_time;
-                                    self.last_scroll_time = time_now;
-                                    // completely arbitrary scroll acceleration curve.
-                                    let speed = 1200.0 * (0.2 - 2. * (self.last_scroll_time - last_scroll_time)).max(0.01);
+                                    // Buttons 4 to 7 are wheel steps up, down, left, and right. X11 doesn't tell us
+                                    // how many lines a step should scroll, or whether it came from a trackpad.
+                                    let step = DEFAULT_LINES_PER_WHEEL_STEP * SCROLL_LINE_HEIGHT;
+                                    let scroll = match button.button {
+                                        4 => Vec2 { x: 0., y: -step },
+                                        5 => Vec2 { x: 0., y: step },
+                                        6 => Vec2 { x: -step, y: 0. },
+                                        _ => Vec2 { x: step, y: 0. },
+                                    };
                                     self.do_callback(&mut vec![Event::PointerScroll(PointerScrollEvent {
                                         digit: 0,
                                         window_id: window.window_id,
-                                        scroll: Vec2 {
-                                            x: if button.button == 6 {
-                                                -speed as f32
-                                            } else if button.button == 7 {
-                                                speed as f32
-                                            } else {
-                                                0.
-                                            },
-                                            y: if button.button == 4 {
-                                                -speed as f32
-                                            } else if button.button == 5 {
-                                                speed as f32
-                                            } else {
-                                                0.
-                                            },
-                                        },
+                                        scroll,
+                                        pinch: 0.,
+                                        device: ScrollDevice::Wheel,
+                                        phase: ScrollPhase::Changed,
                                         abs: window.last_mouse_pos,
                                         rel: window.last_mouse_pos,
                                         rect: Rect::default(),
@@ -529,7 +775,7 @@ impl XlibApp {
                                         modifiers: self.xkeystate_to_modifiers(button.state),
                                         handled_x: false,
                                         handled_y: false,
-                                        time: self.last_scroll_time,
+                                        time: time_now,
                                     })])
                                 } else {
                                     // do all the 'nonclient' area messaging to the window manager
@@ -590,6 +836,18 @@ impl XlibApp {
                                 window.send_pointer_up(button.button as usize, self.xkeystate_to_modifiers(button.state))
                             }
                         }
+                        // Don't keep the pointer stuck when switching to another app. Ignore focus changes caused
+                        // by keyboard grabs, like from a window manager shortcut that we get back from.
+                        X11_sys::FocusOut
+                            if self.pointer_lock_position.is_some() && event.xfocus.mode == X11_sys::NotifyNormal as c_int =>
+                        {
+                            if let Some(window_ptr) = self.window_map.get(&event.xfocus.window) {
+                                let window = &mut (**window_ptr);
+                                self.set_pointer_lock(false);
+                                window
+                                    .do_callback(&mut vec![Event::PointerLockChanged(PointerLockChangedEvent { locked: false })]);
+                            }
+                        }
                         X11_sys::KeyPress => {
                             if let Some(window_ptr) = self.window_map.get(&event.xkey.window) {
                                 let window = &mut (**window_ptr);
@@ -804,13 +1062,21 @@ impl XlibApp {
     }
 
     pub(crate) fn post_signal(signal: Signal, status: StatusId) {
+        let mut signals = HashMap::new();
+        let mut set = BTreeSet::new();
+        set.insert(status);
+        signals.insert(signal, set);
+        XlibApp::send_event_from_any_thread(Event::Signal(SignalEvent { signals }));
+    }
+
+    pub(crate) fn send_event_from_any_thread(event: Event) {
         unsafe {
+            // No event loop (e.g. in tests), so there is no one to send the event to.
+            if GLOBAL_XLIB_APP.is_null() {
+                return;
+            }
             if let Ok(mut signals_locked) = (*GLOBAL_XLIB_APP).signals.lock() {
-                let mut signals = HashMap::new();
-                let mut set = BTreeSet::new();
-                set.insert(status);
-                signals.insert(signal, set);
-                signals_locked.push(Event::Signal(SignalEvent { signals }));
+                signals_locked.push(event);
                 //let mut f = unsafe { File::from_raw_fd((*GLOBAL_XLIB_APP).display_fd) };
                 //let _ = write!(&mut f, "\0");
                 // !TODO unblock the select!
@@ -843,13 +1109,39 @@ impl XlibApp {
         None
     }
 
+    /// Create a cursor from a [`CustomCursor`]. Like for [`XlibApp::load_first_cursor`], the caller has to free it.
+    fn load_custom_cursor(&self, custom_cursor: &CustomCursor) -> Option<c_ulong> {
+        unsafe {
+            let image = X11_sys::XcursorImageCreate(custom_cursor.width as c_int, custom_cursor.height as c_int);
+            if image.is_null() {
+                return None;
+            }
+            (*image).xhot = custom_cursor.hotspot.x as X11_sys::XcursorDim;
+            (*image).yhot = custom_cursor.hotspot.y as X11_sys::XcursorDim;
+            // Xcursor wants premultiplied ARGB.
+            let pixels = std::slice::from_raw_parts_mut((*image).pixels, custom_cursor.width * custom_cursor.height);
+            for (pixel, rgba) in pixels.iter_mut().zip(custom_cursor.rgba.chunks_exact(4)) {
+                let alpha = rgba[3] as u32;
+                let premultiply = |channel: u8| channel as u32 * alpha / 255;
+                *pixel = (alpha << 24) | (premultiply(rgba[0]) << 16) | (premultiply(rgba[1]) << 8) | premultiply(rgba[2]);
+            }
+            let cursor = X11_sys::XcursorImageLoadCursor(self.display, image);
+            X11_sys::XcursorImageDestroy(image);
+            if cursor != 0 {
+                Some(cursor)
+            } else {
+                None
+            }
+        }
+    }
+
     pub(crate) fn set_mouse_cursor(&mut self, cursor: MouseCursor) {
         if self.current_cursor != cursor {
             self.current_cursor = cursor.clone();
             let x11_cursor = match cursor {
-                MouseCursor::Hidden => {
-                    return;
-                }
+                // There's no hidden cursor, so use a transparent one.
+                MouseCursor::Hidden => self.load_custom_cursor(&CustomCursor::new(1, 1, vec![0; 4], Vec2::default())),
+                MouseCursor::Custom(custom_cursor) => self.load_custom_cursor(&custom_cursor),
                 MouseCursor::EResize => self.load_first_cursor(&[b"right_side\0"]),
                 MouseCursor::NResize => self.load_first_cursor(&[b"top_side\0"]),
                 MouseCursor::NeResize => self.load_first_cursor(&[b"top_right_corner\0"]),
@@ -874,6 +1166,8 @@ impl XlibApp {
                 MouseCursor::NwseResize => self.load_first_cursor(&[b"bd_double_arrow\0", b"size_bdiag\0"]),
                 MouseCursor::ColResize => self.load_first_cursor(&[b"split_h\0", b"h_double_arrow\0"]),
                 MouseCursor::RowResize => self.load_first_cursor(&[b"split_v\0", b"v_double_arrow\0"]),
+                MouseCursor::Grab => self.load_first_cursor(&[b"grab\0", b"openhand\0", b"hand1\0"]),
+                MouseCursor::Grabbing => self.load_first_cursor(&[b"grabbing\0", b"closedhand\0", b"fleur\0"]),
             };
             if let Some(x11_cursor) = x11_cursor {
                 unsafe {
@@ -888,6 +1182,44 @@ impl XlibApp {
         }
     }
 
+    /// Lock the pointer where it currently is, or release it. We grab the pointer so that it can't leave our windows
+    /// (with `owner_events` so our windows keep getting their events as usual), and move it back on every motion.
+    pub(crate) fn set_pointer_lock(&mut self, locked: bool) {
+        unsafe {
+            let root_window = X11_sys::XRootWindow(self.display, X11_sys::XDefaultScreen(self.display));
+            if locked {
+                let (mut root, mut child) = (0, 0);
+                let (mut root_x, mut root_y, mut x, mut y, mut mask) = (0, 0, 0, 0, 0);
+                X11_sys::XQueryPointer(
+                    self.display,
+                    root_window,
+                    &mut root,
+                    &mut child,
+                    &mut root_x,
+                    &mut root_y,
+                    &mut x,
+                    &mut y,
+                    &mut mask,
+                );
+                X11_sys::XGrabPointer(
+                    self.display,
+                    root_window,
+                    1,
+                    (X11_sys::ButtonPressMask | X11_sys::ButtonReleaseMask | X11_sys::PointerMotionMask) as c_uint,
+                    X11_sys::GrabModeAsync as c_int,
+                    X11_sys::GrabModeAsync as c_int,
+                    0,
+                    0,
+                    X11_sys::CurrentTime as c_ulong,
+                );
+                self.pointer_lock_position = Some((root_x, root_y));
+            } else if self.pointer_lock_position.take().is_some() {
+                X11_sys::XUngrabPointer(self.display, X11_sys::CurrentTime as c_ulong);
+            }
+            X11_sys::XFlush(self.display);
+        }
+    }
+
     fn xkeystate_to_modifiers(&self, state: c_uint) -> KeyModifiers {
         KeyModifiers {
             alt: state & X11_sys::Mod1Mask != 0,
@@ -902,140 +1234,15 @@ impl XlibApp {
         unsafe {
             X11_sys::XLookupString(key_event, ptr::null_mut(), 0, &mut keysym, ptr::null_mut());
         }
-        match keysym as u32 {
-            X11_sys::XK_a => KeyCode::KeyA,
-            X11_sys::XK_A => KeyCode::KeyA,
-            X11_sys::XK_b => KeyCode::KeyB,
-            X11_sys::XK_B => KeyCode::KeyB,
-            X11_sys::XK_c => KeyCode::KeyC,
-            X11_sys::XK_C => KeyCode::KeyC,
-            X11_sys::XK_d => KeyCode::KeyD,
-            X11_sys::XK_D => KeyCode::KeyD,
-            X11_sys::XK_e => KeyCode::KeyE,
-            X11_sys::XK_E => KeyCode::KeyE,
-            X11_sys::XK_f => KeyCode::KeyF,
-            X11_sys::XK_F => KeyCode::KeyF,
-            X11_sys::XK_g => KeyCode::KeyG,
-            X11_sys::XK_G => KeyCode::KeyG,
-            X11_sys::XK_h => KeyCode::KeyH,
-            X11_sys::XK_H => KeyCode::KeyH,
-            X11_sys::XK_i => KeyCode::KeyI,
-            X11_sys::XK_I => KeyCode::KeyI,
-            X11_sys::XK_j => KeyCode::KeyJ,
-            X11_sys::XK_J => KeyCode::KeyJ,
-            X11_sys::XK_k => KeyCode::KeyK,
-            X11_sys::XK_K => KeyCode::KeyK,
-            X11_sys::XK_l => KeyCode::KeyL,
-            X11_sys::XK_L => KeyCode::KeyL,
-            X11_sys::XK_m => KeyCode::KeyM,
-            X11_sys::XK_M => KeyCode::KeyM,
-            X11_sys::XK_n => KeyCode::KeyN,
-            X11_sys::XK_N => KeyCode::KeyN,
-            X11_sys::XK_o => KeyCode::KeyO,
-            X11_sys::XK_O => KeyCode::KeyO,
-            X11_sys::XK_p => KeyCode::KeyP,
-            X11_sys::XK_P => KeyCode::KeyP,
-            X11_sys::XK_q => KeyCode::KeyQ,
-            X11_sys::XK_Q => KeyCode::KeyQ,
-            X11_sys::XK_r => KeyCode::KeyR,
-            X11_sys::XK_R => KeyCode::KeyR,
-            X11_sys::XK_s => KeyCode::KeyS,
-            X11_sys::XK_S => KeyCode::KeyS,
-            X11_sys::XK_t => KeyCode::KeyT,
-            X11_sys::XK_T => KeyCode::KeyT,
-            X11_sys::XK_u => KeyCode::KeyU,
-            X11_sys::XK_U => KeyCode::KeyU,
-            X11_sys::XK_v => KeyCode::KeyV,
-            X11_sys::XK_V => KeyCode::KeyV,
-            X11_sys::XK_w => KeyCode::KeyW,
-            X11_sys::XK_W => KeyCode::KeyW,
-            X11_sys::XK_x => KeyCode::KeyX,
-            X11_sys::XK_X => KeyCode::KeyX,
-            X11_sys::XK_y => KeyCode::KeyY,
-            X11_sys::XK_Y => KeyCode::KeyY,
-            X11_sys::XK_z => KeyCode::KeyZ,
-            X11_sys::XK_Z => KeyCode::KeyZ,
-
-            X11_sys::XK_0 => KeyCode::Key0,
-            X11_sys::XK_1 => KeyCode::Key1,
-            X11_sys::XK_2 => KeyCode::Key2,
-            X11_sys::XK_3 => KeyCode::Key3,
-            X11_sys::XK_4 => KeyCode::Key4,
-            X11_sys::XK_5 => KeyCode::Key5,
-            X11_sys::XK_6 => KeyCode::Key6,
-            X11_sys::XK_7 => KeyCode::Key7,
-            X11_sys::XK_8 => KeyCode::Key8,
-            X11_sys::XK_9 => KeyCode::Key9,
-
-            X11_sys::XK_Alt_L => KeyCode::Alt,
-            X11_sys::XK_Alt_R => KeyCode::Alt,
-            X11_sys::XK_Meta_L => KeyCode::Logo,
-            X11_sys::XK_Meta_R => KeyCode::Logo,
-            X11_sys::XK_Shift_L => KeyCode::Shift,
-            X11_sys::XK_Shift_R => KeyCode::Shift,
-            X11_sys::XK_Control_L => KeyCode::Control,
-            X11_sys::XK_Control_R => KeyCode::Control,
-
-            X11_sys::XK_equal => KeyCode::Equals,
-            X11_sys::XK_minus => KeyCode::Minus,
-            X11_sys::XK_bracketright => KeyCode::RBracket,
-            X11_sys::XK_bracketleft => KeyCode::LBracket,
-            X11_sys::XK_Return => KeyCode::Return,
-            X11_sys::XK_grave => KeyCode::Backtick,
-            X11_sys::XK_semicolon => KeyCode::Semicolon,
-            X11_sys::XK_backslash => KeyCode::Backslash,
-            X11_sys::XK_comma => KeyCode::Comma,
-            X11_sys::XK_slash => KeyCode::Slash,
-            X11_sys::XK_period => KeyCode::Period,
-            X11_sys::XK_Tab => KeyCode::Tab,
-            X11_sys::XK_ISO_Left_Tab => KeyCode::Tab,
-            X11_sys::XK_space => KeyCode::Space,
-            X11_sys::XK_BackSpace => KeyCode::Backspace,
-            X11_sys::XK_Escape => KeyCode::Escape,
-            X11_sys::XK_Caps_Lock => KeyCode::Capslock,
-            X11_sys::XK_KP_Decimal => KeyCode::NumpadDecimal,
-            X11_sys::XK_KP_Multiply => KeyCode::NumpadMultiply,
-            X11_sys::XK_KP_Add => KeyCode::NumpadAdd,
-            X11_sys::XK_Num_Lock => KeyCode::Numlock,
-            X11_sys::XK_KP_Divide => KeyCode::NumpadDivide,
-            X11_sys::XK_KP_Enter => KeyCode::NumpadEnter,
-            X11_sys::XK_KP_Subtract => KeyCode::NumpadSubtract,
-            //keysim::XK_9 => KeyCode::NumpadEquals,
-            X11_sys::XK_KP_0 => KeyCode::Numpad0,
-            X11_sys::XK_KP_1 => KeyCode::Numpad1,
-            X11_sys::XK_KP_2 => KeyCode::Numpad2,
-            X11_sys::XK_KP_3 => KeyCode::Numpad3,
-            X11_sys::XK_KP_4 => KeyCode::Numpad4,
-            X11_sys::XK_KP_5 => KeyCode::Numpad5,
-            X11_sys::XK_KP_6 => KeyCode::Numpad6,
-            X11_sys::XK_KP_7 => KeyCode::Numpad7,
-            X11_sys::XK_KP_8 => KeyCode::Numpad8,
-            X11_sys::XK_KP_9 => KeyCode::Numpad9,
-
-            X11_sys::XK_F1 => KeyCode::F1,
-            X11_sys::XK_F2 => KeyCode::F2,
-            X11_sys::XK_F3 => KeyCode::F3,
-            X11_sys::XK_F4 => KeyCode::F4,
-            X11_sys::XK_F5 => KeyCode::F5,
-            X11_sys::XK_F6 => KeyCode::F6,
-            X11_sys::XK_F7 => KeyCode::F7,
-            X11_sys::XK_F8 => KeyCode::F8,
-            X11_sys::XK_F9 => KeyCode::F9,
-            X11_sys::XK_F10 => KeyCode::F10,
-            X11_sys::XK_F11 => KeyCode::F11,
-            X11_sys::XK_F12 => KeyCode::F12,
-
-            X11_sys::XK_Print => KeyCode::PrintScreen,
-            X11_sys::XK_Home => KeyCode::Home,
-            X11_sys::XK_Page_Up => KeyCode::PageUp,
-            X11_sys::XK_Delete => KeyCode::Delete,
-            X11_sys::XK_End => KeyCode::End,
-            X11_sys::XK_Page_Down => KeyCode::PageDown,
-            X11_sys::XK_Left => KeyCode::ArrowLeft,
-            X11_sys::XK_Right => KeyCode::ArrowRight,
-            X11_sys::XK_Down => KeyCode::ArrowDown,
-            X11_sys::XK_Up => KeyCode::ArrowUp,
-            _ => KeyCode::Unknown,
+        keysym_to_keycode(keysym)
+    }
+
+    /// Show a [`Tray`] icon, or remove it when passing `None`.
+    pub(crate) fn update_tray(&mut self, tray: Option<&Tray>, command_settings: &HashMap<CommandId, CxCommandSetting>) {
+        // Dropping the old `XlibTray` removes its icon.
+        self.tray = None;
+        if let Some(tray) = tray {
+            self.tray = XlibTray::new(self.display, tray, command_settings);
         }
     }
 
@@ -1055,10 +1262,149 @@ impl XlibApp {
     }
 }
 
+/// Convert an X11 keysym to a [`KeyCode`]. Also used for Wayland, since xkbcommon uses the same keysyms.
+pub(crate) fn keysym_to_keycode(keysym: c_ulong) -> KeyCode {
+    match keysym as u32 {
+        X11_sys::XK_a => KeyCode::KeyA,
+        X11_sys::XK_A => KeyCode::KeyA,
+        X11_sys::XK_b => KeyCode::KeyB,
+        X11_sys::XK_B => KeyCode::KeyB,
+        X11_sys::XK_c => KeyCode::KeyC,
+        X11_sys::XK_C => KeyCode::KeyC,
+        X11_sys::XK_d => KeyCode::KeyD,
+        X11_sys::XK_D => KeyCode::KeyD,
+        X11_sys::XK_e => KeyCode::KeyE,
+        X11_sys::XK_E => KeyCode::KeyE,
+        X11_sys::XK_f => KeyCode::KeyF,
+        X11_sys::XK_F => KeyCode::KeyF,
+        X11_sys::XK_g => KeyCode::KeyG,
+        X11_sys::XK_G => KeyCode::KeyG,
+        X11_sys::XK_h => KeyCode::KeyH,
+        X11_sys::XK_H => KeyCode::KeyH,
+        X11_sys::XK_i => KeyCode::KeyI,
+        X11_sys::XK_I => KeyCode::KeyI,
+        X11_sys::XK_j => KeyCode::KeyJ,
+        X11_sys::XK_J => KeyCode::KeyJ,
+        X11_sys::XK_k => KeyCode::KeyK,
+        X11_sys::XK_K => KeyCode::KeyK,
+        X11_sys::XK_l => KeyCode::KeyL,
+        X11_sys::XK_L => KeyCode::KeyL,
+        X11_sys::XK_m => KeyCode::KeyM,
+        X11_sys::XK_M => KeyCode::KeyM,
+        X11_sys::XK_n => KeyCode::KeyN,
+        X11_sys::XK_N => KeyCode::KeyN,
+        X11_sys::XK_o => KeyCode::KeyO,
+        X11_sys::XK_O => KeyCode::KeyO,
+        X11_sys::XK_p => KeyCode::KeyP,
+        X11_sys::XK_P => KeyCode::KeyP,
+        X11_sys::XK_q => KeyCode::KeyQ,
+        X11_sys::XK_Q => KeyCode::KeyQ,
+        X11_sys::XK_r => KeyCode::KeyR,
+        X11_sys::XK_R => KeyCode::KeyR,
+        X11_sys::XK_s => KeyCode::KeyS,
+        X11_sys::XK_S => KeyCode::KeyS,
+        X11_sys::XK_t => KeyCode::KeyT,
+        X11_sys::XK_T => KeyCode::KeyT,
+        X11_sys::XK_u => KeyCode::KeyU,
+        X11_sys::XK_U => KeyCode::KeyU,
+        X11_sys::XK_v => KeyCode::KeyV,
+        X11_sys::XK_V => KeyCode::KeyV,
+        X11_sys::XK_w => KeyCode::KeyW,
+        X11_sys::XK_W => KeyCode::KeyW,
+        X11_sys::XK_x => KeyCode::KeyX,
+        X11_sys::XK_X => KeyCode::KeyX,
+        X11_sys::XK_y => KeyCode::KeyY,
+        X11_sys::XK_Y => KeyCode::KeyY,
+        X11_sys::XK_z => KeyCode::KeyZ,
+        X11_sys::XK_Z => KeyCode::KeyZ,
+
+        X11_sys::XK_0 => KeyCode::Key0,
+        X11_sys::XK_1 => KeyCode::Key1,
+        X11_sys::XK_2 => KeyCode::Key2,
+        X11_sys::XK_3 => KeyCode::Key3,
+        X11_sys::XK_4 => KeyCode::Key4,
+        X11_sys::XK_5 => KeyCode::Key5,
+        X11_sys::XK_6 => KeyCode::Key6,
+        X11_sys::XK_7 => KeyCode::Key7,
+        X11_sys::XK_8 => KeyCode::Key8,
+        X11_sys::XK_9 => KeyCode::Key9,
+
+        X11_sys::XK_Alt_L => KeyCode::Alt,
+        X11_sys::XK_Alt_R => KeyCode::Alt,
+        X11_sys::XK_Meta_L => KeyCode::Logo,
+        X11_sys::XK_Meta_R => KeyCode::Logo,
+        X11_sys::XK_Shift_L => KeyCode::Shift,
+        X11_sys::XK_Shift_R => KeyCode::Shift,
+        X11_sys::XK_Control_L => KeyCode::Control,
+        X11_sys::XK_Control_R => KeyCode::Control,
+
+        X11_sys::XK_equal => KeyCode::Equals,
+        X11_sys::XK_minus => KeyCode::Minus,
+        X11_sys::XK_bracketright => KeyCode::RBracket,
+        X11_sys::XK_bracketleft => KeyCode::LBracket,
+        X11_sys::XK_Return => KeyCode::Return,
+        X11_sys::XK_grave => KeyCode::Backtick,
+        X11_sys::XK_semicolon => KeyCode::Semicolon,
+        X11_sys::XK_backslash => KeyCode::Backslash,
+        X11_sys::XK_comma => KeyCode::Comma,
+        X11_sys::XK_slash => KeyCode::Slash,
+        X11_sys::XK_period => KeyCode::Period,
+        X11_sys::XK_Tab => KeyCode::Tab,
+        X11_sys::XK_ISO_Left_Tab => KeyCode::Tab,
+        X11_sys::XK_space => KeyCode::Space,
+        X11_sys::XK_BackSpace => KeyCode::Backspace,
+        X11_sys::XK_Escape => KeyCode::Escape,
+        X11_sys::XK_Caps_Lock => KeyCode::Capslock,
+        X11_sys::XK_KP_Decimal => KeyCode::NumpadDecimal,
+        X11_sys::XK_KP_Multiply => KeyCode::NumpadMultiply,
+        X11_sys::XK_KP_Add => KeyCode::NumpadAdd,
+        X11_sys::XK_Num_Lock => KeyCode::Numlock,
+        X11_sys::XK_KP_Divide => KeyCode::NumpadDivide,
+        X11_sys::XK_KP_Enter => KeyCode::NumpadEnter,
+        X11_sys::XK_KP_Subtract => KeyCode::NumpadSubtract,
+        //keysim::XK_9 => KeyCode::NumpadEquals,
+        X11_sys::XK_KP_0 => KeyCode::Numpad0,
+        X11_sys::XK_KP_1 => KeyCode::Numpad1,
+        X11_sys::XK_KP_2 => KeyCode::Numpad2,
+        X11_sys::XK_KP_3 => KeyCode::Numpad3,
+        X11_sys::XK_KP_4 => KeyCode::Numpad4,
+        X11_sys::XK_KP_5 => KeyCode::Numpad5,
+        X11_sys::XK_KP_6 => KeyCode::Numpad6,
+        X11_sys::XK_KP_7 => KeyCode::Numpad7,
+        X11_sys::XK_KP_8 => KeyCode::Numpad8,
+        X11_sys::XK_KP_9 => KeyCode::Numpad9,
+
+        X11_sys::XK_F1 => KeyCode::F1,
+        X11_sys::XK_F2 => KeyCode::F2,
+        X11_sys::XK_F3 => KeyCode::F3,
+        X11_sys::XK_F4 => KeyCode::F4,
+        X11_sys::XK_F5 => KeyCode::F5,
+        X11_sys::XK_F6 => KeyCode::F6,
+        X11_sys::XK_F7 => KeyCode::F7,
+        X11_sys::XK_F8 => KeyCode::F8,
+        X11_sys::XK_F9 => KeyCode::F9,
+        X11_sys::XK_F10 => KeyCode::F10,
+        X11_sys::XK_F11 => KeyCode::F11,
+        X11_sys::XK_F12 => KeyCode::F12,
+
+        X11_sys::XK_Print => KeyCode::PrintScreen,
+        X11_sys::XK_Home => KeyCode::Home,
+        X11_sys::XK_Page_Up => KeyCode::PageUp,
+        X11_sys::XK_Delete => KeyCode::Delete,
+        X11_sys::XK_End => KeyCode::End,
+        X11_sys::XK_Page_Down => KeyCode::PageDown,
+        X11_sys::XK_Left => KeyCode::ArrowLeft,
+        X11_sys::XK_Right => KeyCode::ArrowRight,
+        X11_sys::XK_Down => KeyCode::ArrowDown,
+        X11_sys::XK_Up => KeyCode::ArrowUp,
+        _ => KeyCode::Unknown,
+    }
+}
+
 /// In x11, 1 is left, 3 is right, and 2 is middle click.
 /// Refer: <http://xahlee.info/linux/linux_x11_mouse_button_number.html>
 /// Double checked this using `xev` command in ubuntu.
-fn get_mouse_button_from_digit(digit: usize) -> MouseButton {
+pub(crate) fn get_mouse_button_from_digit(digit: usize) -> MouseButton {
     match digit {
         1 => MouseButton::Left,
         3 => MouseButton::Right,
@@ -1081,6 +1427,7 @@ impl XlibWindow {
             xlib_app,
             last_window_geom: WindowGeom::default(),
             time_start: xlib_app.time_start,
+            style: CxWindowStyle { resizable: true, ..CxWindowStyle::default() },
             last_nc_mode: None,
             ime_spot: Vec2::default(),
             current_cursor: MouseCursor::Default,
@@ -1283,7 +1630,8 @@ impl XlibWindow {
 
     pub(crate) fn set_mouse_cursor(&mut self, _cursor: MouseCursor) {}
 
-    fn restore_or_maximize(&self, add_remove: c_long) {
+    /// Add or remove up to two `_NET_WM_STATE` properties (pass 0 for `second` to only change one).
+    fn change_net_wm_state(&self, add_remove: c_long, first: X11_sys::Atom, second: X11_sys::Atom) {
         unsafe {
             let xlib_app = &(*self.xlib_app);
             let default_screen = X11_sys::XDefaultScreen(xlib_app.display);
@@ -1299,8 +1647,8 @@ impl XlibWindow {
                 data: {
                     let mut msg = mem::zeroed::<X11_sys::XClientMessageEvent__bindgen_ty_1>();
                     msg.l[0] = add_remove;
-                    msg.l[1] = xlib_app.atom_new_wm_state_maximized_horz as c_long;
-                    msg.l[2] = xlib_app.atom_new_wm_state_maximized_vert as c_long;
+                    msg.l[1] = first as c_long;
+                    msg.l[2] = second as c_long;
                     msg
                 },
             };
@@ -1315,11 +1663,123 @@ impl XlibWindow {
     }
 
     pub(crate) fn restore(&self) {
-        self.restore_or_maximize(_NET_WM_STATE_REMOVE);
+        let xlib_app = unsafe { &(*self.xlib_app) };
+        self.change_net_wm_state(
+            _NET_WM_STATE_REMOVE,
+            xlib_app.atom_new_wm_state_maximized_horz,
+            xlib_app.atom_new_wm_state_maximized_vert,
+        );
     }
 
     pub(crate) fn maximize(&self) {
-        self.restore_or_maximize(_NET_WM_STATE_ADD);
+        let xlib_app = unsafe { &(*self.xlib_app) };
+        self.change_net_wm_state(
+            _NET_WM_STATE_ADD,
+            xlib_app.atom_new_wm_state_maximized_horz,
+            xlib_app.atom_new_wm_state_maximized_vert,
+        );
+    }
+
+    pub(crate) fn set_fullscreen(&self, fullscreen: bool) {
+        let xlib_app = unsafe { &(*self.xlib_app) };
+        let add_remove = if fullscreen { _NET_WM_STATE_ADD } else { _NET_WM_STATE_REMOVE };
+        self.change_net_wm_state(add_remove, xlib_app.atom_net_wm_state_fullscreen, 0);
+    }
+
+    pub(crate) fn set_title(&self, title: &str) {
+        unsafe {
+            let xlib_app = &(*self.xlib_app);
+            let window = self.window.unwrap();
+            // `XStoreName` only supports Latin-1, so also set `_NET_WM_NAME`, which most window managers prefer.
+            let title_bytes = format!("{}\0", title);
+            X11_sys::XStoreName(xlib_app.display, window, title_bytes.as_bytes().as_ptr() as *const c_char);
+            X11_sys::XChangeProperty(
+                xlib_app.display,
+                window,
+                xlib_app.atom_net_wm_name,
+                xlib_app.atom_utf8_string,
+                8,
+                X11_sys::PropModeReplace as i32,
+                title.as_ptr(),
+                title.len() as i32,
+            );
+            X11_sys::XFlush(xlib_app.display);
+        }
+    }
+
+    pub(crate) fn set_icon(&self, icon: &WindowIcon) {
+        // `_NET_WM_ICON` is a list of longs: width, height, and then ARGB pixels.
+        let mut data: Vec<c_ulong> = Vec::with_capacity(2 + icon.width * icon.height);
+        data.push(icon.width as c_ulong);
+        data.push(icon.height as c_ulong);
+        data.extend(icon.rgba.chunks_exact(4).map(|rgba| {
+            ((rgba[3] as c_ulong) << 24) | ((rgba[0] as c_ulong) << 16) | ((rgba[1] as c_ulong) << 8) | (rgba[2] as c_ulong)
+        }));
+        unsafe {
+            let xlib_app = &(*self.xlib_app);
+            X11_sys::XChangeProperty(
+                xlib_app.display,
+                self.window.unwrap(),
+                xlib_app.atom_net_wm_icon,
+                xlib_app.atom_cardinal,
+                32,
+                X11_sys::PropModeReplace as i32,
+                data.as_ptr() as *const u8,
+                data.len() as i32,
+            );
+            X11_sys::XFlush(xlib_app.display);
+        }
+    }
+
+    pub(crate) fn set_style(&mut self, style: &CxWindowStyle) {
+        self.style = style.clone();
+        unsafe {
+            let xlib_app = &(*self.xlib_app);
+            let window = self.window.unwrap();
+
+            // Window managers don't have a notion of resizability, so we fix the size instead.
+            let (min_size, max_size) = if style.resizable {
+                (style.min_size, style.max_size)
+            } else {
+                let inner_size = self.get_inner_size();
+                (Some(inner_size), Some(inner_size))
+            };
+            let dpi_factor = self.get_dpi_factor();
+            let mut size_hints = mem::zeroed::<X11_sys::XSizeHints>();
+            if let Some(min_size) = min_size {
+                size_hints.flags |= X11_sys::PMinSize as c_long;
+                size_hints.min_width = (min_size.x * dpi_factor) as c_int;
+                size_hints.min_height = (min_size.y * dpi_factor) as c_int;
+            }
+            if let Some(max_size) = max_size {
+                size_hints.flags |= X11_sys::PMaxSize as c_long;
+                size_hints.max_width = (max_size.x * dpi_factor) as c_int;
+                size_hints.max_height = (max_size.y * dpi_factor) as c_int;
+            }
+            X11_sys::XSetWMNormalHints(xlib_app.display, window, &mut size_hints);
+
+            // With custom window chrome we've already turned off all decorations in `init`.
+            if !LINUX_CUSTOM_WINDOW_CHROME {
+                let hints = MwmHints {
+                    flags: MWM_HINTS_DECORATIONS,
+                    functions: 0,
+                    decorations: if style.borderless { 0 } else { 1 },
+                    input_mode: 0,
+                    status: 0,
+                };
+                X11_sys::XChangeProperty(
+                    xlib_app.display,
+                    window,
+                    xlib_app.atom_motif_wm_hints,
+                    xlib_app.atom_motif_wm_hints,
+                    32,
+                    X11_sys::PropModeReplace as i32,
+                    &hints as *const _ as *const u8,
+                    5,
+                );
+            }
+            X11_sys::XFlush(xlib_app.display);
+        }
     }
 
     pub(crate) fn close_window(&mut self) {
@@ -1350,9 +1810,10 @@ impl XlibWindow {
         WindowGeom {
             xr_is_presenting: false,
             xr_can_present: false,
-            can_fullscreen: false,
+            can_fullscreen: true,
             is_topmost: self.get_is_topmost(),
-            is_fullscreen: self.get_is_maximized(),
+            is_fullscreen: self.get_is_fullscreen(),
+            is_maximized: self.get_is_maximized(),
             inner_size: self.get_inner_size(),
             outer_size: self.get_outer_size(),
             dpi_factor: self.get_dpi_factor(),
@@ -1361,7 +1822,18 @@ impl XlibWindow {
     }
 
     pub(crate) fn get_is_maximized(&self) -> bool {
-        let mut maximized = false;
+        let xlib_app = unsafe { &(*self.xlib_app) };
+        self.has_net_wm_state(&[xlib_app.atom_new_wm_state_maximized_horz, xlib_app.atom_new_wm_state_maximized_vert])
+    }
+
+    pub(crate) fn get_is_fullscreen(&self) -> bool {
+        let xlib_app = unsafe { &(*self.xlib_app) };
+        self.has_net_wm_state(&[xlib_app.atom_net_wm_state_fullscreen])
+    }
+
+    /// Whether any of `atoms` is in the `_NET_WM_STATE` of this window.
+    fn has_net_wm_state(&self, atoms: &[X11_sys::Atom]) -> bool {
+        let mut found = false;
         unsafe {
             let xlib_app = &(*self.xlib_app);
             let mut prop_type = mem::MaybeUninit::uninit();
@@ -1390,16 +1862,11 @@ impl XlibWindow {
             let properties = properties.assume_init();
             if result == 0 && !properties.is_null() {
                 let items = std::slice::from_raw_parts::<c_ulong>(properties as *mut _, n_item as usize);
-                for item in items {
-                    if *item == xlib_app.atom_new_wm_state_maximized_horz || *item == xlib_app.atom_new_wm_state_maximized_vert {
-                        maximized = true;
-                        break;
-                    }
-                }
+                found = items.iter().any(|item| atoms.contains(item));
                 X11_sys::XFree(properties as *mut _);
             }
         }
-        maximized
+        found
     }
 
     pub(crate) fn time_now(&self) -> f64 {
@@ -1452,39 +1919,43 @@ impl XlibWindow {
 
     pub(crate) fn set_position(&mut self, _pos: Vec2) {}
 
-    pub(crate) fn set_outer_size(&self, _size: Vec2) {}
-
-    pub(crate) fn set_inner_size(&self, _size: Vec2) {}
-
-    pub(crate) fn get_dpi_factor(&self) -> f32 {
+    /// Center the window on a display from [`XlibApp::displays`]. Fullscreen windows have to leave fullscreen for
+    /// the window manager to let us move them.
+    pub(crate) fn move_to_display(&mut self, display_id: usize) {
         unsafe {
-            //return 2.0;
-            let display = (*self.xlib_app).display;
-            let resource_string = X11_sys::XResourceManagerString(display);
-            if resource_string.is_null() {
-                return 1.0;
+            let xlib_app = &(*self.xlib_app);
+            let display = if let Some(display) = xlib_app.displays.get(display_id) {
+                display
+            } else {
+                return;
+            };
+            let is_fullscreen = self.get_is_fullscreen();
+            if is_fullscreen {
+                self.set_fullscreen(false);
             }
-            let db = X11_sys::XrmGetStringDatabase(resource_string);
-            let mut ty = mem::MaybeUninit::uninit();
-            let mut value = mem::MaybeUninit::uninit();
-            X11_sys::XrmGetResource(
-                db,
-                CString::new("Xft.dpi").unwrap().as_ptr(),
-                CString::new("String").unwrap().as_ptr(),
-                ty.as_mut_ptr(),
-                value.as_mut_ptr(),
+            let size = self.get_outer_size();
+            let center = (display.position + display.work_area.pos + display.work_area.size / 2.) * xlib_app.dpi_factor;
+            X11_sys::XMoveWindow(
+                xlib_app.display,
+                self.window.unwrap(),
+                (center.x - size.x / 2.).max(display.position.x * xlib_app.dpi_factor) as c_int,
+                (center.y - size.y / 2.).max(display.position.y * xlib_app.dpi_factor) as c_int,
             );
-            //let ty = ty.assume_init();
-            let value = value.assume_init();
-            if value.addr.is_null() {
-                1.0 // TODO find some other way to figure it out
-            } else {
-                let dpi: f32 = CStr::from_ptr(value.addr).to_str().unwrap().parse().unwrap();
-                dpi / 96.0
+            if is_fullscreen {
+                self.set_fullscreen(true);
             }
+            X11_sys::XFlush(xlib_app.display);
         }
     }
 
+    pub(crate) fn set_outer_size(&self, _size: Vec2) {}
+
+    pub(crate) fn set_inner_size(&self, _size: Vec2) {}
+
+    pub(crate) fn get_dpi_factor(&self) -> f32 {
+        unsafe { (*self.xlib_app).dpi_factor }
+    }
+
     pub(crate) fn do_callback(&mut self, events: &mut Vec<Event>) {
         unsafe {
             (*self.xlib_app).do_callback(events);
@@ -1496,7 +1967,7 @@ impl XlibWindow {
         if new_geom.inner_size.x < self.last_window_geom.inner_size.x
             || new_geom.inner_size.y < self.last_window_geom.inner_size.y
         {
-            new_geom.is_fullscreen = false;
+            new_geom.is_maximized = false;
         }
         let old_geom = self.last_window_geom.clone();
         self.last_window_geom = new_geom.clone();
@@ -1919,3 +2390,502 @@ impl DndAtoms {
         }
     }
 }
+
+/// Height of a regular item in the tray menu, in pixels.
+const TRAY_MENU_ITEM_HEIGHT: i32 = 22;
+/// Height of a [`Menu::Line`] in the tray menu, in pixels.
+const TRAY_MENU_LINE_HEIGHT: i32 = 9;
+/// Horizontal padding around item names in the tray menu, in pixels.
+const TRAY_MENU_PADDING: i32 = 12;
+
+const SYSTEM_TRAY_REQUEST_DOCK: c_long = 0;
+const XEMBED_MAPPED: c_ulong = 1 << 0;
+
+/// An item in the tray menu. [`Menu::Sub`] gets flattened into a disabled header followed by its items.
+enum XlibTrayItem {
+    Item { name: Vec<u8>, command: Option<CommandId>, enabled: bool },
+    Line,
+}
+
+struct XlibTrayMenu {
+    window: c_ulong,
+    width: i32,
+    height: i32,
+    hovered: Option<usize>,
+}
+
+/// An icon in the system tray, using the XEmbed system tray protocol; see [`Tray`].
+///
+/// Since we don't have a toolkit, the menu is a plain override-redirect window that we draw using the core X font
+/// functions. For simplicity we assume a TrueColor visual (which is what you'll find pretty much everywhere), so that
+/// pixel values are just RGB.
+pub(crate) struct XlibTray {
+    display: *mut X11_sys::Display,
+    icon_window: c_ulong,
+    icon: WindowIcon,
+    items: Vec<XlibTrayItem>,
+    menu: Option<XlibTrayMenu>,
+    font: *mut X11_sys::XFontStruct,
+    gc: X11_sys::GC,
+}
+
+impl XlibTray {
+    /// Dock a new icon into the system tray. Returns `None` if there is no system tray.
+    pub(crate) fn new(
+        display: *mut X11_sys::Display,
+        tray: &Tray,
+        command_settings: &HashMap<CommandId, CxCommandSetting>,
+    ) -> Option<XlibTray> {
+        unsafe {
+            let screen = X11_sys::XDefaultScreen(display);
+            let atom_tray_selection =
+                X11_sys::XInternAtom(display, CString::new(format!("_NET_SYSTEM_TRAY_S{}", screen)).unwrap().as_ptr(), 0);
+            let tray_owner = X11_sys::XGetSelectionOwner(display, atom_tray_selection);
+            if tray_owner == 0 {
+                log!("No system tray found, so not showing tray icon");
+                return None;
+            }
+
+            let root_window = X11_sys::XRootWindow(display, screen);
+            let icon_window = X11_sys::XCreateSimpleWindow(display, root_window, 0, 0, 22, 22, 0, 0, 0);
+            // Show the tray's background behind transparent pixels.
+            X11_sys::XSetWindowBackgroundPixmap(display, icon_window, X11_sys::ParentRelative as c_ulong);
+            X11_sys::XSelectInput(
+                display,
+                icon_window,
+                (X11_sys::ExposureMask | X11_sys::ButtonPressMask | X11_sys::StructureNotifyMask) as c_long,
+            );
+
+            let atom_xembed_info = X11_sys::XInternAtom(display, CString::new("_XEMBED_INFO").unwrap().as_ptr(), 0);
+            let xembed_info: [c_ulong; 2] = [0, XEMBED_MAPPED];
+            X11_sys::XChangeProperty(
+                display,
+                icon_window,
+                atom_xembed_info,
+                atom_xembed_info,
+                32,
+                X11_sys::PropModeReplace as i32,
+                xembed_info.as_ptr() as *const u8,
+                2,
+            );
+
+            let mut dock_request = X11_sys::XClientMessageEvent {
+                type_: X11_sys::ClientMessage as i32,
+                serial: 0,
+                send_event: 0,
+                display,
+                window: tray_owner,
+                message_type: X11_sys::XInternAtom(display, CString::new("_NET_SYSTEM_TRAY_OPCODE").unwrap().as_ptr(), 0),
+                format: 32,
+                data: {
+                    let mut msg = mem::zeroed::<X11_sys::XClientMessageEvent__bindgen_ty_1>();
+                    msg.l[0] = X11_sys::CurrentTime as c_long;
+                    msg.l[1] = SYSTEM_TRAY_REQUEST_DOCK;
+                    msg.l[2] = icon_window as c_long;
+                    msg
+                },
+            };
+            X11_sys::XSendEvent(
+                display,
+                tray_owner,
+                0,
+                X11_sys::NoEventMask as c_long,
+                &mut dock_request as *mut _ as *mut X11_sys::XEvent,
+            );
+
+            let font = X11_sys::XLoadQueryFont(display, CString::new("fixed").unwrap().as_ptr());
+            let gc = X11_sys::XCreateGC(display, icon_window, 0, ptr::null_mut());
+            if !font.is_null() {
+                X11_sys::XSetFont(display, gc, (*font).fid);
+            }
+            X11_sys::XFlush(display);
+
+            let mut items = Vec::new();
+            Self::add_items(&mut items, &tray.items, command_settings, 0);
+            Some(XlibTray { display, icon_window, icon: tray.icon.clone(), items, menu: None, font, gc })
+        }
+    }
+
+    fn add_items(
+        items: &mut Vec<XlibTrayItem>,
+        menu_items: &[Menu],
+        command_settings: &HashMap<CommandId, CxCommandSetting>,
+        depth: usize,
+    ) {
+        // Core X fonts are Latin-1, so replace anything else.
+        let to_latin1 = |name: &str| -> Vec<u8> {
+            let indent = "  ".repeat(depth);
+            indent.chars().chain(name.chars()).map(|c| if (c as u32) < 256 { c as u8 } else { b'?' }).collect()
+        };
+        for menu_item in menu_items {
+            match menu_item {
+                Menu::Item { name, command } => items.push(XlibTrayItem::Item {
+                    name: to_latin1(name),
+                    command: Some(*command),
+                    enabled: matches!(command_settings.get(command), Some(settings) if settings.enabled),
+                }),
+                Menu::Sub { name, items: sub_items } => {
+                    items.push(XlibTrayItem::Item { name: to_latin1(name), command: None, enabled: false });
+                    Self::add_items(items, sub_items, command_settings, depth + 1);
+                }
+                Menu::Main { items: sub_items } => Self::add_items(items, sub_items, command_settings, depth),
+                Menu::Line => items.push(XlibTrayItem::Line),
+            }
+        }
+    }
+
+    /// Whether an event is for one of our windows, in which case it should be passed to [`XlibTray::handle_event`].
+    pub(crate) fn owns_window(&self, window: c_ulong) -> bool {
+        window == self.icon_window || self.menu.as_ref().map(|menu| menu.window) == Some(window)
+    }
+
+    /// Handle an event for one of our windows. Returns a command if the user picked a menu item.
+    pub(crate) fn handle_event(&mut self, event: &X11_sys::XEvent) -> Option<CommandId> {
+        unsafe {
+            let window = event.xany.window;
+            match event.type_ as u32 {
+                X11_sys::Expose if window == self.icon_window => self.draw_icon(),
+                X11_sys::Expose => self.draw_menu(),
+                X11_sys::ButtonPress if window == self.icon_window => {
+                    if self.menu.is_some() {
+                        self.close_menu();
+                    } else {
+                        self.open_menu(event.xbutton.x_root, event.xbutton.y_root);
+                    }
+                }
+                // While the menu is open we grab the pointer, so we get all clicks, relative to the menu window.
+                X11_sys::ButtonPress => {
+                    if let Some(menu) = &self.menu {
+                        let (x, y) = (event.xbutton.x, event.xbutton.y);
+                        if x < 0 || y < 0 || x >= menu.width || y >= menu.height {
+                            self.close_menu();
+                        }
+                    }
+                }
+                X11_sys::MotionNotify => {
+                    let hovered = self.item_at(event.xmotion.x, event.xmotion.y);
+                    if let Some(menu) = &mut self.menu {
+                        if menu.hovered != hovered {
+                            menu.hovered = hovered;
+                            self.draw_menu();
+                        }
+                    }
+                }
+                // Also activate items on release, so that users can press on the icon and release on an item.
+                X11_sys::ButtonRelease => {
+                    if let Some(index) = self.item_at(event.xbutton.x, event.xbutton.y) {
+                        if let XlibTrayItem::Item { command: Some(command), .. } = self.items[index] {
+                            self.close_menu();
+                            return Some(command);
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        None
+    }
+
+    fn text_width(&self, text: &[u8]) -> i32 {
+        if self.font.is_null() {
+            // The "fixed" font is 6 pixels wide, so this should rarely be wrong.
+            6 * text.len() as i32
+        } else {
+            unsafe { X11_sys::XTextWidth(self.font, text.as_ptr() as *const c_char, text.len() as c_int) }
+        }
+    }
+
+    fn item_height(item: &XlibTrayItem) -> i32 {
+        match item {
+            XlibTrayItem::Item { .. } => TRAY_MENU_ITEM_HEIGHT,
+            XlibTrayItem::Line => TRAY_MENU_LINE_HEIGHT,
+        }
+    }
+
+    /// The index of the enabled item at a position relative to the menu window, if any.
+    fn item_at(&self, x: i32, y: i32) -> Option<usize> {
+        let menu = self.menu.as_ref()?;
+        if x < 0 || x >= menu.width {
+            return None;
+        }
+        let mut item_y = 0;
+        for (index, item) in self.items.iter().enumerate() {
+            let item_height = Self::item_height(item);
+            if y >= item_y && y < item_y + item_height {
+                return match item {
+                    XlibTrayItem::Item { enabled: true, .. } => Some(index),
+                    _ => None,
+                };
+            }
+            item_y += item_height;
+        }
+        None
+    }
+
+    unsafe fn open_menu(&mut self, x_root: i32, y_root: i32) {
+        if self.items.is_empty() {
+            return;
+        }
+        let width = self
+            .items
+            .iter()
+            .map(|item| match item {
+                XlibTrayItem::Item { name, .. } => self.text_width(name),
+                XlibTrayItem::Line => 0,
+            })
+            .max()
+            .unwrap_or(0)
+            + 2 * TRAY_MENU_PADDING;
+        let height: i32 = self.items.iter().map(Self::item_height).sum();
+
+        // Open the menu next to the pointer, while keeping it on screen; trays are often at the bottom or right.
+        let screen = X11_sys::XDefaultScreen(self.display);
+        let screen_width = X11_sys::XDisplayWidth(self.display, screen);
+        let screen_height = X11_sys::XDisplayHeight(self.display, screen);
+        let x = if x_root + width > screen_width { (x_root - width).max(0) } else { x_root };
+        let y = if y_root + height > screen_height { (y_root - height).max(0) } else { y_root };
+
+        let root_window = X11_sys::XRootWindow(self.display, screen);
+        let window = X11_sys::XCreateSimpleWindow(
+            self.display,
+            root_window,
+            x,
+            y,
+            width as c_uint,
+            height as c_uint,
+            1,
+            0x808080,
+            0xf0f0f0,
+        );
+        let mut attributes = mem::zeroed::<X11_sys::XSetWindowAttributes>();
+        attributes.override_redirect = 1;
+        X11_sys::XChangeWindowAttributes(self.display, window, X11_sys::CWOverrideRedirect as c_ulong, &mut attributes);
+        X11_sys::XSelectInput(
+            self.display,
+            window,
+            (X11_sys::ExposureMask | X11_sys::ButtonPressMask | X11_sys::ButtonReleaseMask | X11_sys::PointerMotionMask)
+                as c_long,
+        );
+        X11_sys::XMapRaised(self.display, window);
+        // The window has to be viewable before we can grab the pointer.
+        X11_sys::XSync(self.display, 0);
+        X11_sys::XGrabPointer(
+            self.display,
+            window,
+            0,
+            (X11_sys::ButtonPressMask | X11_sys::ButtonReleaseMask | X11_sys::PointerMotionMask) as c_uint,
+            X11_sys::GrabModeAsync as c_int,
+            X11_sys::GrabModeAsync as c_int,
+            0,
+            0,
+            X11_sys::CurrentTime as c_ulong,
+        );
+        self.menu = Some(XlibTrayMenu { window, width, height, hovered: None });
+    }
+
+    unsafe fn close_menu(&mut self) {
+        if let Some(menu) = self.menu.take() {
+            X11_sys::XUngrabPointer(self.display, X11_sys::CurrentTime as c_ulong);
+            X11_sys::XDestroyWindow(self.display, menu.window);
+            X11_sys::XFlush(self.display);
+        }
+    }
+
+    unsafe fn draw_menu(&self) {
+        let menu = if let Some(menu) = &self.menu { menu } else { return };
+        let (ascent, descent) = if self.font.is_null() { (10, 3) } else { ((*self.font).ascent, (*self.font).descent) };
+        X11_sys::XClearWindow(self.display, menu.window);
+        let mut y = 0;
+        for (index, item) in self.items.iter().enumerate() {
+            match item {
+                XlibTrayItem::Item { name, enabled, .. } => {
+                    let text_color = if menu.hovered == Some(index) {
+                        X11_sys::XSetForeground(self.display, self.gc, 0x3070d0);
+                        X11_sys::XFillRectangle(
+                            self.display,
+                            menu.window,
+                            self.gc,
+                            0,
+                            y,
+                            menu.width as c_uint,
+                            TRAY_MENU_ITEM_HEIGHT as c_uint,
+                        );
+                        0xffffff
+                    } else if *enabled {
+                        0x000000
+                    } else {
+                        0x909090
+                    };
+                    X11_sys::XSetForeground(self.display, self.gc, text_color);
+                    X11_sys::XDrawString(
+                        self.display,
+                        menu.window,
+                        self.gc,
+                        TRAY_MENU_PADDING,
+                        y + (TRAY_MENU_ITEM_HEIGHT + ascent - descent) / 2,
+                        name.as_ptr() as *const c_char,
+                        name.len() as c_int,
+                    );
+                }
+                XlibTrayItem::Line => {
+                    X11_sys::XSetForeground(self.display, self.gc, 0xc0c0c0);
+                    X11_sys::XFillRectangle(
+                        self.display,
+                        menu.window,
+                        self.gc,
+                        4,
+                        y + TRAY_MENU_LINE_HEIGHT / 2,
+                        (menu.width - 8).max(0) as c_uint,
+                        1,
+                    );
+                }
+            }
+            y += Self::item_height(item);
+        }
+        X11_sys::XFlush(self.display);
+    }
+
+    /// Draw the icon scaled to the size that the tray gave us, skipping mostly transparent pixels.
+    unsafe fn draw_icon(&self) {
+        let mut attributes = mem::MaybeUninit::uninit();
+        X11_sys::XGetWindowAttributes(self.display, self.icon_window, attributes.as_mut_ptr());
+        let attributes = attributes.assume_init();
+        let (width, height) = (attributes.width.max(1) as usize, attributes.height.max(1) as usize);
+        X11_sys::XClearWindow(self.display, self.icon_window);
+        if self.icon.width == 0 || self.icon.height == 0 || self.icon.rgba.len() < self.icon.width * self.icon.height * 4 {
+            return;
+        }
+        for y in 0..height {
+            let src_y = y * self.icon.height / height;
+            for x in 0..width {
+                let src_x = x * self.icon.width / width;
+                let rgba = &self.icon.rgba[(src_y * self.icon.width + src_x) * 4..][..4];
+                if rgba[3] >= 128 {
+                    let pixel = ((rgba[0] as c_ulong) << 16) | ((rgba[1] as c_ulong) << 8) | (rgba[2] as c_ulong);
+                    X11_sys::XSetForeground(self.display, self.gc, pixel);
+                    X11_sys::XFillRectangle(self.display, self.icon_window, self.gc, x as c_int, y as c_int, 1, 1);
+                }
+            }
+        }
+        X11_sys::XFlush(self.display);
+    }
+}
+
+impl Drop for XlibTray {
+    fn drop(&mut self) {
+        unsafe {
+            self.close_menu();
+            X11_sys::XDestroyWindow(self.display, self.icon_window);
+            X11_sys::XFreeGC(self.display, self.gc);
+            if !self.font.is_null() {
+                X11_sys::XFreeFont(self.display, self.font);
+            }
+            X11_sys::XFlush(self.display);
+        }
+    }
+}
+
+#[repr(C)]
+#[allow(non_snake_case, dead_code)]
+struct XRRMonitorInfo {
+    name: X11_sys::Atom,
+    primary: c_int,
+    automatic: c_int,
+    noutput: c_int,
+    x: c_int,
+    y: c_int,
+    width: c_int,
+    height: c_int,
+    mwidth: c_int,
+    mheight: c_int,
+    outputs: *mut c_ulong,
+}
+
+#[repr(C)]
+#[allow(non_snake_case, dead_code)]
+struct XRRModeInfo {
+    id: c_ulong,
+    width: c_uint,
+    height: c_uint,
+    dotClock: c_ulong,
+    hSyncStart: c_uint,
+    hSyncEnd: c_uint,
+    hTotal: c_uint,
+    hSkew: c_uint,
+    vSyncStart: c_uint,
+    vSyncEnd: c_uint,
+    vTotal: c_uint,
+    name: *mut c_char,
+    nameLength: c_uint,
+    modeFlags: c_ulong,
+}
+
+#[repr(C)]
+#[allow(non_snake_case, dead_code)]
+struct XRRScreenResources {
+    timestamp: c_ulong,
+    configTimestamp: c_ulong,
+    ncrtc: c_int,
+    crtcs: *mut c_ulong,
+    noutput: c_int,
+    outputs: *mut c_ulong,
+    nmode: c_int,
+    modes: *mut XRRModeInfo,
+}
+
+/// Only the start of `XRRCrtcInfo`, since we never allocate it ourselves.
+#[repr(C)]
+#[allow(dead_code)]
+struct XRRCrtcInfo {
+    timestamp: c_ulong,
+    x: c_int,
+    y: c_int,
+    width: c_uint,
+    height: c_uint,
+    mode: c_ulong,
+}
+
+/// Functions that we use from `libXrandr`, which we load at runtime using `dlopen`, since we only need it for
+/// [`XlibApp::update_displays`].
+#[allow(non_snake_case)]
+struct XrandrLibs {
+    XRRGetMonitors: unsafe extern "C" fn(*mut X11_sys::Display, c_ulong, c_int, *mut c_int) -> *mut XRRMonitorInfo,
+    XRRFreeMonitors: unsafe extern "C" fn(*mut XRRMonitorInfo),
+    XRRGetScreenResourcesCurrent: unsafe extern "C" fn(*mut X11_sys::Display, c_ulong) -> *mut XRRScreenResources,
+    XRRFreeScreenResources: unsafe extern "C" fn(*mut XRRScreenResources),
+    XRRGetCrtcInfo: unsafe extern "C" fn(*mut X11_sys::Display, *mut XRRScreenResources, c_ulong) -> *mut XRRCrtcInfo,
+    XRRFreeCrtcInfo: unsafe extern "C" fn(*mut XRRCrtcInfo),
+}
+
+static XRANDR_LIBS: once_cell::sync::OnceCell<Result<XrandrLibs, String>> = once_cell::sync::OnceCell::new();
+
+impl XrandrLibs {
+    fn get() -> Result<&'static XrandrLibs, String> {
+        XRANDR_LIBS.get_or_init(|| unsafe { Self::load() }).as_ref().map_err(Clone::clone)
+    }
+
+    unsafe fn load() -> Result<XrandrLibs, String> {
+        // Never closed, since we keep the function pointers around.
+        let lib = libc::dlopen("libXrandr.so.2\0".as_ptr() as *const c_char, libc::RTLD_NOW);
+        if lib.is_null() {
+            return Err("Could not load libXrandr.so.2".to_string());
+        }
+        macro_rules! sym {
+            ($name: ident) => {{
+                let ptr = libc::dlsym(lib, concat!(stringify!($name), "\0").as_ptr() as *const c_char);
+                if ptr.is_null() {
+                    return Err(format!("Could not find {}", stringify!($name)));
+                }
+                std::mem::transmute_copy(&ptr)
+            }};
+        }
+        Ok(XrandrLibs {
+            XRRGetMonitors: sym!(XRRGetMonitors),
+            XRRFreeMonitors: sym!(XRRFreeMonitors),
+            XRRGetScreenResourcesCurrent: sym!(XRRGetScreenResourcesCurrent),
+            XRRFreeScreenResources: sym!(XRRFreeScreenResources),
+            XRRGetCrtcInfo: sym!(XRRGetCrtcInfo),
+            XRRFreeCrtcInfo: sym!(XRRFreeCrtcInfo),
+        })
+    }
+}
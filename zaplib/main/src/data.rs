@@ -0,0 +1,852 @@
+//! Reading tabular data (CSV and Apache Arrow IPC) into columns that are ready for rendering.
+//!
+//! Numeric columns become [`Vec<f32>`], which you can use directly to fill instance data, and all other
+//! columns become [`Vec<String>`]. Missing values become [`f32::NAN`] and empty strings, respectively.
+//!
+//! Parsing large files takes a while, so use [`read_csv_async`] or [`read_arrow_async`] to do it on another
+//! thread (also in WebAssembly), and [`Cx::spawn`] to wait for the result:
+//!
+//! ```ignore
+//! let file = UniversalFile::open("data.csv")?;
+//! self.load_task = Some(cx.spawn(data::read_csv_async(file, CsvOptions::default())));
+//! ```
+//!
+//! Both readers stream through the data in chunks, so the raw file never has to be in memory all at once.
+
+use crate::*;
+use std::fmt;
+use std::io::Read;
+
+/// Why reading data failed.
+#[derive(Clone, Debug, PartialEq)]
+pub enum DataError {
+    /// Reading from the underlying [`Read`] failed.
+    Io(String),
+    /// The data is malformed, or uses features that we don't support.
+    InvalidData(String),
+}
+
+impl fmt::Display for DataError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DataError::Io(message) => write!(f, "Error while reading data: {}", message),
+            DataError::InvalidData(message) => write!(f, "Invalid data: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for DataError {}
+
+impl From<std::io::Error> for DataError {
+    fn from(err: std::io::Error) -> Self {
+        DataError::Io(err.to_string())
+    }
+}
+
+fn invalid_data<T>(message: impl Into<String>) -> Result<T, DataError> {
+    Err(DataError::InvalidData(message.into()))
+}
+
+/// Values of a [`DataColumn`].
+#[derive(Clone, Debug, PartialEq)]
+pub enum ColumnValues {
+    /// Numbers, with [`f32::NAN`] for missing values.
+    F32(Vec<f32>),
+    /// Anything else, with empty strings for missing values.
+    String(Vec<String>),
+}
+
+impl ColumnValues {
+    pub fn len(&self) -> usize {
+        match self {
+            ColumnValues::F32(values) => values.len(),
+            ColumnValues::String(values) => values.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Get the numbers, or [`None`] if this is a string column.
+    pub fn as_f32_slice(&self) -> Option<&[f32]> {
+        match self {
+            ColumnValues::F32(values) => Some(values),
+            ColumnValues::String(_) => None,
+        }
+    }
+
+    /// Get the strings, or [`None`] if this is a numeric column.
+    pub fn as_string_slice(&self) -> Option<&[String]> {
+        match self {
+            ColumnValues::F32(_) => None,
+            ColumnValues::String(values) => Some(values),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct DataColumn {
+    pub name: String,
+    pub values: ColumnValues,
+}
+
+/// A table of columns, which all have the same length.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DataTable {
+    pub columns: Vec<DataColumn>,
+}
+
+impl DataTable {
+    /// Number of rows.
+    pub fn len(&self) -> usize {
+        self.columns.first().map_or(0, |column| column.values.len())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Find a column by name.
+    pub fn column(&self, name: &str) -> Option<&ColumnValues> {
+        self.columns.iter().find(|column| column.name == name).map(|column| &column.values)
+    }
+}
+
+/// Read CSV on a new thread; see [`read_csv`].
+pub fn read_csv_async(reader: impl Read + Send + 'static, options: CsvOptions) -> ThreadFuture<Result<DataTable, DataError>> {
+    spawn_blocking(move || read_csv(reader, options))
+}
+
+/// Read an Arrow IPC file or stream on a new thread; see [`read_arrow`].
+pub fn read_arrow_async(reader: impl Read + Send + 'static) -> ThreadFuture<Result<DataTable, DataError>> {
+    spawn_blocking(move || read_arrow(reader))
+}
+
+/// Read data in chunks of this size.
+const CHUNK_SIZE: usize = 1 << 16;
+
+////////////////////////////////////////////////////////////////
+// CSV
+////////////////////////////////////////////////////////////////
+
+#[derive(Clone, Debug)]
+pub struct CsvOptions {
+    /// Character between fields. Defaults to `b','`; use `b'\t'` for TSV.
+    pub delimiter: u8,
+    /// Whether the first row contains column names. If not, columns are named `column_0`, `column_1`, etc.
+    pub has_header: bool,
+}
+
+impl Default for CsvOptions {
+    fn default() -> Self {
+        Self { delimiter: b',', has_header: true }
+    }
+}
+
+/// Builds a single column, which stays numeric for as long as all values parse as numbers.
+enum CsvColumnBuilder {
+    F32(Vec<f32>),
+    String(Vec<String>),
+}
+
+impl CsvColumnBuilder {
+    fn push(&mut self, field: &str) {
+        match self {
+            CsvColumnBuilder::F32(values) => {
+                let trimmed = field.trim();
+                if trimmed.is_empty() {
+                    values.push(f32::NAN);
+                } else if let Ok(value) = trimmed.parse() {
+                    values.push(value);
+                } else {
+                    // Not a numeric column after all. This loses the original formatting of earlier values
+                    // (e.g. "1.50" becomes "1.5"), but we don't want to keep all strings around just in case.
+                    let mut strings: Vec<String> =
+                        values.iter().map(|value| if value.is_nan() { String::new() } else { value.to_string() }).collect();
+                    strings.push(field.to_string());
+                    *self = CsvColumnBuilder::String(strings);
+                }
+            }
+            CsvColumnBuilder::String(values) => values.push(field.to_string()),
+        }
+    }
+
+    fn build(self) -> ColumnValues {
+        match self {
+            CsvColumnBuilder::F32(values) => ColumnValues::F32(values),
+            CsvColumnBuilder::String(values) => ColumnValues::String(values),
+        }
+    }
+}
+
+/// Read CSV data (RFC 4180: fields can be quoted using `"`, with `""` for a literal quote, and quoted fields
+/// can contain delimiters and newlines).
+///
+/// Columns where all non-empty values are numbers become [`ColumnValues::F32`]. Rows with fewer fields than
+/// there are columns get missing values; rows with more fields result in an error.
+pub fn read_csv(mut reader: impl Read, options: CsvOptions) -> Result<DataTable, DataError> {
+    let mut names: Option<Vec<String>> = None;
+    let mut columns: Vec<CsvColumnBuilder> = vec![];
+    let mut row_count = 0;
+    let mut line = 1;
+
+    let mut record: Vec<String> = vec![];
+    let mut field: Vec<u8> = vec![];
+    let mut in_quotes = false;
+    // Whether the previous byte was a quote inside a quoted field, which is either the closing quote or
+    // the first half of an escaped quote.
+    let mut quote_in_quotes = false;
+
+    let mut finish_record = |record: &mut Vec<String>, line: usize| -> Result<(), DataError> {
+        if record.len() == 1 && record[0].is_empty() {
+            // Skip empty lines.
+            record.clear();
+            return Ok(());
+        }
+        if names.is_none() {
+            let column_names = if options.has_header {
+                std::mem::take(record)
+            } else {
+                (0..record.len()).map(|i| format!("column_{}", i)).collect()
+            };
+            columns = column_names.iter().map(|_| CsvColumnBuilder::F32(vec![])).collect();
+            names = Some(column_names);
+            if options.has_header {
+                return Ok(());
+            }
+        }
+        if record.len() > columns.len() {
+            return invalid_data(format!(
+                "line {} has {} fields, but there are only {} columns",
+                line,
+                record.len(),
+                columns.len()
+            ));
+        }
+        for (i, column) in columns.iter_mut().enumerate() {
+            column.push(record.get(i).map_or("", |field| field.as_str()));
+        }
+        record.clear();
+        row_count += 1;
+        Ok(())
+    };
+    let finish_field = |record: &mut Vec<String>, field: &mut Vec<u8>, line: usize| -> Result<(), DataError> {
+        match String::from_utf8(std::mem::take(field)) {
+            Ok(string) => {
+                record.push(string);
+                Ok(())
+            }
+            Err(_) => invalid_data(format!("line {} is not valid UTF-8", line)),
+        }
+    };
+
+    let mut buf = vec![0; CHUNK_SIZE];
+    loop {
+        let bytes_read = match reader.read(&mut buf) {
+            Ok(0) => break,
+            Ok(bytes_read) => bytes_read,
+            Err(err) if err.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err.into()),
+        };
+        for &byte in &buf[..bytes_read] {
+            if in_quotes {
+                if quote_in_quotes {
+                    quote_in_quotes = false;
+                    if byte == b'"' {
+                        field.push(b'"');
+                        continue;
+                    }
+                    // That was the closing quote; handle this byte as if outside of quotes.
+                    in_quotes = false;
+                } else {
+                    if byte == b'"' {
+                        quote_in_quotes = true;
+                    } else {
+                        if byte == b'\n' {
+                            line += 1;
+                        }
+                        field.push(byte);
+                    }
+                    continue;
+                }
+            }
+            if byte == options.delimiter {
+                finish_field(&mut record, &mut field, line)?;
+            } else if byte == b'\n' {
+                finish_field(&mut record, &mut field, line)?;
+                finish_record(&mut record, line)?;
+                line += 1;
+            } else if byte == b'"' && field.is_empty() {
+                in_quotes = true;
+            } else if byte != b'\r' {
+                field.push(byte);
+            }
+        }
+    }
+    if in_quotes && !quote_in_quotes {
+        return invalid_data(format!("unterminated quoted field on line {}", line));
+    }
+    if !field.is_empty() || !record.is_empty() || quote_in_quotes {
+        finish_field(&mut record, &mut field, line)?;
+        finish_record(&mut record, line)?;
+    }
+
+    let table = DataTable {
+        columns: names
+            .unwrap_or_default()
+            .into_iter()
+            .zip(columns)
+            .map(|(name, column)| DataColumn { name, values: column.build() })
+            .collect(),
+    };
+    debug_assert!(table.columns.iter().all(|column| column.values.len() == row_count));
+    Ok(table)
+}
+
+////////////////////////////////////////////////////////////////
+// Apache Arrow IPC
+////////////////////////////////////////////////////////////////
+
+/// Minimal reader for the [FlatBuffers](https://google.github.io/flatbuffers/) tables that Arrow uses for its
+/// metadata. Everything is bounds-checked, returning [`DataError::InvalidData`] for malformed data.
+#[derive(Clone, Copy)]
+struct FlatTable<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+fn read_bytes<const N: usize>(buf: &[u8], pos: usize) -> Result<[u8; N], DataError> {
+    match buf.get(pos..pos.saturating_add(N)) {
+        Some(bytes) => Ok(bytes.try_into().unwrap()),
+        None => invalid_data("out of bounds read in Arrow metadata"),
+    }
+}
+
+fn read_u32(buf: &[u8], pos: usize) -> Result<usize, DataError> {
+    Ok(u32::from_le_bytes(read_bytes(buf, pos)?) as usize)
+}
+
+impl<'a> FlatTable<'a> {
+    /// The root table of a FlatBuffer.
+    fn root(buf: &'a [u8]) -> Result<Self, DataError> {
+        Ok(Self { buf, pos: read_u32(buf, 0)? })
+    }
+
+    /// Position of field number `field` within `buf`, or [`None`] if it's not set.
+    fn field_pos(&self, field: usize) -> Result<Option<usize>, DataError> {
+        let vtable_pos = (self.pos as i64 - i32::from_le_bytes(read_bytes(self.buf, self.pos)?) as i64) as usize;
+        let vtable_size = u16::from_le_bytes(read_bytes(self.buf, vtable_pos)?) as usize;
+        let entry = 4 + 2 * field;
+        if entry + 2 > vtable_size {
+            return Ok(None);
+        }
+        match u16::from_le_bytes(read_bytes(self.buf, vtable_pos + entry)?) {
+            0 => Ok(None),
+            offset => Ok(Some(self.pos + offset as usize)),
+        }
+    }
+
+    fn scalar<const N: usize>(&self, field: usize) -> Result<Option<[u8; N]>, DataError> {
+        self.field_pos(field)?.map(|pos| read_bytes(self.buf, pos)).transpose()
+    }
+
+    fn u8(&self, field: usize) -> Result<u8, DataError> {
+        Ok(self.scalar::<1>(field)?.map_or(0, |bytes| bytes[0]))
+    }
+
+    fn i16(&self, field: usize) -> Result<i16, DataError> {
+        Ok(self.scalar(field)?.map_or(0, i16::from_le_bytes))
+    }
+
+    fn i32(&self, field: usize) -> Result<i32, DataError> {
+        Ok(self.scalar(field)?.map_or(0, i32::from_le_bytes))
+    }
+
+    fn i64(&self, field: usize) -> Result<i64, DataError> {
+        Ok(self.scalar(field)?.map_or(0, i64::from_le_bytes))
+    }
+
+    /// Follow the offset stored in field number `field`.
+    fn offset(&self, field: usize) -> Result<Option<usize>, DataError> {
+        self.field_pos(field)?.map(|pos| Ok(pos + read_u32(self.buf, pos)?)).transpose()
+    }
+
+    fn table(&self, field: usize) -> Result<Option<FlatTable<'a>>, DataError> {
+        Ok(self.offset(field)?.map(|pos| FlatTable { buf: self.buf, pos }))
+    }
+
+    fn string(&self, field: usize) -> Result<String, DataError> {
+        match self.offset(field)? {
+            None => Ok(String::new()),
+            Some(pos) => {
+                let len = read_u32(self.buf, pos)?;
+                match self.buf.get(pos + 4..pos + 4 + len) {
+                    Some(bytes) => Ok(String::from_utf8_lossy(bytes).into_owned()),
+                    None => invalid_data("out of bounds string in Arrow metadata"),
+                }
+            }
+        }
+    }
+
+    fn vector_of_tables(&self, field: usize) -> Result<Vec<FlatTable<'a>>, DataError> {
+        match self.offset(field)? {
+            None => Ok(vec![]),
+            Some(pos) => (0..read_u32(self.buf, pos)?)
+                .map(|i| {
+                    let element_pos = pos + 4 + 4 * i;
+                    Ok(FlatTable { buf: self.buf, pos: element_pos + read_u32(self.buf, element_pos)? })
+                })
+                .collect(),
+        }
+    }
+
+    /// A vector of structs that consist of two `i64`s, which is the case for both `FieldNode` and `Buffer`.
+    fn vector_of_i64_pairs(&self, field: usize) -> Result<Vec<(i64, i64)>, DataError> {
+        match self.offset(field)? {
+            None => Ok(vec![]),
+            Some(pos) => (0..read_u32(self.buf, pos)?)
+                .map(|i| {
+                    let element_pos = pos + 4 + 16 * i;
+                    Ok((
+                        i64::from_le_bytes(read_bytes(self.buf, element_pos)?),
+                        i64::from_le_bytes(read_bytes(self.buf, element_pos + 8)?),
+                    ))
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Arrow types that we support.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum ArrowType {
+    Int { bit_width: i32, signed: bool },
+    Float { bytes: usize },
+    Utf8,
+    LargeUtf8,
+}
+
+fn parse_arrow_schema(schema: FlatTable) -> Result<Vec<(String, ArrowType)>, DataError> {
+    // Schema: endianness = 0, fields = 1. Endianness::Little = 0.
+    if schema.i16(0)? != 0 {
+        return invalid_data("big-endian Arrow data is not supported");
+    }
+    schema
+        .vector_of_tables(1)?
+        .into_iter()
+        .map(|field| {
+            // Field: name = 0, nullable = 1, type_type = 2, type = 3, dictionary = 4.
+            let name = field.string(0)?;
+            if field.field_pos(4)?.is_some() {
+                return invalid_data(format!("column {}: dictionary-encoded columns are not supported", name));
+            }
+            let type_table = field.table(3)?;
+            let arrow_type = match (field.u8(2)?, type_table) {
+                // Int: bitWidth = 0, is_signed = 1.
+                (2, Some(int)) => ArrowType::Int { bit_width: int.i32(0)?, signed: int.u8(1)? != 0 },
+                // FloatingPoint: precision = 0, with HALF = 0, SINGLE = 1, DOUBLE = 2.
+                (3, Some(float)) => match float.i16(0)? {
+                    1 => ArrowType::Float { bytes: 4 },
+                    2 => ArrowType::Float { bytes: 8 },
+                    _ => return invalid_data(format!("column {}: half-precision floats are not supported", name)),
+                },
+                (5, _) => ArrowType::Utf8,
+                (20, _) => ArrowType::LargeUtf8,
+                (type_id, _) => {
+                    return invalid_data(format!("column {}: unsupported type (Arrow type id {})", name, type_id));
+                }
+            };
+            Ok((name, arrow_type))
+        })
+        .collect()
+}
+
+fn arrow_buffer<'a>(body: &'a [u8], buffers: &[(i64, i64)], index: usize) -> Result<&'a [u8], DataError> {
+    let (offset, length) = match buffers.get(index) {
+        Some(&buffer) => buffer,
+        None => return invalid_data("missing buffer in Arrow record batch"),
+    };
+    match body.get(offset as usize..(offset + length) as usize) {
+        Some(bytes) => Ok(bytes),
+        None => invalid_data("out of bounds buffer in Arrow record batch"),
+    }
+}
+
+/// Append the values of one record batch to `columns`.
+fn read_arrow_record_batch(
+    batch: FlatTable,
+    body: &[u8],
+    schema: &[(String, ArrowType)],
+    columns: &mut [ColumnValues],
+) -> Result<(), DataError> {
+    // RecordBatch: length = 0, nodes = 1, buffers = 2, compression = 3.
+    if batch.field_pos(3)?.is_some() {
+        return invalid_data("compressed Arrow data is not supported");
+    }
+    let length = batch.i64(0)? as usize;
+    let nodes = batch.vector_of_i64_pairs(1)?;
+    let buffers = batch.vector_of_i64_pairs(2)?;
+    if nodes.len() != schema.len() {
+        return invalid_data("number of columns in Arrow record batch doesn't match the schema");
+    }
+
+    let mut buffer_index = 0;
+    for (((name, arrow_type), column), &(_node_length, null_count)) in schema.iter().zip(columns.iter_mut()).zip(&nodes) {
+        let validity = arrow_buffer(body, &buffers, buffer_index)?;
+        let is_valid = |i: usize| {
+            null_count == 0 || validity.is_empty() || matches!(validity.get(i / 8), Some(byte) if byte & (1 << (i % 8)) != 0)
+        };
+        let out_of_bounds = || invalid_data(format!("column {}: not enough data in Arrow record batch", name));
+
+        match (arrow_type, column) {
+            (ArrowType::Int { bit_width, signed }, ColumnValues::F32(values)) => {
+                let bytes = (*bit_width / 8) as usize;
+                if ![1, 2, 4, 8].contains(&bytes) {
+                    return invalid_data(format!("column {}: unsupported integer bit width {}", name, bit_width));
+                }
+                let data = arrow_buffer(body, &buffers, buffer_index + 1)?;
+                if data.len() < length * bytes {
+                    return out_of_bounds();
+                }
+                values.extend((0..length).map(|i| {
+                    if !is_valid(i) {
+                        return f32::NAN;
+                    }
+                    let mut le_bytes = [0; 8];
+                    le_bytes[..bytes].copy_from_slice(&data[i * bytes..(i + 1) * bytes]);
+                    if *signed {
+                        // Sign-extend by shifting the value into the top bits and back.
+                        let shift = 64 - 8 * bytes as u32;
+                        ((i64::from_le_bytes(le_bytes) << shift) >> shift) as f32
+                    } else {
+                        u64::from_le_bytes(le_bytes) as f32
+                    }
+                }));
+                buffer_index += 2;
+            }
+            (ArrowType::Float { bytes }, ColumnValues::F32(values)) => {
+                let bytes = *bytes;
+                let data = arrow_buffer(body, &buffers, buffer_index + 1)?;
+                if data.len() < length * bytes {
+                    return out_of_bounds();
+                }
+                values.extend((0..length).map(|i| {
+                    let value = &data[i * bytes..(i + 1) * bytes];
+                    match (is_valid(i), bytes) {
+                        (false, _) => f32::NAN,
+                        (true, 4) => f32::from_le_bytes(value.try_into().unwrap()),
+                        (true, _) => f64::from_le_bytes(value.try_into().unwrap()) as f32,
+                    }
+                }));
+                buffer_index += 2;
+            }
+            (ArrowType::Utf8 | ArrowType::LargeUtf8, ColumnValues::String(values)) => {
+                let offset_bytes = if *arrow_type == ArrowType::Utf8 { 4 } else { 8 };
+                let offsets = arrow_buffer(body, &buffers, buffer_index + 1)?;
+                let data = arrow_buffer(body, &buffers, buffer_index + 2)?;
+                if offsets.len() < (length + 1) * offset_bytes {
+                    return out_of_bounds();
+                }
+                let offset = |i: usize| {
+                    let mut le_bytes = [0; 8];
+                    le_bytes[..offset_bytes].copy_from_slice(&offsets[i * offset_bytes..(i + 1) * offset_bytes]);
+                    u64::from_le_bytes(le_bytes) as usize
+                };
+                for i in 0..length {
+                    if !is_valid(i) {
+                        values.push(String::new());
+                        continue;
+                    }
+                    match data.get(offset(i)..offset(i + 1)) {
+                        Some(bytes) => values.push(String::from_utf8_lossy(bytes).into_owned()),
+                        None => return out_of_bounds(),
+                    }
+                }
+                buffer_index += 3;
+            }
+            _ => unreachable!("columns are created based on the schema"),
+        }
+    }
+    Ok(())
+}
+
+/// Read an [Apache Arrow IPC](https://arrow.apache.org/docs/format/Columnar.html#serialization-and-interprocess-communication-ipc)
+/// file (`.arrow`, `.feather` version 2) or stream.
+///
+/// Supported column types are integers and floats (which become [`ColumnValues::F32`]), and UTF-8 strings.
+/// Compressed and dictionary-encoded data, and big-endian data, are not supported.
+pub fn read_arrow(reader: impl Read) -> Result<DataTable, DataError> {
+    fn read_u32(reader: &mut impl Read) -> Result<Option<u32>, DataError> {
+        let mut bytes = [0; 4];
+        match reader.read_exact(&mut bytes) {
+            Ok(()) => Ok(Some(u32::from_le_bytes(bytes))),
+            // Streams can end without an end-of-stream marker.
+            Err(err) if err.kind() == std::io::ErrorKind::UnexpectedEof => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    let mut reader = std::io::BufReader::with_capacity(CHUNK_SIZE, reader);
+
+    let mut schema: Option<Vec<(String, ArrowType)>> = None;
+    let mut columns: Vec<ColumnValues> = vec![];
+    let mut is_first_message = true;
+    while let Some(mut metadata_len) = read_u32(&mut reader)? {
+        // The file format starts with "ARROW1" and two bytes of padding, followed by the stream format.
+        if is_first_message && metadata_len.to_le_bytes() == *b"ARRO" {
+            let mut rest_of_magic = [0; 4];
+            reader.read_exact(&mut rest_of_magic)?;
+            if &rest_of_magic[..2] != b"W1" {
+                return invalid_data("not an Arrow file");
+            }
+            metadata_len = match read_u32(&mut reader)? {
+                Some(value) => value,
+                None => break,
+            };
+        }
+        is_first_message = false;
+        // Continuation marker (absent in data written by old versions of Arrow).
+        if metadata_len == 0xFFFF_FFFF {
+            metadata_len = match read_u32(&mut reader)? {
+                Some(value) => value,
+                None => break,
+            };
+        }
+        if metadata_len == 0 {
+            // End-of-stream marker. In the file format, this is followed by the footer, which we don't need.
+            break;
+        }
+
+        let mut metadata = vec![0; metadata_len as usize];
+        reader.read_exact(&mut metadata)?;
+        // Message: version = 0, header_type = 1, header = 2, bodyLength = 3.
+        let message = FlatTable::root(&metadata)?;
+        let body_len = message.i64(3)?;
+        if body_len < 0 {
+            return invalid_data("negative body length in Arrow message");
+        }
+        let mut body = vec![0; body_len as usize];
+        reader.read_exact(&mut body)?;
+        let header = match message.table(2)? {
+            Some(header) => header,
+            None => return invalid_data("missing header in Arrow message"),
+        };
+
+        match message.u8(1)? {
+            // Schema
+            1 => {
+                let parsed_schema = parse_arrow_schema(header)?;
+                columns = parsed_schema
+                    .iter()
+                    .map(|(_, arrow_type)| match arrow_type {
+                        ArrowType::Utf8 | ArrowType::LargeUtf8 => ColumnValues::String(vec![]),
+                        _ => ColumnValues::F32(vec![]),
+                    })
+                    .collect();
+                schema = Some(parsed_schema);
+            }
+            // DictionaryBatch
+            2 => return invalid_data("dictionary-encoded Arrow data is not supported"),
+            // RecordBatch
+            3 => match &schema {
+                Some(schema) => read_arrow_record_batch(header, &body, schema, &mut columns)?,
+                None => return invalid_data("Arrow record batch before schema"),
+            },
+            header_type => return invalid_data(format!("unsupported Arrow message type {}", header_type)),
+        }
+    }
+
+    let schema = match schema {
+        Some(schema) => schema,
+        None => return invalid_data("no schema found in Arrow data"),
+    };
+    Ok(DataTable { columns: schema.into_iter().zip(columns).map(|((name, _), values)| DataColumn { name, values }).collect() })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn csv(input: &str) -> Result<DataTable, DataError> {
+        read_csv(input.as_bytes(), CsvOptions::default())
+    }
+
+    #[test]
+    fn test_read_csv() {
+        let table = csv("x,y,label\r\n1,2.5,a\n3,,\"b, \"\"c\"\"\nd\"\n\n-4,1e3,e").unwrap();
+        assert_eq!(table.len(), 3);
+        assert_eq!(table.column("x").unwrap(), &ColumnValues::F32(vec![1., 3., -4.]));
+        let y = table.column("y").unwrap().as_f32_slice().unwrap();
+        assert_eq!(y[0], 2.5);
+        assert!(y[1].is_nan());
+        assert_eq!(y[2], 1000.);
+        assert_eq!(
+            table.column("label").unwrap(),
+            &ColumnValues::String(vec!["a".to_string(), "b, \"c\"\nd".to_string(), "e".to_string()])
+        );
+    }
+
+    #[test]
+    fn test_read_csv_options_and_errors() {
+        let table = read_csv("1\t2\n3\tfoo\n".as_bytes(), CsvOptions { delimiter: b'\t', has_header: false }).unwrap();
+        assert_eq!(table.columns[0].name, "column_0");
+        assert_eq!(table.column("column_0").unwrap(), &ColumnValues::F32(vec![1., 3.]));
+        // Column turns out to not be numeric.
+        assert_eq!(table.column("column_1").unwrap(), &ColumnValues::String(vec!["2".to_string(), "foo".to_string()]));
+
+        // Missing fields are allowed.
+        assert!(csv("a,b\n1\n").unwrap().column("b").unwrap().as_f32_slice().unwrap()[0].is_nan());
+        assert_eq!(csv("a,b\n1,2,3\n"), invalid_data("line 2 has 3 fields, but there are only 2 columns"));
+        assert_eq!(csv("a\n\"oops\n"), invalid_data("unterminated quoted field on line 3"));
+        assert_eq!(csv(""), Ok(DataTable::default()));
+    }
+
+    /// Values for [`flatbuffer`], to build test data.
+    enum Fb {
+        Absent,
+        U8(u8),
+        I16(i16),
+        I32(i32),
+        I64(i64),
+        Str(&'static str),
+        Table(Vec<Fb>),
+        Tables(Vec<Vec<Fb>>),
+        I64Pairs(Vec<(i64, i64)>),
+    }
+
+    /// Append a table to `buf` (preceded by its vtable), and return the position of the table. Referenced
+    /// objects come after the table, since offsets are unsigned.
+    fn write_table(buf: &mut Vec<u8>, fields: &[Fb]) -> usize {
+        let inline_size = |field: &Fb| match field {
+            Fb::Absent => 0,
+            Fb::U8(_) => 1,
+            Fb::I16(_) => 2,
+            Fb::I64(_) => 8,
+            _ => 4,
+        };
+        let vtable_pos = buf.len();
+        buf.extend((4 + 2 * fields.len() as u16).to_le_bytes());
+        buf.extend((4 + fields.iter().map(inline_size).sum::<usize>() as u16).to_le_bytes());
+        let mut offset = 4;
+        for field in fields {
+            let size = inline_size(field);
+            buf.extend((if size == 0 { 0 } else { offset as u16 }).to_le_bytes());
+            offset += size;
+        }
+
+        let table_pos = buf.len();
+        buf.extend(((table_pos - vtable_pos) as i32).to_le_bytes());
+        let mut references = vec![];
+        for field in fields {
+            match field {
+                Fb::Absent => {}
+                Fb::U8(value) => buf.push(*value),
+                Fb::I16(value) => buf.extend(value.to_le_bytes()),
+                Fb::I32(value) => buf.extend(value.to_le_bytes()),
+                Fb::I64(value) => buf.extend(value.to_le_bytes()),
+                _ => {
+                    references.push((buf.len(), field));
+                    buf.extend([0; 4]);
+                }
+            }
+        }
+        for (field_pos, field) in references {
+            let target = match field {
+                Fb::Str(string) => {
+                    let pos = buf.len();
+                    buf.extend((string.len() as u32).to_le_bytes());
+                    buf.extend(string.as_bytes());
+                    pos
+                }
+                Fb::Table(fields) => write_table(buf, fields),
+                Fb::Tables(tables) => {
+                    let pos = buf.len();
+                    buf.extend((tables.len() as u32).to_le_bytes());
+                    buf.extend(vec![0; 4 * tables.len()]);
+                    for (i, fields) in tables.iter().enumerate() {
+                        let element_pos = pos + 4 + 4 * i;
+                        let table_pos = write_table(buf, fields);
+                        buf[element_pos..element_pos + 4].copy_from_slice(&((table_pos - element_pos) as u32).to_le_bytes());
+                    }
+                    pos
+                }
+                Fb::I64Pairs(pairs) => {
+                    let pos = buf.len();
+                    buf.extend((pairs.len() as u32).to_le_bytes());
+                    for (a, b) in pairs {
+                        buf.extend(a.to_le_bytes());
+                        buf.extend(b.to_le_bytes());
+                    }
+                    pos
+                }
+                _ => unreachable!(),
+            };
+            buf[field_pos..field_pos + 4].copy_from_slice(&((target - field_pos) as u32).to_le_bytes());
+        }
+        table_pos
+    }
+
+    fn flatbuffer(fields: &[Fb]) -> Vec<u8> {
+        let mut buf = vec![0; 4];
+        let root = write_table(&mut buf, fields);
+        buf[0..4].copy_from_slice(&(root as u32).to_le_bytes());
+        buf
+    }
+
+    fn write_message(out: &mut Vec<u8>, header_type: u8, header: Vec<Fb>, body: &[u8]) {
+        let metadata = flatbuffer(&[Fb::I16(4), Fb::U8(header_type), Fb::Table(header), Fb::I64(body.len() as i64), Fb::Absent]);
+        out.extend(0xFFFF_FFFFu32.to_le_bytes());
+        out.extend((metadata.len() as u32).to_le_bytes());
+        out.extend(metadata);
+        out.extend(body);
+    }
+
+    #[test]
+    fn test_read_arrow() {
+        let field = |name: &'static str, type_type: u8, type_fields: Vec<Fb>| {
+            vec![Fb::Str(name), Fb::U8(1), Fb::U8(type_type), Fb::Table(type_fields)]
+        };
+        let schema = vec![
+            Fb::I16(0),
+            Fb::Tables(vec![
+                field("ints", 2, vec![Fb::I32(16), Fb::U8(1)]),
+                field("floats", 3, vec![Fb::I16(2)]),
+                field("names", 5, vec![]),
+            ]),
+        ];
+
+        // Two rows; the second float is null.
+        let mut body = vec![];
+        body.extend((-2i16).to_le_bytes());
+        body.extend(7i16.to_le_bytes());
+        body.extend(0.5f64.to_le_bytes());
+        body.extend(0f64.to_le_bytes());
+        body.push(0b01);
+        body.extend([0, 0, 0, 0, 1, 0, 0, 0, 3, 0, 0, 0]);
+        body.extend(b"abc");
+        let record_batch = vec![
+            Fb::I64(2),
+            Fb::I64Pairs(vec![(2, 0), (2, 1), (2, 0)]),
+            Fb::I64Pairs(vec![(0, 0), (0, 4), (20, 1), (4, 16), (0, 0), (21, 12), (33, 3)]),
+        ];
+
+        let mut stream = vec![];
+        write_message(&mut stream, 1, schema, &[]);
+        write_message(&mut stream, 3, record_batch, &body);
+        stream.extend([0xFF, 0xFF, 0xFF, 0xFF, 0, 0, 0, 0]);
+
+        let expected_names = vec!["ints", "floats", "names"];
+        for data in [stream.clone(), [b"ARROW1\0\0".to_vec(), stream].concat()] {
+            let table = read_arrow(&data[..]).unwrap();
+            assert_eq!(table.columns.iter().map(|column| column.name.as_str()).collect::<Vec<_>>(), expected_names);
+            assert_eq!(table.column("ints").unwrap(), &ColumnValues::F32(vec![-2., 7.]));
+            let floats = table.column("floats").unwrap().as_f32_slice().unwrap();
+            assert_eq!(floats[0], 0.5);
+            assert!(floats[1].is_nan());
+            assert_eq!(table.column("names").unwrap(), &ColumnValues::String(vec!["a".to_string(), "bc".to_string()]));
+        }
+
+        assert_eq!(read_arrow(&b"ARROW2\0\0"[..]), invalid_data("not an Arrow file"));
+    }
+}
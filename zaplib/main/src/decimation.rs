@@ -0,0 +1,247 @@
+//! Downsampling of time series, for drawing line charts with many more points than there are pixels.
+//!
+//! * [`lttb`] (Largest-Triangle-Three-Buckets) picks a fixed number of points that preserve the visual shape
+//!   of the line well. Good for static charts.
+//! * [`min_max`] keeps the first, minimum, maximum, and last point of each bin, so that no peaks are lost,
+//!   which is what you want when each bin is about a pixel wide. [`MinMaxDecimator`] does the same
+//!   incrementally, for data that keeps streaming in.
+//!
+//! All functions expect `xs` to be sorted in ascending order, and `xs` and `ys` to have the same length.
+//! Points with a NaN `y` (e.g. missing values from [`crate::data`]) are skipped.
+//!
+//! With the `simd` cargo feature, the inner loops (averages and triangle areas in [`lttb`], minimums and maximums in
+//! [`min_max`] and [`MinMaxDecimator`]) use SIMD instructions.
+
+use crate::*;
+
+/// Downsample to (at most) `threshold` points using the Largest-Triangle-Three-Buckets algorithm, as described
+/// in [Downsampling Time Series for Visual Representation](https://skemman.is/bitstream/1946/15343/3/SS_MSthesis.pdf).
+///
+/// Always keeps the first and last point. If `threshold` is less than 3 or not less than the number of points,
+/// all points are returned.
+pub fn lttb(xs: &[f32], ys: &[f32], threshold: usize) -> Vec<Vec2> {
+    assert_eq!(xs.len(), ys.len(), "xs and ys must have the same length");
+    // Leave out NaNs up front, so that the loops below can work on contiguous slices.
+    let (xs, ys): (Vec<f32>, Vec<f32>) = xs.iter().zip(ys).filter(|(_, y)| !y.is_nan()).map(|(&x, &y)| (x, y)).unzip();
+    let point = |index: usize| vec2(xs[index], ys[index]);
+    let len = xs.len();
+    if threshold < 3 || threshold >= len {
+        return (0..len).map(point).collect();
+    }
+
+    let mut points = Vec::with_capacity(threshold);
+    points.push(point(0));
+    // Buckets for everything except the first and last point.
+    let bucket_size = (len - 2) as f64 / (threshold - 2) as f64;
+    let bucket_start = |bucket: usize| ((bucket as f64 * bucket_size) as usize + 1).min(len - 1);
+    let mut previous = point(0);
+    for bucket in 0..threshold - 2 {
+        // Average of the next bucket (or the last point, for the last bucket).
+        let (next_start, next_end) = (bucket_start(bucket + 1), bucket_start(bucket + 2).max(bucket_start(bucket + 1) + 1));
+        let next_count = (next_end - next_start) as f32;
+        let average = vec2(
+            crate::simd::sum(&xs[next_start..next_end]) / next_count,
+            crate::simd::sum(&ys[next_start..next_end]) / next_count,
+        );
+
+        // Pick the point in this bucket that forms the largest triangle with `previous` and `average`.
+        let (start, end) = (bucket_start(bucket), bucket_start(bucket + 1));
+        if let Some(index) = crate::simd::largest_triangle_index(&xs[start..end], &ys[start..end], previous, average) {
+            previous = point(start + index);
+        }
+        points.push(previous);
+    }
+    points.push(point(len - 1));
+    points
+}
+
+/// Divide the x-range of the data into `bin_count` bins of equal width, and keep the first, minimum,
+/// maximum, and last point of each bin (in their original order, without duplicates). This results in
+/// at most `4 * bin_count` points.
+///
+/// Use the width of the chart in pixels for `bin_count` to get a line that looks the same as when drawing
+/// all points.
+pub fn min_max(xs: &[f32], ys: &[f32], bin_count: usize) -> Vec<Vec2> {
+    assert_eq!(xs.len(), ys.len(), "xs and ys must have the same length");
+    let (first_x, last_x) = match (xs.first(), xs.last()) {
+        (Some(&first_x), Some(&last_x)) if bin_count > 0 => (first_x, last_x),
+        _ => return vec![],
+    };
+    // Make sure that `last_x` falls into the last bin, not into one after it.
+    let bin_width = ((last_x - first_x) / bin_count as f32) * (1. + f32::EPSILON * 4.);
+    let mut decimator = MinMaxDecimator::new(first_x, if bin_width > 0. { bin_width } else { 1. });
+    decimator.extend(xs, ys);
+    decimator.points()
+}
+
+/// A bin of [`MinMaxDecimator`]. Stores indices into [`MinMaxDecimator::points_pushed`] order, along with
+/// the points themselves.
+#[derive(Clone, Copy, Debug)]
+struct MinMaxBin {
+    bin_index: i64,
+    first: (usize, Vec2),
+    min: (usize, Vec2),
+    max: (usize, Vec2),
+    last: (usize, Vec2),
+}
+
+/// Incremental version of [`min_max`], with bins of a fixed width. Push points as they come in, and call
+/// [`MinMaxDecimator::points`] whenever you need to draw. Memory use is proportional to the number of bins,
+/// not the number of points.
+///
+/// ```ignore
+/// // One bin per pixel.
+/// let mut decimator = MinMaxDecimator::new(0., seconds_per_pixel);
+/// // Whenever new data comes in:
+/// decimator.extend(&new_xs, &new_ys);
+/// ```
+///
+/// When the bin width changes (e.g. when zooming), create a new [`MinMaxDecimator`].
+#[derive(Clone, Debug)]
+pub struct MinMaxDecimator {
+    origin: f32,
+    bin_width: f32,
+    bins: Vec<MinMaxBin>,
+    points_pushed: usize,
+}
+
+impl MinMaxDecimator {
+    /// Bins start at `origin`, and are `bin_width` wide.
+    pub fn new(origin: f32, bin_width: f32) -> Self {
+        assert!(bin_width > 0., "bin_width must be greater than 0");
+        Self { origin, bin_width, bins: vec![], points_pushed: 0 }
+    }
+
+    /// Add a point. Points must be pushed in order of ascending `x`; a point with a smaller `x` than the
+    /// previous one still ends up in the bin of the previous point.
+    pub fn push(&mut self, x: f32, y: f32) {
+        self.extend(&[x], &[y]);
+    }
+
+    /// Add many points; see [`MinMaxDecimator::push`].
+    pub fn extend(&mut self, xs: &[f32], ys: &[f32]) {
+        assert_eq!(xs.len(), ys.len(), "xs and ys must have the same length");
+        let mut start = 0;
+        // Add runs of points that go into the same bin at once.
+        while let Some(skipped) = ys[start..].iter().position(|y| !y.is_nan()) {
+            start += skipped;
+            let bin_index = match self.bins.last() {
+                Some(bin) if bin.bin_index >= self.bin_index(xs[start]) => bin.bin_index,
+                _ => self.bin_index(xs[start]),
+            };
+            let end = (start + 1..xs.len()).find(|&i| !ys[i].is_nan() && self.bin_index(xs[i]) > bin_index).unwrap_or(xs.len());
+            self.add_to_bin(bin_index, self.points_pushed + start, &xs[start..end], &ys[start..end]);
+            start = end;
+        }
+        self.points_pushed += xs.len();
+    }
+
+    fn bin_index(&self, x: f32) -> i64 {
+        ((x - self.origin) / self.bin_width).floor() as i64
+    }
+
+    /// Add points to the last bin if it has `bin_index`, or to a new bin otherwise. The first point has order
+    /// `first_order`, and must not have a NaN `y`.
+    fn add_to_bin(&mut self, bin_index: i64, first_order: usize, xs: &[f32], ys: &[f32]) {
+        let (min, max) = crate::simd::min_max_indices(ys).unwrap();
+        let last = ys.iter().rposition(|y| !y.is_nan()).unwrap();
+        let point = |index: usize| (first_order + index, vec2(xs[index], ys[index]));
+        match self.bins.last_mut() {
+            Some(bin) if bin.bin_index == bin_index => {
+                if ys[min] < bin.min.1.y {
+                    bin.min = point(min);
+                }
+                if ys[max] > bin.max.1.y {
+                    bin.max = point(max);
+                }
+                bin.last = point(last);
+            }
+            _ => self.bins.push(MinMaxBin { bin_index, first: point(0), min: point(min), max: point(max), last: point(last) }),
+        }
+    }
+
+    /// Remove all points.
+    pub fn clear(&mut self) {
+        self.bins.clear();
+        self.points_pushed = 0;
+    }
+
+    /// The downsampled points, in the order that they were pushed.
+    pub fn points(&self) -> Vec<Vec2> {
+        let mut points = Vec::with_capacity(self.bins.len() * 4);
+        for bin in &self.bins {
+            let mut bin_points = [bin.first, bin.min, bin.max, bin.last];
+            bin_points.sort_unstable_by_key(|&(order, _)| order);
+            let mut previous_order = None;
+            for (order, point) in bin_points {
+                if previous_order != Some(order) {
+                    points.push(point);
+                    previous_order = Some(order);
+                }
+            }
+        }
+        points
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lttb() {
+        let xs: Vec<f32> = (0..10).map(|i| i as f32).collect();
+        let ys = [0., 1., 0., 0., 5., 0., 0., -3., 0., 0.];
+        assert_eq!(lttb(&xs, &ys, 100).len(), 10);
+        assert_eq!(lttb(&xs, &ys, 2).len(), 10);
+        // Keeps the first and last point, and the peaks.
+        assert_eq!(lttb(&xs, &ys, 4), vec![vec2(0., 0.), vec2(4., 5.), vec2(7., -3.), vec2(9., 0.)]);
+
+        // NaNs are skipped.
+        let ys_with_nan = [0., f32::NAN, 2.];
+        assert_eq!(lttb(&xs[..3], &ys_with_nan, 10), vec![vec2(0., 0.), vec2(2., 2.)]);
+    }
+
+    #[test]
+    fn test_min_max() {
+        let xs: Vec<f32> = (0..8).map(|i| i as f32).collect();
+        let ys = [3., 1., 4., 1., 5., 9., 2., 6.];
+        // Bins: [0, 1, 2, 3] and [4, 5, 6, 7].
+        assert_eq!(
+            min_max(&xs, &ys, 2),
+            vec![vec2(0., 3.), vec2(1., 1.), vec2(2., 4.), vec2(3., 1.), vec2(4., 5.), vec2(5., 9.), vec2(6., 2.), vec2(7., 6.)]
+        );
+        // One bin: first, min, max, last.
+        assert_eq!(min_max(&xs, &ys, 1), vec![vec2(0., 3.), vec2(1., 1.), vec2(5., 9.), vec2(7., 6.)]);
+        assert_eq!(min_max(&[], &[], 10), vec![]);
+        assert_eq!(min_max(&[1.], &[2.], 10), vec![vec2(1., 2.)]);
+    }
+
+    #[test]
+    fn test_min_max_decimator_incremental() {
+        let mut decimator = MinMaxDecimator::new(0., 10.);
+        decimator.extend(&[0., 1., 2.], &[5., 7., 6.]);
+        assert_eq!(decimator.points(), vec![vec2(0., 5.), vec2(1., 7.), vec2(2., 6.)]);
+        decimator.push(3., 8.);
+        decimator.push(15., f32::NAN);
+        decimator.push(15., 1.);
+        assert_eq!(decimator.points(), vec![vec2(0., 5.), vec2(3., 8.), vec2(15., 1.)]);
+        decimator.clear();
+        assert_eq!(decimator.points(), vec![]);
+    }
+
+    #[test]
+    fn test_min_max_decimator_extend() {
+        // Long runs of points in the same bin, with ties and NaNs.
+        let xs: Vec<f32> = (0..100).map(|i| i as f32 * 0.37).collect();
+        let ys: Vec<f32> = (0..100).map(|i| if i % 11 == 0 { f32::NAN } else { ((i * 7) % 13) as f32 }).collect();
+        let mut pushed = MinMaxDecimator::new(0., 2.);
+        for (&x, &y) in xs.iter().zip(&ys) {
+            pushed.push(x, y);
+        }
+        let mut extended = MinMaxDecimator::new(0., 2.);
+        extended.extend(&xs[..50], &ys[..50]);
+        extended.extend(&xs[50..], &ys[50..]);
+        assert_eq!(extended.points(), pushed.points());
+    }
+}
@@ -0,0 +1,273 @@
+//! Opt-in crash and hang reporting, with a buffer of recent "breadcrumbs" (events, log lines, and
+//! custom messages) to help figure out what led up to the problem.
+//!
+//! Call [`enable_diagnostics`] with a callback that receives a [`CrashReport`], and ship it off to your
+//! error reporting service of choice. This works both natively and in WebAssembly.
+
+use crate::*;
+use once_cell::sync::Lazy;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Configuration for [`enable_diagnostics`].
+#[derive(Clone, Debug)]
+pub struct DiagnosticsConfig {
+    /// How many [`Breadcrumb`]s to keep; older ones get dropped.
+    pub max_breadcrumbs: usize,
+    /// Report a [`CrashReportKind::Hang`] when handling a single event or drawing takes longer than this
+    /// many seconds. Checked from a separate thread, so this works even when the main thread is stuck.
+    pub hang_timeout: Option<f64>,
+}
+
+impl DiagnosticsConfig {
+    pub const DEFAULT: DiagnosticsConfig = DiagnosticsConfig { max_breadcrumbs: 100, hang_timeout: Some(5.) };
+}
+
+impl Default for DiagnosticsConfig {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+/// Where a [`Breadcrumb`] came from.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BreadcrumbCategory {
+    /// An [`Event`] that was handled. Very frequent events like [`Event::NextFrame`] and
+    /// [`Event::PointerMove`] are skipped.
+    Event,
+    /// A message logged using the [`log`] crate (when using the logger from [`init_logger`]).
+    Log,
+    /// Added using [`add_breadcrumb`].
+    Custom,
+}
+
+/// Something that happened before a crash.
+#[derive(Clone, Debug)]
+pub struct Breadcrumb {
+    /// Seconds since [`enable_diagnostics`] was called. In WebAssembly every worker has its own clock,
+    /// so this is only accurate for breadcrumbs added on the thread that called [`enable_diagnostics`].
+    pub time: f64,
+    pub category: BreadcrumbCategory,
+    pub message: String,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CrashReportKind {
+    /// A thread panicked. In WebAssembly the app (or worker) can't continue after this.
+    Panic,
+    /// The main thread has been busy for longer than [`DiagnosticsConfig::hang_timeout`]. It might
+    /// still recover.
+    Hang,
+}
+
+/// Passed to the callback given to [`enable_diagnostics`].
+#[derive(Clone, Debug)]
+pub struct CrashReport {
+    pub kind: CrashReportKind,
+    /// For panics, the panic message including its location.
+    pub message: String,
+    /// Name of the thread that panicked, if it has one.
+    pub thread_name: Option<String>,
+    /// Most recent [`Breadcrumb`]s, oldest first.
+    pub breadcrumbs: Vec<Breadcrumb>,
+}
+
+type CrashCallback = Arc<dyn Fn(&CrashReport) + Send + Sync>;
+
+struct Diagnostics {
+    config: DiagnosticsConfig,
+    start: UniversalInstant,
+    breadcrumbs: VecDeque<Breadcrumb>,
+    on_crash: CrashCallback,
+}
+
+impl Diagnostics {
+    fn push(&mut self, category: BreadcrumbCategory, message: String) {
+        if self.config.max_breadcrumbs == 0 {
+            return;
+        }
+        if self.breadcrumbs.len() >= self.config.max_breadcrumbs {
+            self.breadcrumbs.pop_front();
+        }
+        let time = self.start.elapsed().as_secs_f64();
+        self.breadcrumbs.push_back(Breadcrumb { time, category, message });
+    }
+}
+
+static DIAGNOSTICS: Lazy<Mutex<Option<Diagnostics>>> = Lazy::new(|| Mutex::new(None));
+/// Fast check for whether [`DIAGNOSTICS`] is set, since we check this for every event.
+static ENABLED: AtomicBool = AtomicBool::new(false);
+/// An ID for the event that the main thread is currently handling, or 0 when it is idle. We use IDs
+/// instead of timestamps, since in WebAssembly every worker has its own clock.
+static BUSY_EVENT_ID: AtomicU64 = AtomicU64::new(0);
+static LAST_BUSY_EVENT_ID: AtomicU64 = AtomicU64::new(0);
+
+/// Start collecting [`Breadcrumb`]s, and call `on_crash` when a panic (on any thread) or hang happens.
+///
+/// `on_crash` gets called on the thread that panicked, or on a watchdog thread for hangs, so you can't
+/// use [`Cx`] in there. In WebAssembly, the app can't continue after a panic, so send the report
+/// synchronously (e.g. using [`universal_http_stream::request`]), or store it to send on the next start.
+///
+/// ```
+/// enable_diagnostics(DiagnosticsConfig::default(), |report| {
+///     let body = format!("{:?}: {}\n{:#?}", report.kind, report.message, report.breadcrumbs);
+///     // Send `body` to your error reporting service..
+/// });
+/// ```
+pub fn enable_diagnostics(config: DiagnosticsConfig, on_crash: impl Fn(&CrashReport) + Send + Sync + 'static) {
+    let hang_timeout = config.hang_timeout;
+    *DIAGNOSTICS.lock().unwrap() = Some(Diagnostics {
+        breadcrumbs: VecDeque::with_capacity(config.max_breadcrumbs),
+        config,
+        start: UniversalInstant::now(),
+        on_crash: Arc::new(on_crash),
+    });
+    if ENABLED.swap(true, Ordering::AcqRel) {
+        // Already installed the panic hook and watchdog; we just replaced the config and callback.
+        return;
+    }
+
+    let previous_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        report_crash(CrashReportKind::Panic, info.to_string());
+        previous_hook(info);
+    }));
+
+    if let Some(hang_timeout) = hang_timeout {
+        universal_thread::spawn(move || run_watchdog(hang_timeout));
+    }
+}
+
+/// Add a [`Breadcrumb`] with [`BreadcrumbCategory::Custom`], e.g. when the user does something
+/// important. Does nothing when [`enable_diagnostics`] hasn't been called.
+pub fn add_breadcrumb(message: impl Into<String>) {
+    push_breadcrumb(BreadcrumbCategory::Custom, message.into());
+}
+
+pub(crate) fn is_diagnostics_enabled() -> bool {
+    ENABLED.load(Ordering::Acquire)
+}
+
+pub(crate) fn push_breadcrumb(category: BreadcrumbCategory, message: String) {
+    if !is_diagnostics_enabled() {
+        return;
+    }
+    if let Some(diagnostics) = DIAGNOSTICS.lock().unwrap_or_else(|error| error.into_inner()).as_mut() {
+        diagnostics.push(category, message);
+    }
+}
+
+/// Add a [`BreadcrumbCategory::Event`] breadcrumb, skipping very frequent events.
+pub(crate) fn push_event_breadcrumb(event: &Event) {
+    if !is_diagnostics_enabled() {
+        return;
+    }
+    match event {
+        Event::None
+        | Event::NextFrame
+        | Event::XrUpdate(_)
+        | Event::PointerMove(_)
+        | Event::PointerHover(_)
+        | Event::PointerScroll(_)
+        | Event::PointerRawMove(_)
+        | Event::Timer(_)
+        | Event::FileDragUpdate(_)
+        | Event::System(_) => {}
+        _ => {
+            let mut message = format!("{:?}", event);
+            if let Some((index, _)) = message.char_indices().nth(200) {
+                message.truncate(index);
+                message.push('…');
+            }
+            push_breadcrumb(BreadcrumbCategory::Event, message);
+        }
+    }
+}
+
+/// Mark the main thread as busy (handling an event or drawing) or idle, for hang detection.
+pub(crate) fn set_main_thread_busy(busy: bool) {
+    if !is_diagnostics_enabled() {
+        return;
+    }
+    let event_id = if busy { LAST_BUSY_EVENT_ID.fetch_add(1, Ordering::Relaxed) + 1 } else { 0 };
+    BUSY_EVENT_ID.store(event_id, Ordering::Release);
+}
+
+fn report_crash(kind: CrashReportKind, message: String) {
+    // If we panicked while holding the lock, it's poisoned, but the breadcrumbs are still fine.
+    let guard = DIAGNOSTICS.lock().unwrap_or_else(|error| error.into_inner());
+    if let Some(diagnostics) = guard.as_ref() {
+        let on_crash = Arc::clone(&diagnostics.on_crash);
+        let report = CrashReport {
+            kind,
+            message,
+            thread_name: std::thread::current().name().map(str::to_string),
+            breadcrumbs: diagnostics.breadcrumbs.iter().cloned().collect(),
+        };
+        // Release the lock before calling `on_crash`, so it can add breadcrumbs or panic itself.
+        drop(guard);
+        on_crash(&report);
+    }
+}
+
+/// Periodically check if the main thread has been busy for too long.
+fn run_watchdog(hang_timeout: f64) {
+    let hang_timeout = Duration::from_secs_f64(hang_timeout);
+    let mut seen_event_id = 0;
+    let mut seen_since = UniversalInstant::now();
+    let mut reported = false;
+    loop {
+        universal_thread::sleep((hang_timeout / 4).max(Duration::from_millis(10)));
+        let event_id = BUSY_EVENT_ID.load(Ordering::Acquire);
+        if event_id != seen_event_id {
+            seen_event_id = event_id;
+            seen_since = UniversalInstant::now();
+            reported = false;
+        } else if event_id != 0 && !reported && seen_since.elapsed() > hang_timeout {
+            reported = true;
+            report_crash(
+                CrashReportKind::Hang,
+                format!("Main thread has been busy for more than {} seconds", hang_timeout.as_secs_f64()),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_breadcrumbs_ring_buffer() {
+        let mut diagnostics = Diagnostics {
+            config: DiagnosticsConfig { max_breadcrumbs: 2, hang_timeout: None },
+            start: UniversalInstant::now(),
+            breadcrumbs: VecDeque::new(),
+            on_crash: Arc::new(|_| {}),
+        };
+        for message in ["first", "second", "third"] {
+            diagnostics.push(BreadcrumbCategory::Custom, message.to_string());
+        }
+        let messages: Vec<&str> = diagnostics.breadcrumbs.iter().map(|breadcrumb| breadcrumb.message.as_str()).collect();
+        assert_eq!(messages, vec!["second", "third"]);
+    }
+
+    #[test]
+    fn test_panic_report() {
+        let reports = Arc::new(Mutex::new(Vec::new()));
+        let reports_clone = Arc::clone(&reports);
+        enable_diagnostics(DiagnosticsConfig { max_breadcrumbs: 1000, hang_timeout: None }, move |report| {
+            reports_clone.lock().unwrap().push(report.clone());
+        });
+        add_breadcrumb("before boom");
+        assert!(std::panic::catch_unwind(|| panic!("boom")).is_err());
+
+        // Other tests might panic at the same time, so look for ours specifically.
+        let reports = reports.lock().unwrap();
+        let report = reports.iter().find(|report| report.message.contains("boom")).unwrap();
+        assert_eq!(report.kind, CrashReportKind::Panic);
+        assert!(report.breadcrumbs.iter().any(|breadcrumb| breadcrumb.message == "before boom"));
+    }
+}
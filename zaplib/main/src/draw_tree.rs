@@ -43,10 +43,35 @@ pub struct View {
     /// Whether this [`View`] is an overlay/popup, which means all [`DrawCall`]s underneath it
     /// will get rendered last.
     pub(crate) is_overlay: bool,
+    /// Whether this [`View`] only uploads instances to the GPU that changed; see [`View::with_is_retained`].
+    pub(crate) is_retained: bool,
+    /// What the [`View`] was last drawn with, when using [`View::begin_cached_view`].
+    layout_cache: Option<ViewLayoutCache>,
 
     debugger: Debugger,
 }
 
+/// Everything that determines the layout and contents of a [`View`] drawn with [`View::begin_cached_view`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct ViewLayoutCacheKey {
+    content_hash: u64,
+    layout_size: LayoutSize,
+    /// The position of the parent [`CxLayoutBox`].
+    origin: Vec2,
+    /// The space left in the parent [`CxLayoutBox`], which is what [`Width::Fill`] and [`Height::Fill`] use.
+    space_left: Vec2,
+    dpi_factor: f32,
+    /// See [`Cx::view_cache_generation`].
+    generation: u64,
+}
+
+/// A [`ViewLayoutCacheKey`] and the [`Rect`] that the [`View`] walked when it was drawn with it.
+#[derive(Clone, Copy, Debug)]
+struct ViewLayoutCache {
+    key: ViewLayoutCacheKey,
+    rect: Rect,
+}
+
 impl View {
     /// Creates a new, empty [`View`].
 
@@ -56,6 +81,17 @@ impl View {
         Self { is_overlay, ..self }
     }
 
+    /// Retained mode: keep the instances of the [`DrawCall`]s of this [`View`] from the previous draw, and after
+    /// drawing only upload the ones that changed to the GPU. This costs some memory and a comparison of the
+    /// instances on every draw, but it saves a lot of uploading in [`View`]s that mostly stay the same between
+    /// draws, like static parts of the UI.
+    ///
+    /// To also skip generating the instances at all, use [`View::begin_cached_view`].
+    #[must_use]
+    pub fn with_is_retained(self, is_retained: bool) -> Self {
+        Self { is_retained, ..self }
+    }
+
     /// Register the [`View`] in the draw tree.
     ///
     /// This also creates a new [`CxLayoutBox`] with the [`LayoutSize`] that is passed in.
@@ -67,9 +103,61 @@ impl View {
     ///
     /// TODO(JP): Perhaps we should decouple [`CxLayoutBox`] and [`View`] altogether?
     pub fn begin_view(&mut self, cx: &mut Cx, layout_size: LayoutSize) {
+        self.layout_cache = None;
         self.begin_view_with_layout(cx, Layout { direction: Direction::Down, layout_size, ..Layout::default() });
     }
 
+    /// Like [`View::begin_view`], but skips drawing if nothing changed since the last draw. Returns `false` in that
+    /// case, and then you should not draw anything, nor call [`View::end_view`]; the [`View`] just walks the same
+    /// [`Rect`] as last time, and keeps its [`DrawCall`]s (including those of [`View`]s inside of it).
+    ///
+    /// This is useful for big parts of the UI that rarely change, to not have to lay out and draw them (including
+    /// measuring and shaping text) on every draw. Whether something changed is determined by:
+    /// * `content_hash`, which should cover everything that is drawn inside the [`View`], like text and
+    ///   component state (including any animations, focus, and hover states);
+    /// * the `layout_size`;
+    /// * the position and space left in the parent [`CxLayoutBox`];
+    /// * the DPI factor, and [`Cx::invalidate_view_caches`], which also gets called when font atlases get reset.
+    ///
+    /// Some things don't work in a [`View`] that skips drawing: its contents don't get aligned (e.g. by
+    /// [`Cx::begin_center_x_align`]) again, and components inside of it can't register for keyboard focus, since
+    /// that happens while drawing.
+    ///
+    /// The root [`View`] of a [`Pass`] always draws.
+    pub fn begin_cached_view(&mut self, cx: &mut Cx, layout_size: LayoutSize, content_hash: u64) -> bool {
+        let pass_id = *cx.pass_stack.last().expect("No pass found when begin_cached_view");
+        let main_view_id = cx.passes[pass_id].main_view_id;
+        if main_view_id.is_none() || main_view_id == self.view_id {
+            self.begin_view(cx, layout_size);
+            return true;
+        }
+
+        let key = ViewLayoutCacheKey {
+            content_hash,
+            layout_size,
+            origin: cx.get_draw_pos(),
+            space_left: vec2(cx.get_width_left(), cx.get_height_left()),
+            dpi_factor: cx.current_dpi_factor,
+            generation: cx.view_cache_generation,
+        };
+        if let (Some(view_id), Some(layout_cache)) = (self.view_id, self.layout_cache) {
+            if layout_cache.key == key && cx.views[view_id].pass_id == pass_id {
+                self.add_to_parent_view(cx, pass_id, view_id, false);
+                let size = layout_cache.rect.size;
+                let rect = cx.add_box(LayoutSize::new(Width::Fix(size.x), Height::Fix(size.y)));
+                cx.views[view_id].rect = rect;
+                // Make sure that the ViewArea would also be aligned, like in `end_view`.
+                cx.layout_box_align_list.push(Area::View(ViewArea { view_id, redraw_id: self.redraw_id }));
+                return false;
+            }
+        }
+
+        self.begin_view(cx, layout_size);
+        // `end_view` fills in the `Rect`.
+        self.layout_cache = Some(ViewLayoutCache { key, rect: Rect::default() });
+        true
+    }
+
     fn begin_view_with_layout(&mut self, cx: &mut Cx, layout: Layout) {
         if !cx.in_redraw_cycle {
             panic!("calling begin_view outside of redraw cycle is not possible!");
@@ -102,6 +190,39 @@ impl View {
             (layout, false)
         };
 
+        self.add_to_parent_view(cx, pass_id, view_id, is_root_for_pass);
+
+        // TODO(JP): Do we want to keep this? We don't really use this for anything except as a
+        // convenience. I talked with Rik about redrawing of [`View`]s, and one idea was to always
+        // fully invalidate the closest [`View`] parent that did not have a [`Layout`] with
+        // [`Width::Compute`] or [`Height::Compute`], but that seems to fragile to me. It would be
+        // better to check if a [`CxView::rect`] actually changed and in that case trigger a redraw
+        // or even a panic (with some way of manually overriding the panic). So anyway, I think we
+        // should strive to remove this after all.
+        cx.begin_typed_box(CxBoxType::View, override_layout);
+
+        // prepare drawlist for drawing
+        let cxview = &mut cx.views[view_id];
+
+        // TODO(JP): We don't seem to currently support moving a `View` to a different pass. Do we
+        // want to?
+        assert_eq!(cxview.pass_id, pass_id);
+
+        // update drawlist ids
+        self.redraw_id = cx.redraw_id;
+        cxview.redraw_id = cx.redraw_id;
+        cxview.draw_calls_len = 0;
+        cxview.is_retained = self.is_retained;
+
+        cx.view_stack.push(view_id);
+
+        if is_root_for_pass {
+            cx.passes[pass_id].paint_dirty = true;
+        }
+    }
+
+    /// Add a [`DrawCall`] for this [`View`] to its parent [`View`], if it has one.
+    fn add_to_parent_view(&self, cx: &mut Cx, pass_id: usize, view_id: usize, is_root_for_pass: bool) {
         let cxpass = &mut cx.passes[pass_id];
         // find the parent draw list id
         let parent_view_id = if self.is_overlay {
@@ -139,35 +260,9 @@ impl View {
                 let draw = &mut parent_cxview.draw_calls[id];
                 draw.sub_view_id = view_id;
                 draw.redraw_id = cx.redraw_id;
+                draw.batched_into = None;
             }
         }
-
-        // TODO(JP): Do we want to keep this? We don't really use this for anything except as a
-        // convenience. I talked with Rik about redrawing of [`View`]s, and one idea was to always
-        // fully invalidate the closest [`View`] parent that did not have a [`Layout`] with
-        // [`Width::Compute`] or [`Height::Compute`], but that seems to fragile to me. It would be
-        // better to check if a [`CxView::rect`] actually changed and in that case trigger a redraw
-        // or even a panic (with some way of manually overriding the panic). So anyway, I think we
-        // should strive to remove this after all.
-        cx.begin_typed_box(CxBoxType::View, override_layout);
-
-        // prepare drawlist for drawing
-        let cxview = &mut cx.views[view_id];
-
-        // TODO(JP): We don't seem to currently support moving a `View` to a different pass. Do we
-        // want to?
-        assert_eq!(cxview.pass_id, pass_id);
-
-        // update drawlist ids
-        self.redraw_id = cx.redraw_id;
-        cxview.redraw_id = cx.redraw_id;
-        cxview.draw_calls_len = 0;
-
-        cx.view_stack.push(view_id);
-
-        if is_root_for_pass {
-            cx.passes[pass_id].paint_dirty = true;
-        }
     }
 
     fn is_main_view(view_id: usize, cx: &mut Cx) -> bool {
@@ -208,6 +303,9 @@ impl View {
 
         let rect = cx.end_typed_box(CxBoxType::View);
         cx.views[view_id].rect = rect;
+        if let Some(layout_cache) = &mut self.layout_cache {
+            layout_cache.rect = rect;
+        }
         cx.view_stack.pop();
         view_area
     }
@@ -321,6 +419,8 @@ impl Cx {
                 },
                 //current_instance_offset: 0,
                 instance_dirty: true,
+                previous_instances: None,
+                batched_into: None,
                 uniforms_dirty: true,
                 platform: CxPlatformDrawCall::default(),
             });
@@ -328,22 +428,111 @@ impl Cx {
             return dc;
         }
         // reuse an older one, keeping all GPU resources attached
+        let is_retained = cxview.is_retained;
         let dc = &mut cxview.draw_calls[draw_call_id];
+        if is_retained && dc.sub_view_id == 0 && dc.shader_id == shader_id {
+            // Keep the old instances around, to compare against in `Cx::diff_retained_instances`.
+            let mut previous_instances = dc.previous_instances.take().unwrap_or_default();
+            std::mem::swap(&mut dc.instances, &mut previous_instances);
+            dc.previous_instances = Some(previous_instances);
+        } else {
+            dc.previous_instances = None;
+        }
         dc.shader_id = shader_id;
         dc.props = props;
         dc.sub_view_id = 0; // make sure its recognised as a draw call
                             // truncate buffers and set update frame
         dc.redraw_id = self.redraw_id;
+        dc.batched_into = None;
         dc.instances.truncate(0);
         dc.user_uniforms.truncate(0);
         dc.user_uniforms.resize(sh.mapping.user_uniform_props.total_slots, 0.0);
         dc.textures_2d.truncate(0);
         dc.textures_2d.resize(sh.mapping.textures.len(), 0);
-        dc.instance_dirty = true;
+        if dc.previous_instances.is_none() {
+            dc.instance_dirty = true;
+        }
         dc.uniforms_dirty = true;
         dc
     }
 
+    /// Merge consecutive [`DrawCall`]s in the [`View`]s that were just drawn, if they use the same [`Shader`],
+    /// user uniforms, and textures, so they get drawn using a single call to the GPU. Call this at the end of
+    /// drawing, after all alignment has happened.
+    ///
+    /// This catches what [`Cx::create_draw_call`] can't, since it only ever appends to the last [`DrawCall`]. For
+    /// example, a shader group (see [`Cx::begin_shader_group`]) in which some [`Shader`]s didn't get any
+    /// instances leaves empty [`DrawCall`]s in between the ones that did. Those empty [`DrawCall`]s get skipped
+    /// here, since they don't draw anything anyway. [`DrawCall`]s are never merged across sub-[`View`]s.
+    ///
+    /// The instances of a merged [`DrawCall`] get moved to the [`DrawCall`] it got merged into, and
+    /// [`DrawCall::batched_into`] gets set, so that [`Area`]s pointing to it keep working. Merged [`DrawCall`]s
+    /// share user uniforms and textures, so writing those through an [`Area`] undoes the merge first; see
+    /// [`CxView::unbatch_draw_call`].
+    pub(crate) fn batch_draw_calls(&mut self) {
+        let mut stats = DrawCallBatchingStats::default();
+        let redraw_id = self.redraw_id;
+        let disabled = self.debug_flags.disable_draw_call_batching;
+        for cxview in self.views.iter_mut().filter(|cxview| cxview.redraw_id == redraw_id) {
+            let mut target: Option<usize> = None;
+            for draw_call_id in 0..cxview.draw_calls_len {
+                let (before, after) = cxview.draw_calls.split_at_mut(draw_call_id);
+                let dc = &mut after[0];
+                if dc.sub_view_id != 0 {
+                    target = None;
+                    continue;
+                }
+                if dc.instances.is_empty() {
+                    continue;
+                }
+                stats.draw_calls += 1;
+                if let Some(target) = target {
+                    let target_dc = &mut before[target];
+                    if !disabled && target_dc.can_batch_with(dc) {
+                        dc.batched_into = Some((target_dc.draw_call_id, target_dc.instances.len()));
+                        target_dc.instances.append(&mut dc.instances);
+                        target_dc.instance_dirty |= dc.instance_dirty;
+                        dc.instance_dirty = false;
+                        stats.draw_calls -= 1;
+                        stats.merged_draw_calls += 1;
+                        continue;
+                    }
+                }
+                target = Some(draw_call_id);
+            }
+        }
+        self.draw_call_batching_stats = stats;
+    }
+
+    /// How many [`DrawCall`]s got merged by draw call batching during the last draw, and how many are left. Only
+    /// counts [`View`]s that were actually drawn (e.g. not ones skipped by [`View::begin_cached_view`]).
+    ///
+    /// Note that batching can be disabled using [`CxDebugFlags::disable_draw_call_batching`].
+    pub fn get_draw_call_batching_stats(&self) -> DrawCallBatchingStats {
+        self.draw_call_batching_stats
+    }
+
+    /// For [`DrawCall`]s in retained [`View`]s (see [`View::with_is_retained`]) that got drawn again, mark their
+    /// instances as dirty if they changed compared to the previous draw. Call this at the end of drawing, after
+    /// all alignment has happened.
+    pub(crate) fn diff_retained_instances(&mut self) {
+        let redraw_id = self.redraw_id;
+        for cxview in self.views.iter_mut().filter(|cxview| cxview.is_retained && cxview.redraw_id == redraw_id) {
+            for dc in &mut cxview.draw_calls[..cxview.draw_calls_len] {
+                if dc.redraw_id != redraw_id {
+                    continue;
+                }
+                if let Some(previous_instances) = &mut dc.previous_instances {
+                    if dc.instances != *previous_instances {
+                        dc.instance_dirty = true;
+                    }
+                    // Keep the allocation, so we don't have to allocate again on the next draw.
+                    previous_instances.truncate(0);
+                }
+            }
+        }
+    }
+
     /// Add a slice of instances to [`DrawCall::instances`]. See [`Cx::add_instances`].
     fn add_instances_internal<T: 'static + Copy>(&mut self, shader: &'static Shader, data: &[T], props: DrawCallProps) -> Area {
         if data.is_empty() {
@@ -563,6 +752,15 @@ impl DrawUniforms {
     }
 }
 
+/// See [`Cx::get_draw_call_batching_stats`].
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct DrawCallBatchingStats {
+    /// The number of non-empty [`DrawCall`]s that are left after batching.
+    pub draw_calls: usize,
+    /// The number of [`DrawCall`]s that got merged into an earlier [`DrawCall`].
+    pub merged_draw_calls: usize,
+}
+
 /// Some user-defined props to initialize a [`DrawCall`] with.
 #[derive(Default)]
 pub(crate) struct DrawCallProps {
@@ -619,8 +817,15 @@ pub struct DrawCall {
     /// Buffer of texture IDs.
     pub(crate) textures_2d: Vec<u32>,
     /// Whether or not the draw call has been accessed since the last paint.
-    /// Should currently always be the same as [`DrawCall::uniforms_dirty`] below.
+    /// Should currently always be the same as [`DrawCall::uniforms_dirty`] below, except in retained [`View`]s
+    /// (see [`View::with_is_retained`]), where it only gets set if the instances actually changed.
     pub(crate) instance_dirty: bool,
+    /// In retained [`View`]s (see [`View::with_is_retained`]), the instances of the previous draw, until
+    /// [`Cx::diff_retained_instances`] compares them to the new ones.
+    pub(crate) previous_instances: Option<Vec<f32>>,
+    /// If set, the instances of this [`DrawCall`] got moved to an earlier [`DrawCall`] in the same [`CxView`] (with
+    /// this [`DrawCall::draw_call_id`]), starting at this instance offset. See [`Cx::batch_draw_calls`].
+    pub(crate) batched_into: Option<(usize, usize)>,
     /// Whether or not the draw call has been accessed since the last paint.
     /// Should currently always be the same as [`DrawCall::instance_dirty`] above.
     pub(crate) uniforms_dirty: bool,
@@ -632,17 +837,29 @@ pub struct DrawCall {
 }
 
 impl DrawCall {
+    /// Whether the instances of `other` can be drawn as part of this [`DrawCall`]. See [`Cx::batch_draw_calls`].
+    fn can_batch_with(&self, other: &DrawCall) -> bool {
+        self.shader_id == other.shader_id
+            && self.props.is_batchable()
+            && other.props.is_batchable()
+            && self.user_uniforms == other.user_uniforms
+            && self.textures_2d == other.textures_2d
+    }
+
+    /// The total scroll to apply to instances, given the scroll of the parent views and the [`View`] itself.
+    pub(crate) fn get_draw_scroll(&self, scroll: Vec2, local_scroll: Vec2) -> Vec2 {
+        Vec2 {
+            x: if self.props.scroll_sticky_horizontal { scroll.x } else { scroll.x + local_scroll.x },
+            y: if self.props.scroll_sticky_vertical { scroll.y } else { scroll.y + local_scroll.y },
+        }
+    }
+
     /// Set the scroll uniforms in [`DrawCall::draw_uniforms`], as computed when
     /// walking the draw tree during painting.
     pub(crate) fn set_local_scroll(&mut self, scroll: Vec2, local_scroll: Vec2) {
-        self.draw_uniforms.draw_scroll_x = scroll.x;
-        if !self.props.scroll_sticky_horizontal {
-            self.draw_uniforms.draw_scroll_x += local_scroll.x;
-        }
-        self.draw_uniforms.draw_scroll_y = scroll.y;
-        if !self.props.scroll_sticky_vertical {
-            self.draw_uniforms.draw_scroll_y += local_scroll.y;
-        }
+        let draw_scroll = self.get_draw_scroll(scroll, local_scroll);
+        self.draw_uniforms.draw_scroll_x = draw_scroll.x;
+        self.draw_uniforms.draw_scroll_y = draw_scroll.y;
         self.draw_uniforms.draw_local_scroll_x = local_scroll.x;
         self.draw_uniforms.draw_local_scroll_y = local_scroll.y;
     }
@@ -737,6 +954,8 @@ pub struct CxView {
     /// TODO(JP): Is this actually useful? Is caching of resources like that worth it, or
     /// should we do it on a per-platform basis, and only where it's really necessary?
     pub(crate) draw_calls_len: usize,
+    /// See [`View::with_is_retained`].
+    pub(crate) is_retained: bool,
     /// The cumulative scroll offset from all of the parents. Gets set during painting.
     pub(crate) parent_scroll: Vec2,
     /// See [`ViewUniforms`].
@@ -753,6 +972,46 @@ pub struct CxView {
 }
 
 impl CxView {
+    /// Where the instances of an [`InstanceRangeArea`] currently live, taking into account [`DrawCall::batched_into`].
+    /// Returns the index in [`CxView::draw_calls`] and the instance offset in that [`DrawCall`].
+    pub(crate) fn resolve_instance_range(&self, draw_call_id: usize, instance_offset: usize) -> (usize, usize) {
+        match self.draw_calls[draw_call_id].batched_into {
+            Some((target_draw_call_id, target_offset)) => (target_draw_call_id, target_offset + instance_offset),
+            None => (draw_call_id, instance_offset),
+        }
+    }
+
+    /// Undo [`Cx::batch_draw_calls`] for the [`DrawCall`] with `draw_call_id`, and for all other [`DrawCall`]s
+    /// that got merged into the same one. This moves their instances back, so the [`DrawCall`] can get user
+    /// uniforms or textures of its own without affecting the others.
+    pub(crate) fn unbatch_draw_call(&mut self, draw_call_id: usize) {
+        let target_draw_call_id = match self.draw_calls[draw_call_id].batched_into {
+            Some((target_draw_call_id, _)) => target_draw_call_id,
+            None => draw_call_id,
+        };
+        // In order of their offsets, since `batch_draw_calls` appends in order of `draw_call_id`.
+        let merged: Vec<(usize, usize)> = self.draw_calls[..self.draw_calls_len]
+            .iter()
+            .enumerate()
+            .filter_map(|(id, dc)| match dc.batched_into {
+                Some((target, offset)) if target == target_draw_call_id => Some((id, offset)),
+                _ => None,
+            })
+            .collect();
+        if merged.is_empty() {
+            return;
+        }
+        // Take the instances from the end first, so the offsets of the other ones stay valid.
+        for &(id, offset) in merged.iter().rev() {
+            let instances = self.draw_calls[target_draw_call_id].instances.split_off(offset);
+            let dc = &mut self.draw_calls[id];
+            dc.instances = instances;
+            dc.batched_into = None;
+            dc.instance_dirty = true;
+        }
+        self.draw_calls[target_draw_call_id].instance_dirty = true;
+    }
+
     /// Returns the intersection of clip coordinates and [`CxView::rect`], taking
     /// into account [`CxView::parent_scroll`].
     ///
@@ -766,3 +1025,164 @@ impl CxView {
         (Vec2 { x: min_x.max(clip.0.x), y: min_y.max(clip.0.y) }, Vec2 { x: max_x.min(clip.1.x), y: max_y.min(clip.1.y) })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static SHADER: Shader = Shader {
+        build_geom: Some(QuadIns::build_geom),
+        code_to_concatenate: &[
+            Cx::STD_SHADER,
+            QuadIns::SHADER,
+            code_fragment!(
+                r#"
+                fn pixel() -> vec4 {
+                    return #f;
+                }"#
+            ),
+        ],
+        ..Shader::DEFAULT
+    };
+
+    static OTHER_SHADER: Shader = Shader {
+        build_geom: Some(QuadIns::build_geom),
+        code_to_concatenate: &[
+            Cx::STD_SHADER,
+            QuadIns::SHADER,
+            code_fragment!(
+                r#"
+                fn pixel() -> vec4 {
+                    return #0f0;
+                }"#
+            ),
+        ],
+        ..Shader::DEFAULT
+    };
+
+    static UNIFORM_SHADER: Shader = Shader {
+        build_geom: Some(QuadIns::build_geom),
+        code_to_concatenate: &[
+            Cx::STD_SHADER,
+            QuadIns::SHADER,
+            code_fragment!(
+                r#"
+                uniform alpha: float;
+                fn pixel() -> vec4 {
+                    return vec4(1., 1., 1., alpha);
+                }"#
+            ),
+        ],
+        ..Shader::DEFAULT
+    };
+
+    #[test]
+    fn test_batch_draw_calls() {
+        let mut test_cx = TestCx::new(vec2(400., 300.));
+        let quad = |x: f32| QuadIns::from_rect(Rect { pos: vec2(x, 10.), size: vec2(10., 10.) });
+        let mut areas = vec![];
+        let mut draw = |test_cx: &mut TestCx| {
+            areas.clear();
+            test_cx.draw(|cx| {
+                cx.begin_shader_group(&[&SHADER, &OTHER_SHADER]);
+                areas.push(cx.add_instances(&SHADER, &[quad(10.)]));
+                cx.end_shader_group();
+                // The shader group leaves an empty `DrawCall` for `OTHER_SHADER` in between.
+                areas.push(cx.add_instances(&SHADER, &[quad(20.), quad(30.)]));
+            });
+            (
+                test_cx.cx.get_draw_call_batching_stats(),
+                areas.iter().map(|area| area.get_rect_for_first_instance(&test_cx.cx).unwrap().pos.x).collect::<Vec<_>>(),
+                areas[1].get_slice::<QuadIns>(&test_cx.cx).iter().map(|quad| quad.rect_pos.x).collect::<Vec<_>>(),
+            )
+        };
+
+        assert_eq!(
+            draw(&mut test_cx),
+            (DrawCallBatchingStats { draw_calls: 1, merged_draw_calls: 1 }, vec![10., 20.], vec![20., 30.])
+        );
+
+        test_cx.cx.debug_flags_mut().disable_draw_call_batching = true;
+        assert_eq!(
+            draw(&mut test_cx),
+            (DrawCallBatchingStats { draw_calls: 2, merged_draw_calls: 0 }, vec![10., 20.], vec![20., 30.])
+        );
+    }
+
+    #[test]
+    fn test_unbatch_draw_calls_on_write_user_uniforms() {
+        let mut test_cx = TestCx::new(vec2(400., 300.));
+        let quad = |x: f32| QuadIns::from_rect(Rect { pos: vec2(x, 10.), size: vec2(10., 10.) });
+        let mut areas = vec![];
+        test_cx.draw(|cx| {
+            cx.begin_shader_group(&[&UNIFORM_SHADER, &OTHER_SHADER]);
+            areas.push(cx.add_instances(&UNIFORM_SHADER, &[quad(10.)]));
+            cx.end_shader_group();
+            areas.push(cx.add_instances(&UNIFORM_SHADER, &[quad(20.), quad(30.)]));
+        });
+        assert_eq!(test_cx.cx.get_draw_call_batching_stats().merged_draw_calls, 1);
+
+        areas[1].write_user_uniforms(&mut test_cx.cx, 0.5f32);
+        let user_uniforms = |area: &Area| match area {
+            Area::InstanceRange(inst) => test_cx.cx.views[inst.view_id].draw_calls[inst.draw_call_id].user_uniforms.clone(),
+            _ => panic!(),
+        };
+        assert_eq!(user_uniforms(&areas[0]), vec![0.]);
+        assert_eq!(user_uniforms(&areas[1]), vec![0.5]);
+        assert_eq!(areas[0].get_slice::<QuadIns>(&test_cx.cx).iter().map(|quad| quad.rect_pos.x).collect::<Vec<_>>(), vec![10.]);
+        assert_eq!(
+            areas[1].get_slice::<QuadIns>(&test_cx.cx).iter().map(|quad| quad.rect_pos.x).collect::<Vec<_>>(),
+            vec![20., 30.]
+        );
+    }
+
+    #[test]
+    fn test_retained_view() {
+        let mut test_cx = TestCx::new(vec2(400., 300.));
+        let mut view = View::default().with_is_retained(true);
+        // Returns whether the instances need to be uploaded, and then pretends that we uploaded them.
+        let mut draw = |test_cx: &mut TestCx, width: f32| -> bool {
+            test_cx.draw(|cx| {
+                view.begin_view(cx, LayoutSize::FILL);
+                cx.add_instances(&SHADER, &[QuadIns::from_rect(Rect { pos: vec2(10., 10.), size: vec2(width, 20.) })]);
+                view.end_view(cx);
+            });
+            let draw_call = &mut test_cx.cx.views[view.view_id.unwrap()].draw_calls[0];
+            std::mem::replace(&mut draw_call.instance_dirty, false)
+        };
+
+        assert!(draw(&mut test_cx, 50.));
+        assert!(!draw(&mut test_cx, 50.));
+        assert!(draw(&mut test_cx, 60.));
+        assert!(!draw(&mut test_cx, 60.));
+    }
+
+    #[test]
+    fn test_cached_view() {
+        let mut test_cx = TestCx::new(vec2(400., 300.));
+        let mut view = View::default();
+        let mut draw = |test_cx: &mut TestCx, content_hash: u64| -> (bool, Rect) {
+            let mut result = (false, Rect::default());
+            test_cx.draw(|cx| {
+                cx.add_box(LayoutSize::new(Width::Fill, Height::Fix(10.)));
+                let drawn = view.begin_cached_view(cx, LayoutSize::new(Width::Compute, Height::Compute), content_hash);
+                if drawn {
+                    cx.add_box(LayoutSize::new(Width::Fix(50.), Height::Fix(20.)));
+                    view.end_view(cx);
+                }
+                result = (drawn, view.get_rect(cx));
+            });
+            result
+        };
+
+        let (drawn, rect) = draw(&mut test_cx, 1);
+        assert!(drawn);
+        assert_eq!(rect.size, vec2(50., 20.));
+        assert_eq!(draw(&mut test_cx, 1), (false, rect));
+        assert_eq!(draw(&mut test_cx, 2), (true, rect));
+        assert_eq!(draw(&mut test_cx, 2), (false, rect));
+
+        test_cx.draw(|cx| cx.invalidate_view_caches());
+        assert_eq!(draw(&mut test_cx, 2), (true, rect));
+    }
+}
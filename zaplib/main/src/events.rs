@@ -1,7 +1,9 @@
 //! Events coming from user actions, system calls, and so on.
 
 use crate::*;
+use std::any::Any;
 use std::collections::{BTreeSet, HashMap};
+use std::sync::Arc;
 
 /// Modifiers that were held when a key event was fired.
 #[derive(Clone, Debug, PartialEq, Default)]
@@ -156,6 +158,40 @@ pub struct PointerHoverEvent {
     pub time: f64,
 }
 
+/// The kind of device that a [`PointerScrollEvent`] came from.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ScrollDevice {
+    /// A mouse wheel that scrolls in steps.
+    Wheel,
+    /// A trackpad or a high-resolution mouse wheel, which scroll smoothly.
+    Trackpad,
+}
+
+impl Default for ScrollDevice {
+    fn default() -> Self {
+        Self::Wheel
+    }
+}
+
+/// Where a [`PointerScrollEvent`] is in a scroll gesture.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ScrollPhase {
+    /// Regular scrolling, while the wheel turns or the fingers move on the trackpad.
+    Changed,
+    /// The fingers were lifted off the trackpad. Has no [`PointerScrollEvent::scroll`], and can be followed by
+    /// [`ScrollPhase::Momentum`] events.
+    Ended,
+    /// Scrolling that continues after the fingers were lifted off the trackpad, and slows down until it stops. This
+    /// comes from the platform where it has this (Mac OS X, iOS), and is synthesized by us elsewhere.
+    Momentum,
+}
+
+impl Default for ScrollPhase {
+    fn default() -> Self {
+        Self::Changed
+    }
+}
+
 /// See [`Event::PointerScroll`].
 #[derive(Clone, Default, Debug, PartialEq)]
 pub struct PointerScrollEvent {
@@ -166,7 +202,16 @@ pub struct PointerScrollEvent {
     pub rel: Vec2,
     /// TODO(JP): This is only set by [`Event::hits_pointer`]; use an `Option` or a different `struct`?
     pub rect: Rect,
+    /// How far to scroll, in logical pixels, with positive values scrolling down and to the right. Mouse wheel steps
+    /// scroll the number of lines that the OS is configured for (3 by default) times [`SCROLL_LINE_HEIGHT`], and
+    /// pages scroll [`SCROLL_PAGE_HEIGHT`], so this is the same on every platform.
     pub scroll: Vec2,
+    /// How much a trackpad pinch zooms, relative to the current zoom level; e.g. `0.1` when zooming in by 10%, and
+    /// negative when zooming out. Pinches don't scroll, so this is 0 whenever [`PointerScrollEvent::scroll`] isn't.
+    pub pinch: f32,
+    pub device: ScrollDevice,
+    pub phase: ScrollPhase,
+    /// Some platforms report trackpads as [`PointerInputType::Touch`]; [`PointerScrollEvent::device`] is more precise.
     pub input_type: PointerInputType,
     //pub is_wheel: bool,
     pub handled_x: bool,
@@ -175,6 +220,17 @@ pub struct PointerScrollEvent {
     pub time: f64,
 }
 
+/// See [`Event::PointerRawMove`].
+#[derive(Clone, Default, Debug, PartialEq)]
+pub struct PointerRawMoveEvent {
+    pub window_id: usize,
+    /// How far the mouse moved, in roughly logical pixels. Where the platform supports it, this is the unaccelerated
+    /// motion of the device, so it doesn't map exactly to the distance that the cursor would have moved.
+    pub delta: Vec2,
+    pub modifiers: KeyModifiers,
+    pub time: f64,
+}
+
 /// See [`Event::WindowGeomChange`].
 #[derive(Clone, Default, Debug, PartialEq)]
 pub struct WindowGeomChangeEvent {
@@ -183,6 +239,36 @@ pub struct WindowGeomChangeEvent {
     pub new_geom: WindowGeom,
 }
 
+/// See [`Event::WindowDpiChange`].
+#[derive(Clone, Default, Debug, PartialEq)]
+pub struct WindowDpiChangeEvent {
+    pub window_id: usize,
+    pub old_dpi_factor: f32,
+    pub new_dpi_factor: f32,
+}
+
+/// See [`Event::DisplaysChanged`].
+#[derive(Clone, Default, Debug, PartialEq)]
+pub struct DisplaysChangedEvent {
+    /// The new list of displays, which is also what [`Cx::displays`] returns from now on.
+    pub displays: Vec<DisplayInfo>,
+}
+
+/// See [`Event::LocaleChanged`].
+#[derive(Clone, Default, Debug, PartialEq)]
+pub struct LocaleChangedEvent {
+    pub old_locale: String,
+    /// The new locale, which is also what [`Cx::locale`] returns from now on.
+    pub new_locale: String,
+}
+
+/// See [`Event::PointerLockChanged`].
+#[derive(Clone, Default, Debug, PartialEq)]
+pub struct PointerLockChangedEvent {
+    /// Whether the pointer is locked now, which is also what [`Cx::is_pointer_locked`] returns from now on.
+    pub locked: bool,
+}
+
 /// See [`Event::Timer`].
 #[derive(Clone, Debug, PartialEq)]
 pub struct TimerEvent {
@@ -197,6 +283,53 @@ pub struct SignalEvent {
     pub signals: HashMap<Signal, BTreeSet<StatusId>>,
 }
 
+/// See [`Event::UserMessage`].
+///
+/// The message is kept in an [`Arc`], so sending large buffers (e.g. a `Vec<u8>` or an
+/// [`Arc<Vec<f32>>`]) from a worker thread doesn't copy them.
+#[derive(Clone)]
+pub struct UserMessageEvent {
+    message: Arc<dyn Any + Send + Sync>,
+}
+
+impl UserMessageEvent {
+    pub fn new<T: Any + Send + Sync>(message: T) -> Self {
+        Self { message: Arc::new(message) }
+    }
+
+    /// Get the message if it is of type `T`. Use this to check which kind of message you got:
+    ///
+    /// ```
+    /// if let Event::UserMessage(ume) = event {
+    ///     if let Some(ParsedData { rows }) = ume.get::<ParsedData>() {
+    ///         // ..
+    ///     }
+    /// }
+    /// ```
+    pub fn get<T: Any>(&self) -> Option<&T> {
+        self.message.downcast_ref()
+    }
+
+    /// Same as [`UserMessageEvent::get`], but returns a clone of the [`Arc`] that holds the message, so
+    /// you can keep it around without copying it.
+    pub fn get_arc<T: Any + Send + Sync>(&self) -> Option<Arc<T>> {
+        Arc::clone(&self.message).downcast().ok()
+    }
+}
+
+impl std::fmt::Debug for UserMessageEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("UserMessageEvent").finish_non_exhaustive()
+    }
+}
+
+/// See [`Event::WorkerPanic`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct WorkerPanicEvent {
+    /// The panic message. In WebAssembly this includes the location of the panic.
+    pub message: String,
+}
+
 /// Data for various kinds of key-based events ([`Event::KeyDown`], [`Event::KeyUp`], etc).
 #[derive(Clone, Debug, PartialEq)]
 pub struct KeyEvent {
@@ -290,13 +423,17 @@ impl PartialEq for UserFile {
     }
 }
 
-/// Fires when a web worker calls `callRustAsync` to trigger a function in Rust.
+/// Fires when a web worker calls `callRustAsync` or `callRustStream` to trigger a function in Rust.
 #[derive(Clone, Debug)]
 pub struct WebRustCallEvent {
     /// Description of the event.
     pub name: String,
     pub params: Vec<ZapParam>,
     pub callback_id: u32,
+    /// Set for `callRustStream`, which is handled by [`Cx::on_call_rust_stream`] instead.
+    pub streaming: bool,
+    /// Set when JS cancels the call; see [`CallRustCancelToken`].
+    pub cancel_token: CallRustCancelToken,
 }
 
 /// See [`Event::AppOpenFiles`].
@@ -308,7 +445,8 @@ pub struct AppOpenFilesEvent {
 /// See [`Event::System`].
 #[derive(Debug, Clone)]
 pub enum SystemEvent {
-    /// See [`WebRustCallEvent`]. This event must have a handler registered through [`Cx::on_call_rust_async`].
+    /// See [`WebRustCallEvent`]. This event must have a handler registered through [`Cx::on_call_rust_async`]
+    /// (or [`Cx::on_call_rust_stream`] for streaming calls).
     WebRustCall(Option<WebRustCallEvent>),
     Draw,
     /// We're going to repaint our draw tree.
@@ -335,6 +473,9 @@ pub enum Event {
     AppFocusLost,
     /// New frame requested. Useful for animations; you can request this using [`Cx::request_next_frame`].
     NextFrame,
+    /// A new frame of the VR headset, while presenting to one; see [`Window::xr_start_presenting`]. Fires right
+    /// before drawing, so this is the place to move things that follow the head or the controllers.
+    XrUpdate(XrUpdateEvent),
     /// The operating system inquired if a [`Window`] can be dragged.
     WindowDragQuery(WindowDragQueryEvent),
     /// The user requested to close the [`Window`].
@@ -343,6 +484,19 @@ pub enum Event {
     WindowClosed(WindowClosedEvent),
     /// Geometry of a [`Window`] changed (position, size, etc).
     WindowGeomChange(WindowGeomChangeEvent),
+    /// The `dpi_factor` of a [`Window`] changed, e.g. because it moved to a monitor with a different scale, or because
+    /// the scale was changed in the system settings. Fires right after the [`Event::WindowGeomChange`] that contains
+    /// the new `dpi_factor`, and the window gets redrawn automatically. Useful for reloading resolution-dependent
+    /// resources, like images.
+    WindowDpiChange(WindowDpiChangeEvent),
+    /// A display was connected or disconnected, or the resolution, scale, or arrangement of displays changed.
+    DisplaysChanged(DisplaysChangedEvent),
+    /// The locale was changed using [`Cx::set_locale`]. Everything gets redrawn automatically, but you might want to
+    /// update text that you store yourself, like window titles.
+    LocaleChanged(LocaleChangedEvent),
+    /// A selection was shared using [`Cx::publish_selection`]. Fires after the event handler in which it was
+    /// published; see [`Cx::linked_selection_changed`].
+    SelectionChanged(SelectionChangedEvent),
     /// The user started or ended resizing the [`Window`].
     ///
     /// TODO(JP): Mostly for internal use in Windows; we might not want to expose this
@@ -359,12 +513,25 @@ pub enum Event {
     PointerHover(PointerHoverEvent),
     /// A pointer (mouse, touch, etc) was released.
     PointerUp(PointerUpEvent),
-    /// A pointer (mouse, touch, etc) triggered a scroll.
+    /// A mouse wheel or trackpad scrolled, or a trackpad was pinched. See [`PointerScrollEvent`] for how this is made
+    /// consistent across platforms.
     PointerScroll(PointerScrollEvent),
+    /// The mouse was moved, also when it is at the edge of the screen or when the pointer is locked using
+    /// [`Cx::set_pointer_lock`]. Use this instead of [`Event::PointerMove`] for things like rotating a camera, where
+    /// the absolute position of the pointer doesn't matter. Sent in addition to the regular pointer events.
+    PointerRawMove(PointerRawMoveEvent),
+    /// The platform locked or released the mouse pointer by itself, e.g. because the window lost focus, or because
+    /// locking failed. Not sent when the lock changes as requested using [`Cx::set_pointer_lock`].
+    PointerLockChanged(PointerLockChangedEvent),
     /// A [`Timer`] was requested using [`Cx::start_timer`].
     Timer(TimerEvent),
     /// A signal was fired using [`Cx::send_signal`].
     Signal(SignalEvent),
+    /// A message was sent using [`Cx::post_user_message`], typically from another thread.
+    UserMessage(UserMessageEvent),
+    /// A thread spawned using [`universal_thread::spawn`] panicked. The thread (or in WebAssembly: the web
+    /// worker) is gone at this point, so if you were waiting for it you should stop doing so.
+    WorkerPanic(WorkerPanicEvent),
     Command(CommandId),
     /// Keyboard focus changed between components.
     KeyFocus(KeyFocusEvent),
@@ -404,6 +571,26 @@ impl Default for Event {
 }
 
 impl Event {
+    /// The [`Window::window_id`] of the window that a pointer or window event happened in. Returns [`None`] for
+    /// other events.
+    pub fn window_id(&self) -> Option<usize> {
+        match self {
+            Event::PointerDown(pe) => Some(pe.window_id),
+            Event::PointerMove(pe) => Some(pe.window_id),
+            Event::PointerHover(pe) => Some(pe.window_id),
+            Event::PointerUp(pe) => Some(pe.window_id),
+            Event::PointerScroll(pe) => Some(pe.window_id),
+            Event::PointerRawMove(pe) => Some(pe.window_id),
+            Event::WindowDragQuery(wd) => Some(wd.window_id),
+            Event::WindowCloseRequested(wc) => Some(wc.window_id),
+            Event::WindowClosed(wc) => Some(wc.window_id),
+            Event::WindowGeomChange(wg) => Some(wg.window_id),
+            Event::WindowDpiChange(wd) => Some(wd.window_id),
+            Event::WindowResizeLoop(wr) => Some(wr.window_id),
+            _ => None,
+        }
+    }
+
     /// Checks if an [`Event`] is a pointer-event with coordinates falling inside
     /// [`Rect`], or already has an associated [`ComponentId`] that matches the given one.
     ///
@@ -414,6 +601,9 @@ impl Event {
     /// the other way around: if the pointer is associated with the given [`ComponentId`], it
     /// will be returned regardless of [`Rect`].
     ///
+    /// [`Event::PointerRawMove`] is returned regardless of [`Rect`] while a pointer is captured by the given
+    /// [`ComponentId`], i.e. between its [`Event::PointerDown`] and [`Event::PointerUp`].
+    ///
     /// We pass in [`Option<Rect>`] instead of [`Rect`] for convenience, since it often comes
     /// from [`Area::get_rect_for_first_instance`], which returns [`Option<Rect>`]. When passing
     /// in [`None`], we always return [`Event::None`].
@@ -494,6 +684,10 @@ impl Event {
                         });
                     }
                 }
+                // Mouse buttons each have their own digit, so check all of them.
+                Event::PointerRawMove(_) if cx.pointers.iter().any(|pointer| pointer.captured == Some(component_id)) => {
+                    return self.clone();
+                }
                 Event::PointerDown(pe) => {
                     if !pe.handled && rect.contains(pe.abs) {
                         // Scan if any of the pointers already captured this area.
@@ -582,19 +776,22 @@ pub struct Signal {
 /// but can change the underlying implementation whenever.
 pub type StatusId = LocationHash;
 
-/// Created using [`Cx::start_timer`].
+/// Created using [`Cx::start_timer`], [`Cx::start_timeout`], or [`Cx::start_interval`]. Acts as a handle
+/// for cancelling the timer using [`Cx::stop_timer`].
 #[derive(Clone, Debug, Default)]
 pub struct Timer {
     pub timer_id: u64,
 }
 
 impl Timer {
+    /// A [`Timer`] that was never started (or has been stopped), and which will never fire.
     pub fn empty() -> Timer {
         Timer { timer_id: 0 }
     }
 
-    pub fn is_timer(&mut self, te: &TimerEvent) -> bool {
-        te.timer_id == self.timer_id
+    /// Whether `te` was fired by this [`Timer`].
+    pub fn is_timer(&self, te: &TimerEvent) -> bool {
+        self.timer_id != 0 && te.timer_id == self.timer_id
     }
 }
 
@@ -0,0 +1,386 @@
+//! Running [`Future`]s on the main thread, driven by the event loop.
+//!
+//! Use [`Cx::spawn`] to run a [`Future`], and [`Task::take_output`] to get its output back in your
+//! `handle` function. Futures are polled right after events are handled (at the same time that
+//! [`Signal`]s are processed), and can be woken up from any thread.
+//!
+//! Futures can also wait for JS functions to return, using `Cx::call_js_async`.
+
+use crate::*;
+use std::cell::{Cell, RefCell};
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Wake, Waker};
+
+thread_local! {
+    /// Whether the current thread is the one that runs the event loop (and thus the [`CxExecutor`]).
+    static IS_EXECUTOR_THREAD: Cell<bool> = Cell::new(false);
+}
+
+/// A [`Future`] that was spawned using [`Cx::spawn`], along with the [`Signal`] to send when it's done.
+struct CxTask {
+    future: Pin<Box<dyn Future<Output = ()>>>,
+    done_signal: Signal,
+}
+
+/// State for [`Cx::spawn`] and friends. Lives in [`Cx::executor`].
+#[derive(Default)]
+pub(crate) struct CxExecutor {
+    tasks: HashMap<u64, CxTask>,
+    last_task_id: u64,
+    /// Tasks that have been woken up and need to be polled. Can be written to from any thread.
+    woken: Arc<Mutex<Vec<u64>>>,
+    /// Posted when a task is woken up from another thread, so that the event loop wakes up.
+    wake_signal: Signal,
+    /// [`SignalFuture`]s that are waiting for a particular [`Signal`].
+    signal_waiters: HashMap<Signal, Vec<Rc<RefCell<SignalFutureState>>>>,
+    /// [`CallJsFuture`]s that are waiting for a JS function to return, by id.
+    #[cfg(any(target_arch = "wasm32", feature = "cef"))]
+    call_js_waiters: HashMap<u64, Rc<RefCell<CallJsFutureState>>>,
+    #[cfg(any(target_arch = "wasm32", feature = "cef"))]
+    last_call_js_id: u64,
+}
+
+struct TaskWaker {
+    task_id: u64,
+    woken: Arc<Mutex<Vec<u64>>>,
+    wake_signal: Signal,
+}
+
+impl Wake for TaskWaker {
+    fn wake(self: Arc<Self>) {
+        self.woken.lock().unwrap().push(self.task_id);
+        // When woken up from the main thread we'll get polled anyway at the end of the current event.
+        if !IS_EXECUTOR_THREAD.with(|is_executor_thread| is_executor_thread.get()) {
+            Cx::post_signal(self.wake_signal, Cx::STATUS_TASK_WOKEN);
+        }
+    }
+}
+
+/// Handle to a [`Future`] that was spawned using [`Cx::spawn`].
+///
+/// Dropping this doesn't cancel the [`Future`]; it will just run to completion without anyone looking
+/// at the output.
+pub struct Task<T> {
+    done_signal: Signal,
+    output: Rc<RefCell<Option<T>>>,
+}
+
+impl<T> Task<T> {
+    /// Get the output of the [`Future`], if `event` is the [`SignalEvent`] that tells that it completed.
+    /// Returns [`None`] for any other event, and after the output has been taken.
+    pub fn take_output(&self, event: &Event) -> Option<T> {
+        match event {
+            Event::Signal(se) if se.signals.contains_key(&self.done_signal) => self.output.borrow_mut().take(),
+            _ => None,
+        }
+    }
+
+    /// Whether the [`Future`] has completed and its output has not been taken yet.
+    pub fn has_output(&self) -> bool {
+        self.output.borrow().is_some()
+    }
+}
+
+#[derive(Default)]
+struct SignalFutureState {
+    status: Option<StatusId>,
+    waker: Option<Waker>,
+}
+
+/// Resolves when a [`Signal`] is fired, with its [`StatusId`]. Created using [`Cx::wait_for_signal`].
+pub struct SignalFuture {
+    state: Rc<RefCell<SignalFutureState>>,
+}
+
+impl Future for SignalFuture {
+    type Output = StatusId;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<StatusId> {
+        let mut state = self.state.borrow_mut();
+        if let Some(status) = state.status {
+            Poll::Ready(status)
+        } else {
+            state.waker = Some(cx.waker().clone());
+            Poll::Pending
+        }
+    }
+}
+
+/// Why a `Cx::call_js_async` call failed.
+#[derive(Clone, Debug, PartialEq)]
+pub enum CallJsError {
+    /// The JS function threw an error, returned a rejected `Promise`, or was not registered.
+    Thrown(String),
+    /// The JS function did not return within the timeout.
+    TimedOut,
+}
+
+impl fmt::Display for CallJsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CallJsError::Thrown(message) => write!(f, "JS function threw an error: {}", message),
+            CallJsError::TimedOut => write!(f, "JS function timed out"),
+        }
+    }
+}
+
+impl std::error::Error for CallJsError {}
+
+#[derive(Default)]
+struct CallJsFutureState {
+    result: Option<Result<Vec<ZapParam>, CallJsError>>,
+    waker: Option<Waker>,
+}
+
+/// Resolves with the return value of a JS function. Created using `Cx::call_js_async`.
+pub struct CallJsFuture {
+    state: Rc<RefCell<CallJsFutureState>>,
+}
+
+impl Future for CallJsFuture {
+    type Output = Result<Vec<ZapParam>, CallJsError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut state = self.state.borrow_mut();
+        if let Some(result) = state.result.take() {
+            Poll::Ready(result)
+        } else {
+            state.waker = Some(cx.waker().clone());
+            Poll::Pending
+        }
+    }
+}
+
+struct ThreadFutureState<T> {
+    output: Option<T>,
+    waker: Option<Waker>,
+}
+
+/// Resolves with the return value of a function running on another thread. Created using [`spawn_blocking`].
+pub struct ThreadFuture<T> {
+    state: Arc<Mutex<ThreadFutureState<T>>>,
+}
+
+impl<T> Future for ThreadFuture<T> {
+    type Output = T;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<T> {
+        let mut state = self.state.lock().unwrap();
+        if let Some(output) = state.output.take() {
+            Poll::Ready(output)
+        } else {
+            state.waker = Some(cx.waker().clone());
+            Poll::Pending
+        }
+    }
+}
+
+/// Run `f` on a new thread (using [`universal_thread::spawn`], so this also works in WebAssembly), and get
+/// a [`Future`] that resolves with its return value. Useful for blocking work like reading a
+/// [`UserFile`] or making a request using [`universal_http_stream::request`].
+pub fn spawn_blocking<T, F>(f: F) -> ThreadFuture<T>
+where
+    T: Send + 'static,
+    F: FnOnce() -> T + Send + 'static,
+{
+    let state = Arc::new(Mutex::new(ThreadFutureState { output: None, waker: None }));
+    let thread_state = Arc::clone(&state);
+    universal_thread::spawn(move || {
+        let output = f();
+        let mut state = thread_state.lock().unwrap();
+        state.output = Some(output);
+        if let Some(waker) = state.waker.take() {
+            waker.wake();
+        }
+    });
+    ThreadFuture { state }
+}
+
+impl Cx {
+    pub const STATUS_TASK_DONE: StatusId = location_hash!();
+    pub const STATUS_TASK_WOKEN: StatusId = location_hash!();
+
+    /// Run a [`Future`] on the main thread. It first gets polled after handling the current event, and
+    /// subsequently whenever it is woken up. When it completes, you get a [`SignalEvent`], for which
+    /// [`Task::take_output`] returns the output.
+    ///
+    /// ```
+    /// // In `handle`:
+    /// match event {
+    ///     Event::PointerUp(_) => self.task = Some(cx.spawn(spawn_blocking(|| compute_something()))),
+    ///     _ => {}
+    /// }
+    /// if let Some(output) = self.task.as_ref().and_then(|task| task.take_output(event)) {
+    ///     self.output = output;
+    ///     cx.request_draw();
+    /// }
+    /// ```
+    pub fn spawn<T: 'static>(&mut self, future: impl Future<Output = T> + 'static) -> Task<T> {
+        IS_EXECUTOR_THREAD.with(|is_executor_thread| is_executor_thread.set(true));
+        if self.executor.wake_signal.signal_id == 0 {
+            self.executor.wake_signal = self.new_signal();
+        }
+
+        let done_signal = self.new_signal();
+        let output = Rc::new(RefCell::new(None));
+        let task_output = Rc::clone(&output);
+        let future = async move {
+            let value = future.await;
+            *task_output.borrow_mut() = Some(value);
+        };
+
+        self.executor.last_task_id += 1;
+        let task_id = self.executor.last_task_id;
+        self.executor.tasks.insert(task_id, CxTask { future: Box::pin(future), done_signal });
+        self.executor.woken.lock().unwrap().push(task_id);
+        // Make sure that we get to [`Cx::call_signals`] soon, which is where we poll.
+        self.send_signal(self.executor.wake_signal, Cx::STATUS_TASK_WOKEN);
+
+        Task { done_signal, output }
+    }
+
+    /// Get a [`Future`] that resolves the next time that `signal` fires, with its [`StatusId`].
+    pub fn wait_for_signal(&mut self, signal: Signal) -> SignalFuture {
+        let state = Rc::new(RefCell::new(SignalFutureState::default()));
+        self.executor.signal_waiters.entry(signal).or_default().push(Rc::clone(&state));
+        SignalFuture { state }
+    }
+
+    /// Same as [`CxDesktopVsWasmCommon::http_send`], but returns a [`Future`] that resolves to whether
+    /// the request succeeded, instead of sending a [`Signal`].
+    #[allow(clippy::too_many_arguments)]
+    pub fn http_send_async(
+        &mut self,
+        verb: &str,
+        path: &str,
+        proto: &str,
+        domain: &str,
+        port: u16,
+        content_type: &str,
+        body: &[u8],
+    ) -> impl Future<Output = bool> {
+        let signal = self.new_signal();
+        let signal_future = self.wait_for_signal(signal);
+        self.http_send(verb, path, proto, domain, port, content_type, body, signal);
+        async move { signal_future.await == Cx::STATUS_HTTP_SEND_OK }
+    }
+
+    /// Like [`CxDesktopVsWasmCommon::call_js`], but returns a [`Future`] that resolves with the return value
+    /// of the JS function (which may also return a `Promise`). Resolves with a [`CallJsError`] if the function
+    /// throws, or doesn't return within `timeout` seconds. Useful for browser-only APIs, e.g.:
+    ///
+    /// ```ignore
+    /// let location = cx.call_js_async("getLocation", vec![], Some(10.));
+    /// self.task = Some(cx.spawn(async move { location.await.map(|params| params[0].as_str().to_string()) }));
+    /// ```
+    ///
+    /// The JS function is registered using `registerCallJsCallbacks`, and returns an array of parameters (like
+    /// `callRustAsync` in the other direction).
+    #[cfg(any(target_arch = "wasm32", feature = "cef"))]
+    pub fn call_js_async(&mut self, name: &str, params: Vec<ZapParam>, timeout: Option<f64>) -> CallJsFuture {
+        self.executor.last_call_js_id += 1;
+        let call_js_id = self.executor.last_call_js_id;
+        let state = Rc::new(RefCell::new(CallJsFutureState::default()));
+        self.executor.call_js_waiters.insert(call_js_id, Rc::clone(&state));
+
+        let timeout_ms = timeout.map_or(0., |timeout| timeout * 1000.);
+        let mut js_params =
+            vec![call_js_id.to_string().into_param(), name.to_string().into_param(), timeout_ms.to_string().into_param()];
+        js_params.extend(params);
+        self.call_js("_zaplibCallJsAsync", js_params);
+        CallJsFuture { state }
+    }
+
+    /// Resolve the [`CallJsFuture`] for a `_zaplibCallJsReturn` call from JS. Keep in sync with `callJsAsyncImpl`
+    /// in `common.ts`.
+    #[cfg(any(target_arch = "wasm32", feature = "cef"))]
+    pub(crate) fn resolve_call_js(&mut self, mut params: Vec<ZapParam>) {
+        let header: Vec<String> = params.drain(..3).map(ZapParam::into_string).collect();
+        let call_js_id: u64 = header[0].parse().unwrap();
+        let result = match header[1].as_str() {
+            "ok" => Ok(params),
+            "timeout" => Err(CallJsError::TimedOut),
+            _ => Err(CallJsError::Thrown(header[2].clone())),
+        };
+
+        if let Some(waiter) = self.executor.call_js_waiters.remove(&call_js_id) {
+            let mut state = waiter.borrow_mut();
+            state.result = Some(result);
+            if let Some(waker) = state.waker.take() {
+                waker.wake();
+            }
+        }
+    }
+
+    /// Resolve any [`SignalFuture`]s waiting for these `signals`.
+    pub(crate) fn resolve_signal_waiters(&mut self, signals: &HashMap<Signal, BTreeSet<StatusId>>) {
+        if self.executor.signal_waiters.is_empty() {
+            return;
+        }
+        for (signal, statusses) in signals {
+            if let Some(waiters) = self.executor.signal_waiters.remove(signal) {
+                for waiter in waiters {
+                    let mut state = waiter.borrow_mut();
+                    state.status = statusses.iter().next().copied();
+                    if let Some(waker) = state.waker.take() {
+                        waker.wake();
+                    }
+                }
+            }
+        }
+    }
+
+    /// Poll all tasks that have been woken up, and send [`Cx::STATUS_TASK_DONE`] for ones that complete.
+    pub(crate) fn run_woken_tasks(&mut self) {
+        loop {
+            let woken = std::mem::take(&mut *self.executor.woken.lock().unwrap());
+            if woken.is_empty() {
+                break;
+            }
+            for task_id in woken {
+                // Might have already completed, if it was woken up multiple times.
+                if let Some(mut task) = self.executor.tasks.remove(&task_id) {
+                    let waker = Waker::from(Arc::new(TaskWaker {
+                        task_id,
+                        woken: Arc::clone(&self.executor.woken),
+                        wake_signal: self.executor.wake_signal,
+                    }));
+                    match task.future.as_mut().poll(&mut Context::from_waker(&waker)) {
+                        Poll::Ready(()) => self.send_signal(task.done_signal, Cx::STATUS_TASK_DONE),
+                        Poll::Pending => {
+                            self.executor.tasks.insert(task_id, task);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    #[test]
+    fn test_spawn_and_wait_for_signal() {
+        let mut test_cx = TestCx::new(vec2(100., 100.));
+        let signal = test_cx.cx.new_signal();
+        let signal_future = test_cx.cx.wait_for_signal(signal);
+        let task = test_cx.cx.spawn(async move { signal_future.await == Cx::STATUS_HTTP_SEND_OK });
+
+        // Not done yet, since we're waiting for the signal.
+        let outputs = test_cx.dispatch(Event::None, |_cx, event| task.take_output(event));
+        assert_eq!(outputs, vec![None, None]);
+
+        let mut signals = std::collections::HashMap::new();
+        signals.insert(signal, [Cx::STATUS_HTTP_SEND_OK].into_iter().collect());
+        let outputs = test_cx.dispatch(Event::Signal(SignalEvent { signals }), |_cx, event| task.take_output(event));
+        assert!(outputs.contains(&Some(true)));
+        assert!(!task.has_output());
+    }
+}
@@ -0,0 +1,118 @@
+//! Running `ffmpeg` as a separate process to decode video into raw RGBA frames, for [`crate::video`] and
+//! [`crate::camera`] on native platforms.
+
+use std::io::{ErrorKind, Read};
+use std::process::{Child, Command, Stdio};
+use std::sync::mpsc;
+use std::thread::JoinHandle;
+
+/// Number of decoded frames to buffer. When the queue is full, decoding blocks.
+const FRAME_QUEUE_LEN: usize = 4;
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum FfmpegError {
+    /// `ffmpeg` (or `ffprobe`) is not installed, or not on the `PATH`.
+    NotInstalled,
+    /// Anything else, with the error message, typically from `ffmpeg` itself.
+    Failed(String),
+}
+
+impl From<std::io::Error> for FfmpegError {
+    fn from(err: std::io::Error) -> Self {
+        if err.kind() == ErrorKind::NotFound {
+            FfmpegError::NotInstalled
+        } else {
+            FfmpegError::Failed(err.to_string())
+        }
+    }
+}
+
+/// Result of [`Decoder::try_next`].
+pub(crate) enum DecoderOutput {
+    Frame(Vec<u32>),
+    /// No frame is decoded yet.
+    Pending,
+    /// `ffmpeg` exited, and all frames have been returned.
+    Ended,
+}
+
+/// An `ffmpeg` process, and a thread that reads frames from it.
+pub(crate) struct Decoder {
+    child: Child,
+    receiver: Option<mpsc::Receiver<Result<Vec<u32>, FfmpegError>>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Decoder {
+    /// Start `ffmpeg` with `input_args` (which should contain at least "-i") and `output_args` (e.g. for scaling or
+    /// frame rate conversion). The output must have `width` by `height` pixels.
+    pub(crate) fn start(input_args: &[&str], output_args: &[&str], width: usize, height: usize) -> Result<Self, FfmpegError> {
+        let mut child = Command::new("ffmpeg")
+            .args(["-v", "error", "-nostdin"])
+            .args(input_args)
+            .args(["-an", "-sn", "-f", "rawvideo", "-pix_fmt", "rgba"])
+            .args(output_args)
+            .arg("-")
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        let mut stdout = child.stdout.take().unwrap();
+        let mut stderr = child.stderr.take().unwrap();
+        let pixel_count = width * height;
+        let (sender, receiver) = mpsc::sync_channel(FRAME_QUEUE_LEN);
+        let thread = std::thread::Builder::new().name("Zaplib ffmpeg decoder".to_string()).spawn(move || {
+            let mut frame_count = 0;
+            loop {
+                let mut pixels = vec![0u32; pixel_count];
+                // RGBA bytes, which is what textures expect.
+                let bytes = unsafe { std::slice::from_raw_parts_mut(pixels.as_mut_ptr() as *mut u8, pixel_count * 4) };
+                match stdout.read_exact(bytes) {
+                    Ok(()) => {}
+                    Err(err) if err.kind() == ErrorKind::UnexpectedEof => break,
+                    Err(err) => {
+                        let _ = sender.send(Err(FfmpegError::Failed(err.to_string())));
+                        return;
+                    }
+                }
+                if sender.send(Ok(pixels)).is_err() {
+                    // The decoder was dropped.
+                    return;
+                }
+                frame_count += 1;
+            }
+            // Only report errors when we got no frames at all; otherwise it's probably a broken frame at the end, and
+            // we treat it as the end of the video.
+            let mut errors = String::new();
+            let _ = stderr.read_to_string(&mut errors);
+            if frame_count == 0 {
+                let message = if errors.trim().is_empty() { "ffmpeg produced no frames" } else { errors.trim() };
+                let _ = sender.send(Err(FfmpegError::Failed(message.to_string())));
+            }
+        })?;
+        Ok(Self { child, receiver: Some(receiver), thread: Some(thread) })
+    }
+
+    /// Get the next decoded frame, without blocking.
+    pub(crate) fn try_next(&self) -> Result<DecoderOutput, FfmpegError> {
+        match self.receiver.as_ref().unwrap().try_recv() {
+            Ok(Ok(frame)) => Ok(DecoderOutput::Frame(frame)),
+            Ok(Err(err)) => Err(err),
+            Err(mpsc::TryRecvError::Empty) => Ok(DecoderOutput::Pending),
+            Err(mpsc::TryRecvError::Disconnected) => Ok(DecoderOutput::Ended),
+        }
+    }
+}
+
+impl Drop for Decoder {
+    fn drop(&mut self) {
+        // Dropping the receiver unblocks the thread if it's waiting to send, and killing `ffmpeg` if it's waiting to
+        // read.
+        self.receiver = None;
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
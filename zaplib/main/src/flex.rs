@@ -0,0 +1,598 @@
+//! Flexbox-style layout on top of the box layout system (see [`crate::layout_api`]), loosely following
+//! [CSS Flexbox](https://developer.mozilla.org/en-US/docs/Web/CSS/CSS_Flexible_Box_Layout/Basic_Concepts_of_Flexbox).
+//!
+//! Since we lay out in a single pass while drawing, we can't know how big the items are going to be before we
+//! draw them. So a [`Flex`] measures its items while drawing them, and uses the measurements of the previous draw
+//! to arrange them (growing, shrinking, wrapping, and justifying). When measurements change, it calls
+//! [`Cx::request_draw`] so that the next draw uses the new ones. Alignment in the cross axis happens after drawing
+//! each item, by moving it, like [`Cx::begin_center_x_align`] does.
+
+use std::ops::Range;
+
+use crate::*;
+
+/// How to distribute free space along the main axis of a [`Flex`] (the axis of [`FlexProps::direction`]).
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde-support", derive(serde::Serialize, serde::Deserialize))]
+pub enum JustifyContent {
+    /// Put items at the start of the line.
+    Start,
+    /// Put items in the middle of the line.
+    Center,
+    /// Put items at the end of the line.
+    End,
+    /// Put free space between items, with the first and last item at the edges of the line.
+    SpaceBetween,
+    /// Put free space around items, so there's twice as much space between items as at the edges.
+    SpaceAround,
+    /// Put free space between items and at the edges in equal amounts.
+    SpaceEvenly,
+}
+impl JustifyContent {
+    /// TODO(JP): Replace these with JustifyContent::default() when
+    /// <https://github.com/rust-lang/rust/issues/67792> gets done
+    pub const DEFAULT: JustifyContent = JustifyContent::Start;
+}
+impl Default for JustifyContent {
+    fn default() -> Self {
+        JustifyContent::DEFAULT
+    }
+}
+
+/// How to align items within their line, in the cross axis of a [`Flex`].
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde-support", derive(serde::Serialize, serde::Deserialize))]
+pub enum AlignItems {
+    Start,
+    Center,
+    End,
+    /// Make items as big as their line. The line is as big as the [`Flex`] if it has a single line and a fixed
+    /// cross size (e.g. [`Height::Fill`] for [`Direction::Right`]), and otherwise as big as its biggest item.
+    ///
+    /// Items get a fixed size in the cross axis, so things inside of them can use [`Width::Fill`] or [`Height::Fill`].
+    Stretch,
+}
+impl AlignItems {
+    /// TODO(JP): Replace these with AlignItems::default() when
+    /// <https://github.com/rust-lang/rust/issues/67792> gets done
+    ///
+    /// Unlike in CSS, the default is [`AlignItems::Start`], which doesn't need any measurements from a previous draw.
+    pub const DEFAULT: AlignItems = AlignItems::Start;
+
+    fn factor(self) -> f32 {
+        match self {
+            AlignItems::Start | AlignItems::Stretch => 0.,
+            AlignItems::Center => 0.5,
+            AlignItems::End => 1.,
+        }
+    }
+}
+impl Default for AlignItems {
+    fn default() -> Self {
+        AlignItems::DEFAULT
+    }
+}
+
+/// The size of a [`FlexItem`] in the main axis, before growing or shrinking.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde-support", derive(serde::Serialize, serde::Deserialize))]
+pub enum FlexBasis {
+    /// The size of what gets drawn inside the item. Things inside the item should have a fixed or computed size in
+    /// the main axis, not [`Width::Fill`] or [`Height::Fill`], since there is nothing to fill yet when measuring.
+    Content,
+    /// A fixed size. Use `FlexBasis::Fix(0.)` with [`FlexItem::grow`] for proportional layouts, like two columns
+    /// where one is twice as wide as the other. These items get a fixed size in the main axis, so things inside of
+    /// them can use [`Width::Fill`] or [`Height::Fill`].
+    Fix(f32),
+}
+impl FlexBasis {
+    /// TODO(JP): Replace these with FlexBasis::default() when
+    /// <https://github.com/rust-lang/rust/issues/67792> gets done
+    pub const DEFAULT: FlexBasis = FlexBasis::Content;
+}
+impl Default for FlexBasis {
+    fn default() -> Self {
+        FlexBasis::DEFAULT
+    }
+}
+
+/// Props for an item in a [`Flex`]; see [`Flex::begin_item`].
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde-support", derive(serde::Serialize, serde::Deserialize))]
+pub struct FlexItem {
+    /// See [`FlexBasis`].
+    pub basis: FlexBasis,
+    /// How much of the free space in the line this item gets, relative to the other items in the line.
+    pub grow: f32,
+    /// How much this item shrinks when there's not enough space in the line, relative to the other items in the line
+    /// and to its [`FlexItem::basis`]. Items with [`FlexBasis::Content`] shrink their box, but what's inside of it
+    /// doesn't get any smaller.
+    pub shrink: f32,
+    /// Overrides [`FlexProps::align_items`] for this item.
+    pub align_self: Option<AlignItems>,
+}
+impl FlexItem {
+    /// TODO(JP): Replace these with FlexItem::default() when
+    /// <https://github.com/rust-lang/rust/issues/67792> gets done
+    pub const DEFAULT: FlexItem = FlexItem { basis: FlexBasis::DEFAULT, grow: 0., shrink: 1., align_self: None };
+}
+impl Default for FlexItem {
+    fn default() -> Self {
+        FlexItem::DEFAULT
+    }
+}
+
+/// Props for a [`Flex`]; see [`Flex::begin_flex`].
+#[derive(Copy, Clone, Debug)]
+#[cfg_attr(feature = "serde-support", derive(serde::Serialize, serde::Deserialize))]
+pub struct FlexProps {
+    /// The size of the [`Flex`] itself.
+    pub layout_size: LayoutSize,
+    /// The main axis: [`Direction::Right`] for a row, [`Direction::Down`] for a column.
+    pub direction: Direction,
+    /// Whether to start a new line when items don't fit. Requires a fixed size in the main axis.
+    pub line_wrap: LineWrap,
+    /// See [`JustifyContent`].
+    pub justify_content: JustifyContent,
+    /// See [`AlignItems`].
+    pub align_items: AlignItems,
+    /// Space between items in the main axis.
+    pub gap: f32,
+    /// Space between lines, when using [`LineWrap::Overflow`].
+    pub line_gap: f32,
+}
+impl FlexProps {
+    /// TODO(JP): Replace these with FlexProps::default() when
+    /// <https://github.com/rust-lang/rust/issues/67792> gets done
+    pub const DEFAULT: FlexProps = FlexProps {
+        layout_size: LayoutSize::DEFAULT,
+        direction: Direction::DEFAULT,
+        line_wrap: LineWrap::DEFAULT,
+        justify_content: JustifyContent::DEFAULT,
+        align_items: AlignItems::DEFAULT,
+        gap: 0.,
+        line_gap: 0.,
+    };
+}
+impl Default for FlexProps {
+    fn default() -> Self {
+        FlexProps::DEFAULT
+    }
+}
+
+/// Where an item goes in the main axis, as computed by [`plan_flex_items`].
+#[derive(Copy, Clone, Debug, PartialEq)]
+struct PlannedFlexItem {
+    line: usize,
+    /// Relative to the start of the [`Flex`].
+    main_pos: f32,
+    main_size: f32,
+}
+
+/// What we measured about an item, to plan the next draw.
+#[derive(Copy, Clone, Debug, PartialEq)]
+struct FlexItemMeasurement {
+    item: FlexItem,
+    /// The size of what was drawn inside of the item, in the main axis.
+    content_main_size: f32,
+}
+
+/// An item of the current line, which might still need to be moved in the cross axis.
+#[derive(Debug)]
+struct FlexLineItem {
+    align_range: Range<usize>,
+    cross_size: f32,
+    align: AlignItems,
+    /// How far we already moved the item in the cross axis.
+    cross_offset: f32,
+}
+
+/// The item that is currently being drawn.
+#[derive(Debug)]
+struct FlexCurrentItem {
+    item: FlexItem,
+    main_pos: f32,
+    /// [`None`] if we have no plan for this item, and it has [`FlexBasis::Content`].
+    main_size: Option<f32>,
+    /// The cross size of the line, if we know it before drawing the item.
+    line_cross_size: Option<f32>,
+    align_start: usize,
+}
+
+/// State while drawing a [`Flex`].
+#[derive(Debug)]
+struct FlexDraw {
+    props: FlexProps,
+    /// The position of the top-left corner of the [`Flex`].
+    origin: Vec2,
+    cross_size: Option<f32>,
+    plan: Vec<PlannedFlexItem>,
+    items: Vec<FlexItemMeasurement>,
+    line_cross_sizes: Vec<f32>,
+    line: usize,
+    line_items: Vec<FlexLineItem>,
+    /// Relative to `origin`.
+    line_cross_pos: f32,
+    /// Where the next item goes if it's not in `plan`, relative to `origin`.
+    main_cursor: f32,
+    current_item: Option<FlexCurrentItem>,
+    /// Whether the measurements don't match the plan, so we need to draw again.
+    needs_relayout: bool,
+}
+
+/// A container that lays out its items in a row or column, like CSS Flexbox: items can grow and shrink to fill the
+/// available space, get justified and aligned, and wrap onto multiple lines. See [`FlexProps`] and [`FlexItem`].
+///
+/// Keep this around between draws, since it uses the measurements of the previous draw (see the module docs).
+///
+/// ```ignore
+/// flex.begin_flex(cx, &FlexProps { layout_size: LayoutSize::new(Width::Fill, Height::Compute), gap: 8., ..FlexProps::DEFAULT });
+/// flex.begin_item(cx, &FlexItem { basis: FlexBasis::Fix(0.), grow: 1., ..FlexItem::DEFAULT });
+/// // Draw the left column, which gets a third of the width..
+/// flex.end_item(cx);
+/// flex.begin_item(cx, &FlexItem { basis: FlexBasis::Fix(0.), grow: 2., ..FlexItem::DEFAULT });
+/// // Draw the right column, which gets two thirds of the width..
+/// flex.end_item(cx);
+/// flex.end_flex(cx);
+/// ```
+#[derive(Debug, Default)]
+pub struct Flex {
+    previous_items: Vec<FlexItemMeasurement>,
+    previous_line_cross_sizes: Vec<f32>,
+    draw: Option<FlexDraw>,
+}
+
+/// Make a [`Vec2`] out of sizes or positions along the main and cross axes.
+fn main_cross_to_vec2(direction: Direction, main: f32, cross: f32) -> Vec2 {
+    match direction {
+        Direction::Right => vec2(main, cross),
+        Direction::Down => vec2(cross, main),
+    }
+}
+
+/// Split a [`Vec2`] into its main and cross axis components.
+fn vec2_to_main_cross(direction: Direction, v: Vec2) -> (f32, f32) {
+    match direction {
+        Direction::Right => (v.x, v.y),
+        Direction::Down => (v.y, v.x),
+    }
+}
+
+/// Break items into lines, and compute their positions and sizes in the main axis, given their sizes before
+/// growing or shrinking. Without a `main_size`, items just get put next to each other.
+fn plan_flex_items(props: &FlexProps, main_size: Option<f32>, items: &[(FlexItem, f32)]) -> Vec<PlannedFlexItem> {
+    let mut lines: Vec<Range<usize>> = vec![];
+    let mut line_start = 0;
+    let mut line_size = 0.;
+    for (index, (_, size)) in items.iter().enumerate() {
+        if let (LineWrap::Overflow, Some(main_size)) = (props.line_wrap, main_size) {
+            // Same tolerance as the layout system.
+            if index > line_start && line_size + props.gap + size > main_size + 0.01 {
+                lines.push(line_start..index);
+                line_start = index;
+            }
+        }
+        line_size = if index == line_start { *size } else { line_size + props.gap + size };
+    }
+    if line_start < items.len() {
+        lines.push(line_start..items.len());
+    }
+
+    let mut planned = Vec::with_capacity(items.len());
+    for (line, range) in lines.into_iter().enumerate() {
+        let line_items = &items[range];
+        let count = line_items.len() as f32;
+        let mut sizes: Vec<f32> = line_items.iter().map(|(_, size)| *size).collect();
+        let mut free = main_size.map_or(0., |main_size| main_size - sizes.iter().sum::<f32>() - props.gap * (count - 1.));
+
+        let total_grow: f32 = line_items.iter().map(|(item, _)| item.grow.max(0.)).sum();
+        let total_shrink: f32 = line_items.iter().map(|(item, size)| item.shrink.max(0.) * size).sum();
+        if free > 0. && total_grow > 0. {
+            for (size, (item, _)) in sizes.iter_mut().zip(line_items) {
+                *size += free * item.grow.max(0.) / total_grow;
+            }
+            free = 0.;
+        } else if free < 0. && total_shrink > 0. {
+            for (size, (item, basis)) in sizes.iter_mut().zip(line_items) {
+                *size = (*size + free * item.shrink.max(0.) * basis / total_shrink).max(0.);
+            }
+            free = 0.;
+        }
+
+        let free = free.max(0.);
+        let (start, between) = match props.justify_content {
+            JustifyContent::Start => (0., 0.),
+            JustifyContent::Center => (free / 2., 0.),
+            JustifyContent::End => (free, 0.),
+            JustifyContent::SpaceBetween if count > 1. => (0., free / (count - 1.)),
+            JustifyContent::SpaceBetween => (0., 0.),
+            JustifyContent::SpaceAround => (free / count / 2., free / count),
+            JustifyContent::SpaceEvenly => (free / (count + 1.), free / (count + 1.)),
+        };
+        let mut main_pos = start;
+        for size in sizes {
+            planned.push(PlannedFlexItem { line, main_pos, main_size: size });
+            main_pos += size + props.gap + between;
+        }
+    }
+    planned
+}
+
+impl FlexDraw {
+    /// Align the items of the current line in the cross axis, now that we know how big the line is, and move on to
+    /// the next line.
+    fn finish_line(&mut self, cx: &mut Cx) {
+        let line_cross_size = match (self.props.line_wrap, self.cross_size) {
+            (LineWrap::None, Some(cross_size)) => cross_size,
+            _ => self.line_items.iter().map(|line_item| line_item.cross_size).fold(0., f32::max),
+        };
+        for line_item in self.line_items.drain(..) {
+            let cross_offset = line_item.align.factor() * (line_cross_size - line_item.cross_size);
+            if (cross_offset - line_item.cross_offset).abs() > 0.01 {
+                let offset = cross_offset - line_item.cross_offset;
+                cx.move_range_by(main_cross_to_vec2(self.props.direction, 0., offset), line_item.align_range);
+                self.needs_relayout = true;
+            }
+        }
+        self.line_cross_sizes.push(line_cross_size);
+        self.line_cross_pos += line_cross_size + self.props.line_gap;
+        self.line += 1;
+        self.main_cursor = 0.;
+    }
+}
+
+impl Flex {
+    /// Start a [`Flex`]. Call [`Flex::begin_item`] and [`Flex::end_item`] for each item, and then [`Flex::end_flex`].
+    pub fn begin_flex(&mut self, cx: &mut Cx, props: &FlexProps) {
+        assert!(self.draw.is_none(), "Call end_flex before calling begin_flex again");
+        // We position items ourselves, so the box itself never wraps.
+        cx.begin_typed_box(
+            CxBoxType::Flex,
+            Layout { layout_size: props.layout_size, direction: props.direction, ..Layout::default() },
+        );
+        let layout_box = cx.layout_boxes.last().unwrap();
+        let width = if layout_box.width.is_nan() { None } else { Some(layout_box.available_width) };
+        let height = if layout_box.height.is_nan() { None } else { Some(layout_box.available_height) };
+        let (main_size, cross_size) = match props.direction {
+            Direction::Right => (width, height),
+            Direction::Down => (height, width),
+        };
+
+        let hypothetical_sizes: Vec<(FlexItem, f32)> = self
+            .previous_items
+            .iter()
+            .map(|measurement| match measurement.item.basis {
+                FlexBasis::Content => (measurement.item, measurement.content_main_size),
+                FlexBasis::Fix(size) => (measurement.item, size.max(0.)),
+            })
+            .collect();
+
+        self.draw = Some(FlexDraw {
+            props: *props,
+            origin: layout_box.pos,
+            cross_size,
+            plan: plan_flex_items(props, main_size, &hypothetical_sizes),
+            items: Vec::with_capacity(self.previous_items.len()),
+            line_cross_sizes: vec![],
+            line: 0,
+            line_items: vec![],
+            line_cross_pos: 0.,
+            main_cursor: 0.,
+            current_item: None,
+            needs_relayout: false,
+        });
+    }
+
+    /// Start an item of the [`Flex`]. Draw its contents and then call [`Flex::end_item`].
+    pub fn begin_item(&mut self, cx: &mut Cx, item: &FlexItem) {
+        let draw = self.draw.as_mut().expect("Call begin_flex before begin_item");
+        assert!(draw.current_item.is_none(), "Call end_item before calling begin_item again");
+        let index = draw.items.len();
+
+        // Only use the plan if it was made for this item.
+        let planned = draw.plan.get(index).copied().filter(|_| self.previous_items.get(index).map(|m| m.item) == Some(*item));
+        if planned.is_none() {
+            draw.needs_relayout = true;
+        }
+        if let Some(planned) = planned {
+            while draw.line < planned.line {
+                draw.finish_line(cx);
+            }
+        }
+
+        let main_pos = planned.map_or(draw.main_cursor, |planned| planned.main_pos);
+        let main_size = match (planned, item.basis) {
+            (Some(planned), _) => Some(planned.main_size),
+            (None, FlexBasis::Fix(size)) => Some(size.max(0.)),
+            (None, FlexBasis::Content) => None,
+        };
+        let line_cross_size = match (draw.props.line_wrap, draw.cross_size) {
+            (LineWrap::None, Some(cross_size)) => Some(cross_size),
+            _ => self.previous_line_cross_sizes.get(draw.line).copied(),
+        };
+        let align = item.align_self.unwrap_or(draw.props.align_items);
+
+        let main = match item.basis {
+            FlexBasis::Content => None,
+            FlexBasis::Fix(_) => main_size,
+        };
+        let cross = if align == AlignItems::Stretch { line_cross_size } else { None };
+        let layout_size = match draw.props.direction {
+            Direction::Right => {
+                LayoutSize::new(main.map_or(Width::Compute, Width::Fix), cross.map_or(Height::Compute, Height::Fix))
+            }
+            Direction::Down => {
+                LayoutSize::new(cross.map_or(Width::Compute, Width::Fix), main.map_or(Height::Compute, Height::Fix))
+            }
+        };
+
+        cx.layout_boxes.last_mut().unwrap().pos =
+            draw.origin + main_cross_to_vec2(draw.props.direction, main_pos, draw.line_cross_pos);
+        draw.current_item = Some(FlexCurrentItem {
+            item: *item,
+            main_pos,
+            main_size,
+            line_cross_size,
+            align_start: cx.layout_box_align_list.len(),
+        });
+        cx.begin_typed_box(CxBoxType::FlexItem, Layout { layout_size, ..Layout::default() });
+    }
+
+    /// End the item that was started with [`Flex::begin_item`]. Returns the [`Rect`] of the item, after growing or
+    /// shrinking and aligning it.
+    pub fn end_item(&mut self, cx: &mut Cx) -> Rect {
+        cx.assert_last_box_type_matches(CxBoxType::FlexItem);
+        let draw = self.draw.as_mut().expect("Call begin_flex before end_item");
+        let current_item = draw.current_item.take().expect("Call begin_item before end_item");
+        let direction = draw.props.direction;
+
+        // Measure what got drawn, and give the item its final size.
+        let layout_box = cx.layout_boxes.last_mut().unwrap();
+        let content_size = vec2(
+            if layout_box.bound_right_bottom.x == f32::NEG_INFINITY {
+                0.
+            } else {
+                layout_box.bound_right_bottom.x - layout_box.origin.x
+            },
+            if layout_box.bound_right_bottom.y == f32::NEG_INFINITY {
+                0.
+            } else {
+                layout_box.bound_right_bottom.y - layout_box.origin.y
+            },
+        );
+        let (content_main_size, content_cross_size) = vec2_to_main_cross(direction, content_size);
+        let main_size = current_item.main_size.unwrap_or(content_main_size);
+        let (width, height) = match direction {
+            Direction::Right => (&mut layout_box.width, &mut layout_box.height),
+            Direction::Down => (&mut layout_box.height, &mut layout_box.width),
+        };
+        if width.is_nan() {
+            *width = main_size;
+        }
+        if height.is_nan() {
+            *height = content_cross_size;
+        }
+        let rect = cx.end_typed_box(CxBoxType::FlexItem);
+        let align_range = current_item.align_start..cx.layout_box_align_list.len();
+
+        // Align in the cross axis right away if we know how big the line is. Otherwise we do it in
+        // `FlexDraw::finish_line`.
+        let (_, cross_size) = vec2_to_main_cross(direction, rect.size);
+        let align = current_item.item.align_self.unwrap_or(draw.props.align_items);
+        let cross_offset =
+            current_item.line_cross_size.map_or(0., |line_cross_size| align.factor() * (line_cross_size - cross_size));
+        let offset = main_cross_to_vec2(direction, 0., cross_offset);
+        if cross_offset != 0. {
+            cx.move_range_by(offset, align_range.clone());
+        }
+        draw.line_items.push(FlexLineItem { align_range, cross_size, align, cross_offset });
+
+        let previous = self.previous_items.get(draw.items.len());
+        if current_item.item.basis == FlexBasis::Content
+            && previous.map_or(true, |previous| (previous.content_main_size - content_main_size).abs() > 0.01)
+        {
+            draw.needs_relayout = true;
+        }
+        draw.items.push(FlexItemMeasurement { item: current_item.item, content_main_size });
+        draw.main_cursor = current_item.main_pos + main_size + draw.props.gap;
+
+        Rect { pos: rect.pos + offset, size: rect.size }
+    }
+
+    /// End the [`Flex`] that was started with [`Flex::begin_flex`]. Returns its [`Rect`].
+    pub fn end_flex(&mut self, cx: &mut Cx) -> Rect {
+        let mut draw = self.draw.take().expect("Call begin_flex before end_flex");
+        assert!(draw.current_item.is_none(), "Call end_item before end_flex");
+        if !draw.line_items.is_empty() {
+            draw.finish_line(cx);
+        }
+        if draw.items.len() != self.previous_items.len() || draw.line_cross_sizes != self.previous_line_cross_sizes {
+            draw.needs_relayout = true;
+        }
+        self.previous_items = draw.items;
+        self.previous_line_cross_sizes = draw.line_cross_sizes;
+
+        let rect = cx.end_typed_box(CxBoxType::Flex);
+        if draw.needs_relayout {
+            cx.request_draw();
+        }
+        rect
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn plan(props: &FlexProps, main_size: Option<f32>, items: &[(FlexItem, f32)]) -> Vec<(usize, f32, f32)> {
+        plan_flex_items(props, main_size, items)
+            .iter()
+            .map(|planned| (planned.line, planned.main_pos, planned.main_size))
+            .collect()
+    }
+
+    #[test]
+    fn test_plan_grow_and_shrink() {
+        let props = FlexProps { gap: 10., ..FlexProps::DEFAULT };
+        let grow = |grow| FlexItem { grow, ..FlexItem::DEFAULT };
+        assert_eq!(plan(&props, Some(130.), &[(grow(1.), 0.), (grow(2.), 0.)]), vec![(0, 0., 40.), (0, 50., 80.)]);
+        // Bigger items shrink more.
+        assert_eq!(plan(&props, Some(70.), &[(grow(0.), 20.), (grow(0.), 60.)]), vec![(0, 0., 15.), (0, 25., 45.)]);
+        // Without a size, items just go next to each other.
+        assert_eq!(plan(&props, None, &[(grow(1.), 20.), (grow(1.), 30.)]), vec![(0, 0., 20.), (0, 30., 30.)]);
+    }
+
+    #[test]
+    fn test_plan_justify_and_wrap() {
+        let item = (FlexItem::DEFAULT, 20.);
+        let props = FlexProps { justify_content: JustifyContent::Center, ..FlexProps::DEFAULT };
+        assert_eq!(plan(&props, Some(100.), &[item, item]), vec![(0, 30., 20.), (0, 50., 20.)]);
+        let props = FlexProps { justify_content: JustifyContent::SpaceBetween, ..FlexProps::DEFAULT };
+        assert_eq!(plan(&props, Some(100.), &[item, item]), vec![(0, 0., 20.), (0, 80., 20.)]);
+        let props = FlexProps { justify_content: JustifyContent::SpaceEvenly, ..FlexProps::DEFAULT };
+        assert_eq!(plan(&props, Some(100.), &[item, item, item]), vec![(0, 10., 20.), (0, 40., 20.), (0, 70., 20.)]);
+
+        let props = FlexProps { line_wrap: LineWrap::Overflow, gap: 10., ..FlexProps::DEFAULT };
+        assert_eq!(plan(&props, Some(50.), &[item, item, item]), vec![(0, 0., 20.), (0, 30., 20.), (1, 0., 20.)]);
+    }
+
+    #[test]
+    fn test_flex_draw() {
+        let mut test_cx = TestCx::new(vec2(400., 300.));
+        let mut flex = Flex::default();
+        let mut rects = vec![];
+        let mut draw = |test_cx: &mut TestCx, rects: &mut Vec<Rect>| {
+            rects.clear();
+            test_cx.draw(|cx| {
+                flex.begin_flex(
+                    cx,
+                    &FlexProps {
+                        layout_size: LayoutSize::new(Width::Fix(200.), Height::Fix(100.)),
+                        align_items: AlignItems::Center,
+                        gap: 10.,
+                        ..FlexProps::DEFAULT
+                    },
+                );
+                flex.begin_item(cx, &FlexItem::DEFAULT);
+                cx.add_box(LayoutSize::new(Width::Fix(50.), Height::Fix(20.)));
+                rects.push(flex.end_item(cx));
+                flex.begin_item(cx, &FlexItem { basis: FlexBasis::Fix(0.), grow: 1., ..FlexItem::DEFAULT });
+                cx.add_box(LayoutSize::new(Width::Fill, Height::Fix(40.)));
+                rects.push(flex.end_item(cx));
+                flex.end_flex(cx);
+            });
+        };
+
+        // The first draw measures the items, and the second one arranges them.
+        draw(&mut test_cx, &mut rects);
+        assert_eq!(rects[1].size, vec2(0., 40.));
+        draw(&mut test_cx, &mut rects);
+        let arranged = rects.clone();
+        draw(&mut test_cx, &mut rects);
+        assert_eq!(rects, arranged);
+
+        let origin = rects[0].pos - vec2(0., 40.);
+        assert_eq!(rects[0], Rect { pos: origin + vec2(0., 40.), size: vec2(50., 20.) });
+        assert_eq!(rects[1], Rect { pos: origin + vec2(60., 30.), size: vec2(140., 40.) });
+    }
+}
@@ -0,0 +1,174 @@
+//! Keyboard navigation between components using Tab and Shift+Tab.
+//!
+//! Components register themselves as focusable while drawing, using [`Cx::add_focusable`]. Pressing Tab then
+//! moves [`Cx::key_focus`] to the next focusable component, and Shift+Tab to the previous one. Components get
+//! notified through the usual [`Event::KeyFocus`] and [`Event::KeyFocusLost`] events from [`Event::hits_keyboard`].
+
+use crate::*;
+
+/// Options for [`Cx::add_focusable`].
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Focusable {
+    /// Components with a `tab_index` come first when pressing Tab, ordered by increasing `tab_index`. After
+    /// that come all components without one, in the order in which they were drawn. Same as `tabindex` in HTML.
+    pub tab_index: Option<u32>,
+    /// Don't move focus away when pressing Tab while this component has focus, so it can handle Tab itself, e.g.
+    /// for inserting a tab character in a text editor.
+    pub captures_tab: bool,
+}
+
+/// A component that was registered using [`Cx::add_focusable`] during the last draw.
+#[derive(Clone, Debug)]
+pub(crate) struct CxFocusable {
+    component_id: ComponentId,
+    area: Area,
+    focusable: Focusable,
+}
+
+impl Cx {
+    /// Register `component_id` as focusable using Tab and Shift+Tab. Call this on every draw; `area` is used to
+    /// draw the focus ring around, see [`Cx::get_key_focus_rect`].
+    pub fn add_focusable(&mut self, component_id: ComponentId, area: Area, focusable: Focusable) {
+        self.focusables.push(CxFocusable { component_id, area, focusable });
+    }
+
+    /// Whether the focus was last moved using the keyboard, in which case you should show where the focus is,
+    /// e.g. using a focus ring. Gets reset when the user clicks somewhere. Same as `:focus-visible` in CSS.
+    pub fn is_focus_visible(&self) -> bool {
+        self.focus_visible && self.key_focus.is_some()
+    }
+
+    /// The rectangle of the [`Area`] that was passed into [`Cx::add_focusable`] for the component that currently
+    /// has focus, if [`Cx::is_focus_visible`]. Useful for drawing a focus ring.
+    pub fn get_key_focus_rect(&self) -> Option<Rect> {
+        if !self.is_focus_visible() {
+            return None;
+        }
+        let focused = self.focusables.iter().find(|f| Some(f.component_id) == self.key_focus)?;
+        focused.area.get_rect_for_first_instance(self)
+    }
+
+    /// Move the focus to the next (or previous, if `backwards` is set) focusable component. Wraps around at the
+    /// ends, and starts at the beginning (or the end) if no focusable component currently has focus.
+    pub fn focus_next(&mut self, backwards: bool) {
+        let mut order: Vec<&CxFocusable> = self.focusables.iter().collect();
+        // Stable sort, so components without `tab_index` stay in draw order.
+        order.sort_by_key(|f| f.focusable.tab_index.unwrap_or(u32::MAX));
+        if order.is_empty() {
+            return;
+        }
+        let current = order.iter().position(|f| Some(f.component_id) == self.key_focus);
+        let next = match (current, backwards) {
+            (None, false) => 0,
+            (None, true) => order.len() - 1,
+            (Some(index), false) => (index + 1) % order.len(),
+            (Some(index), true) => (index + order.len() - 1) % order.len(),
+        };
+        let component_id = order[next].component_id;
+        self.set_key_focus(Some(component_id));
+        self.focus_visible = true;
+        // For the focus ring.
+        self.request_draw();
+    }
+
+    /// Handle focus navigation after `event` was handled by the app. Called from [`Cx::call_event_handler`].
+    pub(crate) fn process_focus_event(&mut self, event: &Event) {
+        match event {
+            Event::PointerDown(_) if self.focus_visible => {
+                self.focus_visible = false;
+                self.request_draw();
+            }
+            Event::KeyDown(ke) if ke.key_code == KeyCode::Tab => {
+                let KeyModifiers { shift, control, alt, logo } = ke.modifiers;
+                // Leave it alone if the app already changed the focus in response to this key.
+                if control || alt || logo || self.next_key_focus.is_some() {
+                    return;
+                }
+                // Components that have focus but aren't registered as focusable might be using Tab themselves.
+                let can_leave = match self.key_focus {
+                    None => true,
+                    Some(component_id) => {
+                        self.focusables.iter().any(|f| f.component_id == component_id && !f.focusable.captures_tab)
+                    }
+                };
+                if can_leave {
+                    self.focus_next(shift);
+                }
+            }
+            _ => (),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    fn draw_focusables(test_cx: &mut TestCx, focusables: &[(ComponentId, Focusable)]) {
+        test_cx.draw(|cx| {
+            for (component_id, focusable) in focusables {
+                cx.add_focusable(*component_id, Area::Empty, focusable.clone());
+            }
+        });
+    }
+
+    fn press_tab(test_cx: &mut TestCx, shift: bool) {
+        test_cx.key_down(KeyCode::Tab, KeyModifiers { shift, ..KeyModifiers::default() }, |_, _| ());
+    }
+
+    #[test]
+    fn test_tab_cycles_in_draw_order() {
+        let mut test_cx = TestCx::new(vec2(400., 300.));
+        let ids = [ComponentId::default(), ComponentId::default(), ComponentId::default()];
+        draw_focusables(&mut test_cx, &ids.map(|id| (id, Focusable::default())));
+
+        for &id in &ids {
+            press_tab(&mut test_cx, false);
+            assert!(test_cx.cx.has_key_focus(Some(id)));
+        }
+        press_tab(&mut test_cx, false);
+        assert!(test_cx.cx.has_key_focus(Some(ids[0])));
+        press_tab(&mut test_cx, true);
+        assert!(test_cx.cx.has_key_focus(Some(ids[2])));
+        assert!(test_cx.cx.is_focus_visible());
+
+        test_cx.pointer_down(vec2(10., 10.), |_, _| ());
+        assert!(!test_cx.cx.is_focus_visible());
+    }
+
+    #[test]
+    fn test_tab_index_comes_first() {
+        let mut test_cx = TestCx::new(vec2(400., 300.));
+        let (a, b, c) = (ComponentId::default(), ComponentId::default(), ComponentId::default());
+        draw_focusables(
+            &mut test_cx,
+            &[
+                (a, Focusable::default()),
+                (b, Focusable { tab_index: Some(2), ..Focusable::default() }),
+                (c, Focusable { tab_index: Some(1), ..Focusable::default() }),
+            ],
+        );
+
+        let mut order = Vec::new();
+        for _ in 0..3 {
+            press_tab(&mut test_cx, false);
+            order.push(test_cx.cx.key_focus);
+        }
+        assert_eq!(order, vec![Some(c), Some(b), Some(a)]);
+    }
+
+    #[test]
+    fn test_captures_tab() {
+        let mut test_cx = TestCx::new(vec2(400., 300.));
+        let (a, b) = (ComponentId::default(), ComponentId::default());
+        draw_focusables(
+            &mut test_cx,
+            &[(a, Focusable { captures_tab: true, ..Focusable::default() }), (b, Focusable::default())],
+        );
+
+        press_tab(&mut test_cx, false);
+        assert!(test_cx.cx.has_key_focus(Some(a)));
+        press_tab(&mut test_cx, false);
+        assert!(test_cx.cx.has_key_focus(Some(a)));
+    }
+}
@@ -5,13 +5,17 @@
 //! * <https://github.com/Zaplib/zaplib/issues/174>
 //! * <https://github.com/Zaplib/zaplib/issues/175>
 
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::RwLock;
 use std::sync::RwLockReadGuard;
 
+use crate::text_shaping::{ShapedCluster, ShapedGlyph, ShapingCache};
 use crate::*;
 use zaplib_vector::geometry::Trapezoid;
 use zaplib_vector::geometry::{AffineTransformation, Transform, Vector};
-use zaplib_vector::internal_iter::*;
 use zaplib_vector::path::PathIterator;
 use zaplib_vector::trapezoidator::Trapezoidator;
 
@@ -81,6 +85,41 @@ impl Default for TextStyle {
     }
 }
 
+/// Why loading a font using [`Cx::load_font`] or [`Cx::load_system_font`] failed.
+#[derive(Clone, Debug, PartialEq)]
+pub enum FontError {
+    /// There is no installed font with this name, or we can't look for installed fonts on this platform (such as on
+    /// the web).
+    NotFound(String),
+    /// The font could not be parsed. Only fonts with TrueType outlines are supported (`.ttf` and `.ttc` files, but
+    /// typically not `.otf` files).
+    Invalid,
+}
+
+impl fmt::Display for FontError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FontError::NotFound(name) => write!(f, "Font not found: {}", name),
+            FontError::Invalid => write!(f, "Invalid or unsupported font"),
+        }
+    }
+}
+
+impl std::error::Error for FontError {}
+
+/// Installed fonts with good coverage of scripts that our built-in fonts don't have, which
+/// [`Cx::add_system_font_fallbacks`] tries to load. Only ones with TrueType outlines or color glyphs, since we don't
+/// support others.
+const SYSTEM_FALLBACK_FONTS: &[&str] = if cfg!(target_os = "windows") {
+    &["Segoe UI", "Microsoft YaHei", "Yu Gothic", "Malgun Gothic", "Nirmala UI", "Segoe UI Symbol", "Segoe UI Emoji"]
+} else if cfg!(any(target_os = "macos", target_os = "ios")) {
+    &["Arial Unicode MS", "Apple Symbols", "Apple Color Emoji"]
+} else if cfg!(target_os = "android") {
+    &["Roboto", "Droid Sans Fallback", "Noto Sans Symbols", "Noto Color Emoji"]
+} else {
+    &["DejaVu Sans", "Noto Sans", "Droid Sans Fallback", "WenQuanYi Zen Hei", "Noto Color Emoji", "Symbola"]
+};
+
 impl Cx {
     pub(crate) fn load_fonts(&mut self) {
         #[cfg(not(feature = "disable-fonts"))]
@@ -88,29 +127,85 @@ impl Cx {
             let mut write_fonts_data = self.fonts_data.write().unwrap();
             write_fonts_data.fonts = Iterator::map(FONTS_BYTES.iter(), |bytes| {
                 let font = zaplib_vector::ttf_parser::parse_ttf(bytes).expect("Error loading font");
-                CxFont { font_loaded: Some(font), atlas_pages: vec![] }
+                CxFont { font_loaded: Some(font), font_bytes: Cow::Borrowed(*bytes), ..CxFont::default() }
             })
             .collect();
         }
     }
 
+    /// Load a font from the bytes of a `.ttf` file (or the first font in a `.ttc` file), so you can use it in a
+    /// [`TextStyle`].
+    pub fn load_font(&mut self, bytes: &[u8]) -> Result<Font, FontError> {
+        let font = zaplib_vector::ttf_parser::parse_ttf(bytes).map_err(|_| FontError::Invalid)?;
+        Ok(self.add_font(font, bytes.to_vec(), 0))
+    }
+
+    /// Add a parsed font, along with the bytes of the file and the index of the font in it, for text shaping.
+    fn add_font(&mut self, font: zaplib_vector::font::VectorFont, bytes: Vec<u8>, index: u32) -> Font {
+        let mut write_fonts_data = self.fonts_data.write().unwrap();
+        write_fonts_data.fonts.push(CxFont {
+            font_loaded: Some(font),
+            font_bytes: Cow::Owned(bytes),
+            font_index: index,
+            ..CxFont::default()
+        });
+        Font { font_id: write_fonts_data.fonts.len() - 1 }
+    }
+
+    /// Load a font that is installed on the system by its name, like "DejaVu Sans" or "Microsoft YaHei".
+    ///
+    /// This looks through all the font files on the system, so call it once (e.g. when constructing your app) and
+    /// keep the [`Font`] around. Not supported on the web; there you have to get the font file yourself and use
+    /// [`Cx::load_font`].
+    pub fn load_system_font(&mut self, name: &str) -> Result<Font, FontError> {
+        let (font, bytes, index) = find_system_font(name).ok_or_else(|| FontError::NotFound(name.to_string()))?;
+        Ok(self.add_font(font, bytes, index))
+    }
+
+    /// Set the fonts to use for characters that `font` doesn't have, in order of preference. For example, you can
+    /// have a Latin font fall back to a CJK font, and then to an emoji font.
+    pub fn set_font_fallbacks(&mut self, font: Font, fallbacks: &[Font]) {
+        let mut write_fonts_data = self.fonts_data.write().unwrap();
+        write_fonts_data.fonts[font.font_id].fallbacks = fallbacks.iter().map(|f| f.font_id).collect();
+        // Shaping depends on which fonts the characters come from.
+        write_fonts_data.shaping_cache.clear();
+        drop(write_fonts_data);
+        self.invalidate_view_caches();
+    }
+
+    /// Load installed fonts that cover many different scripts (see [`SYSTEM_FALLBACK_FONTS`]), and add them as
+    /// fallbacks to all fonts that are already loaded, so that e.g. CJK text gets drawn instead of boxes.
+    ///
+    /// This reads large font files, so it can take a while; call it once when constructing your app. Returns the
+    /// fonts that were found, which is nothing on the web.
+    pub fn add_system_font_fallbacks(&mut self) -> Vec<Font> {
+        let existing_font_count = self.fonts_data.read().unwrap().fonts.len();
+        let fallbacks: Vec<Font> = SYSTEM_FALLBACK_FONTS.iter().filter_map(|name| self.load_system_font(name).ok()).collect();
+        let mut write_fonts_data = self.fonts_data.write().unwrap();
+        for font in &mut write_fonts_data.fonts[..existing_font_count] {
+            font.fallbacks.extend(fallbacks.iter().map(|f| f.font_id));
+        }
+        write_fonts_data.shaping_cache.clear();
+        drop(write_fonts_data);
+        self.invalidate_view_caches();
+        fallbacks
+    }
+
     pub fn reset_font_atlas_and_redraw(&mut self) {
         {
             // Use a block here to constraint the lifetime of locks
 
             let mut write_fonts = self.fonts_data.write().unwrap();
 
+            write_fonts.reset_fonts_atlas();
             for font in &mut write_fonts.fonts {
                 font.atlas_pages.truncate(0);
             }
-
-            write_fonts.fonts_atlas.alloc_xpos = 0.;
-            write_fonts.fonts_atlas.alloc_ypos = 0.;
-            write_fonts.fonts_atlas.alloc_hmax = 0.;
-            write_fonts.fonts_atlas.clear_buffer = true;
+            write_fonts.color_glyph_atlas.clear();
         }
 
-        self.request_draw();
+        // Cached views have text that points into the old atlas.
+        self.invalidate_view_caches();
     }
 }
 
@@ -198,7 +293,11 @@ static SHADER: Shader = Shader {
                 if chan < 2.5 {
                     return vec4(0., 0., t_area, 0.);
                 }
-                return vec4(t_area, t_area, t_area, 0.);
+                if chan < 3.5 {
+                    return vec4(t_area, t_area, t_area, 0.);
+                }
+                // Clear the area (see `CxFontsAtlas::clear_todo`).
+                return vec4(0., 0., 0., 1.);
             }
 
             fn vertex() -> vec4 {
@@ -228,6 +327,8 @@ impl TrapezoidText {
     // test api for directly drawing a glyph
     /*
     pub(crate) fn draw_char(&mut self, cx: &mut Cx, c: char, font_id: usize, font_size: f32) {
+        use zaplib_vector::internal_iter::*;
+
         // now lets make a draw_character function
         let trapezoids = {
             let cxfont = &cx.fonts[font_id];
@@ -279,6 +380,9 @@ impl TrapezoidText {
 
     // atlas drawing function used by CxAfterDraw
     fn draw_todo(&mut self, cx: &mut Cx, todo: CxFontsAtlasTodo, instances: &mut Vec<(Trapezoid, f32)>) {
+        // Not imported for the whole file, since its `map` is ambiguous with `Iterator::map` and `Option::map`.
+        use zaplib_vector::internal_iter::*;
+
         let mut size = 1.0;
         for i in 0..3 {
             if i == 1 {
@@ -294,14 +398,14 @@ impl TrapezoidText {
                 let atlas_page = &cxfont.atlas_pages[todo.atlas_page_id];
                 let glyph = &font.glyphs[todo.glyph_id];
 
-                if todo.glyph_id == font.char_code_to_glyph_index_map[10]
-                    || todo.glyph_id == font.char_code_to_glyph_index_map[9]
-                    || todo.glyph_id == font.char_code_to_glyph_index_map[13]
+                if todo.glyph_id == font.glyph_index('\n')
+                    || todo.glyph_id == font.glyph_index('\t')
+                    || todo.glyph_id == font.glyph_index('\r')
                 {
                     return;
                 }
 
-                let glyphtc = atlas_page.atlas_glyphs[todo.glyph_id][todo.subpixel_id].unwrap();
+                let glyphtc = atlas_page.atlas_glyphs[&(todo.glyph_id, todo.subpixel_id)].tc;
                 let texture_size = read_fonts.fonts_atlas.texture_size;
                 let tx = glyphtc.tx1 * texture_size.x + todo.subpixel_x_fract * atlas_page.dpi_factor;
                 let ty = 1.0 + glyphtc.ty1 * texture_size.y - todo.subpixel_y_fract * atlas_page.dpi_factor;
@@ -352,6 +456,12 @@ pub struct CxAfterDraw {
     pub(crate) atlas_view: View,
     pub(crate) atlas_texture_handle: TextureHandle,
     pub(crate) counter: usize,
+    /// Whether [`CxFontsAtlas`] overflowed in the previous call to [`CxAfterDraw::after_draw`], so we don't keep
+    /// redrawing when a single frame has more glyphs than fit in the atlas.
+    pub(crate) fonts_atlas_overflowed_last_draw: bool,
+    /// Whether [`CxColorGlyphAtlas`] got cleared in the previous call to [`CxAfterDraw::after_draw`], so we don't
+    /// keep redrawing when a single frame has more color glyphs than fit in the atlas.
+    pub(crate) color_glyph_atlas_cleared_last_draw: bool,
 }
 
 impl CxAfterDraw {
@@ -360,15 +470,17 @@ impl CxAfterDraw {
             let mut texture = Texture::default();
             let texture_handle = texture.get_color(cx);
 
-            let mut fonts_atlas = &mut cx.fonts_data.write().unwrap().fonts_atlas;
-            fonts_atlas.texture_size = Vec2 { x: 2048.0, y: 2048.0 };
-            fonts_atlas.texture_handle = Some(texture_handle);
+            cx.fonts_data.write().unwrap().fonts_atlas.texture_handle = Some(texture_handle);
 
             texture_handle
         };
+        cx.fonts_data.write().unwrap().color_glyph_atlas.texture_handle =
+            Some(Texture::default().get_with_dimensions(cx, COLOR_GLYPH_ATLAS_SIZE, COLOR_GLYPH_ATLAS_SIZE));
 
         Self {
             counter: 0,
+            fonts_atlas_overflowed_last_draw: false,
+            color_glyph_atlas_cleared_last_draw: false,
             trapezoid_text: TrapezoidText::default(),
             atlas_pass: Pass::default(),
             atlas_view: View::default(),
@@ -379,6 +491,18 @@ impl CxAfterDraw {
     pub fn after_draw(&mut self, cx: &mut Cx) {
         //let start = Cx::profile_time_ns();
 
+        let (needs_redraw, overflowed) = {
+            let atlas = &mut cx.fonts_data.write().unwrap().fonts_atlas;
+            atlas.generation += 1;
+            (std::mem::take(&mut atlas.needs_redraw), std::mem::take(&mut atlas.overflowed))
+        };
+        // Text that was drawn before glyphs got moved or evicted has to be redrawn, but don't keep redrawing when a
+        // single frame needs more glyphs than fit in the atlas.
+        if needs_redraw && !(overflowed && self.fonts_atlas_overflowed_last_draw) {
+            cx.invalidate_view_caches();
+        }
+        self.fonts_atlas_overflowed_last_draw = overflowed;
+
         // we need to start a pass that just uses the texture
         if !cx.fonts_data.read().unwrap().fonts_atlas.atlas_todo.is_empty() {
             self.atlas_pass.begin_pass_without_textures(cx);
@@ -395,6 +519,21 @@ impl CxAfterDraw {
             let mut atlas_todo = Vec::new();
             std::mem::swap(&mut cx.fonts_data.write().unwrap().fonts_atlas.atlas_todo, &mut atlas_todo);
 
+            // Clear the space of evicted glyphs first. The shader outputs an alpha of 1 for channel 4, which
+            // overwrites whatever was there because of premultiplied alpha blending. Trapezoids get drawn with
+            // a pixel of padding, so shrink the rects by that.
+            let clear_instances: Vec<[f32; 7]> = std::mem::take(&mut cx.fonts_data.write().unwrap().fonts_atlas.clear_todo)
+                .into_iter()
+                .map(|rect| {
+                    let (x1, y1) = (rect.pos.x + 1., rect.pos.y + 1.);
+                    let (x2, y2) = (rect.pos.x + rect.size.x - 1., rect.pos.y + rect.size.y - 1.);
+                    [x1, x2, y1, y1, y2, y2, 4.]
+                })
+                .collect();
+            if !clear_instances.is_empty() {
+                cx.add_instances(&SHADER, &clear_instances);
+            }
+
             let mut instances = vec![];
             for todo in atlas_todo {
                 self.trapezoid_text.draw_todo(cx, todo, &mut instances);
@@ -405,23 +544,92 @@ impl CxAfterDraw {
             self.atlas_view.end_view(cx);
             self.atlas_pass.end_pass(cx);
         }
+        self.write_color_glyphs(cx);
         //println!("TOTALT TIME {}", Cx::profile_time_ns() - start);
     }
+
+    /// Write new glyphs of [`CxColorGlyphAtlas`] into its texture.
+    fn write_color_glyphs(&mut self, cx: &mut Cx) {
+        let mut write_fonts_data = cx.fonts_data.write().unwrap();
+        let atlas = &mut write_fonts_data.color_glyph_atlas;
+        if atlas.todo.is_empty() && !atlas.cleared {
+            self.color_glyph_atlas_cleared_last_draw = false;
+            return;
+        }
+        let todo = std::mem::take(&mut atlas.todo);
+        let cleared = std::mem::replace(&mut atlas.cleared, false);
+        let texture_handle = atlas.texture_handle.unwrap();
+        let glyph_pixels: Vec<(CxColorGlyphAtlasTodo, Option<Vec<[u8; 4]>>)> = todo
+            .into_iter()
+            .map(|todo| {
+                let cxfont = &write_fonts_data.fonts[todo.font_id];
+                let pixels = match cxfont.font_loaded.as_ref().and_then(|font| font.color_glyphs.get(&todo.glyph_id)) {
+                    Some(zaplib_vector::font::ColorGlyph::Bitmap(bitmap)) => {
+                        render_color_bitmap(&cxfont.font_bytes, bitmap, todo.width, todo.height)
+                    }
+                    _ => None,
+                };
+                (todo, pixels)
+            })
+            .collect();
+        drop(write_fonts_data);
+
+        let image = texture_handle.get_image_mut(cx);
+        if cleared {
+            image.iter_mut().for_each(|pixel| *pixel = 0);
+        }
+        for (todo, pixels) in glyph_pixels {
+            // Glyphs that we can't decode stay transparent.
+            let pixels = match pixels {
+                Some(pixels) => pixels,
+                None => continue,
+            };
+            for (row_index, row) in pixels.chunks_exact(todo.width).enumerate() {
+                let start = (todo.y + row_index) * COLOR_GLYPH_ATLAS_SIZE + todo.x;
+                for (target, pixel) in image[start..start + todo.width].iter_mut().zip(row) {
+                    *target = u32::from_le_bytes(*pixel);
+                }
+            }
+        }
+
+        // Text that was drawn before the atlas got cleared uses glyphs that are gone now.
+        if cleared && !self.color_glyph_atlas_cleared_last_draw {
+            cx.invalidate_view_caches();
+        }
+        self.color_glyph_atlas_cleared_last_draw = cleared;
+    }
 }
 
 #[derive(Default, Debug, Clone)]
 pub(crate) struct CxFont {
     pub(crate) font_loaded: Option<zaplib_vector::font::VectorFont>,
     pub(crate) atlas_pages: Vec<CxFontAtlasPage>,
+    /// Indices in [`CxFontsData::fonts`] to use for characters that this font doesn't have, in order.
+    /// See [`Cx::set_font_fallbacks`].
+    pub(crate) fallbacks: Vec<usize>,
+    /// The font file, which is needed for text shaping, since [`CxFont::font_loaded`] only has the outlines.
+    pub(crate) font_bytes: Cow<'static, [u8]>,
+    /// The index of the font in [`CxFont::font_bytes`], for font collections (`.ttc` files).
+    pub(crate) font_index: u32,
 }
 
 const ATLAS_SUBPIXEL_SLOTS: usize = 64;
 
 #[derive(Clone, Debug)]
 pub(crate) struct CxFontAtlasPage {
-    dpi_factor: f32,
+    pub(crate) dpi_factor: f32,
     font_size: f32,
-    pub(crate) atlas_glyphs: Vec<[Option<CxFontAtlasGlyph>; ATLAS_SUBPIXEL_SLOTS]>,
+    /// Keyed by glyph id and subpixel id (up to [`ATLAS_SUBPIXEL_SLOTS`]). Only contains glyphs that are currently in
+    /// the atlas, so this stays small even for fonts with lots of glyphs, like CJK fonts.
+    pub(crate) atlas_glyphs: HashMap<(usize, usize), CxFontAtlasEntry>,
+}
+
+/// A glyph in [`CxFontsAtlas`].
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct CxFontAtlasEntry {
+    pub(crate) tc: CxFontAtlasGlyph,
+    /// Index in [`CxFontsAtlas::shelves`].
+    pub(crate) shelf_id: usize,
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -444,15 +652,47 @@ pub(crate) struct CxFontsAtlasTodo {
     pub(crate) subpixel_id: usize,
 }
 
+/// Width and height of the texture of [`CxFontsAtlas`] when starting out.
+const FONTS_ATLAS_INITIAL_SIZE: f32 = 2048.0;
+/// Width and height that the texture of [`CxFontsAtlas`] can grow to. Pretty much all GPUs support this size,
+/// including those of phones and WebGL implementations.
+const FONTS_ATLAS_MAX_SIZE: f32 = 4096.0;
+
+/// A row of glyphs in [`CxFontsAtlas`]. Glyphs get evicted per shelf, since we can't easily reuse the space of
+/// individual glyphs of different sizes.
+#[derive(Debug, Default)]
+pub(crate) struct CxFontsAtlasShelf {
+    y: f32,
+    height: f32,
+    alloc_x: f32,
+    /// The last [`CxFontsAtlas::generation`] in which any of its glyphs was drawn. Atomic so it can be updated
+    /// while only holding a read lock on [`CxFontsData`].
+    last_used: AtomicU64,
+    /// The font id, [`CxFontAtlasPage`] index, glyph id, and subpixel id of every glyph in the shelf.
+    glyphs: Vec<(usize, usize, usize, usize)>,
+}
+
 /// An "atlas" for font glyphs, which is like a cached version of glyphs.
+///
+/// Glyphs are packed in shelves. When the atlas is full, we first grow it up to [`FONTS_ATLAS_MAX_SIZE`], and after
+/// that evict the least recently used shelf. Both mean that text that was drawn earlier has to be redrawn, which
+/// [`CxAfterDraw::after_draw`] takes care of.
 #[derive(Debug, Default)]
 pub(crate) struct CxFontsAtlas {
     texture_handle: Option<TextureHandle>,
     texture_size: Vec2,
     clear_buffer: bool,
-    alloc_xpos: f32,
-    alloc_ypos: f32,
-    alloc_hmax: f32,
+    shelves: Vec<CxFontsAtlasShelf>,
+    /// Where the next shelf starts.
+    next_shelf_y: f32,
+    /// Incremented after every draw, for tracking when shelves were last used.
+    generation: u64,
+    /// Space of evicted shelves that has to be cleared before drawing [`CxFontsAtlas::atlas_todo`].
+    clear_todo: Vec<Rect>,
+    /// Whether glyphs got moved or evicted, so text that was drawn before has to be redrawn.
+    needs_redraw: bool,
+    /// Whether we had to evict glyphs that were used in the current draw, so redrawing might not help.
+    overflowed: bool,
     pub(crate) atlas_todo: Vec<CxFontsAtlasTodo>,
 }
 
@@ -474,23 +714,14 @@ pub fn get_font_atlas_page_id(
         }
     }
 
-    let glyphs_len = match &fonts_data_read_lock.fonts[font_id].font_loaded {
-        Some(font) => font.glyphs.len(),
-        _ => panic!("Font not loaded {}", font_id),
-    };
+    if fonts_data_read_lock.fonts[font_id].font_loaded.is_none() {
+        panic!("Font not loaded {}", font_id);
+    }
     drop(fonts_data_read_lock);
 
     let glyph_index = {
         let write_fonts_atlas_pages = &mut fonts_data.write().unwrap().fonts[font_id].atlas_pages;
-        write_fonts_atlas_pages.push(CxFontAtlasPage {
-            dpi_factor,
-            font_size,
-            atlas_glyphs: {
-                let mut v = Vec::new();
-                v.resize(glyphs_len, [None; ATLAS_SUBPIXEL_SLOTS]);
-                v
-            },
-        });
+        write_fonts_atlas_pages.push(CxFontAtlasPage { dpi_factor, font_size, atlas_glyphs: HashMap::new() });
         write_fonts_atlas_pages.len() - 1
     };
 
@@ -498,30 +729,253 @@ pub fn get_font_atlas_page_id(
 }
 
 impl CxFontsAtlas {
-    pub fn alloc_atlas_glyph(&mut self, w: f32, h: f32) -> CxFontAtlasGlyph {
-        if w + self.alloc_xpos >= self.texture_size.x {
-            self.alloc_xpos = 0.0;
-            self.alloc_ypos += self.alloc_hmax + 1.0;
-            self.alloc_hmax = 0.0;
+    /// Mark a glyph as used in the current draw, so it doesn't get evicted.
+    pub(crate) fn touch(&self, entry: &CxFontAtlasEntry) {
+        self.shelves[entry.shelf_id].last_used.store(self.generation, Ordering::Relaxed);
+    }
+
+    /// Find space in an existing shelf, or start a new shelf. Returns the shelf id and x position.
+    fn alloc_in_shelf(&mut self, w: f32, h: f32) -> Option<(usize, f32)> {
+        // Prefer shelves that aren't much higher than the glyph, so we don't waste too much space.
+        let texture_width = self.texture_size.x;
+        let find_shelf = |shelves: &[CxFontsAtlasShelf], max_height: f32| {
+            shelves
+                .iter()
+                .enumerate()
+                .filter(|(_, shelf)| shelf.height >= h && shelf.height <= max_height && shelf.alloc_x + w < texture_width)
+                .min_by(|(_, a), (_, b)| a.height.partial_cmp(&b.height).unwrap())
+                .map(|(shelf_id, _)| shelf_id)
+        };
+
+        let shelf_id = if let Some(shelf_id) = find_shelf(&self.shelves, (h * 1.5).max(h + 4.)) {
+            shelf_id
+        } else if self.next_shelf_y + h < self.texture_size.y {
+            let height = h.ceil();
+            self.shelves.push(CxFontsAtlasShelf { y: self.next_shelf_y, height, ..CxFontsAtlasShelf::default() });
+            self.next_shelf_y += height + 1.0;
+            self.shelves.len() - 1
+        } else {
+            find_shelf(&self.shelves, f32::INFINITY)?
+        };
+
+        let shelf = &mut self.shelves[shelf_id];
+        let x = shelf.alloc_x;
+        shelf.alloc_x += w + 1.0;
+        Some((shelf_id, x))
+    }
+}
+
+impl CxFontsData {
+    /// Allocate space for a new glyph in [`CxFontsAtlas`], and schedule drawing it.
+    pub(crate) fn alloc_atlas_glyph(&mut self, todo: CxFontsAtlasTodo, w: f32, h: f32) -> CxFontAtlasGlyph {
+        debug_assert!(todo.subpixel_id < ATLAS_SUBPIXEL_SLOTS);
+        debug_assert!(self.fonts_atlas.texture_size.x > 0.0, "The atlas size gets set in CxFontsData::new");
+        // Crop glyphs that wouldn't even fit in an empty atlas of the maximum size, since otherwise we'd keep
+        // evicting everything without ever finding space for them.
+        let w = w.min(FONTS_ATLAS_MAX_SIZE - 2.0);
+        let h = h.min(FONTS_ATLAS_MAX_SIZE - 2.0);
+        let (shelf_id, x) = loop {
+            if let Some(alloc) = self.fonts_atlas.alloc_in_shelf(w, h) {
+                break alloc;
+            }
+
+            let atlas = &mut self.fonts_atlas;
+            if atlas.texture_size.x < FONTS_ATLAS_MAX_SIZE {
+                // Growing changes the texture coordinates of all glyphs, so we might as well start from scratch.
+                let texture_size = atlas.texture_size * 2.0;
+                self.reset_fonts_atlas();
+                self.fonts_atlas.texture_size = texture_size;
+                continue;
+            }
+
+            // Evict the least recently used shelf that's high enough, or everything if there is none.
+            let lru_shelf_id = atlas
+                .shelves
+                .iter()
+                .enumerate()
+                .filter(|(_, shelf)| shelf.height >= h)
+                .min_by_key(|(_, shelf)| shelf.last_used.load(Ordering::Relaxed))
+                .map(|(shelf_id, _)| shelf_id);
+            match lru_shelf_id {
+                Some(shelf_id) => self.evict_atlas_shelf(shelf_id),
+                None => {
+                    self.reset_fonts_atlas();
+                    self.fonts_atlas.overflowed = true;
+                }
+            }
+        };
+
+        let atlas = &mut self.fonts_atlas;
+        let shelf = &mut atlas.shelves[shelf_id];
+        shelf.last_used.store(atlas.generation, Ordering::Relaxed);
+        shelf.glyphs.push((todo.font_id, todo.atlas_page_id, todo.glyph_id, todo.subpixel_id));
+
+        let tx1 = x / atlas.texture_size.x;
+        let ty1 = shelf.y / atlas.texture_size.y;
+        let tc = CxFontAtlasGlyph { tx1, ty1, tx2: tx1 + (w / atlas.texture_size.x), ty2: ty1 + (h / atlas.texture_size.y) };
+        self.fonts[todo.font_id].atlas_pages[todo.atlas_page_id]
+            .atlas_glyphs
+            .insert((todo.glyph_id, todo.subpixel_id), CxFontAtlasEntry { tc, shelf_id });
+        atlas.atlas_todo.push(todo);
+        tc
+    }
+
+    /// Remove all glyphs of a shelf from [`CxFontsAtlas`], so its space can be reused.
+    fn evict_atlas_shelf(&mut self, shelf_id: usize) {
+        let atlas = &mut self.fonts_atlas;
+        let shelf = &mut atlas.shelves[shelf_id];
+        if shelf.last_used.load(Ordering::Relaxed) == atlas.generation {
+            atlas.overflowed = true;
         }
-        if h + self.alloc_ypos >= self.texture_size.y {
-            println!("FONT ATLAS FULL, TODO FIX THIS");
+        for &(font_id, atlas_page_id, glyph_id, subpixel_id) in &shelf.glyphs {
+            self.fonts[font_id].atlas_pages[atlas_page_id].atlas_glyphs.remove(&(glyph_id, subpixel_id));
         }
-        if h > self.alloc_hmax {
-            self.alloc_hmax = h;
+        // Glyphs that haven't been drawn into the texture yet don't exist anymore.
+        atlas
+            .atlas_todo
+            .retain(|todo| !shelf.glyphs.contains(&(todo.font_id, todo.atlas_page_id, todo.glyph_id, todo.subpixel_id)));
+        atlas.clear_todo.push(Rect { pos: vec2(0., shelf.y), size: vec2(atlas.texture_size.x, shelf.height + 1.0) });
+        shelf.glyphs.clear();
+        shelf.alloc_x = 0.0;
+        atlas.needs_redraw = true;
+    }
+
+    /// Remove all glyphs from [`CxFontsAtlas`].
+    pub(crate) fn reset_fonts_atlas(&mut self) {
+        for font in &mut self.fonts {
+            for atlas_page in &mut font.atlas_pages {
+                atlas_page.atlas_glyphs.clear();
+            }
         }
+        let atlas = &mut self.fonts_atlas;
+        atlas.shelves.clear();
+        atlas.next_shelf_y = 0.0;
+        atlas.atlas_todo.clear();
+        atlas.clear_todo.clear();
+        atlas.clear_buffer = true;
+        atlas.needs_redraw = true;
+    }
+}
 
-        let tx1 = self.alloc_xpos / self.texture_size.x;
-        let ty1 = self.alloc_ypos / self.texture_size.y;
+/// Width and height of the texture of [`CxColorGlyphAtlas`].
+const COLOR_GLYPH_ATLAS_SIZE: usize = 1024;
 
-        self.alloc_xpos += w + 1.0;
+/// A color glyph that still needs to be written into the texture of [`CxColorGlyphAtlas`].
+#[derive(Debug)]
+pub(crate) struct CxColorGlyphAtlasTodo {
+    font_id: usize,
+    glyph_id: usize,
+    x: usize,
+    y: usize,
+    width: usize,
+    height: usize,
+}
+
+/// An atlas for color bitmap glyphs (see [`zaplib_vector::font::ColorBitmap`]), like emoji. Unlike [`CxFontsAtlas`],
+/// which gets rendered on the GPU, these are PNG images that we decode and scale on the CPU, in
+/// [`CxAfterDraw::after_draw`].
+///
+/// When it's full, everything gets thrown out and redrawn, which is fine since there are typically only a few
+/// different emoji on the screen.
+#[derive(Debug, Default)]
+pub(crate) struct CxColorGlyphAtlas {
+    texture_handle: Option<TextureHandle>,
+    alloc_xpos: usize,
+    alloc_ypos: usize,
+    alloc_hmax: usize,
+    /// Keyed by font id, [`CxFontAtlasPage`] index (for the size), and glyph id.
+    glyphs: HashMap<(usize, usize, usize), CxFontAtlasGlyph>,
+    todo: Vec<CxColorGlyphAtlasTodo>,
+    /// Whether the texture needs to be cleared before writing [`CxColorGlyphAtlas::todo`].
+    cleared: bool,
+}
 
-        if h > self.alloc_hmax {
-            self.alloc_hmax = h;
+impl CxColorGlyphAtlas {
+    /// Get the texture coordinates of a glyph, allocating space for it if needed. The bottom of the glyph is at
+    /// `tx1`/`ty1`, like in [`CxFontsAtlas`]. Returns [`None`] if the glyph doesn't fit in the atlas at all.
+    pub(crate) fn get_or_alloc_glyph(
+        &mut self,
+        font_id: usize,
+        atlas_page_id: usize,
+        glyph_id: usize,
+        width: usize,
+        height: usize,
+    ) -> Option<CxFontAtlasGlyph> {
+        if let Some(glyph) = self.glyphs.get(&(font_id, atlas_page_id, glyph_id)) {
+            return Some(*glyph);
+        }
+        if width > COLOR_GLYPH_ATLAS_SIZE || height > COLOR_GLYPH_ATLAS_SIZE {
+            return None;
+        }
+        if self.alloc_xpos + width > COLOR_GLYPH_ATLAS_SIZE {
+            self.alloc_xpos = 0;
+            self.alloc_ypos += self.alloc_hmax + 1;
+            self.alloc_hmax = 0;
+        }
+        if self.alloc_ypos + height > COLOR_GLYPH_ATLAS_SIZE {
+            self.clear();
         }
 
-        CxFontAtlasGlyph { tx1, ty1, tx2: tx1 + (w / self.texture_size.x), ty2: ty1 + (h / self.texture_size.y) }
+        let (x, y) = (self.alloc_xpos, self.alloc_ypos);
+        self.alloc_xpos += width + 1;
+        self.alloc_hmax = self.alloc_hmax.max(height);
+        self.todo.push(CxColorGlyphAtlasTodo { font_id, glyph_id, x, y, width, height });
+
+        let size = COLOR_GLYPH_ATLAS_SIZE as f32;
+        let glyph = CxFontAtlasGlyph {
+            tx1: x as f32 / size,
+            ty1: (y + height) as f32 / size,
+            tx2: (x + width) as f32 / size,
+            ty2: y as f32 / size,
+        };
+        self.glyphs.insert((font_id, atlas_page_id, glyph_id), glyph);
+        Some(glyph)
     }
+
+    /// Throw out all glyphs. Text that was already drawn using them has to be redrawn.
+    pub(crate) fn clear(&mut self) {
+        self.alloc_xpos = 0;
+        self.alloc_ypos = 0;
+        self.alloc_hmax = 0;
+        self.glyphs.clear();
+        self.todo.clear();
+        self.cleared = true;
+    }
+}
+
+/// Decode a color bitmap glyph and scale it to `width` by `height` pixels, by averaging the pixels that fall within
+/// each target pixel. Returns RGBA pixels with premultiplied alpha, from top to bottom.
+fn render_color_bitmap(
+    bytes: &[u8],
+    bitmap: &zaplib_vector::font::ColorBitmap,
+    width: usize,
+    height: usize,
+) -> Option<Vec<[u8; 4]>> {
+    let png = crate::png::decode_png(bytes.get(bitmap.png_range.clone())?)?;
+    if png.width == 0 || png.height == 0 {
+        return None;
+    }
+    let mut pixels = Vec::with_capacity(width * height);
+    for y in 0..height {
+        let y_start = y * png.height / height;
+        let y_end = ((y + 1) * png.height / height).max(y_start + 1);
+        for x in 0..width {
+            let x_start = x * png.width / width;
+            let x_end = ((x + 1) * png.width / width).max(x_start + 1);
+            let mut sum = [0u32; 4];
+            for source_y in y_start..y_end {
+                for &[r, g, b, a] in &png.pixels[source_y * png.width + x_start..source_y * png.width + x_end] {
+                    sum[0] += r as u32 * a as u32 / 255;
+                    sum[1] += g as u32 * a as u32 / 255;
+                    sum[2] += b as u32 * a as u32 / 255;
+                    sum[3] += a as u32;
+                }
+            }
+            let count = ((y_end - y_start) * (x_end - x_start)) as u32;
+            pixels.push([(sum[0] / count) as u8, (sum[1] / count) as u8, (sum[2] / count) as u8, (sum[3] / count) as u8]);
+        }
+    }
+    Some(pixels)
 }
 
 /// A context object containing everything font releated. This is used in different places to render text
@@ -532,14 +986,271 @@ pub struct CxFontsData {
     pub(crate) fonts: Vec<CxFont>,
     /// See [`CxFontsAtlas`].
     pub(crate) fonts_atlas: CxFontsAtlas,
+    /// See [`crate::text_shaping`].
+    pub(crate) shaping_cache: ShapingCache,
+    /// See [`CxColorGlyphAtlas`].
+    pub(crate) color_glyph_atlas: CxColorGlyphAtlas,
 }
 
 impl CxFontsData {
+    pub(crate) fn new() -> Self {
+        let mut fonts_data = Self::default();
+        // Set right away rather than when creating the texture in `CxAfterDraw::new`, so that glyphs get valid
+        // texture coordinates even without a `CxAfterDraw`, like in tests.
+        fonts_data.fonts_atlas.texture_size = Vec2 { x: FONTS_ATLAS_INITIAL_SIZE, y: FONTS_ATLAS_INITIAL_SIZE };
+        fonts_data
+    }
+
     pub fn get_fonts_atlas_texture_handle(&self) -> TextureHandle {
         self.fonts_atlas.texture_handle.unwrap()
     }
 
+    pub(crate) fn get_color_glyph_atlas_texture_handle(&self) -> TextureHandle {
+        self.color_glyph_atlas.texture_handle.unwrap()
+    }
+
     pub fn new_dummy_for_tests() -> Self {
-        CxFontsData::default()
+        CxFontsData::new()
+    }
+
+    /// Approximate heap usage in bytes, for [`Cx::memory_stats`]. Doesn't include fonts that are compiled into the
+    /// binary, or the outlines in [`CxFont::font_loaded`].
+    pub(crate) fn memory_usage(&self) -> usize {
+        let fonts: usize = self
+            .fonts
+            .iter()
+            .map(|font| {
+                let font_bytes = match &font.font_bytes {
+                    Cow::Borrowed(_) => 0,
+                    Cow::Owned(bytes) => bytes.capacity(),
+                };
+                let atlas_pages: usize = font
+                    .atlas_pages
+                    .iter()
+                    .map(|page| page.atlas_glyphs.capacity() * std::mem::size_of::<((usize, usize), CxFontAtlasEntry)>())
+                    .sum();
+                font_bytes + atlas_pages + font.fallbacks.capacity() * std::mem::size_of::<usize>()
+            })
+            .sum();
+        let shaping_cache: usize = self
+            .shaping_cache
+            .iter()
+            .map(|((_, text), clusters)| {
+                let glyphs: usize =
+                    clusters.iter().map(|cluster| cluster.glyphs.capacity() * std::mem::size_of::<ShapedGlyph>()).sum();
+                text.capacity() + clusters.capacity() * std::mem::size_of::<ShapedCluster>() + glyphs
+            })
+            .sum();
+        let shelves: usize = self
+            .fonts_atlas
+            .shelves
+            .iter()
+            .map(|shelf| {
+                std::mem::size_of::<CxFontsAtlasShelf>()
+                    + shelf.glyphs.capacity() * std::mem::size_of::<(usize, usize, usize, usize)>()
+            })
+            .sum();
+        let atlases = shelves
+            + self.fonts_atlas.atlas_todo.capacity() * std::mem::size_of::<CxFontsAtlasTodo>()
+            + self.color_glyph_atlas.glyphs.capacity() * std::mem::size_of::<((usize, usize, usize), CxFontAtlasGlyph)>()
+            + self.color_glyph_atlas.todo.capacity() * std::mem::size_of::<CxColorGlyphAtlasTodo>();
+        fonts + shaping_cache + atlases
+    }
+}
+
+impl CxFontsData {
+    /// The font and glyph to draw `c` with: the glyph in `font_id` if it has one, and otherwise the glyph in the first
+    /// of its [`CxFont::fallbacks`] that has one. Falls back to the "missing glyph" of `font_id`.
+    pub(crate) fn get_glyph(&self, font_id: usize, c: char) -> (usize, usize) {
+        let cxfont = &self.fonts[font_id];
+        let glyph_id = cxfont.font_loaded.as_ref().map_or(0, |font| font.glyph_index(c));
+        if glyph_id == 0 && !c.is_control() {
+            for &fallback_id in &cxfont.fallbacks {
+                if let Some(fallback_glyph_id) = self.fonts[fallback_id].font_loaded.as_ref().map(|font| font.glyph_index(c)) {
+                    if fallback_glyph_id != 0 {
+                        return (fallback_id, fallback_glyph_id);
+                    }
+                }
+            }
+        }
+        (font_id, glyph_id)
+    }
+}
+
+/// Normalize a font name for comparing, so e.g. "DejaVu Sans" matches "DejaVuSans".
+fn normalize_font_name(name: &str) -> String {
+    name.chars().filter(|c| c.is_alphanumeric()).flat_map(char::to_lowercase).collect()
+}
+
+/// Directories where fonts are installed on this platform.
+fn system_font_dirs() -> Vec<std::path::PathBuf> {
+    use std::path::PathBuf;
+
+    let env_dir = |var: &str, path: &str| std::env::var_os(var).map(|dir| PathBuf::from(dir).join(path));
+    if cfg!(target_os = "windows") {
+        vec![
+            env_dir("WINDIR", "Fonts").unwrap_or_else(|| PathBuf::from("C:\\Windows\\Fonts")),
+            env_dir("LOCALAPPDATA", "Microsoft\\Windows\\Fonts").unwrap_or_default(),
+        ]
+    } else if cfg!(any(target_os = "macos", target_os = "ios")) {
+        vec![
+            PathBuf::from("/System/Library/Fonts"),
+            PathBuf::from("/Library/Fonts"),
+            env_dir("HOME", "Library/Fonts").unwrap_or_default(),
+        ]
+    } else if cfg!(target_os = "android") {
+        vec![PathBuf::from("/system/fonts")]
+    } else {
+        vec![
+            PathBuf::from("/usr/share/fonts"),
+            PathBuf::from("/usr/local/share/fonts"),
+            env_dir("XDG_DATA_HOME", "fonts").or_else(|| env_dir("HOME", ".local/share/fonts")).unwrap_or_default(),
+            env_dir("HOME", ".fonts").unwrap_or_default(),
+        ]
+    }
+}
+
+/// Find an installed font by name. Prefers fonts whose full name matches (like "DejaVu Sans" or "DejaVu Sans Regular"),
+/// over fonts whose family name matches (like "DejaVu Sans Bold").
+///
+/// Returns the parsed font, the bytes of the font file, and the index of the font in the file.
+fn find_system_font(name: &str) -> Option<(zaplib_vector::font::VectorFont, Vec<u8>, u32)> {
+    use zaplib_vector::ttf_parser::{font_count, parse_ttf_names, parse_ttf_with_index};
+
+    if cfg!(target_arch = "wasm32") {
+        return None;
+    }
+
+    fn collect_font_files(dir: &std::path::Path, files: &mut Vec<std::path::PathBuf>) {
+        if let Ok(entries) = std::fs::read_dir(dir) {
+            for path in entries.flatten().map(|entry| entry.path()) {
+                if path.is_dir() {
+                    collect_font_files(&path, files);
+                } else if let Some(extension) = path.extension().and_then(|extension| extension.to_str()) {
+                    if ["ttf", "ttc", "otf"].contains(&extension.to_lowercase().as_str()) {
+                        files.push(path);
+                    }
+                }
+            }
+        }
+    }
+
+    let mut files = Vec::new();
+    for dir in system_font_dirs() {
+        collect_font_files(&dir, &mut files);
+    }
+
+    let name = normalize_font_name(name);
+    let regular_name = format!("{}regular", name);
+    // Font files are often named after the font, so try those first, to avoid reading every font file.
+    let mut files: Vec<(bool, std::path::PathBuf)> = files
+        .into_iter()
+        .map(|path| {
+            let stem = path.file_stem().and_then(|stem| stem.to_str()).map(normalize_font_name).unwrap_or_default();
+            (stem.starts_with(&name), path)
+        })
+        .collect();
+    files.sort_by_key(|(stem_matches, _)| !stem_matches);
+
+    let mut family_match = None;
+    for (stem_matches, path) in files {
+        // Only keep looking for a better match in files that are named after the font.
+        if !stem_matches && family_match.is_some() {
+            break;
+        }
+        let bytes = match std::fs::read(&path) {
+            Ok(bytes) => bytes,
+            Err(_) => continue,
+        };
+        for index in 0..font_count(&bytes) {
+            let names = parse_ttf_names(&bytes, index).unwrap_or_default();
+            let matches = |name_ids: &[u16], names_to_match: &[&str]| {
+                names.iter().any(|(name_id, font_name)| {
+                    name_ids.contains(name_id) && names_to_match.contains(&normalize_font_name(font_name).as_str())
+                })
+            };
+            if matches(&[4], &[name.as_str(), regular_name.as_str()]) {
+                // Fonts that we can't parse (e.g. with CFF outlines) are skipped, since there might be another one
+                // with the same name that we can.
+                if let Ok(font) = parse_ttf_with_index(&bytes, index) {
+                    return Some((font, bytes, index as u32));
+                }
+            } else if family_match.is_none() && matches(&[1, 16], &[name.as_str()]) {
+                family_match = parse_ttf_with_index(&bytes, index).ok().map(|font| (font, bytes.clone(), index as u32));
+            }
+        }
+    }
+    family_match
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn alloc(fonts_data: &mut CxFontsData, glyph_id: usize) -> CxFontAtlasGlyph {
+        fonts_data.alloc_atlas_glyph(CxFontsAtlasTodo { glyph_id, ..CxFontsAtlasTodo::default() }, 100.0, 100.0)
+    }
+
+    fn atlas_glyphs(fonts_data: &CxFontsData) -> &HashMap<(usize, usize), CxFontAtlasEntry> {
+        &fonts_data.fonts[0].atlas_pages[0].atlas_glyphs
+    }
+
+    #[test]
+    fn test_fonts_atlas_growth_and_eviction() {
+        let mut fonts_data = CxFontsData::default();
+        fonts_data.fonts.push(CxFont {
+            atlas_pages: vec![CxFontAtlasPage { dpi_factor: 1.0, font_size: 10.0, atlas_glyphs: HashMap::new() }],
+            ..CxFont::default()
+        });
+        fonts_data.fonts_atlas.texture_size = vec2(FONTS_ATLAS_MAX_SIZE / 2.0, FONTS_ATLAS_MAX_SIZE / 2.0);
+
+        // Fill up the atlas until it grows, which throws out all glyphs.
+        let mut glyph_id = 0;
+        while fonts_data.fonts_atlas.texture_size.x < FONTS_ATLAS_MAX_SIZE {
+            alloc(&mut fonts_data, glyph_id);
+            glyph_id += 1;
+        }
+        assert_eq!(atlas_glyphs(&fonts_data).len(), 1);
+        assert_eq!(fonts_data.fonts_atlas.atlas_todo.len(), 1);
+        assert!(fonts_data.fonts_atlas.needs_redraw);
+        fonts_data.fonts_atlas.needs_redraw = false;
+
+        // 40 shelves of 40 glyphs fit at the maximum size.
+        let first_glyph_id = glyph_id - 1;
+        for _ in 1..1600 {
+            alloc(&mut fonts_data, glyph_id);
+            glyph_id += 1;
+        }
+        assert_eq!(atlas_glyphs(&fonts_data).len(), 1600);
+        assert!(!fonts_data.fonts_atlas.needs_redraw);
+
+        // In the next draw we use the first glyph, so the least recently used shelf is the second one.
+        fonts_data.fonts_atlas.generation += 1;
+        let first_entry = atlas_glyphs(&fonts_data)[&(first_glyph_id, 0)];
+        fonts_data.fonts_atlas.touch(&first_entry);
+        let new_glyph = alloc(&mut fonts_data, glyph_id);
+        assert_eq!(new_glyph.ty1, 101.0 / FONTS_ATLAS_MAX_SIZE);
+        assert_eq!(atlas_glyphs(&fonts_data).len(), 1600 - 40 + 1);
+        assert_eq!(fonts_data.fonts_atlas.atlas_todo.len(), 1600 - 40 + 1);
+        assert!(atlas_glyphs(&fonts_data).contains_key(&(first_glyph_id, 0)));
+        assert!(!atlas_glyphs(&fonts_data).contains_key(&(first_glyph_id + 40, 0)));
+        assert_eq!(fonts_data.fonts_atlas.clear_todo.len(), 1);
+        assert!(fonts_data.fonts_atlas.needs_redraw);
+        assert!(!fonts_data.fonts_atlas.overflowed);
+    }
+
+    #[test]
+    fn test_fonts_atlas_glyph_too_big() {
+        let mut fonts_data = CxFontsData::new();
+        fonts_data.fonts.push(CxFont {
+            atlas_pages: vec![CxFontAtlasPage { dpi_factor: 1.0, font_size: 10.0, atlas_glyphs: HashMap::new() }],
+            ..CxFont::default()
+        });
+
+        let todo = CxFontsAtlasTodo::default();
+        let glyph = fonts_data.alloc_atlas_glyph(todo, FONTS_ATLAS_MAX_SIZE * 2.0, FONTS_ATLAS_MAX_SIZE * 2.0);
+        assert_eq!(fonts_data.fonts_atlas.texture_size, vec2(FONTS_ATLAS_MAX_SIZE, FONTS_ATLAS_MAX_SIZE));
+        assert!(glyph.tx2 <= 1.0 && glyph.ty2 <= 1.0);
+        assert_eq!(atlas_glyphs(&fonts_data).len(), 1);
     }
 }
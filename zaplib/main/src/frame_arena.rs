@@ -0,0 +1,112 @@
+//! A bump allocator for data that only lives during a single draw cycle.
+//!
+//! Allocating lots of small boxes every frame fragments the heap, which in WebAssembly can only ever grow. Instead,
+//! we allocate from a few big chunks, and reuse them every frame. After a frame that needed more than usual, we
+//! release the chunks that the next frame didn't need.
+
+use std::mem::{align_of, size_of};
+
+/// The minimum size of a chunk, in bytes.
+const FRAME_ARENA_CHUNK_SIZE: usize = 16 * 1024;
+
+/// Unit of allocation for chunks, to guarantee their alignment.
+#[derive(Clone, Copy)]
+#[repr(C, align(16))]
+struct Block([u8; 16]);
+
+/// See the module docs.
+#[derive(Default)]
+pub(crate) struct FrameArena {
+    chunks: Vec<Box<[Block]>>,
+    /// Index in [`FrameArena::chunks`] that we're currently allocating from.
+    current_chunk: usize,
+    /// Offset in the current chunk, in bytes.
+    current_offset: usize,
+    /// Bytes allocated since the last [`FrameArena::reset`], excluding alignment padding.
+    used: usize,
+}
+
+impl FrameArena {
+    /// Allocate `value` in the arena. Only `Copy` types are allowed, since nothing gets dropped.
+    pub(crate) fn alloc<T: Copy>(&mut self, value: T) -> &mut T {
+        let size = size_of::<T>();
+        let align = align_of::<T>();
+        assert!(align <= align_of::<Block>(), "Alignment of {align} is not supported in FrameArena");
+
+        loop {
+            if let Some(chunk) = self.chunks.get_mut(self.current_chunk) {
+                let offset = (self.current_offset + align - 1) / align * align;
+                if offset + size <= chunk.len() * size_of::<Block>() {
+                    self.current_offset = offset + size;
+                    self.used += size;
+                    // SAFETY: we just checked that `T` fits at `offset`, which is aligned, and we never hand out this
+                    // part of the chunk again until the next `reset`, which requires a mutable borrow.
+                    unsafe {
+                        let ptr = (chunk.as_mut_ptr() as *mut u8).add(offset) as *mut T;
+                        ptr.write(value);
+                        return &mut *ptr;
+                    }
+                }
+                self.current_chunk += 1;
+                self.current_offset = 0;
+            } else {
+                let blocks = (size.max(FRAME_ARENA_CHUNK_SIZE) + size_of::<Block>() - 1) / size_of::<Block>();
+                self.chunks.push(vec![Block([0; 16]); blocks].into_boxed_slice());
+            }
+        }
+    }
+
+    /// Make all memory available again, which invalidates previous allocations. Releases chunks that weren't
+    /// needed since the last reset.
+    pub(crate) fn reset(&mut self) {
+        self.chunks.truncate(self.current_chunk + 1);
+        self.current_chunk = 0;
+        self.current_offset = 0;
+        self.used = 0;
+    }
+
+    /// The total size of all chunks, in bytes.
+    pub(crate) fn capacity(&self) -> usize {
+        self.chunks.iter().map(|chunk| chunk.len() * size_of::<Block>()).sum()
+    }
+
+    /// Bytes allocated since the last [`FrameArena::reset`].
+    #[cfg(test)]
+    fn used(&self) -> usize {
+        self.used
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_alloc_and_reset() {
+        let mut arena = FrameArena::default();
+        assert_eq!(arena.capacity(), 0);
+
+        let a = arena.alloc(1u8) as *mut u8;
+        let b = arena.alloc([1.0f32, 2.0, 3.0, 4.0]) as *mut [f32; 4];
+        let c = arena.alloc(3u64) as *mut u64;
+        assert_eq!(b as usize % align_of::<f32>(), 0);
+        assert_eq!(c as usize % align_of::<u64>(), 0);
+        unsafe {
+            assert_eq!((*a, *b, *c), (1, [1.0, 2.0, 3.0, 4.0], 3));
+        }
+        assert_eq!(arena.used(), 1 + 16 + 8);
+        assert_eq!(arena.capacity(), FRAME_ARENA_CHUNK_SIZE);
+
+        // Allocations bigger than a chunk get their own chunk.
+        arena.alloc([0u8; FRAME_ARENA_CHUNK_SIZE + 1]);
+        assert_eq!(arena.capacity(), FRAME_ARENA_CHUNK_SIZE * 2 + 16);
+
+        // Chunks get reused after resetting, and the ones that aren't used get released on the next reset.
+        arena.reset();
+        assert_eq!(arena.used(), 0);
+        assert_eq!(arena.capacity(), FRAME_ARENA_CHUNK_SIZE * 2 + 16);
+        assert_eq!(*arena.alloc(5u32), 5);
+        arena.reset();
+        assert_eq!(arena.capacity(), FRAME_ARENA_CHUNK_SIZE);
+    }
+}
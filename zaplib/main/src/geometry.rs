@@ -34,13 +34,20 @@ impl Geometry {
         }
     }
 
-    pub(crate) fn vertices_f32_slice(&self) -> &[f32] {
+    /// The vertex attributes of all vertices, one after another; see [`Geometry::floats_per_vertex`].
+    pub fn vertices_f32_slice(&self) -> &[f32] {
         &self.vertex_attributes
     }
 
-    pub(crate) fn indices_u32_slice(&self) -> &[u32] {
+    /// The vertex indices of all triangles, 3 per triangle.
+    pub fn indices_u32_slice(&self) -> &[u32] {
         &self.triangle_indices
     }
+
+    /// The number of floats that every vertex in [`Geometry::vertices_f32_slice`] takes up.
+    pub fn floats_per_vertex(&self) -> usize {
+        self.vertex_number_of_bytes / std::mem::size_of::<f32>()
+    }
 }
 
 /// A pointer to a [`CxGpuGeometry`] (indexed in [`Cx::gpu_geometries`] using [`GpuGeometry::gpu_geometry_id`]),
@@ -0,0 +1,464 @@
+//! Grid layout on top of the box layout system (see [`crate::layout_api`]), loosely following
+//! [CSS Grid](https://developer.mozilla.org/en-US/docs/Web/CSS/CSS_Grid_Layout/Basic_Concepts_of_Grid_Layout).
+//!
+//! Like [`Flex`], a [`Grid`] measures its cells while drawing them, and uses the measurements of the previous draw
+//! to size [`GridTrack::Auto`] tracks. When measurements change, it calls [`Cx::request_draw`] so that the next draw
+//! uses the new ones.
+
+use crate::*;
+
+/// The size of a column or row of a [`Grid`].
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum GridTrack {
+    /// A fixed size.
+    Fix(f32),
+    /// As big as the biggest cell in the track. Only cells that span a single track are taken into account.
+    Auto,
+    /// A share of the space that is left after the [`GridTrack::Fix`] and [`GridTrack::Auto`] tracks, relative to
+    /// the other [`GridTrack::Fr`] tracks. Behaves like [`GridTrack::Auto`] if the [`Grid`] has a computed size
+    /// in that direction.
+    Fr(f32),
+}
+impl GridTrack {
+    /// TODO(JP): Replace these with GridTrack::default() when
+    /// <https://github.com/rust-lang/rust/issues/67792> gets done
+    pub const DEFAULT: GridTrack = GridTrack::Auto;
+}
+impl Default for GridTrack {
+    fn default() -> Self {
+        GridTrack::DEFAULT
+    }
+}
+
+/// Props for a [`Grid`]; see [`Grid::begin_grid`].
+#[derive(Clone, Debug)]
+pub struct GridProps {
+    /// The size of the [`Grid`] itself. Use a [`View`] around the [`Grid`] to scroll it when it's bigger than the
+    /// space that it's in.
+    pub layout_size: LayoutSize,
+    /// The columns of the grid. Cells outside of these columns create new [`GridTrack::Auto`] columns.
+    pub columns: Vec<GridTrack>,
+    /// The rows of the grid. Cells outside of these rows create new [`GridTrack::Auto`] rows.
+    pub rows: Vec<GridTrack>,
+    /// Named areas, for use with [`Grid::begin_area`]. Each string is a row, with the names of the area of each
+    /// column separated by whitespace, or `.` for columns that don't belong to an area. Areas have to be
+    /// rectangular. E.g.:
+    ///
+    /// ```ignore
+    /// areas: vec!["header header".to_string(), "sidebar main".to_string()],
+    /// ```
+    pub areas: Vec<String>,
+    /// Space between columns.
+    pub column_gap: f32,
+    /// Space between rows.
+    pub row_gap: f32,
+}
+impl GridProps {
+    /// TODO(JP): Replace these with GridProps::default() when
+    /// <https://github.com/rust-lang/rust/issues/67792> gets done
+    pub const DEFAULT: GridProps = GridProps {
+        layout_size: LayoutSize::DEFAULT,
+        columns: Vec::new(),
+        rows: Vec::new(),
+        areas: Vec::new(),
+        column_gap: 0.,
+        row_gap: 0.,
+    };
+}
+impl Default for GridProps {
+    fn default() -> Self {
+        GridProps::DEFAULT
+    }
+}
+
+/// Where to put a cell in a [`Grid`]; see [`Grid::begin_cell`].
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct GridCell {
+    /// The first column of the cell, starting at 0.
+    pub column: usize,
+    /// The first row of the cell, starting at 0.
+    pub row: usize,
+    /// How many columns the cell covers.
+    pub column_span: usize,
+    /// How many rows the cell covers.
+    pub row_span: usize,
+}
+impl GridCell {
+    /// TODO(JP): Replace these with GridCell::default() when
+    /// <https://github.com/rust-lang/rust/issues/67792> gets done
+    pub const DEFAULT: GridCell = GridCell { column: 0, row: 0, column_span: 1, row_span: 1 };
+
+    /// A cell covering a single column and row.
+    pub const fn new(column: usize, row: usize) -> Self {
+        Self { column, row, column_span: 1, row_span: 1 }
+    }
+}
+impl Default for GridCell {
+    fn default() -> Self {
+        GridCell::DEFAULT
+    }
+}
+
+/// Sizes and positions of the columns or rows of a [`Grid`].
+#[derive(Clone, Debug, Default, PartialEq)]
+struct GridTracks {
+    sizes: Vec<f32>,
+    /// Relative to the start of the [`Grid`].
+    offsets: Vec<f32>,
+    /// Whether we know the size of each track before drawing its cells, which is not the case for
+    /// [`GridTrack::Auto`] tracks that we haven't measured yet.
+    known: Vec<bool>,
+    gap: f32,
+}
+
+impl GridTracks {
+    /// Add [`GridTrack::Auto`] tracks with `content_sizes`, up to `count` tracks.
+    fn extend(&mut self, count: usize, content_sizes: &[f32]) {
+        while self.sizes.len() < count {
+            let index = self.sizes.len();
+            let offset = self.end();
+            let offset = if index == 0 { 0. } else { offset + self.gap };
+            let content_size = content_sizes.get(index).copied();
+            self.offsets.push(offset);
+            self.sizes.push(content_size.unwrap_or(0.));
+            self.known.push(content_size.is_some());
+        }
+    }
+
+    /// The position and size of a cell spanning `span` tracks starting at `start`, or [`None`] if we don't know
+    /// the size of all of these tracks.
+    fn span(&self, start: usize, span: usize) -> (f32, Option<f32>) {
+        let last = start + span.max(1) - 1;
+        let size = self.offsets[last] + self.sizes[last] - self.offsets[start];
+        (self.offsets[start], if self.known[start..=last].iter().all(|known| *known) { Some(size) } else { None })
+    }
+
+    /// Where the last track ends.
+    fn end(&self) -> f32 {
+        match (self.offsets.last(), self.sizes.last()) {
+            (Some(offset), Some(size)) => offset + size,
+            _ => 0.,
+        }
+    }
+}
+
+/// Compute the sizes and positions of tracks, given the sizes of what was drawn in each track (for
+/// [`GridTrack::Auto`] tracks and any tracks that we add beyond `tracks`), and the size of the grid if we know it.
+fn compute_grid_tracks(tracks: &[GridTrack], available: Option<f32>, gap: f32, content_sizes: &[f32]) -> GridTracks {
+    let count = tracks.len().max(content_sizes.len());
+    let track = |index: usize| tracks.get(index).copied().unwrap_or(GridTrack::Auto);
+    let fr_auto = available.is_none();
+
+    let mut sizes = Vec::with_capacity(count);
+    let mut known = Vec::with_capacity(count);
+    for index in 0..count {
+        let (size, is_known) = match track(index) {
+            GridTrack::Fix(size) => (size.max(0.), true),
+            GridTrack::Fr(_) if !fr_auto => (0., true),
+            GridTrack::Auto | GridTrack::Fr(_) => {
+                content_sizes.get(index).map_or((0., false), |content_size| (*content_size, true))
+            }
+        };
+        sizes.push(size);
+        known.push(is_known);
+    }
+
+    if let Some(available) = available {
+        let total_fr: f32 =
+            (0..count).filter_map(|index| if let GridTrack::Fr(fr) = track(index) { Some(fr.max(0.)) } else { None }).sum();
+        if total_fr > 0. {
+            let gaps = gap * count.saturating_sub(1) as f32;
+            let free = (available - sizes.iter().sum::<f32>() - gaps).max(0.);
+            for (index, size) in sizes.iter_mut().enumerate() {
+                if let GridTrack::Fr(fr) = track(index) {
+                    *size = free * fr.max(0.) / total_fr;
+                }
+            }
+        }
+    }
+
+    let mut offsets = Vec::with_capacity(count);
+    let mut offset = 0.;
+    for size in &sizes {
+        offsets.push(offset);
+        offset += size + gap;
+    }
+    GridTracks { sizes, offsets, known, gap }
+}
+
+/// Find a named area in [`GridProps::areas`].
+fn find_grid_area(areas: &[String], name: &str) -> Option<GridCell> {
+    let mut found: Option<(GridCell, usize)> = None;
+    for (row, columns) in areas.iter().enumerate() {
+        for (column, _) in columns.split_whitespace().enumerate().filter(|(_, area)| *area == name) {
+            let (cell, count) = found.get_or_insert((GridCell { column, row, column_span: 0, row_span: 0 }, 0));
+            let first_column = cell.column.min(column);
+            cell.column_span = (cell.column + cell.column_span).max(column + 1) - first_column;
+            cell.column = first_column;
+            cell.row_span = row + 1 - cell.row;
+            *count += 1;
+        }
+    }
+    found.map(|(cell, count)| {
+        assert!(count == cell.column_span * cell.row_span, "Grid area \"{}\" is not a rectangle", name);
+        cell
+    })
+}
+
+/// The cell that is currently being drawn.
+#[derive(Debug)]
+struct GridCurrentCell {
+    cell: GridCell,
+}
+
+/// State while drawing a [`Grid`].
+#[derive(Debug)]
+struct GridDraw {
+    areas: Vec<String>,
+    /// The position of the top-left corner of the [`Grid`].
+    origin: Vec2,
+    columns: GridTracks,
+    rows: GridTracks,
+    column_content_sizes: Vec<f32>,
+    row_content_sizes: Vec<f32>,
+    current_cell: Option<GridCurrentCell>,
+}
+
+/// A container that lays out its cells in columns and rows, like CSS Grid. Cells can span multiple columns and rows,
+/// and can be placed by index ([`Grid::begin_cell`]) or by name ([`Grid::begin_area`]). See [`GridProps`].
+///
+/// Keep this around between draws, since it uses the measurements of the previous draw (see the module docs).
+///
+/// ```ignore
+/// grid.begin_grid(cx, &GridProps {
+///     layout_size: LayoutSize::FILL,
+///     columns: vec![GridTrack::Fix(200.), GridTrack::Fr(1.)],
+///     rows: vec![GridTrack::Auto, GridTrack::Fr(1.)],
+///     areas: vec!["header header".to_string(), "sidebar main".to_string()],
+///     column_gap: 8.,
+///     row_gap: 8.,
+/// });
+/// grid.begin_area(cx, "header");
+/// // Draw the header..
+/// grid.end_cell(cx);
+/// grid.begin_area(cx, "sidebar");
+/// // Draw the sidebar..
+/// grid.end_cell(cx);
+/// grid.begin_area(cx, "main");
+/// // Draw the main content..
+/// grid.end_cell(cx);
+/// grid.end_grid(cx);
+/// ```
+#[derive(Debug, Default)]
+pub struct Grid {
+    previous_column_content_sizes: Vec<f32>,
+    previous_row_content_sizes: Vec<f32>,
+    draw: Option<GridDraw>,
+}
+
+impl Grid {
+    /// Start a [`Grid`]. Call [`Grid::begin_cell`] or [`Grid::begin_area`] and [`Grid::end_cell`] for each cell,
+    /// and then [`Grid::end_grid`].
+    pub fn begin_grid(&mut self, cx: &mut Cx, props: &GridProps) {
+        assert!(self.draw.is_none(), "Call end_grid before calling begin_grid again");
+        cx.begin_typed_box(CxBoxType::Grid, Layout { layout_size: props.layout_size, ..Layout::default() });
+        let layout_box = cx.layout_boxes.last().unwrap();
+        let width = if layout_box.width.is_nan() { None } else { Some(layout_box.available_width) };
+        let height = if layout_box.height.is_nan() { None } else { Some(layout_box.available_height) };
+
+        self.draw = Some(GridDraw {
+            areas: props.areas.clone(),
+            origin: layout_box.pos,
+            columns: compute_grid_tracks(&props.columns, width, props.column_gap, &self.previous_column_content_sizes),
+            rows: compute_grid_tracks(&props.rows, height, props.row_gap, &self.previous_row_content_sizes),
+            column_content_sizes: vec![0.; props.columns.len()],
+            row_content_sizes: vec![0.; props.rows.len()],
+            current_cell: None,
+        });
+    }
+
+    /// Start a cell of the [`Grid`]. Draw its contents and then call [`Grid::end_cell`].
+    ///
+    /// The cell gets a fixed size when we know the sizes of its tracks, so things inside of it can use
+    /// [`Width::Fill`] and [`Height::Fill`].
+    pub fn begin_cell(&mut self, cx: &mut Cx, cell: &GridCell) {
+        let draw = self.draw.as_mut().expect("Call begin_grid before begin_cell");
+        assert!(draw.current_cell.is_none(), "Call end_cell before calling begin_cell again");
+        let cell = GridCell { column_span: cell.column_span.max(1), row_span: cell.row_span.max(1), ..*cell };
+
+        draw.columns.extend(cell.column + cell.column_span, &self.previous_column_content_sizes);
+        draw.rows.extend(cell.row + cell.row_span, &self.previous_row_content_sizes);
+        let (x, width) = draw.columns.span(cell.column, cell.column_span);
+        let (y, height) = draw.rows.span(cell.row, cell.row_span);
+
+        cx.layout_boxes.last_mut().unwrap().pos = draw.origin + vec2(x, y);
+        draw.current_cell = Some(GridCurrentCell { cell });
+        cx.begin_typed_box(
+            CxBoxType::GridCell,
+            Layout {
+                layout_size: LayoutSize::new(
+                    width.map_or(Width::Compute, Width::Fix),
+                    height.map_or(Height::Compute, Height::Fix),
+                ),
+                ..Layout::default()
+            },
+        );
+    }
+
+    /// Start a cell of the [`Grid`] for a named area in [`GridProps::areas`]. Panics if there is no such area.
+    /// See [`Grid::begin_cell`].
+    pub fn begin_area(&mut self, cx: &mut Cx, name: &str) {
+        let draw = self.draw.as_ref().expect("Call begin_grid before begin_area");
+        let cell = find_grid_area(&draw.areas, name).unwrap_or_else(|| panic!("Grid area \"{}\" not found", name));
+        self.begin_cell(cx, &cell);
+    }
+
+    /// End the cell that was started with [`Grid::begin_cell`] or [`Grid::begin_area`]. Returns its [`Rect`].
+    pub fn end_cell(&mut self, cx: &mut Cx) -> Rect {
+        cx.assert_last_box_type_matches(CxBoxType::GridCell);
+        let draw = self.draw.as_mut().expect("Call begin_grid before end_cell");
+        let GridCurrentCell { cell } = draw.current_cell.take().expect("Call begin_cell before end_cell");
+
+        // Measure what got drawn.
+        let layout_box = cx.layout_boxes.last_mut().unwrap();
+        let content_width = if layout_box.bound_right_bottom.x == f32::NEG_INFINITY {
+            0.
+        } else {
+            layout_box.bound_right_bottom.x - layout_box.origin.x
+        };
+        let content_height = if layout_box.bound_right_bottom.y == f32::NEG_INFINITY {
+            0.
+        } else {
+            layout_box.bound_right_bottom.y - layout_box.origin.y
+        };
+        if layout_box.width.is_nan() {
+            layout_box.width = content_width;
+        }
+        if layout_box.height.is_nan() {
+            layout_box.height = content_height;
+        }
+
+        if cell.column_span == 1 {
+            if draw.column_content_sizes.len() <= cell.column {
+                draw.column_content_sizes.resize(cell.column + 1, 0.);
+            }
+            draw.column_content_sizes[cell.column] = draw.column_content_sizes[cell.column].max(content_width);
+        }
+        if cell.row_span == 1 {
+            if draw.row_content_sizes.len() <= cell.row {
+                draw.row_content_sizes.resize(cell.row + 1, 0.);
+            }
+            draw.row_content_sizes[cell.row] = draw.row_content_sizes[cell.row].max(content_height);
+        }
+
+        cx.end_typed_box(CxBoxType::GridCell)
+    }
+
+    /// End the [`Grid`] that was started with [`Grid::begin_grid`]. Returns its [`Rect`].
+    pub fn end_grid(&mut self, cx: &mut Cx) -> Rect {
+        let draw = self.draw.take().expect("Call begin_grid before end_grid");
+        assert!(draw.current_cell.is_none(), "Call end_cell before end_grid");
+
+        // A computed size covers all tracks, even if they're empty.
+        let layout_box = cx.layout_boxes.last_mut().unwrap();
+        if layout_box.width.is_nan() {
+            layout_box.width = draw.columns.end();
+        }
+        if layout_box.height.is_nan() {
+            layout_box.height = draw.rows.end();
+        }
+
+        let needs_relayout = draw.column_content_sizes != self.previous_column_content_sizes
+            || draw.row_content_sizes != self.previous_row_content_sizes;
+        self.previous_column_content_sizes = draw.column_content_sizes;
+        self.previous_row_content_sizes = draw.row_content_sizes;
+
+        let rect = cx.end_typed_box(CxBoxType::Grid);
+        if needs_relayout {
+            cx.request_draw();
+        }
+        rect
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compute_grid_tracks() {
+        let tracks = [GridTrack::Fix(100.), GridTrack::Auto, GridTrack::Fr(1.), GridTrack::Fr(3.)];
+        let computed = compute_grid_tracks(&tracks, Some(490.), 10., &[0., 60.]);
+        assert_eq!(computed.sizes, vec![100., 60., 75., 225.]);
+        assert_eq!(computed.offsets, vec![0., 110., 180., 265.]);
+        assert_eq!(computed.known, vec![true, true, true, true]);
+
+        // Without a size, fractional tracks are sized like auto tracks, and tracks we haven't measured are unknown.
+        let computed = compute_grid_tracks(&tracks, None, 10., &[0., 60., 20.]);
+        assert_eq!(computed.sizes, vec![100., 60., 20., 0.]);
+        assert_eq!(computed.known, vec![true, true, true, false]);
+
+        // Measured tracks beyond the defined ones are added as auto tracks.
+        let computed = compute_grid_tracks(&[GridTrack::Fix(10.)], None, 0., &[0., 30.]);
+        assert_eq!(computed.sizes, vec![10., 30.]);
+    }
+
+    #[test]
+    fn test_find_grid_area() {
+        let areas = vec!["header header header".to_string(), "sidebar main main".to_string(), "sidebar main main".to_string()];
+        assert_eq!(find_grid_area(&areas, "header"), Some(GridCell { column: 0, row: 0, column_span: 3, row_span: 1 }));
+        assert_eq!(find_grid_area(&areas, "sidebar"), Some(GridCell { column: 0, row: 1, column_span: 1, row_span: 2 }));
+        assert_eq!(find_grid_area(&areas, "main"), Some(GridCell { column: 1, row: 1, column_span: 2, row_span: 2 }));
+        assert_eq!(find_grid_area(&areas, "footer"), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "is not a rectangle")]
+    fn test_find_grid_area_not_rectangle() {
+        find_grid_area(&["a a".to_string(), "a b".to_string()], "a");
+    }
+
+    #[test]
+    fn test_grid_draw() {
+        let mut test_cx = TestCx::new(vec2(400., 300.));
+        let mut grid = Grid::default();
+        let mut rects = vec![];
+        let mut draw = |test_cx: &mut TestCx, rects: &mut Vec<Rect>| {
+            rects.clear();
+            test_cx.draw(|cx| {
+                grid.begin_grid(
+                    cx,
+                    &GridProps {
+                        layout_size: LayoutSize::new(Width::Fix(400.), Height::Fix(300.)),
+                        columns: vec![GridTrack::Fix(100.), GridTrack::Fr(1.)],
+                        rows: vec![GridTrack::Auto, GridTrack::Fr(1.)],
+                        areas: vec!["header header".to_string(), "sidebar main".to_string()],
+                        column_gap: 10.,
+                        row_gap: 10.,
+                    },
+                );
+                grid.begin_area(cx, "header");
+                cx.add_box(LayoutSize::new(Width::Fix(50.), Height::Fix(30.)));
+                rects.push(grid.end_cell(cx));
+                grid.begin_area(cx, "sidebar");
+                rects.push(grid.end_cell(cx));
+                grid.begin_area(cx, "main");
+                cx.add_box(LayoutSize::FILL);
+                rects.push(grid.end_cell(cx));
+                grid.end_grid(cx);
+            });
+        };
+
+        // The first draw measures the auto row, and the second one uses that.
+        draw(&mut test_cx, &mut rects);
+        draw(&mut test_cx, &mut rects);
+        let arranged = rects.clone();
+        draw(&mut test_cx, &mut rects);
+        assert_eq!(rects, arranged);
+
+        let origin = rects[0].pos;
+        assert_eq!(rects[0], Rect { pos: origin, size: vec2(400., 30.) });
+        assert_eq!(rects[1], Rect { pos: origin + vec2(0., 40.), size: vec2(100., 260.) });
+        assert_eq!(rects[2], Rect { pos: origin + vec2(110., 40.), size: vec2(290., 260.) });
+    }
+}
@@ -0,0 +1,132 @@
+//! Sub-allocation of the instances of draw calls from a few big GPU buffers, instead of having a GPU buffer per
+//! draw call that gets reallocated whenever its instances change. Allocating buffers is particularly expensive
+//! in WebGL, so this cuts down a lot on driver overhead there.
+//!
+//! Ranges are never written to twice. Instead, changed instances get a new range, and buffers get reused only when
+//! none of their ranges are in use anymore. At that point they get "orphaned" by allocating new storage for them,
+//! so that the driver doesn't have to wait for draws that might still use the old contents.
+
+/// The size of a pooled buffer, in floats (so 1MB).
+pub(crate) const INSTANCE_BUFFER_POOL_SIZE: usize = 256 * 1024;
+
+/// Where the instances of a draw call are stored.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct InstanceBufferRange {
+    /// The platform id of the GPU buffer.
+    pub(crate) buffer_id: usize,
+    /// The start of the range in the buffer, in floats.
+    pub(crate) offset: usize,
+    /// The length of the range, in floats.
+    pub(crate) len: usize,
+    /// The index in [`InstanceBufferPool::buffers`].
+    pool_index: usize,
+}
+
+#[derive(Debug)]
+struct PooledInstanceBuffer {
+    buffer_id: usize,
+    /// In floats.
+    capacity: usize,
+    /// How much has been allocated since the buffer was last orphaned, in floats.
+    used: usize,
+    /// How many allocated ranges are still in use.
+    live_ranges: usize,
+}
+
+/// See the module docs.
+#[derive(Debug, Default)]
+pub(crate) struct InstanceBufferPool {
+    buffers: Vec<PooledInstanceBuffer>,
+    /// The buffer that we're currently allocating from.
+    current: Option<usize>,
+}
+
+impl InstanceBufferPool {
+    /// Allocate a range of `len` floats. Call `new_buffer_id` when a new GPU buffer is needed.
+    ///
+    /// Also returns `Some(capacity)` if the GPU buffer of the range has to be (re)allocated with that capacity
+    /// before writing to it. That orphans whatever was in it before.
+    pub(crate) fn alloc(&mut self, len: usize, new_buffer_id: impl FnOnce() -> usize) -> (InstanceBufferRange, Option<usize>) {
+        let mut orphan_capacity = None;
+        let fits_current = self.current.map_or(false, |current| {
+            let buffer = &self.buffers[current];
+            buffer.capacity - buffer.used >= len
+        });
+        if !fits_current {
+            let free_buffer = self
+                .buffers
+                .iter()
+                .enumerate()
+                .position(|(index, buffer)| Some(index) != self.current && buffer.live_ranges == 0 && buffer.capacity >= len);
+            let pool_index = match free_buffer {
+                Some(pool_index) => pool_index,
+                None => {
+                    self.buffers.push(PooledInstanceBuffer {
+                        buffer_id: new_buffer_id(),
+                        capacity: len.max(INSTANCE_BUFFER_POOL_SIZE),
+                        used: 0,
+                        live_ranges: 0,
+                    });
+                    self.buffers.len() - 1
+                }
+            };
+            let buffer = &mut self.buffers[pool_index];
+            buffer.used = 0;
+            orphan_capacity = Some(buffer.capacity);
+            self.current = Some(pool_index);
+        }
+
+        let pool_index = self.current.unwrap();
+        let buffer = &mut self.buffers[pool_index];
+        let range = InstanceBufferRange { buffer_id: buffer.buffer_id, offset: buffer.used, len, pool_index };
+        buffer.used += len;
+        buffer.live_ranges += 1;
+        (range, orphan_capacity)
+    }
+
+    /// Release a range that was returned by [`InstanceBufferPool::alloc`], when it's not going to be drawn anymore.
+    pub(crate) fn free(&mut self, range: InstanceBufferRange) {
+        let buffer = &mut self.buffers[range.pool_index];
+        assert!(buffer.live_ranges > 0, "Freeing a range that was already freed");
+        buffer.live_ranges -= 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_alloc_and_reuse() {
+        let mut pool = InstanceBufferPool::default();
+        let mut next_buffer_id = 10;
+        let mut alloc = |pool: &mut InstanceBufferPool, len| {
+            pool.alloc(len, || {
+                next_buffer_id += 1;
+                next_buffer_id
+            })
+        };
+
+        // Ranges get packed into the same buffer.
+        let (a, orphan) = alloc(&mut pool, 100);
+        assert_eq!((a.buffer_id, a.offset, orphan), (11, 0, Some(INSTANCE_BUFFER_POOL_SIZE)));
+        let (b, orphan) = alloc(&mut pool, 200);
+        assert_eq!((b.buffer_id, b.offset, orphan), (11, 100, None));
+
+        // When the buffer is full, we get a new one.
+        let (c, orphan) = alloc(&mut pool, INSTANCE_BUFFER_POOL_SIZE - 250);
+        assert_eq!((c.buffer_id, c.offset, orphan), (12, 0, Some(INSTANCE_BUFFER_POOL_SIZE)));
+
+        // The first buffer can be reused once none of its ranges are used anymore.
+        pool.free(a);
+        let (d, _) = alloc(&mut pool, 500);
+        assert_eq!(d.buffer_id, 13);
+        pool.free(b);
+        let (e, orphan) = alloc(&mut pool, INSTANCE_BUFFER_POOL_SIZE);
+        assert_eq!((e.buffer_id, e.offset, orphan), (11, 0, Some(INSTANCE_BUFFER_POOL_SIZE)));
+
+        // Big ranges get their own buffer.
+        let (f, orphan) = alloc(&mut pool, INSTANCE_BUFFER_POOL_SIZE * 2);
+        assert_eq!((f.buffer_id, f.offset, orphan), (14, 0, Some(INSTANCE_BUFFER_POOL_SIZE * 2)));
+    }
+}
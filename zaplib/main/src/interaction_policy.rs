@@ -0,0 +1,197 @@
+//! How zoomable components, like charts, maps, and canvases, respond to scrolling and double-clicking.
+//!
+//! There is one [`InteractionPolicy`] for the whole app, stored in [`Cx`], so that e.g. the mouse wheel zooms in
+//! every chart, or scrolls in every chart, but never a mix of both. Apps can let end users remap it using
+//! [`Cx::set_interaction_policy`], and store it along with their other settings.
+
+use crate::*;
+
+/// What scrolling a mouse wheel does in zoomable components, when [`InteractionPolicy::zoom_modifier`] isn't held.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde-support", derive(serde::Serialize, serde::Deserialize))]
+pub enum WheelBehavior {
+    Zoom,
+    /// Pan, like in a regular [`View`]. Trackpads always pan, since they can zoom by pinching.
+    Scroll,
+}
+
+/// A modifier key that changes what scrolling does; see [`InteractionPolicy`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde-support", derive(serde::Serialize, serde::Deserialize))]
+pub enum ModifierKey {
+    Shift,
+    /// Ctrl, or Cmd on Mac.
+    ControlOrCommand,
+    /// Alt, or Option on Mac.
+    Alt,
+}
+
+impl ModifierKey {
+    pub fn is_held(self, modifiers: &KeyModifiers) -> bool {
+        match self {
+            ModifierKey::Shift => modifiers.shift,
+            ModifierKey::ControlOrCommand => modifiers.control || modifiers.logo,
+            ModifierKey::Alt => modifiers.alt,
+        }
+    }
+}
+
+/// Which axes to zoom. Components that zoom both axes by the same factor, like maps, ignore this.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ZoomAxes {
+    Both,
+    X,
+    Y,
+}
+
+impl ZoomAxes {
+    /// The factor to multiply the zoom of each axis by, when zooming by `factor`.
+    pub fn factors(self, factor: f32) -> Vec2 {
+        match self {
+            ZoomAxes::Both => Vec2::all(factor),
+            ZoomAxes::X => vec2(factor, 1.),
+            ZoomAxes::Y => vec2(1., factor),
+        }
+    }
+}
+
+/// What a [`PointerScrollEvent`] should do; see [`InteractionPolicy::scroll_action`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ScrollAction {
+    /// Zoom around the pointer, with `factor` larger than 1 when zooming in.
+    Zoom { factor: f32, axes: ZoomAxes },
+    /// Move the view by this many logical pixels, with positive values moving down and to the right.
+    Pan(Vec2),
+}
+
+/// How zoomable components respond to the mouse wheel, trackpads, and double-clicks. Get the current one using
+/// [`Cx::interaction_policy`]. Components turn scroll events into zooming or panning using
+/// [`InteractionPolicy::scroll_action`]:
+///
+/// ```ignore
+/// match event.hits_pointer(cx, self.component_id, rect) {
+///     Event::PointerScroll(pe) => match cx.interaction_policy().scroll_action(&pe) {
+///         ScrollAction::Zoom { factor, axes } => self.zoom_around(pe.abs, axes.factors(factor)),
+///         ScrollAction::Pan(delta) => self.pan += delta,
+///     },
+///     Event::PointerDown(pe) if cx.interaction_policy().is_reset(&pe) => self.reset_view(cx),
+///     _ => {}
+/// }
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde-support", derive(serde::Serialize, serde::Deserialize))]
+pub struct InteractionPolicy {
+    pub wheel: WheelBehavior,
+    /// Hold this to zoom by scrolling, regardless of [`InteractionPolicy::wheel`], and also on trackpads.
+    pub zoom_modifier: ModifierKey,
+    /// Hold this while zooming to only zoom the horizontal axis.
+    pub x_zoom_modifier: Option<ModifierKey>,
+    /// Hold this while zooming to only zoom the vertical axis.
+    pub y_zoom_modifier: Option<ModifierKey>,
+    /// How much one logical pixel of scrolling zooms; the zoom changes by a factor of `e` every `1 / zoom_speed`
+    /// pixels.
+    pub zoom_speed: f32,
+    /// Whether double-clicking resets the view to show everything again.
+    pub double_click_reset: bool,
+}
+
+impl Default for InteractionPolicy {
+    fn default() -> Self {
+        Self {
+            wheel: WheelBehavior::Zoom,
+            zoom_modifier: ModifierKey::ControlOrCommand,
+            x_zoom_modifier: Some(ModifierKey::Shift),
+            y_zoom_modifier: Some(ModifierKey::Alt),
+            zoom_speed: 0.002,
+            double_click_reset: true,
+        }
+    }
+}
+
+impl InteractionPolicy {
+    /// Whether `pe` should zoom or pan, and by how much.
+    pub fn scroll_action(&self, pe: &PointerScrollEvent) -> ScrollAction {
+        let zoom = pe.pinch != 0.
+            || self.zoom_modifier.is_held(&pe.modifiers)
+            || (pe.device == ScrollDevice::Wheel && self.wheel == WheelBehavior::Zoom);
+        if !zoom {
+            return ScrollAction::Pan(pe.scroll);
+        }
+
+        let held = |modifier: Option<ModifierKey>| modifier.map_or(false, |modifier| modifier.is_held(&pe.modifiers));
+        let axes = if held(self.x_zoom_modifier) {
+            ZoomAxes::X
+        } else if held(self.y_zoom_modifier) {
+            ZoomAxes::Y
+        } else {
+            ZoomAxes::Both
+        };
+        // Some platforms turn vertical mouse wheel scrolling into horizontal scrolling while Shift is held.
+        let amount = if pe.scroll.y != 0. { pe.scroll.y } else { pe.scroll.x };
+        ScrollAction::Zoom { factor: (1. + pe.pinch) * (-amount * self.zoom_speed).exp(), axes }
+    }
+
+    /// Whether `pe` should reset the view, i.e. whether it's the second click of a double-click.
+    pub fn is_reset(&self, pe: &PointerDownEvent) -> bool {
+        self.double_click_reset && pe.button == MouseButton::Left && pe.tap_count == 2
+    }
+}
+
+impl Cx {
+    /// See [`InteractionPolicy`].
+    pub fn interaction_policy(&self) -> &InteractionPolicy {
+        &self.interaction_policy
+    }
+
+    /// Change how all zoomable components respond to scrolling and double-clicking, e.g. after the user changed
+    /// their settings.
+    pub fn set_interaction_policy(&mut self, interaction_policy: InteractionPolicy) {
+        self.interaction_policy = interaction_policy;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn zoom_factor(action: ScrollAction, expected_axes: ZoomAxes) -> f32 {
+        match action {
+            ScrollAction::Zoom { factor, axes } if axes == expected_axes => factor,
+            _ => panic!("Expected to zoom {:?}, got {:?}", expected_axes, action),
+        }
+    }
+
+    #[test]
+    fn test_scroll_action() {
+        let policy = InteractionPolicy::default();
+        let wheel = PointerScrollEvent { scroll: vec2(0., -100.), device: ScrollDevice::Wheel, ..Default::default() };
+        let trackpad = PointerScrollEvent { device: ScrollDevice::Trackpad, ..wheel.clone() };
+        let shift = KeyModifiers { shift: true, ..Default::default() };
+        let control = KeyModifiers { control: true, ..Default::default() };
+
+        assert!((zoom_factor(policy.scroll_action(&wheel), ZoomAxes::Both) - 0.2f32.exp()).abs() < 1e-5);
+        assert_eq!(policy.scroll_action(&trackpad), ScrollAction::Pan(vec2(0., -100.)));
+        let trackpad_control = PointerScrollEvent { modifiers: control.clone(), ..trackpad.clone() };
+        zoom_factor(policy.scroll_action(&trackpad_control), ZoomAxes::Both);
+        // Shift+wheel may arrive as horizontal scrolling.
+        let wheel_shift = PointerScrollEvent { scroll: vec2(-100., 0.), modifiers: shift, ..wheel.clone() };
+        assert!((zoom_factor(policy.scroll_action(&wheel_shift), ZoomAxes::X) - 0.2f32.exp()).abs() < 1e-5);
+        let pinch = PointerScrollEvent { scroll: Vec2::default(), pinch: 0.5, ..trackpad };
+        assert_eq!(zoom_factor(policy.scroll_action(&pinch), ZoomAxes::Both), 1.5);
+
+        let policy = InteractionPolicy { wheel: WheelBehavior::Scroll, ..InteractionPolicy::default() };
+        assert_eq!(policy.scroll_action(&wheel), ScrollAction::Pan(vec2(0., -100.)));
+        let wheel_control = PointerScrollEvent { modifiers: control, ..wheel };
+        zoom_factor(policy.scroll_action(&wheel_control), ZoomAxes::Both);
+    }
+
+    #[test]
+    fn test_is_reset() {
+        let policy = InteractionPolicy::default();
+        assert!(policy.is_reset(&PointerDownEvent { tap_count: 2, ..Default::default() }));
+        assert!(!policy.is_reset(&PointerDownEvent { tap_count: 1, ..Default::default() }));
+        assert!(!policy.is_reset(&PointerDownEvent { tap_count: 2, button: MouseButton::Right, ..Default::default() }));
+        let policy = InteractionPolicy { double_click_reset: false, ..policy };
+        assert!(!policy.is_reset(&PointerDownEvent { tap_count: 2, ..Default::default() }));
+    }
+}
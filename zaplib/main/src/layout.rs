@@ -2,6 +2,7 @@ use crate::*;
 
 /// Indicates when to wrap the current line to a new line. See also [`Direction`].
 #[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde-support", derive(serde::Serialize, serde::Deserialize))]
 pub enum LineWrap {
     /// Never wrap to a new line.
     None,
@@ -63,20 +64,24 @@ impl Default for Layout {
 /// Determines how a [`CxLayoutBox`] should walk. Can be applied to a new [`CxLayoutBox`]
 /// through [`Layout::layout_size`], or directly to move an existing [`CxLayoutBox`] by
 /// using [`Cx::add_box`].
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde-support", derive(serde::Serialize, serde::Deserialize))]
 pub struct LayoutSize {
     pub width: Width,
     pub height: Height,
+    /// See [`SizeConstraints`].
+    pub constraints: SizeConstraints,
 }
 
 impl LayoutSize {
     /// TODO(JP): Replace these with Align::default() when
     /// <https://github.com/rust-lang/rust/issues/67792> gets done
-    pub const DEFAULT: LayoutSize = LayoutSize { width: Width::DEFAULT, height: Height::DEFAULT };
-    pub const FILL: LayoutSize = LayoutSize { width: Width::Fill, height: Height::Fill };
+    pub const DEFAULT: LayoutSize =
+        LayoutSize { width: Width::DEFAULT, height: Height::DEFAULT, constraints: SizeConstraints::DEFAULT };
+    pub const FILL: LayoutSize = LayoutSize { width: Width::Fill, height: Height::Fill, constraints: SizeConstraints::DEFAULT };
 
     pub const fn new(w: Width, h: Height) -> Self {
-        Self { width: w, height: h }
+        Self { width: w, height: h, constraints: SizeConstraints::DEFAULT }
     }
 }
 impl Default for LayoutSize {
@@ -85,6 +90,102 @@ impl Default for LayoutSize {
     }
 }
 
+/// Limits on the size of a box, on top of its [`Width`] and [`Height`], which are applied in this order:
+/// 1. The width and height get clamped to their minimum and maximum.
+/// 2. If only one of them is [`Width::Compute`] or [`Height::Compute`], it gets computed from the other one using
+///    [`SizeConstraints::aspect_ratio`]. If neither is, the box gets shrunk in one direction to fit the aspect ratio,
+///    like `object-fit: contain` in CSS. If both are, the height gets computed from the width at the end of the box.
+/// 3. The result gets clamped again, so minimums and maximums win over the aspect ratio.
+///
+/// A computed size that is known to be limited by a maximum also limits how much space is available inside the
+/// box, so e.g. [`Width::Fill`] children don't grow beyond [`SizeConstraints::max_width`].
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde-support", derive(serde::Serialize, serde::Deserialize))]
+pub struct SizeConstraints {
+    pub min_width: f32,
+    #[cfg_attr(feature = "serde-support", serde(with = "serde_max_size"))]
+    pub max_width: f32,
+    pub min_height: f32,
+    #[cfg_attr(feature = "serde-support", serde(with = "serde_max_size"))]
+    pub max_height: f32,
+    /// Width divided by height, e.g. `16. / 9.`.
+    pub aspect_ratio: Option<f32>,
+}
+
+/// Stores a maximum of [`f32::INFINITY`] as `None`, since formats like JSON can't represent infinity.
+#[cfg(feature = "serde-support")]
+mod serde_max_size {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub(super) fn serialize<S: Serializer>(max: &f32, serializer: S) -> Result<S::Ok, S::Error> {
+        Some(*max).filter(|max| max.is_finite()).serialize(serializer)
+    }
+
+    pub(super) fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<f32, D::Error> {
+        Ok(Option::<f32>::deserialize(deserializer)?.unwrap_or(f32::INFINITY))
+    }
+}
+
+impl SizeConstraints {
+    /// TODO(JP): Replace these with SizeConstraints::default() when
+    /// <https://github.com/rust-lang/rust/issues/67792> gets done
+    pub const DEFAULT: SizeConstraints = SizeConstraints {
+        min_width: 0.,
+        max_width: f32::INFINITY,
+        min_height: 0.,
+        max_height: f32::INFINITY,
+        aspect_ratio: None,
+    };
+
+    /// Apply the constraints to a width and height, either of which can be [`f32::NAN`] if it still needs to be
+    /// computed. Those stay [`f32::NAN`], unless they can be computed using the aspect ratio.
+    pub(crate) fn apply(&self, width: f32, height: f32) -> (f32, f32) {
+        let (mut width, mut height) = (self.clamp_width(width), self.clamp_height(height));
+        if let Some(aspect_ratio) = self.aspect_ratio.filter(|aspect_ratio| *aspect_ratio > 0. && aspect_ratio.is_finite()) {
+            match (width.is_nan(), height.is_nan()) {
+                (false, true) => height = width / aspect_ratio,
+                (true, false) => width = height * aspect_ratio,
+                (false, false) if width > height * aspect_ratio => width = height * aspect_ratio,
+                (false, false) => height = width / aspect_ratio,
+                (true, true) => {}
+            }
+        }
+        (self.clamp_width(width), self.clamp_height(height))
+    }
+
+    /// Apply the constraints to a width and height that were both computed from what got drawn in the box.
+    pub(crate) fn apply_computed(&self, width: f32, height: f32) -> (f32, f32) {
+        let width = self.clamp_width(width);
+        match self.aspect_ratio.filter(|aspect_ratio| *aspect_ratio > 0. && aspect_ratio.is_finite()) {
+            Some(aspect_ratio) => (width, self.clamp_height(width / aspect_ratio)),
+            None => (width, self.clamp_height(height)),
+        }
+    }
+
+    /// Clamp a width, keeping [`f32::NAN`].
+    pub(crate) fn clamp_width(&self, width: f32) -> f32 {
+        if width.is_nan() {
+            width
+        } else {
+            width.min(self.max_width).max(self.min_width)
+        }
+    }
+
+    /// Clamp a height, keeping [`f32::NAN`].
+    pub(crate) fn clamp_height(&self, height: f32) -> f32 {
+        if height.is_nan() {
+            height
+        } else {
+            height.min(self.max_height).max(self.min_height)
+        }
+    }
+}
+impl Default for SizeConstraints {
+    fn default() -> Self {
+        SizeConstraints::DEFAULT
+    }
+}
+
 /// The direction in which the [`CxLayoutBox`] should walk. It will typically walk
 /// in a straight line in this direction. E.g. when walking to [`Direction::Right`],
 /// it will only walk horizontally, not vertically, until it hits the [`CxLayoutBox::width`],
@@ -96,6 +197,7 @@ impl Default for LayoutSize {
 /// but not so much for [`Direction::Down`].. Maybe we should split [`CxLayoutBox`]
 /// into different kinds of behavior?
 #[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde-support", derive(serde::Serialize, serde::Deserialize))]
 pub enum Direction {
     Right,
     Down,
@@ -115,6 +217,7 @@ impl Default for Direction {
 ///
 /// TODO(JP): See [`Height::DEFAULT`] for a related TODO.
 #[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde-support", derive(serde::Serialize, serde::Deserialize))]
 pub enum Width {
     /// Fill up as much of the available space as possible.
     Fill,
@@ -144,6 +247,7 @@ impl Default for Width {
 ///
 /// See [`Width`] for more documentation, since it's analogous.
 #[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde-support", derive(serde::Serialize, serde::Deserialize))]
 pub enum Height {
     /// See [`Width::Fill`].
     Fill,
@@ -189,3 +293,61 @@ impl AlignY {
     #[allow(dead_code)]
     pub(crate) const BOTTOM: AlignY = AlignY(1.0);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_size_constraints_apply() {
+        let constraints =
+            SizeConstraints { min_width: 50., max_height: 100., aspect_ratio: Some(2.), ..SizeConstraints::DEFAULT };
+        assert_eq!(constraints.apply(10., f32::NAN), (50., 25.));
+        assert_eq!(constraints.apply(400., f32::NAN), (400., 100.));
+        assert_eq!(constraints.apply(400., 300.), (200., 100.));
+        let (width, height) = constraints.apply(f32::NAN, f32::NAN);
+        assert!(width.is_nan() && height.is_nan());
+        assert_eq!(constraints.apply_computed(80., 10.), (80., 40.));
+    }
+
+    #[test]
+    fn test_size_constraints_layout() {
+        let mut test_cx = TestCx::new(vec2(400., 300.));
+        let mut view = View::default();
+        let mut computed_view = View::default();
+        let mut rects = vec![];
+        test_cx.draw(|cx| {
+            view.begin_view(
+                cx,
+                LayoutSize {
+                    width: Width::Fill,
+                    height: Height::Compute,
+                    constraints: SizeConstraints { max_width: 200., aspect_ratio: Some(2.), ..SizeConstraints::DEFAULT },
+                },
+            );
+            rects.push(cx.add_box(LayoutSize::FILL));
+            view.end_view(cx);
+            rects.push(view.get_rect(cx));
+
+            computed_view.begin_view(
+                cx,
+                LayoutSize {
+                    width: Width::Compute,
+                    height: Height::Compute,
+                    constraints: SizeConstraints { aspect_ratio: Some(1.), ..SizeConstraints::DEFAULT },
+                },
+            );
+            cx.add_box(LayoutSize::new(Width::Fix(80.), Height::Fix(20.)));
+            computed_view.end_view(cx);
+            rects.push(computed_view.get_rect(cx));
+
+            rects.push(cx.add_box(LayoutSize {
+                constraints: SizeConstraints { min_width: 50., ..SizeConstraints::DEFAULT },
+                ..LayoutSize::new(Width::Fix(10.), Height::Fix(10.))
+            }));
+        });
+
+        let sizes: Vec<Vec2> = rects.iter().map(|rect| rect.size).collect();
+        assert_eq!(sizes, vec![vec2(200., 100.), vec2(200., 100.), vec2(80., 80.), vec2(50., 10.)]);
+    }
+}
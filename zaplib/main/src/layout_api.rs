@@ -29,7 +29,7 @@ impl Cx {
     pub fn begin_row(&mut self, width: Width, height: Height) {
         self.begin_typed_box(
             CxBoxType::Row,
-            Layout { direction: Direction::Right, layout_size: LayoutSize { width, height }, ..Layout::default() },
+            Layout { direction: Direction::Right, layout_size: LayoutSize::new(width, height), ..Layout::default() },
         );
     }
 
@@ -43,7 +43,7 @@ impl Cx {
     pub fn begin_column(&mut self, width: Width, height: Height) {
         self.begin_typed_box(
             CxBoxType::Column,
-            Layout { direction: Direction::Down, layout_size: LayoutSize { width, height }, ..Layout::default() },
+            Layout { direction: Direction::Down, layout_size: LayoutSize::new(width, height), ..Layout::default() },
         );
     }
 
@@ -98,7 +98,7 @@ impl Cx {
             origin: parent.pos,
             pos: parent.pos,
             // fills out all remaining space by both axis
-            layout: Layout { layout_size: LayoutSize { width: Width::Fill, height: Height::Fill }, ..parent.layout },
+            layout: Layout { layout_size: LayoutSize::new(Width::Fill, Height::Fill), ..parent.layout },
             biggest: 0.0,
             bound_right_bottom: Vec2 { x: std::f32::NEG_INFINITY, y: std::f32::NEG_INFINITY },
             width: self.get_width_left(),
@@ -247,12 +247,7 @@ impl Cx {
         let direction = parent.layout.direction;
         self.begin_typed_box(
             CxBoxType::PaddingBox,
-            Layout {
-                direction,
-                layout_size: LayoutSize { width: Width::Compute, height: Height::Compute },
-                padding,
-                ..Layout::default()
-            },
+            Layout { direction, layout_size: LayoutSize::new(Width::Compute, Height::Compute), padding, ..Layout::default() },
         );
     }
 
@@ -271,6 +266,28 @@ impl Cx {
         self.end_typed_box(CxBoxType::AbsoluteBox);
     }
 
+    /// Starts a new box whose contents can be visually moved using [`Cx::end_offset_box`], e.g. for
+    /// sliding things in and out of view.
+    pub fn begin_offset_box(&mut self) {
+        let parent = self.layout_boxes.last().expect("Using offset_box without parent is not supported");
+        let direction = parent.layout.direction;
+        self.begin_typed_box(
+            CxBoxType::OffsetBox,
+            Layout { direction, layout_size: LayoutSize::new(Width::Compute, Height::Compute), ..Layout::default() },
+        );
+    }
+
+    /// Ends the current box that was opened by [`Cx::begin_offset_box`], moving everything that was
+    /// drawn inside of it by `offset`. The parent box still walks the original [`Rect`], so moving
+    /// things around doesn't affect the rest of the layout. Returns that original [`Rect`].
+    pub fn end_offset_box(&mut self, offset: Vec2) -> Rect {
+        self.assert_last_box_type_matches(CxBoxType::OffsetBox);
+        let align_start = self.layout_boxes.last().unwrap().align_list_x_start_index;
+        let rect = self.end_typed_box(CxBoxType::OffsetBox);
+        self.move_by(offset, align_start);
+        rect
+    }
+
     /// Starts new box that is wrapping its content inside.
     /// This is defined in terms of child boxes (e.g. if any of the immediately nested boxes
     /// goes beyond the bounds, it would be wrapped to new line).
@@ -285,7 +302,7 @@ impl Cx {
             Layout {
                 direction,
                 line_wrap: LineWrap::Overflow,
-                layout_size: LayoutSize { width: Width::Compute, height: Height::Compute },
+                layout_size: LayoutSize::new(Width::Compute, Height::Compute),
                 ..Layout::default()
             },
         );
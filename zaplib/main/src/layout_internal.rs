@@ -1,8 +1,14 @@
 //! Layout system. 🐢
 
+use std::ops::Range;
+
 use crate::debug_log::DebugLog;
 use crate::*;
 
+/// How far boxes can stick out of a box with [`LineWrap::Overflow`] before they wrap, to account for rounding errors
+/// in the widths that we add up.
+pub(crate) const LINE_WRAP_TOLERANCE: f32 = 0.01;
+
 #[derive(Copy, Clone, Debug, PartialEq)]
 pub enum CxBoxType {
     Normal,
@@ -16,6 +22,11 @@ pub enum CxBoxType {
     Column,
     AbsoluteBox,
     WrappingBox,
+    OffsetBox,
+    Flex,
+    FlexItem,
+    Grid,
+    GridCell,
     View,
 }
 
@@ -136,25 +147,31 @@ impl Cx {
             abs_size = layout_abs_size;
         }
 
-        let width;
-        let height;
+        let unconstrained_width;
+        let unconstrained_height;
         if layout.absolute {
             // absolute overrides origin to start from (0, 0)
             origin = vec2(0.0, 0.0);
             // absolute overrides the computation of width/height to use the parent absolute
-            width = self.eval_absolute_width(&layout.layout_size.width, abs_size.x);
-            height = self.eval_absolute_height(&layout.layout_size.height, abs_size.y);
+            unconstrained_width = self.eval_absolute_width(&layout.layout_size.width, abs_size.x);
+            unconstrained_height = self.eval_absolute_height(&layout.layout_size.height, abs_size.y);
         } else {
-            width = self.eval_width(&layout.layout_size.width);
-            height = self.eval_height(&layout.layout_size.height);
+            unconstrained_width = self.eval_width(&layout.layout_size.width);
+            unconstrained_height = self.eval_height(&layout.layout_size.height);
         }
+        let constraints = &layout.layout_size.constraints;
+        let (width, height) = constraints.apply(unconstrained_width, unconstrained_height);
 
         let pos = Vec2 { x: origin.x + layout.padding.l, y: origin.y + layout.padding.t };
 
-        let available_width =
-            (self.eval_available_width(&layout.layout_size.width, layout.absolute, abs_size) - layout.padding.r).max(0.);
-        let available_height =
-            (self.eval_available_height(&layout.layout_size.height, layout.absolute, abs_size) - layout.padding.b).max(0.);
+        // If the constraints changed the size, then that's all the space there is. Otherwise only the maximum
+        // limits the space.
+        let mut available_width = self.eval_available_width(&layout.layout_size.width, layout.absolute, abs_size);
+        let mut available_height = self.eval_available_height(&layout.layout_size.height, layout.absolute, abs_size);
+        available_width = constrained_available_size(available_width, unconstrained_width, width, constraints.max_width);
+        available_height = constrained_available_size(available_height, unconstrained_height, height, constraints.max_height);
+        let available_width = (available_width - layout.padding.r).max(0.);
+        let available_height = (available_height - layout.padding.b).max(0.);
 
         // By induction property this values should never be NaN
         assert!(!available_width.is_nan());
@@ -200,42 +217,49 @@ impl Cx {
             // when nesting Fill box inside Compute the former would have nan width
             if old.layout.layout_size.width == Width::Fill {
                 // use all available width + padding
-                Width::Fix(old.available_width + old.layout.padding.r)
+                old.available_width + old.layout.padding.r
             } else if old.bound_right_bottom.x == std::f32::NEG_INFINITY {
                 // nothing happened, use padding
-                Width::Fix(old.layout.padding.l + old.layout.padding.r)
+                old.layout.padding.l + old.layout.padding.r
             } else {
                 // use the bounding box
-                Width::Fix(max_zero_keep_nan(old.bound_right_bottom.x - old.origin.x + old.layout.padding.r))
+                max_zero_keep_nan(old.bound_right_bottom.x - old.origin.x + old.layout.padding.r)
             }
         } else {
-            Width::Fix(old.width)
+            old.width
         };
 
         let h = if old.height.is_nan() {
             // when nesting Fill box inside Compute the former would have nan height
             if old.layout.layout_size.height == Height::Fill {
                 // use all available height + padding
-                Height::Fix(old.available_height + old.layout.padding.b)
+                old.available_height + old.layout.padding.b
             } else if old.bound_right_bottom.y == std::f32::NEG_INFINITY {
                 // nothing happened use the padding
-                Height::Fix(old.layout.padding.t + old.layout.padding.b)
+                old.layout.padding.t + old.layout.padding.b
             } else {
                 // use the bounding box
-                Height::Fix(max_zero_keep_nan(old.bound_right_bottom.y - old.origin.y + old.layout.padding.b))
+                max_zero_keep_nan(old.bound_right_bottom.y - old.origin.y + old.layout.padding.b)
             }
         } else {
-            Height::Fix(old.height)
+            old.height
+        };
+
+        // Sizes that were known at the start of the box have already been constrained.
+        let constraints = &old.layout.layout_size.constraints;
+        let (w, h) = match (old.width.is_nan(), old.height.is_nan()) {
+            (true, true) => constraints.apply_computed(w, h),
+            (true, false) => (constraints.clamp_width(w), h),
+            (false, true) => (w, constraints.clamp_height(h)),
+            (false, false) => (w, h),
         };
 
         let rect = {
             // when a box is absolutely positioned don't walk the parent
             if old.layout.absolute {
-                let w = if let Width::Fix(vw) = w { vw } else { 0. };
-                let h = if let Height::Fix(vh) = h { vh } else { 0. };
                 Rect { pos: vec2(0., 0.), size: vec2(w, h) }
             } else {
-                self.move_box_with_old(LayoutSize { width: w, height: h }, Some(&old))
+                self.move_box_with_old(LayoutSize::new(Width::Fix(w), Height::Fix(h)), Some(&old))
             }
         };
         self.debug_logs.push(DebugLog::EndBox { rect });
@@ -260,13 +284,14 @@ impl Cx {
         // We can probably express this better in type system, but this is good enough for now.
         let w = self.eval_walking_width(&layout_size.width);
         let h = self.eval_walking_height(&layout_size.height);
+        let (w, h) = layout_size.constraints.apply(w, h);
 
         let ret = if let Some(layout_box) = self.layout_boxes.last_mut() {
             let old_pos = match layout_box.layout.direction {
                 Direction::Right => {
                     match layout_box.layout.line_wrap {
                         LineWrap::Overflow => {
-                            if (layout_box.pos.x + w) > (layout_box.origin.x + layout_box.available_width) + 0.01 {
+                            if (layout_box.pos.x + w) > (layout_box.origin.x + layout_box.available_width) + LINE_WRAP_TOLERANCE {
                                 // what is the move delta.
                                 let old_x = layout_box.pos.x;
                                 let old_y = layout_box.pos.y;
@@ -333,14 +358,39 @@ impl Cx {
         self.move_by_x(dx, align_start)
     }
 
+    /// Move items in [`Cx::layout_box_align_list`] by `offset`, in any direction.
+    pub(crate) fn move_by(&mut self, offset: Vec2, align_start: usize) {
+        if offset.x != 0. {
+            self.move_by_x(offset.x, align_start);
+        }
+        if offset.y != 0. {
+            self.move_by_y(offset.y, align_start);
+        }
+    }
+
+    /// Move only the items in `range` of [`Cx::layout_box_align_list`] by `offset`, e.g. to move a single child of
+    /// a box that has other children drawn after it.
+    pub(crate) fn move_range_by(&mut self, offset: Vec2, range: Range<usize>) {
+        if offset.x != 0. {
+            self.move_range_by_x(offset.x, range.clone());
+        }
+        if offset.y != 0. {
+            self.move_range_by_y(offset.y, range);
+        }
+    }
+
+    fn move_by_x(&mut self, dx: f32, align_start: usize) {
+        self.move_range_by_x(dx, align_start..self.layout_box_align_list.len());
+    }
+
     /// Actually perform a horizontal movement of items in [`Cx::layout_box_align_list`].
     /// Unlike "do_align_x" negative moves can happen here because of wrapping behavior.
     ///
     /// TODO(JP): Should we move some of this stuff to [`Area`], where we already seem to do a bunch
     /// of rectangle and position calculations?
-    fn move_by_x(&mut self, dx: f32, align_start: usize) {
+    fn move_range_by_x(&mut self, dx: f32, range: Range<usize>) {
         let dx = (dx * self.current_dpi_factor).floor() / self.current_dpi_factor;
-        for i in align_start..self.layout_box_align_list.len() {
+        for i in range {
             let align_item = &self.layout_box_align_list[i];
             match align_item {
                 Area::InstanceRange(inst) => {
@@ -376,14 +426,18 @@ impl Cx {
         self.move_by_y(dy, align_start);
     }
 
+    fn move_by_y(&mut self, dy: f32, align_start: usize) {
+        self.move_range_by_y(dy, align_start..self.layout_box_align_list.len());
+    }
+
     /// Actually perform a vertical movement of items in [`Cx::layout_box_align_list`].
     /// Unlike "do_align_y" negative moves can happen here because of wrapping behavior.
     ///
     /// TODO(JP): Should we move some of this stuff to [`Area`], where we already seem to do a bunch
     /// of rectangle and position calculations?
-    fn move_by_y(&mut self, dy: f32, align_start: usize) {
+    fn move_range_by_y(&mut self, dy: f32, range: Range<usize>) {
         let dy = (dy * self.current_dpi_factor).floor() / self.current_dpi_factor;
-        for i in align_start..self.layout_box_align_list.len() {
+        for i in range {
             let align_item = &self.layout_box_align_list[i];
             match align_item {
                 Area::InstanceRange(inst) => {
@@ -552,6 +606,16 @@ pub(crate) fn max_zero_keep_nan(v: f32) -> f32 {
     }
 }
 
+/// The space that is available inside a box after applying [`SizeConstraints`]: the `constrained_size` if the
+/// constraints changed the size, or else the `available_size` limited by `max_size`.
+fn constrained_available_size(available_size: f32, unconstrained_size: f32, constrained_size: f32, max_size: f32) -> f32 {
+    if !constrained_size.is_nan() && (unconstrained_size.is_nan() || constrained_size != unconstrained_size) {
+        constrained_size
+    } else {
+        available_size.min(max_size)
+    }
+}
+
 pub(crate) fn min_keep_nan(a: f32, b: f32) -> f32 {
     if a.is_nan() || b.is_nan() {
         f32::NAN
@@ -17,33 +17,54 @@
 // For using [`core::arch::wasm32`].
 #![cfg_attr(target_arch = "wasm32", feature(stdsimd))]
 
+// So that `#[derive(ZapParam)]`, which refers to `::zaplib`, also works within this crate.
+extern crate self as zaplib;
+
 #[macro_use]
 mod macros;
 
+#[cfg(target_os = "linux")]
+mod audio_alsa;
 #[cfg(any(target_os = "linux"))]
 mod cx_linux;
-#[cfg(target_os = "linux")]
+#[cfg(any(target_os = "linux", target_os = "android"))]
 mod cx_opengl;
 #[cfg(target_os = "linux")]
+mod cx_wayland;
+#[cfg(target_os = "linux")]
 mod cx_xlib;
 #[cfg(target_os = "linux")]
 pub(crate) use cx_linux::*;
-#[cfg(target_os = "linux")]
+#[cfg(any(target_os = "linux", target_os = "android"))]
 pub(crate) use cx_opengl::*;
 
-#[cfg(any(target_os = "macos"))]
+#[cfg(target_os = "android")]
+mod cx_android;
+#[cfg(target_os = "android")]
+pub(crate) use cx_android::*;
+
+#[cfg(target_os = "macos")]
+mod audio_coreaudio;
+#[cfg(any(target_os = "macos", target_os = "ios"))]
 mod cx_apple;
 #[cfg(target_os = "macos")]
 mod cx_cocoa;
 #[cfg(any(target_os = "macos"))]
 mod cx_macos;
-#[cfg(target_os = "macos")]
+#[cfg(any(target_os = "macos", target_os = "ios"))]
 mod cx_metal;
 #[cfg(target_os = "macos")]
 pub(crate) use cx_macos::*;
-#[cfg(target_os = "macos")]
+#[cfg(any(target_os = "macos", target_os = "ios"))]
 pub(crate) use cx_metal::*;
 
+#[cfg(target_os = "ios")]
+mod cx_ios;
+#[cfg(target_os = "ios")]
+pub(crate) use cx_ios::*;
+
+#[cfg(target_os = "windows")]
+mod audio_wasapi;
 #[cfg(target_os = "windows")]
 mod cx_dx11;
 #[cfg(target_os = "windows")]
@@ -60,11 +81,14 @@ mod cx_wasm32;
 #[cfg(target_arch = "wasm32")]
 mod cx_webgl;
 
-#[cfg(any(target_os = "linux", target_os = "macos", target_os = "windows"))]
+#[cfg(any(target_os = "linux", target_os = "macos", target_os = "windows", target_os = "android", target_os = "ios"))]
 mod cx_desktop;
-#[cfg(any(target_os = "linux", target_os = "macos", target_os = "windows"))]
+#[cfg(any(target_os = "linux", target_os = "macos", target_os = "windows", target_os = "android", target_os = "ios"))]
 pub(crate) use cx_desktop::*;
 
+#[cfg(not(target_arch = "wasm32"))]
+mod ffmpeg;
+
 #[cfg(target_arch = "wasm32")]
 pub use cx_wasm32::*;
 #[cfg(target_arch = "wasm32")]
@@ -82,34 +106,75 @@ mod zerde;
 
 mod animator;
 mod area;
+pub mod audio;
 pub mod byte_extract;
+mod call_rust_fns;
+pub mod camera;
+pub mod capture;
 pub mod cast;
 mod colors;
 mod component_id;
 mod cursor;
 mod cx;
+pub mod data;
 pub mod debug_log;
 mod debugger;
+pub mod decimation;
+mod diagnostics;
 mod draw_tree;
 mod events;
+mod executor;
+mod flex;
+mod focus;
 mod fonts;
+mod frame_arena;
 mod geometry;
+mod grid;
 mod hash;
+#[cfg_attr(not(target_arch = "wasm32"), allow(dead_code))]
+mod instance_buffer_pool;
+mod interaction_policy;
 mod layout;
 mod layout_api;
 mod layout_internal;
+mod linked_selection;
+mod localization;
+mod logger;
+mod memory_stats;
 mod param;
 mod pass;
+pub mod png;
+pub mod print;
 mod profile;
 mod read_seek;
+mod scroll;
 mod shader;
+#[cfg(not(target_arch = "wasm32"))]
+#[cfg_attr(not(any(target_os = "windows", target_os = "macos", target_os = "ios")), allow(dead_code))]
+mod shader_cache;
+mod simd;
+pub mod spatial_index;
+mod spring;
+#[cfg(feature = "serde-support")]
+pub mod state_snapshot;
+pub mod storage;
+mod style_sheet;
+pub mod system_notification;
+mod test_cx;
 mod texture;
+pub mod thread_pool;
+mod tooltip;
+pub mod undo;
 pub mod universal_file;
 pub mod universal_http_stream;
 mod universal_instant;
 pub mod universal_rand;
 pub mod universal_thread;
+pub mod vector_export;
+pub mod video;
 mod window;
+mod xr;
+mod zerde_record;
 
 mod cube_ins;
 mod image_ins;
@@ -117,41 +182,63 @@ mod menu;
 mod quad_ins;
 mod std_shader;
 mod text_ins;
+mod text_selection;
+mod text_shaping;
 
 use cast::*;
 
 pub use area::*;
+pub use call_rust_fns::*;
 pub use cast::*;
 pub use cube_ins::*;
 pub use cursor::*;
 pub use cx::*;
 pub use debugger::*;
+pub use diagnostics::*;
 pub use events::*;
+pub use executor::*;
+pub use focus::*;
 pub use image_ins::*;
 pub use param::*;
 pub use quad_ins::*;
 pub use std_shader::*;
+pub use test_cx::*;
 pub use text_ins::*;
+pub use text_selection::*;
 pub use texture::*;
+pub use tooltip::*;
 pub use window::*;
+pub use xr::*;
+pub use zaplib_derive::ZapParam;
 pub use zaplib_shader_compiler::code_fragment::CodeFragment;
 pub use zaplib_shader_compiler::math::*;
 pub use zaplib_shader_compiler::ty::Ty;
+pub use zerde_record::*;
 
 pub use animator::*;
 pub use colors::*;
 pub use component_id::*;
 pub use draw_tree::*;
+pub use flex::*;
 pub use fonts::*;
 pub use geometry::*;
+pub use grid::*;
 pub use hash::*;
+pub use interaction_policy::*;
 pub use layout::*;
 pub use layout_api::*;
 pub use layout_internal::*;
+pub use linked_selection::*;
+pub use localization::*;
+pub use logger::*;
 pub use macros::*;
+pub use memory_stats::*;
 pub use menu::*;
 pub use pass::*;
 pub use read_seek::*;
+pub use scroll::*;
 pub use shader::*;
+pub use spring::*;
+pub use style_sheet::*;
 pub use universal_file::*;
 pub use universal_instant::*;
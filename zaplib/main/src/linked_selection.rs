@@ -0,0 +1,208 @@
+//! Selections that are shared between components, so that e.g. brushing points in one chart highlights the same
+//! rows in a table and the same points in another chart, without wiring those components up to each other.
+//!
+//! A selection is a [`SelectionSet`] of indices, e.g. of the rows of a dataset, stored in [`Cx`] under a name. A
+//! component publishes a new selection using [`Cx::publish_selection`], after which [`Event::SelectionChanged`]
+//! gets fired. Other components that show the same data subscribe to it by checking for that event using
+//! [`Cx::linked_selection_changed`]:
+//!
+//! ```ignore
+//! // When the user selects something:
+//! cx.publish_selection("cars", self.component_id, SelectionSet::from_indices(&self.selection));
+//!
+//! // In `handle`:
+//! if let Some(selection) = cx.linked_selection_changed(event, "cars", self.component_id) {
+//!     self.selection = selection.indices().collect();
+//!     cx.request_draw();
+//! }
+//! ```
+
+use crate::*;
+use std::collections::HashMap;
+use std::ops::Range;
+
+/// A set of indices, stored as sorted ranges, so that selecting millions of consecutive rows stays cheap.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SelectionSet {
+    /// Sorted, and never empty, overlapping, or touching.
+    ranges: Vec<Range<usize>>,
+}
+
+impl SelectionSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a set from indices in any order; duplicates are ignored.
+    pub fn from_indices(indices: &[usize]) -> Self {
+        let mut sorted = indices.to_vec();
+        sorted.sort_unstable();
+        let mut ranges: Vec<Range<usize>> = vec![];
+        for index in sorted {
+            match ranges.last_mut() {
+                Some(last) if index <= last.end => last.end = last.end.max(index + 1),
+                _ => ranges.push(index..index + 1),
+            }
+        }
+        Self { ranges }
+    }
+
+    /// Create a set from ranges of indices in any order; they may overlap.
+    pub fn from_ranges(ranges: impl IntoIterator<Item = Range<usize>>) -> Self {
+        let mut sorted: Vec<Range<usize>> = ranges.into_iter().filter(|range| !range.is_empty()).collect();
+        sorted.sort_unstable_by_key(|range| range.start);
+        let mut ranges: Vec<Range<usize>> = vec![];
+        for range in sorted {
+            match ranges.last_mut() {
+                Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+                _ => ranges.push(range),
+            }
+        }
+        Self { ranges }
+    }
+
+    /// The selected ranges, sorted, and without overlapping or touching ranges.
+    pub fn ranges(&self) -> &[Range<usize>] {
+        &self.ranges
+    }
+
+    /// All selected indices, in increasing order.
+    pub fn indices(&self) -> impl Iterator<Item = usize> + '_ {
+        self.ranges.iter().flat_map(|range| range.clone())
+    }
+
+    pub fn contains(&self, index: usize) -> bool {
+        let position = self.ranges.partition_point(|range| range.end <= index);
+        self.ranges.get(position).map_or(false, |range| range.start <= index)
+    }
+
+    /// Number of selected indices.
+    pub fn len(&self) -> usize {
+        self.ranges.iter().map(|range| range.len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+    }
+
+    /// Indices that are in either set.
+    #[must_use]
+    pub fn union(&self, other: &SelectionSet) -> SelectionSet {
+        Self::from_ranges(self.ranges.iter().chain(&other.ranges).cloned())
+    }
+
+    /// Indices that are in both sets.
+    #[must_use]
+    pub fn intersection(&self, other: &SelectionSet) -> SelectionSet {
+        let mut ranges = vec![];
+        let (mut a, mut b) = (0, 0);
+        while a < self.ranges.len() && b < other.ranges.len() {
+            let (range_a, range_b) = (&self.ranges[a], &other.ranges[b]);
+            let start = range_a.start.max(range_b.start);
+            let end = range_a.end.min(range_b.end);
+            if start < end {
+                ranges.push(start..end);
+            }
+            // Move past whichever range ends first.
+            if range_a.end < range_b.end {
+                a += 1;
+            } else {
+                b += 1;
+            }
+        }
+        Self { ranges }
+    }
+}
+
+/// Which selection changed, and who changed it. See [`Event::SelectionChanged`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct SelectionChangedEvent {
+    /// The name passed to [`Cx::publish_selection`]; get the new selection using [`Cx::linked_selection`].
+    pub name: String,
+    /// The component that published the selection; it typically already shows it.
+    pub source: ComponentId,
+}
+
+#[derive(Default)]
+pub(crate) struct CxLinkedSelections {
+    selections: HashMap<String, SelectionSet>,
+    /// Selections that were published during the current event, to fire [`Event::SelectionChanged`] for.
+    pub(crate) changed: Vec<SelectionChangedEvent>,
+}
+
+impl Cx {
+    /// Share a selection under `name`, with everyone who shows the same data. If it differs from the current
+    /// selection, [`Event::SelectionChanged`] gets fired after the current event handler; if the same selection is
+    /// published multiple times during one event, the event only fires once, with the last `source`.
+    ///
+    /// `source` should be the [`ComponentId`] of the component that publishes it, so that it can ignore its own
+    /// changes; see [`Cx::linked_selection_changed`].
+    pub fn publish_selection(&mut self, name: &str, source: ComponentId, selection: SelectionSet) {
+        if *self.linked_selection(name) == selection {
+            return;
+        }
+        self.linked_selections.selections.insert(name.to_string(), selection);
+        self.linked_selections.changed.retain(|changed| changed.name != name);
+        self.linked_selections.changed.push(SelectionChangedEvent { name: name.to_string(), source });
+    }
+
+    /// The selection that was last published under `name`; empty if there is none.
+    pub fn linked_selection(&self, name: &str) -> &SelectionSet {
+        static EMPTY: SelectionSet = SelectionSet { ranges: Vec::new() };
+        self.linked_selections.selections.get(name).unwrap_or(&EMPTY)
+    }
+
+    /// Get the new selection if `event` is an [`Event::SelectionChanged`] for `name`, which was published by
+    /// another component than `component_id`.
+    pub fn linked_selection_changed(&self, event: &Event, name: &str, component_id: ComponentId) -> Option<&SelectionSet> {
+        match event {
+            Event::SelectionChanged(sc) if sc.name == name && sc.source != component_id => Some(self.linked_selection(name)),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_selection_set() {
+        let set = SelectionSet::from_indices(&[5, 1, 2, 3, 3, 9]);
+        assert_eq!(set.ranges(), &[1..4, 5..6, 9..10]);
+        assert_eq!(set.len(), 5);
+        assert!(set.contains(3) && set.contains(9) && !set.contains(4) && !set.contains(10));
+
+        let other = SelectionSet::from_ranges(vec![8..12, 0..2, 2..3]);
+        assert_eq!(other.ranges(), &[0..3, 8..12]);
+        assert_eq!(set.union(&other).ranges(), &[0..4, 5..6, 8..12]);
+        assert_eq!(set.intersection(&other).indices().collect::<Vec<_>>(), vec![1, 2, 9]);
+        assert!(SelectionSet::new().intersection(&set).is_empty());
+    }
+
+    #[test]
+    fn test_publish_selection() {
+        let mut test_cx = TestCx::new(vec2(100., 100.));
+        let (chart, table) = (ComponentId::default(), ComponentId::default());
+        let results = test_cx.dispatch(Event::None, |cx, event| {
+            if let Event::None = event {
+                cx.publish_selection("rows", chart, SelectionSet::from_indices(&[1]));
+                cx.publish_selection("rows", chart, SelectionSet::from_indices(&[2, 3]));
+            }
+            (
+                cx.linked_selection_changed(event, "rows", table).map(|selection| selection.indices().collect::<Vec<_>>()),
+                cx.linked_selection_changed(event, "rows", chart).is_some(),
+            )
+        });
+        assert_eq!(results, vec![(None, false), (Some(vec![2, 3]), false)]);
+
+        // Publishing the same selection again doesn't fire an event.
+        let results = test_cx.dispatch(Event::None, |cx, event| {
+            if let Event::None = event {
+                cx.publish_selection("rows", table, SelectionSet::from_indices(&[3, 2]));
+            }
+            matches!(event, Event::SelectionChanged(_))
+        });
+        assert_eq!(results, vec![false]);
+    }
+}
@@ -0,0 +1,734 @@
+//! Translating text into multiple languages, and formatting numbers and dates for the user's locale.
+//!
+//! Translations live in [`MessageCatalog`]s, one per locale, which use a subset of the
+//! [Fluent](https://projectfluent.org/) syntax. Add them using [`Cx::add_message_catalog`], and look up messages
+//! using [`Cx::translate`]. Change the locale at runtime using [`Cx::set_locale`], which fires an
+//! [`Event::LocaleChanged`] and redraws everything.
+
+use crate::*;
+use std::collections::HashMap;
+use std::fmt;
+
+/// Why parsing a [`MessageCatalog`] failed.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MessageCatalogError {
+    /// The line of the error, starting at 1.
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for MessageCatalogError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Message catalog error on line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for MessageCatalogError {}
+
+/// A value to fill in for a `{ $variable }` in a message. Numbers get formatted for the current locale, and can be
+/// used to select plural forms.
+#[derive(Clone, Debug, PartialEq)]
+pub enum MessageArg {
+    String(String),
+    Number(f64),
+}
+
+impl From<&str> for MessageArg {
+    fn from(value: &str) -> Self {
+        MessageArg::String(value.to_string())
+    }
+}
+
+impl From<String> for MessageArg {
+    fn from(value: String) -> Self {
+        MessageArg::String(value)
+    }
+}
+
+macro_rules! impl_message_arg_from_number {
+    ($($ty:ty),*) => {
+        $(impl From<$ty> for MessageArg {
+            fn from(value: $ty) -> Self {
+                MessageArg::Number(value as f64)
+            }
+        })*
+    };
+}
+impl_message_arg_from_number!(f32, f64, i32, i64, u32, u64, usize);
+
+#[derive(Clone, Debug, PartialEq)]
+enum VariantKey {
+    Number(f64),
+    /// A plural category, like `one` or `other`, or any other string to match against string arguments.
+    Name(String),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum PatternElement {
+    Text(String),
+    Variable(String),
+    MessageReference(String),
+    Select { variable: String, variants: Vec<(VariantKey, Vec<PatternElement>)>, default: usize },
+}
+
+/// Translations of messages for a single locale, in a subset of the [Fluent](https://projectfluent.org/) syntax:
+///
+/// ```text
+/// # Comments start with a hash.
+/// hello = Hello, { $name }!
+/// app-title = My App
+/// about = About { app-title }
+/// emails = { $count ->
+///     [0] No new emails.
+///     [one] One new email.
+///    *[other] { $count } new emails.
+/// }
+/// ```
+///
+/// Messages can span multiple lines by indenting the following lines. Select expressions pick a variant by exact
+/// number, by plural category (`zero`, `one`, `two`, `few`, `many`, or `other`, depending on the language), or by
+/// string; the variant marked with `*` is the default. Attributes, terms, and functions are not supported.
+#[derive(Clone, Debug, Default)]
+pub struct MessageCatalog {
+    messages: HashMap<String, Vec<PatternElement>>,
+}
+
+impl MessageCatalog {
+    pub fn parse(source: &str) -> Result<Self, MessageCatalogError> {
+        let mut messages = HashMap::new();
+        let lines: Vec<&str> = source.lines().collect();
+        let mut line_index = 0;
+        while line_index < lines.len() {
+            let line = lines[line_index];
+            line_index += 1;
+            let first_line = line_index;
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (id, value) = line
+                .split_once('=')
+                .ok_or_else(|| MessageCatalogError { line: first_line, message: "Expected `id = message`".to_string() })?;
+            let id = id.trim();
+            if id.is_empty() || !id.chars().all(|c| c.is_alphanumeric() || c == '-' || c == '_') {
+                return Err(MessageCatalogError { line: first_line, message: format!("Invalid message id `{}`", id) });
+            }
+
+            let mut value = value.trim().to_string();
+            while line_index < lines.len() && is_continuation_line(lines[line_index]) {
+                if !value.is_empty() {
+                    value.push('\n');
+                }
+                value.push_str(lines[line_index].trim());
+                line_index += 1;
+            }
+
+            let mut parser = PatternParser { chars: value.chars().collect(), pos: 0 };
+            let pattern = parser.parse_pattern(false).map_err(|message| MessageCatalogError { line: first_line, message })?;
+            if parser.pos < parser.chars.len() {
+                return Err(MessageCatalogError { line: first_line, message: "Unexpected `}`".to_string() });
+            }
+            if messages.insert(id.to_string(), pattern).is_some() {
+                return Err(MessageCatalogError { line: first_line, message: format!("Duplicate message id `{}`", id) });
+            }
+        }
+        Ok(Self { messages })
+    }
+
+    pub fn contains(&self, id: &str) -> bool {
+        self.messages.contains_key(id)
+    }
+}
+
+fn is_continuation_line(line: &str) -> bool {
+    let trimmed = line.trim_start();
+    !trimmed.is_empty() && (trimmed.len() < line.len() || trimmed.starts_with(['}', '[', '*']))
+}
+
+struct PatternParser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl PatternParser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().map_or(false, char::is_whitespace) {
+            self.pos += 1;
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), String> {
+        self.skip_whitespace();
+        if self.peek() == Some(expected) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            Err(format!("Expected `{}`", expected))
+        }
+    }
+
+    fn parse_identifier(&mut self) -> Result<String, String> {
+        let start = self.pos;
+        while self.peek().map_or(false, |c| c.is_alphanumeric() || c == '-' || c == '_') {
+            self.pos += 1;
+        }
+        if start == self.pos {
+            return Err("Expected an identifier".to_string());
+        }
+        Ok(self.chars[start..self.pos].iter().collect())
+    }
+
+    /// Parse text and placeables until the end of the input or an unmatched `}`. In a variant we also stop at the
+    /// start of the next variant.
+    fn parse_pattern(&mut self, in_variant: bool) -> Result<Vec<PatternElement>, String> {
+        let mut elements = Vec::new();
+        let mut text = String::new();
+        while let Some(c) = self.peek() {
+            match c {
+                '{' => {
+                    self.pos += 1;
+                    if !text.is_empty() {
+                        elements.push(PatternElement::Text(std::mem::take(&mut text)));
+                    }
+                    elements.push(self.parse_placeable()?);
+                }
+                '}' => break,
+                '\n' if in_variant => {
+                    let next = self.chars[self.pos..].iter().find(|c| !c.is_whitespace());
+                    if matches!(next, None | Some('[' | '*' | '}')) {
+                        break;
+                    }
+                    text.push(c);
+                    self.pos += 1;
+                }
+                _ => {
+                    text.push(c);
+                    self.pos += 1;
+                }
+            }
+        }
+        if !text.is_empty() {
+            elements.push(PatternElement::Text(text));
+        }
+        Ok(elements)
+    }
+
+    /// Parse what comes after a `{`, up to and including the matching `}`.
+    fn parse_placeable(&mut self) -> Result<PatternElement, String> {
+        self.skip_whitespace();
+        let element = match self.peek() {
+            Some('"') => {
+                self.pos += 1;
+                let start = self.pos;
+                while self.peek().map_or(false, |c| c != '"') {
+                    self.pos += 1;
+                }
+                let text = self.chars[start..self.pos].iter().collect();
+                self.expect('"')?;
+                PatternElement::Text(text)
+            }
+            Some('$') => {
+                self.pos += 1;
+                let variable = self.parse_identifier()?;
+                self.skip_whitespace();
+                if self.chars[self.pos..].starts_with(&['-', '>']) {
+                    self.pos += 2;
+                    return self.parse_select(variable);
+                }
+                PatternElement::Variable(variable)
+            }
+            _ => PatternElement::MessageReference(self.parse_identifier()?),
+        };
+        self.expect('}')?;
+        Ok(element)
+    }
+
+    /// Parse the variants of a select expression, up to and including the closing `}`.
+    fn parse_select(&mut self, variable: String) -> Result<PatternElement, String> {
+        let mut variants = Vec::new();
+        let mut default = None;
+        loop {
+            self.skip_whitespace();
+            match self.peek() {
+                Some('}') => {
+                    self.pos += 1;
+                    break;
+                }
+                Some('*') => {
+                    if default.is_some() {
+                        return Err("Multiple default variants".to_string());
+                    }
+                    default = Some(variants.len());
+                    self.pos += 1;
+                }
+                Some('[') => {}
+                _ => return Err("Expected a variant like `[one]`".to_string()),
+            }
+            self.expect('[')?;
+            self.skip_whitespace();
+            let start = self.pos;
+            while self.peek().map_or(false, |c| c != ']') {
+                self.pos += 1;
+            }
+            let key: String = self.chars[start..self.pos].iter().collect::<String>().trim().to_string();
+            self.expect(']')?;
+            let key = key.parse().map(VariantKey::Number).unwrap_or(VariantKey::Name(key));
+
+            let mut pattern = self.parse_pattern(true)?;
+            if let Some(PatternElement::Text(text)) = pattern.first_mut() {
+                *text = text.trim_start().to_string();
+            }
+            if let Some(PatternElement::Text(text)) = pattern.last_mut() {
+                *text = text.trim_end().to_string();
+            }
+            variants.push((key, pattern));
+        }
+        let default = default.ok_or_else(|| "Missing a default variant like `*[other]`".to_string())?;
+        Ok(PatternElement::Select { variable, variants, default })
+    }
+}
+
+/// The normalized form of a locale, e.g. `de-DE` for `de_DE.UTF-8`.
+fn normalize_locale(locale: &str) -> String {
+    let locale = locale.split(['.', '@']).next().unwrap_or_default().replace('_', "-");
+    if locale.is_empty() {
+        "en-US".to_string()
+    } else {
+        locale
+    }
+}
+
+/// The language part of a locale, e.g. `de` for `de-CH`.
+fn language(locale: &str) -> String {
+    locale.split('-').next().unwrap_or_default().to_lowercase()
+}
+
+/// The [CLDR plural category](https://www.unicode.org/cldr/charts/latest/supplemental/language_plural_rules.html) of
+/// `n`, for common languages. Other languages use the English rules.
+fn plural_category(locale: &str, n: f64) -> &'static str {
+    let is_integer = n.fract() == 0.;
+    let i = n.abs() as u64;
+    let few_ending = (2..=4).contains(&(i % 10)) && !(12..=14).contains(&(i % 100));
+    match language(locale).as_str() {
+        "ja" | "zh" | "ko" | "vi" | "th" | "id" | "ms" => "other",
+        "fr" | "pt" => {
+            if is_integer && i < 2 {
+                "one"
+            } else {
+                "other"
+            }
+        }
+        "ru" | "uk" | "be" => {
+            if !is_integer {
+                "other"
+            } else if i % 10 == 1 && i % 100 != 11 {
+                "one"
+            } else if few_ending {
+                "few"
+            } else {
+                "many"
+            }
+        }
+        "pl" => {
+            if !is_integer {
+                "other"
+            } else if i == 1 {
+                "one"
+            } else if few_ending {
+                "few"
+            } else {
+                "many"
+            }
+        }
+        "cs" | "sk" => {
+            if !is_integer {
+                "many"
+            } else if i == 1 {
+                "one"
+            } else if (2..=4).contains(&i) {
+                "few"
+            } else {
+                "other"
+            }
+        }
+        _ => {
+            if is_integer && i == 1 {
+                "one"
+            } else {
+                "other"
+            }
+        }
+    }
+}
+
+/// The group and decimal separators of a locale.
+fn number_separators(locale: &str) -> (&'static str, &'static str) {
+    if locale.eq_ignore_ascii_case("de-CH") {
+        return ("’", ".");
+    }
+    match language(locale).as_str() {
+        "de" | "nl" | "it" | "es" | "pt" | "da" | "id" | "tr" | "el" => (".", ","),
+        "fr" | "ru" | "uk" | "pl" | "cs" | "sk" | "sv" | "nb" | "fi" | "hu" => ("\u{a0}", ","),
+        _ => (",", "."),
+    }
+}
+
+/// Add separators to the digits in `formatted`, which looks like `1234.5`.
+fn localize_number(locale: &str, formatted: &str, negative: bool) -> String {
+    let (group_separator, decimal_separator) = number_separators(locale);
+    let (integer, fraction) = formatted.split_once('.').unwrap_or((formatted, ""));
+    let mut result = String::new();
+    if negative {
+        result.push('-');
+    }
+    for (index, digit) in integer.chars().enumerate() {
+        if index > 0 && (integer.len() - index) % 3 == 0 {
+            result.push_str(group_separator);
+        }
+        result.push(digit);
+    }
+    if !fraction.is_empty() {
+        result.push_str(decimal_separator);
+        result.push_str(fraction);
+    }
+    result
+}
+
+fn format_number(locale: &str, value: f64, decimals: Option<usize>) -> String {
+    if value.is_nan() {
+        return "NaN".to_string();
+    }
+    if value.is_infinite() {
+        return if value > 0. { "∞".to_string() } else { "-∞".to_string() };
+    }
+    let formatted = match decimals {
+        Some(decimals) => format!("{:.*}", decimals, value.abs()),
+        None => format!("{}", value.abs()),
+    };
+    // Don't show "-0" when a small negative number gets rounded to zero.
+    let negative = value < 0. && formatted.chars().any(|c| c.is_ascii_digit() && c != '0');
+    localize_number(locale, &formatted, negative)
+}
+
+/// A date and time, e.g. to format using [`Cx::format_date`] and [`Cx::format_time`].
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct DateTime {
+    pub year: i32,
+    /// From 1 to 12.
+    pub month: u32,
+    /// From 1 to 31.
+    pub day: u32,
+    pub hour: u32,
+    pub minute: u32,
+    pub second: u32,
+}
+
+impl DateTime {
+    /// Convert seconds since 1970-01-01 00:00 UTC into a [`DateTime`] in UTC. Add the time zone offset to `timestamp`
+    /// first to get local time.
+    pub fn from_unix_timestamp(timestamp: f64) -> Self {
+        let seconds = timestamp.floor() as i64;
+        let days = seconds.div_euclid(86400);
+        let seconds_of_day = seconds.rem_euclid(86400) as u32;
+
+        // From <http://howardhinnant.github.io/date_algorithms.html#civil_from_days>.
+        let z = days + 719468;
+        let era = z.div_euclid(146097);
+        let day_of_era = z.rem_euclid(146097);
+        let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let mp = (5 * day_of_year + 2) / 153;
+        let day = (day_of_year - (153 * mp + 2) / 5 + 1) as u32;
+        let month = (if mp < 10 { mp + 3 } else { mp - 9 }) as u32;
+        let year = (year_of_era + era * 400 + i64::from(month <= 2)) as i32;
+
+        Self { year, month, day, hour: seconds_of_day / 3600, minute: seconds_of_day / 60 % 60, second: seconds_of_day % 60 }
+    }
+}
+
+fn format_date(locale: &str, date: &DateTime) -> String {
+    let DateTime { year, month, day, .. } = *date;
+    match locale {
+        "en-GB" | "en-AU" | "en-NZ" | "en-IE" | "en-IN" => return format!("{:02}/{:02}/{}", day, month, year),
+        "en-CA" => return format!("{}-{:02}-{:02}", year, month, day),
+        _ => {}
+    }
+    match language(locale).as_str() {
+        "en" => format!("{}/{}/{}", month, day, year),
+        "de" | "ru" | "uk" | "pl" | "cs" | "fi" | "nb" | "da" | "tr" => format!("{:02}.{:02}.{}", day, month, year),
+        "fr" | "es" | "it" | "pt" | "el" | "vi" | "id" => format!("{:02}/{:02}/{}", day, month, year),
+        "nl" => format!("{:02}-{:02}-{}", day, month, year),
+        "ja" | "zh" => format!("{}/{:02}/{:02}", year, month, day),
+        "ko" => format!("{}. {}. {}.", year, month, day),
+        _ => format!("{}-{:02}-{:02}", year, month, day),
+    }
+}
+
+fn format_time(locale: &str, time: &DateTime) -> String {
+    let twelve_hour = matches!(locale, "en-US" | "en-CA" | "en-AU" | "en-NZ" | "en-IN" | "en") || language(locale) == "ko";
+    if twelve_hour {
+        let hour = if time.hour % 12 == 0 { 12 } else { time.hour % 12 };
+        format!("{}:{:02} {}", hour, time.minute, if time.hour < 12 { "AM" } else { "PM" })
+    } else {
+        format!("{:02}:{:02}", time.hour, time.minute)
+    }
+}
+
+/// Initial locale, from the environment variables that are used on Unix-like systems.
+fn system_locale() -> String {
+    #[cfg(not(target_arch = "wasm32"))]
+    for name in ["LC_ALL", "LC_MESSAGES", "LANG"] {
+        if let Ok(value) = std::env::var(name) {
+            if !value.is_empty() && value != "C" && value != "POSIX" {
+                return normalize_locale(&value);
+            }
+        }
+    }
+    "en-US".to_string()
+}
+
+/// Localization state on [`Cx`].
+pub(crate) struct CxLocalization {
+    /// See [`Cx::locale`].
+    pub(crate) locale: String,
+    /// Set by [`Cx::set_locale`], and applied after the current event handler.
+    pub(crate) next_locale: Option<String>,
+    catalogs: HashMap<String, MessageCatalog>,
+    /// See [`Cx::set_fallback_locale`].
+    fallback_locale: Option<String>,
+}
+
+impl Default for CxLocalization {
+    fn default() -> Self {
+        Self { locale: system_locale(), next_locale: None, catalogs: HashMap::new(), fallback_locale: None }
+    }
+}
+
+impl CxLocalization {
+    /// Find the catalog with message `id`: in the current locale, then its language, and then the fallback locale.
+    fn find_catalog(&self, id: &str) -> Option<&MessageCatalog> {
+        let candidates = [Some(self.locale.clone()), Some(language(&self.locale)), self.fallback_locale.clone()];
+        candidates.iter().flatten().filter_map(|locale| self.catalogs.get(locale)).find(|catalog| catalog.contains(id))
+    }
+
+    fn translate(&self, id: &str, args: &[(&str, MessageArg)], depth: usize, output: &mut String) {
+        match self.find_catalog(id) {
+            Some(catalog) if depth < 10 => self.format_pattern(&catalog.messages[id], args, depth, output),
+            // Show the id of missing messages, so they're easy to spot.
+            _ => output.push_str(id),
+        }
+    }
+
+    fn format_pattern(&self, pattern: &[PatternElement], args: &[(&str, MessageArg)], depth: usize, output: &mut String) {
+        for element in pattern {
+            match element {
+                PatternElement::Text(text) => output.push_str(text),
+                PatternElement::Variable(name) => match args.iter().find(|(arg_name, _)| arg_name == name) {
+                    Some((_, MessageArg::String(value))) => output.push_str(value),
+                    Some((_, MessageArg::Number(value))) => output.push_str(&format_number(&self.locale, *value, None)),
+                    None => output.push_str(&format!("{{${}}}", name)),
+                },
+                PatternElement::MessageReference(id) => self.translate(id, args, depth + 1, output),
+                PatternElement::Select { variable, variants, default } => {
+                    let arg = args.iter().find(|(arg_name, _)| arg_name == variable).map(|(_, arg)| arg);
+                    let is_match = |key: &VariantKey| match (key, arg) {
+                        (VariantKey::Number(key), Some(MessageArg::Number(value))) => key == value,
+                        (VariantKey::Name(key), Some(MessageArg::Number(value))) => key == plural_category(&self.locale, *value),
+                        (VariantKey::Name(key), Some(MessageArg::String(value))) => key == value,
+                        _ => false,
+                    };
+                    // Exact numbers take precedence over plural categories.
+                    let index = variants
+                        .iter()
+                        .position(|(key, _)| matches!(key, VariantKey::Number(_)) && is_match(key))
+                        .or_else(|| variants.iter().position(|(key, _)| is_match(key)))
+                        .unwrap_or(*default);
+                    self.format_pattern(&variants[index].1, args, depth, output);
+                }
+            }
+        }
+    }
+}
+
+impl Cx {
+    /// The current locale, like `en-US` or `de-CH`. Natively this starts out as the system locale as configured in
+    /// `LC_ALL`, `LC_MESSAGES`, or `LANG`, and otherwise as `en-US`.
+    pub fn locale(&self) -> &str {
+        &self.localization.locale
+    }
+
+    /// Change the locale, e.g. `de-DE` (`de_DE.UTF-8` works too). After the current event handler an
+    /// [`Event::LocaleChanged`] gets fired, and everything gets redrawn, including cached [`View`]s.
+    pub fn set_locale(&mut self, locale: &str) {
+        self.localization.next_locale = Some(normalize_locale(locale));
+    }
+
+    /// Add the translations for a locale, like `de-DE`, or a language, like `de`. Messages that are missing in the
+    /// current locale are looked up in its language, and then in the fallback locale. Replaces any catalog that was
+    /// previously added for the same locale.
+    pub fn add_message_catalog(&mut self, locale: &str, catalog: MessageCatalog) {
+        self.localization.catalogs.insert(normalize_locale(locale), catalog);
+        self.invalidate_view_caches();
+    }
+
+    /// Set the locale to use for messages that are missing in the current locale, typically the language that the
+    /// app was written in.
+    pub fn set_fallback_locale(&mut self, locale: &str) {
+        self.localization.fallback_locale = Some(normalize_locale(locale));
+    }
+
+    /// Get message `id` in the current locale. Returns `id` itself if the message doesn't exist.
+    pub fn translate(&self, id: &str) -> String {
+        self.translate_with_args(id, &[])
+    }
+
+    /// Get message `id` in the current locale, filling in variables:
+    ///
+    /// ```ignore
+    /// cx.translate_with_args("emails", &[("count", 3.into())])
+    /// ```
+    pub fn translate_with_args(&self, id: &str, args: &[(&str, MessageArg)]) -> String {
+        let mut output = String::new();
+        self.localization.translate(id, args, 0, &mut output);
+        output
+    }
+
+    /// Format a number for the current locale, e.g. `1,234.50` in `en-US` and `1.234,50` in `de-DE`. With
+    /// `decimals` set to [`None`], as many decimals are shown as needed.
+    pub fn format_number(&self, value: f64, decimals: Option<usize>) -> String {
+        format_number(&self.localization.locale, value, decimals)
+    }
+
+    /// Format the date part of `date` in the short numeric style of the current locale, e.g. `3/14/2024` in `en-US`
+    /// and `14.03.2024` in `de-DE`.
+    pub fn format_date(&self, date: &DateTime) -> String {
+        format_date(&self.localization.locale, date)
+    }
+
+    /// Format the hours and minutes of `time` for the current locale, e.g. `2:05 PM` in `en-US` and `14:05` in
+    /// `de-DE`.
+    pub fn format_time(&self, time: &DateTime) -> String {
+        format_time(&self.localization.locale, time)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EN: &str = "
+# A comment.
+hello = Hello, { $name }!
+app-title = My App
+about = About { app-title }
+braces = { \"{\" }literal{ \"}\" }
+emails = { $count ->
+    [0] No new emails.
+    [one] One new email.
+   *[other] { $count } new emails.
+}
+multiline = First line
+    second line
+";
+
+    const DE: &str = "
+hello = Hallo, { $name }!
+emails = { $count ->
+    [one] Eine neue E-Mail.
+   *[other] { $count } neue E-Mails.
+}
+";
+
+    #[test]
+    fn test_parse_errors() {
+        let error = |source| MessageCatalog::parse(source).unwrap_err();
+        assert_eq!(error("\nhello"), MessageCatalogError { line: 2, message: "Expected `id = message`".to_string() });
+        assert_eq!(error("a = { $b"), MessageCatalogError { line: 1, message: "Expected `}`".to_string() });
+        assert_eq!(error("a = b }").message, "Unexpected `}`");
+        assert_eq!(error("a = { $b ->\n  [one] c\n}").message, "Missing a default variant like `*[other]`");
+        assert_eq!(error("a = b\na = c").line, 2);
+    }
+
+    #[test]
+    fn test_translate() {
+        let mut test_cx = TestCx::new(vec2(100., 100.));
+        let cx = &mut test_cx.cx;
+        cx.localization.locale = "en-US".to_string();
+        cx.add_message_catalog("en", MessageCatalog::parse(EN).unwrap());
+        cx.add_message_catalog("de", MessageCatalog::parse(DE).unwrap());
+        cx.set_fallback_locale("en");
+
+        assert_eq!(cx.translate_with_args("hello", &[("name", "World".into())]), "Hello, World!");
+        assert_eq!(cx.translate("hello"), "Hello, {$name}!");
+        assert_eq!(cx.translate("about"), "About My App");
+        assert_eq!(cx.translate("braces"), "{literal}");
+        assert_eq!(cx.translate("multiline"), "First line\nsecond line");
+        assert_eq!(cx.translate("missing"), "missing");
+        assert_eq!(cx.translate_with_args("emails", &[("count", 0.into())]), "No new emails.");
+        assert_eq!(cx.translate_with_args("emails", &[("count", 1.into())]), "One new email.");
+        assert_eq!(cx.translate_with_args("emails", &[("count", 1234.into())]), "1,234 new emails.");
+
+        cx.localization.locale = "de-CH".to_string();
+        assert_eq!(cx.translate_with_args("hello", &[("name", "Welt".into())]), "Hallo, Welt!");
+        assert_eq!(cx.translate_with_args("emails", &[("count", 0.into())]), "0 neue E-Mails.");
+        assert_eq!(cx.translate_with_args("emails", &[("count", 1234.into())]), "1’234 neue E-Mails.");
+        // Falls back to English.
+        assert_eq!(cx.translate("about"), "About My App");
+    }
+
+    #[test]
+    fn test_set_locale() {
+        let mut test_cx = TestCx::new(vec2(100., 100.));
+        let results = test_cx.dispatch(Event::None, |cx, event| match event {
+            Event::None => {
+                cx.set_locale("nl_NL.UTF-8");
+                None
+            }
+            Event::LocaleChanged(lc) => Some(lc.new_locale.clone()),
+            _ => None,
+        });
+        assert_eq!(results, vec![None, Some("nl-NL".to_string())]);
+        assert_eq!(test_cx.cx.locale(), "nl-NL");
+        assert!(test_cx.cx.requested_draw);
+    }
+
+    #[test]
+    fn test_plural_category() {
+        assert_eq!(plural_category("en-US", 1.), "one");
+        assert_eq!(plural_category("en-US", 1.5), "other");
+        assert_eq!(plural_category("fr", 0.), "one");
+        assert_eq!(plural_category("ja", 1.), "other");
+        assert_eq!(plural_category("ru", 21.), "one");
+        assert_eq!(plural_category("ru", 22.), "few");
+        assert_eq!(plural_category("ru", 12.), "many");
+        assert_eq!(plural_category("pl", 25.), "many");
+        assert_eq!(plural_category("cs", 3.), "few");
+    }
+
+    #[test]
+    fn test_format_number() {
+        assert_eq!(format_number("en-US", 1234567.891, Some(2)), "1,234,567.89");
+        assert_eq!(format_number("de-DE", -1234.5, Some(2)), "-1.234,50");
+        assert_eq!(format_number("fr-FR", 1234.5, None), "1\u{a0}234,5");
+        assert_eq!(format_number("en-US", 123., None), "123");
+        assert_eq!(format_number("en-US", -0.001, Some(1)), "0.0");
+    }
+
+    #[test]
+    fn test_format_date_and_time() {
+        let date = DateTime::from_unix_timestamp(1710425100.);
+        assert_eq!(date, DateTime { year: 2024, month: 3, day: 14, hour: 14, minute: 5, second: 0 });
+        assert_eq!(DateTime::from_unix_timestamp(-1.).year, 1969);
+
+        assert_eq!(format_date("en-US", &date), "3/14/2024");
+        assert_eq!(format_date("en-GB", &date), "14/03/2024");
+        assert_eq!(format_date("de-DE", &date), "14.03.2024");
+        assert_eq!(format_date("ja-JP", &date), "2024/03/14");
+        assert_eq!(format_date("sv-SE", &date), "2024-03-14");
+        assert_eq!(format_time("en-US", &date), "2:05 PM");
+        assert_eq!(format_time("de-DE", &date), "14:05");
+    }
+}
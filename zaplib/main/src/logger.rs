@@ -0,0 +1,123 @@
+//! Backend for the [`log`] crate, so that crates using [`log::info!`] and friends print something.
+//!
+//! In WebAssembly this is installed automatically, and logs to the corresponding `console.*` method
+//! in the browser (`console.error`, `console.warn`, etc). Natively you can opt in using [`init_logger`],
+//! or use any other [`log`] backend.
+
+use crate::*;
+use once_cell::sync::Lazy;
+use std::sync::RwLock;
+
+/// Which levels to log, parsed from a filter string like `"info,my_crate::parser=trace"`.
+#[derive(Debug, PartialEq)]
+struct LogFilter {
+    default: log::LevelFilter,
+    /// Module paths with their own level, sorted from longest to shortest so that the most specific
+    /// one matches first.
+    targets: Vec<(String, log::LevelFilter)>,
+}
+
+impl LogFilter {
+    /// Parse a comma-separated list of `level` or `target=level` directives, similar to the
+    /// `RUST_LOG` syntax of `env_logger`. Invalid directives are ignored.
+    fn parse(filter: &str) -> Self {
+        let mut default = log::LevelFilter::Info;
+        let mut targets = Vec::new();
+        for directive in filter.split(',').map(str::trim).filter(|directive| !directive.is_empty()) {
+            match directive.split_once('=') {
+                Some((target, level)) => {
+                    if let Ok(level) = level.trim().parse() {
+                        targets.push((target.trim().to_string(), level));
+                    }
+                }
+                None => {
+                    if let Ok(level) = directive.parse() {
+                        default = level;
+                    }
+                }
+            }
+        }
+        targets.sort_by_key(|(target, _)| std::cmp::Reverse(target.len()));
+        Self { default, targets }
+    }
+
+    fn level_for(&self, target: &str) -> log::LevelFilter {
+        self.targets
+            .iter()
+            .find(|(prefix, _)| {
+                matches!(target.strip_prefix(prefix.as_str()), Some(rest) if rest.is_empty() || rest.starts_with("::"))
+            })
+            .map_or(self.default, |(_, level)| *level)
+    }
+
+    fn max_level(&self) -> log::LevelFilter {
+        self.targets.iter().map(|(_, level)| *level).fold(self.default, std::cmp::max)
+    }
+}
+
+static FILTER: Lazy<RwLock<LogFilter>> = Lazy::new(|| RwLock::new(LogFilter::parse("info")));
+
+struct ZaplibLogger;
+
+impl log::Log for ZaplibLogger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        metadata.level() <= FILTER.read().unwrap().level_for(metadata.target())
+    }
+
+    fn log(&self, record: &log::Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let message = format!("[{}] {} - {}", record.level(), record.target(), record.args());
+        if is_diagnostics_enabled() {
+            push_breadcrumb(BreadcrumbCategory::Log, message.clone());
+        }
+        #[cfg(target_arch = "wasm32")]
+        crate::cx_wasm32::console_log_with_level(record.level(), &message);
+        #[cfg(not(target_arch = "wasm32"))]
+        println!("{}", message);
+    }
+
+    fn flush(&self) {}
+}
+
+/// Install zaplib's [`log`] backend, if no other backend has been installed yet. This already
+/// happens automatically in WebAssembly. Only `info` and up are logged by default; use
+/// [`set_log_filter`] to change that.
+pub fn init_logger() {
+    if log::set_logger(&ZaplibLogger).is_ok() {
+        log::set_max_level(FILTER.read().unwrap().max_level());
+    }
+}
+
+/// Change which log messages are printed, using a comma-separated list of `level` or `target=level`
+/// directives, e.g. `"warn,my_crate=debug,my_crate::parser=trace"`. Targets match the module path and
+/// all of its submodules. Applies to all threads (web workers).
+///
+/// In JavaScript you can call this using `zaplib.setLogFilter("...")`.
+pub fn set_log_filter(filter: &str) {
+    let filter = LogFilter::parse(filter);
+    log::set_max_level(filter.max_level());
+    *FILTER.write().unwrap() = filter;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use log::LevelFilter;
+
+    #[test]
+    fn test_log_filter() {
+        let filter = LogFilter::parse("warn, my_crate=debug,my_crate::parser=trace,bogus=nope,");
+        assert_eq!(filter.default, LevelFilter::Warn);
+        assert_eq!(filter.level_for("other_crate"), LevelFilter::Warn);
+        assert_eq!(filter.level_for("my_crate"), LevelFilter::Debug);
+        assert_eq!(filter.level_for("my_crate::ui"), LevelFilter::Debug);
+        assert_eq!(filter.level_for("my_crate::parser::tokens"), LevelFilter::Trace);
+        // Prefix needs to be a whole module name.
+        assert_eq!(filter.level_for("my_crate_extra"), LevelFilter::Warn);
+        assert_eq!(filter.max_level(), LevelFilter::Trace);
+
+        assert_eq!(LogFilter::parse("").default, LevelFilter::Info);
+    }
+}
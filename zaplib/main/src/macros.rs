@@ -16,37 +16,16 @@ macro_rules! main_app {
             cx.set_finished_app_new();
             cx.event_loop(|cx, mut event| {
                 match event {
-                    Event::System(e) => {
-                        match e {
-                            SystemEvent::Draw => {
-                                app.draw(cx);
-                                cxafterdraw.after_draw(cx);
-                            }
-                            SystemEvent::WebRustCall(e) => {
-                                let WebRustCallEvent { name, params, callback_id } = std::mem::take(e).unwrap();
-                                let call_rust_async_fn =
-                                    cx.call_rust_async_fn.expect("`callRustAsync` called but no on_call_rust_async registered");
-                                unsafe {
-                                    let func = Box::from_raw(
-                                        call_rust_async_fn
-                                            as *mut fn(
-                                                this: &mut $app,
-                                                cx: &mut Cx,
-                                                name: String,
-                                                params: Vec<ZapParam>,
-                                            ) -> Vec<ZapParam>,
-                                    );
-                                    let mut return_params = func(&mut app, cx, name, params);
-
-                                    // Prevent call_rust_async_fn from getting dropped
-                                    Box::into_raw(func);
-
-                                    cx.return_to_js(callback_id, return_params);
-                                }
-                            }
-                            _ => {}
+                    Event::System(e) => match e {
+                        SystemEvent::Draw => {
+                            app.draw(cx);
+                            cxafterdraw.after_draw(cx);
                         }
-                    }
+                        SystemEvent::WebRustCall(e) => unsafe {
+                            cx.handle_web_rust_call(&mut app, std::mem::take(e).unwrap());
+                        },
+                        _ => {}
+                    },
                     _ => {
                         app.handle(cx, &mut event);
                     }
@@ -54,6 +33,17 @@ macro_rules! main_app {
             });
         }
 
+        /// Called by Android's `NativeActivity` on the UI thread, which then runs `main` on a separate thread.
+        #[cfg(target_os = "android")]
+        #[no_mangle]
+        pub unsafe extern "C" fn ANativeActivity_onCreate(
+            activity: *mut std::os::raw::c_void,
+            _saved_state: *mut std::os::raw::c_void,
+            _saved_state_size: usize,
+        ) {
+            Cx::android_on_create(activity, main);
+        }
+
         #[cfg(target_arch = "wasm32")]
         fn main() {}
 
@@ -72,40 +62,19 @@ macro_rules! main_app {
         #[export_name = "processWasmEvents"]
         pub unsafe extern "C" fn process_wasm_events(appcx: u64, msg_bytes: u64) -> u64 {
             let appcx = &*(appcx as *mut (*mut $app, *mut Cx, *mut CxAfterDraw));
-            (*appcx.1).process_wasm_events(msg_bytes, |cx, mut event| {
-                match event {
-                    Event::System(e) => {
-                        match e {
-                            SystemEvent::Draw => {
-                                (*appcx.0).draw(cx);
-                                (*appcx.2).after_draw(cx);
-                            }
-                            SystemEvent::WebRustCall(e) => {
-                                let WebRustCallEvent { name, params, callback_id } = std::mem::take(e).unwrap();
-
-                                let call_rust_async_fn =
-                                    cx.call_rust_async_fn.expect("call_rust called but no on_call_rust_sync registered");
-                                let func = Box::from_raw(
-                                    call_rust_async_fn
-                                        as *mut fn(
-                                            this: &mut $app,
-                                            cx: &mut Cx,
-                                            name: String,
-                                            params: Vec<ZapParam>,
-                                        ) -> Vec<ZapParam>,
-                                );
-                                let mut return_params = func(&mut *appcx.0, cx, name, params);
-                                // Prevent call_rust_async_fn from getting dropped
-                                Box::into_raw(func);
-
-                                cx.return_to_js(callback_id, return_params);
-                            }
-                            _ => {}
-                        }
+            (*appcx.1).process_wasm_events(msg_bytes, |cx, mut event| match event {
+                Event::System(e) => match e {
+                    SystemEvent::Draw => {
+                        (*appcx.0).draw(cx);
+                        (*appcx.2).after_draw(cx);
                     }
-                    _ => {
-                        (*appcx.0).handle(cx, event);
+                    SystemEvent::WebRustCall(e) => {
+                        cx.handle_web_rust_call(&mut *appcx.0, std::mem::take(e).unwrap());
                     }
+                    _ => {}
+                },
+                _ => {
+                    (*appcx.0).handle(cx, event);
                 }
             })
         }
@@ -127,12 +96,26 @@ macro_rules! main_app {
 /// of Zaplib features that end up unused. We should split out the framework in the future
 /// so that thisuse case does not initialize unused parts of Zaplib as well as JavaScript
 /// mouse and keyboard event handlers.
+///
+/// Pass either a `fn call_rust(name: String, params: Vec<ZapParam>) -> Vec<ZapParam>`, or use
+/// `register_call_rust!(fns: call_rust_fns)` with a `fn call_rust_fns() -> CallRustFns`, which
+/// also makes `cargo zaplib generate-types` work.
 #[macro_export]
 macro_rules! register_call_rust {
+    (fns: $ call_rust_fns: ident) => {
+        fn call_rust(name: String, params: Vec<ZapParam>) -> Vec<ZapParam> {
+            CallRustFns::registered($call_rust_fns).call(name, params)
+        }
+        register_call_rust!(@app call_rust, CallRustFns::registered($call_rust_fns).write_typescript_if_requested());
+    };
     ( $ call_rust: ident) => {
+        register_call_rust!(@app $call_rust, ());
+    };
+    (@app $ call_rust: ident, $ on_new: expr) => {
         struct App {}
         impl App {
             fn new(cx: &mut Cx) -> Self {
+                $on_new;
                 cx.on_call_rust_async(Self::on_call_rust_async);
                 cx.on_call_rust_sync(Self::on_call_rust_sync);
                 Self {}
@@ -141,11 +124,11 @@ macro_rules! register_call_rust {
             fn handle(&mut self, cx: &mut Cx, event: &mut Event) {}
 
             fn on_call_rust_async(&mut self, cx: &mut Cx, name: String, params: Vec<ZapParam>) -> Vec<ZapParam> {
-                call_rust(name, params)
+                $call_rust(name, params)
             }
 
             fn on_call_rust_sync(name: String, params: Vec<ZapParam>) -> Vec<ZapParam> {
-                call_rust(name, params)
+                $call_rust(name, params)
             }
 
             fn draw(&mut self, cx: &mut Cx) {}
@@ -0,0 +1,76 @@
+//! Reporting of memory usage, to help track down memory growth in long-running apps.
+
+use crate::*;
+
+/// Approximate heap usage in bytes, broken down by subsystem. See [`Cx::memory_stats`].
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct MemoryStats {
+    /// CPU-side pixel data of [`Texture`]s.
+    pub textures: usize,
+    /// Instances and uniforms of all [`DrawCall`]s, including the previous instances of retained [`View`]s.
+    pub instance_buffers: usize,
+    /// Font files that were loaded at runtime, font atlas bookkeeping, and the text shaping cache.
+    pub fonts: usize,
+    /// Temporary data that gets reused every draw, like the defaults returned by [`Area::get_first`].
+    pub frame_data: usize,
+    /// The total size of the WebAssembly heap. [`None`] natively, where the heap is shared with the rest of the
+    /// process.
+    pub total_heap: Option<usize>,
+    /// Everything in [`MemoryStats::total_heap`] that isn't accounted for by the fields above: your own data, but
+    /// also allocator overhead and free memory. The WebAssembly heap never shrinks, so a steadily growing heap
+    /// with a stable breakdown typically means fragmentation or short-lived allocation spikes.
+    pub user_data: Option<usize>,
+}
+
+impl Cx {
+    /// Get an approximate breakdown of heap usage. Cheap enough to call every frame, e.g. to show in a debug
+    /// overlay or to log periodically.
+    pub fn memory_stats(&self) -> MemoryStats {
+        let textures: usize = self.textures.iter().map(|texture| texture.image_u32.capacity() * std::mem::size_of::<u32>()).sum();
+
+        let instance_buffers: usize = self
+            .views
+            .iter()
+            .flat_map(|view| view.draw_calls.iter())
+            .map(|draw_call| {
+                let floats = draw_call.instances.capacity()
+                    + draw_call.user_uniforms.capacity()
+                    + draw_call.previous_instances.as_ref().map_or(0, |instances| instances.capacity());
+                floats * std::mem::size_of::<f32>() + draw_call.textures_2d.capacity() * std::mem::size_of::<u32>()
+            })
+            .sum();
+
+        let fonts = self.fonts_data.read().unwrap().memory_usage();
+        let frame_data = self.temp_default_data.capacity();
+
+        #[cfg(target_arch = "wasm32")]
+        let total_heap = Some(core::arch::wasm32::memory_size::<0>() * 65536);
+        #[cfg(not(target_arch = "wasm32"))]
+        let total_heap = None;
+
+        let user_data =
+            total_heap.map(|total_heap: usize| total_heap.saturating_sub(textures + instance_buffers + fonts + frame_data));
+
+        MemoryStats { textures, instance_buffers, fonts, frame_data, total_heap, user_data }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_memory_stats() {
+        let mut test_cx = TestCx::new(vec2(100., 100.));
+        let before = test_cx.cx.memory_stats();
+        assert_eq!(before.total_heap, None);
+        assert_eq!(before.user_data, None);
+
+        let handle = Texture::default().get_color(&mut test_cx.cx);
+        handle.set_dimensions(&mut test_cx.cx, 10, 100);
+        test_cx.cx.temp_default_data.alloc(1u32);
+        let after = test_cx.cx.memory_stats();
+        assert_eq!(after.textures, before.textures + 10 * 100 * 4);
+        assert!(after.frame_data > before.frame_data);
+    }
+}
@@ -1,4 +1,4 @@
-//! Defining native menus.
+//! Defining native menus, and icons in the system tray.
 
 use crate::*;
 
@@ -57,7 +57,7 @@ impl CommandId {
 }
 
 /// Represents a single menu, as well as all menus (recursively).
-#[derive(PartialEq, Clone)]
+#[derive(PartialEq, Clone, Debug)]
 pub enum Menu {
     Main { items: Vec<Menu> },
     Item { name: String, command: CommandId },
@@ -82,3 +82,21 @@ impl Menu {
         Menu::Item { name: name.to_string(), command }
     }
 }
+
+/// An icon in the system tray on Windows and Linux, or in the menu bar on Mac, set using
+/// [`CxPlatformCommon::update_tray`]. Clicking the icon opens a menu with [`Tray::items`], and picking an item fires
+/// [`Event::Command`], just like items in the main [`Menu`]. Like there, items are only enabled after calling
+/// [`CommandId::set_enabled`].
+///
+/// This lets long-running tools keep going after minimizing their windows, and be brought back from the tray.
+///
+/// On Linux this uses the XEmbed system tray protocol, which most desktop environments support (GNOME needs an
+/// extension). Submenus are shown inline there, and item names can only contain Latin-1 characters.
+#[derive(PartialEq, Clone, Debug)]
+pub struct Tray {
+    /// Shown when hovering over the icon. Not supported on Linux.
+    pub tooltip: String,
+    pub icon: WindowIcon,
+    /// The items of the menu; see [`Menu::item`], [`Menu::sub`], and [`Menu::line`]. Don't use [`Menu::Main`] here.
+    pub items: Vec<Menu>,
+}
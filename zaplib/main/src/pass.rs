@@ -120,6 +120,37 @@ impl Pass {
         }
     }
 
+    /// Draw this [`Pass`] into the VR headset while its window is presenting to one (see
+    /// [`Window::xr_start_presenting`]), instead of into its [`Texture`]s. It then gets drawn once for every eye,
+    /// using the [`XrUpdateEvent::views`] of the latest [`Event::XrUpdate`] instead of its [`PassMatrixMode`]. Its
+    /// textures keep what was drawn before presenting, so e.g. the page still shows the scene.
+    ///
+    /// Not supported for the main [`Pass`] of a window, and only in WebAssembly, since other platforms can't present
+    /// to headsets.
+    pub fn set_xr_stereo(&mut self, cx: &mut Cx, xr_stereo: bool) {
+        if let Some(pass_id) = self.pass_id {
+            let cxpass = &mut cx.passes[pass_id];
+            if cxpass.xr_stereo != xr_stereo {
+                cxpass.xr_stereo = xr_stereo;
+                cxpass.paint_dirty = true;
+            }
+        }
+    }
+
+    /// Set how text gets drawn in this [`Pass`], e.g. to make small text more legible on low-DPI screens. This is
+    /// not inherited by [`Pass`]es inside of this one.
+    ///
+    /// Call this after [`Pass::begin_pass`], and before drawing any text.
+    pub fn set_text_rendering(&mut self, cx: &mut Cx, text_rendering: TextRendering) {
+        let pass_id = self.pass_id.expect("Please call set_text_rendering after begin_pass");
+        let cxpass = &mut cx.passes[pass_id];
+        if cxpass.text_rendering != text_rendering {
+            cxpass.text_rendering = text_rendering;
+            cxpass.pass_uniforms.text_gamma = text_rendering.gamma;
+            cxpass.paint_dirty = true;
+        }
+    }
+
     pub fn end_pass(&mut self, cx: &mut Cx) {
         cx.pass_stack.pop();
         if !cx.pass_stack.is_empty() {
@@ -173,6 +204,8 @@ pub(crate) struct PassUniforms {
     /// TODO(JP): What does this accomplish exactly? Do we need to compute this globally
     /// or can we make this a helper? It only seems to really be used in text rendering?
     dpi_dilate: f32,
+    /// See [`TextRendering::gamma`].
+    text_gamma: f32,
 }
 
 impl PassUniforms {
@@ -187,7 +220,18 @@ impl PassUniforms {
 #[derive(Clone)]
 pub enum PassMatrixMode {
     Ortho,
-    Projection { fov_y: f32, near: f32, far: f32, cam: Mat4 },
+    Projection {
+        fov_y: f32,
+        near: f32,
+        far: f32,
+        cam: Mat4,
+    },
+    /// Fixed projection and view matrices, e.g. an orthographic projection for rendering a shadow map. Unlike
+    /// [`PassMatrixMode::Projection`], this doesn't adapt to the aspect ratio of the [`Pass`].
+    Custom {
+        projection: Mat4,
+        view: Mat4,
+    },
 }
 
 #[derive(Clone)]
@@ -203,6 +247,11 @@ pub(crate) struct CxPass {
     pub(crate) pass_size: Vec2,
     pub(crate) pass_uniforms: PassUniforms,
     pub(crate) zbias_step: f32,
+    /// See [`Pass::set_text_rendering`].
+    pub(crate) text_rendering: TextRendering,
+    /// See [`Pass::set_xr_stereo`].
+    #[allow(dead_code)] // Not used in all platforms currently.
+    pub(crate) xr_stereo: bool,
     #[allow(dead_code)] // Not used in all platforms currently.
     pub(crate) platform: CxPlatformPass,
 }
@@ -212,7 +261,9 @@ impl Default for CxPass {
         CxPass {
             matrix_mode: PassMatrixMode::Ortho,
             zbias_step: 0.001,
-            pass_uniforms: PassUniforms::default(),
+            pass_uniforms: PassUniforms { text_gamma: TextRendering::DEFAULT.gamma, ..PassUniforms::default() },
+            text_rendering: TextRendering::DEFAULT,
+            xr_stereo: false,
             color_textures: Vec::new(),
             depth_texture: None,
             override_dpi_factor: None,
@@ -279,6 +330,29 @@ impl CxPass {
                 // rotation matrices are orthogonal, meaning that their inverse is equal to their tranpose.
                 self.uniform_inv_camera_rot(&cam.as_rotation().transpose());
             }
+            PassMatrixMode::Custom { projection, view } => {
+                self.uniform_camera_projection(&projection);
+                self.uniform_camera_view(&view);
+                self.uniform_inv_camera_rot(&view.as_rotation().transpose());
+            }
         };
     }
+
+    /// The [`PassUniforms`] for drawing one eye of a [`Pass::set_xr_stereo`] pass, after [`CxPass::set_matrix`].
+    #[allow(dead_code)] // Not used in all platforms currently.
+    pub(crate) fn xr_eye_uniforms(&self, view: &XrView) -> PassUniforms {
+        PassUniforms {
+            camera_projection: view.projection.v,
+            camera_view: view.view.v,
+            inv_camera_rot: view.view.as_rotation().transpose().v,
+            ..self.pass_uniforms.clone()
+        }
+    }
+}
+
+impl Cx {
+    /// The [`TextRendering`] of the [`Pass`] that is currently being drawn; see [`Pass::set_text_rendering`].
+    pub fn current_text_rendering(&self) -> TextRendering {
+        self.pass_stack.last().map_or(TextRendering::DEFAULT, |pass_id| self.passes[*pass_id].text_rendering)
+    }
 }
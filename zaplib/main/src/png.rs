@@ -0,0 +1,147 @@
+//! Decoding PNG images, for color glyphs in fonts (see [`zaplib_vector::font::ColorBitmap`]) and for images
+//! loaded at runtime, like map tiles. For encoding, see [`crate::capture::CapturedFrame::to_png`].
+
+use std::convert::TryInto;
+use std::io::Read;
+
+/// A decoded PNG image.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DecodedPng {
+    pub width: usize,
+    pub height: usize,
+    /// RGBA pixels, from top to bottom, without premultiplied alpha.
+    pub pixels: Vec<[u8; 4]>,
+}
+
+/// Decode a PNG image. Supports 8-bit images of all color types, and palette and grayscale images with fewer bits,
+/// which covers what fonts use. Returns [`None`] for images that are invalid or not supported, like interlaced
+/// ones.
+pub fn decode_png(bytes: &[u8]) -> Option<DecodedPng> {
+    let mut rest = bytes.strip_prefix(b"\x89PNG\r\n\x1a\n")?;
+    let mut header = None;
+    let mut palette: &[u8] = &[];
+    let mut transparency: &[u8] = &[];
+    let mut compressed = Vec::new();
+    while rest.len() >= 12 {
+        let length = u32::from_be_bytes(rest[0..4].try_into().unwrap()) as usize;
+        let data = rest.get(8..8 + length)?;
+        match &rest[4..8] {
+            b"IHDR" => header = Some(data),
+            b"PLTE" => palette = data,
+            b"tRNS" => transparency = data,
+            b"IDAT" => compressed.extend_from_slice(data),
+            b"IEND" => break,
+            _ => {}
+        }
+        // Skip the length, type, data, and checksum.
+        rest = rest.get(12 + length..)?;
+    }
+
+    let header = header.filter(|header| header.len() >= 13)?;
+    let width = u32::from_be_bytes(header[0..4].try_into().unwrap()) as usize;
+    let height = u32::from_be_bytes(header[4..8].try_into().unwrap()) as usize;
+    let (bit_depth, color_type, interlace_method) = (header[8] as usize, header[9], header[12]);
+    let channels = match color_type {
+        0 | 3 => 1,
+        2 => 3,
+        4 => 2,
+        6 => 4,
+        _ => return None,
+    };
+    let supported_bit_depth = bit_depth == 8 || ((color_type == 0 || color_type == 3) && [1, 2, 4].contains(&bit_depth));
+    if !supported_bit_depth || interlace_method != 0 {
+        return None;
+    }
+    let bits_per_pixel = channels * bit_depth;
+    let stride = (width * bits_per_pixel + 7) / 8;
+    // Filters work on whole bytes, so for images with less than a byte per pixel they use the previous byte.
+    let filter_distance = (bits_per_pixel + 7) / 8;
+
+    let mut raw = Vec::new();
+    flate2::read::ZlibDecoder::new(&compressed[..]).read_to_end(&mut raw).ok()?;
+    if raw.len() < height * (stride + 1) {
+        return None;
+    }
+
+    // Every row starts with a byte for the filter that was used, which we undo here.
+    let mut rows = vec![0; height * stride];
+    for y in 0..height {
+        let filter_type = raw[y * (stride + 1)];
+        let filtered = &raw[y * (stride + 1) + 1..(y + 1) * (stride + 1)];
+        let (previous_rows, current_rows) = rows.split_at_mut(y * stride);
+        let previous = if y > 0 { &previous_rows[(y - 1) * stride..] } else { &[][..] };
+        let current = &mut current_rows[..stride];
+        for x in 0..stride {
+            let left = if x >= filter_distance { current[x - filter_distance] } else { 0 };
+            let up = previous.get(x).copied().unwrap_or(0);
+            let up_left = if x >= filter_distance { previous.get(x - filter_distance).copied().unwrap_or(0) } else { 0 };
+            let prediction = match filter_type {
+                0 => 0,
+                1 => left,
+                2 => up,
+                3 => ((left as u16 + up as u16) / 2) as u8,
+                4 => paeth_predictor(left, up, up_left),
+                _ => return None,
+            };
+            current[x] = filtered[x].wrapping_add(prediction);
+        }
+    }
+
+    // Samples of less than 8 bits are packed into bytes, starting at the most significant bits.
+    let sample = |row: &[u8], index: usize| -> u8 {
+        let bit = index * bit_depth;
+        (row[bit / 8] >> (8 - bit_depth - bit % 8)) & ((1 << bit_depth) - 1) as u8
+    };
+    let mut pixels = Vec::with_capacity(width * height);
+    for row in rows.chunks_exact(stride.max(1)).take(height) {
+        for x in 0..width {
+            pixels.push(match color_type {
+                0 => {
+                    let value = (sample(row, x) as usize * 255 / ((1 << bit_depth) - 1)) as u8;
+                    [value, value, value, 255]
+                }
+                2 => [row[x * 3], row[x * 3 + 1], row[x * 3 + 2], 255],
+                3 => {
+                    let index = sample(row, x) as usize;
+                    let rgb = palette.get(index * 3..index * 3 + 3)?;
+                    [rgb[0], rgb[1], rgb[2], transparency.get(index).copied().unwrap_or(255)]
+                }
+                4 => [row[x * 2], row[x * 2], row[x * 2], row[x * 2 + 1]],
+                _ => [row[x * 4], row[x * 4 + 1], row[x * 4 + 2], row[x * 4 + 3]],
+            });
+        }
+    }
+    Some(DecodedPng { width, height, pixels })
+}
+
+fn paeth_predictor(left: u8, up: u8, up_left: u8) -> u8 {
+    let estimate = left as i16 + up as i16 - up_left as i16;
+    let (distance_left, distance_up, distance_up_left) =
+        ((estimate - left as i16).abs(), (estimate - up as i16).abs(), (estimate - up_left as i16).abs());
+    if distance_left <= distance_up && distance_left <= distance_up_left {
+        left
+    } else if distance_up <= distance_up_left {
+        up
+    } else {
+        up_left
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::capture::CapturedFrame;
+
+    #[test]
+    fn test_decode_captured_frame() {
+        let pixels: Vec<[u8; 4]> = (0..12).map(|index| [index * 20, 255 - index * 20, index, index * 10]).collect();
+        let frame =
+            CapturedFrame { width: 4, height: 3, pixels: pixels.iter().map(|pixel| u32::from_ne_bytes(*pixel)).collect() };
+        assert_eq!(decode_png(&frame.to_png()), Some(DecodedPng { width: 4, height: 3, pixels }));
+    }
+
+    #[test]
+    fn test_decode_invalid() {
+        assert_eq!(decode_png(b"not a png"), None);
+    }
+}
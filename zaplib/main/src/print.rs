@@ -0,0 +1,135 @@
+//! Printing what is drawn in the main window, e.g. for report-style apps.
+//!
+//! [`Cx::print`] exports the main window using [`Cx::export_vector_scene`], and splits it over pages of
+//! [`PrintOptions::page_size`], scaled to fit the width of a page:
+//! * On the web we rasterize every page at [`PrintOptions::dpi`] into an offscreen canvas, and open the browser's
+//!   print dialog with those images.
+//! * On Linux and Mac we send a PDF file (see [`crate::vector_export::VectorScene::to_paged_pdf`]) to the default
+//!   printer using the CUPS `lp` command. Since that PDF contains vector graphics, [`PrintOptions::dpi`] is not used.
+//! * Windows is not supported yet.
+//!
+//! See [`crate::vector_export`] for which kinds of drawing can be printed.
+
+use crate::*;
+use std::fmt;
+use std::future::Future;
+
+/// Why printing failed.
+#[derive(Clone, Debug, PartialEq)]
+pub enum PrintError {
+    /// Printing is not supported on this platform, or CUPS is not installed.
+    NotSupported(String),
+    /// Any other error, such as there not being a printer.
+    Other(String),
+}
+
+impl fmt::Display for PrintError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PrintError::NotSupported(message) => write!(f, "Printing not supported: {}", message),
+            PrintError::Other(message) => write!(f, "Print error: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for PrintError {}
+
+#[cfg(target_arch = "wasm32")]
+impl From<CallJsError> for PrintError {
+    fn from(err: CallJsError) -> Self {
+        match err {
+            // See `print.ts`.
+            CallJsError::Thrown(message) if message.starts_with("NotSupportedError") => PrintError::NotSupported(message),
+            CallJsError::Thrown(message) => PrintError::Other(message),
+            CallJsError::TimedOut => PrintError::Other("Timed out".to_string()),
+        }
+    }
+}
+
+/// Options for [`Cx::print`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct PrintOptions {
+    /// Size of a page in inches.
+    pub page_size: Vec2,
+    /// Margin on every side of a page in inches.
+    pub margin: f32,
+    /// Resolution of the images that we print on the web, in dots per inch.
+    pub dpi: f32,
+}
+
+impl Default for PrintOptions {
+    /// US Letter with half an inch of margin, at 300 dpi.
+    fn default() -> Self {
+        Self { page_size: vec2(8.5, 11.), margin: 0.5, dpi: 300. }
+    }
+}
+
+impl Cx {
+    /// Print what was last drawn in the main window. See [`crate::print`] for how this works on every platform.
+    ///
+    /// On the web, the returned [`Future`] resolves once the print dialog is closed, but there is no way to tell if
+    /// the user actually printed anything. On Linux and Mac, it resolves once the job is submitted to the printer.
+    pub fn print(&mut self, options: PrintOptions) -> impl Future<Output = Result<(), PrintError>> {
+        let scene = self.export_vector_scene();
+        #[cfg(target_arch = "wasm32")]
+        {
+            let future = self.call_js_async(
+                "_zaplibPrint",
+                vec![
+                    scene.to_svg().into_param(),
+                    scene.size.x.to_string().into_param(),
+                    scene.size.y.to_string().into_param(),
+                    options.page_size.x.to_string().into_param(),
+                    options.page_size.y.to_string().into_param(),
+                    options.margin.to_string().into_param(),
+                    options.dpi.to_string().into_param(),
+                ],
+                None,
+            );
+            async move {
+                future.await?;
+                Ok(())
+            }
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        async move {
+            if cfg!(target_os = "windows") {
+                return Err(PrintError::NotSupported("Only supported on Linux and Mac".to_string()));
+            }
+            spawn_blocking(move || native::print_scene(&scene, &options)).await
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+mod native {
+    use super::*;
+    use crate::vector_export::VectorScene;
+    use std::io::{ErrorKind, Write};
+    use std::process::{Command, Stdio};
+
+    /// PDF units are 1/72 inch.
+    const POINTS_PER_INCH: f32 = 72.0;
+
+    pub(super) fn print_scene(scene: &VectorScene, options: &PrintOptions) -> Result<(), PrintError> {
+        let pdf = scene.to_paged_pdf(options.page_size * POINTS_PER_INCH, options.margin * POINTS_PER_INCH);
+
+        // Without any files as arguments, `lp` prints whatever we write to stdin.
+        let mut child =
+            Command::new("lp").stdin(Stdio::piped()).stdout(Stdio::null()).stderr(Stdio::piped()).spawn().map_err(|err| {
+                if err.kind() == ErrorKind::NotFound {
+                    PrintError::NotSupported("Printing requires CUPS (the `lp` command)".to_string())
+                } else {
+                    PrintError::Other(err.to_string())
+                }
+            })?;
+        // Dropping stdin closes it, so that `lp` knows that we're done.
+        child.stdin.take().unwrap().write_all(&pdf).map_err(|err| PrintError::Other(err.to_string()))?;
+        let output = child.wait_with_output().map_err(|err| PrintError::Other(err.to_string()))?;
+        if output.status.success() {
+            Ok(())
+        } else {
+            Err(PrintError::Other(String::from_utf8_lossy(&output.stderr).trim().to_string()))
+        }
+    }
+}
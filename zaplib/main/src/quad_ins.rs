@@ -90,8 +90,9 @@ impl QuadIns {
         geometry geom: vec2;
         varying pos: vec2;
 
+        // Snapped so that scrolling by fractional amounts doesn't blur edges.
         fn scroll() -> vec2 {
-            return draw_scroll;
+            return snap_to_pixel(draw_scroll);
         }
 
         fn vertex() -> vec4 {
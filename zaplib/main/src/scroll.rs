@@ -0,0 +1,135 @@
+//! Normalizing scroll input across platforms, and synthesizing momentum where the platform doesn't.
+
+use crate::*;
+
+/// How many logical pixels one line of scrolling is. Mouse wheels scroll a number of lines per step, and browsers
+/// sometimes report scrolling in lines too.
+pub const SCROLL_LINE_HEIGHT: f32 = 40.;
+
+/// How many logical pixels one page of scrolling is, for when the OS or browser is configured to scroll a page at a
+/// time. We don't know how large the scroll view is, so this is a guess.
+pub const SCROLL_PAGE_HEIGHT: f32 = 800.;
+
+/// How many lines a mouse wheel step scrolls on platforms where this can't be configured, which is also the default
+/// on Windows.
+#[cfg(any(target_os = "linux", target_os = "windows", target_os = "android"))]
+pub(crate) const DEFAULT_LINES_PER_WHEEL_STEP: f32 = 3.;
+
+/// How far back to look at trackpad scrolling to estimate the velocity when the fingers are lifted, in seconds.
+const VELOCITY_WINDOW: f64 = 0.1;
+/// How quickly synthesized momentum slows down; the velocity is multiplied by `e^(-DECELERATION * seconds)`. This
+/// matches the feel of Mac OS X, where it's about 0.998 per millisecond.
+const DECELERATION: f32 = 2.;
+/// Stop synthesizing momentum when it's slower than this, in logical pixels per second.
+const MIN_VELOCITY: f32 = 20.;
+
+/// Continues trackpad scrolling after the fingers are lifted, on platforms that don't do this themselves.
+///
+/// Fed every [`Event::PointerScroll`] from the platform in [`Cx::process_post_event`]; when a [`ScrollPhase::Ended`]
+/// comes in, it estimates the velocity from the scrolling just before, and [`Cx::call_next_frame_event`] then sends
+/// [`ScrollPhase::Momentum`] events until it comes to a stop, or until new scrolling comes in.
+#[derive(Default)]
+pub(crate) struct ScrollMomentum {
+    /// Recent trackpad scroll deltas with their [`PointerScrollEvent::time`].
+    samples: Vec<(f64, Vec2)>,
+    /// The event that we're continuing, and its velocity in logical pixels per second.
+    active: Option<(PointerScrollEvent, Vec2)>,
+}
+
+impl ScrollMomentum {
+    /// Whether the platform already sends [`ScrollPhase::Momentum`] events itself.
+    const PLATFORM_HAS_MOMENTUM: bool = cfg!(any(target_os = "macos", target_os = "ios"));
+
+    /// Track a scroll event from the platform. Returns whether momentum started, in which case
+    /// [`ScrollMomentum::next_event`] has to be called on every frame.
+    pub(crate) fn track(&mut self, pe: &PointerScrollEvent) -> bool {
+        self.active = None;
+        if pe.device != ScrollDevice::Trackpad {
+            self.samples.clear();
+            return false;
+        }
+        match pe.phase {
+            ScrollPhase::Changed => {
+                self.samples.retain(|(time, _)| pe.time - time < VELOCITY_WINDOW);
+                self.samples.push((pe.time, pe.scroll));
+                false
+            }
+            ScrollPhase::Ended if !Self::PLATFORM_HAS_MOMENTUM => {
+                let samples = std::mem::take(&mut self.samples);
+                let recent: Vec<&(f64, Vec2)> = samples.iter().filter(|(time, _)| pe.time - time < VELOCITY_WINDOW).collect();
+                if recent.len() < 2 {
+                    return false;
+                }
+                // The first delta happened before the first sample time, so leave it out.
+                let duration = (recent[recent.len() - 1].0 - recent[0].0) as f32;
+                let distance = recent[1..].iter().fold(Vec2::default(), |sum, (_, delta)| sum + *delta);
+                let velocity = distance / duration.max(1. / 120.);
+                if velocity.length() < MIN_VELOCITY {
+                    return false;
+                }
+                self.active = Some((PointerScrollEvent { phase: ScrollPhase::Momentum, ..pe.clone() }, velocity));
+                true
+            }
+            _ => {
+                self.samples.clear();
+                false
+            }
+        }
+    }
+
+    /// The next synthesized momentum event at `time`, if any. If this returns [`Some`], call it again on the next frame.
+    pub(crate) fn next_event(&mut self, time: f64) -> Option<PointerScrollEvent> {
+        let (event, velocity) = self.active.as_mut()?;
+        let dt = (time - event.time).max(0.) as f32;
+        // Integrate the exponentially decaying velocity over `dt`, so the total distance doesn't depend on the frame rate.
+        let decay = (-DECELERATION * dt).exp();
+        let scroll = *velocity * ((1. - decay) / DECELERATION);
+        *velocity *= decay;
+        event.time = time;
+        let scroll_event = PointerScrollEvent { scroll, handled_x: false, handled_y: false, ..event.clone() };
+        if velocity.length() < MIN_VELOCITY {
+            self.active = None;
+        }
+        Some(scroll_event)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn trackpad_scroll(time: f64, y: f32, phase: ScrollPhase) -> PointerScrollEvent {
+        PointerScrollEvent { scroll: vec2(0., y), device: ScrollDevice::Trackpad, phase, time, ..PointerScrollEvent::default() }
+    }
+
+    #[test]
+    #[cfg(not(any(target_os = "macos", target_os = "ios")))]
+    fn test_momentum_slows_down_and_stops() {
+        let mut momentum = ScrollMomentum::default();
+        for i in 0..5 {
+            assert!(!momentum.track(&trackpad_scroll(i as f64 * 0.01, 10., ScrollPhase::Changed)));
+        }
+        assert!(momentum.track(&trackpad_scroll(0.05, 0., ScrollPhase::Ended)));
+
+        let mut last_scroll = f32::MAX;
+        let mut time = 0.05;
+        while let Some(pe) = momentum.next_event(time + 1. / 60.) {
+            time = pe.time;
+            assert_eq!(pe.phase, ScrollPhase::Momentum);
+            assert!(pe.scroll.y > 0. && pe.scroll.y < last_scroll);
+            last_scroll = pe.scroll.y;
+            assert!(time < 10., "momentum should stop");
+        }
+    }
+
+    #[test]
+    fn test_new_scrolling_stops_momentum() {
+        let mut momentum = ScrollMomentum::default();
+        for i in 0..5 {
+            momentum.track(&trackpad_scroll(i as f64 * 0.01, 10., ScrollPhase::Changed));
+        }
+        momentum.track(&trackpad_scroll(0.05, 0., ScrollPhase::Ended));
+        momentum.track(&PointerScrollEvent { scroll: vec2(0., 120.), time: 0.06, ..PointerScrollEvent::default() });
+        assert_eq!(momentum.next_event(0.07), None);
+    }
+}
@@ -89,16 +89,16 @@ pub(crate) struct CxShaderMapping {
     #[cfg(target_os = "windows")]
     pub(crate) instances: Vec<PropDef>,
     /// Raw definition of all user-level uniforms.
-    #[cfg(any(target_arch = "wasm32", target_os = "linux"))]
+    #[cfg(any(target_arch = "wasm32", target_os = "linux", target_os = "android"))]
     pub(crate) user_uniforms: Vec<PropDef>,
     /// Raw definition of all framework-level uniforms that get set per [`DrawCall`].
-    #[cfg(any(target_arch = "wasm32", target_os = "linux"))]
+    #[cfg(any(target_arch = "wasm32", target_os = "linux", target_os = "android"))]
     pub(crate) draw_uniforms: Vec<PropDef>,
     /// Raw definition of all framework-level uniforms that get set per [`View`].
-    #[cfg(any(target_arch = "wasm32", target_os = "linux"))]
+    #[cfg(any(target_arch = "wasm32", target_os = "linux", target_os = "android"))]
     pub(crate) view_uniforms: Vec<PropDef>,
     /// Raw definition of all framework-level uniforms that get set per [`Pass`].
-    #[cfg(any(target_arch = "wasm32", target_os = "linux"))]
+    #[cfg(any(target_arch = "wasm32", target_os = "linux", target_os = "android"))]
     pub(crate) pass_uniforms: Vec<PropDef>,
 }
 
@@ -157,13 +157,13 @@ impl CxShaderMapping {
             instances,
             #[cfg(target_os = "windows")]
             geometries,
-            #[cfg(any(target_arch = "wasm32", target_os = "linux"))]
+            #[cfg(any(target_arch = "wasm32", target_os = "linux", target_os = "android"))]
             pass_uniforms,
-            #[cfg(any(target_arch = "wasm32", target_os = "linux"))]
+            #[cfg(any(target_arch = "wasm32", target_os = "linux", target_os = "android"))]
             view_uniforms,
-            #[cfg(any(target_arch = "wasm32", target_os = "linux"))]
+            #[cfg(any(target_arch = "wasm32", target_os = "linux", target_os = "android"))]
             draw_uniforms,
-            #[cfg(any(target_arch = "wasm32", target_os = "linux"))]
+            #[cfg(any(target_arch = "wasm32", target_os = "linux", target_os = "android"))]
             user_uniforms,
         }
     }
@@ -301,7 +301,12 @@ impl Cx {
     ///
     /// For more information on what [`LocationHash`] is used for here, see [`Shader`].
     pub(crate) fn get_shader_id(&mut self, shader: &'static Shader) -> usize {
-        let shader_id = shader.shader_id.load(Ordering::Relaxed);
+        let shader_id = match &self.local_shader_ids {
+            Some(local_shader_ids) => {
+                local_shader_ids.get(&(shader as *const Shader as usize)).copied().unwrap_or(Shader::UNCOMPILED_SHADER_ID)
+            }
+            None => shader.shader_id.load(Ordering::Relaxed),
+        };
         if shader_id != Shader::UNCOMPILED_SHADER_ID {
             shader_id
         } else {
@@ -322,7 +327,12 @@ impl Cx {
                     });
                     self.shader_recompile_ids.push(shader_id);
 
-                    shader.shader_id.store(shader_id, Ordering::Relaxed);
+                    match &mut self.local_shader_ids {
+                        Some(local_shader_ids) => {
+                            local_shader_ids.insert(shader as *const Shader as usize, shader_id);
+                        }
+                        None => shader.shader_id.store(shader_id, Ordering::Relaxed),
+                    }
 
                     shader_id
                 }
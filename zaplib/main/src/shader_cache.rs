@@ -0,0 +1,92 @@
+//! Persistent cache of compiled shaders, so that only the first run of an app has to compile all of them.
+//!
+//! Entries are files in an app-data directory (see [`crate::storage`]), named after a hash of the kind of entry,
+//! the platform-specific shader source that was compiled, and the zaplib version. Since changing a shader changes
+//! its source, entries never need to be invalidated; stale ones just don't get used anymore.
+//!
+//! Currently used for Direct3D bytecode (see `cx_dx11.rs`) and Metal binary archives (see `cx_metal.rs`). WebGL
+//! has no way to get at compiled programs, and the translation to GLSL itself is cheap compared to generating the
+//! shader AST, which we need regardless, so we don't cache anything there.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// See the module docs.
+#[derive(Clone)]
+pub(crate) struct ShaderCache {
+    /// [`None`] if we couldn't find or create a directory, in which case the cache is disabled.
+    dir: Option<PathBuf>,
+}
+
+impl ShaderCache {
+    pub(crate) fn new() -> Self {
+        Self::with_dir(crate::storage::app_data_dir("shader_cache").ok())
+    }
+
+    fn with_dir(dir: Option<PathBuf>) -> Self {
+        Self { dir }
+    }
+
+    /// The directory that entries are stored in, for platforms that want to manage their own files, like Metal
+    /// binary archives.
+    #[cfg_attr(not(any(target_os = "macos", target_os = "ios")), allow(dead_code))]
+    pub(crate) fn dir(&self) -> Option<&Path> {
+        self.dir.as_deref()
+    }
+
+    /// Get a previously stored entry of `kind` (e.g. "vs" for a vertex shader) for `source`.
+    #[cfg_attr(not(target_os = "windows"), allow(dead_code))]
+    pub(crate) fn get(&self, kind: &str, source: &[u8]) -> Option<Vec<u8>> {
+        fs::read(self.dir.as_ref()?.join(Self::file_name(kind, source))).ok()
+    }
+
+    /// Store an entry of `kind` for `source`. Errors are ignored, since the cache is only an optimization.
+    #[cfg_attr(not(target_os = "windows"), allow(dead_code))]
+    pub(crate) fn set(&self, kind: &str, source: &[u8], value: &[u8]) {
+        if let Some(dir) = &self.dir {
+            // Write to a temporary file first, so that we never leave a partially written entry behind.
+            let file_name = Self::file_name(kind, source);
+            let tmp_path = dir.join(format!(".{}.tmp", file_name));
+            if fs::write(&tmp_path, value).is_ok() {
+                let _ = fs::rename(&tmp_path, dir.join(file_name));
+            }
+        }
+    }
+
+    /// 64-bit FNV-1a, since it has to stay the same across runs (and Rust versions) of the app.
+    #[cfg_attr(not(target_os = "windows"), allow(dead_code))]
+    fn file_name(kind: &str, source: &[u8]) -> String {
+        let mut hash = 0xcbf29ce484222325u64;
+        for bytes in [env!("CARGO_PKG_VERSION").as_bytes(), b"\0", kind.as_bytes(), b"\0", source] {
+            for byte in bytes {
+                hash ^= *byte as u64;
+                hash = hash.wrapping_mul(0x100000001b3);
+            }
+        }
+        format!("{}_{:016x}", kind, hash)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get_set() {
+        let dir = std::env::temp_dir().join(format!("zaplib_shader_cache_test_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let cache = ShaderCache::with_dir(Some(dir.clone()));
+
+        assert_eq!(cache.get("vs", b"source"), None);
+        cache.set("vs", b"source", b"compiled");
+        assert_eq!(cache.get("vs", b"source"), Some(b"compiled".to_vec()));
+        assert_eq!(cache.get("ps", b"source"), None);
+        assert_eq!(cache.get("vs", b"other source"), None);
+
+        let disabled = ShaderCache::with_dir(None);
+        disabled.set("vs", b"source", b"compiled");
+        assert_eq!(disabled.get("vs", b"source"), None);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
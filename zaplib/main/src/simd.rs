@@ -0,0 +1,516 @@
+//! SIMD-accelerated versions of hot loops in text layout and [`crate::decimation`].
+//!
+//! Only used with the `simd` cargo feature; otherwise (and on CPUs or targets that don't support it) we use plain
+//! loops. Natively we detect SSE2 (x86) and NEON (ARM) at runtime. WebAssembly has no runtime detection, so there
+//! the `simd128` target feature has to be enabled at compile time (`-C target-feature=+simd128`).
+//!
+//! Instance quad generation isn't covered: it writes the fields of instance structs that are laid out differently
+//! for every shader, so there are no contiguous values to add up like there are for advance widths.
+//!
+//! Note that the SIMD versions add numbers in a different order, so results can differ in the last bits.
+
+use crate::*;
+
+/// The sum of all `values`.
+pub(crate) fn sum(values: &[f32]) -> f32 {
+    #[cfg(feature = "simd")]
+    if let Some(sum) = arch::sum(values) {
+        return sum;
+    }
+    values.iter().sum()
+}
+
+/// The sums of `start` and all `values` up to and including each one. Useful for turning advance widths into
+/// positions.
+///
+/// The SIMD versions need all values up front, so with the `simd` feature this collects them into a [`Vec`].
+/// Without it this doesn't allocate.
+pub(crate) fn running_sums(values: impl Iterator<Item = f32>, start: f32) -> impl Iterator<Item = f32> {
+    #[cfg(feature = "simd")]
+    {
+        let mut values: Vec<f32> = values.collect();
+        if !arch::running_sum(&mut values, start) {
+            let mut acc = start;
+            for value in &mut values {
+                acc += *value;
+                *value = acc;
+            }
+        }
+        values.into_iter()
+    }
+    #[cfg(not(feature = "simd"))]
+    values.scan(start, |acc, value| {
+        *acc += value;
+        Some(*acc)
+    })
+}
+
+/// The indices of the first minimum and the first maximum of `values`, skipping NaNs. Returns [`None`] if there are
+/// only NaNs.
+pub(crate) fn min_max_indices(values: &[f32]) -> Option<(usize, usize)> {
+    #[cfg(feature = "simd")]
+    if let Some((min_lanes, max_lanes)) = arch::min_max(values) {
+        // The SIMD versions only cover whole chunks of 4 values, and don't keep track of indices.
+        let remainder = &values[values.len() / 4 * 4..];
+        let min = min_lanes.iter().chain(remainder).fold(f32::INFINITY, |min, &value| min.min(value));
+        let max = max_lanes.iter().chain(remainder).fold(f32::NEG_INFINITY, |max, &value| max.max(value));
+        return Some((values.iter().position(|&value| value == min)?, values.iter().position(|&value| value == max)?));
+    }
+    let mut indices: Option<(usize, usize)> = None;
+    for (index, &value) in values.iter().enumerate() {
+        match &mut indices {
+            Some((min, max)) => {
+                if value < values[*min] {
+                    *min = index;
+                }
+                if value > values[*max] {
+                    *max = index;
+                }
+            }
+            None if !value.is_nan() => indices = Some((index, index)),
+            None => {}
+        }
+    }
+    indices
+}
+
+/// Twice the area of the triangle between `a`, `b`, and `(x, y)`.
+fn triangle_area(a: Vec2, b: Vec2, x: f32, y: f32) -> f32 {
+    ((a.x - b.x) * (y - a.y) - (a.x - x) * (b.y - a.y)).abs()
+}
+
+/// The index of the point that forms the largest triangle with `a` and `b` (the first one if there are several),
+/// along with twice its area. Returns [`None`] if all areas are NaN.
+fn largest_triangle_scalar(xs: &[f32], ys: &[f32], a: Vec2, b: Vec2) -> Option<(f32, usize)> {
+    let mut largest: Option<(f32, usize)> = None;
+    for (index, (&x, &y)) in xs.iter().zip(ys).enumerate() {
+        let area = triangle_area(a, b, x, y);
+        if area > largest.map_or(-1., |(max_area, _)| max_area) {
+            largest = Some((area, index));
+        }
+    }
+    largest
+}
+
+/// The index of the point (from `xs` and `ys`) that forms the largest triangle with `a` and `b`, or the first one if
+/// there are several. Returns [`None`] if all areas are NaN.
+///
+/// The SIMD versions compute areas with the same operations, so they pick exactly the same point.
+pub(crate) fn largest_triangle_index(xs: &[f32], ys: &[f32], a: Vec2, b: Vec2) -> Option<usize> {
+    assert_eq!(xs.len(), ys.len(), "xs and ys must have the same length");
+    #[cfg(feature = "simd")]
+    if u32::try_from(xs.len()).is_ok() {
+        if let Some((area_lanes, index_lanes)) = arch::largest_triangle(xs, ys, a, b) {
+            // Each lane has the first largest area of its own points; of those, take the largest with the lowest
+            // index. Then check the points that didn't fill a whole chunk of 4.
+            let mut largest: Option<(f32, usize)> = None;
+            for (&area, &index) in area_lanes.iter().zip(&index_lanes) {
+                let index = index as usize;
+                match largest {
+                    Some((max_area, max_index)) if area < max_area || (area == max_area && index > max_index) => {}
+                    _ if area >= 0. => largest = Some((area, index)),
+                    _ => {}
+                }
+            }
+            let chunked_len = xs.len() / 4 * 4;
+            return match largest_triangle_scalar(&xs[chunked_len..], &ys[chunked_len..], a, b) {
+                Some((area, index)) if area > largest.map_or(-1., |(max_area, _)| max_area) => Some(chunked_len + index),
+                _ => largest.map(|(_, index)| index),
+            };
+        }
+    }
+    largest_triangle_scalar(xs, ys, a, b).map(|(_, index)| index)
+}
+
+#[cfg(all(feature = "simd", any(target_arch = "x86", target_arch = "x86_64")))]
+mod arch {
+    #[cfg(target_arch = "x86")]
+    use std::arch::x86::*;
+    #[cfg(target_arch = "x86_64")]
+    use std::arch::x86_64::*;
+
+    use super::Vec2;
+
+    pub(super) fn sum(values: &[f32]) -> Option<f32> {
+        if !is_x86_feature_detected!("sse2") {
+            return None;
+        }
+        Some(unsafe { sum_sse2(values) })
+    }
+
+    #[target_feature(enable = "sse2")]
+    unsafe fn sum_sse2(values: &[f32]) -> f32 {
+        let chunks = values.chunks_exact(4);
+        let remainder = chunks.remainder();
+        let mut acc = _mm_setzero_ps();
+        for chunk in chunks {
+            acc = _mm_add_ps(acc, _mm_loadu_ps(chunk.as_ptr()));
+        }
+        let mut lanes = [0.; 4];
+        _mm_storeu_ps(lanes.as_mut_ptr(), acc);
+        lanes.iter().sum::<f32>() + remainder.iter().sum::<f32>()
+    }
+
+    pub(super) fn running_sum(values: &mut [f32], start: f32) -> bool {
+        if !is_x86_feature_detected!("sse2") {
+            return false;
+        }
+        unsafe { running_sum_sse2(values, start) };
+        true
+    }
+
+    #[target_feature(enable = "sse2")]
+    unsafe fn running_sum_sse2(values: &mut [f32], start: f32) {
+        let mut carry = _mm_set1_ps(start);
+        let mut chunks = values.chunks_exact_mut(4);
+        for chunk in &mut chunks {
+            // Turn [a, b, c, d] into [a, a+b, a+b+c, a+b+c+d] by adding lane-shifted copies.
+            let mut v = _mm_loadu_ps(chunk.as_ptr());
+            v = _mm_add_ps(v, _mm_castsi128_ps(_mm_slli_si128::<4>(_mm_castps_si128(v))));
+            v = _mm_add_ps(v, _mm_castsi128_ps(_mm_slli_si128::<8>(_mm_castps_si128(v))));
+            v = _mm_add_ps(v, carry);
+            _mm_storeu_ps(chunk.as_mut_ptr(), v);
+            carry = _mm_shuffle_ps::<0b11_11_11_11>(v, v);
+        }
+        let mut acc = _mm_cvtss_f32(carry);
+        for value in chunks.into_remainder() {
+            acc += *value;
+            *value = acc;
+        }
+    }
+
+    pub(super) fn min_max(values: &[f32]) -> Option<([f32; 4], [f32; 4])> {
+        if !is_x86_feature_detected!("sse2") {
+            return None;
+        }
+        Some(unsafe { min_max_sse2(values) })
+    }
+
+    #[target_feature(enable = "sse2")]
+    unsafe fn min_max_sse2(values: &[f32]) -> ([f32; 4], [f32; 4]) {
+        // `_mm_min_ps` and `_mm_max_ps` return their second argument if either one is NaN, so this skips NaNs.
+        let mut min = _mm_set1_ps(f32::INFINITY);
+        let mut max = _mm_set1_ps(f32::NEG_INFINITY);
+        for chunk in values.chunks_exact(4) {
+            let v = _mm_loadu_ps(chunk.as_ptr());
+            min = _mm_min_ps(v, min);
+            max = _mm_max_ps(v, max);
+        }
+        let (mut min_lanes, mut max_lanes) = ([0.; 4], [0.; 4]);
+        _mm_storeu_ps(min_lanes.as_mut_ptr(), min);
+        _mm_storeu_ps(max_lanes.as_mut_ptr(), max);
+        (min_lanes, max_lanes)
+    }
+
+    pub(super) fn largest_triangle(xs: &[f32], ys: &[f32], a: Vec2, b: Vec2) -> Option<([f32; 4], [u32; 4])> {
+        if !is_x86_feature_detected!("sse2") {
+            return None;
+        }
+        Some(unsafe { largest_triangle_sse2(xs, ys, a, b) })
+    }
+
+    #[target_feature(enable = "sse2")]
+    unsafe fn largest_triangle_sse2(xs: &[f32], ys: &[f32], a: Vec2, b: Vec2) -> ([f32; 4], [u32; 4]) {
+        let (a_x, a_y) = (_mm_set1_ps(a.x), _mm_set1_ps(a.y));
+        let (ab_x, ab_y) = (_mm_set1_ps(a.x - b.x), _mm_set1_ps(b.y - a.y));
+        let abs_mask = _mm_castsi128_ps(_mm_set1_epi32(i32::MAX));
+        let mut max_area = _mm_set1_ps(-1.);
+        let mut max_index = _mm_setzero_si128();
+        let mut index = _mm_set_epi32(3, 2, 1, 0);
+        for (x_chunk, y_chunk) in xs.chunks_exact(4).zip(ys.chunks_exact(4)) {
+            let (x, y) = (_mm_loadu_ps(x_chunk.as_ptr()), _mm_loadu_ps(y_chunk.as_ptr()));
+            // Same operations as `super::triangle_area`.
+            let area = _mm_sub_ps(_mm_mul_ps(ab_x, _mm_sub_ps(y, a_y)), _mm_mul_ps(_mm_sub_ps(a_x, x), ab_y));
+            let area = _mm_and_ps(area, abs_mask);
+            // Only replace on larger areas (which excludes NaNs), so that each lane keeps the first of equal ones.
+            let larger = _mm_cmpgt_ps(area, max_area);
+            max_area = _mm_or_ps(_mm_and_ps(larger, area), _mm_andnot_ps(larger, max_area));
+            let larger = _mm_castps_si128(larger);
+            max_index = _mm_or_si128(_mm_and_si128(larger, index), _mm_andnot_si128(larger, max_index));
+            index = _mm_add_epi32(index, _mm_set1_epi32(4));
+        }
+        let (mut area_lanes, mut index_lanes) = ([0.; 4], [0; 4]);
+        _mm_storeu_ps(area_lanes.as_mut_ptr(), max_area);
+        _mm_storeu_si128(index_lanes.as_mut_ptr() as *mut __m128i, max_index);
+        (area_lanes, index_lanes)
+    }
+}
+
+#[cfg(all(feature = "simd", target_arch = "aarch64"))]
+mod arch {
+    use std::arch::aarch64::*;
+
+    use super::Vec2;
+
+    pub(super) fn sum(values: &[f32]) -> Option<f32> {
+        if !std::arch::is_aarch64_feature_detected!("neon") {
+            return None;
+        }
+        Some(unsafe { sum_neon(values) })
+    }
+
+    #[target_feature(enable = "neon")]
+    unsafe fn sum_neon(values: &[f32]) -> f32 {
+        let chunks = values.chunks_exact(4);
+        let remainder = chunks.remainder();
+        let mut acc = vdupq_n_f32(0.);
+        for chunk in chunks {
+            acc = vaddq_f32(acc, vld1q_f32(chunk.as_ptr()));
+        }
+        vaddvq_f32(acc) + remainder.iter().sum::<f32>()
+    }
+
+    pub(super) fn running_sum(values: &mut [f32], start: f32) -> bool {
+        if !std::arch::is_aarch64_feature_detected!("neon") {
+            return false;
+        }
+        unsafe { running_sum_neon(values, start) };
+        true
+    }
+
+    #[target_feature(enable = "neon")]
+    unsafe fn running_sum_neon(values: &mut [f32], start: f32) {
+        let zero = vdupq_n_f32(0.);
+        let mut carry = vdupq_n_f32(start);
+        let mut chunks = values.chunks_exact_mut(4);
+        for chunk in &mut chunks {
+            // Turn [a, b, c, d] into [a, a+b, a+b+c, a+b+c+d] by adding lane-shifted copies.
+            let mut v = vld1q_f32(chunk.as_ptr());
+            v = vaddq_f32(v, vextq_f32::<3>(zero, v));
+            v = vaddq_f32(v, vextq_f32::<2>(zero, v));
+            v = vaddq_f32(v, carry);
+            vst1q_f32(chunk.as_mut_ptr(), v);
+            carry = vdupq_laneq_f32::<3>(v);
+        }
+        let mut acc = vgetq_lane_f32::<0>(carry);
+        for value in chunks.into_remainder() {
+            acc += *value;
+            *value = acc;
+        }
+    }
+
+    pub(super) fn min_max(values: &[f32]) -> Option<([f32; 4], [f32; 4])> {
+        if !std::arch::is_aarch64_feature_detected!("neon") {
+            return None;
+        }
+        Some(unsafe { min_max_neon(values) })
+    }
+
+    #[target_feature(enable = "neon")]
+    unsafe fn min_max_neon(values: &[f32]) -> ([f32; 4], [f32; 4]) {
+        // `vminnmq_f32` and `vmaxnmq_f32` return the other argument if one of them is NaN, so this skips NaNs.
+        let mut min = vdupq_n_f32(f32::INFINITY);
+        let mut max = vdupq_n_f32(f32::NEG_INFINITY);
+        for chunk in values.chunks_exact(4) {
+            let v = vld1q_f32(chunk.as_ptr());
+            min = vminnmq_f32(min, v);
+            max = vmaxnmq_f32(max, v);
+        }
+        let (mut min_lanes, mut max_lanes) = ([0.; 4], [0.; 4]);
+        vst1q_f32(min_lanes.as_mut_ptr(), min);
+        vst1q_f32(max_lanes.as_mut_ptr(), max);
+        (min_lanes, max_lanes)
+    }
+
+    pub(super) fn largest_triangle(xs: &[f32], ys: &[f32], a: Vec2, b: Vec2) -> Option<([f32; 4], [u32; 4])> {
+        if !std::arch::is_aarch64_feature_detected!("neon") {
+            return None;
+        }
+        Some(unsafe { largest_triangle_neon(xs, ys, a, b) })
+    }
+
+    #[target_feature(enable = "neon")]
+    unsafe fn largest_triangle_neon(xs: &[f32], ys: &[f32], a: Vec2, b: Vec2) -> ([f32; 4], [u32; 4]) {
+        let (a_x, a_y) = (vdupq_n_f32(a.x), vdupq_n_f32(a.y));
+        let (ab_x, ab_y) = (vdupq_n_f32(a.x - b.x), vdupq_n_f32(b.y - a.y));
+        let mut max_area = vdupq_n_f32(-1.);
+        let mut max_index = vdupq_n_u32(0);
+        let mut index = vld1q_u32([0, 1, 2, 3].as_ptr());
+        for (x_chunk, y_chunk) in xs.chunks_exact(4).zip(ys.chunks_exact(4)) {
+            let (x, y) = (vld1q_f32(x_chunk.as_ptr()), vld1q_f32(y_chunk.as_ptr()));
+            // Same operations as `super::triangle_area`.
+            let area = vabsq_f32(vsubq_f32(vmulq_f32(ab_x, vsubq_f32(y, a_y)), vmulq_f32(vsubq_f32(a_x, x), ab_y)));
+            // Only replace on larger areas (which excludes NaNs), so that each lane keeps the first of equal ones.
+            let larger = vcgtq_f32(area, max_area);
+            max_area = vbslq_f32(larger, area, max_area);
+            max_index = vbslq_u32(larger, index, max_index);
+            index = vaddq_u32(index, vdupq_n_u32(4));
+        }
+        let (mut area_lanes, mut index_lanes) = ([0.; 4], [0; 4]);
+        vst1q_f32(area_lanes.as_mut_ptr(), max_area);
+        vst1q_u32(index_lanes.as_mut_ptr(), max_index);
+        (area_lanes, index_lanes)
+    }
+}
+
+#[cfg(all(feature = "simd", target_arch = "wasm32", target_feature = "simd128"))]
+mod arch {
+    use core::arch::wasm32::*;
+
+    use super::Vec2;
+
+    pub(super) fn sum(values: &[f32]) -> Option<f32> {
+        let chunks = values.chunks_exact(4);
+        let remainder = chunks.remainder();
+        let mut acc = f32x4_splat(0.);
+        for chunk in chunks {
+            acc = f32x4_add(acc, unsafe { v128_load(chunk.as_ptr() as *const v128) });
+        }
+        let lanes = f32x4_extract_lane::<0>(acc)
+            + f32x4_extract_lane::<1>(acc)
+            + f32x4_extract_lane::<2>(acc)
+            + f32x4_extract_lane::<3>(acc);
+        Some(lanes + remainder.iter().sum::<f32>())
+    }
+
+    pub(super) fn running_sum(values: &mut [f32], start: f32) -> bool {
+        let zero = f32x4_splat(0.);
+        let mut carry = f32x4_splat(start);
+        let mut chunks = values.chunks_exact_mut(4);
+        for chunk in &mut chunks {
+            // Turn [a, b, c, d] into [a, a+b, a+b+c, a+b+c+d] by adding lane-shifted copies.
+            let mut v = unsafe { v128_load(chunk.as_ptr() as *const v128) };
+            v = f32x4_add(v, i32x4_shuffle::<0, 4, 5, 6>(zero, v));
+            v = f32x4_add(v, i32x4_shuffle::<0, 1, 4, 5>(zero, v));
+            v = f32x4_add(v, carry);
+            unsafe { v128_store(chunk.as_mut_ptr() as *mut v128, v) };
+            carry = i32x4_shuffle::<3, 3, 3, 3>(v, v);
+        }
+        let mut acc = f32x4_extract_lane::<0>(carry);
+        for value in chunks.into_remainder() {
+            acc += *value;
+            *value = acc;
+        }
+        true
+    }
+
+    pub(super) fn min_max(values: &[f32]) -> Option<([f32; 4], [f32; 4])> {
+        // `f32x4_pmin(a, b)` and `f32x4_pmax(a, b)` return `a` if either one is NaN, so this skips NaNs.
+        let mut min = f32x4_splat(f32::INFINITY);
+        let mut max = f32x4_splat(f32::NEG_INFINITY);
+        for chunk in values.chunks_exact(4) {
+            let v = unsafe { v128_load(chunk.as_ptr() as *const v128) };
+            min = f32x4_pmin(min, v);
+            max = f32x4_pmax(max, v);
+        }
+        Some((f32_lanes(min), f32_lanes(max)))
+    }
+
+    fn f32_lanes(v: v128) -> [f32; 4] {
+        [f32x4_extract_lane::<0>(v), f32x4_extract_lane::<1>(v), f32x4_extract_lane::<2>(v), f32x4_extract_lane::<3>(v)]
+    }
+
+    pub(super) fn largest_triangle(xs: &[f32], ys: &[f32], a: Vec2, b: Vec2) -> Option<([f32; 4], [u32; 4])> {
+        let (a_x, a_y) = (f32x4_splat(a.x), f32x4_splat(a.y));
+        let (ab_x, ab_y) = (f32x4_splat(a.x - b.x), f32x4_splat(b.y - a.y));
+        let mut max_area = f32x4_splat(-1.);
+        let mut max_index = u32x4_splat(0);
+        let mut index = u32x4(0, 1, 2, 3);
+        for (x_chunk, y_chunk) in xs.chunks_exact(4).zip(ys.chunks_exact(4)) {
+            let x = unsafe { v128_load(x_chunk.as_ptr() as *const v128) };
+            let y = unsafe { v128_load(y_chunk.as_ptr() as *const v128) };
+            // Same operations as `super::triangle_area`.
+            let area = f32x4_abs(f32x4_sub(f32x4_mul(ab_x, f32x4_sub(y, a_y)), f32x4_mul(f32x4_sub(a_x, x), ab_y)));
+            // Only replace on larger areas (which excludes NaNs), so that each lane keeps the first of equal ones.
+            let larger = f32x4_gt(area, max_area);
+            max_area = v128_bitselect(area, max_area, larger);
+            max_index = v128_bitselect(index, max_index, larger);
+            index = u32x4_add(index, u32x4_splat(4));
+        }
+        let index_lanes = [
+            u32x4_extract_lane::<0>(max_index),
+            u32x4_extract_lane::<1>(max_index),
+            u32x4_extract_lane::<2>(max_index),
+            u32x4_extract_lane::<3>(max_index),
+        ];
+        Some((f32_lanes(max_area), index_lanes))
+    }
+}
+
+#[cfg(all(
+    feature = "simd",
+    not(any(
+        target_arch = "x86",
+        target_arch = "x86_64",
+        target_arch = "aarch64",
+        all(target_arch = "wasm32", target_feature = "simd128")
+    ))
+))]
+mod arch {
+    pub(super) fn sum(_values: &[f32]) -> Option<f32> {
+        None
+    }
+
+    pub(super) fn running_sum(_values: &mut [f32], _start: f32) -> bool {
+        false
+    }
+
+    pub(super) fn min_max(_values: &[f32]) -> Option<([f32; 4], [f32; 4])> {
+        None
+    }
+
+    pub(super) fn largest_triangle(_xs: &[f32], _ys: &[f32], _a: super::Vec2, _b: super::Vec2) -> Option<([f32; 4], [u32; 4])> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Using whole numbers, so that the order of additions doesn't matter.
+    fn values(len: usize) -> Vec<f32> {
+        (0..len).map(|index| (index % 7) as f32 - 2.).collect()
+    }
+
+    #[test]
+    fn test_sum() {
+        for len in 0..20 {
+            assert_eq!(sum(&values(len)), values(len).iter().sum::<f32>());
+        }
+    }
+
+    #[test]
+    fn test_running_sums() {
+        for len in 0..20 {
+            let actual: Vec<f32> = running_sums(values(len).into_iter(), 10.).collect();
+            let expected: Vec<f32> = (0..len).map(|index| 10. + values(index + 1).iter().sum::<f32>()).collect();
+            assert_eq!(actual, expected);
+        }
+    }
+
+    #[test]
+    fn test_min_max_indices() {
+        for len in 0..20 {
+            let mut with_nans = values(len);
+            for index in (0..len).step_by(5) {
+                with_nans[index] = f32::NAN;
+            }
+            let valid = || (0..len).filter(|&index| !with_nans[index].is_nan());
+            let min = valid().min_by(|&a, &b| with_nans[a].partial_cmp(&with_nans[b]).unwrap());
+            // `max_by` returns the last of equal elements, so order equal ones by descending index.
+            let max = valid().max_by(|&a, &b| with_nans[a].partial_cmp(&with_nans[b]).unwrap().then(b.cmp(&a)));
+            assert_eq!(min_max_indices(&with_nans), min.zip(max));
+        }
+        assert_eq!(min_max_indices(&[f32::NAN; 9]), None);
+    }
+
+    #[test]
+    fn test_largest_triangle_index() {
+        // A horizontal base, so that areas only depend on `y` and repeat along with `values`.
+        let (a, b) = (vec2(-1., 0.), vec2(30., 0.));
+        for len in 0..20 {
+            let xs: Vec<f32> = (0..len).map(|index| index as f32).collect();
+            let mut ys = values(len);
+            for index in (3..len).step_by(6) {
+                ys[index] = f32::NAN;
+            }
+            let area = |index: usize| triangle_area(a, b, xs[index], ys[index]);
+            let expected = (0..len)
+                .filter(|&index| !ys[index].is_nan())
+                .max_by(|&i, &j| area(i).partial_cmp(&area(j)).unwrap().then(j.cmp(&i)));
+            assert_eq!(largest_triangle_index(&xs, &ys, a, b), expected);
+        }
+        assert_eq!(largest_triangle_index(&[0.; 9], &[f32::NAN; 9], a, b), None);
+    }
+}
@@ -0,0 +1,305 @@
+//! Spatial index for quickly finding which items are at a given position, e.g. to find the point under the
+//! cursor in a scatter plot with millions of points.
+//!
+//! [`SpatialIndex`] is a static R-tree, built in one go using Sort-Tile-Recursive packing. Queries take
+//! O(log n) time (plus the number of results), so they are cheap enough to do on every
+//! [`Event::PointerHover`]. When the items change (e.g. when zooming or panning in pixel coordinates), build a
+//! new [`SpatialIndex`]; building takes O(n log n) time, so for large datasets it's best to do this on a
+//! different thread, e.g. using [`spawn_blocking`] or [`crate::thread_pool`].
+//!
+//! ```ignore
+//! // When the data or the view changes:
+//! self.index = SpatialIndex::from_points(&points_in_pixels);
+//!
+//! // In `handle`:
+//! let event = event.hits_pointer(cx, self.component_id, self.area.get_rect_for_first_instance(cx));
+//! if let Some(index) = self.index.hit_test(&event, 5.) {
+//!     // Show a tooltip for `points_in_pixels[index]`.
+//! }
+//! ```
+
+use crate::*;
+use std::cmp::{Ordering, Reverse};
+use std::collections::BinaryHeap;
+
+/// Maximum number of children of a node. 16 is a common choice that works well both for building and querying.
+const NODE_SIZE: usize = 16;
+
+/// Axis-aligned bounding box. Unlike [`Rect`] this stores the corners, which makes comparisons cheaper.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Bounds {
+    min: Vec2,
+    max: Vec2,
+}
+
+impl Bounds {
+    fn empty() -> Self {
+        Self { min: vec2(f32::INFINITY, f32::INFINITY), max: vec2(f32::NEG_INFINITY, f32::NEG_INFINITY) }
+    }
+
+    fn union(self, other: Bounds) -> Self {
+        Self {
+            min: vec2(self.min.x.min(other.min.x), self.min.y.min(other.min.y)),
+            max: vec2(self.max.x.max(other.max.x), self.max.y.max(other.max.y)),
+        }
+    }
+
+    fn intersects(&self, other: &Bounds) -> bool {
+        self.min.x <= other.max.x && self.max.x >= other.min.x && self.min.y <= other.max.y && self.max.y >= other.min.y
+    }
+
+    fn center(&self) -> Vec2 {
+        vec2((self.min.x + self.max.x) * 0.5, (self.min.y + self.max.y) * 0.5)
+    }
+
+    /// Squared distance from `pos` to the nearest point of these bounds; 0 if `pos` is inside.
+    fn distance_squared(&self, pos: Vec2) -> f32 {
+        let dx = (self.min.x - pos.x).max(pos.x - self.max.x).max(0.);
+        let dy = (self.min.y - pos.y).max(pos.y - self.max.y).max(0.);
+        dx * dx + dy * dy
+    }
+}
+
+impl From<Rect> for Bounds {
+    fn from(rect: Rect) -> Self {
+        Self { min: rect.pos, max: rect.pos + rect.size }
+    }
+}
+
+/// A node of the tree. Its children are `start..end` in [`SpatialIndex::items`] for leaves, and in
+/// [`SpatialIndex::nodes`] otherwise.
+#[derive(Clone, Copy, Debug)]
+struct Node {
+    bounds: Bounds,
+    start: usize,
+    end: usize,
+    is_leaf: bool,
+}
+
+/// Static R-tree over a list of [`Rect`]s or points, which returns indices into that list. See the
+/// [module documentation](self) for an example.
+///
+/// Items with NaN or infinite coordinates are left out.
+#[derive(Clone, Debug, Default)]
+pub struct SpatialIndex {
+    /// Bounds and original index of each item, in tree order.
+    items: Vec<(Bounds, usize)>,
+    /// All nodes, from the leaves up; the last one is the root.
+    nodes: Vec<Node>,
+}
+
+/// Sort `entries` so that each consecutive chunk of [`NODE_SIZE`] entries is spatially close together
+/// (Sort-Tile-Recursive): sort by x, cut into vertical slices, and sort each slice by y.
+fn sort_tile_recursive<T>(entries: &mut [(Bounds, T)]) {
+    let node_count = (entries.len() as f64 / NODE_SIZE as f64).ceil();
+    let slice_size = NODE_SIZE * node_count.sqrt().ceil() as usize;
+    entries.sort_unstable_by(|a, b| a.0.center().x.partial_cmp(&b.0.center().x).unwrap_or(Ordering::Equal));
+    for slice in entries.chunks_mut(slice_size.max(1)) {
+        slice.sort_unstable_by(|a, b| a.0.center().y.partial_cmp(&b.0.center().y).unwrap_or(Ordering::Equal));
+    }
+}
+
+/// Group consecutive chunks of `entries` into parent nodes, whose children start at `offset`.
+fn make_parents<T>(entries: &[(Bounds, T)], offset: usize, is_leaf: bool) -> Vec<(Bounds, Node)> {
+    entries
+        .chunks(NODE_SIZE)
+        .enumerate()
+        .map(|(chunk_index, chunk)| {
+            let bounds = chunk.iter().fold(Bounds::empty(), |bounds, (child_bounds, _)| bounds.union(*child_bounds));
+            let start = offset + chunk_index * NODE_SIZE;
+            (bounds, Node { bounds, start, end: start + chunk.len(), is_leaf })
+        })
+        .collect()
+}
+
+impl SpatialIndex {
+    /// Build an index over `rects`, e.g. the rectangles of bars or markers. Query results are indices into `rects`.
+    pub fn from_rects(rects: &[Rect]) -> Self {
+        Self::build(rects.iter().map(|&rect| Bounds::from(rect)))
+    }
+
+    /// Build an index over `points`, e.g. the centers of the points in a scatter plot. Query results are indices
+    /// into `points`.
+    pub fn from_points(points: &[Vec2]) -> Self {
+        Self::build(points.iter().map(|&pos| Bounds { min: pos, max: pos }))
+    }
+
+    fn build(bounds: impl Iterator<Item = Bounds>) -> Self {
+        let mut items: Vec<(Bounds, usize)> = bounds
+            .enumerate()
+            .filter(|(_, bounds)| {
+                bounds.min.x.is_finite() && bounds.min.y.is_finite() && bounds.max.x.is_finite() && bounds.max.y.is_finite()
+            })
+            .map(|(index, bounds)| (bounds, index))
+            .collect();
+        if items.is_empty() {
+            return Self::default();
+        }
+
+        sort_tile_recursive(&mut items);
+        let mut nodes = vec![];
+        let mut level = make_parents(&items, 0, true);
+        while level.len() > 1 {
+            sort_tile_recursive(&mut level);
+            let offset = nodes.len();
+            nodes.extend(level.iter().map(|(_, node)| *node));
+            level = make_parents(&level, offset, false);
+        }
+        nodes.push(level[0].1);
+        Self { items, nodes }
+    }
+
+    /// Number of items in the index.
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    /// Whether there are no items in the index.
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// Indices of all items that overlap with `rect` (including its edges), in no particular order.
+    pub fn search(&self, rect: Rect) -> Vec<usize> {
+        let query = Bounds::from(rect);
+        let mut results = vec![];
+        let mut stack: Vec<usize> = self.nodes.len().checked_sub(1).into_iter().collect();
+        while let Some(node_index) = stack.pop() {
+            let node = &self.nodes[node_index];
+            if !node.bounds.intersects(&query) {
+                continue;
+            }
+            if node.is_leaf {
+                results.extend(
+                    self.items[node.start..node.end]
+                        .iter()
+                        .filter(|(bounds, _)| bounds.intersects(&query))
+                        .map(|(_, index)| *index),
+                );
+            } else {
+                stack.extend(node.start..node.end);
+            }
+        }
+        results
+    }
+
+    /// Index of the item closest to `pos`, if it's at most `max_distance` away. For rectangles, the distance
+    /// is measured to the nearest edge, so any rectangle that contains `pos` has a distance of 0.
+    pub fn nearest(&self, pos: Vec2, max_distance: f32) -> Option<usize> {
+        let max_distance_squared = max_distance * max_distance;
+        // Best-first search. Non-negative floats compare the same as their bits, which lets us use them as a
+        // key in the heap. Items sort before nodes at the same distance, so we can return them right away.
+        let mut heap = BinaryHeap::new();
+        if let Some(root) = self.nodes.last() {
+            heap.push(Reverse((root.bounds.distance_squared(pos).to_bits(), true, self.nodes.len() - 1)));
+        }
+        while let Some(Reverse((distance_bits, is_node, index))) = heap.pop() {
+            if f32::from_bits(distance_bits) > max_distance_squared {
+                break;
+            }
+            if !is_node {
+                return Some(self.items[index].1);
+            }
+            let node = &self.nodes[index];
+            for child_index in node.start..node.end {
+                let child_bounds = if node.is_leaf { self.items[child_index].0 } else { self.nodes[child_index].bounds };
+                let distance_squared = child_bounds.distance_squared(pos);
+                if distance_squared <= max_distance_squared {
+                    heap.push(Reverse((distance_squared.to_bits(), !node.is_leaf, child_index)));
+                }
+            }
+        }
+        None
+    }
+
+    /// Find the item under the pointer for an event returned by [`Event::hits_pointer`], using
+    /// [`SpatialIndex::nearest`]. The items should be in coordinates relative to the [`Rect`] that was passed to
+    /// [`Event::hits_pointer`], since this uses the `rel` field of the event.
+    ///
+    /// Returns [`None`] for other events, and for [`Event::PointerHover`] with [`HoverState::Out`].
+    pub fn hit_test(&self, event: &Event, max_distance: f32) -> Option<usize> {
+        let rel = match event {
+            Event::PointerHover(pe) if pe.hover_state != HoverState::Out => pe.rel,
+            Event::PointerDown(pe) => pe.rel,
+            Event::PointerMove(pe) => pe.rel,
+            Event::PointerUp(pe) => pe.rel,
+            Event::PointerScroll(pe) => pe.rel,
+            _ => return None,
+        };
+        self.nearest(rel, max_distance)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Deterministic pseudo-random points, so that we don't depend on a random number generator.
+    fn test_points(count: usize) -> Vec<Vec2> {
+        let mut state = 12345u32;
+        let mut next = move || {
+            state = state.wrapping_mul(1103515245).wrapping_add(12345);
+            (state >> 8) as f32 / (1 << 24) as f32 * 1000.
+        };
+        (0..count).map(|_| vec2(next(), next())).collect()
+    }
+
+    fn brute_force_nearest(points: &[Vec2], pos: Vec2, max_distance: f32) -> Option<f32> {
+        points
+            .iter()
+            .map(|p| (*p - pos).length())
+            .filter(|&distance| distance <= max_distance)
+            .min_by(|a, b| a.partial_cmp(b).unwrap())
+    }
+
+    #[test]
+    fn test_search() {
+        let points = test_points(10_000);
+        let index = SpatialIndex::from_points(&points);
+        assert_eq!(index.len(), 10_000);
+        let rect = Rect { pos: vec2(100., 200.), size: vec2(50., 30.) };
+        let mut results = index.search(rect);
+        results.sort_unstable();
+        let expected: Vec<usize> = (0..points.len()).filter(|&i| rect.contains(points[i])).collect();
+        assert!(!expected.is_empty());
+        assert_eq!(results, expected);
+    }
+
+    #[test]
+    fn test_nearest() {
+        let points = test_points(10_000);
+        let index = SpatialIndex::from_points(&points);
+        for pos in test_points(100) {
+            let distance = index.nearest(pos, 20.).map(|i| (points[i] - pos).length());
+            assert_eq!(distance, brute_force_nearest(&points, pos, 20.));
+        }
+        assert_eq!(index.nearest(vec2(-100., -100.), 1.), None);
+    }
+
+    #[test]
+    fn test_rects_and_invalid_items() {
+        let rects = [
+            Rect { pos: vec2(0., 0.), size: vec2(10., 10.) },
+            Rect { pos: vec2(f32::NAN, 0.), size: vec2(10., 10.) },
+            Rect { pos: vec2(20., 0.), size: vec2(10., 10.) },
+        ];
+        let index = SpatialIndex::from_rects(&rects);
+        assert_eq!(index.len(), 2);
+        assert_eq!(index.nearest(vec2(5., 5.), 0.), Some(0));
+        assert_eq!(index.nearest(vec2(16., 5.), 10.), Some(2));
+        assert_eq!(index.search(Rect { pos: vec2(5., 5.), size: vec2(20., 0.) }).len(), 2);
+
+        assert!(SpatialIndex::from_points(&[]).is_empty());
+        assert_eq!(SpatialIndex::from_points(&[]).nearest(vec2(0., 0.), 10.), None);
+    }
+
+    #[test]
+    fn test_hit_test() {
+        let index = SpatialIndex::from_points(&[vec2(10., 10.), vec2(50., 50.)]);
+        let hover = |rel, hover_state| Event::PointerHover(PointerHoverEvent { rel, hover_state, ..Default::default() });
+        assert_eq!(index.hit_test(&hover(vec2(48., 49.), HoverState::Over), 5.), Some(1));
+        assert_eq!(index.hit_test(&hover(vec2(48., 49.), HoverState::Out), 5.), None);
+        assert_eq!(index.hit_test(&hover(vec2(30., 30.), HoverState::Over), 5.), None);
+        assert_eq!(index.hit_test(&Event::None, 5.), None);
+    }
+}
@@ -0,0 +1,231 @@
+//! Physics-based animations using critically damped springs.
+
+use crate::*;
+use std::ops::{Add, Mul, Sub};
+
+/// A value that is animated towards a target using a critically damped spring.
+///
+/// Unlike [`Animator`], a [`Spring`] has no fixed duration; it keeps track of its velocity, so you
+/// can change the target at any time (e.g. while dragging) and it will smoothly move towards it,
+/// without ever oscillating. You can also give it an initial velocity (e.g. when releasing a drag),
+/// which can cause it to overshoot the target a bit before settling, which feels natural for things
+/// like scroll views and panels.
+///
+/// Just like with [`Animator`], call [`Spring::handle`] from your `handle` function, and use
+/// [`Spring::get`] to read the current value when drawing:
+///
+/// ```
+/// let mut spring = Spring::new(0.0);
+/// spring.set_target(cx, 100.0);
+/// // In your `handle` function:
+/// if spring.handle(cx, event) {
+///     cx.request_draw();
+/// }
+/// ```
+#[derive(Clone, Debug)]
+pub struct Spring<T: SpringValue> {
+    /// The current value.
+    value: T,
+    /// The current velocity, in units per second.
+    velocity: T,
+    /// The value that we are moving towards.
+    target: T,
+    /// The last time that we integrated the spring, or [`None`] if we're at rest.
+    last_time: Option<f64>,
+    /// How quickly the spring moves towards its target; this is the angular frequency of the spring.
+    /// Higher values make the spring stiffer. As a rule of thumb, the spring gets to within 1% of its
+    /// target in about `6.6 / stiffness` seconds (when starting without velocity).
+    pub stiffness: f64,
+    /// When both the distance to the target and the velocity (per second) are smaller than this, we
+    /// snap to the target and stop requesting frames.
+    pub rest_threshold: f32,
+}
+
+impl<T: SpringValue> Spring<T> {
+    /// The default [`Spring::stiffness`], which settles in about a third of a second.
+    pub const DEFAULT_STIFFNESS: f64 = 20.;
+    /// The default [`Spring::rest_threshold`], which is about a hundredth of a pixel.
+    pub const DEFAULT_REST_THRESHOLD: f32 = 0.01;
+
+    /// Create a new [`Spring`] that is at rest at `value`.
+    pub fn new(value: T) -> Self {
+        Self {
+            value,
+            velocity: T::ZERO,
+            target: value,
+            last_time: None,
+            stiffness: Self::DEFAULT_STIFFNESS,
+            rest_threshold: Self::DEFAULT_REST_THRESHOLD,
+        }
+    }
+
+    /// Same as [`Spring::new`], but with a custom [`Spring::stiffness`].
+    pub fn with_stiffness(value: T, stiffness: f64) -> Self {
+        Self { stiffness, ..Self::new(value) }
+    }
+
+    /// The current value.
+    pub fn get(&self) -> T {
+        self.value
+    }
+
+    /// The current velocity, in units per second.
+    pub fn get_velocity(&self) -> T {
+        self.velocity
+    }
+
+    /// The value that we are moving towards.
+    pub fn get_target(&self) -> T {
+        self.target
+    }
+
+    /// Whether the spring has settled at its target.
+    pub fn is_at_rest(&self) -> bool {
+        self.last_time.is_none()
+    }
+
+    /// Start moving towards `target`, keeping the current velocity.
+    pub fn set_target(&mut self, cx: &mut Cx, target: T) {
+        self.target = target;
+        self.start(cx);
+    }
+
+    /// Set the velocity (in units per second), e.g. when the user releases a drag.
+    pub fn set_velocity(&mut self, cx: &mut Cx, velocity: T) {
+        self.velocity = velocity;
+        self.start(cx);
+    }
+
+    /// Immediately jump to `value`, and stay there. Useful while dragging, when the value should
+    /// follow the pointer exactly.
+    pub fn set_immediate(&mut self, value: T) {
+        self.value = value;
+        self.target = value;
+        self.velocity = T::ZERO;
+        self.last_time = None;
+    }
+
+    /// Process an [`Event::NextFrame`], moving the value towards the target. Returns true if the value
+    /// has changed, in which case you should redraw.
+    pub fn handle(&mut self, cx: &mut Cx, event: &Event) -> bool {
+        match event {
+            Event::NextFrame => self.step(cx),
+            _ => false,
+        }
+    }
+
+    /// Make sure that we're running and get a next frame.
+    fn start(&mut self, cx: &mut Cx) {
+        if self.last_time.is_none() {
+            self.last_time = Some(cx.last_event_time);
+        }
+        cx.request_next_frame();
+    }
+
+    /// Integrate the spring up until [`Cx::last_event_time`].
+    fn step(&mut self, cx: &mut Cx) -> bool {
+        let last_time = match self.last_time {
+            Some(last_time) => last_time,
+            None => return false,
+        };
+        let dt = cx.last_event_time - last_time;
+        if dt <= 0. {
+            cx.request_next_frame();
+            return false;
+        }
+        self.last_time = Some(cx.last_event_time);
+
+        // Closed-form solution of a critically damped spring, relative to the target, so that this is
+        // stable regardless of the frame rate. See e.g. <https://www.ryanjuckett.com/damped-springs/>.
+        let omega = self.stiffness;
+        let decay = (-omega * dt).exp() as f32;
+        let offset = self.value - self.target;
+        let temp = (self.velocity + offset * omega as f32) * dt as f32;
+        let new_offset = (offset + temp) * decay;
+        self.velocity = (self.velocity - temp * omega as f32) * decay;
+        self.value = self.target + new_offset;
+
+        if new_offset.magnitude() < self.rest_threshold && self.velocity.magnitude() < self.rest_threshold {
+            self.set_immediate(self.target);
+        } else {
+            cx.request_next_frame();
+        }
+        true
+    }
+}
+
+/// Types that can be animated using a [`Spring`].
+pub trait SpringValue: Copy + Add<Output = Self> + Sub<Output = Self> + Mul<f32, Output = Self> {
+    /// The value that represents no offset and no velocity.
+    const ZERO: Self;
+    /// The distance to zero, used for determining when a [`Spring`] is at rest.
+    fn magnitude(self) -> f32;
+}
+
+impl SpringValue for f32 {
+    const ZERO: f32 = 0.;
+    fn magnitude(self) -> f32 {
+        self.abs()
+    }
+}
+
+impl SpringValue for Vec2 {
+    const ZERO: Vec2 = Vec2 { x: 0., y: 0. };
+    fn magnitude(self) -> f32 {
+        self.x.abs().max(self.y.abs())
+    }
+}
+
+impl SpringValue for Vec3 {
+    const ZERO: Vec3 = Vec3 { x: 0., y: 0., z: 0. };
+    fn magnitude(self) -> f32 {
+        self.x.abs().max(self.y.abs()).max(self.z.abs())
+    }
+}
+
+impl SpringValue for Vec4 {
+    const ZERO: Vec4 = Vec4 { x: 0., y: 0., z: 0., w: 0. };
+    fn magnitude(self) -> f32 {
+        self.x.abs().max(self.y.abs()).max(self.z.abs()).max(self.w.abs())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    #[test]
+    fn test_spring_settles() {
+        let mut cx = Cx::new_test();
+        let mut spring = Spring::new(0.0);
+        spring.set_target(&mut cx, 100.0);
+        assert!(cx.requested_next_frame);
+
+        let mut previous = spring.get();
+        while !spring.is_at_rest() {
+            cx.last_event_time += 1. / 60.;
+            assert!(spring.handle(&mut cx, &Event::NextFrame));
+            // Critically damped, so without initial velocity we never overshoot.
+            assert!(spring.get() >= previous && spring.get() <= 100.0);
+            previous = spring.get();
+            assert!(cx.last_event_time < 2.);
+        }
+        assert_eq!(spring.get(), 100.0);
+        assert!(!spring.handle(&mut cx, &Event::NextFrame));
+    }
+
+    #[test]
+    fn test_spring_velocity_overshoots() {
+        let mut cx = Cx::new_test();
+        let mut spring = Spring::new(vec2(0., 0.));
+        spring.set_velocity(&mut cx, vec2(1000., 0.));
+        let mut max_x: f32 = 0.;
+        while !spring.is_at_rest() {
+            cx.last_event_time += 1. / 60.;
+            spring.handle(&mut cx, &Event::NextFrame);
+            max_x = max_x.max(spring.get().x);
+        }
+        assert!(max_x > 10.);
+        assert_eq!(spring.get(), vec2(0., 0.));
+    }
+}
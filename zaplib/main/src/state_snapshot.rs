@@ -0,0 +1,185 @@
+//! Saving and restoring application state, for save files, crash recovery, and time-travel debugging. Requires the
+//! `serde-support` feature.
+//!
+//! With that feature enabled, props and math types like [`Vec4`], [`Rect`], [`LayoutSize`], and [`FlexProps`]
+//! implement [`Serialize`] and [`Deserialize`](serde::Deserialize), so you can derive these for your own component
+//! state as well. Keep things that can't be meaningfully restored, like [`Area`]s, [`ComponentId`]s, and animation
+//! state, out of it (e.g. using `#[serde(skip)]`), and rebuild those in the next draw.
+//!
+//! ```ignore
+//! #[derive(Serialize, Deserialize)]
+//! struct EditorState {
+//!     document: String,
+//!     sidebar_width: f32,
+//!     #[serde(skip)]
+//!     scroll_view: ScrollView,
+//! }
+//!
+//! // Save every few seconds for crash recovery, and restore on startup.
+//! self.save_task = Some(cx.spawn(state_snapshot::save(cx, "recovery", &self.state)));
+//! self.load_task = Some(cx.spawn(state_snapshot::load::<EditorState>(cx, "recovery")));
+//! ```
+//!
+//! Snapshots are JSON, so save files are easy to inspect and stay readable when you add fields (mark new ones with
+//! `#[serde(default)]`).
+
+use crate::storage::StorageError;
+use crate::*;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::fmt;
+use std::future::Future;
+use std::marker::PhantomData;
+
+/// Why saving or restoring a snapshot failed.
+#[derive(Debug)]
+pub enum SnapshotError {
+    /// The state couldn't be serialized, or the snapshot didn't match the type that it was restored to.
+    Serialization(serde_json::Error),
+    /// See [`StorageError`].
+    Storage(StorageError),
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SnapshotError::Serialization(err) => write!(f, "Snapshot serialization error: {}", err),
+            SnapshotError::Storage(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for SnapshotError {}
+
+impl From<serde_json::Error> for SnapshotError {
+    fn from(err: serde_json::Error) -> Self {
+        SnapshotError::Serialization(err)
+    }
+}
+
+impl From<StorageError> for SnapshotError {
+    fn from(err: StorageError) -> Self {
+        SnapshotError::Storage(err)
+    }
+}
+
+/// Serialize `state` into a snapshot.
+pub fn snapshot<T: Serialize + ?Sized>(state: &T) -> Result<Vec<u8>, SnapshotError> {
+    Ok(serde_json::to_vec(state)?)
+}
+
+/// Restore state from a snapshot created with [`snapshot`].
+pub fn restore<T: DeserializeOwned>(snapshot: &[u8]) -> Result<T, SnapshotError> {
+    Ok(serde_json::from_slice(snapshot)?)
+}
+
+/// Save a snapshot of `state` in [`storage`] under `key`. The state gets serialized right away, so you can keep
+/// changing it before awaiting the returned [`Future`].
+pub fn save<T: Serialize + ?Sized>(cx: &mut Cx, key: &str, state: &T) -> impl Future<Output = Result<(), SnapshotError>> {
+    let future = snapshot(state).map(|bytes| storage::set(cx, key, bytes));
+    async move {
+        future?.await?;
+        Ok(())
+    }
+}
+
+/// Load state that was saved with [`save`], or [`None`] if nothing was saved under `key`.
+pub fn load<T: DeserializeOwned>(cx: &mut Cx, key: &str) -> impl Future<Output = Result<Option<T>, SnapshotError>> {
+    let future = storage::get(cx, key);
+    async move { future.await?.map(|bytes| restore(&bytes)).transpose() }
+}
+
+/// A list of snapshots of some state over time, for stepping back and forth through it while debugging.
+///
+/// Call [`StateHistory::record`] after every change (e.g. at the end of every event handler), and restore an older
+/// version with [`StateHistory::get`].
+pub struct StateHistory<T> {
+    snapshots: VecDeque<Vec<u8>>,
+    max_len: usize,
+    phantom: PhantomData<fn() -> T>,
+}
+
+impl<T: Serialize + DeserializeOwned> StateHistory<T> {
+    /// Create a history that keeps the last `max_len` snapshots.
+    pub fn new(max_len: usize) -> Self {
+        assert!(max_len > 0, "StateHistory needs to be able to keep at least one snapshot");
+        Self { snapshots: VecDeque::new(), max_len, phantom: PhantomData }
+    }
+
+    /// Add a snapshot of `state`, unless it's the same as the last one. Returns whether a snapshot was added.
+    pub fn record(&mut self, state: &T) -> Result<bool, SnapshotError> {
+        let bytes = snapshot(state)?;
+        if self.snapshots.back() == Some(&bytes) {
+            return Ok(false);
+        }
+        if self.snapshots.len() == self.max_len {
+            self.snapshots.pop_front();
+        }
+        self.snapshots.push_back(bytes);
+        Ok(true)
+    }
+
+    /// Restore the snapshot at `index`, where 0 is the oldest one that's still kept.
+    pub fn get(&self, index: usize) -> Option<Result<T, SnapshotError>> {
+        self.snapshots.get(index).map(|bytes| restore(bytes))
+    }
+
+    pub fn len(&self) -> usize {
+        self.snapshots.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.snapshots.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.snapshots.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Debug, Serialize, Deserialize)]
+    struct State {
+        color: Vec4,
+        layout_size: LayoutSize,
+        padding: Padding,
+        #[serde(default)]
+        count: u32,
+    }
+
+    #[test]
+    fn test_snapshot_and_restore() {
+        let state = State {
+            color: vec4(1., 0.5, 0., 1.),
+            layout_size: LayoutSize::new(Width::Fix(100.), Height::Compute),
+            padding: Padding::all(4.),
+            count: 3,
+        };
+        let restored: State = restore(&snapshot(&state).unwrap()).unwrap();
+        assert_eq!(restored.color, state.color);
+        assert_eq!(restored.layout_size, state.layout_size);
+        assert_eq!(restored.padding.l, 4.);
+        assert_eq!(restored.count, 3);
+
+        assert!(matches!(restore::<State>(b"{}"), Err(SnapshotError::Serialization(_))));
+    }
+
+    #[test]
+    fn test_state_history() {
+        let mut history = StateHistory::new(2);
+        assert!(history.is_empty());
+        assert!(history.record(&1).unwrap());
+        assert!(!history.record(&1).unwrap());
+        assert!(history.record(&2).unwrap());
+        assert!(history.record(&3).unwrap());
+        assert_eq!(history.len(), 2);
+        assert_eq!(history.get(0).unwrap().unwrap(), 2);
+        assert_eq!(history.get(1).unwrap().unwrap(), 3);
+        assert!(history.get(2).is_none());
+    }
+}
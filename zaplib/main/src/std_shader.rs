@@ -13,6 +13,7 @@ impl Cx {
         uniform inv_camera_rot: mat4 in pass;
         uniform dpi_factor: float in pass;
         uniform dpi_dilate: float in pass;
+        uniform text_gamma: float in pass;
 
         // See [`DrawUniforms`] for documentation on these fields.
         uniform draw_clip: vec4 in draw;
@@ -72,6 +73,12 @@ impl Cx {
             return vec4(abs(q.z + (q.w - q.y) / (6.0 * d + e)), d / (q.x + e), q.x, c.w);
         }
 
+        // Rounds a position in logical pixels to the nearest physical pixel, which keeps edges and hairlines crisp,
+        // also on fractional scales like 125% or 150%.
+        fn snap_to_pixel(v: vec2) -> vec2 {
+            return floor(v * dpi_factor + 0.5) / dpi_factor;
+        }
+
         impl Df {
             // Creates a distance field with the current position
             fn viewport(pos: vec2) -> Df {
@@ -0,0 +1,350 @@
+//! Persistent key-value storage, for caching data and user settings across sessions.
+//!
+//! Values are arbitrary bytes, so you can store anything from a small settings string to a large dataset.
+//! In WebAssembly they are stored in IndexedDB (so they are scoped to the origin of the page). Natively they
+//! are stored as files in an app-data directory, named after the executable (e.g.
+//! `~/.local/share/my_app/storage` on Linux).
+//!
+//! All functions return [`Future`]s; use [`Cx::spawn`] to run them:
+//!
+//! ```ignore
+//! let settings = storage::get(cx, "settings");
+//! self.load_task = Some(cx.spawn(async move { settings.await.ok().flatten() }));
+//! ```
+//!
+//! Operations are applied in the order in which these functions are called, so you can e.g. call [`set`] and
+//! then [`get`] before awaiting either. Natively they are actually applied right away, which blocks the current
+//! thread while the file is being read or written.
+
+use crate::*;
+use std::fmt;
+use std::future::Future;
+
+/// Why a [`storage`](self) operation failed.
+#[derive(Clone, Debug, PartialEq)]
+pub enum StorageError {
+    /// There is not enough space left; either the browser's quota for this origin was reached, or the disk is full.
+    QuotaExceeded,
+    /// Any other error, e.g. an I/O error, or IndexedDB not being available (such as in some private browsing modes).
+    Other(String),
+}
+
+impl fmt::Display for StorageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StorageError::QuotaExceeded => write!(f, "Storage quota exceeded"),
+            StorageError::Other(message) => write!(f, "Storage error: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for StorageError {}
+
+#[cfg(target_arch = "wasm32")]
+impl From<CallJsError> for StorageError {
+    fn from(err: CallJsError) -> Self {
+        match err {
+            // See `runTransaction` in `storage.ts`.
+            CallJsError::Thrown(message) if message.starts_with("QuotaExceededError") => StorageError::QuotaExceeded,
+            CallJsError::Thrown(message) => StorageError::Other(message),
+            CallJsError::TimedOut => StorageError::Other("Timed out".to_string()),
+        }
+    }
+}
+
+/// Get the value stored for `key`, or [`None`] if there is none.
+pub fn get(cx: &mut Cx, key: &str) -> impl Future<Output = Result<Option<Vec<u8>>, StorageError>> {
+    #[cfg(target_arch = "wasm32")]
+    {
+        let future = cx.call_js_async("_zaplibStorageGet", vec![key.to_string().into_param()], None);
+        async move { Ok(future.await?.first().map(|param| param.as_u8_slice().to_vec())) }
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        let _ = cx;
+        std::future::ready(native::storage_dir().and_then(|dir| native::get(&dir, key)))
+    }
+}
+
+/// Store `value` for `key`, overwriting any existing value.
+pub fn set(cx: &mut Cx, key: &str, value: Vec<u8>) -> impl Future<Output = Result<(), StorageError>> {
+    #[cfg(target_arch = "wasm32")]
+    {
+        let future = cx.call_js_async("_zaplibStorageSet", vec![key.to_string().into_param(), value.into_param()], None);
+        async move { future.await.map(|_| ()).map_err(StorageError::from) }
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        let _ = cx;
+        std::future::ready(native::storage_dir().and_then(|dir| native::set(&dir, key, &value)))
+    }
+}
+
+/// Remove the value for `key`. Does nothing if there is no such value.
+pub fn remove(cx: &mut Cx, key: &str) -> impl Future<Output = Result<(), StorageError>> {
+    #[cfg(target_arch = "wasm32")]
+    {
+        let future = cx.call_js_async("_zaplibStorageRemove", vec![key.to_string().into_param()], None);
+        async move { future.await.map(|_| ()).map_err(StorageError::from) }
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        let _ = cx;
+        std::future::ready(native::storage_dir().and_then(|dir| native::remove(&dir, key)))
+    }
+}
+
+/// Get all keys that currently have a value, in no particular order.
+pub fn keys(cx: &mut Cx) -> impl Future<Output = Result<Vec<String>, StorageError>> {
+    #[cfg(target_arch = "wasm32")]
+    {
+        let future = cx.call_js_async("_zaplibStorageKeys", vec![], None);
+        async move { Ok(future.await?.into_iter().map(ZapParam::into_string).collect()) }
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        let _ = cx;
+        std::future::ready(native::storage_dir().and_then(|dir| native::keys(&dir)))
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) use native::app_data_dir;
+
+#[cfg(not(target_arch = "wasm32"))]
+mod native {
+    use super::StorageError;
+    use std::fs;
+    use std::io;
+    use std::path::{Path, PathBuf};
+
+    impl From<io::Error> for StorageError {
+        fn from(err: io::Error) -> Self {
+            // ENOSPC; [`io::ErrorKind::StorageFull`] is not stable yet.
+            #[cfg(unix)]
+            if err.raw_os_error() == Some(28) {
+                return StorageError::QuotaExceeded;
+            }
+            StorageError::Other(err.to_string())
+        }
+    }
+
+    /// The directory that we store values in, which is created if it doesn't exist yet.
+    pub(super) fn storage_dir() -> Result<PathBuf, StorageError> {
+        app_data_dir("storage")
+    }
+
+    /// A directory called `name` for app data, which is created if it doesn't exist yet. Also used by
+    /// `ShaderCache`.
+    pub(crate) fn app_data_dir(name: &str) -> Result<PathBuf, StorageError> {
+        // Android apps get their own sandboxed data directory, so there's no need to namespace by app name.
+        #[cfg(target_os = "android")]
+        {
+            let dir = crate::cx_android::internal_data_path()
+                .ok_or_else(|| StorageError::Other("Could not find a directory for app data".to_string()))?
+                .join(name);
+            fs::create_dir_all(&dir)?;
+            return Ok(dir);
+        }
+        #[allow(unreachable_code)]
+        let home = || std::env::var_os("HOME").map(PathBuf::from);
+        let base = if cfg!(target_os = "windows") {
+            std::env::var_os("APPDATA").map(PathBuf::from)
+        } else if cfg!(any(target_os = "macos", target_os = "ios")) {
+            home().map(|home| home.join("Library/Application Support"))
+        } else {
+            std::env::var_os("XDG_DATA_HOME").map(PathBuf::from).or_else(|| home().map(|home| home.join(".local/share")))
+        }
+        .ok_or_else(|| StorageError::Other("Could not find a directory for app data".to_string()))?;
+
+        let exe = std::env::current_exe()?;
+        let app_name = exe.file_stem().ok_or_else(|| StorageError::Other("Could not determine app name".to_string()))?;
+        let dir = base.join(app_name).join(name);
+        fs::create_dir_all(&dir)?;
+        Ok(dir)
+    }
+
+    /// Most file systems limit file names to 255 bytes; leave some room for the temporary file name in [`set`].
+    const MAX_FILE_NAME_LEN: usize = 200;
+
+    /// Start of the file names of keys that are empty or too long to escape. These files are named after a hash of the
+    /// key, and start with the length of the key (as a little-endian `u32`) followed by the key itself.
+    const HASHED_PREFIX: char = '~';
+
+    /// Escape everything except for a few safe characters, so that any key results in a valid file name. Uppercase
+    /// letters are escaped too, so that different keys never map to the same file on case-insensitive file systems.
+    fn key_to_file_name(key: &str) -> String {
+        let mut file_name = String::new();
+        for byte in key.bytes() {
+            if byte.is_ascii_lowercase() || byte.is_ascii_digit() || byte == b'-' || byte == b'_' {
+                file_name.push(byte as char);
+            } else {
+                file_name.push_str(&format!("%{:02X}", byte));
+            }
+        }
+        // An empty file name would refer to the storage directory itself.
+        if file_name.is_empty() || file_name.len() > MAX_FILE_NAME_LEN {
+            return format!("{}{:032x}", HASHED_PREFIX, hash_key(key));
+        }
+        file_name
+    }
+
+    fn file_name_to_key(file_name: &str) -> Option<String> {
+        let mut bytes = Vec::new();
+        let mut input = file_name.bytes();
+        while let Some(byte) = input.next() {
+            if byte == b'%' {
+                let hex = [input.next()?, input.next()?];
+                bytes.push(u8::from_str_radix(std::str::from_utf8(&hex).ok()?, 16).ok()?);
+            } else {
+                bytes.push(byte);
+            }
+        }
+        String::from_utf8(bytes).ok()
+    }
+
+    /// 128-bit FNV-1a, which is stable across Rust versions (unlike [`std::collections::hash_map::DefaultHasher`]).
+    fn hash_key(key: &str) -> u128 {
+        let mut hash: u128 = 0x6c62272e07bb014262b821756295c58d;
+        for byte in key.bytes() {
+            hash = (hash ^ byte as u128).wrapping_mul(0x0000000001000000000000000000013b);
+        }
+        hash
+    }
+
+    /// Split the contents of a hashed file into its key and value.
+    fn split_hashed_file(contents: &[u8]) -> Option<(&[u8], &[u8])> {
+        let key_len = u32::from_le_bytes(contents.get(..4)?.try_into().unwrap()) as usize;
+        let key = contents.get(4..4 + key_len)?;
+        Some((key, &contents[4 + key_len..]))
+    }
+
+    pub(super) fn get(dir: &Path, key: &str) -> Result<Option<Vec<u8>>, StorageError> {
+        let file_name = key_to_file_name(key);
+        match fs::read(dir.join(&file_name)) {
+            Ok(contents) if file_name.starts_with(HASHED_PREFIX) => match split_hashed_file(&contents) {
+                Some((stored_key, value)) if stored_key == key.as_bytes() => Ok(Some(value.to_vec())),
+                // A different key with the same hash.
+                _ => Ok(None),
+            },
+            Ok(value) => Ok(Some(value)),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    pub(super) fn set(dir: &Path, key: &str, value: &[u8]) -> Result<(), StorageError> {
+        // Write to a temporary file first, so that we never leave a partially written value behind.
+        let file_name = key_to_file_name(key);
+        let tmp_path = dir.join(format!(".{}.tmp", file_name));
+        if file_name.starts_with(HASHED_PREFIX) {
+            let mut contents = Vec::with_capacity(4 + key.len() + value.len());
+            contents.extend_from_slice(&(key.len() as u32).to_le_bytes());
+            contents.extend_from_slice(key.as_bytes());
+            contents.extend_from_slice(value);
+            fs::write(&tmp_path, contents)?;
+        } else {
+            fs::write(&tmp_path, value)?;
+        }
+        fs::rename(&tmp_path, dir.join(file_name))?;
+        Ok(())
+    }
+
+    pub(super) fn remove(dir: &Path, key: &str) -> Result<(), StorageError> {
+        let file_name = key_to_file_name(key);
+        // Don't remove the value of a different key with the same hash.
+        if file_name.starts_with(HASHED_PREFIX) && get(dir, key)?.is_none() {
+            return Ok(());
+        }
+        match fs::remove_file(dir.join(file_name)) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err.into()),
+            _ => Ok(()),
+        }
+    }
+
+    pub(super) fn keys(dir: &Path) -> Result<Vec<String>, StorageError> {
+        let mut keys = vec![];
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            let file_name = entry.file_name();
+            let file_name = match file_name.to_str() {
+                // Skip temporary files from [`set`].
+                Some(file_name) if !file_name.starts_with('.') => file_name,
+                _ => continue,
+            };
+            if file_name.starts_with(HASHED_PREFIX) {
+                let contents = fs::read(entry.path())?;
+                if let Some(key) = split_hashed_file(&contents).and_then(|(key, _)| String::from_utf8(key.to_vec()).ok()) {
+                    keys.push(key);
+                }
+            } else if let Some(key) = file_name_to_key(file_name) {
+                keys.push(key);
+            }
+        }
+        Ok(keys)
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn test_key_to_file_name() {
+            assert_eq!(key_to_file_name("settings"), "settings");
+            assert_eq!(key_to_file_name("../a b/é"), "%2E%2E%2Fa%20b%2F%C3%A9");
+            assert_eq!(file_name_to_key("%2E%2E%2Fa%20b%2F%C3%A9").unwrap(), "../a b/é");
+            assert_eq!(file_name_to_key("%2"), None);
+            assert_eq!(key_to_file_name("Settings"), "%53ettings");
+            assert_eq!(file_name_to_key("%53ettings").unwrap(), "Settings");
+            assert_eq!(key_to_file_name(&"x".repeat(200)), "x".repeat(200));
+            assert!(key_to_file_name(&"x".repeat(201)).starts_with(HASHED_PREFIX));
+            assert_eq!(key_to_file_name(&"x".repeat(201)).len(), 33);
+            assert!(key_to_file_name("").starts_with(HASHED_PREFIX));
+        }
+
+        #[test]
+        fn test_get_set_remove() {
+            let dir = std::env::temp_dir().join(format!("zaplib_storage_test_{}", std::process::id()));
+            fs::create_dir_all(&dir).unwrap();
+
+            assert_eq!(get(&dir, "a/b").unwrap(), None);
+            set(&dir, "a/b", b"hello").unwrap();
+            set(&dir, "c", b"").unwrap();
+            assert_eq!(get(&dir, "a/b").unwrap(), Some(b"hello".to_vec()));
+            let mut all_keys = keys(&dir).unwrap();
+            all_keys.sort();
+            assert_eq!(all_keys, vec!["a/b".to_string(), "c".to_string()]);
+
+            // Only differ in case, so they need different files on case-insensitive file systems.
+            set(&dir, "A/B", b"world").unwrap();
+            assert_eq!(get(&dir, "a/b").unwrap(), Some(b"hello".to_vec()));
+            assert_eq!(get(&dir, "A/B").unwrap(), Some(b"world".to_vec()));
+            remove(&dir, "A/B").unwrap();
+
+            // Too long to fit in a file name.
+            let long_key = "é".repeat(1000);
+            set(&dir, &long_key, b"long").unwrap();
+            assert_eq!(get(&dir, &long_key).unwrap(), Some(b"long".to_vec()));
+            let mut all_keys = keys(&dir).unwrap();
+            all_keys.sort();
+            assert_eq!(all_keys, vec!["a/b".to_string(), "c".to_string(), long_key.clone()]);
+            remove(&dir, &long_key).unwrap();
+
+            // Can't be used as a file name.
+            assert_eq!(get(&dir, "").unwrap(), None);
+            set(&dir, "", b"empty").unwrap();
+            assert_eq!(get(&dir, "").unwrap(), Some(b"empty".to_vec()));
+            let mut all_keys = keys(&dir).unwrap();
+            all_keys.sort();
+            assert_eq!(all_keys, vec!["".to_string(), "a/b".to_string(), "c".to_string()]);
+            remove(&dir, "").unwrap();
+
+            remove(&dir, "a/b").unwrap();
+            remove(&dir, "does not exist").unwrap();
+            assert_eq!(get(&dir, "a/b").unwrap(), None);
+            assert_eq!(keys(&dir).unwrap(), vec!["c".to_string()]);
+
+            fs::remove_dir_all(&dir).unwrap();
+        }
+    }
+}
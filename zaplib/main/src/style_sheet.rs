@@ -0,0 +1,347 @@
+//! Style sheets, for tweaking the look of components (colors, paddings, font sizes, animation durations, and so on)
+//! without recompiling. See [`StyleSheet`] for the format, and [`LiveStyleSheet`] for reloading while the app
+//! is running.
+
+use crate::*;
+use std::collections::HashMap;
+use std::fmt;
+
+/// A value in a [`StyleSheet`].
+#[derive(Clone, Debug, PartialEq)]
+pub enum StyleValue {
+    /// A single number, like `11` or `0.05`.
+    Number(f32),
+    /// Multiple numbers separated by spaces, like `4 8`.
+    Numbers(Vec<f32>),
+    /// A hex color, like `#fff`, `#ffffff80`, or `#3`.
+    Color(Vec4),
+    /// A quoted string, like `"Ubuntu"`.
+    String(String),
+}
+
+/// Why parsing a [`StyleSheet`] failed.
+#[derive(Clone, Debug, PartialEq)]
+pub struct StyleSheetError {
+    /// The line of the error, starting at 1.
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for StyleSheetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Style sheet error on line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for StyleSheetError {}
+
+/// A set of style properties, grouped by selectors. The format looks like CSS:
+///
+/// ```text
+/// // Comments start with two slashes.
+/// button {
+///     background: #333;
+///     text_color: #ccc;
+///     padding: 4 8;
+///     font_size: 11;
+///     hover_duration: 0.05;
+/// }
+///
+/// button.primary {
+///     background: #2a5;
+/// }
+/// ```
+///
+/// Selectors and property names are just names; it's up to your code to decide which ones to read. When looking up
+/// a property for a selector with dots in it, like `button.primary`, we fall back to `button` if it's not set.
+///
+/// ```
+/// let sheet = StyleSheet::parse("button { font_size: 11; }").unwrap();
+/// let font_size = sheet.float("button.primary", "font_size", 8.);
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct StyleSheet {
+    rules: HashMap<String, HashMap<String, StyleValue>>,
+}
+
+impl StyleSheet {
+    /// Parse a style sheet. Later rules for the same selector and property override earlier ones.
+    pub fn parse(source: &str) -> Result<Self, StyleSheetError> {
+        let mut rules: HashMap<String, HashMap<String, StyleValue>> = HashMap::new();
+        let mut current_selector: Option<String> = None;
+
+        for (line_index, line) in source.lines().enumerate() {
+            let error = |message: String| StyleSheetError { line: line_index + 1, message };
+            let mut line = line.split("//").next().unwrap().trim();
+
+            while !line.is_empty() {
+                match &current_selector {
+                    None => {
+                        let brace = line.find('{').ok_or_else(|| error(format!("Expected `{{` after `{line}`")))?;
+                        let selector = line[..brace].trim();
+                        if selector.is_empty() || selector.contains(char::is_whitespace) {
+                            return Err(error(format!("Invalid selector `{selector}`")));
+                        }
+                        rules.entry(selector.to_string()).or_default();
+                        current_selector = Some(selector.to_string());
+                        line = line[brace + 1..].trim();
+                    }
+                    Some(selector) => {
+                        if let Some(rest) = line.strip_prefix('}') {
+                            current_selector = None;
+                            line = rest.trim();
+                            continue;
+                        }
+                        let end = line.find([';', '}']).unwrap_or(line.len());
+                        let declaration = &line[..end];
+                        let (name, value) =
+                            declaration.split_once(':').ok_or_else(|| error(format!("Expected `:` in `{declaration}`")))?;
+                        let name = name.trim();
+                        if name.is_empty() {
+                            return Err(error(format!("Missing property name in `{declaration}`")));
+                        }
+                        let value = Self::parse_value(value.trim()).map_err(error)?;
+                        rules.get_mut(selector).unwrap().insert(name.to_string(), value);
+                        line = line[end..].strip_prefix(';').unwrap_or(&line[end..]).trim();
+                    }
+                }
+            }
+        }
+
+        if let Some(selector) = current_selector {
+            return Err(StyleSheetError { line: source.lines().count(), message: format!("Missing `}}` for `{selector}`") });
+        }
+        Ok(Self { rules })
+    }
+
+    fn parse_value(value: &str) -> Result<StyleValue, String> {
+        if let Some(hex) = value.strip_prefix('#') {
+            return Vec4::from_hex_bytes(hex.as_bytes()).map(StyleValue::Color).map_err(|_| format!("Invalid color `{value}`"));
+        }
+        if let Some(string) = value.strip_prefix('"') {
+            return string
+                .strip_suffix('"')
+                .map(|string| StyleValue::String(string.to_string()))
+                .ok_or_else(|| format!("Missing closing quote in `{value}`"));
+        }
+        let numbers = value
+            .split_whitespace()
+            .map(|number| number.parse::<f32>().map_err(|_| format!("Invalid number `{number}`")))
+            .collect::<Result<Vec<f32>, String>>()?;
+        match numbers.len() {
+            0 => Err("Missing value".to_string()),
+            1 => Ok(StyleValue::Number(numbers[0])),
+            _ => Ok(StyleValue::Numbers(numbers)),
+        }
+    }
+
+    /// Get a property for `selector`, falling back to less specific selectors (`button.primary` falls back to
+    /// `button`).
+    pub fn get(&self, selector: &str, property: &str) -> Option<&StyleValue> {
+        let mut selector = selector;
+        loop {
+            if let Some(value) = self.rules.get(selector).and_then(|properties| properties.get(property)) {
+                return Some(value);
+            }
+            selector = &selector[..selector.rfind('.')?];
+        }
+    }
+
+    /// Get a color, or `default` if it's not set or not a color.
+    pub fn color(&self, selector: &str, property: &str, default: Vec4) -> Vec4 {
+        match self.get(selector, property) {
+            Some(StyleValue::Color(color)) => *color,
+            _ => default,
+        }
+    }
+
+    /// Get a number, or `default` if it's not set or not a single number.
+    pub fn float(&self, selector: &str, property: &str, default: f32) -> f32 {
+        match self.get(selector, property) {
+            Some(StyleValue::Number(number)) => *number,
+            _ => default,
+        }
+    }
+
+    /// Get a string, if it's set.
+    pub fn string(&self, selector: &str, property: &str) -> Option<&str> {
+        match self.get(selector, property) {
+            Some(StyleValue::String(string)) => Some(string),
+            _ => None,
+        }
+    }
+
+    /// Get a [`Padding`], which works like in CSS: one number for all sides, two numbers for vertical and horizontal,
+    /// or four numbers for top, right, bottom, and left. Returns `default` if it's not set or invalid.
+    pub fn padding(&self, selector: &str, property: &str, default: Padding) -> Padding {
+        match self.get(selector, property) {
+            Some(StyleValue::Number(v)) => Padding::all(*v),
+            Some(StyleValue::Numbers(numbers)) => match numbers[..] {
+                [v, h] => Padding { l: h, t: v, r: h, b: v },
+                [t, r, b, l] => Padding { l, t, r, b },
+                _ => default,
+            },
+            _ => default,
+        }
+    }
+
+    /// Get a [`TextStyle`] based on `default`, with `font_size`, `line_spacing`, `top_drop`, `brightness`, and
+    /// `curve` properties overridden if they are set. Fonts have to be loaded in code (e.g. using
+    /// [`Cx::load_font`]), so you can use [`StyleSheet::string`] to pick between those.
+    pub fn text_style(&self, selector: &str, default: TextStyle) -> TextStyle {
+        TextStyle {
+            font_size: self.float(selector, "font_size", default.font_size),
+            line_spacing: self.float(selector, "line_spacing", default.line_spacing),
+            top_drop: self.float(selector, "top_drop", default.top_drop),
+            brightness: self.float(selector, "brightness", default.brightness),
+            curve: self.float(selector, "curve", default.curve),
+            ..default
+        }
+    }
+}
+
+/// A [`StyleSheet`] loaded from a file, which gets reloaded when the file changes.
+///
+/// Watching for changes only happens natively in debug builds, so designers can tweak a running app, while release
+/// builds just load the file once. Call [`LiveStyleSheet::handle`] in your event handler, and read styles from
+/// [`LiveStyleSheet::sheet`] when drawing:
+///
+/// ```
+/// fn handle(&mut self, cx: &mut Cx, event: &mut Event) {
+///     self.style.handle(cx, event);
+///     // ...
+/// }
+///
+/// fn draw(&mut self, cx: &mut Cx) {
+///     let color = self.style.sheet().color("button", "background", vec4(0.2, 0.2, 0.2, 1.));
+///     // ...
+/// }
+/// ```
+pub struct LiveStyleSheet {
+    path: String,
+    sheet: StyleSheet,
+    signal: Signal,
+    /// Set when this gets dropped, to stop the thread that watches the file.
+    #[cfg(all(debug_assertions, not(target_arch = "wasm32")))]
+    stop_watching: std::sync::Arc<std::sync::atomic::AtomicBool>,
+}
+
+impl LiveStyleSheet {
+    /// The status of the [`Signal`] that gets sent when the file changed.
+    pub const STATUS_STYLE_SHEET_CHANGED: StatusId = location_hash!();
+
+    /// Load a style sheet from `path` (see [`universal_file::read_to_string`]), and start watching it for changes.
+    pub fn load(cx: &mut Cx, path: &str) -> Result<Self, String> {
+        let sheet = Self::read(path)?;
+        let signal = cx.new_signal();
+
+        #[cfg(all(debug_assertions, not(target_arch = "wasm32")))]
+        let stop_watching = {
+            use std::sync::atomic::{AtomicBool, Ordering};
+            use std::sync::Arc;
+
+            let stop_watching = Arc::new(AtomicBool::new(false));
+            let stop = Arc::clone(&stop_watching);
+            let watch_path = path.to_string();
+            std::thread::spawn(move || {
+                let modified = || std::fs::metadata(&watch_path).and_then(|metadata| metadata.modified()).ok();
+                let mut last_modified = modified();
+                while !stop.load(Ordering::Relaxed) {
+                    std::thread::sleep(std::time::Duration::from_millis(250));
+                    let new_modified = modified();
+                    if new_modified != last_modified {
+                        last_modified = new_modified;
+                        Cx::post_signal(signal, Self::STATUS_STYLE_SHEET_CHANGED);
+                    }
+                }
+            });
+            stop_watching
+        };
+
+        Ok(Self {
+            path: path.to_string(),
+            sheet,
+            signal,
+            #[cfg(all(debug_assertions, not(target_arch = "wasm32")))]
+            stop_watching,
+        })
+    }
+
+    fn read(path: &str) -> Result<StyleSheet, String> {
+        let source = universal_file::read_to_string(path).map_err(|err| format!("Could not read {path}: {err}"))?;
+        StyleSheet::parse(&source).map_err(|err| format!("{path}: {err}"))
+    }
+
+    /// The current [`StyleSheet`].
+    pub fn sheet(&self) -> &StyleSheet {
+        &self.sheet
+    }
+
+    /// Reload the style sheet when the file changed, and redraw everything. If the new file has errors, they get
+    /// logged and we keep using the previous version. Returns whether the style sheet got reloaded.
+    pub fn handle(&mut self, cx: &mut Cx, event: &Event) -> bool {
+        match event {
+            Event::Signal(se) if se.signals.contains_key(&self.signal) => match Self::read(&self.path) {
+                Ok(sheet) => {
+                    self.sheet = sheet;
+                    cx.invalidate_view_caches();
+                    true
+                }
+                Err(err) => {
+                    log!("{}", err);
+                    false
+                }
+            },
+            _ => false,
+        }
+    }
+}
+
+#[cfg(all(debug_assertions, not(target_arch = "wasm32")))]
+impl Drop for LiveStyleSheet {
+    fn drop(&mut self) {
+        self.stop_watching.store(true, std::sync::atomic::Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let sheet = StyleSheet::parse(
+            r#"
+            // A comment.
+            button {
+                background: #333; // Another comment.
+                padding: 4 8;
+                font_size: 11;
+            }
+            button.primary { background: #ff0000; font: "Ubuntu" }
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(sheet.color("button", "background", Vec4::default()), vec4(0.2, 0.2, 0.2, 1.));
+        assert_eq!(sheet.color("button.primary", "background", Vec4::default()), vec4(1., 0., 0., 1.));
+        let padding = sheet.padding("button.primary", "padding", Padding::ZERO);
+        assert_eq!((padding.l, padding.t, padding.r, padding.b), (8., 4., 8., 4.));
+        assert_eq!(sheet.float("button.primary.large", "font_size", 8.), 11.);
+        assert_eq!(sheet.float("button", "missing", 8.), 8.);
+        assert_eq!(sheet.float("button", "background", 8.), 8.);
+        assert_eq!(sheet.string("button.primary", "font"), Some("Ubuntu"));
+        assert_eq!(sheet.string("button", "font"), None);
+        assert_eq!(sheet.text_style("button", TextStyle::default()).font_size, 11.);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let error_line = |source| StyleSheet::parse(source).unwrap_err().line;
+        assert_eq!(error_line("button"), 1);
+        assert_eq!(error_line("button {\n  color #fff;\n}"), 2);
+        assert_eq!(error_line("button {\n  color: #ggg;\n}"), 2);
+        assert_eq!(error_line("button {\n  size: 1 x;\n}"), 2);
+        assert_eq!(error_line("button {\n  size: 1;\n"), 2);
+    }
+}
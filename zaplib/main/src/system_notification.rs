@@ -0,0 +1,134 @@
+//! Notifications from the operating system or browser, e.g. for when the app isn't focused.
+//!
+//! [`Cx::show_system_notification`] uses:
+//! * On the web, the browser's [Notification API](https://developer.mozilla.org/en-US/docs/Web/API/Notifications_API).
+//!   The first time, this asks the user for permission.
+//! * On Linux, the `notify-send` command (from libnotify).
+//! * On Mac, `osascript`, so the notification shows up as coming from "Script Editor".
+//! * Windows is not supported yet.
+//!
+//! For notifications within the app, see `zaplib_components::Notifications`.
+
+use crate::*;
+use std::fmt;
+use std::future::Future;
+
+/// Why showing a system notification failed.
+#[derive(Clone, Debug, PartialEq)]
+pub enum SystemNotificationError {
+    /// System notifications are not supported on this platform, or `notify-send` is not installed.
+    NotSupported(String),
+    /// The user didn't give permission to show notifications.
+    NotAllowed(String),
+    /// Any other error.
+    Other(String),
+}
+
+impl fmt::Display for SystemNotificationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SystemNotificationError::NotSupported(message) => write!(f, "System notifications not supported: {}", message),
+            SystemNotificationError::NotAllowed(message) => write!(f, "System notifications not allowed: {}", message),
+            SystemNotificationError::Other(message) => write!(f, "System notification error: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for SystemNotificationError {}
+
+#[cfg(target_arch = "wasm32")]
+impl From<CallJsError> for SystemNotificationError {
+    fn from(err: CallJsError) -> Self {
+        match err {
+            // See `notification.ts`.
+            CallJsError::Thrown(message) if message.starts_with("NotSupportedError") => {
+                SystemNotificationError::NotSupported(message)
+            }
+            CallJsError::Thrown(message) if message.starts_with("NotAllowedError") => {
+                SystemNotificationError::NotAllowed(message)
+            }
+            CallJsError::Thrown(message) => SystemNotificationError::Other(message),
+            CallJsError::TimedOut => SystemNotificationError::Other("Timed out".to_string()),
+        }
+    }
+}
+
+impl Cx {
+    /// Show a notification with `title` and `body` outside of the app. See [`crate::system_notification`] for how
+    /// this works on every platform.
+    ///
+    /// The returned [`Future`] resolves once the notification has been handed off to the system.
+    pub fn show_system_notification(
+        &mut self,
+        title: &str,
+        body: &str,
+    ) -> impl Future<Output = Result<(), SystemNotificationError>> {
+        #[cfg(target_arch = "wasm32")]
+        {
+            let future = self.call_js_async(
+                "_zaplibShowNotification",
+                vec![title.to_string().into_param(), body.to_string().into_param()],
+                None,
+            );
+            async move {
+                future.await?;
+                Ok(())
+            }
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            let title = title.to_string();
+            let body = body.to_string();
+            async move {
+                if cfg!(target_os = "windows") {
+                    return Err(SystemNotificationError::NotSupported("Only supported on Linux and Mac".to_string()));
+                }
+                spawn_blocking(move || native::show_notification(&title, &body)).await
+            }
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+mod native {
+    use super::*;
+    use std::io::ErrorKind;
+    use std::process::{Command, Output};
+
+    /// Quote `text` as an AppleScript string literal.
+    fn applescript_string(text: &str) -> String {
+        format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+    }
+
+    pub(super) fn show_notification(title: &str, body: &str) -> Result<(), SystemNotificationError> {
+        let output: std::io::Result<Output> = if cfg!(target_os = "macos") {
+            let script = format!("display notification {} with title {}", applescript_string(body), applescript_string(title));
+            Command::new("osascript").arg("-e").arg(script).output()
+        } else {
+            // "--" so that titles starting with a dash don't get parsed as options.
+            Command::new("notify-send").arg("--").arg(title).arg(body).output()
+        };
+        let output = output.map_err(|err| {
+            if err.kind() == ErrorKind::NotFound {
+                SystemNotificationError::NotSupported("System notifications require libnotify (`notify-send`)".to_string())
+            } else {
+                SystemNotificationError::Other(err.to_string())
+            }
+        })?;
+        if output.status.success() {
+            Ok(())
+        } else {
+            Err(SystemNotificationError::Other(String::from_utf8_lossy(&output.stderr).trim().to_string()))
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn test_applescript_string() {
+            assert_eq!(applescript_string(r#"Say "hi" \o/"#), r#""Say \"hi\" \\o/""#);
+        }
+    }
+}
@@ -0,0 +1,304 @@
+//! A harness for writing unit tests for components, without needing an actual window or GPU.
+
+use crate::*;
+use std::any::TypeId;
+use std::collections::HashMap;
+
+/// Wraps a [`Cx`] so that tests can run draw cycles and inject synthetic events, without
+/// having to start a real event loop.
+///
+/// Everything goes through the same code paths as a normal app (e.g. [`Cx::call_event_handler`]
+/// for key focus handling, and [`Event::hits_pointer`] for pointer capturing), so you can test
+/// components the same way they are used in practice:
+///
+/// ```
+/// let mut button = Button::default();
+/// let mut test_cx = TestCx::new(vec2(400., 300.));
+/// test_cx.draw(|cx| button.draw(cx, "Hello"));
+///
+/// let events = test_cx.click(vec2(10., 10.), |cx, event| button.handle(cx, event));
+/// assert!(events.contains(&ButtonEvent::Clicked));
+/// ```
+///
+/// Note that nothing ever gets painted; you can only inspect the draw tree, e.g. using
+/// [`Area::get_rect_for_first_instance`] or [`Area::get_first`].
+pub struct TestCx {
+    /// The underlying [`Cx`], which you can use directly for inspecting state.
+    pub cx: Cx,
+    /// The size of the fake window that we draw into.
+    size: Vec2,
+    window: Window,
+    pass: Pass,
+    view: View,
+    after_draw: CxAfterDraw,
+}
+
+impl TestCx {
+    /// Create a new [`TestCx`] with a fake window of the given `size`.
+    pub fn new(size: Vec2) -> Self {
+        let mut cx = Cx::new(TypeId::of::<TestCx>());
+        // Tests run in parallel in the same process, so don't share shader ids with other `Cx`es.
+        cx.local_shader_ids = Some(HashMap::new());
+        cx.load_fonts();
+        let after_draw = CxAfterDraw::new(&mut cx);
+        cx.set_finished_app_new();
+        Self { cx, size, window: Window::default(), pass: Pass::default(), view: View::default(), after_draw }
+    }
+
+    /// Advance [`Cx::last_event_time`] by `seconds`. Subsequent events will be stamped with this time,
+    /// which is useful for testing animations and double clicks.
+    pub fn advance_time(&mut self, seconds: f64) {
+        self.cx.last_event_time += seconds;
+    }
+
+    /// Whether [`Cx::request_draw`] was called since the last [`TestCx::draw`].
+    pub fn requested_draw(&self) -> bool {
+        self.cx.requested_draw
+    }
+
+    /// Whether [`Cx::request_next_frame`] was called since the last [`TestCx::next_frame`].
+    pub fn requested_next_frame(&self) -> bool {
+        self.cx.requested_next_frame
+    }
+
+    /// Run a full draw cycle, calling `draw_fn` inside a root [`View`] that fills the fake window.
+    pub fn draw<F>(&mut self, mut draw_fn: F)
+    where
+        F: FnMut(&mut Cx),
+    {
+        let Self { cx, size, window, pass, view, after_draw } = self;
+        window.create_inner_size = Some(*size);
+        let mut event_handler = |cx: &mut Cx, event: &mut Event| {
+            if let Event::System(SystemEvent::Draw) = event {
+                window.begin_window(cx);
+                pass.begin_pass(cx, Vec4::default());
+                view.begin_view(cx, LayoutSize::FILL);
+                draw_fn(cx);
+                view.end_view(cx);
+                pass.end_pass(cx);
+                window.end_window(cx);
+                after_draw.after_draw(cx);
+            }
+        };
+        Self::with_event_handler(cx, &mut event_handler, |cx| {
+            cx.call_draw_event();
+            // See the note on `redraw_id` in `Cx::call_draw_event`; in tests we don't want that hack.
+            cx.requested_draw = false;
+            cx.call_signals();
+        });
+        // Normally done while painting, but we need it for things like [`Area::get_rect_for_first_instance`].
+        let view_id = cx.passes[pass.pass_id.unwrap()].main_view_id.unwrap();
+        Self::update_draw_uniforms(cx, view_id, Vec2::default(), (vec2(-50000., -50000.), vec2(50000., 50000.)));
+    }
+
+    /// Set the scroll and clip uniforms of all [`DrawCall`]s, like `render_view` does on the platforms.
+    fn update_draw_uniforms(cx: &mut Cx, view_id: usize, scroll: Vec2, clip: (Vec2, Vec2)) {
+        cx.views[view_id].parent_scroll = scroll;
+        let local_scroll = cx.views[view_id].snapped_scroll;
+        let clip = cx.views[view_id].intersect_clip(clip);
+        for draw_call_id in 0..cx.views[view_id].draw_calls_len {
+            let sub_view_id = cx.views[view_id].draw_calls[draw_call_id].sub_view_id;
+            if sub_view_id != 0 {
+                Self::update_draw_uniforms(cx, sub_view_id, scroll + local_scroll, clip);
+            } else {
+                let draw_call = &mut cx.views[view_id].draw_calls[draw_call_id];
+                draw_call.set_local_scroll(scroll, local_scroll);
+                draw_call.set_clip(clip);
+            }
+        }
+    }
+
+    /// Fire an [`Event::NextFrame`], after advancing time by `seconds`.
+    ///
+    /// Returns all the values that `handler` returned, in order.
+    pub fn next_frame<F, R>(&mut self, seconds: f64, handler: F) -> Vec<R>
+    where
+        F: FnMut(&mut Cx, &mut Event) -> R,
+    {
+        self.advance_time(seconds);
+        self.dispatch(Event::NextFrame, handler)
+    }
+
+    /// Fire an arbitrary [`Event`], going through the same pre- and post-processing as a real event loop.
+    ///
+    /// `handler` gets called for the event itself, and potentially for resulting events, such as
+    /// [`Event::KeyFocus`] and [`Event::Signal`]. Returns all the values that `handler` returned, in order.
+    pub fn dispatch<F, R>(&mut self, mut event: Event, mut handler: F) -> Vec<R>
+    where
+        F: FnMut(&mut Cx, &mut Event) -> R,
+    {
+        let mut results = Vec::new();
+        let mut event_handler = |cx: &mut Cx, event: &mut Event| results.push(handler(cx, event));
+        Self::with_event_handler(&mut self.cx, &mut event_handler, |cx| {
+            if let Event::NextFrame = event {
+                cx.call_next_frame_event();
+            } else {
+                cx.process_pre_event(&mut event);
+                cx.call_event_handler(&mut event);
+                cx.process_post_event(&mut event);
+            }
+            cx.call_signals();
+        });
+        results
+    }
+
+    /// Fire an [`Event::PointerDown`] at `abs`, with the left mouse button.
+    pub fn pointer_down<F, R>(&mut self, abs: Vec2, handler: F) -> Vec<R>
+    where
+        F: FnMut(&mut Cx, &mut Event) -> R,
+    {
+        let time = self.cx.last_event_time;
+        self.dispatch(Event::PointerDown(PointerDownEvent { abs, time, ..PointerDownEvent::default() }), handler)
+    }
+
+    /// Fire an [`Event::PointerMove`] at `abs`; as if the pointer is being dragged.
+    pub fn pointer_move<F, R>(&mut self, abs: Vec2, handler: F) -> Vec<R>
+    where
+        F: FnMut(&mut Cx, &mut Event) -> R,
+    {
+        let time = self.cx.last_event_time;
+        self.dispatch(Event::PointerMove(PointerMoveEvent { abs, time, ..PointerMoveEvent::default() }), handler)
+    }
+
+    /// Fire an [`Event::PointerUp`] at `abs`, with the left mouse button.
+    pub fn pointer_up<F, R>(&mut self, abs: Vec2, handler: F) -> Vec<R>
+    where
+        F: FnMut(&mut Cx, &mut Event) -> R,
+    {
+        let time = self.cx.last_event_time;
+        self.dispatch(Event::PointerUp(PointerUpEvent { abs, time, ..PointerUpEvent::default() }), handler)
+    }
+
+    /// Fire an [`Event::PointerHover`] at `abs`; as if the mouse is moved without holding it down.
+    pub fn pointer_hover<F, R>(&mut self, abs: Vec2, handler: F) -> Vec<R>
+    where
+        F: FnMut(&mut Cx, &mut Event) -> R,
+    {
+        let time = self.cx.last_event_time;
+        self.dispatch(Event::PointerHover(PointerHoverEvent { abs, time, ..PointerHoverEvent::default() }), handler)
+    }
+
+    /// Fire an [`Event::PointerScroll`] at `abs`, scrolling by `scroll`.
+    pub fn pointer_scroll<F, R>(&mut self, abs: Vec2, scroll: Vec2, handler: F) -> Vec<R>
+    where
+        F: FnMut(&mut Cx, &mut Event) -> R,
+    {
+        let time = self.cx.last_event_time;
+        self.dispatch(Event::PointerScroll(PointerScrollEvent { abs, scroll, time, ..PointerScrollEvent::default() }), handler)
+    }
+
+    /// Fire an [`Event::PointerDown`] followed by an [`Event::PointerUp`] at `abs`.
+    pub fn click<F, R>(&mut self, abs: Vec2, mut handler: F) -> Vec<R>
+    where
+        F: FnMut(&mut Cx, &mut Event) -> R,
+    {
+        let mut results = self.pointer_down(abs, &mut handler);
+        results.extend(self.pointer_up(abs, &mut handler));
+        results
+    }
+
+    /// Fire an [`Event::KeyDown`] for `key_code`.
+    pub fn key_down<F, R>(&mut self, key_code: KeyCode, modifiers: KeyModifiers, handler: F) -> Vec<R>
+    where
+        F: FnMut(&mut Cx, &mut Event) -> R,
+    {
+        let time = self.cx.last_event_time;
+        self.dispatch(Event::KeyDown(KeyEvent { key_code, is_repeat: false, modifiers, time }), handler)
+    }
+
+    /// Fire an [`Event::KeyUp`] for `key_code`.
+    pub fn key_up<F, R>(&mut self, key_code: KeyCode, modifiers: KeyModifiers, handler: F) -> Vec<R>
+    where
+        F: FnMut(&mut Cx, &mut Event) -> R,
+    {
+        let time = self.cx.last_event_time;
+        self.dispatch(Event::KeyUp(KeyEvent { key_code, is_repeat: false, modifiers, time }), handler)
+    }
+
+    /// Fire an [`Event::TextInput`] with `input`, as if it was typed in.
+    pub fn text_input<F, R>(&mut self, input: &str, handler: F) -> Vec<R>
+    where
+        F: FnMut(&mut Cx, &mut Event) -> R,
+    {
+        self.dispatch(
+            Event::TextInput(TextInputEvent { input: input.to_string(), replace_last: false, was_paste: false }),
+            handler,
+        )
+    }
+
+    /// Temporarily install `event_handler` as [`Cx::event_handler`] while running `f`, just like
+    /// the platform-specific `event_loop` functions do.
+    fn with_event_handler(cx: &mut Cx, event_handler: &mut dyn FnMut(&mut Cx, &mut Event), f: impl FnOnce(&mut Cx)) {
+        // Erase the lifetime; this is safe since we unset `event_handler` before returning.
+        cx.event_handler = Some(unsafe {
+            std::mem::transmute::<*mut (dyn FnMut(&mut Cx, &mut Event) + '_), *mut dyn FnMut(&mut Cx, &mut Event)>(event_handler)
+        });
+        f(cx);
+        cx.event_handler = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    static SHADER: Shader = Shader {
+        build_geom: Some(QuadIns::build_geom),
+        code_to_concatenate: &[
+            Cx::STD_SHADER,
+            QuadIns::SHADER,
+            code_fragment!(
+                r#"
+                fn pixel() -> vec4 {
+                    return #f;
+                }"#
+            ),
+        ],
+        ..Shader::DEFAULT
+    };
+
+    #[derive(Default)]
+    struct TestButton {
+        component_id: ComponentId,
+        area: Area,
+    }
+
+    impl TestButton {
+        fn draw(&mut self, cx: &mut Cx) {
+            self.area = cx.add_instances(&SHADER, &[QuadIns::from_rect(Rect { pos: vec2(10., 10.), size: vec2(50., 20.) })]);
+        }
+
+        fn handle(&mut self, cx: &mut Cx, event: &mut Event) -> Option<&'static str> {
+            match event.hits_pointer(cx, self.component_id, self.area.get_rect_for_first_instance(cx)) {
+                Event::PointerDown(_) => {
+                    cx.set_key_focus(Some(self.component_id));
+                    Some("down")
+                }
+                Event::PointerUp(pe) if pe.is_over => Some("clicked"),
+                _ => match event.hits_keyboard(cx, self.component_id) {
+                    Event::KeyDown(_) => Some("key"),
+                    _ => None,
+                },
+            }
+        }
+    }
+
+    #[test]
+    fn test_click_and_key_focus() {
+        let mut button = TestButton::default();
+        let mut test_cx = TestCx::new(vec2(400., 300.));
+        test_cx.draw(|cx| button.draw(cx));
+
+        // Missing the button altogether.
+        let events = test_cx.click(vec2(100., 100.), |cx, event| button.handle(cx, event));
+        assert!(events.iter().all(|e| e.is_none()));
+
+        let events = test_cx.click(vec2(20., 20.), |cx, event| button.handle(cx, event));
+        assert!(events.contains(&Some("down")));
+        assert!(events.contains(&Some("clicked")));
+        assert!(test_cx.cx.has_key_focus(Some(button.component_id)));
+
+        let events = test_cx.key_down(KeyCode::KeyA, KeyModifiers::default(), |cx, event| button.handle(cx, event));
+        assert_eq!(events, vec![Some("key")]);
+    }
+}
@@ -7,7 +7,9 @@
 
 use std::{borrow::Borrow, sync::RwLock};
 
+use crate::text_shaping::shape_text;
 use crate::*;
+use zaplib_vector::font::ColorGlyph;
 
 #[derive(Clone, Copy, Debug)]
 #[repr(C)]
@@ -32,6 +34,9 @@ pub struct TextIns {
     pub char_offset: f32,
     /// TODO(JP): document.
     pub marker: f32,
+    /// 1.0 if this is a color bitmap (like an emoji) from the color glyph atlas, in which case `font_t1` and `font_t2`
+    /// refer to that atlas, and only the alpha of `color` is used. 0.0 for regular glyphs.
+    pub color_bitmap: f32,
 }
 
 #[repr(C)]
@@ -50,6 +55,7 @@ pub static TEXT_INS_SHADER: Shader = Shader {
             uniform curve: float;
 
             texture texture: texture2D;
+            texture color_texture: texture2D;
 
             instance font_t1: vec2;
             instance font_t2: vec2;
@@ -61,6 +67,7 @@ pub static TEXT_INS_SHADER: Shader = Shader {
             instance font_size: float;
             instance char_offset: float;
             instance marker: float;
+            instance color_bitmap: float;
 
             geometry geom: vec2;
 
@@ -73,7 +80,19 @@ pub static TEXT_INS_SHADER: Shader = Shader {
                 return color;
             }
 
+            // Approximate blending in linear color space (see `TextRendering::gamma`), assuming that the background
+            // contrasts with the text: light text gets bolder and dark text gets thinner.
+            fn apply_text_gamma(s: float, rgb: vec3) -> float {
+                let luminance = dot(rgb, vec3(0.2126, 0.7152, 0.0722));
+                return mix(1.0 - pow(1.0 - s, 1.0 / text_gamma), pow(s, 1.0 / text_gamma), luminance);
+            }
+
             fn pixel() -> vec4 {
+                if color_bitmap > 0.5 {
+                    // Color bitmaps are stored with premultiplied alpha.
+                    return sample2d(color_texture, tex_coord1.xy) * color.a;
+                }
+
                 let dx = dFdx(vec2(tex_coord1.x * 2048.0, 0.)).x;
                 let dp = 1.0 / 2048.0;
 
@@ -104,20 +123,23 @@ pub static TEXT_INS_SHADER: Shader = Shader {
 
                 s = pow(s, curve);
                 let col = get_color(); //color!(white);//get_color();
+                s = apply_text_gamma(s, col.rgb);
                 return vec4(s * col.rgb * brightness * col.a, s * col.a);
             }
 
             fn vertex() -> vec4 {
                 let min_pos = vec2(rect_pos.x, rect_pos.y);
                 let max_pos = vec2(rect_pos.x + rect_size.x, rect_pos.y - rect_size.y);
+                // Glyphs are positioned on physical pixels, so keep them there when scrolling by fractional amounts.
+                let snapped_scroll = snap_to_pixel(draw_scroll);
 
                 clipped = clamp(
-                    mix(min_pos, max_pos, geom) - draw_scroll,
+                    mix(min_pos, max_pos, geom) - snapped_scroll,
                     draw_clip.xy,
                     draw_clip.zw
                 );
 
-                let normalized: vec2 = (clipped - min_pos + draw_scroll) / vec2(rect_size.x, -rect_size.y);
+                let normalized: vec2 = (clipped - min_pos + snapped_scroll) / vec2(rect_size.x, -rect_size.y);
                 //rect = vec4(min_pos.x, min_pos.y, max_pos.x, max_pos.y) - draw_scroll.xyxy;
 
                 tex_coord1 = mix(
@@ -201,6 +223,7 @@ impl Default for TextInsProps {
 /// Determines when to emit a set of glyphs, which has roughly the effect of
 /// wrapping at these boundaries.
 #[derive(Copy, Clone, Debug)]
+#[cfg_attr(feature = "serde-support", derive(serde::Serialize, serde::Deserialize))]
 pub enum Wrapping {
     None,
     Char,
@@ -218,6 +241,59 @@ impl Default for Wrapping {
     }
 }
 
+/// How to position glyphs relative to physical pixels; see [`TextRendering::subpixel_positioning`].
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde-support", derive(serde::Serialize, serde::Deserialize))]
+pub enum SubpixelPositioning {
+    /// Position glyphs at fractions of physical pixels, both horizontally and vertically, for fonts up to 32pt.
+    Full,
+    /// Position glyphs at quarters of physical pixels horizontally, so spacing between characters stays even, but
+    /// put baselines on whole physical pixels, which keeps horizontal strokes crisp. Good for small text on low-DPI
+    /// screens.
+    Horizontal,
+    /// Put the origins of glyphs on whole physical pixels. Crispest, but spacing between characters gets uneven.
+    Off,
+}
+impl SubpixelPositioning {
+    /// TODO(JP): Replace these with SubpixelPositioning::default() when
+    /// <https://github.com/rust-lang/rust/issues/67792> gets done
+    pub const DEFAULT: SubpixelPositioning = SubpixelPositioning::Full;
+}
+impl Default for SubpixelPositioning {
+    fn default() -> Self {
+        SubpixelPositioning::DEFAULT
+    }
+}
+
+/// How text gets drawn within a [`Pass`]; see [`Pass::set_text_rendering`].
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde-support", derive(serde::Serialize, serde::Deserialize))]
+pub struct TextRendering {
+    /// See [`SubpixelPositioning`].
+    pub subpixel_positioning: SubpixelPositioning,
+    /// The gamma to use for blending text with its background, to approximate blending in linear color space, like
+    /// browsers do. Without this (at 1.0, the default), light text on dark backgrounds looks thin, and dark text on
+    /// light backgrounds looks bold and blurry. 2.2 is a typical value, but lower values can look better with some
+    /// fonts.
+    pub gamma: f32,
+}
+impl TextRendering {
+    /// TODO(JP): Replace these with TextRendering::default() when
+    /// <https://github.com/rust-lang/rust/issues/67792> gets done
+    pub const DEFAULT: TextRendering = TextRendering { subpixel_positioning: SubpixelPositioning::DEFAULT, gamma: 1.0 };
+}
+impl Default for TextRendering {
+    fn default() -> Self {
+        TextRendering::DEFAULT
+    }
+}
+
+/// Subpixel ids (see [`CxFontsAtlasTodo::subpixel_id`]) that [`SubpixelPositioning::Full`] never uses. For the other
+/// modes the position of a glyph within a physical pixel only depends on the glyph and this id, so the glyph can be
+/// reused exactly.
+const SUBPIXEL_ID_HORIZONTAL_START: usize = 56;
+const SUBPIXEL_ID_OFF: usize = 63;
+
 #[derive(Default)]
 pub struct DrawGlyphsProps {
     pub text_style: TextStyle,
@@ -242,10 +318,12 @@ impl TextIns {
     /// * <https://github.com/Zaplib/zaplib/issues/169>
     /// * <https://github.com/Zaplib/zaplib/issues/174>
     /// * <https://github.com/Zaplib/zaplib/issues/175>
+    #[allow(clippy::too_many_arguments)]
     pub fn generate_2d_glyphs<F>(
         text_style: &TextStyle,
         fonts_data: &RwLock<CxFontsData>,
         dpi_factor: f32,
+        subpixel_positioning: SubpixelPositioning,
         font_scale: f32,
         draw_depth: f32,
         color: Vec4,
@@ -257,111 +335,235 @@ impl TextIns {
     where
         F: FnMut(char, usize, f32, f32) -> f32,
     {
-        let chars = chars.into_iter();
-        let mut ret = Vec::with_capacity(chars.size_hint().0);
+        let chars: Vec<char> = chars.into_iter().map(|c| *c.borrow()).collect();
+        let clusters = shape_text(fonts_data, text_style.font.font_id, &chars);
+        let mut ret = Vec::with_capacity(chars.len());
 
-        let font_id = text_style.font.font_id;
+        // The font that the current glyph comes from, which can be one of the fallbacks of `text_style.font`.
+        let mut font_id = text_style.font.font_id;
 
-        let (atlas_page_id, mut read_lock) = get_font_atlas_page_id(fonts_data, font_id, dpi_factor, text_style.font_size);
-
-        let font_size_logical =
-            text_style.font_size * 96.0 / (72.0 * read_lock.fonts[font_id].font_loaded.as_ref().unwrap().units_per_em);
-        let font_size_pixels = font_size_logical * dpi_factor;
+        let (mut atlas_page_id, mut read_lock) = get_font_atlas_page_id(fonts_data, font_id, dpi_factor, text_style.font_size);
 
+        // The size of an em in logical pixels, for converting the units of `ShapedCluster`.
+        let em_size = text_style.font_size * 96.0 / 72.0;
         let mut x = pos.x;
-        let mut char_offset = char_offset;
-
-        for wc in chars {
-            let unicode = *wc.borrow() as usize;
-
-            // Scope the `cxfont` borrow to these variables.
-            let (glyph_id, advance, w, h, min_pos_x, subpixel_x_fract, subpixel_y_fract, scaled_min_pos_x, scaled_min_pos_y) = {
-                let cxfont = read_lock.fonts[font_id].font_loaded.as_ref().unwrap();
-                let glyph_id = cxfont.char_code_to_glyph_index_map[unicode];
-                if glyph_id >= cxfont.glyphs.len() {
-                    println!("GLYPHID OUT OF BOUNDS {} {} len is {}", unicode, glyph_id, cxfont.glyphs.len());
-                    continue;
-                }
-
-                let glyph = &cxfont.glyphs[glyph_id];
-
-                let advance = glyph.horizontal_metrics.advance_width * font_size_logical * font_scale;
-
-                // snap width/height to pixel granularity
-                let w = ((glyph.bounds.p_max.x - glyph.bounds.p_min.x) * font_size_pixels).ceil() + 1.0;
-                let h = ((glyph.bounds.p_max.y - glyph.bounds.p_min.y) * font_size_pixels).ceil() + 1.0;
-
-                // this one needs pixel snapping
-                let min_pos_x = x + font_size_logical * glyph.bounds.p_min.x;
-                let min_pos_y = pos.y - font_size_logical * glyph.bounds.p_min.y + text_style.font_size * text_style.top_drop;
-
-                // compute subpixel shift
-                // TODO(JP): It's confusing that subpixel_x_fract and subpixel_y_fract are not 0 even when the glyph
-                // is not subpixel-shifted.
-                // See https://github.com/Zaplib/zaplib/issues/175
-                let subpixel_x_fract = min_pos_x - (min_pos_x * dpi_factor).floor() / dpi_factor;
-                let subpixel_y_fract = min_pos_y - (min_pos_y * dpi_factor).floor() / dpi_factor;
-
-                // scale and snap it
-                let scaled_min_pos_x = x + font_size_logical * font_scale * glyph.bounds.p_min.x - subpixel_x_fract;
-                let scaled_min_pos_y = pos.y - font_size_logical * font_scale * glyph.bounds.p_min.y
-                    + text_style.font_size * font_scale * text_style.top_drop
-                    - subpixel_y_fract;
-
-                (glyph_id, advance, w, h, min_pos_x, subpixel_x_fract, subpixel_y_fract, scaled_min_pos_x, scaled_min_pos_y)
-            };
-
-            // only use a subpixel id for small fonts
-            let subpixel_id = if text_style.font_size > 32.0 {
-                0
-            } else {
-                // subtle 64 index subpixel id
-                // TODO(JP): Should this be 8.0??
-                ((subpixel_y_fract * 7.0) as usize) << 3 | (subpixel_x_fract * 7.0) as usize
-            };
+        // Where each cluster ends. This is a hot loop for big texts, so see `crate::simd`.
+        let cluster_ends = crate::simd::running_sums(clusters.iter().map(|cluster| cluster.advance * em_size * font_scale), x);
+
+        for (cluster, cluster_end) in clusters.iter().zip(cluster_ends) {
+            let advance = cluster_end - x;
+            // Characters in a ligature share its glyph, so give each of them an equal part of it, like browsers do
+            // for placing the cursor.
+            let char_advance = advance / cluster.char_count as f32;
+            let markers: Vec<f32> = (0..cluster.char_count)
+                .map(|index| {
+                    let char_index = cluster.char_index + index;
+                    char_callback(chars[char_index], char_offset + char_index, x + char_advance * index as f32, char_advance)
+                })
+                .collect();
 
-            let tc = if let Some(tc) = read_lock.fonts[font_id].atlas_pages[atlas_page_id].atlas_glyphs[glyph_id][subpixel_id] {
-                tc
-            } else {
-                // Drop `read_lock` to do some writes, and then reacquire it.
-                drop(read_lock);
-                {
-                    let mut write_fonts_data = fonts_data.write().unwrap();
-
-                    write_fonts_data.fonts_atlas.atlas_todo.push(CxFontsAtlasTodo {
-                        subpixel_x_fract,
-                        subpixel_y_fract,
-                        font_id,
-                        atlas_page_id,
-                        glyph_id,
-                        subpixel_id,
+            for shaped_glyph in &cluster.glyphs {
+                if shaped_glyph.font_id != font_id {
+                    font_id = shaped_glyph.font_id;
+                    // Drop `read_lock`, since getting the atlas page might need to write.
+                    drop(read_lock);
+                    let (new_atlas_page_id, new_read_lock) =
+                        get_font_atlas_page_id(fonts_data, font_id, dpi_factor, text_style.font_size);
+                    atlas_page_id = new_atlas_page_id;
+                    read_lock = new_read_lock;
+                }
+                let glyph_x = x + shaped_glyph.offset.x * em_size * font_scale;
+                let glyph_y = pos.y - shaped_glyph.offset.y * em_size * font_scale;
+                // Where to draw outlines, which can be snapped to physical pixels, depending on `subpixel_positioning`.
+                let (draw_x, draw_y, snapped_subpixel_id) = {
+                    let baseline_y = glyph_y + text_style.font_size * font_scale * text_style.top_drop;
+                    let snapped_y = glyph_y + (baseline_y * dpi_factor).round() / dpi_factor - baseline_y;
+                    match subpixel_positioning {
+                        SubpixelPositioning::Full => (glyph_x, glyph_y, None),
+                        SubpixelPositioning::Horizontal => {
+                            let quarter_pixels = (glyph_x * dpi_factor * 4.0).round();
+                            (
+                                quarter_pixels / (dpi_factor * 4.0),
+                                snapped_y,
+                                Some(SUBPIXEL_ID_HORIZONTAL_START + quarter_pixels.rem_euclid(4.0) as usize),
+                            )
+                        }
+                        SubpixelPositioning::Off => {
+                            ((glyph_x * dpi_factor).round() / dpi_factor, snapped_y, Some(SUBPIXEL_ID_OFF))
+                        }
+                    }
+                };
+                let color_glyph =
+                    read_lock.fonts[font_id].font_loaded.as_ref().unwrap().color_glyphs.get(&shaped_glyph.glyph_id).cloned();
+
+                // Glyphs to draw from the font atlas, with their colors. Layered color glyphs are drawn on top of each
+                // other, in order.
+                let layers: Vec<(usize, Vec4)> = match color_glyph {
+                    Some(ColorGlyph::Bitmap(bitmap)) => {
+                        // Color bitmaps are images, so they come from a separate atlas.
+                        drop(read_lock);
+                        let bitmap_scale = em_size / bitmap.pixels_per_em;
+                        let width = (bitmap.width * bitmap_scale * dpi_factor).ceil();
+                        let height = (bitmap.height * bitmap_scale * dpi_factor).ceil();
+                        let tc = fonts_data.write().unwrap().color_glyph_atlas.get_or_alloc_glyph(
+                            font_id,
+                            atlas_page_id,
+                            shaped_glyph.glyph_id,
+                            width as usize,
+                            height as usize,
+                        );
+                        read_lock = fonts_data.read().unwrap();
+                        // If it's too big for the atlas, fall back to the outline, which is typically empty.
+                        if let Some(tc) = tc {
+                            let min_pos_x = glyph_x + bitmap.left * bitmap_scale * font_scale;
+                            ret.push(TextIns {
+                                font_t1: vec2(tc.tx1, tc.ty1),
+                                font_t2: vec2(tc.tx2, tc.ty2),
+                                color,
+                                rect_pos: vec2(
+                                    min_pos_x,
+                                    glyph_y + text_style.font_size * font_scale * text_style.top_drop
+                                        - bitmap.bottom * bitmap_scale * font_scale,
+                                ),
+                                rect_size: vec2(width * font_scale / dpi_factor, height * font_scale / dpi_factor),
+                                char_depth: draw_depth + 0.00001 * min_pos_x,
+                                base: vec2(glyph_x, pos.y),
+                                font_size: text_style.font_size,
+                                char_offset: (char_offset + cluster.char_index) as f32,
+                                marker: markers[0],
+                                color_bitmap: 1.0,
+                            });
+                            vec![]
+                        } else {
+                            vec![(shaped_glyph.glyph_id, color)]
+                        }
+                    }
+                    Some(ColorGlyph::Layers(layers)) => layers
+                        .into_iter()
+                        .map(|(layer_glyph_id, layer_color)| {
+                            let layer_color = layer_color.map_or(color, |[r, g, b, a]| {
+                                vec4(r as f32 / 255., g as f32 / 255., b as f32 / 255., a as f32 / 255. * color.w)
+                            });
+                            (layer_glyph_id, layer_color)
+                        })
+                        .collect(),
+                    None => vec![(shaped_glyph.glyph_id, color)],
+                };
+                let layered = layers.len() > 1;
+
+                for (layer_index, (glyph_id, color)) in layers.into_iter().enumerate() {
+                    // Scope the `cxfont` borrow to these variables.
+                    let (w, h, min_pos_x, subpixel_x_fract, subpixel_y_fract, scaled_min_pos_x, scaled_min_pos_y) = {
+                        let cxfont = read_lock.fonts[font_id].font_loaded.as_ref().unwrap();
+                        let font_size_logical = text_style.font_size * 96.0 / (72.0 * cxfont.units_per_em);
+                        let font_size_pixels = font_size_logical * dpi_factor;
+
+                        let glyph = &cxfont.glyphs[glyph_id];
+
+                        // snap width/height to pixel granularity
+                        let w = ((glyph.bounds.p_max.x - glyph.bounds.p_min.x) * font_size_pixels).ceil() + 1.0;
+                        let h = ((glyph.bounds.p_max.y - glyph.bounds.p_min.y) * font_size_pixels).ceil() + 1.0;
+
+                        // this one needs pixel snapping
+                        let min_pos_x = draw_x + font_size_logical * glyph.bounds.p_min.x;
+                        let min_pos_y =
+                            draw_y - font_size_logical * glyph.bounds.p_min.y + text_style.font_size * text_style.top_drop;
+
+                        // compute subpixel shift
+                        // TODO(JP): It's confusing that subpixel_x_fract and subpixel_y_fract are not 0 even when the glyph
+                        // is not subpixel-shifted.
+                        // See https://github.com/Zaplib/zaplib/issues/175
+                        let subpixel_x_fract = min_pos_x - (min_pos_x * dpi_factor).floor() / dpi_factor;
+                        let subpixel_y_fract = min_pos_y - (min_pos_y * dpi_factor).floor() / dpi_factor;
+
+                        // scale and snap it
+                        let scaled_min_pos_x = draw_x + font_size_logical * font_scale * glyph.bounds.p_min.x - subpixel_x_fract;
+                        let scaled_min_pos_y = draw_y - font_size_logical * font_scale * glyph.bounds.p_min.y
+                            + text_style.font_size * font_scale * text_style.top_drop
+                            - subpixel_y_fract;
+
+                        (w, h, min_pos_x, subpixel_x_fract, subpixel_y_fract, scaled_min_pos_x, scaled_min_pos_y)
+                    };
+
+                    // only use a subpixel id for small fonts
+                    let subpixel_id = if let Some(snapped_subpixel_id) = snapped_subpixel_id {
+                        snapped_subpixel_id
+                    } else if text_style.font_size > 32.0 {
+                        0
+                    } else {
+                        // subtle 64 index subpixel id, based on the fraction of a physical pixel (the fractions above are in
+                        // logical pixels, which would only use part of the ids on high-DPI and fractional scales)
+                        // TODO(JP): Should this be 8.0??
+                        ((subpixel_y_fract * dpi_factor * 7.0) as usize) << 3 | (subpixel_x_fract * dpi_factor * 7.0) as usize
+                    };
+
+                    let tc = if let Some(entry) =
+                        read_lock.fonts[font_id].atlas_pages[atlas_page_id].atlas_glyphs.get(&(glyph_id, subpixel_id))
+                    {
+                        read_lock.fonts_atlas.touch(entry);
+                        entry.tc
+                    } else {
+                        // Drop `read_lock` to do some writes, and then reacquire it.
+                        drop(read_lock);
+                        let new_glyph = fonts_data.write().unwrap().alloc_atlas_glyph(
+                            CxFontsAtlasTodo {
+                                subpixel_x_fract,
+                                subpixel_y_fract,
+                                font_id,
+                                atlas_page_id,
+                                glyph_id,
+                                subpixel_id,
+                            },
+                            w,
+                            h,
+                        );
+                        read_lock = fonts_data.read().unwrap();
+                        new_glyph
+                    };
+
+                    ret.push(TextIns {
+                        font_t1: vec2(tc.tx1, tc.ty1),
+                        font_t2: vec2(tc.tx2, tc.ty2),
+                        color,
+                        rect_pos: vec2(scaled_min_pos_x, scaled_min_pos_y),
+                        rect_size: vec2(w * font_scale / dpi_factor, h * font_scale / dpi_factor),
+                        // Keep layers in order, even when they're not in the same place.
+                        char_depth: if layered {
+                            draw_depth + 0.00001 * glyph_x + 0.0000001 * layer_index as f32
+                        } else {
+                            draw_depth + 0.00001 * min_pos_x
+                        },
+                        base: vec2(glyph_x, pos.y),
+                        font_size: text_style.font_size,
+                        char_offset: (char_offset + cluster.char_index) as f32,
+                        marker: markers[0],
+                        color_bitmap: 0.0,
                     });
-
-                    let new_glyph = write_fonts_data.fonts_atlas.alloc_atlas_glyph(w, h);
-                    write_fonts_data.fonts[font_id].atlas_pages[atlas_page_id].atlas_glyphs[glyph_id][subpixel_id] =
-                        Some(new_glyph);
                 }
-                read_lock = fonts_data.read().unwrap();
-                read_lock.fonts[font_id].atlas_pages[atlas_page_id].atlas_glyphs[glyph_id][subpixel_id].unwrap()
-            };
+            }
 
-            ret.push(TextIns {
-                font_t1: vec2(tc.tx1, tc.ty1),
-                font_t2: vec2(tc.tx2, tc.ty2),
-                color,
-                rect_pos: vec2(scaled_min_pos_x, scaled_min_pos_y),
-                rect_size: vec2(w * font_scale / dpi_factor, h * font_scale / dpi_factor),
-                char_depth: draw_depth + 0.00001 * min_pos_x,
-                base: vec2(x, pos.y),
-                font_size: text_style.font_size,
-                char_offset: char_offset as f32,
-
-                // give the callback a chance to do things
-                marker: char_callback(*wc.borrow(), char_offset, x, advance),
-            });
-
-            x += advance;
-            char_offset += 1;
+            // Make sure that every character has at least one `TextIns`, even when it's part of a ligature or has no
+            // glyph at all (like a newline), by adding empty ones. That way, you can always find where a character
+            // is, e.g. for selecting text.
+            let first_empty_index = if cluster.glyphs.is_empty() { 0 } else { 1 };
+            for (index, &marker) in markers.iter().enumerate().skip(first_empty_index) {
+                let char_x = x + char_advance * index as f32;
+                ret.push(TextIns {
+                    font_t1: Vec2::default(),
+                    font_t2: Vec2::default(),
+                    color,
+                    rect_pos: vec2(char_x, pos.y),
+                    rect_size: Vec2::default(),
+                    char_depth: draw_depth,
+                    base: vec2(char_x, pos.y),
+                    font_size: text_style.font_size,
+                    char_offset: (char_offset + cluster.char_index + index) as f32,
+                    marker,
+                    color_bitmap: 0.0,
+                });
+            }
+
+            x = cluster_end;
         }
 
         ret
@@ -376,8 +578,12 @@ impl TextIns {
 
     fn write_uniforms(cx: &mut Cx, area: &Area, text_style: &TextStyle) {
         if area.is_first_instance() {
-            let texture_handle = cx.fonts_data.read().unwrap().get_fonts_atlas_texture_handle();
+            let fonts_data = cx.fonts_data.read().unwrap();
+            let texture_handle = fonts_data.get_fonts_atlas_texture_handle();
+            let color_texture_handle = fonts_data.get_color_glyph_atlas_texture_handle();
+            drop(fonts_data);
             area.write_texture_2d(cx, "texture", texture_handle);
+            area.write_texture_2d(cx, "color_texture", color_texture_handle);
             area.write_user_uniforms(cx, TextInsUniforms { brightness: text_style.brightness, curve: text_style.curve });
         }
     }
@@ -422,69 +628,55 @@ impl TextIns {
         area
     }
 
-    /// Measures the width of the text, not including newlines.
-    fn measure_width(cx: &Cx, chars: &[char], props: &TextInsProps) -> f32 {
+    /// The advance width of each character in `chars` after shaping, taking font fallbacks into account (see
+    /// [`Cx::set_font_fallbacks`]). Characters in a ligature each get an equal part of its width.
+    fn char_advances(cx: &Cx, chars: &[char], props: &TextInsProps) -> Vec<f32> {
         let text_style = &props.text_style;
-        let font_id = text_style.font.font_id;
-        let read_fonts = &cx.fonts_data.read().unwrap().fonts;
-        let font_size_logical =
-            text_style.font_size * 96.0 / (72.0 * read_fonts[font_id].font_loaded.as_ref().unwrap().units_per_em);
-
-        let mut width = 0.0;
-        for &c in chars {
-            if c < '\u{10000}' && c != '\n' {
-                let slot = read_fonts[font_id].font_loaded.as_ref().unwrap().char_code_to_glyph_index_map[c as usize];
-                let glyph = &read_fonts[font_id].font_loaded.as_ref().unwrap().glyphs[slot];
-                let glyph_width = glyph.horizontal_metrics.advance_width * font_size_logical * props.font_scale;
-                width += glyph_width;
-            }
+        let em_size = text_style.font_size * 96.0 / 72.0;
+        let clusters = shape_text(&cx.fonts_data, text_style.font.font_id, chars);
+        let mut advances = vec![0.; chars.len()];
+        for cluster in clusters.iter() {
+            let char_advance = cluster.advance * em_size * props.font_scale / cluster.char_count as f32;
+            advances[cluster.char_index..cluster.char_index + cluster.char_count].fill(char_advance);
         }
-        width
+        advances
+    }
+
+    /// Measures the width of the text, not including newlines.
+    fn measure_width(cx: &Cx, chars: &[char], props: &TextInsProps) -> f32 {
+        // Newlines get shaped without any width.
+        crate::simd::sum(&Self::char_advances(cx, chars, props))
     }
 
     /// Adds an ellipsis to the end of the text if it is too long, or nothing at all
     /// if even the ellipsis is too long.
     fn truncate_to_ellipsis(cx: &Cx, text: &str, props: &TextInsProps, max_width: f32) -> TextChunk {
-        let text_style = &props.text_style;
-        let font_id = text_style.font.font_id;
-        let read_fonts = &cx.fonts_data.read().unwrap().fonts;
-        let font_size_logical =
-            text_style.font_size * 96.0 / (72.0 * read_fonts[font_id].font_loaded.as_ref().unwrap().units_per_em);
-
         // Actual ellipsis char "…" is not supported in our current fonts.
-        let ellipsis_slot = read_fonts[font_id].font_loaded.as_ref().unwrap().char_code_to_glyph_index_map['.' as usize];
-        let ellipsis_width = 3.0
-            * read_fonts[font_id].font_loaded.as_ref().unwrap().glyphs[ellipsis_slot].horizontal_metrics.advance_width
-            * font_size_logical
-            * props.font_scale;
+        let ellipsis_width = Self::measure_width(cx, &['.', '.', '.'], props);
 
-        let mut iter = text.chars().peekable();
+        let text_chars: Vec<char> = text.chars().collect();
+        let advances = Self::char_advances(cx, &text_chars, props);
         let mut width = 0.0;
-        let mut chars = Vec::with_capacity(text.len() + 2);
-
-        while let Some(c) = iter.next() {
-            let last = iter.peek().is_none();
-
-            if c < '\u{10000}' {
-                let slot = read_fonts[font_id].font_loaded.as_ref().unwrap().char_code_to_glyph_index_map[c as usize];
-                let glyph = &read_fonts[font_id].font_loaded.as_ref().unwrap().glyphs[slot];
-                let glyph_width = glyph.horizontal_metrics.advance_width * font_size_logical * props.font_scale;
-                // Put in an ellipsis if we'd otherwise overflow, but DON'T put an ellipsis if we're at the end
-                // already and the current glyph is less wide than the ellipsis itself.
-                if width + glyph_width >= max_width - ellipsis_width && !(last && glyph_width <= ellipsis_width) {
-                    // If there's no room for the ellipsis, return, but don't actually print it.
-                    if width + ellipsis_width <= max_width {
-                        // Actual ellipsis char "…" is not supported in our current fonts.
-                        chars.push('.');
-                        chars.push('.');
-                        chars.push('.');
-                        width += ellipsis_width;
-                    }
-                    return TextChunk { chars, width, newline: false };
+        let mut chars = Vec::with_capacity(text_chars.len() + 3);
+
+        for (index, (&c, &glyph_width)) in text_chars.iter().zip(&advances).enumerate() {
+            let last = index == text_chars.len() - 1;
+
+            // Put in an ellipsis if we'd otherwise overflow, but DON'T put an ellipsis if we're at the end
+            // already and the current glyph is less wide than the ellipsis itself.
+            if width + glyph_width >= max_width - ellipsis_width && !(last && glyph_width <= ellipsis_width) {
+                // If there's no room for the ellipsis, return, but don't actually print it.
+                if width + ellipsis_width <= max_width {
+                    // Actual ellipsis char "…" is not supported in our current fonts.
+                    chars.push('.');
+                    chars.push('.');
+                    chars.push('.');
+                    width += ellipsis_width;
                 }
-                chars.push(c);
-                width += glyph_width;
+                return TextChunk { chars, width, newline: false };
             }
+            chars.push(c);
+            width += glyph_width;
         }
         TextChunk { chars, width, newline: false }
     }
@@ -536,6 +728,7 @@ impl TextIns {
             &props.text_style,
             &cx.fonts_data,
             cx.current_dpi_factor,
+            cx.current_text_rendering().subpixel_positioning,
             props.font_scale,
             props.draw_depth,
             props.color,
@@ -567,6 +760,11 @@ impl TextIns {
         let height_factor = text_style.height_factor;
 
         let mut glyphs: Vec<TextIns> = Vec::with_capacity(text.len());
+        // Only needed for `TextSelection`.
+        let selectable = !cx.selectable_text_stack.is_empty();
+        let mut chars: Vec<char> = Vec::new();
+        // The number of characters drawn so far, so that `TextIns::char_offset` is unique within the `Area`.
+        let mut char_offset = 0;
 
         cx.begin_row(Width::Compute, Height::Compute);
         cx.begin_padding_box(props.padding);
@@ -574,21 +772,26 @@ impl TextIns {
 
         for chunk in Self::apply_wrapping(cx, text, props) {
             let height = font_size * height_factor * props.font_scale;
-            let rect = cx.add_box(LayoutSize { width: Width::Fix(chunk.width), height: Height::Fix(height) });
+            let rect = cx.add_box(LayoutSize::new(Width::Fix(chunk.width), Height::Fix(height)));
 
             if !rect.pos.x.is_nan() && !rect.pos.y.is_nan() {
                 glyphs.extend(Self::generate_2d_glyphs(
                     &props.text_style,
                     &cx.fonts_data,
                     cx.current_dpi_factor,
+                    cx.current_text_rendering().subpixel_positioning,
                     props.font_scale,
                     props.draw_depth,
                     props.color,
                     rect.pos,
-                    0,
-                    chunk.chars,
+                    char_offset,
+                    &chunk.chars,
                     |_, _, _, _| 0.0,
                 ));
+                char_offset += chunk.chars.len();
+                if selectable {
+                    chars.extend(&chunk.chars);
+                }
             }
 
             if chunk.newline {
@@ -600,7 +803,7 @@ impl TextIns {
         cx.end_padding_box();
         cx.end_row();
 
-        Self::draw_glyphs(
+        let area = Self::draw_glyphs(
             cx,
             &glyphs,
             &DrawGlyphsProps {
@@ -608,7 +811,11 @@ impl TextIns {
                 // Position anchoring is ignored when using walk
                 ..DrawGlyphsProps::default()
             },
-        )
+        );
+        if selectable {
+            cx.add_selectable_text(area, chars, font_size * height_factor * props.font_scale);
+        }
+        area
     }
 
     /// Looks up text with the behavior of a text selection mouse cursor.
@@ -661,6 +868,82 @@ impl TextIns {
     }
 }
 
+/// The size of a piece of text, as returned by [`Cx::measure_text`] and [`Cx::measure_wrapped_text`].
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct TextMetrics {
+    /// The width of the widest line.
+    pub width: f32,
+    /// The height of all lines together.
+    pub height: f32,
+    /// The number of lines, which is 0 for empty text.
+    pub line_count: usize,
+    /// The height of a single line (when it's not followed by an explicit newline).
+    pub line_height: f32,
+}
+
+/// A line of text, as returned by [`Cx::wrap_text`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct TextLine {
+    /// The text of the line, including trailing whitespace and newlines, and the ellipsis when using
+    /// [`Wrapping::Ellipsis`].
+    pub text: String,
+    /// The width of the line, including trailing whitespace.
+    pub width: f32,
+    /// Whether the line ends in an explicit newline, as opposed to being wrapped.
+    pub newline: bool,
+}
+
+impl Cx {
+    /// Measure text as it would be drawn by [`TextIns::draw_walk`] when there's enough room, so without wrapping
+    /// other than at newlines. The size includes [`TextInsProps::padding`].
+    ///
+    /// Use this to size custom components to their text before drawing it.
+    pub fn measure_text(&self, props: &TextInsProps, text: &str) -> TextMetrics {
+        self.measure_wrapped_text(props, text, f32::INFINITY)
+    }
+
+    /// Like [`Cx::measure_text`], but wrapping lines at `max_width` (not including padding), as
+    /// [`TextIns::draw_walk`] would in a box of that width. See [`Cx::wrap_text`].
+    pub fn measure_wrapped_text(&self, props: &TextInsProps, text: &str, max_width: f32) -> TextMetrics {
+        let lines = self.wrap_text(props, text, max_width);
+        let text_style = &props.text_style;
+        let line_height = text_style.font_size * text_style.height_factor * props.font_scale;
+        let newline_height = (text_style.font_size * text_style.line_spacing * props.font_scale).max(line_height);
+
+        let mut metrics = TextMetrics { line_count: lines.len(), line_height, ..TextMetrics::default() };
+        for (index, line) in lines.iter().enumerate() {
+            metrics.width = metrics.width.max(line.width);
+            metrics.height += if line.newline && index < lines.len() - 1 { newline_height } else { line_height };
+        }
+        metrics.width += props.padding.l + props.padding.r;
+        metrics.height += props.padding.t + props.padding.b;
+        metrics
+    }
+
+    /// Break text into lines that fit within `max_width`, the same way [`TextIns::draw_walk`] does: at the boundaries
+    /// given by [`TextInsProps::wrapping`] (so only at newlines for [`Wrapping::None`]), and after newlines.
+    ///
+    /// Chunks that are wider than `max_width` by themselves (like long words) get a line of their own, and
+    /// overflow it.
+    pub fn wrap_text(&self, props: &TextInsProps, text: &str, max_width: f32) -> Vec<TextLine> {
+        let mut lines: Vec<TextLine> = Vec::new();
+        let mut start_new_line = true;
+        for chunk in TextIns::apply_wrapping(self, text, props) {
+            match lines.last_mut() {
+                // Add up the widths and compare them the same way as the layout system, so we wrap at the same chunks.
+                Some(line) if !start_new_line && line.width + chunk.width <= max_width + LINE_WRAP_TOLERANCE => {
+                    line.text.extend(&chunk.chars);
+                    line.width += chunk.width;
+                    line.newline = chunk.newline;
+                }
+                _ => lines.push(TextLine { text: chunk.chars.iter().collect(), width: chunk.width, newline: chunk.newline }),
+            }
+            start_new_line = chunk.newline;
+        }
+        lines
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::*;
@@ -679,6 +962,66 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_measure_and_wrap_text() {
+        let cx = Cx::new_test();
+        let text_style = TextStyle { font_size: 10.0, ..TEXT_STYLE_MONO };
+        let props = TextInsProps { text_style, wrapping: Wrapping::Word, ..TextInsProps::default() };
+        // Roughly 8, but measure it so the line below that exactly fits `max_width` doesn't depend on rounding.
+        let glyph_width = cx.measure_text(&props, "x").width;
+
+        let metrics = cx.measure_text(
+            &props,
+            "Hello World
+foo",
+        );
+        assert_eq!(metrics.line_count, 2);
+        assert!((metrics.width - 11. * glyph_width).abs() < 0.1);
+        assert!(metrics.height > 2. * metrics.line_height - 0.1);
+        assert_eq!(cx.measure_text(&props, ""), TextMetrics { line_height: metrics.line_height, ..TextMetrics::default() });
+
+        let lines = cx.wrap_text(&props, "Hello World foo", 12. * glyph_width);
+        assert_eq!(
+            lines.iter().map(|line| (line.text.as_str(), line.newline)).collect::<Vec<_>>(),
+            vec![("Hello World ", false), ("foo", false)]
+        );
+        let wrapped = cx.measure_wrapped_text(&props, "Hello World foo", 12. * glyph_width);
+        assert_eq!(wrapped.line_count, 2);
+        assert!((wrapped.height - 2. * wrapped.line_height).abs() < 0.1);
+
+        let padded = cx.measure_text(&TextInsProps { padding: Padding::all(5.), ..props }, "Hello");
+        assert!((padded.width - (5. * glyph_width + 10.)).abs() < 0.1);
+    }
+
+    #[test]
+    fn test_subpixel_positioning() {
+        let cx = Cx::new_test();
+        let glyph_at = |subpixel_positioning, x| {
+            TextIns::generate_2d_glyphs(
+                &TEXT_STYLE_NORMAL,
+                &cx.fonts_data,
+                1.0,
+                subpixel_positioning,
+                1.0,
+                0.0,
+                COLOR_WHITE,
+                vec2(x, 0.),
+                0,
+                ['a'],
+                |_, _, _, _| 0.0,
+            )[0]
+        };
+
+        // The same glyph at different fractions of a pixel uses different atlas entries, unless positions get snapped.
+        assert_ne!(glyph_at(SubpixelPositioning::Full, 0.3).font_t1, glyph_at(SubpixelPositioning::Full, 0.6).font_t1);
+        assert_eq!(glyph_at(SubpixelPositioning::Off, 0.3).font_t1, glyph_at(SubpixelPositioning::Off, 0.6).font_t1);
+        assert_eq!(glyph_at(SubpixelPositioning::Off, 0.6).rect_pos.x - glyph_at(SubpixelPositioning::Off, 0.3).rect_pos.x, 1.0);
+        assert_eq!(
+            glyph_at(SubpixelPositioning::Horizontal, 0.3).font_t1,
+            glyph_at(SubpixelPositioning::Horizontal, 1.3).font_t1
+        );
+    }
+
     #[test]
     fn test_apply_wrapping() {
         let cx = Cx::new_test();
@@ -0,0 +1,361 @@
+//! Selecting and copying text that isn't otherwise editable, like labels and log output.
+
+use crate::*;
+
+#[derive(Clone, Copy, Default)]
+#[repr(C)]
+struct SelectionIns {
+    quad: QuadIns,
+    color: Vec4,
+}
+
+static SHADER: Shader = Shader {
+    build_geom: Some(QuadIns::build_geom),
+    code_to_concatenate: &[
+        Cx::STD_SHADER,
+        QuadIns::SHADER,
+        code_fragment!(
+            r#"
+            instance color: vec4;
+            fn pixel() -> vec4 {
+                return vec4(color.rgb * color.a, color.a);
+            }"#
+        ),
+    ],
+    ..Shader::DEFAULT
+};
+
+const DEFAULT_SELECTION_COLOR: Vec4 = vec4(0.25, 0.5, 1., 0.4);
+
+/// A run of text that was drawn using [`TextIns::draw_walk`] in between [`TextSelection::begin_draw`] and
+/// [`TextSelection::end_draw`].
+pub(crate) struct SelectableTextRun {
+    area: Area,
+    /// The characters for the glyphs in `area`, in the same order.
+    chars: Vec<char>,
+    line_height: f32,
+}
+
+impl SelectableTextRun {
+    /// The boxes that the glyphs take up in the layout (not the glyph shapes themselves), in view coordinates.
+    fn glyph_boxes(&self, cx: &Cx) -> Vec<Rect> {
+        if !self.area.is_valid(cx) {
+            return Vec::new();
+        }
+        let glyphs = self.area.get_slice::<TextIns>(cx);
+        // The first glyph of every character. Characters can have more than one glyph (e.g. combining marks), and
+        // every character has at least one, see `TextIns::generate_2d_glyphs`.
+        let mut char_glyphs: Vec<Option<&TextIns>> = vec![None; self.chars.len()];
+        for glyph in glyphs {
+            if let Some(char_glyph) = char_glyphs.get_mut(glyph.char_offset as usize) {
+                char_glyph.get_or_insert(glyph);
+            }
+        }
+        let char_glyphs: Vec<&TextIns> = char_glyphs.into_iter().flatten().collect();
+        char_glyphs
+            .iter()
+            .enumerate()
+            .map(|(index, glyph)| {
+                let width = match char_glyphs.get(index + 1) {
+                    Some(next) if next.base.y == glyph.base.y => next.base.x - glyph.base.x,
+                    _ => glyph.rect_size.x,
+                };
+                Rect { pos: glyph.base, size: vec2(width, self.line_height) }
+            })
+            .collect()
+    }
+}
+
+/// A position in between two characters: the index in [`TextSelection::runs`], and the character offset in that run.
+type TextSelectionPos = (usize, usize);
+
+/// Lets the user select text by dragging over it, and copy it to the clipboard, like they're used to in browsers.
+///
+/// All text drawn using [`TextIns::draw_walk`] in between [`TextSelection::begin_draw`] and
+/// [`TextSelection::end_draw`] becomes selectable, even across multiple calls, so you can wrap a whole panel or log
+/// view. Double-click selects a word, triple-click a whole run of text, and Ctrl+A (Cmd+A on Mac) selects
+/// everything.
+///
+/// Call [`TextSelection::handle`] after handling the components inside of it, so that e.g. buttons still get clicks.
+/// Draw the text in the same [`View`] as the [`TextSelection`] itself, since that's where the selection is drawn.
+#[derive(Default)]
+pub struct TextSelection {
+    component_id: ComponentId,
+    runs: Vec<SelectableTextRun>,
+    /// The bounding box of all [`TextSelection::runs`], in view coordinates.
+    bounds: Option<Rect>,
+    /// Where the selection started, and where the pointer is now. Not necessarily in order.
+    selection: Option<(TextSelectionPos, TextSelectionPos)>,
+    /// Whether the pointer is held down, and how many times it was tapped (so we select words or runs while dragging).
+    dragging: Option<u32>,
+    color: Option<Vec4>,
+}
+
+impl TextSelection {
+    /// Set the color of the selection highlight, which is drawn on top of the text.
+    #[must_use]
+    pub fn with_color(self, color: Vec4) -> Self {
+        Self { color: Some(color), ..self }
+    }
+
+    /// Start collecting the text that is drawn, to make it selectable.
+    pub fn begin_draw(&mut self, cx: &mut Cx) {
+        cx.selectable_text_stack.push(Vec::new());
+    }
+
+    /// Stop collecting text, and draw the selection.
+    pub fn end_draw(&mut self, cx: &mut Cx) {
+        self.runs = cx.selectable_text_stack.pop().expect("TextSelection::end_draw called without begin_draw");
+
+        let mut bounds: Option<Rect> = None;
+        let mut highlights: Vec<SelectionIns> = Vec::new();
+        let selection = self.get_ordered_selection();
+        let color = self.color.unwrap_or(DEFAULT_SELECTION_COLOR);
+        for (run_index, run) in self.runs.iter().enumerate() {
+            for (char_index, glyph_box) in run.glyph_boxes(cx).into_iter().enumerate() {
+                bounds = Some(match bounds {
+                    Some(bounds) => {
+                        let min = vec2(bounds.pos.x.min(glyph_box.pos.x), bounds.pos.y.min(glyph_box.pos.y));
+                        let max = vec2(
+                            (bounds.pos.x + bounds.size.x).max(glyph_box.pos.x + glyph_box.size.x),
+                            (bounds.pos.y + bounds.size.y).max(glyph_box.pos.y + glyph_box.size.y),
+                        );
+                        Rect { pos: min, size: max - min }
+                    }
+                    None => glyph_box,
+                });
+
+                if let Some((start, end)) = selection {
+                    if (run_index, char_index) < start || (run_index, char_index) >= end {
+                        continue;
+                    }
+                    // Merge adjacent characters on the same line into a single rectangle.
+                    match highlights.last_mut() {
+                        Some(last)
+                            if last.quad.rect_pos.y == glyph_box.pos.y
+                                && (last.quad.rect_pos.x + last.quad.rect_size.x - glyph_box.pos.x).abs() < 0.01 =>
+                        {
+                            last.quad.rect_size.x += glyph_box.size.x;
+                        }
+                        _ => highlights.push(SelectionIns { quad: QuadIns::from_rect(glyph_box), color }),
+                    }
+                }
+            }
+        }
+        self.bounds = bounds;
+        if !highlights.is_empty() {
+            cx.add_instances(&SHADER, &highlights);
+        }
+    }
+
+    /// The current selection, with the start before the end. [`None`] if nothing is selected.
+    fn get_ordered_selection(&self) -> Option<(TextSelectionPos, TextSelectionPos)> {
+        let (anchor, head) = self.selection?;
+        if anchor == head {
+            return None;
+        }
+        Some((anchor.min(head), anchor.max(head)))
+    }
+
+    /// The position in between characters that is closest to `abs`.
+    fn get_pos_at(&self, cx: &Cx, abs: Vec2) -> Option<TextSelectionPos> {
+        let mut closest = None;
+        let mut closest_distance = f32::MAX;
+        for (run_index, run) in self.runs.iter().enumerate() {
+            if !run.area.is_valid(cx) {
+                continue;
+            }
+            let pos = abs + run.area.get_scroll_pos(cx);
+            for (char_index, glyph_box) in run.glyph_boxes(cx).into_iter().enumerate() {
+                let distance_to_range = |value: f32, min: f32, max: f32| (min - value).max(value - max).max(0.);
+                let dx = distance_to_range(pos.x, glyph_box.pos.x, glyph_box.pos.x + glyph_box.size.x);
+                let dy = distance_to_range(pos.y, glyph_box.pos.y, glyph_box.pos.y + glyph_box.size.y);
+                // Being on the right line is much more important than being close horizontally.
+                let distance = dy * 1000. + dx;
+                if distance < closest_distance {
+                    closest_distance = distance;
+                    let after = pos.x > glyph_box.pos.x + glyph_box.size.x * 0.5;
+                    closest = Some((run_index, char_index + after as usize));
+                }
+            }
+        }
+        closest
+    }
+
+    /// Expand the selection to whole words (double-click) or runs of text (triple-click), depending on `tap_count`.
+    fn expand(
+        &self,
+        (anchor, head): (TextSelectionPos, TextSelectionPos),
+        tap_count: u32,
+    ) -> (TextSelectionPos, TextSelectionPos) {
+        let (start, end) = (anchor.min(head), anchor.max(head));
+        let (start, end) = match tap_count {
+            0 | 1 => return (anchor, head),
+            2 => {
+                let is_word_char =
+                    |pos: TextSelectionPos| self.runs[pos.0].chars.get(pos.1).map_or(false, |c| c.is_alphanumeric());
+                let mut start = start;
+                while start.1 > 0 && is_word_char((start.0, start.1 - 1)) {
+                    start.1 -= 1;
+                }
+                let mut end = end;
+                while is_word_char(end) {
+                    end.1 += 1;
+                }
+                (start, end)
+            }
+            _ => ((start.0, 0), (end.0, self.runs[end.0].chars.len())),
+        };
+        if head < anchor {
+            (end, start)
+        } else {
+            (start, end)
+        }
+    }
+
+    /// The text that is currently selected. Runs on separate lines are separated by newlines, and runs on the same
+    /// line by spaces.
+    pub fn get_selected_text(&self, cx: &Cx) -> String {
+        let mut text = String::new();
+        let ((start_run, start_offset), (end_run, end_offset)) = match self.get_ordered_selection() {
+            Some(selection) => selection,
+            None => return text,
+        };
+        let mut last_line_y = None;
+        for run_index in start_run..=end_run.min(self.runs.len().saturating_sub(1)) {
+            let run = &self.runs[run_index];
+            let start = if run_index == start_run { start_offset } else { 0 };
+            let end = if run_index == end_run { end_offset } else { run.chars.len() };
+            let glyph_boxes = run.glyph_boxes(cx);
+            if let (Some(last_line_y), Some(first)) = (last_line_y, glyph_boxes.first()) {
+                text.push(if first.pos.y == last_line_y { ' ' } else { '\n' });
+            }
+            text.extend(&run.chars[start.min(run.chars.len())..end.min(run.chars.len())]);
+            last_line_y = glyph_boxes.last().map(|glyph_box| glyph_box.pos.y);
+        }
+        text
+    }
+
+    /// Select all text.
+    pub fn select_all(&mut self, cx: &mut Cx) {
+        if let Some(last) = self.runs.last() {
+            self.selection = Some(((0, 0), (self.runs.len() - 1, last.chars.len())));
+            cx.request_draw();
+        }
+    }
+
+    /// Clear the selection.
+    pub fn clear(&mut self, cx: &mut Cx) {
+        if self.selection.is_some() {
+            self.selection = None;
+            cx.request_draw();
+        }
+    }
+
+    pub fn handle(&mut self, cx: &mut Cx, event: &mut Event) {
+        let rect = match (self.bounds, self.runs.first()) {
+            (Some(bounds), Some(run)) if run.area.is_valid(cx) => {
+                Some(Rect { pos: bounds.pos - run.area.get_scroll_pos(cx), size: bounds.size })
+            }
+            _ => None,
+        };
+        match event.hits_pointer(cx, self.component_id, rect) {
+            Event::PointerDown(pe) => {
+                cx.set_key_focus(Some(self.component_id));
+                cx.set_down_mouse_cursor(MouseCursor::Text);
+                self.selection = match (self.selection, self.get_pos_at(cx, pe.abs)) {
+                    // Shift-click extends the existing selection.
+                    (Some((anchor, _)), Some(pos)) if pe.modifiers.shift => Some((anchor, pos)),
+                    (_, pos) => pos.map(|pos| self.expand((pos, pos), pe.tap_count)),
+                };
+                self.dragging = Some(pe.tap_count);
+                cx.request_draw();
+            }
+            Event::PointerMove(pe) => {
+                if let (Some(tap_count), Some((anchor, _)), Some(head)) =
+                    (self.dragging, self.selection, self.get_pos_at(cx, pe.abs))
+                {
+                    self.selection = Some(self.expand((anchor, head), tap_count));
+                    cx.request_draw();
+                }
+            }
+            Event::PointerUp(_) => {
+                self.dragging = None;
+            }
+            Event::PointerHover(_) => {
+                cx.set_hover_mouse_cursor(MouseCursor::Text);
+            }
+            _ => (),
+        }
+
+        match event.hits_keyboard(cx, self.component_id) {
+            Event::KeyDown(ke) if ke.key_code == KeyCode::KeyA && (ke.modifiers.control || ke.modifiers.logo) => {
+                self.select_all(cx);
+            }
+            Event::KeyFocusLost(_) => {
+                self.clear(cx);
+            }
+            Event::TextCopy => {
+                let text = self.get_selected_text(cx);
+                if !text.is_empty() {
+                    cx.copy_text_to_clipboard(&text);
+                }
+            }
+            _ => (),
+        }
+    }
+}
+
+impl Cx {
+    /// Make text drawn by [`TextIns::draw_walk`] selectable, if we're in between [`TextSelection::begin_draw`] and
+    /// [`TextSelection::end_draw`].
+    pub(crate) fn add_selectable_text(&mut self, area: Area, chars: Vec<char>, line_height: f32) {
+        if let Some(runs) = self.selectable_text_stack.last_mut() {
+            runs.push(SelectableTextRun { area, chars, line_height });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    #[test]
+    fn test_drag_and_copy() {
+        let mut selection = TextSelection::default();
+        let mut test_cx = TestCx::new(vec2(400., 300.));
+        let draw = |test_cx: &mut TestCx, selection: &mut TextSelection| {
+            test_cx.draw(|cx| {
+                selection.begin_draw(cx);
+                cx.begin_column(Width::Fill, Height::Compute);
+                TextIns::draw_walk(cx, "Hello world", &TextInsProps::DEFAULT);
+                TextIns::draw_walk(cx, "Second line", &TextInsProps::DEFAULT);
+                cx.end_column();
+                selection.end_draw(cx);
+            })
+        };
+        draw(&mut test_cx, &mut selection);
+
+        test_cx.pointer_down(vec2(1., 5.), |cx, event| selection.handle(cx, event));
+        test_cx.pointer_move(vec2(390., 5.), |cx, event| selection.handle(cx, event));
+        assert_eq!(selection.get_selected_text(&test_cx.cx), "Hello world");
+
+        test_cx.pointer_move(vec2(390., 250.), |cx, event| selection.handle(cx, event));
+        test_cx.pointer_up(vec2(390., 250.), |cx, event| selection.handle(cx, event));
+        assert_eq!(selection.get_selected_text(&test_cx.cx), "Hello world\nSecond line");
+
+        // Selections stay after drawing again.
+        draw(&mut test_cx, &mut selection);
+        assert_eq!(selection.get_selected_text(&test_cx.cx), "Hello world\nSecond line");
+
+        // Double-click selects a word.
+        let pointer_down = PointerDownEvent { abs: vec2(1., 5.), tap_count: 2, ..PointerDownEvent::default() };
+        test_cx.dispatch(Event::PointerDown(pointer_down), |cx, event| selection.handle(cx, event));
+        test_cx.pointer_up(vec2(1., 5.), |cx, event| selection.handle(cx, event));
+        assert_eq!(selection.get_selected_text(&test_cx.cx), "Hello");
+
+        // Clicking elsewhere clears the selection.
+        test_cx.click(vec2(390., 290.), |cx, event| selection.handle(cx, event));
+        assert_eq!(selection.get_selected_text(&test_cx.cx), "");
+    }
+}
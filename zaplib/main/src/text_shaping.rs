@@ -0,0 +1,210 @@
+//! Turning text into positioned glyphs using [rustybuzz](https://github.com/RazrFalcon/rustybuzz) (a port of
+//! HarfBuzz), so that ligatures, combining marks, kerning, and scripts like Arabic and Devanagari come out right,
+//! instead of just putting the glyphs of the characters next to each other.
+//!
+//! Shaping is relatively slow, and we draw the same runs of text over and over, so results are cached in
+//! [`CxFontsData::shaping_cache`], keyed by font and text.
+//!
+//! Mixed left-to-right and right-to-left text is not reordered (there is no bidi algorithm); every run of text is
+//! shaped in the direction of its script.
+
+use std::collections::HashMap;
+use std::ops::Range;
+use std::sync::{Arc, RwLock};
+
+use crate::*;
+
+/// Clear [`CxFontsData::shaping_cache`] when it gets this big, so it doesn't grow forever.
+const MAX_SHAPING_CACHE_ENTRIES: usize = 10_000;
+
+/// Shaping results per [`Font::font_id`] and text.
+pub(crate) type ShapingCache = HashMap<(usize, String), Arc<Vec<ShapedCluster>>>;
+
+/// A glyph within a [`ShapedCluster`].
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct ShapedGlyph {
+    /// Index in [`CxFontsData::fonts`], which can be one of the fallbacks of the font that was used for shaping.
+    pub(crate) font_id: usize,
+    pub(crate) glyph_id: usize,
+    /// Where to draw the glyph relative to the start of the cluster, in ems. Positive y is up.
+    pub(crate) offset: Vec2,
+}
+
+/// One or more characters that get drawn using one or more glyphs, and that can't be split up, like a ligature
+/// ("fi") or a letter with combining marks.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct ShapedCluster {
+    /// The index of the first character of the cluster in the text that was shaped.
+    pub(crate) char_index: usize,
+    pub(crate) char_count: usize,
+    /// How far to move after drawing the cluster, in ems.
+    pub(crate) advance: f32,
+    /// Empty for control characters such as newlines and tabs.
+    pub(crate) glyphs: Vec<ShapedGlyph>,
+}
+
+/// Shape `chars` using `font_id` and its fallbacks (see [`Cx::set_font_fallbacks`]), using the cache if possible.
+/// Returns clusters in the order in which they should be drawn, from left to right.
+///
+/// Don't hold a lock on `fonts_data` when calling this, since this might need to write to the cache.
+pub(crate) fn shape_text(fonts_data: &RwLock<CxFontsData>, font_id: usize, chars: &[char]) -> Arc<Vec<ShapedCluster>> {
+    let key = (font_id, chars.iter().collect::<String>());
+    let read_fonts_data = fonts_data.read().unwrap();
+    if let Some(clusters) = read_fonts_data.shaping_cache.get(&key) {
+        return Arc::clone(clusters);
+    }
+    let clusters = Arc::new(read_fonts_data.shape_uncached(font_id, chars));
+    drop(read_fonts_data);
+
+    let mut write_fonts_data = fonts_data.write().unwrap();
+    if write_fonts_data.shaping_cache.len() >= MAX_SHAPING_CACHE_ENTRIES {
+        write_fonts_data.shaping_cache.clear();
+    }
+    write_fonts_data.shaping_cache.insert(key, Arc::clone(&clusters));
+    clusters
+}
+
+impl CxFontsData {
+    fn shape_uncached(&self, font_id: usize, chars: &[char]) -> Vec<ShapedCluster> {
+        let mut clusters = Vec::new();
+        // Shaping happens per font, so split the text into runs of characters that use the same font.
+        let mut run_start = 0;
+        let mut run_font_id = None;
+        for (index, &c) in chars.iter().enumerate() {
+            if c.is_control() {
+                if let Some(run_font_id) = run_font_id {
+                    self.shape_run(run_font_id, chars, run_start..index, &mut clusters);
+                }
+                clusters.push(ShapedCluster { char_index: index, char_count: 1, advance: 0., glyphs: vec![] });
+                run_start = index + 1;
+                run_font_id = None;
+                continue;
+            }
+            let (char_font_id, _) = self.get_glyph(font_id, c);
+            match run_font_id {
+                // Keep spaces in the current run if its font has them, so e.g. CJK text with spaces doesn't get
+                // split up.
+                Some(current_font_id) if c.is_whitespace() && self.font_has_glyph(current_font_id, c) => continue,
+                Some(current_font_id) if current_font_id != char_font_id => {
+                    self.shape_run(current_font_id, chars, run_start..index, &mut clusters);
+                    run_start = index;
+                }
+                _ => (),
+            }
+            run_font_id = Some(char_font_id);
+        }
+        if let Some(run_font_id) = run_font_id {
+            self.shape_run(run_font_id, chars, run_start..chars.len(), &mut clusters);
+        }
+        clusters
+    }
+
+    fn font_has_glyph(&self, font_id: usize, c: char) -> bool {
+        self.fonts[font_id].font_loaded.as_ref().map_or(false, |font| font.has_glyph(c))
+    }
+
+    /// Shape `chars[range]` using just `font_id`, and add the resulting clusters to `clusters`.
+    fn shape_run(&self, font_id: usize, chars: &[char], range: Range<usize>, clusters: &mut Vec<ShapedCluster>) {
+        let cxfont = &self.fonts[font_id];
+        let font = match &cxfont.font_loaded {
+            Some(font) => font,
+            None => return,
+        };
+        let units_per_em = font.units_per_em;
+
+        let face = match rustybuzz::Face::from_slice(&cxfont.font_bytes, cxfont.font_index) {
+            Some(face) => face,
+            None => {
+                // We don't have the font file (or rustybuzz can't read it), so just put the glyphs next to each other.
+                for (index, &c) in chars[range.clone()].iter().enumerate() {
+                    let glyph_id = font.glyph_index(c);
+                    clusters.push(ShapedCluster {
+                        char_index: range.start + index,
+                        char_count: 1,
+                        advance: font.glyphs[glyph_id].horizontal_metrics.advance_width / units_per_em,
+                        glyphs: vec![ShapedGlyph { font_id, glyph_id, offset: Vec2::default() }],
+                    });
+                }
+                return;
+            }
+        };
+
+        let text: String = chars[range.clone()].iter().collect();
+        let mut buffer = rustybuzz::UnicodeBuffer::new();
+        buffer.push_str(&text);
+        buffer.guess_segment_properties();
+        let glyph_buffer = rustybuzz::shape(&face, &[], buffer);
+
+        // rustybuzz gives clusters as byte offsets in `text`, so map those to character indices.
+        let mut byte_to_char_index = vec![0; text.len()];
+        for (char_index, (byte_index, _)) in text.char_indices().enumerate() {
+            byte_to_char_index[byte_index] = char_index;
+        }
+        let mut cluster_starts: Vec<usize> =
+            glyph_buffer.glyph_infos().iter().map(|info| byte_to_char_index[info.cluster as usize]).collect();
+        cluster_starts.sort_unstable();
+        cluster_starts.dedup();
+
+        let first_cluster = clusters.len();
+        for (info, position) in glyph_buffer.glyph_infos().iter().zip(glyph_buffer.glyph_positions()) {
+            let char_index = range.start + byte_to_char_index[info.cluster as usize];
+            // Glyphs of the same cluster are always next to each other.
+            if clusters.len() == first_cluster || clusters.last().unwrap().char_index != char_index {
+                let next_start =
+                    cluster_starts.iter().map(|start| range.start + start).find(|&start| start > char_index).unwrap_or(range.end);
+                clusters.push(ShapedCluster { char_index, char_count: next_start - char_index, advance: 0., glyphs: vec![] });
+            }
+            let cluster = clusters.last_mut().unwrap();
+            let glyph_id = info.glyph_id as usize;
+            cluster.glyphs.push(ShapedGlyph {
+                font_id,
+                glyph_id: if glyph_id < font.glyphs.len() { glyph_id } else { 0 },
+                offset: vec2(cluster.advance + position.x_offset as f32 / units_per_em, position.y_offset as f32 / units_per_em),
+            });
+            cluster.advance += position.x_advance as f32 / units_per_em;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn shape(cx: &Cx, text: &str) -> Arc<Vec<ShapedCluster>> {
+        shape_text(&cx.fonts_data, TEXT_STYLE_NORMAL.font.font_id, &text.chars().collect::<Vec<char>>())
+    }
+
+    #[test]
+    fn test_clusters_cover_all_chars() {
+        let cx = Cx::new_test();
+        let text = "Hello,\tworld!\n";
+        let clusters = shape(&cx, text);
+
+        let mut char_index = 0;
+        for cluster in clusters.iter() {
+            assert_eq!(cluster.char_index, char_index);
+            char_index += cluster.char_count;
+        }
+        assert_eq!(char_index, text.chars().count());
+
+        let newline = clusters.last().unwrap();
+        assert_eq!(newline.advance, 0.);
+        assert!(newline.glyphs.is_empty());
+    }
+
+    #[test]
+    fn test_kerning() {
+        let cx = Cx::new_test();
+        let separate = shape(&cx, "A")[0].advance + shape(&cx, "V")[0].advance;
+        let together: f32 = shape(&cx, "AV").iter().map(|cluster| cluster.advance).sum();
+        assert!(together < separate);
+    }
+
+    #[test]
+    fn test_cache() {
+        let cx = Cx::new_test();
+        let first = shape(&cx, "cached");
+        let second = shape(&cx, "cached");
+        assert!(Arc::ptr_eq(&first, &second));
+    }
+}
@@ -66,6 +66,28 @@ impl Texture {
         }
     }
 
+    /// Get a cube texture, with 6 faces of `size` by `size` pixels, for `textureCube` shader properties. Write the
+    /// faces using [`TextureHandle::write_cube_map`].
+    pub fn get_cube_with_size(&mut self, cx: &mut Cx, size: usize) -> TextureHandle {
+        if let Some(handle) = self.handle {
+            handle
+        } else {
+            let handle = TextureHandle {
+                texture_id: {
+                    let cx_texture = CxTexture {
+                        desc: TextureDesc { width: Some(size), height: Some(size), cube: true, ..Default::default() },
+                        image_u32: vec![0; size * size * 6],
+                        ..CxTexture::default()
+                    };
+                    cx.textures.push(cx_texture);
+                    (cx.textures.len() - 1) as u32
+                },
+            };
+            self.handle = Some(handle);
+            handle
+        }
+    }
+
     pub fn unwrap_texture_handle(&self) -> TextureHandle {
         self.handle.unwrap()
     }
@@ -83,10 +105,31 @@ impl TextureHandle {
         cx_texture.update_image = true;
         &mut cx_texture.image_u32
     }
+
+    /// Change the dimensions of a texture created using [`Texture::get_with_dimensions`], clearing its image.
+    pub fn set_dimensions(&self, cx: &mut Cx, width: usize, height: usize) {
+        let cx_texture = cx.textures.get_mut(self.texture_id as usize).unwrap();
+        if cx_texture.desc.width != Some(width) || cx_texture.desc.height != Some(height) {
+            cx_texture.desc.width = Some(width);
+            cx_texture.desc.height = Some(height);
+            cx_texture.image_u32 = vec![0; width * height];
+            cx_texture.update_image = true;
+        }
+    }
+
+    /// Replace the faces of a cube texture created using [`Texture::get_cube_with_size`], changing its size if needed.
+    pub fn write_cube_map(&self, cx: &mut Cx, cube_map: &CubeMapImage) {
+        let cx_texture = cx.textures.get_mut(self.texture_id as usize).unwrap();
+        assert!(cx_texture.desc.cube, "write_cube_map needs a texture created using Texture::get_cube_with_size");
+        cx_texture.desc.width = Some(cube_map.size);
+        cx_texture.desc.height = Some(cube_map.size);
+        cx_texture.image_u32.clear();
+        cx_texture.image_u32.extend_from_slice(&cube_map.pixels);
+        cx_texture.update_image = true;
+    }
 }
 
 // TODO(Paras): Standardize and test all platforms on RGBA.
-// TODO(Paras): Make image_u32 updating work on Linux.
 #[derive(Copy, Clone, PartialEq)]
 pub(crate) enum TextureFormat {
     ImageRGBA,
@@ -99,11 +142,14 @@ pub(crate) struct TextureDesc {
     pub(crate) width: Option<usize>,
     pub(crate) height: Option<usize>,
     pub(crate) multisample: Option<usize>,
+    /// Whether this is a cube texture, with 6 faces of `width` by `height` pixels stored one after another in the
+    /// order of [`CubeFace::ALL`].
+    pub(crate) cube: bool,
 }
 
 impl Default for TextureDesc {
     fn default() -> Self {
-        TextureDesc { format: TextureFormat::ImageRGBA, width: None, height: None, multisample: None }
+        TextureDesc { format: TextureFormat::ImageRGBA, width: None, height: None, multisample: None, cube: false }
     }
 }
 
@@ -117,3 +163,177 @@ pub(crate) struct CxTexture {
     #[cfg_attr(target_arch = "wasm32", allow(dead_code))]
     pub(crate) platform: CxPlatformTexture,
 }
+
+/// A face of a cube texture. Faces follow the usual cube map conventions of graphics APIs, so e.g. skybox images made
+/// for OpenGL work as they are.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CubeFace {
+    PositiveX,
+    NegativeX,
+    PositiveY,
+    NegativeY,
+    PositiveZ,
+    NegativeZ,
+}
+
+impl CubeFace {
+    /// All faces, in the order that they're stored in.
+    pub const ALL: [CubeFace; 6] = [
+        CubeFace::PositiveX,
+        CubeFace::NegativeX,
+        CubeFace::PositiveY,
+        CubeFace::NegativeY,
+        CubeFace::PositiveZ,
+        CubeFace::NegativeZ,
+    ];
+
+    /// The direction (not normalized) that `sample_cube` needs to get the point at `u`, `v` of this face, where both
+    /// go from 0 to 1, and `v` goes down the rows of the image.
+    pub fn direction(self, u: f32, v: f32) -> Vec3 {
+        let (a, b) = (u * 2. - 1., v * 2. - 1.);
+        match self {
+            CubeFace::PositiveX => vec3(1., -b, -a),
+            CubeFace::NegativeX => vec3(-1., -b, a),
+            CubeFace::PositiveY => vec3(a, 1., b),
+            CubeFace::NegativeY => vec3(a, -1., -b),
+            CubeFace::PositiveZ => vec3(a, -b, 1.),
+            CubeFace::NegativeZ => vec3(-a, -b, -1.),
+        }
+    }
+}
+
+/// The pixels of the 6 faces of a cube texture, e.g. for skyboxes and reflections. See
+/// [`Texture::get_cube_with_size`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct CubeMapImage {
+    size: usize,
+    pixels: Vec<u32>,
+}
+
+impl CubeMapImage {
+    /// Create a cube map from 6 faces of `size` by `size` RGBA pixels (see [`u32::from_le_bytes`]), from top to
+    /// bottom, in the order of [`CubeFace::ALL`].
+    pub fn from_faces(size: usize, faces: [&[u32]; 6]) -> Self {
+        let mut pixels = Vec::with_capacity(size * size * 6);
+        for face in faces {
+            assert_eq!(face.len(), size * size, "Expected every face to have size * size pixels");
+            pixels.extend_from_slice(face);
+        }
+        Self { size, pixels }
+    }
+
+    /// Create a cube map from 6 square PNG images of the same size, in the order of [`CubeFace::ALL`]. Returns
+    /// [`None`] if an image can't be decoded, or if they're not square or not all the same size.
+    pub fn from_png_faces(faces: [&[u8]; 6]) -> Option<Self> {
+        let mut size = None;
+        let mut pixels = vec![];
+        for bytes in faces {
+            let png = crate::png::decode_png(bytes)?;
+            if png.width != png.height || size.map_or(false, |size| size != png.width) {
+                return None;
+            }
+            size = Some(png.width);
+            pixels.extend(png.pixels.iter().map(|&pixel| u32::from_le_bytes(pixel)));
+        }
+        Some(Self { size: size.unwrap(), pixels })
+    }
+
+    /// Create a cube map with faces of `size` by `size` pixels from an equirectangular image (also known as a
+    /// latitude/longitude image), as often used for panoramas and HDRI environments. The center of the image ends up
+    /// in the -Z direction, and the top in the +Y direction.
+    pub fn from_equirectangular(width: usize, height: usize, image: &[u32], size: usize) -> Self {
+        assert_eq!(image.len(), width * height, "Expected width * height pixels");
+        let mut pixels = Vec::with_capacity(size * size * 6);
+        for face in CubeFace::ALL {
+            for y in 0..size {
+                for x in 0..size {
+                    let direction = face.direction((x as f32 + 0.5) / size as f32, (y as f32 + 0.5) / size as f32);
+                    let direction = direction.normalize();
+                    let u = 0.5 + direction.x.atan2(-direction.z) / (2. * std::f32::consts::PI);
+                    let v = 0.5 - direction.y.clamp(-1., 1.).asin() / std::f32::consts::PI;
+                    pixels.push(sample_bilinear(width, height, image, u * width as f32 - 0.5, v * height as f32 - 0.5));
+                }
+            }
+        }
+        Self { size, pixels }
+    }
+
+    /// Like [`CubeMapImage::from_equirectangular`], but for a PNG image. Returns [`None`] if it can't be decoded.
+    pub fn from_equirectangular_png(bytes: &[u8], size: usize) -> Option<Self> {
+        let png = crate::png::decode_png(bytes)?;
+        let image: Vec<u32> = png.pixels.iter().map(|&pixel| u32::from_le_bytes(pixel)).collect();
+        (!image.is_empty()).then(|| Self::from_equirectangular(png.width, png.height, &image, size))
+    }
+
+    /// The width and height of every face, in pixels.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// The pixels of `face`.
+    pub fn face(&self, face: CubeFace) -> &[u32] {
+        let face_len = self.size * self.size;
+        let index = CubeFace::ALL.iter().position(|&other| other == face).unwrap();
+        &self.pixels[index * face_len..(index + 1) * face_len]
+    }
+}
+
+/// Sample `image` at pixel coordinates `x`, `y` (where pixel centers are at whole numbers), wrapping around
+/// horizontally and clamping vertically, like an equirectangular image.
+fn sample_bilinear(width: usize, height: usize, image: &[u32], x: f32, y: f32) -> u32 {
+    let (x0, y0) = (x.floor(), y.floor());
+    let (fx, fy) = (x - x0, y - y0);
+    let column = |x: f32| (x as isize).rem_euclid(width as isize) as usize;
+    let row = |y: f32| (y.max(0.) as usize).min(height - 1);
+    let pixel = |x: f32, y: f32| image[row(y) * width + column(x)].to_le_bytes();
+    let (top_left, top_right) = (pixel(x0, y0), pixel(x0 + 1., y0));
+    let (bottom_left, bottom_right) = (pixel(x0, y0 + 1.), pixel(x0 + 1., y0 + 1.));
+    let mut result = [0; 4];
+    for channel in 0..4 {
+        let top = top_left[channel] as f32 * (1. - fx) + top_right[channel] as f32 * fx;
+        let bottom = bottom_left[channel] as f32 * (1. - fx) + bottom_right[channel] as f32 * fx;
+        result[channel] = (top * (1. - fy) + bottom * fy).round() as u8;
+    }
+    u32::from_le_bytes(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cube_face_direction() {
+        // The center of every face points along its axis.
+        assert_eq!(CubeFace::PositiveX.direction(0.5, 0.5), vec3(1., 0., 0.));
+        assert_eq!(CubeFace::NegativeY.direction(0.5, 0.5), vec3(0., -1., 0.));
+        assert_eq!(CubeFace::NegativeZ.direction(0.5, 0.5), vec3(0., 0., -1.));
+        // The top of the side faces points up, and the top of +Y points towards -Z.
+        assert_eq!(CubeFace::PositiveZ.direction(0.5, 0.), vec3(0., 1., 1.));
+        assert_eq!(CubeFace::PositiveY.direction(0.5, 0.), vec3(0., 1., -1.));
+    }
+
+    #[test]
+    fn test_from_equirectangular() {
+        // Left half red and right half blue, with a green top row and a white bottom row.
+        let (red, green, blue, white) = (0xff0000ff, 0xff00ff00, 0xffff0000, 0xffffffff);
+        let (width, height) = (8, 4);
+        let image: Vec<u32> = (0..width * height)
+            .map(|index| match (index % width, index / width) {
+                (_, 0) => green,
+                (_, 3) => white,
+                (x, _) if x < width / 2 => red,
+                _ => blue,
+            })
+            .collect();
+        let cube_map = CubeMapImage::from_equirectangular(width, height, &image, 4);
+        let center = |face| cube_map.face(face)[4 * 2 + 2];
+        // The center of the image is at -Z, so -X is in the left half and +X in the right half.
+        assert_eq!(center(CubeFace::NegativeX), red);
+        assert_eq!(center(CubeFace::PositiveX), blue);
+        assert_eq!(center(CubeFace::PositiveY), green);
+        assert_eq!(center(CubeFace::NegativeY), white);
+
+        let faces: [&[u32]; 6] = [&[1, 2, 3, 4], &[5; 4], &[6; 4], &[7; 4], &[8; 4], &[9; 4]];
+        assert_eq!(CubeMapImage::from_faces(2, faces).face(CubeFace::NegativeX), &[5; 4]);
+    }
+}
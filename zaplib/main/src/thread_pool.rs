@@ -0,0 +1,206 @@
+//! A pool of worker threads (web workers in WebAssembly), for spreading CPU-heavy work over all cores.
+//!
+//! Built on top of [`universal_thread::spawn`], so this works the same natively and in WebAssembly.
+//! Each worker has its own queue of jobs, and steals from the other queues when it runs out of work.
+
+use crate::universal_thread;
+use std::collections::VecDeque;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+
+type Job = Box<dyn FnOnce() + Send + 'static>;
+
+/// State that is shared between the [`ThreadPool`] and its workers.
+struct Shared {
+    /// One queue per worker. Workers take from the front of their own queue, and steal from the back
+    /// of other queues.
+    queues: Vec<Mutex<VecDeque<Job>>>,
+    /// Number of jobs that are in a queue and haven't been taken yet. Workers sleep while this is 0.
+    pending: Mutex<usize>,
+    condvar: Condvar,
+    /// Which queue to push the next job onto.
+    next_queue: AtomicUsize,
+    shutdown: AtomicBool,
+}
+
+impl Shared {
+    fn push(&self, job: Job) {
+        let queue_index = self.next_queue.fetch_add(1, Ordering::Relaxed) % self.queues.len();
+        // Increment `pending` first, so that it never drops below 0 when the job gets taken right away.
+        *self.pending.lock().unwrap() += 1;
+        self.queues[queue_index].lock().unwrap().push_back(job);
+        self.condvar.notify_one();
+    }
+
+    /// Take a job from the queue at `queue_index`, or otherwise steal one from any other queue.
+    fn take(&self, queue_index: usize) -> Option<Job> {
+        let len = self.queues.len();
+        // Take our own job first, and release that lock before trying the other queues.
+        let own_job = self.queues[queue_index % len].lock().unwrap().pop_front();
+        let job = own_job
+            .or_else(|| (1..len).find_map(|offset| self.queues[(queue_index + offset) % len].lock().unwrap().pop_back()))?;
+        *self.pending.lock().unwrap() -= 1;
+        Some(job)
+    }
+
+    fn run_worker(&self, queue_index: usize) {
+        loop {
+            if let Some(job) = self.take(queue_index) {
+                job();
+                continue;
+            }
+            let mut pending = self.pending.lock().unwrap();
+            while *pending == 0 && !self.shutdown.load(Ordering::Acquire) {
+                pending = self.condvar.wait(pending).unwrap();
+            }
+            if self.shutdown.load(Ordering::Acquire) {
+                return;
+            }
+        }
+    }
+}
+
+/// A fixed set of worker threads that run jobs, with work-stealing between them.
+///
+/// Create one when your app starts, and keep it around; spawning workers is relatively expensive,
+/// especially in WebAssembly. When the [`ThreadPool`] is dropped, its workers finish the jobs they are
+/// currently running and then exit.
+///
+/// ```
+/// let pool = ThreadPool::new(ThreadPool::default_num_threads());
+/// // Compute the minimum and maximum of each chunk of 10000 values, using all cores.
+/// let min_max: Vec<(f32, f32)> = pool.par_chunks(&values, 10000, |chunk| {
+///     chunk.iter().fold((f32::MAX, f32::MIN), |(min, max), &v| (min.min(v), max.max(v)))
+/// });
+/// ```
+pub struct ThreadPool {
+    shared: Arc<Shared>,
+}
+
+impl ThreadPool {
+    /// Create a new [`ThreadPool`] with `num_threads` workers. With 0 workers all the work happens
+    /// on the thread that calls [`ThreadPool::par_chunks`], and [`ThreadPool::spawn`] never runs.
+    pub fn new(num_threads: usize) -> Self {
+        let shared = Arc::new(Shared {
+            queues: (0..num_threads.max(1)).map(|_| Mutex::new(VecDeque::new())).collect(),
+            pending: Mutex::new(0),
+            condvar: Condvar::new(),
+            next_queue: AtomicUsize::new(0),
+            shutdown: AtomicBool::new(false),
+        });
+        for queue_index in 0..num_threads {
+            let shared = Arc::clone(&shared);
+            universal_thread::spawn(move || shared.run_worker(queue_index));
+        }
+        Self { shared }
+    }
+
+    /// A reasonable number of workers for this machine: the number of available cores, or 4 if we
+    /// can't tell (which is currently always the case in WebAssembly).
+    pub fn default_num_threads() -> usize {
+        std::thread::available_parallelism().map(|n| n.get()).unwrap_or(4)
+    }
+
+    /// Run `f` on one of the workers. Use e.g. [`Cx::post_user_message`](crate::Cx::post_user_message)
+    /// to send back the result. A panic inside `f` doesn't take down the worker.
+    pub fn spawn(&self, f: impl FnOnce() + Send + 'static) {
+        self.shared.push(Box::new(move || {
+            let _ = catch_unwind(AssertUnwindSafe(f));
+        }));
+    }
+
+    /// Split `data` into chunks of `chunk_size` (the last one may be shorter), run `f` on each chunk in
+    /// parallel, and return the results in the same order as the chunks. Similar to rayon's
+    /// `par_chunks().map().collect()`, but restricted to this [`ThreadPool`].
+    ///
+    /// This blocks until all chunks are done. The calling thread helps out by running chunks itself,
+    /// and it doesn't go to sleep, so it's fine to call this from the main thread in WebAssembly
+    /// (where blocking isn't allowed). Panics if `f` panicked for any of the chunks.
+    pub fn par_chunks<T, R, F>(&self, data: &[T], chunk_size: usize, f: F) -> Vec<R>
+    where
+        T: Sync,
+        R: Send,
+        F: Fn(&[T]) -> R + Sync,
+    {
+        assert!(chunk_size > 0, "par_chunks: chunk_size must be greater than 0");
+        let chunks: Vec<&[T]> = data.chunks(chunk_size).collect();
+        let results: Vec<Mutex<Option<R>>> = chunks.iter().map(|_| Mutex::new(None)).collect();
+        let remaining = AtomicUsize::new(chunks.len());
+        let panicked = AtomicBool::new(false);
+
+        for (chunk, result) in chunks.iter().zip(&results) {
+            let (f, remaining, panicked) = (&f, &remaining, &panicked);
+            let job: Box<dyn FnOnce() + Send + '_> = Box::new(move || {
+                match catch_unwind(AssertUnwindSafe(|| f(chunk))) {
+                    Ok(value) => *result.lock().unwrap() = Some(value),
+                    Err(_) => panicked.store(true, Ordering::Release),
+                }
+                remaining.fetch_sub(1, Ordering::AcqRel);
+            });
+            // SAFETY: the job only borrows from this stack frame, and we don't return until `remaining`
+            // is 0, i.e. until every job has run to completion (panics are caught above).
+            let job = unsafe { std::mem::transmute::<Box<dyn FnOnce() + Send + '_>, Job>(job) };
+            self.shared.push(job);
+        }
+
+        // Help out until all our chunks are done. We might also run jobs that belong to someone else,
+        // which is fine, since they'll all need to run at some point anyway.
+        let mut queue_index = 0;
+        while remaining.load(Ordering::Acquire) > 0 {
+            if let Some(job) = self.shared.take(queue_index) {
+                job();
+            } else {
+                std::hint::spin_loop();
+            }
+            queue_index += 1;
+        }
+
+        if panicked.load(Ordering::Acquire) {
+            panic!("par_chunks: a chunk panicked");
+        }
+        results.into_iter().map(|result| result.into_inner().unwrap().unwrap()).collect()
+    }
+}
+
+impl Drop for ThreadPool {
+    fn drop(&mut self) {
+        self.shared.shutdown.store(true, Ordering::Release);
+        // Take the lock so that no worker is between checking `shutdown` and going to sleep.
+        let _pending = self.shared.pending.lock().unwrap();
+        self.shared.condvar.notify_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc::channel;
+
+    #[test]
+    fn test_par_chunks() {
+        let pool = ThreadPool::new(4);
+        let data: Vec<u64> = (0..10_000).collect();
+        let sums = pool.par_chunks(&data, 1000, |chunk| chunk.iter().sum::<u64>());
+        assert_eq!(sums.len(), 10);
+        assert_eq!(sums[0], (0..1000).sum::<u64>());
+        assert_eq!(sums.iter().sum::<u64>(), data.iter().sum::<u64>());
+
+        // Without workers, the calling thread does all the work.
+        assert_eq!(ThreadPool::new(0).par_chunks(&data, 3000, |chunk| chunk.len()), vec![3000, 3000, 3000, 1000]);
+    }
+
+    #[test]
+    fn test_spawn() {
+        let pool = ThreadPool::new(2);
+        let (sender, receiver) = channel();
+        pool.spawn(|| panic!("this should not take down the worker"));
+        for i in 0..10 {
+            let sender = sender.clone();
+            pool.spawn(move || sender.send(i).unwrap());
+        }
+        let mut received: Vec<i32> = (0..10).map(|_| receiver.recv().unwrap()).collect();
+        received.sort_unstable();
+        assert_eq!(received, (0..10).collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,275 @@
+//! Tooltips that show up when hovering over a component for a moment.
+//!
+//! Components register their tooltip text while drawing, using [`Cx::add_tooltip`]. We then keep track of which
+//! component the pointer is hovering over, and after [`Cx::set_tooltip_delay`] the tooltip becomes active. Draw it
+//! on top of everything using `zaplib_components::Tooltip`, or draw your own using [`Cx::get_active_tooltip`].
+
+use crate::*;
+
+/// A tooltip that was registered using [`Cx::add_tooltip`] during the last draw.
+#[derive(Clone, Debug)]
+struct CxTooltip {
+    component_id: ComponentId,
+    area: Area,
+    text: String,
+}
+
+/// The component with a tooltip that the pointer is currently over.
+#[derive(Clone, Copy, Debug)]
+struct CxTooltipHover {
+    component_id: ComponentId,
+    /// When the pointer started hovering over the component.
+    since: f64,
+    /// Where the pointer was when the tooltip got shown (or the last position before that).
+    pointer_pos: Vec2,
+    /// When the delay passed and the tooltip got shown.
+    shown_at: Option<f64>,
+    /// Hide the tooltip until the pointer leaves the component, e.g. after clicking it.
+    dismissed: bool,
+}
+
+pub(crate) struct CxTooltips {
+    registered: Vec<CxTooltip>,
+    hover: Option<CxTooltipHover>,
+    /// See [`Cx::set_tooltip_delay`].
+    delay: f64,
+    /// How long it takes a tooltip to fade in, in seconds.
+    fade_duration: f64,
+}
+
+impl Default for CxTooltips {
+    fn default() -> Self {
+        Self { registered: Vec::new(), hover: None, delay: 0.5, fade_duration: 0.15 }
+    }
+}
+
+impl CxTooltips {
+    /// Clear the tooltips of the previous draw.
+    pub(crate) fn begin_draw(&mut self) {
+        self.registered.clear();
+    }
+}
+
+/// The tooltip to draw; see [`Cx::get_active_tooltip`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct ActiveTooltip {
+    pub component_id: ComponentId,
+    pub text: String,
+    /// The rect of the area that was passed into [`Cx::add_tooltip`].
+    pub target_rect: Rect,
+    /// Where the pointer was when the tooltip got shown.
+    pub pointer_pos: Vec2,
+    /// From 0 to 1, for fading in the tooltip.
+    pub opacity: f32,
+}
+
+impl ActiveTooltip {
+    /// The top left position for a tooltip of `size`, so that it sits just below the pointer, or above it when
+    /// there's no room below. It never extends outside of `bounds` (typically the window), unless it doesn't fit.
+    pub fn position(&self, size: Vec2, bounds: Rect) -> Vec2 {
+        /// Room for the mouse cursor.
+        const BELOW_POINTER: f32 = 20.;
+        const ABOVE_POINTER: f32 = 8.;
+        const MARGIN: f32 = 4.;
+
+        let max = bounds.pos + bounds.size - size - vec2(MARGIN, MARGIN);
+        let mut y = self.pointer_pos.y + BELOW_POINTER;
+        if y > max.y {
+            y = self.pointer_pos.y - ABOVE_POINTER - size.y;
+        }
+        let x = self.pointer_pos.x.min(max.x).max(bounds.pos.x + MARGIN);
+        let y = y.min(max.y).max(bounds.pos.y + MARGIN);
+        vec2(x, y)
+    }
+}
+
+impl Cx {
+    /// Register a tooltip for `component_id`, showing `text` when hovering over `area`. Call this on every draw.
+    pub fn add_tooltip(&mut self, component_id: ComponentId, area: Area, text: &str) {
+        self.tooltips.registered.push(CxTooltip { component_id, area, text: text.to_string() });
+    }
+
+    /// How long the pointer has to hover over a component before its tooltip shows up, in seconds. Defaults to 0.5.
+    /// When moving from one component to another while a tooltip is showing, the new tooltip shows up right away.
+    pub fn set_tooltip_delay(&mut self, delay: f64) {
+        self.tooltips.delay = delay;
+    }
+
+    /// The tooltip that should be showing right now, if any. Call this after everything else has been drawn, so
+    /// that all tooltips have been registered.
+    pub fn get_active_tooltip(&self) -> Option<ActiveTooltip> {
+        let hover = self.tooltips.hover?;
+        let shown_at = hover.shown_at?;
+        let tooltip = self.tooltips.registered.iter().find(|tooltip| tooltip.component_id == hover.component_id)?;
+        let opacity = if self.tooltips.fade_duration > 0. {
+            ((self.last_event_time - shown_at) / self.tooltips.fade_duration).clamp(0., 1.) as f32
+        } else {
+            1.
+        };
+        Some(ActiveTooltip {
+            component_id: tooltip.component_id,
+            text: tooltip.text.clone(),
+            target_rect: tooltip.area.get_rect_for_first_instance(self)?,
+            pointer_pos: hover.pointer_pos,
+            opacity,
+        })
+    }
+
+    /// Track hovering and dismissing of tooltips. Called before the event handler.
+    pub(crate) fn process_tooltip_event(&mut self, event: &Event) {
+        match event {
+            Event::PointerHover(pe) => {
+                let hit = self
+                    .tooltips
+                    .registered
+                    .iter()
+                    .rev()
+                    .find(|tooltip| tooltip.area.get_rect_for_first_instance(self).map_or(false, |rect| rect.contains(pe.abs)))
+                    .map(|tooltip| tooltip.component_id);
+                let was_shown = self.tooltips.hover.map_or(false, |hover| hover.shown_at.is_some() && !hover.dismissed);
+                let current = self.tooltips.hover.map(|hover| hover.component_id);
+                match (hit, current) {
+                    (Some(component_id), Some(current)) if component_id == current => {
+                        if let Some(hover) = &mut self.tooltips.hover {
+                            if hover.shown_at.is_none() {
+                                hover.pointer_pos = pe.abs;
+                            }
+                        }
+                    }
+                    (Some(component_id), _) => {
+                        // Skip the delay when moving directly from one tooltip to the next.
+                        let since = if was_shown { self.last_event_time - self.tooltips.delay } else { self.last_event_time };
+                        self.tooltips.hover =
+                            Some(CxTooltipHover { component_id, since, pointer_pos: pe.abs, shown_at: None, dismissed: false });
+                        self.request_next_frame();
+                        if was_shown {
+                            self.request_draw();
+                        }
+                    }
+                    (None, Some(_)) => {
+                        self.tooltips.hover = None;
+                        if was_shown {
+                            self.request_draw();
+                        }
+                    }
+                    (None, None) => {}
+                }
+            }
+            Event::PointerDown(_) | Event::PointerScroll(_) | Event::KeyDown(_) => {
+                if let Some(hover) = &mut self.tooltips.hover {
+                    let was_shown = hover.shown_at.is_some() && !hover.dismissed;
+                    hover.dismissed = true;
+                    hover.shown_at = None;
+                    if was_shown {
+                        self.request_draw();
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Show tooltips after their delay, and fade them in. Called on every [`Event::NextFrame`].
+    pub(crate) fn update_tooltip_timing(&mut self) {
+        let now = self.last_event_time;
+        let hover = match self.tooltips.hover {
+            Some(hover) if !hover.dismissed => hover,
+            _ => return,
+        };
+        match hover.shown_at {
+            None if now >= hover.since + self.tooltips.delay => {
+                self.tooltips.hover = Some(CxTooltipHover { shown_at: Some(now), ..hover });
+                self.request_draw();
+                self.request_next_frame();
+            }
+            None => self.request_next_frame(),
+            Some(shown_at) if now < shown_at + self.tooltips.fade_duration => {
+                self.request_draw();
+                self.request_next_frame();
+            }
+            Some(_) => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    static SHADER: Shader = Shader {
+        build_geom: Some(QuadIns::build_geom),
+        code_to_concatenate: &[
+            Cx::STD_SHADER,
+            QuadIns::SHADER,
+            code_fragment!(
+                r#"
+                fn pixel() -> vec4 {
+                    return #f;
+                }"#
+            ),
+        ],
+        ..Shader::DEFAULT
+    };
+
+    fn draw_buttons(test_cx: &mut TestCx, ids: &[ComponentId]) {
+        test_cx.draw(|cx| {
+            for (index, &component_id) in ids.iter().enumerate() {
+                let rect = Rect { pos: vec2(10. + index as f32 * 100., 10.), size: vec2(50., 20.) };
+                let area = cx.add_instances(&SHADER, &[QuadIns::from_rect(rect)]);
+                cx.add_tooltip(component_id, area, &format!("Button {}", index));
+            }
+        });
+    }
+
+    fn active_text(test_cx: &TestCx) -> Option<String> {
+        test_cx.cx.get_active_tooltip().map(|tooltip| tooltip.text)
+    }
+
+    #[test]
+    fn test_hover_delay_and_dismiss() {
+        let mut test_cx = TestCx::new(vec2(400., 300.));
+        let ids = [ComponentId::default(), ComponentId::default()];
+        draw_buttons(&mut test_cx, &ids);
+
+        test_cx.pointer_hover(vec2(20., 20.), |_, _| ());
+        test_cx.next_frame(0.2, |_, _| ());
+        assert_eq!(active_text(&test_cx), None);
+        test_cx.next_frame(0.4, |_, _| ());
+        draw_buttons(&mut test_cx, &ids);
+        let tooltip = test_cx.cx.get_active_tooltip().unwrap();
+        assert_eq!(tooltip.text, "Button 0");
+        assert_eq!(tooltip.target_rect, Rect { pos: vec2(10., 10.), size: vec2(50., 20.) });
+        assert_eq!(tooltip.opacity, 0.);
+
+        // Moving to another button shows its tooltip right away.
+        test_cx.pointer_hover(vec2(120., 20.), |_, _| ());
+        test_cx.next_frame(0.01, |_, _| ());
+        assert_eq!(active_text(&test_cx), Some("Button 1".to_string()));
+
+        test_cx.pointer_down(vec2(120., 20.), |_, _| ());
+        assert_eq!(active_text(&test_cx), None);
+        test_cx.next_frame(1., |_, _| ());
+        assert_eq!(active_text(&test_cx), None);
+
+        test_cx.pointer_hover(vec2(200., 200.), |_, _| ());
+        test_cx.next_frame(1., |_, _| ());
+        assert_eq!(active_text(&test_cx), None);
+    }
+
+    #[test]
+    fn test_position() {
+        let tooltip = ActiveTooltip {
+            component_id: ComponentId::default(),
+            text: String::new(),
+            target_rect: Rect::default(),
+            pointer_pos: vec2(50., 50.),
+            opacity: 1.,
+        };
+        let bounds = Rect { pos: vec2(0., 0.), size: vec2(400., 300.) };
+        assert_eq!(tooltip.position(vec2(100., 20.), bounds), vec2(50., 70.));
+
+        // Near the bottom right corner it goes above the pointer, and moves left.
+        let tooltip = ActiveTooltip { pointer_pos: vec2(390., 290.), ..tooltip };
+        assert_eq!(tooltip.position(vec2(100., 20.), bounds), vec2(296., 262.));
+    }
+}
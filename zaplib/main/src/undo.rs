@@ -0,0 +1,344 @@
+//! Undo and redo, for apps that edit things.
+//!
+//! Every change is an [`UndoCommand`] that knows how to apply and revert itself. Push commands onto an
+//! [`UndoStack`], which applies them, merges rapid edits (like typing characters) into a single undo step, and
+//! handles the usual keyboard shortcuts and menu commands.
+//!
+//! ```ignore
+//! struct SetColor { index: usize, old: Vec4, new: Vec4 }
+//!
+//! impl UndoCommand for SetColor {
+//!     type Target = Vec<Vec4>;
+//!     fn apply(&mut self, colors: &mut Vec<Vec4>) { colors[self.index] = self.new; }
+//!     fn revert(&mut self, colors: &mut Vec<Vec4>) { colors[self.index] = self.old; }
+//!     // Dragging a color picker results in lots of commands, which we undo all at once.
+//!     fn merge(&mut self, next: &Self) -> bool {
+//!         let same_index = self.index == next.index;
+//!         if same_index { self.new = next.new; }
+//!         same_index
+//!     }
+//! }
+//!
+//! let mut undo_stack = UndoStack::new();
+//! undo_stack.push(&mut colors, SetColor { index: 0, old: colors[0], new: COLOR_RED }, cx.last_event_time);
+//! ```
+
+use crate::*;
+use std::collections::VecDeque;
+
+/// A change that can be undone. See the module docs.
+pub trait UndoCommand {
+    /// The thing that gets changed, like a document.
+    type Target: ?Sized;
+
+    /// Make the change. Gets called when pushing the command onto an [`UndoStack`], and when redoing it.
+    fn apply(&mut self, target: &mut Self::Target);
+
+    /// Undo the change that [`UndoCommand::apply`] made.
+    fn revert(&mut self, target: &mut Self::Target);
+
+    /// Merge `next`, which got applied right after this command, into this command, so that they get undone in one
+    /// go. Return whether that worked. By default commands never get merged.
+    fn merge(&mut self, _next: &Self) -> bool {
+        false
+    }
+}
+
+/// One step of undoing or redoing, which can consist of multiple commands.
+struct UndoEntry<C> {
+    commands: Vec<C>,
+    /// When a command was last added, for [`UndoStack::with_merge_timeout`].
+    time: f64,
+    /// Whether new commands can't get merged into this entry anymore.
+    sealed: bool,
+}
+
+/// A stack of [`UndoCommand`]s. See the module docs.
+pub struct UndoStack<C: UndoCommand> {
+    undo: VecDeque<UndoEntry<C>>,
+    redo: Vec<UndoEntry<C>>,
+    max_depth: Option<usize>,
+    merge_timeout: f64,
+    /// How many times [`UndoStack::begin_group`] has been called without [`UndoStack::end_group`].
+    group_depth: usize,
+}
+
+impl<C: UndoCommand> Default for UndoStack<C> {
+    fn default() -> Self {
+        Self { undo: VecDeque::new(), redo: Vec::new(), max_depth: Some(1000), merge_timeout: 1.0, group_depth: 0 }
+    }
+}
+
+impl<C: UndoCommand> UndoStack<C> {
+    /// Create a stack that keeps up to 1000 undo steps, and merges commands that are less than a second apart.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Keep at most this many undo steps, or any number of them when `None`. The oldest ones get dropped first.
+    #[must_use]
+    pub fn with_max_depth(self, max_depth: Option<usize>) -> Self {
+        Self { max_depth, ..self }
+    }
+
+    /// Only merge commands (see [`UndoCommand::merge`]) that are at most this many seconds apart.
+    #[must_use]
+    pub fn with_merge_timeout(self, merge_timeout: f64) -> Self {
+        Self { merge_timeout, ..self }
+    }
+
+    /// Apply `command` to `target`, and add it to the stack. Clears everything that could be redone.
+    ///
+    /// `time` is used for merging commands; typically you want to use [`Cx::last_event_time`].
+    pub fn push(&mut self, target: &mut C::Target, mut command: C, time: f64) {
+        command.apply(target);
+        self.redo.clear();
+
+        if let Some(entry) = self.undo.back_mut() {
+            let in_group = self.group_depth > 0 && !entry.sealed;
+            let can_merge = in_group || (!entry.sealed && time - entry.time <= self.merge_timeout);
+            if can_merge {
+                entry.time = time;
+                let merged = entry.commands.last_mut().map_or(false, |last| last.merge(&command));
+                if !merged && in_group {
+                    entry.commands.push(command);
+                    return;
+                }
+                if merged {
+                    return;
+                }
+            }
+            entry.sealed = true;
+        }
+
+        // A new group starts with the first command that gets pushed in it.
+        self.undo.push_back(UndoEntry { commands: vec![command], time, sealed: false });
+        if let Some(max_depth) = self.max_depth {
+            while self.undo.len() > max_depth {
+                self.undo.pop_front();
+            }
+        }
+    }
+
+    /// Make all commands that get pushed until [`UndoStack::end_group`] one undo step, e.g. for a drag, or an edit
+    /// at multiple cursors. Groups can be nested, in which case they all become one step.
+    pub fn begin_group(&mut self) {
+        if self.group_depth == 0 {
+            self.seal();
+        }
+        self.group_depth += 1;
+    }
+
+    /// End a group started with [`UndoStack::begin_group`].
+    pub fn end_group(&mut self) {
+        assert!(self.group_depth > 0, "end_group called without begin_group");
+        self.group_depth -= 1;
+        if self.group_depth == 0 {
+            self.seal();
+        }
+    }
+
+    /// Don't merge the next command into the previous ones, e.g. when the user moved the text cursor in between.
+    pub fn seal(&mut self) {
+        if let Some(entry) = self.undo.back_mut() {
+            entry.sealed = true;
+        }
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    /// Revert the last undo step. Returns whether there was anything to undo.
+    ///
+    /// Does nothing while a group is open (e.g. in the middle of a drag), since the group isn't a complete step yet.
+    pub fn undo(&mut self, target: &mut C::Target) -> bool {
+        if self.group_depth > 0 {
+            return false;
+        }
+        if let Some(mut entry) = self.undo.pop_back() {
+            for command in entry.commands.iter_mut().rev() {
+                command.revert(target);
+            }
+            self.redo.push(entry);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Apply the last undo step that got undone again. Returns whether there was anything to redo.
+    ///
+    /// Like [`UndoStack::undo`], this does nothing while a group is open.
+    pub fn redo(&mut self, target: &mut C::Target) -> bool {
+        if self.group_depth > 0 {
+            return false;
+        }
+        if let Some(mut entry) = self.redo.pop() {
+            for command in entry.commands.iter_mut() {
+                command.apply(target);
+            }
+            entry.sealed = true;
+            self.undo.push_back(entry);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Remove all undo and redo steps, e.g. after loading a different document.
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+    }
+
+    /// Undo on Ctrl/Cmd+Z and [`Cx::COMMAND_UNDO`], and redo on Ctrl/Cmd+Shift+Z, Ctrl/Cmd+Y, and
+    /// [`Cx::COMMAND_REDO`] (e.g. from a [`Menu`]). Requests a draw and returns `true` if something changed.
+    ///
+    /// Only call this when the thing that you're editing has focus, so you don't undo changes in one place while
+    /// the user is typing somewhere else.
+    pub fn handle(&mut self, cx: &mut Cx, event: &Event, target: &mut C::Target) -> bool {
+        let changed = match event {
+            Event::KeyDown(ke) if ke.modifiers.control || ke.modifiers.logo => match ke.key_code {
+                KeyCode::KeyZ if ke.modifiers.shift => self.redo(target),
+                KeyCode::KeyZ => self.undo(target),
+                KeyCode::KeyY => self.redo(target),
+                _ => false,
+            },
+            Event::Command(command) if *command == Cx::COMMAND_UNDO => self.undo(target),
+            Event::Command(command) if *command == Cx::COMMAND_REDO => self.redo(target),
+            _ => false,
+        };
+        if changed {
+            cx.request_draw();
+        }
+        changed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Appends a string, and merges with other appends of single characters, like typing.
+    struct Append(String);
+
+    impl UndoCommand for Append {
+        type Target = String;
+
+        fn apply(&mut self, target: &mut String) {
+            target.push_str(&self.0);
+        }
+
+        fn revert(&mut self, target: &mut String) {
+            target.truncate(target.len() - self.0.len());
+        }
+
+        fn merge(&mut self, next: &Self) -> bool {
+            let typing = next.0.len() == 1;
+            if typing {
+                self.0.push_str(&next.0);
+            }
+            typing
+        }
+    }
+
+    fn append(text: &str) -> Append {
+        Append(text.to_string())
+    }
+
+    #[test]
+    fn test_undo_redo_and_merging() {
+        let mut text = String::new();
+        let mut undo_stack = UndoStack::new();
+
+        undo_stack.push(&mut text, append("a"), 0.0);
+        undo_stack.push(&mut text, append("b"), 0.5);
+        // Too long after the previous command to be merged.
+        undo_stack.push(&mut text, append("c"), 2.0);
+        // Doesn't want to be merged.
+        undo_stack.push(&mut text, append("de"), 2.1);
+        undo_stack.seal();
+        undo_stack.push(&mut text, append("f"), 2.2);
+        assert_eq!(text, "abcdef");
+
+        assert!(undo_stack.undo(&mut text));
+        assert_eq!(text, "abcde");
+        assert!(undo_stack.undo(&mut text));
+        assert_eq!(text, "abc");
+        assert!(undo_stack.undo(&mut text));
+        assert_eq!(text, "ab");
+        assert!(undo_stack.redo(&mut text));
+        assert_eq!(text, "abc");
+        assert!(undo_stack.undo(&mut text));
+        assert!(undo_stack.undo(&mut text));
+        assert_eq!(text, "");
+        assert!(!undo_stack.undo(&mut text));
+
+        // Pushing clears the redo stack.
+        undo_stack.push(&mut text, append("x"), 3.0);
+        assert!(!undo_stack.can_redo());
+        assert!(!undo_stack.redo(&mut text));
+        assert_eq!(text, "x");
+    }
+
+    #[test]
+    fn test_groups_and_max_depth() {
+        let mut text = String::new();
+        let mut undo_stack = UndoStack::new().with_max_depth(Some(2));
+
+        undo_stack.push(&mut text, append("a"), 0.0);
+        undo_stack.begin_group();
+        undo_stack.push(&mut text, append("bb"), 0.1);
+        undo_stack.begin_group();
+        undo_stack.push(&mut text, append("cc"), 5.0);
+        // Undoing and redoing are ignored while a group is open.
+        assert!(!undo_stack.undo(&mut text));
+        assert!(!undo_stack.redo(&mut text));
+        assert_eq!(text, "abbcc");
+        undo_stack.end_group();
+        undo_stack.end_group();
+        undo_stack.push(&mut text, append("d"), 5.1);
+        assert_eq!(text, "abbccd");
+
+        // The first step got dropped because of the maximum depth.
+        assert!(undo_stack.undo(&mut text));
+        assert_eq!(text, "abbcc");
+        assert!(undo_stack.undo(&mut text));
+        assert_eq!(text, "a");
+        assert!(!undo_stack.undo(&mut text));
+    }
+
+    #[test]
+    fn test_handle() {
+        let mut test_cx = TestCx::new(vec2(100., 100.));
+        let mut text = String::new();
+        let mut undo_stack = UndoStack::new();
+        undo_stack.push(&mut text, append("a"), 0.0);
+
+        let control = KeyModifiers { control: true, ..KeyModifiers::default() };
+        let control_shift = KeyModifiers { control: true, shift: true, ..KeyModifiers::default() };
+        test_cx.key_down(KeyCode::KeyZ, KeyModifiers::default(), |cx, event| undo_stack.handle(cx, event, &mut text));
+        assert_eq!(text, "a");
+        test_cx.key_down(KeyCode::KeyZ, control.clone(), |cx, event| undo_stack.handle(cx, event, &mut text));
+        assert_eq!(text, "");
+        test_cx.key_down(KeyCode::KeyZ, control_shift, |cx, event| undo_stack.handle(cx, event, &mut text));
+        assert_eq!(text, "a");
+        test_cx.dispatch(Event::Command(Cx::COMMAND_UNDO), |cx, event| undo_stack.handle(cx, event, &mut text));
+        assert_eq!(text, "");
+
+        // In the middle of a drag.
+        undo_stack.begin_group();
+        undo_stack.push(&mut text, append("b"), 1.0);
+        let changed = test_cx.key_down(KeyCode::KeyZ, control.clone(), |cx, event| undo_stack.handle(cx, event, &mut text));
+        assert_eq!(changed, vec![false]);
+        assert_eq!(text, "b");
+        undo_stack.end_group();
+        test_cx.key_down(KeyCode::KeyZ, control, |cx, event| undo_stack.handle(cx, event, &mut text));
+        assert_eq!(text, "");
+    }
+}
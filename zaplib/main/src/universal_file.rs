@@ -7,6 +7,9 @@ extern "C" {
     fn readUrlSync(url_ptr: usize, url_len: usize, buf_ptr_out: *mut u32, buf_len_out: *mut u32) -> u32;
 }
 
+use crate::*;
+use std::future::Future;
+
 enum UniversalFileInner {
     /// Actually resolved data; contains the entire file.
     FullyLoaded { data: std::sync::Arc<Vec<u8>>, pos: u64 },
@@ -46,8 +49,9 @@ enum UniversalFileInner {
 ///   state. Also note that it's not a `try_clone` -- it will always succeed. This means that if you
 ///   clone a handle to a file that doesn't exist anymore, then you'll get that error on the next
 ///   read, not while cloning.
-/// * Currently it only supports reading, not writing. This might change in the future, but requires
-///   some thinking about how that should work in WebAssembly.
+/// * Writing is only supported for local files on native targets, using [`UniversalFile::create`] or
+///   [`UniversalFile::open_append`]. When saving documents, use [`save_file`] (or [`save_file_async`], which
+///   also works in WebAssembly) instead, which replaces the file atomically.
 ///
 /// Note that you typically want to load files in a thread. Even on native targets the file system
 /// can be slow, e.g. when the user has mounted a remote file system, so you want to avoid blocking
@@ -81,7 +85,8 @@ impl UniversalFile {
     /// an [`crate::AppOpenFilesEvent`].
     ///
     /// On the web target, this will load files relative to the base path, which you can override using the
-    /// [<base> tag](https://developer.mozilla.org/en-US/docs/Web/HTML/Element/base).
+    /// [<base> tag](https://developer.mozilla.org/en-US/docs/Web/HTML/Element/base). In Node.js, files are read
+    /// from disk, relative to `baseUri` in `zaplib.initialize` (by default the current working directory).
     pub fn open(path: &str) -> std::io::Result<Self> {
         if is_absolute_url(path) {
             return Err(std::io::Error::new(
@@ -100,6 +105,97 @@ impl UniversalFile {
         }
     }
 
+    /// Open a local file for writing, creating it if it doesn't exist, and truncating it if it does. Only
+    /// available on native targets; in WebAssembly this returns an [`std::io::ErrorKind::Unsupported`] error.
+    ///
+    /// Note that the file can also be read from (e.g. after seeking back), but [`Clone::clone`] always opens a
+    /// new read-only handle.
+    pub fn create(path: &str) -> std::io::Result<Self> {
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            let file = std::fs::OpenOptions::new().read(true).write(true).create(true).truncate(true).open(path)?;
+            Ok(Self(UniversalFileInner::LocalFile { path: path.to_string(), file: Some(file) }))
+        }
+        #[cfg(target_arch = "wasm32")]
+        {
+            Err(write_unsupported_error(path))
+        }
+    }
+
+    /// Open a local file for appending, creating it if it doesn't exist. Only available on native targets; in
+    /// WebAssembly this returns an [`std::io::ErrorKind::Unsupported`] error.
+    pub fn open_append(path: &str) -> std::io::Result<Self> {
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            let file = std::fs::OpenOptions::new().read(true).append(true).create(true).open(path)?;
+            Ok(Self(UniversalFileInner::LocalFile { path: path.to_string(), file: Some(file) }))
+        }
+        #[cfg(target_arch = "wasm32")]
+        {
+            Err(write_unsupported_error(path))
+        }
+    }
+
+    /// Truncate or extend the file to `size` bytes, like [`std::fs::File::set_len`]. Only supported for files
+    /// opened using [`UniversalFile::create`] or [`UniversalFile::open_append`].
+    pub fn set_len(&mut self, size: u64) -> std::io::Result<()> {
+        match &mut self.0 {
+            #[cfg(not(target_arch = "wasm32"))]
+            UniversalFileInner::LocalFile { path, file } => get_local_file(path, file)?.set_len(size),
+            _ => {
+                let _ = size;
+                Err(write_unsupported_error("this file"))
+            }
+        }
+    }
+
+    /// Map the whole file into memory, without copying it into the heap. This is useful for huge local
+    /// datasets, where the operating system only loads the parts that are actually accessed.
+    ///
+    /// Supported for local files on Linux and macOS. For files that are already fully loaded in memory (e.g.
+    /// from [`UniversalFile::open_url`]) this returns the existing data, without copying. Otherwise (e.g. in
+    /// WebAssembly) this returns an [`std::io::ErrorKind::Unsupported`] error, in which case you can fall back to
+    /// reading the file in chunks using [`UniversalFile::chunks`].
+    ///
+    /// # Safety
+    /// The file must not be modified or truncated while the [`MappedFile`] is alive (also not by other
+    /// processes), since that changes (or invalidates) the memory behind the returned slice.
+    pub unsafe fn mmap(&mut self) -> std::io::Result<MappedFile> {
+        match &mut self.0 {
+            UniversalFileInner::FullyLoaded { data, pos: _ } => {
+                Ok(MappedFile(MappedFileInner::Loaded(std::sync::Arc::clone(data))))
+            }
+            #[cfg(unix)]
+            UniversalFileInner::LocalFile { path, file } => {
+                use std::os::unix::io::AsRawFd;
+
+                let file = get_local_file(path, file)?;
+                let len = file.metadata()?.len() as usize;
+                if len == 0 {
+                    // `mmap` doesn't allow mapping 0 bytes.
+                    return Ok(MappedFile(MappedFileInner::Loaded(std::sync::Arc::new(vec![]))));
+                }
+                let ptr = libc::mmap(std::ptr::null_mut(), len, libc::PROT_READ, libc::MAP_PRIVATE, file.as_raw_fd(), 0);
+                if ptr == libc::MAP_FAILED {
+                    return Err(std::io::Error::last_os_error());
+                }
+                Ok(MappedFile(MappedFileInner::Mapped { ptr: ptr as *const u8, len }))
+            }
+            #[allow(unreachable_patterns)]
+            _ => Err(std::io::Error::new(
+                std::io::ErrorKind::Unsupported,
+                "Memory-mapping is not supported for this file; use `UniversalFile::chunks` instead",
+            )),
+        }
+    }
+
+    /// Read the rest of the file in chunks of (at most) `chunk_size` bytes, starting at the current position.
+    /// Works on all targets, so this is a good fallback for when [`UniversalFile::mmap`] is not supported.
+    pub fn chunks(&mut self, chunk_size: usize) -> Chunks<'_> {
+        assert!(chunk_size > 0, "chunk_size must be greater than 0");
+        Chunks { file: self, chunk_size, done: false }
+    }
+
     /// Open an absolute URL. This will always block until the entire file is loaded.
     ///
     /// Will return an error if the file does not exist.
@@ -162,6 +258,180 @@ impl UniversalFile {
     }
 }
 
+fn write_unsupported_error(path: &str) -> std::io::Error {
+    std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        format!("Writing to {} is not supported; only local files on native targets can be written to", path),
+    )
+}
+
+/// Atomically replace the contents of the local file at `path` with `data`, creating it if it doesn't exist.
+///
+/// The data is first written to a temporary file in the same directory, which is then renamed to `path`. So
+/// if anything goes wrong (e.g. the app crashes or the disk is full), the original file is left untouched.
+///
+/// Only available on native targets; in WebAssembly this returns an [`std::io::ErrorKind::Unsupported`] error.
+/// Use [`save_file_async`] for something that works on all targets.
+pub fn save_file(path: &str, data: &[u8]) -> std::io::Result<()> {
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        use std::io::Write;
+
+        let path = std::path::Path::new(path);
+        let file_name =
+            path.file_name().ok_or_else(|| std::io::Error::new(std::io::ErrorKind::InvalidInput, "'path' has no file name"))?;
+        let tmp_path = path.with_file_name(format!(".{}.{}.tmp", file_name.to_string_lossy(), std::process::id()));
+        let result = (|| {
+            let mut file = std::fs::File::create(&tmp_path)?;
+            file.write_all(data)?;
+            file.sync_all()?;
+            std::fs::rename(&tmp_path, path)
+        })();
+        if result.is_err() {
+            let _ = std::fs::remove_file(&tmp_path);
+        }
+        result
+    }
+    #[cfg(target_arch = "wasm32")]
+    {
+        let _ = data;
+        Err(write_unsupported_error(path))
+    }
+}
+
+/// Like [`save_file`], but also works in WebAssembly, where files are stored in the [Origin Private File
+/// System](https://developer.mozilla.org/en-US/docs/Web/API/File_System_Access_API#origin_private_file_system)
+/// (OPFS). These files are private to the origin of the page, and can be read back using [`load_file_async`].
+///
+/// Natively the file is written right away, blocking the current thread.
+pub fn save_file_async(cx: &mut Cx, path: &str, data: Vec<u8>) -> impl Future<Output = std::io::Result<()>> {
+    #[cfg(target_arch = "wasm32")]
+    {
+        let future = cx.call_js_async("_zaplibSaveFile", vec![path.to_string().into_param(), data.into_param()], None);
+        async move { future.await.map(|_| ()).map_err(call_js_error_to_io_error) }
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        let _ = cx;
+        std::future::ready(save_file(path, &data))
+    }
+}
+
+/// Read a whole file that was saved using [`save_file_async`]. In WebAssembly this reads from the Origin
+/// Private File System; natively it reads the local file (blocking the current thread).
+///
+/// Returns an [`std::io::ErrorKind::NotFound`] error if the file doesn't exist.
+pub fn load_file_async(cx: &mut Cx, path: &str) -> impl Future<Output = std::io::Result<Vec<u8>>> {
+    #[cfg(target_arch = "wasm32")]
+    {
+        let future = cx.call_js_async("_zaplibLoadFile", vec![path.to_string().into_param()], None);
+        async move {
+            let params = future.await.map_err(call_js_error_to_io_error)?;
+            Ok(params[0].as_u8_slice().to_vec())
+        }
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        let _ = cx;
+        std::future::ready(std::fs::read(path))
+    }
+}
+
+/// See `fileSystemCallJsCallbacks` in `file_system.ts`, which prefixes errors with their name.
+#[cfg(target_arch = "wasm32")]
+fn call_js_error_to_io_error(err: CallJsError) -> std::io::Error {
+    match err {
+        CallJsError::Thrown(message) if message.starts_with("NotFoundError") => {
+            std::io::Error::new(std::io::ErrorKind::NotFound, message)
+        }
+        err => std::io::Error::new(std::io::ErrorKind::Other, err.to_string()),
+    }
+}
+
+enum MappedFileInner {
+    Loaded(std::sync::Arc<Vec<u8>>),
+    #[cfg(unix)]
+    Mapped {
+        ptr: *const u8,
+        len: usize,
+    },
+}
+
+/// Memory returned by [`UniversalFile::mmap`]. Use it as a `&[u8]`. Unmaps the file when dropped.
+pub struct MappedFile(MappedFileInner);
+
+// The mapping is read-only, so it's fine to access from multiple threads.
+unsafe impl Send for MappedFile {}
+unsafe impl Sync for MappedFile {}
+
+impl std::ops::Deref for MappedFile {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match &self.0 {
+            MappedFileInner::Loaded(data) => data,
+            #[cfg(unix)]
+            MappedFileInner::Mapped { ptr, len } => unsafe { std::slice::from_raw_parts(*ptr, *len) },
+        }
+    }
+}
+
+impl Drop for MappedFile {
+    fn drop(&mut self) {
+        #[cfg(unix)]
+        if let MappedFileInner::Mapped { ptr, len } = self.0 {
+            unsafe {
+                libc::munmap(ptr as *mut libc::c_void, len);
+            }
+        }
+    }
+}
+
+impl std::fmt::Debug for MappedFile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<MappedFile ({} bytes)>", self.len())
+    }
+}
+
+/// Iterator returned by [`UniversalFile::chunks`].
+pub struct Chunks<'a> {
+    file: &'a mut UniversalFile,
+    chunk_size: usize,
+    done: bool,
+}
+
+impl Iterator for Chunks<'_> {
+    type Item = std::io::Result<Vec<u8>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let mut chunk = vec![0; self.chunk_size];
+        let mut filled = 0;
+        // A single read can return fewer bytes than requested, so keep reading until the chunk is full.
+        while filled < self.chunk_size {
+            match std::io::Read::read(self.file, &mut chunk[filled..]) {
+                Ok(0) => break,
+                Ok(bytes_read) => filled += bytes_read,
+                Err(err) if err.kind() == std::io::ErrorKind::Interrupted => {}
+                Err(err) => {
+                    self.done = true;
+                    return Some(Err(err));
+                }
+            }
+        }
+        if filled < self.chunk_size {
+            self.done = true;
+            if filled == 0 {
+                return None;
+            }
+            chunk.truncate(filled);
+        }
+        Some(Ok(chunk))
+    }
+}
+
 /// Convenience function to load a local file path into a [`String`].
 ///
 /// Might be faster than manually using [`std::io::Read::read_to_string`] if we can preallocate
@@ -258,6 +528,27 @@ impl std::io::Seek for UniversalFile {
     }
 }
 
+impl std::io::Write for UniversalFile {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match &mut self.0 {
+            #[cfg(not(target_arch = "wasm32"))]
+            UniversalFileInner::LocalFile { path, file } => get_local_file(path, file)?.write(buf),
+            _ => {
+                let _ = buf;
+                Err(write_unsupported_error("this file"))
+            }
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match &mut self.0 {
+            #[cfg(not(target_arch = "wasm32"))]
+            UniversalFileInner::LocalFile { path, file } => get_local_file(path, file)?.flush(),
+            _ => Ok(()),
+        }
+    }
+}
+
 impl crate::ReadSeek for UniversalFile {}
 
 impl std::fmt::Debug for UniversalFile {
@@ -265,3 +556,59 @@ impl std::fmt::Debug for UniversalFile {
         write!(f, "<UniversalFile>")
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Read, Seek, SeekFrom, Write};
+
+    #[test]
+    fn test_write_append_and_save() {
+        let dir = std::env::temp_dir().join(format!("zaplib_universal_file_test_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("file.txt").to_str().unwrap().to_string();
+
+        let mut file = UniversalFile::create(&path).unwrap();
+        file.write_all(b"hello world").unwrap();
+        file.set_len(5).unwrap();
+        file.seek(SeekFrom::Start(0)).unwrap();
+        let mut contents = String::new();
+        file.read_to_string(&mut contents).unwrap();
+        assert_eq!(contents, "hello");
+
+        let mut file = UniversalFile::open_append(&path).unwrap();
+        file.write_all(b"!").unwrap();
+        assert_eq!(read_to_string(&path).unwrap(), "hello!");
+
+        // Opened read-only.
+        assert!(UniversalFile::open(&path).unwrap().write_all(b"nope").is_err());
+
+        save_file(&path, b"saved").unwrap();
+        assert_eq!(read_to_string(&path).unwrap(), "saved");
+        // No temporary files left behind.
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_mmap_and_chunks() {
+        let path = std::env::temp_dir().join(format!("zaplib_universal_file_mmap_test_{}", std::process::id()));
+        let path = path.to_str().unwrap();
+        std::fs::write(path, b"hello world").unwrap();
+
+        let mut file = UniversalFile::open(path).unwrap();
+        let mapped = unsafe { file.mmap() }.unwrap();
+        assert_eq!(&mapped[..], b"hello world");
+
+        let chunks: Vec<Vec<u8>> = file.chunks(4).map(Result::unwrap).collect();
+        assert_eq!(chunks, vec![b"hell".to_vec(), b"o wo".to_vec(), b"rld".to_vec()]);
+        assert_eq!(file.chunks(4).count(), 0);
+
+        drop(mapped);
+        std::fs::write(path, b"").unwrap();
+        assert!(unsafe { UniversalFile::open(path).unwrap().mmap() }.unwrap().is_empty());
+
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -1,5 +1,8 @@
 //! Version of [`std::thread`] that also works in WebAssembly.
 
+use crate::*;
+#[cfg(target_arch = "wasm32")]
+use std::cell::Cell;
 use std::thread;
 use std::time::Duration;
 /// See [`Thread`].
@@ -11,6 +14,8 @@ struct UniversalThread();
 trait Thread {
     /// Run function in a non-blocking thread.
     ///
+    /// If the function panics, an [`Event::WorkerPanic`] is sent to the main thread.
+    ///
     /// Check out the `test_multithread` for sample usage.
     ///
     /// TODO(Paras): Implement join handles. When we do, we can use the same
@@ -25,7 +30,11 @@ trait Thread {
 impl Thread for UniversalThread {
     /// See [`Thread::spawn`].
     fn spawn(f: impl FnOnce() + Send + 'static) {
-        thread::spawn(f);
+        thread::spawn(move || {
+            if let Err(payload) = std::panic::catch_unwind(std::panic::AssertUnwindSafe(f)) {
+                report_worker_panic(panic_payload_to_string(&*payload));
+            }
+        });
     }
 
     /// See [`Thread::sleep`].
@@ -34,6 +43,35 @@ impl Thread for UniversalThread {
     }
 }
 
+#[cfg(target_arch = "wasm32")]
+thread_local! {
+    /// Whether the current web worker was started using [`spawn`].
+    static IS_WORKER: Cell<bool> = Cell::new(false);
+}
+
+/// Whether we're running in a web worker that was started using [`spawn`]. Used by the panic hook
+/// in [`Cx::init_error_handlers`] to decide whether to call [`report_worker_panic`].
+#[cfg(target_arch = "wasm32")]
+pub(crate) fn is_worker() -> bool {
+    IS_WORKER.with(|is_worker| is_worker.get())
+}
+
+/// Get the message of a panic caught using [`std::panic::catch_unwind`].
+pub(crate) fn panic_payload_to_string(payload: &(dyn std::any::Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "Box<dyn Any>".to_string()
+    }
+}
+
+/// Tell the main thread that a thread panicked, by sending an [`Event::WorkerPanic`].
+pub(crate) fn report_worker_panic(message: String) {
+    Cx::send_event_from_any_thread(Event::WorkerPanic(WorkerPanicEvent { message }));
+}
+
 #[cfg(target_arch = "wasm32")]
 struct WorkerContext {
     func: Box<dyn FnOnce() + Send>,
@@ -64,6 +102,7 @@ extern "C" {
 #[cfg(target_arch = "wasm32")]
 #[export_name = "runFunctionPointer"]
 unsafe extern "C" fn fn_to_run_in_worker(ctx_ptr: u64) {
+    IS_WORKER.with(|is_worker| is_worker.set(true));
     let ctx = Box::from_raw(ctx_ptr as *mut WorkerContext);
     (ctx.func)();
 }
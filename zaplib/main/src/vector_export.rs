@@ -0,0 +1,714 @@
+//! Exporting what is drawn in the main window as vector graphics, e.g. for putting charts in papers and reports.
+//!
+//! [`Cx::export_vector_scene`] replays the draw tree of the main window into a [`VectorScene`], which you can turn
+//! into an SVG or PDF file using [`VectorScene::to_svg`] and [`VectorScene::to_pdf`]. Files are returned in memory; to
+//! write them to a file, use e.g. [`save_file_async`] or [`std::fs::write`].
+//!
+//! Since shaders can draw anything, we can only export what we know how to interpret:
+//! * Text drawn using [`TextIns`] becomes the outlines of its glyphs, so it stays sharp at any zoom level.
+//! * Instances of shaders with a single texture that has pixels on the CPU (such as [`ImageIns`] with a texture that
+//!   was filled using [`TextureHandle::get_image_mut`]) become embedded PNG images.
+//! * Other instances with `rect_pos`, `rect_size`, and `color` (such as most shaders based on [`QuadIns`]) become
+//!   rectangles filled with that color. Anything else their `pixel` function does, like rounded corners or gradients,
+//!   is not exported.
+//! * Everything else, such as 3d geometry or textures rendered by other [`Pass`]es, is skipped.
+
+use crate::capture::CapturedFrame;
+use crate::*;
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::sync::atomic::Ordering;
+use zaplib_vector::path::PathCommand;
+
+/// A scene exported using [`Cx::export_vector_scene`].
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct VectorScene {
+    /// Size in logical pixels.
+    pub size: Vec2,
+    /// Color that the whole scene is filled with before drawing [`VectorScene::items`], if any.
+    pub background_color: Option<Vec4>,
+    /// Items from back to front.
+    pub items: Vec<VectorItem>,
+}
+
+/// A single shape in a [`VectorScene`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct VectorItem {
+    pub shape: VectorShape,
+    /// If set, only the part of [`VectorItem::shape`] within this rectangle is visible.
+    pub clip: Option<Rect>,
+}
+
+/// See [`VectorItem`].
+#[derive(Clone, Debug, PartialEq)]
+pub enum VectorShape {
+    /// A rectangle filled with a (non-premultiplied) color.
+    Rect { rect: Rect, color: Vec4 },
+    /// A path filled with a (non-premultiplied) color, using the nonzero winding rule.
+    Path { segments: Vec<PathSegment>, color: Vec4 },
+    /// An image stretched to fill a rectangle.
+    Image { rect: Rect, image: CapturedFrame },
+}
+
+/// See [`VectorShape::Path`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PathSegment {
+    MoveTo(Vec2),
+    LineTo(Vec2),
+    /// A quadratic Bézier curve with a control point and an end point.
+    QuadraticTo(Vec2, Vec2),
+    Close,
+}
+
+impl VectorScene {
+    /// Encode as an SVG file.
+    pub fn to_svg(&self) -> String {
+        let (width, height) = (fmt_num(self.size.x), fmt_num(self.size.y));
+        let mut svg = format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{width}\" height=\"{height}\" viewBox=\"0 0 {width} {height}\">\n"
+        );
+        if let Some(color) = self.background_color {
+            writeln!(svg, "<rect width=\"{width}\" height=\"{height}\"{}/>", svg_fill(color)).unwrap();
+        }
+
+        let mut clips: Vec<Rect> = Vec::new();
+        for item in &self.items {
+            let clip_attribute = if let Some(clip) = item.clip {
+                let clip_id = clips.iter().position(|other| *other == clip).unwrap_or_else(|| {
+                    writeln!(svg, "<clipPath id=\"clip{}\"><rect{}/></clipPath>", clips.len(), svg_rect(clip)).unwrap();
+                    clips.push(clip);
+                    clips.len() - 1
+                });
+                format!(" clip-path=\"url(#clip{clip_id})\"")
+            } else {
+                String::new()
+            };
+
+            match &item.shape {
+                VectorShape::Rect { rect, color } => {
+                    writeln!(svg, "<rect{}{}{clip_attribute}/>", svg_rect(*rect), svg_fill(*color)).unwrap();
+                }
+                VectorShape::Path { segments, color } => {
+                    let mut path_data = String::new();
+                    for segment in segments {
+                        match segment {
+                            PathSegment::MoveTo(p) => write!(path_data, "M{} {}", fmt_num(p.x), fmt_num(p.y)),
+                            PathSegment::LineTo(p) => write!(path_data, "L{} {}", fmt_num(p.x), fmt_num(p.y)),
+                            PathSegment::QuadraticTo(c, p) => {
+                                write!(path_data, "Q{} {} {} {}", fmt_num(c.x), fmt_num(c.y), fmt_num(p.x), fmt_num(p.y))
+                            }
+                            PathSegment::Close => write!(path_data, "Z"),
+                        }
+                        .unwrap();
+                    }
+                    writeln!(svg, "<path d=\"{path_data}\"{}{clip_attribute}/>", svg_fill(*color)).unwrap();
+                }
+                VectorShape::Image { rect, image } => {
+                    writeln!(
+                        svg,
+                        "<image{} preserveAspectRatio=\"none\" href=\"data:image/png;base64,{}\"{clip_attribute}/>",
+                        svg_rect(*rect),
+                        base64_encode(&image.to_png())
+                    )
+                    .unwrap();
+                }
+            }
+        }
+        svg.push_str("</svg>\n");
+        svg
+    }
+
+    /// Encode as a single-page PDF file. The page has the same size as the scene would have when printed from a
+    /// browser, i.e. 96 logical pixels per inch.
+    pub fn to_pdf(&self) -> Vec<u8> {
+        self.write_pdf(self.size * POINTS_PER_PIXEL, 0.0, POINTS_PER_PIXEL)
+    }
+
+    /// Encode as a PDF file with pages of `page_size` and a `margin` on every side, both in points (1/72 inch). The
+    /// scene is scaled to fit the width of the pages, and split over as many pages as needed.
+    pub fn to_paged_pdf(&self, page_size: Vec2, margin: f32) -> Vec<u8> {
+        let printable_width = (page_size.x - 2.0 * margin).max(1.0);
+        self.write_pdf(page_size, margin, printable_width / self.size.x.max(1.0))
+    }
+
+    /// Write a PDF file, where `scale` is the number of points per logical pixel.
+    fn write_pdf(&self, page_size: Vec2, margin: f32, scale: f32) -> Vec<u8> {
+        let printable_size = vec2((page_size.x - 2.0 * margin).max(1.0), (page_size.y - 2.0 * margin).max(1.0));
+        let scene_height_per_page = printable_size.y / scale;
+        // Allow for some rounding errors, so that we don't add an empty page.
+        let page_count = ((self.size.y / scene_height_per_page - 0.001).ceil() as usize).max(1);
+
+        let mut opacities: Vec<f32> = Vec::new();
+        let mut images: Vec<&CapturedFrame> = Vec::new();
+
+        // All pages draw the entire scene, just shifted and clipped differently.
+        let mut content = String::new();
+        if let Some(color) = self.background_color {
+            pdf_fill(&mut content, color, &mut opacities);
+            writeln!(content, "0 0 {} {} re f", fmt_num(self.size.x), fmt_num(self.size.y)).unwrap();
+        }
+        for item in &self.items {
+            content.push_str("q\n");
+            if let Some(clip) = item.clip {
+                writeln!(content, "{} re W n", pdf_rect(clip)).unwrap();
+            }
+            match &item.shape {
+                VectorShape::Rect { rect, color } => {
+                    pdf_fill(&mut content, *color, &mut opacities);
+                    writeln!(content, "{} re f", pdf_rect(*rect)).unwrap();
+                }
+                VectorShape::Path { segments, color } => {
+                    pdf_fill(&mut content, *color, &mut opacities);
+                    // PDF only has cubic curves, so we need to keep track of the current point to convert
+                    // quadratic ones.
+                    let mut start = Vec2::default();
+                    let mut current = Vec2::default();
+                    for segment in segments {
+                        match *segment {
+                            PathSegment::MoveTo(p) => {
+                                writeln!(content, "{} {} m", fmt_num(p.x), fmt_num(p.y)).unwrap();
+                                start = p;
+                                current = p;
+                            }
+                            PathSegment::LineTo(p) => {
+                                writeln!(content, "{} {} l", fmt_num(p.x), fmt_num(p.y)).unwrap();
+                                current = p;
+                            }
+                            PathSegment::QuadraticTo(c, p) => {
+                                let c1 = current + (c - current) * (2.0 / 3.0);
+                                let c2 = p + (c - p) * (2.0 / 3.0);
+                                writeln!(
+                                    content,
+                                    "{} {} {} {} {} {} c",
+                                    fmt_num(c1.x),
+                                    fmt_num(c1.y),
+                                    fmt_num(c2.x),
+                                    fmt_num(c2.y),
+                                    fmt_num(p.x),
+                                    fmt_num(p.y)
+                                )
+                                .unwrap();
+                                current = p;
+                            }
+                            PathSegment::Close => {
+                                content.push_str("h\n");
+                                current = start;
+                            }
+                        }
+                    }
+                    content.push_str("f\n");
+                }
+                VectorShape::Image { rect, image } => {
+                    // Images are drawn into the unit square, with the first row at the top.
+                    writeln!(
+                        content,
+                        "{} 0 0 {} {} {} cm /Im{} Do",
+                        fmt_num(rect.size.x),
+                        fmt_num(-rect.size.y),
+                        fmt_num(rect.pos.x),
+                        fmt_num(rect.pos.y + rect.size.y),
+                        images.len()
+                    )
+                    .unwrap();
+                    images.push(image);
+                }
+            }
+            content.push_str("Q\n");
+        }
+
+        let mut resources = String::from("/ExtGState <<");
+        for (index, opacity) in opacities.iter().enumerate() {
+            write!(resources, " /GA{index} << /ca {} >>", fmt_num(*opacity)).unwrap();
+        }
+        resources.push_str(" >> /XObject <<");
+        // Objects 1 and 2 are the catalog and pages, then every page is followed by its content, and every image by
+        // its alpha mask.
+        let first_image_object_id = 3 + page_count * 2;
+        for index in 0..images.len() {
+            write!(resources, " /Im{index} {} 0 R", first_image_object_id + index * 2).unwrap();
+        }
+        resources.push_str(" >>");
+
+        let mut pdf = PdfWriter::new();
+        pdf.object("<< /Type /Catalog /Pages 2 0 R >>");
+        let kids: Vec<String> = (0..page_count).map(|page| format!("{} 0 R", 3 + page * 2)).collect();
+        pdf.object(&format!("<< /Type /Pages /Kids [{}] /Count {page_count} >>", kids.join(" ")));
+        for page in 0..page_count {
+            pdf.object(&format!(
+                "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {} {}] /Resources << {resources} >> /Contents {} 0 R >>",
+                fmt_num(page_size.x),
+                fmt_num(page_size.y),
+                4 + page * 2
+            ));
+            // Clip to the printable area, and flip the y-axis, since PDF coordinates start at the bottom.
+            let page_content = format!(
+                "{} {} {} {} re W n\n{} 0 0 {} {} {} cm\n{content}",
+                fmt_num(margin),
+                fmt_num(margin),
+                fmt_num(printable_size.x),
+                fmt_num(printable_size.y),
+                fmt_num(scale),
+                fmt_num(-scale),
+                fmt_num(margin),
+                fmt_num(page_size.y - margin + page as f32 * printable_size.y)
+            );
+            pdf.stream_object("", page_content.as_bytes());
+        }
+        for (index, image) in images.iter().enumerate() {
+            let mut rgb = Vec::with_capacity(image.pixels.len() * 3);
+            let mut alpha = Vec::with_capacity(image.pixels.len());
+            for pixel in &image.pixels {
+                let [r, g, b, a] = pixel.to_ne_bytes();
+                rgb.extend_from_slice(&[r, g, b]);
+                alpha.push(a);
+            }
+            let image_dict =
+                format!("/Type /XObject /Subtype /Image /Width {} /Height {} /BitsPerComponent 8", image.width, image.height);
+            let mask_object_id = first_image_object_id + index * 2 + 1;
+            pdf.stream_object(&format!("{image_dict} /ColorSpace /DeviceRGB /SMask {mask_object_id} 0 R"), &rgb);
+            pdf.stream_object(&format!("{image_dict} /ColorSpace /DeviceGray"), &alpha);
+        }
+        pdf.finish()
+    }
+}
+
+/// PDF units are 1/72 inch, and logical pixels 1/96 inch.
+const POINTS_PER_PIXEL: f32 = 72.0 / 96.0;
+
+/// Writes PDF objects with consecutive ids, starting at 1, and keeps track of their offsets for the cross-reference
+/// table.
+struct PdfWriter {
+    bytes: Vec<u8>,
+    offsets: Vec<usize>,
+}
+
+impl PdfWriter {
+    fn new() -> Self {
+        // The second line has some binary characters to signal that the file contains binary data.
+        Self { bytes: b"%PDF-1.4\n%\xe2\xe3\xcf\xd3\n".to_vec(), offsets: Vec::new() }
+    }
+
+    fn begin_object(&mut self) {
+        self.offsets.push(self.bytes.len());
+        self.bytes.extend_from_slice(format!("{} 0 obj\n", self.offsets.len()).as_bytes());
+    }
+
+    fn object(&mut self, object: &str) {
+        self.begin_object();
+        self.bytes.extend_from_slice(object.as_bytes());
+        self.bytes.extend_from_slice(b"\nendobj\n");
+    }
+
+    /// Write a compressed stream; `dict` contains any extra entries of the stream dictionary.
+    fn stream_object(&mut self, dict: &str, data: &[u8]) {
+        let mut encoder = flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+        std::io::Write::write_all(&mut encoder, data).unwrap();
+        let data = encoder.finish().unwrap();
+
+        self.begin_object();
+        let separator = if dict.is_empty() { "" } else { " " };
+        self.bytes.extend_from_slice(
+            format!("<< {dict}{separator}/Length {} /Filter /FlateDecode >>\nstream\n", data.len()).as_bytes(),
+        );
+        self.bytes.extend_from_slice(&data);
+        self.bytes.extend_from_slice(b"\nendstream\nendobj\n");
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        let xref_offset = self.bytes.len();
+        let mut xref = format!("xref\n0 {}\n0000000000 65535 f \n", self.offsets.len() + 1);
+        for offset in &self.offsets {
+            writeln!(xref, "{offset:010} 00000 n ").unwrap();
+        }
+        write!(xref, "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{xref_offset}\n%%EOF\n", self.offsets.len() + 1).unwrap();
+        self.bytes.extend_from_slice(xref.as_bytes());
+        self.bytes
+    }
+}
+
+/// Format a number for SVG and PDF files, which don't support exponents, and for which 3 decimals is plenty.
+fn fmt_num(value: f32) -> String {
+    let formatted = format!("{:.3}", value);
+    let trimmed = formatted.trim_end_matches('0').trim_end_matches('.');
+    if trimmed == "-0" {
+        "0".to_string()
+    } else {
+        trimmed.to_string()
+    }
+}
+
+fn color_byte(value: f32) -> u8 {
+    (value.clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
+fn svg_rect(rect: Rect) -> String {
+    format!(
+        " x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\"",
+        fmt_num(rect.pos.x),
+        fmt_num(rect.pos.y),
+        fmt_num(rect.size.x),
+        fmt_num(rect.size.y)
+    )
+}
+
+fn svg_fill(color: Vec4) -> String {
+    let mut fill = format!(" fill=\"#{:02x}{:02x}{:02x}\"", color_byte(color.x), color_byte(color.y), color_byte(color.z));
+    if color.w < 1.0 {
+        write!(fill, " fill-opacity=\"{}\"", fmt_num(color.w.max(0.0))).unwrap();
+    }
+    fill
+}
+
+fn pdf_rect(rect: Rect) -> String {
+    format!("{} {} {} {}", fmt_num(rect.pos.x), fmt_num(rect.pos.y), fmt_num(rect.size.x), fmt_num(rect.size.y))
+}
+
+/// Set the fill color, using a graphics state from `opacities` for transparent colors.
+fn pdf_fill(content: &mut String, color: Vec4, opacities: &mut Vec<f32>) {
+    let channel = |value: f32| fmt_num(value.clamp(0.0, 1.0));
+    writeln!(content, "{} {} {} rg", channel(color.x), channel(color.y), channel(color.z)).unwrap();
+    if color.w < 1.0 {
+        let opacity = color.w.max(0.0);
+        let index = opacities.iter().position(|other| *other == opacity).unwrap_or_else(|| {
+            opacities.push(opacity);
+            opacities.len() - 1
+        });
+        writeln!(content, "/GA{index} gs").unwrap();
+    }
+}
+
+fn base64_encode(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::with_capacity(data.len() * 4 / 3 + 4);
+    for chunk in data.chunks(3) {
+        let bytes = [chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+        let bits = (bytes[0] as u32) << 16 | (bytes[1] as u32) << 8 | bytes[2] as u32;
+        for index in 0..4 {
+            if index <= chunk.len() {
+                encoded.push(ALPHABET[(bits >> (18 - index * 6) & 63) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+/// Where a glyph lives in the fonts atlas, so that we can go back from [`TextIns::font_t1`] to the glyph outline.
+struct AtlasGlyphLocation {
+    font_id: usize,
+    glyph_id: usize,
+    dpi_factor: f32,
+}
+
+/// Walks the draw tree like the renderers do (see e.g. `render_view` in `cx_opengl.rs`), collecting [`VectorItem`]s.
+struct SceneExporter<'a> {
+    cx: &'a Cx,
+    fonts_data: &'a CxFontsData,
+    /// Lazily built, since it requires going through the entire atlas.
+    atlas_glyph_locations: Option<HashMap<(u32, u32), AtlasGlyphLocation>>,
+    items: Vec<VectorItem>,
+}
+
+impl<'a> SceneExporter<'a> {
+    fn export_view(&mut self, view_id: usize, scroll: Vec2, clip: (Vec2, Vec2)) {
+        let cxview = &self.cx.views[view_id];
+        let local_scroll = cxview.snapped_scroll;
+        let clip = (
+            vec2((cxview.rect.pos.x - scroll.x).max(clip.0.x), (cxview.rect.pos.y - scroll.y).max(clip.0.y)),
+            vec2(
+                (cxview.rect.pos.x + cxview.rect.size.x - scroll.x).min(clip.1.x),
+                (cxview.rect.pos.y + cxview.rect.size.y - scroll.y).min(clip.1.y),
+            ),
+        );
+        if clip.0.x >= clip.1.x || clip.0.y >= clip.1.y {
+            return;
+        }
+        for draw_call in &cxview.draw_calls[..cxview.draw_calls_len] {
+            if draw_call.sub_view_id != 0 {
+                self.export_view(draw_call.sub_view_id, local_scroll + scroll, clip);
+            } else {
+                let clip_rect = Rect { pos: clip.0, size: clip.1 - clip.0 };
+                self.export_draw_call(draw_call, draw_call.get_draw_scroll(scroll, local_scroll), clip_rect);
+            }
+        }
+    }
+
+    fn export_draw_call(&mut self, draw_call: &DrawCall, scroll: Vec2, clip: Rect) {
+        let sh = &self.cx.shaders[draw_call.shader_id];
+        let total_slots = sh.mapping.instance_props.total_slots;
+        if total_slots == 0 || draw_call.instances.is_empty() {
+            return;
+        }
+
+        if draw_call.shader_id == TEXT_INS_SHADER.shader_id.load(Ordering::Relaxed) {
+            self.export_text(cast_slice(&draw_call.instances), scroll, clip);
+            return;
+        }
+
+        let (rect_pos, rect_size) = match (sh.mapping.rect_instance_props.rect_pos, sh.mapping.rect_instance_props.rect_size) {
+            (Some(rect_pos), Some(rect_size)) => (rect_pos, rect_size),
+            _ => return,
+        };
+        let get_rect = |instance: &[f32]| Rect {
+            pos: vec2(instance[rect_pos], instance[rect_pos + 1]) - scroll,
+            size: vec2(instance[rect_size], instance[rect_size + 1]),
+        };
+
+        if let [texture_id] = draw_call.textures_2d[..] {
+            let cxtexture = &self.cx.textures[texture_id as usize];
+            if let (Some(width), Some(height)) = (cxtexture.desc.width, cxtexture.desc.height) {
+                if width > 0 && height > 0 && cxtexture.image_u32.len() == width * height {
+                    let image = CapturedFrame { width, height, pixels: cxtexture.image_u32.clone() };
+                    for instance in draw_call.instances.chunks_exact(total_slots) {
+                        let rect = get_rect(instance);
+                        if let Some(clip) = clip_for(rect, clip) {
+                            self.items.push(VectorItem { shape: VectorShape::Image { rect, image: image.clone() }, clip });
+                        }
+                    }
+                }
+            }
+            return;
+        }
+
+        let mut slot = 0;
+        let mut color_slot = None;
+        for prop in &sh.mapping.instance_props.props {
+            if prop.name == "color" && prop.slots == 4 {
+                color_slot = Some(slot);
+            }
+            slot += prop.slots;
+        }
+        if let Some(color_slot) = color_slot {
+            for instance in draw_call.instances.chunks_exact(total_slots) {
+                let color =
+                    vec4(instance[color_slot], instance[color_slot + 1], instance[color_slot + 2], instance[color_slot + 3]);
+                let rect = get_rect(instance);
+                // Clip rectangles directly, instead of adding a clip path.
+                let pos = vec2(rect.pos.x.max(clip.pos.x), rect.pos.y.max(clip.pos.y));
+                let end = vec2(
+                    (rect.pos.x + rect.size.x).min(clip.pos.x + clip.size.x),
+                    (rect.pos.y + rect.size.y).min(clip.pos.y + clip.size.y),
+                );
+                if color.w > 0.0 && pos.x < end.x && pos.y < end.y {
+                    self.items
+                        .push(VectorItem { shape: VectorShape::Rect { rect: Rect { pos, size: end - pos }, color }, clip: None });
+                }
+            }
+        }
+    }
+
+    fn export_text(&mut self, glyphs: &[TextIns], scroll: Vec2, clip: Rect) {
+        // Scoped to this function, since it conflicts with methods on [`Iterator`].
+        use zaplib_vector::internal_iter::InternalIterator;
+
+        let fonts_data = self.fonts_data;
+        let atlas_glyph_locations = self.atlas_glyph_locations.get_or_insert_with(|| {
+            let mut locations = HashMap::new();
+            for (font_id, cxfont) in fonts_data.fonts.iter().enumerate() {
+                for atlas_page in &cxfont.atlas_pages {
+                    for (&(glyph_id, _), entry) in &atlas_page.atlas_glyphs {
+                        locations.insert(
+                            (entry.tc.tx1.to_bits(), entry.tc.ty1.to_bits()),
+                            AtlasGlyphLocation { font_id, glyph_id, dpi_factor: atlas_page.dpi_factor },
+                        );
+                    }
+                }
+            }
+            locations
+        });
+
+        let first_item = self.items.len();
+        for glyph in glyphs {
+            // Characters without a glyph of their own (like newlines, or characters in a ligature) get an empty
+            // `TextIns`, which points at the start of the atlas. Color bitmaps (like emoji) are images, which we
+            // can't export as outlines.
+            if glyph.rect_size.x <= 0. || glyph.color_bitmap > 0.5 {
+                continue;
+            }
+            let location = match atlas_glyph_locations.get(&(glyph.font_t1.x.to_bits(), glyph.font_t1.y.to_bits())) {
+                Some(location) => location,
+                None => continue,
+            };
+            let font = match &fonts_data.fonts[location.font_id].font_loaded {
+                Some(font) => font,
+                None => continue,
+            };
+            let vector_glyph = &font.glyphs[location.glyph_id];
+            let bounds = vector_glyph.bounds;
+
+            // Recover the `font_scale` that was passed into `TextIns::generate_2d_glyphs` from the width of the
+            // glyph's rectangle.
+            let font_size_logical = glyph.font_size * 96.0 / (72.0 * font.units_per_em);
+            let atlas_width = ((bounds.p_max.x - bounds.p_min.x) * font_size_logical * location.dpi_factor).ceil() + 1.0;
+            let scale = font_size_logical * glyph.rect_size.x * location.dpi_factor / atlas_width;
+
+            // `base.x` is where the glyph starts, and the bottom of the glyph's rectangle is at `bounds.p_min.y`.
+            let origin = vec2(glyph.base.x, glyph.rect_pos.y + bounds.p_min.y * scale) - scroll;
+            let glyph_rect =
+                Rect { pos: vec2(glyph.rect_pos.x, glyph.rect_pos.y - glyph.rect_size.y) - scroll, size: glyph.rect_size };
+            let item_clip = match clip_for(glyph_rect, clip) {
+                Some(item_clip) => item_clip,
+                None => continue,
+            };
+
+            let mut segments = Vec::new();
+            let to_vec2 = |point: zaplib_vector::geometry::Point| vec2(origin.x + point.x * scale, origin.y - point.y * scale);
+            vector_glyph.outline.commands().for_each(&mut |command| {
+                segments.push(match command {
+                    PathCommand::MoveTo(p) => PathSegment::MoveTo(to_vec2(p)),
+                    PathCommand::LineTo(p) => PathSegment::LineTo(to_vec2(p)),
+                    PathCommand::QuadraticTo(c, p) => PathSegment::QuadraticTo(to_vec2(c), to_vec2(p)),
+                    PathCommand::Close => PathSegment::Close,
+                });
+                true
+            });
+            if segments.is_empty() {
+                continue;
+            }
+
+            // Merge glyphs of the same run into a single path.
+            if self.items.len() > first_item {
+                let last_item = self.items.last_mut().unwrap();
+                if let VectorShape::Path { segments: last_segments, color } = &mut last_item.shape {
+                    if *color == glyph.color && last_item.clip == item_clip {
+                        last_segments.append(&mut segments);
+                        continue;
+                    }
+                }
+            }
+            self.items.push(VectorItem { shape: VectorShape::Path { segments, color: glyph.color }, clip: item_clip });
+        }
+    }
+}
+
+/// Returns the [`VectorItem::clip`] for a shape with bounds `rect`, or [`None`] if it's not visible at all.
+fn clip_for(rect: Rect, clip: Rect) -> Option<Option<Rect>> {
+    let clip_end = clip.pos + clip.size;
+    let rect_end = rect.pos + rect.size;
+    if rect.pos.x >= clip_end.x || rect.pos.y >= clip_end.y || rect_end.x <= clip.pos.x || rect_end.y <= clip.pos.y {
+        None
+    } else if rect.pos.x >= clip.pos.x && rect.pos.y >= clip.pos.y && rect_end.x <= clip_end.x && rect_end.y <= clip_end.y {
+        Some(None)
+    } else {
+        Some(Some(clip))
+    }
+}
+
+impl Cx {
+    /// Export what was last drawn in the main window as a [`VectorScene`]. See [`crate::vector_export`] for what
+    /// can be exported.
+    ///
+    /// ```ignore
+    /// let scene = cx.export_vector_scene();
+    /// std::fs::write("chart.svg", scene.to_svg()).unwrap();
+    /// std::fs::write("chart.pdf", scene.to_pdf()).unwrap();
+    /// ```
+    pub fn export_vector_scene(&self) -> VectorScene {
+        let mut scene = VectorScene::default();
+        let pass_id = match self.windows.first().and_then(|window| window.main_pass_id) {
+            Some(pass_id) => pass_id,
+            None => return scene,
+        };
+        let cxpass = &self.passes[pass_id];
+        scene.size = cxpass.pass_size;
+        scene.background_color = cxpass.color_textures.first().map(|color_texture| match color_texture.clear_color {
+            ClearColor::InitWith(color) | ClearColor::ClearWith(color) => color,
+        });
+        if let Some(view_id) = cxpass.main_view_id {
+            let fonts_data = self.fonts_data.read().unwrap();
+            let mut exporter =
+                SceneExporter { cx: self, fonts_data: &fonts_data, atlas_glyph_locations: None, items: Vec::new() };
+            exporter.export_view(view_id, Vec2::default(), (vec2(-50000., -50000.), vec2(50000., 50000.)));
+            scene.items = exporter.items;
+        }
+        scene
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_scene() -> VectorScene {
+        VectorScene {
+            size: vec2(100., 50.),
+            background_color: Some(vec4(1., 1., 1., 1.)),
+            items: vec![
+                VectorItem {
+                    shape: VectorShape::Rect {
+                        rect: Rect { pos: vec2(10., 5.), size: vec2(20.5, 10.) },
+                        color: vec4(1., 0., 0., 0.5),
+                    },
+                    clip: None,
+                },
+                VectorItem {
+                    shape: VectorShape::Path {
+                        segments: vec![
+                            PathSegment::MoveTo(vec2(0., 0.)),
+                            PathSegment::QuadraticTo(vec2(3., 0.), vec2(3., 3.)),
+                            PathSegment::Close,
+                        ],
+                        color: vec4(0., 0., 1., 1.),
+                    },
+                    clip: Some(Rect { pos: vec2(0., 0.), size: vec2(2., 2.) }),
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn test_fmt_num() {
+        assert_eq!(fmt_num(1.0), "1");
+        assert_eq!(fmt_num(0.25), "0.25");
+        assert_eq!(fmt_num(-0.0001), "0");
+        assert_eq!(fmt_num(1.0 / 3.0), "0.333");
+    }
+
+    #[test]
+    fn test_base64_encode() {
+        assert_eq!(base64_encode(b""), "");
+        assert_eq!(base64_encode(b"f"), "Zg==");
+        assert_eq!(base64_encode(b"fo"), "Zm8=");
+        assert_eq!(base64_encode(b"foobar"), "Zm9vYmFy");
+    }
+
+    #[test]
+    fn test_to_svg() {
+        assert_eq!(
+            test_scene().to_svg(),
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"100\" height=\"50\" viewBox=\"0 0 100 50\">\n<rect width=\"100\" \
+             height=\"50\" fill=\"#ffffff\"/>\n<rect x=\"10\" y=\"5\" width=\"20.5\" height=\"10\" fill=\"#ff0000\" \
+             fill-opacity=\"0.5\"/>\n<clipPath id=\"clip0\"><rect x=\"0\" y=\"0\" width=\"2\" height=\"2\"/></clipPath>\n<path \
+             d=\"M0 0Q3 0 3 3Z\" fill=\"#0000ff\" clip-path=\"url(#clip0)\"/>\n</svg>\n"
+        );
+    }
+
+    #[test]
+    fn test_to_pdf() {
+        let pdf = test_scene().to_pdf();
+        assert!(pdf.starts_with(b"%PDF-1.4\n"));
+        assert!(pdf.ends_with(b"%%EOF\n"));
+
+        // Every entry in the cross-reference table should point at the start of its object.
+        let text = String::from_utf8_lossy(&pdf);
+        let xref = &text[text.rfind("\nxref\n").unwrap() + 1..];
+        let offsets: Vec<usize> =
+            xref.lines().skip(3).take_while(|line| line.ends_with(" n ")).map(|line| line[..10].parse().unwrap()).collect();
+        assert_eq!(offsets.len(), 4);
+        for (index, offset) in offsets.into_iter().enumerate() {
+            assert!(pdf[offset..].starts_with(format!("{} 0 obj\n", index + 1).as_bytes()));
+        }
+        assert!(text.contains("/MediaBox [0 0 75 37.5]"));
+        assert!(text.contains("/GA0 << /ca 0.5 >>"));
+    }
+
+    #[test]
+    fn test_to_paged_pdf() {
+        // With a 2 point margin, pages fit 40 points of the scene, i.e. 20 logical pixels, so we need 3 pages.
+        let pdf = test_scene().to_paged_pdf(vec2(204., 44.), 2.);
+        let text = String::from_utf8_lossy(&pdf);
+        assert!(text.contains("/Kids [3 0 R 5 0 R 7 0 R] /Count 3"));
+        assert_eq!(text.matches("/MediaBox [0 0 204 44]").count(), 3);
+    }
+}
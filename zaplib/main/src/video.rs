@@ -0,0 +1,740 @@
+//! Playing video into a [`Texture`], so that it can be composited inside a scene, e.g. in annotation tools.
+//!
+//! ```ignore
+//! // In `handle`:
+//! if let Event::PointerUp(_) = event {
+//!     self.video.load(cx, "https://example.com/video.mp4");
+//! }
+//! match self.video.handle(cx, event) {
+//!     Some(VideoEvent::Loaded(_)) => self.video.play(cx),
+//!     Some(VideoEvent::Error(err)) => log!("{}", err),
+//!     _ => {}
+//! }
+//!
+//! // In `draw`:
+//! if let Some(texture_handle) = self.video.texture_handle() {
+//!     ImageIns::draw(cx, rect, texture_handle);
+//! }
+//! ```
+//!
+//! [`VideoTexture::handle`] uploads a new frame to the texture whenever there is one, and calls [`Cx::request_draw`]
+//! so you can redraw.
+//!
+//! Backends:
+//! * WebAssembly: an `HTMLVideoElement`. For every frame we draw the video into a canvas, and copy its pixels into
+//!   Wasm memory. Videos from other origins need CORS headers for this to work. Browsers only allow playing videos
+//!   with sound in response to user input, so call [`VideoTexture::play`] when handling e.g. [`Event::PointerDown`],
+//!   or [mute](VideoTexture::set_muted) the video first; otherwise you get [`VideoError::NotAllowed`].
+//! * Native: `ffmpeg` and `ffprobe`, which need to be installed and on the `PATH`, run as separate processes. We
+//!   don't play audio on native platforms.
+
+use crate::*;
+use std::fmt;
+
+/// Information about a loaded video, as given by [`VideoEvent::Loaded`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct VideoInfo {
+    /// Width of the video (and thus the texture) in pixels.
+    pub width: usize,
+    /// Height of the video (and thus the texture) in pixels.
+    pub height: usize,
+    /// Duration in seconds. Infinite for live streams.
+    pub duration: f64,
+}
+
+/// Returned by [`VideoTexture::handle`].
+#[derive(Clone, Debug, PartialEq)]
+pub enum VideoEvent {
+    /// The video passed to [`VideoTexture::load`] is loaded, and its first frame is on its way to the texture.
+    Loaded(VideoInfo),
+    /// Playback reached the end of the video.
+    Ended,
+    /// Loading, playing, or decoding failed.
+    Error(VideoError),
+}
+
+/// Why loading or playing a video failed.
+#[derive(Clone, Debug, PartialEq)]
+pub enum VideoError {
+    /// The browser did not allow playing the video with sound, because it wasn't started in response to user input.
+    NotAllowed,
+    /// The video format is not supported, or there is no decoder (e.g. `ffmpeg` is not installed).
+    NotSupported(String),
+    /// Any other error, e.g. when the video doesn't exist.
+    Other(String),
+}
+
+impl fmt::Display for VideoError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VideoError::NotAllowed => write!(f, "Video is not allowed to play without user input"),
+            VideoError::NotSupported(message) => write!(f, "Video not supported: {}", message),
+            VideoError::Other(message) => write!(f, "Video error: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for VideoError {}
+
+#[cfg(target_arch = "wasm32")]
+impl From<CallJsError> for VideoError {
+    fn from(err: CallJsError) -> Self {
+        match err {
+            // See `video.ts`.
+            CallJsError::Thrown(message) if message.starts_with("NotAllowedError") => VideoError::NotAllowed,
+            CallJsError::Thrown(message) if message.starts_with("NotSupportedError") => VideoError::NotSupported(message),
+            CallJsError::Thrown(message) => VideoError::Other(message),
+            CallJsError::TimedOut => VideoError::Other("Timed out".to_string()),
+        }
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+use web::Video;
+
+#[cfg(not(target_arch = "wasm32"))]
+use native::Video;
+
+/// A [`Texture`] that shows the current frame of a video, with controls for playback.
+///
+/// Call [`VideoTexture::handle`] for every event. See the [module documentation](self) for an example.
+#[derive(Default)]
+pub struct VideoTexture {
+    texture: Texture,
+    video: Option<Video>,
+    info: Option<VideoInfo>,
+    load_task: Option<Task<Result<(Video, VideoInfo), VideoError>>>,
+    is_playing: bool,
+    is_muted: bool,
+    current_time: f64,
+    /// Whether to update the texture even when not playing, e.g. after loading or seeking.
+    needs_frame: bool,
+    /// Frame being copied into by JS, and the playback state at that time.
+    #[cfg(target_arch = "wasm32")]
+    frame_task: Option<Task<web::FrameOutput>>,
+    /// Buffer for the next [`VideoTexture::frame_task`], reused between frames.
+    #[cfg(target_arch = "wasm32")]
+    frame_buffer: Vec<u32>,
+    /// Pending calls to play or seek, which resolve with whether a new frame is needed.
+    #[cfg(target_arch = "wasm32")]
+    control_tasks: Vec<Task<Result<bool, VideoError>>>,
+}
+
+impl VideoTexture {
+    /// Start loading a video from a URL (or on native platforms, also a file path), replacing any current video.
+    /// [`VideoTexture::handle`] returns [`VideoEvent::Loaded`] once it's ready to play.
+    pub fn load(&mut self, cx: &mut Cx, url: &str) {
+        self.video = None;
+        self.info = None;
+        self.is_playing = false;
+        self.current_time = 0.;
+        self.needs_frame = false;
+        #[cfg(target_arch = "wasm32")]
+        {
+            self.frame_task = None;
+            self.control_tasks.clear();
+        }
+        let future = Video::load(cx, url, self.is_muted);
+        self.load_task = Some(cx.spawn(future));
+    }
+
+    /// Handle an event. Returns a [`VideoEvent`] when loading completed, the video ended, or something failed.
+    pub fn handle(&mut self, cx: &mut Cx, event: &mut Event) -> Option<VideoEvent> {
+        if let Some(result) = self.load_task.as_ref().and_then(|task| task.take_output(event)) {
+            self.load_task = None;
+            return match result {
+                Ok((video, info)) => {
+                    if let Some(texture_handle) = self.texture.handle {
+                        texture_handle.set_dimensions(cx, info.width, info.height);
+                    } else {
+                        self.texture.get_with_dimensions(cx, info.width, info.height);
+                    }
+                    self.video = Some(video);
+                    self.info = Some(info.clone());
+                    self.needs_frame = true;
+                    cx.request_next_frame();
+                    Some(VideoEvent::Loaded(info))
+                }
+                Err(err) => Some(VideoEvent::Error(err)),
+            };
+        }
+
+        #[cfg(target_arch = "wasm32")]
+        {
+            if let Some(video_event) = self.handle_web_tasks(cx, event) {
+                return Some(video_event);
+            }
+        }
+
+        if let Event::NextFrame = event {
+            return self.next_frame(cx);
+        }
+        None
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn handle_web_tasks(&mut self, cx: &mut Cx, event: &mut Event) -> Option<VideoEvent> {
+        let mut control_result = None;
+        self.control_tasks.retain(|task| match task.take_output(event) {
+            Some(result) => {
+                control_result = Some(result);
+                false
+            }
+            None => true,
+        });
+        match control_result {
+            Some(Ok(true)) => {
+                self.needs_frame = true;
+                cx.request_next_frame();
+            }
+            Some(Err(err)) => {
+                self.is_playing = false;
+                return Some(VideoEvent::Error(err));
+            }
+            Some(Ok(false)) | None => {}
+        }
+
+        let (frame_buffer, result) = self.frame_task.as_ref().and_then(|task| task.take_output(event))?;
+        self.frame_task = None;
+        self.video.as_ref()?;
+        match result {
+            Ok(state) => {
+                if let Some(texture_handle) = self.texture.handle {
+                    texture_handle.get_image_mut(cx).copy_from_slice(&frame_buffer);
+                    cx.request_draw();
+                }
+                self.frame_buffer = frame_buffer;
+                self.current_time = state.current_time;
+                if self.is_playing {
+                    if state.ended {
+                        self.is_playing = false;
+                        return Some(VideoEvent::Ended);
+                    }
+                    cx.request_next_frame();
+                }
+                None
+            }
+            Err(err) => {
+                self.is_playing = false;
+                Some(VideoEvent::Error(err))
+            }
+        }
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn next_frame(&mut self, cx: &mut Cx) -> Option<VideoEvent> {
+        let (video, info) = (self.video.as_ref()?, self.info.as_ref()?);
+        if (self.is_playing || self.needs_frame) && self.frame_task.is_none() {
+            self.needs_frame = false;
+            let mut frame_buffer = std::mem::take(&mut self.frame_buffer);
+            frame_buffer.resize(info.width * info.height, 0);
+            let future = video.copy_frame(cx, frame_buffer, info.width, info.height);
+            self.frame_task = Some(cx.spawn(async move {
+                let (frame_buffer, result) = future.await;
+                (frame_buffer, result.map_err(VideoError::from))
+            }));
+        }
+        None
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn next_frame(&mut self, cx: &mut Cx) -> Option<VideoEvent> {
+        let video = self.video.as_mut()?;
+        if !self.is_playing && !self.needs_frame {
+            return None;
+        }
+        let result = video.next_frame();
+        self.current_time = video.current_time();
+        match result {
+            Ok(native::NextFrame { frame, ended }) => {
+                if let (Some(frame), Some(texture_handle)) = (frame, self.texture.handle) {
+                    texture_handle.get_image_mut(cx).copy_from_slice(&frame);
+                    cx.request_draw();
+                    self.needs_frame = false;
+                }
+                if ended {
+                    self.needs_frame = false;
+                    if self.is_playing {
+                        self.is_playing = false;
+                        video.pause();
+                        return Some(VideoEvent::Ended);
+                    }
+                    return None;
+                }
+                cx.request_next_frame();
+                None
+            }
+            Err(err) => {
+                self.is_playing = false;
+                self.needs_frame = false;
+                video.pause();
+                Some(VideoEvent::Error(err))
+            }
+        }
+    }
+
+    /// Start or resume playing. When the video has ended, this starts playing from the beginning.
+    pub fn play(&mut self, cx: &mut Cx) {
+        if let (Some(video), false) = (&mut self.video, self.is_playing) {
+            self.is_playing = true;
+            #[cfg(target_arch = "wasm32")]
+            {
+                let play = video.play(cx);
+                self.control_tasks.push(cx.spawn(play));
+            }
+            #[cfg(not(target_arch = "wasm32"))]
+            video.play();
+            cx.request_next_frame();
+        }
+    }
+
+    /// Pause playing. The texture keeps showing the current frame.
+    pub fn pause(&mut self, cx: &mut Cx) {
+        if let (Some(video), true) = (&mut self.video, self.is_playing) {
+            self.is_playing = false;
+            #[cfg(target_arch = "wasm32")]
+            video.pause(cx);
+            #[cfg(not(target_arch = "wasm32"))]
+            {
+                let _ = cx;
+                video.pause();
+            }
+        }
+    }
+
+    /// Jump to `time` (in seconds). The texture gets updated with the frame at that time, also when paused.
+    pub fn seek(&mut self, cx: &mut Cx, time: f64) {
+        if let (Some(video), Some(info)) = (&mut self.video, &self.info) {
+            self.current_time = time.max(0.).min(info.duration);
+            #[cfg(target_arch = "wasm32")]
+            {
+                let seek = video.seek(cx, self.current_time);
+                self.control_tasks.push(cx.spawn(seek));
+            }
+            #[cfg(not(target_arch = "wasm32"))]
+            {
+                video.seek(self.current_time);
+                self.needs_frame = true;
+                cx.request_next_frame();
+            }
+        }
+    }
+
+    /// Mute or unmute the video. Videos are not muted by default. Has no effect on native platforms, where we don't
+    /// play audio at all.
+    pub fn set_muted(&mut self, cx: &mut Cx, is_muted: bool) {
+        self.is_muted = is_muted;
+        #[cfg(target_arch = "wasm32")]
+        if let Some(video) = &self.video {
+            video.set_muted(cx, is_muted);
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        let _ = cx;
+    }
+
+    /// Whether the video is playing, i.e. [`VideoTexture::play`] was called and it hasn't been paused or ended since.
+    pub fn is_playing(&self) -> bool {
+        self.is_playing
+    }
+
+    /// Whether the video is muted; see [`VideoTexture::set_muted`].
+    pub fn is_muted(&self) -> bool {
+        self.is_muted
+    }
+
+    /// Playback position in seconds, as of the last frame.
+    pub fn current_time(&self) -> f64 {
+        self.current_time
+    }
+
+    /// Information about the loaded video, or [`None`] if there's no video or it's still loading.
+    pub fn info(&self) -> Option<&VideoInfo> {
+        self.info.as_ref()
+    }
+
+    /// The texture to draw, e.g. using [`ImageIns::draw`]. [`None`] until the first video has loaded.
+    pub fn texture_handle(&self) -> Option<TextureHandle> {
+        self.texture.handle
+    }
+}
+
+/// Backend based on an `HTMLVideoElement`; see `video.ts`. Also used by [`crate::camera`].
+#[cfg(target_arch = "wasm32")]
+pub(crate) mod web {
+    use super::*;
+    use once_cell::sync::Lazy;
+    use std::future::Future;
+    use std::sync::atomic::{AtomicI32, Ordering};
+    use std::sync::Mutex;
+
+    /// Values of the status that is shared with JS. Keep in sync with `video.ts`.
+    const STATUS_ALIVE: i32 = 0;
+    const STATUS_DROPPED: i32 = 1;
+    const STATUS_REMOVED: i32 = 2;
+
+    /// Statuses of dropped videos, which we free once JS has removed the video element.
+    static DROPPED_STATUSES: Lazy<Mutex<Vec<usize>>> = Lazy::new(|| Mutex::new(Vec::new()));
+
+    /// Playback state when a frame was copied.
+    pub(crate) struct FrameState {
+        pub(crate) current_time: f64,
+        pub(crate) ended: bool,
+    }
+
+    /// The buffer that a frame was copied into (also on errors, so it can be reused), and the playback state.
+    pub(crate) type FrameOutput<E = VideoError> = (Vec<u32>, Result<FrameState, E>);
+
+    /// A video element in JS. When dropped, we set the status to [`STATUS_DROPPED`], after which JS removes the video
+    /// element (it checks regularly).
+    pub(crate) struct Video {
+        id: u32,
+        /// Pointer to a leaked [`AtomicI32`] that is shared with JS.
+        status_ptr: usize,
+    }
+
+    impl Video {
+        /// Call JS function `name` that creates a video element, passing `params` and the status pointer. It should
+        /// return the id of the video element, followed by any other values, which we return.
+        pub(crate) fn open(
+            cx: &mut Cx,
+            name: &str,
+            mut params: Vec<ZapParam>,
+        ) -> impl Future<Output = Result<(Video, Vec<String>), CallJsError>> {
+            free_removed_statuses();
+            let status_ptr = Box::into_raw(Box::new(AtomicI32::new(STATUS_ALIVE))) as usize;
+            params.push(status_ptr.to_string().into_param());
+            let future = cx.call_js_async(name, params, None);
+            async move {
+                match future.await {
+                    Ok(params) => {
+                        let mut params = params.into_iter().map(ZapParam::into_string);
+                        let video = Video { id: params.next().unwrap().parse().unwrap(), status_ptr };
+                        Ok((video, params.collect()))
+                    }
+                    Err(err) => {
+                        // JS didn't create a video element, so nothing else uses the status.
+                        drop(unsafe { Box::from_raw(status_ptr as *mut AtomicI32) });
+                        Err(err)
+                    }
+                }
+            }
+        }
+
+        pub(super) fn load(
+            cx: &mut Cx,
+            url: &str,
+            is_muted: bool,
+        ) -> impl Future<Output = Result<(Video, VideoInfo), VideoError>> {
+            let params = vec![url.to_string().into_param(), if is_muted { "1" } else { "0" }.to_string().into_param()];
+            let future = Self::open(cx, "_zaplibVideoLoad", params);
+            async move {
+                let (video, params) = future.await?;
+                let parse_error = || VideoError::Other("Invalid video metadata".to_string());
+                let info = VideoInfo {
+                    width: params[0].parse().map_err(|_| parse_error())?,
+                    height: params[1].parse().map_err(|_| parse_error())?,
+                    duration: params[2].parse().map_err(|_| parse_error())?,
+                };
+                Ok((video, info))
+            }
+        }
+
+        /// Copy the current frame into `frame_buffer`, which should have `width * height` pixels.
+        pub(crate) fn copy_frame(
+            &self,
+            cx: &mut Cx,
+            frame_buffer: Vec<u32>,
+            width: usize,
+            height: usize,
+        ) -> impl Future<Output = FrameOutput<CallJsError>> {
+            let params = vec![
+                self.id.to_string().into_param(),
+                (frame_buffer.as_ptr() as usize).to_string().into_param(),
+                width.to_string().into_param(),
+                height.to_string().into_param(),
+            ];
+            let future = cx.call_js_async("_zaplibVideoCopyFrame", params, None);
+            // We own `frame_buffer` until JS is done writing to it, even if the `VideoTexture` is dropped.
+            async move {
+                let result = future.await.map(|params| FrameState {
+                    current_time: params[0].as_str().parse().unwrap_or(0.),
+                    ended: params[1].as_str() == "1",
+                });
+                (frame_buffer, result)
+            }
+        }
+
+        /// Resolves with `false` once playing; see [`VideoTexture::control_tasks`].
+        pub(super) fn play(&self, cx: &mut Cx) -> impl Future<Output = Result<bool, VideoError>> {
+            let future = cx.call_js_async("_zaplibVideoPlay", vec![self.id.to_string().into_param()], None);
+            async move {
+                future.await?;
+                Ok(false)
+            }
+        }
+
+        pub(super) fn pause(&self, cx: &mut Cx) {
+            cx.call_js("_zaplibVideoPause", vec![self.id.to_string().into_param()]);
+        }
+
+        /// Resolves with `true` once the frame at `time` is available; see [`VideoTexture::control_tasks`].
+        pub(super) fn seek(&self, cx: &mut Cx, time: f64) -> impl Future<Output = Result<bool, VideoError>> {
+            let params = vec![self.id.to_string().into_param(), time.to_string().into_param()];
+            let future = cx.call_js_async("_zaplibVideoSeek", params, None);
+            async move {
+                future.await?;
+                Ok(true)
+            }
+        }
+
+        pub(super) fn set_muted(&self, cx: &mut Cx, is_muted: bool) {
+            let params = vec![self.id.to_string().into_param(), if is_muted { "1" } else { "0" }.to_string().into_param()];
+            cx.call_js("_zaplibVideoSetMuted", params);
+        }
+    }
+
+    impl Drop for Video {
+        fn drop(&mut self) {
+            unsafe { &*(self.status_ptr as *const AtomicI32) }.store(STATUS_DROPPED, Ordering::SeqCst);
+            DROPPED_STATUSES.lock().unwrap().push(self.status_ptr);
+        }
+    }
+
+    fn free_removed_statuses() {
+        DROPPED_STATUSES.lock().unwrap().retain(|&status_ptr| {
+            let status = unsafe { &*(status_ptr as *const AtomicI32) };
+            if status.load(Ordering::SeqCst) == STATUS_REMOVED {
+                drop(unsafe { Box::from_raw(status_ptr as *mut AtomicI32) });
+                false
+            } else {
+                true
+            }
+        });
+    }
+}
+
+/// Backend that runs `ffprobe` to get information about the video, and `ffmpeg` to decode it to raw RGBA frames.
+///
+/// Decoding happens on a separate thread, which puts frames in a small queue; see [`crate::ffmpeg`]. The main thread
+/// takes them out when it's time to show them, based on a clock that starts when playing. When the queue is full,
+/// decoding blocks, which is also how pausing works. Seeking restarts `ffmpeg`.
+#[cfg(not(target_arch = "wasm32"))]
+mod native {
+    use super::*;
+    use crate::ffmpeg::{Decoder, DecoderOutput, FfmpegError};
+    use std::future::Future;
+    use std::process::{Command, Stdio};
+
+    /// Frame rate to use when `ffprobe` doesn't know it.
+    const DEFAULT_FRAME_RATE: &str = "30";
+
+    /// A decoded frame and its time in seconds.
+    type Frame = (f64, Vec<u32>);
+
+    /// Result of [`Video::next_frame`].
+    pub(super) struct NextFrame {
+        /// The frame to show now, if there's a new one.
+        pub(super) frame: Option<Vec<u32>>,
+        /// Whether all frames have been shown.
+        pub(super) ended: bool,
+    }
+
+    pub(super) struct Video {
+        url: String,
+        info: VideoInfo,
+        /// Frame rate as given by `ffprobe`, e.g. "30000/1001".
+        frame_rate: String,
+        decoder: Option<Decoder>,
+        /// Time of the first frame of `decoder`.
+        decoder_start_time: f64,
+        /// Number of frames we got from `decoder`, to compute the time of the next one.
+        decoded_frame_count: usize,
+        /// The time at `started_at`, or the current time when paused.
+        base_time: f64,
+        started_at: Option<UniversalInstant>,
+        /// The next frame, when it isn't time yet to show it.
+        pending: Option<Frame>,
+        ended: bool,
+    }
+
+    impl Video {
+        pub(super) fn load(
+            cx: &mut Cx,
+            url: &str,
+            _is_muted: bool,
+        ) -> impl Future<Output = Result<(Video, VideoInfo), VideoError>> {
+            let _ = cx;
+            let url = url.to_string();
+            spawn_blocking(move || {
+                let output = Command::new("ffprobe")
+                    .args(["-v", "error", "-select_streams", "v:0"])
+                    .args(["-show_entries", "stream=width,height,r_frame_rate:format=duration"])
+                    .args(["-of", "default=noprint_wrappers=1"])
+                    .arg(&url)
+                    .stdin(Stdio::null())
+                    .output()
+                    .map_err(|err| VideoError::from(FfmpegError::from(err)))?;
+                if !output.status.success() {
+                    return Err(VideoError::Other(String::from_utf8_lossy(&output.stderr).trim().to_string()));
+                }
+                let (info, frame_rate) = parse_probe_output(&String::from_utf8_lossy(&output.stdout))?;
+                let video = Video {
+                    url,
+                    info: info.clone(),
+                    frame_rate,
+                    decoder: None,
+                    decoder_start_time: 0.,
+                    decoded_frame_count: 0,
+                    base_time: 0.,
+                    started_at: None,
+                    pending: None,
+                    ended: false,
+                };
+                Ok((video, info))
+            })
+        }
+
+        pub(super) fn current_time(&self) -> f64 {
+            let elapsed = self.started_at.map_or(0., |started_at| started_at.elapsed().as_secs_f64());
+            (self.base_time + elapsed).min(self.info.duration)
+        }
+
+        pub(super) fn play(&mut self) {
+            if self.ended {
+                self.seek(0.);
+            }
+            self.started_at = Some(UniversalInstant::now());
+        }
+
+        pub(super) fn pause(&mut self) {
+            self.base_time = self.current_time();
+            self.started_at = None;
+        }
+
+        pub(super) fn seek(&mut self, time: f64) {
+            self.base_time = time;
+            if self.started_at.is_some() {
+                self.started_at = Some(UniversalInstant::now());
+            }
+            // Restarted by `next_frame`.
+            self.decoder = None;
+            self.pending = None;
+            self.ended = false;
+        }
+
+        /// Get the latest frame that is due, starting `ffmpeg` if needed.
+        pub(super) fn next_frame(&mut self) -> Result<NextFrame, VideoError> {
+            if self.ended {
+                return Ok(NextFrame { frame: None, ended: true });
+            }
+            if self.decoder.is_none() {
+                let start_time = self.base_time.to_string();
+                // Output a constant frame rate, so that we can compute the time of every frame.
+                self.decoder = Some(Decoder::start(
+                    &["-ss", &start_time, "-i", &self.url],
+                    &["-r", &self.frame_rate],
+                    self.info.width,
+                    self.info.height,
+                )?);
+                self.decoder_start_time = self.base_time;
+                self.decoded_frame_count = 0;
+            }
+            let frames_per_second = parse_frame_rate(&self.frame_rate).unwrap();
+            let now = self.current_time();
+            let decoder = self.decoder.as_ref().unwrap();
+            let mut frame = None;
+            loop {
+                if self.pending.is_none() {
+                    match decoder.try_next()? {
+                        DecoderOutput::Frame(pixels) => {
+                            let time = self.decoder_start_time + self.decoded_frame_count as f64 / frames_per_second;
+                            self.decoded_frame_count += 1;
+                            self.pending = Some((time, pixels));
+                        }
+                        DecoderOutput::Pending => break,
+                        DecoderOutput::Ended => {
+                            self.ended = true;
+                            break;
+                        }
+                    }
+                }
+                // Show frames up to half a frame early, so that we don't lag behind.
+                match &self.pending {
+                    Some((time, _)) if *time <= now + 0.5 / frames_per_second => {
+                        frame = self.pending.take().map(|(_, pixels)| pixels);
+                    }
+                    _ => break,
+                }
+            }
+            Ok(NextFrame { frame, ended: self.ended })
+        }
+    }
+
+    impl From<FfmpegError> for VideoError {
+        fn from(err: FfmpegError) -> Self {
+            match err {
+                FfmpegError::NotInstalled => VideoError::NotSupported("Playing video requires ffmpeg and ffprobe".to_string()),
+                FfmpegError::Failed(message) => VideoError::Other(message),
+            }
+        }
+    }
+
+    /// Parse the output of `ffprobe` with `-of default=noprint_wrappers=1`, which has lines like "width=1920".
+    /// Returns the [`VideoInfo`] and the frame rate.
+    pub(super) fn parse_probe_output(output: &str) -> Result<(VideoInfo, String), VideoError> {
+        let mut width = None;
+        let mut height = None;
+        let mut duration = f64::INFINITY;
+        let mut frame_rate = DEFAULT_FRAME_RATE.to_string();
+        for line in output.lines() {
+            match line.trim().split_once('=') {
+                Some(("width", value)) => width = value.parse().ok(),
+                Some(("height", value)) => height = value.parse().ok(),
+                // "N/A" for live streams.
+                Some(("duration", value)) => duration = value.parse().unwrap_or(f64::INFINITY),
+                // "0/0" when unknown.
+                Some(("r_frame_rate", value)) if parse_frame_rate(value).is_some() => frame_rate = value.to_string(),
+                _ => {}
+            }
+        }
+        match (width, height) {
+            (Some(width), Some(height)) if width > 0 && height > 0 => Ok((VideoInfo { width, height, duration }, frame_rate)),
+            _ => Err(VideoError::NotSupported("No video stream found".to_string())),
+        }
+    }
+
+    /// Parse a frame rate like "30000/1001" or "25".
+    pub(super) fn parse_frame_rate(frame_rate: &str) -> Option<f64> {
+        let frames_per_second = match frame_rate.split_once('/') {
+            Some((numerator, denominator)) => numerator.parse::<f64>().ok()? / denominator.parse::<f64>().ok()?,
+            None => frame_rate.parse().ok()?,
+        };
+        if frames_per_second.is_finite() && frames_per_second > 0. {
+            Some(frames_per_second)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use super::native::*;
+    use super::*;
+
+    #[test]
+    fn test_parse_probe_output() {
+        let (info, frame_rate) =
+            parse_probe_output("width=1920\nheight=1080\nr_frame_rate=30000/1001\nduration=12.345000\n").unwrap();
+        assert_eq!(info, VideoInfo { width: 1920, height: 1080, duration: 12.345 });
+        assert_eq!(frame_rate, "30000/1001");
+        assert!((parse_frame_rate(&frame_rate).unwrap() - 29.97).abs() < 0.001);
+
+        // Live streams have no duration, and the frame rate can be unknown.
+        let (info, frame_rate) = parse_probe_output("width=640\nheight=480\nr_frame_rate=0/0\nduration=N/A\n").unwrap();
+        assert_eq!(info.duration, f64::INFINITY);
+        assert_eq!(frame_rate, "30");
+
+        assert!(matches!(parse_probe_output("duration=1.0\n"), Err(VideoError::NotSupported(_))));
+    }
+}
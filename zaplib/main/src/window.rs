@@ -1,4 +1,20 @@
-//! Managing individual windows on native platforms.
+//! Managing windows.
+//!
+//! On native platforms every [`Window`] is an OS window. An app can open several of them, e.g. for tool palettes
+//! or detachable inspectors; they all share the same textures and shaders, and events for all of them go through
+//! the same event handler. Use [`Window::is_event_for_window`] to only pass pointer events of a window to the
+//! components drawn in it.
+//!
+//! On the web, the first window is drawn into the main canvas of the page, and other windows into their own canvas;
+//! see [`Window::create_canvas_id`].
+//!
+//! The title, icon, size limits, resizability and decorations of a window can be set when creating it (using the
+//! `create_*` fields of [`Window`]) and changed later (using e.g. [`Window::set_title`]). Most of these only apply to
+//! native windows; on the web we only set the document title for the first window, and use the browser's Fullscreen
+//! API for [`Window::fullscreen_window`].
+//!
+//! [`Cx::displays`] lists the connected monitors, and [`Window::create_display`] and [`Window::move_to_display`] put
+//! a window on one of them.
 
 use crate::*;
 
@@ -6,7 +22,7 @@ use crate::*;
 use crate::cef_browser::GetResourceUrlCallback;
 
 /// A pointer to a [`CxWindow`] (indexed in [`Cx::windows`] using [`Window::window_id`]),
-#[derive(Clone, Default)]
+#[derive(Clone)]
 pub struct Window {
     /// The id referring to [`CxWindow`], once instantiated. It's an index in [`Cx::windows`].
     pub window_id: Option<usize>,
@@ -17,9 +33,39 @@ pub struct Window {
     /// The position on the screen of the native window when it's created for the first time.
     pub create_position: Option<Vec2>,
 
-    /// The title that the window will get once created.
+    /// The [`DisplayInfo::display_id`] of the display to open the window on, centered in its work area. Combine with
+    /// [`Window::fullscreen_window`] to cover a particular display, e.g. for kiosks. Change it later using
+    /// [`Window::move_to_display`].
+    pub create_display: Option<usize>,
+
+    /// The title that the window will get once created. Change it later using [`Window::set_title`].
     pub create_title: String,
 
+    /// The icon that the window will get once created, e.g. in the taskbar. On Mac this is the icon of the whole app,
+    /// in the dock. Change it later using [`Window::set_icon`].
+    pub create_icon: Option<WindowIcon>,
+
+    /// The minimum inner size that the user can resize the window to. Change it later using [`Window::set_min_size`].
+    pub create_min_size: Option<Vec2>,
+
+    /// The maximum inner size that the user can resize the window to. Change it later using [`Window::set_max_size`].
+    pub create_max_size: Option<Vec2>,
+
+    /// Whether the user can resize the window. Defaults to true. Change it later using [`Window::set_resizable`].
+    pub create_resizable: bool,
+
+    /// Whether to hide all decorations of the native window, such as the buttons in the title bar on Mac. Note that
+    /// on Linux and Windows we never show a native title bar (see [`crate::WindowDragQueryEvent`]), so there this
+    /// just removes the resize borders. Change it later using [`Window::set_borderless`].
+    pub create_borderless: bool,
+
+    /// On the web, the id of the `<canvas>` element to draw this window into. Only used for windows other than the
+    /// first one, since that one is drawn into the canvas passed to `zaplib.initialize`. If not set, we add a new
+    /// canvas of [`Window::create_inner_size`] to the page.
+    ///
+    /// Note that a canvas can only be used for a single window, even after closing it.
+    pub create_canvas_id: Option<String>,
+
     /// Set to true if the window should as a drop target for a AppOpenFiles events.
     ///
     /// TODO(JP): only works on the wasm32 and mac targets for now.
@@ -35,6 +81,29 @@ pub struct Window {
     pub get_resource_url_callback: Option<GetResourceUrlCallback>,
 }
 
+impl Default for Window {
+    fn default() -> Self {
+        Self {
+            window_id: None,
+            create_inner_size: None,
+            create_position: None,
+            create_display: None,
+            create_title: String::new(),
+            create_icon: None,
+            create_min_size: None,
+            create_max_size: None,
+            create_resizable: true,
+            create_borderless: false,
+            create_canvas_id: None,
+            create_add_drop_target_for_app_open_files: false,
+            #[cfg(feature = "cef")]
+            create_cef_url: None,
+            #[cfg(feature = "cef-server")]
+            get_resource_url_callback: None,
+        }
+    }
+}
+
 impl Window {
     pub fn begin_window(&mut self, cx: &mut Cx) {
         // if we are not at ground level for viewports,
@@ -49,6 +118,7 @@ impl Window {
                     },
                     position: self.create_position,
                     add_drop_target_for_app_open_files: self.create_add_drop_target_for_app_open_files,
+                    canvas_id: self.create_canvas_id.clone(),
 
                     #[cfg(feature = "cef")]
                     cef_url: self.create_cef_url.clone(),
@@ -56,6 +126,15 @@ impl Window {
                     #[cfg(feature = "cef-server")]
                     get_resource_url_callback: self.get_resource_url_callback,
                 },
+                window_set_icon: self.create_icon.clone(),
+                window_style: CxWindowStyle {
+                    min_size: self.create_min_size,
+                    max_size: self.create_max_size,
+                    resizable: self.create_resizable,
+                    borderless: self.create_borderless,
+                },
+                window_style_changed: true,
+                window_set_display: self.create_display,
                 ..Default::default()
             };
             let window_id;
@@ -93,6 +172,74 @@ impl Window {
         }
     }
 
+    /// Move the window to the display with the given [`DisplayInfo::display_id`] (see [`Cx::displays`]), centered in
+    /// its work area. A fullscreen window moves over to cover the new display, except on Mac OS X, where fullscreen
+    /// windows live in their own space. Not supported on the web, and on Wayland this only picks the display to go
+    /// fullscreen on, since clients can't position their windows.
+    pub fn move_to_display(&mut self, cx: &mut Cx, display_id: usize) {
+        if let Some(window_id) = self.window_id {
+            cx.windows[window_id].window_set_display = Some(display_id);
+        }
+    }
+
+    /// Change the title of the window. On the web this sets the document title, but only for the first window.
+    pub fn set_title(&mut self, cx: &mut Cx, title: &str) {
+        if let Some(window_id) = self.window_id {
+            cx.windows[window_id].window_set_title = Some(title.to_string());
+        }
+    }
+
+    /// Change the icon of the window; see [`Window::create_icon`]. Not supported on the web.
+    pub fn set_icon(&mut self, cx: &mut Cx, icon: WindowIcon) {
+        if let Some(window_id) = self.window_id {
+            cx.windows[window_id].window_set_icon = Some(icon);
+        }
+    }
+
+    /// Change the minimum inner size of the window, or remove it with `None`. Not supported on the web.
+    pub fn set_min_size(&mut self, cx: &mut Cx, min_size: Option<Vec2>) {
+        if let Some(window_id) = self.window_id {
+            cx.windows[window_id].window_style.min_size = min_size;
+            cx.windows[window_id].window_style_changed = true;
+        }
+    }
+
+    /// Change the maximum inner size of the window, or remove it with `None`. Not supported on the web.
+    pub fn set_max_size(&mut self, cx: &mut Cx, max_size: Option<Vec2>) {
+        if let Some(window_id) = self.window_id {
+            cx.windows[window_id].window_style.max_size = max_size;
+            cx.windows[window_id].window_style_changed = true;
+        }
+    }
+
+    /// Change whether the user can resize the window. Not supported on the web.
+    pub fn set_resizable(&mut self, cx: &mut Cx, resizable: bool) {
+        if let Some(window_id) = self.window_id {
+            cx.windows[window_id].window_style.resizable = resizable;
+            cx.windows[window_id].window_style_changed = true;
+        }
+    }
+
+    /// Change whether the window has decorations; see [`Window::create_borderless`]. Not supported on the web.
+    pub fn set_borderless(&mut self, cx: &mut Cx, borderless: bool) {
+        if let Some(window_id) = self.window_id {
+            cx.windows[window_id].window_style.borderless = borderless;
+            cx.windows[window_id].window_style_changed = true;
+        }
+    }
+
+    /// Whether an [`Event`] is for this window. This is false for pointer events and window events (like
+    /// [`Event::WindowGeomChange`]) of other windows, and true for all other events.
+    ///
+    /// [`Event::hits_pointer`] only looks at coordinates, so when drawing different components in different windows,
+    /// use this to only pass a window's pointer events to its own components.
+    pub fn is_event_for_window(&self, event: &Event) -> bool {
+        match event.window_id() {
+            Some(window_id) => self.window_id == Some(window_id),
+            None => true,
+        }
+    }
+
     pub fn handle_window(&mut self, _cx: &mut Cx, _event: &mut Event) -> bool {
         false
     }
@@ -126,6 +273,17 @@ impl Window {
         }
     }
 
+    /// Go fullscreen if the window isn't already, otherwise go back to normal.
+    ///
+    /// On the web, browsers only allow going fullscreen in response to user input, like a click.
+    pub fn toggle_fullscreen(&mut self, cx: &mut Cx) {
+        if self.is_fullscreen(cx) {
+            self.normal_window(cx);
+        } else {
+            self.fullscreen_window(cx);
+        }
+    }
+
     pub fn can_fullscreen(&mut self, cx: &mut Cx) -> bool {
         if let Some(window_id) = self.window_id {
             cx.windows[window_id].window_geom.can_fullscreen
@@ -134,6 +292,8 @@ impl Window {
         }
     }
 
+    /// Whether a VR headset is available, so [`Window::xr_start_presenting`] can work. Only ever true in
+    /// WebAssembly, for the main window.
     pub fn xr_can_present(&mut self, cx: &mut Cx) -> bool {
         if let Some(window_id) = self.window_id {
             cx.windows[window_id].window_geom.xr_can_present
@@ -150,6 +310,15 @@ impl Window {
         }
     }
 
+    pub fn is_maximized(&mut self, cx: &mut Cx) -> bool {
+        if let Some(window_id) = self.window_id {
+            cx.windows[window_id].window_geom.is_maximized
+        } else {
+            false
+        }
+    }
+
+    /// Whether the window is presenting to a VR headset; see [`Window::xr_start_presenting`].
     pub fn xr_is_presenting(&mut self, cx: &mut Cx) -> bool {
         if let Some(window_id) = self.window_id {
             cx.windows[window_id].window_geom.xr_is_presenting
@@ -158,12 +327,21 @@ impl Window {
         }
     }
 
+    /// Start a WebXR session on the VR headset, if [`Window::xr_can_present`]. Browsers only allow this in response
+    /// to a click or key press, so call this when handling one.
+    ///
+    /// Once the session has started, [`Window::xr_is_presenting`] becomes true, with an [`Event::WindowGeomChange`].
+    /// From then on, there's an [`Event::XrUpdate`] for every frame of the headset, with the poses of the head and
+    /// controllers, and [`Pass`]es with [`Pass::set_xr_stereo`] get drawn into the headset. The session ends when
+    /// calling [`Window::xr_stop_presenting`], or when the user takes it off or exits using the headset; again with
+    /// an [`Event::WindowGeomChange`].
     pub fn xr_start_presenting(&mut self, cx: &mut Cx) {
         if let Some(window_id) = self.window_id {
             cx.windows[window_id].window_command = CxWindowCmd::XrStartPresenting;
         }
     }
 
+    /// End the session started with [`Window::xr_start_presenting`].
     pub fn xr_stop_presenting(&mut self, cx: &mut Cx) {
         if let Some(window_id) = self.window_id {
             cx.windows[window_id].window_command = CxWindowCmd::XrStopPresenting;
@@ -205,12 +383,56 @@ pub struct WindowGeom {
     pub xr_can_present: bool,
     pub xr_is_presenting: bool,
     pub is_fullscreen: bool,
+    pub is_maximized: bool,
     pub is_topmost: bool,
     pub position: Vec2,
     pub inner_size: Vec2,
     pub outer_size: Vec2,
 }
 
+/// An icon for a [`Window`]; see [`Window::create_icon`].
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct WindowIcon {
+    pub width: usize,
+    pub height: usize,
+    /// 8-bit RGBA pixels (not premultiplied), row by row starting at the top left.
+    pub rgba: Vec<u8>,
+}
+
+/// A monitor connected to the computer; see [`Cx::displays`].
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DisplayInfo {
+    /// Index in [`Cx::displays`], to pass to [`Window::move_to_display`]. Only valid until the next
+    /// [`Event::DisplaysChanged`].
+    pub display_id: usize,
+    /// Name of the display as shown by the OS, e.g. "DELL U2720Q" or "HDMI-1". Can be empty.
+    pub name: String,
+    /// Position of the top left corner in the desktop, relative to the top left of the primary display, with y
+    /// pointing down. In logical pixels of this display, so when displays have different scales this can only be used
+    /// to tell which display is where, and not to line them up exactly.
+    pub position: Vec2,
+    /// Size in logical pixels.
+    pub size: Vec2,
+    /// The part of the display that's not covered by taskbars, docks, and menu bars, relative to
+    /// [`DisplayInfo::position`]. Equal to the whole display if we can't tell.
+    pub work_area: Rect,
+    /// The scale of the display, like [`WindowGeom::dpi_factor`] for windows on it.
+    pub dpi_factor: f32,
+    /// Refresh rate in Hz, or 0 if unknown.
+    pub refresh_rate: f32,
+    pub is_primary: bool,
+}
+
+/// The properties of a [`CxWindow`] that apply to the native window as a whole, which backends set all at once
+/// whenever [`CxWindow::window_style_changed`] is true.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub(crate) struct CxWindowStyle {
+    pub(crate) min_size: Option<Vec2>,
+    pub(crate) max_size: Option<Vec2>,
+    pub(crate) resizable: bool,
+    pub(crate) borderless: bool,
+}
+
 #[derive(Clone)]
 pub(crate) enum CxWindowState {
     Create {
@@ -221,6 +443,9 @@ pub(crate) enum CxWindowState {
         #[allow(dead_code)] // Not supported in all platforms yet.
         add_drop_target_for_app_open_files: bool,
 
+        #[allow(dead_code)] // Only used on the web.
+        canvas_id: Option<String>,
+
         #[allow(dead_code)] // Not supported in all platforms yet.
         #[cfg(feature = "cef")]
         cef_url: Option<String>,
@@ -262,7 +487,12 @@ pub(crate) struct CxWindow {
     pub(crate) window_state: CxWindowState,
     pub(crate) window_command: CxWindowCmd,
     pub(crate) window_set_position: Option<Vec2>,
+    pub(crate) window_set_display: Option<usize>,
     pub(crate) window_topmost: Option<bool>,
+    pub(crate) window_set_title: Option<String>,
+    pub(crate) window_set_icon: Option<WindowIcon>,
+    pub(crate) window_style: CxWindowStyle,
+    pub(crate) window_style_changed: bool,
     pub(crate) window_geom: WindowGeom,
     pub(crate) main_pass_id: Option<usize>,
 }
@@ -0,0 +1,132 @@
+//! Types for showing 3D content in VR headsets from the browser, using [WebXR](https://immersive-web.github.io/webxr/).
+//!
+//! See [`Window::xr_start_presenting`] for how to start a session, and [`Pass::set_xr_stereo`] for what gets drawn.
+
+use crate::*;
+
+/// Which hand an [`XrInput`] is held in.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum XrHandedness {
+    /// E.g. a gaze-based input, or a controller that can be held in either hand.
+    None,
+    Left,
+    Right,
+}
+
+impl Default for XrHandedness {
+    fn default() -> Self {
+        XrHandedness::None
+    }
+}
+
+/// A button of an [`XrInput`], including triggers and the squeeze buttons on the grip.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct XrButton {
+    pub pressed: bool,
+    /// From 0 to 1; analog buttons like triggers go in between.
+    pub value: f32,
+}
+
+/// A controller or tracked hand of the headset.
+///
+/// Buttons and axes follow the ["xr-standard" mapping](https://immersive-web.github.io/webxr-gamepads-module/#xr-standard-gamepad-mapping),
+/// so e.g. `buttons[0]` is the trigger, `buttons[1]` the squeeze button, and `axes[2]` and `axes[3]` the X and Y of
+/// the thumbstick.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct XrInput {
+    pub handedness: XrHandedness,
+    /// Where the input is held, e.g. for drawing a controller model. [`None`] for inputs that aren't held.
+    pub grip: Option<Transform>,
+    /// The origin of the pointing ray, which points along -Z of the orientation.
+    pub ray: Transform,
+    pub buttons: Vec<XrButton>,
+    pub axes: Vec<f32>,
+}
+
+impl XrInput {
+    /// The direction of [`XrInput::ray`], e.g. for picking things in the scene.
+    pub fn ray_direction(&self) -> Vec3 {
+        self.ray.orientation.rotate_vec(vec3(0., 0., -1.))
+    }
+}
+
+/// One eye of the headset, as drawn by a [`Pass`] with [`Pass::set_xr_stereo`].
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct XrView {
+    /// Becomes `camera_projection` in shaders.
+    pub projection: Mat4,
+    /// Becomes `camera_view` in shaders.
+    pub view: Mat4,
+    /// The part of the framebuffer of the headset that this eye gets drawn into, in pixels.
+    pub viewport: Rect,
+}
+
+/// See [`Event::XrUpdate`].
+///
+/// All poses are in meters, relative to the floor below where the session was started, with +Y pointing up.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct XrUpdateEvent {
+    /// In seconds, as reported by the browser for the frame of the headset.
+    pub time: f64,
+    pub head: Transform,
+    /// Usually one for each eye, left first. Used automatically for drawing [`Pass`]es with [`Pass::set_xr_stereo`].
+    pub views: Vec<XrView>,
+    pub inputs: Vec<XrInput>,
+    /// The [`XrUpdateEvent::inputs`] of the previous update, for checking which buttons changed.
+    pub last_inputs: Vec<XrInput>,
+}
+
+impl XrUpdateEvent {
+    /// The first input held in the given hand, if any.
+    pub fn input(&self, handedness: XrHandedness) -> Option<&XrInput> {
+        self.inputs.iter().find(|input| input.handedness == handedness)
+    }
+
+    fn button(inputs: &[XrInput], handedness: XrHandedness, button: usize) -> bool {
+        inputs
+            .iter()
+            .find(|input| input.handedness == handedness)
+            .and_then(|input| input.buttons.get(button))
+            .map_or(false, |button| button.pressed)
+    }
+
+    /// Whether the button with the given index of the input in the given hand went down since the last update.
+    pub fn button_pressed(&self, handedness: XrHandedness, button: usize) -> bool {
+        Self::button(&self.inputs, handedness, button) && !Self::button(&self.last_inputs, handedness, button)
+    }
+
+    /// Whether the button with the given index of the input in the given hand went up since the last update.
+    pub fn button_released(&self, handedness: XrHandedness, button: usize) -> bool {
+        !Self::button(&self.inputs, handedness, button) && Self::button(&self.last_inputs, handedness, button)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_xr_update_buttons() {
+        let trigger = |pressed| XrInput {
+            handedness: XrHandedness::Right,
+            buttons: vec![XrButton { pressed, value: if pressed { 1. } else { 0. } }],
+            ..XrInput::default()
+        };
+        let event = XrUpdateEvent { inputs: vec![trigger(true)], last_inputs: vec![trigger(false)], ..XrUpdateEvent::default() };
+        assert!(event.button_pressed(XrHandedness::Right, 0));
+        assert!(!event.button_released(XrHandedness::Right, 0));
+        // Buttons and inputs that don't exist are never pressed.
+        assert!(!event.button_pressed(XrHandedness::Right, 1));
+        assert!(!event.button_pressed(XrHandedness::Left, 0));
+
+        let event = XrUpdateEvent { inputs: vec![], last_inputs: vec![trigger(true)], ..XrUpdateEvent::default() };
+        assert!(event.button_released(XrHandedness::Right, 0));
+        assert_eq!(event.input(XrHandedness::Right), None);
+
+        let input = XrInput {
+            ray: Transform { orientation: Quat { a: 0., b: 0., c: 0., d: 1. }, ..Transform::default() },
+            ..trigger(false)
+        };
+        assert_eq!(input.ray_direction(), vec3(0., 0., -1.));
+    }
+}
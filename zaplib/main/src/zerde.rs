@@ -26,6 +26,9 @@
 //!
 //! Zerde buffers will automatically grow as you put more data in it.
 //!
+//! Zerde is positional, so both sides must agree on the exact layout. For data that needs to stay compatible when
+//! JS and Rust are versioned independently, use [`ZerdeRecord`] instead.
+//!
 //! Arrays are not natively supported, but it's typical to pack arrays of data in a similar way to strings, with a
 //! preceding length value.
 //!
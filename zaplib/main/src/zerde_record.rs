@@ -0,0 +1,664 @@
+//! Tagged, versioned records for sending structured data between JavaScript and Rust.
+//!
+//! Keep in sync with zerde_record.ts!
+//!
+//! Regular Zerde buffers (and most hand-rolled encodings on top of [`ZapParam`]s) are positional: both
+//! sides have to agree on the exact order and type of every value, so changing a struct on one side
+//! silently breaks the other. That's fine when JS and Rust are always deployed together, but not when
+//! they are versioned independently (e.g. a cached JS bundle talking to a newer WebAssembly file).
+//!
+//! A [`ZerdeRecord`] instead stores a version number and a list of named, typed fields. It is sent as a
+//! [`ZapParam::MutableU8Buffer`] (use [`IntoParam`] and [`ZerdeRecord::from_param`]), and can be built and
+//! read in JS using `zaplib.encodeRecord` and `zaplib.decodeRecord`.
+//!
+//! For structs, use `#[derive(ZapParam)]` (see [`ZapParamStruct`]) instead of reading and writing fields by hand.
+//!
+//! Compatibility rules:
+//! * Adding a field is fine. Readers ignore fields they don't know about, and readers of older data should
+//!   use [`ZerdeRecord::get_or`] to fall back to a default when the field is missing.
+//! * Removing a field is fine as long as all readers use [`ZerdeRecord::get_or`] for it.
+//! * Widening a number is fine: `u32` can be read as `u64`, and `f32` as `f64`.
+//! * Any other type change is an error ([`ZerdeError::TypeMismatch`]), which names the offending field.
+//!   Add a field with a new name instead.
+//! * For changes that can't be made compatible, bump the version, and have readers check it using
+//!   [`ZerdeRecord::check_version`].
+//!
+//! Encoding (all numbers little-endian):
+//! * record: version (u32), field count (u32), then for each field: name length in bytes (u32), name
+//!   (UTF-8), and a value.
+//! * value: type tag (u8), length of the data in bytes (u32), data. The length makes it possible to skip
+//!   values with tags that we don't know about.
+//! * data per tag: 1 = bool (1 byte), 2 = u32, 3 = f32, 4 = u64, 5 = f64, 6 = string (UTF-8), 7 = bytes,
+//!   8 = nested record, 9 = list (count as u32, followed by that many values), 10 = null (no data).
+
+use crate::*;
+use std::convert::TryInto;
+use std::fmt;
+
+const TAG_BOOL: u8 = 1;
+const TAG_U32: u8 = 2;
+const TAG_F32: u8 = 3;
+const TAG_U64: u8 = 4;
+const TAG_F64: u8 = 5;
+const TAG_STRING: u8 = 6;
+const TAG_BYTES: u8 = 7;
+const TAG_RECORD: u8 = 8;
+const TAG_LIST: u8 = 9;
+const TAG_NULL: u8 = 10;
+
+/// A single value in a [`ZerdeRecord`].
+#[derive(Clone, Debug, PartialEq)]
+pub enum ZerdeValue {
+    Bool(bool),
+    U32(u32),
+    F32(f32),
+    U64(u64),
+    F64(f64),
+    String(String),
+    Bytes(Vec<u8>),
+    Record(ZerdeRecord),
+    List(Vec<ZerdeValue>),
+    /// No value, e.g. for an [`Option`] that is [`None`].
+    Null,
+    /// A value with a type tag that this version doesn't know about. Kept around so that the record can
+    /// be passed along unchanged.
+    Unknown {
+        tag: u8,
+        data: Vec<u8>,
+    },
+}
+
+impl ZerdeValue {
+    /// Name of the type, as used in [`ZerdeError::TypeMismatch`].
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            ZerdeValue::Bool(_) => "bool",
+            ZerdeValue::U32(_) => "u32",
+            ZerdeValue::F32(_) => "f32",
+            ZerdeValue::U64(_) => "u64",
+            ZerdeValue::F64(_) => "f64",
+            ZerdeValue::String(_) => "string",
+            ZerdeValue::Bytes(_) => "bytes",
+            ZerdeValue::Record(_) => "record",
+            ZerdeValue::List(_) => "list",
+            ZerdeValue::Null => "null",
+            ZerdeValue::Unknown { .. } => "unknown",
+        }
+    }
+
+    fn write(&self, out: &mut Vec<u8>) {
+        let (tag, data) = match self {
+            ZerdeValue::Bool(v) => (TAG_BOOL, vec![*v as u8]),
+            ZerdeValue::U32(v) => (TAG_U32, v.to_le_bytes().to_vec()),
+            ZerdeValue::F32(v) => (TAG_F32, v.to_le_bytes().to_vec()),
+            ZerdeValue::U64(v) => (TAG_U64, v.to_le_bytes().to_vec()),
+            ZerdeValue::F64(v) => (TAG_F64, v.to_le_bytes().to_vec()),
+            ZerdeValue::String(v) => (TAG_STRING, v.as_bytes().to_vec()),
+            ZerdeValue::Bytes(v) => (TAG_BYTES, v.clone()),
+            ZerdeValue::Record(v) => (TAG_RECORD, v.to_bytes()),
+            ZerdeValue::List(values) => {
+                let mut data = (values.len() as u32).to_le_bytes().to_vec();
+                for value in values {
+                    value.write(&mut data);
+                }
+                (TAG_LIST, data)
+            }
+            ZerdeValue::Null => (TAG_NULL, Vec::new()),
+            ZerdeValue::Unknown { tag, data } => (*tag, data.clone()),
+        };
+        out.push(tag);
+        out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        out.extend_from_slice(&data);
+    }
+
+    fn read(reader: &mut RecordReader) -> Result<Self, ZerdeError> {
+        let tag = reader.read_u8()?;
+        let len = reader.read_u32()? as usize;
+        let data = reader.read_bytes(len)?;
+        let mut data_reader = RecordReader { bytes: data, pos: 0 };
+        Ok(match tag {
+            TAG_BOOL => ZerdeValue::Bool(data_reader.read_u8()? != 0),
+            TAG_U32 => ZerdeValue::U32(data_reader.read_u32()?),
+            TAG_F32 => ZerdeValue::F32(f32::from_bits(data_reader.read_u32()?)),
+            TAG_U64 => ZerdeValue::U64(data_reader.read_u64()?),
+            TAG_F64 => ZerdeValue::F64(f64::from_bits(data_reader.read_u64()?)),
+            TAG_STRING => ZerdeValue::String(
+                String::from_utf8(data.to_vec()).map_err(|_| ZerdeError::Malformed("invalid UTF-8 in string".to_string()))?,
+            ),
+            TAG_BYTES => ZerdeValue::Bytes(data.to_vec()),
+            TAG_RECORD => ZerdeValue::Record(ZerdeRecord::from_bytes(data)?),
+            TAG_LIST => {
+                let count = data_reader.read_u32()?;
+                ZerdeValue::List((0..count).map(|_| ZerdeValue::read(&mut data_reader)).collect::<Result<_, _>>()?)
+            }
+            TAG_NULL => ZerdeValue::Null,
+            tag => ZerdeValue::Unknown { tag, data: data.to_vec() },
+        })
+    }
+}
+
+/// A type that can be stored in a [`ZerdeRecord`] field. Implemented for `bool`, `u32`, `f32`, `u64`, `f64`,
+/// [`String`], `Vec<u8>` (as bytes), [`ZerdeRecord`], [`Option`] and [`Vec`] of these, and structs that use
+/// `#[derive(ZapParam)]`.
+pub trait ZerdeField: Sized {
+    /// Name of the type, as used in [`ZerdeError::TypeMismatch`].
+    const TYPE_NAME: &'static str;
+    /// Whether the field can be missing, in which case [`ZerdeField::missing`] is used.
+    const OPTIONAL: bool = false;
+
+    fn to_zerde_value(&self) -> ZerdeValue;
+
+    /// Convert back from a [`ZerdeValue`]; `field` is used in errors.
+    fn from_zerde_value(value: &ZerdeValue, field: &str) -> Result<Self, ZerdeError>;
+
+    /// Value to use when the field is missing, if [`ZerdeField::OPTIONAL`].
+    fn missing() -> Option<Self> {
+        None
+    }
+
+    /// TypeScript type of the decoded value, used in [`ZapParamStruct::typescript_declaration`].
+    fn typescript_type() -> String;
+
+    /// Expression for the `ZapParamSchema` of this field, used in [`ZapParamStruct::typescript_declaration`].
+    fn typescript_schema() -> String;
+
+    /// Add [`ZapParamStruct::typescript_declaration`] of this type and any nested structs to `declarations`,
+    /// if they're not in there yet. Does nothing for types that don't need a declaration.
+    fn collect_typescript_declarations(_declarations: &mut Vec<String>) {}
+}
+
+fn type_mismatch<T: ZerdeField>(value: &ZerdeValue, field: &str) -> ZerdeError {
+    ZerdeError::TypeMismatch { field: field.to_string(), expected: T::TYPE_NAME, found: value.type_name() }
+}
+
+macro_rules! impl_zerde_field {
+    ($ty:ty, $variant:ident, $type_name:expr, $typescript_type:expr) => {
+        impl From<$ty> for ZerdeValue {
+            fn from(value: $ty) -> Self {
+                ZerdeValue::$variant(value)
+            }
+        }
+        impl ZerdeField for $ty {
+            const TYPE_NAME: &'static str = $type_name;
+            fn to_zerde_value(&self) -> ZerdeValue {
+                ZerdeValue::$variant(self.clone())
+            }
+            fn from_zerde_value(value: &ZerdeValue, field: &str) -> Result<Self, ZerdeError> {
+                match value {
+                    ZerdeValue::$variant(v) => Ok(v.clone()),
+                    _ => Err(type_mismatch::<Self>(value, field)),
+                }
+            }
+            fn typescript_type() -> String {
+                $typescript_type.to_string()
+            }
+            fn typescript_schema() -> String {
+                format!("\"{}\"", $type_name)
+            }
+        }
+    };
+}
+
+impl_zerde_field!(bool, Bool, "bool", "boolean");
+impl_zerde_field!(u32, U32, "u32", "number");
+impl_zerde_field!(f32, F32, "f32", "number");
+impl_zerde_field!(String, String, "string", "string");
+impl_zerde_field!(Vec<u8>, Bytes, "bytes", "Uint8Array");
+impl_zerde_field!(ZerdeRecord, Record, "record", "ZerdeRecord");
+
+impl From<u64> for ZerdeValue {
+    fn from(value: u64) -> Self {
+        ZerdeValue::U64(value)
+    }
+}
+impl ZerdeField for u64 {
+    const TYPE_NAME: &'static str = "u64";
+    fn to_zerde_value(&self) -> ZerdeValue {
+        ZerdeValue::U64(*self)
+    }
+    fn from_zerde_value(value: &ZerdeValue, field: &str) -> Result<Self, ZerdeError> {
+        match value {
+            ZerdeValue::U64(v) => Ok(*v),
+            ZerdeValue::U32(v) => Ok(*v as u64),
+            _ => Err(type_mismatch::<Self>(value, field)),
+        }
+    }
+    fn typescript_type() -> String {
+        "bigint".to_string()
+    }
+    fn typescript_schema() -> String {
+        "\"u64\"".to_string()
+    }
+}
+
+impl From<f64> for ZerdeValue {
+    fn from(value: f64) -> Self {
+        ZerdeValue::F64(value)
+    }
+}
+impl ZerdeField for f64 {
+    const TYPE_NAME: &'static str = "f64";
+    fn to_zerde_value(&self) -> ZerdeValue {
+        ZerdeValue::F64(*self)
+    }
+    fn from_zerde_value(value: &ZerdeValue, field: &str) -> Result<Self, ZerdeError> {
+        match value {
+            ZerdeValue::F64(v) => Ok(*v),
+            ZerdeValue::F32(v) => Ok(*v as f64),
+            _ => Err(type_mismatch::<Self>(value, field)),
+        }
+    }
+    fn typescript_type() -> String {
+        "number".to_string()
+    }
+    fn typescript_schema() -> String {
+        "\"f64\"".to_string()
+    }
+}
+
+impl<T: ZerdeField> ZerdeField for Option<T> {
+    const TYPE_NAME: &'static str = T::TYPE_NAME;
+    const OPTIONAL: bool = true;
+    fn to_zerde_value(&self) -> ZerdeValue {
+        match self {
+            Some(value) => value.to_zerde_value(),
+            None => ZerdeValue::Null,
+        }
+    }
+    fn from_zerde_value(value: &ZerdeValue, field: &str) -> Result<Self, ZerdeError> {
+        match value {
+            ZerdeValue::Null => Ok(None),
+            value => T::from_zerde_value(value, field).map(Some),
+        }
+    }
+    fn missing() -> Option<Self> {
+        Some(None)
+    }
+    fn typescript_type() -> String {
+        format!("{} | undefined", T::typescript_type())
+    }
+    fn typescript_schema() -> String {
+        format!("{{ optional: {} }}", T::typescript_schema())
+    }
+    fn collect_typescript_declarations(declarations: &mut Vec<String>) {
+        T::collect_typescript_declarations(declarations);
+    }
+}
+
+/// Lists of anything but `u8`, since `Vec<u8>` is encoded as bytes.
+impl<T: ZerdeField> ZerdeField for Vec<T> {
+    const TYPE_NAME: &'static str = "list";
+    fn to_zerde_value(&self) -> ZerdeValue {
+        ZerdeValue::List(self.iter().map(ZerdeField::to_zerde_value).collect())
+    }
+    fn from_zerde_value(value: &ZerdeValue, field: &str) -> Result<Self, ZerdeError> {
+        match value {
+            ZerdeValue::List(list) => list
+                .iter()
+                .enumerate()
+                .map(|(index, value)| T::from_zerde_value(value, &format!("{}[{}]", field, index)))
+                .collect(),
+            _ => Err(type_mismatch::<Self>(value, field)),
+        }
+    }
+    fn typescript_type() -> String {
+        format!("({})[]", T::typescript_type())
+    }
+    fn typescript_schema() -> String {
+        format!("{{ list: {} }}", T::typescript_schema())
+    }
+    fn collect_typescript_declarations(declarations: &mut Vec<String>) {
+        T::collect_typescript_declarations(declarations);
+    }
+}
+
+impl From<Vec<ZerdeValue>> for ZerdeValue {
+    fn from(value: Vec<ZerdeValue>) -> Self {
+        ZerdeValue::List(value)
+    }
+}
+
+impl From<&str> for ZerdeValue {
+    fn from(value: &str) -> Self {
+        ZerdeValue::String(value.to_string())
+    }
+}
+
+/// Error when reading a [`ZerdeRecord`].
+#[derive(Clone, Debug, PartialEq)]
+pub enum ZerdeError {
+    /// A required field was not present; use [`ZerdeRecord::get_or`] for optional fields.
+    MissingField { field: String },
+    /// A field was present but had a different type than expected.
+    TypeMismatch { field: String, expected: &'static str, found: &'static str },
+    /// The record's version is not in the range passed to [`ZerdeRecord::check_version`].
+    UnsupportedVersion { version: u32, min: u32, max: u32 },
+    /// The bytes don't form a valid record.
+    Malformed(String),
+}
+
+impl fmt::Display for ZerdeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ZerdeError::MissingField { field } => write!(f, "Missing field \"{}\"", field),
+            ZerdeError::TypeMismatch { field, expected, found } => {
+                write!(f, "Field \"{}\" has type {}, but expected {}", field, found, expected)
+            }
+            ZerdeError::UnsupportedVersion { version, min, max } => {
+                write!(f, "Unsupported record version {} (supported: {} to {})", version, min, max)
+            }
+            ZerdeError::Malformed(message) => write!(f, "Malformed record: {}", message),
+        }
+    }
+}
+
+impl ZerdeError {
+    /// Prefix the field name with the name of a parent field, for errors in nested records.
+    fn in_field(self, parent: &str) -> Self {
+        match self {
+            ZerdeError::MissingField { field } => ZerdeError::MissingField { field: format!("{}.{}", parent, field) },
+            ZerdeError::TypeMismatch { field, expected, found } => {
+                ZerdeError::TypeMismatch { field: format!("{}.{}", parent, field), expected, found }
+            }
+            error => error,
+        }
+    }
+}
+
+impl std::error::Error for ZerdeError {}
+
+/// A versioned set of named fields; see the [module-level documentation](self).
+///
+/// ```
+/// let record = ZerdeRecord::new(2).with("name", "Ada").with("zoom", 1.5f32);
+/// let param = record.into_param();
+///
+/// let record = ZerdeRecord::from_param(&param)?;
+/// record.check_version(1, 2)?;
+/// let name: String = record.get("name")?;
+/// let zoom: f32 = record.get_or("zoom", 1.0)?; // Not sent by version 1.
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ZerdeRecord {
+    pub version: u32,
+    fields: Vec<(String, ZerdeValue)>,
+}
+
+impl ZerdeRecord {
+    pub fn new(version: u32) -> Self {
+        Self { version, fields: Vec::new() }
+    }
+
+    /// Set a field, replacing any existing field with the same name.
+    pub fn insert(&mut self, name: &str, value: impl Into<ZerdeValue>) {
+        let value = value.into();
+        match self.fields.iter_mut().find(|(field_name, _)| field_name == name) {
+            Some((_, existing)) => *existing = value,
+            None => self.fields.push((name.to_string(), value)),
+        }
+    }
+
+    /// Builder-style version of [`ZerdeRecord::insert`].
+    #[must_use]
+    pub fn with(mut self, name: &str, value: impl Into<ZerdeValue>) -> Self {
+        self.insert(name, value);
+        self
+    }
+
+    pub fn get_value(&self, name: &str) -> Option<&ZerdeValue> {
+        self.fields.iter().find(|(field_name, _)| field_name == name).map(|(_, value)| value)
+    }
+
+    /// All fields, in the order they were inserted (or encoded).
+    pub fn fields(&self) -> impl Iterator<Item = (&str, &ZerdeValue)> {
+        self.fields.iter().map(|(name, value)| (name.as_str(), value))
+    }
+
+    /// Get a required field. [`Option`] fields are [`None`] when missing.
+    pub fn get<T: ZerdeField>(&self, name: &str) -> Result<T, ZerdeError> {
+        match self.get_value(name) {
+            Some(value) => T::from_zerde_value(value, name),
+            None => T::missing().ok_or_else(|| ZerdeError::MissingField { field: name.to_string() }),
+        }
+    }
+
+    /// Get an optional field, returning `default` if it's missing. It's still an error if the field
+    /// is present with an incompatible type.
+    pub fn get_or<T: ZerdeField>(&self, name: &str, default: T) -> Result<T, ZerdeError> {
+        self.get_or_else(name, || default)
+    }
+
+    /// Like [`ZerdeRecord::get_or`], but only computes the default when needed.
+    pub fn get_or_else<T: ZerdeField>(&self, name: &str, default: impl FnOnce() -> T) -> Result<T, ZerdeError> {
+        match self.get_value(name) {
+            Some(value) => T::from_zerde_value(value, name),
+            None => Ok(default()),
+        }
+    }
+
+    /// Returns [`ZerdeError::UnsupportedVersion`] if the version is not within `min..=max`.
+    pub fn check_version(&self, min: u32, max: u32) -> Result<(), ZerdeError> {
+        if self.version < min || self.version > max {
+            return Err(ZerdeError::UnsupportedVersion { version: self.version, min, max });
+        }
+        Ok(())
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        out.extend_from_slice(&self.version.to_le_bytes());
+        out.extend_from_slice(&(self.fields.len() as u32).to_le_bytes());
+        for (name, value) in &self.fields {
+            out.extend_from_slice(&(name.len() as u32).to_le_bytes());
+            out.extend_from_slice(name.as_bytes());
+            value.write(&mut out);
+        }
+        out
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, ZerdeError> {
+        let mut reader = RecordReader { bytes, pos: 0 };
+        let version = reader.read_u32()?;
+        let field_count = reader.read_u32()?;
+        let mut record = Self::new(version);
+        for _ in 0..field_count {
+            let name_len = reader.read_u32()? as usize;
+            let name = std::str::from_utf8(reader.read_bytes(name_len)?)
+                .map_err(|_| ZerdeError::Malformed("invalid UTF-8 in field name".to_string()))?;
+            let value = ZerdeValue::read(&mut reader)?;
+            record.fields.push((name.to_string(), value));
+        }
+        Ok(record)
+    }
+
+    /// Read a record from a [`ZapParam::MutableU8Buffer`] or [`ZapParam::ReadOnlyU8Buffer`].
+    pub fn from_param(param: &ZapParam) -> Result<Self, ZerdeError> {
+        match param {
+            ZapParam::MutableU8Buffer(_) | ZapParam::ReadOnlyU8Buffer(_) => Self::from_bytes(param.as_u8_slice()),
+            _ => Err(ZerdeError::Malformed(format!("expected a U8Buffer, got {:?}", param))),
+        }
+    }
+}
+
+/// A struct that is sent between JS and Rust as a [`ZerdeRecord`], so that both sides can be versioned
+/// independently. Implement this using `#[derive(ZapParam)]`, which also implements [`ZerdeField`] (so these
+/// structs can be nested) and [`IntoParam`].
+///
+/// ```
+/// #[derive(ZapParam)]
+/// #[zap_param(version = 2, min_version = 1)]
+/// struct User {
+///     name: String,
+///     #[zap_param(default)] // Added in version 2.
+///     zoom: f32,
+///     address: Option<Address>,
+/// }
+///
+/// fn call_rust(name: String, params: Vec<ZapParam>) -> Vec<ZapParam> {
+///     let user = User::from_param(&params[0]).unwrap();
+///     vec![user.into_param()]
+/// }
+/// ```
+///
+/// In JS, use [`ZapParamStruct::typescript_declaration`] to get a type and a schema, and pass those to
+/// `zaplib.encodeZapParam` and `zaplib.decodeZapParam`.
+pub trait ZapParamStruct: Sized {
+    /// Version that gets encoded, set using `#[zap_param(version = ..)]`.
+    const VERSION: u32;
+    /// Oldest version that can still be decoded, set using `#[zap_param(min_version = ..)]`.
+    const MIN_VERSION: u32;
+
+    fn to_zerde_record(&self) -> ZerdeRecord;
+
+    fn from_zerde_record(record: &ZerdeRecord) -> Result<Self, ZerdeError>;
+
+    /// TypeScript source with a type for this struct, and a `ZapParamSchema` named after the struct with
+    /// "Schema" appended. Nested structs need their own declarations as well; use
+    /// [`ZerdeField::collect_typescript_declarations`] to get all of them.
+    fn typescript_declaration() -> String;
+
+    /// Read from a [`ZapParam::MutableU8Buffer`] or [`ZapParam::ReadOnlyU8Buffer`].
+    fn from_param(param: &ZapParam) -> Result<Self, ZerdeError> {
+        Self::from_zerde_record(&ZerdeRecord::from_param(param)?)
+    }
+}
+
+/// Used by `#[derive(ZapParam)]` to implement [`ZerdeField::from_zerde_value`].
+#[doc(hidden)]
+pub fn from_nested_zerde_record<T: ZapParamStruct + ZerdeField>(value: &ZerdeValue, field: &str) -> Result<T, ZerdeError> {
+    match value {
+        ZerdeValue::Record(record) => T::from_zerde_record(record).map_err(|error| error.in_field(field)),
+        _ => Err(type_mismatch::<T>(value, field)),
+    }
+}
+
+impl IntoParam for ZerdeRecord {
+    fn into_param(self) -> ZapParam {
+        self.to_bytes().into_param()
+    }
+}
+
+struct RecordReader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> RecordReader<'a> {
+    fn read_bytes(&mut self, len: usize) -> Result<&'a [u8], ZerdeError> {
+        let end = self.pos.checked_add(len).filter(|&end| end <= self.bytes.len()).ok_or_else(|| {
+            ZerdeError::Malformed(format!("unexpected end of data at byte {} (reading {} bytes)", self.pos, len))
+        })?;
+        let bytes = &self.bytes[self.pos..end];
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    fn read_u8(&mut self) -> Result<u8, ZerdeError> {
+        Ok(self.read_bytes(1)?[0])
+    }
+
+    fn read_u32(&mut self) -> Result<u32, ZerdeError> {
+        Ok(u32::from_le_bytes(self.read_bytes(4)?.try_into().unwrap()))
+    }
+
+    fn read_u64(&mut self) -> Result<u64, ZerdeError> {
+        Ok(u64::from_le_bytes(self.read_bytes(8)?.try_into().unwrap()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_roundtrip() {
+        let nested = ZerdeRecord::new(1).with("x", 1u32);
+        let record = ZerdeRecord::new(3)
+            .with("flag", true)
+            .with("count", 7u32)
+            .with("zoom", 1.5f32)
+            .with("id", u64::MAX)
+            .with("time", 0.25f64)
+            .with("name", "héllo")
+            .with("data", vec![1u8, 2, 3])
+            .with("nested", nested)
+            .with("list", vec![ZerdeValue::U32(1), ZerdeValue::String("two".to_string())]);
+        let decoded = ZerdeRecord::from_bytes(&record.to_bytes()).unwrap();
+        assert_eq!(decoded, record);
+        assert_eq!(ZerdeRecord::from_param(&record.into_param()).unwrap(), decoded);
+    }
+
+    #[test]
+    fn test_record_compatibility() {
+        // An unknown tag from a future version is skipped over, and the fields after it still work.
+        let mut bytes = ZerdeRecord::new(2).with("future", ZerdeValue::Unknown { tag: 200, data: vec![9, 9] }).to_bytes();
+        bytes[4] = 2; // Bump the field count, and append another field.
+        bytes.extend_from_slice(&ZerdeRecord::new(0).with("count", 5u32).to_bytes()[8..]);
+        let record = ZerdeRecord::from_bytes(&bytes).unwrap();
+        assert_eq!(record.get::<u32>("count"), Ok(5));
+
+        // Missing fields.
+        assert_eq!(record.get::<String>("name"), Err(ZerdeError::MissingField { field: "name".to_string() }));
+        assert_eq!(record.get_or("name", "default".to_string()), Ok("default".to_string()));
+
+        // Widening is allowed, other type changes name the field.
+        assert_eq!(record.get::<u64>("count"), Ok(5));
+        let error = record.get_or::<f32>("count", 0.).unwrap_err();
+        assert_eq!(error, ZerdeError::TypeMismatch { field: "count".to_string(), expected: "f32", found: "u32" });
+        assert_eq!(error.to_string(), "Field \"count\" has type u32, but expected f32");
+
+        assert!(record.check_version(1, 2).is_ok());
+        assert_eq!(record.check_version(3, 4), Err(ZerdeError::UnsupportedVersion { version: 2, min: 3, max: 4 }));
+        assert!(matches!(ZerdeRecord::from_bytes(&bytes[..bytes.len() - 1]), Err(ZerdeError::Malformed(_))));
+    }
+
+    #[derive(Debug, PartialEq, ZapParam)]
+    struct Address {
+        city: String,
+    }
+
+    #[derive(Debug, PartialEq, ZapParam)]
+    #[zap_param(version = 2, min_version = 1)]
+    struct User {
+        name: String,
+        #[zap_param(default)]
+        zoom: f32,
+        #[zap_param(rename = "userId")]
+        id: u64,
+        address: Option<Address>,
+        previous: Vec<Address>,
+    }
+
+    #[test]
+    fn test_derive_zap_param() {
+        let user = User {
+            name: "Ada".to_string(),
+            zoom: 1.5,
+            id: 7,
+            address: Some(Address { city: "London".to_string() }),
+            previous: vec![Address { city: "Paris".to_string() }],
+        };
+        assert_eq!(User::from_param(&user.to_zerde_record().into_param()), Ok(user));
+
+        // Version 1 didn't have `zoom` yet, and `address` is optional.
+        let v1 = ZerdeRecord::new(1).with("name", "Bob").with("userId", 8u32).with("previous", Vec::<ZerdeValue>::new());
+        let user = User::from_zerde_record(&v1).unwrap();
+        assert_eq!((user.zoom, user.id, user.address), (0., 8, None));
+
+        let nested = v1.clone().with("previous", vec![ZerdeValue::Record(ZerdeRecord::new(1).with("city", 3u32))]);
+        assert_eq!(
+            User::from_zerde_record(&nested).unwrap_err().to_string(),
+            "Field \"previous[0].city\" has type u32, but expected string"
+        );
+        assert_eq!(
+            User::from_zerde_record(&ZerdeRecord { version: 3, ..v1 }),
+            Err(ZerdeError::UnsupportedVersion { version: 3, min: 1, max: 2 })
+        );
+
+        let declaration = User::typescript_declaration();
+        assert!(declaration.starts_with("export type User = {\n  \"name\": string;\n  \"zoom\"?: number;\n"));
+        assert!(declaration.contains("  \"address\"?: Address | undefined;\n  \"previous\": (Address)[];\n};"));
+        assert!(declaration.contains("export const UserSchema: ZapParamSchema = {\n  version: 2,\n  minVersion: 1,\n"));
+        assert!(declaration.contains("    \"previous\": { list: { record: AddressSchema } },\n"));
+    }
+}
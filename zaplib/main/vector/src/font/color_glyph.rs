@@ -0,0 +1,28 @@
+use std::ops::Range;
+
+/// How to draw a glyph in color, for fonts that have color glyphs (typically emoji fonts).
+#[derive(Clone, Debug, PartialEq)]
+pub enum ColorGlyph {
+    /// Other glyphs of the font, drawn on top of each other, each in their own RGBA color. A color of [`None`] means
+    /// that the layer should be drawn in the color of the text. From the `COLR` and `CPAL` tables.
+    Layers(Vec<(usize, Option<[u8; 4]>)>),
+    /// A PNG image. From the `CBDT` or `sbix` table.
+    Bitmap(ColorBitmap),
+}
+
+/// A PNG image for a glyph. Only the largest size in the font is kept, which gets scaled down when drawing.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ColorBitmap {
+    /// Where the PNG file is in the bytes of the font file.
+    pub png_range: Range<usize>,
+    /// The size of an em in pixels of the image.
+    pub pixels_per_em: f32,
+    /// The position of the left edge of the image, relative to the origin of the glyph, in pixels of the image.
+    pub left: f32,
+    /// The position of the bottom edge of the image, relative to the baseline, in pixels of the image. Positive is up.
+    pub bottom: f32,
+    /// The width of the image in pixels.
+    pub width: f32,
+    /// The height of the image in pixels.
+    pub height: f32,
+}
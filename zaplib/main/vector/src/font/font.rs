@@ -1,5 +1,6 @@
-use crate::font::Glyph;
+use crate::font::{ColorGlyph, Glyph};
 use crate::geometry::Rectangle;
+use std::collections::HashMap;
 
 /// A font.
 #[derive(Clone, Debug, PartialEq)]
@@ -9,6 +10,31 @@ pub struct VectorFont {
     pub(crate) descender: f32,
     pub(crate) line_gap: f32,
     pub(crate) bounds: Rectangle,
+    /// Glyph indices for characters below U+10000 (the Basic Multilingual Plane), indexed by char code.
     pub char_code_to_glyph_index_map: Vec<usize>,
+    /// Glyph indices for characters from U+10000, like most emoji. Only fonts with a format 12 `cmap` have these.
+    pub supplementary_char_code_to_glyph_index_map: HashMap<u32, usize>,
     pub glyphs: Vec<Glyph>,
+    /// Color versions of glyphs, by index in [`VectorFont::glyphs`]. Fonts with color glyphs typically don't have
+    /// outlines for them, so these should be drawn instead of the outline when they're there.
+    pub color_glyphs: HashMap<usize, ColorGlyph>,
+}
+
+impl VectorFont {
+    /// The index in [`VectorFont::glyphs`] for `c`, or 0 if the font doesn't have it. Glyph 0 is the "missing glyph",
+    /// which is typically drawn as a box.
+    pub fn glyph_index(&self, c: char) -> usize {
+        let code = c as u32;
+        let index = if code < 0x10000 {
+            self.char_code_to_glyph_index_map.get(code as usize).copied()
+        } else {
+            self.supplementary_char_code_to_glyph_index_map.get(&code).copied()
+        };
+        index.filter(|&index| index < self.glyphs.len()).unwrap_or(0)
+    }
+
+    /// Whether the font has a glyph for `c`.
+    pub fn has_glyph(&self, c: char) -> bool {
+        self.glyph_index(c) != 0
+    }
 }
@@ -3,11 +3,13 @@
 
 pub mod outline;
 
+mod color_glyph;
 mod font;
 mod glyph;
 mod horizontal_metrics;
 mod outline_point;
 
+pub use self::color_glyph::{ColorBitmap, ColorGlyph};
 pub use self::font::VectorFont;
 pub use self::glyph::Glyph;
 pub use self::horizontal_metrics::HorizontalMetrics;
@@ -8,5 +8,5 @@ mod path_iterator;
 
 pub(crate) use self::line_path_command::LinePathCommand;
 pub(crate) use self::line_path_iterator::LinePathIterator;
-pub(crate) use self::path_command::PathCommand;
+pub use self::path_command::PathCommand;
 pub use self::path_iterator::PathIterator;
@@ -0,0 +1,345 @@
+//! Color glyphs, as used for emoji: layers of other glyphs (`COLR` and `CPAL` tables), and PNG images (`CBLC` and
+//! `CBDT` tables, or the `sbix` table).
+
+use super::{Error, Reader, Result};
+use crate::font::{ColorBitmap, ColorGlyph};
+use std::collections::HashMap;
+use std::ops::Range;
+
+/// Parse the color glyphs of a font from its `tables`. `bytes` is the entire font file, since
+/// [`ColorBitmap::png_range`] points into it.
+///
+/// Broken color tables are ignored, since the font might still be usable without them.
+pub(super) fn parse_color_glyphs(
+    bytes: &[u8],
+    tables: &HashMap<[u8; 4], &[u8]>,
+    glyph_count: usize,
+) -> HashMap<usize, ColorGlyph> {
+    let mut color_glyphs = HashMap::new();
+    let bitmaps = if let (Some(cblc), Some(cbdt)) = (tables.get(b"CBLC"), tables.get(b"CBDT")) {
+        parse_cbdt(bytes, cblc, cbdt).unwrap_or_default()
+    } else if let Some(sbix) = tables.get(b"sbix") {
+        parse_sbix(bytes, sbix, glyph_count).unwrap_or_default()
+    } else {
+        Vec::new()
+    };
+    for (glyph_id, bitmap) in bitmaps {
+        if glyph_id < glyph_count {
+            color_glyphs.insert(glyph_id, ColorGlyph::Bitmap(bitmap));
+        }
+    }
+    // Layers scale better than bitmaps, so prefer them if a font has both.
+    if let (Some(colr), Some(cpal)) = (tables.get(b"COLR"), tables.get(b"CPAL")) {
+        for (glyph_id, layers) in parse_colr(colr, cpal).unwrap_or_default() {
+            if glyph_id < glyph_count && layers.iter().all(|(layer_glyph_id, _)| *layer_glyph_id < glyph_count) {
+                color_glyphs.insert(glyph_id, ColorGlyph::Layers(layers));
+            }
+        }
+    }
+    color_glyphs
+}
+
+/// The range of `table_bytes[start..start + length]` in `bytes`, where `table_bytes` is a slice of `bytes`.
+fn range_in_file(bytes: &[u8], table_bytes: &[u8], start: usize, length: usize) -> Result<Range<usize>> {
+    if start + length > table_bytes.len() {
+        return Err(Error);
+    }
+    let table_offset = table_bytes.as_ptr() as usize - bytes.as_ptr() as usize;
+    Ok(table_offset + start..table_offset + start + length)
+}
+
+/// Size and position of a bitmap in the `CBDT` table, in pixels.
+#[derive(Clone, Copy, Debug)]
+struct BitmapMetrics {
+    width: f32,
+    height: f32,
+    /// Position of the left edge relative to the glyph origin.
+    bearing_x: f32,
+    /// Position of the top edge relative to the baseline; positive is up.
+    bearing_y: f32,
+}
+
+impl BitmapMetrics {
+    fn read_small(reader: &mut Reader) -> Result<BitmapMetrics> {
+        let height = reader.read_u8()? as f32;
+        let width = reader.read_u8()? as f32;
+        let bearing_x = reader.read_i8()? as f32;
+        let bearing_y = reader.read_i8()? as f32;
+        // Advance.
+        reader.skip(1)?;
+        Ok(BitmapMetrics { width, height, bearing_x, bearing_y })
+    }
+
+    fn read_big(reader: &mut Reader) -> Result<BitmapMetrics> {
+        let metrics = Self::read_small(reader)?;
+        // Vertical metrics.
+        reader.skip(3)?;
+        Ok(metrics)
+    }
+}
+
+/// Parse the PNG images of the largest size in the `CBLC` and `CBDT` tables (used by e.g. Noto Color Emoji).
+fn parse_cbdt(bytes: &[u8], cblc: &[u8], cbdt: &[u8]) -> Result<Vec<(usize, ColorBitmap)>> {
+    let mut reader = Reader::new(cblc);
+    reader.skip(4)?;
+    let size_count = reader.read_u32()? as usize;
+    let mut largest_size = None;
+    for _ in 0..size_count {
+        let index_subtable_array_offset = reader.read_u32()? as usize;
+        // Size of the index subtables.
+        reader.skip(4)?;
+        let index_subtable_count = reader.read_u32()? as usize;
+        // Color ref, line metrics, and start and end glyph index.
+        reader.skip(4 + 12 + 12 + 2 + 2)?;
+        let _ppem_x = reader.read_u8()?;
+        let ppem_y = reader.read_u8()?;
+        // Bit depth and flags.
+        reader.skip(2)?;
+        if largest_size.map_or(true, |(largest_ppem, _, _)| ppem_y > largest_ppem) {
+            largest_size = Some((ppem_y, index_subtable_array_offset, index_subtable_count));
+        }
+    }
+    let (ppem, index_subtable_array_offset, index_subtable_count) = match largest_size {
+        Some(largest_size) => largest_size,
+        None => return Ok(Vec::new()),
+    };
+
+    let mut bitmaps = Vec::new();
+    let mut array_reader = Reader::new(cblc.get(index_subtable_array_offset..).ok_or(Error)?);
+    for _ in 0..index_subtable_count {
+        let first_glyph_id = array_reader.read_u16()? as usize;
+        let last_glyph_id = array_reader.read_u16()? as usize;
+        let subtable_offset = index_subtable_array_offset + array_reader.read_u32()? as usize;
+        if last_glyph_id < first_glyph_id {
+            return Err(Error);
+        }
+
+        let mut reader = Reader::new(cblc.get(subtable_offset..).ok_or(Error)?);
+        let index_format = reader.read_u16()?;
+        let image_format = reader.read_u16()?;
+        let image_data_offset = reader.read_u32()? as usize;
+        // Glyph id, and the offset and length of its data in `cbdt`.
+        let mut locations = Vec::new();
+        // Some index formats have the metrics for all their glyphs, instead of having them with the image.
+        let mut index_metrics = None;
+        match index_format {
+            1 | 3 => {
+                let mut offsets = Vec::with_capacity(last_glyph_id - first_glyph_id + 2);
+                for _ in first_glyph_id..=last_glyph_id + 1 {
+                    offsets.push(if index_format == 1 { reader.read_u32()? as usize } else { reader.read_u16()? as usize });
+                }
+                for (glyph_id, offsets) in (first_glyph_id..=last_glyph_id).zip(offsets.windows(2)) {
+                    if offsets[1] > offsets[0] {
+                        locations.push((glyph_id, image_data_offset + offsets[0], offsets[1] - offsets[0]));
+                    }
+                }
+            }
+            2 => {
+                let image_size = reader.read_u32()? as usize;
+                index_metrics = Some(BitmapMetrics::read_big(&mut reader)?);
+                for (index, glyph_id) in (first_glyph_id..=last_glyph_id).enumerate() {
+                    locations.push((glyph_id, image_data_offset + index * image_size, image_size));
+                }
+            }
+            4 => {
+                let glyph_count = reader.read_u32()? as usize;
+                let mut glyph_offsets = Vec::with_capacity(glyph_count + 1);
+                for _ in 0..=glyph_count {
+                    glyph_offsets.push((reader.read_u16()? as usize, reader.read_u16()? as usize));
+                }
+                for pair in glyph_offsets.windows(2) {
+                    let ((glyph_id, offset), (_, next_offset)) = (pair[0], pair[1]);
+                    if next_offset > offset {
+                        locations.push((glyph_id, image_data_offset + offset, next_offset - offset));
+                    }
+                }
+            }
+            5 => {
+                let image_size = reader.read_u32()? as usize;
+                index_metrics = Some(BitmapMetrics::read_big(&mut reader)?);
+                let glyph_count = reader.read_u32()? as usize;
+                for index in 0..glyph_count {
+                    let glyph_id = reader.read_u16()? as usize;
+                    locations.push((glyph_id, image_data_offset + index * image_size, image_size));
+                }
+            }
+            _ => continue,
+        }
+
+        for (glyph_id, offset, length) in locations {
+            let glyph_bytes = cbdt.get(offset..).and_then(|bytes| bytes.get(..length)).ok_or(Error)?;
+            let mut reader = Reader::new(glyph_bytes);
+            let metrics = match image_format {
+                17 => BitmapMetrics::read_small(&mut reader)?,
+                18 => BitmapMetrics::read_big(&mut reader)?,
+                19 => index_metrics.ok_or(Error)?,
+                // Not a PNG image.
+                _ => continue,
+            };
+            let png_length = reader.read_u32()? as usize;
+            let png_start = offset + (length - reader.bytes.len());
+            bitmaps.push((
+                glyph_id,
+                ColorBitmap {
+                    png_range: range_in_file(bytes, cbdt, png_start, png_length)?,
+                    pixels_per_em: ppem as f32,
+                    left: metrics.bearing_x,
+                    bottom: metrics.bearing_y - metrics.height,
+                    width: metrics.width,
+                    height: metrics.height,
+                },
+            ));
+        }
+    }
+    Ok(bitmaps)
+}
+
+/// The width and height of a PNG image, from its header.
+fn png_size(png: &[u8]) -> Option<(f32, f32)> {
+    if png.get(12..16)? != b"IHDR" {
+        return None;
+    }
+    let mut reader = Reader::new(png.get(16..24)?);
+    Some((reader.read_u32().ok()? as f32, reader.read_u32().ok()? as f32))
+}
+
+/// Parse the PNG images of the largest size in the `sbix` table (used by e.g. Apple Color Emoji).
+fn parse_sbix(bytes: &[u8], sbix: &[u8], glyph_count: usize) -> Result<Vec<(usize, ColorBitmap)>> {
+    let mut reader = Reader::new(sbix);
+    reader.skip(4)?;
+    let strike_count = reader.read_u32()? as usize;
+    let mut largest_strike = None;
+    for _ in 0..strike_count {
+        let strike_offset = reader.read_u32()? as usize;
+        let ppem = Reader::new(sbix.get(strike_offset..).ok_or(Error)?).read_u16()?;
+        if largest_strike.map_or(true, |(largest_ppem, _)| ppem > largest_ppem) {
+            largest_strike = Some((ppem, strike_offset));
+        }
+    }
+    let (ppem, strike_offset) = match largest_strike {
+        Some(largest_strike) => largest_strike,
+        None => return Ok(Vec::new()),
+    };
+
+    let strike = sbix.get(strike_offset..).ok_or(Error)?;
+    let mut reader = Reader::new(strike);
+    // Pixels per em, and pixels per inch.
+    reader.skip(4)?;
+    let mut offsets = Vec::with_capacity(glyph_count + 1);
+    for _ in 0..=glyph_count {
+        offsets.push(reader.read_u32()? as usize);
+    }
+    /// The start of the data for `glyph_id` in `strike`, and the data itself.
+    fn glyph_data<'a>(strike: &'a [u8], offsets: &[usize], glyph_id: usize) -> Option<(usize, &'a [u8])> {
+        let (start, end) = (offsets[glyph_id], offsets[glyph_id + 1]);
+        if end > start {
+            strike.get(start..end).map(|data| (start, data))
+        } else {
+            None
+        }
+    }
+
+    let mut bitmaps = Vec::new();
+    for glyph_id in 0..glyph_count {
+        let (mut start, mut data) = match glyph_data(strike, &offsets, glyph_id) {
+            Some(glyph_data) => glyph_data,
+            None => continue,
+        };
+        // A "dupe" uses the image of another glyph.
+        if data.get(4..8) == Some(b"dupe") {
+            let dupe_glyph_id = Reader::new(&data[8..]).read_u16()? as usize;
+            match glyph_data(strike, &offsets, dupe_glyph_id.min(glyph_count - 1)) {
+                Some((dupe_start, dupe_data)) => {
+                    start = dupe_start;
+                    data = dupe_data;
+                }
+                None => continue,
+            }
+        }
+        if data.get(4..8) != Some(b"png ") {
+            continue;
+        }
+        let mut reader = Reader::new(data);
+        let left = reader.read_i16()? as f32;
+        let bottom = reader.read_i16()? as f32;
+        let png = &data[8..];
+        let (width, height) = match png_size(png) {
+            Some(size) => size,
+            None => continue,
+        };
+        bitmaps.push((
+            glyph_id,
+            ColorBitmap {
+                png_range: range_in_file(bytes, strike, start + 8, png.len())?,
+                pixels_per_em: ppem as f32,
+                left,
+                bottom,
+                width,
+                height,
+            },
+        ));
+    }
+    Ok(bitmaps)
+}
+
+/// The glyph id of a layer and its RGBA color, or [`None`] to use the text color.
+type ColorLayer = (usize, Option<[u8; 4]>);
+
+/// Parse the layers of color glyphs in the `COLR` table (version 0, which version 1 tables also have for
+/// compatibility), with colors from the first palette in the `CPAL` table.
+fn parse_colr(colr: &[u8], cpal: &[u8]) -> Result<Vec<(usize, Vec<ColorLayer>)>> {
+    let mut reader = Reader::new(cpal);
+    reader.skip(2)?;
+    let palette_entry_count = reader.read_u16()? as usize;
+    // Number of palettes and color records.
+    reader.skip(4)?;
+    let color_records_offset = reader.read_u32()? as usize;
+    let first_palette_index = reader.read_u16()? as usize;
+    let mut reader = Reader::new(cpal.get(color_records_offset + first_palette_index * 4..).ok_or(Error)?);
+    let mut palette = Vec::with_capacity(palette_entry_count);
+    for _ in 0..palette_entry_count {
+        let (blue, green, red, alpha) = (reader.read_u8()?, reader.read_u8()?, reader.read_u8()?, reader.read_u8()?);
+        palette.push([red, green, blue, alpha]);
+    }
+
+    let mut reader = Reader::new(colr);
+    reader.skip(2)?;
+    let base_glyph_count = reader.read_u16()? as usize;
+    let base_glyphs_offset = reader.read_u32()? as usize;
+    let layers_offset = reader.read_u32()? as usize;
+    let layer_count = reader.read_u16()? as usize;
+
+    let mut reader = Reader::new(colr.get(layers_offset..).ok_or(Error)?);
+    let mut layers = Vec::with_capacity(layer_count);
+    for _ in 0..layer_count {
+        let glyph_id = reader.read_u16()? as usize;
+        let palette_index = reader.read_u16()?;
+        // 0xFFFF means the text color.
+        let color = if palette_index == 0xFFFF { None } else { Some(*palette.get(palette_index as usize).ok_or(Error)?) };
+        layers.push((glyph_id, color));
+    }
+
+    let mut reader = Reader::new(colr.get(base_glyphs_offset..).ok_or(Error)?);
+    let mut color_glyphs = Vec::with_capacity(base_glyph_count);
+    for _ in 0..base_glyph_count {
+        let glyph_id = reader.read_u16()? as usize;
+        let first_layer_index = reader.read_u16()? as usize;
+        let layer_count = reader.read_u16()? as usize;
+        color_glyphs.push((glyph_id, layers.get(first_layer_index..first_layer_index + layer_count).ok_or(Error)?.to_vec()));
+    }
+    Ok(color_glyphs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_colr() {
+        // Two colors in BGRA order, with the records right after the 14-byte header.
+        let cpal = [0, 0, 0, 2, 0, 1, 0, 2, 0, 0, 0, 14, 0, 0, 10, 20, 30, 255, 40, 50, 60, 128];
+        // Glyph 5 consists of glyph 3 in the second color, and glyph 4 in the color of the text.
+        let colr = [0, 0, 0, 1, 0, 0, 0, 14, 0, 0, 0, 20, 0, 2, 0, 5, 0, 0, 0, 2, 0, 3, 0, 1, 0, 4, 0xFF, 0xFF];
+        assert_eq!(parse_colr(&colr, &cpal).unwrap(), vec![(5, vec![(3, Some([60, 50, 40, 128])), (4, None)])]);
+    }
+}
@@ -1,7 +1,10 @@
 use crate::font::{Glyph, HorizontalMetrics, Outline, OutlinePoint, VectorFont};
 use crate::geometry::{AffineTransformation, LinearTransformation, Point, Rectangle, Transform, Vector};
+use std::collections::HashMap;
 use std::{mem, result};
 
+mod color;
+
 #[derive(Clone, Debug)]
 pub(crate) struct GlyphsParser<'a> {
     glyphs: Vec<Option<Glyph>>,
@@ -47,6 +50,11 @@ impl<'a> GlyphsParser<'a> {
     }
 
     fn parse_glyph(&mut self, index: usize) -> Result<Glyph> {
+        // Fonts with only color bitmaps (like most emoji fonts) don't have outlines.
+        if self.glyf_table_bytes.is_empty() {
+            let horizontal_metrics = self.parse_horizontal_metrics(index)?;
+            return Ok(Glyph { horizontal_metrics, bounds: Rectangle::default(), outline: Outline::default() });
+        }
         let start = self.parse_offset(index)?;
         let end = self.parse_offset(index + 1)?;
         let bytes = &self.glyf_table_bytes[start..end];
@@ -444,46 +452,69 @@ pub type Result<T> = result::Result<T, Error>;
 #[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
 pub struct Error;
 
+/// Parse a TrueType font (`.ttf`), or the first font in a TrueType collection (`.ttc`).
+///
+/// Only fonts with TrueType outlines (a `glyf` table) are supported, not ones with CFF outlines (most `.otf` files).
+/// Fonts without outlines but with color bitmaps (like most emoji fonts) are supported too; see
+/// [`VectorFont::color_glyphs`].
 pub fn parse_ttf(bytes: &[u8]) -> Result<VectorFont> {
-    let mut reader = Reader::new(&bytes[0..12]);
+    parse_ttf_with_index(bytes, 0)
+}
+
+/// The number of fonts in a TrueType collection (`.ttc`); 1 for a regular font file.
+pub fn font_count(bytes: &[u8]) -> usize {
+    if bytes.starts_with(b"ttcf") {
+        bytes.get(8..).and_then(|bytes| Reader::new(bytes).read_u32().ok()).map_or(0, |count| count as usize)
+    } else {
+        1
+    }
+}
+
+/// Find the tables of the font at `index` in a TrueType collection, or of the font itself if it's not a collection.
+fn parse_table_directory(bytes: &[u8], index: usize) -> Result<HashMap<[u8; 4], &[u8]>> {
+    let font_offset = if bytes.starts_with(b"ttcf") {
+        if index >= font_count(bytes) {
+            return Err(Error);
+        }
+        let mut reader = Reader::new(bytes);
+        reader.skip(12 + index * 4)?;
+        reader.read_u32()? as usize
+    } else if index == 0 {
+        0
+    } else {
+        return Err(Error);
+    };
+
+    let mut reader = Reader::new(bytes.get(font_offset..).ok_or(Error)?);
     let sfnt_version = reader.read_u32()?;
     if ![0x00010000, u32::from_be_bytes(*b"true")].contains(&sfnt_version) {
         return Err(Error);
     }
     let table_count = reader.read_u16()? as usize;
     reader.skip(6)?;
-    let mut cmap_table_bytes = None;
-    let mut glyf_table_bytes = None;
-    let mut head_table_bytes = None;
-    let mut hhea_table_bytes = None;
-    let mut hmtx_table_bytes = None;
-    let mut loca_table_bytes = None;
-    let mut maxp_table_bytes = None;
-    for index in 0..table_count {
-        let mut reader = Reader::new(&bytes[(12 + index * 16)..][..16]);
+    let mut tables = HashMap::new();
+    for _ in 0..table_count {
         let table_tag = reader.read_u32()?;
         reader.skip(4)?;
         let offset = reader.read_u32()? as usize;
         let length = reader.read_u32()? as usize;
-        let table_bytes = &bytes[offset..][..length];
-        match &table_tag.to_be_bytes() {
-            b"cmap" => cmap_table_bytes = Some(table_bytes),
-            b"glyf" => glyf_table_bytes = Some(table_bytes),
-            b"head" => head_table_bytes = Some(table_bytes),
-            b"hhea" => hhea_table_bytes = Some(table_bytes),
-            b"hmtx" => hmtx_table_bytes = Some(table_bytes),
-            b"loca" => loca_table_bytes = Some(table_bytes),
-            b"maxp" => maxp_table_bytes = Some(table_bytes),
-            _ => {}
-        }
+        // Offsets are relative to the start of the file, even in collections.
+        let table_bytes = bytes.get(offset..).and_then(|bytes| bytes.get(..length)).ok_or(Error)?;
+        tables.insert(table_tag.to_be_bytes(), table_bytes);
     }
-    let cmap_table_bytes = cmap_table_bytes.ok_or(Error)?;
-    let glyf_table_bytes = glyf_table_bytes.ok_or(Error)?;
-    let head_table_bytes = head_table_bytes.ok_or(Error)?;
-    let hhea_table_bytes = hhea_table_bytes.ok_or(Error)?;
-    let hmtx_table_bytes = hmtx_table_bytes.ok_or(Error)?;
-    let loca_table_bytes = loca_table_bytes.ok_or(Error)?;
-    let maxp_table_bytes = maxp_table_bytes.ok_or(Error)?;
+    Ok(tables)
+}
+
+/// Parse the font at `index` in a TrueType collection (`.ttc`). See also [`font_count`].
+pub fn parse_ttf_with_index(bytes: &[u8], index: usize) -> Result<VectorFont> {
+    let tables = parse_table_directory(bytes, index)?;
+    let cmap_table_bytes = *tables.get(b"cmap").ok_or(Error)?;
+    let glyf_table_bytes = tables.get(b"glyf").copied().unwrap_or_default();
+    let head_table_bytes = *tables.get(b"head").ok_or(Error)?;
+    let hhea_table_bytes = *tables.get(b"hhea").ok_or(Error)?;
+    let hmtx_table_bytes = *tables.get(b"hmtx").ok_or(Error)?;
+    let loca_table_bytes = tables.get(b"loca").copied().unwrap_or_default();
+    let maxp_table_bytes = *tables.get(b"maxp").ok_or(Error)?;
     let mut reader = Reader::new(hhea_table_bytes);
     reader.skip(4)?;
     let ascender = reader.read_i16()? as f32;
@@ -506,13 +537,20 @@ pub fn parse_ttf(bytes: &[u8]) -> Result<VectorFont> {
     reader.skip(6)?;
     let index_to_loc_format = IndexToLocFormat::from_i16(reader.read_i16()?).ok_or(Error)?;
     reader.skip(2)?;
+    let (char_code_to_glyph_index_map, supplementary_char_code_to_glyph_index_map) =
+        parse_char_code_to_glyph_index_map(cmap_table_bytes)?;
+    let color_glyphs = color::parse_color_glyphs(bytes, &tables, glyph_count);
+    if glyf_table_bytes.is_empty() && color_glyphs.is_empty() {
+        return Err(Error);
+    }
     Ok(VectorFont {
         units_per_em,
         ascender,
         descender,
         line_gap,
         bounds,
-        char_code_to_glyph_index_map: parse_char_code_to_glyph_index_map(cmap_table_bytes)?,
+        char_code_to_glyph_index_map,
+        supplementary_char_code_to_glyph_index_map,
         glyphs: GlyphsParser::new(
             glyph_count,
             advance_width_count,
@@ -522,30 +560,38 @@ pub fn parse_ttf(bytes: &[u8]) -> Result<VectorFont> {
             glyf_table_bytes,
         )
         .parse_glyphs()?,
+        color_glyphs,
     })
 }
 
-fn parse_char_code_to_glyph_index_map(bytes: &[u8]) -> Result<Vec<usize>> {
+/// Glyph indices for characters below U+10000 (indexed by char code), and for characters from U+10000.
+type CharCodeToGlyphIndexMaps = (Vec<usize>, HashMap<u32, usize>);
+
+fn parse_char_code_to_glyph_index_map(bytes: &[u8]) -> Result<CharCodeToGlyphIndexMaps> {
     let mut reader = Reader::new(bytes);
     reader.skip(2)?;
-    let mut subtable_bytes = None;
+    let mut bmp_subtable_bytes = None;
+    let mut full_subtable_bytes = None;
     let subtable_count = reader.read_u16()? as usize;
     for _ in 0..subtable_count {
         let platform_id = reader.read_u16()?;
         let encoding_id = reader.read_u16()?;
         let offset = reader.read_u32()? as usize;
         if let (0, _) | (3, 1) | (3, 10) = (platform_id, encoding_id) {
-            subtable_bytes = Some(&bytes[offset..]);
-            break;
+            let subtable_bytes = bytes.get(offset..).ok_or(Error)?;
+            match Reader::new(subtable_bytes).read_u16()? {
+                4 => bmp_subtable_bytes = bmp_subtable_bytes.or(Some(subtable_bytes)),
+                // Format 12 covers all of Unicode, so prefer it when it's there.
+                12 => full_subtable_bytes = full_subtable_bytes.or(Some(subtable_bytes)),
+                _ => {}
+            }
         }
     }
-    let subtable_bytes = subtable_bytes.ok_or(Error)?;
-    let mut reader = Reader::new(subtable_bytes);
-    let format = reader.read_u16()?;
-    let bytes = &subtable_bytes[2..];
-    match format {
-        4 => parse_char_code_to_glyph_index_map_format_4(bytes),
-        _ => Err(Error),
+    if let Some(subtable_bytes) = full_subtable_bytes {
+        parse_char_code_to_glyph_index_map_format_12(&subtable_bytes[2..])
+    } else {
+        let subtable_bytes = bmp_subtable_bytes.ok_or(Error)?;
+        Ok((parse_char_code_to_glyph_index_map_format_4(&subtable_bytes[2..])?, HashMap::new()))
     }
 }
 
@@ -572,7 +618,7 @@ fn parse_char_code_to_glyph_index_map_format_4(bytes: &[u8]) -> Result<Vec<usize
         let start_code = start_code_reader.read_u16()?;
         let id_delta = id_delta_reader.read_u16()? as usize;
         let id_range_offset = id_range_offset_reader.read_u16()? as usize;
-        for code in start_code..end_code {
+        for code in start_code..=end_code {
             let mut id = if id_range_offset == 0 {
                 code
             } else {
@@ -592,6 +638,74 @@ fn parse_char_code_to_glyph_index_map_format_4(bytes: &[u8]) -> Result<Vec<usize
     Ok(char_code_to_glyph_index_map)
 }
 
+fn parse_char_code_to_glyph_index_map_format_12(bytes: &[u8]) -> Result<CharCodeToGlyphIndexMaps> {
+    let mut reader = Reader::new(bytes);
+    reader.skip(10)?;
+    let group_count = reader.read_u32()? as usize;
+    let mut char_code_to_glyph_index_map = Vec::new();
+    let mut supplementary_char_code_to_glyph_index_map = HashMap::new();
+    for _ in 0..group_count {
+        let start_code = reader.read_u32()?;
+        let end_code = reader.read_u32()?;
+        let start_id = reader.read_u32()? as usize;
+        if end_code < start_code || end_code > 0x10FFFF {
+            return Err(Error);
+        }
+        for code in start_code..=end_code {
+            let id = start_id + (code - start_code) as usize;
+            if code < 0x10000 {
+                if char_code_to_glyph_index_map.len() <= code as usize {
+                    char_code_to_glyph_index_map.resize(code as usize + 1, 0);
+                }
+                char_code_to_glyph_index_map[code as usize] = id;
+            } else {
+                supplementary_char_code_to_glyph_index_map.insert(code, id);
+            }
+        }
+    }
+    Ok((char_code_to_glyph_index_map, supplementary_char_code_to_glyph_index_map))
+}
+
+/// The names of the font at `index` (see [`font_count`]) that people might use to refer to it, with their name ids:
+/// the family name (1, e.g. "DejaVu Sans"), the full name (4, e.g. "DejaVu Sans Bold"), and the typographic family
+/// name (16, only for fonts with more than the four basic styles).
+pub fn parse_ttf_names(bytes: &[u8], index: usize) -> Result<Vec<(u16, String)>> {
+    let tables = parse_table_directory(bytes, index)?;
+    let name_table_bytes = *tables.get(b"name").ok_or(Error)?;
+    let mut reader = Reader::new(name_table_bytes);
+    reader.skip(2)?;
+    let count = reader.read_u16()? as usize;
+    let storage_offset = reader.read_u16()? as usize;
+    let storage_bytes = name_table_bytes.get(storage_offset..).ok_or(Error)?;
+    let mut names = Vec::new();
+    for _ in 0..count {
+        let platform_id = reader.read_u16()?;
+        let encoding_id = reader.read_u16()?;
+        reader.skip(2)?;
+        let name_id = reader.read_u16()?;
+        let length = reader.read_u16()? as usize;
+        let offset = reader.read_u16()? as usize;
+        // Family name, full name, and typographic family name.
+        if ![1, 4, 16].contains(&name_id) {
+            continue;
+        }
+        let string_bytes = storage_bytes.get(offset..).and_then(|bytes| bytes.get(..length)).ok_or(Error)?;
+        let name = match (platform_id, encoding_id) {
+            // UTF-16BE.
+            (0, _) | (3, 0) | (3, 1) | (3, 10) => String::from_utf16_lossy(
+                &string_bytes.chunks_exact(2).map(|pair| u16::from_be_bytes([pair[0], pair[1]])).collect::<Vec<u16>>(),
+            ),
+            // Mac Roman, which is the same as ASCII for the names we care about.
+            (1, 0) => string_bytes.iter().map(|&byte| byte as char).collect(),
+            _ => continue,
+        };
+        if !names.contains(&(name_id, name.clone())) {
+            names.push((name_id, name));
+        }
+    }
+    Ok(names)
+}
+
 #[cfg(test)]
 mod tests {
     use std::fs;
@@ -611,4 +725,18 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn test_names_and_glyph_index() {
+        let bytes = fs::read(Path::new(env!("CARGO_MANIFEST_DIR")).join("../resources/Ubuntu-R.ttf")).unwrap();
+        assert_eq!(crate::ttf_parser::font_count(&bytes), 1);
+        let names = crate::ttf_parser::parse_ttf_names(&bytes, 0).unwrap();
+        assert!(names.contains(&(1, "Ubuntu".to_string())), "{:?}", names);
+
+        let font = crate::ttf_parser::parse_ttf(&bytes).unwrap();
+        assert!(font.has_glyph('a'));
+        assert!(font.has_glyph('z'));
+        assert!(!font.has_glyph('\u{4e2d}'));
+        assert_eq!(font.glyph_index('\u{1f600}'), 0);
+    }
 }
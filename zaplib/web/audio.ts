@@ -0,0 +1,304 @@
+// AudioWorklet backend for `zaplib::audio`. These functions are called from Rust using
+// `call_js_async`; keep in sync with `audio.rs`.
+//
+// Rust allocates a ring buffer in Wasm memory. For output, a thread fills it by calling the
+// audio callback, and the worklet below reads from it. For input, the worklet writes to it and
+// a thread calls the callback. Since Wasm memory is a SharedArrayBuffer, we can pass it to the
+// worklet directly.
+
+import { CallJsCallback } from "types";
+
+// Source of the AudioWorkletProcessors, which get loaded from a Blob URL so that we don't
+// need to serve a separate file. `state` contains the write index, read index, and status
+// (in that order), as in `RingBuffer` in `audio.rs`.
+const processorSource = `
+class ZaplibAudioProcessor extends AudioWorkletProcessor {
+  constructor(options) {
+    super();
+    const { memory, statePtr, samplesPtr, capacity, channels } = options.processorOptions;
+    this.state = new Int32Array(memory, statePtr, 3);
+    this.samples = new Float32Array(memory, samplesPtr, capacity * channels);
+    this.capacityMask = capacity - 1;
+    this.channels = channels;
+  }
+
+  process(_inputs, outputs) {
+    const state = this.state;
+    if (Atomics.load(state, 2) !== 0) {
+      // Stop requested; acknowledge so Rust can free the ring buffer.
+      Atomics.store(state, 2, 2);
+      Atomics.notify(state, 2);
+      this.port.postMessage("stopped");
+      return false;
+    }
+    const output = outputs[0];
+    const frames = output[0].length;
+    const writeIndex = Atomics.load(state, 0);
+    const readIndex = Atomics.load(state, 1);
+    // On underrun we leave the rest of the output silent.
+    const available = Math.min((writeIndex - readIndex) | 0, frames);
+    for (let frame = 0; frame < available; frame++) {
+      const offset = ((readIndex + frame) & this.capacityMask) * this.channels;
+      for (let channel = 0; channel < output.length; channel++) {
+        output[channel][frame] = this.samples[offset + channel];
+      }
+    }
+    Atomics.store(state, 1, (readIndex + available) | 0);
+    Atomics.notify(state, 1);
+    return true;
+  }
+}
+registerProcessor("zaplib-audio", ZaplibAudioProcessor);
+
+class ZaplibAudioInputProcessor extends ZaplibAudioProcessor {
+  process(inputs) {
+    const state = this.state;
+    if (Atomics.load(state, 2) !== 0) {
+      Atomics.store(state, 2, 2);
+      Atomics.notify(state, 2);
+      this.port.postMessage("stopped");
+      return false;
+    }
+    const input = inputs[0];
+    // No channels means that the source is not producing anything (yet).
+    if (input.length === 0) return true;
+    const writeIndex = Atomics.load(state, 0);
+    const readIndex = Atomics.load(state, 1);
+    // When the callback can't keep up, we drop what doesn't fit.
+    const free = this.capacityMask + 1 - ((writeIndex - readIndex) | 0);
+    const frames = Math.min(input[0].length, free);
+    for (let frame = 0; frame < frames; frame++) {
+      const offset = ((writeIndex + frame) & this.capacityMask) * this.channels;
+      for (let channel = 0; channel < this.channels; channel++) {
+        const samples = input[channel] || input[0];
+        this.samples[offset + channel] = samples[frame];
+      }
+    }
+    Atomics.store(state, 0, (writeIndex + frames) | 0);
+    Atomics.notify(state, 0);
+    return true;
+  }
+}
+registerProcessor("zaplib-audio-input", ZaplibAudioInputProcessor);
+`;
+
+// Not in TypeScript's DOM types yet.
+type AudioContextWithSinkId = AudioContext & {
+  setSinkId?: (sinkId: string) => Promise<void>;
+};
+
+// Throw an error that Rust maps to a specific `AudioError` (or e.g. `CameraError`; see
+// `video.ts`).
+export const namedError = (name: string, message: string): Error =>
+  new Error(`${name}: ${message}`);
+
+// Prefix the message of a DOMException (e.g. from `getUserMedia`) with its name, so that Rust
+// can map it to an `AudioError` (or e.g. `CameraError`).
+export const toNamedError = (e: unknown): unknown =>
+  e instanceof Error && !e.message.startsWith(e.name)
+    ? namedError(e.name, e.message)
+    : e;
+
+// List devices as flat [id, label, isDefault] triples.
+export const listDevices = async (kind: MediaDeviceKind): Promise<string[]> => {
+  const devices = navigator.mediaDevices
+    ? await navigator.mediaDevices.enumerateDevices()
+    : [];
+  const matching = devices.filter((device) => device.kind === kind);
+  // Some browsers don't list devices (at least without permission), so we always return at
+  // least the default device, which has an empty id.
+  const hasDefault = matching.some((device) => device.deviceId === "default");
+  const result: string[] = hasDefault ? [] : ["", "", "1"];
+  for (const device of matching) {
+    const isDefault = device.deviceId === "default";
+    result.push(device.deviceId, device.label, isDefault ? "1" : "0");
+  }
+  return result;
+};
+
+// Parameters of `_zaplibAudioStart` and `_zaplibAudioInputStart`; see `start_worklet` in
+// `audio.rs`.
+type StartParams = {
+  deviceId: string;
+  sampleRate: number;
+  channels: number;
+  statePtr: number;
+  samplesPtr: number;
+  capacity: number;
+};
+
+const parseStartParams = (params: string[]): StartParams => {
+  const [deviceId, sampleRate, channels, statePtr, samplesPtr, capacity] =
+    params;
+  return {
+    deviceId,
+    sampleRate: parseInt(sampleRate),
+    channels: parseInt(channels),
+    statePtr: parseInt(statePtr),
+    samplesPtr: parseInt(samplesPtr),
+    capacity: parseInt(capacity),
+  };
+};
+
+const createContext = (sampleRate: number): AudioContextWithSinkId => {
+  if (typeof AudioWorkletNode === "undefined") {
+    throw namedError("NotSupportedError", "AudioWorklet is not available");
+  }
+  return new AudioContext({
+    latencyHint: "interactive",
+    ...(sampleRate ? { sampleRate } : {}),
+  });
+};
+
+// `resume` never resolves if the page is not allowed to play audio, since it waits for user
+// input. So give up after a short while.
+const resumeContext = async (context: AudioContext): Promise<void> => {
+  await Promise.race([
+    context.resume(),
+    new Promise((resolve) => setTimeout(resolve, 500)),
+  ]);
+  if (context.state !== "running") {
+    throw namedError(
+      "NotAllowedError",
+      "Audio can only be started in response to user input"
+    );
+  }
+};
+
+// Load the processors, and create a node for `name` that shares the ring buffer.
+const createWorkletNode = async (
+  context: AudioContext,
+  name: string,
+  options: AudioWorkletNodeOptions,
+  memory: WebAssembly.Memory,
+  { statePtr, samplesPtr, capacity, channels }: StartParams
+): Promise<AudioWorkletNode> => {
+  const url = URL.createObjectURL(
+    new Blob([processorSource], { type: "application/javascript" })
+  );
+  try {
+    await context.audioWorklet.addModule(url);
+  } finally {
+    URL.revokeObjectURL(url);
+  }
+  return new AudioWorkletNode(context, name, {
+    ...options,
+    processorOptions: {
+      memory: memory.buffer,
+      statePtr,
+      samplesPtr,
+      capacity,
+      channels,
+    },
+  });
+};
+
+export const makeAudioCallJsCallbacks = (
+  getMemory: () => WebAssembly.Memory
+): Record<string, CallJsCallback> => ({
+  async _zaplibAudioOutputDevices() {
+    return listDevices("audiooutput");
+  },
+  async _zaplibAudioInputDevices() {
+    return listDevices("audioinput");
+  },
+  async _zaplibAudioStart(params) {
+    const startParams = parseStartParams(params as string[]);
+    const { deviceId, channels } = startParams;
+    const context = createContext(startParams.sampleRate);
+    try {
+      if (deviceId && deviceId !== "default") {
+        if (!context.setSinkId) {
+          throw namedError(
+            "NotSupportedError",
+            "Selecting an audio device is not supported in this browser"
+          );
+        }
+        await context.setSinkId(deviceId);
+      }
+      await resumeContext(context);
+      const node = await createWorkletNode(
+        context,
+        "zaplib-audio",
+        { numberOfInputs: 0, outputChannelCount: [channels] },
+        getMemory(),
+        startParams
+      );
+      node.port.onmessage = () => {
+        node.disconnect();
+        context.close();
+      };
+      node.connect(context.destination);
+    } catch (e) {
+      context.close();
+      throw toNamedError(e);
+    }
+    return [context.sampleRate.toString()];
+  },
+  async _zaplibAudioInputStart(params) {
+    const startParams = parseStartParams(params as string[]);
+    const { deviceId, channels } = startParams;
+    if (!navigator.mediaDevices?.getUserMedia) {
+      throw namedError("NotSupportedError", "Audio input is not available");
+    }
+    let stream: MediaStream;
+    try {
+      // Asks the user for permission, and throws a NotAllowedError if they deny it. We turn
+      // off processing that is meant for calls, since it distorts e.g. music.
+      stream = await navigator.mediaDevices.getUserMedia({
+        audio: {
+          ...(deviceId ? { deviceId: { exact: deviceId } } : {}),
+          channelCount: { ideal: channels },
+          echoCancellation: false,
+          noiseSuppression: false,
+          autoGainControl: false,
+        },
+      });
+    } catch (e) {
+      throw toNamedError(e);
+    }
+    const stopTracks = () => {
+      for (const track of stream.getTracks()) track.stop();
+    };
+    // The context resamples the input if we request a sample rate different from the
+    // device's.
+    let context: AudioContext;
+    try {
+      context = createContext(startParams.sampleRate);
+    } catch (e) {
+      stopTracks();
+      throw e;
+    }
+    try {
+      await resumeContext(context);
+      const source = context.createMediaStreamSource(stream);
+      // The processor only runs when connected to the destination, so it has an output, which
+      // stays silent. The input gets mixed to the requested number of channels.
+      const node = await createWorkletNode(
+        context,
+        "zaplib-audio-input",
+        {
+          numberOfInputs: 1,
+          outputChannelCount: [1],
+          channelCount: channels,
+          channelCountMode: "explicit",
+        },
+        getMemory(),
+        startParams
+      );
+      node.port.onmessage = () => {
+        source.disconnect();
+        node.disconnect();
+        stopTracks();
+        context.close();
+      };
+      source.connect(node);
+      node.connect(context.destination);
+    } catch (e) {
+      stopTracks();
+      context.close();
+      throw toNamedError(e);
+    }
+    return [context.sampleRate.toString()];
+  },
+});
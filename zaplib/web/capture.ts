@@ -0,0 +1,150 @@
+// Canvas capture backend for `zaplib::capture`. These functions are called from Rust using
+// `call_js` and `call_js_async`; keep in sync with `capture.rs`.
+//
+// We capture the canvas element on the browser's main thread, which also works when rendering
+// happens in a worker using an OffscreenCanvas.
+
+import { namedError } from "audio";
+import { CallJsCallback } from "types";
+
+type Recording = {
+  format: "webm" | "png";
+  // Resolves with the recorded files once stopped.
+  stop: () => Promise<Uint8Array[]>;
+};
+
+let recording: Recording | undefined;
+
+const blobToBytes = async (blob: Blob): Promise<Uint8Array> =>
+  new Uint8Array(await blob.arrayBuffer());
+
+const getCanvasOrThrow = (
+  getCanvas: () => HTMLCanvasElement | undefined
+): HTMLCanvasElement => {
+  const canvas = getCanvas();
+  if (!canvas) {
+    throw namedError("NotSupportedError", "There is no canvas to capture");
+  }
+  return canvas;
+};
+
+const startWebmRecording = (
+  canvas: HTMLCanvasElement,
+  frameRate: number
+): Recording => {
+  const mimeType = "video/webm";
+  if (
+    typeof MediaRecorder === "undefined" ||
+    !MediaRecorder.isTypeSupported(mimeType)
+  ) {
+    throw namedError(
+      "NotSupportedError",
+      "Recording WebM is not supported in this browser"
+    );
+  }
+  const chunks: Blob[] = [];
+  const recorder = new MediaRecorder(canvas.captureStream(frameRate), {
+    mimeType,
+  });
+  recorder.ondataavailable = (event) => chunks.push(event.data);
+  recorder.start();
+  return {
+    format: "webm",
+    stop: () =>
+      new Promise((resolve, reject) => {
+        recorder.onstop = () => {
+          blobToBytes(new Blob(chunks, { type: mimeType })).then(
+            (bytes) => resolve([bytes]),
+            reject
+          );
+        };
+        recorder.stop();
+      }),
+  };
+};
+
+const startPngRecording = (
+  canvas: HTMLCanvasElement,
+  frameRate: number
+): Recording => {
+  const frames: Promise<Uint8Array>[] = [];
+  const interval = setInterval(() => {
+    frames.push(
+      new Promise((resolve, reject) => {
+        canvas.toBlob((blob) => {
+          if (blob) {
+            blobToBytes(blob).then(resolve, reject);
+          } else {
+            reject(new Error("Could not capture canvas"));
+          }
+        }, "image/png");
+      })
+    );
+  }, 1000 / frameRate);
+  return {
+    format: "png",
+    stop: () => {
+      clearInterval(interval);
+      return Promise.all(frames);
+    },
+  };
+};
+
+export const makeCaptureCallJsCallbacks = (
+  getCanvas: () => HTMLCanvasElement | undefined
+): Record<string, CallJsCallback> => {
+  // Set when starting fails, and thrown when stopping, since `_zaplibRecordingStart` is called
+  // without waiting for the result.
+  let startError: unknown;
+
+  return {
+    async _zaplibCaptureFrame() {
+      const canvas = getCanvasOrThrow(getCanvas);
+      const captureCanvas = document.createElement("canvas");
+      captureCanvas.width = canvas.width;
+      captureCanvas.height = canvas.height;
+      const context = captureCanvas.getContext("2d");
+      if (!context) {
+        throw namedError("NotSupportedError", "Could not create canvas");
+      }
+      // Wait for the next frame to be drawn, since Rust requests a redraw.
+      await new Promise((resolve) => requestAnimationFrame(resolve));
+      context.drawImage(canvas, 0, 0);
+      const imageData = context.getImageData(
+        0,
+        0,
+        captureCanvas.width,
+        captureCanvas.height
+      );
+      return [
+        captureCanvas.width.toString(),
+        captureCanvas.height.toString(),
+        new Uint8Array(imageData.data.buffer),
+      ];
+    },
+    _zaplibRecordingStart(params) {
+      const [format, frameRate] = params as string[];
+      startError = undefined;
+      try {
+        const canvas = getCanvasOrThrow(getCanvas);
+        recording =
+          format === "webm"
+            ? startWebmRecording(canvas, parseFloat(frameRate))
+            : startPngRecording(canvas, parseFloat(frameRate));
+      } catch (e) {
+        startError = e;
+      }
+    },
+    async _zaplibRecordingStop() {
+      if (startError !== undefined) {
+        throw startError;
+      }
+      if (!recording) {
+        throw new Error("Not recording");
+      }
+      const { format, stop } = recording;
+      recording = undefined;
+      return [format, ...(await stop())];
+    },
+  };
+};
@@ -1,19 +1,25 @@
 import { cursorMap } from "cursor_map";
 import {
+  AsyncQueue,
+  callJsAsyncImpl,
   copyArrayToRustBuffer,
   getZapParamType,
+  makeInitializeIdempotent,
   normalizeInitParams,
 } from "common";
 import { makeTextarea, TextareaEvent } from "make_textarea";
 import {
   CallRustAsync,
+  CallRustStream,
   CallJsCallback,
   CallRustSync,
   ZapParam,
+  ZapArray,
   PostMessageTypedArray,
   ZapParamType,
   Initialize,
   IsInitialized,
+  RuntimeInstance,
 } from "types";
 import {
   getCachedZapBuffer,
@@ -21,6 +27,7 @@ import {
   isZapBuffer,
   checkValidZapArray,
   getZapBufferCef,
+  releaseZapBuffer,
   ZapBuffer,
 } from "zap_buffer";
 import { ZerdeBuilder } from "zerde";
@@ -37,7 +44,8 @@ declare global {
     cefCallRustAsync: (
       name: string,
       params: CefParams,
-      callbackId: number
+      callbackId: number,
+      streaming: boolean
     ) => void;
     cefCallRustSync: (name: string, params: CefParams) => FromCefParams;
     cefReadyForMessages: () => void;
@@ -62,6 +70,8 @@ overwriteTypedArraysWithZapArrays();
 let newCallbackId = 0;
 // keeping track of pending callbacks from rust side
 const pendingCallbacks: Record<number, (arg0: ZapParam[]) => void> = {};
+// Chunks of `callRustStream` calls; `undefined` marks the end of the stream.
+const pendingStreams: Record<number, AsyncQueue<ZapParam[] | undefined>> = {};
 
 const transformParamsForRust = (params: ZapParam[]): CefParams =>
   params.map((param) => {
@@ -87,7 +97,7 @@ const transformParamsForRust = (params: ZapParam[]): CefParams =>
 export const callRustAsync: CallRustAsync = <T extends ZapParam[]>(
   name: string,
   params: ZapParam[] = []
-): Promise<T> => {
+) => {
   const callbackId = newCallbackId++;
   const promise = new Promise<T>((resolve, _reject) => {
     pendingCallbacks[callbackId] = (data) => {
@@ -95,8 +105,41 @@ export const callRustAsync: CallRustAsync = <T extends ZapParam[]>(
       resolve(data as T);
     };
   });
-  window.cefCallRustAsync(name, transformParamsForRust(params), callbackId);
-  return promise;
+  window.cefCallRustAsync(
+    name,
+    transformParamsForRust(params),
+    callbackId,
+    false
+  );
+  // TODO(JP): Support cancellation in CEF; for now the `CallRustCancelToken` is never cancelled.
+  return Object.assign(promise, { cancel: () => undefined });
+};
+
+export const callRustStream: CallRustStream = async function* <
+  T extends ZapParam[]
+>(name: string, params: ZapParam[] = []) {
+  const callbackId = newCallbackId++;
+  const stream = new AsyncQueue<ZapParam[] | undefined>();
+  pendingStreams[callbackId] = stream;
+  // Called by `CallRustStream::finish`.
+  pendingCallbacks[callbackId] = () => {
+    stream.push(undefined);
+    delete pendingStreams[callbackId];
+  };
+  window.cefCallRustAsync(
+    name,
+    transformParamsForRust(params),
+    callbackId,
+    true
+  );
+
+  while (true) {
+    const chunk = await stream.next();
+    if (!chunk) {
+      return;
+    }
+    yield chunk as T;
+  }
 };
 
 function _zaplibReturnParams(params: ZapParam[]) {
@@ -105,9 +148,15 @@ function _zaplibReturnParams(params: ZapParam[]) {
   delete pendingCallbacks[callbackId];
 }
 
+function _zaplibStreamChunk(params: ZapParam[]) {
+  const callbackId = JSON.parse(params[0] as string);
+  pendingStreams[callbackId].push(params.slice(1));
+}
+
 // Initial set of framework-specific functions
 const fromCefJsFunctions: Record<string, CallJsCallback> = {
   _zaplibReturnParams,
+  _zaplibStreamChunk,
 };
 
 /// Users must call this function to register functions as runnable from
@@ -169,6 +218,15 @@ const transformReturnParams = (returnParams: FromCefParams): ZapParam[] =>
     }
   });
 
+// Drop our reference to a buffer that we got from Rust right away, instead of when it gets garbage
+// collected. The array (and any other arrays over the same buffer) must not be used afterwards.
+export const releaseBuffer = (zapArray: ZapArray): void => {
+  if (!(typeof zapArray === "object" && isZapBuffer(zapArray.buffer))) {
+    throw new Error("Only pass Zap arrays to releaseBuffer");
+  }
+  releaseZapBuffer(zapArray.buffer as ZapBuffer);
+};
+
 // TODO(JP): Some of this code is duplicated with callRustAsync/call_js; see if we can reuse some.
 export const callRustSync: CallRustSync = <T extends ZapParam[]>(
   name: string,
@@ -202,7 +260,7 @@ export const deserializeZapArrayFromPostMessage = (
 let initialized = false;
 export const isInitialized: IsInitialized = () => initialized;
 
-export const initialize: Initialize = (initParams) =>
+const initializeOnce: Initialize = (initParams) =>
   new Promise<void>((resolve) => {
     initParams = normalizeInitParams(initParams);
 
@@ -213,6 +271,14 @@ export const initialize: Initialize = (initParams) =>
     };
 
     window.fromCefCallJsFunction = (name, params) => {
+      if (name === "_zaplibCallJsAsync") {
+        callJsAsyncImpl({
+          params: transformReturnParams(params),
+          jsFunctions: fromCefJsFunctions,
+          callRustAsync,
+        });
+        return;
+      }
       fromCefJsFunctions[name](transformReturnParams(params));
     };
 
@@ -288,4 +354,14 @@ export const initialize: Initialize = (initParams) =>
     });
   });
 
+export const initialize: Initialize = makeInitializeIdempotent(initializeOnce);
+
 export const close = (): void => void 0;
+
+export const setLogFilter = (_filter: string): void => {
+  console.warn("setLogFilter is only supported in WebAssembly");
+};
+
+export const createInstance = (): RuntimeInstance => {
+  throw new Error("Multiple Zaplib instances are not supported in Zapium");
+};
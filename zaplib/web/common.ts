@@ -3,10 +3,14 @@
 
 import { RpcSpec } from "rpc_types";
 import {
+  CallJsCallback,
+  CallRustAsync,
   CallRustSync,
   CreateBuffer,
   FileHandle,
+  Initialize,
   InitParams,
+  LoadingProgress,
   MutableBufferData,
   RustZapParam,
   TlsAndStackData,
@@ -16,7 +20,7 @@ import {
   ZapParam,
   ZapParamType,
 } from "types";
-import { inWorker } from "type_of_runtime";
+import { inNodeJs, inWorker } from "type_of_runtime";
 import {
   checkValidZapArray,
   getCachedZapBuffer,
@@ -71,6 +75,150 @@ function _createLinkedChannels(): { local: Channel; remote: Channel } {
   return { local, remote };
 }
 
+// Values that get pushed as they come in, and pulled by awaiting `next()`, in the same order.
+export class AsyncQueue<T> {
+  private values: T[] = [];
+  private waiting: ((value: T) => void)[] = [];
+
+  push(value: T): void {
+    const resolve = this.waiting.shift();
+    if (resolve) {
+      resolve(value);
+    } else {
+      this.values.push(value);
+    }
+  }
+
+  next(): Promise<T> {
+    if (this.values.length > 0) {
+      return Promise.resolve(this.values.shift() as T);
+    }
+    return new Promise((resolve) => this.waiting.push(resolve));
+  }
+}
+
+// Flag that Rust reads as a `CallRustCancelToken`, created with `createCancelToken`. We hold on to a
+// reference until the call has finished (`drop`), so that `cancel` can safely write to it until then.
+export type CancelToken = { ptr: number; cancel: () => void; drop: () => void };
+
+export const createCancelToken = (
+  wasmMemory: WebAssembly.Memory,
+  wasmExports: WasmExports
+): CancelToken => {
+  const ptr = Number(wasmExports.createCancelToken());
+  let dropped = false;
+  return {
+    ptr,
+    cancel: () => {
+      if (!dropped) {
+        Atomics.store(new Uint32Array(wasmMemory.buffer, ptr, 1), 0, 1);
+      }
+    },
+    drop: () => {
+      if (!dropped) {
+        dropped = true;
+        wasmExports.dropCancelToken(BigInt(ptr));
+      }
+    },
+  };
+};
+
+// Iterates over the chunks of a `callRustStream` call. When the caller stops iterating early (e.g. using
+// `break` in a `for await` loop), we cancel the call and discard the remaining chunks in the background,
+// transforming them so that their buffers get freed.
+export async function* callRustStreamImpl<T>({
+  next,
+  cancelToken,
+  transformParamsFromRust,
+}: {
+  next: () => Promise<{ done: false; chunk: RustZapParam[] } | { done: true }>;
+  cancelToken: CancelToken;
+  transformParamsFromRust: (params: RustZapParam[]) => ZapParam[];
+}): AsyncGenerator<T, void, undefined> {
+  let done = false;
+  try {
+    while (true) {
+      const result = await next();
+      if (result.done) {
+        done = true;
+        return;
+      }
+      yield transformParamsFromRust(result.chunk) as unknown as T;
+    }
+  } finally {
+    if (done) {
+      cancelToken.drop();
+    } else {
+      cancelToken.cancel();
+      const drain = async () => {
+        while (true) {
+          const result = await next();
+          if (result.done) {
+            return;
+          }
+          transformParamsFromRust(result.chunk);
+        }
+      };
+      // Errors mean that the WebAssembly instance has crashed, which gets reported elsewhere.
+      drain().then(cancelToken.drop, () => undefined);
+    }
+  }
+}
+
+// Handles `Cx::call_js_async`: calls a function registered using `registerCallJsCallbacks`, and sends its
+// return value (or error) back to Rust. Keep in sync with `Cx::resolve_call_js` in `executor.rs`.
+export const callJsAsyncImpl = async ({
+  params,
+  jsFunctions,
+  callRustAsync,
+}: {
+  params: ZapParam[];
+  jsFunctions: Record<string, CallJsCallback>;
+  callRustAsync: CallRustAsync;
+}): Promise<void> => {
+  const [callJsId, fnName, timeoutMs, ...fnParams] = params as [
+    string,
+    string,
+    string,
+    ...ZapParam[]
+  ];
+  let status = "ok";
+  let message = "";
+  let returnParams: ZapParam[] = [];
+  let timeoutId: ReturnType<typeof setTimeout> | undefined;
+  try {
+    const fn = jsFunctions[fnName];
+    if (!fn) {
+      throw new Error(
+        `call_js_async with ${fnName} is not available. Have you registered it using \`registerCallJsCallbacks\`?`
+      );
+    }
+    const timeout = new Promise<never>((_resolve, reject) => {
+      if (Number(timeoutMs) > 0) {
+        timeoutId = setTimeout(() => {
+          status = "timeout";
+          reject();
+        }, Number(timeoutMs));
+      }
+    });
+    const result = await Promise.race([fn(fnParams), timeout]);
+    returnParams = (result as ZapParam[] | undefined) || [];
+  } catch (e) {
+    if (status !== "timeout") {
+      status = "error";
+      message = e instanceof Error ? e.message : String(e);
+    }
+  } finally {
+    clearTimeout(timeoutId);
+  }
+  await callRustAsync("_zaplibCallJsReturn", [
+    callJsId,
+    status,
+    message,
+    ...returnParams,
+  ]);
+};
+
 // This class allows you to hook up bi-directional async calls across web-worker
 // boundaries where a single call to or from a worker can 'wait' on the response.
 // Errors in receivers are propigated back to the caller as a rejection.
@@ -424,6 +572,82 @@ export const makeZerdeBuilder = (
   });
 };
 
+// Node.js built-in modules, using the `require` that `zaplib_nodejs_polyfill` stores. We can't use `require`
+// directly, since the browser builds don't have it, and our workers get evaluated without it.
+export const nodeRequire = (id: string): any => {
+  const zaplibNodeRequire = (globalThis as any).zaplibNodeRequire;
+  if (!zaplibNodeRequire) {
+    throw new Error(
+      "Import zaplib/dist/zaplib_nodejs_polyfill.development to use Zaplib in Node.js"
+    );
+  }
+  return zaplibNodeRequire(id);
+};
+
+// Node.js doesn't have `XMLHttpRequest` (or synchronous `fetch`), so we read `file:` URLs from disk instead.
+export const readFileSyncNodeJs = (url: URL): Uint8Array => {
+  if (url.protocol !== "file:") {
+    throw new Error(
+      `Only file: URLs can be loaded in Node.js (got ${url.href}); set \`baseUri\` in \`zaplib.initialize\` to a file: URL`
+    );
+  }
+  return new Uint8Array(
+    nodeRequire("fs").readFileSync(nodeRequire("url").fileURLToPath(url))
+  );
+};
+
+// Like `fetch`, but calls `onProgress` whenever a chunk of the body has been downloaded.
+// The returned `Response` can be passed directly into e.g. `WebAssembly.compileStreaming`.
+export const fetchWithProgress = async (
+  url: string,
+  onProgress: (progress: LoadingProgress) => void
+): Promise<Response> => {
+  const response = await fetch(url);
+  if (!response.ok || !response.body) return response;
+
+  // With compression, `Content-Length` is the compressed size, while we count decompressed bytes.
+  const contentLength = response.headers.get("Content-Length");
+  const total =
+    contentLength && !response.headers.get("Content-Encoding")
+      ? parseInt(contentLength, 10)
+      : undefined;
+  let loaded = 0;
+  onProgress({ loaded, total });
+
+  const reader = response.body.getReader();
+  const body = new ReadableStream<Uint8Array>({
+    async pull(controller) {
+      const { done, value } = await reader.read();
+      if (done) {
+        controller.close();
+        return;
+      }
+      loaded += value.byteLength;
+      onProgress({ loaded, total });
+      controller.enqueue(value);
+    },
+    cancel(reason) {
+      return reader.cancel(reason);
+    },
+  });
+  return new Response(body, {
+    status: response.status,
+    statusText: response.statusText,
+    headers: response.headers,
+  });
+};
+
+// Default for `baseUri` in `zaplib.initialize`, which is used to resolve relative paths.
+export const getDefaultBaseUri = (): string => {
+  if (globalThis.location) {
+    return `${globalThis.location.protocol}//${globalThis.location.host}/`;
+  }
+  if (inNodeJs) {
+    return `${nodeRequire("url").pathToFileURL(process.cwd()).href}/`;
+  }
+  return "unknown://";
+};
+
 export const getWasmEnv = ({
   getExports,
   memory,
@@ -459,6 +683,19 @@ export const getWasmEnv = ({
       const out = parseString(parseInt(charsPtr), parseInt(len));
       console.log(out);
     },
+    _consoleLogWithLevel: (level, charsPtr, len) => {
+      const out = parseString(parseInt(charsPtr), parseInt(len));
+      // See https://docs.rs/log/latest/log/enum.Level.html
+      const logFunctions = [
+        console.log,
+        console.error,
+        console.warn,
+        console.info,
+        console.debug,
+        console.trace,
+      ];
+      (logFunctions[level] || console.log)(out);
+    },
     _throwError: (charsPtr, len) => {
       throw new RustPanic(parseString(parseInt(charsPtr), parseInt(len)));
     },
@@ -504,32 +741,37 @@ export const getWasmEnv = ({
       sendEventFromAnyThread(eventPtr);
     },
     readUrlSync: (urlPtr, urlLen, bufPtrOut, bufLenOut) => {
-      if (!inWorker) {
-        // Main browser thread doesn't support synchronous+arraybuffer XMLHttpRequest.
-        // TODO(JP): Use task worker for this instead.
-        throw new Error("Not yet implemented");
-      }
-
-      const url = parseString(urlPtr, urlLen);
-      const request = new XMLHttpRequest();
-      request.responseType = "arraybuffer";
-      request.open("GET", new URL(url, baseUri).href, false /* synchronous */);
-      request.send(null);
-
-      if (request.status === 200) {
-        const exports = getExports();
-        const outputBufPtr = createWasmBuffer(
-          memory,
-          exports,
-          new Uint8Array(request.response)
-        );
-        new Uint32Array(memory.buffer, bufPtrOut, 1)[0] = outputBufPtr;
-        new Uint32Array(memory.buffer, bufLenOut, 1)[0] =
-          request.response.byteLength;
-        return 1;
+      const url = new URL(parseString(urlPtr, urlLen), baseUri);
+      let data: Uint8Array;
+      if (inNodeJs) {
+        try {
+          data = readFileSyncNodeJs(url);
+        } catch (e) {
+          console.error(e);
+          return 0;
+        }
       } else {
-        return 0;
+        if (!inWorker) {
+          // Main browser thread doesn't support synchronous+arraybuffer XMLHttpRequest.
+          // TODO(JP): Use task worker for this instead.
+          throw new Error("Not yet implemented");
+        }
+
+        const request = new XMLHttpRequest();
+        request.responseType = "arraybuffer";
+        request.open("GET", url.href, false /* synchronous */);
+        request.send(null);
+        if (request.status !== 200) {
+          return 0;
+        }
+        data = new Uint8Array(request.response);
       }
+
+      const exports = getExports();
+      const outputBufPtr = createWasmBuffer(memory, exports, data);
+      new Uint32Array(memory.buffer, bufPtrOut, 1)[0] = outputBufPtr;
+      new Uint32Array(memory.buffer, bufLenOut, 1)[0] = data.byteLength;
+      return 1;
     },
     randomU64: () =>
       new BigUint64Array(
@@ -642,7 +884,11 @@ export const callRustSyncImpl: (options: {
       zerdeBuilder.sendU32(ZapParamType.String);
       zerdeBuilder.sendString(param);
     } else {
-      if (param.buffer instanceof ZapBuffer) {
+      if (
+        param.buffer instanceof ZapBuffer &&
+        // Buffers of other Zaplib instances get copied below.
+        param.buffer.__zaplibWasmMemory === wasmMemory
+      ) {
         checkValidZapArray(param);
         if (param.buffer.__zaplibBufferData.readonly) {
           zerdeBuilder.sendU32(getZapParamType(param, true));
@@ -726,6 +972,30 @@ export const createErrorCheckers = (
   };
 };
 
+// Wraps `initialize`, so that calling it again with the same `wasmModule` and `canvas` returns the same
+// `Promise` instead of initializing again. This makes it safe to call from UI components that can get
+// mounted multiple times (e.g. in React's StrictMode).
+export const makeInitializeIdempotent = (
+  initializeOnce: Initialize
+): Initialize => {
+  let firstCall: { initParams: InitParams; promise: Promise<void> } | undefined;
+  return (initParams) => {
+    if (firstCall) {
+      if (
+        firstCall.initParams.wasmModule !== initParams.wasmModule ||
+        firstCall.initParams.canvas !== initParams.canvas
+      ) {
+        throw new Error(
+          "Only call zaplib.initialize() once (calling it again is only allowed with the same `wasmModule` and `canvas`)"
+        );
+      }
+      return firstCall.promise;
+    }
+    firstCall = { initParams, promise: initializeOnce(initParams) };
+    return firstCall.promise;
+  };
+};
+
 export function normalizeInitParams(initParams: InitParams): InitParams {
   const newInitParams: InitParams = { ...initParams };
   if (
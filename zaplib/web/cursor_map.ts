@@ -24,4 +24,7 @@ export const cursorMap = [
   "nwse-resize", // NwseResize=>21,
   "col-resize", // ColResize=>22,
   "row-resize", // RowResize=>23,
+  "grab", // Grab=>24,
+  "grabbing", // Grabbing=>25,
+  // Custom=>26 is sent using a separate message; see `defineCustomMouseCursor`.
 ];
@@ -0,0 +1,289 @@
+// Framework-agnostic core of the UI framework integrations (`zaplib_react.ts`, `zaplib_vue.ts`,
+// `zaplib_svelte.ts`). The integrations are thin wrappers around this, so that they all behave the
+// same way; put any logic that isn't specific to a framework in here.
+
+import * as zaplib from "zaplib_runtime";
+import {
+  CallJsCallback,
+  CallRustAsyncPromise,
+  InitParams,
+  ZaplibInstance,
+  ZapParam,
+} from "types";
+
+export type EmbedInitParams = Omit<InitParams, "canvas">;
+
+// The canvas that the default Zaplib instance renders to when embedded in a UI framework. We only
+// ever make one, since `zaplib.initialize` transfers control of the canvas to a worker, which can only
+// happen once per canvas. Reusing it makes mounting, unmounting, and mounting again (e.g. in React's
+// StrictMode) idempotent. Isolated embeds have a canvas of their own; see `ZaplibEmbed`.
+let sharedCanvas: HTMLCanvasElement | undefined;
+const getSharedCanvas = (): HTMLCanvasElement => {
+  if (!sharedCanvas) {
+    sharedCanvas = document.createElement("canvas");
+  }
+  return sharedCanvas;
+};
+
+// Move the canvas of `embed` into `container`, which it fills entirely. Returns a function that takes
+// it out again, if it's still in `container` by then.
+export const mountCanvas = (
+  embed: ZaplibEmbed,
+  container: HTMLElement
+): (() => void) => {
+  const canvas = embed.canvas;
+  // The canvas is positioned absolutely (see `zaplib.css`).
+  if (getComputedStyle(container).position === "static") {
+    container.style.position = "relative";
+  }
+  container.appendChild(canvas);
+  // Zaplib only checks the canvas size when the window resizes, so pretend that happened.
+  window.dispatchEvent(new Event("resize"));
+  return () => {
+    if (canvas.parentNode === container) {
+      container.removeChild(canvas);
+    }
+  };
+};
+
+export const paramsEqual = (
+  a: ZapParam[] | undefined,
+  b: ZapParam[] | undefined
+): boolean =>
+  a === b ||
+  (!!a &&
+    !!b &&
+    a.length === b.length &&
+    a.every((param, index) => param === b[index]));
+
+// Buffers don't have a value we can use in a cache key, so we give each of them a unique id instead.
+const bufferIds = new WeakMap<object, number>();
+let nextBufferId = 0;
+const paramKey = (param: ZapParam): string => {
+  if (typeof param === "string") {
+    return JSON.stringify(param);
+  }
+  let id = bufferIds.get(param);
+  if (id === undefined) {
+    id = nextBufferId++;
+    bufferIds.set(param, id);
+  }
+  return `buffer:${id}`;
+};
+
+type CachedCall =
+  | { status: "pending"; promise: Promise<void> }
+  | { status: "done"; result: ZapParam[] }
+  | { status: "error"; error: unknown };
+
+// Zaplib as used by the components of one app: the parameters to initialize it with, and the state
+// that the components share.
+export class ZaplibEmbed {
+  readonly initParams: EmbedInitParams;
+  // Render to a canvas, which gets shown by the `ZaplibCanvas` component of the integration.
+  readonly withCanvas: boolean;
+  // The default Zaplib instance, or one created using `zaplib.createInstance` for isolated embeds.
+  readonly instance: ZaplibInstance;
+  private ownCanvas: HTMLCanvasElement | undefined;
+  private initialized: Promise<void> | undefined;
+  private cachedCalls = new Map<string, CachedCall>();
+
+  // Isolated embeds get their own Zaplib instance and canvas, so multiple apps can be shown on the
+  // same page. Otherwise all embeds share the default instance.
+  constructor(
+    initParams: EmbedInitParams,
+    withCanvas: boolean,
+    isolated: boolean
+  ) {
+    this.initParams = initParams;
+    this.withCanvas = withCanvas;
+    this.instance = isolated ? zaplib.createInstance() : zaplib;
+    if (isolated) {
+      this.ownCanvas = document.createElement("canvas");
+    }
+  }
+
+  get canvas(): HTMLCanvasElement {
+    return this.ownCanvas || getSharedCanvas();
+  }
+
+  // Initialize Zaplib, the first time this is called. This doesn't have to wait for the canvas to be
+  // mounted, since `mountCanvas` tells Zaplib about the new size.
+  initialize(): Promise<void> {
+    if (!this.initialized) {
+      this.initialized = this.instance.initialize({
+        ...this.initParams,
+        canvas: this.withCanvas ? this.canvas : undefined,
+      });
+    }
+    return this.initialized;
+  }
+
+  // `zaplib.callRustAsync`, after initializing Zaplib.
+  callRustAsync<T extends ZapParam[] = ZapParam[]>(
+    name: string,
+    params?: ZapParam[]
+  ): CallRustAsyncPromise<T> {
+    let cancelled = false;
+    let call: CallRustAsyncPromise<T> | undefined;
+    const promise = this.initialize().then(() => {
+      call = this.instance.callRustAsync<T>(name, params);
+      if (cancelled) {
+        call.cancel();
+      }
+      return call;
+    });
+    return Object.assign(promise, {
+      cancel: () => {
+        cancelled = true;
+        call?.cancel();
+      },
+    });
+  }
+
+  // Returns the result of calling `name` with `params` if we have done so before, and otherwise
+  // calls it and returns a `Promise` that resolves once the result is available. Throws the error
+  // if the call failed. Strings are compared by value, buffers by identity.
+  //
+  // This is needed for things like React's Suspense: a component that suspends gets thrown away, so
+  // it can't remember the call it was waiting for by itself.
+  readCached(
+    name: string,
+    params: ZapParam[] = []
+  ): ZapParam[] | Promise<void> {
+    const key = [JSON.stringify(name), ...params.map(paramKey)].join(",");
+    const call = this.cachedCalls.get(key);
+    if (!call) {
+      const promise = this.callRustAsync(name, params).then(
+        (result) => {
+          this.cachedCalls.set(key, { status: "done", result });
+        },
+        (error) => {
+          this.cachedCalls.set(key, { status: "error", error });
+        }
+      );
+      this.cachedCalls.set(key, { status: "pending", promise });
+      return promise;
+    }
+    if (call.status === "pending") {
+      return call.promise;
+    } else if (call.status === "error") {
+      throw call.error;
+    }
+    return call.result;
+  }
+
+  // Forget the results of `readCached`, so the next call goes to Rust again.
+  clearCache(): void {
+    this.cachedCalls.clear();
+  }
+}
+
+export type CallRustState<T extends ZapParam[]> = {
+  result: T | undefined;
+  error: unknown;
+  loading: boolean;
+};
+
+export const initialCallRustState: CallRustState<never> = {
+  result: undefined,
+  error: undefined,
+  loading: true,
+};
+
+// Calls `name` with `params`, and reports changes in the `CallRustState` to `onUpdate`. The previous
+// `result` is kept while loading a new one. Returns a function that cancels the call (see
+// `CallRustCancelToken`) and stops reporting, for when the parameters change or the component unmounts.
+export const watchCallRust = <T extends ZapParam[]>(
+  embed: ZaplibEmbed,
+  name: string,
+  params: ZapParam[] | undefined,
+  onUpdate: (update: Partial<CallRustState<T>>) => void
+): (() => void) => {
+  let active = true;
+  onUpdate({ loading: true });
+  const promise = embed.callRustAsync<T>(name, params);
+  promise.then(
+    (result) => {
+      if (active) {
+        onUpdate({ result, error: undefined, loading: false });
+      }
+    },
+    (error) => {
+      if (active) {
+        onUpdate({ result: undefined, error, loading: false });
+      }
+    }
+  );
+  return () => {
+    active = false;
+    promise.cancel();
+  };
+};
+
+// Props of the `ZaplibCanvas` components.
+export type CanvasBindingProps = {
+  // Name of a function registered in Rust, which gets called with `params` whenever they change. Use this
+  // to sync props of your component to Rust.
+  rustFn?: string;
+  params?: ZapParam[];
+  // Functions that Rust can call using `Cx::call_js`, e.g. to send events back. Registered using
+  // `zaplib.registerCallJsCallbacks` while the component is mounted, so the names have to be unique
+  // within a Zaplib instance.
+  callbacks?: Record<string, CallJsCallback>;
+};
+
+// Keeps Rust up to date with the props of a `ZaplibCanvas` component.
+export class CanvasBinding {
+  private embed: ZaplibEmbed;
+  private rustFn: string | undefined;
+  private params: ZapParam[] | undefined;
+  private running: CallRustAsyncPromise<ZapParam[]> | undefined;
+  private callbacks: Record<string, CallJsCallback> = {};
+  private registeredNames: string[] = [];
+
+  constructor(embed: ZaplibEmbed) {
+    this.embed = embed;
+  }
+
+  update({ rustFn, params, callbacks = {} }: CanvasBindingProps): void {
+    if (
+      rustFn &&
+      params &&
+      !(rustFn === this.rustFn && paramsEqual(params, this.params))
+    ) {
+      // Only the latest params matter, so tell Rust that it can stop working on the previous ones.
+      this.running?.cancel();
+      this.running = this.embed.callRustAsync(rustFn, params);
+      this.running.catch(console.error);
+    }
+    this.rustFn = rustFn;
+    this.params = params;
+
+    // Register wrappers that call the latest callbacks, so components can pass in new functions on
+    // every render without having to register them again.
+    this.callbacks = callbacks;
+    const names = Object.keys(callbacks);
+    const removed = this.registeredNames.filter(
+      (name) => names.indexOf(name) === -1
+    );
+    if (removed.length > 0) {
+      this.embed.instance.unregisterCallJsCallbacks(removed);
+    }
+    const added: Record<string, CallJsCallback> = {};
+    for (const name of names) {
+      if (this.registeredNames.indexOf(name) === -1) {
+        added[name] = (params) => this.callbacks[name](params);
+      }
+    }
+    if (Object.keys(added).length > 0) {
+      this.embed.instance.registerCallJsCallbacks(added);
+    }
+    this.registeredNames = names;
+  }
+
+  dispose(): void {
+    this.running?.cancel();
+    this.update({});
+  }
+}
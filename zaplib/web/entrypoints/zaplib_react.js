@@ -0,0 +1,9 @@
+/* eslint-env node */
+
+"use strict";
+
+if (process.env.NODE_ENV === "production") {
+  module.exports = require("./zaplib_react.production.js");
+} else {
+  module.exports = require("./zaplib_react.development.js");
+}
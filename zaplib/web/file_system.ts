@@ -0,0 +1,72 @@
+// Origin Private File System (OPFS) backend for `save_file_async` and `load_file_async`. These
+// functions are called from Rust using `call_js_async`; keep in sync with `universal_file.rs`.
+
+import { CallJsCallback } from "types";
+
+// Not in TypeScript's DOM types yet.
+type FileSystemWritableFileStream = {
+  write(data: Uint8Array): Promise<void>;
+  close(): Promise<void>;
+  abort(): Promise<void>;
+};
+type WritableFileHandle = FileSystemFileHandle & {
+  createWritable(): Promise<FileSystemWritableFileStream>;
+};
+
+// Get the directory handle and file name for a path like "documents/notes.txt", creating
+// directories along the way if `create` is set.
+const resolvePath = async (
+  path: string,
+  create: boolean
+): Promise<[FileSystemDirectoryHandle, string]> => {
+  const parts = path.split("/").filter((part) => part !== "" && part !== ".");
+  const fileName = parts.pop();
+  if (!fileName || parts.includes("..")) {
+    throw new Error(`TypeError: Invalid path: ${path}`);
+  }
+  let directory = await navigator.storage.getDirectory();
+  for (const part of parts) {
+    directory = await directory.getDirectoryHandle(part, { create });
+  }
+  return [directory, fileName];
+};
+
+// Prefix errors with their name, so that Rust can detect e.g. "NotFoundError".
+const withErrorName =
+  (fn: CallJsCallback): CallJsCallback =>
+  async (params) => {
+    try {
+      return await fn(params);
+    } catch (e) {
+      throw e instanceof Error && !e.message.startsWith(e.name)
+        ? new Error(`${e.name}: ${e.message}`)
+        : e;
+    }
+  };
+
+export const fileSystemCallJsCallbacks: Record<string, CallJsCallback> = {
+  _zaplibSaveFile: withErrorName(async (params) => {
+    const [directory, fileName] = await resolvePath(params[0] as string, true);
+    // Copy the data out of the Wasm memory, since it can't be written directly from a SharedArrayBuffer.
+    const data = (params[1] as Uint8Array).slice();
+    const fileHandle = (await directory.getFileHandle(fileName, {
+      create: true,
+    })) as WritableFileHandle;
+    // Writes go to a temporary file, which only replaces the actual file when closing the stream,
+    // so this is atomic.
+    const writable = await fileHandle.createWritable();
+    try {
+      await writable.write(data);
+    } catch (e) {
+      await writable.abort();
+      throw e;
+    }
+    await writable.close();
+    return [];
+  }),
+  _zaplibLoadFile: withErrorName(async (params) => {
+    const [directory, fileName] = await resolvePath(params[0] as string, false);
+    const file = await (await directory.getFileHandle(fileName)).getFile();
+    return [new Uint8Array(await file.arrayBuffer())];
+  }),
+};
@@ -0,0 +1,125 @@
+// Declarations for the parts of the frameworks that our integrations use. The integrations are
+// built against these instead of the frameworks' own packages, so building Zaplib doesn't pull in
+// React or Vue; apps provide the real packages (see `peerDependencies` in package.json).
+//
+// Only declare names that the real typings export as well, since the generated declaration files
+// refer to them.
+
+declare module "react" {
+  type ReactNode =
+    | ReactElement
+    | string
+    | number
+    | boolean
+    | null
+    | undefined
+    | ReactNode[];
+
+  interface ReactElement {
+    type: unknown;
+    props: unknown;
+    key: string | number | null;
+  }
+
+  type CSSProperties = { [property: string]: string | number | undefined };
+
+  interface HTMLAttributes<T> {
+    className?: string;
+    style?: CSSProperties;
+    ref?: RefObject<T>;
+    [attribute: string]: unknown;
+  }
+
+  interface RefObject<T> {
+    readonly current: T | null;
+  }
+  interface MutableRefObject<T> {
+    current: T;
+  }
+
+  interface Provider<T> {
+    (props: { value: T; children?: ReactNode }): ReactElement | null;
+  }
+  interface Context<T> {
+    Provider: Provider<T>;
+  }
+
+  type DependencyList = readonly unknown[];
+  type EffectCallback = () => void | (() => void);
+  type SetStateAction<S> = S | ((previousState: S) => S);
+
+  function createContext<T>(defaultValue: T): Context<T>;
+  function createElement(
+    type: string | Provider<any>,
+    props?: Record<string, unknown> | null,
+    ...children: ReactNode[]
+  ): ReactElement;
+  function useContext<T>(context: Context<T>): T;
+  function useState<S>(
+    initialState: S | (() => S)
+  ): [S, (value: SetStateAction<S>) => void];
+  function useRef<T>(initialValue: T): MutableRefObject<T>;
+  function useRef<T>(initialValue: T | null): RefObject<T>;
+  function useRef<T = undefined>(): MutableRefObject<T | undefined>;
+  function useEffect(effect: EffectCallback, deps?: DependencyList): void;
+  function useLayoutEffect(effect: EffectCallback, deps?: DependencyList): void;
+  function useCallback<T extends (...args: any[]) => unknown>(
+    callback: T,
+    deps: DependencyList
+  ): T;
+}
+
+declare module "vue" {
+  interface App {
+    provide<T>(key: InjectionKey<T> | string, value: T): this;
+  }
+
+  // eslint-disable-next-line @typescript-eslint/ban-types
+  interface InjectionKey<T> extends Symbol {
+    readonly valueType?: T;
+  }
+
+  interface Ref<T> {
+    value: T;
+  }
+
+  interface VNode {
+    type: unknown;
+    props: Record<string, unknown> | null;
+  }
+
+  type PropConstructor<T> =
+    | { new (...args: any[]): T & Record<never, never> }
+    | { (): T };
+  type PropType<T> = PropConstructor<T> | PropConstructor<T>[];
+
+  interface DefineComponent<Props> {
+    props?: Props;
+  }
+
+  function defineComponent<Props>(options: {
+    name?: string;
+    props?: Record<string, PropType<unknown>>;
+    setup: (props: Props) => () => VNode;
+  }): DefineComponent<Props>;
+  function h(
+    type: string,
+    props?: Record<string, unknown> | null,
+    children?: unknown
+  ): VNode;
+  function inject<T>(key: InjectionKey<T> | string): T | undefined;
+  function onMounted(hook: () => void): void;
+  function onBeforeUnmount(hook: () => void): void;
+  function reactive<T extends object>(target: T): T;
+  function ref<T>(value: T): Ref<T>;
+  function ref<T = any>(): Ref<T | undefined>;
+  function toRaw<T>(observed: T): T;
+  function unref<T>(ref: T | Ref<T>): T;
+  function watch<T>(
+    source: () => T,
+    callback: (value: T, oldValue: T) => void
+  ): () => void;
+  function watchEffect(
+    effect: (onInvalidate: (fn: () => void) => void) => void
+  ): () => void;
+}
@@ -6,6 +6,7 @@ require("regenerator-runtime/runtime");
 require("../dist/zaplib_nodejs_polyfill.development");
 
 const fs = require("fs");
+const url = require("url");
 
 // @ts-ignore
 // eslint-disable-next-line @typescript-eslint/no-var-requires
@@ -27,12 +28,20 @@ test("calls dummy worker", async () => {
 test("initializes zaplib and calls rust", async () => {
   const wasmPath = "../../target/wasm32-unknown-unknown/debug/test_suite.wasm";
   const wasmModule = WebAssembly.compile(fs.readFileSync(wasmPath));
-  await zaplib.initialize({ wasmModule: wasmModule });
+  // Set explicitly since jsdom has a `location`, which we'd otherwise use.
+  const baseUri = `${url.pathToFileURL(process.cwd()).href}/`;
+  await zaplib.initialize({ wasmModule: wasmModule, baseUri });
   const buffer = new SharedArrayBuffer(8);
   const data = new Uint8Array(buffer);
   data.set([1, 2, 3, 4, 5, 6, 7, 8]);
   const [result] = await zaplib.callRustAsync("total_sum", [data]);
   expect(result).toBe("36");
+
+  // Files are read using `fs` in Node.js.
+  const [packageJson] = await zaplib.callRustAsync("read_file_to_string", [
+    "package.json",
+  ]);
+  expect(JSON.parse(packageJson).name).toBe("zaplib");
 });
 
 test("creates worker with undefined url", () => {
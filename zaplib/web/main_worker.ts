@@ -1,5 +1,6 @@
 import { cursorMap } from "cursor_map";
 import {
+  AsyncQueue,
   Rpc,
   getWasmEnv,
   makeThreadLocalStorageAndStackDataOnExistingThread,
@@ -17,6 +18,7 @@ import {
   SizingData,
   MutableBufferData,
   RustZapParam,
+  XrFrameData,
 } from "types";
 import { ZerdeParser } from "zerde";
 import { ZerdeEventloopEvents } from "zerde_eventloop_events";
@@ -28,6 +30,7 @@ import {
   WasmWorkerRpc,
   WebWorkerRpc,
   WorkerCallRustAsyncParams,
+  WorkerCallRustStreamChunk,
   WorkerEvent,
   MainWorkerChannelEvent,
 } from "rpc_types";
@@ -42,6 +45,7 @@ const isFirefox =
 type Timer = { id: number; repeats: number; sysId: NodeJS.Timer };
 
 export type Pointer = {
+  windowId: number;
   x: number;
   y: number;
   button: number;
@@ -54,6 +58,7 @@ export type Pointer = {
 export type PointerScroll = Pointer & {
   scrollX: number;
   scrollY: number;
+  pinch: number;
   isWheel: boolean;
 };
 
@@ -80,14 +85,17 @@ export class WasmApp {
   private zerdeEventloopEvents: ZerdeEventloopEvents;
   private appPtr: BigInt;
   private doWasmBlock!: boolean;
-  private xrCanPresent = false;
-  private xrIsPresenting = false;
   private zerdeParser!: ZerdeParser;
   private callRustAsyncNewCallbackId: number;
   private callRustAsyncPendingCallbacks: Record<
     number,
     (arg0: RustZapParam[]) => void
   >;
+  // Chunks of `callRustStream` calls, by callback id, until the final chunk has been picked up.
+  private callRustStreams: Record<
+    number,
+    AsyncQueue<WorkerCallRustStreamChunk>
+  >;
   // WebGLRenderer if we're using an OffscreenCanvas. If not, this is undefined.
   private webglRenderer: WebGLRenderer | undefined;
   // Promise which is set when we have an active RunWebGL call in the main browser thread.
@@ -128,6 +136,7 @@ export class WasmApp {
 
     this.callRustAsyncNewCallbackId = 0;
     this.callRustAsyncPendingCallbacks = {};
+    this.callRustStreams = {};
 
     if (offscreenCanvas) {
       this.webglRenderer = new WebGLRenderer(
@@ -140,6 +149,19 @@ export class WasmApp {
       );
     }
 
+    rpc.receive(
+      WorkerEvent.WindowCanvasResize,
+      ({ windowId, width, height, dpiFactor }) => {
+        this.zerdeEventloopEvents.windowCanvasResize(
+          windowId,
+          width,
+          height,
+          dpiFactor
+        );
+        this.requestAnimationFrame();
+      }
+    );
+
     rpc.receive(WorkerEvent.ScreenResize, (sizingData: SizingData) => {
       this.sizingData = sizingData;
       if (this.webglRenderer) {
@@ -150,14 +172,32 @@ export class WasmApp {
         width: this.sizingData.width,
         height: this.sizingData.height,
         dpiFactor: this.sizingData.dpiFactor,
-        xrIsPresenting: this.xrIsPresenting,
-        xrCanPresent: this.xrCanPresent,
+        xrIsPresenting: this.sizingData.xrIsPresenting,
+        xrCanPresent: this.sizingData.xrCanPresent,
         isFullscreen: this.sizingData.isFullscreen,
       });
       this.requestAnimationFrame();
     });
 
-    // this.run_async_webxr_check();
+    // Frames of the headset while presenting, which replace the regular animation frames. See `xr.ts`.
+    rpc.receive(WorkerEvent.XrFrame, async (frame: XrFrameData) => {
+      if (this.runWebGLPromise) {
+        await this.runWebGLPromise;
+      }
+      this.zerdeEventloopEvents.xrUpdate(frame);
+      this.zerdeEventloopEvents.animationFrame();
+      try {
+        this.doWasmIo();
+      } catch (e) {
+        if (e instanceof Error && e.name === "RustPanic") {
+          Atomics.store(wasmOnline, 0, 0);
+          rpc.send(WorkerEvent.Panic, e);
+        } else {
+          throw e;
+        }
+      }
+    });
+
     this.bindMouseAndTouch();
     this.bindKeyboard();
 
@@ -169,10 +209,15 @@ export class WasmApp {
       this.zerdeEventloopEvents.windowFocus(false);
       this.doWasmIo();
     });
+    rpc.receive(WorkerEvent.PointerLockChange, (locked: boolean) => {
+      this.zerdeEventloopEvents.pointerLockChanged(locked);
+      this.doWasmIo();
+    });
 
     const callRustAsync = ({
       name,
       params,
+      cancelTokenPtr,
     }: WorkerCallRustAsyncParams): Promise<RustZapParam[]> => {
       const callbackId = this.callRustAsyncNewCallbackId++;
       const promise = new Promise<RustZapParam[]>((resolve, _reject) => {
@@ -184,12 +229,53 @@ export class WasmApp {
         };
       });
 
-      this.zerdeEventloopEvents.callRustAsync(name, params, callbackId);
+      this.zerdeEventloopEvents.callRustAsync(
+        name,
+        params,
+        callbackId,
+        false,
+        cancelTokenPtr
+      );
       this.doWasmIo();
       return promise;
     };
     rpc.receive(WorkerEvent.CallRustAsync, callRustAsync);
 
+    const callRustStream = ({
+      name,
+      params,
+      cancelTokenPtr,
+    }: WorkerCallRustAsyncParams): number => {
+      const callbackId = this.callRustAsyncNewCallbackId++;
+      const stream = new AsyncQueue<WorkerCallRustStreamChunk>();
+      this.callRustStreams[callbackId] = stream;
+      // Called by `CallRustStream::finish`.
+      this.callRustAsyncPendingCallbacks[callbackId] = () => {
+        stream.push({ done: true });
+      };
+
+      this.zerdeEventloopEvents.callRustAsync(
+        name,
+        params,
+        callbackId,
+        true,
+        cancelTokenPtr
+      );
+      this.doWasmIo();
+      return callbackId;
+    };
+    const callRustStreamNext = async (
+      callbackId: number
+    ): Promise<WorkerCallRustStreamChunk> => {
+      const result = await this.callRustStreams[callbackId].next();
+      if (result.done) {
+        delete this.callRustStreams[callbackId];
+      }
+      return result;
+    };
+    rpc.receive(WorkerEvent.CallRustStream, callRustStream);
+    rpc.receive(WorkerEvent.CallRustStreamNext, callRustStreamNext);
+
     rpc.receive(WorkerEvent.IncrementArc, (arcPtr: number) => {
       this.exports.incrementArc(BigInt(arcPtr));
     });
@@ -233,6 +319,14 @@ export class WasmApp {
         MainWorkerChannelEvent.CallRustAsync,
         callRustAsync
       );
+      userWorkerRpc.receive(
+        MainWorkerChannelEvent.CallRustStream,
+        callRustStream
+      );
+      userWorkerRpc.receive(
+        MainWorkerChannelEvent.CallRustStreamNext,
+        callRustStreamNext
+      );
 
       userWorkerRpc.receive(
         MainWorkerChannelEvent.SendEventFromAnyThread,
@@ -261,7 +355,7 @@ export class WasmApp {
       width: this.sizingData.width,
       height: this.sizingData.height,
       dpiFactor: this.sizingData.dpiFactor,
-      xrCanPresent: this.xrCanPresent,
+      xrCanPresent: this.sizingData.xrCanPresent,
       canFullscreen: this.sizingData.canFullscreen,
       xrIsPresenting: false,
     });
@@ -297,8 +391,43 @@ export class WasmApp {
     rpc.send(WorkerEvent.SetDocumentTitle, title);
   }
 
+  // See `attach_window_canvas` in `cx_wasm32.rs`.
+  private attachWindowCanvas(
+    windowId: number,
+    canvasId: string,
+    width: number,
+    height: number,
+    x: number,
+    y: number
+  ): void {
+    rpc
+      .send(WorkerEvent.AttachWindowCanvas, {
+        windowId,
+        canvasId,
+        width,
+        height,
+        x,
+        y,
+      })
+      .then((result) => {
+        if (result && this.webglRenderer) {
+          this.webglRenderer.attachWindowCanvas(
+            windowId,
+            result.offscreenCanvas
+          );
+        }
+      });
+  }
+
+  private detachWindowCanvas(windowId: number): void {
+    rpc.send(WorkerEvent.DetachWindowCanvas, windowId);
+    if (this.webglRenderer) {
+      this.webglRenderer.detachWindowCanvas(windowId);
+    }
+  }
+
   private bindMouseAndTouch(): void {
-    let lastMousePointer;
+    let lastMousePointer: Pointer | undefined;
     // TODO(JP): Some day bring back touch scroll support..
     // let use_touch_scroll_overlay = window.ontouchstart === null;
     // if (use_touch_scroll_overlay) {
@@ -362,6 +491,7 @@ export class WasmApp {
     // }
 
     const mousePointers: {
+      windowId: number;
       x: number;
       y: number;
       button: number;
@@ -373,8 +503,9 @@ export class WasmApp {
     function mouseToPointer(e: RpcMouseEvent | RpcWheelEvent): Pointer {
       // @ts-ignore; TypeScript does not like the empty object declaration below, but we immediately fill every field
       const mf = mousePointers[e.button] || (mousePointers[e.button] = {});
-      mf.x = e.pageX;
-      mf.y = e.pageY;
+      mf.windowId = e.windowId;
+      mf.x = e.x;
+      mf.y = e.y;
       mf.button = e.button;
       mf.digit = e.button;
       mf.time = performance.now() / 1000.0;
@@ -402,8 +533,20 @@ export class WasmApp {
           this.zerdeEventloopEvents.pointerMove(mf);
         }
       }
-      lastMousePointer = mouseToPointer(event);
+      // Let the window that we were hovering over know that the mouse left it.
+      if (lastMousePointer && lastMousePointer.windowId !== event.windowId) {
+        this.zerdeEventloopEvents.pointerOut(lastMousePointer);
+      }
+      lastMousePointer = { ...mouseToPointer(event) };
       this.zerdeEventloopEvents.pointerHover(lastMousePointer);
+      // The movement keeps coming at the edge of the screen and while the pointer is locked.
+      if (event.movementX !== 0 || event.movementY !== 0) {
+        this.zerdeEventloopEvents.pointerRawMove(
+          lastMousePointer,
+          event.movementX,
+          event.movementY
+        );
+      }
       this.doWasmIo();
     });
     rpc.receive(WorkerEvent.WindowMouseOut, (event: RpcMouseEvent) => {
@@ -421,8 +564,9 @@ export class WasmApp {
         touchIdsByDigit[digit] = touch.identifier;
 
         this.zerdeEventloopEvents.pointerDown({
-          x: touch.pageX,
-          y: touch.pageY,
+          windowId: touch.windowId,
+          x: touch.x,
+          y: touch.y,
           button: 0,
           digit,
           time: performance.now() / 1000.0,
@@ -440,8 +584,9 @@ export class WasmApp {
           continue;
         }
         this.zerdeEventloopEvents.pointerMove({
-          x: touch.pageX,
-          y: touch.pageY,
+          windowId: touch.windowId,
+          x: touch.x,
+          y: touch.y,
           button: 0,
           digit,
           time: performance.now() / 1000.0,
@@ -462,8 +607,9 @@ export class WasmApp {
           }
           touchIdsByDigit[digit] = undefined;
           this.zerdeEventloopEvents.pointerUp({
-            x: touch.pageX,
-            y: touch.pageY,
+            windowId: touch.windowId,
+            x: touch.x,
+            y: touch.y,
             button: 0,
             digit,
             time: performance.now() / 1000.0,
@@ -499,6 +645,7 @@ export class WasmApp {
       }
       //console.log(event.deltaY / event.wheelDeltaY);
       //last_delta = delta;
+      // Keep these in sync with `SCROLL_LINE_HEIGHT` and `SCROLL_PAGE_HEIGHT` in scroll.rs.
       let fac = 1;
       if (event.deltaMode === 1) {
         fac = 40;
@@ -508,10 +655,14 @@ export class WasmApp {
         const offsetHeight = 800;
         fac = offsetHeight;
       }
+      // Browsers send trackpad pinches as wheel events with `ctrlKey` set, where the page would be zoomed
+      // by a factor of `exp(-deltaY / 100)`.
+      const isPinch = event.ctrlKey && !lastWasWheel;
       const pointerScroll = {
         ...pointer,
-        scrollX: event.deltaX * fac,
-        scrollY: event.deltaY * fac,
+        scrollX: isPinch ? 0 : event.deltaX * fac,
+        scrollY: isPinch ? 0 : event.deltaY * fac,
+        pinch: isPinch ? Math.exp(-event.deltaY / 100) - 1 : 0,
         isWheel: lastWasWheel,
       };
       this.zerdeEventloopEvents.pointerScroll(pointerScroll);
@@ -692,7 +843,7 @@ export class WasmApp {
   }
 
   private async requestAnimationFrame(): Promise<void> {
-    if (this.xrIsPresenting || this.hasRequestedAnimationFrame) {
+    if (this.sizingData.xrIsPresenting || this.hasRequestedAnimationFrame) {
       return;
     }
     this.hasRequestedAnimationFrame = true;
@@ -704,7 +855,7 @@ export class WasmApp {
         await this.runWebGLPromise;
       }
       this.hasRequestedAnimationFrame = false;
-      if (this.xrIsPresenting) {
+      if (this.sizingData.xrIsPresenting) {
         return;
       }
       this.zerdeEventloopEvents.animationFrame();
@@ -721,91 +872,17 @@ export class WasmApp {
     });
   }
 
-  // private runAsyncWebXRCheck(): void {
-  //   this.xrCanPresent = false;
-  //   this.xrIsPresenting = false;
-
-  //   // ok this changes a bunch in how the renderflow works.
-  //   // first thing we are going to do is get the vr displays.
-  //   // @ts-ignore - Let's not worry about XR.
-  //   const xrSystem = globalThis.navigator.xr;
-  //   if (xrSystem) {
-  //     xrSystem.isSessionSupported("immersive-vr").then((supported) => {
-  //       if (supported) {
-  //         this.xrCanPresent = true;
-  //       }
-  //     });
-  //   } else {
-  //     console.log("No webVR support found");
-  //   }
-  // }
-
+  // WebXR is only available on the main thread; see `xr.ts`.
   private xrStartPresenting(): void {
-    // TODO(JP): Some day bring back XR support?
-    // if (this.xr_can_present) {
-    //     navigator.xr.requestSession('immersive-vr', {requiredFeatures: ['local-floor']}).then(xr_session => {
-    //         let xr_layer = new XRWebGLLayer(xr_session, this.gl, {
-    //             antialias: false,
-    //             depth: true,
-    //             stencil: false,
-    //             alpha: false,
-    //             ignoreDepthValues: false,
-    //             framebufferScaleFactor: 1.5
-    //         });
-    //         xr_session.updateRenderState({baseLayer: xr_layer});
-    //         xr_session.requestReferenceSpace("local-floor").then(xr_reference_space => {
-    //             window.localStorage.setItem("xr_presenting", "true");
-    //             this.xr_reference_space = xr_reference_space;
-    //             this.xr_session = xr_session;
-    //             this.xr_is_presenting = true;
-    //             let first_on_resize = true;
-    //             // read shit off the gamepad
-    //             xr_session.gamepad;
-    //             // lets start the loop
-    //             let inputs = [];
-    //             let alternate = false;
-    //             let last_time;
-    //             let xr_on_request_animation_frame = (time, xr_frame) => {
-    //                 if (first_on_resize) {
-    //                     this.on_screen_resize();
-    //                     first_on_resize = false;
-    //                 }
-    //                 if (!this.xr_is_presenting) {
-    //                     return;
-    //                 }
-    //                 this.xr_session.requestAnimationFrame(xr_on_request_animation_frame);
-    //                 this.xr_pose = xr_frame.getViewerPose(this.xr_reference_space);
-    //                 if (!this.xr_pose) {
-    //                     return;
-    //                 }
-    //                 this.zerdeEventloopEvents.xr_update_inputs(xr_frame, xr_session, time, this.xr_pose, this.xr_reference_space)
-    //                 this.zerdeEventloopEvents.animation_frame(time / 1000.0);
-    //                 this.in_animation_frame = true;
-    //                 let start = performance.now();
-    //                 this.do_wasm_io();
-    //                 this.in_animation_frame = false;
-    //                 this.xr_pose = null;
-    //                 //let new_time = performance.now();
-    //                 //if (new_time - last_time > 13.) {
-    //                 //    console.log(new_time - last_time);
-    //                 // }
-    //                 //last_time = new_time;
-    //             }
-    //             this.xr_session.requestAnimationFrame(xr_on_request_animation_frame);
-    //             this.xr_session.addEventListener("end", () => {
-    //                 window.localStorage.setItem("xr_presenting", "false");
-    //                 this.xr_is_presenting = false;
-    //                 this.on_screen_resize();
-    //                 this.zerdeEventloopEvents.paint_dirty();
-    //                 this.request_animation_frame();
-    //             })
-    //         })
-    //     })
-    // }
+    if (this.sizingData.xrCanPresent) {
+      rpc.send(WorkerEvent.XrStartPresenting);
+    }
   }
 
   private xrStopPresenting(): void {
-    // ignore for now
+    if (this.sizingData.xrIsPresenting) {
+      rpc.send(WorkerEvent.XrStopPresenting);
+    }
   }
 
   sendEventFromAnyThread(eventPtr: BigInt): void {
@@ -947,10 +1024,55 @@ export class WasmApp {
         const callbackId = JSON.parse(params[0] as string);
         zelf.callRustAsyncPendingCallbacks[callbackId](params.slice(1));
         delete zelf.callRustAsyncPendingCallbacks[callbackId];
+      } else if (fnName === "_zaplibStreamChunk") {
+        const callbackId = JSON.parse(params[0] as string);
+        zelf.callRustStreams[callbackId].push({
+          done: false,
+          chunk: params.slice(1),
+        });
       } else {
         rpc.send(WorkerEvent.CallJs, { fnName, params });
       }
     },
+    // attach_window_canvas
+    function attachWindowCanvas19(zelf) {
+      const windowId = zelf.zerdeParser.parseU32();
+      const canvasId = zelf.zerdeParser.parseString();
+      const width = zelf.zerdeParser.parseF32();
+      const height = zelf.zerdeParser.parseF32();
+      const x = zelf.zerdeParser.parseF32();
+      const y = zelf.zerdeParser.parseF32();
+      zelf.attachWindowCanvas(windowId, canvasId, width, height, x, y);
+    },
+    // detach_window_canvas
+    function detachWindowCanvas20(zelf) {
+      zelf.detachWindowCanvas(zelf.zerdeParser.parseU32());
+    },
+    // define_custom_mouse_cursor
+    function defineCustomMouseCursor21(zelf) {
+      const id = zelf.zerdeParser.parseF64();
+      const width = zelf.zerdeParser.parseU32();
+      const height = zelf.zerdeParser.parseU32();
+      const hotspotX = zelf.zerdeParser.parseF32();
+      const hotspotY = zelf.zerdeParser.parseF32();
+      const rgba = zelf.zerdeParser.parseU8Slice();
+      rpc.send(WorkerEvent.DefineCustomMouseCursor, {
+        id,
+        width,
+        height,
+        hotspotX,
+        hotspotY,
+        rgba,
+      });
+    },
+    // set_custom_mouse_cursor
+    function setCustomMouseCursor22(zelf) {
+      rpc.send(WorkerEvent.SetCustomMouseCursor, zelf.zerdeParser.parseF64());
+    },
+    // set_pointer_lock
+    function setPointerLock23(zelf) {
+      rpc.send(WorkerEvent.SetPointerLock, zelf.zerdeParser.parseU32() !== 0);
+    },
   ];
 }
 
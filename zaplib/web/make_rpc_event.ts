@@ -1,12 +1,32 @@
+// Position of the canvas on the page. Coordinates in events are relative to this, since there can
+// be multiple canvases on the page (see `createInstance`).
+export type CanvasOffset = { x: number; y: number };
+
+// Position of a pointer within one of the app's windows. The main window (with id 0) is
+// drawn into the main canvas, so for that one the position is relative to that canvas. See
+// `getWindowPosition` in `wasm_runtime.ts`.
+export type WindowPosition = { windowId: number; x: number; y: number };
+
 export type RpcMouseEvent = Pick<
   MouseEvent,
-  "button" | "pageX" | "pageY" | "shiftKey" | "metaKey" | "ctrlKey" | "altKey"
->;
-export const makeRpcMouseEvent = (event: MouseEvent): RpcMouseEvent => {
+  | "button"
+  | "movementX"
+  | "movementY"
+  | "shiftKey"
+  | "metaKey"
+  | "ctrlKey"
+  | "altKey"
+> &
+  WindowPosition;
+export const makeRpcMouseEvent = (
+  event: MouseEvent,
+  position: WindowPosition
+): RpcMouseEvent => {
   return {
-    pageX: event.pageX,
-    pageY: event.pageY,
+    ...position,
     button: event.button,
+    movementX: event.movementX,
+    movementY: event.movementY,
     shiftKey: event.shiftKey,
     ctrlKey: event.ctrlKey,
     metaKey: event.metaKey,
@@ -14,20 +34,22 @@ export const makeRpcMouseEvent = (event: MouseEvent): RpcMouseEvent => {
   };
 };
 
-type RpcTouch = Pick<Touch, "pageX" | "pageY" | "identifier">;
+type RpcTouch = Pick<Touch, "identifier"> & WindowPosition;
 export type RpcTouchEvent = Pick<
   TouchEvent,
   "shiftKey" | "metaKey" | "ctrlKey" | "altKey"
 > & { changedTouches: RpcTouch[] };
-export const makeRpcTouchEvent = (event: TouchEvent): RpcTouchEvent => {
+export const makeRpcTouchEvent = (
+  event: TouchEvent,
+  getPosition: (touch: Touch) => WindowPosition
+): RpcTouchEvent => {
   return {
     shiftKey: event.shiftKey,
     ctrlKey: event.ctrlKey,
     metaKey: event.metaKey,
     altKey: event.altKey,
     changedTouches: Array.from(event.changedTouches).map((touch) => ({
-      pageX: touch.pageX,
-      pageY: touch.pageY,
+      ...getPosition(touch),
       identifier: touch.identifier,
     })),
   };
@@ -41,17 +63,18 @@ export type RpcWheelEvent = Pick<
   | "deltaY"
   | "wheelDeltaY"
   | "button"
-  | "pageX"
-  | "pageY"
   | "shiftKey"
   | "metaKey"
   | "ctrlKey"
   | "altKey"
->;
-export const makeRpcWheelEvent = (event: WheelEvent): RpcWheelEvent => {
+> &
+  WindowPosition;
+export const makeRpcWheelEvent = (
+  event: WheelEvent,
+  position: WindowPosition
+): RpcWheelEvent => {
   return {
-    pageX: event.pageX,
-    pageY: event.pageY,
+    ...position,
     button: event.button,
     timeStamp: event.timeStamp,
     deltaMode: event.deltaMode,
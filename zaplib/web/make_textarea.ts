@@ -24,8 +24,12 @@ export type TextareaEvent =
   | TextareaEventTextInput
   | TextareaEventTextCopy;
 
-// Create a hidden textarea which is purely used for text input into Rust.
-export function makeTextarea(callback: (taEvent: TextareaEvent) => void): {
+// Create a hidden textarea which is purely used for text input into Rust. With multiple canvases on
+// the page (see `createInstance`), only the textarea for which `isActive` returns true grabs focus.
+export function makeTextarea(
+  callback: (taEvent: TextareaEvent) => void,
+  isActive: () => boolean = () => true
+): {
   showTextIME: (pos: { x: number; y: number }) => void;
   textareaHasFocus: () => boolean;
 } {
@@ -36,6 +40,7 @@ export function makeTextarea(callback: (taEvent: TextareaEvent) => void): {
     setTimeout(() => {
       if (
         ta &&
+        isActive() &&
         document.activeElement !== ta &&
         !document
           .getElementById("zaplib_js_root")
@@ -90,7 +95,9 @@ export function makeTextarea(callback: (taEvent: TextareaEvent) => void): {
       return false;
     });
     document.body.appendChild(ta);
-    ta.focus();
+    if (isActive()) {
+      ta.focus();
+    }
     updateTextAreaPos();
 
     ta.addEventListener("cut", () => {
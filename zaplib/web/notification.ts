@@ -0,0 +1,29 @@
+// Backend for `Cx::show_system_notification`. These functions are called from Rust using `call_js_async`;
+// keep in sync with `system_notification.rs`.
+
+import { namedError } from "audio";
+import { CallJsCallback } from "types";
+
+export const notificationCallJsCallbacks: Record<string, CallJsCallback> = {
+  async _zaplibShowNotification(params) {
+    const [title, body] = params as string[];
+    if (typeof Notification === "undefined") {
+      throw namedError(
+        "NotSupportedError",
+        "Notifications are not supported in this browser"
+      );
+    }
+    // Browsers only show the permission prompt once; after that this resolves right away.
+    if (Notification.permission === "default") {
+      await Notification.requestPermission();
+    }
+    if (Notification.permission !== "granted") {
+      throw namedError(
+        "NotAllowedError",
+        "Permission to show notifications was denied"
+      );
+    }
+    new Notification(title, { body });
+    return [];
+  },
+};
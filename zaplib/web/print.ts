@@ -0,0 +1,114 @@
+// Printing backend for `zaplib::print`. These functions are called from Rust using `call_js_async`;
+// keep in sync with `print.rs`.
+//
+// Rust sends an SVG of the main window, which we rasterize into an offscreen canvas for every page
+// at the requested resolution. We then print those images from a hidden iframe, so that the rest of
+// the page doesn't get printed.
+
+import { namedError } from "audio";
+import { CallJsCallback } from "types";
+
+// CSS pixels per inch, which is also what Rust uses for logical pixels.
+const PIXELS_PER_INCH = 96;
+
+const loadImage = async (url: string): Promise<HTMLImageElement> => {
+  const image = new Image();
+  image.src = url;
+  await image.decode();
+  return image;
+};
+
+const canvasToUrl = (canvas: HTMLCanvasElement): Promise<string> =>
+  new Promise((resolve, reject) => {
+    canvas.toBlob((blob) => {
+      if (blob) {
+        resolve(URL.createObjectURL(blob));
+      } else {
+        reject(new Error("Could not rasterize page"));
+      }
+    }, "image/png");
+  });
+
+export const printCallJsCallbacks: Record<string, CallJsCallback> = {
+  async _zaplibPrint(params) {
+    const [svg, ...numbers] = params as string[];
+    const [sceneWidth, sceneHeight, pageWidth, pageHeight, margin, dpi] =
+      numbers.map(parseFloat);
+
+    // Size of the printable area in inches, and how much of the scene fits on a page.
+    const printableWidth = pageWidth - 2 * margin;
+    const printableHeight = pageHeight - 2 * margin;
+    const dotsPerScenePixel = (printableWidth * dpi) / sceneWidth;
+    const sceneHeightPerPage = (printableHeight * dpi) / dotsPerScenePixel;
+    // Allow for some rounding errors, so that we don't add an empty page.
+    const pageCount = Math.max(
+      1,
+      Math.ceil(sceneHeight / sceneHeightPerPage - 0.001)
+    );
+
+    const svgUrl = URL.createObjectURL(
+      new Blob([svg], { type: "image/svg+xml" })
+    );
+    const pageUrls: string[] = [];
+    const iframe = document.createElement("iframe");
+    try {
+      const image = await loadImage(svgUrl);
+      for (let page = 0; page < pageCount; page++) {
+        const canvas = document.createElement("canvas");
+        canvas.width = Math.round(printableWidth * dpi);
+        canvas.height = Math.round(printableHeight * dpi);
+        const context = canvas.getContext("2d");
+        if (!context) {
+          throw namedError("NotSupportedError", "Could not create canvas");
+        }
+        // Drawing the SVG at the final size keeps it sharp.
+        context.drawImage(
+          image,
+          0,
+          -page * sceneHeightPerPage * dotsPerScenePixel,
+          sceneWidth * dotsPerScenePixel,
+          sceneHeight * dotsPerScenePixel
+        );
+        pageUrls.push(await canvasToUrl(canvas));
+      }
+
+      iframe.style.position = "fixed";
+      iframe.style.width = "0";
+      iframe.style.height = "0";
+      iframe.style.border = "0";
+      iframe.style.visibility = "hidden";
+      document.body.appendChild(iframe);
+      const printWindow = iframe.contentWindow;
+      if (!printWindow) {
+        throw namedError("NotSupportedError", "Could not create print frame");
+      }
+      const printDocument = printWindow.document;
+      const style = printDocument.createElement("style");
+      style.textContent = `
+        @page { size: ${pageWidth}in ${pageHeight}in; margin: ${margin}in; }
+        body { margin: 0; }
+        img { display: block; width: ${printableWidth}in; height: ${printableHeight}in; break-after: page; }
+        img:last-child { break-after: auto; }
+      `;
+      printDocument.head.appendChild(style);
+      const images = await Promise.all(pageUrls.map(loadImage));
+      for (const pageImage of images) {
+        printDocument.body.appendChild(pageImage);
+      }
+
+      // `print()` blocks in most browsers, but not all, so also wait for `afterprint`.
+      await new Promise<void>((resolve) => {
+        printWindow.addEventListener("afterprint", () => resolve(), {
+          once: true,
+        });
+        printWindow.focus();
+        printWindow.print();
+      });
+    } finally {
+      iframe.remove();
+      URL.revokeObjectURL(svgUrl);
+      for (const url of pageUrls) URL.revokeObjectURL(url);
+    }
+    return [];
+  },
+};
@@ -12,6 +12,7 @@ import {
   RustZapParam,
   SizingData,
   TlsAndStackData,
+  XrFrameData,
   ZapArray,
 } from "types";
 
@@ -37,10 +38,19 @@ export type Worker<T extends RpcSpec> = {
 export type WorkerCallRustAsyncParams = {
   name: string;
   params: (string | PostMessageTypedArray | ZapArray)[];
+  // See `createCancelToken` in `common.ts`.
+  cancelTokenPtr: number;
 };
 
+// Result of polling a `callRustStream` call; see `CallRustStreamNext` below.
+export type WorkerCallRustStreamChunk =
+  | { done: false; chunk: RustZapParam[] }
+  | { done: true };
+
 export enum WorkerEvent {
   CallRustAsync = "WorkerEvent.CallRustAsync",
+  CallRustStream = "WorkerEvent.CallRustStream",
+  CallRustStreamNext = "WorkerEvent.CallRustStreamNext",
   BindMainWorkerPort = "WorkerEvent.BindMainWorkerPort",
   DecrementArc = "WorkerEvent.DecrementArc",
   DeallocVec = "WorkerEvent.DeallocVec",
@@ -60,6 +70,10 @@ export enum WorkerEvent {
   ShowIncompatibleBrowserNotification = "WorkerEvent.ShowIncompatibleBrowserNotification",
   SetDocumentTitle = "WorkerEvent.SetDocumentTitle",
   SetMouseCursor = "WorkerEvent.SetMouseCursor",
+  DefineCustomMouseCursor = "WorkerEvent.DefineCustomMouseCursor",
+  SetCustomMouseCursor = "WorkerEvent.SetCustomMouseCursor",
+  SetPointerLock = "WorkerEvent.SetPointerLock",
+  PointerLockChange = "WorkerEvent.PointerLockChange",
   Fullscreen = "WorkerEvent.Fullscreen",
   Normalscreen = "WorkerEvent.Normalscreen",
   TextCopyResponse = "WorkerEvent.TextCopyResponse",
@@ -77,6 +91,12 @@ export enum WorkerEvent {
   WindowTouchMove = "WorkerEvent.WindowTouchMove",
   WindowTouchEndCancelLeave = "WorkerEvent.WindowTouchEndCancelLeave",
   Panic = "WorkerEvent.Panic",
+  AttachWindowCanvas = "WorkerEvent.AttachWindowCanvas",
+  DetachWindowCanvas = "WorkerEvent.DetachWindowCanvas",
+  WindowCanvasResize = "WorkerEvent.WindowCanvasResize",
+  XrStartPresenting = "WorkerEvent.XrStartPresenting",
+  XrStopPresenting = "WorkerEvent.XrStopPresenting",
+  XrFrame = "WorkerEvent.XrFrame",
 }
 export type WasmWorkerRpc = {
   send: {
@@ -88,6 +108,13 @@ export type WasmWorkerRpc = {
       WorkerCallRustAsyncParams,
       Promise<RustZapParam[]>
     ];
+    // Starts a stream and returns its id, which is then passed to `CallRustStreamNext` to wait for
+    // the next chunk.
+    [WorkerEvent.CallRustStream]: [WorkerCallRustAsyncParams, number];
+    [WorkerEvent.CallRustStreamNext]: [
+      number,
+      Promise<WorkerCallRustStreamChunk>
+    ];
     [WorkerEvent.DragEnter]: [void, void];
     [WorkerEvent.DragOver]: [{ x: number; y: number }, void];
     [WorkerEvent.DragLeave]: [void, void];
@@ -105,11 +132,18 @@ export type WasmWorkerRpc = {
     [WorkerEvent.CanvasWheel]: [RpcWheelEvent, void];
     [WorkerEvent.WindowFocus]: [RpcWheelEvent, void];
     [WorkerEvent.WindowBlur]: [RpcWheelEvent, void];
+    [WorkerEvent.PointerLockChange]: [boolean, void];
     [WorkerEvent.KeyDown]: [TextareaEventKeyDown, void];
     [WorkerEvent.KeyUp]: [TextareaEventKeyUp, void];
     [WorkerEvent.TextInput]: [TextareaEventTextInput, void];
     [WorkerEvent.TextCopy]: [TextareaEvent, void];
     [WorkerEvent.ScreenResize]: [SizingData, void];
+    [WorkerEvent.WindowCanvasResize]: [
+      { windowId: number; width: number; height: number; dpiFactor: number },
+      void
+    ];
+    // Resolves when the worker has handled the frame, which includes sending `RunWebGL`.
+    [WorkerEvent.XrFrame]: [XrFrameData, void];
     [WorkerEvent.ShowIncompatibleBrowserNotification]: [void, void];
     [WorkerEvent.Init]: [
       {
@@ -130,6 +164,19 @@ export type WasmWorkerRpc = {
     [WorkerEvent.ShowIncompatibleBrowserNotification]: [void, void];
     [WorkerEvent.SetDocumentTitle]: [string, void];
     [WorkerEvent.SetMouseCursor]: [string, void];
+    [WorkerEvent.DefineCustomMouseCursor]: [
+      {
+        id: number;
+        width: number;
+        height: number;
+        hotspotX: number;
+        hotspotY: number;
+        rgba: Uint8Array;
+      },
+      void
+    ];
+    [WorkerEvent.SetCustomMouseCursor]: [number, void];
+    [WorkerEvent.SetPointerLock]: [boolean, void];
     [WorkerEvent.Fullscreen]: [void, void];
     [WorkerEvent.Normalscreen]: [void, void];
     [WorkerEvent.TextCopyResponse]: [string, void];
@@ -151,6 +198,21 @@ export type WasmWorkerRpc = {
       void
     ];
     [WorkerEvent.Panic]: [Error, void];
+    // Returns an OffscreenCanvas if the worker should do the rendering.
+    [WorkerEvent.AttachWindowCanvas]: [
+      {
+        windowId: number;
+        canvasId: string;
+        width: number;
+        height: number;
+        x: number;
+        y: number;
+      },
+      { offscreenCanvas: OffscreenCanvas } | undefined
+    ];
+    [WorkerEvent.DetachWindowCanvas]: [number, void];
+    [WorkerEvent.XrStartPresenting]: [void, void];
+    [WorkerEvent.XrStopPresenting]: [void, void];
   };
 };
 
@@ -205,6 +267,8 @@ export enum MainWorkerChannelEvent {
   Init = "MainWorkerChannelEvent.Init",
   BindMainWorkerPort = "MainWorkerChannelEvent.BindMainWorkerPort",
   CallRustAsync = "MainWorkerChannelEvent.CallRustAsync",
+  CallRustStream = "MainWorkerChannelEvent.CallRustStream",
+  CallRustStreamNext = "MainWorkerChannelEvent.CallRustStreamNext",
   SendEventFromAnyThread = "MainWorkerChannelEvent.SendEventFromAnyThread",
 }
 export type WebWorkerRpc = {
@@ -227,6 +291,11 @@ export type WebWorkerRpc = {
       WorkerCallRustAsyncParams,
       Promise<RustZapParam[]>
     ];
+    [MainWorkerChannelEvent.CallRustStream]: [WorkerCallRustAsyncParams, number];
+    [MainWorkerChannelEvent.CallRustStreamNext]: [
+      number,
+      Promise<WorkerCallRustStreamChunk>
+    ];
   };
   receive: Record<string, never>;
 };
@@ -0,0 +1,74 @@
+// IndexedDB backend for `zaplib::storage`. These functions are called from Rust using
+// `call_js_async`; keep in sync with `storage.rs`.
+
+import { CallJsCallback } from "types";
+
+const DB_NAME = "zaplib_storage";
+const STORE_NAME = "values";
+
+let dbPromise: Promise<IDBDatabase> | undefined;
+const getDb = (): Promise<IDBDatabase> => {
+  if (!dbPromise) {
+    dbPromise = new Promise((resolve, reject) => {
+      const request = indexedDB.open(DB_NAME, 1);
+      request.onupgradeneeded = () => {
+        request.result.createObjectStore(STORE_NAME);
+      };
+      request.onsuccess = () => resolve(request.result);
+      request.onerror = () => reject(request.error);
+    });
+    // Allow retrying, e.g. when IndexedDB was temporarily unavailable.
+    dbPromise.catch(() => {
+      dbPromise = undefined;
+    });
+  }
+  return dbPromise;
+};
+
+// Run `makeRequest` in a new transaction, and resolve with the result of the request
+// when the transaction completes (so that writes have actually been committed).
+const runTransaction = async <T>(
+  mode: IDBTransactionMode,
+  makeRequest: (store: IDBObjectStore) => IDBRequest<T>
+): Promise<T> => {
+  const db = await getDb();
+  return new Promise((resolve, reject) => {
+    const transaction = db.transaction(STORE_NAME, mode);
+    const request = makeRequest(transaction.objectStore(STORE_NAME));
+    transaction.oncomplete = () => resolve(request.result);
+    transaction.onerror = transaction.onabort = () => {
+      const error = transaction.error || request.error;
+      // Prefix with the error name, so that Rust can detect e.g. "QuotaExceededError".
+      reject(new Error(error ? `${error.name}: ${error.message}` : "Aborted"));
+    };
+  });
+};
+
+export const storageCallJsCallbacks: Record<string, CallJsCallback> = {
+  async _zaplibStorageGet(params) {
+    const key = params[0] as string;
+    const value = await runTransaction<ArrayBuffer | undefined>(
+      "readonly",
+      (store) => store.get(key)
+    );
+    return value === undefined ? [] : [new Uint8Array(value)];
+  },
+  async _zaplibStorageSet(params) {
+    const key = params[0] as string;
+    // Copy the data out of the Wasm memory, since IndexedDB can't store views on a SharedArrayBuffer.
+    const value = (params[1] as Uint8Array).slice().buffer;
+    await runTransaction("readwrite", (store) => store.put(value, key));
+    return [];
+  },
+  async _zaplibStorageRemove(params) {
+    const key = params[0] as string;
+    await runTransaction("readwrite", (store) => store.delete(key));
+    return [];
+  },
+  async _zaplibStorageKeys() {
+    const keys = await runTransaction("readonly", (store) =>
+      store.getAllKeys()
+    );
+    return keys.map(String);
+  },
+};
@@ -15,12 +15,15 @@ pub struct TestSuiteApp {
     buffers: Vec<Arc<Vec<u8>>>,
     signal: Signal,
     panic_draw: bool,
+    /// Tasks whose output gets sent as a single chunk to a `callRustStream` call.
+    stream_tasks: Vec<(Task<Vec<ZapParam>>, CallRustStream)>,
 }
 
 impl TestSuiteApp {
     pub fn new(cx: &mut Cx) -> Self {
         cx.on_call_rust_sync(Self::on_call_rust_sync);
         cx.on_call_rust_async(Self::on_call_rust_async);
+        cx.on_call_rust_stream(Self::on_call_rust_stream);
         let buffer = Arc::new(vec![1; 8]);
         let buffers = vec![buffer];
         Self {
@@ -38,6 +41,7 @@ impl TestSuiteApp {
             signal: cx.new_signal(),
             buffers,
             panic_draw: false,
+            stream_tasks: vec![],
         }
     }
 
@@ -71,6 +75,17 @@ impl TestSuiteApp {
             _ => {}
         }
 
+        let mut i = 0;
+        while i < self.stream_tasks.len() {
+            if let Some(chunk) = self.stream_tasks[i].0.take_output(event) {
+                let (_, stream) = self.stream_tasks.remove(i);
+                stream.send_chunk(cx, chunk);
+                stream.finish(cx);
+            } else {
+                i += 1;
+            }
+        }
+
         if let ButtonEvent::Clicked = self.send_button.handle(cx, event) {
             let mut params = vec!["hello world :-)".to_string().into_param()];
             for buffer in &self.buffers {
@@ -122,6 +137,7 @@ impl TestSuiteApp {
                 let sum: u8 = buffer.iter().sum();
                 vec![sum.to_string().into_param()]
             }
+            "read_file_to_string" => vec![universal_file::read_to_string(params[0].as_str()).unwrap().into_param()],
             "call_rust_no_return" => {
                 // Note: not returning anything to test destructor behavior
                 vec![]
@@ -133,6 +149,16 @@ impl TestSuiteApp {
                 let _ = Arc::into_raw(arc);
                 vec![vec![count as u8].into_param()]
             }
+            "wait_for_cancel" => {
+                // Blocks the main Rust thread, so this only returns early if JS can cancel while we're blocked.
+                let cancel_token = cx.call_rust_cancel_token();
+                let mut spins: u64 = 0;
+                while !cancel_token.is_cancelled() && spins < 1_000_000_000 {
+                    std::hint::spin_loop();
+                    spins += 1;
+                }
+                vec![cancel_token.is_cancelled().to_string().into_param()]
+            }
             "panic" => {
                 panic!("I am panicking!");
             }
@@ -151,6 +177,65 @@ impl TestSuiteApp {
         }
     }
 
+    fn on_call_rust_stream(&mut self, cx: &mut Cx, name: String, params: Vec<ZapParam>, stream: CallRustStream) {
+        match name.as_str() {
+            "count_chunks" => {
+                let count: u8 = serde_json::from_str(params[0].as_str()).unwrap();
+                for i in 0..count {
+                    stream.send_chunk(cx, vec![i.to_string().into_param(), Arc::new(vec![i; 4]).into_param()]);
+                }
+                stream.finish(cx);
+            }
+            "call_js_async" => {
+                let timeout: f64 = serde_json::from_str(params[1].as_str()).unwrap();
+                let future = cx.call_js_async(params[0].as_str(), params[2..].to_vec(), Some(timeout));
+                let task = cx.spawn(async move {
+                    match future.await {
+                        Ok(params) => [vec!["ok".to_string().into_param()], params].concat(),
+                        Err(err) => vec![err.to_string().into_param()],
+                    }
+                });
+                self.stream_tasks.push((task, stream));
+            }
+            "storage" => {
+                // All operations are applied in order, so we can start them all before awaiting any.
+                let key = params[0].as_str();
+                let set = storage::set(cx, key, params[1].as_u8_slice().to_vec());
+                let get = storage::get(cx, key);
+                let keys = storage::keys(cx);
+                let remove = storage::remove(cx, key);
+                let get_after_remove = storage::get(cx, key);
+                let task = cx.spawn(async move {
+                    set.await.unwrap();
+                    let value = get.await.unwrap().unwrap();
+                    let keys = keys.await.unwrap();
+                    remove.await.unwrap();
+                    let value_after_remove = get_after_remove.await.unwrap();
+                    vec![value.into_param(), keys.join(",").into_param(), value_after_remove.is_none().to_string().into_param()]
+                });
+                self.stream_tasks.push((task, stream));
+            }
+            "save_file_async" => {
+                let future = save_file_async(cx, params[0].as_str(), params[1].as_u8_slice().to_vec());
+                let task = cx.spawn(async move { vec![future.await.is_ok().to_string().into_param()] });
+                self.stream_tasks.push((task, stream));
+            }
+            "load_file_async" => {
+                let future = load_file_async(cx, params[0].as_str());
+                let task = cx.spawn(async move {
+                    match future.await {
+                        Ok(data) => vec![data.into_param()],
+                        Err(err) => vec![format!("{:?}", err.kind()).into_param()],
+                    }
+                });
+                self.stream_tasks.push((task, stream));
+            }
+            unknown_name => {
+                panic!("Unknown function name: {}", unknown_name)
+            }
+        }
+    }
+
     pub fn draw(&mut self, cx: &mut Cx) {
         if self.panic_draw {
             panic!("Panic draw");
@@ -190,6 +275,11 @@ impl TestSuiteApp {
                 let b = params[1].as_f32_slice();
                 vec![Arc::<Vec<f32>>::new(b.iter().map(|x| *x * value).collect()).into_param()]
             }
+            "total_sum" => {
+                let buffer = params[0].as_u8_slice();
+                let sum: u8 = buffer.iter().sum();
+                vec![sum.to_string().into_param()]
+            }
             "send_signal" => {
                 // This is a fake signal ID
                 Cx::post_signal(Signal { signal_id: 123 }, location_hash!());
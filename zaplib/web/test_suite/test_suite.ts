@@ -3,8 +3,9 @@ import TestSuiteWorker from "worker-loader?inline=no-fallback!test_suite/test_su
 
 import { assertNotNull, Rpc } from "common";
 import { TestSuiteTests } from "test_suite/test_suite_worker";
-import { PostMessageTypedArray, ZapArray } from "types";
+import { PostMessageTypedArray, ZapArray, ZapParam } from "types";
 import { zapBufferTests } from "test_suite/zap_buffer_test";
+import { allocatedArcs, ZapBuffer } from "zap_buffer";
 import * as zaplib from "zaplib_runtime";
 import {
   expect,
@@ -86,8 +87,34 @@ zaplib
         // worker.postMessage(buffers[0]);
         rpc.send("sendWorker", zaplib.serializeZapArrayForPostMessage(toSend));
       },
+      async addAsync(params) {
+        await new Promise((resolve) => setTimeout(resolve, 10));
+        return [String(Number(params[0]) + Number(params[1]))];
+      },
+      throwError() {
+        throw new Error("Oops");
+      },
+      neverReturn() {
+        return new Promise(() => undefined);
+      },
     });
 
+    // For `callRustStream` handlers in Rust that send a single chunk with the result of some async work.
+    const callRustStreamFirstChunk = async <T extends ZapParam[]>(
+      name: string,
+      params: ZapParam[]
+    ) => {
+      const chunks = [];
+      for await (const chunk of zaplib.callRustStream<T>(name, params)) {
+        chunks.push(chunk);
+      }
+      return chunks[0];
+    };
+
+    // Calls `cx.call_js_async` in Rust, which returns the result of the JS function in a stream chunk.
+    const callJsAsyncFromRust = (params: string[]) =>
+      callRustStreamFirstChunk<string[]>("call_js_async", params);
+
     const runtimeSpecificTests =
       zaplib.jsRuntime === "wasm"
         ? {
@@ -142,12 +169,48 @@ zaplib
             "Send signal from worker": runWorkerTest(
               "testCallRustAsyncSyncWithSignal"
             ),
+            "Multiple instances": async () => {
+              const instance = zaplib.createInstance();
+              expect(instance.isInitialized(), false);
+              await instance.initialize({
+                wasmModule: `target/wasm32-unknown-unknown/${env}/test_suite.wasm`,
+              });
+              expect(instance.isInitialized(), true);
+
+              // Buffers of the default instance get copied into the other one.
+              const [buffer] = await zaplib.callRustAsync<[Uint8Array]>(
+                "array_multiply_u8",
+                [JSON.stringify(2), new Uint8Array([1, 2, 3, 4])]
+              );
+              expect(
+                (await instance.callRustAsync("total_sum", [buffer]))[0],
+                "20"
+              );
+              expect(instance.callRustSync("total_sum", [buffer])[0], "20");
+              expectThrow(
+                () => instance.releaseBuffer(buffer),
+                "This Zap array belongs to a different Zaplib instance"
+              );
+              expect(buffer[3], 8);
+
+              instance.close();
+            },
           }
         : {
             // CEF
           };
 
     const tests = {
+      "Initialize again": async () => {
+        await zaplib.initialize({
+          wasmModule: `target/wasm32-unknown-unknown/${env}/test_suite.wasm`,
+        });
+        expect(zaplib.isInitialized(), true);
+        expectThrow(
+          () => zaplib.initialize({ wasmModule: "other.wasm" }),
+          "Only call zaplib.initialize() once (calling it again is only allowed with the same `wasmModule` and `canvas`)"
+        );
+      },
       "Call Rust": async () => {
         const buffer = new SharedArrayBuffer(8);
         new Uint8Array(buffer).set([1, 2, 3, 4, 5, 6, 7, 8]);
@@ -162,6 +225,85 @@ zaplib
         expect(result[2], 50);
         expect(result[3], 60);
       },
+      "Call Rust (streaming)": async () => {
+        const chunks: [string, Uint8Array][] = [];
+        for await (const chunk of zaplib.callRustStream<[string, Uint8Array]>(
+          "count_chunks",
+          [JSON.stringify(3)]
+        )) {
+          chunks.push(chunk);
+        }
+        expect(chunks.length, 3);
+        expect(chunks[0][0], "0");
+        expect(chunks[2][0], "2");
+        expect(chunks[2][1].length, 4);
+        expect(chunks[2][1][0], 2);
+      },
+      "Call Rust (cancelled)": async () => {
+        const promise = zaplib.callRustAsync("wait_for_cancel");
+        promise.cancel();
+        const [cancelled] = await promise;
+        expect(cancelled, "true");
+      },
+      "Call Rust (streaming, stopped early)": async () => {
+        for await (const [value] of zaplib.callRustStream<[string]>(
+          "count_chunks",
+          [JSON.stringify(3)]
+        )) {
+          expect(value, "0");
+          break;
+        }
+        // The remaining chunks are discarded, and later calls still work.
+        const [result] = await zaplib.callRustAsync("total_sum", [
+          new Uint8Array([1, 2, 3]),
+        ]);
+        expect(result, "6");
+      },
+      "Call JS from Rust (async)": async () => {
+        const [status, result] = await callJsAsyncFromRust([
+          "addAsync",
+          "10",
+          "2",
+          "3",
+        ]);
+        expect(status, "ok");
+        expect(result, "5");
+      },
+      "Call JS from Rust (error)": async () => {
+        const [error] = await callJsAsyncFromRust(["throwError", "10"]);
+        expect(error, "JS function threw an error: Oops");
+      },
+      "Call JS from Rust (timeout)": async () => {
+        const [error] = await callJsAsyncFromRust(["neverReturn", "0.05"]);
+        expect(error, "JS function timed out");
+      },
+      "Persistent storage": async () => {
+        const [value, keys, removed] = await callRustStreamFirstChunk<
+          [Uint8Array, string, string]
+        >("storage", ["test_suite_key", new Uint8Array([1, 2, 3])]);
+        expect(value.length, 3);
+        expect(value[2], 3);
+        expect(keys.split(",").includes("test_suite_key"), true);
+        expect(removed, "true");
+      },
+      "Save and load file": async () => {
+        const [saved] = await callRustStreamFirstChunk<[string]>(
+          "save_file_async",
+          ["test_suite/file.txt", new Uint8Array([1, 2, 3])]
+        );
+        expect(saved, "true");
+        const [loaded] = await callRustStreamFirstChunk<[Uint8Array]>(
+          "load_file_async",
+          ["test_suite/file.txt"]
+        );
+        expect(loaded.length, 3);
+        expect(loaded[2], 3);
+        const [notFound] = await callRustStreamFirstChunk<[string]>(
+          "load_file_async",
+          ["test_suite/does_not_exist.txt"]
+        );
+        expect(notFound, "NotFound");
+      },
       "Call Rust (no return)": async () => {
         const result = await zaplib.callRustAsync("call_rust_no_return");
         expect(result.length, 0);
@@ -372,6 +514,44 @@ zaplib
           "Cannot call Rust with a buffer which has been cast to a different type. Expected ReadOnlyF32Buffer but got ReadOnlyU8Buffer"
         );
       },
+      "Release buffers explicitly": async () => {
+        const [result] = await zaplib.callRustAsync<[Uint8Array]>(
+          "array_multiply_u8_readonly",
+          [JSON.stringify(10), new Uint8Array([1, 2, 3])]
+        );
+        expect(result[2], 30);
+        const bufferData = (result.buffer as ZapBuffer).__zaplibBufferData;
+
+        zaplib.releaseBuffer(result);
+        if (zaplib.jsRuntime === "wasm" && bufferData.readonly) {
+          expect(allocatedArcs[bufferData.arcPtr], false);
+        }
+
+        const releasedError =
+          "Cannot use a buffer after calling `releaseBuffer` on it (or on another array over the same buffer)";
+        expectThrow(
+          () =>
+            zaplib.callRustSync("array_multiply_u8_readonly", [
+              JSON.stringify(10),
+              result,
+            ]),
+          releasedError
+        );
+        expectThrow(() => zaplib.releaseBuffer(result), releasedError);
+      },
+      "Detect modified read-only buffers": () => {
+        zaplib.enableBufferChecks();
+        const buffer = zaplib.createReadOnlyBuffer(new Uint8Array([1, 2, 3]));
+        buffer[0] = 5;
+        expectThrow(
+          () =>
+            zaplib.callRustSync("array_multiply_u8_readonly", [
+              JSON.stringify(10),
+              buffer,
+            ]),
+          "A read-only buffer was modified from JS; use `.slice()` to make a copy that you can modify"
+        );
+      },
       "On the main thread inWorker returns false": () => {
         expect(inWorker, false);
       },
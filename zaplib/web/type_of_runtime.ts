@@ -3,10 +3,6 @@
 // CEF and WASM code separate for bundle size.
 export const jsRuntime = "cefCallRustAsync" in globalThis ? "cef" : "wasm";
 
-// Whether or not we're in a WebWorker.
-// From https://stackoverflow.com/a/23619712
-export const inWorker = typeof importScripts === "function";
-
 // Only Node.JS has a process variable that is of `Class` `process`
 // From https://github.com/iliakan/detect-node/blob/00381fd0fdbdefa625ac7b8230adfc1df11d49ad/index.js
 export const inNodeJs =
@@ -14,6 +10,16 @@ export const inNodeJs =
     typeof process !== "undefined" ? process : 0
   ) === "[object process]";
 
+// Whether or not we're in a WebWorker. In Node.js, the Worker polyfill in `zaplib_nodejs_polyfill` runs
+// workers in `worker_threads` with a global `postMessage` (which the main thread doesn't have, unless
+// it's emulating a browser with jsdom).
+// From https://stackoverflow.com/a/23619712
+export const inWorker =
+  typeof importScripts === "function" ||
+  (inNodeJs &&
+    typeof window === "undefined" &&
+    typeof globalThis.postMessage === "function");
+
 // Injected using webpack.DefinePlugin.
 declare const __GIT_SHA__: string;
 export const gitSha = __GIT_SHA__;
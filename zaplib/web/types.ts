@@ -5,7 +5,14 @@ export type InitParams = {
   baseUri?: string;
   defaultStyles?: boolean;
   onPanic?: (error: Error) => void;
+  onWorkerPanic?: (error: Error) => void;
+  onLoadingProgress?: (progress: LoadingProgress) => void;
+  renderPlaceholder?: (progress: LoadingProgress, done: boolean) => void;
+  xr?: boolean;
 };
+// `total` is undefined if the server doesn't tell us the size of the download
+// (e.g. when it's compressed).
+export type LoadingProgress = { loaded: number; total: number | undefined };
 export type Initialize = (initParams: InitParams) => Promise<void>;
 
 export type IsInitialized = () => boolean;
@@ -17,7 +24,8 @@ export type UniformType =
   | "vec4"
   | "mat2"
   | "mat3"
-  | "mat4";
+  | "mat4"
+  | `mat4[${number}]`;
 export type Uniform = { ty: UniformType; name: string };
 
 export type ShaderAttributes = {
@@ -49,6 +57,7 @@ export type FileHandle = {
 export type WasmEnv = {
   memory: WebAssembly.Memory;
   _consoleLog: (charsPtr: string, len: string) => void;
+  _consoleLogWithLevel: (level: number, charsPtr: string, len: string) => void;
   _throwError: (charsPtr: string, len: string) => void;
   readUserFileRange: (
     userFileId: number,
@@ -82,6 +91,9 @@ export type WasmExports = {
   createArcVec: (vecPtr: BigInt, vecLen: BigInt, paramType: BigInt) => BigInt;
   deallocVec: (vecPtr: BigInt, vecLen: BigInt, vecCap: BigInt) => BigInt;
   runFunctionPointer: (ctxPtr: BigInt) => void;
+  setLogFilter: (vecPtr: BigInt, vecLen: BigInt) => void;
+  createCancelToken: () => BigInt;
+  dropCancelToken: (ptr: BigInt) => void;
   // __tls_size and __wasm_init_tls are automatically generated; see e.g.
   // https://github.com/WebAssembly/tool-conventions/blob/main/Linking.md#thread-local-storage
   // eslint-disable-next-line camelcase
@@ -123,17 +135,80 @@ export type RustZapParam = BufferData | string;
 
 export type CreateBuffer = <T extends ZapArray>(data: T) => T;
 
-export type CallJsCallback = (params: ZapParam[]) => void;
+// Functions called using `Cx::call_js_async` can return values to Rust, optionally using a `Promise`.
+export type CallJsCallback = (
+  params: ZapParam[]
+) => void | ZapParam[] | Promise<void | ZapParam[]>;
+
+// `cancel()` sets the `CallRustCancelToken` of the call in Rust. The promise still resolves with
+// whatever the Rust function returns.
+export type CallRustAsyncPromise<T> = Promise<T> & { cancel: () => void };
 
 export type CallRustAsync = <T extends ZapParam[] = ZapParam[]>(
   name: string,
   params?: ZapParam[]
-) => Promise<T>;
+) => CallRustAsyncPromise<T>;
 
 export type CallRustSync = <T extends ZapParam[] = ZapParam[]>(
   ...args: Parameters<CallRustAsync>
 ) => T;
 
+// Yields every chunk sent using `CallRustStream::send_chunk` in Rust, and ends when Rust calls
+// `CallRustStream::finish`. Stopping to iterate early cancels the `CallRustCancelToken` of the stream.
+export type CallRustStream = <T extends ZapParam[] = ZapParam[]>(
+  ...args: Parameters<CallRustAsync>
+) => AsyncGenerator<T, void, undefined>;
+
+// The functions of a single Zaplib instance, as returned by `zaplib.createInstance()`. The module
+// itself exports the same functions for the default instance.
+export type ZaplibInstance = {
+  initialize: Initialize;
+  isInitialized: IsInitialized;
+  close: () => void;
+  newWorkerPort: () => MessagePort;
+  registerCallJsCallbacks: (fns: Record<string, CallJsCallback>) => void;
+  unregisterCallJsCallbacks: (fnNames: string[]) => void;
+  callRustAsync: CallRustAsync;
+  callRustStream: CallRustStream;
+  callRustSync: CallRustSync;
+  serializeZapArrayForPostMessage: (
+    zapArray: ZapArray
+  ) => PostMessageTypedArray;
+  deserializeZapArrayFromPostMessage: (
+    postMessageData: PostMessageTypedArray
+  ) => Uint8Array;
+  releaseBuffer: (zapArray: ZapArray) => void;
+  setLogFilter: (filter: string) => void;
+  createMutableBuffer: CreateBuffer;
+  createReadOnlyBuffer: CreateBuffer;
+};
+
+// A `ZaplibInstance` as created by a runtime, before adding the buffer helpers that only depend on
+// `callRustSync`.
+export type RuntimeInstance = Omit<
+  ZaplibInstance,
+  "createMutableBuffer" | "createReadOnlyBuffer"
+>;
+
+// Parameter and return types of a function registered in Rust, as generated by
+// `cargo zaplib generate-types`.
+export type RustFunctionSpec = { params: ZapParam[]; returns: ZapParam[] };
+
+// Type-checked versions of `CallRustAsync` and `CallRustSync`, for the `RustFunctions` interface
+// generated by `cargo zaplib generate-types`. Use e.g.:
+// `const callRustAsync = zaplib.callRustAsync as TypedCallRustAsync<RustFunctions>;`
+export type TypedCallRustAsync<F extends { [N in keyof F]: RustFunctionSpec }> =
+  <N extends keyof F & string>(
+    name: N,
+    params: F[N]["params"]
+  ) => CallRustAsyncPromise<F[N]["returns"]>;
+
+export type TypedCallRustSync<F extends { [N in keyof F]: RustFunctionSpec }> =
+  <N extends keyof F & string>(
+    name: N,
+    params: F[N]["params"]
+  ) => F[N]["returns"];
+
 // Keep in sync with `param.rs`
 export enum ZapParamType {
   String = 0,
@@ -151,6 +226,30 @@ export type SizingData = {
   dpiFactor: number;
   canFullscreen: boolean;
   isFullscreen: boolean;
+  xrCanPresent: boolean;
+  xrIsPresenting: boolean;
+};
+
+// A pose from WebXR, with the orientation as a quaternion (x, y, z, w).
+export type XrPoseData = { orientation: number[]; position: number[] };
+
+// A frame of a WebXR session, which the main thread sends to the worker. See `xr.ts`.
+export type XrFrameData = {
+  time: number;
+  head: XrPoseData;
+  views: {
+    projection: number[];
+    view: number[];
+    // x, y, width, height; in pixels from the bottom left of the framebuffer.
+    viewport: number[];
+  }[];
+  inputs: {
+    handedness: "none" | "left" | "right";
+    grip: XrPoseData | undefined;
+    ray: XrPoseData;
+    buttons: { pressed: boolean; value: number }[];
+    axes: number[];
+  }[];
 };
 
 export type TlsAndStackData = {
@@ -0,0 +1,195 @@
+// HTMLVideoElement backend for `zaplib::video` and `zaplib::camera`. These functions are called
+// from Rust using `call_js` and `call_js_async`; keep in sync with `video.rs` and `camera.rs`.
+//
+// Frames are copied by drawing the video into a canvas and reading back its pixels into Wasm
+// memory, which Rust then uploads to a texture. Cameras play a `getUserMedia` stream in a video
+// element, so that we can copy frames in the same way.
+
+import { listDevices, namedError, toNamedError } from "audio";
+import { CallJsCallback } from "types";
+
+// Values of the status that is shared with Rust; see `video.rs`.
+const STATUS_DROPPED = 1;
+const STATUS_REMOVED = 2;
+
+// How often to check whether Rust dropped a video.
+const DROPPED_CHECK_INTERVAL_MS = 1000;
+
+type ZaplibVideo = {
+  video: HTMLVideoElement;
+  canvas: HTMLCanvasElement;
+  context: CanvasRenderingContext2D;
+};
+
+const videos: Record<number, ZaplibVideo> = {};
+let nextVideoId = 1;
+
+const getVideo = (id: string): ZaplibVideo => {
+  const zaplibVideo = videos[parseInt(id)];
+  if (!zaplibVideo) {
+    throw new Error(`Video ${id} does not exist`);
+  }
+  return zaplibVideo;
+};
+
+const removeVideo = (id: number) => {
+  const { video } = videos[id];
+  delete videos[id];
+  video.pause();
+  if (video.srcObject instanceof MediaStream) {
+    // Turns off the camera.
+    for (const track of video.srcObject.getTracks()) track.stop();
+    video.srcObject = null;
+  }
+  // Stops downloading the video.
+  video.removeAttribute("src");
+  video.load();
+};
+
+// Register a video element, and remove it once Rust has dropped it, as signalled by the status
+// at `statusPtr`. Returns the id to pass back to Rust.
+const addVideo = (
+  video: HTMLVideoElement,
+  statusPtr: string,
+  memory: WebAssembly.Memory
+): number => {
+  const canvas = document.createElement("canvas");
+  canvas.width = video.videoWidth;
+  canvas.height = video.videoHeight;
+  const context = canvas.getContext("2d", { willReadFrequently: true });
+  if (!context) {
+    throw namedError("NotSupportedError", "Could not create canvas");
+  }
+  const id = nextVideoId++;
+  videos[id] = { video, canvas, context };
+
+  const status = new Int32Array(memory.buffer, parseInt(statusPtr), 1);
+  const interval = setInterval(() => {
+    if (Atomics.load(status, 0) === STATUS_DROPPED) {
+      clearInterval(interval);
+      removeVideo(id);
+      Atomics.store(status, 0, STATUS_REMOVED);
+    }
+  }, DROPPED_CHECK_INTERVAL_MS);
+  return id;
+};
+
+export const makeVideoCallJsCallbacks = (
+  getMemory: () => WebAssembly.Memory
+): Record<string, CallJsCallback> => ({
+  async _zaplibVideoLoad(params) {
+    const [url, muted, statusPtr] = params as string[];
+    const video = document.createElement("video");
+    // Without this we can't read back pixels of videos from other origins.
+    video.crossOrigin = "anonymous";
+    video.playsInline = true;
+    video.preload = "auto";
+    video.muted = muted === "1";
+    await new Promise<void>((resolve, reject) => {
+      video.onloadeddata = () => resolve();
+      video.onerror = () => {
+        const error = video.error;
+        if (error?.code === MediaError.MEDIA_ERR_SRC_NOT_SUPPORTED) {
+          reject(namedError("NotSupportedError", error.message || url));
+        } else {
+          reject(new Error(error?.message || `Failed to load ${url}`));
+        }
+      };
+      video.src = url;
+    });
+    video.onloadeddata = null;
+    video.onerror = null;
+    const id = addVideo(video, statusPtr, getMemory());
+    return [
+      id.toString(),
+      video.videoWidth.toString(),
+      video.videoHeight.toString(),
+      video.duration.toString(),
+    ];
+  },
+  async _zaplibVideoCopyFrame(params) {
+    const [id, ptr, width, height] = params as string[];
+    const { video, canvas, context } = getVideo(id);
+    if (
+      canvas.width !== parseInt(width) ||
+      canvas.height !== parseInt(height)
+    ) {
+      throw new Error("Video dimensions changed");
+    }
+    context.drawImage(video, 0, 0);
+    const imageData = context.getImageData(0, 0, canvas.width, canvas.height);
+    new Uint8Array(
+      getMemory().buffer,
+      parseInt(ptr),
+      imageData.data.length
+    ).set(imageData.data);
+    return [video.currentTime.toString(), video.ended ? "1" : "0"];
+  },
+  async _zaplibVideoPlay(params) {
+    const { video } = getVideo(params[0] as string);
+    try {
+      // Throws a NotAllowedError when playing with sound without user input.
+      await video.play();
+    } catch (e) {
+      throw toNamedError(e);
+    }
+    return [];
+  },
+  _zaplibVideoPause(params) {
+    getVideo(params[0] as string).video.pause();
+  },
+  async _zaplibVideoSeek(params) {
+    const { video } = getVideo(params[0] as string);
+    await new Promise<void>((resolve) => {
+      video.addEventListener("seeked", () => resolve(), { once: true });
+      video.currentTime = parseFloat(params[1] as string);
+    });
+    return [];
+  },
+  _zaplibVideoSetMuted(params) {
+    getVideo(params[0] as string).video.muted = params[1] === "1";
+  },
+  async _zaplibCameraDevices() {
+    return listDevices("videoinput");
+  },
+  async _zaplibCameraStart(params) {
+    const [deviceId, width, height, frameRate, statusPtr] = params as string[];
+    if (!navigator.mediaDevices?.getUserMedia) {
+      throw namedError("NotSupportedError", "Camera capture is not available");
+    }
+    let stream: MediaStream;
+    try {
+      // Asks the user for permission, and throws a NotAllowedError if they deny it.
+      stream = await navigator.mediaDevices.getUserMedia({
+        video: {
+          ...(deviceId ? { deviceId: { exact: deviceId } } : {}),
+          width: { ideal: parseInt(width) },
+          height: { ideal: parseInt(height) },
+          frameRate: { ideal: parseFloat(frameRate) },
+        },
+      });
+    } catch (e) {
+      throw toNamedError(e);
+    }
+    const video = document.createElement("video");
+    video.playsInline = true;
+    video.muted = true;
+    video.srcObject = stream;
+    try {
+      await new Promise<void>((resolve) => {
+        video.onloadeddata = () => resolve();
+      });
+      video.onloadeddata = null;
+      await video.play();
+    } catch (e) {
+      for (const track of stream.getTracks()) track.stop();
+      throw toNamedError(e);
+    }
+    const id = addVideo(video, statusPtr, getMemory());
+    return [
+      id.toString(),
+      video.videoWidth.toString(),
+      video.videoHeight.toString(),
+    ];
+  },
+});
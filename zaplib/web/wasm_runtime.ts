@@ -10,25 +10,38 @@ import TaskWorker from "worker-loader?inline=no-fallback!task_worker";
 import {
   getZapBufferWasm,
   isZapBuffer,
+  isZapBufferOf,
   overwriteTypedArraysWithZapArrays,
   unregisterMutableBuffer,
   ZapBuffer,
   checkValidZapArray,
+  checkZapBufferUsable,
+  releaseZapBuffer,
 } from "zap_buffer";
 import {
+  callJsAsyncImpl,
+  callRustStreamImpl,
   callRustSyncImpl,
+  CancelToken,
+  createCancelToken,
   createErrorCheckers,
+  createWasmBuffer,
+  fetchWithProgress,
+  getDefaultBaseUri,
   getWasmEnv,
   initTaskWorkerSab,
   initThreadLocalStorageMainWorker,
+  makeInitializeIdempotent,
   makeThreadLocalStorageAndStackDataOnExistingThread,
   normalizeInitParams,
+  readFileSyncNodeJs,
   Rpc,
   transformParamsFromRustImpl,
 } from "common";
 import { makeTextarea, TextareaEvent } from "make_textarea";
 import {
   CallRustAsync,
+  CallRustStream,
   CallJsCallback,
   PostMessageTypedArray,
   CallRustSync,
@@ -43,12 +56,17 @@ import {
   IsInitialized,
   ZapParam,
   InitParams,
+  LoadingProgress,
+  RuntimeInstance,
 } from "types";
 import { WebGLRenderer } from "webgl_renderer";
+import { XrPresenter } from "xr";
 import {
+  CanvasOffset,
   makeRpcMouseEvent,
   makeRpcTouchEvent,
   makeRpcWheelEvent,
+  WindowPosition,
 } from "make_rpc_event";
 import {
   AsyncWorkerRpc,
@@ -58,7 +76,14 @@ import {
   AsyncWorkerEvent,
 } from "rpc_types";
 import { addLoadingIndicator, removeLoadingIndicator } from "loading_indicator";
+import { makeAudioCallJsCallbacks } from "audio";
+import { makeCaptureCallJsCallbacks } from "capture";
+import { notificationCallJsCallbacks } from "notification";
+import { printCallJsCallbacks } from "print";
+import { storageCallJsCallbacks } from "storage";
+import { makeVideoCallJsCallbacks } from "video";
 import { addDefaultStyles } from "default_styles";
+import { fileSystemCallJsCallbacks } from "file_system";
 import { inNodeJs, inWorker } from "type_of_runtime";
 
 declare global {
@@ -79,6 +104,10 @@ declare global {
 
 overwriteTypedArraysWithZapArrays();
 
+// The canvas that keyboard input goes to, when there are multiple instances with a canvas. This is
+// the one that was clicked last.
+let focusedCanvas: HTMLCanvasElement | undefined;
+
 type CanvasData = {
   // Set to undefined if there's no canvas to render to. Set to OffscreenCanvas
   // if the browser supports that. Otherwise we use the WebGLRenderer on this thread.
@@ -87,804 +116,1225 @@ type CanvasData = {
   onScreenResize: () => void;
 };
 
-const jsFunctions: Record<string, CallJsCallback> = {};
-
-/// Users must call this function to register functions as runnable from
-/// Rust via `[Cx::call_js]`.
-export const registerCallJsCallbacks = (
-  fns: Record<string, CallJsCallback>
-): void => {
-  // Check that all new functions are unique
-  for (const key of Object.keys(fns)) {
-    if (key in jsFunctions) {
-      throw new Error(
-        `Error: overwriting existing function "${key}" in window.jsFunctions`
-      );
-    }
-  }
+// Everything that belongs to a single instance of the WebAssembly module: its memory, workers,
+// canvas, and so on. See `createInstance`.
+const createWasmInstance = (): RuntimeInstance => {
+  // The canvas that we render to, if any; used for capturing frames and recordings.
+  let zaplibCanvas: HTMLCanvasElement | undefined;
+
+  const jsFunctions: Record<string, CallJsCallback> = {
+    ...makeAudioCallJsCallbacks(() => wasmMemory),
+    ...makeCaptureCallJsCallbacks(() => zaplibCanvas),
+    ...fileSystemCallJsCallbacks,
+    ...notificationCallJsCallbacks,
+    ...printCallJsCallbacks,
+    ...storageCallJsCallbacks,
+    ...makeVideoCallJsCallbacks(() => wasmMemory),
+  };
 
-  Object.assign(jsFunctions, fns);
-};
-/// Users must call this function to unregister functions as runnable from
-/// Rust via `[Cx::call_js]`.
-export const unregisterCallJsCallbacks = (fnNames: string[]): void => {
-  for (const name of fnNames) {
-    // Check that functions are registered
-    if (!(name in jsFunctions)) {
-      throw new Error(`Error: unregistering non-existent function "${name}".`);
+  /// Users must call this function to register functions as runnable from
+  /// Rust via `[Cx::call_js]`.
+  const registerCallJsCallbacks = (
+    fns: Record<string, CallJsCallback>
+  ): void => {
+    // Check that all new functions are unique
+    for (const key of Object.keys(fns)) {
+      if (key in jsFunctions) {
+        throw new Error(
+          `Error: overwriting existing function "${key}" in window.jsFunctions`
+        );
+      }
     }
 
-    delete jsFunctions[name];
-  }
-};
+    Object.assign(jsFunctions, fns);
+  };
+  /// Users must call this function to unregister functions as runnable from
+  /// Rust via `[Cx::call_js]`.
+  const unregisterCallJsCallbacks = (fnNames: string[]): void => {
+    for (const name of fnNames) {
+      // Check that functions are registered
+      if (!(name in jsFunctions)) {
+        throw new Error(
+          `Error: unregistering non-existent function "${name}".`
+        );
+      }
 
-const wasmOnline = new Uint8Array(new SharedArrayBuffer(1));
-Atomics.store(wasmOnline, 0, 0);
-const wasmInitialized = () => Atomics.load(wasmOnline, 0) === 1;
-const { checkWasm, wrapWasmExports } = createErrorCheckers(wasmInitialized);
+      delete jsFunctions[name];
+    }
+  };
 
-// Gets overridden when `initParams.onPanic` is set.
-let onPanic: (e: unknown) => void = (e: unknown) => {
+  const wasmOnline = new Uint8Array(new SharedArrayBuffer(1));
   Atomics.store(wasmOnline, 0, 0);
-  console.warn(
-    "Specify `onPanic` to catch errors from rendering. See https://zaplib.com/docs/bridge_api_basics.html#zaplibinitialize."
-  );
-  // We are likely in a Promse.catch handler, so also make sure an error is thrown
-  // globally, since not everyone might catch unresolved promise errors globally.
-  setTimeout(() => {
+  const wasmInitialized = () => Atomics.load(wasmOnline, 0) === 1;
+  const { checkWasm, wrapWasmExports } = createErrorCheckers(wasmInitialized);
+
+  // Gets overridden when `initParams.onPanic` is set.
+  let onPanic: (e: unknown) => void = (e: unknown) => {
+    Atomics.store(wasmOnline, 0, 0);
+    console.warn(
+      "Specify `onPanic` to catch errors from rendering. See https://zaplib.com/docs/bridge_api_basics.html#zaplibinitialize."
+    );
+    // We are likely in a Promse.catch handler, so also make sure an error is thrown
+    // globally, since not everyone might catch unresolved promise errors globally.
+    setTimeout(() => {
+      throw e;
+    });
     throw e;
-  });
-  throw e;
-};
+  };
 
-const _workers = new Set<Worker>();
-const newWorker = (
-  workerType: MainWorker | TaskWorker | AsyncWorker
-): Worker => {
-  const worker = new workerType();
-  _workers.add(worker);
-  return worker;
-};
+  // Gets overridden when `initParams.onWorkerPanic` is set.
+  let onWorkerPanic: (e: Error) => void = (e: Error) => {
+    console.error("Panic in worker thread", e);
+  };
 
-// Wrap RPC so we can globally catch Rust panics
-let _rpc: Rpc<WasmWorkerRpc>;
-const rpc: Pick<typeof _rpc, "send" | "receive"> = {
-  send: async (...args) => {
-    try {
-      return await _rpc.send(...args);
-    } catch (ev) {
-      if (ev instanceof Error && ev.name === "RustPanic") {
-        Atomics.store(wasmOnline, 0, 0);
-      }
-      throw ev;
-    }
-  },
-  receive: (topic, handler) => {
-    _rpc.receive(topic, (...args) => {
+  const _workers = new Set<Worker>();
+  const newWorker = (
+    workerType: MainWorker | TaskWorker | AsyncWorker
+  ): Worker => {
+    const worker = new workerType();
+    _workers.add(worker);
+    return worker;
+  };
+
+  // Wrap RPC so we can globally catch Rust panics
+  let _rpc: Rpc<WasmWorkerRpc>;
+  const rpc: Pick<typeof _rpc, "send" | "receive"> = {
+    send: async (...args) => {
       try {
-        return handler(...args);
-      } catch (e) {
-        onPanic(e);
-        throw e;
+        return await _rpc.send(...args);
+      } catch (ev) {
+        if (ev instanceof Error && ev.name === "RustPanic") {
+          Atomics.store(wasmOnline, 0, 0);
+        }
+        throw ev;
       }
-    });
-  },
-};
-
-export const newWorkerPort = (): MessagePort => {
-  const channel = new MessageChannel();
-  rpc
-    .send(WorkerEvent.BindMainWorkerPort, channel.port1, [channel.port1])
-    .catch(onPanic);
-  return channel.port2;
-};
+    },
+    receive: (topic, handler) => {
+      _rpc.receive(topic, (...args) => {
+        try {
+          return handler(...args);
+        } catch (e) {
+          onPanic(e);
+          throw e;
+        }
+      });
+    },
+  };
 
-let wasmMemory: WebAssembly.Memory;
-let wasmExports: WasmExports;
-let wasmAppPtr: BigInt;
+  const newWorkerPort = (): MessagePort => {
+    const channel = new MessageChannel();
+    rpc
+      .send(WorkerEvent.BindMainWorkerPort, channel.port1, [channel.port1])
+      .catch(onPanic);
+    return channel.port2;
+  };
 
-const destructor = (arcPtr: number) => {
-  rpc.send(WorkerEvent.DecrementArc, arcPtr).catch(onPanic);
-};
+  let wasmMemory: WebAssembly.Memory;
+  let wasmExports: WasmExports;
+  let wasmAppPtr: BigInt;
 
-const mutableDestructor = (bufferData: MutableBufferData) => {
-  rpc.send(WorkerEvent.DeallocVec, bufferData).catch(onPanic);
-};
+  const destructor = (arcPtr: number) => {
+    rpc.send(WorkerEvent.DecrementArc, arcPtr).catch(onPanic);
+  };
 
-const transformParamsFromRust = (params: RustZapParam[]) =>
-  transformParamsFromRustImpl(
-    wasmMemory,
-    destructor,
-    mutableDestructor,
-    params
-  );
-
-// TODO(JP): Somewhat duplicated with the other implementation.
-const temporarilyHeldBuffersForPostMessage = new Set();
-export const serializeZapArrayForPostMessage = (
-  zapArray: ZapArray
-): PostMessageTypedArray => {
-  if (!(typeof zapArray === "object" && isZapBuffer(zapArray.buffer))) {
-    throw new Error("Only pass Zap arrays to serializeZapArrayForPostMessage");
-  }
-  const zapBuffer = zapArray.buffer as ZapBuffer;
+  const mutableDestructor = (bufferData: MutableBufferData) => {
+    rpc.send(WorkerEvent.DeallocVec, bufferData).catch(onPanic);
+  };
 
-  if (zapBuffer.__zaplibBufferData.readonly) {
-    // Store the buffer temporarily until we've received confirmation that the Arc has been incremented.
-    // Otherwise it might get garbage collected and deallocated (if the Arc's count was 1) before it gets
-    // incremented.
-    temporarilyHeldBuffersForPostMessage.add(zapBuffer);
-    rpc
-      .send(WorkerEvent.IncrementArc, zapBuffer.__zaplibBufferData.arcPtr)
-      .then(() => {
-        temporarilyHeldBuffersForPostMessage.delete(zapBuffer);
-      });
-  } else {
-    unregisterMutableBuffer(zapBuffer);
-  }
+  const transformParamsFromRust = (params: RustZapParam[]) =>
+    transformParamsFromRustImpl(
+      wasmMemory,
+      destructor,
+      mutableDestructor,
+      params
+    );
 
-  return {
-    bufferData: zapBuffer.__zaplibBufferData,
-    byteOffset: zapArray.byteOffset,
-    byteLength: zapArray.byteLength,
+  const checkOwnZapArray = (zapArray: ZapArray): void => {
+    if (!isZapBufferOf(zapArray.buffer, wasmMemory)) {
+      throw new Error("This Zap array belongs to a different Zaplib instance");
+    }
   };
-};
 
-export const callRustAsync: CallRustAsync = async <T extends ZapParam[]>(
-  name: string,
-  params: ZapParam[] = []
-): Promise<T> => {
-  checkWasm();
-
-  const transformedParams = params.map((param) => {
-    if (typeof param === "string") {
-      return param;
-    } else if (isZapBuffer(param.buffer)) {
-      checkValidZapArray(param);
-      return serializeZapArrayForPostMessage(param);
+  // TODO(JP): Somewhat duplicated with the other implementation.
+  const temporarilyHeldBuffersForPostMessage = new Set();
+  const serializeZapArrayForPostMessage = (
+    zapArray: ZapArray
+  ): PostMessageTypedArray => {
+    if (!(typeof zapArray === "object" && isZapBuffer(zapArray.buffer))) {
+      throw new Error(
+        "Only pass Zap arrays to serializeZapArrayForPostMessage"
+      );
+    }
+    checkOwnZapArray(zapArray);
+    const zapBuffer = zapArray.buffer as ZapBuffer;
+    checkZapBufferUsable(zapBuffer);
+
+    if (zapBuffer.__zaplibBufferData.readonly) {
+      // Store the buffer temporarily until we've received confirmation that the Arc has been incremented.
+      // Otherwise it might get garbage collected and deallocated (if the Arc's count was 1) before it gets
+      // incremented.
+      temporarilyHeldBuffersForPostMessage.add(zapBuffer);
+      rpc
+        .send(WorkerEvent.IncrementArc, zapBuffer.__zaplibBufferData.arcPtr)
+        .then(() => {
+          temporarilyHeldBuffersForPostMessage.delete(zapBuffer);
+        });
     } else {
-      if (!(param.buffer instanceof SharedArrayBuffer)) {
-        console.warn(
-          "Consider passing Uint8Arrays backed by ZapBuffer or SharedArrayBuffer into `callRustAsync` to prevent copying data"
-        );
-      }
-      return param;
+      unregisterMutableBuffer(zapBuffer);
     }
-  });
 
-  return transformParamsFromRust(
-    await rpc.send(WorkerEvent.CallRustAsync, {
+    return {
+      bufferData: zapBuffer.__zaplibBufferData,
+      byteOffset: zapArray.byteOffset,
+      byteLength: zapArray.byteLength,
+    };
+  };
+
+  // Free the data of a buffer that we got from Rust right away, instead of when it gets garbage collected.
+  // The array (and any other arrays over the same buffer) must not be used afterwards.
+  const releaseBuffer = (zapArray: ZapArray): void => {
+    if (!(typeof zapArray === "object" && isZapBuffer(zapArray.buffer))) {
+      throw new Error("Only pass Zap arrays to releaseBuffer");
+    }
+    checkOwnZapArray(zapArray);
+    releaseZapBuffer(
+      zapArray.buffer as ZapBuffer,
+      destructor,
+      mutableDestructor
+    );
+  };
+
+  const transformParamsForRust = (params: ZapParam[]) =>
+    params.map((param) => {
+      if (typeof param === "string") {
+        return param;
+      } else if (isZapBufferOf(param.buffer, wasmMemory)) {
+        checkValidZapArray(param);
+        return serializeZapArrayForPostMessage(param);
+      } else if (isZapBuffer(param.buffer)) {
+        // Belongs to another Zaplib instance, so Rust can't read it directly.
+        return param.slice();
+      } else {
+        if (!(param.buffer instanceof SharedArrayBuffer)) {
+          console.warn(
+            "Consider passing Uint8Arrays backed by ZapBuffer or SharedArrayBuffer into `callRustAsync` to prevent copying data"
+          );
+        }
+        return param;
+      }
+    });
+
+  const callRustAsync: CallRustAsync = <T extends ZapParam[]>(
+    name: string,
+    params: ZapParam[] = []
+  ) => {
+    let cancelToken: CancelToken | undefined;
+    const promise = (async () => {
+      checkWasm();
+      cancelToken = createCancelToken(wasmMemory, wasmExports);
+      const result = await rpc.send(WorkerEvent.CallRustAsync, {
+        name,
+        params: transformParamsForRust(params),
+        cancelTokenPtr: cancelToken.ptr,
+      });
+      // Not dropped on errors, since then the WebAssembly instance has crashed.
+      cancelToken.drop();
+      return transformParamsFromRust(result) as T;
+    })();
+    return Object.assign(promise, { cancel: () => cancelToken?.cancel() });
+  };
+
+  const callRustStream: CallRustStream = async function* <
+    T extends ZapParam[]
+  >(name: string, params: ZapParam[] = []) {
+    checkWasm();
+
+    const cancelToken = createCancelToken(wasmMemory, wasmExports);
+    const streamId = await rpc.send(WorkerEvent.CallRustStream, {
       name,
-      params: transformedParams,
-    })
-  ) as T;
-};
+      params: transformParamsForRust(params),
+      cancelTokenPtr: cancelToken.ptr,
+    });
+    yield* callRustStreamImpl<T>({
+      next: () => rpc.send(WorkerEvent.CallRustStreamNext, streamId),
+      cancelToken,
+      transformParamsFromRust,
+    });
+  };
 
-export const callRustSync: CallRustSync = <T extends ZapParam[]>(
-  name: string,
-  params: ZapParam[] = []
-) =>
-  callRustSyncImpl({
-    name,
-    params,
-    checkWasm,
-    wasmMemory,
-    wasmExports,
-    wasmAppPtr,
-    transformParamsFromRust,
-  }) as T;
-
-export const deserializeZapArrayFromPostMessage = (
-  postMessageData: PostMessageTypedArray
-): Uint8Array => {
-  const zapBuffer = getZapBufferWasm(
-    wasmMemory,
-    postMessageData.bufferData,
-    destructor,
-    mutableDestructor
-  );
-  return new Uint8Array(
-    zapBuffer,
-    postMessageData.byteOffset,
-    postMessageData.byteLength
-  );
-};
+  const callRustSync: CallRustSync = <T extends ZapParam[]>(
+    name: string,
+    params: ZapParam[] = []
+  ) =>
+    callRustSyncImpl({
+      name,
+      params,
+      checkWasm,
+      wasmMemory,
+      wasmExports,
+      wasmAppPtr,
+      transformParamsFromRust,
+    }) as T;
+
+  // Change which Rust `log` messages get printed to the console, e.g. "warn,my_crate=debug".
+  const setLogFilter = (filter: string): void => {
+    const bytes = new TextEncoder().encode(filter);
+    const vecPtr = createWasmBuffer(wasmMemory, wasmExports, bytes);
+    wasmExports.setLogFilter(BigInt(vecPtr), BigInt(bytes.byteLength));
+  };
 
-function initializeCanvas(
-  canvas: HTMLCanvasElement,
-  initParams: InitParams
-): CanvasData {
-  require("./zaplib.css");
+  const deserializeZapArrayFromPostMessage = (
+    postMessageData: PostMessageTypedArray
+  ): Uint8Array => {
+    const zapBuffer = getZapBufferWasm(
+      wasmMemory,
+      postMessageData.bufferData,
+      destructor,
+      mutableDestructor
+    );
+    return new Uint8Array(
+      zapBuffer,
+      postMessageData.byteOffset,
+      postMessageData.byteLength
+    );
+  };
 
-  canvas.className = "zaplib_canvas";
+  function initializeCanvas(
+    canvas: HTMLCanvasElement,
+    initParams: InitParams
+  ): CanvasData {
+    require("./zaplib.css");
 
-  document.addEventListener("contextmenu", (event) => {
-    if (
-      event.target instanceof Element &&
-      !document.getElementById("zaplib_js_root")?.contains(event.target)
-    ) {
-      event.preventDefault();
-    }
-  });
+    canvas.className = "zaplib_canvas";
 
-  document.addEventListener("mousedown", (event) => {
-    if (wasmInitialized()) {
-      rpc
-        .send(WorkerEvent.CanvasMouseDown, makeRpcMouseEvent(event))
-        .catch(onPanic);
-    }
-  });
-  window.addEventListener("mouseup", (event) => {
-    if (wasmInitialized()) {
-      rpc
-        .send(WorkerEvent.WindowMouseUp, makeRpcMouseEvent(event))
-        .catch(onPanic);
-    }
-  });
-  window.addEventListener("mousemove", (event) => {
-    if (wasmInitialized()) {
-      rpc
-        .send(WorkerEvent.WindowMouseMove, makeRpcMouseEvent(event))
-        .catch(onPanic);
-    }
-  });
-  window.addEventListener("mouseout", (event) => {
-    if (wasmInitialized()) {
-      rpc
-        .send(WorkerEvent.WindowMouseOut, makeRpcMouseEvent(event))
-        .catch(onPanic);
+    document.addEventListener("contextmenu", (event) => {
+      if (
+        event.target instanceof Element &&
+        !document.getElementById("zaplib_js_root")?.contains(event.target)
+      ) {
+        event.preventDefault();
+      }
+    });
+
+    // There can be multiple canvases on the page (see `createInstance`), so we only handle events
+    // that start on this canvas, and make coordinates relative to it.
+    const getCanvasOffset = (): CanvasOffset => {
+      const rect = canvas.getBoundingClientRect();
+      return { x: rect.left + window.scrollX, y: rect.top + window.scrollY };
+    };
+    const isOnCanvas = (point: { clientX: number; clientY: number }) => {
+      const rect = canvas.getBoundingClientRect();
+      return (
+        point.clientX >= rect.left &&
+        point.clientX < rect.right &&
+        point.clientY >= rect.top &&
+        point.clientY < rect.bottom
+      );
+    };
+    if (!focusedCanvas) {
+      focusedCanvas = canvas;
     }
-  });
 
-  document.addEventListener(
-    "touchstart",
-    (event: TouchEvent) => {
-      event.preventDefault();
+    // Canvases of windows other than the main window, by window id. See
+    // `WorkerEvent.AttachWindowCanvas` below.
+    const windowCanvases = new Map<number, HTMLCanvasElement>();
+    const isInWindow = (point: {
+      target: EventTarget | null;
+      clientX: number;
+      clientY: number;
+    }) =>
+      isOnCanvas(point) ||
+      Array.from(windowCanvases.values()).some(
+        (windowCanvas) => windowCanvas === point.target
+      );
+    // Find the window that a pointer is in, and the position of the pointer relative to that
+    // window. Pass in `windowId` to get the position relative to a particular window instead.
+    const getWindowPosition = (
+      point: { target: EventTarget | null; pageX: number; pageY: number },
+      windowId?: number
+    ): WindowPosition => {
+      if (windowId === undefined) {
+        windowId = 0;
+        for (const [id, windowCanvas] of windowCanvases) {
+          if (windowCanvas === point.target) {
+            windowId = id;
+          }
+        }
+      }
+      const windowCanvas = windowCanvases.get(windowId);
+      if (!windowCanvas) {
+        const offset = getCanvasOffset();
+        return {
+          windowId: 0,
+          x: point.pageX - offset.x,
+          y: point.pageY - offset.y,
+        };
+      }
+      const rect = windowCanvas.getBoundingClientRect();
+      return {
+        windowId,
+        x: point.pageX - rect.left - window.scrollX,
+        y: point.pageY - rect.top - window.scrollY,
+      };
+    };
+    // While mouse buttons are down, we keep sending events to the window in which the first
+    // button was pressed, just like operating systems do.
+    let mouseDownWindowId: number | undefined;
+
+    document.addEventListener("mousedown", (event) => {
+      if (mouseDownWindowId === undefined && !isInWindow(event)) {
+        return;
+      }
+      focusedCanvas = canvas;
       if (wasmInitialized()) {
+        const position = getWindowPosition(event, mouseDownWindowId);
+        mouseDownWindowId = position.windowId;
         rpc
-          .send(WorkerEvent.WindowTouchStart, makeRpcTouchEvent(event))
+          .send(WorkerEvent.CanvasMouseDown, makeRpcMouseEvent(event, position))
           .catch(onPanic);
       }
-    },
-    { passive: false }
-  );
-  window.addEventListener(
-    "touchmove",
-    (event: TouchEvent) => {
-      event.preventDefault();
+    });
+    window.addEventListener("mouseup", (event) => {
       if (wasmInitialized()) {
+        const position = getWindowPosition(event, mouseDownWindowId);
+        if (event.buttons === 0) {
+          mouseDownWindowId = undefined;
+        }
         rpc
-          .send(WorkerEvent.WindowTouchMove, makeRpcTouchEvent(event))
+          .send(WorkerEvent.WindowMouseUp, makeRpcMouseEvent(event, position))
           .catch(onPanic);
       }
-    },
-    { passive: false }
-  );
-  const touchEndCancelLeave = (event: TouchEvent) => {
-    event.preventDefault();
-    if (wasmInitialized()) {
-      rpc
-        .send(WorkerEvent.WindowTouchEndCancelLeave, makeRpcTouchEvent(event))
-        .catch(onPanic);
-    }
-  };
-  window.addEventListener("touchend", touchEndCancelLeave);
-  window.addEventListener("touchcancel", touchEndCancelLeave);
-
-  document.addEventListener("wheel", (event) => {
-    if (wasmInitialized()) {
-      rpc
-        .send(WorkerEvent.CanvasWheel, makeRpcWheelEvent(event))
-        .catch(onPanic);
-    }
-  });
-  window.addEventListener("focus", () => {
-    if (wasmInitialized()) {
-      rpc.send(WorkerEvent.WindowFocus).catch(onPanic);
-    }
-  });
-  window.addEventListener("blur", () => {
-    if (wasmInitialized()) {
-      rpc.send(WorkerEvent.WindowBlur).catch(onPanic);
-    }
-  });
-
-  const isMobileSafari = globalThis.navigator.platform.match(/iPhone|iPad/i);
-  const isAndroid = globalThis.navigator.userAgent.match(/Android/i);
-
-  if (!isMobileSafari && !isAndroid && initParams.createTextArea) {
-    // mobile keyboards are unusable on a UI like this
-    const { showTextIME } = makeTextarea((taEvent: TextareaEvent) => {
+    });
+    window.addEventListener("mousemove", (event) => {
+      if (wasmInitialized()) {
+        const position = getWindowPosition(event, mouseDownWindowId);
+        rpc
+          .send(WorkerEvent.WindowMouseMove, makeRpcMouseEvent(event, position))
+          .catch(onPanic);
+      }
+    });
+    window.addEventListener("mouseout", (event) => {
       if (wasmInitialized()) {
-        rpc.send(taEvent.type, taEvent).catch(onPanic);
+        const position = getWindowPosition(event);
+        rpc
+          .send(WorkerEvent.WindowMouseOut, makeRpcMouseEvent(event, position))
+          .catch(onPanic);
       }
     });
-    rpc.receive(WorkerEvent.ShowTextIME, showTextIME);
-  }
 
-  const getSizingData = () => {
-    const canFullscreen = !!(
-      document.fullscreenEnabled ||
-      document.webkitFullscreenEnabled ||
-      document.mozFullscreenEnabled
+    // Touches keep the element in which they started as `target`, so we don't need to track
+    // that ourselves.
+    const getTouchPosition = (touch: Touch) => getWindowPosition(touch);
+    document.addEventListener(
+      "touchstart",
+      (event: TouchEvent) => {
+        if (!isInWindow(event.changedTouches[0])) {
+          return;
+        }
+        event.preventDefault();
+        if (wasmInitialized()) {
+          rpc
+            .send(
+              WorkerEvent.WindowTouchStart,
+              makeRpcTouchEvent(event, getTouchPosition)
+            )
+            .catch(onPanic);
+        }
+      },
+      { passive: false }
     );
-    const isFullscreen = !!(
-      document.fullscreenElement ||
-      document.webkitFullscreenElement ||
-      document.mozFullscreenElement
+    window.addEventListener(
+      "touchmove",
+      (event: TouchEvent) => {
+        event.preventDefault();
+        if (wasmInitialized()) {
+          rpc
+            .send(
+              WorkerEvent.WindowTouchMove,
+              makeRpcTouchEvent(event, getTouchPosition)
+            )
+            .catch(onPanic);
+        }
+      },
+      { passive: false }
     );
-    return {
-      width: canvas.offsetWidth,
-      height: canvas.offsetHeight,
-      dpiFactor: window.devicePixelRatio,
-      canFullscreen,
-      isFullscreen,
-    };
-  };
-
-  let webglRenderer: WebGLRenderer;
-
-  const onScreenResize = () => {
-    // TODO(JP): Some day bring this back?
-    // if (is_add_to_homescreen_safari) { // extremely ugly. but whatever.
-    //     if (window.orientation == 90 || window.orientation == -90) {
-    //         h = screen.width;
-    //         w = screen.height - 90;
-    //     }
-    //     else {
-    //         w = screen.width;
-    //         h = screen.height - 80;
-    //     }
-    // }
-
-    const sizingData = getSizingData();
-    if (webglRenderer) {
-      webglRenderer.resize(sizingData);
-    }
-    if (wasmInitialized()) {
-      rpc.send(WorkerEvent.ScreenResize, sizingData).catch(onPanic);
-    }
-  };
-  window.addEventListener("resize", () => onScreenResize());
-  window.addEventListener("orientationchange", () => onScreenResize());
-
-  let dpiFactor = window.devicePixelRatio;
-  const mqString = "(resolution: " + window.devicePixelRatio + "dppx)";
-  const mq = matchMedia(mqString);
-  if (mq && mq.addEventListener) {
-    mq.addEventListener("change", () => onScreenResize());
-  } else {
-    // poll for it. yes. its terrible
-    globalThis.setInterval(() => {
-      if (window.devicePixelRatio != dpiFactor) {
-        dpiFactor = window.devicePixelRatio;
-        onScreenResize();
-      }
-    }, 1000);
-  }
-
-  // If the browser supports OffscreenCanvas, then we'll use that. Otherwise, we render on
-  // the browser's main thread using WebGLRenderer.
-  let renderingMethod: OffscreenCanvas | WebGLRenderer;
-  try {
-    renderingMethod = canvas.transferControlToOffscreen();
-  } catch (_) {
-    webglRenderer = new WebGLRenderer(
-      canvas,
-      wasmMemory,
-      getSizingData(),
-      () => {
+    const touchEndCancelLeave = (event: TouchEvent) => {
+      event.preventDefault();
+      if (wasmInitialized()) {
         rpc
-          .send(WorkerEvent.ShowIncompatibleBrowserNotification)
+          .send(
+            WorkerEvent.WindowTouchEndCancelLeave,
+            makeRpcTouchEvent(event, getTouchPosition)
+          )
           .catch(onPanic);
       }
+    };
+    window.addEventListener("touchend", touchEndCancelLeave);
+    window.addEventListener("touchcancel", touchEndCancelLeave);
+
+    document.addEventListener(
+      "wheel",
+      (event) => {
+        if (!isInWindow(event)) {
+          return;
+        }
+        // Trackpad pinches come in as wheel events with `ctrlKey` set. We send those to the app, so don't let the
+        // browser zoom the page.
+        if (event.ctrlKey) {
+          event.preventDefault();
+        }
+        if (wasmInitialized()) {
+          rpc
+            .send(
+              WorkerEvent.CanvasWheel,
+              makeRpcWheelEvent(event, getWindowPosition(event))
+            )
+            .catch(onPanic);
+        }
+      },
+      { passive: false }
     );
-    rpc.receive(WorkerEvent.RunWebGL, (zerdeParserPtr) => {
-      webglRenderer.processMessages(zerdeParserPtr);
-      return new Promise((resolve) => {
-        requestAnimationFrame(() => {
-          resolve(undefined);
-        });
-      });
+    window.addEventListener("focus", () => {
+      if (wasmInitialized()) {
+        rpc.send(WorkerEvent.WindowFocus).catch(onPanic);
+      }
+    });
+    window.addEventListener("blur", () => {
+      if (wasmInitialized()) {
+        rpc.send(WorkerEvent.WindowBlur).catch(onPanic);
+      }
     });
-    renderingMethod = webglRenderer;
-  }
 
-  return { renderingMethod, onScreenResize, getSizingData };
-}
+    const isMobileSafari = globalThis.navigator.platform.match(/iPhone|iPad/i);
+    const isAndroid = globalThis.navigator.userAgent.match(/Android/i);
 
-// Once set to true, it will never go back to false (even in case of an error).
-let initialized = false;
-export const isInitialized: IsInitialized = () => initialized;
+    if (!isMobileSafari && !isAndroid && initParams.createTextArea) {
+      // mobile keyboards are unusable on a UI like this
+      const { showTextIME } = makeTextarea(
+        (taEvent: TextareaEvent) => {
+          if (wasmInitialized()) {
+            rpc.send(taEvent.type, taEvent).catch(onPanic);
+          }
+        },
+        () => focusedCanvas === canvas
+      );
+      rpc.receive(WorkerEvent.ShowTextIME, ({ x, y }) => {
+        const offset = getCanvasOffset();
+        showTextIME({ x: x + offset.x, y: y + offset.y });
+      });
+    }
 
-let alreadyCalledInitialize = false;
-export const initialize: Initialize = (initParams) => {
-  initParams = normalizeInitParams(initParams);
+    const getSizingData = () => {
+      const canFullscreen = !!(
+        document.fullscreenEnabled ||
+        document.webkitFullscreenEnabled ||
+        document.mozFullscreenEnabled
+      );
+      const isFullscreen = !!(
+        document.fullscreenElement ||
+        document.webkitFullscreenElement ||
+        document.mozFullscreenElement
+      );
+      return {
+        width: canvas.offsetWidth,
+        height: canvas.offsetHeight,
+        dpiFactor: window.devicePixelRatio,
+        canFullscreen,
+        isFullscreen,
+        xrCanPresent: !!xrPresenter?.canPresent,
+        xrIsPresenting: !!xrPresenter?.isPresenting(),
+      };
+    };
 
-  if (alreadyCalledInitialize) {
-    throw new Error("Only call zaplib.initialize() once");
-  }
-  alreadyCalledInitialize = true;
-
-  if (initParams.onPanic) {
-    const newOnRenderingPanic = initParams.onPanic;
-    onPanic = (e: unknown) => {
-      Atomics.store(wasmOnline, 0, 0);
-      if (e instanceof Error) {
-        newOnRenderingPanic(e);
-      } else {
-        newOnRenderingPanic(new Error("" + e));
+    let webglRenderer: WebGLRenderer;
+    let xrPresenter: XrPresenter | undefined;
+
+    const onScreenResize = () => {
+      // TODO(JP): Some day bring this back?
+      // if (is_add_to_homescreen_safari) { // extremely ugly. but whatever.
+      //     if (window.orientation == 90 || window.orientation == -90) {
+      //         h = screen.width;
+      //         w = screen.height - 90;
+      //     }
+      //     else {
+      //         w = screen.width;
+      //         h = screen.height - 80;
+      //     }
+      // }
+
+      const sizingData = getSizingData();
+      if (webglRenderer) {
+        webglRenderer.resize(sizingData);
+      }
+      if (wasmInitialized()) {
+        rpc.send(WorkerEvent.ScreenResize, sizingData).catch(onPanic);
       }
     };
-  }
+    window.addEventListener("resize", () => onScreenResize());
+    window.addEventListener("orientationchange", () => onScreenResize());
+
+    let dpiFactor = window.devicePixelRatio;
+    const mqString = "(resolution: " + window.devicePixelRatio + "dppx)";
+    const mq = matchMedia(mqString);
+    if (mq && mq.addEventListener) {
+      mq.addEventListener("change", () => onScreenResize());
+    } else {
+      // poll for it. yes. its terrible
+      globalThis.setInterval(() => {
+        if (window.devicePixelRatio != dpiFactor) {
+          dpiFactor = window.devicePixelRatio;
+          onScreenResize();
+        }
+      }, 1000);
+    }
 
-  if (self.Worker !== globalThis.Worker) {
-    // This can happen e.g. when using a custom Jest environment that overrides self.Worker.
-    console.warn(
-      "self.Worker is not set; this means that we can't instantiate Zaplib Workers. This may be caused by overwriting `self` (such as in a test mock). In Node.js you might need to import zaplib/dist/zaplib_nodejs_polyfill.development and/or set globalThis.self.Worker = globalThis.Worker."
-    );
-  }
+    // If the browser supports OffscreenCanvas, then we'll use that. Otherwise, we render on
+    // the browser's main thread using WebGLRenderer. WebXR is only available on the main thread,
+    // so with `initParams.xr` we always render there; see `xr.ts`.
+    let offscreenCanvas: OffscreenCanvas | undefined;
+    if (!initParams.xr) {
+      try {
+        offscreenCanvas = canvas.transferControlToOffscreen();
+      } catch (_) {
+        // Not supported; render on the main thread below.
+      }
+    }
+    let renderingMethod: OffscreenCanvas | WebGLRenderer;
+    if (offscreenCanvas) {
+      renderingMethod = offscreenCanvas;
+    } else {
+      webglRenderer = new WebGLRenderer(
+        canvas,
+        wasmMemory,
+        getSizingData(),
+        () => {
+          rpc
+            .send(WorkerEvent.ShowIncompatibleBrowserNotification)
+            .catch(onPanic);
+        },
+        initParams.xr
+      );
+      if (initParams.xr) {
+        const presenter = new XrPresenter(
+          webglRenderer,
+          onScreenResize,
+          (frame) => rpc.send(WorkerEvent.XrFrame, frame)
+        );
+        rpc.receive(WorkerEvent.XrStartPresenting, () => {
+          presenter.startPresenting();
+        });
+        rpc.receive(WorkerEvent.XrStopPresenting, () => {
+          presenter.stopPresenting();
+        });
+        xrPresenter = presenter;
+      }
+      rpc.receive(WorkerEvent.RunWebGL, (zerdeParserPtr) => {
+        if (xrPresenter?.isPresenting()) {
+          return xrPresenter.runWebGL(zerdeParserPtr);
+        }
+        webglRenderer.processMessages(zerdeParserPtr);
+        return new Promise((resolve) => {
+          requestAnimationFrame(() => {
+            resolve(undefined);
+          });
+        });
+      });
+      renderingMethod = webglRenderer;
+    }
 
-  if (inWorker && !inNodeJs) {
-    console.warn(
-      "zaplib.initialize() should be called on the browser's main thread. It might work in a Web Worker, but not all browsers currently support this (e.g. Safari doesn't)"
+    // Windows other than the main window get drawn into their own canvas. We still render them
+    // using the WebGL context of the main canvas, since WebGL contexts can't share textures or
+    // shaders, and then copy the pixels over. So when using an OffscreenCanvas, we transfer the
+    // window's canvas to the worker as well.
+    const windowCanvasResizeObservers = new Map<number, ResizeObserver>();
+    const createdWindowCanvases = new Set<HTMLCanvasElement>();
+    rpc.receive(
+      WorkerEvent.AttachWindowCanvas,
+      ({ windowId, canvasId, width, height, x, y }) => {
+        let windowCanvas = canvasId ? document.getElementById(canvasId) : null;
+        if (canvasId && !(windowCanvas instanceof HTMLCanvasElement)) {
+          throw new Error(`Could not find canvas with id "${canvasId}"`);
+        }
+        if (!(windowCanvas instanceof HTMLCanvasElement)) {
+          windowCanvas = document.createElement("canvas");
+          windowCanvas.className = "zaplib_window_canvas";
+          windowCanvas.style.left = `${x}px`;
+          windowCanvas.style.top = `${y}px`;
+          windowCanvas.style.width = `${width}px`;
+          windowCanvas.style.height = `${height}px`;
+          document.body.appendChild(windowCanvas);
+          createdWindowCanvases.add(windowCanvas);
+        }
+        const attachedCanvas = windowCanvas;
+        windowCanvases.set(windowId, attachedCanvas);
+
+        // This also fires right after we start observing, which sends the initial size.
+        const resizeObserver = new ResizeObserver(() => {
+          if (wasmInitialized()) {
+            rpc
+              .send(WorkerEvent.WindowCanvasResize, {
+                windowId,
+                width: attachedCanvas.offsetWidth,
+                height: attachedCanvas.offsetHeight,
+                dpiFactor: window.devicePixelRatio,
+              })
+              .catch(onPanic);
+          }
+        });
+        resizeObserver.observe(attachedCanvas);
+        windowCanvasResizeObservers.set(windowId, resizeObserver);
+
+        if (webglRenderer) {
+          webglRenderer.attachWindowCanvas(windowId, attachedCanvas);
+          return undefined;
+        }
+        const offscreenCanvas = attachedCanvas.transferControlToOffscreen();
+        return { offscreenCanvas, [Rpc.transferrables]: [offscreenCanvas] };
+      }
     );
+    rpc.receive(WorkerEvent.DetachWindowCanvas, (windowId: number) => {
+      windowCanvasResizeObservers.get(windowId)?.disconnect();
+      windowCanvasResizeObservers.delete(windowId);
+      const windowCanvas = windowCanvases.get(windowId);
+      windowCanvases.delete(windowId);
+      if (windowCanvas && createdWindowCanvases.has(windowCanvas)) {
+        windowCanvas.remove();
+        createdWindowCanvases.delete(windowCanvas);
+      }
+      if (webglRenderer) {
+        webglRenderer.detachWindowCanvas(windowId);
+      }
+    });
+
+    return { renderingMethod, onScreenResize, getSizingData };
   }
 
-  return new Promise<void>((resolve, reject) => {
-    _rpc = new Rpc(newWorker(MainWorker));
-
-    const baseUri =
-      initParams.baseUri ??
-      (globalThis.location
-        ? `${globalThis.location.protocol}//${globalThis.location.host}/`
-        : "unknown://");
-
-    let wasmModulePromise: Promise<WebAssembly.Module>;
-    if (typeof initParams.wasmModule == "string") {
-      const wasmPath = new URL(initParams.wasmModule, baseUri).href;
-      // Safari (as of version 15.2) needs the WebAssembly Module to be compiled on the browser's
-      // main thread. This also allows us to start compiling while still waiting for the DOM to load.
-      wasmModulePromise = WebAssembly.compileStreaming(fetch(wasmPath));
-    } else {
-      wasmModulePromise = initParams.wasmModule;
+  // Once set to true, it will never go back to false (even in case of an error).
+  let initialized = false;
+  const isInitialized: IsInitialized = () => initialized;
+
+  const initialize: Initialize = makeInitializeIdempotent((initParams) => {
+    initParams = normalizeInitParams(initParams);
+
+    if (initParams.onPanic) {
+      const newOnRenderingPanic = initParams.onPanic;
+      onPanic = (e: unknown) => {
+        Atomics.store(wasmOnline, 0, 0);
+        if (e instanceof Error) {
+          newOnRenderingPanic(e);
+        } else {
+          newOnRenderingPanic(new Error("" + e));
+        }
+      };
     }
 
-    // TODO(JP): These file handles are only sent to a worker when it starts running;
-    // it currently can't receive any file handles added after that.
-    const fileHandles: FileHandle[] = [];
+    if (initParams.onWorkerPanic) {
+      onWorkerPanic = initParams.onWorkerPanic;
+    }
 
-    const loader = () => {
-      if (initParams.defaultStyles) {
-        addDefaultStyles();
-        addLoadingIndicator();
+    if (self.Worker !== globalThis.Worker) {
+      // This can happen e.g. when using a custom Jest environment that overrides self.Worker.
+      console.warn(
+        "self.Worker is not set; this means that we can't instantiate Zaplib Workers. This may be caused by overwriting `self` (such as in a test mock). In Node.js you might need to import zaplib/dist/zaplib_nodejs_polyfill.development and/or set globalThis.self.Worker = globalThis.Worker."
+      );
+    }
+
+    if (inWorker && !inNodeJs) {
+      console.warn(
+        "zaplib.initialize() should be called on the browser's main thread. It might work in a Web Worker, but not all browsers currently support this (e.g. Safari doesn't)"
+      );
+    }
+
+    // The latest download progress, for `renderPlaceholder`.
+    let loadingProgress: LoadingProgress = { loaded: 0, total: undefined };
+    const onLoadingProgress =
+      initParams.onLoadingProgress || initParams.renderPlaceholder
+        ? (progress: LoadingProgress) => {
+            loadingProgress = progress;
+            initParams.onLoadingProgress?.(progress);
+          }
+        : undefined;
+
+    const initializePromise = new Promise<void>((resolve, reject) => {
+      _rpc = new Rpc(newWorker(MainWorker));
+
+      const baseUri = initParams.baseUri ?? getDefaultBaseUri();
+
+      let wasmModulePromise: Promise<WebAssembly.Module>;
+      if (typeof initParams.wasmModule == "string" && inNodeJs) {
+        wasmModulePromise = WebAssembly.compile(
+          readFileSyncNodeJs(new URL(initParams.wasmModule, baseUri))
+        );
+      } else if (typeof initParams.wasmModule == "string") {
+        const wasmPath = new URL(initParams.wasmModule, baseUri).href;
+        // Safari (as of version 15.2) needs the WebAssembly Module to be compiled on the browser's
+        // main thread. This also allows us to start compiling while still waiting for the DOM to load.
+        wasmModulePromise = WebAssembly.compileStreaming(
+          onLoadingProgress
+            ? fetchWithProgress(wasmPath, onLoadingProgress)
+            : fetch(wasmPath)
+        );
+      } else {
+        wasmModulePromise = initParams.wasmModule;
       }
 
-      // Some browsers (e.g. Safari 15.2) require SharedArrayBuffers to be initialized
-      // on the browser's main thread; so that's why this has to happen here.
-      //
-      // We also do this before initializing `WebAssembly.Memory`, to make sure we have
-      // enough memory for both.. (This is mostly relevant on mobile; see note below.)
-      const taskWorkerSab = initTaskWorkerSab();
-      const taskWorkerRpc = new Rpc(newWorker(TaskWorker));
-      taskWorkerRpc.send(TaskWorkerEvent.Init, {
-        taskWorkerSab,
-        wasmMemory,
-      });
+      // TODO(JP): These file handles are only sent to a worker when it starts running;
+      // it currently can't receive any file handles added after that.
+      const fileHandles: FileHandle[] = [];
 
-      // Initial has to be equal to or higher than required by the app (which at the time of writing
-      // is around 20 pages).
-      // Maximum has to be equal to or lower than that of the app, which we've currently set to
-      // the maximum for wasm32 (4GB). Browsers should use virtual memory, as to not actually take up
-      // all this space until requested by the app. TODO(JP): We might need to check this behavior in
-      // different browsers at some point (in Chrome it seems to work fine).
-      //
-      // In Safari on my phone (JP), using maximum:65535 causes an out-of-memory error, so we instead
-      // try a hardcoded value of ~400MB.. Note that especially on mobile, all of
-      // this is quite tricky; see e.g. https://github.com/WebAssembly/design/issues/1397
-      //
-      // TODO(JP): It looks like when using shared memory, the maximum might get fully allocated on
-      // some devices (mobile?), which means that there is little room left for JS objects, and it
-      // means that the web page is at higher risk of getting evicted when switching tabs. There are a
-      // few options here:
-      // 1. Allow the user to specify a maximum by hand for mobile in general; or for specific
-      //    devices (cumbersome!).
-      // 2. Allow single-threaded operation, where we don't specify a maximum (but run the risk of
-      //    getting much less memory to use and therefore the app crashing; see again
-      //    https://github.com/WebAssembly/design/issues/1397 for more details).
-      try {
-        wasmMemory = new WebAssembly.Memory({
-          initial: 40,
-          maximum: 65535,
-          shared: true,
+      const loader = () => {
+        if (initParams.defaultStyles) {
+          addDefaultStyles();
+          addLoadingIndicator();
+        }
+
+        // Some browsers (e.g. Safari 15.2) require SharedArrayBuffers to be initialized
+        // on the browser's main thread; so that's why this has to happen here.
+        //
+        // We also do this before initializing `WebAssembly.Memory`, to make sure we have
+        // enough memory for both.. (This is mostly relevant on mobile; see note below.)
+        const taskWorkerSab = initTaskWorkerSab();
+        const taskWorkerRpc = new Rpc(newWorker(TaskWorker));
+        taskWorkerRpc.send(TaskWorkerEvent.Init, {
+          taskWorkerSab,
+          wasmMemory,
         });
-      } catch (_) {
-        console.log("Can't allocate full WebAssembly memory; trying ~400MB");
+
+        // Initial has to be equal to or higher than required by the app (which at the time of writing
+        // is around 20 pages).
+        // Maximum has to be equal to or lower than that of the app, which we've currently set to
+        // the maximum for wasm32 (4GB). Browsers should use virtual memory, as to not actually take up
+        // all this space until requested by the app. TODO(JP): We might need to check this behavior in
+        // different browsers at some point (in Chrome it seems to work fine).
+        //
+        // In Safari on my phone (JP), using maximum:65535 causes an out-of-memory error, so we instead
+        // try a hardcoded value of ~400MB.. Note that especially on mobile, all of
+        // this is quite tricky; see e.g. https://github.com/WebAssembly/design/issues/1397
+        //
+        // TODO(JP): It looks like when using shared memory, the maximum might get fully allocated on
+        // some devices (mobile?), which means that there is little room left for JS objects, and it
+        // means that the web page is at higher risk of getting evicted when switching tabs. There are a
+        // few options here:
+        // 1. Allow the user to specify a maximum by hand for mobile in general; or for specific
+        //    devices (cumbersome!).
+        // 2. Allow single-threaded operation, where we don't specify a maximum (but run the risk of
+        //    getting much less memory to use and therefore the app crashing; see again
+        //    https://github.com/WebAssembly/design/issues/1397 for more details).
         try {
           wasmMemory = new WebAssembly.Memory({
             initial: 40,
-            maximum: 6000,
+            maximum: 65535,
             shared: true,
           });
         } catch (_) {
-          throw new Error("Can't initilialize WebAssembly memory..");
+          console.log("Can't allocate full WebAssembly memory; trying ~400MB");
+          try {
+            wasmMemory = new WebAssembly.Memory({
+              initial: 40,
+              maximum: 6000,
+              shared: true,
+            });
+          } catch (_) {
+            throw new Error("Can't initilialize WebAssembly memory..");
+          }
         }
-      }
-
-      rpc.receive(WorkerEvent.ShowIncompatibleBrowserNotification, () => {
-        const span = document.createElement("span");
-        span.style.color = "white";
-        span.innerHTML =
-          "Sorry, we need browser support for WebGL to run<br/>Please update your browser to a more modern one<br/>Update to at least iOS 10, Safari 10, latest Chrome, Edge or Firefox<br/>Go and update and come back, your browser will be better, faster and more secure!<br/>If you are using chrome on OSX on a 2011/2012 mac please enable your GPU at: Override software rendering list:Enable (the top item) in: <a href='about://flags'>about://flags</a>. Or switch to Firefox or Safari.";
-      });
 
-      rpc.receive(WorkerEvent.SetDocumentTitle, (title: string) => {
-        if (globalThis.document) document.title = title;
-      });
+        rpc.receive(WorkerEvent.ShowIncompatibleBrowserNotification, () => {
+          const span = document.createElement("span");
+          span.style.color = "white";
+          span.innerHTML =
+            "Sorry, we need browser support for WebGL to run<br/>Please update your browser to a more modern one<br/>Update to at least iOS 10, Safari 10, latest Chrome, Edge or Firefox<br/>Go and update and come back, your browser will be better, faster and more secure!<br/>If you are using chrome on OSX on a 2011/2012 mac please enable your GPU at: Override software rendering list:Enable (the top item) in: <a href='about://flags'>about://flags</a>. Or switch to Firefox or Safari.";
+        });
 
-      rpc.receive(WorkerEvent.SetMouseCursor, (style: string) => {
-        if (globalThis.document) document.body.style.cursor = style;
-      });
+        rpc.receive(WorkerEvent.SetDocumentTitle, (title: string) => {
+          if (globalThis.document) document.title = title;
+        });
 
-      rpc.receive(WorkerEvent.Fullscreen, () => {
-        if (document.body.requestFullscreen) {
-          document.body.requestFullscreen();
-        } else if (document.body.webkitRequestFullscreen) {
-          document.body.webkitRequestFullscreen();
-        } else if (document.body.mozRequestFullscreen) {
-          document.body.mozRequestFullscreen();
-        }
-      });
+        rpc.receive(WorkerEvent.SetMouseCursor, (style: string) => {
+          if (globalThis.document) document.body.style.cursor = style;
+        });
 
-      rpc.receive(WorkerEvent.Normalscreen, () => {
-        if (document.exitFullscreen) {
-          document.exitFullscreen();
-        } else if (document.webkitExitFullscreen) {
-          document.webkitExitFullscreen();
-        } else if (document.mozExitFullscreen) {
-          document.mozExitFullscreen();
-        }
-      });
+        // CSS cursor values for `CustomCursor`s, by id. These are only sent once per cursor, since
+        // encoding the image is relatively slow.
+        const customCursors = new Map<number, string>();
+        rpc.receive(
+          WorkerEvent.DefineCustomMouseCursor,
+          ({ id, width, height, hotspotX, hotspotY, rgba }) => {
+            const canvas = document.createElement("canvas");
+            canvas.width = width;
+            canvas.height = height;
+            const context = canvas.getContext("2d");
+            if (!context) return;
+            context.putImageData(
+              new ImageData(new Uint8ClampedArray(rgba), width, height),
+              0,
+              0
+            );
+            const x = Math.round(hotspotX);
+            const y = Math.round(hotspotY);
+            customCursors.set(id, `url(${canvas.toDataURL()}) ${x} ${y}, auto`);
+          }
+        );
+        rpc.receive(WorkerEvent.SetCustomMouseCursor, (id: number) => {
+          if (globalThis.document)
+            document.body.style.cursor = customCursors.get(id) || "default";
+        });
 
-      rpc.receive(WorkerEvent.TextCopyResponse, (textCopyResponse: string) => {
-        window.navigator.clipboard.writeText(textCopyResponse);
-      });
+        // Browsers only allow locking while handling a user gesture, and release the lock when the
+        // user presses Escape, so we report all changes back to Rust. Other Zaplib instances on the
+        // page can lock the pointer too, so we check which element has it.
+        const getPointerLockElement = () => zaplibCanvas || document.body;
+        rpc.receive(WorkerEvent.SetPointerLock, (locked: boolean) => {
+          if (locked) {
+            getPointerLockElement().requestPointerLock();
+          } else if (document.pointerLockElement === getPointerLockElement()) {
+            document.exitPointerLock();
+          }
+        });
+        document.addEventListener("pointerlockchange", () => {
+          rpc
+            .send(
+              WorkerEvent.PointerLockChange,
+              document.pointerLockElement === getPointerLockElement()
+            )
+            .catch(onPanic);
+        });
+        document.addEventListener("pointerlockerror", () => {
+          rpc.send(WorkerEvent.PointerLockChange, false).catch(onPanic);
+        });
 
-      rpc.receive(WorkerEvent.EnableGlobalFileDropTarget, () => {
-        document.addEventListener("dragenter", (ev) => {
-          const dataTransfer = ev.dataTransfer;
-          // dataTransfer isn't guaranteed to exist by spec, so it must be checked
-          if (
-            dataTransfer &&
-            dataTransfer.types.length === 1 &&
-            dataTransfer.types[0] === "Files"
-          ) {
-            ev.stopPropagation();
-            ev.preventDefault();
-            dataTransfer.dropEffect = "copy";
-            if (wasmInitialized()) {
-              rpc.send(WorkerEvent.DragEnter).catch(onPanic);
-            }
+        rpc.receive(WorkerEvent.Fullscreen, () => {
+          if (document.body.requestFullscreen) {
+            document.body.requestFullscreen();
+          } else if (document.body.webkitRequestFullscreen) {
+            document.body.webkitRequestFullscreen();
+          } else if (document.body.mozRequestFullscreen) {
+            document.body.mozRequestFullscreen();
           }
         });
-        document.addEventListener("dragover", (ev) => {
-          ev.stopPropagation();
-          ev.preventDefault();
-          if (wasmInitialized()) {
-            rpc
-              .send(WorkerEvent.DragOver, { x: ev.clientX, y: ev.clientY })
-              .catch(onPanic);
+
+        rpc.receive(WorkerEvent.Normalscreen, () => {
+          if (document.exitFullscreen) {
+            document.exitFullscreen();
+          } else if (document.webkitExitFullscreen) {
+            document.webkitExitFullscreen();
+          } else if (document.mozExitFullscreen) {
+            document.mozExitFullscreen();
           }
         });
-        document.addEventListener("dragleave", (ev) => {
-          ev.stopPropagation();
-          ev.preventDefault();
-          if (wasmInitialized()) {
-            rpc.send(WorkerEvent.DragLeave).catch(onPanic);
+
+        rpc.receive(
+          WorkerEvent.TextCopyResponse,
+          (textCopyResponse: string) => {
+            window.navigator.clipboard.writeText(textCopyResponse);
           }
+        );
+
+        rpc.receive(WorkerEvent.EnableGlobalFileDropTarget, () => {
+          document.addEventListener("dragenter", (ev) => {
+            const dataTransfer = ev.dataTransfer;
+            // dataTransfer isn't guaranteed to exist by spec, so it must be checked
+            if (
+              dataTransfer &&
+              dataTransfer.types.length === 1 &&
+              dataTransfer.types[0] === "Files"
+            ) {
+              ev.stopPropagation();
+              ev.preventDefault();
+              dataTransfer.dropEffect = "copy";
+              if (wasmInitialized()) {
+                rpc.send(WorkerEvent.DragEnter).catch(onPanic);
+              }
+            }
+          });
+          document.addEventListener("dragover", (ev) => {
+            ev.stopPropagation();
+            ev.preventDefault();
+            if (wasmInitialized()) {
+              rpc
+                .send(WorkerEvent.DragOver, { x: ev.clientX, y: ev.clientY })
+                .catch(onPanic);
+            }
+          });
+          document.addEventListener("dragleave", (ev) => {
+            ev.stopPropagation();
+            ev.preventDefault();
+            if (wasmInitialized()) {
+              rpc.send(WorkerEvent.DragLeave).catch(onPanic);
+            }
+          });
+          document.addEventListener("drop", (ev) => {
+            if (!ev.dataTransfer) {
+              return;
+            }
+            const files = Array.from(ev.dataTransfer.files);
+            if (!files.length) {
+              return;
+            }
+            ev.preventDefault();
+            ev.stopPropagation();
+            const fileHandlesToSend: FileHandle[] = [];
+            for (const file of files) {
+              const fileHandle = {
+                id: fileHandles.length,
+                basename: file.name,
+                file,
+                lastReadStart: -1,
+                lastReadEnd: -1,
+              };
+              fileHandlesToSend.push(fileHandle);
+              fileHandles.push(fileHandle);
+            }
+            if (wasmInitialized()) {
+              rpc
+                .send(WorkerEvent.Drop, { fileHandles, fileHandlesToSend })
+                .catch(onPanic);
+            }
+          });
         });
-        document.addEventListener("drop", (ev) => {
-          if (!ev.dataTransfer) {
+
+        rpc.receive(WorkerEvent.CallJs, ({ fnName, params }) => {
+          if (fnName === "_zaplibCallJsAsync") {
+            callJsAsyncImpl({
+              params: transformParamsFromRust(params),
+              jsFunctions,
+              callRustAsync,
+            }).catch(onPanic);
             return;
           }
-          const files = Array.from(ev.dataTransfer.files);
-          if (!files.length) {
+
+          const fn = jsFunctions[fnName];
+          if (!fn) {
+            console.error(
+              `call_js with ${fnName} is not available. Have you registered it using \`registerCallJsCallbacks\`?`
+            );
             return;
           }
-          ev.preventDefault();
-          ev.stopPropagation();
-          const fileHandlesToSend: FileHandle[] = [];
-          for (const file of files) {
-            const fileHandle = {
-              id: fileHandles.length,
-              basename: file.name,
-              file,
-              lastReadStart: -1,
-              lastReadEnd: -1,
-            };
-            fileHandlesToSend.push(fileHandle);
-            fileHandles.push(fileHandle);
-          }
-          if (wasmInitialized()) {
-            rpc
-              .send(WorkerEvent.Drop, { fileHandles, fileHandlesToSend })
-              .catch(onPanic);
-          }
+
+          fn(transformParamsFromRust(params));
         });
-      });
 
-      rpc.receive(WorkerEvent.CallJs, ({ fnName, params }) => {
-        const fn = jsFunctions[fnName];
-        if (!fn) {
-          console.error(
-            `call_js with ${fnName} is not available. Have you registered it using \`registerCallJsCallbacks\`?`
-          );
-          return;
+        let canvasData: CanvasData = {
+          getSizingData: () => {
+            // Dummy sizing data if we're not rendering.
+            // TODO(JP): We should make it so we're not even sending SizingData
+            // at all if we're not rendering.
+            return {
+              width: 0,
+              height: 0,
+              dpiFactor: 1,
+              canFullscreen: false,
+              isFullscreen: false,
+              xrCanPresent: false,
+              xrIsPresenting: false,
+            };
+          },
+          onScreenResize: () => {
+            // Dummy function for if we're not rendering.
+          },
+          renderingMethod: undefined,
+        };
+
+        let canvas: HTMLCanvasElement | undefined = initParams.canvas;
+        if (!canvas && initParams.defaultStyles) {
+          canvas = document.createElement("canvas");
+          document.body.appendChild(canvas);
+        }
+        if (canvas) {
+          canvasData = initializeCanvas(canvas, initParams);
+          zaplibCanvas = canvas;
         }
 
-        fn(transformParamsFromRust(params));
-      });
+        rpc.receive(WorkerEvent.Panic, onPanic);
+
+        wasmModulePromise.then((wasmModule) => {
+          // Threads need to be spawned on the browser's main thread, otherwise Safari (as of version 15.2)
+          // throws errors.
+          const asyncWorkers = new Set();
+          const threadSpawn = ({
+            ctxPtr,
+            tlsAndStackData,
+          }: {
+            ctxPtr: BigInt;
+            tlsAndStackData: TlsAndStackData;
+          }) => {
+            const worker = newWorker(AsyncWorker);
+            const workerErrorHandler = (event: unknown) => {
+              console.log("Async worker error event: ", event);
+            };
+            worker.onerror = workerErrorHandler;
+            worker.onmessageerror = workerErrorHandler;
+            const workerRpc = new Rpc<AsyncWorkerRpc>(worker);
 
-      let canvasData: CanvasData = {
-        getSizingData: () => {
-          // Dummy sizing data if we're not rendering.
-          // TODO(JP): We should make it so we're not even sending SizingData
-          // at all if we're not rendering.
-          return {
-            width: 0,
-            height: 0,
-            dpiFactor: 1,
-            canFullscreen: false,
-            isFullscreen: false,
-          };
-        },
-        onScreenResize: () => {
-          // Dummy function for if we're not rendering.
-        },
-        renderingMethod: undefined,
-      };
+            // Add the worker to an array of workers, to prevent them getting killed when
+            // during garbage collection in Firefox; see https://bugzilla.mozilla.org/show_bug.cgi?id=1592227
+            asyncWorkers.add(worker);
 
-      let canvas: HTMLCanvasElement | undefined = initParams.canvas;
-      if (!canvas && initParams.defaultStyles) {
-        canvas = document.createElement("canvas");
-        document.body.appendChild(canvas);
-      }
-      if (canvas) {
-        canvasData = initializeCanvas(canvas, initParams);
-      }
+            const channel = new MessageChannel();
+            rpc
+              .send(WorkerEvent.BindMainWorkerPort, channel.port1, [
+                channel.port1,
+              ])
+              .catch(onPanic);
 
-      rpc.receive(WorkerEvent.Panic, onPanic);
-
-      wasmModulePromise.then((wasmModule) => {
-        // Threads need to be spawned on the browser's main thread, otherwise Safari (as of version 15.2)
-        // throws errors.
-        const asyncWorkers = new Set();
-        const threadSpawn = ({
-          ctxPtr,
-          tlsAndStackData,
-        }: {
-          ctxPtr: BigInt;
-          tlsAndStackData: TlsAndStackData;
-        }) => {
-          const worker = newWorker(AsyncWorker);
-          const workerErrorHandler = (event: unknown) => {
-            console.log("Async worker error event: ", event);
+            workerRpc.receive(AsyncWorkerEvent.ThreadSpawn, threadSpawn);
+
+            workerRpc
+              .send(
+                AsyncWorkerEvent.Run,
+                {
+                  wasmModule,
+                  memory: wasmMemory,
+                  taskWorkerSab,
+                  ctxPtr,
+                  fileHandles,
+                  baseUri,
+                  tlsAndStackData,
+                  mainWorkerPort: channel.port2,
+                },
+                [channel.port2]
+              )
+              .catch((e) => {
+                // The Rust side already sent an `Event::WorkerPanic` to the main thread; this is for
+                // reporting in JS, including the stack trace of the worker.
+                onWorkerPanic(e instanceof Error ? e : new Error("" + e));
+              })
+              .finally(() => {
+                worker.terminate();
+                asyncWorkers.delete(worker);
+                _workers.delete(worker);
+              });
           };
-          worker.onerror = workerErrorHandler;
-          worker.onmessageerror = workerErrorHandler;
-          const workerRpc = new Rpc<AsyncWorkerRpc>(worker);
+          rpc.receive(WorkerEvent.ThreadSpawn, threadSpawn);
 
-          // Add the worker to an array of workers, to prevent them getting killed when
-          // during garbage collection in Firefox; see https://bugzilla.mozilla.org/show_bug.cgi?id=1592227
-          asyncWorkers.add(worker);
-
-          const channel = new MessageChannel();
-          rpc
-            .send(WorkerEvent.BindMainWorkerPort, channel.port1, [
-              channel.port1,
-            ])
-            .catch(onPanic);
-
-          workerRpc.receive(AsyncWorkerEvent.ThreadSpawn, threadSpawn);
+          function getExports() {
+            return wasmExports;
+          }
 
-          workerRpc
-            .send(
-              AsyncWorkerEvent.Run,
-              {
-                wasmModule,
-                memory: wasmMemory,
-                taskWorkerSab,
-                ctxPtr,
-                fileHandles,
-                baseUri,
-                tlsAndStackData,
-                mainWorkerPort: channel.port2,
-              },
-              [channel.port2]
-            )
-            .catch((e) => {
-              console.error("async worker failed", e);
-            })
-            .finally(() => {
-              worker.terminate();
-              asyncWorkers.delete(worker);
-              _workers.delete(worker);
-            });
-        };
-        rpc.receive(WorkerEvent.ThreadSpawn, threadSpawn);
+          const env = getWasmEnv({
+            getExports,
+            memory: wasmMemory,
+            taskWorkerSab,
+            fileHandles: [], // TODO(JP): implement at some point..
+            sendEventFromAnyThread: (_eventPtr: BigInt) => {
+              throw new Error("Not yet implemented");
+            },
+            threadSpawn: () => {
+              throw new Error("Not yet implemented");
+            },
+            baseUri,
+          });
 
-        function getExports() {
-          return wasmExports;
-        }
+          WebAssembly.instantiate(wasmModule, { env }).then((instance: any) => {
+            const offscreenCanvas =
+              globalThis.OffscreenCanvas &&
+              canvasData.renderingMethod instanceof OffscreenCanvas
+                ? canvasData.renderingMethod
+                : undefined;
+
+            wasmExports = instance.exports as WasmExports;
+            initThreadLocalStorageMainWorker(wasmExports);
+            const tlsAndStackData =
+              makeThreadLocalStorageAndStackDataOnExistingThread(wasmExports);
+            wasmAppPtr = wasmExports.createWasmApp();
+            // The calls above are safe when wasm isn't online yet, but after that let's
+            // wrap for safety.
+            wasmExports = wrapWasmExports(wasmExports);
 
-        const env = getWasmEnv({
-          getExports,
-          memory: wasmMemory,
-          taskWorkerSab,
-          fileHandles: [], // TODO(JP): implement at some point..
-          sendEventFromAnyThread: (_eventPtr: BigInt) => {
-            throw new Error("Not yet implemented");
-          },
-          threadSpawn: () => {
-            throw new Error("Not yet implemented");
-          },
-          baseUri,
+            rpc
+              .send(
+                WorkerEvent.Init,
+                {
+                  wasmModule,
+                  offscreenCanvas,
+                  sizingData: canvasData.getSizingData(),
+                  baseUri,
+                  memory: wasmMemory,
+                  taskWorkerSab,
+                  tlsAndStackData,
+                  appPtr: wasmAppPtr,
+                  wasmOnline,
+                },
+                offscreenCanvas ? [offscreenCanvas] : []
+              )
+              .then(() => {
+                canvasData.onScreenResize();
+                if (initParams.defaultStyles) {
+                  removeLoadingIndicator();
+                }
+                initialized = true;
+                resolve();
+              });
+          }, reject);
         });
+      };
 
-        WebAssembly.instantiate(wasmModule, { env }).then((instance: any) => {
-          const offscreenCanvas =
-            globalThis.OffscreenCanvas &&
-            canvasData.renderingMethod instanceof OffscreenCanvas
-              ? canvasData.renderingMethod
-              : undefined;
-
-          wasmExports = instance.exports as WasmExports;
-          initThreadLocalStorageMainWorker(wasmExports);
-          const tlsAndStackData =
-            makeThreadLocalStorageAndStackDataOnExistingThread(wasmExports);
-          wasmAppPtr = wasmExports.createWasmApp();
-          // The calls above are safe when wasm isn't online yet, but after that let's
-          // wrap for safety.
-          wasmExports = wrapWasmExports(wasmExports);
-
-          rpc
-            .send(
-              WorkerEvent.Init,
-              {
-                wasmModule,
-                offscreenCanvas,
-                sizingData: canvasData.getSizingData(),
-                baseUri,
-                memory: wasmMemory,
-                taskWorkerSab,
-                tlsAndStackData,
-                appPtr: wasmAppPtr,
-                wasmOnline,
-              },
-              offscreenCanvas ? [offscreenCanvas] : []
-            )
-            .then(() => {
-              canvasData.onScreenResize();
-              if (initParams.defaultStyles) {
-                removeLoadingIndicator();
-              }
-              initialized = true;
-              resolve();
-            });
-        }, reject);
-      });
-    };
+      if (!globalThis.document || document.readyState !== "loading") {
+        loader();
+      } else {
+        document.addEventListener("DOMContentLoaded", loader);
+      }
+    });
 
-    if (!globalThis.document || document.readyState !== "loading") {
-      loader();
-    } else {
-      document.addEventListener("DOMContentLoaded", loader);
+    const { renderPlaceholder } = initParams;
+    if (renderPlaceholder && globalThis.requestAnimationFrame) {
+      // Call `renderPlaceholder` at most once per frame, however often the progress changes.
+      let done = false;
+      const renderFrame = () => {
+        if (done) return;
+        renderPlaceholder(loadingProgress, false);
+        requestAnimationFrame(renderFrame);
+      };
+      requestAnimationFrame(renderFrame);
+      const finish = () => {
+        done = true;
+        renderPlaceholder(loadingProgress, true);
+      };
+      initializePromise.then(finish, finish);
     }
+
+    return initializePromise;
   });
+
+  const close = (): void =>
+    _workers.forEach((worker) => {
+      worker.terminate();
+      _workers.delete(worker);
+    });
+
+  return {
+    initialize,
+    isInitialized,
+    close,
+    newWorkerPort,
+    registerCallJsCallbacks,
+    unregisterCallJsCallbacks,
+    callRustAsync,
+    callRustStream,
+    callRustSync,
+    serializeZapArrayForPostMessage,
+    deserializeZapArrayFromPostMessage,
+    releaseBuffer,
+    setLogFilter,
+  };
 };
 
-export const close = (): void =>
-  _workers.forEach((worker) => {
-    worker.terminate();
-    _workers.delete(worker);
-  });
+// Create a new instance of Zaplib, which is completely independent from the default one (the one
+// that the functions exported from this module use). Useful for showing multiple apps on one page.
+export const createInstance = (): RuntimeInstance => createWasmInstance();
+
+export const {
+  initialize,
+  isInitialized,
+  close,
+  newWorkerPort,
+  registerCallJsCallbacks,
+  unregisterCallJsCallbacks,
+  callRustAsync,
+  callRustStream,
+  callRustSync,
+  serializeZapArrayForPostMessage,
+  deserializeZapArrayFromPostMessage,
+  releaseBuffer,
+  setLogFilter,
+} = createWasmInstance();
@@ -39,9 +39,27 @@ export class WebGLRenderer {
     geomIbId: number;
     geomVbId: number;
     instVbId: number;
+    // Range of the instance buffer that this VAO uses, in floats.
+    instOffset: number;
+    instLen: number;
   }[];
   private textures: Texture[];
   private framebuffers: WebGLFramebuffer[];
+  // Framebuffers that we render windows other than the main window into, by window id. We then
+  // copy the pixels to the 2d context of the window's canvas, if it's attached already.
+  private windowTargets: Record<
+    number,
+    {
+      framebuffer: WebGLFramebuffer;
+      colorTexture: WebGLTexture;
+      depthRenderbuffer: WebGLRenderbuffer;
+      width: number;
+      height: number;
+      pixels: Uint8Array;
+      imageData: ImageData;
+    }
+  >;
+  private windowContexts: Record<number, CanvasRenderingContext2D>;
   private gl: WebGLRenderingContext;
   // eslint-disable-next-line camelcase
   private OESVertexArrayObject!: OES_vertex_array_object;
@@ -55,6 +73,8 @@ export class WebGLRenderer {
   private clearB: number;
   private clearA: number;
   private clearDepth: number;
+  // The framebuffer of the WebXR session, while drawing a frame of the headset. See `xr.ts`.
+  private xrFramebuffer: WebGLFramebuffer | null = null;
 
   private zerdeParser!: ZerdeParser;
   private basef32!: Float32Array;
@@ -64,7 +84,9 @@ export class WebGLRenderer {
     canvas: HTMLCanvasElement | OffscreenCanvas,
     memory: WebAssembly.Memory,
     sizingData: SizingData,
-    incompatibleBrowserCallback: () => void
+    incompatibleBrowserCallback: () => void,
+    // Whether the context can be used for presenting to VR headsets using WebXR.
+    xrCompatible = false
   ) {
     this.canvas = canvas;
     this.memory = memory;
@@ -76,6 +98,8 @@ export class WebGLRenderer {
     this.vaos = [];
     this.textures = [];
     this.framebuffers = [];
+    this.windowTargets = {};
+    this.windowContexts = {};
 
     this.targetWidth = 0;
     this.targetHeight = 0;
@@ -85,11 +109,10 @@ export class WebGLRenderer {
     this.clearB = 0;
     this.clearA = 0;
     this.clearDepth = 0;
-    // this.isMainCanvas = false;
 
     const options = {
       preferLowPowerToHighPerformance: true,
-      // xrCompatible: true // TODO(JP): Bring back some day?
+      xrCompatible,
     };
     // @ts-ignore - TODO(Paras): Get proper support for OffscreenCanvas
     this.gl =
@@ -137,6 +160,38 @@ export class WebGLRenderer {
     this.canvas.height = sizingData.height * sizingData.dpiFactor;
   }
 
+  // For creating the `XRWebGLLayer` of a WebXR session.
+  getContext(): WebGLRenderingContext {
+    return this.gl;
+  }
+
+  // Set the framebuffer that `beginXrCanvas` draws into, or `null` for the canvas.
+  setXrFramebuffer(framebuffer: WebGLFramebuffer | null): void {
+    this.xrFramebuffer = framebuffer;
+  }
+
+  attachWindowCanvas(
+    windowId: number,
+    canvas: HTMLCanvasElement | OffscreenCanvas
+  ): void {
+    // @ts-ignore - TODO(Paras): Get proper support for OffscreenCanvas
+    const context: CanvasRenderingContext2D | null = canvas.getContext("2d");
+    if (context) {
+      this.windowContexts[windowId] = context;
+    }
+  }
+
+  detachWindowCanvas(windowId: number): void {
+    delete this.windowContexts[windowId];
+    const target = this.windowTargets[windowId];
+    if (target) {
+      this.gl.deleteFramebuffer(target.framebuffer);
+      this.gl.deleteTexture(target.colorTexture);
+      this.gl.deleteRenderbuffer(target.depthRenderbuffer);
+      delete this.windowTargets[windowId];
+    }
+  }
+
   private getAttribLocations(
     program: WebGLProgram,
     base: string,
@@ -173,8 +228,13 @@ export class WebGLRenderer {
     let offset = 0;
     for (let i = 0; i < uniforms.length; i++) {
       const uniform = uniforms[i];
+      // Arrays of mat4 (e.g. joint matrices) are the only arrays in uniforms.
+      const arrayMatch = /^mat4\[(\d+)\]$/.exec(uniform.ty);
+      const arrayLength = arrayMatch ? parseInt(arrayMatch[1]) : 0;
       // lets align the uniform
-      const slots = uniformSizeTable[uniform.ty];
+      const slots = arrayMatch
+        ? 16 * arrayLength
+        : uniformSizeTable[uniform.ty as keyof typeof uniformSizeTable];
 
       if ((offset & 3) != 0 && (offset & 3) + slots > 4) {
         // goes over the boundary
@@ -185,7 +245,9 @@ export class WebGLRenderer {
         offset: offset << 2,
         ty: uniform.ty,
         loc: gl.getUniformLocation(program, uniform.name),
-        fn: this.uniformFnTable[uniform.ty],
+        fn: arrayMatch
+          ? this.makeSetMat4Array(arrayLength)
+          : this.uniformFnTable[uniform.ty],
       });
       offset += slots;
     }
@@ -263,6 +325,37 @@ export class WebGLRenderer {
     gl.bindBuffer(gl.ARRAY_BUFFER, null);
   }
 
+  // Allocate new storage for a pooled instance buffer, without waiting for draws that use the old storage.
+  private orphanArrayBuffer(arrayBufferId: number, capacity: number): void {
+    const gl = this.gl;
+    let buf = this.arrayBuffers[arrayBufferId];
+    if (buf === undefined) {
+      buf = this.arrayBuffers[arrayBufferId] = {
+        glBuf: assertNotNull(gl.createBuffer()),
+        length: capacity,
+      };
+    } else {
+      buf.length = capacity;
+    }
+    gl.bindBuffer(gl.ARRAY_BUFFER, buf.glBuf);
+    gl.bufferData(gl.ARRAY_BUFFER, capacity * 4, gl.DYNAMIC_DRAW);
+    gl.bindBuffer(gl.ARRAY_BUFFER, null);
+  }
+
+  private updateArrayBufferRange(
+    arrayBufferId: number,
+    offset: number,
+    array: Float32Array
+  ): void {
+    if (array.length === 0) {
+      return;
+    }
+    const gl = this.gl;
+    gl.bindBuffer(gl.ARRAY_BUFFER, this.arrayBuffers[arrayBufferId].glBuf);
+    gl.bufferSubData(gl.ARRAY_BUFFER, offset * 4, array);
+    gl.bindBuffer(gl.ARRAY_BUFFER, null);
+  }
+
   private allocIndexBuffer(indexBufferId: number, array: Uint32Array): void {
     const gl = this.gl;
 
@@ -285,7 +378,9 @@ export class WebGLRenderer {
     shaderId: number,
     geomIbId: number,
     geomVbId: number,
-    instVbId: number
+    instVbId: number,
+    instOffset: number,
+    instLen: number
   ): void {
     const gl = this.gl;
     const oldVao = this.vaos[vaoId];
@@ -295,7 +390,14 @@ export class WebGLRenderer {
     const glVao = assertNotNull(
       this.OESVertexArrayObject.createVertexArrayOES()
     );
-    const vao = (this.vaos[vaoId] = { glVao, geomIbId, geomVbId, instVbId });
+    const vao = (this.vaos[vaoId] = {
+      glVao,
+      geomIbId,
+      geomVbId,
+      instVbId,
+      instOffset,
+      instLen,
+    });
 
     this.OESVertexArrayObject.bindVertexArrayOES(vao.glVao);
     gl.bindBuffer(gl.ARRAY_BUFFER, this.arrayBuffers[geomVbId].glBuf);
@@ -331,7 +433,7 @@ export class WebGLRenderer {
         gl.FLOAT,
         false,
         attr.stride,
-        attr.offset
+        attr.offset + instOffset * 4
       );
       gl.enableVertexAttribArray(attr.loc);
       this.ANGLEInstancedArrays.vertexAttribDivisorANGLE(attr.loc, 1);
@@ -360,12 +462,9 @@ export class WebGLRenderer {
     this.OESVertexArrayObject.bindVertexArrayOES(vao.glVao);
 
     const indexBuffer = this.indexBuffers[vao.geomIbId];
-    const instanceBuffer = this.arrayBuffers[vao.instVbId];
     // set up uniforms TODO do this a bit more incremental based on uniform layer
     // also possibly use webGL2 uniform buffers. For now this will suffice for webGL 1 compat
     const passUniforms = shader.passUniforms;
-    // if vr_presenting
-
     const viewUniforms = shader.viewUniforms;
     for (let i = 0; i < viewUniforms.length; i++) {
       const uni = viewUniforms[i];
@@ -387,31 +486,15 @@ export class WebGLRenderer {
       const texId = this.baseu32[(texturesPtr >> 2) + i];
       const texObj = this.textures[texId];
       gl.activeTexture(gl.TEXTURE0 + i);
-      gl.bindTexture(gl.TEXTURE_2D, texObj);
+      gl.bindTexture(
+        texSlot.ty === "samplerCube" ? gl.TEXTURE_CUBE_MAP : gl.TEXTURE_2D,
+        texObj
+      );
       gl.uniform1i(texSlot.loc, i);
     }
     const indices = indexBuffer.length;
-    const instances = instanceBuffer.length / shader.instanceSlots;
-
-    // if (this.isMainCanvas && xrIsPresenting) {
-    // for (let i = 3; i < pass_uniforms.length; i ++) {
-    //     let uni = pass_uniforms[i];
-    //     uni.fn(this, uni.loc, uni.offset + pass_uniforms_ptr);
-    // }
-    // // the first 2 matrices are project and view
-    // let left_viewport = this.xr_left_viewport;
-    // gl.viewport(left_viewport.x, left_viewport.y, left_viewport.width, left_viewport.height);
-    // gl.uniformMatrix4fv(pass_uniforms[0].loc, false, this.xr_left_projection_matrix);
-    // gl.uniformMatrix4fv(pass_uniforms[1].loc, false, this.xr_left_transform_matrix);
-    // gl.uniformMatrix4fv(pass_uniforms[2].loc, false, this.xr_left_invtransform_matrix);
-    // this.ANGLE_instanced_arrays.drawElementsInstancedANGLE(gl.TRIANGLES, indices, gl.UNSIGNED_INT, 0, instances);
-    // let right_viewport = this.xr_right_viewport;
-    // gl.viewport(right_viewport.x, right_viewport.y, right_viewport.width, right_viewport.height);
-    // gl.uniformMatrix4fv(pass_uniforms[0].loc, false, this.xr_right_projection_matrix);
-    // gl.uniformMatrix4fv(pass_uniforms[1].loc, false, this.xr_right_transform_matrix);
-    // gl.uniformMatrix4fv(pass_uniforms[2].loc, false, this.xr_right_invtransform_matrix);
-    // this.ANGLE_instanced_arrays.drawElementsInstancedANGLE(gl.TRIANGLES, indices, gl.UNSIGNED_INT, 0, instances);
-    // } else {
+    const instances = vao.instLen / shader.instanceSlots;
+
     for (let i = 0; i < passUniforms.length; i++) {
       const uni = passUniforms[i];
       uni.fn(this, uni.loc, uni.offset + passUniformsPtr);
@@ -423,7 +506,6 @@ export class WebGLRenderer {
       0,
       instances
     );
-    // }
     this.OESVertexArrayObject.bindVertexArrayOES(null);
   }
 
@@ -461,6 +543,44 @@ export class WebGLRenderer {
     this.textures[textureId] = glTex as Texture;
   }
 
+  // The 6 faces are stored one after another, in the order of the
+  // TEXTURE_CUBE_MAP_POSITIVE_X..TEXTURE_CUBE_MAP_NEGATIVE_Z constants.
+  private allocTextureCube(
+    textureId: number,
+    size: number,
+    dataPtr: number
+  ): void {
+    const gl = this.gl;
+    const glTex = this.textures[textureId] || gl.createTexture();
+
+    gl.bindTexture(gl.TEXTURE_CUBE_MAP, glTex);
+    gl.texParameteri(gl.TEXTURE_CUBE_MAP, gl.TEXTURE_MAG_FILTER, gl.LINEAR);
+    gl.texParameteri(gl.TEXTURE_CUBE_MAP, gl.TEXTURE_MIN_FILTER, gl.LINEAR);
+    gl.texParameteri(gl.TEXTURE_CUBE_MAP, gl.TEXTURE_WRAP_S, gl.CLAMP_TO_EDGE);
+    gl.texParameteri(gl.TEXTURE_CUBE_MAP, gl.TEXTURE_WRAP_T, gl.CLAMP_TO_EDGE);
+
+    const faceBytes = size * size * 4;
+    for (let face = 0; face < 6; face++) {
+      const data = new Uint8Array(
+        this.memory.buffer,
+        dataPtr + face * faceBytes,
+        faceBytes
+      );
+      gl.texImage2D(
+        gl.TEXTURE_CUBE_MAP_POSITIVE_X + face,
+        0,
+        gl.RGBA,
+        size,
+        size,
+        0,
+        gl.RGBA,
+        gl.UNSIGNED_BYTE,
+        data
+      );
+    }
+    this.textures[textureId] = glTex as Texture;
+  }
+
   private beginRenderTargets(
     passId: number,
     width: number,
@@ -470,7 +590,6 @@ export class WebGLRenderer {
     this.targetWidth = width;
     this.targetHeight = height;
     this.clearFlags = 0;
-    // this.isMainCanvas = false;
     const glFramebuffer =
       this.framebuffers[passId] ||
       (this.framebuffers[passId] = assertNotNull(gl.createFramebuffer()));
@@ -610,25 +729,6 @@ export class WebGLRenderer {
     depth: number
   ): void {
     const gl = this.gl;
-    // this.isMainCanvas = true;
-    // if (this.xrIsPresenting) {
-    // let xr_webgllayer = this.xr_session.renderState.baseLayer;
-    // this.gl.bindFramebuffer(gl.FRAMEBUFFER, xr_webgllayer.framebuffer);
-    // gl.viewport(0, 0, xr_webgllayer.framebufferWidth, xr_webgllayer.framebufferHeight);
-    // // quest 1 is 3648
-    // // quest 2 is 4096
-    // let left_view = this.xr_pose.views[0];
-    // let right_view = this.xr_pose.views[1];
-    // this.xr_left_viewport = xr_webgllayer.getViewport(left_view);
-    // this.xr_right_viewport = xr_webgllayer.getViewport(right_view);
-    // this.xr_left_projection_matrix = left_view.projectionMatrix;
-    // this.xr_left_transform_matrix = left_view.transform.inverse.matrix;
-    // this.xr_left_invtransform_matrix = left_view.transform.matrix;
-    // this.xr_right_projection_matrix = right_view.projectionMatrix;
-    // this.xr_right_transform_matrix = right_view.transform.inverse.matrix;
-    // this.xr_right_camera_pos = right_view.transform.inverse.position;
-    // this.xr_right_invtransform_matrix = right_view.transform.matrix;
-    // } else {
     gl.bindFramebuffer(gl.FRAMEBUFFER, null);
     gl.viewport(
       0,
@@ -636,13 +736,143 @@ export class WebGLRenderer {
       this.sizingData.width * this.sizingData.dpiFactor,
       this.sizingData.height * this.sizingData.dpiFactor
     );
-    // }
 
     gl.clearColor(r, g, b, a);
     gl.clearDepth(depth);
     gl.clear(gl.COLOR_BUFFER_BIT | gl.DEPTH_BUFFER_BIT);
   }
 
+  // Clear the whole framebuffer of the headset; the eyes then set their part using
+  // `setXrEyeViewport`.
+  private beginXrCanvas(
+    r: number,
+    g: number,
+    b: number,
+    a: number,
+    depth: number
+  ): void {
+    const gl = this.gl;
+    gl.bindFramebuffer(gl.FRAMEBUFFER, this.xrFramebuffer);
+    gl.clearColor(r, g, b, a);
+    gl.clearDepth(depth);
+    gl.clear(gl.COLOR_BUFFER_BIT | gl.DEPTH_BUFFER_BIT);
+  }
+
+  private setXrEyeViewport(
+    x: number,
+    y: number,
+    width: number,
+    height: number
+  ): void {
+    this.gl.viewport(x, y, width, height);
+  }
+
+  private beginWindowCanvas(
+    windowId: number,
+    width: number,
+    height: number,
+    r: number,
+    g: number,
+    b: number,
+    a: number,
+    depth: number
+  ): void {
+    const gl = this.gl;
+    width = Math.max(width, 1);
+    height = Math.max(height, 1);
+
+    let target = this.windowTargets[windowId];
+    if (!target) {
+      target = this.windowTargets[windowId] = {
+        framebuffer: assertNotNull(gl.createFramebuffer()),
+        colorTexture: assertNotNull(gl.createTexture()),
+        depthRenderbuffer: assertNotNull(gl.createRenderbuffer()),
+        width: 0,
+        height: 0,
+        pixels: new Uint8Array(0),
+        imageData: new ImageData(1, 1),
+      };
+    }
+    gl.bindFramebuffer(gl.FRAMEBUFFER, target.framebuffer);
+
+    if (target.width != width || target.height != height) {
+      target.width = width;
+      target.height = height;
+      target.pixels = new Uint8Array(width * height * 4);
+      target.imageData = new ImageData(width, height);
+
+      gl.bindTexture(gl.TEXTURE_2D, target.colorTexture);
+      gl.texParameteri(gl.TEXTURE_2D, gl.TEXTURE_MAG_FILTER, gl.LINEAR);
+      gl.texParameteri(gl.TEXTURE_2D, gl.TEXTURE_MIN_FILTER, gl.LINEAR);
+      gl.texParameteri(gl.TEXTURE_2D, gl.TEXTURE_WRAP_S, gl.CLAMP_TO_EDGE);
+      gl.texParameteri(gl.TEXTURE_2D, gl.TEXTURE_WRAP_T, gl.CLAMP_TO_EDGE);
+      gl.texImage2D(
+        gl.TEXTURE_2D,
+        0,
+        gl.RGBA,
+        width,
+        height,
+        0,
+        gl.RGBA,
+        gl.UNSIGNED_BYTE,
+        null
+      );
+      gl.framebufferTexture2D(
+        gl.FRAMEBUFFER,
+        gl.COLOR_ATTACHMENT0,
+        gl.TEXTURE_2D,
+        target.colorTexture,
+        0
+      );
+
+      gl.bindRenderbuffer(gl.RENDERBUFFER, target.depthRenderbuffer);
+      gl.renderbufferStorage(
+        gl.RENDERBUFFER,
+        gl.DEPTH_COMPONENT16,
+        width,
+        height
+      );
+      gl.framebufferRenderbuffer(
+        gl.FRAMEBUFFER,
+        gl.DEPTH_ATTACHMENT,
+        gl.RENDERBUFFER,
+        target.depthRenderbuffer
+      );
+    }
+
+    gl.viewport(0, 0, width, height);
+    gl.clearColor(r, g, b, a);
+    gl.clearDepth(depth);
+    gl.clear(gl.COLOR_BUFFER_BIT | gl.DEPTH_BUFFER_BIT);
+  }
+
+  // Copy what we rendered in `beginWindowCanvas` to the window's canvas. Note that the pixels
+  // have premultiplied alpha while `putImageData` expects them without, which is fine as long
+  // as the window is opaque.
+  private presentWindowCanvas(windowId: number): void {
+    const gl = this.gl;
+    const target = this.windowTargets[windowId];
+    const context = this.windowContexts[windowId];
+    if (!target || !context) {
+      return;
+    }
+    const { width, height, pixels, imageData } = target;
+    gl.readPixels(0, 0, width, height, gl.RGBA, gl.UNSIGNED_BYTE, pixels);
+    // WebGL has its origin at the bottom left, so flip the rows.
+    const rowSize = width * 4;
+    for (let y = 0; y < height; y++) {
+      imageData.data.set(
+        pixels.subarray(y * rowSize, (y + 1) * rowSize),
+        (height - 1 - y) * rowSize
+      );
+    }
+    if (context.canvas.width != width || context.canvas.height != height) {
+      context.canvas.width = width;
+      context.canvas.height = height;
+    }
+    context.putImageData(imageData, 0, 0);
+  }
+
   private uniformFnTable: Record<
     string,
     (zelf: WebGLRenderer, loc: WebGLUniformLocation | null, off: number) => void
@@ -697,6 +927,15 @@ export class WebGLRenderer {
     },
   };
 
+  private makeSetMat4Array(
+    length: number
+  ): WebGLRenderer["uniformFnTable"][number] {
+    return function setMat4Array(zelf, loc, off) {
+      const mat4s = new Float32Array(zelf.memory.buffer, off, 16 * length);
+      zelf.gl.uniformMatrix4fv(loc, false, mat4s);
+    };
+  }
+
   // Array of function id's wasm can call on us; `zelf` is pointer to WebGLRenderer.
   // (It's not called `self` as to not overload https://developer.mozilla.org/en-US/docs/Web/API/Window/self)
   // Function names are suffixed with the index in the array, and annotated with
@@ -757,7 +996,17 @@ export class WebGLRenderer {
       const geomIbId = zelf.zerdeParser.parseU32();
       const geomVbId = zelf.zerdeParser.parseU32();
       const instVbId = zelf.zerdeParser.parseU32();
-      zelf.allocVao(vaoId, shaderId, geomIbId, geomVbId, instVbId);
+      const instOffset = zelf.zerdeParser.parseU32();
+      const instLen = zelf.zerdeParser.parseU32();
+      zelf.allocVao(
+        vaoId,
+        shaderId,
+        geomIbId,
+        geomVbId,
+        instVbId,
+        instOffset,
+        instLen
+      );
     },
     // draw_call
     function drawCall5(zelf) {
@@ -827,6 +1076,61 @@ export class WebGLRenderer {
       const depth = zelf.zerdeParser.parseF32();
       zelf.beginMainCanvas(r, g, b, a, depth);
     },
+    // begin_window_canvas
+    function beginWindowCanvas13(zelf) {
+      const windowId = zelf.zerdeParser.parseU32();
+      const width = zelf.zerdeParser.parseU32();
+      const height = zelf.zerdeParser.parseU32();
+      const r = zelf.zerdeParser.parseF32();
+      const g = zelf.zerdeParser.parseF32();
+      const b = zelf.zerdeParser.parseF32();
+      const a = zelf.zerdeParser.parseF32();
+      const depth = zelf.zerdeParser.parseF32();
+      zelf.beginWindowCanvas(windowId, width, height, r, g, b, a, depth);
+    },
+    // present_window_canvas
+    function presentWindowCanvas14(zelf) {
+      zelf.presentWindowCanvas(zelf.zerdeParser.parseU32());
+    },
+    // orphan_array_buffer
+    function orphanArrayBuffer15(zelf) {
+      const arrayBufferId = zelf.zerdeParser.parseU32();
+      const capacity = zelf.zerdeParser.parseU32();
+      zelf.orphanArrayBuffer(arrayBufferId, capacity);
+    },
+    // update_array_buffer_range
+    function updateArrayBufferRange16(zelf) {
+      const arrayBufferId = zelf.zerdeParser.parseU32();
+      const offset = zelf.zerdeParser.parseU32();
+      const len = zelf.zerdeParser.parseU32();
+      const pointer = zelf.zerdeParser.parseU32();
+      const array = new Float32Array(zelf.memory.buffer, pointer, len);
+      zelf.updateArrayBufferRange(arrayBufferId, offset, array);
+    },
+    // update_texture_cube
+    function allocTextureCube17(zelf) {
+      const textureId = zelf.zerdeParser.parseU32();
+      const size = zelf.zerdeParser.parseU32();
+      const dataPtr = zelf.zerdeParser.parseU32();
+      zelf.allocTextureCube(textureId, size, dataPtr);
+    },
+    // begin_xr_canvas
+    function beginXrCanvas18(zelf) {
+      const r = zelf.zerdeParser.parseF32();
+      const g = zelf.zerdeParser.parseF32();
+      const b = zelf.zerdeParser.parseF32();
+      const a = zelf.zerdeParser.parseF32();
+      const depth = zelf.zerdeParser.parseF32();
+      zelf.beginXrCanvas(r, g, b, a, depth);
+    },
+    // set_xr_eye_viewport
+    function setXrEyeViewport19(zelf) {
+      const x = zelf.zerdeParser.parseF32();
+      const y = zelf.zerdeParser.parseF32();
+      const width = zelf.zerdeParser.parseF32();
+      const height = zelf.zerdeParser.parseF32();
+      zelf.setXrEyeViewport(x, y, width, height);
+    },
   ];
 }
 
@@ -92,4 +92,49 @@ const nodeJsConfig = (env, argv) => {
   };
 };
 
-module.exports = [browserConfig, nodeJsConfig];
+// Framework integrations get their own bundles, which use the `zaplib` and framework packages that the
+// app already has, instead of bundling their own copies.
+const integrationsConfig = (env, argv) => {
+  const commonConfig = common(env, argv);
+  return {
+    ...commonConfig,
+    output: {
+      ...commonConfig.output,
+      library: {
+        name: "zaplibIntegrations",
+        type: "umd",
+      },
+    },
+    entry: {
+      /* eslint-disable camelcase */
+      zaplib_react: "./zaplib_react.ts",
+      zaplib_vue: "./zaplib_vue.ts",
+      zaplib_svelte: "./zaplib_svelte.ts",
+      /* eslint-enable camelcase */
+    },
+    externals: {
+      /* eslint-disable camelcase */
+      zaplib_runtime: {
+        commonjs: "zaplib",
+        commonjs2: "zaplib",
+        amd: "zaplib",
+        root: "zaplib",
+      },
+      /* eslint-enable camelcase */
+      react: {
+        commonjs: "react",
+        commonjs2: "react",
+        amd: "react",
+        root: "React",
+      },
+      vue: {
+        commonjs: "vue",
+        commonjs2: "vue",
+        amd: "vue",
+        root: "Vue",
+      },
+    },
+  };
+};
+
+module.exports = [browserConfig, nodeJsConfig, integrationsConfig];
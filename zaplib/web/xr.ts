@@ -0,0 +1,226 @@
+// WebXR sessions for presenting to VR headsets; see `xr.rs`.
+//
+// WebXR is only available on the browser's main thread, and the framebuffer of the headset can only
+// be drawn into during the session's own animation frame callback. So with `initParams.xr` we always
+// render on the main thread, and on every frame of the headset we:
+// 1. Draw the rendering commands that the worker sent since the last frame, if any. These use the
+//    eye matrices of the previous frame, since Rust needs them to draw.
+// 2. Send the poses of this frame to the worker, which calls Rust with an `XrUpdateEvent` and sends
+//    new rendering commands. We skip this if the worker is still busy with the previous frame.
+
+import { XrFrameData, XrPoseData } from "types";
+import { WebGLRenderer } from "webgl_renderer";
+
+// The parts of the WebXR API that we use, since TypeScript doesn't have types for it yet.
+type XRRigidTransform = {
+  position: DOMPointReadOnly;
+  orientation: DOMPointReadOnly;
+  inverse: XRRigidTransform;
+  matrix: Float32Array;
+};
+type XRSpace = unknown;
+type XRView = { projectionMatrix: Float32Array; transform: XRRigidTransform };
+type XRFrame = {
+  getViewerPose(
+    space: XRSpace
+  ): { transform: XRRigidTransform; views: XRView[] } | undefined;
+  getPose(
+    space: XRSpace,
+    baseSpace: XRSpace
+  ): { transform: XRRigidTransform } | undefined;
+};
+type XRWebGLLayer = {
+  framebuffer: WebGLFramebuffer | null;
+  getViewport(view: XRView): {
+    x: number;
+    y: number;
+    width: number;
+    height: number;
+  };
+};
+type XRSession = {
+  inputSources: {
+    handedness: "none" | "left" | "right";
+    gripSpace?: XRSpace;
+    targetRaySpace: XRSpace;
+    gamepad?: Gamepad;
+  }[];
+  renderState: { baseLayer?: XRWebGLLayer };
+  updateRenderState(state: { baseLayer: XRWebGLLayer }): void;
+  requestReferenceSpace(type: string): Promise<XRSpace>;
+  requestAnimationFrame(callback: (time: number, frame: XRFrame) => void): void;
+  end(): Promise<void>;
+  addEventListener(type: "end", listener: () => void): void;
+};
+type XRSystem = {
+  isSessionSupported(mode: string): Promise<boolean>;
+  requestSession(
+    mode: string,
+    options: { requiredFeatures: string[] }
+  ): Promise<XRSession>;
+};
+
+const getXrSystem = (): XRSystem | undefined =>
+  (globalThis.navigator as Navigator & { xr?: XRSystem }).xr;
+
+const makePoseData = (transform: XRRigidTransform): XrPoseData => ({
+  orientation: [
+    transform.orientation.x,
+    transform.orientation.y,
+    transform.orientation.z,
+    transform.orientation.w,
+  ],
+  position: [transform.position.x, transform.position.y, transform.position.z],
+});
+
+export class XrPresenter {
+  canPresent = false;
+  private webglRenderer: WebGLRenderer;
+  // Called when `canPresent` or `isPresenting` changes.
+  private onChange: () => void;
+  // Sends a frame to the worker, and resolves once the worker has handled it.
+  private sendFrame: (frame: XrFrameData) => Promise<void>;
+  private session: XRSession | undefined;
+  private referenceSpace: XRSpace | undefined;
+  // Rendering commands from the worker that wait for the next frame of the headset.
+  private pendingWebGL: { zerdeParserPtr: number; resolve: () => void }[] = [];
+  private isSendingFrame = false;
+
+  constructor(
+    webglRenderer: WebGLRenderer,
+    onChange: () => void,
+    sendFrame: (frame: XrFrameData) => Promise<void>
+  ) {
+    this.webglRenderer = webglRenderer;
+    this.onChange = onChange;
+    this.sendFrame = sendFrame;
+
+    getXrSystem()
+      ?.isSessionSupported("immersive-vr")
+      .then((supported) => {
+        if (supported) {
+          this.canPresent = true;
+          this.onChange();
+        }
+      });
+  }
+
+  isPresenting(): boolean {
+    return !!this.session;
+  }
+
+  async startPresenting(): Promise<void> {
+    const xrSystem = getXrSystem();
+    if (!xrSystem || !this.canPresent || this.session) {
+      return;
+    }
+    try {
+      const session = await xrSystem.requestSession("immersive-vr", {
+        requiredFeatures: ["local-floor"],
+      });
+      const XRWebGLLayer = (
+        globalThis as unknown as {
+          XRWebGLLayer: new (
+            session: XRSession,
+            gl: WebGLRenderingContext
+          ) => XRWebGLLayer;
+        }
+      ).XRWebGLLayer;
+      session.updateRenderState({
+        baseLayer: new XRWebGLLayer(session, this.webglRenderer.getContext()),
+      });
+      this.referenceSpace = await session.requestReferenceSpace("local-floor");
+      this.session = session;
+      session.addEventListener("end", () => {
+        this.session = undefined;
+        this.referenceSpace = undefined;
+        // Draw what's left into the canvas, so that the worker doesn't wait forever.
+        this.runPendingWebGL();
+        this.onChange();
+      });
+      session.requestAnimationFrame(this.onXrFrame);
+      this.onChange();
+    } catch (e) {
+      // E.g. when not called in response to a click, or when the user declined.
+      console.error("Could not start WebXR session", e);
+    }
+  }
+
+  stopPresenting(): void {
+    this.session?.end();
+  }
+
+  // Draw rendering commands during the next frame of the headset.
+  runWebGL(zerdeParserPtr: number): Promise<void> {
+    return new Promise((resolve) => {
+      this.pendingWebGL.push({ zerdeParserPtr, resolve });
+    });
+  }
+
+  private runPendingWebGL(): void {
+    for (const { zerdeParserPtr, resolve } of this.pendingWebGL.splice(0)) {
+      this.webglRenderer.processMessages(zerdeParserPtr);
+      resolve();
+    }
+  }
+
+  private onXrFrame = (time: number, frame: XRFrame): void => {
+    const session = this.session;
+    if (!session) {
+      return;
+    }
+    session.requestAnimationFrame(this.onXrFrame);
+
+    const baseLayer = session.renderState.baseLayer;
+    if (!baseLayer) {
+      return;
+    }
+    this.webglRenderer.setXrFramebuffer(baseLayer.framebuffer);
+    this.runPendingWebGL();
+    this.webglRenderer.setXrFramebuffer(null);
+
+    const viewerPose = frame.getViewerPose(this.referenceSpace);
+    if (!viewerPose || this.isSendingFrame) {
+      return;
+    }
+    const inputs: XrFrameData["inputs"] = [];
+    for (const inputSource of session.inputSources) {
+      const rayPose = frame.getPose(
+        inputSource.targetRaySpace,
+        this.referenceSpace
+      );
+      if (!rayPose) {
+        continue;
+      }
+      const gripPose =
+        inputSource.gripSpace &&
+        frame.getPose(inputSource.gripSpace, this.referenceSpace);
+      const gamepad = inputSource.gamepad;
+      inputs.push({
+        handedness: inputSource.handedness,
+        grip: gripPose ? makePoseData(gripPose.transform) : undefined,
+        ray: makePoseData(rayPose.transform),
+        buttons: gamepad
+          ? gamepad.buttons.map(({ pressed, value }) => ({ pressed, value }))
+          : [],
+        axes: gamepad ? Array.from(gamepad.axes) : [],
+      });
+    }
+    this.isSendingFrame = true;
+    this.sendFrame({
+      time: time / 1000,
+      head: makePoseData(viewerPose.transform),
+      views: viewerPose.views.map((view) => {
+        const viewport = baseLayer.getViewport(view);
+        return {
+          projection: Array.from(view.projectionMatrix),
+          view: Array.from(view.transform.inverse.matrix),
+          viewport: [viewport.x, viewport.y, viewport.width, viewport.height],
+        };
+      }),
+      inputs,
+    }).then(() => {
+      this.isSendingFrame = false;
+    });
+  };
+}
@@ -9,17 +9,31 @@ export class ZapBuffer extends SharedArrayBuffer {
   // This class supports both SharedArrayBuffer (wasm usecase) and ArrayBuffer (CEF)
   // In the future we can migrate to SharedArrayBuffer-s only once CEF supports those
   __zaplibWasmBuffer: SharedArrayBuffer | ArrayBuffer;
+  // The memory of the WebAssembly instance that owns this buffer; undefined on CEF. There can be
+  // multiple instances per page (see `createInstance`), which can't read each other's buffers.
+  __zaplibWasmMemory: WebAssembly.Memory | undefined;
   __zaplibBufferData: BufferData;
-
-  constructor(buffer: SharedArrayBuffer | ArrayBuffer, bufferData: BufferData) {
+  // Set by `releaseZapBuffer`; the underlying memory might be reused by Rust after this.
+  __zaplibReleased = false;
+  // Hash of the contents of read-only buffers, only set when `enableBufferChecks` was called.
+  __zaplibChecksum: number | undefined;
+
+  constructor(
+    buffer: SharedArrayBuffer | ArrayBuffer,
+    bufferData: BufferData,
+    wasmMemory?: WebAssembly.Memory
+  ) {
     super(0);
     this.__zaplibWasmBuffer = buffer;
+    this.__zaplibWasmMemory = wasmMemory;
     this.__zaplibBufferData = bufferData;
+    if (bufferChecksEnabled && bufferData.readonly) {
+      this.__zaplibChecksum = checksumZapBuffer(this);
+    }
   }
 
-  // TODO(Paras): Actually enforce this flag and prevent mutation of ZapArrays marked as readonly.
-  // Potentially, we can do this by hashing read only buffer data and periodically checking in debug
-  // builds if they have been modified/raising errors.
+  // We can't prevent mutation of ZapArrays marked as readonly, but when `enableBufferChecks` was
+  // called, we detect it in `checkZapBufferUsable`.
   get readonly(): boolean {
     return this.__zaplibBufferData.readonly;
   }
@@ -44,7 +58,7 @@ export class ZapBuffer extends SharedArrayBuffer {
 //
 // The Rust side assumes that underlying data buffer is immutable,
 // however it still could be accidentally modified on JS side leading to weird behavior
-// TODO(Dmitry): Throw an error if there is mutation of the data
+// (which `enableBufferChecks` detects when the buffer is passed back to Rust).
 function zapBufferExtends(cls: any) {
   return class ZapTypedArray extends cls {
     constructor(...args: any) {
@@ -108,6 +122,51 @@ function zapBufferExtends(cls: any) {
   };
 }
 
+// Per-thread flag for extra (slow) checks on ZapBuffers; see `enableBufferChecks`.
+let bufferChecksEnabled = false;
+
+// Detect read-only buffers that were modified from JS, whenever they are passed back to Rust or
+// released. Read-only buffers get hashed when we receive them from Rust, which is slow for large
+// buffers, so only use this during development.
+//
+// This only applies to the current thread, so call it in every WebWorker as well.
+export const enableBufferChecks = (): void => {
+  bufferChecksEnabled = true;
+};
+
+// FNV-1a hash of the bytes that a ZapBuffer covers.
+function checksumZapBuffer(zapBuffer: ZapBuffer): number {
+  const { bufferPtr, bufferLen } = zapBuffer.__zaplibBufferData;
+  const bytes = new Uint8Array(
+    zapBuffer.__zaplibWasmBuffer,
+    bufferPtr,
+    bufferLen
+  );
+  let hash = 0x811c9dc5;
+  for (let i = 0; i < bytes.length; i++) {
+    hash = Math.imul(hash ^ bytes[i], 0x01000193);
+  }
+  return hash >>> 0;
+}
+
+// Throws if the ZapBuffer has been released, or (with `enableBufferChecks`) if a read-only buffer
+// has been modified.
+export function checkZapBufferUsable(zapBuffer: ZapBuffer): void {
+  if (zapBuffer.__zaplibReleased) {
+    throw new Error(
+      "Cannot use a buffer after calling `releaseBuffer` on it (or on another array over the same buffer)"
+    );
+  }
+  if (
+    zapBuffer.__zaplibChecksum !== undefined &&
+    zapBuffer.__zaplibChecksum !== checksumZapBuffer(zapBuffer)
+  ) {
+    throw new Error(
+      "A read-only buffer was modified from JS; use `.slice()` to make a copy that you can modify"
+    );
+  }
+}
+
 // Extending all typed arrays
 // See https://developer.mozilla.org/en-US/docs/Web/JavaScript/Reference/Global_Objects#indexed_collections
 export const classesToExtend = {
@@ -231,11 +290,24 @@ export function isZapBuffer(potentialZapBuffer: ArrayBufferLike): boolean {
   );
 }
 
+// Whether this is a ZapBuffer that belongs to the WebAssembly instance with `wasmMemory`. ZapBuffers
+// of other instances have to be copied when passing them to Rust.
+export function isZapBufferOf(
+  potentialZapBuffer: ArrayBufferLike,
+  wasmMemory: WebAssembly.Memory
+): boolean {
+  return (
+    isZapBuffer(potentialZapBuffer) &&
+    (potentialZapBuffer as ZapBuffer).__zaplibWasmMemory === wasmMemory
+  );
+}
+
 export function checkValidZapArray(zapArray: ZapArray): void {
   if (!isZapBuffer(zapArray.buffer)) {
     throw new Error("zapArray.buffer is not a ZapBuffer in checkValidZapArray");
   }
   const buffer = zapArray.buffer as ZapBuffer;
+  checkZapBufferUsable(buffer);
 
   const bufferCoversZapBuffer =
     zapArray.byteOffset === buffer.__zaplibBufferData.bufferPtr &&
@@ -257,8 +329,24 @@ export function checkValidZapArray(zapArray: ZapArray): void {
 }
 
 // Cache ZapBuffers so that we have a stable identity for ZapBuffers pointing to the same
-// Arc. This is useful for any downstream caches in user code.
-const bufferCache: { [arcPtr: number]: WeakRef<ZapBuffer> } = {};
+// Arc. This is useful for any downstream caches in user code. There is one cache per WebAssembly
+// memory, since different instances can use the same pointers.
+type BufferCache = { [arcPtr: number]: WeakRef<ZapBuffer> };
+const bufferCaches = new WeakMap<WebAssembly.Memory, BufferCache>();
+const cefBufferCache: BufferCache = {};
+const getBufferCache = (
+  wasmMemory: WebAssembly.Memory | undefined
+): BufferCache => {
+  if (!wasmMemory) {
+    return cefBufferCache;
+  }
+  let bufferCache = bufferCaches.get(wasmMemory);
+  if (!bufferCache) {
+    bufferCache = {};
+    bufferCaches.set(wasmMemory, bufferCache);
+  }
+  return bufferCache;
+};
 
 export const allocatedArcs: Record<number, boolean> = {};
 export const allocatedVecs: Record<number, boolean> = {};
@@ -266,9 +354,11 @@ export const allocatedVecs: Record<number, boolean> = {};
 const bufferRegistry = new FinalizationRegistry(
   ({
     arcPtr,
+    bufferCache,
     destructor,
   }: {
     arcPtr: number;
+    bufferCache: BufferCache;
     destructor?: (arcPtr: number) => void;
   }) => {
     if (inTest) {
@@ -318,18 +408,23 @@ export const getZapBufferWasm = (
   mutableDestructor: (bufferData: MutableBufferData) => void
 ): ZapBuffer => {
   if (bufferData.readonly) {
+    const bufferCache = getBufferCache(wasmMemory);
     if (!bufferCache[bufferData.arcPtr]?.deref()) {
       if (inTest) {
         allocatedArcs[bufferData.arcPtr] = true;
       }
 
-      const zapBuffer = new ZapBuffer(wasmMemory.buffer, bufferData);
+      const zapBuffer = new ZapBuffer(
+        wasmMemory.buffer,
+        bufferData,
+        wasmMemory
+      );
 
-      bufferRegistry.register(zapBuffer, {
-        arcPtr: bufferData.arcPtr,
-        destructor,
-        /* no unregisterToken here since we never need to unregister */
-      });
+      bufferRegistry.register(
+        zapBuffer,
+        { arcPtr: bufferData.arcPtr, bufferCache, destructor },
+        zapBuffer
+      );
 
       bufferCache[bufferData.arcPtr] = new WeakRef(zapBuffer);
     } else {
@@ -344,7 +439,7 @@ export const getZapBufferWasm = (
       allocatedVecs[bufferData.bufferPtr] = true;
     }
 
-    const zapBuffer = new ZapBuffer(wasmMemory.buffer, bufferData);
+    const zapBuffer = new ZapBuffer(wasmMemory.buffer, bufferData, wasmMemory);
 
     mutableZapBufferRegistry.register(
       zapBuffer,
@@ -375,6 +470,40 @@ export const unregisterMutableBuffer = (zapBuffer: ZapBuffer): void => {
   }
 };
 
+// Give up our reference to the data of a ZapBuffer right away, instead of waiting for it to get
+// garbage collected. Read-only buffers get their Arc decremented, and mutable buffers get deallocated.
+// Any typed arrays over this buffer must not be used anymore afterwards.
+export const releaseZapBuffer = (
+  zapBuffer: ZapBuffer,
+  destructor?: (arcPtr: number) => void,
+  mutableDestructor?: (bufferData: MutableBufferData) => void
+): void => {
+  checkZapBufferUsable(zapBuffer);
+  zapBuffer.__zaplibReleased = true;
+
+  const bufferData = zapBuffer.__zaplibBufferData;
+  if (bufferData.readonly) {
+    bufferRegistry.unregister(zapBuffer);
+    const bufferCache = getBufferCache(zapBuffer.__zaplibWasmMemory);
+    // On CEF `bufferData.arcPtr` is not set, so find the cache entry by identity.
+    for (const [arcPtr, ref] of Object.entries(bufferCache)) {
+      if (ref.deref() === zapBuffer) {
+        delete bufferCache[Number(arcPtr)];
+        if (inTest) {
+          allocatedArcs[Number(arcPtr)] = false;
+        }
+      }
+    }
+    if (destructor) destructor(bufferData.arcPtr);
+  } else {
+    mutableZapBufferRegistry.unregister(zapBuffer);
+    if (inTest) {
+      allocatedVecs[bufferData.bufferPtr] = false;
+    }
+    if (mutableDestructor) mutableDestructor(bufferData);
+  }
+};
+
 // Return a buffer with a stable identity based on arcPtr
 export const getZapBufferCef = (
   buffer: ArrayBuffer,
@@ -382,6 +511,7 @@ export const getZapBufferCef = (
   paramType: ZapParamType
 ): ZapBuffer => {
   if (arcPtr) {
+    const bufferCache = cefBufferCache;
     if (!bufferCache[arcPtr]?.deref()) {
       const zapBuffer = new ZapBuffer(buffer, {
         bufferPtr: 0,
@@ -392,7 +522,7 @@ export const getZapBufferCef = (
         arcPtr: -1,
       });
 
-      bufferRegistry.register(zapBuffer, { arcPtr });
+      bufferRegistry.register(zapBuffer, { arcPtr, bufferCache }, zapBuffer);
       bufferCache[arcPtr] = new WeakRef(zapBuffer);
     }
     return bufferCache[arcPtr].deref() as ZapBuffer;
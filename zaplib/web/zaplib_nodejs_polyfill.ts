@@ -10,6 +10,11 @@ globalThis.Worker = Worker;
 const threads = require("worker_threads");
 globalThis.MessageChannel = threads.MessageChannel;
 
+// Zaplib's workers get evaluated without `require`, so store it for loading built-in modules like `fs`;
+// see `nodeRequire` in `common.ts`. Using eval to prevent Webpack from replacing it.
+// @ts-ignore
+globalThis.zaplibNodeRequire = eval("require");
+
 // Webpack's worker-loader needs this.
 // https://github.com/webpack-contrib/worker-loader/blob/a37f4b2caff11bb0bad5b54090a6de940504a3cb/src/runtime/inline.js#L5
 // TODO(JP): worker-loader is deprecated, so see if we can get the equivalent
@@ -0,0 +1,168 @@
+// React integration, exported as `zaplib/dist/zaplib_react`. Works with React 18's StrictMode and
+// concurrent rendering: mounting and unmounting components multiple times doesn't initialize
+// Zaplib again, and calls into Rust are cached so they can be used with Suspense.
+//
+// Usage:
+//   <ZaplibProvider initParams={{ wasmModule: "target/.../my_app.wasm" }} withCanvas>
+//     <ZaplibCanvas style={{ width: 800, height: 600 }} />
+//   </ZaplibProvider>
+
+import * as React from "react";
+import {
+  CallRustState,
+  CanvasBinding,
+  CanvasBindingProps,
+  EmbedInitParams,
+  initialCallRustState,
+  mountCanvas,
+  paramsEqual,
+  watchCallRust,
+  ZaplibEmbed,
+} from "embed_core";
+import { ZaplibInstance, ZapParam } from "types";
+
+export type { CallRustState, CanvasBindingProps, EmbedInitParams };
+
+const ZaplibContext = React.createContext<ZaplibEmbed | undefined>(undefined);
+
+const useEmbed = (): ZaplibEmbed => {
+  const embed = React.useContext(ZaplibContext);
+  if (!embed) {
+    throw new Error("Zaplib hooks can only be used inside <ZaplibProvider>");
+  }
+  return embed;
+};
+
+// Only change identity when the contents change, so callers can pass in a new array every render.
+const useStableParams = (
+  params: ZapParam[] | undefined
+): ZapParam[] | undefined => {
+  const paramsRef = React.useRef(params);
+  if (!paramsEqual(paramsRef.current, params)) {
+    paramsRef.current = params;
+  }
+  return paramsRef.current;
+};
+
+export type ZaplibProviderProps = {
+  // Only the first value is used, since Zaplib can only be initialized once.
+  initParams: EmbedInitParams;
+  // Render to a canvas, which gets shown by `<ZaplibCanvas>`.
+  withCanvas?: boolean;
+  // Use a separate Zaplib instance (see `zaplib.createInstance`), to show multiple apps on one page.
+  isolated?: boolean;
+  children?: React.ReactNode;
+};
+
+// Initializes Zaplib for all components inside it.
+export const ZaplibProvider = ({
+  initParams,
+  withCanvas = false,
+  isolated = false,
+  children,
+}: ZaplibProviderProps): React.ReactElement => {
+  const [embed] = React.useState(
+    () => new ZaplibEmbed(initParams, withCanvas, isolated)
+  );
+  React.useEffect(() => {
+    embed.initialize();
+  }, [embed]);
+
+  return React.createElement(
+    ZaplibContext.Provider,
+    { value: embed },
+    children
+  );
+};
+
+export type ZaplibCanvasProps = CanvasBindingProps &
+  React.HTMLAttributes<HTMLDivElement>;
+
+// Shows the canvas that Zaplib renders to. Requires `withCanvas` on `<ZaplibProvider>`. See
+// `CanvasBindingProps` for syncing props to Rust and receiving events from Rust.
+export const ZaplibCanvas = ({
+  rustFn,
+  params,
+  callbacks,
+  ...divProps
+}: ZaplibCanvasProps): React.ReactElement => {
+  const embed = useEmbed();
+  const containerRef = React.useRef<HTMLDivElement>(null);
+  const bindingRef = React.useRef<CanvasBinding>();
+
+  React.useLayoutEffect(() => {
+    if (!embed.withCanvas) {
+      throw new Error("<ZaplibCanvas> requires <ZaplibProvider withCanvas>");
+    }
+    if (!containerRef.current) {
+      return undefined;
+    }
+    return mountCanvas(embed, containerRef.current);
+  }, [embed]);
+
+  React.useEffect(() => {
+    const binding = new CanvasBinding(embed);
+    bindingRef.current = binding;
+    return () => {
+      binding.dispose();
+      bindingRef.current = undefined;
+    };
+  }, [embed]);
+  React.useEffect(() => {
+    bindingRef.current?.update({ rustFn, params, callbacks });
+  });
+
+  return React.createElement("div", { ...divProps, ref: containerRef });
+};
+
+// Suspends until Zaplib is initialized, and returns the instance that the `<ZaplibProvider>` uses.
+export const useZaplib = (): ZaplibInstance => {
+  const embed = useEmbed();
+  if (!embed.instance.isInitialized()) {
+    throw embed.initialize();
+  }
+  return embed.instance;
+};
+
+// Calls a function registered in Rust using `zaplib.callRustAsync`, and calls it again whenever
+// `name` or the contents of `params` change. A call that is still running gets cancelled when that
+// happens or when the component unmounts; see `CallRustCancelToken`.
+export const useZaplibCallRust = <T extends ZapParam[] = ZapParam[]>(
+  name: string,
+  params?: ZapParam[]
+): CallRustState<T> => {
+  const embed = useEmbed();
+  const stableParams = useStableParams(params);
+  const [state, setState] =
+    React.useState<CallRustState<T>>(initialCallRustState);
+  React.useEffect(
+    () =>
+      watchCallRust<T>(embed, name, stableParams, (update) =>
+        setState((previous) => ({ ...previous, ...update }))
+      ),
+    [embed, name, stableParams]
+  );
+  return state;
+};
+
+// Like `useZaplibCallRust`, but suspends until the result is available, and throws errors to the
+// nearest error boundary. Results are cached by `name` and `params` for as long as the
+// `<ZaplibProvider>` is mounted (strings are compared by value, buffers by identity), so don't use
+// this for functions that return something different every time. Use `useZaplibCallRustInvalidate`
+// to clear the cache.
+export const useZaplibCallRustSuspense = <T extends ZapParam[] = ZapParam[]>(
+  name: string,
+  params?: ZapParam[]
+): T => {
+  const result = useEmbed().readCached(name, params);
+  if (result instanceof Promise) {
+    throw result;
+  }
+  return result as T;
+};
+
+// Returns a function that clears the results cached by `useZaplibCallRustSuspense`.
+export const useZaplibCallRustInvalidate = (): (() => void) => {
+  const embed = useEmbed();
+  return React.useCallback(() => embed.clearCache(), [embed]);
+};
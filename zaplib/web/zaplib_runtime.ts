@@ -5,9 +5,52 @@
 import * as wasm from "wasm_runtime";
 import * as cef from "cef_runtime";
 import { jsRuntime } from "type_of_runtime";
-import { isZapBuffer } from "zap_buffer";
-import { CreateBuffer } from "types";
+import { enableBufferChecks, isZapBuffer } from "zap_buffer";
+import { CreateBuffer, RuntimeInstance, ZaplibInstance } from "types";
 import { createMutableBufferImpl, createReadOnlyBufferImpl } from "common";
+import {
+  checkRecordVersion,
+  decodeRecord,
+  decodeZapParam,
+  encodeRecord,
+  encodeZapParam,
+  getRecordField,
+  ZerdeMismatchError,
+} from "zerde_record";
+
+export type {
+  CallRustAsyncPromise,
+  LoadingProgress,
+  RustFunctionSpec,
+  TypedCallRustAsync,
+  TypedCallRustSync,
+  ZaplibInstance,
+} from "types";
+export type {
+  ZapParamBytes,
+  ZapParamFieldSchema,
+  ZapParamSchema,
+  ZerdeRecord,
+  ZerdeValue,
+} from "zerde_record";
+
+const runtime = jsRuntime === "cef" ? cef : wasm;
+
+const withBufferHelpers = (instance: RuntimeInstance): ZaplibInstance => {
+  const createMutableBuffer: CreateBuffer = createMutableBufferImpl({
+    callRustSync: instance.callRustSync,
+  });
+  const createReadOnlyBuffer: CreateBuffer = createReadOnlyBufferImpl({
+    callRustSync: instance.callRustSync,
+    createMutableBuffer,
+  });
+  return { ...instance, createMutableBuffer, createReadOnlyBuffer };
+};
+
+// Create a Zaplib instance that is independent from the default one, with its own WebAssembly
+// memory, workers, and canvas. Only supported in WebAssembly.
+const createInstance = (): ZaplibInstance =>
+  withBufferHelpers(runtime.createInstance());
 
 const {
   initialize,
@@ -17,20 +60,18 @@ const {
   registerCallJsCallbacks,
   unregisterCallJsCallbacks,
   callRustAsync,
+  callRustStream,
   serializeZapArrayForPostMessage,
   deserializeZapArrayFromPostMessage,
   callRustSync,
-} = jsRuntime === "cef" ? cef : wasm;
-
-const createMutableBuffer: CreateBuffer = createMutableBufferImpl({
-  callRustSync,
-});
-const createReadOnlyBuffer: CreateBuffer = createReadOnlyBufferImpl({
-  callRustSync,
+  setLogFilter,
+  releaseBuffer,
   createMutableBuffer,
-});
+  createReadOnlyBuffer,
+} = withBufferHelpers(runtime);
 
 export {
+  createInstance,
   initialize,
   close,
   isInitialized,
@@ -38,6 +79,7 @@ export {
   registerCallJsCallbacks,
   unregisterCallJsCallbacks,
   callRustAsync,
+  callRustStream,
   serializeZapArrayForPostMessage,
   deserializeZapArrayFromPostMessage,
   callRustSync,
@@ -45,4 +87,14 @@ export {
   createMutableBuffer,
   createReadOnlyBuffer,
   isZapBuffer,
+  releaseBuffer,
+  enableBufferChecks,
+  setLogFilter,
+  encodeRecord,
+  decodeRecord,
+  checkRecordVersion,
+  getRecordField,
+  ZerdeMismatchError,
+  encodeZapParam,
+  decodeZapParam,
 };
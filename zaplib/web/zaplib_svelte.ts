@@ -0,0 +1,120 @@
+// Svelte integration, exported as `zaplib/dist/zaplib_svelte`. Mirrors `zaplib_react.ts`; see
+// `embed_core.ts` for the shared logic. Uses Svelte's action and store contracts, so it doesn't
+// depend on Svelte itself.
+//
+// Usage:
+//   const zaplib = createZaplib({ wasmModule: "target/.../my_app.wasm" }, { withCanvas: true });
+//   $: sum = zaplib.callRust("sum", [values]);
+//   <div use:zaplib.canvas={{ rustFn: "set_props", params }} style="width: 800px; height: 600px" />
+//   {#if !$sum.loading}{$sum.result[0]}{/if}
+
+import {
+  CallRustState,
+  CanvasBinding,
+  CanvasBindingProps,
+  EmbedInitParams,
+  initialCallRustState,
+  mountCanvas,
+  watchCallRust,
+  ZaplibEmbed,
+} from "embed_core";
+import { CallRustAsyncPromise, ZapParam } from "types";
+
+export type { CallRustState, CanvasBindingProps, EmbedInitParams };
+
+// See https://svelte.dev/docs#component-format-script-4-prefix-stores-with-$-to-access-their-values
+export type Readable<T> = {
+  subscribe: (run: (value: T) => void) => () => void;
+};
+
+// See https://svelte.dev/docs#template-syntax-element-directives-use-action
+export type Action<P> = (
+  node: HTMLElement,
+  props: P
+) => { update: (props: P) => void; destroy: () => void };
+
+export type Zaplib = {
+  // Resolves once Zaplib is initialized.
+  ready: Promise<void>;
+  // Action that shows the canvas that Zaplib renders to in the element it's used on. Requires
+  // `withCanvas`. See `CanvasBindingProps` for syncing props to Rust and receiving events from Rust.
+  canvas: Action<CanvasBindingProps | undefined>;
+  // Store that calls a function registered in Rust using `zaplib.callRustAsync` while it has
+  // subscribers. Create a new store when the parameters change (e.g. using `$:`); unsubscribing from
+  // the old one cancels its call if it's still running (see `CallRustCancelToken`).
+  callRust: <T extends ZapParam[] = ZapParam[]>(
+    name: string,
+    params?: ZapParam[]
+  ) => Readable<CallRustState<T>>;
+  // `zaplib.callRustAsync`, after Zaplib is initialized. Useful with `{#await}` blocks.
+  callRustAsync: <T extends ZapParam[] = ZapParam[]>(
+    name: string,
+    params?: ZapParam[]
+  ) => CallRustAsyncPromise<T>;
+};
+
+// Initializes Zaplib. Call this once, e.g. in a module that your components import. Use `isolated`
+// for a separate Zaplib instance (see `zaplib.createInstance`), to show multiple apps on one page.
+export const createZaplib = (
+  initParams: EmbedInitParams,
+  {
+    withCanvas = false,
+    isolated = false,
+  }: { withCanvas?: boolean; isolated?: boolean } = {}
+): Zaplib => {
+  const embed = new ZaplibEmbed(initParams, withCanvas, isolated);
+
+  const canvas: Action<CanvasBindingProps | undefined> = (node, props) => {
+    if (!withCanvas) {
+      throw new Error("use:canvas requires `withCanvas` in createZaplib");
+    }
+    const unmountCanvas = mountCanvas(embed, node);
+    const binding = new CanvasBinding(embed);
+    binding.update(props || {});
+    return {
+      update: (props) => binding.update(props || {}),
+      destroy: () => {
+        binding.dispose();
+        unmountCanvas();
+      },
+    };
+  };
+
+  const callRust = <T extends ZapParam[] = ZapParam[]>(
+    name: string,
+    params?: ZapParam[]
+  ): Readable<CallRustState<T>> => {
+    let state: CallRustState<T> = initialCallRustState;
+    const subscribers = new Set<(value: CallRustState<T>) => void>();
+    let stop: (() => void) | undefined;
+    return {
+      subscribe: (run) => {
+        subscribers.add(run);
+        if (!stop) {
+          stop = watchCallRust<T>(embed, name, params, (update) => {
+            state = { ...state, ...update };
+            subscribers.forEach((subscriber) => subscriber(state));
+          });
+        }
+        run(state);
+        return () => {
+          subscribers.delete(run);
+          if (subscribers.size === 0 && stop) {
+            stop();
+            stop = undefined;
+          }
+        };
+      },
+    };
+  };
+
+  return {
+    ready: embed.initialize(),
+    canvas,
+    callRust,
+    callRustAsync: <T extends ZapParam[] = ZapParam[]>(
+      name: string,
+      params?: ZapParam[]
+    ) => embed.callRustAsync<T>(name, params),
+  };
+};
@@ -0,0 +1,139 @@
+// Vue 3 integration, exported as `zaplib/dist/zaplib_vue`. Mirrors `zaplib_react.ts`; see
+// `embed_core.ts` for the shared logic.
+//
+// Usage:
+//   app.use(ZaplibPlugin, { initParams: { wasmModule: "target/.../my_app.wasm" }, withCanvas: true });
+//   <ZaplibCanvas :style="{ width: '800px', height: '600px' }" />
+
+import {
+  App,
+  defineComponent,
+  h,
+  inject,
+  InjectionKey,
+  onBeforeUnmount,
+  onMounted,
+  PropType,
+  reactive,
+  Ref,
+  ref,
+  toRaw,
+  unref,
+  watch,
+  watchEffect,
+} from "vue";
+import {
+  CallRustState,
+  CanvasBinding,
+  CanvasBindingProps,
+  EmbedInitParams,
+  initialCallRustState,
+  mountCanvas,
+  watchCallRust,
+  ZaplibEmbed,
+} from "embed_core";
+import { CallJsCallback, ZapParam } from "types";
+
+export type { CallRustState, CanvasBindingProps, EmbedInitParams };
+
+const zaplibKey: InjectionKey<ZaplibEmbed> = Symbol("zaplib");
+
+const useEmbed = (): ZaplibEmbed => {
+  const embed = inject(zaplibKey);
+  if (!embed) {
+    throw new Error("Zaplib composables require `app.use(ZaplibPlugin)`");
+  }
+  return embed;
+};
+
+// Vue wraps arrays in proxies, but Zaplib needs the actual typed arrays.
+const rawParams = (
+  params: ZapParam[] | undefined
+): ZapParam[] | undefined =>
+  params && toRaw(params).map((param) => toRaw(param));
+
+export type ZaplibPluginOptions = {
+  initParams: EmbedInitParams;
+  // Render to a canvas, which gets shown by `<ZaplibCanvas>`.
+  withCanvas?: boolean;
+  // Use a separate Zaplib instance (see `zaplib.createInstance`), to show multiple apps on one page.
+  isolated?: boolean;
+};
+
+// Initializes Zaplib for the app.
+export const ZaplibPlugin = {
+  install(
+    app: App,
+    { initParams, withCanvas = false, isolated = false }: ZaplibPluginOptions
+  ): void {
+    const embed = new ZaplibEmbed(initParams, withCanvas, isolated);
+    app.provide(zaplibKey, embed);
+    embed.initialize();
+  },
+};
+
+// Shows the canvas that Zaplib renders to. Requires `withCanvas` in the `ZaplibPlugin` options. See
+// `CanvasBindingProps` for syncing props to Rust and receiving events from Rust.
+export const ZaplibCanvas = defineComponent({
+  name: "ZaplibCanvas",
+  props: {
+    rustFn: String,
+    params: Array as PropType<ZapParam[]>,
+    callbacks: Object as PropType<Record<string, CallJsCallback>>,
+  },
+  setup(props: CanvasBindingProps) {
+    const embed = useEmbed();
+    if (!embed.withCanvas) {
+      throw new Error("<ZaplibCanvas> requires `withCanvas` in ZaplibPlugin");
+    }
+    const container = ref<HTMLDivElement>();
+    const binding = new CanvasBinding(embed);
+    const update = () =>
+      binding.update({
+        rustFn: props.rustFn,
+        params: rawParams(props.params),
+        callbacks: props.callbacks,
+      });
+
+    let unmountCanvas: (() => void) | undefined;
+    onMounted(() => {
+      if (container.value) {
+        unmountCanvas = mountCanvas(embed, container.value);
+      }
+      update();
+    });
+    watch(() => [props.rustFn, props.params, props.callbacks], update);
+    onBeforeUnmount(() => {
+      unmountCanvas?.();
+      binding.dispose();
+    });
+
+    return () => h("div", { ref: container });
+  },
+});
+
+// Calls a function registered in Rust using `zaplib.callRustAsync`, and calls it again whenever
+// `name` or `params` change (they can be refs). A call that is still running gets cancelled when that
+// happens or when the component unmounts; see `CallRustCancelToken`.
+//
+// To use this with `<Suspense>`, await `zaplibReady()` in an async `setup` first.
+export const useZaplibCallRust = <T extends ZapParam[] = ZapParam[]>(
+  name: string | Ref<string>,
+  params?: ZapParam[] | Ref<ZapParam[] | undefined>
+): CallRustState<T> => {
+  const embed = useEmbed();
+  const state = reactive<CallRustState<T>>({ ...initialCallRustState });
+  watchEffect((onInvalidate) => {
+    const stop = watchCallRust<T>(
+      embed,
+      unref(name),
+      rawParams(unref(params)),
+      (update) => Object.assign(state, update)
+    );
+    onInvalidate(stop);
+  });
+  return state as CallRustState<T>;
+};
+
+// Resolves once Zaplib is initialized.
+export const zaplibReady = (): Promise<void> => useEmbed().initialize();
@@ -10,7 +10,10 @@
 // Currently this is only supported in WebAssembly, not when using CEF.
 
 import {
+  callRustStreamImpl,
   callRustSyncImpl,
+  CancelToken,
+  createCancelToken,
   createErrorCheckers,
   createMutableBufferImpl,
   createReadOnlyBufferImpl,
@@ -22,6 +25,7 @@ import {
 import { MainWorkerChannelEvent, WebWorkerRpc } from "rpc_types";
 import {
   CallRustAsync,
+  CallRustStream,
   CallRustSync,
   PostMessageTypedArray,
   WasmExports,
@@ -40,6 +44,9 @@ import {
   unregisterMutableBuffer,
   ZapBuffer,
   checkValidZapArray,
+  checkZapBufferUsable,
+  enableBufferChecks,
+  releaseZapBuffer,
 } from "zap_buffer";
 
 overwriteTypedArraysWithZapArrays();
@@ -155,13 +162,8 @@ export const newWorkerPort = (): MessagePort => {
 };
 
 // TODO(JP): Allocate buffers on the wasm memory directly here.
-export const callRustAsync: CallRustAsync = async <T extends ZapParam[]>(
-  name: string,
-  params: ZapParam[] = []
-) => {
-  checkWasm();
-
-  const transformedParams = params.map((param) => {
+const transformParamsForRust = (params: ZapParam[]) =>
+  params.map((param) => {
     if (typeof param === "string") {
       return param;
     } else if (isZapBuffer(param.buffer)) {
@@ -177,12 +179,42 @@ export const callRustAsync: CallRustAsync = async <T extends ZapParam[]>(
     }
   });
 
-  return transformParamsFromRust(
-    await rpc.send(MainWorkerChannelEvent.CallRustAsync, {
+export const callRustAsync: CallRustAsync = <T extends ZapParam[]>(
+  name: string,
+  params: ZapParam[] = []
+) => {
+  let cancelToken: CancelToken | undefined;
+  const promise = (async () => {
+    checkWasm();
+    cancelToken = createCancelToken(wasmMemory, wasmExports);
+    const result = await rpc.send(MainWorkerChannelEvent.CallRustAsync, {
       name,
-      params: transformedParams,
-    })
-  ) as T;
+      params: transformParamsForRust(params),
+      cancelTokenPtr: cancelToken.ptr,
+    });
+    // Not dropped on errors, since then the WebAssembly instance has crashed.
+    cancelToken.drop();
+    return transformParamsFromRust(result) as T;
+  })();
+  return Object.assign(promise, { cancel: () => cancelToken?.cancel() });
+};
+
+export const callRustStream: CallRustStream = async function* <
+  T extends ZapParam[]
+>(name: string, params: ZapParam[] = []) {
+  checkWasm();
+
+  const cancelToken = createCancelToken(wasmMemory, wasmExports);
+  const streamId = await rpc.send(MainWorkerChannelEvent.CallRustStream, {
+    name,
+    params: transformParamsForRust(params),
+    cancelTokenPtr: cancelToken.ptr,
+  });
+  yield* callRustStreamImpl<T>({
+    next: () => rpc.send(MainWorkerChannelEvent.CallRustStreamNext, streamId),
+    cancelToken,
+    transformParamsFromRust,
+  });
 };
 
 export const callRustSync: CallRustSync = <T extends ZapParam[]>(
@@ -216,6 +248,7 @@ export const serializeZapArrayForPostMessage = (
     throw new Error("Only pass Zap arrays to serializeZapArrayForPostMessage");
   }
   const zapBuffer = zapArray.buffer as ZapBuffer;
+  checkZapBufferUsable(zapBuffer);
   if (zapBuffer.__zaplibBufferData.readonly) {
     wasmExports.incrementArc(BigInt(zapBuffer.__zaplibBufferData.arcPtr));
   } else {
@@ -244,4 +277,26 @@ export const deserializeZapArrayFromPostMessage = (
   );
 };
 
-export { isZapBuffer };
+// Free the data of a buffer that we got from Rust right away, instead of when it gets garbage collected.
+// The array (and any other arrays over the same buffer) must not be used afterwards.
+export const releaseBuffer = (zapArray: ZapArray): void => {
+  if (!(typeof zapArray === "object" && isZapBuffer(zapArray.buffer))) {
+    throw new Error("Only pass Zap arrays to releaseBuffer");
+  }
+  releaseZapBuffer(
+    zapArray.buffer as ZapBuffer,
+    destructor,
+    mutableDestructor
+  );
+};
+
+export { isZapBuffer, enableBufferChecks };
+export {
+  checkRecordVersion,
+  decodeRecord,
+  decodeZapParam,
+  encodeRecord,
+  encodeZapParam,
+  getRecordField,
+  ZerdeMismatchError,
+} from "zerde_record";
@@ -1,6 +1,7 @@
 // Zerde is our lightweight manual serialization/deserialization system.
 //
-// Keep in sync with zerde.rs, and see there for more information.
+// Keep in sync with zerde.rs, and see there for more information. For data that needs to stay
+// compatible when JS and Rust are versioned independently, see zerde_record.ts.
 
 import { RustZapParam, ZapParamType } from "types";
 
@@ -8,6 +8,8 @@ import {
 import {
   FileHandle,
   PostMessageTypedArray,
+  XrFrameData,
+  XrPoseData,
   ZapArray,
   ZapParamType,
 } from "types";
@@ -28,6 +30,7 @@ const MSG_TYPE_POINTER_SCROLL = 10;
 const MSG_TYPE_POINTER_OUT = 11;
 const MSG_TYPE_TIMER_FIRED = 18;
 const MSG_TYPE_WINDOW_FOCUS = 19;
+const MSG_TYPE_XR_UPDATE = 20;
 const MSG_TYPE_PAINT_DIRTY = 21;
 const MSG_TYPE_HTTP_SEND_RESPONSE = 22;
 const MSG_TYPE_WEBSOCKET_MESSAGE = 23;
@@ -38,12 +41,17 @@ const MSG_TYPE_DRAG_ENTER = 27;
 const MSG_TYPE_DRAG_LEAVE = 28;
 const MSG_TYPE_DRAG_OVER = 29;
 const MSG_TYPE_CALL_RUST = 30;
+const MSG_TYPE_WINDOW_RESIZE = 31;
+const MSG_TYPE_POINTER_LOCK_CHANGED = 32;
+const MSG_TYPE_POINTER_RAW_MOVE = 33;
 
 // A set of events. Each event starts with a u32 representing the event type, with 0 indicating the end. And
 // it is prefixed by a timestamp.
 export class ZerdeEventloopEvents {
   private _wasmApp: WasmApp;
   private _zerdeBuilder: ZerdeBuilder;
+  // Timers that already fired in this set of events, so we can coalesce repeated firings.
+  private _firedTimerIds = new Set<number>();
 
   constructor(wasmApp: WasmApp) {
     this._wasmApp = wasmApp;
@@ -107,12 +115,27 @@ export class ZerdeEventloopEvents {
     this._zerdeBuilder.sendU32(info.isFullscreen ? 1 : 0);
   }
 
+  // Size of the canvas of a window other than the main window.
+  windowCanvasResize(
+    windowId: number,
+    width: number,
+    height: number,
+    dpiFactor: number
+  ): void {
+    this._zerdeBuilder.sendU32(MSG_TYPE_WINDOW_RESIZE);
+    this._zerdeBuilder.sendU32(windowId);
+    this._zerdeBuilder.sendF32(width);
+    this._zerdeBuilder.sendF32(height);
+    this._zerdeBuilder.sendF32(dpiFactor);
+  }
+
   animationFrame(): void {
     this._zerdeBuilder.sendU32(MSG_TYPE_ANIMATION_FRAME);
   }
 
   pointerDown(pointer: Pointer): void {
     this._zerdeBuilder.sendU32(MSG_TYPE_POINTER_DOWN);
+    this._zerdeBuilder.sendU32(pointer.windowId);
     this._zerdeBuilder.sendF32(pointer.x);
     this._zerdeBuilder.sendF32(pointer.y);
     this._zerdeBuilder.sendU32(pointer.button);
@@ -124,6 +147,7 @@ export class ZerdeEventloopEvents {
 
   pointerUp(pointer: Pointer): void {
     this._zerdeBuilder.sendU32(MSG_TYPE_POINTER_UP);
+    this._zerdeBuilder.sendU32(pointer.windowId);
     this._zerdeBuilder.sendF32(pointer.x);
     this._zerdeBuilder.sendF32(pointer.y);
     this._zerdeBuilder.sendU32(pointer.button);
@@ -135,6 +159,7 @@ export class ZerdeEventloopEvents {
 
   pointerMove(pointer: Pointer): void {
     this._zerdeBuilder.sendU32(MSG_TYPE_POINTER_MOVE);
+    this._zerdeBuilder.sendU32(pointer.windowId);
     this._zerdeBuilder.sendF32(pointer.x);
     this._zerdeBuilder.sendF32(pointer.y);
     this._zerdeBuilder.sendU32(pointer.digit);
@@ -145,6 +170,7 @@ export class ZerdeEventloopEvents {
 
   pointerHover(pointer: Pointer): void {
     this._zerdeBuilder.sendU32(MSG_TYPE_POINTER_HOVER);
+    this._zerdeBuilder.sendU32(pointer.windowId);
     this._zerdeBuilder.sendF32(pointer.x);
     this._zerdeBuilder.sendF32(pointer.y);
     this._zerdeBuilder.sendU32(pointer.modifiers);
@@ -153,17 +179,29 @@ export class ZerdeEventloopEvents {
 
   pointerScroll(pointer: PointerScroll): void {
     this._zerdeBuilder.sendU32(MSG_TYPE_POINTER_SCROLL);
+    this._zerdeBuilder.sendU32(pointer.windowId);
     this._zerdeBuilder.sendF32(pointer.x);
     this._zerdeBuilder.sendF32(pointer.y);
     this._zerdeBuilder.sendF32(pointer.scrollX);
     this._zerdeBuilder.sendF32(pointer.scrollY);
+    this._zerdeBuilder.sendF32(pointer.pinch);
     this._zerdeBuilder.sendU32(pointer.isWheel ? 1 : 0);
     this._zerdeBuilder.sendU32(pointer.modifiers);
     this._zerdeBuilder.sendF64(pointer.time);
   }
 
+  pointerRawMove(pointer: Pointer, deltaX: number, deltaY: number): void {
+    this._zerdeBuilder.sendU32(MSG_TYPE_POINTER_RAW_MOVE);
+    this._zerdeBuilder.sendU32(pointer.windowId);
+    this._zerdeBuilder.sendF32(deltaX);
+    this._zerdeBuilder.sendF32(deltaY);
+    this._zerdeBuilder.sendU32(pointer.modifiers);
+    this._zerdeBuilder.sendF64(pointer.time);
+  }
+
   pointerOut(pointer: Pointer): void {
     this._zerdeBuilder.sendU32(MSG_TYPE_POINTER_OUT);
+    this._zerdeBuilder.sendU32(pointer.windowId);
     this._zerdeBuilder.sendF32(pointer.x);
     this._zerdeBuilder.sendF32(pointer.y);
     this._zerdeBuilder.sendU32(pointer.modifiers);
@@ -187,6 +225,12 @@ export class ZerdeEventloopEvents {
   }
 
   timerFired(id: number): void {
+    // If a repeating timer fired multiple times before we got to process events, only send it once,
+    // to be consistent with native platforms.
+    if (this._firedTimerIds.has(id)) {
+      return;
+    }
+    this._firedTimerIds.add(id);
     this._zerdeBuilder.sendU32(MSG_TYPE_TIMER_FIRED);
     this._zerdeBuilder.sendF64(id);
   }
@@ -197,64 +241,41 @@ export class ZerdeEventloopEvents {
     this._zerdeBuilder.sendU32(isFocus ? 1 : 0);
   }
 
-  xrUpdateHead(_inputsLen: unknown, _time: unknown): void {
-    //this._zerde_builder.send_f64(time);
-  }
-
-  xrUpdateInputs(
-    _xrFrame: unknown,
-    _xrSessions: unknown,
-    _time: unknown,
-    _xrPose: unknown,
-    _xrReferenceSpace: unknown
-  ): void {
-    // send_pose_transform(pose_transform) {
-    //     let pos = this._fit(7)
-    //     let inv_orient = pose_transform.inverse.orientation;
-    //     this._f32[pos++] = inv_orient.x;
-    //     this._f32[pos++] = inv_orient.y;
-    //     this._f32[pos++] = inv_orient.z;
-    //     this._f32[pos++] = inv_orient.w;
-    //     let tpos = pose_transform.position;
-    //     this._f32[pos++] = tpos.x;
-    //     this._f32[pos++] = tpos.y;
-    //     this._f32[pos++] = tpos.z;
-    // }
-    // let input_len = xr_session.inputSources.length;
-    // let pos = this.fit(2);
-    // this.mu32[pos ++] = 20;
-    // this.mu32[pos ++] = input_len;
-    // this._zerde_builder.send_f64(time / 1000.0);
-    // this.send_pose_transform(xr_pose.transform);
-    // for (let i = 0; i < input_len; i ++) {
-    //     let input = xr_session.inputSources[i];
-    //     let grip_pose = xr_frame.getPose(input.gripSpace, xr_reference_space);
-    //     let ray_pose = xr_frame.getPose(input.targetRaySpace, xr_reference_space);
-    //     if (grip_pose == null || ray_pose == null) {
-    //         let pos = this.fit(1);
-    //         this.mu32[pos ++] = 0;
-    //         continue
-    //     }
-    //     let pos = this.fit(1);
-    //     this.mu32[pos ++] = 1;
-    //     this.send_pose_transform(grip_pose.transform)
-    //     this.send_pose_transform(ray_pose.transform)
-    //     let buttons = input.gamepad.buttons;
-    //     let axes = input.gamepad.axes;
-    //     let buttons_len = buttons.length;
-    //     let axes_len = axes.length;
-    //     pos = this.fit(3 + buttons_len * 2 + axes_len);
-    //     this.mu32[pos ++] = input.handedness == "left"? 1: input.handedness == "right"? 2: 0;
-    //     this.mu32[pos ++] = buttons_len;
-    //     for (let i = 0; i < buttons_len; i ++) {
-    //         this.mu32[pos ++] = buttons[i].pressed? 1: 0;
-    //         this.mf32[pos ++] = buttons[i].value;
-    //     }
-    //     this.mu32[pos ++] = axes_len;
-    //     for (let i = 0; i < axes_len; i ++) {
-    //         this.mf32[pos ++] = axes[i]
-    //     }
-    // }
+  pointerLockChanged(locked: boolean): void {
+    this._zerdeBuilder.sendU32(MSG_TYPE_POINTER_LOCK_CHANGED);
+    this._zerdeBuilder.sendU32(locked ? 1 : 0);
+  }
+
+  xrUpdate(frame: XrFrameData): void {
+    const sendPose = (pose: XrPoseData) => {
+      pose.orientation.forEach((value) => this._zerdeBuilder.sendF32(value));
+      pose.position.forEach((value) => this._zerdeBuilder.sendF32(value));
+    };
+    this._zerdeBuilder.sendU32(MSG_TYPE_XR_UPDATE);
+    this._zerdeBuilder.sendF64(frame.time);
+    sendPose(frame.head);
+    this._zerdeBuilder.sendU32(frame.views.length);
+    for (const view of frame.views) {
+      view.projection.forEach((value) => this._zerdeBuilder.sendF32(value));
+      view.view.forEach((value) => this._zerdeBuilder.sendF32(value));
+      view.viewport.forEach((value) => this._zerdeBuilder.sendF32(value));
+    }
+    this._zerdeBuilder.sendU32(frame.inputs.length);
+    for (const input of frame.inputs) {
+      this._zerdeBuilder.sendU32(
+        input.handedness === "left" ? 1 : input.handedness === "right" ? 2 : 0
+      );
+      this._zerdeBuilder.sendU32(input.grip ? 1 : 0);
+      sendPose(input.grip || input.ray);
+      sendPose(input.ray);
+      this._zerdeBuilder.sendU32(input.buttons.length);
+      for (const button of input.buttons) {
+        this._zerdeBuilder.sendU32(button.pressed ? 1 : 0);
+        this._zerdeBuilder.sendF32(button.value);
+      }
+      this._zerdeBuilder.sendU32(input.axes.length);
+      input.axes.forEach((value) => this._zerdeBuilder.sendF32(value));
+    }
   }
 
   paintDirty(_time: unknown, _frameData: unknown): void {
@@ -314,7 +335,9 @@ export class ZerdeEventloopEvents {
   callRustAsync(
     name: string,
     params: (string | ZapArray | PostMessageTypedArray)[],
-    callbackId: number
+    callbackId: number,
+    streaming: boolean,
+    cancelTokenPtr: number
   ): void {
     this._zerdeBuilder.sendU32(MSG_TYPE_CALL_RUST);
     this._zerdeBuilder.sendString(name);
@@ -344,6 +367,8 @@ export class ZerdeEventloopEvents {
       }
     }
     this._zerdeBuilder.sendU32(callbackId);
+    this._zerdeBuilder.sendU32(streaming ? 1 : 0);
+    this._zerdeBuilder.sendU32(cancelTokenPtr);
   }
 
   end(): number {
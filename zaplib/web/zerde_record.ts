@@ -0,0 +1,467 @@
+// Tagged, versioned records for sending structured data between JavaScript and Rust.
+//
+// Keep in sync with zerde_record.rs, and see there for more information about the encoding
+// and the compatibility rules.
+
+const TAG_BOOL = 1;
+const TAG_U32 = 2;
+const TAG_F32 = 3;
+const TAG_U64 = 4;
+const TAG_F64 = 5;
+const TAG_STRING = 6;
+const TAG_BYTES = 7;
+const TAG_RECORD = 8;
+const TAG_LIST = 9;
+const TAG_NULL = 10;
+
+export type ZerdeValue =
+  | { type: "bool"; value: boolean }
+  | { type: "u32"; value: number }
+  | { type: "f32"; value: number }
+  | { type: "u64"; value: bigint }
+  | { type: "f64"; value: number }
+  | { type: "string"; value: string }
+  | { type: "bytes"; value: Uint8Array }
+  | { type: "record"; value: ZerdeRecord }
+  | { type: "list"; value: ZerdeValue[] }
+  | { type: "null" }
+  // A value with a type tag that this version doesn't know about.
+  | { type: "unknown"; tag: number; value: Uint8Array };
+
+export type ZerdeValueType = ZerdeValue["type"];
+
+export type ZerdeRecord = {
+  version: number;
+  fields: Record<string, ZerdeValue>;
+};
+
+// Thrown when reading a field with the wrong type, or a required field that is missing, or when
+// the record's version is not supported. `field` names the offending field, if any.
+export class ZerdeMismatchError extends Error {
+  field: string | undefined;
+
+  constructor(message: string, field?: string) {
+    super(message);
+    this.name = "ZerdeMismatchError";
+    this.field = field;
+  }
+}
+
+const textEncoder = new TextEncoder();
+const textDecoder = new TextDecoder("utf-8", { fatal: true });
+
+class RecordWriter {
+  private _bytes = new Uint8Array(64);
+  private _view = new DataView(this._bytes.buffer);
+  length = 0;
+
+  private _fit(bytes: number): number {
+    if (this.length + bytes > this._bytes.length) {
+      const newBytes = new Uint8Array(
+        Math.max(this.length + bytes, this._bytes.length * 2)
+      );
+      newBytes.set(this._bytes);
+      this._bytes = newBytes;
+      this._view = new DataView(newBytes.buffer);
+    }
+    const pos = this.length;
+    this.length += bytes;
+    return pos;
+  }
+
+  writeU8(value: number): void {
+    this._view.setUint8(this._fit(1), value);
+  }
+
+  writeU32(value: number): void {
+    this._view.setUint32(this._fit(4), value, true);
+  }
+
+  writeBytes(value: Uint8Array): void {
+    this._bytes.set(value, this._fit(value.length));
+  }
+
+  // Reserve space for a u32 length, and fill it in once `write` is done.
+  writeWithLength(write: () => void): void {
+    const pos = this._fit(4);
+    write();
+    this._view.setUint32(pos, this.length - pos - 4, true);
+  }
+
+  writeF32(value: number): void {
+    this._view.setFloat32(this._fit(4), value, true);
+  }
+
+  writeU64(value: bigint): void {
+    this._view.setBigUint64(this._fit(8), value, true);
+  }
+
+  writeF64(value: number): void {
+    this._view.setFloat64(this._fit(8), value, true);
+  }
+
+  getData(): Uint8Array {
+    return this._bytes.slice(0, this.length);
+  }
+}
+
+class RecordReader {
+  private _bytes: Uint8Array;
+  private _view: DataView;
+  private _pos = 0;
+
+  constructor(bytes: Uint8Array) {
+    this._bytes = bytes;
+    this._view = new DataView(bytes.buffer, bytes.byteOffset, bytes.byteLength);
+  }
+
+  private _take(bytes: number): number {
+    if (this._pos + bytes > this._bytes.length) {
+      throw new ZerdeMismatchError(
+        `Malformed record: unexpected end of data at byte ${this._pos} (reading ${bytes} bytes)`
+      );
+    }
+    const pos = this._pos;
+    this._pos += bytes;
+    return pos;
+  }
+
+  readU8(): number {
+    return this._view.getUint8(this._take(1));
+  }
+
+  readU32(): number {
+    return this._view.getUint32(this._take(4), true);
+  }
+
+  readF32(): number {
+    return this._view.getFloat32(this._take(4), true);
+  }
+
+  readU64(): bigint {
+    return this._view.getBigUint64(this._take(8), true);
+  }
+
+  readF64(): number {
+    return this._view.getFloat64(this._take(8), true);
+  }
+
+  readBytes(length: number): Uint8Array {
+    const pos = this._take(length);
+    return this._bytes.subarray(pos, pos + length);
+  }
+
+  readString(length: number): string {
+    try {
+      return textDecoder.decode(this.readBytes(length));
+    } catch (e) {
+      throw new ZerdeMismatchError("Malformed record: invalid UTF-8");
+    }
+  }
+}
+
+const writeRecord = (writer: RecordWriter, record: ZerdeRecord): void => {
+  const names = Object.keys(record.fields);
+  writer.writeU32(record.version);
+  writer.writeU32(names.length);
+  for (const name of names) {
+    const nameBytes = textEncoder.encode(name);
+    writer.writeU32(nameBytes.length);
+    writer.writeBytes(nameBytes);
+    writeValue(writer, record.fields[name]);
+  }
+};
+
+const writeValue = (writer: RecordWriter, value: ZerdeValue): void => {
+  switch (value.type) {
+    case "bool":
+      writer.writeU8(TAG_BOOL);
+      writer.writeWithLength(() => writer.writeU8(value.value ? 1 : 0));
+      break;
+    case "u32":
+      writer.writeU8(TAG_U32);
+      writer.writeWithLength(() => writer.writeU32(value.value));
+      break;
+    case "f32":
+      writer.writeU8(TAG_F32);
+      writer.writeWithLength(() => writer.writeF32(value.value));
+      break;
+    case "u64":
+      writer.writeU8(TAG_U64);
+      writer.writeWithLength(() => writer.writeU64(value.value));
+      break;
+    case "f64":
+      writer.writeU8(TAG_F64);
+      writer.writeWithLength(() => writer.writeF64(value.value));
+      break;
+    case "string":
+      writer.writeU8(TAG_STRING);
+      writer.writeWithLength(() =>
+        writer.writeBytes(textEncoder.encode(value.value))
+      );
+      break;
+    case "bytes":
+      writer.writeU8(TAG_BYTES);
+      writer.writeWithLength(() => writer.writeBytes(value.value));
+      break;
+    case "record":
+      writer.writeU8(TAG_RECORD);
+      writer.writeWithLength(() => writeRecord(writer, value.value));
+      break;
+    case "list":
+      writer.writeU8(TAG_LIST);
+      writer.writeWithLength(() => {
+        writer.writeU32(value.value.length);
+        for (const item of value.value) {
+          writeValue(writer, item);
+        }
+      });
+      break;
+    case "null":
+      writer.writeU8(TAG_NULL);
+      writer.writeU32(0);
+      break;
+    case "unknown":
+      writer.writeU8(value.tag);
+      writer.writeWithLength(() => writer.writeBytes(value.value));
+      break;
+  }
+};
+
+const readRecord = (reader: RecordReader): ZerdeRecord => {
+  const version = reader.readU32();
+  const fieldCount = reader.readU32();
+  const fields: Record<string, ZerdeValue> = {};
+  for (let i = 0; i < fieldCount; i++) {
+    const name = reader.readString(reader.readU32());
+    fields[name] = readValue(reader);
+  }
+  return { version, fields };
+};
+
+const readValue = (reader: RecordReader): ZerdeValue => {
+  const tag = reader.readU8();
+  const data = reader.readBytes(reader.readU32());
+  const dataReader = new RecordReader(data);
+  switch (tag) {
+    case TAG_BOOL:
+      return { type: "bool", value: dataReader.readU8() !== 0 };
+    case TAG_U32:
+      return { type: "u32", value: dataReader.readU32() };
+    case TAG_F32:
+      return { type: "f32", value: dataReader.readF32() };
+    case TAG_U64:
+      return { type: "u64", value: dataReader.readU64() };
+    case TAG_F64:
+      return { type: "f64", value: dataReader.readF64() };
+    case TAG_STRING:
+      return { type: "string", value: dataReader.readString(data.length) };
+    case TAG_BYTES:
+      return { type: "bytes", value: data.slice() };
+    case TAG_RECORD:
+      return { type: "record", value: readRecord(dataReader) };
+    case TAG_LIST: {
+      const count = dataReader.readU32();
+      const value: ZerdeValue[] = [];
+      for (let i = 0; i < count; i++) {
+        value.push(readValue(dataReader));
+      }
+      return { type: "list", value };
+    }
+    case TAG_NULL:
+      return { type: "null" };
+    default:
+      return { type: "unknown", tag, value: data.slice() };
+  }
+};
+
+// Encode a record into a Uint8Array, which can be passed to Rust as a parameter to `callRust`,
+// and read there using `ZerdeRecord::from_param`.
+export const encodeRecord = (record: ZerdeRecord): Uint8Array => {
+  const writer = new RecordWriter();
+  writeRecord(writer, record);
+  return writer.getData();
+};
+
+// Decode a record that was sent from Rust (e.g. using `ZerdeRecord::into_param`).
+export const decodeRecord = (data: Uint8Array): ZerdeRecord =>
+  readRecord(new RecordReader(data));
+
+// Throws a `ZerdeMismatchError` if the version of `record` is not within `min` and `max` (inclusive).
+export const checkRecordVersion = (
+  record: ZerdeRecord,
+  min: number,
+  max: number
+): void => {
+  if (record.version < min || record.version > max) {
+    throw new ZerdeMismatchError(
+      `Unsupported record version ${record.version} (supported: ${min} to ${max})`
+    );
+  }
+};
+
+type ZerdeValueWithValue = Exclude<ZerdeValue, { type: "null" }>;
+
+// Get the value of a field with a given type. Widening is allowed (reading a "u32" as "u64", or "f32"
+// as "f64"). Throws a `ZerdeMismatchError` that names the field if it has a different type, or if it
+// is missing and no `defaultValue` was given.
+export const getRecordField = <T extends ZerdeValueWithValue["type"]>(
+  record: ZerdeRecord,
+  name: string,
+  type: T,
+  defaultValue?: Extract<ZerdeValueWithValue, { type: T }>["value"]
+): Extract<ZerdeValueWithValue, { type: T }>["value"] => {
+  type Value = Extract<ZerdeValueWithValue, { type: T }>["value"];
+  const field = Object.prototype.hasOwnProperty.call(record.fields, name)
+    ? record.fields[name]
+    : undefined;
+  if (!field) {
+    if (defaultValue !== undefined) return defaultValue;
+    throw new ZerdeMismatchError(`Missing field "${name}"`, name);
+  }
+  if (field.type === "u32" && type === "u64") {
+    return BigInt(field.value) as Value;
+  }
+  if (field.type === "f32" && type === "f64") return field.value as Value;
+  if (field.type === type && field.type !== "null") {
+    return field.value as Value;
+  }
+  throw new ZerdeMismatchError(
+    `Field "${name}" has type ${field.type}, but expected ${type}`,
+    name
+  );
+};
+
+// A `Uint8Array` with an encoded record for a struct of type `T`, as created by `encodeZapParam`
+// and read by `decodeZapParam`. `T` is only used for type checking.
+export type ZapParamBytes<T> = Uint8Array & { readonly __zapParamType?: T };
+
+// Describes how to encode a plain JS object as a record. Generated for Rust structs that use
+// `#[derive(ZapParam)]`, using `ZapParamStruct::typescript_declaration`.
+export type ZapParamSchema = {
+  version: number;
+  minVersion: number;
+  fields: Record<string, ZapParamFieldSchema>;
+};
+
+export type ZapParamFieldSchema =
+  | Exclude<ZerdeValueType, "list" | "null" | "unknown">
+  | { record: ZapParamSchema }
+  | { list: ZapParamFieldSchema }
+  | { optional: ZapParamFieldSchema };
+
+const valueFromSchema = (
+  value: unknown,
+  schema: ZapParamFieldSchema
+): ZerdeValue => {
+  if (typeof schema === "object") {
+    if ("record" in schema) {
+      return {
+        type: "record",
+        value: recordFromSchema(value as Record<string, unknown>, schema.record),
+      };
+    } else if ("list" in schema) {
+      return {
+        type: "list",
+        value: (value as unknown[]).map((item) =>
+          valueFromSchema(item, schema.list)
+        ),
+      };
+    } else {
+      return value === undefined || value === null
+        ? { type: "null" }
+        : valueFromSchema(value, schema.optional);
+    }
+  }
+  // The generated TypeScript types make sure that `value` matches the type.
+  return { type: schema, value } as ZerdeValue;
+};
+
+const recordFromSchema = (
+  value: Record<string, unknown>,
+  schema: ZapParamSchema
+): ZerdeRecord => {
+  const fields: Record<string, ZerdeValue> = {};
+  for (const [name, fieldSchema] of Object.entries(schema.fields)) {
+    // Leave out missing optional fields; Rust treats them the same as `null`.
+    if (value[name] !== undefined) {
+      fields[name] = valueFromSchema(value[name], fieldSchema);
+    }
+  }
+  return { version: schema.version, fields };
+};
+
+const valueToSchema = (
+  record: ZerdeRecord,
+  name: string,
+  path: string,
+  schema: ZapParamFieldSchema
+): unknown => {
+  const field = Object.prototype.hasOwnProperty.call(record.fields, name)
+    ? record.fields[name]
+    : undefined;
+  if (typeof schema === "object" && "optional" in schema) {
+    if (!field || field.type === "null") return undefined;
+    return valueToSchema(record, name, path, schema.optional);
+  }
+  if (!field) {
+    throw new ZerdeMismatchError(`Missing field "${path}"`, path);
+  }
+  const mismatch = (expected: string) =>
+    new ZerdeMismatchError(
+      `Field "${path}" has type ${field.type}, but expected ${expected}`,
+      path
+    );
+  if (typeof schema === "object" && "record" in schema) {
+    if (field.type !== "record") throw mismatch("record");
+    return recordToSchema(field.value, schema.record, `${path}.`);
+  }
+  if (typeof schema === "object" && "list" in schema) {
+    if (field.type !== "list") throw mismatch("list");
+    // Reuse the field logic by wrapping every list item in a record.
+    return field.value.map((item, index) =>
+      valueToSchema(
+        { version: 0, fields: { item } },
+        "item",
+        `${path}[${index}]`,
+        schema.list
+      )
+    );
+  }
+  return getRecordField({ version: 0, fields: { [path]: field } }, path, schema);
+};
+
+const recordToSchema = (
+  record: ZerdeRecord,
+  schema: ZapParamSchema,
+  pathPrefix = ""
+): Record<string, unknown> => {
+  checkRecordVersion(record, schema.minVersion, schema.version);
+  const value: Record<string, unknown> = {};
+  for (const [name, fieldSchema] of Object.entries(schema.fields)) {
+    value[name] = valueToSchema(
+      record,
+      name,
+      `${pathPrefix}${name}`,
+      fieldSchema
+    );
+  }
+  return value;
+};
+
+// Encode a plain object into a Uint8Array using a schema generated by `#[derive(ZapParam)]`, to pass
+// to Rust, where it can be read using `ZapParamStruct::from_param`.
+export const encodeZapParam = <T>(
+  value: T,
+  schema: ZapParamSchema
+): ZapParamBytes<T> =>
+  encodeRecord(
+    recordFromSchema(value as unknown as Record<string, unknown>, schema)
+  );
+
+// Decode a struct that was sent from Rust using `#[derive(ZapParam)]`. Throws a `ZerdeMismatchError`
+// naming the offending field if the data doesn't match the schema.
+export const decodeZapParam = <T>(
+  data: ZapParamBytes<T>,
+  schema: ZapParamSchema
+): T => recordToSchema(decodeRecord(data), schema) as unknown as T;